@@ -2,13 +2,30 @@
 
 use axum::{
     async_trait,
-    extract::FromRequestParts,
-    http::{request::Parts, StatusCode},
-    RequestPartsExt,
+    extract::{FromRef, FromRequestParts},
+    http::request::Parts,
 };
+use commercerack_config::AppConfig;
+use commercerack_customer::sessions::SessionService;
 use chrono::{Duration, Utc};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use sea_orm::DatabaseConnection;
 use serde::{Deserialize, Serialize};
+use std::marker::PhantomData;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::error::ApiError;
+
+/// Caller role, ordered from least to most privileged
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    #[default]
+    Customer,
+    MerchantAdmin,
+    PlatformAdmin,
+}
 
 /// JWT claims structure
 #[derive(Debug, Serialize, Deserialize, Clone, utoipa::ToSchema)]
@@ -17,20 +34,102 @@ pub struct Claims {
     pub mid: i32,         // Merchant ID
     pub exp: i64,         // Expiration time
     pub iat: i64,         // Issued at
+    #[serde(default)]
+    pub role: Role,       // Tokens minted before roles existed are customers
+    #[serde(default)]
+    pub mfa: bool,        // Login passed a second factor
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<i32>, // Login session; none for tokens minted outside a login
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Customer => "customer",
+            Role::MerchantAdmin => "merchant_admin",
+            Role::PlatformAdmin => "platform_admin",
+        }
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [Role::Customer, Role::MerchantAdmin, Role::PlatformAdmin]
+            .into_iter()
+            .find(|role| role.as_str() == s)
+            .ok_or_else(|| format!("unknown role {}", s))
+    }
 }
 
 impl Claims {
     /// Create new claims with 24h expiration
     pub fn new(customer_id: i32, mid: i32) -> Self {
+        Self::with_ttl(customer_id, mid, Duration::hours(24))
+    }
+
+    /// Create new claims expiring after `ttl`
+    pub fn with_ttl(customer_id: i32, mid: i32, ttl: Duration) -> Self {
         let now = Utc::now();
         Self {
             sub: customer_id.to_string(),
             mid,
             iat: now.timestamp(),
-            exp: (now + Duration::hours(24)).timestamp(),
+            exp: (now + ttl).timestamp(),
+            role: Role::Customer,
+            mfa: false,
+            sid: None,
         }
     }
 
+    /// Set the role carried by these claims
+    pub fn with_role(mut self, role: Role) -> Self {
+        self.role = role;
+        self
+    }
+
+    /// Mark these claims as coming from a login that passed a second factor
+    pub fn with_mfa(mut self, mfa: bool) -> Self {
+        self.mfa = mfa;
+        self
+    }
+
+    /// Tie these claims to a login session
+    pub fn with_session(mut self, sid: Option<i32>) -> Self {
+        self.sid = sid;
+        self
+    }
+
+    /// Whether the caller holds at least `role`
+    pub fn has_role(&self, role: Role) -> bool {
+        self.role >= role
+    }
+
+    /// Whether the caller may act on merchant `mid`
+    pub fn can_access_mid(&self, mid: i32) -> bool {
+        self.mid == mid || self.has_role(Role::PlatformAdmin)
+    }
+
+    /// The merchant a request should be scoped to: the caller's own `mid`,
+    /// unless a platform admin explicitly asked for another one
+    pub fn scoped_mid(&self, requested: i32) -> i32 {
+        if self.has_role(Role::PlatformAdmin) {
+            requested
+        } else {
+            self.mid
+        }
+    }
+
+    /// Customers may only act on their own account; merchant staff may act
+    /// for any of their customers
+    pub fn ensure_self(&self, cid: i32) -> Result<(), ApiError> {
+        if self.role == Role::Customer && self.sub != cid.to_string() {
+            return Err(ApiError::Forbidden("Customers may only act on their own account".to_string()));
+        }
+        Ok(())
+    }
+
     /// Encode claims into JWT token
     pub fn encode(&self, secret: &str) -> Result<String, jsonwebtoken::errors::Error> {
         encode(
@@ -56,37 +155,229 @@ impl Claims {
 impl<S> FromRequestParts<S> for Claims
 where
     S: Send + Sync,
+    Arc<AppConfig>: FromRef<S>,
 {
-    type Rejection = (StatusCode, String);
+    type Rejection = ApiError;
 
-    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
         // Extract Authorization header
         let auth_header = parts
             .headers
             .get("Authorization")
             .and_then(|h| h.to_str().ok())
-            .ok_or((
-                StatusCode::UNAUTHORIZED,
-                "Missing Authorization header".to_string(),
-            ))?;
+            .ok_or_else(|| ApiError::Unauthorized("Missing Authorization header".to_string()))?;
 
         // Parse Bearer token
         let token = auth_header
             .strip_prefix("Bearer ")
-            .ok_or((
-                StatusCode::UNAUTHORIZED,
-                "Invalid Authorization header format".to_string(),
-            ))?;
+            .ok_or_else(|| ApiError::Unauthorized("Invalid Authorization header format".to_string()))?;
 
         // Decode and validate JWT
-        // TODO: Get secret from config
-        let secret = std::env::var("JWT_SECRET").unwrap_or_else(|_| "dev-secret-key".to_string());
-
-        Claims::decode(token, &secret).map_err(|e| {
-            (
-                StatusCode::UNAUTHORIZED,
-                format!("Invalid token: {}", e),
-            )
-        })
+        let config = Arc::<AppConfig>::from_ref(state);
+        let claims = Claims::decode(token, &config.jwt.secret)
+            .map_err(|e| ApiError::Unauthorized(format!("Invalid token: {}", e)))?;
+
+        if claims.has_role(Role::MerchantAdmin) && !claims.mfa && config.two_factor.required_for_admins_of(claims.mid) {
+            return Err(ApiError::Forbidden("Admin access requires a two-factor login".to_string()));
+        }
+        Ok(claims)
+    }
+}
+
+/// Axum extractor for JWT authentication that also refuses tokens whose
+/// login session has been signed out, for routes where a revoked session
+/// must stop working at once rather than when its access token expires
+pub struct ActiveSession(pub Claims);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ActiveSession
+where
+    S: Send + Sync,
+    Arc<AppConfig>: FromRef<S>,
+    Arc<DatabaseConnection>: FromRef<S>,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let claims = Claims::from_request_parts(parts, state).await?;
+
+        if let Some(sid) = claims.sid {
+            let db = Arc::<DatabaseConnection>::from_ref(state);
+            if !SessionService::is_active(&*db, sid).await? {
+                return Err(ApiError::Unauthorized("Session was signed out".to_string()));
+            }
+        }
+        Ok(Self(claims))
+    }
+}
+
+/// Marker for the minimum role a `RequireRole` extractor accepts
+pub trait MinimumRole: Send + Sync {
+    const ROLE: Role;
+}
+
+pub struct MerchantAdminRole;
+
+impl MinimumRole for MerchantAdminRole {
+    const ROLE: Role = Role::MerchantAdmin;
+}
+
+pub struct PlatformAdminRole;
+
+impl MinimumRole for PlatformAdminRole {
+    const ROLE: Role = Role::PlatformAdmin;
+}
+
+/// Axum extractor that authenticates the caller and requires at least role `R`.
+/// Responds 401 without a valid token and 403 when the role is insufficient.
+pub struct RequireRole<R: MinimumRole>(pub Claims, pub PhantomData<R>);
+
+impl<R: MinimumRole> RequireRole<R> {
+    pub fn new(claims: Claims) -> Self {
+        Self(claims, PhantomData)
+    }
+}
+
+pub type RequireMerchantAdmin = RequireRole<MerchantAdminRole>;
+pub type RequirePlatformAdmin = RequireRole<PlatformAdminRole>;
+
+#[async_trait]
+impl<S, R> FromRequestParts<S> for RequireRole<R>
+where
+    S: Send + Sync,
+    Arc<AppConfig>: FromRef<S>,
+    R: MinimumRole,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let claims = Claims::from_request_parts(parts, state).await?;
+
+        if !claims.has_role(R::ROLE) {
+            return Err(ApiError::Forbidden(format!("Requires {:?} role", R::ROLE)));
+        }
+
+        Ok(Self::new(claims))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use commercerack_core::Timestamp;
+
+    #[test]
+    fn test_role_hierarchy() {
+        let claims = Claims::new(1, 1);
+        assert!(claims.has_role(Role::Customer));
+        assert!(!claims.has_role(Role::MerchantAdmin));
+
+        let admin = Claims::new(1, 1).with_role(Role::MerchantAdmin);
+        assert!(admin.has_role(Role::MerchantAdmin));
+        assert!(!admin.has_role(Role::PlatformAdmin));
+        assert!(Claims::new(1, 1).with_role(Role::PlatformAdmin).has_role(Role::MerchantAdmin));
+    }
+
+    #[test]
+    fn test_mid_scoping() {
+        let admin = Claims::new(1, 7).with_role(Role::MerchantAdmin);
+        assert!(admin.can_access_mid(7));
+        assert!(!admin.can_access_mid(8));
+        assert_eq!(admin.scoped_mid(8), 7);
+
+        let platform = Claims::new(1, 7).with_role(Role::PlatformAdmin);
+        assert!(platform.can_access_mid(8));
+        assert_eq!(platform.scoped_mid(8), 8);
+    }
+
+    #[test]
+    fn test_customers_only_act_for_themselves() {
+        let customer = Claims::new(7, 1);
+        assert!(customer.ensure_self(7).is_ok());
+        assert!(matches!(customer.ensure_self(8), Err(ApiError::Forbidden(_))));
+
+        let admin = Claims::new(1, 1).with_role(Role::MerchantAdmin);
+        assert!(admin.ensure_self(8).is_ok());
+    }
+
+    #[test]
+    fn test_legacy_token_defaults_to_customer() {
+        let secret = "test-secret";
+        let legacy = serde_json::json!({ "sub": "1", "mid": 1, "iat": 0, "exp": 4102444800i64 });
+        let token = encode(&Header::default(), &legacy, &EncodingKey::from_secret(secret.as_bytes())).unwrap();
+
+        let claims = Claims::decode(&token, secret).unwrap();
+        assert_eq!(claims.role, Role::Customer);
+        assert!(!claims.mfa);
+    }
+
+    #[tokio::test]
+    async fn test_admin_without_second_factor_is_refused_where_required() {
+        let mut config = AppConfig::default();
+        config.jwt.secret = "test-secret".to_string();
+        config.two_factor.required_for_admins = vec![7];
+        let config = Arc::new(config);
+
+        let extract = |claims: Claims| {
+            let config = config.clone();
+            async move {
+                let token = claims.encode(&config.jwt.secret).unwrap();
+                let request = axum::http::Request::builder()
+                    .header("Authorization", format!("Bearer {}", token))
+                    .body(())
+                    .unwrap();
+                let (mut parts, _) = request.into_parts();
+                Claims::from_request_parts(&mut parts, &config).await
+            }
+        };
+
+        let admin = Claims::new(1, 7).with_role(Role::MerchantAdmin);
+        assert!(matches!(extract(admin.clone()).await, Err(ApiError::Forbidden(_))));
+        assert!(extract(admin.clone().with_mfa(true)).await.is_ok());
+        assert!(extract(Claims::new(1, 7)).await.is_ok());
+        assert!(extract(Claims::new(1, 8).with_role(Role::MerchantAdmin)).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_signed_out_session_is_refused_by_active_session() {
+        use sea_orm::{DatabaseBackend, MockDatabase};
+
+        let mut config = AppConfig::default();
+        config.jwt.secret = "test-secret".to_string();
+        let signed_out = ::entity::prelude::Session {
+            id: 5,
+            mid: 1,
+            cid: 42,
+            device: String::new(),
+            ip: String::new(),
+            created_gmt: Timestamp::EPOCH,
+            last_seen_gmt: Timestamp::EPOCH,
+            revoked_gmt: Some(Timestamp::from_unix(1)),
+        };
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![signed_out]])
+            .into_connection();
+        let state = crate::AppState {
+            config: Arc::new(config),
+            ..crate::AppState::mock(db)
+        };
+
+        let extract = |claims: Claims| {
+            let state = state.clone();
+            async move {
+                let token = claims.encode(&state.config.jwt.secret).unwrap();
+                let request = axum::http::Request::builder()
+                    .header("Authorization", format!("Bearer {}", token))
+                    .body(())
+                    .unwrap();
+                let (mut parts, _) = request.into_parts();
+                ActiveSession::from_request_parts(&mut parts, &state).await
+            }
+        };
+
+        // Tokens from before sessions have nothing to check
+        assert!(extract(Claims::new(42, 1)).await.is_ok());
+        let result = extract(Claims::new(42, 1).with_session(Some(5))).await;
+        assert!(matches!(result, Err(ApiError::Unauthorized(_))));
     }
 }
@@ -0,0 +1,47 @@
+//! Re-encrypt stored customer PII
+//!
+//! Reads the configuration like the API and rewrites every address line,
+//! phone number and (when enabled) email in its current form: plaintext
+//! from before encryption was turned on gets encrypted, and values under
+//! retired keys move to `encryption.active_key`. Run after enabling
+//! encryption or rotating the key; it is safe to interrupt and rerun.
+//!
+//! Usage: `reencrypt_pii [batch size]`
+
+use anyhow::{bail, Context};
+use commercerack_api::{pii_keyring, AppConfig};
+use commercerack_customer::pii;
+use sea_orm::Database;
+use tracing::info;
+use tracing_subscriber::EnvFilter;
+
+const DEFAULT_BATCH: u64 = 500;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    dotenvy::dotenv().ok();
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")))
+        .init();
+
+    let batch = match std::env::args().nth(1) {
+        Some(arg) => arg.parse().context("batch size must be a positive number")?,
+        None => DEFAULT_BATCH,
+    };
+    if batch == 0 {
+        bail!("batch size must be a positive number");
+    }
+
+    let config = AppConfig::load()?;
+    let Some(keyring) = pii_keyring(&config.encryption) else {
+        bail!("encryption.active_key is not set; nothing to encrypt with");
+    };
+    let db = Database::connect(&config.database.url).await.context("connecting to the database")?;
+
+    let stats = pii::reencrypt(&db, &keyring, batch).await?;
+    info!(
+        "🔐 Re-encrypted {} customers, {} addresses and {} two-factor secrets",
+        stats.customers, stats.addresses, stats.two_factor
+    );
+    Ok(())
+}
@@ -0,0 +1,329 @@
+//! Cache of public catalog responses
+//!
+//! Storefronts read the same product pages and category listings over and
+//! over. The JSON bodies of those routes are cached per merchant, keyed by
+//! request path and query, so repeat reads skip the database.
+//!
+//! Every merchant has a generation that is part of its keys. A product,
+//! media or category change relayed from the outbox bumps the generation,
+//! which orphans all of the merchant's cached responses at once; orphaned
+//! entries are never read again and age out. A response loaded while the
+//! generation moved is stored under the old generation, so it cannot
+//! outlive the change that made it stale.
+//!
+//! Each event is relayed by one API instance, so with the in-memory backend
+//! the other instances serve their copies until the TTL lapses. The Redis
+//! backend is shared and sees every bump.
+
+use anyhow::Result;
+use axum::{
+    async_trait,
+    body::{Body, Bytes},
+    extract::{FromRequestParts, Query, RawPathParams, Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use commercerack_events::{DomainEvent, EventHandler};
+use moka::future::Cache;
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::OnceCell;
+use tracing::warn;
+
+/// A response as kept in the cache: its JSON body and the headers that
+/// describe it
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedResponse {
+    pub body: Bytes,
+    pub last_modified: Option<String>,
+}
+
+/// Where cached responses are kept
+#[async_trait]
+pub trait CatalogCache: Send + Sync {
+    /// The merchant's current generation
+    async fn generation(&self, mid: i32) -> Result<u64>;
+
+    async fn get(&self, key: &str) -> Result<Option<CachedResponse>>;
+
+    async fn put(&self, key: &str, response: CachedResponse) -> Result<()>;
+
+    /// Drop every response cached for the merchant
+    async fn invalidate(&self, mid: i32) -> Result<()>;
+}
+
+/// Cache in this process, bounded in entries and time
+pub struct MemoryCatalogCache {
+    responses: Cache<String, CachedResponse>,
+    generations: Mutex<HashMap<i32, u64>>,
+}
+
+impl MemoryCatalogCache {
+    pub fn new(max_entries: u64, ttl: Duration) -> Self {
+        Self {
+            responses: Cache::builder().max_capacity(max_entries).time_to_live(ttl).build(),
+            generations: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl CatalogCache for MemoryCatalogCache {
+    async fn generation(&self, mid: i32) -> Result<u64> {
+        Ok(self.generations.lock().unwrap().get(&mid).copied().unwrap_or(0))
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<CachedResponse>> {
+        Ok(self.responses.get(key).await)
+    }
+
+    async fn put(&self, key: &str, response: CachedResponse) -> Result<()> {
+        self.responses.insert(key.to_string(), response).await;
+        Ok(())
+    }
+
+    async fn invalidate(&self, mid: i32) -> Result<()> {
+        *self.generations.lock().unwrap().entry(mid).or_default() += 1;
+        Ok(())
+    }
+}
+
+/// Cache in Redis, shared by every API instance. Responses are stored as
+/// hashes under `catalog:{mid}:{generation}:{path}` and generations under
+/// `catalog:{mid}:generation`.
+pub struct RedisCatalogCache {
+    client: redis::Client,
+    conn: OnceCell<ConnectionManager>,
+    ttl: Duration,
+}
+
+impl RedisCatalogCache {
+    /// Open a cache at a `redis://` URL. The connection is established
+    /// lazily on first use.
+    pub fn open(url: &str, ttl: Duration) -> Result<Self> {
+        Ok(Self { client: redis::Client::open(url)?, conn: OnceCell::new(), ttl })
+    }
+
+    async fn connection(&self) -> Result<ConnectionManager> {
+        let conn = self
+            .conn
+            .get_or_try_init(|| ConnectionManager::new(self.client.clone()))
+            .await?;
+        Ok(conn.clone())
+    }
+}
+
+fn generation_key(mid: i32) -> String {
+    format!("catalog:{}:generation", mid)
+}
+
+#[async_trait]
+impl CatalogCache for RedisCatalogCache {
+    async fn generation(&self, mid: i32) -> Result<u64> {
+        let generation: Option<u64> = self.connection().await?.get(generation_key(mid)).await?;
+        Ok(generation.unwrap_or(0))
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<CachedResponse>> {
+        let mut fields: HashMap<String, Vec<u8>> = self.connection().await?.hgetall(format!("catalog:{}", key)).await?;
+        let Some(body) = fields.remove("body") else {
+            return Ok(None);
+        };
+        Ok(Some(CachedResponse {
+            body: Bytes::from(body),
+            last_modified: fields.remove("last_modified").and_then(|value| String::from_utf8(value).ok()),
+        }))
+    }
+
+    async fn put(&self, key: &str, response: CachedResponse) -> Result<()> {
+        let key = format!("catalog:{}", key);
+        let mut fields = vec![("body", response.body.to_vec())];
+        if let Some(last_modified) = response.last_modified {
+            fields.push(("last_modified", last_modified.into_bytes()));
+        }
+        redis::pipe()
+            .atomic()
+            .hset_multiple(&key, &fields)
+            .expire(&key, self.ttl.as_secs() as i64)
+            .query_async::<_, ()>(&mut self.connection().await?)
+            .await?;
+        Ok(())
+    }
+
+    async fn invalidate(&self, mid: i32) -> Result<()> {
+        self.connection().await?.incr::<_, _, ()>(generation_key(mid), 1).await?;
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+struct MidQuery {
+    mid: i32,
+}
+
+/// The merchant a catalog request reads from: the `:mid` path parameter,
+/// or else the `mid` query parameter
+async fn merchant(parts: &mut axum::http::request::Parts) -> Option<i32> {
+    if let Ok(params) = RawPathParams::from_request_parts(parts, &()).await {
+        if let Some((_, mid)) = params.iter().find(|(name, _)| *name == "mid") {
+            return mid.parse().ok();
+        }
+    }
+    Query::<MidQuery>::try_from_uri(&parts.uri).ok().map(|Query(query)| query.mid)
+}
+
+fn json(cached: CachedResponse) -> Response {
+    let mut response = ([(header::CONTENT_TYPE, "application/json")], cached.body).into_response();
+    if let Some(last_modified) = cached.last_modified.and_then(|value| HeaderValue::from_str(&value).ok()) {
+        response.headers_mut().insert(header::LAST_MODIFIED, last_modified);
+    }
+    response
+}
+
+/// Middleware serving a route's successful responses from the catalog
+/// cache. Only for public routes: the response must not depend on who is
+/// asking.
+pub async fn respond(State(cache): State<Arc<dyn CatalogCache>>, req: Request, next: Next) -> Response {
+    let (mut parts, body) = req.into_parts();
+    let Some(mid) = merchant(&mut parts).await else {
+        return next.run(Request::from_parts(parts, body)).await;
+    };
+    let generation = match cache.generation(mid).await {
+        Ok(generation) => generation,
+        Err(e) => {
+            warn!("Catalog cache unavailable: {}", e);
+            return next.run(Request::from_parts(parts, body)).await;
+        }
+    };
+
+    let path = parts.uri.path_and_query().map(|p| p.as_str()).unwrap_or_else(|| parts.uri.path());
+    let key = format!("{}:{}:{}", mid, generation, path);
+    match cache.get(&key).await {
+        Ok(Some(cached)) => return json(cached),
+        Ok(None) => {}
+        Err(e) => warn!("Reading {} from the catalog cache failed: {}", key, e),
+    }
+
+    let response = next.run(Request::from_parts(parts, body)).await;
+    if response.status() != StatusCode::OK {
+        return response;
+    }
+    let (parts, body) = response.into_parts();
+    let body = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(e) => {
+            warn!("Reading the response to {} failed: {}", key, e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let cached = CachedResponse {
+        body: body.clone(),
+        last_modified: parts.headers.get(header::LAST_MODIFIED).and_then(|value| value.to_str().ok()).map(String::from),
+    };
+    if let Err(e) = cache.put(&key, cached).await {
+        warn!("Writing {} to the catalog cache failed: {}", key, e);
+    }
+    Response::from_parts(parts, Body::from(body))
+}
+
+/// Drops a merchant's cached responses when its catalog changes
+pub struct CatalogInvalidator {
+    cache: Arc<dyn CatalogCache>,
+}
+
+impl CatalogInvalidator {
+    pub fn new(cache: Arc<dyn CatalogCache>) -> Self {
+        Self { cache }
+    }
+}
+
+#[async_trait]
+impl EventHandler for CatalogInvalidator {
+    async fn handle(&self, event: &DomainEvent) {
+        if !matches!(
+            event,
+            DomainEvent::ProductCreated(_)
+                | DomainEvent::ProductUpdated(_)
+                | DomainEvent::ProductDeleted { .. }
+                | DomainEvent::ProductMediaChanged { .. }
+                | DomainEvent::CategoryChanged { .. }
+        ) {
+            return;
+        }
+        let mid = event.mid().get();
+        if let Err(e) = self.cache.invalidate(mid).await {
+            warn!("Dropping merchant {}'s cached catalog failed: {}", mid, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use commercerack_core::MerchantId;
+
+    #[tokio::test]
+    async fn test_catalog_changes_orphan_the_merchants_responses() {
+        let cache = Arc::new(MemoryCatalogCache::new(100, Duration::from_secs(60)));
+        let invalidator = CatalogInvalidator::new(cache.clone());
+
+        invalidator.handle(&DomainEvent::SkuDeleted { mid: MerchantId::new(1), id: 5 }).await;
+        assert_eq!(cache.generation(1).await.unwrap(), 0);
+
+        invalidator.handle(&DomainEvent::CategoryChanged { mid: MerchantId::new(1), id: 3 }).await;
+        assert_eq!(cache.generation(1).await.unwrap(), 1);
+        assert_eq!(cache.generation(2).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_repeat_reads_are_served_from_the_cache() {
+        use axum::{extract::Path, middleware, routing::get, Router};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use tower::ServiceExt;
+
+        static LOADS: AtomicUsize = AtomicUsize::new(0);
+        async fn product(Path((mid, id)): Path<(i32, i32)>) -> impl IntoResponse {
+            LOADS.fetch_add(1, Ordering::SeqCst);
+            ([(header::LAST_MODIFIED, "Tue, 02 Jan 2024 00:00:00 GMT")], format!("{{\"mid\":{},\"id\":{}}}", mid, id))
+        }
+
+        let cache: Arc<dyn CatalogCache> = Arc::new(MemoryCatalogCache::new(100, Duration::from_secs(60)));
+        let app = Router::new().route(
+            "/api/products/:mid/:id",
+            get(product).route_layer(middleware::from_fn_with_state(cache.clone(), respond)),
+        );
+        let read = |uri: &str| {
+            let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(request).await.unwrap();
+                assert_eq!(response.headers()[header::LAST_MODIFIED], "Tue, 02 Jan 2024 00:00:00 GMT");
+                axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()
+            }
+        };
+
+        assert_eq!(read("/api/products/1/5").await, read("/api/products/1/5").await);
+        assert_eq!(LOADS.load(Ordering::SeqCst), 1);
+        assert_eq!(&read("/api/products/2/5").await[..], br#"{"mid":2,"id":5}"#);
+        assert_eq!(LOADS.load(Ordering::SeqCst), 2);
+
+        cache.invalidate(1).await.unwrap();
+        read("/api/products/1/5").await;
+        read("/api/products/2/5").await;
+        assert_eq!(LOADS.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_merchant_from_query() {
+        let (mut parts, _) = Request::builder().uri("/api/categories?mid=7&limit=5").body(()).unwrap().into_parts();
+        assert_eq!(merchant(&mut parts).await, Some(7));
+
+        let (mut parts, _) = Request::builder().uri("/api/categories").body(()).unwrap().into_parts();
+        assert_eq!(merchant(&mut parts).await, None);
+    }
+}
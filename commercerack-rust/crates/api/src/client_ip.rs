@@ -0,0 +1,73 @@
+//! Client IP of a request
+//!
+//! The peer address is used when the server was started with connect
+//! info. Behind a proxy every request comes from the proxy, so with
+//! `api.trust_forwarded_for` the last `X-Forwarded-For` entry is used
+//! instead: proxies append the address they saw, so that one was written
+//! by ours, while anything to its left came from the client.
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRef, FromRequestParts},
+    http::request::Parts,
+};
+use commercerack_config::AppConfig;
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+
+pub const FORWARDED_FOR_HEADER: &str = "x-forwarded-for";
+
+/// The IP a request came from, as text; `unknown` if it can't be told
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientIp(pub String);
+
+fn forwarded_for(parts: &Parts) -> Option<IpAddr> {
+    let header = parts.headers.get(FORWARDED_FOR_HEADER)?.to_str().ok()?;
+    header.rsplit(',').next()?.trim().parse().ok()
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientIp
+where
+    S: Send + Sync,
+    Arc<AppConfig>: FromRef<S>,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let config = Arc::<AppConfig>::from_ref(state);
+        let forwarded = config.api.trust_forwarded_for.then(|| forwarded_for(parts)).flatten();
+        let peer = parts.extensions.get::<ConnectInfo<SocketAddr>>().map(|info| info.0.ip());
+        let ip = forwarded.or(peer).map_or_else(|| "unknown".to_string(), |ip| ip.to_string());
+        Ok(ClientIp(ip))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Request;
+
+    async fn extract(trust: bool, forwarded: Option<&str>) -> String {
+        let mut config = AppConfig::default();
+        config.api.trust_forwarded_for = trust;
+        let mut request = Request::builder();
+        if let Some(forwarded) = forwarded {
+            request = request.header(FORWARDED_FOR_HEADER, forwarded);
+        }
+        let (mut parts, _) = request.body(()).unwrap().into_parts();
+        parts.extensions.insert(ConnectInfo(SocketAddr::from(([10, 0, 0, 1], 443))));
+        ClientIp::from_request_parts(&mut parts, &Arc::new(config)).await.unwrap().0
+    }
+
+    #[tokio::test]
+    async fn test_forwarded_for_only_when_trusted() {
+        assert_eq!(extract(false, Some("203.0.113.7")).await, "10.0.0.1");
+        assert_eq!(extract(true, Some("203.0.113.7")).await, "203.0.113.7");
+        // Whatever the client sent comes first; our proxy appends the real address
+        assert_eq!(extract(true, Some("198.51.100.1, 203.0.113.7")).await, "203.0.113.7");
+        assert_eq!(extract(true, Some("not an ip")).await, "10.0.0.1");
+        assert_eq!(extract(true, None).await, "10.0.0.1");
+    }
+}
@@ -0,0 +1,120 @@
+//! Conditional GETs
+//!
+//! Successful responses of the routes wrapped with [`etag`] carry a strong
+//! ETag hashed from their body. A request whose `If-None-Match` lists that
+//! tag gets an empty 304 instead, so clients and CDNs can revalidate their
+//! copy without downloading it again. The handler still runs; what is saved
+//! is the transfer. Handlers that know when their resource last changed
+//! send `Last-Modified` themselves, using [`http_date`].
+
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use commercerack_core::Timestamp;
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+/// Headers a 304 repeats from the response it stands in for
+const NOT_MODIFIED_HEADERS: [header::HeaderName; 5] =
+    [header::ETAG, header::LAST_MODIFIED, header::CACHE_CONTROL, header::EXPIRES, header::VARY];
+
+/// A timestamp as an HTTP date, e.g. `Tue, 02 Jan 2024 00:00:00 GMT`
+pub fn http_date(ts: Timestamp) -> HeaderValue {
+    let date = ts.to_datetime().format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+    HeaderValue::from_str(&date).expect("formatted dates are valid header values")
+}
+
+/// Strong entity tag of a response body
+fn entity_tag(body: &[u8]) -> HeaderValue {
+    let tag = format!("\"{}\"", hex::encode(&Sha256::digest(body)[..16]));
+    HeaderValue::from_str(&tag).expect("hex tags are valid header values")
+}
+
+/// Whether `If-None-Match` lists `tag`. Comparison is weak, as RFC 9110
+/// requires for `If-None-Match`: `W/"x"` matches `"x"`.
+fn none_match(headers: &HeaderMap, tag: &HeaderValue) -> bool {
+    let opaque = |t: &str| t.trim().trim_start_matches("W/").to_string();
+    let Ok(tag) = tag.to_str().map(opaque) else {
+        return false;
+    };
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|candidate| candidate.trim() == "*" || opaque(candidate) == tag)
+}
+
+/// Middleware tagging successful responses and answering 304 to requests
+/// that already hold them
+pub async fn etag(req: Request, next: Next) -> Response {
+    let conditions = req.headers().clone();
+    let response = next.run(req).await;
+    if response.status() != StatusCode::OK {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let body = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(e) => {
+            warn!("Reading a response to tag it failed: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let tag = entity_tag(&body);
+    parts.headers.insert(header::ETAG, tag.clone());
+
+    if none_match(&conditions, &tag) {
+        let mut not_modified = StatusCode::NOT_MODIFIED.into_response();
+        for name in NOT_MODIFIED_HEADERS {
+            if let Some(value) = parts.headers.get(&name) {
+                not_modified.headers_mut().insert(name, value.clone());
+            }
+        }
+        return not_modified;
+    }
+    Response::from_parts(parts, Body::from(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{middleware, routing::get, Router};
+    use tower::ServiceExt;
+
+    async fn send(app: &Router, if_none_match: Option<&str>) -> Response {
+        let mut request = Request::builder().uri("/api/products/1/5");
+        if let Some(tag) = if_none_match {
+            request = request.header(header::IF_NONE_MATCH, tag);
+        }
+        app.clone().oneshot(request.body(Body::empty()).unwrap()).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_matching_tag_is_not_modified() {
+        let app = Router::new().route(
+            "/api/products/:mid/:id",
+            get(|| async { ([(header::LAST_MODIFIED, http_date(Timestamp::from_unix(1_704_153_600)))], "{\"id\":5}") })
+                .route_layer(middleware::from_fn(etag)),
+        );
+
+        let response = send(&app, None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::LAST_MODIFIED], "Tue, 02 Jan 2024 00:00:00 GMT");
+        let tag = response.headers()[header::ETAG].to_str().unwrap().to_string();
+
+        let response = send(&app, Some(&format!("\"other\", W/{}", tag))).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[header::ETAG], tag.as_str());
+        assert_eq!(response.headers()[header::LAST_MODIFIED], "Tue, 02 Jan 2024 00:00:00 GMT");
+        assert!(axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap().is_empty());
+
+        assert_eq!(send(&app, Some("\"other\"")).await.status(), StatusCode::OK);
+        assert_eq!(send(&app, Some("*")).await.status(), StatusCode::NOT_MODIFIED);
+    }
+}
@@ -0,0 +1,687 @@
+//! API error type returned by every handler
+//!
+//! Service errors convert into `ApiError` with `?`, picking the status code
+//! for their variant. Every error is rendered as an [`ErrorBody`]. Details
+//! of internal failures are logged and never sent to the client.
+
+use axum::{
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use commercerack_audit::AuditError;
+use commercerack_channels::ChannelError;
+use commercerack_customer::{tokens::RefreshError, CustomerError};
+use commercerack_db::pagination::CursorError;
+use commercerack_giftcards::GiftCardError;
+use commercerack_inventory::InventoryError;
+use commercerack_jobs::JobError;
+use commercerack_merchant::MerchantError;
+use commercerack_order::approvals::ApprovalError;
+use commercerack_order::checkout::CheckoutError;
+use commercerack_order::cold_storage::ColdStorageError;
+use commercerack_order::digital::DownloadError;
+use commercerack_order::invoices::InvoiceError;
+use commercerack_order::payment::OrderPaymentError;
+use commercerack_order::refunds::RefundError;
+use commercerack_order::returns::ReturnError;
+use commercerack_order::OrderError;
+use commercerack_payment::PaymentError;
+use commercerack_product::availability::AvailabilityError;
+use commercerack_product::bulk_price::BulkPriceError;
+use commercerack_product::category::CategoryError;
+use commercerack_product::digital::DigitalError;
+use commercerack_product::media::MediaError;
+use commercerack_product::pricing::PricingError;
+use commercerack_product::ProductError;
+use commercerack_promotion::CouponError;
+use commercerack_reports::ReportError;
+use commercerack_geo::ZoneError;
+use commercerack_shipping::ShippingError;
+use commercerack_tax::TaxError;
+use sea_orm::DbErr;
+use serde::Serialize;
+use thiserror::Error;
+use tracing::error;
+
+use crate::oauth::OAuthError;
+
+/// A problem with one field of the request
+#[derive(Debug, Clone, PartialEq, Serialize, utoipa::ToSchema)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+/// JSON body of every error response
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct ErrorBody {
+    /// Stable machine-readable error code, e.g. `not_found`
+    pub code: &'static str,
+    pub message: String,
+    /// Per-field problems, present for validation errors
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
+    /// State of the resource now, present for version conflicts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub current: Option<serde_json::Value>,
+}
+
+#[derive(Error, Debug)]
+pub enum ApiError {
+    #[error("{0}")]
+    BadRequest(String),
+
+    #[error("{0}")]
+    Unauthorized(String),
+
+    #[error("{0}")]
+    PaymentRequired(String),
+
+    #[error("{0}")]
+    Forbidden(String),
+
+    #[error("{0}")]
+    NotFound(String),
+
+    #[error("{0}")]
+    Conflict(String),
+
+    /// The client edited a stale version; `current` lets it merge and retry
+    #[error("{message}")]
+    VersionConflict { message: String, current: serde_json::Value },
+
+    #[error("Request validation failed")]
+    Validation(Vec<FieldError>),
+
+    /// Sent with `Retry-After`
+    #[error("{message}")]
+    TooManyRequests { message: String, retry_after: u64 },
+
+    #[error("{0}")]
+    NotImplemented(String),
+
+    #[error("{0}")]
+    BadGateway(String),
+
+    #[error("{0}")]
+    ServiceUnavailable(String),
+
+    /// Logged in full; clients only see a generic message
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl ApiError {
+    pub fn not_found(what: &str) -> Self {
+        ApiError::NotFound(format!("{} not found", what))
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::PaymentRequired(_) => StatusCode::PAYMENT_REQUIRED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Conflict(_) | ApiError::VersionConflict { .. } => StatusCode::CONFLICT,
+            ApiError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::TooManyRequests { .. } => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::NotImplemented(_) => StatusCode::NOT_IMPLEMENTED,
+            ApiError::BadGateway(_) => StatusCode::BAD_GATEWAY,
+            ApiError::ServiceUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::PaymentRequired(_) => "payment_required",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::Conflict(_) => "conflict",
+            ApiError::VersionConflict { .. } => "version_conflict",
+            ApiError::Validation(_) => "validation_failed",
+            ApiError::TooManyRequests { .. } => "too_many_requests",
+            ApiError::NotImplemented(_) => "not_implemented",
+            ApiError::BadGateway(_) => "bad_gateway",
+            ApiError::ServiceUnavailable(_) => "service_unavailable",
+            ApiError::Internal(_) => "internal_error",
+        }
+    }
+
+    pub fn body(&self) -> ErrorBody {
+        let (message, errors) = match self {
+            ApiError::Internal(_) => ("Internal server error".to_string(), Vec::new()),
+            ApiError::Validation(errors) => (self.to_string(), errors.clone()),
+            _ => (self.to_string(), Vec::new()),
+        };
+        let current = match self {
+            ApiError::VersionConflict { current, .. } => Some(current.clone()),
+            _ => None,
+        };
+        ErrorBody {
+            code: self.code(),
+            message,
+            errors,
+            current,
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        if let ApiError::Internal(detail) = &self {
+            error!("Internal error: {}", detail);
+        }
+        let mut response = (self.status(), Json(self.body())).into_response();
+        if let ApiError::TooManyRequests { retry_after, .. } = &self {
+            response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(*retry_after));
+        }
+        response
+    }
+}
+
+impl From<DbErr> for ApiError {
+    fn from(e: DbErr) -> Self {
+        ApiError::Internal(format!("Database error: {}", e))
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(e: anyhow::Error) -> Self {
+        ApiError::Internal(format!("{:#}", e))
+    }
+}
+
+impl From<CustomerError> for ApiError {
+    fn from(e: CustomerError) -> Self {
+        match e {
+            CustomerError::NotFound
+            | CustomerError::AddressNotFound
+            | CustomerError::DataRequestNotFound
+            | CustomerError::SessionNotFound
+            | CustomerError::NoteNotFound
+            | CustomerError::CompanyNotFound
+            | CustomerError::BuyerNotFound
+            | CustomerError::CartNotFound
+            | CustomerError::SegmentNotFound
+            | CustomerError::SkuNotFound(_) => {
+                ApiError::NotFound(e.to_string())
+            }
+            CustomerError::Throttled { retry_after } => ApiError::TooManyRequests { message: e.to_string(), retry_after },
+            CustomerError::TwoFactorEnabled
+            | CustomerError::TwoFactorNotEnrolled
+            | CustomerError::InOtherCompany(_)
+            | CustomerError::CartNameTaken(_)
+            | CustomerError::SegmentNameTaken(_) => {
+                ApiError::Conflict(e.to_string())
+            }
+            CustomerError::InvalidTwoFactorCode => ApiError::Validation(vec![FieldError::new("code", e.to_string())]),
+            CustomerError::InvalidTag(_) => ApiError::Validation(vec![FieldError::new("tag", e.to_string())]),
+            CustomerError::InvalidSegmentRule(_) => ApiError::Validation(vec![FieldError::new("rules", e.to_string())]),
+            CustomerError::UnverifiedIdentity => ApiError::Forbidden(e.to_string()),
+            CustomerError::Token(e) => e.into(),
+            CustomerError::Cursor(e) => e.into(),
+            CustomerError::Password(_) | CustomerError::Pii(_) | CustomerError::Db(_) => ApiError::Internal(e.to_string()),
+        }
+    }
+}
+
+impl From<RefreshError> for ApiError {
+    fn from(e: RefreshError) -> Self {
+        match e {
+            RefreshError::Unknown | RefreshError::Expired | RefreshError::Reused | RefreshError::SessionRevoked => {
+                ApiError::Unauthorized(e.to_string())
+            }
+            RefreshError::Db(e) => e.into(),
+        }
+    }
+}
+
+impl From<OAuthError> for ApiError {
+    fn from(e: OAuthError) -> Self {
+        match e {
+            OAuthError::UnknownProvider(_) => ApiError::NotFound(e.to_string()),
+            OAuthError::NotConfigured(_) => ApiError::ServiceUnavailable(e.to_string()),
+            OAuthError::Provider(_) | OAuthError::InvalidIdToken(_) => ApiError::Unauthorized(e.to_string()),
+            OAuthError::Http(_) => ApiError::BadGateway(e.to_string()),
+            OAuthError::Key(_) => ApiError::Internal(e.to_string()),
+        }
+    }
+}
+
+impl From<CursorError> for ApiError {
+    fn from(e: CursorError) -> Self {
+        ApiError::Validation(vec![FieldError::new("cursor", e.to_string())])
+    }
+}
+
+impl From<ProductError> for ApiError {
+    fn from(e: ProductError) -> Self {
+        match e {
+            ProductError::NotFound => ApiError::NotFound(e.to_string()),
+            ProductError::Cursor(e) => e.into(),
+            ProductError::Db(e) => e.into(),
+        }
+    }
+}
+
+impl From<DigitalError> for ApiError {
+    fn from(e: DigitalError) -> Self {
+        match e {
+            DigitalError::ProductNotFound => ApiError::NotFound(e.to_string()),
+            DigitalError::UnknownSku(_) | DigitalError::InvalidDelivery(_) => ApiError::BadRequest(e.to_string()),
+            DigitalError::Db(e) => e.into(),
+        }
+    }
+}
+
+impl From<CategoryError> for ApiError {
+    fn from(e: CategoryError) -> Self {
+        match e {
+            CategoryError::NotFound | CategoryError::ProductNotFound => ApiError::NotFound(e.to_string()),
+            CategoryError::ParentNotFound => {
+                ApiError::Validation(vec![FieldError::new("parent_id", "must be an existing category")])
+            }
+            CategoryError::Cycle => ApiError::Conflict(e.to_string()),
+            CategoryError::Db(e) => e.into(),
+        }
+    }
+}
+
+impl From<AvailabilityError> for ApiError {
+    fn from(e: AvailabilityError) -> Self {
+        match e {
+            AvailabilityError::ProductNotFound => ApiError::NotFound(e.to_string()),
+            AvailabilityError::InvalidWindow => {
+                ApiError::Validation(vec![FieldError::new("available_until", e.to_string())])
+            }
+            AvailabilityError::Db(e) => e.into(),
+        }
+    }
+}
+
+impl From<BulkPriceError> for ApiError {
+    fn from(e: BulkPriceError) -> Self {
+        match e {
+            BulkPriceError::ProductNotFound(_) | BulkPriceError::SkuNotFound(_) => {
+                ApiError::Validation(vec![FieldError::new("changes", e.to_string())])
+            }
+            BulkPriceError::CategoryNotFound => {
+                ApiError::Validation(vec![FieldError::new("adjustment.category_id", "must be an existing category")])
+            }
+            BulkPriceError::NegativePrice(_) => {
+                ApiError::Validation(vec![FieldError::new("adjustment.percent", e.to_string())])
+            }
+            BulkPriceError::Db(e) => e.into(),
+        }
+    }
+}
+
+impl From<MediaError> for ApiError {
+    fn from(e: MediaError) -> Self {
+        match e {
+            MediaError::NotFound | MediaError::ProductNotFound => ApiError::NotFound(e.to_string()),
+            MediaError::SkuNotFound => ApiError::Validation(vec![FieldError::new("sku_id", e.to_string())]),
+            MediaError::InvalidOrder => ApiError::Validation(vec![FieldError::new("ids", e.to_string())]),
+            MediaError::Db(e) => e.into(),
+        }
+    }
+}
+
+impl From<PricingError> for ApiError {
+    fn from(e: PricingError) -> Self {
+        match e {
+            PricingError::NotFound => ApiError::NotFound(e.to_string()),
+            PricingError::UnknownSku(_) => ApiError::Validation(vec![FieldError::new("sku", e.to_string())]),
+            PricingError::Db(e) => e.into(),
+        }
+    }
+}
+
+impl From<CouponError> for ApiError {
+    fn from(e: CouponError) -> Self {
+        match e {
+            CouponError::NotFound => ApiError::NotFound(e.to_string()),
+            CouponError::DuplicateCode(_) => ApiError::Conflict(e.to_string()),
+            CouponError::Merchant(e) => e.into(),
+            CouponError::Db(e) => e.into(),
+            _ => ApiError::Validation(vec![FieldError::new("code", e.to_string())]),
+        }
+    }
+}
+
+impl From<GiftCardError> for ApiError {
+    fn from(e: GiftCardError) -> Self {
+        match e {
+            GiftCardError::NotFound => ApiError::NotFound(e.to_string()),
+            GiftCardError::DuplicateCode(_) => ApiError::Conflict(e.to_string()),
+            GiftCardError::NegativeBalance => ApiError::Validation(vec![FieldError::new("amount", e.to_string())]),
+            GiftCardError::Db(e) => e.into(),
+            _ => ApiError::Validation(vec![FieldError::new("code", e.to_string())]),
+        }
+    }
+}
+
+impl From<AuditError> for ApiError {
+    fn from(e: AuditError) -> Self {
+        match e {
+            AuditError::Db(e) => e.into(),
+        }
+    }
+}
+
+impl From<JobError> for ApiError {
+    fn from(e: JobError) -> Self {
+        match e {
+            JobError::Db(e) => e.into(),
+            JobError::Payload(_) => ApiError::Internal(e.to_string()),
+        }
+    }
+}
+
+impl From<ReportError> for ApiError {
+    fn from(e: ReportError) -> Self {
+        match e {
+            ReportError::NotFound => ApiError::NotFound(e.to_string()),
+            ReportError::Merchant(e) => e.into(),
+            ReportError::Db(e) => e.into(),
+        }
+    }
+}
+
+impl From<TaxError> for ApiError {
+    fn from(e: TaxError) -> Self {
+        match e {
+            TaxError::NotFound => ApiError::NotFound(e.to_string()),
+            TaxError::UnknownZone(_) => ApiError::Validation(vec![FieldError::new("zone_id", e.to_string())]),
+            TaxError::Zone(e) => e.into(),
+            TaxError::Provider(_) => ApiError::BadGateway(e.to_string()),
+            TaxError::Db(e) => e.into(),
+        }
+    }
+}
+
+impl From<ShippingError> for ApiError {
+    fn from(e: ShippingError) -> Self {
+        match e {
+            ShippingError::ZoneNotFound | ShippingError::RateNotFound | ShippingError::TransitNotFound => {
+                ApiError::NotFound(e.to_string())
+            }
+            ShippingError::UnknownZone(_) => ApiError::Validation(vec![FieldError::new("zone_id", e.to_string())]),
+            ShippingError::MethodUnavailable(_) => ApiError::Validation(vec![FieldError::new("ship_method", e.to_string())]),
+            ShippingError::Provider(_) => ApiError::BadGateway(e.to_string()),
+            ShippingError::Zone(e) => e.into(),
+            ShippingError::Db(e) => e.into(),
+        }
+    }
+}
+
+impl From<ZoneError> for ApiError {
+    fn from(e: ZoneError) -> Self {
+        match e {
+            ZoneError::NotFound => ApiError::NotFound(e.to_string()),
+            ZoneError::NameTaken(_) | ZoneError::InUse => ApiError::Conflict(e.to_string()),
+            ZoneError::InvalidZip(..) => ApiError::Validation(vec![FieldError::new("zips", e.to_string())]),
+            ZoneError::Db(e) => e.into(),
+        }
+    }
+}
+
+impl From<OrderError> for ApiError {
+    fn from(e: OrderError) -> Self {
+        match e {
+            OrderError::NotFound | OrderError::ItemNotFound | OrderError::ShipmentNotFound | OrderError::LocationNotFound => {
+                ApiError::NotFound(e.to_string())
+            }
+            OrderError::NothingToShip
+            | OrderError::OverShipment { .. }
+            | OrderError::NoLabel
+            | OrderError::NotPickup
+            | OrderError::NotReadyForPickup
+            | OrderError::AlreadyPickedUp
+            | OrderError::NotEditable(_)
+            | OrderError::ItemNotEditable
+            | OrderError::NegativeTotal
+            | OrderError::Cancelled
+            | OrderError::NotCancellable(_) => ApiError::Conflict(e.to_string()),
+            OrderError::NoPaymentGateway => ApiError::ServiceUnavailable(e.to_string()),
+            OrderError::VersionConflict(ref order) => ApiError::VersionConflict {
+                message: e.to_string(),
+                current: serde_json::to_value(order).unwrap_or_default(),
+            },
+            OrderError::Cursor(e) => e.into(),
+            OrderError::Merchant(e) => e.into(),
+            OrderError::Shipping(e) => e.into(),
+            OrderError::Inventory(e) => e.into(),
+            OrderError::Payment(e) => e.into(),
+            OrderError::Db(e) => e.into(),
+        }
+    }
+}
+
+impl From<DownloadError> for ApiError {
+    fn from(e: DownloadError) -> Self {
+        match e {
+            DownloadError::NotFound => ApiError::NotFound(e.to_string()),
+            DownloadError::Expired | DownloadError::LimitReached => ApiError::Forbidden(e.to_string()),
+            DownloadError::Db(e) => e.into(),
+        }
+    }
+}
+
+impl From<ColdStorageError> for ApiError {
+    fn from(e: ColdStorageError) -> Self {
+        match e {
+            ColdStorageError::NotFound => ApiError::NotFound(e.to_string()),
+            ColdStorageError::NotConfigured => ApiError::ServiceUnavailable(e.to_string()),
+            ColdStorageError::Storage(_) => ApiError::BadGateway(e.to_string()),
+            ColdStorageError::Corrupt(_) => ApiError::Internal(e.to_string()),
+            ColdStorageError::Db(e) => e.into(),
+        }
+    }
+}
+
+impl From<InvoiceError> for ApiError {
+    fn from(e: InvoiceError) -> Self {
+        match e {
+            InvoiceError::OrderNotFound => ApiError::NotFound(e.to_string()),
+            InvoiceError::InvalidBranding(message) => {
+                ApiError::Validation(vec![FieldError::new("accent_color", message)])
+            }
+            InvoiceError::Merchant(e) => e.into(),
+            InvoiceError::Db(e) => e.into(),
+        }
+    }
+}
+
+impl From<InventoryError> for ApiError {
+    fn from(e: InventoryError) -> Self {
+        match e {
+            InventoryError::UnknownSku(_) => ApiError::BadRequest(e.to_string()),
+            InventoryError::Insufficient { .. }
+            | InventoryError::DuplicateWarehouse(_)
+            | InventoryError::TransferClosed(_)
+            | InventoryError::PurchaseOrderStatus(_) => ApiError::Conflict(e.to_string()),
+            InventoryError::WarehouseNotFound
+            | InventoryError::TransferNotFound
+            | InventoryError::PurchaseOrderNotFound => ApiError::NotFound(e.to_string()),
+            InventoryError::InvalidTransfer(_) | InventoryError::InvalidPurchaseOrder(_) => {
+                ApiError::BadRequest(e.to_string())
+            }
+            InventoryError::Db(e) => e.into(),
+        }
+    }
+}
+
+impl From<CheckoutError> for ApiError {
+    fn from(e: CheckoutError) -> Self {
+        match e {
+            CheckoutError::EmptyCart | CheckoutError::InvalidItem { .. } => ApiError::BadRequest(e.to_string()),
+            CheckoutError::AlreadyCheckedOut(_) | CheckoutError::PriceChanged { .. } => ApiError::Conflict(e.to_string()),
+            CheckoutError::GuestEmailRequired => ApiError::Validation(vec![FieldError::new("email", "is required for guest checkout")]),
+            CheckoutError::SpendingLimit { .. } => ApiError::Forbidden(e.to_string()),
+            CheckoutError::Rejected(_) => ApiError::Validation(vec![FieldError::new("cart", e.to_string())]),
+            CheckoutError::Customer(e) => e.into(),
+            CheckoutError::Merchant(e) => e.into(),
+            CheckoutError::Pricing(e) => e.into(),
+            CheckoutError::Inventory(e) => e.into(),
+            CheckoutError::Coupon(e) => e.into(),
+            CheckoutError::Tax(e) => e.into(),
+            CheckoutError::GiftCard(e) => e.into(),
+            CheckoutError::Db(e) => e.into(),
+        }
+    }
+}
+
+impl From<ApprovalError> for ApiError {
+    fn from(e: ApprovalError) -> Self {
+        match e {
+            ApprovalError::OrderNotFound => ApiError::NotFound(e.to_string()),
+            ApprovalError::NotAwaitingApproval => ApiError::Conflict(e.to_string()),
+            ApprovalError::NotApprover => ApiError::Forbidden(e.to_string()),
+            ApprovalError::Customer(e) => e.into(),
+            ApprovalError::Db(e) => e.into(),
+        }
+    }
+}
+
+impl From<ReturnError> for ApiError {
+    fn from(e: ReturnError) -> Self {
+        match e {
+            ReturnError::NotFound => ApiError::NotFound(e.to_string()),
+            ReturnError::Order(e) => e.into(),
+            ReturnError::Empty | ReturnError::NotReturnable { .. } => ApiError::Validation(vec![FieldError::new("items", e.to_string())]),
+            ReturnError::InvalidState(_) => ApiError::Conflict(e.to_string()),
+            ReturnError::Inventory(e) => e.into(),
+            ReturnError::Payment(e) => e.into(),
+            ReturnError::Db(e) => e.into(),
+        }
+    }
+}
+
+impl From<RefundError> for ApiError {
+    fn from(e: RefundError) -> Self {
+        match e {
+            RefundError::Order(e) => e.into(),
+            RefundError::Empty | RefundError::NotRefundable { .. } => ApiError::Validation(vec![FieldError::new("items", e.to_string())]),
+            RefundError::NothingCaptured => ApiError::Conflict(e.to_string()),
+            RefundError::Payment(e) => e.into(),
+            RefundError::Db(e) => e.into(),
+        }
+    }
+}
+
+impl From<PaymentError> for ApiError {
+    fn from(e: PaymentError) -> Self {
+        match e {
+            PaymentError::Declined(_) => ApiError::PaymentRequired(e.to_string()),
+            PaymentError::InvalidAmount(_) => ApiError::BadRequest(e.to_string()),
+            PaymentError::Unsupported(_) => ApiError::NotImplemented(e.to_string()),
+            PaymentError::InvalidSignature => ApiError::BadRequest(e.to_string()),
+            _ => ApiError::BadGateway(e.to_string()),
+        }
+    }
+}
+
+impl From<OrderPaymentError> for ApiError {
+    fn from(e: OrderPaymentError) -> Self {
+        match e {
+            OrderPaymentError::NotFound => ApiError::NotFound(e.to_string()),
+            OrderPaymentError::InvalidState(_) | OrderPaymentError::NotApproved | OrderPaymentError::Cancelled => {
+                ApiError::Conflict(e.to_string())
+            }
+            OrderPaymentError::ExceedsAvailable { .. } => {
+                ApiError::Validation(vec![FieldError::new("amount", e.to_string())])
+            }
+            OrderPaymentError::Gateway(e) => e.into(),
+            OrderPaymentError::Merchant(e) => e.into(),
+            OrderPaymentError::Db(e) => e.into(),
+        }
+    }
+}
+
+impl From<ChannelError> for ApiError {
+    fn from(e: ChannelError) -> Self {
+        match e {
+            ChannelError::NotFound => ApiError::NotFound(e.to_string()),
+            ChannelError::AlreadyRegistered(_) => ApiError::Conflict(e.to_string()),
+            ChannelError::UnknownMarketplace(_) => ApiError::Validation(vec![FieldError::new("marketplace", e.to_string())]),
+            ChannelError::InvalidSettings(_) => ApiError::Validation(vec![FieldError::new("settings", e.to_string())]),
+            ChannelError::InvalidOrder(_) => ApiError::BadRequest(e.to_string()),
+            ChannelError::Marketplace(_) => ApiError::BadGateway(e.to_string()),
+            ChannelError::RateLimited { retry_after_secs } => ApiError::TooManyRequests {
+                message: e.to_string(),
+                retry_after: retry_after_secs.max(0) as u64,
+            },
+            ChannelError::Order(e) => e.into(),
+            ChannelError::Db(e) => e.into(),
+        }
+    }
+}
+
+impl From<MerchantError> for ApiError {
+    fn from(e: MerchantError) -> Self {
+        match e {
+            MerchantError::NotFound => ApiError::NotFound(e.to_string()),
+            MerchantError::DomainTaken(_) => ApiError::Conflict(e.to_string()),
+            MerchantError::InvalidDomain(_) => ApiError::Validation(vec![FieldError::new("sdomain", e.to_string())]),
+            MerchantError::UnknownSetting(ref key) => ApiError::Validation(vec![FieldError::new(key, "is not a setting")]),
+            MerchantError::InvalidSetting { ref key, ref reason } => ApiError::Validation(vec![FieldError::new(key, reason)]),
+            MerchantError::Tax(e) => e.into(),
+            MerchantError::Db(e) => e.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_body() {
+        let body = serde_json::to_value(ApiError::not_found("Customer").body()).unwrap();
+        assert_eq!(body, serde_json::json!({ "code": "not_found", "message": "Customer not found" }));
+
+        let err = ApiError::Validation(vec![FieldError::new("email", "must be a valid email address")]);
+        assert_eq!(err.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(serde_json::to_value(err.body()).unwrap()["errors"][0]["field"], "email");
+
+        // Internal details stay in the logs
+        let err = ApiError::from(DbErr::Custom("connection refused".to_string()));
+        assert_eq!(err.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(err.body().message, "Internal server error");
+    }
+
+    #[test]
+    fn test_service_error_statuses() {
+        assert_eq!(ApiError::from(OrderError::ItemNotFound).status(), StatusCode::NOT_FOUND);
+        assert_eq!(ApiError::from(RefreshError::Reused).status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            ApiError::from(CheckoutError::Inventory(InventoryError::Insufficient {
+                sku: "SKU001".to_string(),
+                requested: 2,
+                available: 1,
+            }))
+            .status(),
+            StatusCode::CONFLICT
+        );
+        assert_eq!(
+            ApiError::from(OrderPaymentError::Gateway(PaymentError::Declined("card_declined".to_string()))).status(),
+            StatusCode::PAYMENT_REQUIRED
+        );
+    }
+}
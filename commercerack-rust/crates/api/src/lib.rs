@@ -1,48 +1,589 @@
 //! Axum API server for CommerceRack with SeaORM, JWT, and OpenAPI
 
 use axum::{
-    extract::{Path, Query, State},
-    http::StatusCode,
-    routing::{get, post, put, delete},
-    Json, Router,
+    extract::FromRef,
+    http::{header, HeaderName, HeaderValue},
+    middleware,
+    routing::{get, post, put, delete, MethodRouter},
+    Router,
 };
-use commercerack_cart::CartStore;
-use sea_orm::DatabaseConnection;
-use std::sync::{Arc, Mutex};
+use commercerack_cart::{AbandonedCartService, CartStorage, DbCartStorage, MemoryCartStorage, RedisCartStorage};
+use commercerack_config::{
+    AllocationRule, ArchiveConfig, CartBackend, CartConfig, CatalogCacheBackend, CatalogCacheConfig, CorsConfig, EncryptionConfig, PaymentProvider, PaymentsConfig,
+    LabelBackend, RoundingRule, ShippingConfig, TaxConfig, TaxProvider,
+};
+use commercerack_core::{money, Currency, MoneySettings, Rounding};
+use commercerack_customer::pii::{self, Keyring};
+use commercerack_inventory::warehouses::AllocationStrategy;
+use commercerack_order::cold_storage::{ArchiveStore, S3Settings, S3Store};
+use commercerack_order::dunning::DunningService;
+use commercerack_payment::{PayPalGateway, PaymentGateway, StripeGateway};
+use commercerack_shipping::{LabelProvider, ShippingRateProvider, StubLabelProvider, TableRateProvider};
+use commercerack_tax::{RateTableCalculator, TaxCalculator};
+use commercerack_webhooks::{WebhookDispatcher, WebhookSubscriber};
+use migration::{Migrator, MigratorTrait};
+use catalog_cache::{CatalogCache, CatalogInvalidator, MemoryCatalogCache, RedisCatalogCache};
+use commercerack_events::EventHandler;
+use outbox::OutboxRelay;
+use sea_orm::{DatabaseConnection, DbErr};
+use std::sync::Arc;
+use std::time::Duration;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
+use tower_http::trace::TraceLayer;
+use tracing::info;
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 use utoipa_rapidoc::RapiDoc;
 
+pub use commercerack_config::AppConfig;
+
 pub mod auth;
+pub mod catalog_cache;
+pub mod client_ip;
+pub mod conditional;
+pub mod error;
+pub mod oauth;
+pub mod outbox;
 pub mod routes;
+pub mod telemetry;
+pub mod tenant;
+pub mod validation;
+pub mod versioning;
 
 /// API Documentation
 #[derive(OpenApi)]
 #[openapi(
     paths(
+        routes::auth::login,
+        routes::auth::register,
+        routes::auth::refresh,
+        routes::auth::logout,
+        routes::auth::unlock,
+        routes::two_factor::enroll,
+        routes::two_factor::confirm,
+        routes::two_factor::verify,
+        routes::two_factor::disable,
+        routes::two_factor::backup_codes,
+        routes::sessions::list,
+        routes::sessions::revoke,
+        routes::oauth::authorize,
+        routes::oauth::callback,
+        routes::oauth::callback_form,
         routes::customers::create,
         routes::customers::get,
+        routes::customers::list,
+        routes::customers::set_price_group,
+        routes::customers::set_group,
+        routes::companies::create,
+        routes::companies::list,
+        routes::companies::get,
+        routes::companies::update,
+        routes::companies::delete,
+        routes::companies::buyers,
+        routes::companies::set_buyer,
+        routes::companies::remove_buyer,
+        routes::companies::orders,
+        routes::companies::approvals,
+        routes::companies::approve,
+        routes::companies::reject,
+        routes::tags::list,
+        routes::tags::bulk_tag,
+        routes::tags::bulk_untag,
+        routes::tags::customer_tags,
+        routes::tags::add,
+        routes::tags::remove,
+        routes::segments::list,
+        routes::segments::create,
+        routes::segments::get,
+        routes::segments::update,
+        routes::segments::delete,
+        routes::segments::members,
+        routes::segments::rfm,
+        routes::segments::customer_segments,
+        routes::notes::list,
+        routes::notes::add,
+        routes::notes::delete,
+        routes::privacy::export,
+        routes::privacy::erasure,
+        routes::privacy::get,
+        routes::privacy::download,
+        routes::addresses::create,
+        routes::addresses::list,
+        routes::addresses::update,
+        routes::addresses::delete,
+        routes::addresses::set_default,
+        routes::wishlists::get,
+        routes::wishlists::add_item,
+        routes::wishlists::remove_item,
+        routes::wishlists::move_to_cart,
+        routes::wishlists::save_from_cart,
+        routes::wishlists::share,
+        routes::wishlists::unshare,
+        routes::wishlists::shared,
+        routes::wishlists::counts,
+        routes::saved_carts::list,
+        routes::saved_carts::create,
+        routes::saved_carts::rename,
+        routes::saved_carts::delete,
+        routes::saved_carts::activate,
+        routes::saved_carts::move_items,
         routes::products::create,
         routes::products::get,
+        routes::products::batch_get,
+        routes::bulk_price::update,
+        routes::products::list,
+        routes::products::search,
+        routes::products::export,
+        routes::catalog_changes::changes,
+        routes::recommendations::list,
+        routes::digital::set_delivery,
+        routes::availability::set_availability,
+        routes::delivery_estimates::product_estimate,
+        routes::digital::add_license_keys,
+        routes::digital::license_key_pool,
+        routes::digital::order_downloads,
+        routes::digital::download,
+        routes::invoices::invoice_pdf,
+        routes::invoices::credit_memos,
+        routes::invoices::credit_memo_pdf,
+        routes::invoices::get_branding,
+        routes::invoices::set_branding,
+        routes::media::list,
+        routes::media::add,
+        routes::media::reorder,
+        routes::media::set_primary,
+        routes::media::delete,
+        routes::categories::create,
+        routes::categories::tree,
+        routes::categories::get,
+        routes::categories::update,
+        routes::categories::move_category,
+        routes::categories::delete,
+        routes::categories::products,
+        routes::categories::product_categories,
+        routes::categories::assign_product,
+        routes::categories::unassign_product,
+        routes::skus::create,
+        routes::skus::list,
+        routes::skus::get,
+        routes::skus::update,
+        routes::skus::delete,
+        routes::pricing::set_tier,
+        routes::pricing::list_tiers,
+        routes::pricing::delete_tier,
         routes::orders::create,
         routes::orders::get,
+        routes::orders::batch_get,
+        routes::order_stream::order_events,
+        routes::order_stream::merchant_stream,
+        routes::orders::update,
+        routes::orders::edit,
+        routes::orders::cancel,
+        routes::orders::list,
+        routes::orders::search,
+        routes::orders::pools,
+        routes::archived_orders::list,
+        routes::archived_orders::get,
+        routes::orders::list_items,
+        routes::orders::add_item,
+        routes::orders::update_item,
+        routes::orders::remove_item,
+        routes::orders::list_shipments,
+        routes::orders::create_shipment,
+        routes::orders::void_label,
+        routes::orders::track_shipment,
+        routes::orders::pickup_ready,
+        routes::orders::pickup_confirm,
+        routes::store_locations::create,
+        routes::store_locations::list,
+        routes::store_locations::get,
+        routes::store_locations::update,
+        routes::cart::checkout,
+        routes::abandoned_carts::list,
+        routes::abandoned_carts::metrics,
+        routes::abandoned_carts::recover,
+        routes::stats::get,
+        routes::reports::list,
+        routes::reports::get,
+        routes::reports::download,
+        routes::audit::list,
+        routes::coupons::create,
+        routes::coupons::list,
+        routes::coupons::get,
+        routes::coupons::update,
+        routes::coupons::delete,
+        routes::giftcards::issue,
+        routes::giftcards::list,
+        routes::giftcards::get,
+        routes::giftcards::adjust,
+        routes::giftcards::void,
+        routes::giftcards::balance,
+        routes::inventory::adjust,
+        routes::inventory::history,
+        routes::inventory::low_stock,
+        routes::inventory::reserve,
+        routes::inventory::release,
+        routes::warehouses::create,
+        routes::warehouses::list,
+        routes::warehouses::update,
+        routes::warehouses::stock,
+        routes::warehouses::sku_stock,
+        routes::warehouses::allocations,
+        routes::transfers::create,
+        routes::transfers::list,
+        routes::transfers::get,
+        routes::transfers::receive,
+        routes::transfers::cancel,
+        routes::purchase_orders::create,
+        routes::purchase_orders::list,
+        routes::purchase_orders::get,
+        routes::purchase_orders::update,
+        routes::purchase_orders::send,
+        routes::purchase_orders::receive,
+        routes::payments::pay,
+        routes::payments::list_payments,
+        routes::payments::add_payment,
+        routes::payments::capture,
+        routes::payments::refund,
+        routes::payments::create_session,
+        routes::payments::webhook,
+        routes::refunds::quote_refund,
+        routes::refunds::create_refund,
+        routes::refunds::list_refunds,
+        routes::dunning::list_dunning,
+        routes::returns::create_return,
+        routes::returns::list_returns,
+        routes::returns::approve_return,
+        routes::returns::reject_return,
+        routes::returns::refund_return,
+        routes::cart::create_cart,
+        routes::cart::get_cart,
+        routes::cart::add_item,
+        routes::cart::update_quantity,
+        routes::cart::remove_item,
+        routes::cart::clear_cart,
+        routes::cart::delete_cart,
+        routes::cart::merge_cart,
+        routes::cart::estimate,
+        routes::cart::apply_coupon,
+        routes::cart::remove_coupon,
+        routes::cart::set_contact,
+        routes::cart::apply_gift_card,
+        routes::cart::remove_gift_card,
+        routes::cart::shipping_quotes,
+        routes::cart::shipments,
+        routes::cart_sync::sync_token,
+        routes::cart_sync::connect,
+        routes::shipping::create_zone,
+        routes::shipping::list_zones,
+        routes::shipping::delete_zone,
+        routes::shipping::add_rate,
+        routes::shipping::delete_rate,
+        routes::shipping::set_transit,
+        routes::shipping::delete_transit,
+        routes::tax::create_rate,
+        routes::tax::list_rates,
+        routes::tax::get_rate,
+        routes::tax::update_rate,
+        routes::tax::delete_rate,
+        routes::zones::list,
+        routes::zones::create,
+        routes::zones::get,
+        routes::zones::update,
+        routes::zones::delete,
+        routes::zones::resolve,
+        routes::webhooks::create,
+        routes::webhooks::list,
+        routes::webhooks::get,
+        routes::webhooks::update,
+        routes::webhooks::delete,
+        routes::webhooks::deliveries,
+        routes::channels::create,
+        routes::channels::list,
+        routes::channels::get,
+        routes::channels::update,
+        routes::channels::delete,
+        routes::channels::sync,
+        routes::channels::ingest,
+        routes::channels::reconciliation,
+        routes::merchants::onboard,
+        routes::merchants::list,
+        routes::merchants::get,
+        routes::merchants::update,
+        routes::merchants::close,
+        routes::merchants::get_settings,
+        routes::merchants::update_settings,
+        routes::merchants::reset_setting,
+        routes::merchants::store,
+        routes::health::legacy_live,
+        routes::health::live,
+        routes::health::ready,
     ),
     components(
         schemas(
             auth::Claims,
+            error::ErrorBody,
+            error::FieldError,
+            routes::auth::LoginRequest,
+            routes::auth::LoginResponse,
+            routes::auth::RegisterRequest,
+            routes::auth::RefreshRequest,
+            routes::auth::TokenResponse,
+            routes::auth::UnlockRequest,
+            routes::auth::TwoFactorChallenge,
+            routes::two_factor::CodeRequest,
+            routes::two_factor::VerifyRequest,
+            routes::two_factor::EnrollmentResponse,
+            routes::two_factor::BackupCodesResponse,
+            routes::sessions::LoginSessionResponse,
+            routes::oauth::CallbackParams,
             routes::customers::CreateCustomerRequest,
             routes::customers::CustomerResponse,
+            routes::customers::CustomerListResponse,
+            routes::customers::PriceGroupRequest,
+            routes::customers::CustomerGroupRequest,
+            routes::companies::CreateCompanyRequest,
+            routes::companies::UpdateCompanyRequest,
+            routes::companies::CompanyResponse,
+            routes::companies::BuyerRequest,
+            routes::companies::BuyerResponse,
+            routes::tags::TagCountResponse,
+            routes::tags::BulkTagRequest,
+            routes::tags::BulkTagResponse,
+            routes::tags::CustomerTagsResponse,
+            routes::segments::SegmentRuleRequest,
+            routes::segments::SegmentRequest,
+            routes::segments::CreateSegmentRequest,
+            routes::segments::SegmentRuleResponse,
+            routes::segments::SegmentResponse,
+            routes::segments::CustomerMetricsResponse,
+            routes::segments::SegmentMembersResponse,
+            routes::segments::RfmCountResponse,
+            routes::segments::CustomerSegmentsResponse,
+            routes::notes::NoteRequest,
+            routes::notes::NoteResponse,
+            routes::privacy::DataRequestResponse,
+            routes::addresses::AddressRequest,
+            routes::addresses::SetDefaultRequest,
+            routes::addresses::AddressResponse,
+            routes::wishlists::AddWishlistItemRequest,
+            routes::wishlists::MoveToCartRequest,
+            routes::wishlists::SaveFromCartRequest,
+            routes::wishlists::WishlistItemResponse,
+            routes::wishlists::WishlistResponse,
+            routes::wishlists::SharedWishlistResponse,
+            routes::wishlists::ShareResponse,
+            routes::wishlists::WishlistCountResponse,
+            routes::saved_carts::CreateSavedCartRequest,
+            routes::saved_carts::RenameSavedCartRequest,
+            routes::saved_carts::MoveItemsRequest,
+            routes::saved_carts::SavedCartResponse,
+            routes::saved_carts::MoveItemsResponse,
             routes::products::CreateProductRequest,
             routes::products::ProductResponse,
+            routes::products::ProductBatchResponse,
+            routes::batch::BatchGetRequest,
+            routes::batch::BatchGetError,
+            routes::bulk_price::BulkPriceUpdateRequest,
+            routes::bulk_price::PriceChangeRequest,
+            routes::bulk_price::CategoryAdjustmentRequest,
+            routes::bulk_price::BulkPriceUpdateResponse,
+            routes::bulk_price::RepricedResponse,
+            routes::products::ProductListResponse,
+            routes::catalog_changes::CatalogChangeResponse,
+            routes::catalog_changes::CatalogChangesResponse,
+            routes::recommendations::RecommendationsResponse,
+            routes::products::ProductSearchResponse,
+            routes::digital::DeliveryRequest,
+            routes::digital::DeliveryResponse,
+            routes::availability::AvailabilityRequest,
+            routes::availability::AvailabilityResponse,
+            routes::delivery_estimates::DeliveryEstimateResponse,
+            routes::delivery_estimates::DeliveryWindowResponse,
+            routes::digital::AddLicenseKeysRequest,
+            routes::digital::KeyPoolResponse,
+            routes::digital::DownloadResponse,
+            routes::digital::LicenseKeyResponse,
+            routes::digital::DigitalDeliveryResponse,
+            routes::invoices::BrandingRequest,
+            routes::invoices::BrandingResponse,
+            routes::invoices::InvoiceResponse,
+            routes::media::AddMediaRequest,
+            routes::media::ReorderMediaRequest,
+            routes::media::MediaResponse,
+            routes::categories::CreateCategoryRequest,
+            routes::categories::UpdateCategoryRequest,
+            routes::categories::MoveCategoryRequest,
+            routes::categories::CategoryResponse,
+            routes::categories::CategoryTreeResponse,
+            routes::skus::SkuRequest,
+            routes::skus::SkuResponse,
+            routes::pricing::PriceTierRequest,
+            routes::pricing::PriceTierResponse,
             routes::orders::CreateOrderRequest,
             routes::orders::OrderResponse,
+            routes::orders::FulfillmentGroupResponse,
+            routes::orders::FulfillmentGroupItemResponse,
+            routes::orders::OrderBatchResponse,
+            routes::order_stream::OrderStatusEvent,
+            routes::orders::OrderListResponse,
+            routes::orders::PoolCountResponse,
+            routes::archived_orders::ArchivedOrderResponse,
+            routes::archived_orders::ColdOrderResponse,
+            routes::orders::UpdateOrderRequest,
+            routes::orders::EditOrderRequest,
+            routes::orders::CancelOrderRequest,
+            routes::orders::OrderEditRequest,
+            routes::orders::OrderItemRequest,
+            routes::orders::UpdateOrderItemRequest,
+            routes::orders::OrderItemResponse,
+            routes::orders::ShipmentRequest,
+            routes::orders::ShipmentItemRequest,
+            routes::orders::ShipmentResponse,
+            routes::orders::ShipmentLabelRequest,
+            routes::orders::LabelAddressRequest,
+            routes::orders::ShipmentLabelResponse,
+            routes::orders::TrackingResponse,
+            routes::orders::ShipmentItemResponse,
+            routes::cart::AddItemRequest,
+            routes::cart::MergeCartRequest,
+            routes::cart::EstimateRequest,
+            routes::cart::EstimateResponse,
+            routes::cart::ItemOptionsRequest,
+            routes::cart::UpdateQuantityRequest,
+            routes::cart::ApplyCouponRequest,
+            routes::cart::CartContactRequest,
+            routes::cart::ApplyGiftCardRequest,
+            routes::cart::CartResponse,
+            routes::cart::CheckoutRequest,
+            routes::cart::ShipTo,
+            routes::abandoned_carts::AbandonedCartResponse,
+            routes::abandoned_carts::AbandonmentMetricsResponse,
+            routes::stats::StatsResponse,
+            routes::stats::TopProductResponse,
+            routes::reports::ReportResponse,
+            routes::audit::AuditEntryResponse,
+            routes::coupons::CouponRequest,
+            routes::coupons::CreateCouponRequest,
+            routes::coupons::CouponResponse,
+            routes::giftcards::IssueGiftCardRequest,
+            routes::giftcards::AdjustGiftCardRequest,
+            routes::giftcards::VoidGiftCardRequest,
+            routes::giftcards::BalanceRequest,
+            routes::giftcards::GiftCardResponse,
+            routes::giftcards::GiftCardTransactionResponse,
+            routes::giftcards::GiftCardDetailResponse,
+            routes::giftcards::BalanceResponse,
+            routes::inventory::AdjustInventoryRequest,
+            routes::inventory::AdjustmentResponse,
+            routes::inventory::LowStockResponse,
+            routes::inventory::ReserveRequest,
+            routes::inventory::ReservationResponse,
+            routes::warehouses::WarehouseRequest,
+            routes::warehouses::CreateWarehouseRequest,
+            routes::warehouses::WarehouseResponse,
+            routes::warehouses::WarehouseStockResponse,
+            routes::warehouses::AllocationResponse,
+            routes::store_locations::StoreLocationRequest,
+            routes::store_locations::CreateStoreLocationRequest,
+            routes::store_locations::StoreLocationResponse,
+            routes::transfers::TransferItemRequest,
+            routes::transfers::CreateTransferRequest,
+            routes::transfers::TransferItemResponse,
+            routes::transfers::TransferResponse,
+            routes::purchase_orders::PurchaseOrderItemRequest,
+            routes::purchase_orders::PurchaseOrderRequest,
+            routes::purchase_orders::CreatePurchaseOrderRequest,
+            routes::purchase_orders::ReceiveItemRequest,
+            routes::purchase_orders::ReceivePurchaseOrderRequest,
+            routes::purchase_orders::PurchaseOrderItemResponse,
+            routes::purchase_orders::PurchaseOrderResponse,
+            routes::payments::PayRequest,
+            routes::payments::AddPaymentRequest,
+            routes::payments::PaymentResponse,
+            routes::payments::OrderPaymentsResponse,
+            routes::payments::RefundRequest,
+            routes::payments::SessionRequest,
+            routes::payments::SessionResponse,
+            routes::refunds::OrderRefundRequest,
+            routes::refunds::OrderRefundItemRequest,
+            routes::refunds::OrderRefundItemResponse,
+            routes::refunds::RefundQuoteResponse,
+            routes::refunds::OrderRefundResponse,
+            routes::dunning::DunningCaseResponse,
+            routes::returns::ReturnRequest,
+            routes::returns::ReturnItemRequest,
+            routes::returns::ApproveReturnRequest,
+            routes::returns::ReturnRefundRequest,
+            routes::returns::ReturnResponse,
+            routes::returns::ReturnItemResponse,
+            routes::cart::ShippingQuoteRequest,
+            routes::cart::ShippingQuoteResponse,
+            routes::cart::PlannedShipmentResponse,
+            routes::cart::PlannedShipmentItemResponse,
+            routes::cart::ShipmentMethodRequest,
+            routes::cart_sync::CartTokenResponse,
+            routes::shipping::ZoneRequest,
+            routes::shipping::RateRequest,
+            routes::shipping::ZoneResponse,
+            routes::shipping::RateResponse,
+            routes::shipping::TransitRequest,
+            routes::shipping::TransitResponse,
+            routes::tax::TaxRateRequest,
+            routes::tax::CreateTaxRateRequest,
+            routes::tax::TaxRateResponse,
+            routes::zones::GeoZoneRequest,
+            routes::zones::CreateGeoZoneRequest,
+            routes::zones::GeoZoneResponse,
+            routes::zones::ResolvedZoneResponse,
+            routes::webhooks::CreateEndpointRequest,
+            routes::webhooks::UpdateEndpointRequest,
+            routes::webhooks::EndpointResponse,
+            routes::webhooks::DeliveryResponse,
+            routes::channels::CreateChannelRequest,
+            routes::channels::UpdateChannelRequest,
+            routes::channels::ChannelResponse,
+            routes::channels::StockMismatchResponse,
+            routes::channels::ReconciliationResponse,
+            routes::merchants::OnboardMerchantRequest,
+            routes::merchants::UpdateMerchantRequest,
+            routes::merchants::MerchantResponse,
+            routes::merchants::OnboardedResponse,
+            routes::merchants::StoreResponse,
+            routes::merchants::UpdateSettingsRequest,
+            routes::merchants::SettingsResponse,
+            routes::health::DependencyStatus,
+            routes::health::ReadinessResponse,
         )
     ),
     tags(
+        (name = "auth", description = "Login and token management endpoints"),
         (name = "customers", description = "Customer management endpoints"),
+        (name = "companies", description = "B2B company accounts, their buyers and order approval"),
+        (name = "wishlists", description = "Customer wishlists, sharing and per-product counts"),
         (name = "products", description = "Product catalog endpoints"),
+        (name = "pricing", description = "Quantity-break and customer-group price tiers"),
+        (name = "categories", description = "Category tree and product assignment endpoints"),
         (name = "orders", description = "Order management endpoints"),
         (name = "cart", description = "Shopping cart endpoints"),
+        (name = "saved_carts", description = "A customer's named carts, which one is active, and moving items between them"),
+        (name = "abandoned_carts", description = "Abandoned cart recovery and metrics"),
+        (name = "stats", description = "Sales figures for merchant dashboards"),
+        (name = "reports", description = "Scheduled daily, weekly and monthly sales reports"),
+        (name = "audit", description = "Who changed prices, orders, refunds and customers"),
+        (name = "coupons", description = "Coupon management endpoints"),
+        (name = "giftcards", description = "Gift card issuing, adjustment and balance lookup"),
+        (name = "inventory", description = "Stock levels, reservations, adjustments, warehouses, transfers and purchase orders"),
+        (name = "payments", description = "Order payment endpoints"),
+        (name = "returns", description = "Return authorization (RMA) endpoints"),
+        (name = "shipping", description = "Shipping zone and rate management endpoints"),
+        (name = "tax", description = "Tax rate management endpoints"),
+        (name = "zones", description = "Geo zones shared by shipping zones and tax rates"),
+        (name = "webhooks", description = "Outbound webhook endpoints"),
+        (name = "channels", description = "Marketplace channels, their order, stock and tracking syncs and stock reconciliation"),
+        (name = "merchants", description = "Merchant onboarding and settings, and store lookup by domain"),
+        (name = "health", description = "Liveness and readiness probes"),
     ),
     security(
         ("bearer" = [])
@@ -53,33 +594,441 @@ pub struct ApiDoc;
 #[derive(Clone)]
 pub struct AppState {
     pub db: Arc<DatabaseConnection>,
-    pub cart_store: Arc<Mutex<CartStore>>,
+    pub cart_store: Arc<dyn CartStorage>,
+    /// `None` when no payment gateway is configured
+    pub payments: Option<Arc<dyn PaymentGateway>>,
+    /// `None` when orders are not taxed
+    pub tax: Option<Arc<dyn TaxCalculator>>,
+    /// Every provider is asked for quotes; empty when nothing ships
+    pub shipping: Vec<Arc<dyn ShippingRateProvider>>,
+    /// `None` when shipping labels are not bought through the API
+    pub labels: Option<Arc<dyn LabelProvider>>,
+    pub config: Arc<AppConfig>,
+}
+
+#[cfg(test)]
+impl AppState {
+    /// State over `db` with default config and no gateway, tax, shipping or
+    /// label providers; tests override what they need
+    pub(crate) fn mock(db: DatabaseConnection) -> Self {
+        Self {
+            db: Arc::new(db),
+            cart_store: Arc::new(MemoryCartStorage::new()),
+            payments: None,
+            tax: None,
+            shipping: Vec::new(),
+            labels: None,
+            config: Arc::new(AppConfig::default()),
+        }
+    }
+}
+
+impl FromRef<AppState> for Arc<AppConfig> {
+    fn from_ref(state: &AppState) -> Self {
+        state.config.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<DatabaseConnection> {
+    fn from_ref(state: &AppState) -> Self {
+        state.db.clone()
+    }
+}
+
+/// How often expired Redis carts are archived as abandoned
+const CART_SWEEP_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// How often database carts are checked for having gone idle
+const IDLE_CART_SCAN_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// How often queued webhooks are delivered
+const WEBHOOK_DELIVERY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How often events recorded in the outbox are relayed
+const OUTBOX_RELAY_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How often declined payments due a retry are charged again
+const DUNNING_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Open the configured cart storage backend
+fn cart_storage(config: &CartConfig, db: &Arc<DatabaseConnection>) -> Arc<dyn CartStorage> {
+    match config.backend {
+        CartBackend::Memory => Arc::new(MemoryCartStorage::new()),
+        CartBackend::Redis => {
+            let store = Arc::new(RedisCartStorage::open(&config.redis_url).expect("invalid cart.redis_url"));
+            store.clone().spawn_sweeper(db.clone(), CART_SWEEP_INTERVAL);
+            store
+        }
+        CartBackend::Database => {
+            if config.abandoned_after_hours > 0 {
+                let idle = Duration::from_secs(config.abandoned_after_hours as u64 * 60 * 60);
+                AbandonedCartService::spawn_detector(db.clone(), idle, IDLE_CART_SCAN_INTERVAL);
+            }
+            Arc::new(DbCartStorage::new(db.clone()))
+        }
+    }
+}
+
+/// Build the configured payment gateway. Returns `None` when it has no
+/// credentials.
+fn payment_gateway(config: &PaymentsConfig) -> Option<Arc<dyn PaymentGateway>> {
+    match config.gateway {
+        PaymentProvider::PayPal => {
+            let paypal = &config.paypal;
+            let (client_id, client_secret) = (paypal.client_id.as_ref()?, paypal.client_secret.as_ref()?);
+            let gateway = match &paypal.api_base {
+                Some(base_url) => PayPalGateway::with_base_url(client_id, client_secret, base_url),
+                None => PayPalGateway::new(client_id, client_secret),
+            };
+            let gateway = match &paypal.webhook_id {
+                Some(webhook_id) => gateway.with_webhook_id(webhook_id),
+                None => gateway,
+            };
+            Some(Arc::new(gateway))
+        }
+        PaymentProvider::Stripe => {
+            let stripe = &config.stripe;
+            let secret_key = stripe.secret_key.as_ref()?;
+            Some(Arc::new(match &stripe.api_base {
+                Some(base_url) => StripeGateway::with_base_url(secret_key, base_url),
+                None => StripeGateway::new(secret_key),
+            }))
+        }
+    }
 }
 
-/// Build the Axum router with all routes and OpenAPI documentation
-pub fn app(db: DatabaseConnection) -> Router {
-    let cart_store = Arc::new(Mutex::new(CartStore::new()));
+/// Build the configured tax calculator, `None` when orders are not taxed
+fn tax_calculator(config: &TaxConfig, db: &Arc<DatabaseConnection>) -> Option<Arc<dyn TaxCalculator>> {
+    match config.provider {
+        TaxProvider::None => None,
+        TaxProvider::RateTable => Some(Arc::new(RateTableCalculator::new(db.clone()))),
+    }
+}
+
+/// Build the configured label provider, `None` when labels are not bought
+fn label_provider(config: &ShippingConfig) -> Option<Arc<dyn LabelProvider>> {
+    match config.labels {
+        LabelBackend::None => None,
+        LabelBackend::Stub => Some(Arc::new(StubLabelProvider::new())),
+    }
+}
+
+/// The bucket archived orders are moved to, if one is configured
+fn archive_store(config: &ArchiveConfig) -> Option<Arc<dyn ArchiveStore>> {
+    Some(Arc::new(S3Store::new(S3Settings {
+        endpoint: config.endpoint.clone(),
+        region: config.region.clone(),
+        bucket: config.bucket.clone()?,
+        access_key_id: config.access_key_id.clone()?,
+        secret_access_key: config.secret_access_key.clone()?,
+    })))
+}
+
+/// Open the configured catalog cache, `None` when it is off
+fn catalog_cache(config: &CatalogCacheConfig) -> Option<Arc<dyn CatalogCache>> {
+    let ttl = Duration::from_secs(config.ttl_seconds);
+    match config.backend {
+        CatalogCacheBackend::Off => None,
+        CatalogCacheBackend::Memory => Some(Arc::new(MemoryCatalogCache::new(config.max_entries, ttl))),
+        CatalogCacheBackend::Redis => Some(Arc::new(
+            RedisCatalogCache::open(&config.redis_url, ttl).expect("invalid catalog_cache.redis_url"),
+        )),
+    }
+}
+
+/// The keyring for customer PII, if encryption is configured. `config` is
+/// expected to have passed [`AppConfig::validate`].
+pub fn pii_keyring(config: &EncryptionConfig) -> Option<Keyring> {
+    let active = config.active_key.as_deref()?;
+    let key = |id: &str| config.key(id).expect("invalid encryption key");
+    let mut keyring = Keyring::new(active, key(active)).expect("invalid encryption.active_key");
+    for id in config.keys.keys().filter(|id| *id != active) {
+        keyring = keyring.with_retired_key(id, key(id)).expect("invalid encryption key id");
+    }
+    Some(keyring.with_email_encryption(config.encrypt_email))
+}
+
+/// Allow browsers on the configured origins to call the API
+fn cors_layer(config: &CorsConfig) -> CorsLayer {
+    let origins = &config.allowed_origins;
+    let allow_origin = if origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(origins.iter().filter_map(|origin| origin.parse::<HeaderValue>().ok()))
+    };
+
+    CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods(Any)
+        .allow_headers(Any)
+        .expose_headers([
+            HeaderName::from_static(telemetry::REQUEST_ID_HEADER),
+            HeaderName::from_static(routes::customers::TOTAL_COUNT_HEADER),
+            HeaderName::from_static(versioning::DEPRECATION_HEADER),
+            HeaderName::from_static(versioning::SUNSET_HEADER),
+            header::LINK,
+            header::ETAG,
+        ])
+}
+
+/// Apply pending migrations if `database.auto_migrate` is set. Call before
+/// [`app`] so the schema is current when requests arrive.
+pub async fn auto_migrate(db: &DatabaseConnection, config: &AppConfig) -> Result<(), DbErr> {
+    if !config.database.auto_migrate {
+        return Ok(());
+    }
+    info!("🗄️ Applying pending migrations");
+    Migrator::up(db, None).await
+}
+
+/// Build the Axum router with all routes and OpenAPI documentation.
+/// `config` is expected to have passed [`AppConfig::validate`]. Deployments
+/// with their own checkout stages install their pipeline with
+/// [`commercerack_order::pipeline::install`] first.
+pub fn app(db: DatabaseConnection, config: AppConfig) -> Router {
+    if let Some(keyring) = pii_keyring(&config.encryption) {
+        pii::install(keyring);
+    }
+    commercerack_order::duplicates::configure(config.orders.duplicate_window_minutes);
+    if let Some(store) = archive_store(&config.archive) {
+        commercerack_order::cold_storage::install(store);
+    }
+    commercerack_order::digital::configure(commercerack_order::digital::DownloadSettings {
+        ttl_hours: config.digital.download_ttl_hours,
+        max_downloads: config.digital.download_limit,
+    });
+    commercerack_inventory::warehouses::configure(match config.inventory.allocation {
+        AllocationRule::Nearest => AllocationStrategy::Nearest,
+        AllocationRule::MostStock => AllocationStrategy::MostStock,
+    });
+    money::configure(MoneySettings {
+        currency: Currency::new(&config.money.currency).expect("money.currency is validated on load"),
+        rounding: match config.money.rounding {
+            RoundingRule::Bankers => Rounding::Bankers,
+            RoundingRule::HalfUp => Rounding::HalfUp,
+        },
+    });
+    let db = Arc::new(db);
+    Arc::new(WebhookDispatcher::new()).spawn(db.clone(), WEBHOOK_DELIVERY_INTERVAL);
+    let catalog = catalog_cache(&config.catalog_cache);
+    let mut handlers: Vec<Arc<dyn EventHandler>> = vec![Arc::new(WebhookSubscriber::new(db.clone()))];
+    if let Some(cache) = &catalog {
+        handlers.push(Arc::new(CatalogInvalidator::new(cache.clone())));
+    }
+    Arc::new(OutboxRelay::new(handlers)).spawn(db.clone(), OUTBOX_RELAY_INTERVAL);
+    let payments = payment_gateway(&config.payments);
+    if let Some(gateway) = &payments {
+        DunningService::spawn_runner(db.clone(), gateway.clone(), DUNNING_INTERVAL);
+    }
+    // Public catalog reads are served from the cache when one is configured
+    let cached = |route: MethodRouter<AppState>| match &catalog {
+        Some(cache) => route.route_layer(middleware::from_fn_with_state(cache.clone(), catalog_cache::respond)),
+        None => route,
+    };
+
     let state = AppState {
-        db: Arc::new(db),
-        cart_store: cart_store.clone(),
+        cart_store: cart_storage(&config.cart, &db),
+        payments,
+        tax: tax_calculator(&config.tax, &db),
+        shipping: vec![Arc::new(TableRateProvider::new(db.clone()))],
+        labels: label_provider(&config.shipping),
+        db,
+        config: Arc::new(config),
     };
+    let cors = cors_layer(&state.config.cors);
+    let versioning = versioning::Versioning::new(&state.config.api);
 
-    Router::new()
+    let routes = Router::new()
         // OpenAPI documentation
         .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi()))
         .merge(RapiDoc::new("/api-docs/openapi.json").path("/rapidoc"))
+        // Auth routes
+        .route("/api/auth/login", post(routes::auth::login))
+        .route("/api/auth/register", post(routes::auth::register))
+        .route("/api/auth/refresh", post(routes::auth::refresh))
+        .route("/api/auth/logout", post(routes::auth::logout))
+        .route("/api/auth/unlock", post(routes::auth::unlock))
+        .route("/api/auth/2fa/enroll", post(routes::two_factor::enroll))
+        .route("/api/auth/2fa/confirm", post(routes::two_factor::confirm))
+        .route("/api/auth/2fa/verify", post(routes::two_factor::verify))
+        .route("/api/auth/2fa/disable", post(routes::two_factor::disable))
+        .route("/api/auth/2fa/backup-codes", post(routes::two_factor::backup_codes))
+        .route("/api/auth/sessions", get(routes::sessions::list))
+        .route("/api/auth/sessions/:id", delete(routes::sessions::revoke))
+        .route("/api/auth/oauth/:provider/authorize", get(routes::oauth::authorize))
+        .route("/api/auth/oauth/:provider/callback", get(routes::oauth::callback).post(routes::oauth::callback_form))
         // Customer routes
         .route("/api/customers", post(routes::customers::create))
         .route("/api/customers/:mid/:id", get(routes::customers::get))
         .route("/api/customers", get(routes::customers::list))
+        .route("/api/customers/:mid/:id/price-group", put(routes::customers::set_price_group))
+        .route("/api/customers/:mid/:id/group", put(routes::customers::set_group))
+        .route("/api/customers/:mid/:id/tags", get(routes::tags::customer_tags))
+        .route("/api/customers/:mid/:id/tags/:tag", put(routes::tags::add).delete(routes::tags::remove))
+        .route("/api/customers/:mid/:id/notes", get(routes::notes::list).post(routes::notes::add))
+        .route("/api/customers/:mid/:id/notes/:note_id", delete(routes::notes::delete))
+        .route("/api/customer-tags", get(routes::tags::list))
+        .route("/api/customer-tags/tag", post(routes::tags::bulk_tag))
+        .route("/api/customer-tags/untag", post(routes::tags::bulk_untag))
+        .route("/api/customers/:mid/:id/segments", get(routes::segments::customer_segments))
+        .route("/api/segments", get(routes::segments::list).post(routes::segments::create))
+        .route("/api/segments/rfm", get(routes::segments::rfm))
+        .route("/api/segments/:mid/:id", get(routes::segments::get).put(routes::segments::update).delete(routes::segments::delete))
+        .route("/api/segments/:mid/:id/customers", get(routes::segments::members))
+        .route("/api/customers/:mid/:id/data-export", post(routes::privacy::export))
+        .route("/api/customers/:mid/:id/erasure", post(routes::privacy::erasure))
+        .route("/api/customers/:mid/:id/data-requests/:request_id", get(routes::privacy::get))
+        .route("/api/customers/:mid/:id/data-requests/:request_id/download", get(routes::privacy::download))
+        // Company account routes
+        .route("/api/companies", post(routes::companies::create).get(routes::companies::list))
+        .route("/api/companies/:mid/:id", get(routes::companies::get).put(routes::companies::update).delete(routes::companies::delete))
+        .route("/api/companies/:mid/:id/buyers", get(routes::companies::buyers))
+        .route("/api/companies/:mid/:id/buyers/:cid", put(routes::companies::set_buyer).delete(routes::companies::remove_buyer))
+        .route("/api/companies/:mid/:id/orders", get(routes::companies::orders))
+        .route("/api/companies/:mid/:id/approvals", get(routes::companies::approvals))
+        .route("/api/orders/:mid/:id/approve", post(routes::companies::approve))
+        .route("/api/orders/:mid/:id/reject", post(routes::companies::reject))
+        // Customer address book routes
+        .route("/api/customers/:mid/:id/addresses", post(routes::addresses::create))
+        .route("/api/customers/:mid/:id/addresses", get(routes::addresses::list))
+        .route("/api/customers/:mid/:id/addresses/:addr_id", put(routes::addresses::update))
+        .route("/api/customers/:mid/:id/addresses/:addr_id", delete(routes::addresses::delete))
+        .route("/api/customers/:mid/:id/addresses/:addr_id/default", post(routes::addresses::set_default))
+        // Wishlist routes
+        .route("/api/customers/:mid/:id/recommendations", get(routes::recommendations::list))
+        .route("/api/customers/:mid/:id/wishlist", get(routes::wishlists::get))
+        .route("/api/customers/:mid/:id/wishlist/items", post(routes::wishlists::add_item))
+        .route("/api/customers/:mid/:id/wishlist/items/:sku", delete(routes::wishlists::remove_item))
+        .route("/api/customers/:mid/:id/wishlist/items/:sku/move-to-cart", post(routes::wishlists::move_to_cart))
+        .route("/api/customers/:mid/:id/wishlist/from-cart", post(routes::wishlists::save_from_cart))
+        .route("/api/customers/:mid/:id/wishlist/share", post(routes::wishlists::share).delete(routes::wishlists::unshare))
+        .route("/api/wishlists/shared/:token", get(routes::wishlists::shared))
+        .route("/api/wishlists/counts", get(routes::wishlists::counts))
+        .route("/api/customers/:mid/:id/carts", get(routes::saved_carts::list).post(routes::saved_carts::create))
+        .route("/api/customers/:mid/:id/carts/:cart_id", put(routes::saved_carts::rename).delete(routes::saved_carts::delete))
+        .route("/api/customers/:mid/:id/carts/:cart_id/activate", post(routes::saved_carts::activate))
+        .route("/api/customers/:mid/:id/carts/:cart_id/move", post(routes::saved_carts::move_items))
         // Product routes
         .route("/api/products", post(routes::products::create))
-        .route("/api/products/:mid/:id", get(routes::products::get))
+        .route("/api/products/search", get(routes::products::search))
+        .route("/api/products/batch-get", post(routes::products::batch_get))
+        .route("/api/products/bulk-price-update", post(routes::bulk_price::update))
+        .route("/api/products/export", get(routes::products::export))
+        .route("/api/products/changes", get(routes::catalog_changes::changes))
+        .route("/api/products/:mid/:id", cached(get(routes::products::get)).route_layer(middleware::from_fn(conditional::etag)))
         .route("/api/products", get(routes::products::list))
+        .route("/api/products/:mid/:id/delivery", put(routes::digital::set_delivery))
+        .route("/api/products/:mid/:id/availability", put(routes::availability::set_availability))
+        .route("/api/products/:mid/:id/delivery-estimate", get(routes::delivery_estimates::product_estimate))
+        .route("/api/license-keys", post(routes::digital::add_license_keys))
+        .route("/api/license-keys/:mid/:sku", get(routes::digital::license_key_pool))
+        .route("/api/products/:mid/:id/media", get(routes::media::list))
+        .route("/api/products/:mid/:id/media", post(routes::media::add))
+        .route("/api/products/:mid/:id/media/order", put(routes::media::reorder))
+        .route("/api/products/:mid/:id/media/:media_id/primary", post(routes::media::set_primary))
+        .route("/api/products/:mid/:id/media/:media_id", delete(routes::media::delete))
+        .route("/api/products/:mid/:id/categories", get(routes::categories::product_categories))
+        .route("/api/products/:mid/:id/categories/:category_id", put(routes::categories::assign_product))
+        .route("/api/products/:mid/:id/categories/:category_id", delete(routes::categories::unassign_product))
+        // Category routes
+        .route("/api/categories", post(routes::categories::create))
+        .route("/api/categories", cached(get(routes::categories::tree)))
+        .route("/api/categories/:mid/:id", cached(get(routes::categories::get)))
+        .route("/api/categories/:mid/:id", put(routes::categories::update))
+        .route("/api/categories/:mid/:id", delete(routes::categories::delete))
+        .route("/api/categories/:mid/:id/move", post(routes::categories::move_category))
+        .route("/api/categories/:mid/:id/products", cached(get(routes::categories::products)))
+        // SKU routes (product variants)
+        .route("/api/products/:mid/:id/skus", post(routes::skus::create))
+        .route("/api/products/:mid/:id/skus", get(routes::skus::list))
+        .route("/api/products/:mid/:id/skus/:sku_id", get(routes::skus::get))
+        .route("/api/products/:mid/:id/skus/:sku_id", put(routes::skus::update))
+        .route("/api/products/:mid/:id/skus/:sku_id", delete(routes::skus::delete))
+        // Price tier routes
+        .route("/api/price-tiers", post(routes::pricing::set_tier).get(routes::pricing::list_tiers))
+        .route("/api/price-tiers/:mid/:id", delete(routes::pricing::delete_tier))
         // Order routes
         .route("/api/orders", post(routes::orders::create))
-        .route("/api/orders/:mid/:id", get(routes::orders::get))
+        .route("/api/orders/:mid/:id", get(routes::orders::get).route_layer(middleware::from_fn(conditional::etag)).put(routes::orders::update))
+        .route("/api/orders/:mid/:id/events", get(routes::order_stream::order_events))
+        .route("/api/merchants/:mid/order-stream", get(routes::order_stream::merchant_stream))
         .route("/api/orders", get(routes::orders::list))
+        .route("/api/orders/search", get(routes::orders::search))
+        .route("/api/orders/batch-get", post(routes::orders::batch_get))
+        .route("/api/orders/pools", get(routes::orders::pools))
+        .route("/api/archived-orders", get(routes::archived_orders::list))
+        .route("/api/archived-orders/:mid/:id", get(routes::archived_orders::get))
+        .route("/api/orders/:mid/:id/items", get(routes::orders::list_items))
+        .route("/api/orders/:mid/:id/items", post(routes::orders::add_item))
+        .route("/api/orders/:mid/:id/items/:item_id", put(routes::orders::update_item))
+        .route("/api/orders/:mid/:id/items/:item_id", delete(routes::orders::remove_item))
+        .route("/api/orders/:mid/:id/edits", post(routes::orders::edit))
+        .route("/api/orders/:mid/:id/cancel", post(routes::orders::cancel))
+        .route("/api/orders/:mid/:id/shipments", get(routes::orders::list_shipments))
+        .route("/api/orders/:mid/:id/shipments", post(routes::orders::create_shipment))
+        .route("/api/orders/:mid/:id/shipments/:shipment_id/label", delete(routes::orders::void_label))
+        .route("/api/orders/:mid/:id/shipments/:shipment_id/tracking", get(routes::orders::track_shipment))
+        .route("/api/orders/:mid/:id/pickup/ready", post(routes::orders::pickup_ready))
+        .route("/api/orders/:mid/:id/pickup/confirm", post(routes::orders::pickup_confirm))
+        .route("/api/store-locations", post(routes::store_locations::create).get(routes::store_locations::list))
+        .route("/api/store-locations/:mid/:id", get(routes::store_locations::get).put(routes::store_locations::update))
+        .route("/api/orders/:mid/:id/allocations", get(routes::warehouses::allocations))
+        .route("/api/orders/:mid/:id/downloads", get(routes::digital::order_downloads))
+        .route("/api/downloads/:token", get(routes::digital::download))
+        .route("/api/orders/:mid/:id/invoice.pdf", get(routes::invoices::invoice_pdf))
+        .route("/api/orders/:mid/:id/credit-memos", get(routes::invoices::credit_memos))
+        .route("/api/orders/:mid/:id/credit-memos/:memo_id", get(routes::invoices::credit_memo_pdf))
+        .route("/api/invoice-branding/:mid", get(routes::invoices::get_branding).put(routes::invoices::set_branding))
+        .route("/api/orders/:mid/:id/pay", post(routes::payments::pay))
+        .route("/api/orders/:mid/:id/payments", get(routes::payments::list_payments).post(routes::payments::add_payment))
+        .route("/api/orders/:mid/:id/capture", post(routes::payments::capture))
+        .route("/api/orders/:mid/:id/refund", post(routes::payments::refund))
+        .route("/api/orders/:mid/:id/payment-session", post(routes::payments::create_session))
+        .route("/api/payments/webhook", post(routes::payments::webhook))
+        .route("/api/orders/:mid/:id/refunds/quote", post(routes::refunds::quote_refund))
+        .route("/api/orders/:mid/:id/refunds", post(routes::refunds::create_refund))
+        .route("/api/orders/:mid/:id/refunds", get(routes::refunds::list_refunds))
+        .route("/api/dunning/:mid", get(routes::dunning::list_dunning))
+        .route("/api/orders/:mid/:id/returns", post(routes::returns::create_return))
+        .route("/api/orders/:mid/:id/returns", get(routes::returns::list_returns))
+        .route("/api/orders/:mid/:id/returns/:return_id/approve", post(routes::returns::approve_return))
+        .route("/api/orders/:mid/:id/returns/:return_id/reject", post(routes::returns::reject_return))
+        .route("/api/orders/:mid/:id/returns/:return_id/refund", post(routes::returns::refund_return))
+        // Shipping routes
+        .route("/api/shipping/zones", post(routes::shipping::create_zone))
+        .route("/api/shipping/zones", get(routes::shipping::list_zones))
+        .route("/api/shipping/zones/:mid/:id", delete(routes::shipping::delete_zone))
+        .route("/api/shipping/zones/:mid/:id/rates", post(routes::shipping::add_rate))
+        .route("/api/shipping/rates/:mid/:id", delete(routes::shipping::delete_rate))
+        .route("/api/shipping/zones/:mid/:id/transit/:method", put(routes::shipping::set_transit).delete(routes::shipping::delete_transit))
+        // Tax routes
+        .route("/api/tax/rates", post(routes::tax::create_rate))
+        .route("/api/tax/rates", get(routes::tax::list_rates))
+        .route("/api/tax/rates/:mid/:id", get(routes::tax::get_rate))
+        .route("/api/tax/rates/:mid/:id", put(routes::tax::update_rate))
+        .route("/api/tax/rates/:mid/:id", delete(routes::tax::delete_rate))
+        .route("/api/zones", get(routes::zones::list).post(routes::zones::create))
+        .route("/api/zones/resolve", get(routes::zones::resolve))
+        .route("/api/zones/:mid/:id", get(routes::zones::get).put(routes::zones::update).delete(routes::zones::delete))
+        // Outbound webhook routes
+        .route("/api/webhooks", post(routes::webhooks::create))
+        .route("/api/webhooks", get(routes::webhooks::list))
+        .route("/api/webhooks/:mid/:id", get(routes::webhooks::get))
+        .route("/api/webhooks/:mid/:id", put(routes::webhooks::update))
+        .route("/api/webhooks/:mid/:id", delete(routes::webhooks::delete))
+        .route("/api/webhooks/:mid/:id/deliveries", get(routes::webhooks::deliveries))
+        // Marketplace channel routes
+        .route("/api/channels", post(routes::channels::create).get(routes::channels::list))
+        .route("/api/channels/:mid/:id", get(routes::channels::get).put(routes::channels::update).delete(routes::channels::delete))
+        .route("/api/channels/:mid/:id/sync", post(routes::channels::sync))
+        .route("/api/channels/:mid/:id/orders/ingest", post(routes::channels::ingest))
+        .route("/api/channels/:mid/:id/inventory/reconciliation", get(routes::channels::reconciliation))
+        .route("/api/merchants", get(routes::merchants::list))
+        .route("/api/merchants/onboard", post(routes::merchants::onboard))
+        .route("/api/merchants/:mid", get(routes::merchants::get).put(routes::merchants::update).delete(routes::merchants::close))
+        .route("/api/merchants/:mid/settings", get(routes::merchants::get_settings).put(routes::merchants::update_settings))
+        .route("/api/merchants/:mid/settings/:key", delete(routes::merchants::reset_setting))
+        .route("/api/stores/:sdomain", get(routes::merchants::store))
         // Cart routes
         .route("/api/carts", post(routes::cart::create_cart))
         .route("/api/carts/:cart_id", get(routes::cart::get_cart))
@@ -88,21 +1037,81 @@ pub fn app(db: DatabaseConnection) -> Router {
         .route("/api/carts/:cart_id/items/:sku", delete(routes::cart::remove_item))
         .route("/api/carts/:cart_id/clear", post(routes::cart::clear_cart))
         .route("/api/carts/:cart_id", delete(routes::cart::delete_cart))
-        // Health check
-        .route("/health", get(health_check))
-        .with_state(state)
-}
+        .route("/api/carts/:cart_id/merge", post(routes::cart::merge_cart))
+        .route("/api/carts/:cart_id/coupon", post(routes::cart::apply_coupon))
+        .route("/api/carts/:cart_id/coupon", delete(routes::cart::remove_coupon))
+        .route("/api/carts/:cart_id/contact", put(routes::cart::set_contact))
+        .route("/api/carts/:cart_id/gift-cards", post(routes::cart::apply_gift_card))
+        .route("/api/carts/:cart_id/gift-cards/:code", delete(routes::cart::remove_gift_card))
+        .route("/api/carts/:cart_id/shipping-quotes", post(routes::cart::shipping_quotes))
+        .route("/api/carts/:cart_id/shipments", post(routes::cart::shipments))
+        .route("/api/carts/:cart_id/estimate", post(routes::cart::estimate))
+        .route("/api/carts/:cart_id/checkout", post(routes::cart::checkout))
+        .route("/api/carts/:cart_id/sync-token", post(routes::cart_sync::sync_token))
+        .route("/ws/carts/:cart_id", get(routes::cart_sync::connect))
+        // Abandoned cart routes
+        .route("/api/abandoned-carts", get(routes::abandoned_carts::list))
+        .route("/api/abandoned-carts/metrics", get(routes::abandoned_carts::metrics))
+        .route("/api/abandoned-carts/recover/:token", post(routes::abandoned_carts::recover))
+        .route("/api/admin/stats", get(routes::stats::get))
+        .route("/api/reports", get(routes::reports::list))
+        .route("/api/reports/:mid/:id", get(routes::reports::get))
+        .route("/api/reports/:mid/:id/download", get(routes::reports::download))
+        .route("/api/audit", get(routes::audit::list))
+        // Coupon routes
+        .route("/api/coupons", post(routes::coupons::create))
+        .route("/api/coupons", get(routes::coupons::list))
+        .route("/api/coupons/:mid/:id", get(routes::coupons::get))
+        .route("/api/coupons/:mid/:id", put(routes::coupons::update))
+        .route("/api/coupons/:mid/:id", delete(routes::coupons::delete))
+        // Gift card routes
+        .route("/api/gift-cards", post(routes::giftcards::issue))
+        .route("/api/gift-cards", get(routes::giftcards::list))
+        .route("/api/gift-cards/balance", post(routes::giftcards::balance))
+        .route("/api/gift-cards/:mid/:id", get(routes::giftcards::get))
+        .route("/api/gift-cards/:mid/:id/adjust", post(routes::giftcards::adjust))
+        .route("/api/gift-cards/:mid/:id/void", post(routes::giftcards::void))
+        // Inventory routes
+        .route("/api/inventory/adjust", post(routes::inventory::adjust))
+        .route("/api/inventory/:mid/:sku/adjustments", get(routes::inventory::history))
+        .route("/api/inventory/low-stock", get(routes::inventory::low_stock))
+        .route("/api/inventory/reservations", post(routes::inventory::reserve))
+        .route("/api/inventory/reservations/:cart_id", delete(routes::inventory::release))
+        .route("/api/inventory/:mid/:sku/warehouses", get(routes::warehouses::sku_stock))
+        .route("/api/warehouses", post(routes::warehouses::create).get(routes::warehouses::list))
+        .route("/api/warehouses/:mid/:id", put(routes::warehouses::update))
+        .route("/api/warehouses/:mid/:id/stock", get(routes::warehouses::stock))
+        .route("/api/stock-transfers", post(routes::transfers::create).get(routes::transfers::list))
+        .route("/api/stock-transfers/:mid/:id", get(routes::transfers::get))
+        .route("/api/stock-transfers/:mid/:id/receive", post(routes::transfers::receive))
+        .route("/api/stock-transfers/:mid/:id/cancel", post(routes::transfers::cancel))
+        .route("/api/purchase-orders", post(routes::purchase_orders::create).get(routes::purchase_orders::list))
+        .route("/api/purchase-orders/:mid/:id", get(routes::purchase_orders::get).put(routes::purchase_orders::update))
+        .route("/api/purchase-orders/:mid/:id/send", post(routes::purchase_orders::send))
+        .route("/api/purchase-orders/:mid/:id/receive", post(routes::purchase_orders::receive))
+        // Health checks; `/health` predates the split and stays a liveness probe
+        .route("/health", get(routes::health::legacy_live))
+        .route("/health/live", get(routes::health::live))
+        .route("/health/ready", get(routes::health::ready))
+        // Reject tokens used against another merchant's mid
+        .route_layer(middleware::from_fn_with_state(state.clone(), tenant::tenant_guard))
+        // Outermost last: the ID is set before the span opens and echoed
+        // on the response
+        .layer(PropagateRequestIdLayer::x_request_id())
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(telemetry::request_span)
+                .on_response(telemetry::record_response),
+        )
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+        // Preflight requests are answered before reaching any route
+        .layer(cors)
+        .with_state(state);
 
-/// Health check endpoint
-#[utoipa::path(
-    get,
-    path = "/health",
-    responses(
-        (status = 200, description = "Service is healthy")
-    )
-)]
-async fn health_check() -> &'static str {
-    "OK"
+    // `/api/v1/...` and `/api/v2/...` are mapped onto the routes above
+    Router::new()
+        .fallback_service(routes)
+        .layer(middleware::from_fn_with_state(versioning, versioning::route_version))
 }
 
 #[cfg(test)]
@@ -121,7 +1130,7 @@ mod tests {
         let db = MockDatabase::new(DatabaseBackend::Postgres)
             .into_connection();
 
-        let app = app(db);
+        let app = app(db, AppConfig::default());
 
         let response = app
             .oneshot(Request::builder().uri("/health").body(Body::empty()).unwrap())
@@ -131,12 +1140,128 @@ mod tests {
         assert_eq!(response.status(), StatusCode::OK);
     }
 
+    #[tokio::test]
+    async fn test_request_id_is_echoed_or_generated() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .into_connection();
+
+        let app = app(db, AppConfig::default());
+
+        let request = Request::builder()
+            .uri("/health")
+            .header(telemetry::REQUEST_ID_HEADER, "req-123")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.headers()[telemetry::REQUEST_ID_HEADER], "req-123");
+
+        let response = app
+            .oneshot(Request::builder().uri("/health").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let generated = response.headers()[telemetry::REQUEST_ID_HEADER].to_str().unwrap();
+        assert_eq!(generated.len(), 36, "{}", generated);
+    }
+
+    #[tokio::test]
+    async fn test_auto_migrate_is_off_by_default() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .into_connection();
+
+        auto_migrate(&db, &AppConfig::default()).await.unwrap();
+        assert!(db.into_transaction_log().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_cors_allows_configured_origins() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .into_connection();
+
+        let mut config = AppConfig::default();
+        config.cors.allowed_origins = vec!["https://shop.example".to_string()];
+        let app = app(db, config);
+
+        let preflight = |origin: &str| {
+            Request::builder()
+                .method("OPTIONS")
+                .uri("/api/products")
+                .header("origin", origin)
+                .header("access-control-request-method", "POST")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = app.clone().oneshot(preflight("https://shop.example")).await.unwrap();
+        assert_eq!(response.headers()["access-control-allow-origin"], "https://shop.example");
+
+        let response = app.oneshot(preflight("https://evil.example")).await.unwrap();
+        assert!(!response.headers().contains_key("access-control-allow-origin"));
+    }
+
+    #[tokio::test]
+    async fn test_versioned_paths_reach_the_routes() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .into_connection();
+
+        let mut config = AppConfig::default();
+        config.api.unversioned_sunset = chrono::NaiveDate::from_ymd_opt(2027, 6, 30);
+        let app = app(db, config);
+        let get = |uri: &str| Request::builder().uri(uri).body(Body::empty()).unwrap();
+
+        // Routed (rather than 404) and asking for a token
+        for uri in ["/api/v1/reports?mid=1", "/api/v2/reports?mid=1"] {
+            let response = app.clone().oneshot(get(uri)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "{}", uri);
+            assert!(!response.headers().contains_key("deprecation"), "{}", uri);
+        }
+
+        let response = app.clone().oneshot(get("/api/reports?mid=1")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(response.headers()["deprecation"], "true");
+        assert_eq!(response.headers()["sunset"], "Wed, 30 Jun 2027 00:00:00 GMT");
+        assert_eq!(response.headers()["link"], "</api/v1/reports>; rel=\"successor-version\"");
+
+        let response = app.oneshot(get("/api/v9/reports?mid=1")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_anonymous_requests_naming_a_merchant_need_a_public_route() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .into_connection();
+
+        let app = app(db, AppConfig::default());
+        let get = |uri: &str| Request::builder().uri(uri).body(Body::empty()).unwrap();
+
+        for uri in ["/api/orders/1/9/items", "/api/customers/1/7", "/api/customers/1/7/addresses"] {
+            let response = app.clone().oneshot(get(uri)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "{}", uri);
+        }
+
+        // Reaches the catalog handler, which finds no database behind it
+        let response = app.oneshot(get("/api/products/1/5/skus")).await.unwrap();
+        assert_ne!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn test_public_routes_are_registered() {
+        let source = include_str!("lib.rs");
+        for (method, route) in tenant::PUBLIC_ROUTES {
+            let registered = source
+                .lines()
+                .map(str::trim)
+                .filter_map(|line| line.strip_prefix(&format!(".route(\"{}\", ", route)))
+                .any(|handlers| handlers.contains(&format!("{}(", method.as_str().to_lowercase())));
+            assert!(registered, "{} {} is not a route", method, route);
+        }
+    }
+
     #[tokio::test]
     async fn test_swagger_ui_available() {
         let db = MockDatabase::new(DatabaseBackend::Postgres)
             .into_connection();
 
-        let app = app(db);
+        let app = app(db, AppConfig::default());
 
         let response = app
             .oneshot(Request::builder().uri("/swagger-ui/").body(Body::empty()).unwrap())
@@ -145,4 +1270,53 @@ mod tests {
 
         assert_eq!(response.status(), StatusCode::OK);
     }
+
+    /// Path with every parameter written `{}`. Routes sharing a segment
+    /// must share its parameter name in axum, so route and spec names differ.
+    fn normalized(path: &str) -> String {
+        path.split('/')
+            .map(|segment| if segment.starts_with(':') || segment.starts_with('{') { "{}" } else { segment })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    #[test]
+    fn test_every_route_is_documented() {
+        let source = include_str!("lib.rs");
+        let mut routes = Vec::new();
+        for line in source.lines().map(str::trim).filter(|line| line.starts_with(".route(\"")) {
+            let (path, handlers) = line[".route(\"".len()..].split_once('"').unwrap();
+            for method in ["get", "post", "put", "delete", "patch"] {
+                let registered = handlers
+                    .match_indices(&format!("{}(", method))
+                    .any(|(i, _)| matches!(handlers[..i].chars().last(), Some(' ' | '.')));
+                if registered {
+                    routes.push((method, normalized(path)));
+                }
+            }
+        }
+        assert!(routes.len() > 100, "found only {} routes in app()", routes.len());
+
+        let spec = ApiDoc::openapi();
+        let documented: Vec<(&str, String)> = spec
+            .paths
+            .paths
+            .iter()
+            .flat_map(|(path, item)| {
+                [
+                    ("get", item.get.is_some()),
+                    ("post", item.post.is_some()),
+                    ("put", item.put.is_some()),
+                    ("delete", item.delete.is_some()),
+                    ("patch", item.patch.is_some()),
+                ]
+                .into_iter()
+                .filter(|(_, present)| *present)
+                .map(move |(method, _)| (method, normalized(path)))
+            })
+            .collect();
+
+        let missing: Vec<_> = routes.iter().filter(|route| !documented.contains(route)).collect();
+        assert!(missing.is_empty(), "routes missing from ApiDoc: {:?}", missing);
+    }
 }
@@ -0,0 +1,125 @@
+//! Relay from the event outbox to event handlers
+//!
+//! Services record domain events in the outbox on the transaction that
+//! makes the change. The relay polls for recorded events, runs every
+//! handler on each one, publishes it on the in-process bus, and only then
+//! marks it delivered.
+
+use commercerack_events::{outbox, EventHandler};
+use sea_orm::{DatabaseConnection, DbErr, TransactionTrait};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// Events relayed per pass
+const BATCH_SIZE: u64 = 100;
+
+/// Publishes events recorded in the outbox
+pub struct OutboxRelay {
+    handlers: Vec<Arc<dyn EventHandler>>,
+}
+
+impl OutboxRelay {
+    pub fn new(handlers: Vec<Arc<dyn EventHandler>>) -> Self {
+        Self { handlers }
+    }
+
+    /// Relay one batch of recorded events in the order they were recorded.
+    /// Returns how many were relayed.
+    pub async fn relay_pending(&self, db: &DatabaseConnection) -> Result<usize, DbErr> {
+        // The rows stay locked until the commit, so concurrent relays
+        // never pick up the same events
+        let txn = db.begin().await?;
+        let rows = outbox::pending(&txn, BATCH_SIZE).await?;
+
+        let mut relayed = Vec::with_capacity(rows.len());
+        for row in &rows {
+            match outbox::decode(row) {
+                Ok(event) => {
+                    for handler in &self.handlers {
+                        handler.handle(&event).await;
+                    }
+                    commercerack_events::publish(event);
+                }
+                // Retrying can't fix the payload; don't let it block the rest
+                Err(e) => warn!("Dropping undecodable {} event {} from the outbox: {}", row.event, row.id, e),
+            }
+            relayed.push(row.id);
+        }
+
+        outbox::mark_delivered(&txn, &relayed).await?;
+        txn.commit().await?;
+        Ok(relayed.len())
+    }
+
+    /// Relay recorded events every `interval` on a background task
+    pub fn spawn(
+        self: Arc<Self>,
+        db: Arc<DatabaseConnection>,
+        interval: Duration,
+    ) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                match self.relay_pending(&db).await {
+                    Ok(0) => {}
+                    Ok(n) => info!("Relayed {} events from the outbox", n),
+                    Err(e) => warn!("Outbox relay pass failed: {}", e),
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::async_trait;
+    use commercerack_core::{MerchantId, Timestamp};
+    use commercerack_events::DomainEvent;
+    use ::entity::prelude::OutboxEvent;
+    use sea_orm::{DatabaseBackend, MockDatabase, MockExecResult};
+    use std::sync::Mutex;
+
+    struct Recorder(Mutex<Vec<DomainEvent>>);
+
+    #[async_trait]
+    impl EventHandler for Recorder {
+        async fn handle(&self, event: &DomainEvent) {
+            self.0.lock().unwrap().push(event.clone());
+        }
+    }
+
+    fn row(id: i32, payload: &str) -> OutboxEvent {
+        OutboxEvent {
+            id,
+            mid: 1,
+            event: "product.deleted".to_string(),
+            payload: payload.to_string(),
+            created_gmt: Timestamp::EPOCH,
+            delivered_gmt: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_relay_handles_then_marks_delivered() {
+        let event = DomainEvent::ProductDeleted { mid: MerchantId::new(1), id: 42 };
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![row(7, &serde_json::to_string(&event).unwrap()), row(8, "{")]])
+            .append_exec_results([MockExecResult { last_insert_id: 0, rows_affected: 2 }])
+            .into_connection();
+
+        let recorder = Arc::new(Recorder(Mutex::new(Vec::new())));
+        let relay = OutboxRelay::new(vec![recorder.clone()]);
+        assert_eq!(relay.relay_pending(&db).await.unwrap(), 2);
+        assert_eq!(*recorder.0.lock().unwrap(), vec![event]);
+
+        let log = db.into_transaction_log();
+        let statements = log[0].statements();
+        assert!(statements[1].sql.contains("FOR UPDATE SKIP LOCKED"), "{}", statements[1].sql);
+        assert!(statements[2].to_string().contains(r#""event_outbox"."id" IN (7, 8)"#), "{}", statements[2]);
+        assert_eq!(statements[3].sql, "COMMIT");
+    }
+}
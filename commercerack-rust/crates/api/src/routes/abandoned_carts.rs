@@ -0,0 +1,251 @@
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use chrono::Duration;
+use commercerack_cart::{abandoned, AbandonedCartService, CartItem};
+use ::entity::prelude::AbandonedCart;
+use commercerack_core::Timestamp;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use crate::auth::RequireMerchantAdmin;
+use crate::error::{ApiError, ErrorBody};
+use crate::routes::cart::{save_cart, CartResponse};
+use crate::validation::{self, Validate, Validator};
+use crate::AppState;
+
+/// Default metrics window when `from` is omitted
+const DEFAULT_METRICS_DAYS: i64 = 30;
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct AbandonedCartResponse {
+    pub id: i32,
+    pub cart_id: String,
+    /// 0 for guests
+    pub cid: i32,
+    pub email: String,
+    #[schema(value_type = Vec<Object>)]
+    pub items: Vec<CartItem>,
+    pub item_count: i32,
+    pub subtotal: String,
+    #[schema(value_type = i64)]
+    pub abandoned_gmt: Timestamp,
+    /// When the recovery link was first opened
+    #[schema(value_type = Option<i64>)]
+    pub recovered_gmt: Option<Timestamp>,
+    /// The order the cart was checked out as afterwards
+    pub order_id: Option<i32>,
+    pub order_total: Option<String>,
+    #[schema(value_type = Option<i64>)]
+    pub ordered_gmt: Option<Timestamp>,
+}
+
+impl From<AbandonedCart> for AbandonedCartResponse {
+    fn from(cart: AbandonedCart) -> Self {
+        Self {
+            // Rows archived before items were validated may not parse
+            items: serde_json::from_str(&cart.items).unwrap_or_default(),
+            id: cart.id,
+            cart_id: cart.cart_id,
+            cid: cart.cid,
+            email: cart.email,
+            item_count: cart.item_count,
+            subtotal: cart.subtotal.to_string(),
+            abandoned_gmt: cart.abandoned_gmt,
+            recovered_gmt: cart.recovered_gmt,
+            order_id: cart.order_id,
+            order_total: cart.order_total.map(|total| total.to_string()),
+            ordered_gmt: cart.ordered_gmt,
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct AbandonmentMetricsResponse {
+    #[schema(value_type = i64)]
+    pub from: Timestamp,
+    #[schema(value_type = i64)]
+    pub to: Timestamp,
+    pub abandoned: i64,
+    pub abandoned_value: String,
+    /// Recovery links opened
+    pub recovered: i64,
+    /// Abandoned carts later checked out
+    pub ordered: i64,
+    pub ordered_value: String,
+    /// `ordered` over `abandoned`, 0 to 1
+    pub recovery_rate: f64,
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct ListQuery {
+    pub mid: i32,
+    /// Only carts that were, or weren't, checked out afterwards
+    pub ordered: Option<bool>,
+    #[serde(default = "default_limit")]
+    pub limit: u64,
+}
+
+impl Validate for ListQuery {
+    fn validate(&self, v: &mut Validator) {
+        v.check((1..=100).contains(&self.limit), "limit", "must be between 1 and 100");
+    }
+}
+
+fn default_limit() -> u64 {
+    50
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct MetricsQuery {
+    pub mid: i32,
+    /// Unix time; defaults to 30 days before `to`
+    #[param(value_type = Option<i64>)]
+    pub from: Option<Timestamp>,
+    /// Unix time; defaults to now
+    #[param(value_type = Option<i64>)]
+    pub to: Option<Timestamp>,
+}
+
+impl Validate for MetricsQuery {
+    fn validate(&self, v: &mut Validator) {
+        if let (Some(from), Some(to)) = (self.from, self.to) {
+            v.check(from <= to, "to", "must not be before from");
+        }
+    }
+}
+
+/// A merchant's abandoned carts
+#[utoipa::path(
+    get,
+    path = "/api/abandoned-carts",
+    params(ListQuery),
+    responses(
+        (status = 200, description = "Abandoned carts, newest first", body = Vec<AbandonedCartResponse>),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 422, description = "Invalid limit", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "abandoned_carts"
+)]
+pub async fn list(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Query(query): Query<ListQuery>,
+) -> Result<Json<Vec<AbandonedCartResponse>>, ApiError> {
+    validation::validate(&query)?;
+
+    let mid = admin.0.scoped_mid(query.mid);
+    let carts = AbandonedCartService::list(&*state.db, mid, query.ordered, query.limit).await?;
+    Ok(Json(carts.into_iter().map(|cart| cart.into()).collect()))
+}
+
+/// How many carts were abandoned in a period and how many were won back
+#[utoipa::path(
+    get,
+    path = "/api/abandoned-carts/metrics",
+    params(MetricsQuery),
+    responses(
+        (status = 200, description = "Abandonment and recovery totals", body = AbandonmentMetricsResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 422, description = "Invalid period", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "abandoned_carts"
+)]
+pub async fn metrics(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Query(query): Query<MetricsQuery>,
+) -> Result<Json<AbandonmentMetricsResponse>, ApiError> {
+    validation::validate(&query)?;
+
+    let to = query.to.unwrap_or_else(Timestamp::now);
+    let from = query.from.unwrap_or(to - Duration::days(DEFAULT_METRICS_DAYS));
+    let metrics = AbandonedCartService::metrics(&*state.db, admin.0.scoped_mid(query.mid), from, to).await?;
+
+    Ok(Json(AbandonmentMetricsResponse {
+        from,
+        to,
+        recovery_rate: metrics.recovery_rate(),
+        abandoned: metrics.abandoned,
+        abandoned_value: metrics.abandoned_value.unwrap_or(Decimal::ZERO).to_string(),
+        recovered: metrics.recovered,
+        ordered: metrics.ordered,
+        ordered_value: metrics.ordered_value.unwrap_or(Decimal::ZERO).to_string(),
+    }))
+}
+
+/// Restore an abandoned cart from its recovery link. The cart keeps its
+/// original ID; if it is still in storage it is returned as it is now.
+#[utoipa::path(
+    post,
+    path = "/api/abandoned-carts/recover/{token}",
+    params(("token" = String, Path, description = "Recovery token from the cart.abandoned event")),
+    responses(
+        (status = 200, description = "Restored cart"),
+        (status = 404, description = "Unknown recovery token", body = ErrorBody),
+        (status = 409, description = "Cart has already been checked out", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "abandoned_carts"
+)]
+pub async fn recover(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+) -> Result<Json<CartResponse>, ApiError> {
+    let record = AbandonedCartService::find_by_token(&*state.db, &token)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Abandoned cart"))?;
+    if record.order_id.is_some() {
+        return Err(ApiError::Conflict("Cart has already been checked out".to_string()));
+    }
+
+    let _lock = state.cart_store.lock_cart(&record.cart_id).await;
+    let mut cart = match state.cart_store.get_cart(&record.cart_id).await? {
+        Some(cart) if !cart.is_empty() => cart,
+        _ => abandoned::restore(&record)?,
+    };
+
+    let response = save_cart(&state, &mut cart).await?;
+    AbandonedCartService::mark_recovered(&*state.db, record.id).await?;
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unparseable_items_are_left_out() {
+        let cart = AbandonedCart {
+            id: 1,
+            cart_id: "cart-1".to_string(),
+            items: "not json".to_string(),
+            item_count: 1,
+            subtotal: Decimal::new(500, 2),
+            abandoned_gmt: Timestamp::from_unix(1_700_000_000),
+            mid: 1,
+            cid: 0,
+            email: "guest@example.com".to_string(),
+            recovery_token: None,
+            recovered_gmt: None,
+            order_id: Some(9),
+            order_total: Some(Decimal::new(750, 2)),
+            ordered_gmt: Some(Timestamp::from_unix(1_700_000_100)),
+        };
+
+        let response = AbandonedCartResponse::from(cart);
+        assert!(response.items.is_empty());
+        assert_eq!(response.subtotal, "5.00");
+        assert_eq!(response.order_total.as_deref(), Some("7.50"));
+    }
+
+    #[test]
+    fn test_metrics_period_validation() {
+        let query = MetricsQuery { mid: 1, from: Some(Timestamp::from_unix(200)), to: Some(Timestamp::from_unix(100)) };
+        assert!(validation::validate(&query).is_err());
+    }
+}
@@ -0,0 +1,368 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use commercerack_customer::address::{AddressKind, AddressService, CustomerAddress};
+use serde::{Deserialize, Serialize};
+use crate::auth::Claims;
+use crate::error::{ApiError, ErrorBody};
+use crate::validation::{ValidatedJson, Validate, Validator};
+use crate::AppState;
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct AddressRequest {
+    #[serde(default)]
+    pub label: String,
+    pub firstname: String,
+    pub lastname: String,
+    #[serde(default)]
+    pub company: String,
+    pub address1: String,
+    #[serde(default)]
+    pub address2: String,
+    pub city: String,
+    pub state: String,
+    pub zip: String,
+    pub country: String,
+    #[serde(default)]
+    pub phone: String,
+}
+
+impl Validate for AddressRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.max_len("label", &self.label, 15)
+            .required("firstname", &self.firstname, 30)
+            .required("lastname", &self.lastname, 30)
+            .max_len("company", &self.company, 30)
+            .required("address1", &self.address1, 60)
+            .max_len("address2", &self.address2, 60)
+            .required("city", &self.city, 30)
+            .max_len("state", &self.state, 20)
+            .required("zip", &self.zip, 10)
+            .check(
+                self.country.len() == 2 && self.country.chars().all(|c| c.is_ascii_alphabetic()),
+                "country",
+                "must be a two-letter country code",
+            )
+            .max_len("phone", &self.phone, 12);
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct SetDefaultRequest {
+    /// Either `billing` or `shipping`
+    #[schema(value_type = String)]
+    pub kind: AddressKind,
+}
+
+impl Validate for SetDefaultRequest {
+    fn validate(&self, _v: &mut Validator) {}
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct AddressResponse {
+    pub id: i32,
+    pub cid: i32,
+    pub mid: i32,
+    pub label: String,
+    pub firstname: String,
+    pub lastname: String,
+    pub company: String,
+    pub address1: String,
+    pub address2: String,
+    pub city: String,
+    pub state: String,
+    pub zip: String,
+    pub country: String,
+    pub phone: String,
+    pub is_default_billing: bool,
+    pub is_default_shipping: bool,
+}
+
+impl From<CustomerAddress> for AddressResponse {
+    fn from(addr: CustomerAddress) -> Self {
+        Self {
+            id: addr.id,
+            cid: addr.cid,
+            mid: addr.mid,
+            label: addr.label,
+            firstname: addr.firstname,
+            lastname: addr.lastname,
+            company: addr.company,
+            address1: addr.address1,
+            address2: addr.address2,
+            city: addr.city,
+            state: addr.state,
+            zip: addr.zip,
+            country: addr.country,
+            phone: addr.phone,
+            is_default_billing: addr.is_default_billing,
+            is_default_shipping: addr.is_default_shipping,
+        }
+    }
+}
+
+impl AddressRequest {
+    fn into_address(self, id: i32, mid: i32, cid: i32) -> CustomerAddress {
+        CustomerAddress {
+            id,
+            cid,
+            mid,
+            label: self.label,
+            firstname: self.firstname,
+            lastname: self.lastname,
+            company: self.company,
+            address1: self.address1,
+            address2: self.address2,
+            city: self.city,
+            state: self.state,
+            zip: self.zip,
+            country: self.country,
+            phone: self.phone,
+            is_default_billing: false,
+            is_default_shipping: false,
+        }
+    }
+}
+
+/// Look up an address, making sure it belongs to the customer in the path
+async fn find_owned(
+    state: &AppState,
+    mid: i32,
+    cid: i32,
+    id: i32,
+) -> Result<CustomerAddress, ApiError> {
+    AddressService::find_by_id(&state.db, mid, id)
+        .await?
+        .filter(|addr| addr.cid == cid)
+        .ok_or_else(|| ApiError::not_found("Address"))
+}
+
+/// Add an address to a customer's address book
+#[utoipa::path(
+    post,
+    path = "/api/customers/{mid}/{cid}/addresses",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("cid" = i32, Path, description = "Customer ID")
+    ),
+    request_body = AddressRequest,
+    responses(
+        (status = 201, description = "Address created successfully", body = AddressResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Not the customer's own address book", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "customers"
+)]
+pub async fn create(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((mid, cid)): Path<(i32, i32)>,
+    ValidatedJson(req): ValidatedJson<AddressRequest>,
+) -> Result<(StatusCode, Json<AddressResponse>), ApiError> {
+    claims.ensure_self(cid)?;
+    let mid = claims.scoped_mid(mid);
+
+    AddressService::create(&state.db, req.into_address(0, mid, cid))
+        .await
+        .map(|addr| (StatusCode::CREATED, Json(addr.into())))
+        .map_err(ApiError::from)
+}
+
+/// List a customer's addresses
+#[utoipa::path(
+    get,
+    path = "/api/customers/{mid}/{cid}/addresses",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("cid" = i32, Path, description = "Customer ID")
+    ),
+    responses(
+        (status = 200, description = "Customer addresses", body = Vec<AddressResponse>),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Not the customer's own address book", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "customers"
+)]
+pub async fn list(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((mid, cid)): Path<(i32, i32)>,
+) -> Result<Json<Vec<AddressResponse>>, ApiError> {
+    claims.ensure_self(cid)?;
+    let mid = claims.scoped_mid(mid);
+
+    AddressService::get_by_customer(&state.db, mid, cid)
+        .await
+        .map(|addrs| Json(addrs.into_iter().map(|a| a.into()).collect()))
+        .map_err(ApiError::from)
+}
+
+/// Replace an address
+#[utoipa::path(
+    put,
+    path = "/api/customers/{mid}/{cid}/addresses/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("cid" = i32, Path, description = "Customer ID"),
+        ("id" = i32, Path, description = "Address ID")
+    ),
+    request_body = AddressRequest,
+    responses(
+        (status = 200, description = "Address updated", body = AddressResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Not the customer's own address book", body = ErrorBody),
+        (status = 404, description = "Address not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "customers"
+)]
+pub async fn update(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((mid, cid, id)): Path<(i32, i32, i32)>,
+    ValidatedJson(req): ValidatedJson<AddressRequest>,
+) -> Result<Json<AddressResponse>, ApiError> {
+    claims.ensure_self(cid)?;
+    let mid = claims.scoped_mid(mid);
+    find_owned(&state, mid, cid, id).await?;
+
+    AddressService::update(&state.db, req.into_address(id, mid, cid))
+        .await
+        .map(|addr| Json(addr.into()))
+        .map_err(ApiError::from)
+}
+
+/// Delete an address
+#[utoipa::path(
+    delete,
+    path = "/api/customers/{mid}/{cid}/addresses/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("cid" = i32, Path, description = "Customer ID"),
+        ("id" = i32, Path, description = "Address ID")
+    ),
+    responses(
+        (status = 204, description = "Address deleted"),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Not the customer's own address book", body = ErrorBody),
+        (status = 404, description = "Address not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "customers"
+)]
+pub async fn delete(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((mid, cid, id)): Path<(i32, i32, i32)>,
+) -> Result<StatusCode, ApiError> {
+    claims.ensure_self(cid)?;
+    let mid = claims.scoped_mid(mid);
+    find_owned(&state, mid, cid, id).await?;
+
+    AddressService::delete(&state.db, mid, id)
+        .await
+        .map(|_| StatusCode::NO_CONTENT)
+        .map_err(ApiError::from)
+}
+
+/// Make an address the default billing or shipping address
+#[utoipa::path(
+    post,
+    path = "/api/customers/{mid}/{cid}/addresses/{id}/default",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("cid" = i32, Path, description = "Customer ID"),
+        ("id" = i32, Path, description = "Address ID")
+    ),
+    request_body = SetDefaultRequest,
+    responses(
+        (status = 200, description = "Default updated", body = AddressResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Not the customer's own address book", body = ErrorBody),
+        (status = 404, description = "Address not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "customers"
+)]
+pub async fn set_default(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((mid, cid, id)): Path<(i32, i32, i32)>,
+    ValidatedJson(req): ValidatedJson<SetDefaultRequest>,
+) -> Result<Json<AddressResponse>, ApiError> {
+    claims.ensure_self(cid)?;
+    let mid = claims.scoped_mid(mid);
+    find_owned(&state, mid, cid, id).await?;
+
+    AddressService::set_default(&state.db, mid, cid, id, req.kind)
+        .await
+        .map(|addr| Json(addr.into()))
+        .map_err(ApiError::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::Role;
+    use sea_orm::{DatabaseBackend, MockDatabase};
+
+    fn address_model(cid: i32) -> CustomerAddress {
+        CustomerAddress {
+            id: 5,
+            cid,
+            mid: 1,
+            label: "home".to_string(),
+            firstname: "Test".to_string(),
+            lastname: "User".to_string(),
+            company: String::new(),
+            address1: "1 Main St".to_string(),
+            address2: String::new(),
+            city: "Springfield".to_string(),
+            state: "IL".to_string(),
+            zip: "62701".to_string(),
+            country: "US".to_string(),
+            phone: String::new(),
+            is_default_billing: false,
+            is_default_shipping: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_list_addresses() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![address_model(2)]])
+            .into_connection();
+
+        let Json(addrs) = list(State(AppState::mock(db)), Claims::new(2, 1), Path((1, 2))).await.unwrap();
+        assert_eq!(addrs.len(), 1);
+        assert!(addrs[0].is_default_shipping);
+    }
+
+    #[tokio::test]
+    async fn test_delete_other_customers_address() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![address_model(3)]])
+            .into_connection();
+
+        let result = delete(State(AppState::mock(db)), Claims::new(2, 1), Path((1, 2, 5))).await;
+        assert_eq!(result.err().map(|e| e.status()), Some(StatusCode::NOT_FOUND));
+    }
+
+    #[tokio::test]
+    async fn test_list_another_customers_addresses() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+        let result = list(State(AppState::mock(db)), Claims::new(3, 1), Path((1, 2))).await;
+        assert_eq!(result.err().map(|e| e.status()), Some(StatusCode::FORBIDDEN));
+
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![address_model(2)]])
+            .into_connection();
+        let admin = Claims::new(3, 1).with_role(Role::MerchantAdmin);
+        let Json(addrs) = list(State(AppState::mock(db)), admin, Path((1, 2))).await.unwrap();
+        assert_eq!(addrs.len(), 1);
+    }
+}
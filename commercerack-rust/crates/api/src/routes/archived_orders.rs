@@ -0,0 +1,241 @@
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use commercerack_core::Timestamp;
+use commercerack_order::cold_storage::{self, ColdOrder, ColdStorageService};
+use commercerack_order::shipments::ShipmentWithItems;
+use ::entity::prelude::ArchivedOrder;
+use serde::{Deserialize, Serialize};
+use crate::auth::RequireMerchantAdmin;
+use crate::error::{ApiError, ErrorBody};
+use crate::routes::orders::{OrderResponse, ShipmentResponse};
+use crate::routes::payments::PaymentResponse;
+use crate::validation::{self, Validate, Validator};
+use crate::AppState;
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct ArchivedListQuery {
+    pub mid: i32,
+    /// Only the order with this number
+    pub orderid: Option<String>,
+    #[serde(default = "default_limit")]
+    pub limit: u64,
+    #[serde(default)]
+    pub offset: u64,
+}
+
+fn default_limit() -> u64 {
+    20
+}
+
+impl Validate for ArchivedListQuery {
+    fn validate(&self, v: &mut Validator) {
+        if let Some(orderid) = &self.orderid {
+            v.max_len("orderid", orderid, 30);
+        }
+        v.check((1..=100).contains(&self.limit), "limit", "must be between 1 and 100");
+    }
+}
+
+/// An order in cold storage, as listed
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ArchivedOrderResponse {
+    /// The order's `id` before it was archived; fetch it with
+    /// `GET /api/archived-orders/{mid}/{id}`
+    pub id: i32,
+    pub mid: i32,
+    pub orderid: String,
+    pub customer: i32,
+    pub total: String,
+    #[schema(value_type = i64)]
+    pub created_gmt: Timestamp,
+    #[schema(value_type = i64)]
+    pub archived_gmt: Timestamp,
+    /// Bytes of the compressed snapshot
+    pub size: i64,
+}
+
+impl From<ArchivedOrder> for ArchivedOrderResponse {
+    fn from(archived: ArchivedOrder) -> Self {
+        Self {
+            id: archived.order_id,
+            mid: archived.mid,
+            orderid: archived.orderid,
+            customer: archived.customer,
+            total: archived.total.to_string(),
+            created_gmt: archived.created_gmt,
+            archived_gmt: archived.archived_gmt,
+            size: archived.size,
+        }
+    }
+}
+
+/// An order read back from cold storage, as it was when archived
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ColdOrderResponse {
+    #[schema(value_type = i64)]
+    pub archived_gmt: Timestamp,
+    pub order: OrderResponse,
+    pub payments: Vec<PaymentResponse>,
+    pub shipments: Vec<ShipmentResponse>,
+}
+
+impl ColdOrderResponse {
+    fn new(archived: ArchivedOrder, cold: ColdOrder) -> Self {
+        let mut order = OrderResponse::from(cold.order);
+        order.items = cold.items.into_iter().map(|i| i.into()).collect();
+        let shipment_items = cold.shipment_items;
+        let shipments = cold
+            .shipments
+            .into_iter()
+            .map(|shipment| {
+                let items = shipment_items.iter().filter(|i| i.shipment_id == shipment.id).cloned().collect();
+                ShipmentWithItems { shipment, items }.into()
+            })
+            .collect();
+        Self {
+            archived_gmt: archived.archived_gmt,
+            order,
+            payments: cold.payments.into_iter().map(|p| p.into()).collect(),
+            shipments,
+        }
+    }
+}
+
+/// List a merchant's orders in cold storage, most recently placed first
+#[utoipa::path(
+    get,
+    path = "/api/archived-orders",
+    params(ArchivedListQuery),
+    responses(
+        (status = 200, description = "Archived orders", body = Vec<ArchivedOrderResponse>),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 422, description = "Invalid order number or limit", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "orders"
+)]
+pub async fn list(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Query(query): Query<ArchivedListQuery>,
+) -> Result<Json<Vec<ArchivedOrderResponse>>, ApiError> {
+    validation::validate(&query)?;
+
+    let mid = admin.0.scoped_mid(query.mid);
+    let orders = ColdStorageService::list(&*state.db, mid, query.orderid.as_deref(), query.limit, query.offset).await?;
+    Ok(Json(orders.into_iter().map(|o| o.into()).collect()))
+}
+
+/// Read an order back from cold storage
+///
+/// The order is not restored to the order tables; this is a view of it as
+/// it was when archived.
+#[utoipa::path(
+    get,
+    path = "/api/archived-orders/{mid}/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "The order's ID before it was archived")
+    ),
+    responses(
+        (status = 200, description = "The archived order", body = ColdOrderResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Order is not in cold storage", body = ErrorBody),
+        (status = 502, description = "Object store failed", body = ErrorBody),
+        (status = 503, description = "Cold storage is not configured", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "orders"
+)]
+pub async fn get(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Json<ColdOrderResponse>, ApiError> {
+    let mid = admin.0.scoped_mid(mid);
+    let store = cold_storage::store()?;
+    let (archived, cold) = ColdStorageService::rehydrate(&*state.db, store.as_ref(), mid, id).await?;
+    Ok(Json(ColdOrderResponse::new(archived, cold)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::entity::prelude::{Order, Shipment, ShipmentItem};
+    use rust_decimal::Decimal;
+
+    #[test]
+    fn test_shipments_keep_their_items() {
+        let order = Order {
+            id: 12,
+            mid: 7,
+            orderid: "2024-01-05-ABC123".to_string(),
+            cartid: String::new(),
+            customer: 3,
+            pool: "ARCHIVE".to_string(),
+            total: Decimal::new(4500, 2),
+            created_gmt: Timestamp::from_unix(1_704_412_800),
+            paid_gmt: None,
+            paid_txn: None,
+            order_payment_status: None,
+            order_payment_lookup: None,
+            bs_settlement: None,
+            shipped_gmt: None,
+            inv_gmt: None,
+            company_id: None,
+            review_status: None,
+            ship_method: None,
+            bill_email: String::new(),
+            mkt: 0,
+            erefid: None,
+            mkt_bitstr: String::new(),
+            pickup_location_id: None,
+            pickup_ready_gmt: None,
+            picked_up_gmt: None,
+            cancelled_gmt: None,
+            cancel_reason: None,
+            v: 1,
+        };
+        let shipment = |id| Shipment {
+            id,
+            mid: 7,
+            order_id: 12,
+            carrier: "UPS".to_string(),
+            tracking_number: String::new(),
+            shipped_gmt: Timestamp::from_unix(1_704_500_000),
+            label_provider: None,
+            label_id: None,
+            label_url: None,
+            label_cost: None,
+        };
+        let shipped = |id, shipment_id| ShipmentItem { id, mid: 7, shipment_id, order_item_id: 30, quantity: 1 };
+        let cold = ColdOrder {
+            order,
+            items: Vec::new(),
+            payments: Vec::new(),
+            shipments: vec![shipment(5), shipment(6)],
+            shipment_items: vec![shipped(1, 5), shipped(2, 6), shipped(3, 6)],
+        };
+        let archived = ArchivedOrder {
+            id: 1,
+            mid: 7,
+            order_id: 12,
+            orderid: "2024-01-05-ABC123".to_string(),
+            customer: 3,
+            total: Decimal::new(4500, 2),
+            created_gmt: Timestamp::from_unix(1_704_412_800),
+            object_key: "orders/7/12.json.gz".to_string(),
+            size: 200,
+            archived_gmt: Timestamp::from_unix(1_763_434_800),
+        };
+
+        let response = ColdOrderResponse::new(archived, cold);
+        assert_eq!(response.order.id, 12);
+        assert_eq!(response.shipments[0].items.len(), 1);
+        assert_eq!(response.shipments[1].items.len(), 2);
+    }
+}
@@ -0,0 +1,167 @@
+//! Audit log of admin changes
+//!
+//! Handlers making an admin change call [`record`] once it has succeeded.
+//! The change is already made by then, so a failure to write the entry is
+//! logged rather than failing the request.
+
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use commercerack_audit::{Actor, AuditFilter, AuditService, Change};
+use ::entity::prelude::AuditEntry;
+use commercerack_core::Timestamp;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use crate::auth::{Claims, RequireMerchantAdmin};
+use crate::error::{ApiError, ErrorBody};
+use crate::validation::{self, Validate, Validator};
+use crate::AppState;
+
+impl From<&Claims> for Actor {
+    fn from(claims: &Claims) -> Self {
+        Self {
+            id: claims.sub.clone(),
+            role: claims.role.as_str().to_string(),
+        }
+    }
+}
+
+/// Record `change` to one of `mid`'s records, made by `claims`' holder
+pub(crate) async fn record(state: &AppState, claims: &Claims, mid: i32, change: Change) {
+    if let Err(e) = AuditService::record(&*state.db, mid, &Actor::from(claims), change).await {
+        error!(error = %e, mid, "failed to write audit log entry");
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct AuditEntryResponse {
+    pub id: i32,
+    /// Token subject of whoever made the change
+    pub actor: String,
+    pub actor_role: String,
+    /// `order`, `order_item`, `return`, `sku`, `price_tier`, `customer` or
+    /// `customer_note`
+    pub entity_type: String,
+    pub entity_id: String,
+    /// `create`, `update`, `set`, `delete` or `refund`
+    pub action: String,
+    /// Changed fields with their old values
+    pub before: Option<serde_json::Value>,
+    /// Changed fields with their new values
+    pub after: Option<serde_json::Value>,
+    #[schema(value_type = i64)]
+    pub created_gmt: Timestamp,
+}
+
+impl From<AuditEntry> for AuditEntryResponse {
+    fn from(entry: AuditEntry) -> Self {
+        let parse = |json: Option<String>| json.and_then(|json| serde_json::from_str(&json).ok());
+        Self {
+            id: entry.id,
+            actor: entry.actor,
+            actor_role: entry.actor_role,
+            entity_type: entry.entity_type,
+            entity_id: entry.entity_id,
+            action: entry.action,
+            before: parse(entry.before),
+            after: parse(entry.after),
+            created_gmt: entry.created_gmt,
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct ListQuery {
+    pub mid: i32,
+    pub entity_type: Option<String>,
+    pub entity_id: Option<String>,
+    /// Token subject of the actor
+    pub actor: Option<String>,
+    #[param(value_type = Option<i64>)]
+    pub from: Option<Timestamp>,
+    /// Exclusive
+    #[param(value_type = Option<i64>)]
+    pub to: Option<Timestamp>,
+    #[serde(default = "default_limit")]
+    pub limit: u64,
+    #[serde(default)]
+    pub offset: u64,
+}
+
+impl Validate for ListQuery {
+    fn validate(&self, v: &mut Validator) {
+        if let (Some(from), Some(to)) = (self.from, self.to) {
+            v.check(from < to, "to", "must be after from");
+        }
+        v.check((1..=100).contains(&self.limit), "limit", "must be between 1 and 100");
+    }
+}
+
+fn default_limit() -> u64 {
+    50
+}
+
+/// Who changed what, newest first
+#[utoipa::path(
+    get,
+    path = "/api/audit",
+    params(ListQuery),
+    responses(
+        (status = 200, description = "Audit entries", body = Vec<AuditEntryResponse>),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 422, description = "Invalid date range or limit", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "audit"
+)]
+pub async fn list(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Query(query): Query<ListQuery>,
+) -> Result<Json<Vec<AuditEntryResponse>>, ApiError> {
+    validation::validate(&query)?;
+
+    let filter = AuditFilter {
+        entity_type: query.entity_type,
+        entity_id: query.entity_id,
+        actor: query.actor,
+        from: query.from,
+        to: query.to,
+    };
+    let mid = admin.0.scoped_mid(query.mid);
+    let entries = AuditService::list(&*state.db, mid, &filter, query.limit, query.offset).await?;
+    Ok(Json(entries.into_iter().map(|entry| entry.into()).collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_diff_is_returned_as_json() {
+        let entry = AuditEntry {
+            id: 1,
+            mid: 3,
+            actor: "12".to_string(),
+            actor_role: "merchant_admin".to_string(),
+            entity_type: "sku".to_string(),
+            entity_id: "4".to_string(),
+            action: "update".to_string(),
+            before: Some(r#"{"price":"9.99"}"#.to_string()),
+            after: Some(r#"{"price":"7.99"}"#.to_string()),
+            created_gmt: Timestamp::from_unix(1_700_000_000),
+        };
+
+        let response = AuditEntryResponse::from(entry);
+        assert_eq!(response.before, Some(serde_json::json!({"price": "9.99"})));
+        assert_eq!(response.after, Some(serde_json::json!({"price": "7.99"})));
+    }
+
+    #[test]
+    fn test_actor_is_the_token_holder() {
+        let claims = Claims::new(12, 3).with_role(crate::auth::Role::MerchantAdmin);
+        assert_eq!(Actor::from(&claims), Actor { id: "12".to_string(), role: "merchant_admin".to_string() });
+    }
+}
@@ -0,0 +1,417 @@
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use chrono::Duration;
+use commercerack_customer::sessions::SessionService;
+use commercerack_customer::throttle::LoginThrottle;
+use commercerack_customer::tokens::{IssuedToken, RefreshTokenService};
+use commercerack_customer::two_factor::TwoFactorService;
+use commercerack_customer::{CustomerError, CustomerService};
+use serde::{Deserialize, Serialize};
+use crate::auth::{Claims, Role};
+use crate::client_ip::ClientIp;
+use crate::error::{ApiError, ErrorBody};
+use crate::routes::customers::{self, CustomerResponse};
+use crate::routes::{sessions, two_factor};
+use crate::validation::{ValidatedJson, Validate, Validator};
+use crate::AppState;
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct LoginRequest {
+    pub mid: i32,
+    pub email: String,
+    pub password: String,
+}
+
+impl Validate for LoginRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("email", &self.email, 65).required("password", &self.password, 128);
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct RegisterRequest {
+    pub mid: i32,
+    pub email: String,
+    pub firstname: String,
+    pub lastname: String,
+    pub password: String,
+}
+
+impl Validate for RegisterRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.email("email", &self.email, 65)
+            .required("firstname", &self.firstname, 50)
+            .required("lastname", &self.lastname, 50)
+            .required("password", &self.password, 128);
+        v.check(self.password.chars().count() >= 8, "password", "must be at least 8 characters");
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+impl Validate for RefreshRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("refresh_token", &self.refresh_token, 128);
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct UnlockRequest {
+    /// Token from the `customer.locked_out` event
+    pub token: String,
+}
+
+impl Validate for UnlockRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("token", &self.token, 128);
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub token_type: String,
+    /// Access token lifetime in seconds
+    pub expires_in: i64,
+}
+
+/// Answer to a correct password: tokens, or a challenge for the second
+/// factor when the customer has two-factor on
+#[derive(Serialize, utoipa::ToSchema)]
+#[serde(untagged)]
+pub enum LoginResponse {
+    Tokens(TokenResponse),
+    TwoFactor(TwoFactorChallenge),
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct TwoFactorChallenge {
+    /// Send with a code to `/api/auth/2fa/verify`
+    pub two_factor_token: String,
+    /// Seconds left to send the code
+    pub expires_in: i64,
+}
+
+/// Sign a short-lived access token for the session of a refresh token and
+/// pair the two. The token carries the role stored on the account; `mfa`
+/// says whether the session passed a second factor.
+pub(crate) async fn token_response(
+    state: &AppState,
+    issued: IssuedToken,
+    mfa: bool,
+) -> Result<TokenResponse, ApiError> {
+    let role = CustomerService::role_of(&*state.db, issued.record.mid, issued.record.cid)
+        .await?
+        .ok_or_else(|| ApiError::Unauthorized("Account no longer exists".to_string()))?;
+    // A role this build doesn't know grants no more than a customer's
+    let role: Role = role.parse().unwrap_or_default();
+
+    let jwt = &state.config.jwt;
+    let ttl = Duration::minutes(jwt.access_token_ttl_minutes);
+    let access_token = Claims::with_ttl(issued.record.cid, issued.record.mid, ttl)
+        .with_role(role)
+        .with_mfa(mfa)
+        .with_session(issued.record.session_id)
+        .encode(&jwt.secret)
+        .map_err(|e| ApiError::Internal(format!("Failed to sign access token: {}", e)))?;
+
+    Ok(TokenResponse {
+        access_token,
+        refresh_token: issued.token,
+        token_type: "Bearer".to_string(),
+        expires_in: ttl.num_seconds(),
+    })
+}
+
+/// Unknown email and wrong password look the same to the caller
+fn invalid_credentials() -> ApiError {
+    ApiError::Unauthorized("Invalid email or password".to_string())
+}
+
+/// Log in with email and password
+///
+/// Repeated failures for an email slow down further attempts from the
+/// same IP and eventually lock the account; see
+/// `commercerack_customer::throttle`. Unknown emails are throttled and
+/// answered exactly like wrong passwords. With two-factor on, the answer
+/// is a challenge to complete at `/api/auth/2fa/verify` instead of tokens.
+#[utoipa::path(
+    post,
+    path = "/api/auth/login",
+    request_body = LoginRequest,
+    responses(
+        (status = 200, description = "Logged in, or a second factor is needed", body = LoginResponse),
+        (status = 401, description = "Invalid credentials", body = ErrorBody),
+        (status = 429, description = "Too many failed logins; retry after the Retry-After header's seconds", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "auth"
+)]
+pub async fn login(
+    State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
+    headers: HeaderMap,
+    ValidatedJson(req): ValidatedJson<LoginRequest>,
+) -> Result<Json<LoginResponse>, ApiError> {
+    let db = &*state.db;
+    LoginThrottle::check(db, req.mid, &req.email, &ip).await?;
+
+    let Some(customer) = CustomerService::authenticate(db, req.mid, &req.email, &req.password).await? else {
+        LoginThrottle::failed(db, req.mid, &req.email, &ip).await?;
+        return Err(invalid_credentials());
+    };
+
+    // Failures stay counted until the second factor passes too
+    if TwoFactorService::is_enabled(db, customer.mid, customer.cid).await? {
+        let challenge = two_factor::challenge(&state.config.jwt, customer.mid, customer.cid)?;
+        return Ok(Json(LoginResponse::TwoFactor(challenge)));
+    }
+    LoginThrottle::succeeded(db, req.mid, &req.email).await?;
+
+    let issued = SessionService::start(db, customer.mid, customer.cid, &sessions::device(&headers), &ip).await?;
+
+    let tokens = token_response(&state, issued, false).await?;
+    Ok(Json(LoginResponse::Tokens(tokens)))
+}
+
+/// Sign up for a customer account at a storefront
+///
+/// Needs no token. The account is always a plain customer, and any guest
+/// orders billed to its email become its own. Log in afterwards for tokens.
+#[utoipa::path(
+    post,
+    path = "/api/auth/register",
+    request_body = RegisterRequest,
+    responses(
+        (status = 201, description = "Account created", body = CustomerResponse),
+        (status = 409, description = "An account with this email already exists", body = ErrorBody),
+        (status = 422, description = "Invalid email, name or password", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "auth"
+)]
+pub async fn register(
+    State(state): State<AppState>,
+    ValidatedJson(req): ValidatedJson<RegisterRequest>,
+) -> Result<(StatusCode, Json<CustomerResponse>), ApiError> {
+    if CustomerService::find_by_email(&*state.db, req.mid, &req.email).await?.is_some() {
+        return Err(ApiError::Conflict("An account with this email already exists".to_string()));
+    }
+
+    let customer =
+        customers::create_account(&state, req.mid, &req.email, &req.firstname, &req.lastname, Some(&req.password))
+            .await?;
+    Ok((StatusCode::CREATED, Json(customer.into())))
+}
+
+/// Lift an account lockout with the token from its `customer.locked_out` event
+#[utoipa::path(
+    post,
+    path = "/api/auth/unlock",
+    request_body = UnlockRequest,
+    responses(
+        (status = 204, description = "Account unlocked"),
+        (status = 404, description = "Unknown or already used unlock token", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "auth"
+)]
+pub async fn unlock(
+    State(state): State<AppState>,
+    ValidatedJson(req): ValidatedJson<UnlockRequest>,
+) -> Result<StatusCode, ApiError> {
+    if !LoginThrottle::unlock(&*state.db, &req.token).await? {
+        return Err(ApiError::NotFound("Unknown or already used unlock token".to_string()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Exchange a refresh token for a new access/refresh token pair
+#[utoipa::path(
+    post,
+    path = "/api/auth/refresh",
+    request_body = RefreshRequest,
+    responses(
+        (status = 200, description = "Tokens rotated", body = TokenResponse),
+        (status = 401, description = "Refresh token invalid, expired, or reused", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "auth"
+)]
+pub async fn refresh(
+    State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
+    ValidatedJson(req): ValidatedJson<RefreshRequest>,
+) -> Result<Json<TokenResponse>, ApiError> {
+    let issued = RefreshTokenService::rotate(&state.db, &req.refresh_token).await?;
+    if let Some(session_id) = issued.record.session_id {
+        SessionService::touch(&*state.db, session_id, &ip).await?;
+    }
+
+    // Refresh tokens are only issued once every factor the customer has is passed
+    let mfa = TwoFactorService::is_enabled(&*state.db, issued.record.mid, issued.record.cid).await?;
+    token_response(&state, issued, mfa).await.map(Json)
+}
+
+/// Revoke a refresh token, signing its session out
+#[utoipa::path(
+    post,
+    path = "/api/auth/logout",
+    request_body = RefreshRequest,
+    responses(
+        (status = 204, description = "Logged out"),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "auth"
+)]
+pub async fn logout(
+    State(state): State<AppState>,
+    ValidatedJson(req): ValidatedJson<RefreshRequest>,
+) -> Result<StatusCode, ApiError> {
+    // Logging out an unknown or already revoked token is not an error
+    let record = RefreshTokenService::find(&*state.db, &req.refresh_token).await?;
+    if let Some((session_id, record)) = record.and_then(|record| Some((record.session_id?, record))) {
+        match SessionService::revoke(&*state.db, record.mid, record.cid, session_id).await {
+            Ok(()) | Err(CustomerError::SessionNotFound) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    RefreshTokenService::revoke(&state.db, &req.refresh_token).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sea_orm::{DatabaseBackend, MockDatabase, MockExecResult};
+
+    #[tokio::test]
+    async fn test_refresh_unknown_token() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([Vec::<::entity::prelude::RefreshToken>::new()])
+            .into_connection();
+
+        let state = AppState::mock(db);
+
+        let req = RefreshRequest { refresh_token: "nope".to_string() };
+        let ip = ClientIp("203.0.113.9".to_string());
+        let result = refresh(State(state), ip, ValidatedJson(req)).await;
+        assert_eq!(result.err().map(|e| e.status()), Some(StatusCode::UNAUTHORIZED));
+    }
+
+    #[tokio::test]
+    async fn test_access_token_carries_the_accounts_role() {
+        let customer = ::entity::customers::Model {
+            cid: 7,
+            mid: 1,
+            email: String::new(),
+            firstname: String::new(),
+            lastname: String::new(),
+            created_gmt: commercerack_core::Timestamp::EPOCH,
+            modified_gmt: commercerack_core::Timestamp::EPOCH,
+            passhash: String::new(),
+            passsalt: String::new(),
+            price_group: String::new(),
+            customer_group: "retail".to_string(),
+            role: "merchant_admin".to_string(),
+        };
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![customer]])
+            .into_connection();
+        let config = commercerack_config::AppConfig::default();
+        let secret = config.jwt.secret.clone();
+        let state = AppState {
+            config: std::sync::Arc::new(config),
+            ..AppState::mock(db)
+        };
+
+        let issued = IssuedToken {
+            token: "refresh".to_string(),
+            record: ::entity::refresh_tokens::Model {
+                id: 1,
+                mid: 1,
+                cid: 7,
+                token_hash: String::new(),
+                created_gmt: commercerack_core::Timestamp::EPOCH,
+                expires_gmt: commercerack_core::Timestamp::EPOCH,
+                revoked_gmt: None,
+                replaced_by: None,
+                session_id: Some(3),
+            },
+        };
+        let tokens = token_response(&state, issued, true).await.unwrap();
+        let claims = Claims::decode(&tokens.access_token, &secret).unwrap();
+        assert_eq!(claims.role, Role::MerchantAdmin);
+        assert_eq!(claims.sid, Some(3));
+        assert!(claims.mfa);
+    }
+
+    fn customer(cid: i32) -> ::entity::customers::Model {
+        ::entity::customers::Model {
+            cid,
+            mid: 1,
+            email: "ann@example.com".to_string(),
+            firstname: "Ann".to_string(),
+            lastname: "Smith".to_string(),
+            created_gmt: commercerack_core::Timestamp::EPOCH,
+            modified_gmt: commercerack_core::Timestamp::EPOCH,
+            passhash: String::new(),
+            passsalt: String::new(),
+            price_group: String::new(),
+            customer_group: "retail".to_string(),
+            role: "customer".to_string(),
+        }
+    }
+
+    fn register_request() -> RegisterRequest {
+        RegisterRequest {
+            mid: 1,
+            email: "ann@example.com".to_string(),
+            firstname: "Ann".to_string(),
+            lastname: "Smith".to_string(),
+            password: "password123".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_register_creates_a_customer_with_their_guest_orders() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([Vec::<::entity::customers::Model>::new(), vec![customer(7)]])
+            .append_exec_results([
+                MockExecResult { last_insert_id: 0, rows_affected: 1 },
+                MockExecResult { last_insert_id: 0, rows_affected: 2 },
+            ])
+            .into_connection();
+        let state = AppState::mock(db);
+        let db = state.db.clone();
+
+        let (status, Json(account)) = register(State(state), ValidatedJson(register_request())).await.unwrap();
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(account.cid, 7);
+
+        let log = std::sync::Arc::try_unwrap(db).ok().unwrap().into_transaction_log();
+        let statements: Vec<String> = log.iter().flat_map(|t| t.statements()).map(|s| s.to_string()).collect();
+        // No role is written, so the account gets the column's `customer`
+        let insert = statements.iter().find(|sql| sql.starts_with(r#"INSERT INTO "customers""#)).unwrap();
+        let (columns, _) = insert.split_once(" VALUES ").unwrap();
+        assert!(!columns.contains(r#""role""#), "{}", insert);
+        assert!(statements.iter().any(|sql| sql.starts_with(r#"UPDATE "orders" SET "customer" = 7"#)), "{:?}", statements);
+    }
+
+    #[tokio::test]
+    async fn test_register_rejects_a_taken_email() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![customer(7)]])
+            .into_connection();
+
+        let result = register(State(AppState::mock(db)), ValidatedJson(register_request())).await;
+        assert_eq!(result.err().map(|e| e.status()), Some(StatusCode::CONFLICT));
+    }
+}
@@ -0,0 +1,139 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use commercerack_core::Timestamp;
+use commercerack_product::availability::{Availability, ProductStatus};
+use commercerack_product::ProductService;
+use entity::prelude::Product;
+use serde::{Deserialize, Serialize};
+use crate::auth::RequireMerchantAdmin;
+use crate::error::{ApiError, ErrorBody};
+use crate::validation::{ValidatedJson, Validate, Validator};
+use crate::AppState;
+
+/// Whether and when a product is on sale; active and open-ended unless
+/// set otherwise
+#[derive(Deserialize, Default, utoipa::ToSchema)]
+pub struct AvailabilityRequest {
+    /// `draft`, `active` or `archived`. A draft with `available_from` is
+    /// activated once that moment passes.
+    pub status: Option<String>,
+    #[schema(value_type = Option<i64>)]
+    pub available_from: Option<Timestamp>,
+    #[schema(value_type = Option<i64>)]
+    pub available_until: Option<Timestamp>,
+}
+
+impl AvailabilityRequest {
+    /// Settings of a validated request
+    pub fn availability(&self) -> Availability {
+        Availability {
+            status: self
+                .status
+                .as_deref()
+                .and_then(|status| status.parse().ok())
+                .unwrap_or_default(),
+            available_from: self.available_from,
+            available_until: self.available_until,
+        }
+    }
+}
+
+impl Validate for AvailabilityRequest {
+    fn validate(&self, v: &mut Validator) {
+        if let Some(status) = &self.status {
+            v.check(
+                status.parse::<ProductStatus>().is_ok(),
+                "status",
+                "must be draft, active or archived",
+            );
+        }
+        if let Err(e) = self.availability().validate() {
+            v.error("available_until", e.to_string());
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct AvailabilityResponse {
+    pub id: i32,
+    pub mid: i32,
+    pub status: String,
+    #[schema(value_type = Option<i64>)]
+    pub available_from: Option<Timestamp>,
+    #[schema(value_type = Option<i64>)]
+    pub available_until: Option<Timestamp>,
+}
+
+impl From<Product> for AvailabilityResponse {
+    fn from(product: Product) -> Self {
+        Self {
+            id: product.id,
+            mid: product.mid,
+            status: product.status,
+            available_from: product.available_from,
+            available_until: product.available_until,
+        }
+    }
+}
+
+/// Change whether and when a product is on sale
+#[utoipa::path(
+    put,
+    path = "/api/products/{mid}/{id}/availability",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Product ID")
+    ),
+    request_body = AvailabilityRequest,
+    responses(
+        (status = 200, description = "Availability updated", body = AvailabilityResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Product not found", body = ErrorBody),
+        (status = 422, description = "Unknown status or a window that ends before it starts", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "products"
+)]
+pub async fn set_availability(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+    ValidatedJson(req): ValidatedJson<AvailabilityRequest>,
+) -> Result<Json<AvailabilityResponse>, ApiError> {
+    ProductService::set_availability(&state.db, admin.0.scoped_mid(mid), id, req.availability())
+        .await
+        .map(|product| Json(product.into()))
+        .map_err(ApiError::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validation;
+
+    fn invalid_fields(req: &AvailabilityRequest) -> Vec<String> {
+        match validation::validate(req) {
+            Err(ApiError::Validation(errors)) => errors.into_iter().map(|e| e.field).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_availability_request_validation() {
+        assert!(invalid_fields(&AvailabilityRequest::default()).is_empty());
+        assert_eq!(AvailabilityRequest::default().availability().status, ProductStatus::Active);
+
+        let scheduled = AvailabilityRequest {
+            status: Some("draft".to_string()),
+            available_from: Some(Timestamp::from_unix(200)),
+            available_until: Some(Timestamp::from_unix(100)),
+        };
+        assert_eq!(invalid_fields(&scheduled), vec!["available_until"]);
+
+        let unknown = AvailabilityRequest { status: Some("hidden".to_string()), ..Default::default() };
+        assert_eq!(invalid_fields(&unknown), vec!["status"]);
+    }
+}
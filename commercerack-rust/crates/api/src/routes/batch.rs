@@ -0,0 +1,90 @@
+//! Shared shapes of the batch-get routes
+//!
+//! Integration code that needs many products or orders asks for them in
+//! one request instead of looping over single GETs. Each ID is answered
+//! separately: found entities come back in the order they were asked for,
+//! and IDs that could not be returned are listed with the reason.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use crate::validation::{Validate, Validator};
+
+/// Most IDs one batch-get request may ask for
+pub const MAX_BATCH_IDS: usize = 100;
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct BatchGetRequest {
+    pub mid: i32,
+    /// Repeated IDs are answered once
+    pub ids: Vec<i32>,
+}
+
+impl Validate for BatchGetRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.check(
+            (1..=MAX_BATCH_IDS).contains(&self.ids.len()),
+            "ids",
+            "must list between 1 and 100 IDs",
+        );
+    }
+}
+
+impl BatchGetRequest {
+    /// The requested IDs, first occurrence of each
+    pub fn unique_ids(&self) -> Vec<i32> {
+        let mut seen = HashSet::new();
+        self.ids.iter().copied().filter(|id| seen.insert(*id)).collect()
+    }
+}
+
+/// Why one requested ID is missing from a batch-get response
+#[derive(Debug, Serialize, PartialEq, utoipa::ToSchema)]
+pub struct BatchGetError {
+    pub id: i32,
+    /// Stable machine-readable code, e.g. `not_found`
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl BatchGetError {
+    pub fn not_found(id: i32, what: &str) -> Self {
+        Self { id, code: "not_found", message: format!("{} not found", what) }
+    }
+}
+
+/// Put `found` in the order of `ids`, keyed by `key`, and report the IDs
+/// that are not among them to `missing`
+pub(crate) fn in_request_order<T>(
+    ids: &[i32],
+    found: Vec<T>,
+    key: impl Fn(&T) -> i32,
+    mut missing: impl FnMut(i32),
+) -> Vec<T> {
+    let mut by_id: std::collections::HashMap<i32, T> = found.into_iter().map(|entity| (key(&entity), entity)).collect();
+    ids.iter()
+        .filter_map(|id| {
+            let entity = by_id.remove(id);
+            if entity.is_none() {
+                missing(*id);
+            }
+            entity
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_results_follow_the_request() {
+        let req = BatchGetRequest { mid: 1, ids: vec![3, 1, 3, 9, 2] };
+        let ids = req.unique_ids();
+        assert_eq!(ids, vec![3, 1, 9, 2]);
+
+        let mut missing = Vec::new();
+        let found = in_request_order(&ids, vec![1, 2, 3], |id| *id, |id| missing.push(id));
+        assert_eq!(found, vec![3, 1, 2]);
+        assert_eq!(missing, vec![9]);
+    }
+}
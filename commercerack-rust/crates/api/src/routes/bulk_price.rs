@@ -0,0 +1,231 @@
+use axum::{extract::State, Json};
+use commercerack_merchant::settings::SettingsService;
+use commercerack_product::bulk_price::{
+    BulkPriceService, BulkPriceUpdate, CategoryAdjustment, PriceChange, PriceTarget, RepricedItem,
+};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use crate::auth::RequireMerchantAdmin;
+use crate::error::{ApiError, ErrorBody};
+use crate::routes::parse_decimal;
+use crate::validation::{ValidatedJson, Validate, Validator};
+use crate::AppState;
+
+/// Most prices one request may set
+pub const MAX_PRICE_CHANGES: usize = 500;
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct BulkPriceUpdateRequest {
+    pub mid: i32,
+    /// Work out the changes without saving them
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Prices to set; send either this or `adjustment`
+    #[serde(default)]
+    pub changes: Vec<PriceChangeRequest>,
+    pub adjustment: Option<CategoryAdjustmentRequest>,
+}
+
+/// New price and cost of one product or SKU; omitted amounts are kept
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct PriceChangeRequest {
+    pub product_id: Option<i32>,
+    pub sku_id: Option<i32>,
+    pub price: Option<String>,
+    pub cost: Option<String>,
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct CategoryAdjustmentRequest {
+    pub category_id: i32,
+    /// Percentage to move prices by, e.g. `-10` for ten percent off
+    pub percent: String,
+    /// Adjust the SKUs of the category's products too
+    #[serde(default = "default_include_skus")]
+    pub include_skus: bool,
+}
+
+fn default_include_skus() -> bool {
+    true
+}
+
+impl Validate for BulkPriceUpdateRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.check(
+            self.changes.is_empty() != self.adjustment.is_none(),
+            "changes",
+            "send either changes or adjustment",
+        )
+        .check(self.changes.len() <= MAX_PRICE_CHANGES, "changes", "must list at most 500 changes")
+        .each("changes", &self.changes);
+        if let Some(adjustment) = &self.adjustment {
+            v.nested("adjustment", adjustment);
+        }
+    }
+}
+
+impl Validate for PriceChangeRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.check(
+            self.product_id.is_some() != self.sku_id.is_some(),
+            "product_id",
+            "send either product_id or sku_id",
+        )
+        .check(self.price.is_some() || self.cost.is_some(), "price", "send price, cost or both");
+        if let Some(price) = &self.price {
+            v.amount("price", price);
+        }
+        if let Some(cost) = &self.cost {
+            v.amount("cost", cost);
+        }
+    }
+}
+
+impl Validate for CategoryAdjustmentRequest {
+    fn validate(&self, v: &mut Validator) {
+        match self.percent.trim().parse::<Decimal>() {
+            Ok(percent) => {
+                v.check(
+                    percent > -Decimal::ONE_HUNDRED && percent <= Decimal::ONE_THOUSAND,
+                    "percent",
+                    "must be above -100 and at most 1000",
+                );
+            }
+            Err(_) => v.error("percent", "must be a decimal number"),
+        }
+    }
+}
+
+impl BulkPriceUpdateRequest {
+    fn update(&self) -> Result<BulkPriceUpdate, ApiError> {
+        if let Some(adjustment) = &self.adjustment {
+            return Ok(BulkPriceUpdate::Adjust(CategoryAdjustment {
+                category_id: adjustment.category_id,
+                percent: parse_decimal("percent", adjustment.percent.trim())?,
+                include_skus: adjustment.include_skus,
+            }));
+        }
+        let amount = |field, value: &Option<String>| value.as_deref().map(|v| parse_decimal(field, v.trim())).transpose();
+        self.changes
+            .iter()
+            .map(|change| {
+                Ok(PriceChange {
+                    target: match (change.product_id, change.sku_id) {
+                        (_, Some(sku_id)) => PriceTarget::Sku(sku_id),
+                        (Some(product_id), None) => PriceTarget::Product(product_id),
+                        (None, None) => return Err(ApiError::BadRequest("Missing product_id or sku_id".to_string())),
+                    },
+                    price: amount("price", &change.price)?,
+                    cost: amount("cost", &change.cost)?,
+                })
+            })
+            .collect::<Result<_, _>>()
+            .map(BulkPriceUpdate::Set)
+    }
+}
+
+/// One price that changed, or would change on a dry run
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct RepricedResponse {
+    pub product_id: Option<i32>,
+    pub sku_id: Option<i32>,
+    pub old_price: String,
+    pub new_price: String,
+    pub old_cost: String,
+    pub new_cost: String,
+}
+
+impl From<RepricedItem> for RepricedResponse {
+    fn from(item: RepricedItem) -> Self {
+        let (product_id, sku_id) = match item.target {
+            PriceTarget::Product(id) => (Some(id), None),
+            PriceTarget::Sku(id) => (None, Some(id)),
+        };
+        Self {
+            product_id,
+            sku_id,
+            old_price: item.old_price.to_string(),
+            new_price: item.new_price.to_string(),
+            old_cost: item.old_cost.to_string(),
+            new_cost: item.new_cost.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct BulkPriceUpdateResponse {
+    /// True when nothing was saved
+    pub dry_run: bool,
+    /// Prices and costs that changed; unchanged items are left out
+    pub changes: Vec<RepricedResponse>,
+}
+
+/// Set many prices, or move a category's prices by a percentage, at once
+#[utoipa::path(
+    post,
+    path = "/api/products/bulk-price-update",
+    request_body = BulkPriceUpdateRequest,
+    responses(
+        (status = 200, description = "Changes applied, or worked out on a dry run", body = BulkPriceUpdateResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 422, description = "Invalid change, or unknown product, SKU or category; nothing was saved", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "products"
+)]
+pub async fn update(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    ValidatedJson(req): ValidatedJson<BulkPriceUpdateRequest>,
+) -> Result<Json<BulkPriceUpdateResponse>, ApiError> {
+    let update = req.update()?;
+    let mid = admin.0.scoped_mid(req.mid);
+    let currency = SettingsService::get(&*state.db, mid).await?.currency;
+    let repriced = BulkPriceService::apply(&state.db, mid, currency, &update, req.dry_run).await?;
+    Ok(Json(BulkPriceUpdateResponse {
+        dry_run: req.dry_run,
+        changes: repriced.into_iter().map(RepricedResponse::from).collect(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validation;
+
+    fn change(product_id: Option<i32>, sku_id: Option<i32>, price: Option<&str>) -> PriceChangeRequest {
+        PriceChangeRequest { product_id, sku_id, price: price.map(str::to_string), cost: None }
+    }
+
+    fn fields(req: &BulkPriceUpdateRequest) -> Vec<String> {
+        match validation::validate(req) {
+            Err(ApiError::Validation(errors)) => errors.into_iter().map(|e| e.field).collect(),
+            Err(_) => unreachable!("validation only fails with field errors"),
+            Ok(()) => Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_request_names_one_target_per_change() {
+        let mut req = BulkPriceUpdateRequest {
+            mid: 1,
+            dry_run: true,
+            changes: vec![
+                change(Some(1), None, Some("9.99")),
+                change(Some(1), Some(2), Some("9.99")),
+                change(None, Some(2), None),
+                change(None, Some(3), Some("-1")),
+            ],
+            adjustment: None,
+        };
+        assert_eq!(fields(&req), vec!["changes[1].product_id", "changes[2].price", "changes[3].price"]);
+
+        req.changes.truncate(1);
+        assert!(fields(&req).is_empty());
+        assert!(matches!(req.update().unwrap(), BulkPriceUpdate::Set(changes) if changes[0].target == PriceTarget::Product(1)));
+
+        req.adjustment = Some(CategoryAdjustmentRequest { category_id: 4, percent: "-100".to_string(), include_skus: true });
+        assert_eq!(fields(&req), vec!["changes", "adjustment.percent"]);
+    }
+}
@@ -1,32 +1,551 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
     Json,
 };
-use commercerack_cart::{Cart, CartItem};
+use commercerack_cart::{AppliedCoupon, AppliedGiftCard, Cart, CartContact, CartGuard, CartItem, ItemOptions};
+use commercerack_core::Currency;
+use commercerack_customer::address::{AddressKind, AddressService, CustomerAddress};
+use commercerack_customer::carts::CustomerCartService;
+use commercerack_customer::CustomerService;
+use commercerack_giftcards::GiftCardService;
+use commercerack_inventory::warehouses;
+use commercerack_merchant::settings::SettingsService;
+use commercerack_order::checkout::{CheckoutService, Fulfillment, TaxContext};
+use commercerack_order::fulfillment_groups::FulfillmentGroupService;
+use commercerack_order::pickup::{self, StoreLocationService};
+use commercerack_order::{OrderError, GUEST_CUSTOMER};
+use commercerack_product::pricing::PricingService;
+use commercerack_promotion::CouponService;
+use commercerack_shipping::split::{self, PlannedShipment};
+use commercerack_shipping::{DeliveryEstimate, DeliveryService, Destination, Parcel, QuotedShipment, ShippingQuote};
+use commercerack_tax::{TaxAddress, TaxLine};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use crate::auth::{Claims, Role};
+use crate::routes::cart_sync;
+use crate::routes::delivery_estimates::{self, DeliveryWindowResponse};
+use crate::routes::orders::OrderResponse;
+use crate::error::{ApiError, ErrorBody, FieldError};
+use crate::routes::parse_decimal;
+use crate::validation::{ValidatedJson, Validate, Validator};
 use crate::AppState;
 
-#[derive(Deserialize)]
+#[derive(Deserialize, utoipa::ToSchema)]
 pub struct AddItemRequest {
+    /// Merchant whose catalog prices the item, tier prices included.
+    /// Signed-in shoppers are always priced by their own merchant.
+    #[serde(default)]
+    pub mid: Option<i32>,
     pub sku: String,
+    /// Defaults to the SKU title when the catalog prices the item
+    #[serde(default)]
     pub product_name: String,
     pub quantity: i32,
-    pub unit_price: String, // Decimal as string from JSON
+    /// Decimal as a string. Only used, and then required, when no merchant is known.
+    #[serde(default)]
+    pub unit_price: Option<String>,
+    /// The same SKU with different options is a separate cart line
+    #[serde(default)]
+    pub options: Option<ItemOptionsRequest>,
 }
 
-#[derive(Deserialize)]
+impl Validate for AddItemRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("sku", &self.sku, 45)
+            .max_len("product_name", &self.product_name, 80)
+            .positive("quantity", self.quantity);
+        if let Some(unit_price) = &self.unit_price {
+            v.amount("unit_price", unit_price);
+        }
+        if let Some(options) = &self.options {
+            v.nested("options", options);
+        }
+    }
+}
+
+/// How the shopper wants the item made up
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct ItemOptionsRequest {
+    /// Chosen variations by name, e.g. `{"size": "M", "color": "red"}`
+    #[serde(default)]
+    pub selections: BTreeMap<String, String>,
+    /// Text to engrave or print on the item
+    #[serde(default)]
+    pub engraving: String,
+    /// Adds the merchant's gift wrap surcharge to each unit
+    #[serde(default)]
+    pub gift_wrap: bool,
+}
+
+impl Validate for ItemOptionsRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.check(self.selections.len() <= 10, "selections", "must have at most 10 entries")
+            .max_len("engraving", &self.engraving, 200);
+        for (name, value) in &self.selections {
+            v.required(&format!("selections.{}", name), name, 40)
+                .required(&format!("selections.{}", name), value, 80);
+        }
+    }
+}
+
+impl From<ItemOptionsRequest> for ItemOptions {
+    fn from(req: ItemOptionsRequest) -> Self {
+        Self {
+            selections: req
+                .selections
+                .into_iter()
+                .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+                .collect(),
+            engraving: req.engraving.trim().to_string(),
+            gift_wrap: req.gift_wrap,
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct MergeCartRequest {
+    /// ID of the visitor's session cart, merged in and then deleted
+    pub cart_id: String,
+}
+
+impl Validate for MergeCartRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("cart_id", &self.cart_id, 36);
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
 pub struct UpdateQuantityRequest {
+    /// Merchant whose tier prices apply at the new quantity
+    #[serde(default)]
+    pub mid: Option<i32>,
+    pub quantity: i32,
+}
+
+impl Validate for UpdateQuantityRequest {
+    fn validate(&self, v: &mut Validator) {
+        // Zero removes the item
+        v.non_negative("quantity", self.quantity);
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct CheckoutRequest {
+    pub mid: i32,
+    /// Customer staff place the order for; `0` (the default) checks out as
+    /// a guest. Signed-in customers always order as themselves, and callers
+    /// without a token always as guests.
+    #[serde(default)]
+    pub customer: i32,
+    /// Billing email. Required for guests: registering with it later links
+    /// their orders to the new account.
+    #[serde(default)]
+    pub email: Option<String>,
+    /// Customer address the order ships to, which decides its tax.
+    /// Defaults to the customer's default shipping address.
+    #[serde(default)]
+    pub address_id: Option<i32>,
+    /// Destination when not shipping to a saved address, e.g. for guests
+    #[serde(default)]
+    pub ship_to: Option<ShipTo>,
+    /// Shipping method code from a shipping quote; requires an address.
+    /// Every shipment of an order split across warehouses ships by it
+    /// unless `shipment_methods` says otherwise.
+    #[serde(default)]
+    pub ship_method: Option<String>,
+    /// Methods for particular shipments, as planned by
+    /// `POST /api/carts/{cart_id}/shipments`; requires `ship_method`
+    #[serde(default)]
+    pub shipment_methods: Vec<ShipmentMethodRequest>,
+    /// `ship` (the default) or `pickup`
+    #[serde(default = "default_fulfillment")]
+    pub fulfillment: String,
+    /// Active store location to collect a pickup order from
+    #[serde(default)]
+    pub pickup_location_id: Option<i32>,
+}
+
+fn default_fulfillment() -> String {
+    pickup::SHIP.to_string()
+}
+
+impl CheckoutRequest {
+    fn is_pickup(&self) -> bool {
+        self.fulfillment == pickup::PICKUP
+    }
+}
+
+impl Validate for CheckoutRequest {
+    fn validate(&self, v: &mut Validator) {
+        if let Some(email) = &self.email {
+            v.email("email", email, 65);
+        }
+        v.check(
+            [pickup::SHIP, pickup::PICKUP].contains(&self.fulfillment.as_str()),
+            "fulfillment",
+            "must be ship or pickup",
+        );
+        if self.is_pickup() {
+            // A pickup order's address is the store's
+            v.check(self.pickup_location_id.is_some(), "pickup_location_id", "is required for pickup")
+                .check(self.ship_method.is_none(), "ship_method", "cannot be combined with pickup")
+                .check(self.shipment_methods.is_empty(), "shipment_methods", "cannot be combined with pickup")
+                .check(self.ship_to.is_none(), "ship_to", "cannot be combined with pickup")
+                .check(self.address_id.is_none(), "address_id", "cannot be combined with pickup");
+        } else {
+            v.check(self.pickup_location_id.is_none(), "pickup_location_id", "is only used for pickup")
+                .check(
+                    self.shipment_methods.is_empty() || self.ship_method.is_some(),
+                    "ship_method",
+                    "is required with shipment_methods",
+                )
+                .each("shipment_methods", &self.shipment_methods);
+        }
+        if let Some(ship_to) = &self.ship_to {
+            v.check(self.address_id.is_none(), "ship_to", "cannot be combined with address_id")
+                .nested("ship_to", ship_to);
+        }
+    }
+}
+
+/// The method one shipment of a split order ships by
+#[derive(Debug, Clone, Deserialize, utoipa::ToSchema)]
+pub struct ShipmentMethodRequest {
+    /// Warehouse of the shipment; omit it for units no warehouse holds
+    #[serde(default)]
+    pub warehouse_id: Option<i32>,
+    pub ship_method: String,
+}
+
+impl Validate for ShipmentMethodRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("ship_method", &self.ship_method, 40);
+    }
+}
+
+/// Where an order ships, which also decides its tax
+#[derive(Debug, Clone, Deserialize, utoipa::ToSchema)]
+pub struct ShipTo {
+    /// ISO 3166-1 alpha-2 code
+    pub country: String,
+    #[serde(default)]
+    pub state: String,
+    #[serde(default)]
+    pub zip: String,
+}
+
+impl Validate for ShipTo {
+    fn validate(&self, v: &mut Validator) {
+        v.check(
+            self.country.trim().len() == 2 && self.country.trim().chars().all(|c| c.is_ascii_alphabetic()),
+            "country",
+            "must be a two-letter country code",
+        )
+        .max_len("state", &self.state, 20)
+        .max_len("zip", &self.zip, 20);
+    }
+}
+
+impl From<&::entity::prelude::StoreLocation> for ShipTo {
+    fn from(location: &::entity::prelude::StoreLocation) -> Self {
+        Self {
+            country: location.country.clone(),
+            state: location.state.clone(),
+            zip: location.zip.clone(),
+        }
+    }
+}
+
+impl From<CustomerAddress> for ShipTo {
+    fn from(address: CustomerAddress) -> Self {
+        Self {
+            country: address.country,
+            state: address.state,
+            zip: address.zip,
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct ApplyCouponRequest {
+    pub mid: i32,
+    pub code: String,
+}
+
+impl Validate for ApplyCouponRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("code", &self.code, 30);
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct CartContactRequest {
+    pub mid: i32,
+    /// Required for guests; signed-in shoppers default to their account email
+    pub email: Option<String>,
+}
+
+impl Validate for CartContactRequest {
+    fn validate(&self, v: &mut Validator) {
+        if let Some(email) = &self.email {
+            v.email("email", email, 65);
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct ApplyGiftCardRequest {
+    pub mid: i32,
+    pub code: String,
+}
+
+impl Validate for ApplyGiftCardRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("code", &self.code, 32);
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct ShippingQuoteRequest {
+    pub mid: i32,
+    /// ISO 3166-1 alpha-2 code
+    pub country: String,
+    #[serde(default)]
+    pub state: String,
+    #[serde(default)]
+    pub zip: String,
+}
+
+impl Validate for ShippingQuoteRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.check(
+            self.country.trim().len() == 2 && self.country.trim().chars().all(|c| c.is_ascii_alphabetic()),
+            "country",
+            "must be a two-letter country code",
+        )
+        .max_len("state", &self.state, 20)
+        .max_len("zip", &self.zip, 20);
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ShippingQuoteResponse {
+    pub carrier: String,
+    /// Pass as `ship_method` at checkout
+    pub method: String,
+    pub name: String,
+    pub amount: String,
+    /// When the method delivers; absent when its transit time is unknown
+    pub delivery: Option<DeliveryWindowResponse>,
+}
+
+impl ShippingQuoteResponse {
+    fn new(quote: ShippingQuote, free_shipping: bool, delivery: &DeliveryEstimate) -> Self {
+        Self {
+            delivery: delivery.window(&quote.method).map(Into::into),
+            carrier: quote.carrier,
+            method: quote.method,
+            name: quote.name,
+            amount: if free_shipping { Decimal::ZERO } else { quote.amount }.to_string(),
+        }
+    }
+}
+
+/// Part of a cart that ships from one warehouse
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct PlannedShipmentResponse {
+    /// Pass with a method in `shipment_methods` at checkout; absent for
+    /// units no warehouse holds
+    pub warehouse_id: Option<i32>,
+    pub items: Vec<PlannedShipmentItemResponse>,
+    /// Ways to ship this part, cheapest first
+    pub quotes: Vec<ShippingQuoteResponse>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct PlannedShipmentItemResponse {
+    pub sku: String,
     pub quantity: i32,
 }
 
-#[derive(Serialize)]
+impl PlannedShipmentResponse {
+    fn new(shipment: PlannedShipment, quotes: Vec<ShippingQuote>, free_shipping: bool, delivery: &DeliveryEstimate) -> Self {
+        Self {
+            warehouse_id: shipment.warehouse_id,
+            items: shipment
+                .lines
+                .into_iter()
+                .map(|(sku, quantity)| PlannedShipmentItemResponse { sku, quantity })
+                .collect(),
+            quotes: quotes.into_iter().map(|quote| ShippingQuoteResponse::new(quote, free_shipping, delivery)).collect(),
+        }
+    }
+}
+
+/// The cart's items as `(sku, quantity)` lines
+fn cart_lines(cart: &Cart) -> Vec<(String, i32)> {
+    cart.items.iter().map(|item| (item.sku.clone(), item.quantity)).collect()
+}
+
+/// When `lines` of the cart would arrive by each method
+async fn delivery_estimate(
+    state: &AppState,
+    mid: i32,
+    lines: &[(String, i32)],
+    destination: &Destination,
+) -> Result<DeliveryEstimate, ApiError> {
+    let placed = delivery_estimates::merchant_now(&state.db, mid).await?;
+    Ok(DeliveryService::estimate(&state.db, mid, lines, destination, placed).await?)
+}
+
+/// The shipments the cart leaves in, each quoted for its method: the one
+/// `methods` gives for its warehouse, or else `method`
+async fn quote_shipments(
+    state: &AppState,
+    mid: i32,
+    cart: &Cart,
+    destination: &Destination,
+    method: &str,
+    methods: &[ShipmentMethodRequest],
+) -> Result<Vec<QuotedShipment>, ApiError> {
+    let planned = split::plan(&*state.db, mid, &cart_lines(cart), destination).await?;
+    if let Some(unplanned) = methods
+        .iter()
+        .find(|chosen| !planned.iter().any(|shipment| shipment.warehouse_id == chosen.warehouse_id))
+    {
+        let message = match unplanned.warehouse_id {
+            Some(id) => format!("warehouse {} has no shipment in this order", id),
+            None => "every unit ships from a warehouse".to_string(),
+        };
+        return Err(ApiError::Validation(vec![FieldError::new("shipment_methods", message)]));
+    }
+
+    let mut shipments = Vec::with_capacity(planned.len());
+    for shipment in planned {
+        let method = methods
+            .iter()
+            .find(|chosen| chosen.warehouse_id == shipment.warehouse_id)
+            .map_or(method, |chosen| chosen.ship_method.as_str());
+        let parcel = Parcel::for_lines(&state.db, mid, cart, &shipment.lines).await?;
+        let quote = commercerack_shipping::quote_method(&state.shipping, mid, destination, &parcel, method).await?;
+        shipments.push(QuotedShipment { shipment, quote });
+    }
+    Ok(shipments)
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct EstimateRequest {
+    pub mid: i32,
+    /// ISO 3166-1 alpha-2 code
+    pub country: String,
+    #[serde(default)]
+    pub state: String,
+    #[serde(default)]
+    pub zip: String,
+    /// Coupon to price the cart with instead of the applied one; the cart
+    /// itself is left as it is
+    #[serde(default)]
+    pub coupon: Option<String>,
+    /// Shipping method the grand total includes; the cheapest when omitted
+    #[serde(default)]
+    pub ship_method: Option<String>,
+}
+
+impl Validate for EstimateRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.check(
+            self.country.trim().len() == 2 && self.country.trim().chars().all(|c| c.is_ascii_alphabetic()),
+            "country",
+            "must be a two-letter country code",
+        )
+        .max_len("state", &self.state, 20)
+        .max_len("zip", &self.zip, 20);
+        if let Some(code) = &self.coupon {
+            v.required("coupon", code, 30);
+        }
+    }
+}
+
+/// What checking the cart out to a destination would cost
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct EstimateResponse {
+    pub cart_id: String,
+    #[schema(value_type = Vec<Object>)]
+    pub items: Vec<CartItem>,
+    #[schema(value_type = String)]
+    pub subtotal: Decimal,
+    #[schema(value_type = Option<Object>)]
+    pub coupon: Option<AppliedCoupon>,
+    #[schema(value_type = String)]
+    pub discount: Decimal,
+    #[schema(value_type = Vec<Object>)]
+    pub tax: Vec<TaxLine>,
+    #[schema(value_type = String)]
+    pub tax_total: Decimal,
+    /// Ways to ship to the destination, cheapest first
+    pub shipping_options: Vec<ShippingQuoteResponse>,
+    /// The option the grand total includes; absent if nothing ships there
+    pub shipping: Option<ShippingQuoteResponse>,
+    #[schema(value_type = String)]
+    pub shipping_total: Decimal,
+    /// Subtotal less the discount, plus tax and shipping. Applied gift
+    /// cards pay towards it at checkout.
+    #[schema(value_type = String)]
+    pub grand_total: Decimal,
+    /// ISO 4217 code every amount is in
+    #[schema(value_type = String, example = "USD")]
+    pub currency: Currency,
+}
+
+/// Where to estimate a cart's tax for
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct TaxEstimateQuery {
+    pub mid: Option<i32>,
+    /// ISO 3166-1 alpha-2 code
+    pub country: Option<String>,
+    #[serde(default)]
+    pub state: String,
+    #[serde(default)]
+    pub zip: String,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
 pub struct CartResponse {
     pub cart_id: String,
+    /// Items that no longer match the catalog carry `warnings`
+    #[schema(value_type = Vec<Object>)]
     pub items: Vec<CartItem>,
+    #[schema(value_type = String)]
     pub subtotal: Decimal,
+    #[schema(value_type = Option<Object>)]
+    pub coupon: Option<AppliedCoupon>,
+    #[schema(value_type = String)]
+    pub discount: Decimal,
+    /// Settled against the order total at checkout
+    #[schema(value_type = Vec<Object>)]
+    pub gift_cards: Vec<AppliedGiftCard>,
+    /// Who is reminded if the cart is abandoned
+    #[schema(value_type = Option<Object>)]
+    pub contact: Option<CartContact>,
+    /// Only estimated when the cart is fetched with a destination
+    #[schema(value_type = Vec<Object>)]
+    pub tax: Vec<TaxLine>,
+    #[schema(value_type = String)]
+    pub total: Decimal,
     pub item_count: i32,
+    /// ISO 4217 code every amount is in
+    #[schema(value_type = String, example = "USD")]
+    pub currency: Currency,
+}
+
+impl CartResponse {
+    fn with_tax(mut self, tax: Vec<TaxLine>) -> Self {
+        self.total += commercerack_tax::total(&tax);
+        self.tax = tax;
+        self
+    }
 }
 
 impl From<&Cart> for CartResponse {
@@ -34,114 +553,1431 @@ impl From<&Cart> for CartResponse {
         Self {
             cart_id: cart.cart_id.clone(),
             items: cart.items.clone(),
-            subtotal: cart.subtotal(),
+            subtotal: cart.subtotal().amount(),
+            coupon: cart.coupon.clone(),
+            discount: cart.discount().amount(),
+            gift_cards: cart.gift_cards.clone(),
+            contact: cart.contact.clone(),
+            tax: Vec::new(),
+            total: cart.total().amount(),
             item_count: cart.item_count(),
+            currency: cart.currency,
         }
     }
 }
 
+/// Load a cart from storage or 404
+pub(crate) async fn load_cart(state: &AppState, cart_id: &str) -> Result<Cart, ApiError> {
+    state
+        .cart_store
+        .get_cart(cart_id)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Cart"))
+}
+
+/// Load a cart to change it. Other changes to the cart wait until the
+/// returned guard is dropped.
+pub(crate) async fn lock_cart(state: &AppState, cart_id: &str) -> Result<(CartGuard, Cart), ApiError> {
+    let guard = state.cart_store.lock_cart(cart_id).await;
+    let cart = load_cart(state, cart_id).await?;
+    Ok((guard, cart))
+}
+
+/// Persist a cart whose items changed and render it. An applied coupon is
+/// recalculated first, and dropped if it no longer applies.
+pub(crate) async fn save_cart(state: &AppState, cart: &mut Cart) -> Result<Json<CartResponse>, ApiError> {
+    CouponService::refresh(&state.db, cart).await?;
+    store_cart(state, cart).await
+}
+
+/// Persist a cart as it is, render it and push it to the cart's open sync
+/// connections
+async fn store_cart(state: &AppState, cart: &Cart) -> Result<Json<CartResponse>, ApiError> {
+    state.cart_store.save_cart(cart).await?;
+    let response = CartResponse::from(cart);
+    cart_sync::publish_cart(&response);
+    Ok(Json(response))
+}
+
 /// Create a new cart
+#[utoipa::path(
+    post,
+    path = "/api/carts",
+    responses(
+        (status = 200, description = "Empty cart", body = CartResponse),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "cart"
+)]
 pub async fn create_cart(
     State(state): State<AppState>,
-) -> Result<Json<CartResponse>, StatusCode> {
-    let mut store = state.cart_store.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    let cart_id = store.create_cart();
-    let cart = store
-        .get_cart(&cart_id)
-        .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
-    Ok(Json(CartResponse::from(cart)))
+) -> Result<Json<CartResponse>, ApiError> {
+    let cart = state.cart_store.create_cart().await?;
+    Ok(Json(CartResponse::from(&cart)))
 }
 
-/// Get cart by ID
+/// Get cart by ID. When the merchant is known (from `mid` or a signed-in
+/// shopper) items are first checked against the catalog: repriced, and
+/// marked with warnings if their price changed or they went out of stock.
+/// With `mid` and a destination `country` (plus optional `state` and
+/// `zip`) the response includes estimated tax.
+#[utoipa::path(
+    get,
+    path = "/api/carts/{cart_id}",
+    params(
+        ("cart_id" = String, Path, description = "Cart ID"),
+        TaxEstimateQuery
+    ),
+    responses(
+        (status = 200, description = "Cart, with estimated tax when a destination was given", body = CartResponse),
+        (status = 401, description = "Invalid token", body = ErrorBody),
+        (status = 404, description = "Cart not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody),
+        (status = 502, description = "The tax provider failed", body = ErrorBody)
+    ),
+    tag = "cart"
+)]
 pub async fn get_cart(
     State(state): State<AppState>,
+    claims: Option<Claims>,
     Path(cart_id): Path<String>,
-) -> Result<Json<CartResponse>, StatusCode> {
-    let store = state.cart_store.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    let cart = store.get_cart(&cart_id).ok_or(StatusCode::NOT_FOUND)?;
-    Ok(Json(CartResponse::from(cart)))
+    Query(query): Query<TaxEstimateQuery>,
+) -> Result<Json<CartResponse>, ApiError> {
+    let (cart, response) = match price_context(&state, &claims, query.mid).await? {
+        Some((mid, group)) => {
+            let (_lock, mut cart) = lock_cart(&state, &cart_id).await?;
+            let before = cart.items.clone();
+            cart.revalidate(&*state.db, mid, Some(&group)).await?;
+            let currency = SettingsService::get(&*state.db, mid).await?.currency;
+            let response = if cart.items != before || cart.currency != currency {
+                cart.currency = currency;
+                save_cart(&state, &mut cart).await?.0
+            } else {
+                CartResponse::from(&cart)
+            };
+            (cart, response)
+        }
+        None => {
+            let cart = load_cart(&state, &cart_id).await?;
+            let response = CartResponse::from(&cart);
+            (cart, response)
+        }
+    };
+
+    match (&state.tax, query.mid, query.country) {
+        (Some(calculator), Some(mid), Some(country)) => {
+            let address = TaxAddress::new(&country, &query.state, &query.zip);
+            let tax = calculator.calculate(mid, &address, cart.total().amount()).await?;
+            Ok(Json(response.with_tax(tax)))
+        }
+        _ => Ok(Json(response)),
+    }
 }
 
 /// Add item to cart
+#[utoipa::path(
+    post,
+    path = "/api/carts/{cart_id}/items",
+    params(
+        ("cart_id" = String, Path, description = "Cart ID")
+    ),
+    request_body = AddItemRequest,
+    responses(
+        (status = 200, description = "Updated cart", body = CartResponse),
+        (status = 404, description = "Cart not found", body = ErrorBody),
+        (status = 422, description = "Validation failed, or the SKU is unknown or not for sale", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "cart"
+)]
 pub async fn add_item(
     State(state): State<AppState>,
+    claims: Option<Claims>,
     Path(cart_id): Path<String>,
-    Json(req): Json<AddItemRequest>,
-) -> Result<Json<CartResponse>, StatusCode> {
-    let unit_price = req
-        .unit_price
-        .parse::<Decimal>()
-        .map_err(|_| StatusCode::BAD_REQUEST)?;
-
-    let mut store = state.cart_store.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    let cart = store
-        .get_cart_mut(&cart_id)
-        .ok_or(StatusCode::NOT_FOUND)?;
+    ValidatedJson(req): ValidatedJson<AddItemRequest>,
+) -> Result<Json<CartResponse>, ApiError> {
+    let pricing = price_context(&state, &claims, req.mid).await?;
+    let options: ItemOptions = req.options.map(Into::into).unwrap_or_default();
+    let surcharge = if options.gift_wrap { state.config.cart.gift_wrap_surcharge } else { Decimal::ZERO };
+    let line_id = commercerack_cart::line_id(&req.sku, &options);
 
-    cart.add_item(req.sku, req.product_name, req.quantity, unit_price);
+    let (_lock, mut cart) = lock_cart(&state, &cart_id).await?;
+    match pricing {
+        Some((mid, group)) => {
+            // Tiers apply to the quantity the line ends up holding
+            let quantity = cart.get_item(&line_id).map_or(0, |item| item.quantity) + req.quantity;
+            let resolved = PricingService::resolve_price(&*state.db, mid, &req.sku, Some(&group), quantity).await?;
+            let product_name = if req.product_name.is_empty() { resolved.sku.title } else { req.product_name };
+            let item = CartItem::new(req.sku, product_name, req.quantity, resolved.unit_price);
+            cart.add_line(item.with_options(options, surcharge));
+            cart.set_unit_price(&line_id, resolved.unit_price);
+            cart.currency = SettingsService::get(&*state.db, mid).await?.currency;
+        }
+        None => {
+            let Some(unit_price) = &req.unit_price else {
+                return Err(ApiError::Validation(vec![FieldError::new(
+                    "unit_price",
+                    "is required unless mid is given to price the item from the catalog",
+                )]));
+            };
+            let unit_price = parse_decimal("unit_price", unit_price)?;
+            let item = CartItem::new(req.sku, req.product_name, req.quantity, unit_price);
+            cart.add_line(item.with_options(options, surcharge));
+        }
+    }
 
-    Ok(Json(CartResponse::from(&*cart)))
+    save_cart(&state, &mut cart).await
 }
 
-/// Update item quantity
+/// Update item quantity, repricing it when the merchant is known
+#[utoipa::path(
+    put,
+    path = "/api/carts/{cart_id}/items/{sku}",
+    params(
+        ("cart_id" = String, Path, description = "Cart ID"),
+        ("sku" = String, Path, description = "Line ID, or SKU for its first line")
+    ),
+    request_body = UpdateQuantityRequest,
+    responses(
+        (status = 200, description = "Updated cart; a quantity of 0 removes the item", body = CartResponse),
+        (status = 404, description = "Cart not found or item not in the cart", body = ErrorBody),
+        (status = 422, description = "Validation failed, or the SKU is no longer for sale", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "cart"
+)]
 pub async fn update_quantity(
     State(state): State<AppState>,
+    claims: Option<Claims>,
     Path((cart_id, sku)): Path<(String, String)>,
-    Json(req): Json<UpdateQuantityRequest>,
-) -> Result<Json<CartResponse>, StatusCode> {
-    let mut store = state.cart_store.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    let cart = store
-        .get_cart_mut(&cart_id)
-        .ok_or(StatusCode::NOT_FOUND)?;
+    ValidatedJson(req): ValidatedJson<UpdateQuantityRequest>,
+) -> Result<Json<CartResponse>, ApiError> {
+    let pricing = price_context(&state, &claims, req.mid).await?;
+    let (_lock, mut cart) = lock_cart(&state, &cart_id).await?;
 
     if !cart.update_quantity(&sku, req.quantity) {
-        return Err(StatusCode::NOT_FOUND);
+        return Err(ApiError::NotFound(format!("Item {} is not in the cart", sku)));
+    }
+    if let (Some((mid, group)), true) = (pricing, req.quantity > 0) {
+        let resolved = PricingService::resolve_price(&*state.db, mid, &sku, Some(&group), req.quantity).await?;
+        cart.set_unit_price(&sku, resolved.unit_price);
+        cart.currency = SettingsService::get(&*state.db, mid).await?.currency;
     }
 
-    Ok(Json(CartResponse::from(&*cart)))
+    save_cart(&state, &mut cart).await
 }
 
 /// Remove item from cart
+#[utoipa::path(
+    delete,
+    path = "/api/carts/{cart_id}/items/{sku}",
+    params(
+        ("cart_id" = String, Path, description = "Cart ID"),
+        ("sku" = String, Path, description = "Line ID, or SKU for its first line")
+    ),
+    responses(
+        (status = 200, description = "Updated cart", body = CartResponse),
+        (status = 404, description = "Cart not found or item not in the cart", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "cart"
+)]
 pub async fn remove_item(
     State(state): State<AppState>,
     Path((cart_id, sku)): Path<(String, String)>,
-) -> Result<Json<CartResponse>, StatusCode> {
-    let mut store = state.cart_store.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    let cart = store
-        .get_cart_mut(&cart_id)
-        .ok_or(StatusCode::NOT_FOUND)?;
+) -> Result<Json<CartResponse>, ApiError> {
+    let (_lock, mut cart) = lock_cart(&state, &cart_id).await?;
 
     if !cart.remove_item(&sku) {
-        return Err(StatusCode::NOT_FOUND);
+        return Err(ApiError::NotFound(format!("Item {} is not in the cart", sku)));
     }
 
-    Ok(Json(CartResponse::from(&*cart)))
+    save_cart(&state, &mut cart).await
 }
 
 /// Clear all items from cart
+#[utoipa::path(
+    post,
+    path = "/api/carts/{cart_id}/clear",
+    params(
+        ("cart_id" = String, Path, description = "Cart ID")
+    ),
+    responses(
+        (status = 200, description = "Emptied cart", body = CartResponse),
+        (status = 404, description = "Cart not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "cart"
+)]
 pub async fn clear_cart(
     State(state): State<AppState>,
     Path(cart_id): Path<String>,
-) -> Result<Json<CartResponse>, StatusCode> {
-    let mut store = state.cart_store.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    let cart = store
-        .get_cart_mut(&cart_id)
-        .ok_or(StatusCode::NOT_FOUND)?;
+) -> Result<Json<CartResponse>, ApiError> {
+    let (_lock, mut cart) = lock_cart(&state, &cart_id).await?;
 
     cart.clear();
-    Ok(Json(CartResponse::from(&*cart)))
+    save_cart(&state, &mut cart).await
+}
+
+/// Merchant and price group that price cart items: a signed-in shopper's
+/// (see [`commercerack_customer::pricing_group`]), otherwise the merchant
+/// the request names, with no group. `None` when neither is known and the
+/// client's price stands.
+async fn price_context(
+    state: &AppState,
+    claims: &Option<Claims>,
+    mid: Option<i32>,
+) -> Result<Option<(i32, String)>, ApiError> {
+    if let Some((mid, customer)) = shopper(claims)? {
+        let group = CustomerService::pricing_group_of(&*state.db, mid, customer).await?;
+        return Ok(Some((mid, group)));
+    }
+
+    let mid = match claims {
+        Some(claims) => mid.map(|mid| claims.scoped_mid(mid)),
+        None => mid,
+    };
+    Ok(mid.map(|mid| (mid, String::new())))
+}
+
+/// Signed-in shoppers always act as themselves, for their own merchant
+fn shopper(claims: &Option<Claims>) -> Result<Option<(i32, i32)>, ApiError> {
+    match claims {
+        Some(claims) if claims.role == Role::Customer => {
+            let customer = claims
+                .sub
+                .parse()
+                .map_err(|_| ApiError::Unauthorized("Token subject is not a customer ID".to_string()))?;
+            Ok(Some((claims.mid, customer)))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Apply a coupon code to the cart, replacing any coupon already applied
+#[utoipa::path(
+    post,
+    path = "/api/carts/{cart_id}/coupon",
+    params(
+        ("cart_id" = String, Path, description = "Cart ID")
+    ),
+    request_body = ApplyCouponRequest,
+    responses(
+        (status = 200, description = "Cart with the coupon applied", body = CartResponse),
+        (status = 404, description = "Cart not found", body = ErrorBody),
+        (status = 422, description = "Unknown coupon, or it does not apply to the cart", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "cart"
+)]
+pub async fn apply_coupon(
+    State(state): State<AppState>,
+    claims: Option<Claims>,
+    Path(cart_id): Path<String>,
+    ValidatedJson(req): ValidatedJson<ApplyCouponRequest>,
+) -> Result<Json<CartResponse>, ApiError> {
+    let (_lock, mut cart) = lock_cart(&state, &cart_id).await?;
+
+    // Per-customer limits can only be checked for signed-in shoppers here;
+    // checkout checks them again for everyone
+    let (mid, customer) = match shopper(&claims)? {
+        Some((mid, customer)) => (mid, Some(customer)),
+        None => (req.mid, None),
+    };
+    CouponService::apply(&state.db, mid, &req.code, &mut cart, customer).await?;
+
+    store_cart(&state, &cart).await
+}
+
+/// Remove the cart's coupon
+#[utoipa::path(
+    delete,
+    path = "/api/carts/{cart_id}/coupon",
+    params(
+        ("cart_id" = String, Path, description = "Cart ID")
+    ),
+    responses(
+        (status = 200, description = "Cart without a coupon", body = CartResponse),
+        (status = 404, description = "Cart not found or no coupon applied", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "cart"
+)]
+pub async fn remove_coupon(
+    State(state): State<AppState>,
+    Path(cart_id): Path<String>,
+) -> Result<Json<CartResponse>, ApiError> {
+    let (_lock, mut cart) = lock_cart(&state, &cart_id).await?;
+
+    if !cart.remove_coupon() {
+        return Err(ApiError::NotFound("No coupon is applied to the cart".to_string()));
+    }
+
+    store_cart(&state, &cart).await
+}
+
+/// Set who to remind about the cart if it is abandoned. Signed-in
+/// shoppers are always the contact for their own carts.
+#[utoipa::path(
+    put,
+    path = "/api/carts/{cart_id}/contact",
+    params(
+        ("cart_id" = String, Path, description = "Cart ID")
+    ),
+    request_body = CartContactRequest,
+    responses(
+        (status = 200, description = "Cart with its contact", body = CartResponse),
+        (status = 404, description = "Cart or customer not found", body = ErrorBody),
+        (status = 422, description = "Validation failed", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "cart"
+)]
+pub async fn set_contact(
+    State(state): State<AppState>,
+    claims: Option<Claims>,
+    Path(cart_id): Path<String>,
+    ValidatedJson(req): ValidatedJson<CartContactRequest>,
+) -> Result<Json<CartResponse>, ApiError> {
+    let (_lock, mut cart) = lock_cart(&state, &cart_id).await?;
+
+    let contact = match shopper(&claims)? {
+        Some((mid, cid)) => {
+            let email = match req.email {
+                Some(email) => email,
+                None => {
+                    CustomerService::find_by_id(&state.db, mid, cid)
+                        .await?
+                        .ok_or_else(|| ApiError::not_found("Customer"))?
+                        .email
+                }
+            };
+            CartContact { mid, cid, email }
+        }
+        None => {
+            let email = req
+                .email
+                .ok_or_else(|| ApiError::Validation(vec![FieldError::new("email", "is required for guests")]))?;
+            CartContact { mid: req.mid, cid: GUEST_CUSTOMER, email }
+        }
+    };
+    cart.contact = Some(CartContact {
+        email: contact.email.trim().to_string(),
+        ..contact
+    });
+
+    store_cart(&state, &cart).await
+}
+
+/// Apply a gift card to the cart. Several cards can be applied; they are
+/// drawn down in the order they were added.
+#[utoipa::path(
+    post,
+    path = "/api/carts/{cart_id}/gift-cards",
+    params(
+        ("cart_id" = String, Path, description = "Cart ID")
+    ),
+    request_body = ApplyGiftCardRequest,
+    responses(
+        (status = 200, description = "Cart with the gift card applied", body = CartResponse),
+        (status = 404, description = "Cart or gift card not found", body = ErrorBody),
+        (status = 422, description = "Gift card voided, expired or empty", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "cart"
+)]
+pub async fn apply_gift_card(
+    State(state): State<AppState>,
+    claims: Option<Claims>,
+    Path(cart_id): Path<String>,
+    ValidatedJson(req): ValidatedJson<ApplyGiftCardRequest>,
+) -> Result<Json<CartResponse>, ApiError> {
+    let (_lock, mut cart) = lock_cart(&state, &cart_id).await?;
+
+    let mid = shopper(&claims)?.map_or(req.mid, |(mid, _)| mid);
+    GiftCardService::apply(&*state.db, mid, &req.code, &mut cart).await?;
+
+    store_cart(&state, &cart).await
+}
+
+/// Take a gift card off the cart
+#[utoipa::path(
+    delete,
+    path = "/api/carts/{cart_id}/gift-cards/{code}",
+    params(
+        ("cart_id" = String, Path, description = "Cart ID"),
+        ("code" = String, Path, description = "Gift card code")
+    ),
+    responses(
+        (status = 200, description = "Cart without the gift card", body = CartResponse),
+        (status = 404, description = "Cart not found or gift card not applied", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "cart"
+)]
+pub async fn remove_gift_card(
+    State(state): State<AppState>,
+    Path((cart_id, code)): Path<(String, String)>,
+) -> Result<Json<CartResponse>, ApiError> {
+    let (_lock, mut cart) = lock_cart(&state, &cart_id).await?;
+
+    if !cart.remove_gift_card(&code) {
+        return Err(ApiError::NotFound("Gift card is not applied to the cart".to_string()));
+    }
+
+    store_cart(&state, &cart).await
 }
 
 /// Delete cart
+#[utoipa::path(
+    delete,
+    path = "/api/carts/{cart_id}",
+    params(
+        ("cart_id" = String, Path, description = "Cart ID")
+    ),
+    responses(
+        (status = 204, description = "Cart deleted"),
+        (status = 404, description = "Cart not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "cart"
+)]
 pub async fn delete_cart(
     State(state): State<AppState>,
     Path(cart_id): Path<String>,
-) -> Result<StatusCode, StatusCode> {
-    let mut store = state.cart_store.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+) -> Result<StatusCode, ApiError> {
+    let deleted = state.cart_store.delete_cart(&cart_id).await?;
 
-    if store.delete_cart(&cart_id) {
+    if deleted {
+        cart_sync::publish_deleted(&cart_id);
         Ok(StatusCode::NO_CONTENT)
     } else {
-        Err(StatusCode::NOT_FOUND)
+        Err(ApiError::not_found("Cart"))
+    }
+}
+
+/// Merge a visitor's session cart into the signed-in customer's cart, as
+/// the login flow does. Lines both carts hold have their quantities
+/// combined at the session cart's price. The session cart is deleted.
+#[utoipa::path(
+    post,
+    path = "/api/carts/{cart_id}/merge",
+    params(
+        ("cart_id" = String, Path, description = "ID of the customer's cart, which is kept")
+    ),
+    request_body = MergeCartRequest,
+    responses(
+        (status = 200, description = "Merged cart", body = CartResponse),
+        (status = 401, description = "Not signed in", body = ErrorBody),
+        (status = 403, description = "Not a customer, or the cart belongs to another customer", body = ErrorBody),
+        (status = 404, description = "Either cart not found", body = ErrorBody),
+        (status = 422, description = "Validation failed", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "cart"
+)]
+pub async fn merge_cart(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(cart_id): Path<String>,
+    ValidatedJson(req): ValidatedJson<MergeCartRequest>,
+) -> Result<Json<CartResponse>, ApiError> {
+    let Some((mid, cid)) = shopper(&Some(claims))? else {
+        return Err(ApiError::Forbidden("Only customers can merge carts".to_string()));
+    };
+    if req.cart_id == cart_id {
+        return Err(ApiError::Validation(vec![FieldError::new("cart_id", "must be a different cart")]));
+    }
+
+    // Always lock in the same order so two merges of one pair can't deadlock
+    let (first, second) = if req.cart_id < cart_id { (&req.cart_id, &cart_id) } else { (&cart_id, &req.cart_id) };
+    let _first = state.cart_store.lock_cart(first).await;
+    let _second = state.cart_store.lock_cart(second).await;
+
+    let cart = load_cart(&state, &cart_id).await?;
+    if let Some(contact) = &cart.contact {
+        if contact.cid != GUEST_CUSTOMER && (contact.mid, contact.cid) != (mid, cid) {
+            return Err(ApiError::Forbidden("Cart belongs to another customer".to_string()));
+        }
+    }
+    let mut merged = state
+        .cart_store
+        .merge(&req.cart_id, &cart_id)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Cart"))?;
+    cart_sync::publish_deleted(&req.cart_id);
+
+    save_cart(&state, &mut merged).await
+}
+
+/// Ways to ship the cart to a destination, cheapest first
+#[utoipa::path(
+    post,
+    path = "/api/carts/{cart_id}/shipping-quotes",
+    params(
+        ("cart_id" = String, Path, description = "Cart ID")
+    ),
+    request_body = ShippingQuoteRequest,
+    responses(
+        (status = 200, description = "Available shipping methods; empty if nothing ships there", body = Vec<ShippingQuoteResponse>),
+        (status = 404, description = "Cart not found", body = ErrorBody),
+        (status = 422, description = "Invalid destination", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody),
+        (status = 502, description = "A carrier failed to quote", body = ErrorBody)
+    ),
+    tag = "cart"
+)]
+pub async fn shipping_quotes(
+    State(state): State<AppState>,
+    claims: Option<Claims>,
+    Path(cart_id): Path<String>,
+    ValidatedJson(req): ValidatedJson<ShippingQuoteRequest>,
+) -> Result<Json<Vec<ShippingQuoteResponse>>, ApiError> {
+    let cart = load_cart(&state, &cart_id).await?;
+    let mid = shopper(&claims)?.map(|(mid, _)| mid).unwrap_or(req.mid);
+
+    let destination = Destination::new(&req.country, &req.state, &req.zip);
+    let parcel = Parcel::for_cart(&state.db, mid, &cart).await?;
+    let quotes = commercerack_shipping::quote_all(&state.shipping, mid, &destination, &parcel).await?;
+    let delivery = delivery_estimate(&state, mid, &cart_lines(&cart), &destination).await?;
+
+    let free_shipping = cart.coupon.as_ref().is_some_and(|coupon| coupon.free_shipping);
+    Ok(Json(quotes.into_iter().map(|quote| ShippingQuoteResponse::new(quote, free_shipping, &delivery)).collect()))
+}
+
+/// The shipments the cart would leave in for a destination, one per
+/// warehouse stocking its items, each with the ways to ship it. Choose a
+/// method per shipment with `shipment_methods` at checkout.
+#[utoipa::path(
+    post,
+    path = "/api/carts/{cart_id}/shipments",
+    params(
+        ("cart_id" = String, Path, description = "Cart ID")
+    ),
+    request_body = ShippingQuoteRequest,
+    responses(
+        (status = 200, description = "Planned shipments, warehouses by ID and units no warehouse holds last", body = Vec<PlannedShipmentResponse>),
+        (status = 404, description = "Cart not found", body = ErrorBody),
+        (status = 422, description = "Invalid destination", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody),
+        (status = 502, description = "A carrier failed to quote", body = ErrorBody)
+    ),
+    tag = "cart"
+)]
+pub async fn shipments(
+    State(state): State<AppState>,
+    claims: Option<Claims>,
+    Path(cart_id): Path<String>,
+    ValidatedJson(req): ValidatedJson<ShippingQuoteRequest>,
+) -> Result<Json<Vec<PlannedShipmentResponse>>, ApiError> {
+    let cart = load_cart(&state, &cart_id).await?;
+    let mid = shopper(&claims)?.map(|(mid, _)| mid).unwrap_or(req.mid);
+
+    let destination = Destination::new(&req.country, &req.state, &req.zip);
+    let free_shipping = cart.coupon.as_ref().is_some_and(|coupon| coupon.free_shipping);
+    let planned = split::plan(&*state.db, mid, &cart_lines(&cart), &destination).await?;
+    let mut shipments = Vec::with_capacity(planned.len());
+    for shipment in planned {
+        let parcel = Parcel::for_lines(&state.db, mid, &cart, &shipment.lines).await?;
+        let quotes = commercerack_shipping::quote_all(&state.shipping, mid, &destination, &parcel).await?;
+        let delivery = delivery_estimate(&state, mid, &shipment.lines, &destination).await?;
+        shipments.push(PlannedShipmentResponse::new(shipment, quotes, free_shipping, &delivery));
+    }
+    Ok(Json(shipments))
+}
+
+/// Price the cart for a destination without placing an order: subtotal,
+/// discount, tax, the ways to ship it and the grand total. A `coupon` is
+/// tried out on a copy; the stored cart is not changed.
+#[utoipa::path(
+    post,
+    path = "/api/carts/{cart_id}/estimate",
+    params(
+        ("cart_id" = String, Path, description = "Cart ID")
+    ),
+    request_body = EstimateRequest,
+    responses(
+        (status = 200, description = "Itemized totals", body = EstimateResponse),
+        (status = 404, description = "Cart or coupon not found", body = ErrorBody),
+        (status = 422, description = "Invalid destination, coupon does not apply, or ship_method not offered", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody),
+        (status = 502, description = "A carrier or the tax provider failed", body = ErrorBody)
+    ),
+    tag = "cart"
+)]
+pub async fn estimate(
+    State(state): State<AppState>,
+    claims: Option<Claims>,
+    Path(cart_id): Path<String>,
+    ValidatedJson(req): ValidatedJson<EstimateRequest>,
+) -> Result<Json<EstimateResponse>, ApiError> {
+    let mut cart = load_cart(&state, &cart_id).await?;
+    let (mid, customer) = match shopper(&claims)? {
+        Some((mid, customer)) => (mid, Some(customer)),
+        None => (req.mid, None),
+    };
+    if let Some(code) = &req.coupon {
+        CouponService::apply(&state.db, mid, code, &mut cart, customer).await?;
+    }
+
+    let tax = match &state.tax {
+        Some(calculator) => {
+            let address = TaxAddress::new(&req.country, &req.state, &req.zip);
+            calculator.calculate(mid, &address, cart.total().amount()).await?
+        }
+        None => Vec::new(),
+    };
+
+    let destination = Destination::new(&req.country, &req.state, &req.zip);
+    let parcel = Parcel::for_cart(&state.db, mid, &cart).await?;
+    let quotes = commercerack_shipping::quote_all(&state.shipping, mid, &destination, &parcel).await?;
+    let delivery = delivery_estimate(&state, mid, &cart_lines(&cart), &destination).await?;
+    let free_shipping = cart.coupon.as_ref().is_some_and(|coupon| coupon.free_shipping);
+    let chosen = match &req.ship_method {
+        Some(method) => Some(quotes.iter().find(|quote| &quote.method == method).ok_or_else(|| {
+            ApiError::Validation(vec![FieldError::new("ship_method", "is not offered for this destination")])
+        })?),
+        None => quotes.first(),
+    };
+    let shipping_total = match chosen {
+        Some(quote) if !free_shipping => quote.amount,
+        _ => Decimal::ZERO,
+    };
+    let shipping = chosen.cloned().map(|quote| ShippingQuoteResponse::new(quote, free_shipping, &delivery));
+
+    let tax_total = commercerack_tax::total(&tax);
+    Ok(Json(EstimateResponse {
+        cart_id: cart.cart_id.clone(),
+        items: cart.items.clone(),
+        subtotal: cart.subtotal().amount(),
+        coupon: cart.coupon.clone(),
+        discount: cart.discount().amount(),
+        grand_total: cart.total().amount() + tax_total + shipping_total,
+        currency: SettingsService::get(&*state.db, mid).await?.currency,
+        tax,
+        tax_total,
+        shipping_options: quotes
+            .into_iter()
+            .map(|quote| ShippingQuoteResponse::new(quote, free_shipping, &delivery))
+            .collect(),
+        shipping,
+        shipping_total,
+    }))
+}
+
+/// The address an order ships to: the requested one, which must belong to
+/// the customer, or else their default shipping address if they have one
+async fn shipping_address(
+    state: &AppState,
+    mid: i32,
+    customer: i32,
+    address_id: Option<i32>,
+) -> Result<Option<CustomerAddress>, ApiError> {
+    let address = match address_id {
+        Some(id) => Some(
+            AddressService::find_by_id(&state.db, mid, id)
+                .await?
+                .filter(|address| address.cid == customer)
+                .ok_or_else(|| ApiError::Validation(vec![FieldError::new("address_id", "is not an address of this customer")]))?,
+        ),
+        None => AddressService::find_default(&state.db, mid, customer, AddressKind::Shipping).await?,
+    };
+
+    Ok(address)
+}
+
+/// Check a cart about to be checked out against the catalog. If any item
+/// changed, the repriced cart is saved and checkout is refused so the
+/// shopper can review it. Checkout prices the order from the catalog again
+/// itself; this is what hands the shopper the updated cart.
+async fn revalidate_for_checkout(state: &AppState, cart: &mut Cart, mid: i32, customer: i32) -> Result<(), ApiError> {
+    // Checkout refuses empty carts anyway
+    if cart.is_empty() {
+        return Ok(());
+    }
+    let group = if customer == GUEST_CUSTOMER {
+        String::new()
+    } else {
+        CustomerService::pricing_group_of(&*state.db, mid, customer).await?
+    };
+    if !cart.revalidate(&*state.db, mid, Some(&group)).await? {
+        return Ok(());
+    }
+
+    let Json(saved) = save_cart(state, cart).await?;
+    let changes: Vec<String> = saved
+        .items
+        .iter()
+        .flat_map(|item| item.warnings.iter().map(move |warning| format!("{}: {}", item.sku, warning)))
+        .collect();
+    Err(ApiError::Conflict(format!("Cart changed since it was last priced; {}", changes.join("; "))))
+}
+
+/// Convert a cart into an order
+#[utoipa::path(
+    post,
+    path = "/api/carts/{cart_id}/checkout",
+    params(
+        ("cart_id" = String, Path, description = "Cart ID")
+    ),
+    request_body = CheckoutRequest,
+    responses(
+        (status = 201, description = "Order placed", body = OrderResponse),
+        (status = 400, description = "Cart is empty or has invalid or unknown items", body = ErrorBody),
+        (status = 404, description = "Cart not found", body = ErrorBody),
+        (status = 409, description = "Cart was already checked out, or an item is out of stock, left the catalog or was repriced; the cart is saved with warnings", body = ErrorBody),
+        (status = 422, description = "Applied coupon can no longer be used, unknown address, shipping method unavailable, pickup store unavailable, guest without an email, or refused by a custom checkout stage", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "cart"
+)]
+pub async fn checkout(
+    State(state): State<AppState>,
+    claims: Option<Claims>,
+    Path(cart_id): Path<String>,
+    ValidatedJson(req): ValidatedJson<CheckoutRequest>,
+) -> Result<(StatusCode, Json<OrderResponse>), ApiError> {
+    // Held until the cart is deleted, so the same cart can't be checked out twice at once
+    let (_lock, mut cart) = lock_cart(&state, &cart_id).await?;
+
+    // Only staff may check out for a customer; anyone without a token is a guest
+    let (mid, customer) = match (shopper(&claims)?, &claims) {
+        (Some(shopper), _) => shopper,
+        (None, Some(claims)) => (claims.scoped_mid(req.mid), req.customer),
+        (None, None) => (req.mid, GUEST_CUSTOMER),
+    };
+    revalidate_for_checkout(&state, &mut cart, mid, customer).await?;
+
+    let location = match req.pickup_location_id.filter(|_| req.is_pickup()) {
+        Some(id) => Some(StoreLocationService::pickup_location(&*state.db, mid, id).await.map_err(|e| match e {
+            OrderError::LocationNotFound => {
+                ApiError::Validation(vec![FieldError::new("pickup_location_id", "is not a store open for pickup")])
+            }
+            e => e.into(),
+        })?),
+        None => None,
+    };
+
+    let guest = customer == GUEST_CUSTOMER;
+    let address = match req.ship_to {
+        // Pickup orders are taxed where they are collected
+        _ if location.is_some() => location.as_ref().map(ShipTo::from),
+        Some(ship_to) => Some(ship_to),
+        // Guests have no saved addresses to fall back on
+        None if guest && req.address_id.is_none() => None,
+        None if state.tax.is_some() || req.ship_method.is_some() => {
+            shipping_address(&state, mid, customer, req.address_id).await?.map(ShipTo::from)
+        }
+        None => None,
+    };
+
+    let shipments = match (&req.ship_method, &address) {
+        (Some(method), Some(address)) => {
+            let destination = Destination::new(&address.country, &address.state, &address.zip);
+            quote_shipments(&state, mid, &cart, &destination, method, &req.shipment_methods).await?
+        }
+        (Some(_), None) => {
+            return Err(ApiError::Validation(vec![FieldError::new("address_id", "a shipping address is required to ship the order")]));
+        }
+        (None, _) => Vec::new(),
+    };
+
+    let tax = match (&state.tax, &address) {
+        (Some(calculator), Some(address)) => Some(TaxContext {
+            calculator: calculator.as_ref(),
+            address: TaxAddress::new(&address.country, &address.state, &address.zip),
+        }),
+        _ => None,
+    };
+
+    let email = req.email.as_deref().unwrap_or_default();
+    let ship_to = address.as_ref().map(|address| warehouses::ShipTo::new(&address.country, &address.state));
+    let split = !shipments.is_empty();
+    let fulfillment = match location {
+        Some(location) => Fulfillment::Pickup(Box::new(location)),
+        None => Fulfillment::Ship(shipments),
+    };
+    let order = CheckoutService::place_order(&*state.db, mid, customer, email, &cart, tax, fulfillment, ship_to.as_ref())
+        .await?;
+
+    // The order is committed; the cart is spent
+    state.cart_store.delete_cart(&cart_id).await?;
+    cart_sync::publish_deleted(&cart_id);
+    if customer != GUEST_CUSTOMER {
+        CustomerCartService::forget(&*state.db, &cart_id).await?;
+    }
+
+    let groups = if split {
+        FulfillmentGroupService::for_order(&*state.db, mid, order.order.id).await?
+    } else {
+        Vec::new()
+    };
+    let mut response = OrderResponse::from(order);
+    response.fulfillment_groups = groups.into_iter().map(Into::into).collect();
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sea_orm::{DatabaseBackend, MockDatabase, MockExecResult};
+    use commercerack_core::Timestamp;
+
+    fn catalog_sku(price: Decimal) -> ::entity::prelude::Sku {
+        ::entity::prelude::Sku {
+            id: 5,
+            pid: 2,
+            mid: 1,
+            sku: "SKU001".to_string(),
+            title: "Widget".to_string(),
+            price,
+            cost: Decimal::ZERO,
+            upc: String::new(),
+            inv_available: 100,
+            qty_onshelf: 100,
+            weight: Decimal::ZERO,
+            reorder_point: None,
+            reorder_quantity: 0,
+            low_stock_gmt: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_checkout_empty_cart() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+        let state = AppState::mock(db);
+
+        let cart = state.cart_store.create_cart().await.unwrap();
+        let req = CheckoutRequest {
+            mid: 1,
+            customer: 1,
+            email: None,
+            address_id: None,
+            ship_to: None,
+            ship_method: None,
+            shipment_methods: Vec::new(),
+            fulfillment: default_fulfillment(),
+            pickup_location_id: None,
+        };
+
+        let result = checkout(State(state.clone()), None, Path(cart.cart_id.clone()), ValidatedJson(req)).await;
+        assert_eq!(result.err().map(|e| e.status()), Some(StatusCode::BAD_REQUEST));
+
+        // A failed checkout leaves the cart intact
+        assert!(state.cart_store.get_cart(&cart.cart_id).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_guest_checkout_requires_email() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![catalog_sku(Decimal::new(1000, 2))]])
+            .append_query_results([Vec::<::entity::prelude::PriceTier>::new()])
+            .into_connection();
+        let state = AppState::mock(db);
+
+        let mut cart = state.cart_store.create_cart().await.unwrap();
+        cart.add_item("SKU001".to_string(), "Widget".to_string(), 1, Decimal::new(1000, 2));
+        state.cart_store.save_cart(&cart).await.unwrap();
+
+        let req = CheckoutRequest {
+            mid: 1,
+            customer: GUEST_CUSTOMER,
+            email: None,
+            address_id: None,
+            ship_to: Some(ShipTo { country: "US".to_string(), state: "NY".to_string(), zip: "10001".to_string() }),
+            ship_method: None,
+            shipment_methods: Vec::new(),
+            fulfillment: default_fulfillment(),
+            pickup_location_id: None,
+        };
+        match checkout(State(state), None, Path(cart.cart_id), ValidatedJson(req)).await.err() {
+            Some(ApiError::Validation(errors)) => assert_eq!(errors[0].field, "email"),
+            other => panic!("unexpected result {:?}", other.map(|e| e.status())),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_item_uses_tier_price() {
+        let sku = catalog_sku(Decimal::new(1000, 2));
+        let tier = ::entity::prelude::PriceTier {
+            id: 9,
+            mid: 1,
+            sku: "SKU001".to_string(),
+            price_group: String::new(),
+            min_qty: 10,
+            price: Decimal::new(850, 2),
+            created_gmt: Timestamp::EPOCH,
+            modified_gmt: Timestamp::EPOCH,
+        };
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![sku]])
+            .append_query_results([vec![tier]])
+            .append_query_results([vec![::entity::prelude::MerchantSetting {
+                id: 1,
+                mid: 58,
+                key: "currency".to_string(),
+                value: "\"JPY\"".to_string(),
+                modified_gmt: Timestamp::EPOCH,
+            }]])
+            .into_connection();
+        let state = AppState::mock(db);
+        let cart = state.cart_store.create_cart().await.unwrap();
+
+        // The client's price is ignored once the catalog can price the item
+        let req = AddItemRequest {
+            mid: Some(58),
+            sku: "SKU001".to_string(),
+            product_name: String::new(),
+            quantity: 12,
+            unit_price: Some("0.01".to_string()),
+            options: None,
+        };
+        let Json(response) = add_item(State(state), None, Path(cart.cart_id), ValidatedJson(req)).await.unwrap();
+        assert_eq!(response.items[0].unit_price, Decimal::new(850, 2));
+        assert_eq!(response.items[0].product_name, "Widget");
+        // Priced from the merchant's catalog, so in their currency
+        assert_eq!(response.currency, Currency::JPY);
+    }
+
+    #[tokio::test]
+    async fn test_add_item_without_merchant_needs_price() {
+        let state = AppState::mock(MockDatabase::new(DatabaseBackend::Postgres).into_connection());
+        let cart = state.cart_store.create_cart().await.unwrap();
+
+        let req = AddItemRequest {
+            mid: None,
+            sku: "SKU001".to_string(),
+            product_name: "Widget".to_string(),
+            quantity: 1,
+            unit_price: None,
+            options: None,
+        };
+        match add_item(State(state), None, Path(cart.cart_id), ValidatedJson(req)).await.err() {
+            Some(ApiError::Validation(errors)) => assert_eq!(errors[0].field, "unit_price"),
+            other => panic!("unexpected result {:?}", other.map(|e| e.status())),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_item_with_options_adds_a_line() {
+        let mut config = commercerack_config::AppConfig::default();
+        config.cart.gift_wrap_surcharge = Decimal::new(300, 2);
+        let state = AppState {
+            config: std::sync::Arc::new(config),
+            ..AppState::mock(MockDatabase::new(DatabaseBackend::Postgres).into_connection())
+        };
+        let cart = state.cart_store.create_cart().await.unwrap();
+        let req = |options: Option<ItemOptionsRequest>| AddItemRequest {
+            mid: None,
+            sku: "SKU001".to_string(),
+            product_name: "Widget".to_string(),
+            quantity: 1,
+            unit_price: Some("10.00".to_string()),
+            options,
+        };
+        let wrapped = || ItemOptionsRequest {
+            selections: BTreeMap::from([("size".to_string(), " M ".to_string())]),
+            engraving: String::new(),
+            gift_wrap: true,
+        };
+
+        let mut response = None;
+        for options in [None, Some(wrapped()), Some(wrapped())] {
+            let Json(added) =
+                add_item(State(state.clone()), None, Path(cart.cart_id.clone()), ValidatedJson(req(options))).await.unwrap();
+            response = Some(added);
+        }
+        let response = response.unwrap();
+
+        assert_eq!(response.items.len(), 2);
+        assert_eq!(response.items[0].quantity, 1);
+        assert_eq!(response.items[1].quantity, 2);
+        assert_eq!(response.items[1].options.selections["size"], "M");
+        assert_eq!(response.items[1].surcharge, Decimal::new(300, 2));
+        assert_eq!(response.subtotal, Decimal::new(3600, 2));
+    }
+
+    #[tokio::test]
+    async fn test_merge_cart_on_login() {
+        let state = AppState::mock(MockDatabase::new(DatabaseBackend::Postgres).into_connection());
+        let mut session = state.cart_store.create_cart().await.unwrap();
+        session.add_item("SKU001".to_string(), "Widget".to_string(), 1, Decimal::new(900, 2));
+        state.cart_store.save_cart(&session).await.unwrap();
+        let mut customer_cart = state.cart_store.create_cart().await.unwrap();
+        customer_cart.add_item("SKU001".to_string(), "Widget".to_string(), 2, Decimal::new(1000, 2));
+        customer_cart.contact = Some(CartContact { mid: 1, cid: 7, email: "a@example.com".to_string() });
+        state.cart_store.save_cart(&customer_cart).await.unwrap();
+
+        let req = || MergeCartRequest { cart_id: session.cart_id.clone() };
+        let result = merge_cart(
+            State(state.clone()),
+            Claims::new(8, 1),
+            Path(customer_cart.cart_id.clone()),
+            ValidatedJson(req()),
+        )
+        .await;
+        assert!(matches!(result, Err(ApiError::Forbidden(_))));
+
+        let Json(merged) =
+            merge_cart(State(state.clone()), Claims::new(7, 1), Path(customer_cart.cart_id), ValidatedJson(req()))
+                .await
+                .unwrap();
+        assert_eq!(merged.items.len(), 1);
+        assert_eq!(merged.items[0].quantity, 3);
+        assert_eq!(merged.items[0].unit_price, Decimal::new(900, 2));
+        assert!(state.cart_store.get_cart(&session.cart_id).await.unwrap().is_none());
+    }
+
+    struct FixedRates;
+
+    #[async_trait::async_trait]
+    impl commercerack_shipping::ShippingRateProvider for FixedRates {
+        fn name(&self) -> &'static str {
+            "fixed"
+        }
+
+        async fn quote(
+            &self,
+            _mid: i32,
+            _destination: &Destination,
+            _parcel: &Parcel,
+        ) -> Result<Vec<ShippingQuote>, commercerack_shipping::ShippingError> {
+            let quote = |method: &str, amount| ShippingQuote {
+                carrier: "fixed".to_string(),
+                method: method.to_string(),
+                name: method.to_string(),
+                amount: Decimal::new(amount, 2),
+            };
+            Ok(vec![quote("express", 1500), quote("ground", 500)])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_estimate_itemizes_totals() {
+        let state = AppState {
+            shipping: vec![std::sync::Arc::new(FixedRates)],
+            ..AppState::mock(
+                MockDatabase::new(DatabaseBackend::Postgres)
+                    // Each estimate weighs the cart, then reads the merchant's
+                    // timezone, warehouses and shipping zones; none exist
+                    .append_query_results((0..8).map(|_| Vec::<::entity::prelude::Sku>::new()))
+                    .into_connection(),
+            )
+        };
+        let mut cart = state.cart_store.create_cart().await.unwrap();
+        cart.add_item("SKU001".to_string(), "Widget".to_string(), 2, Decimal::new(1000, 2));
+        state.cart_store.save_cart(&cart).await.unwrap();
+        let req = |ship_method: Option<&str>| EstimateRequest {
+            mid: 1,
+            country: "US".to_string(),
+            state: String::new(),
+            zip: "97201".to_string(),
+            coupon: None,
+            ship_method: ship_method.map(str::to_string),
+        };
+
+        let Json(breakdown) =
+            estimate(State(state.clone()), None, Path(cart.cart_id.clone()), ValidatedJson(req(None))).await.unwrap();
+        assert_eq!(breakdown.subtotal, Decimal::new(2000, 2));
+        assert_eq!(breakdown.shipping_options.len(), 2);
+        assert_eq!(breakdown.shipping.unwrap().method, "ground");
+        assert_eq!(breakdown.grand_total, Decimal::new(2500, 2));
+
+        let Json(breakdown) =
+            estimate(State(state.clone()), None, Path(cart.cart_id.clone()), ValidatedJson(req(Some("express"))))
+                .await
+                .unwrap();
+        assert_eq!(breakdown.shipping_total, Decimal::new(1500, 2));
+        assert_eq!(breakdown.grand_total, Decimal::new(3500, 2));
+    }
+
+    #[test]
+    fn test_item_options_validation() {
+        let options = ItemOptionsRequest {
+            selections: BTreeMap::from([(String::new(), "M".to_string())]),
+            engraving: "x".repeat(201),
+            gift_wrap: false,
+        };
+        match crate::validation::validate(&options) {
+            Err(ApiError::Validation(errors)) => {
+                let fields: Vec<_> = errors.iter().map(|e| e.field.as_str()).collect();
+                assert_eq!(fields, vec!["engraving", "selections."]);
+            }
+            _ => panic!("expected validation errors"),
+        }
+    }
+
+    #[test]
+    fn test_checkout_validation() {
+        let req = CheckoutRequest {
+            mid: 1,
+            customer: GUEST_CUSTOMER,
+            email: Some("not-an-email".to_string()),
+            address_id: Some(3),
+            ship_to: Some(ShipTo { country: "USA".to_string(), state: String::new(), zip: String::new() }),
+            ship_method: None,
+            shipment_methods: Vec::new(),
+            fulfillment: default_fulfillment(),
+            pickup_location_id: None,
+        };
+        match crate::validation::validate(&req) {
+            Err(ApiError::Validation(errors)) => {
+                let fields: Vec<_> = errors.iter().map(|e| e.field.as_str()).collect();
+                assert_eq!(fields, vec!["email", "ship_to", "ship_to.country"]);
+            }
+            other => panic!("unexpected result {:?}", other.err().map(|e| e.status())),
+        }
+    }
+
+    #[test]
+    fn test_pickup_checkout_validation() {
+        let req = CheckoutRequest {
+            mid: 1,
+            customer: 1,
+            email: None,
+            address_id: None,
+            ship_to: None,
+            ship_method: Some("GROUND".to_string()),
+            shipment_methods: Vec::new(),
+            fulfillment: pickup::PICKUP.to_string(),
+            pickup_location_id: None,
+        };
+        match crate::validation::validate(&req) {
+            Err(ApiError::Validation(errors)) => {
+                let fields: Vec<_> = errors.iter().map(|e| e.field.as_str()).collect();
+                assert_eq!(fields, vec!["pickup_location_id", "ship_method"]);
+            }
+            other => panic!("unexpected result {:?}", other.err().map(|e| e.status())),
+        }
+
+        let req = CheckoutRequest { ship_method: None, pickup_location_id: Some(4), ..req };
+        assert!(crate::validation::validate(&req).is_ok());
+        let req = CheckoutRequest { fulfillment: "drone".to_string(), ..req };
+        assert!(crate::validation::validate(&req).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_apply_unknown_coupon() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([Vec::<::entity::prelude::Coupon>::new()])
+            .into_connection();
+        let state = AppState::mock(db);
+
+        let cart = state.cart_store.create_cart().await.unwrap();
+        let req = ApplyCouponRequest { mid: 1, code: "NOPE".to_string() };
+
+        let result = apply_coupon(State(state), None, Path(cart.cart_id), ValidatedJson(req)).await;
+        assert_eq!(result.err().map(|e| e.status()), Some(StatusCode::NOT_FOUND));
+    }
+
+    #[tokio::test]
+    async fn test_get_cart_estimates_tax() {
+        let rates = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![::entity::prelude::TaxRate {
+                id: 1,
+                mid: 1,
+                name: "NY State Tax".to_string(),
+                country: "US".to_string(),
+                state: "NY".to_string(),
+                zip: String::new(),
+                rate: Decimal::new(4, 0),
+                zone_id: None,
+                created_gmt: Timestamp::EPOCH,
+                modified_gmt: Timestamp::EPOCH,
+            }]])
+            .into_connection();
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![catalog_sku(Decimal::new(1000, 2))]])
+            .append_query_results([Vec::<::entity::prelude::PriceTier>::new()])
+            .into_connection();
+        let state = AppState {
+            tax: Some(std::sync::Arc::new(commercerack_tax::RateTableCalculator::new(std::sync::Arc::new(rates)))),
+            ..AppState::mock(db)
+        };
+
+        let mut cart = state.cart_store.create_cart().await.unwrap();
+        cart.add_item("SKU001".to_string(), "Widget".to_string(), 2, Decimal::new(1000, 2));
+        state.cart_store.save_cart(&cart).await.unwrap();
+
+        let query = TaxEstimateQuery {
+            mid: Some(1),
+            country: Some("us".to_string()),
+            state: "ny".to_string(),
+            zip: "10001".to_string(),
+        };
+        let Json(response) = get_cart(State(state), None, Path(cart.cart_id), Query(query)).await.unwrap();
+        assert_eq!(response.tax.len(), 1);
+        assert_eq!(response.tax[0].amount, Decimal::new(80, 2));
+        assert_eq!(response.total, Decimal::new(2080, 2));
+    }
+
+    #[tokio::test]
+    async fn test_checkout_refuses_repriced_cart() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![catalog_sku(Decimal::new(1200, 2))]])
+            .append_query_results([Vec::<::entity::prelude::PriceTier>::new()])
+            .into_connection();
+        let state = AppState::mock(db);
+
+        let mut cart = state.cart_store.create_cart().await.unwrap();
+        cart.add_item("SKU001".to_string(), "Widget".to_string(), 1, Decimal::new(1000, 2));
+        state.cart_store.save_cart(&cart).await.unwrap();
+
+        let req = CheckoutRequest {
+            mid: 1,
+            customer: GUEST_CUSTOMER,
+            email: Some("guest@example.com".to_string()),
+            address_id: None,
+            ship_to: None,
+            ship_method: None,
+            shipment_methods: Vec::new(),
+            fulfillment: default_fulfillment(),
+            pickup_location_id: None,
+        };
+        match checkout(State(state.clone()), None, Path(cart.cart_id.clone()), ValidatedJson(req)).await.err() {
+            Some(ApiError::Conflict(message)) => assert!(message.contains("SKU001: price changed from 10.00 to 12.00")),
+            other => panic!("unexpected result {:?}", other.map(|e| e.status())),
+        }
+
+        // The shopper gets the cart back at the new price, with the warning
+        let saved = state.cart_store.get_cart(&cart.cart_id).await.unwrap().unwrap();
+        assert_eq!(saved.items[0].unit_price, Decimal::new(1200, 2));
+        assert_eq!(saved.items[0].warnings.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_checkout_refuses_items_not_for_sale() {
+        // The SKU's product is a draft, so pricing doesn't find it
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([Vec::<::entity::prelude::Sku>::new()])
+            .into_connection();
+        let state = AppState::mock(db);
+
+        let mut cart = state.cart_store.create_cart().await.unwrap();
+        cart.add_item("SKU001".to_string(), "Widget".to_string(), 1, Decimal::new(1000, 2));
+        state.cart_store.save_cart(&cart).await.unwrap();
+
+        let req = CheckoutRequest {
+            mid: 1,
+            customer: GUEST_CUSTOMER,
+            email: Some("guest@example.com".to_string()),
+            address_id: None,
+            ship_to: None,
+            ship_method: None,
+            shipment_methods: Vec::new(),
+            fulfillment: default_fulfillment(),
+            pickup_location_id: None,
+        };
+        match checkout(State(state), None, Path(cart.cart_id), ValidatedJson(req)).await.err() {
+            Some(ApiError::Conflict(message)) => assert!(message.contains("SKU001: no longer sold"), "{}", message),
+            other => panic!("unexpected result {:?}", other.map(|e| e.status())),
+        }
+    }
+
+    fn guest_order() -> ::entity::prelude::Order {
+        ::entity::prelude::Order {
+            id: 9,
+            mid: 41,
+            orderid: "2025-01-01-9".to_string(),
+            cartid: "cart-1".to_string(),
+            customer: GUEST_CUSTOMER,
+            pool: "RECENT".to_string(),
+            total: Decimal::new(1000, 2),
+            created_gmt: Timestamp::from_unix(1_700_000_000),
+            paid_gmt: None,
+            paid_txn: None,
+            order_payment_status: None,
+            order_payment_lookup: None,
+            bs_settlement: None,
+            shipped_gmt: None,
+            inv_gmt: None,
+            company_id: None,
+            review_status: None,
+            ship_method: None,
+            bill_email: "guest@example.com".to_string(),
+            v: 1,
+            mkt: 0,
+            erefid: None,
+            mkt_bitstr: String::new(),
+            pickup_location_id: None,
+            pickup_ready_gmt: None,
+            picked_up_gmt: None,
+            cancelled_gmt: None,
+            cancel_reason: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_anonymous_checkout_naming_a_customer_is_a_guest_order() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![catalog_sku(Decimal::new(1000, 2))]])
+            .append_query_results([Vec::<::entity::prelude::PriceTier>::new()])
+            .append_query_results([Vec::<::entity::prelude::Order>::new()])
+            .append_query_results([Vec::<::entity::prelude::MerchantSetting>::new()])
+            .append_query_results([vec![catalog_sku(Decimal::new(1000, 2))]])
+            .append_query_results([Vec::<::entity::prelude::PriceTier>::new()])
+            .append_query_results([vec![guest_order()]])
+            .append_query_results([vec![::entity::order_items::Model {
+                id: 1,
+                mid: 41,
+                order_id: 9,
+                sku: "SKU001".to_string(),
+                product_name: "Widget".to_string(),
+                quantity: 1,
+                unit_price: Decimal::new(1000, 2),
+                options: String::new(),
+            }]])
+            .append_query_results([vec![guest_order()]])
+            .append_query_results([Vec::<::entity::prelude::Warehouse>::new()])
+            .append_query_results([vec![catalog_sku(Decimal::new(1000, 2))]])
+            .append_query_results([Vec::<::entity::prelude::InventoryReservation>::new()])
+            .append_query_results([vec![::entity::inventory_adjustments::Model {
+                id: 1,
+                mid: 41,
+                sku: "SKU001".to_string(),
+                delta: -1,
+                inv_available_after: 99,
+                reason: "order".to_string(),
+                note: None,
+                order_id: Some(9),
+                actor: None,
+                warehouse_id: None,
+                created_gmt: Timestamp::from_unix(1_700_000_000),
+            }]])
+            .append_query_results([Vec::<::entity::prelude::Order>::new()])
+            .append_exec_results([
+                MockExecResult { last_insert_id: 0, rows_affected: 1 },
+                MockExecResult { last_insert_id: 0, rows_affected: 1 },
+                MockExecResult { last_insert_id: 0, rows_affected: 0 },
+                MockExecResult { last_insert_id: 0, rows_affected: 1 },
+            ])
+            .into_connection();
+        let state = AppState::mock(db);
+        let db = state.db.clone();
+
+        let mut cart = state.cart_store.create_cart().await.unwrap();
+        cart.add_item("SKU001".to_string(), "Widget".to_string(), 1, Decimal::new(1000, 2));
+        state.cart_store.save_cart(&cart).await.unwrap();
+
+        let req = CheckoutRequest {
+            mid: 41,
+            customer: 7,
+            email: Some("guest@example.com".to_string()),
+            address_id: None,
+            ship_to: None,
+            ship_method: None,
+            shipment_methods: Vec::new(),
+            fulfillment: default_fulfillment(),
+            pickup_location_id: None,
+        };
+        let (status, _) = checkout(State(state), None, Path(cart.cart_id), ValidatedJson(req)).await.unwrap();
+        assert_eq!(status, StatusCode::CREATED);
+
+        // Priced as a guest and written as one; customer 7's account is never read
+        let log = std::sync::Arc::try_unwrap(db).ok().unwrap().into_transaction_log();
+        let statements: Vec<String> = log.iter().flat_map(|t| t.statements()).map(|s| s.to_string()).collect();
+        assert!(!statements.iter().any(|sql| sql.contains(r#"FROM "customers""#)), "{:?}", statements);
+        let insert = statements.iter().find(|sql| sql.starts_with(r#"INSERT INTO "orders""#)).unwrap();
+        assert!(insert.contains(&format!(", {}, 'RECENT', ", GUEST_CUSTOMER)), "{}", insert);
     }
 }
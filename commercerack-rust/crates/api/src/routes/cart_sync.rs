@@ -0,0 +1,298 @@
+//! Live cart synchronization over WebSockets
+//!
+//! Every tab or device showing a cart opens `/ws/carts/{cart_id}` and is
+//! sent the cart as it is, then again after every change made through the
+//! API, so they never drift apart. Browsers can't set headers on a
+//! WebSocket, so the connection is authorized by a short-lived cart token
+//! in the query string, fetched beforehand from
+//! `/api/carts/{cart_id}/sync-token`. The token only has to be valid when
+//! connecting.
+//!
+//! Frames are JSON text: `{"type":"cart","cart":{...}}` with the full cart,
+//! or `{"type":"deleted"}` once the cart is gone (checked out or deleted),
+//! after which the server closes the socket. The server pings on an
+//! interval and drops connections that stop answering.
+//!
+//! Changes are broadcast within this process only.
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Path, Query, State,
+    },
+    response::Response,
+    Json,
+};
+use chrono::{Duration as TokenTtl, Utc};
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::time::Instant;
+use crate::error::{ApiError, ErrorBody};
+use crate::routes::cart::{load_cart, CartResponse};
+use crate::AppState;
+
+/// How long a cart token can be used to connect
+const CART_TOKEN_TTL_SECS: i64 = 5 * 60;
+
+/// Updates buffered per connection before a slow one is resynced
+const CHANNEL_CAPACITY: usize = 16;
+
+/// How often connections are pinged
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A connection silent for this long is considered dead
+const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(75);
+
+/// What happened to a cart, as broadcast to its connections
+#[derive(Debug, Clone, PartialEq)]
+enum CartUpdate {
+    /// The cart's new state, already encoded as a `cart` frame
+    Changed(Arc<str>),
+    Deleted,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Frame<'a> {
+    Cart { cart: &'a CartResponse },
+    Deleted,
+}
+
+fn encode_frame(frame: &Frame) -> Arc<str> {
+    // Plain structs always serialize
+    serde_json::to_string(frame).unwrap_or_default().into()
+}
+
+/// Broadcast channels of the carts that have connections open
+#[derive(Default)]
+struct CartChannels {
+    channels: Mutex<HashMap<String, broadcast::Sender<CartUpdate>>>,
+}
+
+impl CartChannels {
+    fn subscribe(&self, cart_id: &str) -> broadcast::Receiver<CartUpdate> {
+        let mut channels = self.channels.lock().unwrap_or_else(|e| e.into_inner());
+        channels
+            .entry(cart_id.to_string())
+            .or_insert_with(|| broadcast::channel(CHANNEL_CAPACITY).0)
+            .subscribe()
+    }
+
+    fn publish(&self, cart_id: &str, update: CartUpdate) {
+        let mut channels = self.channels.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(sender) = channels.get(cart_id) {
+            if sender.send(update).is_err() {
+                channels.remove(cart_id);
+            }
+        }
+    }
+
+    /// Forget the channel once its last connection has closed
+    fn release(&self, cart_id: &str) {
+        let mut channels = self.channels.lock().unwrap_or_else(|e| e.into_inner());
+        if channels.get(cart_id).is_some_and(|sender| sender.receiver_count() == 0) {
+            channels.remove(cart_id);
+        }
+    }
+}
+
+fn channels() -> &'static CartChannels {
+    static CHANNELS: OnceLock<CartChannels> = OnceLock::new();
+    CHANNELS.get_or_init(CartChannels::default)
+}
+
+/// Tell the cart's connections about its new state
+pub(crate) fn publish_cart(cart: &CartResponse) {
+    channels().publish(&cart.cart_id, CartUpdate::Changed(encode_frame(&Frame::Cart { cart })));
+}
+
+/// Tell the cart's connections that it no longer exists
+pub(crate) fn publish_deleted(cart_id: &str) {
+    channels().publish(cart_id, CartUpdate::Deleted);
+}
+
+/// Claims of a cart token. Access tokens lack `cart` and so never pass.
+#[derive(Debug, Serialize, Deserialize)]
+struct CartTokenClaims {
+    cart: String,
+    iat: i64,
+    exp: i64,
+}
+
+fn issue_token(cart_id: &str, secret: &str) -> Result<String, jsonwebtoken::errors::Error> {
+    let now = Utc::now();
+    let claims = CartTokenClaims {
+        cart: cart_id.to_string(),
+        iat: now.timestamp(),
+        exp: (now + TokenTtl::seconds(CART_TOKEN_TTL_SECS)).timestamp(),
+    };
+    encode(&Header::default(), &claims, &EncodingKey::from_secret(secret.as_bytes()))
+}
+
+fn verify_token(token: &str, cart_id: &str, secret: &str) -> Result<(), ApiError> {
+    let claims = decode::<CartTokenClaims>(token, &DecodingKey::from_secret(secret.as_bytes()), &Validation::default())
+        .map_err(|e| ApiError::Unauthorized(format!("Invalid cart token: {}", e)))?
+        .claims;
+    if claims.cart != cart_id {
+        return Err(ApiError::Unauthorized("Cart token is for another cart".to_string()));
+    }
+    Ok(())
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct CartTokenResponse {
+    pub token: String,
+    /// Seconds the token can be used to connect
+    pub expires_in: i64,
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct ConnectQuery {
+    /// From `/api/carts/{cart_id}/sync-token`
+    pub token: String,
+}
+
+/// Get a token to connect to the cart's sync socket with
+#[utoipa::path(
+    post,
+    path = "/api/carts/{cart_id}/sync-token",
+    params(("cart_id" = String, Path, description = "Cart ID")),
+    responses(
+        (status = 200, description = "Short-lived cart token", body = CartTokenResponse),
+        (status = 404, description = "Cart not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "cart"
+)]
+pub async fn sync_token(
+    State(state): State<AppState>,
+    Path(cart_id): Path<String>,
+) -> Result<Json<CartTokenResponse>, ApiError> {
+    load_cart(&state, &cart_id).await?;
+    let token = issue_token(&cart_id, &state.config.jwt.secret)
+        .map_err(|e| ApiError::Internal(format!("Failed to issue cart token: {}", e)))?;
+    Ok(Json(CartTokenResponse { token, expires_in: CART_TOKEN_TTL_SECS }))
+}
+
+/// Follow a cart's changes over a WebSocket
+#[utoipa::path(
+    get,
+    path = "/ws/carts/{cart_id}",
+    params(("cart_id" = String, Path, description = "Cart ID"), ConnectQuery),
+    responses(
+        (status = 101, description = "Switching to the WebSocket protocol; the cart is sent first"),
+        (status = 401, description = "Missing, expired or mismatched cart token", body = ErrorBody),
+        (status = 404, description = "Cart not found", body = ErrorBody)
+    ),
+    tag = "cart"
+)]
+pub async fn connect(
+    State(state): State<AppState>,
+    Path(cart_id): Path<String>,
+    Query(query): Query<ConnectQuery>,
+    ws: WebSocketUpgrade,
+) -> Result<Response, ApiError> {
+    verify_token(&query.token, &cart_id, &state.config.jwt.secret)?;
+    // Subscribed before loading so no change slips in between
+    let updates = channels().subscribe(&cart_id);
+    let cart = CartResponse::from(&load_cart(&state, &cart_id).await?);
+    let first = encode_frame(&Frame::Cart { cart: &cart });
+
+    Ok(ws.on_upgrade(move |socket| async move {
+        sync(socket, &state, &cart_id, first, updates).await;
+        channels().release(&cart_id);
+    }))
+}
+
+/// Relay updates to the socket until either side goes away
+async fn sync(
+    mut socket: WebSocket,
+    state: &AppState,
+    cart_id: &str,
+    first: Arc<str>,
+    mut updates: broadcast::Receiver<CartUpdate>,
+) {
+    if socket.send(Message::Text(first.to_string())).await.is_err() {
+        return;
+    }
+
+    let mut heartbeat = tokio::time::interval_at(Instant::now() + HEARTBEAT_INTERVAL, HEARTBEAT_INTERVAL);
+    let mut last_seen = Instant::now();
+    loop {
+        let outgoing = tokio::select! {
+            update = updates.recv() => match update {
+                Ok(CartUpdate::Changed(frame)) => Message::Text(frame.to_string()),
+                Ok(CartUpdate::Deleted) | Err(RecvError::Closed) => {
+                    let _ = socket.send(Message::Text(encode_frame(&Frame::Deleted).to_string())).await;
+                    let _ = socket.send(Message::Close(None)).await;
+                    return;
+                }
+                // Missed some; the current state supersedes them all
+                Err(RecvError::Lagged(_)) => match state.cart_store.get_cart(cart_id).await {
+                    Ok(Some(cart)) => Message::Text(encode_frame(&Frame::Cart { cart: &CartResponse::from(&cart) }).to_string()),
+                    _ => continue,
+                },
+            },
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                // Pongs, and anything else the client sends, show it is alive
+                Some(Ok(_)) => {
+                    last_seen = Instant::now();
+                    continue;
+                }
+            },
+            _ = heartbeat.tick() => {
+                if last_seen.elapsed() > HEARTBEAT_TIMEOUT {
+                    let _ = socket.send(Message::Close(None)).await;
+                    return;
+                }
+                Message::Ping(Vec::new())
+            }
+        };
+        if socket.send(outgoing).await.is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "test-secret-that-is-long-enough-for-hs256";
+
+    #[test]
+    fn test_cart_token_is_bound_to_its_cart() {
+        let token = issue_token("cart-1", SECRET).unwrap();
+        assert!(verify_token(&token, "cart-1", SECRET).is_ok());
+        assert!(verify_token(&token, "cart-2", SECRET).is_err());
+        assert!(verify_token(&token, "cart-1", "another-secret-that-is-long-enough").is_err());
+
+        // An access token is not a cart token
+        let access = crate::auth::Claims::new(1, 1).encode(SECRET).unwrap();
+        assert!(verify_token(&access, "cart-1", SECRET).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_updates_reach_only_the_carts_connections() {
+        let channels = CartChannels::default();
+        let mut first = channels.subscribe("cart-1");
+        let mut other = channels.subscribe("cart-2");
+
+        channels.publish("cart-1", CartUpdate::Changed("{}".into()));
+        channels.publish("cart-1", CartUpdate::Deleted);
+        assert_eq!(first.recv().await.unwrap(), CartUpdate::Changed("{}".into()));
+        assert_eq!(first.recv().await.unwrap(), CartUpdate::Deleted);
+        assert!(other.try_recv().is_err());
+
+        // Channels are dropped once nobody listens
+        drop(first);
+        channels.release("cart-1");
+        assert!(!channels.channels.lock().unwrap().contains_key("cart-1"));
+        assert!(channels.channels.lock().unwrap().contains_key("cart-2"));
+    }
+}
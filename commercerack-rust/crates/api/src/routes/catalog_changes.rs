@@ -0,0 +1,138 @@
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use commercerack_core::Timestamp;
+use commercerack_db::pagination::Cursor;
+use commercerack_product::changes::{CatalogChange, ChangeFeed, Changed};
+use serde::{Deserialize, Serialize};
+use crate::auth::RequireMerchantAdmin;
+use crate::error::{ApiError, ErrorBody};
+use crate::routes::products::ProductResponse;
+use crate::routes::skus::SkuResponse;
+use crate::validation::{self, Validate, Validator};
+use crate::AppState;
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct ChangesQuery {
+    pub mid: i32,
+    /// `next_cursor` of the previous response; omit to start from the
+    /// oldest kept change
+    pub since: Option<String>,
+    #[serde(default = "default_limit")]
+    pub limit: u64,
+}
+
+fn default_limit() -> u64 {
+    100
+}
+
+impl Validate for ChangesQuery {
+    fn validate(&self, v: &mut Validator) {
+        if let Some(since) = &self.since {
+            v.check(since.parse::<Cursor>().is_ok(), "since", "is not a cursor from a previous response");
+        }
+        v.check((1..=500).contains(&self.limit), "limit", "must be between 1 and 500");
+    }
+}
+
+/// One product or SKU change
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct CatalogChangeResponse {
+    /// Position in the feed; later changes have higher numbers
+    pub seq: i32,
+    /// `product` or `sku`
+    pub entity: &'static str,
+    pub id: i32,
+    /// `created`, `updated` or `deleted`
+    pub action: &'static str,
+    #[schema(value_type = i64)]
+    pub at: Timestamp,
+    /// The product right after the change. Absent for deletions and media
+    /// changes, and never carries media; fetch the product for those.
+    pub product: Option<ProductResponse>,
+    /// The SKU right after the change; absent for deletions
+    pub sku: Option<SkuResponse>,
+}
+
+impl From<CatalogChange> for CatalogChangeResponse {
+    fn from(change: CatalogChange) -> Self {
+        let (entity, id, product, sku) = match change.changed {
+            Changed::Product { id, product } => ("product", id, product.map(|product| ProductResponse::from(*product)), None),
+            Changed::Sku { id, sku } => ("sku", id, None, sku.map(SkuResponse::from)),
+        };
+        Self {
+            seq: change.seq,
+            entity,
+            id,
+            action: change.kind.as_str(),
+            at: change.at,
+            product,
+            sku,
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct CatalogChangesResponse {
+    /// Oldest first
+    pub changes: Vec<CatalogChangeResponse>,
+    /// Pass as `since` to get the changes after these; absent only while
+    /// the feed has never had a change
+    pub next_cursor: Option<String>,
+    /// More changes are waiting; ask again right away instead of polling
+    pub has_more: bool,
+}
+
+/// Follow a merchant's product and SKU changes from a cursor
+#[utoipa::path(
+    get,
+    path = "/api/products/changes",
+    params(ChangesQuery),
+    responses(
+        (status = 200, description = "Changes after the cursor, oldest first", body = CatalogChangesResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 422, description = "Invalid cursor or limit", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "products"
+)]
+pub async fn changes(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Query(query): Query<ChangesQuery>,
+) -> Result<Json<CatalogChangesResponse>, ApiError> {
+    validation::validate(&query)?;
+    let since = query.since.as_deref().map(str::parse::<Cursor>).transpose()?;
+
+    let (changes, next, more) = ChangeFeed::since(&state.db, admin.0.scoped_mid(query.mid), since.as_ref(), query.limit).await?;
+    Ok(Json(CatalogChangesResponse {
+        changes: changes.into_iter().map(CatalogChangeResponse::from).collect(),
+        next_cursor: next.map(|cursor| cursor.to_string()),
+        has_more: more,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use commercerack_product::changes::ChangeKind;
+
+    #[test]
+    fn test_deletion_has_no_snapshot() {
+        let change = CatalogChange {
+            seq: 12,
+            kind: ChangeKind::Deleted,
+            changed: Changed::Sku { id: 9, sku: None },
+            at: Timestamp::from_unix(100),
+        };
+        let json = serde_json::to_value(CatalogChangeResponse::from(change)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "seq": 12, "entity": "sku", "id": 9, "action": "deleted", "at": 100, "product": null, "sku": null
+            })
+        );
+    }
+}
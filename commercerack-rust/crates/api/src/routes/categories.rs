@@ -0,0 +1,449 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use commercerack_core::Timestamp;
+use commercerack_product::category::{CategoryError, CategoryNode, CategoryService};
+use ::entity::prelude::Category;
+use serde::{Deserialize, Serialize};
+use crate::auth::RequireMerchantAdmin;
+use crate::error::{ApiError, ErrorBody};
+use crate::routes::products::{with_media, ProductResponse};
+use crate::validation::{ValidatedJson, Validate, Validator};
+use crate::AppState;
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct CreateCategoryRequest {
+    pub mid: i32,
+    pub name: String,
+    /// Parent category; omit for a top-level category
+    pub parent_id: Option<i32>,
+    #[serde(default)]
+    pub position: i32,
+}
+
+impl Validate for CreateCategoryRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("name", &self.name, 80);
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct UpdateCategoryRequest {
+    pub name: String,
+    #[serde(default)]
+    pub position: i32,
+}
+
+impl Validate for UpdateCategoryRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("name", &self.name, 80);
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct MoveCategoryRequest {
+    /// New parent category; null moves the category to the top level
+    pub parent_id: Option<i32>,
+}
+
+impl Validate for MoveCategoryRequest {
+    fn validate(&self, _v: &mut Validator) {}
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct CategoryResponse {
+    pub id: i32,
+    pub mid: i32,
+    pub parent_id: Option<i32>,
+    pub name: String,
+    pub slug: String,
+    pub position: i32,
+    #[schema(value_type = i64)]
+    pub created_gmt: Timestamp,
+    #[schema(value_type = i64)]
+    pub modified_gmt: Timestamp,
+}
+
+impl From<Category> for CategoryResponse {
+    fn from(category: Category) -> Self {
+        Self {
+            id: category.id,
+            mid: category.mid,
+            parent_id: category.parent_id,
+            name: category.name,
+            slug: category.slug,
+            position: category.position,
+            created_gmt: category.created_gmt,
+            modified_gmt: category.modified_gmt,
+        }
+    }
+}
+
+/// A category with its subcategories nested beneath it
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct CategoryTreeResponse {
+    pub id: i32,
+    pub parent_id: Option<i32>,
+    pub name: String,
+    pub slug: String,
+    pub position: i32,
+    #[schema(no_recursion)]
+    pub children: Vec<CategoryTreeResponse>,
+}
+
+impl From<CategoryNode> for CategoryTreeResponse {
+    fn from(node: CategoryNode) -> Self {
+        Self {
+            id: node.category.id,
+            parent_id: node.category.parent_id,
+            name: node.category.name,
+            slug: node.category.slug,
+            position: node.category.position,
+            children: node.children.into_iter().map(|c| c.into()).collect(),
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct TreeQuery {
+    pub mid: i32,
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct ProductsQuery {
+    #[serde(default = "default_limit")]
+    pub limit: u64,
+    #[serde(default)]
+    pub offset: u64,
+}
+
+fn default_limit() -> u64 {
+    20
+}
+
+/// Create a category
+#[utoipa::path(
+    post,
+    path = "/api/categories",
+    request_body = CreateCategoryRequest,
+    responses(
+        (status = 201, description = "Category created", body = CategoryResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 422, description = "Missing name or unknown parent", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "categories"
+)]
+pub async fn create(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    ValidatedJson(req): ValidatedJson<CreateCategoryRequest>,
+) -> Result<(StatusCode, Json<CategoryResponse>), ApiError> {
+    CategoryService::create(&state.db, admin.0.scoped_mid(req.mid), &req.name, req.parent_id, req.position)
+        .await
+        .map(|category| (StatusCode::CREATED, Json(category.into())))
+        .map_err(ApiError::from)
+}
+
+/// A merchant's category tree
+#[utoipa::path(
+    get,
+    path = "/api/categories",
+    params(TreeQuery),
+    responses(
+        (status = 200, description = "Top-level categories with nested children", body = Vec<CategoryTreeResponse>),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "categories"
+)]
+pub async fn tree(
+    State(state): State<AppState>,
+    Query(query): Query<TreeQuery>,
+) -> Result<Json<Vec<CategoryTreeResponse>>, ApiError> {
+    CategoryService::tree(&state.db, query.mid)
+        .await
+        .map(|tree| Json(tree.into_iter().map(|node| node.into()).collect()))
+        .map_err(ApiError::from)
+}
+
+/// Get a category
+#[utoipa::path(
+    get,
+    path = "/api/categories/{mid}/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Category ID")
+    ),
+    responses(
+        (status = 200, description = "Category found", body = CategoryResponse),
+        (status = 404, description = "Category not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "categories"
+)]
+pub async fn get(
+    State(state): State<AppState>,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Json<CategoryResponse>, ApiError> {
+    CategoryService::find_by_id(&state.db, mid, id)
+        .await?
+        .map(|category| Json(category.into()))
+        .ok_or_else(|| CategoryError::NotFound.into())
+}
+
+/// Rename or reorder a category
+#[utoipa::path(
+    put,
+    path = "/api/categories/{mid}/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Category ID")
+    ),
+    request_body = UpdateCategoryRequest,
+    responses(
+        (status = 200, description = "Category updated", body = CategoryResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Category not found", body = ErrorBody),
+        (status = 422, description = "Missing name", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "categories"
+)]
+pub async fn update(
+    State(state): State<AppState>,
+    _admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+    ValidatedJson(req): ValidatedJson<UpdateCategoryRequest>,
+) -> Result<Json<CategoryResponse>, ApiError> {
+    let category = CategoryService::find_by_id(&state.db, mid, id)
+        .await?
+        .ok_or(CategoryError::NotFound)?;
+
+    CategoryService::update(&state.db, category, &req.name, req.position)
+        .await
+        .map(|category| Json(category.into()))
+        .map_err(ApiError::from)
+}
+
+/// Move a category and its subtree under a new parent
+#[utoipa::path(
+    post,
+    path = "/api/categories/{mid}/{id}/move",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Category ID")
+    ),
+    request_body = MoveCategoryRequest,
+    responses(
+        (status = 200, description = "Category moved", body = CategoryResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Category not found", body = ErrorBody),
+        (status = 409, description = "New parent is the category itself or one of its descendants", body = ErrorBody),
+        (status = 422, description = "Unknown parent", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "categories"
+)]
+pub async fn move_category(
+    State(state): State<AppState>,
+    _admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+    ValidatedJson(req): ValidatedJson<MoveCategoryRequest>,
+) -> Result<Json<CategoryResponse>, ApiError> {
+    CategoryService::move_to(&state.db, mid, id, req.parent_id)
+        .await
+        .map(|category| Json(category.into()))
+        .map_err(ApiError::from)
+}
+
+/// Delete a category; its subcategories move up to its parent
+#[utoipa::path(
+    delete,
+    path = "/api/categories/{mid}/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Category ID")
+    ),
+    responses(
+        (status = 204, description = "Category deleted"),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Category not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "categories"
+)]
+pub async fn delete(
+    State(state): State<AppState>,
+    _admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<StatusCode, ApiError> {
+    if CategoryService::delete(&state.db, mid, id).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(CategoryError::NotFound.into())
+    }
+}
+
+/// Products assigned to a category
+#[utoipa::path(
+    get,
+    path = "/api/categories/{mid}/{id}/products",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Category ID"),
+        ProductsQuery
+    ),
+    responses(
+        (status = 200, description = "Products in the category, by name", body = Vec<ProductResponse>),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "categories"
+)]
+pub async fn products(
+    State(state): State<AppState>,
+    Path((mid, id)): Path<(i32, i32)>,
+    Query(query): Query<ProductsQuery>,
+) -> Result<Json<Vec<ProductResponse>>, ApiError> {
+    let products = CategoryService::products(&state.db, mid, id, query.limit, query.offset).await?;
+    Ok(Json(with_media(&state.db, mid, products).await?))
+}
+
+/// Categories a product is assigned to
+#[utoipa::path(
+    get,
+    path = "/api/products/{mid}/{id}/categories",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Product ID")
+    ),
+    responses(
+        (status = 200, description = "Assigned categories", body = Vec<CategoryResponse>),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "categories"
+)]
+pub async fn product_categories(
+    State(state): State<AppState>,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Json<Vec<CategoryResponse>>, ApiError> {
+    CategoryService::for_product(&state.db, mid, id)
+        .await
+        .map(|categories| Json(categories.into_iter().map(|c| c.into()).collect()))
+        .map_err(ApiError::from)
+}
+
+/// Assign a product to a category
+#[utoipa::path(
+    put,
+    path = "/api/products/{mid}/{id}/categories/{category_id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Product ID"),
+        ("category_id" = i32, Path, description = "Category ID")
+    ),
+    responses(
+        (status = 204, description = "Product assigned"),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Product or category not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "categories"
+)]
+pub async fn assign_product(
+    State(state): State<AppState>,
+    _admin: RequireMerchantAdmin,
+    Path((mid, id, category_id)): Path<(i32, i32, i32)>,
+) -> Result<StatusCode, ApiError> {
+    CategoryService::assign_product(&state.db, mid, id, category_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Remove a product from a category
+#[utoipa::path(
+    delete,
+    path = "/api/products/{mid}/{id}/categories/{category_id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Product ID"),
+        ("category_id" = i32, Path, description = "Category ID")
+    ),
+    responses(
+        (status = 204, description = "Product removed from the category"),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Product is not in the category", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "categories"
+)]
+pub async fn unassign_product(
+    State(state): State<AppState>,
+    _admin: RequireMerchantAdmin,
+    Path((mid, id, category_id)): Path<(i32, i32, i32)>,
+) -> Result<StatusCode, ApiError> {
+    if CategoryService::unassign_product(&state.db, mid, id, category_id).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::not_found("Category assignment"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::{Claims, Role};
+    use sea_orm::{DatabaseBackend, MockDatabase};
+
+    fn category(id: i32, parent_id: Option<i32>, name: &str) -> Category {
+        Category {
+            id,
+            mid: 1,
+            parent_id,
+            name: name.to_string(),
+            slug: name.to_lowercase(),
+            position: 0,
+            created_gmt: Timestamp::EPOCH,
+            modified_gmt: Timestamp::EPOCH,
+        }
+    }
+
+    fn state(db: MockDatabase) -> AppState {
+        AppState::mock(db.into_connection())
+    }
+
+    #[tokio::test]
+    async fn test_tree_nests_children() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres).append_query_results([vec![
+            category(2, Some(1), "Sneakers"),
+            category(1, None, "Shoes"),
+        ]]);
+
+        let Json(tree) = tree(State(state(db)), Query(TreeQuery { mid: 1 })).await.unwrap();
+        assert_eq!(tree.len(), 1);
+        assert_eq!(tree[0].slug, "shoes");
+        assert_eq!(tree[0].children[0].name, "Sneakers");
+        assert!(tree[0].children[0].children.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_create_rejects_unknown_parent() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres).append_query_results([Vec::<Category>::new()]);
+        let admin = RequireMerchantAdmin::new(Claims::new(1, 1).with_role(Role::MerchantAdmin));
+        let req = CreateCategoryRequest {
+            mid: 1,
+            name: "Sneakers".to_string(),
+            parent_id: Some(42),
+            position: 0,
+        };
+
+        let result = create(State(state(db)), admin, ValidatedJson(req)).await;
+        assert_eq!(result.err().map(|e| e.status()), Some(StatusCode::UNPROCESSABLE_ENTITY));
+    }
+}
@@ -0,0 +1,426 @@
+//! Marketplace channels
+//!
+//! A merchant registers one channel per marketplace. Registered channels
+//! are synced on a schedule by the worker; `POST .../sync` queues one
+//! right away and answers 202. Orders a marketplace pushes are posted to
+//! `.../orders/ingest` as it sent them. `.../inventory/reconciliation`
+//! lists the SKUs whose stock on the marketplace differs from ours.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use commercerack_channels::inventory::{reconcile, ReconciliationReport, StockMismatch};
+use commercerack_channels::{connector, ingest_order, ChannelService, Marketplace, NewChannel};
+use commercerack_core::Timestamp;
+use commercerack_jobs::channels::SyncChannel;
+use commercerack_jobs::JobQueue;
+use ::entity::prelude::Channel;
+use serde::{Deserialize, Serialize};
+use crate::auth::RequireMerchantAdmin;
+use crate::error::{ApiError, ErrorBody};
+use crate::routes::orders::OrderResponse;
+use crate::validation::{ValidatedJson, Validate, Validator};
+use crate::AppState;
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct CreateChannelRequest {
+    pub mid: i32,
+    /// `amazon` or `ebay`
+    pub marketplace: String,
+    pub name: String,
+    /// Seller account settings; `seller_id` and `marketplace_id` for
+    /// `amazon`, `seller_username` and `marketplace_id` for `ebay`
+    #[schema(value_type = Object)]
+    pub settings: serde_json::Value,
+}
+
+impl Validate for CreateChannelRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("name", &self.name, 60);
+        if self.marketplace.parse::<Marketplace>().is_err() {
+            v.error("marketplace", format!("unknown marketplace {}", self.marketplace));
+        }
+        v.check(self.settings.is_object(), "settings", "must be an object");
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct UpdateChannelRequest {
+    pub name: String,
+    #[schema(value_type = Object)]
+    pub settings: serde_json::Value,
+    /// Whether scheduled syncs run
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+impl Validate for UpdateChannelRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("name", &self.name, 60);
+        v.check(self.settings.is_object(), "settings", "must be an object");
+    }
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ChannelResponse {
+    pub id: i32,
+    pub mid: i32,
+    pub marketplace: String,
+    pub name: String,
+    #[schema(value_type = Object)]
+    pub settings: serde_json::Value,
+    pub enabled: bool,
+    /// Orders placed up to here have been pulled
+    #[schema(value_type = Option<i64>)]
+    pub orders_synced_gmt: Option<Timestamp>,
+    #[schema(value_type = Option<i64>)]
+    pub tracking_synced_gmt: Option<Timestamp>,
+    /// Stock moved up to here has been pushed
+    #[schema(value_type = Option<i64>)]
+    pub inventory_synced_gmt: Option<Timestamp>,
+    /// The marketplace rate limited us; syncs resume after this
+    #[schema(value_type = Option<i64>)]
+    pub throttled_until_gmt: Option<Timestamp>,
+    /// Why the last sync failed, until one succeeds
+    pub last_error: Option<String>,
+    #[schema(value_type = i64)]
+    pub created_gmt: Timestamp,
+    #[schema(value_type = i64)]
+    pub modified_gmt: Timestamp,
+}
+
+impl From<Channel> for ChannelResponse {
+    fn from(channel: Channel) -> Self {
+        Self {
+            settings: serde_json::from_str(&channel.settings).unwrap_or_default(),
+            id: channel.id,
+            mid: channel.mid,
+            marketplace: channel.marketplace,
+            name: channel.name,
+            enabled: channel.enabled,
+            orders_synced_gmt: channel.orders_synced_gmt,
+            tracking_synced_gmt: channel.tracking_synced_gmt,
+            inventory_synced_gmt: channel.inventory_synced_gmt,
+            throttled_until_gmt: channel.throttled_until_gmt,
+            last_error: channel.last_error,
+            created_gmt: channel.created_gmt,
+            modified_gmt: channel.modified_gmt,
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct StockMismatchResponse {
+    pub sku: String,
+    /// Our sellable stock, as pushed; never below 0
+    pub internal: i32,
+    /// What the marketplace lists; null if it does not list the SKU
+    pub channel: Option<i32>,
+}
+
+impl From<StockMismatch> for StockMismatchResponse {
+    fn from(mismatch: StockMismatch) -> Self {
+        Self {
+            sku: mismatch.sku,
+            internal: mismatch.internal,
+            channel: mismatch.channel,
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ReconciliationResponse {
+    /// SKUs compared
+    pub checked: usize,
+    pub mismatches: Vec<StockMismatchResponse>,
+}
+
+impl From<ReconciliationReport> for ReconciliationResponse {
+    fn from(report: ReconciliationReport) -> Self {
+        Self {
+            checked: report.checked,
+            mismatches: report.mismatches.into_iter().map(|m| m.into()).collect(),
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct ListQuery {
+    pub mid: i32,
+}
+
+/// Register a marketplace channel
+#[utoipa::path(
+    post,
+    path = "/api/channels",
+    request_body = CreateChannelRequest,
+    responses(
+        (status = 201, description = "Channel registered", body = ChannelResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 409, description = "A channel for the marketplace is already registered", body = ErrorBody),
+        (status = 422, description = "Unknown marketplace or invalid settings", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "channels"
+)]
+pub async fn create(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    ValidatedJson(req): ValidatedJson<CreateChannelRequest>,
+) -> Result<(StatusCode, Json<ChannelResponse>), ApiError> {
+    let channel = NewChannel {
+        marketplace: req.marketplace.parse()?,
+        name: req.name,
+        settings: req.settings,
+    };
+    let channel = ChannelService::register(&*state.db, admin.0.scoped_mid(req.mid), channel).await?;
+    Ok((StatusCode::CREATED, Json(channel.into())))
+}
+
+/// List a merchant's channels
+#[utoipa::path(
+    get,
+    path = "/api/channels",
+    params(ListQuery),
+    responses(
+        (status = 200, description = "Registered channels", body = Vec<ChannelResponse>),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "channels"
+)]
+pub async fn list(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Query(query): Query<ListQuery>,
+) -> Result<Json<Vec<ChannelResponse>>, ApiError> {
+    let channels = ChannelService::list(&*state.db, admin.0.scoped_mid(query.mid)).await?;
+    Ok(Json(channels.into_iter().map(|c| c.into()).collect()))
+}
+
+/// Get a channel and how its syncs are going
+#[utoipa::path(
+    get,
+    path = "/api/channels/{mid}/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Channel ID")
+    ),
+    responses(
+        (status = 200, description = "Channel found", body = ChannelResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Channel not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "channels"
+)]
+pub async fn get(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Json<ChannelResponse>, ApiError> {
+    ChannelService::find(&*state.db, admin.0.scoped_mid(mid), id)
+        .await?
+        .map(|channel| Json(channel.into()))
+        .ok_or_else(|| ApiError::not_found("Channel"))
+}
+
+/// Rename, reconfigure, pause or resume a channel
+#[utoipa::path(
+    put,
+    path = "/api/channels/{mid}/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Channel ID")
+    ),
+    request_body = UpdateChannelRequest,
+    responses(
+        (status = 200, description = "Channel updated", body = ChannelResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Channel not found", body = ErrorBody),
+        (status = 422, description = "Invalid settings", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "channels"
+)]
+pub async fn update(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+    ValidatedJson(req): ValidatedJson<UpdateChannelRequest>,
+) -> Result<Json<ChannelResponse>, ApiError> {
+    let channel = ChannelService::find(&*state.db, admin.0.scoped_mid(mid), id)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Channel"))?;
+
+    let channel = ChannelService::update(&*state.db, channel, req.name, req.settings, req.enabled).await?;
+    Ok(Json(channel.into()))
+}
+
+/// Delete a channel; orders pulled from it stay
+#[utoipa::path(
+    delete,
+    path = "/api/channels/{mid}/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Channel ID")
+    ),
+    responses(
+        (status = 204, description = "Channel deleted"),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Channel not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "channels"
+)]
+pub async fn delete(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<StatusCode, ApiError> {
+    if ChannelService::delete(&*state.db, admin.0.scoped_mid(mid), id).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::not_found("Channel"))
+    }
+}
+
+/// Sync a channel now instead of waiting for the schedule
+#[utoipa::path(
+    post,
+    path = "/api/channels/{mid}/{id}/sync",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Channel ID")
+    ),
+    responses(
+        (status = 202, description = "Sync queued; poll the channel for its outcome", body = ChannelResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Channel not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "channels"
+)]
+pub async fn sync(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<(StatusCode, Json<ChannelResponse>), ApiError> {
+    let channel = ChannelService::find(&*state.db, admin.0.scoped_mid(mid), id)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Channel"))?;
+
+    JobQueue::enqueue(&*state.db, &SyncChannel { mid: channel.mid, channel_id: channel.id }).await?;
+    Ok((StatusCode::ACCEPTED, Json(channel.into())))
+}
+
+/// Record an order the channel's marketplace pushed
+///
+/// The body is the order as the marketplace sent it: an SP-API order with
+/// its `OrderItems` for Amazon, a Fulfillment API order for eBay. Posting
+/// the same marketplace order again returns the order recorded the first
+/// time.
+#[utoipa::path(
+    post,
+    path = "/api/channels/{mid}/{id}/orders/ingest",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Channel ID")
+    ),
+    request_body(content = Object, description = "Marketplace order payload"),
+    responses(
+        (status = 201, description = "Order recorded", body = OrderResponse),
+        (status = 200, description = "Order was already recorded", body = OrderResponse),
+        (status = 400, description = "Payload is not an order of the channel's marketplace", body = ErrorBody),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Channel not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "channels"
+)]
+pub async fn ingest(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+    Json(payload): Json<serde_json::Value>,
+) -> Result<(StatusCode, Json<OrderResponse>), ApiError> {
+    let channel = ChannelService::find(&*state.db, admin.0.scoped_mid(mid), id)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Channel"))?;
+
+    let ingested = ingest_order(&*state.db, &channel, &payload).await?;
+    let status = if ingested.created { StatusCode::CREATED } else { StatusCode::OK };
+    Ok((status, Json(ingested.order.into())))
+}
+
+/// Compare our stock with what the channel's marketplace lists
+#[utoipa::path(
+    get,
+    path = "/api/channels/{mid}/{id}/inventory/reconciliation",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Channel ID")
+    ),
+    responses(
+        (status = 200, description = "SKUs whose stock differs", body = ReconciliationResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Channel not found", body = ErrorBody),
+        (status = 429, description = "The marketplace rate limited us; see Retry-After", body = ErrorBody),
+        (status = 502, description = "The marketplace failed", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "channels"
+)]
+pub async fn reconciliation(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Json<ReconciliationResponse>, ApiError> {
+    let channel = ChannelService::find(&*state.db, admin.0.scoped_mid(mid), id)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Channel"))?;
+
+    let connector = connector(&channel)?;
+    let report = reconcile(&*state.db, &channel, connector.as_ref()).await?;
+    Ok(Json(report.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_validate_channel() {
+        let request = |marketplace: &str, settings: serde_json::Value| CreateChannelRequest {
+            mid: 1,
+            marketplace: marketplace.to_string(),
+            name: "Amazon US".to_string(),
+            settings,
+        };
+        let invalid_fields = |req: &CreateChannelRequest| match crate::validation::validate(req) {
+            Ok(()) => Vec::new(),
+            Err(ApiError::Validation(errors)) => errors.into_iter().map(|e| e.field).collect(),
+            Err(e) => panic!("unexpected error {e:?}"),
+        };
+
+        let settings = json!({ "seller_id": "A1B2C3", "marketplace_id": "ATVPDKIKX0DER" });
+        assert!(invalid_fields(&request("amazon", settings.clone())).is_empty());
+        assert_eq!(invalid_fields(&request("walmart", settings)), vec!["marketplace"]);
+        assert_eq!(invalid_fields(&request("amazon", json!("A1B2C3"))), vec!["settings"]);
+    }
+}
@@ -0,0 +1,582 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use commercerack_core::Timestamp;
+use commercerack_customer::companies::{is_approver, BuyerRole, CompanyDetails, CompanyService};
+use commercerack_order::approvals::{ApprovalService, Decider};
+use commercerack_order::OrderService;
+use ::entity::prelude::{Company, CompanyBuyer};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use crate::auth::{Claims, RequireMerchantAdmin, Role};
+use crate::error::{ApiError, ErrorBody};
+use crate::routes::orders::OrderResponse;
+use crate::routes::parse_decimal;
+use crate::validation::{self, ValidatedJson, Validate, Validator};
+use crate::AppState;
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct CreateCompanyRequest {
+    pub mid: i32,
+    pub name: String,
+    /// Price group of the company's buyers; empty leaves each buyer's own
+    #[serde(default)]
+    pub price_group: String,
+    /// Orders with a larger total wait for an approver; omit to approve all
+    pub approval_threshold: Option<String>,
+}
+
+impl Validate for CreateCompanyRequest {
+    fn validate(&self, v: &mut Validator) {
+        validate_company(v, &self.name, &self.price_group, self.approval_threshold.as_deref());
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct UpdateCompanyRequest {
+    pub name: String,
+    #[serde(default)]
+    pub price_group: String,
+    pub approval_threshold: Option<String>,
+}
+
+impl Validate for UpdateCompanyRequest {
+    fn validate(&self, v: &mut Validator) {
+        validate_company(v, &self.name, &self.price_group, self.approval_threshold.as_deref());
+    }
+}
+
+fn validate_company(v: &mut Validator, name: &str, price_group: &str, approval_threshold: Option<&str>) {
+    v.required("name", name, 120).max_len("price_group", price_group, 20);
+    if let Some(threshold) = approval_threshold {
+        v.amount("approval_threshold", threshold);
+    }
+}
+
+/// Settings of a validated request
+fn details(name: &str, price_group: &str, approval_threshold: Option<&str>) -> Result<CompanyDetails, ApiError> {
+    Ok(CompanyDetails {
+        name: name.trim().to_string(),
+        price_group: price_group.trim().to_string(),
+        approval_threshold: approval_threshold
+            .map(|threshold| parse_decimal("approval_threshold", threshold.trim()))
+            .transpose()?,
+    })
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct CompanyResponse {
+    pub id: i32,
+    pub mid: i32,
+    pub name: String,
+    pub price_group: String,
+    pub approval_threshold: Option<String>,
+    #[schema(value_type = i64)]
+    pub created_gmt: Timestamp,
+    #[schema(value_type = i64)]
+    pub modified_gmt: Timestamp,
+}
+
+impl From<Company> for CompanyResponse {
+    fn from(company: Company) -> Self {
+        Self {
+            id: company.id,
+            mid: company.mid,
+            name: company.name,
+            price_group: company.price_group,
+            approval_threshold: company.approval_threshold.map(|threshold| threshold.to_string()),
+            created_gmt: company.created_gmt,
+            modified_gmt: company.modified_gmt,
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct BuyerRequest {
+    /// `buyer` (default) or `approver`
+    pub role: Option<String>,
+    /// Largest order total the buyer may place; omit for no limit
+    pub spending_limit: Option<String>,
+}
+
+impl Validate for BuyerRequest {
+    fn validate(&self, v: &mut Validator) {
+        if let Some(role) = &self.role {
+            v.check(role.parse::<BuyerRole>().is_ok(), "role", "must be buyer or approver");
+        }
+        if let Some(limit) = &self.spending_limit {
+            v.amount("spending_limit", limit);
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct BuyerResponse {
+    pub company_id: i32,
+    pub cid: i32,
+    pub role: String,
+    pub spending_limit: Option<String>,
+    #[schema(value_type = i64)]
+    pub created_gmt: Timestamp,
+}
+
+impl From<CompanyBuyer> for BuyerResponse {
+    fn from(buyer: CompanyBuyer) -> Self {
+        Self {
+            company_id: buyer.company_id,
+            cid: buyer.cid,
+            role: buyer.role,
+            spending_limit: buyer.spending_limit.map(|limit| limit.to_string()),
+            created_gmt: buyer.created_gmt,
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct ListQuery {
+    pub mid: i32,
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct OrdersQuery {
+    #[serde(default = "default_limit")]
+    pub limit: u64,
+    #[serde(default)]
+    pub offset: u64,
+}
+
+impl Validate for OrdersQuery {
+    fn validate(&self, v: &mut Validator) {
+        v.check((1..=100).contains(&self.limit), "limit", "must be between 1 and 100");
+    }
+}
+
+fn default_limit() -> u64 {
+    20
+}
+
+/// Customer ID of a signed-in shopper; `None` for merchant staff
+fn shopper_cid(claims: &Claims) -> Result<Option<i32>, ApiError> {
+    if claims.role != Role::Customer {
+        return Ok(None);
+    }
+    claims
+        .sub
+        .parse()
+        .map(Some)
+        .map_err(|_| ApiError::Unauthorized("Token subject is not a customer ID".to_string()))
+}
+
+/// Let merchant staff through, and customers who buy for the company;
+/// `approver` also requires them to approve its orders
+async fn ensure_buyer(state: &AppState, claims: &Claims, mid: i32, company_id: i32, approver: bool) -> Result<(), ApiError> {
+    let Some(cid) = shopper_cid(claims)? else {
+        return Ok(());
+    };
+    let buyer = CompanyService::membership(&*state.db, mid, cid)
+        .await?
+        .filter(|(company, _)| company.id == company_id)
+        .map(|(_, buyer)| buyer)
+        .ok_or_else(|| ApiError::not_found("Company"))?;
+    if approver && !is_approver(&buyer) {
+        return Err(ApiError::Forbidden("Only the company's approvers can do this".to_string()));
+    }
+    Ok(())
+}
+
+/// Create a company account
+#[utoipa::path(
+    post,
+    path = "/api/companies",
+    request_body = CreateCompanyRequest,
+    responses(
+        (status = 201, description = "Company created", body = CompanyResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 422, description = "Missing name or invalid threshold", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "companies"
+)]
+pub async fn create(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    ValidatedJson(req): ValidatedJson<CreateCompanyRequest>,
+) -> Result<(StatusCode, Json<CompanyResponse>), ApiError> {
+    let details = details(&req.name, &req.price_group, req.approval_threshold.as_deref())?;
+    let company = CompanyService::create(&*state.db, admin.0.scoped_mid(req.mid), details).await?;
+    Ok((StatusCode::CREATED, Json(company.into())))
+}
+
+/// List a merchant's companies
+#[utoipa::path(
+    get,
+    path = "/api/companies",
+    params(ListQuery),
+    responses(
+        (status = 200, description = "Companies by name", body = Vec<CompanyResponse>),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "companies"
+)]
+pub async fn list(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Query(query): Query<ListQuery>,
+) -> Result<Json<Vec<CompanyResponse>>, ApiError> {
+    let companies = CompanyService::list(&*state.db, admin.0.scoped_mid(query.mid)).await?;
+    Ok(Json(companies.into_iter().map(|c| c.into()).collect()))
+}
+
+/// Get a company
+#[utoipa::path(
+    get,
+    path = "/api/companies/{mid}/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Company ID")
+    ),
+    responses(
+        (status = 200, description = "Company found", body = CompanyResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 404, description = "Company not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "companies"
+)]
+pub async fn get(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Json<CompanyResponse>, ApiError> {
+    let mid = claims.scoped_mid(mid);
+    ensure_buyer(&state, &claims, mid, id, false).await?;
+
+    CompanyService::find(&*state.db, mid, id)
+        .await?
+        .map(|company| Json(company.into()))
+        .ok_or_else(|| ApiError::not_found("Company"))
+}
+
+/// Update a company
+#[utoipa::path(
+    put,
+    path = "/api/companies/{mid}/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Company ID")
+    ),
+    request_body = UpdateCompanyRequest,
+    responses(
+        (status = 200, description = "Company updated", body = CompanyResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Company not found", body = ErrorBody),
+        (status = 422, description = "Missing name or invalid threshold", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "companies"
+)]
+pub async fn update(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+    ValidatedJson(req): ValidatedJson<UpdateCompanyRequest>,
+) -> Result<Json<CompanyResponse>, ApiError> {
+    let details = details(&req.name, &req.price_group, req.approval_threshold.as_deref())?;
+    CompanyService::update(&*state.db, admin.0.scoped_mid(mid), id, details)
+        .await
+        .map(|company| Json(company.into()))
+        .map_err(ApiError::from)
+}
+
+/// Delete a company; its orders keep their company ID
+#[utoipa::path(
+    delete,
+    path = "/api/companies/{mid}/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Company ID")
+    ),
+    responses(
+        (status = 204, description = "Company and its buyer list deleted"),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Company not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "companies"
+)]
+pub async fn delete(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<StatusCode, ApiError> {
+    if CompanyService::delete(&*state.db, admin.0.scoped_mid(mid), id).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::not_found("Company"))
+    }
+}
+
+/// A company's buyers
+#[utoipa::path(
+    get,
+    path = "/api/companies/{mid}/{id}/buyers",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Company ID")
+    ),
+    responses(
+        (status = 200, description = "Buyers in the order they were added", body = Vec<BuyerResponse>),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Approver of the company required", body = ErrorBody),
+        (status = 404, description = "Company not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "companies"
+)]
+pub async fn buyers(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Json<Vec<BuyerResponse>>, ApiError> {
+    let mid = claims.scoped_mid(mid);
+    ensure_buyer(&state, &claims, mid, id, true).await?;
+
+    let buyers = CompanyService::buyers(&*state.db, mid, id).await?;
+    Ok(Json(buyers.into_iter().map(|b| b.into()).collect()))
+}
+
+/// Add a customer to a company's buyers, or change their role and limit
+#[utoipa::path(
+    put,
+    path = "/api/companies/{mid}/{id}/buyers/{cid}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Company ID"),
+        ("cid" = i32, Path, description = "Customer ID")
+    ),
+    request_body = BuyerRequest,
+    responses(
+        (status = 200, description = "Buyer saved", body = BuyerResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Company or customer not found", body = ErrorBody),
+        (status = 409, description = "Customer already buys for another company", body = ErrorBody),
+        (status = 422, description = "Unknown role or invalid limit", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "companies"
+)]
+pub async fn set_buyer(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id, cid)): Path<(i32, i32, i32)>,
+    ValidatedJson(req): ValidatedJson<BuyerRequest>,
+) -> Result<Json<BuyerResponse>, ApiError> {
+    let role = req.role.as_deref().map(str::parse).transpose().map_err(ApiError::BadRequest)?.unwrap_or_default();
+    let limit: Option<Decimal> = req
+        .spending_limit
+        .as_deref()
+        .map(|limit| parse_decimal("spending_limit", limit.trim()))
+        .transpose()?;
+
+    CompanyService::set_buyer(&*state.db, admin.0.scoped_mid(mid), id, cid, role, limit)
+        .await
+        .map(|buyer| Json(buyer.into()))
+        .map_err(ApiError::from)
+}
+
+/// Take a customer off a company's buyers
+#[utoipa::path(
+    delete,
+    path = "/api/companies/{mid}/{id}/buyers/{cid}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Company ID"),
+        ("cid" = i32, Path, description = "Customer ID")
+    ),
+    responses(
+        (status = 204, description = "Buyer removed"),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Customer is not a buyer for the company", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "companies"
+)]
+pub async fn remove_buyer(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id, cid)): Path<(i32, i32, i32)>,
+) -> Result<StatusCode, ApiError> {
+    CompanyService::remove_buyer(&*state.db, admin.0.scoped_mid(mid), id, cid).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Orders the company's buyers placed for it
+#[utoipa::path(
+    get,
+    path = "/api/companies/{mid}/{id}/orders",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Company ID"),
+        OrdersQuery
+    ),
+    responses(
+        (status = 200, description = "Orders, newest first", body = Vec<OrderResponse>),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 404, description = "Company not found", body = ErrorBody),
+        (status = 422, description = "Invalid limit", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "companies"
+)]
+pub async fn orders(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((mid, id)): Path<(i32, i32)>,
+    Query(query): Query<OrdersQuery>,
+) -> Result<Json<Vec<OrderResponse>>, ApiError> {
+    validation::validate(&query)?;
+    let mid = claims.scoped_mid(mid);
+    ensure_buyer(&state, &claims, mid, id, false).await?;
+
+    let orders = OrderService::list_by_company(&*state.db, mid, id, query.limit, query.offset).await?;
+    Ok(Json(orders.into_iter().map(|o| o.into()).collect()))
+}
+
+/// A company's orders waiting for approval
+#[utoipa::path(
+    get,
+    path = "/api/companies/{mid}/{id}/approvals",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Company ID")
+    ),
+    responses(
+        (status = 200, description = "Orders waiting for approval, oldest first", body = Vec<OrderResponse>),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Approver of the company required", body = ErrorBody),
+        (status = 404, description = "Company not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "companies"
+)]
+pub async fn approvals(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Json<Vec<OrderResponse>>, ApiError> {
+    let mid = claims.scoped_mid(mid);
+    ensure_buyer(&state, &claims, mid, id, true).await?;
+
+    let orders = ApprovalService::pending(&*state.db, mid, id).await?;
+    Ok(Json(orders.into_iter().map(|o| o.into()).collect()))
+}
+
+/// Approve a company order, releasing it for payment
+#[utoipa::path(
+    post,
+    path = "/api/orders/{mid}/{id}/approve",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Order ID")
+    ),
+    responses(
+        (status = 200, description = "Order approved", body = OrderResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Approver of the order's company required", body = ErrorBody),
+        (status = 404, description = "Order not found", body = ErrorBody),
+        (status = 409, description = "Order is not waiting for approval", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "companies"
+)]
+pub async fn approve(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Json<OrderResponse>, ApiError> {
+    let decider = shopper_cid(&claims)?.map_or(Decider::Merchant, Decider::Customer);
+    ApprovalService::approve(&*state.db, claims.scoped_mid(mid), id, decider)
+        .await
+        .map(|order| Json(order.into()))
+        .map_err(ApiError::from)
+}
+
+/// Reject a company order; it can't be paid
+#[utoipa::path(
+    post,
+    path = "/api/orders/{mid}/{id}/reject",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Order ID")
+    ),
+    responses(
+        (status = 200, description = "Order rejected", body = OrderResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Approver of the order's company required", body = ErrorBody),
+        (status = 404, description = "Order not found", body = ErrorBody),
+        (status = 409, description = "Order is not waiting for approval", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "companies"
+)]
+pub async fn reject(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Json<OrderResponse>, ApiError> {
+    let decider = shopper_cid(&claims)?.map_or(Decider::Merchant, Decider::Customer);
+    ApprovalService::reject(&*state.db, claims.scoped_mid(mid), id, decider)
+        .await
+        .map(|order| Json(order.into()))
+        .map_err(ApiError::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_company_and_buyer() {
+        fn invalid_fields(req: &impl Validate) -> Vec<String> {
+            match crate::validation::validate(req) {
+                Ok(()) => Vec::new(),
+                Err(ApiError::Validation(errors)) => errors.into_iter().map(|e| e.field).collect(),
+                Err(e) => panic!("unexpected error {e:?}"),
+            }
+        }
+
+        let company = CreateCompanyRequest {
+            mid: 1,
+            name: "Acme Corp".to_string(),
+            price_group: "wholesale".to_string(),
+            approval_threshold: Some("1000.00".to_string()),
+        };
+        assert!(invalid_fields(&company).is_empty());
+        let details = details(&company.name, &company.price_group, company.approval_threshold.as_deref()).unwrap();
+        assert_eq!(details.approval_threshold, Some(Decimal::new(100000, 2)));
+
+        let unnamed = CreateCompanyRequest { name: " ".to_string(), approval_threshold: Some("-5".to_string()), ..company };
+        assert_eq!(invalid_fields(&unnamed), vec!["name", "approval_threshold"]);
+
+        let buyer = BuyerRequest { role: Some("approver".to_string()), spending_limit: Some("250".to_string()) };
+        assert!(invalid_fields(&buyer).is_empty());
+        let owner = BuyerRequest { role: Some("owner".to_string()), spending_limit: Some("lots".to_string()) };
+        assert_eq!(invalid_fields(&owner), vec!["role", "spending_limit"]);
+    }
+
+    #[test]
+    fn test_staff_decide_as_the_merchant() {
+        let admin = Claims::new(1, 1).with_role(Role::MerchantAdmin);
+        assert_eq!(shopper_cid(&admin).unwrap(), None);
+        assert_eq!(shopper_cid(&Claims::new(7, 1)).unwrap(), Some(7));
+    }
+}
@@ -0,0 +1,376 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use commercerack_core::Timestamp;
+use commercerack_customer::tags::normalize_tag;
+use commercerack_promotion::{
+    coupon_category_ids, coupon_customer_tags, coupon_skus, CouponError, CouponInput, CouponKind, CouponService,
+};
+use ::entity::prelude::Coupon;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use crate::auth::RequireMerchantAdmin;
+use crate::error::{ApiError, ErrorBody};
+use crate::routes::parse_decimal;
+use crate::validation::{ValidatedJson, Validate, Validator};
+use crate::AppState;
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct CouponRequest {
+    /// Matched case-insensitively; stored uppercase
+    pub code: String,
+    #[serde(default)]
+    pub description: String,
+    /// `percent`, `fixed` or `free_shipping`
+    pub kind: String,
+    /// Percent off, or amount off as a decimal string; ignored for free shipping
+    #[serde(default = "default_value")]
+    pub value: String,
+    /// Smallest cart subtotal the coupon applies to
+    pub min_subtotal: Option<String>,
+    #[schema(value_type = Option<i64>)]
+    pub starts_gmt: Option<Timestamp>,
+    #[schema(value_type = Option<i64>)]
+    pub ends_gmt: Option<Timestamp>,
+    pub max_uses: Option<i32>,
+    pub max_uses_per_customer: Option<i32>,
+    /// Only discount these SKUs
+    #[serde(default)]
+    pub skus: Vec<String>,
+    /// Only discount products in these categories or their subcategories
+    #[serde(default)]
+    pub category_ids: Vec<i32>,
+    /// Only customers with one of these tags may use the coupon
+    #[serde(default)]
+    pub customer_tags: Vec<String>,
+    #[serde(default = "default_active")]
+    pub active: bool,
+}
+
+fn default_value() -> String {
+    "0".to_string()
+}
+
+fn default_active() -> bool {
+    true
+}
+
+impl Validate for CouponRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("code", &self.code, 30)
+            .check(
+                self.code.trim().chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'),
+                "code",
+                "may only contain letters, digits, - and _",
+            )
+            .max_len("description", &self.description, 255);
+
+        match self.kind.parse::<CouponKind>() {
+            Ok(CouponKind::FreeShipping) => {
+                v.amount("value", &self.value);
+            }
+            Ok(kind) => {
+                v.positive_amount("value", &self.value);
+                if kind == CouponKind::Percent {
+                    let percent = self.value.trim().parse::<Decimal>().unwrap_or_default();
+                    v.check(percent <= Decimal::ONE_HUNDRED, "value", "must be at most 100 percent");
+                }
+            }
+            Err(_) => v.error("kind", "must be percent, fixed or free_shipping"),
+        }
+
+        if let Some(min_subtotal) = &self.min_subtotal {
+            v.amount("min_subtotal", min_subtotal);
+        }
+        if let (Some(starts), Some(ends)) = (self.starts_gmt, self.ends_gmt) {
+            v.check(ends > starts, "ends_gmt", "must be after starts_gmt");
+        }
+        if let Some(max_uses) = self.max_uses {
+            v.positive("max_uses", max_uses);
+        }
+        if let Some(max_uses) = self.max_uses_per_customer {
+            v.positive("max_uses_per_customer", max_uses);
+        }
+        for sku in &self.skus {
+            v.required("skus", sku, 45);
+        }
+        for tag in &self.customer_tags {
+            v.check(normalize_tag(tag).is_ok(), "customer_tags", "may only contain letters, digits, - and _, up to 32 each");
+        }
+    }
+}
+
+impl CouponRequest {
+    fn into_input(self) -> Result<CouponInput, ApiError> {
+        Ok(CouponInput {
+            kind: self.kind.parse().map_err(ApiError::BadRequest)?,
+            value: parse_decimal("value", &self.value)?,
+            min_subtotal: self.min_subtotal.map(|min| parse_decimal("min_subtotal", &min)).transpose()?,
+            code: self.code,
+            description: self.description,
+            starts_gmt: self.starts_gmt,
+            ends_gmt: self.ends_gmt,
+            max_uses: self.max_uses,
+            max_uses_per_customer: self.max_uses_per_customer,
+            skus: self.skus,
+            category_ids: self.category_ids,
+            customer_tags: self.customer_tags,
+            active: self.active,
+        })
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct CreateCouponRequest {
+    pub mid: i32,
+    #[serde(flatten)]
+    pub coupon: CouponRequest,
+}
+
+impl Validate for CreateCouponRequest {
+    fn validate(&self, v: &mut Validator) {
+        self.coupon.validate(v);
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct CouponResponse {
+    pub id: i32,
+    pub mid: i32,
+    pub code: String,
+    pub description: String,
+    pub kind: String,
+    pub value: String,
+    pub min_subtotal: Option<String>,
+    #[schema(value_type = Option<i64>)]
+    pub starts_gmt: Option<Timestamp>,
+    #[schema(value_type = Option<i64>)]
+    pub ends_gmt: Option<Timestamp>,
+    pub max_uses: Option<i32>,
+    pub max_uses_per_customer: Option<i32>,
+    pub times_used: i32,
+    pub skus: Vec<String>,
+    pub category_ids: Vec<i32>,
+    pub customer_tags: Vec<String>,
+    pub active: bool,
+    #[schema(value_type = i64)]
+    pub created_gmt: Timestamp,
+    #[schema(value_type = i64)]
+    pub modified_gmt: Timestamp,
+}
+
+impl From<Coupon> for CouponResponse {
+    fn from(coupon: Coupon) -> Self {
+        Self {
+            skus: coupon_skus(&coupon),
+            category_ids: coupon_category_ids(&coupon),
+            customer_tags: coupon_customer_tags(&coupon),
+            id: coupon.id,
+            mid: coupon.mid,
+            code: coupon.code,
+            description: coupon.description,
+            kind: coupon.kind,
+            value: coupon.value.to_string(),
+            min_subtotal: coupon.min_subtotal.map(|min| min.to_string()),
+            starts_gmt: coupon.starts_gmt,
+            ends_gmt: coupon.ends_gmt,
+            max_uses: coupon.max_uses,
+            max_uses_per_customer: coupon.max_uses_per_customer,
+            times_used: coupon.times_used,
+            active: coupon.active,
+            created_gmt: coupon.created_gmt,
+            modified_gmt: coupon.modified_gmt,
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct ListQuery {
+    pub mid: i32,
+}
+
+/// Create a coupon
+#[utoipa::path(
+    post,
+    path = "/api/coupons",
+    request_body = CreateCouponRequest,
+    responses(
+        (status = 201, description = "Coupon created", body = CouponResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 409, description = "Code already in use", body = ErrorBody),
+        (status = 422, description = "Invalid coupon settings", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "coupons"
+)]
+pub async fn create(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    ValidatedJson(req): ValidatedJson<CreateCouponRequest>,
+) -> Result<(StatusCode, Json<CouponResponse>), ApiError> {
+    let mid = admin.0.scoped_mid(req.mid);
+    CouponService::create(&state.db, mid, req.coupon.into_input()?)
+        .await
+        .map(|coupon| (StatusCode::CREATED, Json(coupon.into())))
+        .map_err(ApiError::from)
+}
+
+/// A merchant's coupons
+#[utoipa::path(
+    get,
+    path = "/api/coupons",
+    params(ListQuery),
+    responses(
+        (status = 200, description = "Coupons by code", body = Vec<CouponResponse>),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "coupons"
+)]
+pub async fn list(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Query(query): Query<ListQuery>,
+) -> Result<Json<Vec<CouponResponse>>, ApiError> {
+    CouponService::list(&state.db, admin.0.scoped_mid(query.mid))
+        .await
+        .map(|coupons| Json(coupons.into_iter().map(|c| c.into()).collect()))
+        .map_err(ApiError::from)
+}
+
+/// Get a coupon
+#[utoipa::path(
+    get,
+    path = "/api/coupons/{mid}/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Coupon ID")
+    ),
+    responses(
+        (status = 200, description = "Coupon found", body = CouponResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Coupon not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "coupons"
+)]
+pub async fn get(
+    State(state): State<AppState>,
+    _admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Json<CouponResponse>, ApiError> {
+    CouponService::find_by_id(&state.db, mid, id)
+        .await?
+        .map(|coupon| Json(coupon.into()))
+        .ok_or_else(|| CouponError::NotFound.into())
+}
+
+/// Replace a coupon's settings
+#[utoipa::path(
+    put,
+    path = "/api/coupons/{mid}/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Coupon ID")
+    ),
+    request_body = CouponRequest,
+    responses(
+        (status = 200, description = "Coupon updated", body = CouponResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Coupon not found", body = ErrorBody),
+        (status = 409, description = "Code already in use", body = ErrorBody),
+        (status = 422, description = "Invalid coupon settings", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "coupons"
+)]
+pub async fn update(
+    State(state): State<AppState>,
+    _admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+    ValidatedJson(req): ValidatedJson<CouponRequest>,
+) -> Result<Json<CouponResponse>, ApiError> {
+    let coupon = CouponService::find_by_id(&state.db, mid, id)
+        .await?
+        .ok_or(CouponError::NotFound)?;
+
+    CouponService::update(&state.db, coupon, req.into_input()?)
+        .await
+        .map(|coupon| Json(coupon.into()))
+        .map_err(ApiError::from)
+}
+
+/// Delete a coupon
+#[utoipa::path(
+    delete,
+    path = "/api/coupons/{mid}/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Coupon ID")
+    ),
+    responses(
+        (status = 204, description = "Coupon deleted"),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Coupon not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "coupons"
+)]
+pub async fn delete(
+    State(state): State<AppState>,
+    _admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<StatusCode, ApiError> {
+    if CouponService::delete(&state.db, mid, id).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(CouponError::NotFound.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(kind: &str, value: &str) -> CouponRequest {
+        CouponRequest {
+            code: "SPRING-10".to_string(),
+            description: String::new(),
+            kind: kind.to_string(),
+            value: value.to_string(),
+            min_subtotal: None,
+            starts_gmt: Some(Timestamp::from_unix(200)),
+            ends_gmt: Some(Timestamp::from_unix(100)),
+            max_uses: None,
+            max_uses_per_customer: Some(0),
+            skus: vec![],
+            category_ids: vec![],
+            customer_tags: vec!["VIP".to_string(), "big spender".to_string()],
+            active: true,
+        }
+    }
+
+    #[test]
+    fn test_coupon_validation() {
+        match crate::validation::validate(&request("percent", "150")) {
+            Err(ApiError::Validation(errors)) => {
+                let fields: Vec<_> = errors.iter().map(|e| e.field.as_str()).collect();
+                assert_eq!(fields, vec!["value", "ends_gmt", "max_uses_per_customer", "customer_tags"]);
+            }
+            other => panic!("unexpected result {:?}", other.err().map(|e| e.status())),
+        }
+
+        let mut free_shipping = request("free_shipping", "0");
+        free_shipping.ends_gmt = None;
+        free_shipping.max_uses_per_customer = Some(1);
+        free_shipping.customer_tags.pop();
+        assert!(crate::validation::validate(&free_shipping).is_ok());
+    }
+}
@@ -1,11 +1,19 @@
 use axum::{
     extract::{Path, Query, State},
-    http::StatusCode,
+    http::{HeaderMap, HeaderValue, StatusCode},
     Json,
 };
-use commercerack_customer::CustomerService;
+use commercerack_audit::Change;
+use commercerack_core::Timestamp;
+use commercerack_customer::{CustomerFilter, CustomerGroup, CustomerService, CustomerSort};
+use commercerack_db::pagination::Cursor;
 use ::entity::prelude::Customer;
+use sea_orm::TransactionTrait;
 use serde::{Deserialize, Serialize};
+use crate::auth::{Claims, RequireMerchantAdmin};
+use crate::error::{ApiError, ErrorBody};
+use crate::routes::audit;
+use crate::validation::{self, ValidatedJson, Validate, Validator};
 use crate::AppState;
 
 #[derive(Deserialize, utoipa::ToSchema)]
@@ -17,6 +25,17 @@ pub struct CreateCustomerRequest {
     pub password: Option<String>,
 }
 
+impl Validate for CreateCustomerRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.email("email", &self.email, 65)
+            .required("firstname", &self.firstname, 50)
+            .required("lastname", &self.lastname, 50);
+        if let Some(password) = &self.password {
+            v.check(password.chars().count() >= 8, "password", "must be at least 8 characters");
+        }
+    }
+}
+
 #[derive(Serialize, utoipa::ToSchema)]
 pub struct CustomerResponse {
     pub cid: i32,
@@ -24,8 +43,14 @@ pub struct CustomerResponse {
     pub email: String,
     pub firstname: String,
     pub lastname: String,
-    pub created_gmt: i32,
-    pub modified_gmt: i32,
+    /// Price group whose tier prices the customer gets; empty for none
+    pub price_group: String,
+    /// `retail`, `wholesale` or `employee`
+    pub customer_group: String,
+    #[schema(value_type = i64)]
+    pub created_gmt: Timestamp,
+    #[schema(value_type = i64)]
+    pub modified_gmt: Timestamp,
 }
 
 impl From<Customer> for CustomerResponse {
@@ -36,51 +61,150 @@ impl From<Customer> for CustomerResponse {
             email: customer.email,
             firstname: customer.firstname,
             lastname: customer.lastname,
+            price_group: customer.price_group,
+            customer_group: customer.customer_group,
             created_gmt: customer.created_gmt,
             modified_gmt: customer.modified_gmt,
         }
     }
 }
 
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct PriceGroupRequest {
+    /// Empty to take the customer out of any price group
+    #[serde(default)]
+    pub price_group: String,
+}
+
+impl Validate for PriceGroupRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.max_len("price_group", &self.price_group, 20);
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct CustomerGroupRequest {
+    /// `retail`, `wholesale` or `employee`
+    pub customer_group: String,
+}
+
+impl Validate for CustomerGroupRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.check(
+            self.customer_group.parse::<CustomerGroup>().is_ok(),
+            "customer_group",
+            "must be one of retail, wholesale, employee",
+        );
+    }
+}
+
 #[derive(Deserialize, utoipa::IntoParams)]
 pub struct ListQuery {
     pub mid: i32,
+    /// Case-insensitive substring of the email
+    pub email: Option<String>,
+    /// Case-insensitive substring of the first or last name
+    pub name: Option<String>,
+    /// Only customers created at or after this Unix time
+    #[param(value_type = Option<i64>)]
+    pub created_from: Option<Timestamp>,
+    /// Only customers created at or before this Unix time
+    #[param(value_type = Option<i64>)]
+    pub created_to: Option<Timestamp>,
+    /// Only customers with this tag
+    pub tag: Option<String>,
+    /// Only customers in this customer group
+    pub group: Option<String>,
+    /// One of `newest` (default), `oldest`, `email`, `name`
+    pub sort: Option<String>,
     #[serde(default = "default_limit")]
     pub limit: u64,
-    #[serde(default)]
-    pub offset: u64,
+    /// `next_cursor` of the previous page, listed with the same sort
+    pub cursor: Option<String>,
+}
+
+impl Validate for ListQuery {
+    fn validate(&self, v: &mut Validator) {
+        if let Some(sort) = &self.sort {
+            v.check(sort.parse::<CustomerSort>().is_ok(), "sort", "must be one of newest, oldest, email, name");
+        }
+        if let Some(group) = &self.group {
+            v.check(group.parse::<CustomerGroup>().is_ok(), "group", "must be one of retail, wholesale, employee");
+        }
+        if let Some(cursor) = &self.cursor {
+            v.check(cursor.parse::<Cursor>().is_ok(), "cursor", "is not a cursor from a previous page");
+        }
+        if let (Some(from), Some(to)) = (self.created_from, self.created_to) {
+            v.check(from <= to, "created_to", "must not be before created_from");
+        }
+        v.check((1..=100).contains(&self.limit), "limit", "must be between 1 and 100");
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct CustomerListResponse {
+    pub customers: Vec<CustomerResponse>,
+    /// Pass as `cursor` to get the next page; absent on the last page
+    pub next_cursor: Option<String>,
 }
 
 fn default_limit() -> u64 {
     20
 }
 
-/// Create a new customer
+/// Header carrying the number of matches across all pages
+pub const TOTAL_COUNT_HEADER: &str = "x-total-count";
+
+/// Create a new customer, linking any guest orders billed to their email
 #[utoipa::path(
     post,
     path = "/api/customers",
     request_body = CreateCustomerRequest,
     responses(
         (status = 201, description = "Customer created successfully", body = CustomerResponse),
-        (status = 500, description = "Internal server error")
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
     ),
     tag = "customers"
 )]
 pub async fn create(
     State(state): State<AppState>,
-    Json(req): Json<CreateCustomerRequest>,
-) -> Result<(StatusCode, Json<CustomerResponse>), StatusCode> {
-    CustomerService::create(
-        &*state.db,
-        req.mid,
+    admin: RequireMerchantAdmin,
+    ValidatedJson(req): ValidatedJson<CreateCustomerRequest>,
+) -> Result<(StatusCode, Json<CustomerResponse>), ApiError> {
+    let customer = create_account(
+        &state,
+        admin.0.scoped_mid(req.mid),
         &req.email,
         &req.firstname,
         &req.lastname,
         req.password.as_deref(),
     )
-    .await
-    .map(|customer| (StatusCode::CREATED, Json(customer.into())))
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+    .await?;
+
+    Ok((StatusCode::CREATED, Json(customer.into())))
+}
+
+/// Create a customer account and hand it the guest orders billed to its
+/// email; shared by staff-created accounts and storefront signup
+pub(crate) async fn create_account(
+    state: &AppState,
+    mid: i32,
+    email: &str,
+    firstname: &str,
+    lastname: &str,
+    password: Option<&str>,
+) -> Result<Customer, ApiError> {
+    // The account and its claim on earlier guest orders commit together
+    let txn = state.db.begin().await?;
+    let customer = CustomerService::create(&txn, mid, email, firstname, lastname, password).await?;
+
+    // Orders they placed as a guest now belong to the account
+    CustomerService::claim_guest_orders(&txn, customer.mid, &customer.email, customer.cid).await?;
+    txn.commit().await?;
+
+    Ok(customer)
 }
 
 /// Get a customer by ID
@@ -93,39 +217,148 @@ pub async fn create(
     ),
     responses(
         (status = 200, description = "Customer found", body = CustomerResponse),
-        (status = 404, description = "Customer not found"),
-        (status = 500, description = "Internal server error")
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Customers may only see their own account", body = ErrorBody),
+        (status = 404, description = "Customer not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
     ),
     tag = "customers"
 )]
 pub async fn get(
     State(state): State<AppState>,
+    claims: Claims,
     Path((mid, id)): Path<(i32, i32)>,
-) -> Result<Json<CustomerResponse>, StatusCode> {
-    CustomerService::find_by_id(&*state.db, mid, id)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+) -> Result<Json<CustomerResponse>, ApiError> {
+    claims.ensure_self(id)?;
+
+    CustomerService::find_by_id(&state.db, claims.scoped_mid(mid), id)
+        .await?
         .map(|customer| Json(customer.into()))
-        .ok_or(StatusCode::NOT_FOUND)
+        .ok_or_else(|| ApiError::not_found("Customer"))
+}
+
+/// Assign a customer to a price group
+#[utoipa::path(
+    put,
+    path = "/api/customers/{mid}/{id}/price-group",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Customer ID")
+    ),
+    request_body = PriceGroupRequest,
+    responses(
+        (status = 200, description = "Customer updated", body = CustomerResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Customer not found", body = ErrorBody),
+        (status = 422, description = "Price group too long", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "customers"
+)]
+pub async fn set_price_group(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+    ValidatedJson(req): ValidatedJson<PriceGroupRequest>,
+) -> Result<Json<CustomerResponse>, ApiError> {
+    let before = CustomerService::find_by_id(&state.db, mid, id)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Customer"))?;
+
+    let customer = CustomerService::set_price_group(&*state.db, mid, id, req.price_group.trim()).await?;
+    audit::record(&state, &admin.0, mid, Change::new("customer", id, "update").diff(&before, &customer)).await;
+    Ok(Json(customer.into()))
+}
+
+/// Move a customer to another customer group
+#[utoipa::path(
+    put,
+    path = "/api/customers/{mid}/{id}/group",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Customer ID")
+    ),
+    request_body = CustomerGroupRequest,
+    responses(
+        (status = 200, description = "Customer updated", body = CustomerResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Customer not found", body = ErrorBody),
+        (status = 422, description = "Unknown customer group", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "customers"
+)]
+pub async fn set_group(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+    ValidatedJson(req): ValidatedJson<CustomerGroupRequest>,
+) -> Result<Json<CustomerResponse>, ApiError> {
+    let mid = admin.0.scoped_mid(mid);
+    let group = req.customer_group.parse::<CustomerGroup>().map_err(ApiError::BadRequest)?;
+    let before = CustomerService::find_by_id(&state.db, mid, id)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Customer"))?;
+
+    let customer = CustomerService::set_group(&*state.db, mid, id, group).await?;
+    audit::record(&state, &admin.0, mid, Change::new("customer", id, "update").diff(&before, &customer)).await;
+    Ok(Json(customer.into()))
 }
 
-/// List customers (placeholder - not implemented in CustomerService yet)
+/// List and search a merchant's customers
+#[utoipa::path(
+    get,
+    path = "/api/customers",
+    params(ListQuery),
+    responses(
+        (status = 200, description = "One page of matching customers",
+            body = CustomerListResponse,
+            headers(("x-total-count" = u64, description = "Number of matching customers across all pages"))),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 422, description = "Invalid sort order, date range, cursor or limit", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "customers"
+)]
 pub async fn list(
     State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
     Query(query): Query<ListQuery>,
-) -> Result<Json<Vec<CustomerResponse>>, StatusCode> {
-    // TODO: Implement list in CustomerService
-    Ok(Json(vec![]))
+) -> Result<(HeaderMap, Json<CustomerListResponse>), ApiError> {
+    validation::validate(&query)?;
+
+    let filter = CustomerFilter {
+        email: query.email,
+        name: query.name,
+        created_from: query.created_from,
+        created_to: query.created_to,
+        tag: query.tag,
+        group: query.group.as_deref().map(str::parse).transpose().map_err(ApiError::BadRequest)?,
+        sort: query.sort.as_deref().map(str::parse).transpose().map_err(ApiError::BadRequest)?.unwrap_or_default(),
+    };
+
+    let cursor = query.cursor.as_deref().map(str::parse::<Cursor>).transpose()?;
+
+    let mid = admin.0.scoped_mid(query.mid);
+    let (customers, total, next) = CustomerService::list(&state.db, mid, &filter, query.limit, cursor.as_ref()).await?;
+
+    let mut headers = HeaderMap::new();
+    headers.insert(TOTAL_COUNT_HEADER, HeaderValue::from(total));
+    Ok((headers, Json(CustomerListResponse {
+        customers: customers.into_iter().map(|c| c.into()).collect(),
+        next_cursor: next.map(|cursor| cursor.to_string()),
+    })))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use sea_orm::{DatabaseBackend, MockDatabase, MockExecResult};
-    use axum::{
-        body::Body,
-        http::{Request, StatusCode},
-    };
+    use crate::auth::Role;
+    use sea_orm::{DatabaseBackend, MockDatabase, MockExecResult, Value};
+    use std::collections::BTreeMap;
 
     #[tokio::test]
     async fn test_create_customer() {
@@ -138,12 +371,7 @@ mod tests {
             ])
             .into_connection();
 
-        let state = AppState {
-            db: std::sync::Arc::new(db),
-            cart_store: std::sync::Arc::new(std::sync::Mutex::new(
-                commercerack_cart::CartStore::new()
-            )),
-        };
+        let state = AppState::mock(db);
 
         let req = CreateCustomerRequest {
             mid: 1,
@@ -154,9 +382,109 @@ mod tests {
         };
 
         // This will fail in mock but validates the structure
-        let result = create(State(state), Json(req)).await;
+        let admin = RequireMerchantAdmin::new(Claims::new(1, 1).with_role(Role::MerchantAdmin));
+        let result = create(State(state), admin, ValidatedJson(req)).await;
 
         // We expect an error with mock database, but this validates the code compiles
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_create_claims_guest_orders_in_same_transaction() {
+        let customer = Customer {
+            cid: 7,
+            mid: 1,
+            email: "Ann@Example.com".to_string(),
+            firstname: "Ann".to_string(),
+            lastname: "Smith".to_string(),
+            created_gmt: Timestamp::EPOCH,
+            modified_gmt: Timestamp::EPOCH,
+            passhash: String::new(),
+            passsalt: String::new(),
+            price_group: String::new(),
+            customer_group: "retail".to_string(),
+            role: "customer".to_string(),
+        };
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![customer]])
+            .append_exec_results([
+                MockExecResult { last_insert_id: 0, rows_affected: 1 },
+                MockExecResult { last_insert_id: 0, rows_affected: 2 },
+            ])
+            .into_connection();
+
+        let state = AppState::mock(db);
+        let db = state.db.clone();
+        let req = CreateCustomerRequest {
+            mid: 1,
+            email: "Ann@Example.com".to_string(),
+            firstname: "Ann".to_string(),
+            lastname: "Smith".to_string(),
+            password: None,
+        };
+
+        let admin = RequireMerchantAdmin::new(Claims::new(1, 1).with_role(Role::MerchantAdmin));
+        let (status, _) = create(State(state), admin, ValidatedJson(req)).await.unwrap();
+        assert_eq!(status, StatusCode::CREATED);
+
+        let log = std::sync::Arc::try_unwrap(db).ok().unwrap().into_transaction_log();
+        assert_eq!(log.len(), 1);
+        let statements: Vec<String> = log[0].statements().iter().map(|s| s.to_string()).collect();
+        assert!(statements.iter().any(|sql| sql.starts_with(r#"INSERT INTO "customers""#)), "{:?}", statements);
+        assert!(statements.iter().any(|sql| sql.starts_with(r#"UPDATE "orders" SET "customer" = 7"#)), "{:?}", statements);
+        assert_eq!(statements.last().map(String::as_str), Some("COMMIT"));
+    }
+
+    #[tokio::test]
+    async fn test_list_sets_total_count() {
+        let customer = |cid: i32| Customer {
+            cid,
+            mid: 1,
+            email: "ann@example.com".to_string(),
+            firstname: "Ann".to_string(),
+            lastname: "Smith".to_string(),
+            created_gmt: Timestamp::EPOCH,
+            modified_gmt: Timestamp::EPOCH,
+            passhash: String::new(),
+            passsalt: String::new(),
+            price_group: String::new(),
+            customer_group: "retail".to_string(),
+            role: "customer".to_string(),
+        };
+        let count = BTreeMap::from([("num_items".to_string(), Value::BigInt(Some(41)))]);
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![count]])
+            .append_query_results([vec![customer(7), customer(8)]])
+            .into_connection();
+
+        let state = AppState::mock(db);
+        let query = ListQuery {
+            mid: 1,
+            email: None,
+            name: Some("smith".to_string()),
+            created_from: None,
+            created_to: None,
+            tag: None,
+            group: None,
+            sort: Some("name".to_string()),
+            limit: 1,
+            cursor: None,
+        };
+
+        let admin = RequireMerchantAdmin::new(Claims::new(1, 1).with_role(Role::MerchantAdmin));
+        let (headers, Json(page)) = list(State(state), admin, Query(query)).await.unwrap();
+        assert_eq!(headers[TOTAL_COUNT_HEADER], "41");
+        assert_eq!(page.customers.iter().map(|c| c.cid).collect::<Vec<_>>(), vec![7]);
+
+        let next: Cursor = page.next_cursor.unwrap().parse().unwrap();
+        assert_eq!(next.sort, "name");
+    }
+
+    #[tokio::test]
+    async fn test_get_is_limited_to_the_customers_own_account() {
+        let state = AppState::mock(MockDatabase::new(DatabaseBackend::Postgres).into_connection());
+
+        let result = get(State(state), Claims::new(7, 1), Path((1, 8))).await;
+        assert_eq!(result.err().map(|e| e.status()), Some(StatusCode::FORBIDDEN));
+    }
 }
@@ -0,0 +1,177 @@
+//! Delivery estimate routes
+//!
+//! Shoppers see when a product would arrive before adding it to a cart;
+//! cart shipping options carry the same estimate. Estimates combine the
+//! handling time of the warehouses the units would ship from with the
+//! transit times recorded for the destination's shipping zone.
+
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+use commercerack_core::Timestamp;
+use commercerack_merchant::settings::SettingsService;
+use commercerack_product::availability::Visibility;
+use commercerack_product::sku::SKUService;
+use commercerack_product::{ProductError, ProductService};
+use commercerack_shipping::{DeliveryEstimate, DeliveryService, DeliveryWindow, Destination};
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use crate::error::{ApiError, ErrorBody};
+use crate::validation::{self, Validate, Validator};
+use crate::AppState;
+
+/// The merchant's wall-clock time, which warehouse cutoff hours are in
+pub(crate) async fn merchant_now(db: &DatabaseConnection, mid: i32) -> Result<NaiveDateTime, ApiError> {
+    let settings = SettingsService::get(db, mid).await?;
+    Ok(Utc::now().with_timezone(&settings.timezone).naive_local())
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct DeliveryWindowResponse {
+    pub method: String,
+    /// Earliest day the parcel arrives
+    pub earliest: NaiveDate,
+    /// Latest day the parcel arrives
+    pub latest: NaiveDate,
+}
+
+impl From<&DeliveryWindow> for DeliveryWindowResponse {
+    fn from(window: &DeliveryWindow) -> Self {
+        Self {
+            method: window.method.clone(),
+            earliest: window.earliest,
+            latest: window.latest,
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct DeliveryEstimateResponse {
+    /// Day the last parcel leaves the warehouse
+    pub ships_on: NaiveDate,
+    /// Shipping methods of the destination with known transit times
+    pub options: Vec<DeliveryWindowResponse>,
+}
+
+impl From<DeliveryEstimate> for DeliveryEstimateResponse {
+    fn from(estimate: DeliveryEstimate) -> Self {
+        Self {
+            ships_on: estimate.ships_on,
+            options: estimate.windows.iter().map(Into::into).collect(),
+        }
+    }
+}
+
+fn default_country() -> String {
+    "US".to_string()
+}
+
+fn default_quantity() -> i32 {
+    1
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct ProductEstimateQuery {
+    pub zip: String,
+    /// ISO 3166-1 alpha-2 code; `US` when omitted
+    #[serde(default = "default_country")]
+    pub country: String,
+    #[serde(default)]
+    pub state: String,
+    /// Variant to estimate; the product's soonest shipping SKU when omitted
+    pub sku: Option<String>,
+    #[serde(default = "default_quantity")]
+    pub quantity: i32,
+}
+
+impl Validate for ProductEstimateQuery {
+    fn validate(&self, v: &mut Validator) {
+        v.required("zip", &self.zip, 20)
+            .check(
+                self.country.trim().len() == 2 && self.country.trim().chars().all(|c| c.is_ascii_alphabetic()),
+                "country",
+                "must be a two-letter country code",
+            )
+            .max_len("state", &self.state, 20)
+            .check((1..=1000).contains(&self.quantity), "quantity", "must be between 1 and 1000");
+    }
+}
+
+/// When a product would arrive at a zip code
+#[utoipa::path(
+    get,
+    path = "/api/products/{mid}/{id}/delivery-estimate",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Product ID"),
+        ProductEstimateQuery
+    ),
+    responses(
+        (status = 200, description = "Ship date and delivery window of each method", body = DeliveryEstimateResponse),
+        (status = 404, description = "Product not found or not on sale, or SKU not of the product", body = ErrorBody),
+        (status = 422, description = "Invalid destination or quantity", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "products"
+)]
+pub async fn product_estimate(
+    State(state): State<AppState>,
+    Path((mid, id)): Path<(i32, i32)>,
+    Query(query): Query<ProductEstimateQuery>,
+) -> Result<Json<DeliveryEstimateResponse>, ApiError> {
+    validation::validate(&query)?;
+    ProductService::find_by_id(&state.db, mid, id)
+        .await?
+        .filter(|product| Visibility::Storefront.includes(product, Timestamp::now()))
+        .ok_or(ProductError::NotFound)?;
+
+    let skus: Vec<String> = SKUService::find_by_product(&state.db, mid, id)
+        .await?
+        .into_iter()
+        .map(|sku| sku.sku)
+        .filter(|sku| query.sku.as_ref().is_none_or(|wanted| wanted == sku))
+        .collect();
+    if query.sku.is_some() && skus.is_empty() {
+        return Err(ApiError::not_found("SKU"));
+    }
+
+    let destination = Destination::new(&query.country, &query.state, &query.zip);
+    let placed = merchant_now(&state.db, mid).await?;
+    let mut best: Option<DeliveryEstimate> = None;
+    for sku in skus {
+        let lines = [(sku, query.quantity)];
+        let estimate = DeliveryService::estimate(&state.db, mid, &lines, &destination, placed).await?;
+        if best.as_ref().is_none_or(|best| estimate.ships_on < best.ships_on) {
+            best = Some(estimate);
+        }
+    }
+    let estimate = match best {
+        Some(estimate) => estimate,
+        None => DeliveryService::estimate(&state.db, mid, &[], &destination, placed).await?,
+    };
+
+    Ok(Json(estimate.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_product_estimate_query_validation() {
+        let query: ProductEstimateQuery = serde_json::from_str(r#"{"zip": "97201"}"#).unwrap();
+        assert_eq!((query.country.as_str(), query.quantity), ("US", 1));
+        assert!(validation::validate(&query).is_ok());
+
+        let query = ProductEstimateQuery { country: "USA".to_string(), quantity: 0, ..query };
+        match validation::validate(&query) {
+            Err(ApiError::Validation(errors)) => {
+                let fields: Vec<_> = errors.iter().map(|e| e.field.as_str()).collect();
+                assert_eq!(fields, vec!["country", "quantity"]);
+            }
+            other => panic!("unexpected result {:?}", other.err().map(|e| e.status())),
+        }
+    }
+}
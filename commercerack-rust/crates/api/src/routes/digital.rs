@@ -0,0 +1,355 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Redirect,
+    Json,
+};
+use commercerack_core::Timestamp;
+use commercerack_order::digital::DownloadService;
+use commercerack_product::digital::{Delivery, DigitalError, LicenseKeyService, ProductType};
+use commercerack_product::ProductService;
+use entity::prelude::{LicenseKey, OrderDownload, Product};
+use serde::{Deserialize, Serialize};
+use crate::auth::{Claims, RequireMerchantAdmin};
+use crate::error::{ApiError, ErrorBody};
+use crate::routes::payments::ensure_owner;
+use crate::validation::{ValidatedJson, Validate, Validator};
+use crate::AppState;
+
+/// How a product is delivered; physical unless `product_type` says otherwise
+#[derive(Deserialize, Default, utoipa::ToSchema)]
+pub struct DeliveryRequest {
+    /// `physical` or `digital`
+    pub product_type: Option<String>,
+    /// File buyers are redirected to by their download links
+    pub download_url: Option<String>,
+    /// Downloads per purchase; the configured default when omitted
+    pub download_limit: Option<i32>,
+}
+
+impl DeliveryRequest {
+    /// Settings of a validated request
+    pub fn delivery(&self) -> Delivery {
+        Delivery {
+            product_type: self
+                .product_type
+                .as_deref()
+                .and_then(|product_type| product_type.parse().ok())
+                .unwrap_or_default(),
+            download_url: self.download_url.clone(),
+            download_limit: self.download_limit,
+        }
+    }
+}
+
+impl Validate for DeliveryRequest {
+    fn validate(&self, v: &mut Validator) {
+        if let Some(product_type) = &self.product_type {
+            v.check(
+                product_type.parse::<ProductType>().is_ok(),
+                "product_type",
+                "must be physical or digital",
+            );
+        }
+        if let Some(url) = &self.download_url {
+            v.required("download_url", url, 1024).check(
+                url.starts_with("https://") || url.starts_with("http://"),
+                "download_url",
+                "must be an http(s) URL",
+            );
+        }
+        if let Err(DigitalError::InvalidDelivery(message)) = self.delivery().validate() {
+            v.error("product_type", message);
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct DeliveryResponse {
+    pub id: i32,
+    pub mid: i32,
+    pub product_type: String,
+    pub download_url: Option<String>,
+    pub download_limit: Option<i32>,
+}
+
+impl From<Product> for DeliveryResponse {
+    fn from(product: Product) -> Self {
+        Self {
+            id: product.id,
+            mid: product.mid,
+            product_type: product.product_type,
+            download_url: product.download_url,
+            download_limit: product.download_limit,
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct AddLicenseKeysRequest {
+    pub mid: i32,
+    pub sku: String,
+    pub keys: Vec<String>,
+}
+
+impl Validate for AddLicenseKeysRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("sku", &self.sku, 45)
+            .check(!self.keys.is_empty(), "keys", "must contain at least one key")
+            .check(self.keys.len() <= 1000, "keys", "at most 1000 keys per request");
+        for (i, key) in self.keys.iter().enumerate() {
+            v.required(&format!("keys[{}]", i), key, 255);
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct KeyPoolResponse {
+    pub sku: String,
+    /// Keys added by this request; duplicates of pooled keys are skipped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub added: Option<usize>,
+    /// Keys left to assign
+    pub available: u64,
+    pub assigned: u64,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct DownloadResponse {
+    pub order_item_id: i32,
+    pub sku: String,
+    /// Download link, good until `expires_gmt` or `max_downloads` uses
+    pub path: String,
+    pub downloads: i32,
+    pub max_downloads: i32,
+    #[schema(value_type = i64)]
+    pub expires_gmt: Timestamp,
+}
+
+impl From<OrderDownload> for DownloadResponse {
+    fn from(download: OrderDownload) -> Self {
+        Self {
+            path: format!("/api/downloads/{}", download.token),
+            order_item_id: download.order_item_id,
+            sku: download.sku,
+            downloads: download.downloads,
+            max_downloads: download.max_downloads,
+            expires_gmt: download.expires_gmt,
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct LicenseKeyResponse {
+    pub order_item_id: Option<i32>,
+    pub sku: String,
+    pub license_key: String,
+}
+
+impl From<LicenseKey> for LicenseKeyResponse {
+    fn from(key: LicenseKey) -> Self {
+        Self {
+            order_item_id: key.order_item_id,
+            sku: key.sku,
+            license_key: key.license_key,
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct DigitalDeliveryResponse {
+    pub downloads: Vec<DownloadResponse>,
+    pub license_keys: Vec<LicenseKeyResponse>,
+}
+
+/// Change how a product is delivered
+#[utoipa::path(
+    put,
+    path = "/api/products/{mid}/{id}/delivery",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Product ID")
+    ),
+    request_body = DeliveryRequest,
+    responses(
+        (status = 200, description = "Delivery updated", body = DeliveryResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Product not found", body = ErrorBody),
+        (status = 422, description = "Invalid product type, URL or limit", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "products"
+)]
+pub async fn set_delivery(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+    ValidatedJson(req): ValidatedJson<DeliveryRequest>,
+) -> Result<Json<DeliveryResponse>, ApiError> {
+    ProductService::set_delivery(&state.db, admin.0.scoped_mid(mid), id, req.delivery())
+        .await
+        .map(|product| Json(product.into()))
+        .map_err(ApiError::from)
+}
+
+/// Add license keys to a SKU's pool
+#[utoipa::path(
+    post,
+    path = "/api/license-keys",
+    request_body = AddLicenseKeysRequest,
+    responses(
+        (status = 201, description = "Keys added", body = KeyPoolResponse),
+        (status = 400, description = "Unknown SKU", body = ErrorBody),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 422, description = "No keys or a blank key", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "products"
+)]
+pub async fn add_license_keys(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    ValidatedJson(req): ValidatedJson<AddLicenseKeysRequest>,
+) -> Result<(StatusCode, Json<KeyPoolResponse>), ApiError> {
+    let mid = admin.0.scoped_mid(req.mid);
+
+    let added = LicenseKeyService::add(&state.db, mid, &req.sku, &req.keys).await?;
+    let pool = LicenseKeyService::pool(&state.db, mid, &req.sku).await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(KeyPoolResponse {
+            sku: req.sku,
+            added: Some(added.len()),
+            available: pool.available,
+            assigned: pool.assigned,
+        }),
+    ))
+}
+
+/// How many license keys a SKU has left
+#[utoipa::path(
+    get,
+    path = "/api/license-keys/{mid}/{sku}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("sku" = String, Path, description = "SKU code")
+    ),
+    responses(
+        (status = 200, description = "Key pool", body = KeyPoolResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "products"
+)]
+pub async fn license_key_pool(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, sku)): Path<(i32, String)>,
+) -> Result<Json<KeyPoolResponse>, ApiError> {
+    let pool = LicenseKeyService::pool(&state.db, admin.0.scoped_mid(mid), &sku).await?;
+
+    Ok(Json(KeyPoolResponse {
+        sku,
+        added: None,
+        available: pool.available,
+        assigned: pool.assigned,
+    }))
+}
+
+/// Download links and license keys delivered with an order
+#[utoipa::path(
+    get,
+    path = "/api/orders/{mid}/{id}/downloads",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Order ID")
+    ),
+    responses(
+        (status = 200, description = "Digital delivery; empty until the order is paid", body = DigitalDeliveryResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 404, description = "Order not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "orders"
+)]
+pub async fn order_downloads(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Json<DigitalDeliveryResponse>, ApiError> {
+    let mid = claims.scoped_mid(mid);
+    ensure_owner(&state, &claims, mid, id).await?;
+
+    let delivery = DownloadService::for_order(&state.db, mid, id).await?;
+    Ok(Json(DigitalDeliveryResponse {
+        downloads: delivery.downloads.into_iter().map(|d| d.into()).collect(),
+        license_keys: delivery.license_keys.into_iter().map(|k| k.into()).collect(),
+    }))
+}
+
+/// Follow a download link to the product's file
+#[utoipa::path(
+    get,
+    path = "/api/downloads/{token}",
+    params(
+        ("token" = String, Path, description = "Download token")
+    ),
+    responses(
+        (status = 307, description = "Redirect to the file"),
+        (status = 403, description = "Link expired or download limit reached", body = ErrorBody),
+        (status = 404, description = "Unknown link", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "orders"
+)]
+pub async fn download(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+) -> Result<Redirect, ApiError> {
+    DownloadService::redeem(&state.db, &token)
+        .await
+        .map(|url| Redirect::temporary(&url))
+        .map_err(ApiError::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invalid_fields(req: &DeliveryRequest) -> Vec<String> {
+        match crate::validation::validate(req) {
+            Ok(()) => Vec::new(),
+            Err(ApiError::Validation(errors)) => errors.into_iter().map(|e| e.field).collect(),
+            Err(e) => panic!("unexpected error {e:?}"),
+        }
+    }
+
+    #[test]
+    fn test_validate_delivery() {
+        assert!(invalid_fields(&DeliveryRequest::default()).is_empty());
+        assert_eq!(DeliveryRequest::default().delivery(), Delivery::default());
+
+        let ebook = DeliveryRequest {
+            product_type: Some("digital".to_string()),
+            download_url: Some("https://files.example/ebook.pdf".to_string()),
+            download_limit: Some(3),
+        };
+        assert!(invalid_fields(&ebook).is_empty());
+        assert_eq!(ebook.delivery().product_type, ProductType::Digital);
+
+        let bad_type = DeliveryRequest { product_type: Some("virtual".to_string()), ..Default::default() };
+        assert_eq!(invalid_fields(&bad_type), vec!["product_type"]);
+        let bad_url = DeliveryRequest {
+            product_type: Some("digital".to_string()),
+            download_url: Some("ftp://files.example/ebook.pdf".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(invalid_fields(&bad_url), vec!["download_url"]);
+        let physical_file = DeliveryRequest { download_url: Some("https://files.example/a".to_string()), ..Default::default() };
+        assert_eq!(invalid_fields(&physical_file), vec!["product_type"]);
+    }
+}
@@ -0,0 +1,125 @@
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use commercerack_core::Timestamp;
+use commercerack_order::dunning::{DunningCaseWithOrder, DunningService, DunningStatus};
+use serde::{Deserialize, Serialize};
+use crate::auth::RequireMerchantAdmin;
+use crate::error::{ApiError, ErrorBody};
+use crate::validation::{self, Validate, Validator};
+use crate::AppState;
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct DunningQuery {
+    /// Only cases that are `retrying`, `recovered`, `suspended` or `closed`
+    pub status: Option<String>,
+}
+
+impl Validate for DunningQuery {
+    fn validate(&self, v: &mut Validator) {
+        if let Some(status) = &self.status {
+            v.check(
+                status.parse::<DunningStatus>().is_ok(),
+                "status",
+                "must be retrying, recovered, suspended or closed",
+            );
+        }
+    }
+}
+
+/// An order whose payment was declined, and where retrying it stands.
+/// The payment method token is not shown.
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct DunningCaseResponse {
+    pub id: i32,
+    pub order_id: i32,
+    pub orderid: String,
+    pub customer: i32,
+    pub bill_email: String,
+    pub total: String,
+    /// `retrying`, `recovered`, `suspended` or `closed`
+    pub status: String,
+    /// Declined charges so far, the first included
+    pub attempts: i32,
+    /// When the next retry is due; `null` once the case stops retrying
+    #[schema(value_type = Option<i64>)]
+    pub next_attempt_gmt: Option<Timestamp>,
+    /// Why the last charge was declined
+    pub last_error: String,
+    #[schema(value_type = i64)]
+    pub created_gmt: Timestamp,
+    #[schema(value_type = i64)]
+    pub modified_gmt: Timestamp,
+}
+
+impl From<DunningCaseWithOrder> for DunningCaseResponse {
+    fn from(dunning: DunningCaseWithOrder) -> Self {
+        Self {
+            id: dunning.case.id,
+            order_id: dunning.order.id,
+            orderid: dunning.order.orderid,
+            customer: dunning.order.customer,
+            bill_email: dunning.order.bill_email,
+            total: dunning.order.total.to_string(),
+            status: dunning.case.status,
+            attempts: dunning.case.attempts,
+            next_attempt_gmt: dunning.case.next_attempt_gmt,
+            last_error: dunning.case.last_error,
+            created_gmt: dunning.case.created_gmt,
+            modified_gmt: dunning.case.modified_gmt,
+        }
+    }
+}
+
+/// A merchant's orders in dunning
+///
+/// Cases open when a charge made with `dunning` set is declined; see
+/// `POST /api/orders/{mid}/{id}/payments`. The merchant's
+/// `dunning_retry_days` setting decides when they are retried.
+#[utoipa::path(
+    get,
+    path = "/api/dunning/{mid}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        DunningQuery
+    ),
+    responses(
+        (status = 200, description = "Cases, soonest retry first, then newest", body = Vec<DunningCaseResponse>),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 422, description = "Unknown status", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "payments"
+)]
+pub async fn list_dunning(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path(mid): Path<i32>,
+    Query(query): Query<DunningQuery>,
+) -> Result<Json<Vec<DunningCaseResponse>>, ApiError> {
+    validation::validate(&query)?;
+
+    let mid = admin.0.scoped_mid(mid);
+    let status = query.status.as_deref().and_then(|status| status.parse().ok());
+    DunningService::list(&*state.db, mid, status)
+        .await
+        .map(|cases| Json(cases.into_iter().map(|case| case.into()).collect()))
+        .map_err(ApiError::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_checks_status() {
+        assert!(validation::validate(&DunningQuery { status: None }).is_ok());
+        assert!(validation::validate(&DunningQuery { status: Some("suspended".to_string()) }).is_ok());
+        match validation::validate(&DunningQuery { status: Some("failed".to_string()) }) {
+            Err(ApiError::Validation(errors)) => assert_eq!(errors[0].field, "status"),
+            other => panic!("unexpected result {:?}", other.err().map(|e| e.status())),
+        }
+    }
+}
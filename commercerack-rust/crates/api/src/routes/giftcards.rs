@@ -0,0 +1,424 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use commercerack_core::Timestamp;
+use commercerack_giftcards::{check_usable, mask_code, GiftCardError, GiftCardService, NewGiftCard};
+use ::entity::prelude::{GiftCard, GiftCardTransaction};
+use serde::{Deserialize, Serialize};
+use crate::auth::RequireMerchantAdmin;
+use crate::error::{ApiError, ErrorBody};
+use crate::routes::parse_decimal;
+use crate::validation::{self, ValidatedJson, Validate, Validator};
+use crate::AppState;
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct IssueGiftCardRequest {
+    pub mid: i32,
+    /// Opening balance as a decimal string
+    pub amount: String,
+    /// Generated when omitted; matched case-insensitively and stored uppercase
+    pub code: Option<String>,
+    /// Customer the card is issued to
+    #[serde(default)]
+    pub cid: i32,
+    #[schema(value_type = Option<i64>)]
+    pub expires_gmt: Option<Timestamp>,
+    #[serde(default)]
+    pub note: String,
+}
+
+impl Validate for IssueGiftCardRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.positive_amount("amount", &self.amount)
+            .non_negative("cid", self.cid)
+            .max_len("note", &self.note, 255);
+        if let Some(code) = &self.code {
+            v.required("code", code, 32).check(
+                code.trim().chars().all(|c| c.is_ascii_alphanumeric() || c == '-'),
+                "code",
+                "may only contain letters, digits and -",
+            );
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct AdjustGiftCardRequest {
+    /// Added to the balance; negative to take from it
+    pub amount: String,
+    #[serde(default)]
+    pub note: String,
+}
+
+impl Validate for AdjustGiftCardRequest {
+    fn validate(&self, v: &mut Validator) {
+        match self.amount.trim().parse::<rust_decimal::Decimal>() {
+            Ok(amount) => {
+                v.check(!amount.is_zero(), "amount", "must not be zero");
+            }
+            Err(_) => v.error("amount", "must be a decimal number"),
+        }
+        v.max_len("note", &self.note, 255);
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct VoidGiftCardRequest {
+    #[serde(default)]
+    pub note: String,
+}
+
+impl Validate for VoidGiftCardRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.max_len("note", &self.note, 255);
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct BalanceRequest {
+    pub mid: i32,
+    pub code: String,
+}
+
+impl Validate for BalanceRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("code", &self.code, 32);
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct GiftCardResponse {
+    pub id: i32,
+    pub mid: i32,
+    pub code: String,
+    pub initial_balance: String,
+    pub balance: String,
+    /// 0 when not issued to a customer
+    pub cid: i32,
+    pub voided: bool,
+    #[schema(value_type = Option<i64>)]
+    pub expires_gmt: Option<Timestamp>,
+    #[schema(value_type = i64)]
+    pub created_gmt: Timestamp,
+    #[schema(value_type = i64)]
+    pub modified_gmt: Timestamp,
+}
+
+impl From<GiftCard> for GiftCardResponse {
+    fn from(card: GiftCard) -> Self {
+        Self {
+            id: card.id,
+            mid: card.mid,
+            code: card.code,
+            initial_balance: card.initial_balance.to_string(),
+            balance: card.balance.to_string(),
+            cid: card.cid,
+            voided: card.voided,
+            expires_gmt: card.expires_gmt,
+            created_gmt: card.created_gmt,
+            modified_gmt: card.modified_gmt,
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct GiftCardTransactionResponse {
+    pub id: i32,
+    /// `issue`, `redeem`, `adjust` or `void`
+    pub kind: String,
+    /// Change to the balance; redemptions are negative
+    pub amount: String,
+    pub balance_after: String,
+    pub order_id: Option<i32>,
+    pub note: String,
+    #[schema(value_type = i64)]
+    pub created_gmt: Timestamp,
+}
+
+impl From<GiftCardTransaction> for GiftCardTransactionResponse {
+    fn from(transaction: GiftCardTransaction) -> Self {
+        Self {
+            id: transaction.id,
+            kind: transaction.kind,
+            amount: transaction.amount.to_string(),
+            balance_after: transaction.balance_after.to_string(),
+            order_id: transaction.order_id,
+            note: transaction.note,
+            created_gmt: transaction.created_gmt,
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct GiftCardDetailResponse {
+    #[serde(flatten)]
+    pub card: GiftCardResponse,
+    /// Oldest first
+    pub transactions: Vec<GiftCardTransactionResponse>,
+}
+
+/// What a shopper sees when checking a card
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct BalanceResponse {
+    /// Only the last four characters are shown
+    pub code: String,
+    pub balance: String,
+    #[schema(value_type = Option<i64>)]
+    pub expires_gmt: Option<Timestamp>,
+    /// `active`, `voided`, `expired` or `empty`
+    pub status: String,
+}
+
+impl From<GiftCard> for BalanceResponse {
+    fn from(card: GiftCard) -> Self {
+        let status = match check_usable(&card, Timestamp::now()) {
+            Ok(()) => "active",
+            Err(GiftCardError::Voided) => "voided",
+            Err(GiftCardError::Expired) => "expired",
+            Err(_) => "empty",
+        };
+        Self {
+            code: mask_code(&card.code),
+            balance: card.balance.to_string(),
+            expires_gmt: card.expires_gmt,
+            status: status.to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct ListQuery {
+    pub mid: i32,
+    /// Only cards issued to this customer
+    pub cid: Option<i32>,
+    #[serde(default = "default_limit")]
+    pub limit: u64,
+}
+
+impl Validate for ListQuery {
+    fn validate(&self, v: &mut Validator) {
+        v.check((1..=100).contains(&self.limit), "limit", "must be between 1 and 100");
+    }
+}
+
+fn default_limit() -> u64 {
+    50
+}
+
+/// Issue a gift card
+#[utoipa::path(
+    post,
+    path = "/api/gift-cards",
+    request_body = IssueGiftCardRequest,
+    responses(
+        (status = 201, description = "Gift card issued", body = GiftCardResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 409, description = "Code already in use", body = ErrorBody),
+        (status = 422, description = "Invalid amount or code", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "giftcards"
+)]
+pub async fn issue(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    ValidatedJson(req): ValidatedJson<IssueGiftCardRequest>,
+) -> Result<(StatusCode, Json<GiftCardResponse>), ApiError> {
+    let mid = admin.0.scoped_mid(req.mid);
+    let card = NewGiftCard {
+        amount: parse_decimal("amount", &req.amount)?,
+        code: req.code,
+        cid: req.cid,
+        expires_gmt: req.expires_gmt,
+        note: req.note,
+    };
+
+    let card = GiftCardService::issue(&*state.db, mid, card).await?;
+    Ok((StatusCode::CREATED, Json(card.into())))
+}
+
+/// A merchant's gift cards
+#[utoipa::path(
+    get,
+    path = "/api/gift-cards",
+    params(ListQuery),
+    responses(
+        (status = 200, description = "Gift cards, newest first", body = Vec<GiftCardResponse>),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 422, description = "Invalid limit", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "giftcards"
+)]
+pub async fn list(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Query(query): Query<ListQuery>,
+) -> Result<Json<Vec<GiftCardResponse>>, ApiError> {
+    validation::validate(&query)?;
+
+    let mid = admin.0.scoped_mid(query.mid);
+    let cards = GiftCardService::list(&*state.db, mid, query.cid, query.limit).await?;
+    Ok(Json(cards.into_iter().map(|card| card.into()).collect()))
+}
+
+/// Get a gift card with its ledger
+#[utoipa::path(
+    get,
+    path = "/api/gift-cards/{mid}/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Gift card ID")
+    ),
+    responses(
+        (status = 200, description = "Gift card found", body = GiftCardDetailResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Gift card not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "giftcards"
+)]
+pub async fn get(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Json<GiftCardDetailResponse>, ApiError> {
+    let mid = admin.0.scoped_mid(mid);
+    let card = GiftCardService::find_by_id(&*state.db, mid, id)
+        .await?
+        .ok_or(GiftCardError::NotFound)?;
+    let transactions = GiftCardService::transactions(&*state.db, mid, id).await?;
+
+    Ok(Json(GiftCardDetailResponse {
+        card: card.into(),
+        transactions: transactions.into_iter().map(|t| t.into()).collect(),
+    }))
+}
+
+/// Add to or take from a gift card's balance
+#[utoipa::path(
+    post,
+    path = "/api/gift-cards/{mid}/{id}/adjust",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Gift card ID")
+    ),
+    request_body = AdjustGiftCardRequest,
+    responses(
+        (status = 200, description = "Balance adjusted", body = GiftCardResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Gift card not found", body = ErrorBody),
+        (status = 422, description = "Card voided, or balance would go below zero", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "giftcards"
+)]
+pub async fn adjust(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+    ValidatedJson(req): ValidatedJson<AdjustGiftCardRequest>,
+) -> Result<Json<GiftCardResponse>, ApiError> {
+    let amount = parse_decimal("amount", &req.amount)?;
+    let card = GiftCardService::adjust(&*state.db, admin.0.scoped_mid(mid), id, amount, &req.note).await?;
+    Ok(Json(card.into()))
+}
+
+/// Void a gift card, forfeiting its balance
+#[utoipa::path(
+    post,
+    path = "/api/gift-cards/{mid}/{id}/void",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Gift card ID")
+    ),
+    request_body = VoidGiftCardRequest,
+    responses(
+        (status = 200, description = "Gift card voided", body = GiftCardResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Gift card not found", body = ErrorBody),
+        (status = 422, description = "Already voided", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "giftcards"
+)]
+pub async fn void(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+    ValidatedJson(req): ValidatedJson<VoidGiftCardRequest>,
+) -> Result<Json<GiftCardResponse>, ApiError> {
+    let card = GiftCardService::void(&*state.db, admin.0.scoped_mid(mid), id, &req.note).await?;
+    Ok(Json(card.into()))
+}
+
+/// Check a gift card's balance by its code
+#[utoipa::path(
+    post,
+    path = "/api/gift-cards/balance",
+    request_body = BalanceRequest,
+    responses(
+        (status = 200, description = "Gift card found", body = BalanceResponse),
+        (status = 404, description = "Gift card not found", body = ErrorBody),
+        (status = 422, description = "Missing code", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "giftcards"
+)]
+pub async fn balance(
+    State(state): State<AppState>,
+    ValidatedJson(req): ValidatedJson<BalanceRequest>,
+) -> Result<Json<BalanceResponse>, ApiError> {
+    GiftCardService::find_by_code(&*state.db, req.mid, &req.code)
+        .await?
+        .map(|card| Json(card.into()))
+        .ok_or_else(|| GiftCardError::NotFound.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+
+    fn card(balance: i64, voided: bool) -> GiftCard {
+        GiftCard {
+            id: 1,
+            mid: 1,
+            code: "ABCD1234EFGH5678".to_string(),
+            initial_balance: Decimal::new(5000, 2),
+            balance: Decimal::new(balance, 2),
+            cid: 0,
+            voided,
+            expires_gmt: None,
+            created_gmt: Timestamp::EPOCH,
+            modified_gmt: Timestamp::EPOCH,
+        }
+    }
+
+    #[test]
+    fn test_balance_response_masks_code() {
+        let response = BalanceResponse::from(card(1250, false));
+        assert_eq!(response.code, "****5678");
+        assert_eq!(response.balance, "12.50");
+        assert_eq!(response.status, "active");
+
+        assert_eq!(BalanceResponse::from(card(0, true)).status, "voided");
+        assert_eq!(BalanceResponse::from(card(0, false)).status, "empty");
+    }
+
+    #[test]
+    fn test_adjust_validation() {
+        let zero = AdjustGiftCardRequest { amount: "0".to_string(), note: String::new() };
+        assert!(validation::validate(&zero).is_err());
+
+        let debit = AdjustGiftCardRequest { amount: "-5.00".to_string(), note: "Refund correction".to_string() };
+        assert!(validation::validate(&debit).is_ok());
+    }
+}
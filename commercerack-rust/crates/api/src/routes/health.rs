@@ -0,0 +1,183 @@
+//! Liveness and readiness probes
+//!
+//! `/health/live` only shows the process is answering requests; restarting
+//! on its failure is the right remedy. `/health/ready` also checks the
+//! dependencies requests need and answers 503 while any of them is down,
+//! so traffic moves to other instances until it recovers.
+
+use axum::{extract::State, http::StatusCode, Json};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::future::Future;
+use std::time::{Duration, Instant};
+use crate::AppState;
+
+/// How long each dependency gets to answer
+pub const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct DependencyStatus {
+    /// `ok` or `error`
+    pub status: &'static str,
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ReadinessResponse {
+    /// `ok` when every dependency is, otherwise `unavailable`
+    pub status: &'static str,
+    /// Keyed by `database`, `cart_store` and, when a gateway is
+    /// configured, `payments`
+    pub dependencies: BTreeMap<&'static str, DependencyStatus>,
+}
+
+/// Run one check, turning errors and timeouts into a failed status
+async fn check<E: Display>(probe: impl Future<Output = Result<(), E>>) -> DependencyStatus {
+    let started = Instant::now();
+    let error = match tokio::time::timeout(CHECK_TIMEOUT, probe).await {
+        Ok(Ok(())) => None,
+        Ok(Err(e)) => Some(e.to_string()),
+        Err(_) => Some(format!("No answer within {} ms", CHECK_TIMEOUT.as_millis())),
+    };
+
+    DependencyStatus {
+        status: if error.is_none() { "ok" } else { "error" },
+        latency_ms: started.elapsed().as_millis() as u64,
+        error,
+    }
+}
+
+/// Liveness probe
+#[utoipa::path(
+    get,
+    path = "/health/live",
+    responses(
+        (status = 200, description = "Process is serving requests")
+    ),
+    tag = "health"
+)]
+pub async fn live() -> &'static str {
+    "OK"
+}
+
+/// Liveness probe under its original path; prefer `/health/live`
+#[utoipa::path(
+    get,
+    path = "/health",
+    responses(
+        (status = 200, description = "Process is serving requests")
+    ),
+    tag = "health"
+)]
+pub async fn legacy_live() -> &'static str {
+    live().await
+}
+
+/// Readiness probe: the database, cart store and payment gateway are all
+/// reachable
+#[utoipa::path(
+    get,
+    path = "/health/ready",
+    responses(
+        (status = 200, description = "Every dependency is reachable", body = ReadinessResponse),
+        (status = 503, description = "At least one dependency is down", body = ReadinessResponse)
+    ),
+    tag = "health"
+)]
+pub async fn ready(State(state): State<AppState>) -> (StatusCode, Json<ReadinessResponse>) {
+    let payments = async {
+        match &state.payments {
+            Some(gateway) => Some(check(gateway.ping()).await),
+            None => None,
+        }
+    };
+    let (database, cart_store, payments) = tokio::join!(
+        check(state.db.ping()),
+        check(state.cart_store.ping()),
+        payments,
+    );
+
+    let mut dependencies = BTreeMap::from([("database", database), ("cart_store", cart_store)]);
+    if let Some(payments) = payments {
+        dependencies.insert("payments", payments);
+    }
+
+    let ready = dependencies.values().all(|dependency| dependency.error.is_none());
+    let (code, status) = if ready {
+        (StatusCode::OK, "ok")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "unavailable")
+    };
+    (code, Json(ReadinessResponse { status, dependencies }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use commercerack_core::Money;
+    use commercerack_payment::{AuthorizeRequest, GatewayTransaction, PaymentError, PaymentGateway};
+    use sea_orm::{DatabaseBackend, MockDatabase};
+    use std::sync::Arc;
+
+    /// Gateway whose pings fail
+    struct Unreachable;
+
+    #[async_trait]
+    impl PaymentGateway for Unreachable {
+        fn name(&self) -> &'static str {
+            "unreachable"
+        }
+
+        async fn authorize(&self, _request: &AuthorizeRequest) -> Result<GatewayTransaction, PaymentError> {
+            unimplemented!()
+        }
+
+        async fn capture(&self, _id: &str, _amount: Option<Money>) -> Result<GatewayTransaction, PaymentError> {
+            unimplemented!()
+        }
+
+        async fn refund(&self, _id: &str, _amount: Option<Money>) -> Result<GatewayTransaction, PaymentError> {
+            unimplemented!()
+        }
+
+        async fn void(&self, _id: &str) -> Result<GatewayTransaction, PaymentError> {
+            unimplemented!()
+        }
+
+        async fn ping(&self) -> Result<(), PaymentError> {
+            Err(PaymentError::Gateway("connection refused".to_string()))
+        }
+    }
+
+    fn state(payments: Option<Arc<dyn PaymentGateway>>) -> AppState {
+        AppState {
+            payments,
+            ..AppState::mock(MockDatabase::new(DatabaseBackend::Postgres).into_connection())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ready_when_dependencies_answer() {
+        let (code, Json(body)) = ready(State(state(None))).await;
+        assert_eq!(code, StatusCode::OK);
+        assert_eq!(body.status, "ok");
+        assert_eq!(body.dependencies.keys().copied().collect::<Vec<_>>(), vec!["cart_store", "database"]);
+    }
+
+    #[tokio::test]
+    async fn test_unavailable_when_a_dependency_fails() {
+        let (code, Json(body)) = ready(State(state(Some(Arc::new(Unreachable))))).await;
+        assert_eq!(code, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body.status, "unavailable");
+        assert_eq!(body.dependencies["database"].status, "ok");
+        assert_eq!(body.dependencies["payments"].status, "error");
+        assert_eq!(
+            body.dependencies["payments"].error.as_deref(),
+            Some("Gateway error: connection refused")
+        );
+    }
+}
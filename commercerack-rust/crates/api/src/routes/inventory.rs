@@ -0,0 +1,325 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use commercerack_core::Timestamp;
+use commercerack_inventory::low_stock::LowStockService;
+use commercerack_inventory::{InventoryError, InventoryService};
+use entity::prelude::{InventoryAdjustment, InventoryReservation, Sku};
+use serde::{Deserialize, Serialize};
+use crate::auth::{Claims, RequireMerchantAdmin};
+use crate::error::{ApiError, ErrorBody, FieldError};
+use crate::validation::{ValidatedJson, Validate, Validator};
+use crate::AppState;
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct AdjustInventoryRequest {
+    pub mid: i32,
+    pub sku: String,
+    pub delta: i32,
+    pub note: Option<String>,
+    /// Warehouse whose count moves too; see `/api/warehouses`
+    pub warehouse_id: Option<i32>,
+}
+
+impl Validate for AdjustInventoryRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("sku", &self.sku, 45)
+            .check(self.delta != 0, "delta", "must not be zero");
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct AdjustmentResponse {
+    pub id: i32,
+    pub mid: i32,
+    pub sku: String,
+    pub delta: i32,
+    pub inv_available_after: i32,
+    pub reason: String,
+    pub note: Option<String>,
+    pub order_id: Option<i32>,
+    pub actor: Option<i32>,
+    pub warehouse_id: Option<i32>,
+    #[schema(value_type = i64)]
+    pub created_gmt: Timestamp,
+}
+
+impl From<InventoryAdjustment> for AdjustmentResponse {
+    fn from(adjustment: InventoryAdjustment) -> Self {
+        Self {
+            id: adjustment.id,
+            mid: adjustment.mid,
+            sku: adjustment.sku,
+            delta: adjustment.delta,
+            inv_available_after: adjustment.inv_available_after,
+            reason: adjustment.reason,
+            note: adjustment.note,
+            order_id: adjustment.order_id,
+            actor: adjustment.actor,
+            warehouse_id: adjustment.warehouse_id,
+            created_gmt: adjustment.created_gmt,
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct ReserveRequest {
+    pub mid: i32,
+    pub cart_id: String,
+}
+
+impl Validate for ReserveRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("cart_id", &self.cart_id, 36);
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ReservationResponse {
+    pub id: i32,
+    pub sku: String,
+    pub quantity: i32,
+    #[schema(value_type = i64)]
+    pub expires_gmt: Timestamp,
+}
+
+impl From<InventoryReservation> for ReservationResponse {
+    fn from(reservation: InventoryReservation) -> Self {
+        Self {
+            id: reservation.id,
+            sku: reservation.sku,
+            quantity: reservation.quantity,
+            expires_gmt: reservation.expires_gmt,
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct LowStockResponse {
+    pub id: i32,
+    pub pid: i32,
+    pub sku: String,
+    pub title: String,
+    pub inv_available: i32,
+    pub reorder_point: i32,
+    /// Suggested quantity to reorder
+    pub reorder_quantity: i32,
+    /// When `inventory.low_stock` was raised; null until the next scheduled check
+    #[schema(value_type = Option<i64>)]
+    pub alerted_gmt: Option<Timestamp>,
+}
+
+impl From<Sku> for LowStockResponse {
+    fn from(sku: Sku) -> Self {
+        Self {
+            id: sku.id,
+            pid: sku.pid,
+            sku: sku.sku,
+            title: sku.title,
+            inv_available: sku.inv_available,
+            reorder_point: sku.reorder_point.unwrap_or_default(),
+            reorder_quantity: sku.reorder_quantity,
+            alerted_gmt: sku.low_stock_gmt,
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct MidQuery {
+    pub mid: i32,
+}
+
+/// Manually adjust the stock of a SKU
+#[utoipa::path(
+    post,
+    path = "/api/inventory/adjust",
+    request_body = AdjustInventoryRequest,
+    responses(
+        (status = 200, description = "Stock adjusted", body = AdjustmentResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "SKU not found", body = ErrorBody),
+        (status = 409, description = "Adjustment would make stock negative", body = ErrorBody),
+        (status = 422, description = "Invalid SKU, delta or warehouse", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "inventory"
+)]
+pub async fn adjust(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    ValidatedJson(req): ValidatedJson<AdjustInventoryRequest>,
+) -> Result<Json<AdjustmentResponse>, ApiError> {
+    let mid = admin.0.scoped_mid(req.mid);
+    let actor = admin.0.sub.parse().ok();
+
+    InventoryService::adjust(&*state.db, mid, &req.sku, req.delta, req.note, actor, req.warehouse_id)
+        .await
+        .map(|adjustment| Json(adjustment.into()))
+        .map_err(|e| match e {
+            // The SKU being adjusted is the resource itself
+            InventoryError::UnknownSku(_) => ApiError::NotFound(e.to_string()),
+            InventoryError::WarehouseNotFound => {
+                ApiError::Validation(vec![FieldError::new("warehouse_id", "is not a warehouse of this merchant")])
+            }
+            e => e.into(),
+        })
+}
+
+/// Audit trail of stock movements for a SKU
+#[utoipa::path(
+    get,
+    path = "/api/inventory/{mid}/{sku}/adjustments",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("sku" = String, Path, description = "SKU code")
+    ),
+    responses(
+        (status = 200, description = "Adjustments, newest first", body = Vec<AdjustmentResponse>),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "inventory"
+)]
+pub async fn history(
+    State(state): State<AppState>,
+    _admin: RequireMerchantAdmin,
+    Path((mid, sku)): Path<(i32, String)>,
+) -> Result<Json<Vec<AdjustmentResponse>>, ApiError> {
+    InventoryService::history(&state.db, mid, &sku)
+        .await
+        .map(|adjustments| Json(adjustments.into_iter().map(|a| a.into()).collect()))
+        .map_err(ApiError::from)
+}
+
+/// SKUs at or below their reorder point, emptiest first
+#[utoipa::path(
+    get,
+    path = "/api/inventory/low-stock",
+    params(MidQuery),
+    responses(
+        (status = 200, description = "Low stock SKUs", body = Vec<LowStockResponse>),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "inventory"
+)]
+pub async fn low_stock(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Query(query): Query<MidQuery>,
+) -> Result<Json<Vec<LowStockResponse>>, ApiError> {
+    LowStockService::list(&state.db, admin.0.scoped_mid(query.mid))
+        .await
+        .map(|skus| Json(skus.into_iter().map(|s| s.into()).collect()))
+        .map_err(ApiError::from)
+}
+
+/// Hold stock for a cart's items while checkout completes
+#[utoipa::path(
+    post,
+    path = "/api/inventory/reservations",
+    request_body = ReserveRequest,
+    responses(
+        (status = 201, description = "Stock reserved", body = Vec<ReservationResponse>),
+        (status = 400, description = "Cart contains an unknown SKU", body = ErrorBody),
+        (status = 404, description = "Cart not found", body = ErrorBody),
+        (status = 409, description = "Not enough stock", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "inventory"
+)]
+pub async fn reserve(
+    State(state): State<AppState>,
+    claims: Option<Claims>,
+    ValidatedJson(req): ValidatedJson<ReserveRequest>,
+) -> Result<(StatusCode, Json<Vec<ReservationResponse>>), ApiError> {
+    let mid = claims.as_ref().map_or(req.mid, |claims| claims.scoped_mid(req.mid));
+
+    let cart = state
+        .cart_store
+        .get_cart(&req.cart_id)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Cart"))?;
+    let lines: Vec<(&str, i32)> = cart.items.iter().map(|item| (item.sku.as_str(), item.quantity)).collect();
+
+    InventoryService::reserve(&*state.db, mid, &cart.cart_id, &lines)
+        .await
+        .map(|reservations| {
+            (
+                StatusCode::CREATED,
+                Json(reservations.into_iter().map(|r| r.into()).collect()),
+            )
+        })
+        .map_err(ApiError::from)
+}
+
+/// Release a cart's reservations early. Staff only: a shopper's holds
+/// lapse on their own after `RESERVATION_TTL_SECS`.
+#[utoipa::path(
+    delete,
+    path = "/api/inventory/reservations/{cart_id}",
+    params(
+        ("cart_id" = String, Path, description = "Cart ID"),
+        MidQuery
+    ),
+    responses(
+        (status = 204, description = "Reservations released"),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "inventory"
+)]
+pub async fn release(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path(cart_id): Path<String>,
+    Query(query): Query<MidQuery>,
+) -> Result<StatusCode, ApiError> {
+    InventoryService::release(&*state.db, admin.0.scoped_mid(query.mid), &cart_id)
+        .await
+        .map(|_| StatusCode::NO_CONTENT)
+        .map_err(ApiError::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::Role;
+    use sea_orm::{DatabaseBackend, MockDatabase};
+
+    #[tokio::test]
+    async fn test_adjust_unknown_sku() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([Vec::<entity::prelude::Sku>::new()])
+            .into_connection();
+        let admin = RequireMerchantAdmin::new(Claims::new(1, 1).with_role(Role::MerchantAdmin));
+        let req = AdjustInventoryRequest {
+            mid: 1,
+            sku: "MISSING".to_string(),
+            delta: 5,
+            note: None,
+            warehouse_id: None,
+        };
+
+        let result = adjust(State(AppState::mock(db)), admin, ValidatedJson(req)).await;
+        assert_eq!(result.err().map(|e| e.status()), Some(StatusCode::NOT_FOUND));
+    }
+
+    #[tokio::test]
+    async fn test_reserve_missing_cart() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+        let req = ReserveRequest {
+            mid: 1,
+            cart_id: "nope".to_string(),
+        };
+
+        let result = reserve(State(AppState::mock(db)), None, ValidatedJson(req)).await;
+        assert_eq!(result.err().map(|e| e.status()), Some(StatusCode::NOT_FOUND));
+    }
+}
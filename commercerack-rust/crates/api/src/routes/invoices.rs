@@ -0,0 +1,296 @@
+use axum::{
+    extract::{Path, State},
+    http::header,
+    response::{IntoResponse, Response},
+    Json,
+};
+use commercerack_core::Timestamp;
+use commercerack_order::invoices::{Branding, InvoiceKind, InvoiceService};
+use entity::prelude::Invoice;
+use serde::{Deserialize, Serialize};
+use crate::auth::{Claims, RequireMerchantAdmin};
+use crate::error::{ApiError, ErrorBody};
+use crate::routes::payments::ensure_owner;
+use crate::validation::{ValidatedJson, Validate, Validator};
+use crate::AppState;
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct BrandingRequest {
+    /// Merchant name printed above the address
+    #[serde(default)]
+    pub name: String,
+    /// Address, one line per line
+    #[serde(default)]
+    pub address: String,
+    #[serde(default)]
+    pub tax_id: String,
+    /// Closing line of every page, e.g. payment terms
+    #[serde(default)]
+    pub footer: String,
+    /// `#RRGGBB` of the title band
+    #[serde(default = "default_accent_color")]
+    pub accent_color: String,
+}
+
+fn default_accent_color() -> String {
+    Branding::default().accent_color
+}
+
+impl BrandingRequest {
+    fn branding(self) -> Branding {
+        Branding {
+            name: self.name,
+            address: self.address,
+            tax_id: self.tax_id,
+            footer: self.footer,
+            accent_color: self.accent_color,
+        }
+    }
+}
+
+impl Validate for BrandingRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.max_len("name", &self.name, 120)
+            .max_len("address", &self.address, 500)
+            .max_len("tax_id", &self.tax_id, 40)
+            .max_len("footer", &self.footer, 255);
+        let accent = self.accent_color.strip_prefix('#').unwrap_or_default();
+        v.check(
+            accent.len() == 6 && accent.chars().all(|c| c.is_ascii_hexdigit()),
+            "accent_color",
+            "must be #RRGGBB",
+        );
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct BrandingResponse {
+    pub mid: i32,
+    pub name: String,
+    pub address: String,
+    pub tax_id: String,
+    pub footer: String,
+    pub accent_color: String,
+}
+
+impl BrandingResponse {
+    fn new(mid: i32, branding: Branding) -> Self {
+        Self {
+            mid,
+            name: branding.name,
+            address: branding.address,
+            tax_id: branding.tax_id,
+            footer: branding.footer,
+            accent_color: branding.accent_color,
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct InvoiceResponse {
+    pub id: i32,
+    pub order_id: i32,
+    /// `invoice` or `credit_memo`
+    pub kind: String,
+    pub number: String,
+    /// Order total for an invoice, amount refunded for a credit memo
+    pub amount: String,
+    #[schema(value_type = i64)]
+    pub created_gmt: Timestamp,
+    /// Where to fetch the PDF
+    pub path: String,
+}
+
+impl From<Invoice> for InvoiceResponse {
+    fn from(invoice: Invoice) -> Self {
+        Self {
+            path: format!("/api/orders/{}/{}/credit-memos/{}", invoice.mid, invoice.order_id, invoice.id),
+            id: invoice.id,
+            order_id: invoice.order_id,
+            kind: invoice.kind,
+            number: invoice.number,
+            amount: invoice.amount.to_string(),
+            created_gmt: invoice.created_gmt,
+        }
+    }
+}
+
+/// The document as an inline PDF named after its number
+fn pdf_response(invoice: Invoice) -> Response {
+    let disposition = format!("inline; filename=\"{}.pdf\"", invoice.number);
+    (
+        [
+            (header::CONTENT_TYPE, "application/pdf".to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        invoice.pdf,
+    )
+        .into_response()
+}
+
+/// An order's invoice as a PDF, issued on first request
+#[utoipa::path(
+    get,
+    path = "/api/orders/{mid}/{id}/invoice.pdf",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Order ID")
+    ),
+    responses(
+        (status = 200, description = "Invoice PDF", content_type = "application/pdf"),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 404, description = "Order not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "orders"
+)]
+pub async fn invoice_pdf(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Response, ApiError> {
+    let mid = claims.scoped_mid(mid);
+    ensure_owner(&state, &claims, mid, id).await?;
+
+    let invoice = InvoiceService::invoice(&*state.db, mid, id).await?;
+    Ok(pdf_response(invoice))
+}
+
+/// Credit memos issued for an order's refunds
+#[utoipa::path(
+    get,
+    path = "/api/orders/{mid}/{id}/credit-memos",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Order ID")
+    ),
+    responses(
+        (status = 200, description = "Credit memos, oldest first", body = Vec<InvoiceResponse>),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 404, description = "Order not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "orders"
+)]
+pub async fn credit_memos(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Json<Vec<InvoiceResponse>>, ApiError> {
+    let mid = claims.scoped_mid(mid);
+    ensure_owner(&state, &claims, mid, id).await?;
+
+    let memos = InvoiceService::credit_memos(&*state.db, mid, id).await?;
+    Ok(Json(memos.into_iter().map(|memo| memo.into()).collect()))
+}
+
+/// A credit memo as a PDF
+#[utoipa::path(
+    get,
+    path = "/api/orders/{mid}/{id}/credit-memos/{memo_id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Order ID"),
+        ("memo_id" = i32, Path, description = "Credit memo ID")
+    ),
+    responses(
+        (status = 200, description = "Credit memo PDF", content_type = "application/pdf"),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 404, description = "Order or credit memo not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "orders"
+)]
+pub async fn credit_memo_pdf(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((mid, id, memo_id)): Path<(i32, i32, i32)>,
+) -> Result<Response, ApiError> {
+    let mid = claims.scoped_mid(mid);
+    ensure_owner(&state, &claims, mid, id).await?;
+
+    InvoiceService::find(&*state.db, mid, id, memo_id)
+        .await?
+        .filter(|memo| memo.kind == InvoiceKind::CreditMemo.as_str())
+        .map(pdf_response)
+        .ok_or_else(|| ApiError::not_found("Credit memo"))
+}
+
+/// How a merchant's invoices look
+#[utoipa::path(
+    get,
+    path = "/api/invoice-branding/{mid}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID")
+    ),
+    responses(
+        (status = 200, description = "Invoice branding; the defaults until set", body = BrandingResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "orders"
+)]
+pub async fn get_branding(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path(mid): Path<i32>,
+) -> Result<Json<BrandingResponse>, ApiError> {
+    let mid = admin.0.scoped_mid(mid);
+    let branding = InvoiceService::branding(&*state.db, mid).await?;
+    Ok(Json(BrandingResponse::new(mid, branding)))
+}
+
+/// Set how a merchant's invoices look; invoices already issued are unchanged
+#[utoipa::path(
+    put,
+    path = "/api/invoice-branding/{mid}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID")
+    ),
+    request_body = BrandingRequest,
+    responses(
+        (status = 200, description = "Branding saved", body = BrandingResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 422, description = "Field too long or invalid accent color", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "orders"
+)]
+pub async fn set_branding(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path(mid): Path<i32>,
+    ValidatedJson(req): ValidatedJson<BrandingRequest>,
+) -> Result<Json<BrandingResponse>, ApiError> {
+    let mid = admin.0.scoped_mid(mid);
+    let branding = InvoiceService::set_branding(&*state.db, mid, req.branding()).await?;
+    Ok(Json(BrandingResponse::new(mid, branding)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_branding() {
+        let request = |accent_color: &str, name: &str| BrandingRequest {
+            name: name.to_string(),
+            address: "1 Main St\nSpringfield".to_string(),
+            tax_id: String::new(),
+            footer: String::new(),
+            accent_color: accent_color.to_string(),
+        };
+        let invalid_fields = |req: &BrandingRequest| match crate::validation::validate(req) {
+            Ok(()) => Vec::new(),
+            Err(ApiError::Validation(errors)) => errors.into_iter().map(|e| e.field).collect(),
+            Err(e) => panic!("unexpected error {e:?}"),
+        };
+
+        assert!(invalid_fields(&request("#1A2b3C", "Acme")).is_empty());
+        assert_eq!(invalid_fields(&request("1a2b3c", "Acme")), vec!["accent_color"]);
+        assert_eq!(invalid_fields(&request("#1a2b3", "Acme")), vec!["accent_color"]);
+        assert_eq!(invalid_fields(&request("#333333", &"x".repeat(121))), vec!["name"]);
+    }
+}
@@ -0,0 +1,288 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use commercerack_core::Timestamp;
+use commercerack_product::media::{MediaError, MediaKind, MediaService, NewMedia};
+use ::entity::prelude::ProductMediaItem;
+use serde::{Deserialize, Serialize};
+use crate::auth::RequireMerchantAdmin;
+use crate::error::{ApiError, ErrorBody};
+use crate::validation::{ValidatedJson, Validate, Validator};
+use crate::AppState;
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct AddMediaRequest {
+    /// Variant the media shows; omit for media of the whole product
+    pub sku_id: Option<i32>,
+    /// `image` (default) or `video`
+    #[serde(default = "default_kind")]
+    pub kind: String,
+    pub url: String,
+    #[serde(default)]
+    pub alt_text: String,
+    /// Make this the product's primary media
+    #[serde(default)]
+    pub is_primary: bool,
+}
+
+fn default_kind() -> String {
+    MediaKind::Image.to_string()
+}
+
+impl Validate for AddMediaRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.check(self.kind.parse::<MediaKind>().is_ok(), "kind", "must be image or video")
+            .required("url", &self.url, 500)
+            .check(
+                self.url.starts_with("https://") || self.url.starts_with("http://"),
+                "url",
+                "must be an http(s) URL",
+            )
+            .max_len("alt_text", &self.alt_text, 255);
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct ReorderMediaRequest {
+    /// Every media ID of the product, in the new display order
+    pub ids: Vec<i32>,
+}
+
+impl Validate for ReorderMediaRequest {
+    fn validate(&self, _v: &mut Validator) {}
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct MediaResponse {
+    pub id: i32,
+    pub sku_id: Option<i32>,
+    pub kind: String,
+    pub url: String,
+    pub alt_text: String,
+    pub position: i32,
+    pub is_primary: bool,
+    #[schema(value_type = i64)]
+    pub created_gmt: Timestamp,
+}
+
+impl From<ProductMediaItem> for MediaResponse {
+    fn from(item: ProductMediaItem) -> Self {
+        Self {
+            id: item.id,
+            sku_id: item.sku_id,
+            kind: item.kind,
+            url: item.url,
+            alt_text: item.alt_text,
+            position: item.position,
+            is_primary: item.is_primary,
+            created_gmt: item.created_gmt,
+        }
+    }
+}
+
+/// Media of a product in display order
+#[utoipa::path(
+    get,
+    path = "/api/products/{mid}/{id}/media",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Product ID")
+    ),
+    responses(
+        (status = 200, description = "Product media", body = Vec<MediaResponse>),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "products"
+)]
+pub async fn list(
+    State(state): State<AppState>,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Json<Vec<MediaResponse>>, ApiError> {
+    MediaService::list(&state.db, mid, id)
+        .await
+        .map(|media| Json(media.into_iter().map(|m| m.into()).collect()))
+        .map_err(ApiError::from)
+}
+
+/// Attach an image or video to a product
+#[utoipa::path(
+    post,
+    path = "/api/products/{mid}/{id}/media",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Product ID")
+    ),
+    request_body = AddMediaRequest,
+    responses(
+        (status = 201, description = "Media attached", body = MediaResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Product not found", body = ErrorBody),
+        (status = 422, description = "Invalid URL or kind, or SKU of another product", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "products"
+)]
+pub async fn add(
+    State(state): State<AppState>,
+    _admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+    ValidatedJson(req): ValidatedJson<AddMediaRequest>,
+) -> Result<(StatusCode, Json<MediaResponse>), ApiError> {
+    let media = NewMedia {
+        sku_id: req.sku_id,
+        kind: req.kind.parse().map_err(ApiError::BadRequest)?,
+        url: req.url,
+        alt_text: req.alt_text,
+        is_primary: req.is_primary,
+    };
+
+    MediaService::add(&state.db, mid, id, media)
+        .await
+        .map(|item| (StatusCode::CREATED, Json(item.into())))
+        .map_err(ApiError::from)
+}
+
+/// Set the display order of a product's media
+#[utoipa::path(
+    put,
+    path = "/api/products/{mid}/{id}/media/order",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Product ID")
+    ),
+    request_body = ReorderMediaRequest,
+    responses(
+        (status = 200, description = "Media in the new order", body = Vec<MediaResponse>),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 422, description = "IDs do not match the product's media", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "products"
+)]
+pub async fn reorder(
+    State(state): State<AppState>,
+    _admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+    ValidatedJson(req): ValidatedJson<ReorderMediaRequest>,
+) -> Result<Json<Vec<MediaResponse>>, ApiError> {
+    MediaService::reorder(&state.db, mid, id, &req.ids)
+        .await
+        .map(|media| Json(media.into_iter().map(|m| m.into()).collect()))
+        .map_err(ApiError::from)
+}
+
+/// Make a media item the product's primary
+#[utoipa::path(
+    post,
+    path = "/api/products/{mid}/{id}/media/{media_id}/primary",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Product ID"),
+        ("media_id" = i32, Path, description = "Media ID")
+    ),
+    responses(
+        (status = 200, description = "Primary media updated", body = MediaResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Media not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "products"
+)]
+pub async fn set_primary(
+    State(state): State<AppState>,
+    _admin: RequireMerchantAdmin,
+    Path((mid, id, media_id)): Path<(i32, i32, i32)>,
+) -> Result<Json<MediaResponse>, ApiError> {
+    MediaService::set_primary(&state.db, mid, id, media_id)
+        .await
+        .map(|item| Json(item.into()))
+        .map_err(ApiError::from)
+}
+
+/// Remove a media item from a product
+#[utoipa::path(
+    delete,
+    path = "/api/products/{mid}/{id}/media/{media_id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Product ID"),
+        ("media_id" = i32, Path, description = "Media ID")
+    ),
+    responses(
+        (status = 204, description = "Media removed"),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Media not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "products"
+)]
+pub async fn delete(
+    State(state): State<AppState>,
+    _admin: RequireMerchantAdmin,
+    Path((mid, id, media_id)): Path<(i32, i32, i32)>,
+) -> Result<StatusCode, ApiError> {
+    if MediaService::delete(&state.db, mid, id, media_id).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(MediaError::NotFound.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::{Claims, Role};
+    use sea_orm::{DatabaseBackend, MockDatabase};
+
+    fn media(id: i32, position: i32) -> ProductMediaItem {
+        ProductMediaItem {
+            id,
+            mid: 1,
+            product_id: 7,
+            sku_id: None,
+            kind: "image".to_string(),
+            url: format!("https://cdn.example.com/{}.jpg", id),
+            alt_text: String::new(),
+            position,
+            is_primary: id == 1,
+            created_gmt: Timestamp::EPOCH,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reorder_requires_every_media_id() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![media(1, 0), media(2, 1)]])
+            .into_connection();
+        let state = AppState::mock(db);
+        let admin = RequireMerchantAdmin::new(Claims::new(1, 1).with_role(Role::MerchantAdmin));
+
+        let req = ReorderMediaRequest { ids: vec![2] };
+        let result = reorder(State(state), admin, Path((1, 7)), ValidatedJson(req)).await;
+        assert_eq!(result.err().map(|e| e.status()), Some(StatusCode::UNPROCESSABLE_ENTITY));
+    }
+
+    #[test]
+    fn test_add_media_validation() {
+        let req = AddMediaRequest {
+            sku_id: None,
+            kind: "gif".to_string(),
+            url: "ftp://cdn.example.com/1.gif".to_string(),
+            alt_text: String::new(),
+            is_primary: false,
+        };
+        match crate::validation::validate(&req) {
+            Err(ApiError::Validation(errors)) => {
+                let fields: Vec<_> = errors.iter().map(|e| e.field.as_str()).collect();
+                assert_eq!(fields, vec!["kind", "url"]);
+            }
+            other => panic!("unexpected result {:?}", other.err().map(|e| e.status())),
+        }
+    }
+}
@@ -0,0 +1,476 @@
+//! Merchants and store lookup
+//!
+//! Platform admins onboard, list and close merchants. A merchant admin may
+//! read and rename their own merchant and change its settings; only a
+//! platform admin changes its status. `GET /api/stores/{sdomain}` is public
+//! so storefronts can find the merchant they serve.
+//!
+//! A merchant's typed settings (currency, timezone, order number format,
+//! email sender, order archiving and feature flags) are read and changed
+//! under `/api/merchants/{mid}/settings`.
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use commercerack_core::Timestamp;
+use commercerack_merchant::settings::{Settings, SettingsService};
+use commercerack_merchant::{normalize_domain, MerchantError, MerchantService, MerchantStatus, MerchantUpdate, Onboarding};
+use ::entity::prelude::Merchant;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use crate::auth::{RequireMerchantAdmin, RequirePlatformAdmin, Role};
+use crate::error::{ApiError, ErrorBody};
+use crate::routes::tax::{TaxRateRequest, TaxRateResponse};
+use crate::validation::{ValidatedJson, Validate, Validator};
+use crate::AppState;
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct OnboardMerchantRequest {
+    pub name: String,
+    /// Storefront domain, e.g. `shop.example.com`
+    pub sdomain: String,
+    /// Rates to start the merchant's tax table with
+    #[serde(default)]
+    pub tax_rates: Vec<TaxRateRequest>,
+}
+
+impl Validate for OnboardMerchantRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("name", &self.name, 100);
+        v.check(normalize_domain(&self.sdomain).is_ok(), "sdomain", "must be a domain name");
+        for rate in &self.tax_rates {
+            rate.validate(v);
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct UpdateMerchantRequest {
+    pub name: Option<String>,
+    pub sdomain: Option<String>,
+    /// Replaces the settings as a whole
+    #[schema(value_type = Option<Object>)]
+    pub settings: Option<serde_json::Value>,
+    /// `active`, `suspended` or `closed`; platform admins only
+    pub status: Option<String>,
+}
+
+impl Validate for UpdateMerchantRequest {
+    fn validate(&self, v: &mut Validator) {
+        if let Some(name) = &self.name {
+            v.required("name", name, 100);
+        }
+        if let Some(sdomain) = &self.sdomain {
+            v.check(normalize_domain(sdomain).is_ok(), "sdomain", "must be a domain name");
+        }
+        if let Some(settings) = &self.settings {
+            v.check(settings.is_object(), "settings", "must be an object");
+        }
+        if let Some(status) = &self.status {
+            if let Err(e) = status.parse::<MerchantStatus>() {
+                v.error("status", e);
+            }
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct MerchantResponse {
+    pub mid: i32,
+    pub name: String,
+    pub sdomain: String,
+    /// Order pools, tax calculation and the like
+    #[schema(value_type = Object)]
+    pub settings: serde_json::Value,
+    pub status: String,
+    #[schema(value_type = i64)]
+    pub created_gmt: Timestamp,
+    #[schema(value_type = i64)]
+    pub modified_gmt: Timestamp,
+}
+
+impl From<Merchant> for MerchantResponse {
+    fn from(merchant: Merchant) -> Self {
+        Self {
+            settings: serde_json::from_str(&merchant.settings).unwrap_or_default(),
+            mid: merchant.mid,
+            name: merchant.name,
+            sdomain: merchant.sdomain,
+            status: merchant.status,
+            created_gmt: merchant.created_gmt,
+            modified_gmt: merchant.modified_gmt,
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct OnboardedResponse {
+    pub merchant: MerchantResponse,
+    pub tax_rates: Vec<TaxRateResponse>,
+}
+
+/// What a storefront needs to know about the store it serves
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct StoreResponse {
+    pub mid: i32,
+    pub name: String,
+    pub sdomain: String,
+}
+
+impl From<Merchant> for StoreResponse {
+    fn from(merchant: Merchant) -> Self {
+        Self {
+            mid: merchant.mid,
+            name: merchant.name,
+            sdomain: merchant.sdomain,
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct UpdateSettingsRequest {
+    /// Settings to change by key, e.g. `{"timezone": "Europe/Berlin",
+    /// "features.gift_wrap": true}`; others keep their value
+    #[schema(value_type = Object)]
+    pub values: BTreeMap<String, serde_json::Value>,
+}
+
+impl Validate for UpdateSettingsRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.check(!self.values.is_empty(), "values", "must set at least one setting");
+        let mut check = Settings::default();
+        for (key, value) in &self.values {
+            match check.apply(key, value) {
+                Ok(()) => {}
+                Err(MerchantError::InvalidSetting { reason, .. }) => v.error(key, reason),
+                Err(_) => v.error(key, "is not a setting"),
+            }
+        }
+    }
+}
+
+/// Every setting, defaults included
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct SettingsResponse {
+    pub currency: String,
+    /// IANA timezone the merchant's days and report periods follow
+    pub timezone: String,
+    /// e.g. `{date}-{random}`
+    pub order_number_format: String,
+    /// `null` when emails go out from the platform's address
+    pub email_sender: Option<String>,
+    /// Days a done order stays in the working pools before it is archived
+    pub archive_after_days: u32,
+    /// Days after a declined payment it is charged again
+    pub dunning_retry_days: Vec<u32>,
+    /// Feature flags the merchant set, by name
+    pub features: BTreeMap<String, bool>,
+}
+
+impl From<&Settings> for SettingsResponse {
+    fn from(settings: &Settings) -> Self {
+        Self {
+            currency: settings.currency.to_string(),
+            timezone: settings.timezone.name().to_string(),
+            order_number_format: settings.order_number_format.clone(),
+            email_sender: settings.email_sender.clone(),
+            archive_after_days: settings.archive_after_days,
+            dunning_retry_days: settings.dunning_retry_days.clone(),
+            features: settings.features.clone(),
+        }
+    }
+}
+
+/// Onboard a merchant with the default pools and tax settings
+#[utoipa::path(
+    post,
+    path = "/api/merchants/onboard",
+    request_body = OnboardMerchantRequest,
+    responses(
+        (status = 201, description = "Merchant onboarded", body = OnboardedResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Platform admin role required", body = ErrorBody),
+        (status = 409, description = "Domain taken by another store", body = ErrorBody),
+        (status = 422, description = "Invalid name, domain or tax rate", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "merchants"
+)]
+pub async fn onboard(
+    State(state): State<AppState>,
+    _admin: RequirePlatformAdmin,
+    ValidatedJson(req): ValidatedJson<OnboardMerchantRequest>,
+) -> Result<(StatusCode, Json<OnboardedResponse>), ApiError> {
+    let onboarding = Onboarding {
+        name: req.name,
+        sdomain: req.sdomain,
+        tax_rates: req.tax_rates.into_iter().map(TaxRateRequest::into_input).collect::<Result<_, _>>()?,
+    };
+    let onboarded = MerchantService::onboard(&*state.db, onboarding).await?;
+    Ok((
+        StatusCode::CREATED,
+        Json(OnboardedResponse {
+            merchant: onboarded.merchant.into(),
+            tax_rates: onboarded.tax_rates.into_iter().map(|r| r.into()).collect(),
+        }),
+    ))
+}
+
+/// List every merchant
+#[utoipa::path(
+    get,
+    path = "/api/merchants",
+    responses(
+        (status = 200, description = "Merchants, oldest first", body = Vec<MerchantResponse>),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Platform admin role required", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "merchants"
+)]
+pub async fn list(
+    State(state): State<AppState>,
+    _admin: RequirePlatformAdmin,
+) -> Result<Json<Vec<MerchantResponse>>, ApiError> {
+    let merchants = MerchantService::list(&*state.db).await?;
+    Ok(Json(merchants.into_iter().map(|m| m.into()).collect()))
+}
+
+/// Get a merchant
+#[utoipa::path(
+    get,
+    path = "/api/merchants/{mid}",
+    params(("mid" = i32, Path, description = "Merchant ID")),
+    responses(
+        (status = 200, description = "Merchant found", body = MerchantResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Merchant not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "merchants"
+)]
+pub async fn get(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path(mid): Path<i32>,
+) -> Result<Json<MerchantResponse>, ApiError> {
+    MerchantService::find(&*state.db, admin.0.scoped_mid(mid))
+        .await?
+        .map(|merchant| Json(merchant.into()))
+        .ok_or_else(|| ApiError::not_found("Merchant"))
+}
+
+/// Rename a merchant, move it to another domain, or change its settings
+/// or status
+#[utoipa::path(
+    put,
+    path = "/api/merchants/{mid}",
+    params(("mid" = i32, Path, description = "Merchant ID")),
+    request_body = UpdateMerchantRequest,
+    responses(
+        (status = 200, description = "Merchant updated", body = MerchantResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required; platform admin to change the status", body = ErrorBody),
+        (status = 404, description = "Merchant not found", body = ErrorBody),
+        (status = 409, description = "Domain taken by another store", body = ErrorBody),
+        (status = 422, description = "Invalid name, domain, settings or status", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "merchants"
+)]
+pub async fn update(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path(mid): Path<i32>,
+    ValidatedJson(req): ValidatedJson<UpdateMerchantRequest>,
+) -> Result<Json<MerchantResponse>, ApiError> {
+    if req.status.is_some() && !admin.0.has_role(Role::PlatformAdmin) {
+        return Err(ApiError::Forbidden("Only platform admins change a merchant's status".to_string()));
+    }
+
+    let update = MerchantUpdate {
+        name: req.name,
+        sdomain: req.sdomain,
+        settings: req.settings,
+        status: req.status.as_deref().map(str::parse).transpose().map_err(ApiError::BadRequest)?,
+    };
+    let merchant = MerchantService::update(&*state.db, admin.0.scoped_mid(mid), update).await?;
+    Ok(Json(merchant.into()))
+}
+
+/// Close a merchant; its data is kept
+#[utoipa::path(
+    delete,
+    path = "/api/merchants/{mid}",
+    params(("mid" = i32, Path, description = "Merchant ID")),
+    responses(
+        (status = 200, description = "Merchant closed", body = MerchantResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Platform admin role required", body = ErrorBody),
+        (status = 404, description = "Merchant not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "merchants"
+)]
+pub async fn close(
+    State(state): State<AppState>,
+    _admin: RequirePlatformAdmin,
+    Path(mid): Path<i32>,
+) -> Result<Json<MerchantResponse>, ApiError> {
+    let merchant = MerchantService::close(&*state.db, mid).await?;
+    Ok(Json(merchant.into()))
+}
+
+/// Get a merchant's settings
+#[utoipa::path(
+    get,
+    path = "/api/merchants/{mid}/settings",
+    params(("mid" = i32, Path, description = "Merchant ID")),
+    responses(
+        (status = 200, description = "Settings, defaults included", body = SettingsResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "merchants"
+)]
+pub async fn get_settings(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path(mid): Path<i32>,
+) -> Result<Json<SettingsResponse>, ApiError> {
+    let settings = SettingsService::get(&*state.db, admin.0.scoped_mid(mid)).await?;
+    Ok(Json(settings.as_ref().into()))
+}
+
+/// Change some of a merchant's settings
+#[utoipa::path(
+    put,
+    path = "/api/merchants/{mid}/settings",
+    params(("mid" = i32, Path, description = "Merchant ID")),
+    request_body = UpdateSettingsRequest,
+    responses(
+        (status = 200, description = "Settings updated", body = SettingsResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 422, description = "Unknown setting or invalid value", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "merchants"
+)]
+pub async fn update_settings(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path(mid): Path<i32>,
+    ValidatedJson(req): ValidatedJson<UpdateSettingsRequest>,
+) -> Result<Json<SettingsResponse>, ApiError> {
+    let settings = SettingsService::set(&*state.db, admin.0.scoped_mid(mid), &req.values).await?;
+    Ok(Json(settings.as_ref().into()))
+}
+
+/// Put one of a merchant's settings back to its default
+#[utoipa::path(
+    delete,
+    path = "/api/merchants/{mid}/settings/{key}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("key" = String, Path, description = "Setting key, e.g. `timezone` or `features.gift_wrap`")
+    ),
+    responses(
+        (status = 200, description = "Setting reset", body = SettingsResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "merchants"
+)]
+pub async fn reset_setting(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, key)): Path<(i32, String)>,
+) -> Result<Json<SettingsResponse>, ApiError> {
+    let settings = SettingsService::unset(&*state.db, admin.0.scoped_mid(mid), &key).await?;
+    Ok(Json(settings.as_ref().into()))
+}
+
+/// Find the store served on a domain
+#[utoipa::path(
+    get,
+    path = "/api/stores/{sdomain}",
+    params(("sdomain" = String, Path, description = "Storefront domain")),
+    responses(
+        (status = 200, description = "Store found", body = StoreResponse),
+        (status = 404, description = "No active store on the domain", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "merchants"
+)]
+pub async fn store(
+    State(state): State<AppState>,
+    Path(sdomain): Path<String>,
+) -> Result<Json<StoreResponse>, ApiError> {
+    MerchantService::find_store(&*state.db, &sdomain)
+        .await?
+        .map(|merchant| Json(merchant.into()))
+        .ok_or_else(|| ApiError::not_found("Store"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn errors(req: &impl Validate) -> Vec<String> {
+        match crate::validation::validate(req) {
+            Ok(()) => Vec::new(),
+            Err(ApiError::Validation(errors)) => errors.into_iter().map(|e| e.field).collect(),
+            Err(e) => panic!("unexpected error {e:?}"),
+        }
+    }
+
+    #[test]
+    fn test_validate_merchant() {
+        let onboard: OnboardMerchantRequest = serde_json::from_value(serde_json::json!({
+            "name": "Widgets",
+            "sdomain": "shop.example.com",
+            "tax_rates": [{ "name": "WA state", "country": "US", "state": "WA", "rate": "6.5" }]
+        }))
+        .unwrap();
+        assert!(errors(&onboard).is_empty());
+
+        let invalid = OnboardMerchantRequest {
+            name: String::new(),
+            sdomain: "https://shop.example.com".to_string(),
+            tax_rates: Vec::new(),
+        };
+        assert_eq!(errors(&invalid), vec!["name", "sdomain"]);
+
+        let update = UpdateMerchantRequest {
+            name: None,
+            sdomain: None,
+            settings: Some(serde_json::json!([])),
+            status: Some("deleted".to_string()),
+        };
+        assert_eq!(errors(&update), vec!["settings", "status"]);
+    }
+
+    #[test]
+    fn test_validate_settings() {
+        let update: UpdateSettingsRequest = serde_json::from_value(serde_json::json!({
+            "values": { "timezone": "Europe/Berlin", "features.gift_wrap": true, "order_number_format": "W{yyyy}-{random}" }
+        }))
+        .unwrap();
+        assert!(errors(&update).is_empty());
+
+        let invalid: UpdateSettingsRequest = serde_json::from_value(serde_json::json!({
+            "values": { "currency": "dollars", "colour": "blue", "features.gift_wrap": "yes" }
+        }))
+        .unwrap();
+        assert_eq!(errors(&invalid), vec!["colour", "currency", "features.gift_wrap"]);
+
+        let empty = UpdateSettingsRequest { values: BTreeMap::new() };
+        assert_eq!(errors(&empty), vec!["values"]);
+    }
+}
@@ -1,4 +1,63 @@
+pub mod audit;
+pub mod availability;
+pub mod auth;
+pub mod batch;
+pub mod bulk_price;
+pub mod health;
 pub mod customers;
+pub mod delivery_estimates;
+pub mod digital;
+pub mod dunning;
+pub mod addresses;
+pub mod archived_orders;
 pub mod products;
+pub mod catalog_changes;
+pub mod categories;
+pub mod channels;
+pub mod companies;
+pub mod media;
+pub mod merchants;
+pub mod notes;
 pub mod orders;
+pub mod order_stream;
+pub mod skus;
+pub mod pricing;
 pub mod cart;
+pub mod cart_sync;
+pub mod abandoned_carts;
+pub mod coupons;
+pub mod giftcards;
+pub mod inventory;
+pub mod invoices;
+pub mod oauth;
+pub mod payments;
+pub mod privacy;
+pub mod reports;
+pub mod refunds;
+pub mod returns;
+pub mod saved_carts;
+pub mod segments;
+pub mod sessions;
+pub mod shipping;
+pub mod stats;
+pub mod store_locations;
+pub mod tags;
+pub mod tax;
+pub mod transfers;
+pub mod purchase_orders;
+pub mod recommendations;
+pub mod two_factor;
+pub mod webhooks;
+pub mod warehouses;
+pub mod wishlists;
+pub mod zones;
+
+use rust_decimal::Decimal;
+use crate::error::ApiError;
+
+/// Parse a decimal amount sent as a string
+pub(crate) fn parse_decimal(field: &str, value: &str) -> Result<Decimal, ApiError> {
+    value
+        .parse::<Decimal>()
+        .map_err(|_| ApiError::BadRequest(format!("Invalid {} {:?}", field, value)))
+}
@@ -0,0 +1,134 @@
+//! Customer note routes
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use commercerack_audit::Change;
+use commercerack_core::Timestamp;
+use commercerack_customer::notes::NoteService;
+use ::entity::prelude::CustomerNote;
+use serde::{Deserialize, Serialize};
+use crate::auth::RequireMerchantAdmin;
+use crate::error::{ApiError, ErrorBody};
+use crate::routes::audit;
+use crate::validation::{ValidatedJson, Validate, Validator};
+use crate::AppState;
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct NoteRequest {
+    pub note: String,
+}
+
+impl Validate for NoteRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("note", &self.note, 2000);
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct NoteResponse {
+    pub id: i32,
+    pub note: String,
+    /// Token subject of whoever wrote it; empty for imported notes
+    pub author: String,
+    #[schema(value_type = i64)]
+    pub created_gmt: Timestamp,
+}
+
+impl From<CustomerNote> for NoteResponse {
+    fn from(note: CustomerNote) -> Self {
+        Self {
+            id: note.id,
+            note: note.note,
+            author: note.luser,
+            created_gmt: note.created_gmt,
+        }
+    }
+}
+
+/// List the notes kept on a customer
+#[utoipa::path(
+    get,
+    path = "/api/customers/{mid}/{id}/notes",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Customer ID")
+    ),
+    responses(
+        (status = 200, description = "Notes, newest first", body = Vec<NoteResponse>),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "customers"
+)]
+pub async fn list(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Json<Vec<NoteResponse>>, ApiError> {
+    let notes = NoteService::list(&*state.db, admin.0.scoped_mid(mid), id).await?;
+    Ok(Json(notes.into_iter().map(Into::into).collect()))
+}
+
+/// Add a note to a customer
+#[utoipa::path(
+    post,
+    path = "/api/customers/{mid}/{id}/notes",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Customer ID")
+    ),
+    request_body = NoteRequest,
+    responses(
+        (status = 201, description = "Note added", body = NoteResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Customer not found", body = ErrorBody),
+        (status = 422, description = "Empty or too long note", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "customers"
+)]
+pub async fn add(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+    ValidatedJson(req): ValidatedJson<NoteRequest>,
+) -> Result<(StatusCode, Json<NoteResponse>), ApiError> {
+    let mid = admin.0.scoped_mid(mid);
+    let note = NoteService::add(&*state.db, mid, id, &admin.0.sub, &req.note).await?;
+    audit::record(&state, &admin.0, mid, Change::new("customer_note", note.id, "create").created(&note)).await;
+    Ok((StatusCode::CREATED, Json(note.into())))
+}
+
+/// Delete a note
+#[utoipa::path(
+    delete,
+    path = "/api/customers/{mid}/{id}/notes/{note_id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Customer ID"),
+        ("note_id" = i32, Path, description = "Note ID")
+    ),
+    responses(
+        (status = 204, description = "Note deleted"),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Note not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "customers"
+)]
+pub async fn delete(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id, note_id)): Path<(i32, i32, i32)>,
+) -> Result<StatusCode, ApiError> {
+    let mid = admin.0.scoped_mid(mid);
+    let note = NoteService::delete(&*state.db, mid, id, note_id).await?;
+    audit::record(&state, &admin.0, mid, Change::new("customer_note", note_id, "delete").removed(&note)).await;
+    Ok(StatusCode::NO_CONTENT)
+}
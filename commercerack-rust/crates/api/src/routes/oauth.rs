@@ -0,0 +1,324 @@
+//! Sign-in with Google and Apple
+//!
+//! A storefront sends the browser to `authorize` with its merchant and the
+//! page to come back to. After the provider's consent screen, the callback
+//! signs the customer in (see `commercerack_customer::identities`) and
+//! redirects to that page with the result in the URL fragment: the same
+//! fields as a `/api/auth/login` response, or `error` and
+//! `error_description`.
+//!
+//! The `state` parameter is signed, and its nonce is also kept in a cookie
+//! so that a callback only completes in the browser that started it.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap},
+    response::{IntoResponse, Redirect, Response},
+    Form,
+};
+use chrono::{Duration, Utc};
+use commercerack_customer::identities::IdentityService;
+use commercerack_customer::sessions::SessionService;
+use commercerack_customer::two_factor::TwoFactorService;
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use crate::client_ip::ClientIp;
+use crate::error::{ApiError, ErrorBody};
+use crate::oauth::{OAuthClient, Provider};
+use crate::routes::auth::{token_response, LoginResponse, TwoFactorChallenge};
+use crate::routes::{sessions, two_factor};
+use crate::AppState;
+
+/// Time allowed on the provider's pages
+const OAUTH_STATE_TTL_SECS: i64 = 10 * 60;
+
+const NONCE_COOKIE: &str = "commercerack_oauth_nonce";
+
+/// Claims of the `state` parameter. Lacking `sub`, it never passes as an
+/// access token.
+#[derive(Debug, Serialize, Deserialize)]
+struct OAuthState {
+    provider: String,
+    mid: i32,
+    return_to: String,
+    nonce: String,
+    iat: i64,
+    exp: i64,
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct AuthorizeQuery {
+    /// Merchant whose customer is signing in
+    pub mid: i32,
+    /// Storefront page to return to; its origin must be listed in
+    /// `cors.allowed_origins`
+    pub return_to: String,
+}
+
+/// What providers send back to the callback
+#[derive(Deserialize, utoipa::IntoParams, utoipa::ToSchema)]
+pub struct CallbackParams {
+    pub state: String,
+    pub code: Option<String>,
+    /// Set instead of `code` when the customer declined
+    pub error: Option<String>,
+    /// Apple's JSON with the customer's name, sent on first sign-in only
+    pub user: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct AppleUser {
+    name: Option<AppleName>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AppleName {
+    first_name: Option<String>,
+    last_name: Option<String>,
+}
+
+fn client(state: &AppState, provider: &str) -> Result<OAuthClient, ApiError> {
+    Ok(OAuthClient::from_config(&state.config.oauth, provider.parse::<Provider>()?)?)
+}
+
+/// `return_to`, if it is on one of the merchant's storefronts. Tokens end up
+/// in its URL, so a wildcard origin is not enough.
+fn storefront_url(return_to: &str, allowed_origins: &[String]) -> Result<Url, ApiError> {
+    let refused = || ApiError::BadRequest("return_to must be on an origin listed in cors.allowed_origins".to_string());
+    let url = Url::parse(return_to).map_err(|_| refused())?;
+    let origin = url.origin().ascii_serialization();
+    if !allowed_origins.contains(&origin) {
+        return Err(refused());
+    }
+    Ok(url)
+}
+
+fn nonce_cookie(nonce: &str, max_age: i64) -> String {
+    // SameSite=None so that Apple's cross-site form post carries it
+    format!("{}={}; Path=/; Max-Age={}; HttpOnly; Secure; SameSite=None", NONCE_COOKIE, nonce, max_age)
+}
+
+fn cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .find_map(|pair| pair.trim().strip_prefix(name)?.strip_prefix('='))
+}
+
+/// URL fragment of `fields`, escaped
+fn fragment(fields: &[(&str, &str)]) -> String {
+    fields
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, utf8_percent_encode(value, NON_ALPHANUMERIC)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Start signing in with a provider
+#[utoipa::path(
+    get,
+    path = "/api/auth/oauth/{provider}/authorize",
+    params(
+        ("provider" = String, Path, description = "`google` or `apple`"),
+        AuthorizeQuery
+    ),
+    responses(
+        (status = 303, description = "Redirect to the provider's sign-in page"),
+        (status = 400, description = "return_to is not on a storefront origin", body = ErrorBody),
+        (status = 404, description = "Unknown provider", body = ErrorBody),
+        (status = 503, description = "Provider not configured", body = ErrorBody)
+    ),
+    tag = "auth"
+)]
+pub async fn authorize(
+    State(state): State<AppState>,
+    Path(provider): Path<String>,
+    Query(query): Query<AuthorizeQuery>,
+) -> Result<Response, ApiError> {
+    let client = client(&state, &provider)?;
+    let return_to = storefront_url(&query.return_to, &state.config.cors.allowed_origins)?;
+
+    let now = Utc::now();
+    let nonce = uuid::Uuid::new_v4().simple().to_string();
+    let claims = OAuthState {
+        provider: client.provider().as_str().to_string(),
+        mid: query.mid,
+        return_to: return_to.into(),
+        nonce: nonce.clone(),
+        iat: now.timestamp(),
+        exp: (now + Duration::seconds(OAUTH_STATE_TTL_SECS)).timestamp(),
+    };
+    let signed = encode(&Header::default(), &claims, &EncodingKey::from_secret(state.config.jwt.secret.as_bytes()))
+        .map_err(|e| ApiError::Internal(format!("Failed to sign OAuth state: {}", e)))?;
+
+    Ok((
+        [(header::SET_COOKIE, nonce_cookie(&nonce, OAUTH_STATE_TTL_SECS))],
+        Redirect::to(&client.authorize_url(&signed, &nonce)),
+    )
+        .into_response())
+}
+
+/// Return from a provider, for providers that redirect (Google)
+#[utoipa::path(
+    get,
+    path = "/api/auth/oauth/{provider}/callback",
+    params(
+        ("provider" = String, Path, description = "`google` or `apple`"),
+        CallbackParams
+    ),
+    responses(
+        (status = 303, description = "Redirect back to the storefront with the outcome in the fragment"),
+        (status = 401, description = "Invalid or expired state, or started in another browser", body = ErrorBody),
+        (status = 404, description = "Unknown provider", body = ErrorBody)
+    ),
+    tag = "auth"
+)]
+pub async fn callback(
+    State(state): State<AppState>,
+    Path(provider): Path<String>,
+    ClientIp(ip): ClientIp,
+    headers: HeaderMap,
+    Query(params): Query<CallbackParams>,
+) -> Result<Response, ApiError> {
+    finish(&state, &provider, &ip, &headers, params).await
+}
+
+/// Return from a provider, for providers that post a form (Apple)
+#[utoipa::path(
+    post,
+    path = "/api/auth/oauth/{provider}/callback",
+    params(("provider" = String, Path, description = "`google` or `apple`")),
+    request_body(content = CallbackParams, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 303, description = "Redirect back to the storefront with the outcome in the fragment"),
+        (status = 401, description = "Invalid or expired state, or started in another browser", body = ErrorBody),
+        (status = 404, description = "Unknown provider", body = ErrorBody)
+    ),
+    tag = "auth"
+)]
+pub async fn callback_form(
+    State(state): State<AppState>,
+    Path(provider): Path<String>,
+    ClientIp(ip): ClientIp,
+    headers: HeaderMap,
+    Form(params): Form<CallbackParams>,
+) -> Result<Response, ApiError> {
+    finish(&state, &provider, &ip, &headers, params).await
+}
+
+/// Check the state, then send the browser back to the storefront with the
+/// outcome of signing in
+async fn finish(
+    state: &AppState,
+    provider: &str,
+    ip: &str,
+    headers: &HeaderMap,
+    params: CallbackParams,
+) -> Result<Response, ApiError> {
+    let client = client(state, provider)?;
+    let claims = decode::<OAuthState>(
+        &params.state,
+        &DecodingKey::from_secret(state.config.jwt.secret.as_bytes()),
+        &Validation::default(),
+    )
+    .map_err(|e| ApiError::Unauthorized(format!("Invalid OAuth state: {}", e)))?
+    .claims;
+    if claims.provider != client.provider().as_str() {
+        return Err(ApiError::Unauthorized("OAuth state is for another provider".to_string()));
+    }
+    if cookie(headers, NONCE_COOKIE) != Some(claims.nonce.as_str()) {
+        return Err(ApiError::Unauthorized("Sign-in was started in another browser".to_string()));
+    }
+    let mut return_to = storefront_url(&claims.return_to, &state.config.cors.allowed_origins)?;
+
+    let outcome = match sign_in(state, &client, &claims, params, ip, &sessions::device(headers)).await {
+        Ok(LoginResponse::Tokens(tokens)) => fragment(&[
+            ("access_token", &tokens.access_token),
+            ("refresh_token", &tokens.refresh_token),
+            ("token_type", &tokens.token_type),
+            ("expires_in", &tokens.expires_in.to_string()),
+        ]),
+        Ok(LoginResponse::TwoFactor(TwoFactorChallenge { two_factor_token, expires_in })) => {
+            fragment(&[("two_factor_token", &two_factor_token), ("expires_in", &expires_in.to_string())])
+        }
+        Err(e) => {
+            warn!(mid = claims.mid, provider = %claims.provider, "sign-in failed: {}", e);
+            let description = match &e {
+                ApiError::Internal(_) => "Sign-in failed".to_string(),
+                e => e.to_string(),
+            };
+            fragment(&[("error", e.code()), ("error_description", &description)])
+        }
+    };
+    return_to.set_fragment(Some(&outcome));
+
+    Ok((
+        [(header::SET_COOKIE, nonce_cookie("", 0))],
+        Redirect::to(return_to.as_str()),
+    )
+        .into_response())
+}
+
+async fn sign_in(
+    state: &AppState,
+    client: &OAuthClient,
+    claims: &OAuthState,
+    params: CallbackParams,
+    ip: &str,
+    device: &str,
+) -> Result<LoginResponse, ApiError> {
+    if let Some(error) = params.error {
+        return Err(ApiError::Unauthorized(format!("Sign-in was not completed: {}", error)));
+    }
+    let code = params.code.ok_or_else(|| ApiError::BadRequest("Missing code".to_string()))?;
+    let mut identity = client.exchange(&code, &claims.nonce).await?;
+
+    if let Some(name) = params.user.and_then(|user| serde_json::from_str::<AppleUser>(&user).ok()?.name) {
+        identity.firstname = name.first_name.unwrap_or_default();
+        identity.lastname = name.last_name.unwrap_or_default();
+    }
+
+    let db = &*state.db;
+    let customer = IdentityService::sign_in(db, claims.mid, &identity).await?;
+    if TwoFactorService::is_enabled(db, customer.mid, customer.cid).await? {
+        return two_factor::challenge(&state.config.jwt, customer.mid, customer.cid).map(LoginResponse::TwoFactor);
+    }
+
+    let issued = SessionService::start(db, customer.mid, customer.cid, device, ip).await?;
+    token_response(state, issued, false).await.map(LoginResponse::Tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_return_to_must_be_a_listed_storefront() {
+        let origins = vec!["https://shop.example".to_string(), "*".to_string()];
+        assert!(storefront_url("https://shop.example/account?x=1", &origins).is_ok());
+        for refused in ["https://evil.example/", "https://shop.example.evil.example/", "javascript:alert(1)", "/account"] {
+            assert!(matches!(storefront_url(refused, &origins), Err(ApiError::BadRequest(_))), "{}", refused);
+        }
+    }
+
+    #[test]
+    fn test_nonce_cookie_round_trip() {
+        let mut headers = HeaderMap::new();
+        let set = nonce_cookie("abc123", 600);
+        let pair = set.split(';').next().unwrap();
+        headers.insert(header::COOKIE, format!("theme=dark; {}", pair).parse().unwrap());
+        assert_eq!(cookie(&headers, NONCE_COOKIE), Some("abc123"));
+        assert_eq!(cookie(&headers, "missing"), None);
+    }
+
+    #[test]
+    fn test_fragment_escapes_values() {
+        assert_eq!(fragment(&[("error", "unauthorized"), ("error_description", "a b&c")]), "error=unauthorized&error_description=a%20b%26c");
+    }
+}
@@ -0,0 +1,250 @@
+//! Live order updates as server-sent events
+//!
+//! Each stream subscribes to the process-wide event bus, which the outbox
+//! relay feeds once order changes are committed, and forwards the order
+//! events it is interested in. Events are named after the domain event
+//! (`order.paid`, `order.shipped`, ...). A client too slow to keep up gets
+//! a `lagged` event instead of the ones it missed and should refetch.
+
+use axum::{
+    extract::{Path, State},
+    response::sse::{Event, KeepAlive, Sse},
+};
+use commercerack_core::{MerchantId, OrderId, Timestamp};
+use commercerack_events::DomainEvent;
+use commercerack_order::{OrderError, OrderService};
+use ::entity::prelude::Order;
+use serde::Serialize;
+use std::convert::Infallible;
+use tokio::sync::{broadcast::error::RecvError, mpsc};
+use tokio_stream::wrappers::ReceiverStream;
+use crate::auth::{Claims, RequireMerchantAdmin};
+use crate::error::{ApiError, ErrorBody};
+use crate::routes::payments::ensure_owner;
+use crate::AppState;
+
+/// Events buffered per client before the forwarder waits for it
+const STREAM_BUFFER: usize = 16;
+
+type EventStream = Sse<ReceiverStream<Result<Event, Infallible>>>;
+
+/// Status of an order after a change, sent as the event data
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct OrderStatusEvent {
+    pub id: i32,
+    pub orderid: String,
+    pub total: String,
+    #[schema(value_type = Option<i64>)]
+    pub paid_gmt: Option<Timestamp>,
+    pub order_payment_status: Option<String>,
+    #[schema(value_type = Option<i64>)]
+    pub shipped_gmt: Option<Timestamp>,
+    #[schema(value_type = Option<i64>)]
+    pub cancelled_gmt: Option<Timestamp>,
+    pub review_status: Option<String>,
+    /// Version after the change
+    pub v: i32,
+}
+
+impl From<&Order> for OrderStatusEvent {
+    fn from(order: &Order) -> Self {
+        Self {
+            id: order.id,
+            orderid: order.orderid.clone(),
+            total: order.total.to_string(),
+            paid_gmt: order.paid_gmt,
+            order_payment_status: order.order_payment_status.clone(),
+            shipped_gmt: order.shipped_gmt,
+            cancelled_gmt: order.cancelled_gmt,
+            review_status: order.review_status.clone(),
+            v: order.v,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct OrderDeletedEvent {
+    id: OrderId,
+}
+
+/// The event name and JSON data to send for `event`, if it is an update
+/// to one of `mid`'s orders (to order `id` only, when given)
+fn order_event(event: &DomainEvent, mid: MerchantId, id: Option<OrderId>) -> Option<(&'static str, String)> {
+    if event.mid() != mid {
+        return None;
+    }
+    let (order_id, data) = match event {
+        DomainEvent::OrderCreated { order, .. }
+        | DomainEvent::OrderUpdated(order)
+        | DomainEvent::OrderPaid(order)
+        | DomainEvent::OrderShipped(order)
+        | DomainEvent::OrderCancelled(order) => (order.id.into(), serde_json::to_string(&OrderStatusEvent::from(order))),
+        DomainEvent::OrderDeleted { id, .. } => (*id, serde_json::to_string(&OrderDeletedEvent { id: *id })),
+        _ => return None,
+    };
+    if id.is_some_and(|id| id != order_id) {
+        return None;
+    }
+    // Plain structs always serialize
+    Some((event.name(), data.ok()?))
+}
+
+/// Forward matching bus events to a new SSE response until the client
+/// disconnects, or the order is deleted when following a single one
+fn stream(mid: MerchantId, id: Option<OrderId>) -> EventStream {
+    let mut receiver = commercerack_events::global().subscribe();
+    let (tx, rx) = mpsc::channel::<Result<Event, Infallible>>(STREAM_BUFFER);
+    tokio::spawn(async move {
+        loop {
+            let event = match receiver.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(missed)) => {
+                    let lagged = Event::default().event("lagged").data(missed.to_string());
+                    if tx.send(Ok(lagged)).await.is_err() {
+                        return;
+                    }
+                    continue;
+                }
+                Err(RecvError::Closed) => return,
+            };
+            let Some((name, data)) = order_event(&event, mid, id) else {
+                continue;
+            };
+            if tx.send(Ok(Event::default().event(name).data(data))).await.is_err() {
+                return;
+            }
+            if id.is_some() && matches!(*event, DomainEvent::OrderDeleted { .. }) {
+                return;
+            }
+        }
+    });
+
+    Sse::new(ReceiverStream::new(rx)).keep_alive(KeepAlive::default())
+}
+
+/// Follow one order's status changes
+///
+/// Shoppers may only follow their own orders, or those placed for the
+/// company they buy for.
+#[utoipa::path(
+    get,
+    path = "/api/orders/{mid}/{id}/events",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Order ID")
+    ),
+    responses(
+        (status = 200, description = "Stream of `order.*` events carrying an OrderStatusEvent; ends when the order is deleted", content_type = "text/event-stream"),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 404, description = "Order not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "orders"
+)]
+pub async fn order_events(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<EventStream, ApiError> {
+    let mid = claims.scoped_mid(mid);
+    ensure_owner(&state, &claims, mid, id).await?;
+
+    OrderService::find_by_id(&*state.db, mid, id)
+        .await?
+        .ok_or(OrderError::NotFound)?;
+    Ok(stream(mid.into(), Some(id.into())))
+}
+
+/// Follow status changes of all of a merchant's orders
+#[utoipa::path(
+    get,
+    path = "/api/merchants/{mid}/order-stream",
+    params(("mid" = i32, Path, description = "Merchant ID")),
+    responses(
+        (status = 200, description = "Stream of `order.*` events carrying an OrderStatusEvent", content_type = "text/event-stream"),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody)
+    ),
+    tag = "orders"
+)]
+pub async fn merchant_stream(admin: RequireMerchantAdmin, Path(mid): Path<i32>) -> EventStream {
+    stream(admin.0.scoped_mid(mid).into(), None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use commercerack_core::CustomerId;
+    use rust_decimal::Decimal;
+
+    fn order(mid: i32, id: i32) -> Order {
+        Order {
+            id,
+            mid,
+            orderid: format!("2025-01-01-{}", id),
+            cartid: "cart-1".to_string(),
+            customer: 0,
+            pool: "RECENT".to_string(),
+            total: Decimal::new(2500, 2),
+            created_gmt: Timestamp::from_unix(1_700_000_000),
+            paid_gmt: Some(Timestamp::from_unix(1_700_000_100)),
+            paid_txn: None,
+            order_payment_status: Some("002".to_string()),
+            order_payment_lookup: None,
+            bs_settlement: None,
+            shipped_gmt: None,
+            inv_gmt: None,
+            company_id: None,
+            review_status: None,
+            ship_method: None,
+            bill_email: "guest@example.com".to_string(),
+            v: 3,
+            mkt: 0,
+            erefid: None,
+            mkt_bitstr: String::new(),
+            pickup_location_id: None,
+            pickup_ready_gmt: None,
+            picked_up_gmt: None,
+            cancelled_gmt: None,
+            cancel_reason: None,
+        }
+    }
+
+    #[test]
+    fn test_order_events_are_filtered_by_merchant_and_order() {
+        let paid = DomainEvent::OrderPaid(order(1, 7));
+
+        let (name, data) = order_event(&paid, MerchantId::new(1), None).unwrap();
+        assert_eq!(name, "order.paid");
+        let data: serde_json::Value = serde_json::from_str(&data).unwrap();
+        assert_eq!(data["id"], 7);
+        assert_eq!(data["total"], "25.00");
+        assert_eq!(data["v"], 3);
+
+        assert!(order_event(&paid, MerchantId::new(1), Some(OrderId::new(7))).is_some());
+        assert!(order_event(&paid, MerchantId::new(1), Some(OrderId::new(8))).is_none());
+        assert!(order_event(&paid, MerchantId::new(2), None).is_none());
+        let other = DomainEvent::CustomerDeleted { mid: MerchantId::new(1), cid: CustomerId::new(7) };
+        assert!(order_event(&other, MerchantId::new(1), None).is_none());
+
+        let deleted = DomainEvent::OrderDeleted { mid: MerchantId::new(1), id: OrderId::new(7) };
+        assert_eq!(
+            order_event(&deleted, MerchantId::new(1), Some(OrderId::new(7))),
+            Some(("order.deleted", r#"{"id":7}"#.to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_shoppers_only_follow_their_own_orders() {
+        use axum::http::StatusCode;
+        use sea_orm::{DatabaseBackend, MockDatabase};
+
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![order(1, 7)]])
+            .into_connection();
+        let state = AppState::mock(db);
+
+        let result = order_events(State(state), Claims::new(5, 1), Path((1, 7))).await;
+        assert_eq!(result.err().map(|e| e.status()), Some(StatusCode::NOT_FOUND));
+    }
+}
@@ -3,10 +3,28 @@ use axum::{
     http::StatusCode,
     Json,
 };
-use commercerack_order::OrderService;
-use ::entity::prelude::Order as OrderModel;
-use rust_decimal::Decimal;
+use commercerack_audit::Change;
+use commercerack_cart::ItemOptions;
+use commercerack_core::Timestamp;
+use commercerack_order::items::{self, line_total, NewOrderItem, OrderItemService};
+use commercerack_order::shipments::{LabeledShipment, NewShipment, ShipmentLine, ShipmentWithItems};
+use commercerack_order::payment::PaymentStatus;
+use commercerack_order::cancellation::{CancelReason, CancellationService};
+use commercerack_order::cold_storage::ColdStorageService;
+use commercerack_order::edits::{OrderEdit, OrderEditService};
+use commercerack_order::fulfillment_groups::{FulfillmentGroup, FulfillmentGroupService};
+use commercerack_order::pickup::{self, PickupService};
+use commercerack_order::pools::{is_pool, PoolCount, PoolService};
+use commercerack_db::pagination::Cursor;
+use commercerack_shipping::{LabelAddress, LabelProvider, Tracking};
+use commercerack_order::{OrderError, OrderFilter, OrderService, OrderWithItems};
+use ::entity::prelude::{Order as OrderModel, OrderItem, ShipmentItem};
 use serde::{Deserialize, Serialize};
+use crate::auth::{Claims, RequireMerchantAdmin};
+use crate::error::{ApiError, ErrorBody};
+use crate::routes::batch::{self, BatchGetError, BatchGetRequest};
+use crate::routes::{audit, parse_decimal, payments};
+use crate::validation::{self, ValidatedJson, Validate, Validator};
 use crate::AppState;
 
 #[derive(Deserialize, utoipa::ToSchema)]
@@ -16,7 +34,288 @@ pub struct CreateOrderRequest {
     pub cartid: String,
     pub customer: i32,
     pub pool: String,
-    pub total: String,
+    pub items: Vec<OrderItemRequest>,
+}
+
+impl Validate for CreateOrderRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("orderid", &self.orderid, 30)
+            .max_len("cartid", &self.cartid, 30)
+            .required("pool", &self.pool, 20)
+            .check(is_pool(self.pool.trim()), "pool", "is not a known order pool")
+            .check(!self.items.is_empty(), "items", "must contain at least one item")
+            .each("items", &self.items);
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct OrderItemRequest {
+    pub sku: String,
+    pub product_name: String,
+    pub quantity: i32,
+    pub unit_price: String,
+}
+
+impl Validate for OrderItemRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("sku", &self.sku, 45)
+            .required("product_name", &self.product_name, 80)
+            .positive("quantity", self.quantity)
+            .amount("unit_price", &self.unit_price);
+    }
+}
+
+fn invalid_quantity() -> ApiError {
+    ApiError::BadRequest("Quantity must be positive".to_string())
+}
+
+impl OrderItemRequest {
+    fn into_new_item(self) -> Result<NewOrderItem, ApiError> {
+        let unit_price = parse_decimal("unit_price", &self.unit_price)?;
+        if self.quantity <= 0 {
+            return Err(invalid_quantity());
+        }
+
+        Ok(NewOrderItem {
+            sku: self.sku,
+            product_name: self.product_name,
+            quantity: self.quantity,
+            unit_price,
+            options: Default::default(),
+        })
+    }
+}
+
+/// Edits to apply to a placed order, in order
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct EditOrderRequest {
+    /// `v` of the order as last read; the edits are refused if it has
+    /// changed since
+    pub v: i32,
+    pub edits: Vec<OrderEditRequest>,
+}
+
+impl Validate for EditOrderRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.check(!self.edits.is_empty(), "edits", "must contain at least one edit")
+            .check(self.edits.len() <= 100, "edits", "must contain at most 100 edits")
+            .each("edits", &self.edits);
+    }
+}
+
+/// One change to a placed order
+#[derive(Deserialize, utoipa::ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OrderEditRequest {
+    /// Add a product line
+    AddItem {
+        sku: String,
+        product_name: String,
+        quantity: i32,
+        unit_price: String,
+    },
+    /// Change how many of a product line the buyer gets
+    SetQuantity { item_id: i32, quantity: i32 },
+    /// Remove a product line or a manual discount
+    RemoveItem { item_id: i32 },
+    /// Take `amount` off the order as a `%DISCOUNT` line
+    Discount {
+        amount: String,
+        /// Shown to the buyer as the line's name; `Discount` if empty
+        #[serde(default)]
+        reason: String,
+    },
+}
+
+impl Validate for OrderEditRequest {
+    fn validate(&self, v: &mut Validator) {
+        match self {
+            OrderEditRequest::AddItem { sku, product_name, quantity, unit_price } => {
+                v.required("sku", sku, 45)
+                    .check(!sku.trim().starts_with('%'), "sku", "must not be an adjustment SKU")
+                    .required("product_name", product_name, 80)
+                    .positive("quantity", *quantity)
+                    .amount("unit_price", unit_price);
+            }
+            OrderEditRequest::SetQuantity { quantity, .. } => {
+                v.positive("quantity", *quantity);
+            }
+            OrderEditRequest::RemoveItem { .. } => {}
+            OrderEditRequest::Discount { amount, reason } => {
+                v.positive_amount("amount", amount).max_len("reason", reason, 80);
+            }
+        }
+    }
+}
+
+impl OrderEditRequest {
+    fn into_edit(self) -> Result<OrderEdit, ApiError> {
+        Ok(match self {
+            OrderEditRequest::AddItem { sku, product_name, quantity, unit_price } => OrderEdit::AddItem(NewOrderItem {
+                sku: sku.trim().to_string(),
+                product_name,
+                quantity,
+                unit_price: parse_decimal("unit_price", unit_price.trim())?,
+                options: Default::default(),
+            }),
+            OrderEditRequest::SetQuantity { item_id, quantity } => OrderEdit::SetQuantity { item_id, quantity },
+            OrderEditRequest::RemoveItem { item_id } => OrderEdit::RemoveItem { item_id },
+            OrderEditRequest::Discount { amount, reason } => OrderEdit::Discount {
+                amount: parse_decimal("amount", amount.trim())?,
+                reason,
+            },
+        })
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct CancelOrderRequest {
+    /// One of `customer_request`, `out_of_stock`, `fraud`,
+    /// `payment_failed`, `duplicate`, `other`
+    pub reason: String,
+}
+
+impl Validate for CancelOrderRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.check(
+            self.reason.parse::<CancelReason>().is_ok(),
+            "reason",
+            "must be one of customer_request, out_of_stock, fraud, payment_failed, duplicate, other",
+        );
+    }
+}
+
+/// Order fields an admin can edit; unset fields are left as they are
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct UpdateOrderRequest {
+    /// `v` of the order as last read; the update is refused if it has
+    /// changed since
+    pub v: i32,
+    pub pool: Option<String>,
+    /// Legacy three character review code, e.g. `AOK`; `DUP` holds a likely duplicate order
+    pub review_status: Option<String>,
+    pub ship_method: Option<String>,
+    pub bill_email: Option<String>,
+}
+
+impl Validate for UpdateOrderRequest {
+    fn validate(&self, v: &mut Validator) {
+        if let Some(pool) = &self.pool {
+            v.required("pool", pool, 20)
+                .check(is_pool(pool.trim()), "pool", "is not a known order pool");
+        }
+        if let Some(review_status) = &self.review_status {
+            v.max_len("review_status", review_status, 3);
+        }
+        if let Some(ship_method) = &self.ship_method {
+            v.max_len("ship_method", ship_method, 10);
+        }
+        if let Some(bill_email) = &self.bill_email {
+            v.email("bill_email", bill_email, 65);
+        }
+    }
+}
+
+impl UpdateOrderRequest {
+    fn apply(self, order: &mut OrderModel) {
+        order.v = self.v;
+        if let Some(pool) = self.pool {
+            order.pool = pool.trim().to_string();
+        }
+        if let Some(review_status) = self.review_status {
+            order.review_status = Some(review_status);
+        }
+        if let Some(ship_method) = self.ship_method {
+            order.ship_method = Some(ship_method);
+        }
+        if let Some(bill_email) = self.bill_email {
+            order.bill_email = bill_email.trim().to_string();
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct UpdateOrderItemRequest {
+    pub quantity: Option<i32>,
+    pub unit_price: Option<String>,
+}
+
+impl Validate for UpdateOrderItemRequest {
+    fn validate(&self, v: &mut Validator) {
+        if let Some(quantity) = self.quantity {
+            v.positive("quantity", quantity);
+        }
+        if let Some(unit_price) = &self.unit_price {
+            v.amount("unit_price", unit_price);
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct OrderItemResponse {
+    pub id: i32,
+    pub sku: String,
+    pub product_name: String,
+    pub quantity: i32,
+    pub unit_price: String,
+    pub line_total: String,
+    /// Selections, engraving and gift wrap the shopper chose
+    #[serde(skip_serializing_if = "ItemOptions::is_empty")]
+    #[schema(value_type = Object)]
+    pub options: ItemOptions,
+}
+
+impl From<OrderItem> for OrderItemResponse {
+    fn from(item: OrderItem) -> Self {
+        Self {
+            line_total: line_total(&item).to_string(),
+            options: items::options(&item),
+            id: item.id,
+            sku: item.sku,
+            product_name: item.product_name,
+            quantity: item.quantity,
+            unit_price: item.unit_price.to_string(),
+        }
+    }
+}
+
+/// A planned shipment of an order from one warehouse
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct FulfillmentGroupResponse {
+    pub id: i32,
+    /// Absent for units no warehouse held when the order was placed
+    pub warehouse_id: Option<i32>,
+    pub carrier: String,
+    pub ship_method: String,
+    pub ship_name: String,
+    /// What the buyer was charged to ship it
+    pub shipping: String,
+    pub items: Vec<FulfillmentGroupItemResponse>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct FulfillmentGroupItemResponse {
+    pub sku: String,
+    pub quantity: i32,
+}
+
+impl From<FulfillmentGroup> for FulfillmentGroupResponse {
+    fn from(planned: FulfillmentGroup) -> Self {
+        let group = planned.group;
+        Self {
+            id: group.id,
+            warehouse_id: group.warehouse_id,
+            carrier: group.carrier,
+            ship_method: group.ship_method,
+            ship_name: group.ship_name,
+            shipping: group.shipping.to_string(),
+            items: planned
+                .items
+                .into_iter()
+                .map(|item| FulfillmentGroupItemResponse { sku: item.sku, quantity: item.quantity })
+                .collect(),
+        }
+    }
 }
 
 #[derive(Serialize, utoipa::ToSchema)]
@@ -25,16 +324,69 @@ pub struct OrderResponse {
     pub mid: i32,
     pub orderid: String,
     pub cartid: String,
+    /// `0` for guest orders
     pub customer: i32,
     pub pool: String,
     pub total: String,
-    pub created_gmt: i32,
-    pub paid_gmt: Option<i32>,
-    pub shipped_gmt: Option<i32>,
+    #[schema(value_type = i64)]
+    pub created_gmt: Timestamp,
+    #[schema(value_type = Option<i64>)]
+    pub paid_gmt: Option<Timestamp>,
+    pub paid_txn: Option<String>,
+    pub order_payment_status: Option<String>,
+    pub order_payment_lookup: Option<String>,
+    #[schema(value_type = Option<i64>)]
+    pub bs_settlement: Option<Timestamp>,
+    #[schema(value_type = Option<i64>)]
+    pub shipped_gmt: Option<Timestamp>,
+    /// When the invoice was issued
+    #[schema(value_type = Option<i64>)]
+    pub inv_gmt: Option<Timestamp>,
+    /// Company account a buyer placed the order for
+    pub company_id: Option<i32>,
+    pub review_status: Option<String>,
+    pub ship_method: Option<String>,
+    pub bill_email: String,
+    /// Version; send it back when updating the order
+    pub v: i32,
+    /// Bit of the marketplace the order was placed on; `0` for the storefront
+    pub mkt: i32,
+    /// The marketplace's own order reference
+    pub erefid: Option<String>,
+    /// `mkt` as a comma-separated list of its set bits
+    pub mkt_bitstr: String,
+    /// `ship` or `pickup`
+    pub fulfillment: String,
+    /// Store location a pickup order is collected from
+    pub pickup_location_id: Option<i32>,
+    /// When the buyer was told the order is ready to collect
+    #[schema(value_type = Option<i64>)]
+    pub pickup_ready_gmt: Option<Timestamp>,
+    /// When the buyer collected the order
+    #[schema(value_type = Option<i64>)]
+    pub picked_up_gmt: Option<Timestamp>,
+    /// When the order was cancelled
+    #[schema(value_type = Option<i64>)]
+    pub cancelled_gmt: Option<Timestamp>,
+    /// Why it was cancelled, e.g. `customer_request`
+    pub cancel_reason: Option<String>,
+    pub items: Vec<OrderItemResponse>,
+    /// Shipments the order was planned as, one per warehouse shipping it;
+    /// filled in where the order is read on its own
+    pub fulfillment_groups: Vec<FulfillmentGroupResponse>,
+}
+
+impl From<OrderWithItems> for OrderResponse {
+    fn from(placed: OrderWithItems) -> Self {
+        let mut response = OrderResponse::from(placed.order);
+        response.items = placed.items.into_iter().map(|i| i.into()).collect();
+        response
+    }
 }
 
 impl From<OrderModel> for OrderResponse {
     fn from(order: OrderModel) -> Self {
+        let fulfillment = pickup::fulfillment(&order).to_string();
         Self {
             id: order.id,
             mid: order.mid,
@@ -45,7 +397,196 @@ impl From<OrderModel> for OrderResponse {
             total: order.total.to_string(),
             created_gmt: order.created_gmt,
             paid_gmt: order.paid_gmt,
+            paid_txn: order.paid_txn,
+            order_payment_status: order.order_payment_status,
+            order_payment_lookup: order.order_payment_lookup,
+            bs_settlement: order.bs_settlement,
             shipped_gmt: order.shipped_gmt,
+            inv_gmt: order.inv_gmt,
+            company_id: order.company_id,
+            review_status: order.review_status,
+            ship_method: order.ship_method,
+            bill_email: order.bill_email,
+            v: order.v,
+            mkt: order.mkt,
+            erefid: order.erefid,
+            mkt_bitstr: order.mkt_bitstr,
+            fulfillment,
+            pickup_location_id: order.pickup_location_id,
+            pickup_ready_gmt: order.pickup_ready_gmt,
+            picked_up_gmt: order.picked_up_gmt,
+            cancelled_gmt: order.cancelled_gmt,
+            cancel_reason: order.cancel_reason,
+            items: Vec::new(),
+            fulfillment_groups: Vec::new(),
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct ShipmentRequest {
+    /// e.g. `UPS`; omit when buying a label
+    #[serde(default)]
+    pub carrier: String,
+    #[serde(default)]
+    pub tracking_number: String,
+    /// Omit to ship everything not shipped yet
+    #[serde(default)]
+    pub items: Vec<ShipmentItemRequest>,
+    /// Buy a label for the shipment, which supplies its carrier and
+    /// tracking number
+    #[serde(default)]
+    pub label: Option<ShipmentLabelRequest>,
+}
+
+impl Validate for ShipmentRequest {
+    fn validate(&self, v: &mut Validator) {
+        match &self.label {
+            Some(label) => {
+                v.check(
+                    self.carrier.trim().is_empty() && self.tracking_number.trim().is_empty(),
+                    "label",
+                    "cannot be combined with a carrier or tracking number",
+                )
+                .nested("label", label);
+            }
+            None => {
+                v.required("carrier", &self.carrier, 40)
+                    .max_len("tracking_number", &self.tracking_number, 80);
+            }
+        }
+        v.each("items", &self.items);
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct ShipmentLabelRequest {
+    /// Service level, e.g. `ground`
+    pub service: String,
+    pub ship_to: LabelAddressRequest,
+}
+
+impl Validate for ShipmentLabelRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("service", &self.service, 40).nested("ship_to", &self.ship_to);
+    }
+}
+
+/// Where the labeled parcel goes
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct LabelAddressRequest {
+    pub name: String,
+    pub street1: String,
+    #[serde(default)]
+    pub street2: String,
+    pub city: String,
+    #[serde(default)]
+    pub state: String,
+    pub zip: String,
+    /// ISO 3166-1 alpha-2 code
+    pub country: String,
+}
+
+impl Validate for LabelAddressRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("name", &self.name, 100)
+            .required("street1", &self.street1, 100)
+            .max_len("street2", &self.street2, 100)
+            .required("city", &self.city, 60)
+            .max_len("state", &self.state, 20)
+            .max_len("zip", &self.zip, 20)
+            .check(
+                self.country.trim().len() == 2 && self.country.trim().chars().all(|c| c.is_ascii_alphabetic()),
+                "country",
+                "must be a two-letter country code",
+            );
+    }
+}
+
+impl From<LabelAddressRequest> for LabelAddress {
+    fn from(address: LabelAddressRequest) -> Self {
+        Self {
+            name: address.name.trim().to_string(),
+            street1: address.street1.trim().to_string(),
+            street2: address.street2.trim().to_string(),
+            city: address.city.trim().to_string(),
+            state: address.state.trim().to_uppercase(),
+            zip: address.zip.trim().to_uppercase(),
+            country: address.country.trim().to_uppercase(),
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct ShipmentItemRequest {
+    pub order_item_id: i32,
+    pub quantity: i32,
+}
+
+impl Validate for ShipmentItemRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.positive("quantity", self.quantity);
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ShipmentItemResponse {
+    pub order_item_id: i32,
+    pub quantity: i32,
+}
+
+impl From<ShipmentItem> for ShipmentItemResponse {
+    fn from(item: ShipmentItem) -> Self {
+        Self {
+            order_item_id: item.order_item_id,
+            quantity: item.quantity,
+        }
+    }
+}
+
+/// A label bought for a shipment
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ShipmentLabelResponse {
+    /// Label service, e.g. `stub`
+    pub provider: String,
+    /// Printable label
+    pub url: String,
+    /// What the label cost
+    pub cost: String,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ShipmentResponse {
+    pub id: i32,
+    pub order_id: i32,
+    pub carrier: String,
+    pub tracking_number: String,
+    #[schema(value_type = i64)]
+    pub shipped_gmt: Timestamp,
+    pub items: Vec<ShipmentItemResponse>,
+    /// Absent when no label was bought through the API
+    pub label: Option<ShipmentLabelResponse>,
+}
+
+impl From<ShipmentWithItems> for ShipmentResponse {
+    fn from(shipped: ShipmentWithItems) -> Self {
+        let shipment = shipped.shipment;
+        let label = match (shipment.label_provider, shipment.label_url) {
+            (Some(provider), Some(url)) => Some(ShipmentLabelResponse {
+                provider,
+                url,
+                cost: shipment.label_cost.unwrap_or_default().to_string(),
+            }),
+            _ => None,
+        };
+        Self {
+            id: shipment.id,
+            order_id: shipment.order_id,
+            carrier: shipment.carrier,
+            tracking_number: shipment.tracking_number,
+            shipped_gmt: shipment.shipped_gmt,
+            items: shipped.items.into_iter().map(|i| i.into()).collect(),
+            label,
         }
     }
 }
@@ -53,16 +594,86 @@ impl From<OrderModel> for OrderResponse {
 #[derive(Deserialize, utoipa::IntoParams)]
 pub struct ListQuery {
     pub mid: i32,
+    pub pool: Option<String>,
+    /// e.g. `paid`, `authorized`, `refunded`
+    pub payment_status: Option<String>,
+    /// Legacy three character review code, e.g. `AOK`; `DUP` holds a likely duplicate order
+    pub review_status: Option<String>,
+    pub customer: Option<i32>,
+    /// Only orders created at or after this Unix time
+    #[param(value_type = Option<i64>)]
+    pub created_from: Option<Timestamp>,
+    /// Only orders created at or before this Unix time
+    #[param(value_type = Option<i64>)]
+    pub created_to: Option<Timestamp>,
     #[serde(default = "default_limit")]
     pub limit: u64,
-    #[serde(default)]
-    pub offset: u64,
+    /// `next_cursor` of the previous page
+    pub cursor: Option<String>,
+}
+
+impl Validate for ListQuery {
+    fn validate(&self, v: &mut Validator) {
+        if let Some(status) = &self.payment_status {
+            v.check(status.parse::<PaymentStatus>().is_ok(), "payment_status", "is not a known payment status");
+        }
+        if let Some(cursor) = &self.cursor {
+            v.check(cursor.parse::<Cursor>().is_ok(), "cursor", "is not a cursor from a previous page");
+        }
+        if let (Some(from), Some(to)) = (self.created_from, self.created_to) {
+            v.check(from <= to, "created_to", "must not be before created_from");
+        }
+        v.check((1..=100).contains(&self.limit), "limit", "must be between 1 and 100");
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct OrderListResponse {
+    pub orders: Vec<OrderResponse>,
+    /// Pass as `cursor` to get the next page; absent on the last page
+    pub next_cursor: Option<String>,
 }
 
 fn default_limit() -> u64 {
     20
 }
 
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct PoolsQuery {
+    pub mid: i32,
+}
+
+/// How many of the merchant's orders are in a pool
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct PoolCountResponse {
+    pub pool: String,
+    pub orders: i64,
+}
+
+impl From<PoolCount> for PoolCountResponse {
+    fn from(count: PoolCount) -> Self {
+        Self { pool: count.pool, orders: count.orders }
+    }
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct SearchQuery {
+    pub mid: i32,
+    /// Part of a billing name, email or phone number, or the end of a
+    /// payment transaction ID
+    pub q: String,
+    #[serde(default = "default_limit")]
+    pub limit: u64,
+}
+
+impl Validate for SearchQuery {
+    fn validate(&self, v: &mut Validator) {
+        v.check(self.q.trim().chars().count() >= 3, "q", "must be at least 3 characters")
+            .max_len("q", &self.q, 100)
+            .check((1..=100).contains(&self.limit), "limit", "must be between 1 and 100");
+    }
+}
+
 /// Create a new order
 #[utoipa::path(
     post,
@@ -70,29 +681,35 @@ fn default_limit() -> u64 {
     request_body = CreateOrderRequest,
     responses(
         (status = 201, description = "Order created successfully", body = OrderResponse),
-        (status = 500, description = "Internal server error")
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
     ),
     tag = "orders"
 )]
 pub async fn create(
     State(state): State<AppState>,
-    Json(req): Json<CreateOrderRequest>,
-) -> Result<(StatusCode, Json<OrderResponse>), StatusCode> {
-    let total = req.total.parse::<Decimal>()
-        .map_err(|_| StatusCode::BAD_REQUEST)?;
+    admin: RequireMerchantAdmin,
+    ValidatedJson(req): ValidatedJson<CreateOrderRequest>,
+) -> Result<(StatusCode, Json<OrderResponse>), ApiError> {
+    let items = req
+        .items
+        .into_iter()
+        .map(OrderItemRequest::into_new_item)
+        .collect::<Result<Vec<_>, _>>()?;
 
     OrderService::create(
         &*state.db,
-        req.mid,
+        admin.0.scoped_mid(req.mid),
         &req.orderid,
         &req.cartid,
         req.customer,
         &req.pool,
-        total,
+        &items,
     )
     .await
     .map(|order| (StatusCode::CREATED, Json(order.into())))
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+    .map_err(ApiError::from)
 }
 
 /// Get an order by ID
@@ -104,35 +721,741 @@ pub async fn create(
         ("id" = i32, Path, description = "Order ID")
     ),
     responses(
-        (status = 200, description = "Order found", body = OrderResponse),
-        (status = 404, description = "Order not found"),
-        (status = 500, description = "Internal server error")
+        (status = 200, description = "Order found", body = OrderResponse,
+            headers(("ETag" = String, description = "Send back in If-None-Match to revalidate"))),
+        (status = 304, description = "Unchanged since the If-None-Match tag"),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 404, description = "Order not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
     ),
     tag = "orders"
 )]
 pub async fn get(
     State(state): State<AppState>,
+    claims: Claims,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Json<OrderResponse>, ApiError> {
+    let mid = claims.scoped_mid(mid);
+    payments::ensure_owner(&state, &claims, mid, id).await?;
+
+    let order = OrderService::find_by_id(&*state.db, mid, id)
+        .await?
+        .ok_or(OrderError::NotFound)?;
+    let items = OrderItemService::list(&*state.db, mid, id).await?;
+    let groups = FulfillmentGroupService::for_order(&*state.db, mid, id).await?;
+
+    let mut response = OrderResponse::from(OrderWithItems { order, items });
+    response.fulfillment_groups = groups.into_iter().map(Into::into).collect();
+    Ok(Json(response))
+}
+
+/// Orders of a batch-get, with the IDs that could not be returned
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct OrderBatchResponse {
+    /// In the order they were asked for
+    pub orders: Vec<OrderResponse>,
+    /// Orders moved to cold storage are reported as `archived`; read them
+    /// with `GET /api/archived-orders/{mid}/{id}`
+    pub errors: Vec<BatchGetError>,
+}
+
+/// Get many orders by ID in one request
+#[utoipa::path(
+    post,
+    path = "/api/orders/batch-get",
+    request_body = BatchGetRequest,
+    responses(
+        (status = 200, description = "Found orders with their items, and an error for each ID that was not", body = OrderBatchResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 422, description = "Too few or many IDs", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "orders"
+)]
+pub async fn batch_get(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    ValidatedJson(req): ValidatedJson<BatchGetRequest>,
+) -> Result<Json<OrderBatchResponse>, ApiError> {
+    let mid = admin.0.scoped_mid(req.mid);
+    let ids = req.unique_ids();
+    let found = OrderService::find_by_ids(&*state.db, mid, &ids).await?;
+    let found_ids: Vec<i32> = found.iter().map(|o| o.id).collect();
+    let mut items = OrderItemService::list_for_orders(&*state.db, mid, &found_ids).await?;
+
+    let mut missing = Vec::new();
+    let orders = batch::in_request_order(&ids, found, |o| o.id, |id| missing.push(id));
+    let archived = ColdStorageService::archived_ids(&*state.db, mid, &missing).await?;
+    let errors = missing
+        .into_iter()
+        .map(|id| {
+            if archived.contains(&id) {
+                BatchGetError { id, code: "archived", message: "Order is in cold storage".to_string() }
+            } else {
+                BatchGetError::not_found(id, "Order")
+            }
+        })
+        .collect();
+
+    Ok(Json(OrderBatchResponse {
+        orders: orders
+            .into_iter()
+            .map(|order| {
+                let items = items.remove(&order.id).unwrap_or_default();
+                OrderWithItems { order, items }.into()
+            })
+            .collect(),
+        errors,
+    }))
+}
+
+/// Edit an order
+///
+/// Send the `v` the order was read at. If someone else changed it since,
+/// nothing is saved and the 409 body's `current` holds the order as it is
+/// now, to merge the edit into and retry with its `v`.
+#[utoipa::path(
+    put,
+    path = "/api/orders/{mid}/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Order ID")
+    ),
+    request_body = UpdateOrderRequest,
+    responses(
+        (status = 200, description = "Order updated", body = OrderResponse),
+        (status = 404, description = "Order not found", body = ErrorBody),
+        (status = 409, description = "Order changed since `v` was read", body = ErrorBody),
+        (status = 422, description = "Validation failed", body = ErrorBody),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "orders"
+)]
+pub async fn update(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+    ValidatedJson(req): ValidatedJson<UpdateOrderRequest>,
+) -> Result<Json<OrderResponse>, ApiError> {
+    let before = OrderService::find_by_id(&*state.db, mid, id)
+        .await?
+        .ok_or(OrderError::NotFound)?;
+    if before.v != req.v {
+        return Err(version_conflict(&state, before).await);
+    }
+    let mut order = before.clone();
+    req.apply(&mut order);
+
+    let order = match OrderService::update(&*state.db, order).await {
+        Err(OrderError::VersionConflict(current)) => return Err(version_conflict(&state, *current).await),
+        result => result?,
+    };
+    audit::record(&state, &admin.0, mid, Change::new("order", id, "update").diff(&before, &order)).await;
+    let items = OrderItemService::list(&*state.db, mid, id).await?;
+
+    Ok(Json(OrderWithItems { order, items }.into()))
+}
+
+/// Edit the items of a placed order
+///
+/// Orders can be edited until any of them ships. The edits are applied
+/// together, in order: stock moves with the quantities, tax is rescaled to
+/// the new taxable amount at the rate charged before, and the total and
+/// payment status are recomputed, so editing a paid order can leave a
+/// balance to collect or a refund to give. Shipping charges stay as they
+/// were placed. Each edit gets its own audit log entry. As with other
+/// order updates, send the `v` the order was read at.
+#[utoipa::path(
+    post,
+    path = "/api/orders/{mid}/{id}/edits",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Order ID")
+    ),
+    request_body = EditOrderRequest,
+    responses(
+        (status = 200, description = "Order edited", body = OrderResponse),
+        (status = 400, description = "Unknown SKU", body = ErrorBody),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Order or item not found", body = ErrorBody),
+        (status = 409, description = "Order changed since `v` was read, can no longer be edited, or is short of stock", body = ErrorBody),
+        (status = 422, description = "Validation failed", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "orders"
+)]
+pub async fn edit(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+    ValidatedJson(req): ValidatedJson<EditOrderRequest>,
+) -> Result<Json<OrderResponse>, ApiError> {
+    let mid = admin.0.scoped_mid(mid);
+    let edits = req
+        .edits
+        .into_iter()
+        .map(OrderEditRequest::into_edit)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let edited = match OrderEditService::apply(&*state.db, mid, id, req.v, edits).await {
+        Err(OrderError::VersionConflict(current)) => return Err(version_conflict(&state, *current).await),
+        result => result?,
+    };
+    for applied in &edited.edits {
+        let change = Change::new("order", id, applied.edit.action()).diff(&applied.before, &applied.after);
+        audit::record(&state, &admin.0, mid, change).await;
+    }
+    Ok(Json(edited.order.into()))
+}
+
+/// Cancel an order
+///
+/// Orders can be cancelled until any of them ships. Authorized payments
+/// are voided and captured ones refunded through the payment gateway,
+/// which must be configured if the order has any; stock is put back and
+/// the order is stamped with `reason`. Gift card redemptions and coupon
+/// uses are not given back. Sends `order.cancelled`.
+#[utoipa::path(
+    post,
+    path = "/api/orders/{mid}/{id}/cancel",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Order ID")
+    ),
+    request_body = CancelOrderRequest,
+    responses(
+        (status = 200, description = "Order cancelled", body = OrderResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Order not found", body = ErrorBody),
+        (status = 409, description = "Order was already cancelled or has shipped", body = ErrorBody),
+        (status = 422, description = "Unknown reason", body = ErrorBody),
+        (status = 502, description = "Payment gateway refused to void or refund", body = ErrorBody),
+        (status = 503, description = "Order has payments but no payment gateway is configured", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "orders"
+)]
+pub async fn cancel(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
     Path((mid, id)): Path<(i32, i32)>,
-) -> Result<Json<OrderResponse>, StatusCode> {
+    ValidatedJson(req): ValidatedJson<CancelOrderRequest>,
+) -> Result<Json<OrderResponse>, ApiError> {
+    let mid = admin.0.scoped_mid(mid);
+    let reason: CancelReason = req.reason.parse().map_err(ApiError::BadRequest)?;
+    let before = OrderService::find_by_id(&*state.db, mid, id)
+        .await?
+        .ok_or(OrderError::NotFound)?;
+
+    let order = CancellationService::cancel(&state.db, state.payments.as_deref(), mid, id, reason).await?;
+    audit::record(&state, &admin.0, mid, Change::new("order", id, "cancel").diff(&before, &order)).await;
+    let items = OrderItemService::list(&*state.db, mid, id).await?;
+
+    Ok(Json(OrderWithItems { order, items }.into()))
+}
+
+/// 409 carrying the order as it is now, items included
+async fn version_conflict(state: &AppState, current: OrderModel) -> ApiError {
+    let message = OrderError::VersionConflict(Box::new(current.clone())).to_string();
+    match OrderItemService::list(&*state.db, current.mid, current.id).await {
+        Ok(items) => ApiError::VersionConflict {
+            message,
+            current: serde_json::to_value(OrderResponse::from(OrderWithItems { order: current, items }))
+                .unwrap_or_default(),
+        },
+        Err(e) => e.into(),
+    }
+}
+
+/// Make sure an order exists for this merchant before touching its items
+async fn ensure_order(state: &AppState, mid: i32, id: i32) -> Result<(), ApiError> {
     OrderService::find_by_id(&*state.db, mid, id)
+        .await?
+        .map(|_| ())
+        .ok_or_else(|| OrderError::NotFound.into())
+}
+
+/// Look up an item, making sure it belongs to the order in the path
+async fn find_item(state: &AppState, mid: i32, id: i32, item_id: i32) -> Result<OrderItem, ApiError> {
+    OrderItemService::find_by_id(&state.db, mid, item_id)
+        .await?
+        .filter(|item| item.order_id == id)
+        .ok_or_else(|| OrderError::ItemNotFound.into())
+}
+
+/// List the line items of an order
+#[utoipa::path(
+    get,
+    path = "/api/orders/{mid}/{id}/items",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Order ID")
+    ),
+    responses(
+        (status = 200, description = "Order items", body = Vec<OrderItemResponse>),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 404, description = "Order not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "orders"
+)]
+pub async fn list_items(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Json<Vec<OrderItemResponse>>, ApiError> {
+    let mid = claims.scoped_mid(mid);
+    payments::ensure_owner(&state, &claims, mid, id).await?;
+    ensure_order(&state, mid, id).await?;
+
+    OrderItemService::list(&*state.db, mid, id)
+        .await
+        .map(|items| Json(items.into_iter().map(|i| i.into()).collect()))
+        .map_err(ApiError::from)
+}
+
+/// Add a line item to an order
+#[utoipa::path(
+    post,
+    path = "/api/orders/{mid}/{id}/items",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Order ID")
+    ),
+    request_body = OrderItemRequest,
+    responses(
+        (status = 201, description = "Item added", body = OrderItemResponse),
+        (status = 400, description = "Invalid quantity or price", body = ErrorBody),
+        (status = 404, description = "Order not found", body = ErrorBody),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "orders"
+)]
+pub async fn add_item(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+    ValidatedJson(req): ValidatedJson<OrderItemRequest>,
+) -> Result<(StatusCode, Json<OrderItemResponse>), ApiError> {
+    let item = req.into_new_item()?;
+    ensure_order(&state, mid, id).await?;
+
+    let item = OrderItemService::add(&*state.db, mid, id, item).await?;
+    audit::record(&state, &admin.0, mid, Change::new("order_item", item.id, "create").created(&item)).await;
+    Ok((StatusCode::CREATED, Json(item.into())))
+}
+
+/// Change the quantity or unit price of a line item
+#[utoipa::path(
+    put,
+    path = "/api/orders/{mid}/{id}/items/{item_id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Order ID"),
+        ("item_id" = i32, Path, description = "Order item ID")
+    ),
+    request_body = UpdateOrderItemRequest,
+    responses(
+        (status = 200, description = "Item updated", body = OrderItemResponse),
+        (status = 400, description = "Invalid quantity or price", body = ErrorBody),
+        (status = 404, description = "Item not found", body = ErrorBody),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "orders"
+)]
+pub async fn update_item(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id, item_id)): Path<(i32, i32, i32)>,
+    ValidatedJson(req): ValidatedJson<UpdateOrderItemRequest>,
+) -> Result<Json<OrderItemResponse>, ApiError> {
+    let unit_price = req
+        .unit_price
+        .as_deref()
+        .map(|price| parse_decimal("unit_price", price))
+        .transpose()?;
+    if req.quantity.is_some_and(|q| q <= 0) {
+        return Err(invalid_quantity());
+    }
+    let before = find_item(&state, mid, id, item_id).await?;
+
+    let item = OrderItemService::update(&*state.db, mid, item_id, req.quantity, unit_price).await?;
+    audit::record(&state, &admin.0, mid, Change::new("order_item", item_id, "update").diff(&before, &item)).await;
+    Ok(Json(item.into()))
+}
+
+/// Remove a line item from an order
+#[utoipa::path(
+    delete,
+    path = "/api/orders/{mid}/{id}/items/{item_id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Order ID"),
+        ("item_id" = i32, Path, description = "Order item ID")
+    ),
+    responses(
+        (status = 204, description = "Item removed"),
+        (status = 404, description = "Item not found", body = ErrorBody),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "orders"
+)]
+pub async fn remove_item(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id, item_id)): Path<(i32, i32, i32)>,
+) -> Result<StatusCode, ApiError> {
+    let before = find_item(&state, mid, id, item_id).await?;
+
+    OrderItemService::remove(&*state.db, mid, item_id).await?;
+    audit::record(&state, &admin.0, mid, Change::new("order_item", item_id, "delete").removed(&before)).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// List the shipments of an order
+#[utoipa::path(
+    get,
+    path = "/api/orders/{mid}/{id}/shipments",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Order ID")
+    ),
+    responses(
+        (status = 200, description = "Shipments, oldest first", body = Vec<ShipmentResponse>),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 404, description = "Order not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "orders"
+)]
+pub async fn list_shipments(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Json<Vec<ShipmentResponse>>, ApiError> {
+    let mid = claims.scoped_mid(mid);
+    payments::ensure_owner(&state, &claims, mid, id).await?;
+    ensure_order(&state, mid, id).await?;
+
+    OrderService::list_shipments(&state.db, mid, id)
+        .await
+        .map(|shipments| Json(shipments.into_iter().map(|s| s.into()).collect()))
+        .map_err(ApiError::from)
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct TrackingResponse {
+    /// `pre_transit`, `in_transit`, `out_for_delivery`, `delivered`,
+    /// `returned`, `failure` or `unknown`
+    pub status: &'static str,
+    /// The carrier's description of the latest scan
+    pub detail: String,
+    /// When the latest scan happened; absent before the first
+    #[schema(value_type = Option<i64>)]
+    pub updated_gmt: Option<Timestamp>,
+}
+
+impl From<Tracking> for TrackingResponse {
+    fn from(tracking: Tracking) -> Self {
+        Self {
+            status: tracking.status.as_str(),
+            detail: tracking.detail,
+            updated_gmt: tracking.updated_gmt,
+        }
+    }
+}
+
+/// The configured label provider
+fn label_provider(state: &AppState) -> Result<&dyn LabelProvider, ApiError> {
+    state
+        .labels
+        .as_deref()
+        .ok_or_else(|| ApiError::ServiceUnavailable("Shipping labels are not configured".to_string()))
+}
+
+/// Record a shipment of some or all of an order's items
+#[utoipa::path(
+    post,
+    path = "/api/orders/{mid}/{id}/shipments",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Order ID")
+    ),
+    request_body = ShipmentRequest,
+    responses(
+        (status = 201, description = "Shipment recorded; the order is marked shipped once every item has shipped", body = ShipmentResponse),
+        (status = 404, description = "Order or item not found", body = ErrorBody),
+        (status = 409, description = "More than the outstanding quantity, or nothing left to ship", body = ErrorBody),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 422, description = "Missing carrier, invalid quantity or invalid label address", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody),
+        (status = 502, description = "Label service failed", body = ErrorBody),
+        (status = 503, description = "A label was requested but labels are not configured", body = ErrorBody)
+    ),
+    tag = "orders"
+)]
+pub async fn create_shipment(
+    State(state): State<AppState>,
+    _admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+    ValidatedJson(req): ValidatedJson<ShipmentRequest>,
+) -> Result<(StatusCode, Json<ShipmentResponse>), ApiError> {
+    let lines = req
+        .items
+        .iter()
+        .map(|item| ShipmentLine { order_item_id: item.order_item_id, quantity: item.quantity })
+        .collect();
+
+    let shipped = match req.label {
+        Some(label) => {
+            let shipment = LabeledShipment {
+                service: label.service.trim().to_string(),
+                ship_to: label.ship_to.into(),
+                lines,
+            };
+            OrderService::create_labeled_shipment(&state.db, label_provider(&state)?, mid, id, shipment).await?
+        }
+        None => {
+            let shipment = NewShipment {
+                carrier: req.carrier.trim().to_string(),
+                tracking_number: req.tracking_number.trim().to_string(),
+                lines,
+            };
+            OrderService::create_shipment(&state.db, mid, id, shipment).await?
+        }
+    };
+
+    Ok((StatusCode::CREATED, Json(shipped.into())))
+}
+
+/// Void a shipment's label, taking the shipment back
+#[utoipa::path(
+    delete,
+    path = "/api/orders/{mid}/{id}/shipments/{shipment_id}/label",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Order ID"),
+        ("shipment_id" = i32, Path, description = "Shipment ID")
+    ),
+    responses(
+        (status = 204, description = "Label voided; the shipment's items are outstanding again"),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Order or shipment not found", body = ErrorBody),
+        (status = 409, description = "Shipment was not sent with a bought label", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody),
+        (status = 502, description = "Label service refused or failed", body = ErrorBody),
+        (status = 503, description = "Labels are not configured", body = ErrorBody)
+    ),
+    tag = "orders"
+)]
+pub async fn void_label(
+    State(state): State<AppState>,
+    _admin: RequireMerchantAdmin,
+    Path((mid, id, shipment_id)): Path<(i32, i32, i32)>,
+) -> Result<StatusCode, ApiError> {
+    OrderService::void_label(&state.db, label_provider(&state)?, mid, id, shipment_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Latest tracking of a shipment sent with a bought label
+#[utoipa::path(
+    get,
+    path = "/api/orders/{mid}/{id}/shipments/{shipment_id}/tracking",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Order ID"),
+        ("shipment_id" = i32, Path, description = "Shipment ID")
+    ),
+    responses(
+        (status = 200, description = "Latest tracking", body = TrackingResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Order or shipment not found", body = ErrorBody),
+        (status = 409, description = "Shipment was not sent with a bought label", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody),
+        (status = 502, description = "Label service failed", body = ErrorBody),
+        (status = 503, description = "Labels are not configured", body = ErrorBody)
+    ),
+    tag = "orders"
+)]
+pub async fn track_shipment(
+    State(state): State<AppState>,
+    _admin: RequireMerchantAdmin,
+    Path((mid, id, shipment_id)): Path<(i32, i32, i32)>,
+) -> Result<Json<TrackingResponse>, ApiError> {
+    let tracking = OrderService::track_shipment(&state.db, label_provider(&state)?, mid, id, shipment_id).await?;
+    Ok(Json(tracking.into()))
+}
+
+/// Mark a pickup order ready to collect, telling the buyer where with an
+/// `order.ready_for_pickup` webhook. Marking it again changes nothing.
+#[utoipa::path(
+    post,
+    path = "/api/orders/{mid}/{id}/pickup/ready",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Order ID")
+    ),
+    responses(
+        (status = 200, description = "Order is ready for pickup", body = OrderResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Order not found", body = ErrorBody),
+        (status = 409, description = "Order is not for pickup, or was already picked up", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "orders"
+)]
+pub async fn pickup_ready(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Json<OrderResponse>, ApiError> {
+    PickupService::ready(&*state.db, admin.0.scoped_mid(mid), id)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
         .map(|order| Json(order.into()))
-        .ok_or(StatusCode::NOT_FOUND)
+        .map_err(ApiError::from)
 }
 
-/// List orders (placeholder - needs implementation in OrderService)
+/// Record that the buyer collected a pickup order, completing it
+#[utoipa::path(
+    post,
+    path = "/api/orders/{mid}/{id}/pickup/confirm",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Order ID")
+    ),
+    responses(
+        (status = 200, description = "Order picked up and completed", body = OrderResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Order not found", body = ErrorBody),
+        (status = 409, description = "Order is not for pickup, not ready yet, or was already picked up", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "orders"
+)]
+pub async fn pickup_confirm(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Json<OrderResponse>, ApiError> {
+    PickupService::confirm(&*state.db, admin.0.scoped_mid(mid), id)
+        .await
+        .map(|order| Json(order.into()))
+        .map_err(ApiError::from)
+}
+
+/// List a merchant's orders, newest first
+#[utoipa::path(
+    get,
+    path = "/api/orders",
+    params(ListQuery),
+    responses(
+        (status = 200, description = "One page of matching orders", body = OrderListResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 422, description = "Invalid filter, cursor or limit", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "orders"
+)]
 pub async fn list(
     State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
     Query(query): Query<ListQuery>,
-) -> Result<Json<Vec<OrderResponse>>, StatusCode> {
-    // TODO: Implement general list in OrderService
-    Ok(Json(vec![]))
+) -> Result<Json<OrderListResponse>, ApiError> {
+    validation::validate(&query)?;
+
+    let filter = OrderFilter {
+        pool: query.pool,
+        payment_status: query.payment_status.as_deref().map(str::parse).transpose().map_err(ApiError::BadRequest)?,
+        review_status: query.review_status,
+        customer: query.customer,
+        created_from: query.created_from,
+        created_to: query.created_to,
+    };
+    let cursor = query.cursor.as_deref().map(str::parse::<Cursor>).transpose()?;
+
+    let mid = admin.0.scoped_mid(query.mid);
+    let (orders, next) = OrderService::list(&*state.db, mid, &filter, query.limit, cursor.as_ref()).await?;
+    Ok(Json(OrderListResponse {
+        orders: orders.into_iter().map(|o| o.into()).collect(),
+        next_cursor: next.map(|cursor| cursor.to_string()),
+    }))
+}
+
+/// Count a merchant's orders in each pool
+///
+/// Pools come in workflow order, from `RECENT` to `ARCHIVE`, followed by
+/// any pools left over from before the workflow was fixed. List a pool's
+/// orders with `GET /api/orders?pool=`.
+#[utoipa::path(
+    get,
+    path = "/api/orders/pools",
+    params(PoolsQuery),
+    responses(
+        (status = 200, description = "Order count of every pool", body = Vec<PoolCountResponse>),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "orders"
+)]
+pub async fn pools(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Query(query): Query<PoolsQuery>,
+) -> Result<Json<Vec<PoolCountResponse>>, ApiError> {
+    let mid = admin.0.scoped_mid(query.mid);
+    let counts = PoolService::counts(&*state.db, mid).await?;
+    Ok(Json(counts.into_iter().map(PoolCountResponse::from).collect()))
+}
+
+/// Find orders by partial customer details
+#[utoipa::path(
+    get,
+    path = "/api/orders/search",
+    params(SearchQuery),
+    responses(
+        (status = 200, description = "Matching orders, newest first", body = Vec<OrderResponse>),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 422, description = "Search term too short or too long, or invalid limit", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "orders"
+)]
+pub async fn search(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Query(query): Query<SearchQuery>,
+) -> Result<Json<Vec<OrderResponse>>, ApiError> {
+    validation::validate(&query)?;
+
+    let mid = admin.0.scoped_mid(query.mid);
+    let orders = OrderService::search(&*state.db, mid, &query.q, query.limit).await?;
+    Ok(Json(orders.into_iter().map(|o| o.into()).collect()))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rust_decimal::Decimal;
+    use crate::auth::Role;
     use sea_orm::{DatabaseBackend, MockDatabase, MockExecResult};
 
     #[tokio::test]
@@ -146,12 +1469,7 @@ mod tests {
             ])
             .into_connection();
 
-        let state = AppState {
-            db: std::sync::Arc::new(db),
-            cart_store: std::sync::Arc::new(std::sync::Mutex::new(
-                commercerack_cart::CartStore::new()
-            )),
-        };
+        let state = AppState::mock(db);
 
         let req = CreateOrderRequest {
             mid: 1,
@@ -159,11 +1477,372 @@ mod tests {
             cartid: "CART001".to_string(),
             customer: 1,
             pool: "RECENT".to_string(),
-            total: "199.99".to_string(),
+            items: vec![OrderItemRequest {
+                sku: "SKU001".to_string(),
+                product_name: "Widget".to_string(),
+                quantity: 1,
+                unit_price: "199.99".to_string(),
+            }],
         };
 
         // This will fail in mock but validates the structure
-        let result = create(State(state), Json(req)).await;
+        let admin = RequireMerchantAdmin::new(Claims::new(1, 1).with_role(Role::MerchantAdmin));
+        let result = create(State(state), admin, ValidatedJson(req)).await;
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_get_order_includes_items() {
+        let order = OrderModel {
+            id: 9,
+            mid: 1,
+            orderid: "2025-11-18-ABCDEF12".to_string(),
+            cartid: "CART001".to_string(),
+            customer: 1,
+            pool: "RECENT".to_string(),
+            total: Decimal::new(3998, 2),
+            created_gmt: Timestamp::EPOCH,
+            paid_gmt: None,
+            paid_txn: None,
+            order_payment_status: None,
+            order_payment_lookup: None,
+            bs_settlement: None,
+            shipped_gmt: None,
+            inv_gmt: None,
+            company_id: None,
+            review_status: None,
+            ship_method: None,
+            bill_email: String::new(),
+            v: 0,
+            mkt: 0,
+            erefid: None,
+            mkt_bitstr: String::new(),
+            pickup_location_id: None,
+            pickup_ready_gmt: None,
+            picked_up_gmt: None,
+            cancelled_gmt: None,
+            cancel_reason: None,
+        };
+        let item = OrderItem {
+            id: 1,
+            mid: 1,
+            order_id: 9,
+            sku: "SKU001".to_string(),
+            product_name: "Widget".to_string(),
+            quantity: 2,
+            unit_price: Decimal::new(1999, 2),
+            options: String::new(),
+        };
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![order]])
+            .append_query_results([vec![item]])
+            .append_query_results([Vec::<::entity::prelude::OrderFulfillmentGroup>::new()])
+            .into_connection();
+
+        let state = AppState::mock(db);
+
+        let admin = Claims::new(1, 1).with_role(Role::MerchantAdmin);
+        let Json(response) = get(State(state), admin, Path((1, 9))).await.unwrap();
+        assert_eq!(response.items.len(), 1);
+        assert_eq!(response.items[0].line_total, "39.98");
+        assert!(response.fulfillment_groups.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_batch_get_reports_missing_and_archived_orders() {
+        let order = |id| OrderModel {
+            id,
+            mid: 1,
+            orderid: format!("2025-11-18-ORDER{}", id),
+            cartid: String::new(),
+            customer: 1,
+            pool: "RECENT".to_string(),
+            total: Decimal::new(1999, 2),
+            created_gmt: Timestamp::EPOCH,
+            paid_gmt: None,
+            paid_txn: None,
+            order_payment_status: None,
+            order_payment_lookup: None,
+            bs_settlement: None,
+            shipped_gmt: None,
+            inv_gmt: None,
+            company_id: None,
+            review_status: None,
+            ship_method: None,
+            bill_email: String::new(),
+            v: 0,
+            mkt: 0,
+            erefid: None,
+            mkt_bitstr: String::new(),
+            pickup_location_id: None,
+            pickup_ready_gmt: None,
+            picked_up_gmt: None,
+            cancelled_gmt: None,
+            cancel_reason: None,
+        };
+        let item = OrderItem {
+            id: 1,
+            mid: 1,
+            order_id: 4,
+            sku: "SKU001".to_string(),
+            product_name: "Widget".to_string(),
+            quantity: 1,
+            unit_price: Decimal::new(1999, 2),
+            options: String::new(),
+        };
+        let archived = std::collections::BTreeMap::from([("order_id", sea_orm::Value::from(7))]);
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![order(9), order(4)]])
+            .append_query_results([vec![item]])
+            .append_query_results([vec![archived]])
+            .into_connection();
+        let state = AppState::mock(db);
+        let admin = RequireMerchantAdmin::new(Claims::new(1, 1).with_role(Role::MerchantAdmin));
+
+        let req = BatchGetRequest { mid: 1, ids: vec![4, 7, 9, 4, 8] };
+        let Json(response) = batch_get(State(state), admin, ValidatedJson(req)).await.unwrap();
+        let ids: Vec<_> = response.orders.iter().map(|o| (o.id, o.items.len())).collect();
+        assert_eq!(ids, vec![(4, 1), (9, 0)]);
+        let errors: Vec<_> = response.errors.iter().map(|e| (e.id, e.code)).collect();
+        assert_eq!(errors, vec![(7, "archived"), (8, "not_found")]);
+    }
+
+    #[tokio::test]
+    async fn test_update_with_stale_version_returns_current_order() {
+        let order = OrderModel {
+            id: 9,
+            mid: 1,
+            orderid: "2025-11-18-ABCDEF12".to_string(),
+            cartid: "CART001".to_string(),
+            customer: 1,
+            pool: "RECENT".to_string(),
+            total: Decimal::new(3998, 2),
+            created_gmt: Timestamp::EPOCH,
+            paid_gmt: Some(Timestamp::from_unix(100)),
+            paid_txn: None,
+            order_payment_status: None,
+            order_payment_lookup: None,
+            bs_settlement: None,
+            shipped_gmt: None,
+            inv_gmt: None,
+            company_id: None,
+            review_status: None,
+            ship_method: None,
+            bill_email: String::new(),
+            v: 2,
+            mkt: 0,
+            erefid: None,
+            mkt_bitstr: String::new(),
+            pickup_location_id: None,
+            pickup_ready_gmt: None,
+            picked_up_gmt: None,
+            cancelled_gmt: None,
+            cancel_reason: None,
+        };
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![order]])
+            .append_query_results([Vec::<OrderItem>::new()])
+            .into_connection();
+
+        let state = AppState::mock(db);
+
+        let req = UpdateOrderRequest {
+            v: 1,
+            pool: Some("ARCHIVE".to_string()),
+            review_status: None,
+            ship_method: None,
+            bill_email: None,
+        };
+        let admin = RequireMerchantAdmin::new(Claims::new(1, 1).with_role(Role::MerchantAdmin));
+        let err = update(State(state), admin, Path((1, 9)), ValidatedJson(req)).await.err().unwrap();
+        assert_eq!(err.status(), StatusCode::CONFLICT);
+
+        let body = err.body();
+        assert_eq!(body.code, "version_conflict");
+        let current = body.current.unwrap();
+        assert_eq!(current["v"], 2);
+        assert_eq!(current["pool"], "RECENT");
+        assert_eq!(current["paid_gmt"], 100);
+    }
+
+    #[tokio::test]
+    async fn test_create_shipment_rejects_over_shipment() {
+        let order = OrderModel {
+            id: 9,
+            mid: 1,
+            orderid: "2025-11-18-ABCDEF12".to_string(),
+            cartid: "CART001".to_string(),
+            customer: 1,
+            pool: "RECENT".to_string(),
+            total: Decimal::new(3998, 2),
+            created_gmt: Timestamp::EPOCH,
+            paid_gmt: None,
+            paid_txn: None,
+            order_payment_status: None,
+            order_payment_lookup: None,
+            bs_settlement: None,
+            shipped_gmt: None,
+            inv_gmt: None,
+            company_id: None,
+            review_status: None,
+            ship_method: None,
+            bill_email: String::new(),
+            v: 0,
+            mkt: 0,
+            erefid: None,
+            mkt_bitstr: String::new(),
+            pickup_location_id: None,
+            pickup_ready_gmt: None,
+            picked_up_gmt: None,
+            cancelled_gmt: None,
+            cancel_reason: None,
+        };
+        let item = OrderItem {
+            id: 1,
+            mid: 1,
+            order_id: 9,
+            sku: "SKU001".to_string(),
+            product_name: "Widget".to_string(),
+            quantity: 2,
+            unit_price: Decimal::new(1999, 2),
+            options: String::new(),
+        };
+        let shipped = ShipmentItem { id: 1, mid: 1, shipment_id: 1, order_item_id: 1, quantity: 1 };
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![order]])
+            .append_query_results([vec![item]])
+            .append_query_results([vec![shipped]])
+            .into_connection();
+
+        let state = AppState::mock(db);
+
+        let req = ShipmentRequest {
+            carrier: "UPS".to_string(),
+            tracking_number: "1Z999".to_string(),
+            items: vec![ShipmentItemRequest { order_item_id: 1, quantity: 2 }],
+            label: None,
+        };
+        let admin = RequireMerchantAdmin::new(Claims::new(1, 1).with_role(Role::MerchantAdmin));
+        let result = create_shipment(State(state), admin, Path((1, 9)), ValidatedJson(req)).await;
+        assert_eq!(result.err().map(|e| e.status()), Some(StatusCode::CONFLICT));
+    }
+
+    #[tokio::test]
+    async fn test_labeled_shipment_needs_a_label_provider() {
+        let state = AppState::mock(MockDatabase::new(DatabaseBackend::Postgres).into_connection());
+        let label = || ShipmentLabelRequest {
+            service: "ground".to_string(),
+            ship_to: LabelAddressRequest {
+                name: "Pat Doe".to_string(),
+                street1: "1 Main St".to_string(),
+                street2: String::new(),
+                city: "Portland".to_string(),
+                state: "or".to_string(),
+                zip: "97201".to_string(),
+                country: "us".to_string(),
+            },
+        };
+
+        // A label supplies the carrier and tracking number
+        let mixed = ShipmentRequest {
+            carrier: "UPS".to_string(),
+            tracking_number: String::new(),
+            items: Vec::new(),
+            label: Some(label()),
+        };
+        match validation::validate(&mixed) {
+            Err(ApiError::Validation(errors)) => assert_eq!(errors[0].field, "label"),
+            other => panic!("unexpected result {:?}", other.err().map(|e| e.status())),
+        }
+
+        let req = ShipmentRequest { carrier: String::new(), ..mixed };
+        assert!(validation::validate(&req).is_ok());
+        let admin = RequireMerchantAdmin::new(Claims::new(1, 1).with_role(Role::MerchantAdmin));
+        let result = create_shipment(State(state), admin, Path((1, 9)), ValidatedJson(req)).await;
+        assert_eq!(result.err().map(|e| e.status()), Some(StatusCode::SERVICE_UNAVAILABLE));
+    }
+
+    #[test]
+    fn test_list_query_validation() {
+        let query = ListQuery {
+            mid: 1,
+            pool: None,
+            payment_status: Some("settled".to_string()),
+            review_status: None,
+            customer: None,
+            created_from: Some(Timestamp::from_unix(200)),
+            created_to: Some(Timestamp::from_unix(100)),
+            limit: 20,
+            cursor: Some("page2".to_string()),
+        };
+        match validation::validate(&query) {
+            Err(ApiError::Validation(errors)) => {
+                let fields: Vec<_> = errors.iter().map(|e| e.field.as_str()).collect();
+                assert_eq!(fields, vec!["payment_status", "cursor", "created_to"]);
+            }
+            other => panic!("unexpected result {:?}", other.err().map(|e| e.status())),
+        }
+    }
+
+    #[test]
+    fn test_search_query_needs_three_characters() {
+        let query = SearchQuery { mid: 1, q: " ab ".to_string(), limit: 20 };
+        assert!(validation::validate(&query).is_err());
+
+        let query = SearchQuery { mid: 1, q: "4242".to_string(), limit: 20 };
+        assert!(validation::validate(&query).is_ok());
+    }
+
+    #[test]
+    fn test_update_needs_known_pool() {
+        let update = |pool: &str| UpdateOrderRequest {
+            v: 1,
+            pool: Some(pool.to_string()),
+            review_status: None,
+            ship_method: None,
+            bill_email: None,
+        };
+        assert!(validation::validate(&update(" ARCHIVE ")).is_ok());
+        assert!(validation::validate(&update("archive")).is_err());
+        assert!(validation::validate(&update("SHIPPED")).is_err());
+    }
+
+    #[test]
+    fn test_edit_request_validation() {
+        let req: EditOrderRequest = serde_json::from_value(serde_json::json!({
+            "v": 3,
+            "edits": [
+                { "type": "add_item", "sku": "%SHIP", "product_name": "Ground", "quantity": 1, "unit_price": "5.00" },
+                { "type": "set_quantity", "item_id": 4, "quantity": 0 },
+                { "type": "remove_item", "item_id": 5 },
+                { "type": "discount", "amount": "-2.00" }
+            ]
+        }))
+        .unwrap();
+        match validation::validate(&req) {
+            Err(ApiError::Validation(errors)) => {
+                let fields: Vec<_> = errors.iter().map(|e| e.field.as_str()).collect();
+                assert_eq!(fields, vec!["edits[0].sku", "edits[1].quantity", "edits[3].amount"]);
+            }
+            other => panic!("unexpected result {:?}", other.err().map(|e| e.status())),
+        }
+
+        let discount = OrderEditRequest::Discount { amount: " 2.50 ".to_string(), reason: "Late delivery".to_string() };
+        assert_eq!(
+            discount.into_edit().unwrap(),
+            OrderEdit::Discount { amount: rust_decimal::Decimal::new(250, 2), reason: "Late delivery".to_string() }
+        );
+    }
+
+    #[test]
+    fn test_cancel_request_needs_a_known_reason() {
+        let req = CancelOrderRequest { reason: "out_of_stock".to_string() };
+        assert!(validation::validate(&req).is_ok());
+
+        let req = CancelOrderRequest { reason: "changed_mind".to_string() };
+        match validation::validate(&req) {
+            Err(ApiError::Validation(errors)) => assert_eq!(errors[0].field, "reason"),
+            other => panic!("unexpected result {:?}", other.err().map(|e| e.status())),
+        }
+    }
 }
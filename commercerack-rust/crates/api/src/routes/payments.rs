@@ -0,0 +1,471 @@
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use commercerack_audit::Change;
+use commercerack_core::Timestamp;
+use commercerack_customer::companies::CompanyService;
+use commercerack_order::dunning::DunningService;
+use commercerack_order::payment::{self, OrderPaymentError, OrderPaymentService};
+use commercerack_order::{OrderError, OrderService};
+use commercerack_payment::{PaymentError, PaymentGateway};
+use entity::prelude::{Order as OrderModel, OrderPayment};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tracing::warn;
+use crate::auth::{Claims, RequireMerchantAdmin, Role};
+use crate::routes::orders::OrderResponse;
+use crate::error::{ApiError, ErrorBody};
+use crate::routes::{audit, parse_decimal};
+use crate::validation::{ValidatedJson, Validate, Validator};
+use crate::AppState;
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct PayRequest {
+    /// Tokenized payment method from the payment provider's client library
+    pub payment_method: String,
+    /// Capture immediately; otherwise the funds are only authorized
+    #[serde(default = "default_capture")]
+    pub capture: bool,
+}
+
+impl Validate for PayRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("payment_method", &self.payment_method, 255);
+    }
+}
+
+fn default_capture() -> bool {
+    true
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct AddPaymentRequest {
+    /// Tokenized payment method from the payment provider's client library
+    pub payment_method: String,
+    /// Part of the open balance to charge; all of it when omitted
+    pub amount: Option<String>,
+    /// Capture immediately; otherwise the funds are only authorized
+    #[serde(default = "default_capture")]
+    pub capture: bool,
+    /// If the charge is declined, charge the open balance to the same
+    /// payment method again on the merchant's dunning schedule
+    #[serde(default)]
+    pub dunning: bool,
+}
+
+impl Validate for AddPaymentRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("payment_method", &self.payment_method, 255);
+        if let Some(amount) = &self.amount {
+            v.positive_amount("amount", amount);
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct RefundRequest {
+    /// Amount to refund; the full order total when omitted
+    pub amount: Option<String>,
+}
+
+impl Validate for RefundRequest {
+    fn validate(&self, v: &mut Validator) {
+        if let Some(amount) = &self.amount {
+            v.positive_amount("amount", amount);
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct SessionRequest {
+    /// Capture once approved; otherwise the funds are only authorized
+    #[serde(default = "default_capture")]
+    pub capture: bool,
+}
+
+impl Validate for SessionRequest {
+    fn validate(&self, _v: &mut Validator) {}
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct SessionResponse {
+    /// Pass as `payment_method` to the pay endpoint once the buyer approved
+    pub id: String,
+    /// Where to send the buyer for approval
+    pub approve_url: Option<String>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct PaymentResponse {
+    pub id: i32,
+    /// Gateway transaction reference
+    pub reference: Option<String>,
+    pub amount: String,
+    pub refunded: String,
+    /// `authorized`, `captured`, `pending`, `declined`, `refunded` or `voided`
+    pub status: String,
+    #[schema(value_type = i64)]
+    pub created_gmt: Timestamp,
+    #[schema(value_type = i64)]
+    pub modified_gmt: Timestamp,
+}
+
+impl From<OrderPayment> for PaymentResponse {
+    fn from(payment: OrderPayment) -> Self {
+        Self {
+            id: payment.id,
+            reference: payment.reference,
+            amount: payment.amount.to_string(),
+            refunded: payment.refunded.to_string(),
+            status: payment.status,
+            created_gmt: payment.created_gmt,
+            modified_gmt: payment.modified_gmt,
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct OrderPaymentsResponse {
+    pub order_id: i32,
+    pub total: String,
+    /// What is left to pay
+    pub balance: String,
+    pub order_payment_status: Option<String>,
+    /// Oldest first
+    pub payments: Vec<PaymentResponse>,
+}
+
+impl OrderPaymentsResponse {
+    fn new(order: &OrderModel, payments: Vec<OrderPayment>) -> Self {
+        Self {
+            order_id: order.id,
+            total: order.total.to_string(),
+            balance: payment::balance(order.total, &payments).to_string(),
+            order_payment_status: order.order_payment_status.clone(),
+            payments: payments.into_iter().map(|p| p.into()).collect(),
+        }
+    }
+}
+
+pub(crate) fn gateway(state: &AppState) -> Result<&dyn PaymentGateway, ApiError> {
+    state
+        .payments
+        .as_deref()
+        .ok_or_else(|| ApiError::ServiceUnavailable("No payment gateway is configured".to_string()))
+}
+
+/// Shoppers may only pay for their own orders, or those placed for the
+/// company they buy for
+pub(crate) async fn ensure_owner(state: &AppState, claims: &Claims, mid: i32, id: i32) -> Result<(), ApiError> {
+    if claims.role != Role::Customer {
+        return Ok(());
+    }
+
+    let order = OrderService::find_by_id(&*state.db, mid, id)
+        .await?
+        .ok_or(OrderError::NotFound)?;
+    if order.customer.to_string() == claims.sub {
+        return Ok(());
+    }
+    let Some(company_id) = order.company_id else {
+        return Err(OrderError::NotFound.into());
+    };
+    let cid = claims.sub.parse().map_err(|_| OrderError::NotFound)?;
+    match CompanyService::membership(&*state.db, mid, cid).await? {
+        Some((company, _)) if company.id == company_id => Ok(()),
+        _ => Err(OrderError::NotFound.into()),
+    }
+}
+
+/// Pay for an order
+#[utoipa::path(
+    post,
+    path = "/api/orders/{mid}/{id}/pay",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Order ID")
+    ),
+    request_body = PayRequest,
+    responses(
+        (status = 200, description = "Payment authorized or captured", body = OrderResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 402, description = "Payment declined", body = ErrorBody),
+        (status = 404, description = "Order not found", body = ErrorBody),
+        (status = 409, description = "Order is already paid", body = ErrorBody),
+        (status = 502, description = "Payment gateway error", body = ErrorBody),
+        (status = 503, description = "No payment gateway configured", body = ErrorBody)
+    ),
+    tag = "payments"
+)]
+pub async fn pay(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((mid, id)): Path<(i32, i32)>,
+    ValidatedJson(req): ValidatedJson<PayRequest>,
+) -> Result<Json<OrderResponse>, ApiError> {
+    let gateway = gateway(&state)?;
+    ensure_owner(&state, &claims, mid, id).await?;
+
+    OrderPaymentService::pay(&state.db, gateway, mid, id, &req.payment_method, req.capture)
+        .await
+        .map(|order| Json(order.into()))
+        .map_err(ApiError::from)
+}
+
+/// An order's payments and what is left to pay
+#[utoipa::path(
+    get,
+    path = "/api/orders/{mid}/{id}/payments",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Order ID")
+    ),
+    responses(
+        (status = 200, description = "Payments, oldest first", body = OrderPaymentsResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 404, description = "Order not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "payments"
+)]
+pub async fn list_payments(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Json<OrderPaymentsResponse>, ApiError> {
+    ensure_owner(&state, &claims, mid, id).await?;
+
+    let order = OrderService::find_by_id(&*state.db, mid, id)
+        .await?
+        .ok_or(OrderError::NotFound)?;
+    let payments = OrderPaymentService::payments(&*state.db, mid, id).await?;
+    Ok(Json(OrderPaymentsResponse::new(&order, payments)))
+}
+
+/// Pay part or all of an order's open balance, e.g. a deposit
+///
+/// With `dunning` set, a declined charge still fails with 402, and the
+/// order's open balance is retried on the merchant's schedule.
+#[utoipa::path(
+    post,
+    path = "/api/orders/{mid}/{id}/payments",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Order ID")
+    ),
+    request_body = AddPaymentRequest,
+    responses(
+        (status = 201, description = "Payment authorized or captured", body = OrderPaymentsResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 402, description = "Payment declined", body = ErrorBody),
+        (status = 404, description = "Order not found", body = ErrorBody),
+        (status = 409, description = "Nothing left to pay", body = ErrorBody),
+        (status = 422, description = "Amount is more than the open balance", body = ErrorBody),
+        (status = 502, description = "Payment gateway error", body = ErrorBody),
+        (status = 503, description = "No payment gateway configured", body = ErrorBody)
+    ),
+    tag = "payments"
+)]
+pub async fn add_payment(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((mid, id)): Path<(i32, i32)>,
+    ValidatedJson(req): ValidatedJson<AddPaymentRequest>,
+) -> Result<(StatusCode, Json<OrderPaymentsResponse>), ApiError> {
+    let gateway = gateway(&state)?;
+    ensure_owner(&state, &claims, mid, id).await?;
+    let amount = req
+        .amount
+        .as_deref()
+        .map(|amount| parse_decimal("amount", amount))
+        .transpose()?;
+
+    let charged =
+        OrderPaymentService::add_payment(&state.db, gateway, mid, id, &req.payment_method, amount, req.capture).await;
+    let order = match charged {
+        Err(OrderPaymentError::Gateway(PaymentError::Declined(reason))) if req.dunning => {
+            if let Err(e) = DunningService::open(&*state.db, mid, id, &req.payment_method, &reason).await {
+                warn!(mid, id, "could not start retrying a declined payment: {}", e);
+            }
+            return Err(OrderPaymentError::Gateway(PaymentError::Declined(reason)).into());
+        }
+        charged => charged?,
+    };
+    let payments = OrderPaymentService::payments(&*state.db, mid, id).await?;
+    Ok((StatusCode::CREATED, Json(OrderPaymentsResponse::new(&order, payments))))
+}
+
+/// Capture an order's authorized payments
+#[utoipa::path(
+    post,
+    path = "/api/orders/{mid}/{id}/capture",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Order ID")
+    ),
+    responses(
+        (status = 200, description = "Payment captured", body = OrderResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Order not found", body = ErrorBody),
+        (status = 409, description = "Order has no authorized payment", body = ErrorBody),
+        (status = 502, description = "Payment gateway error", body = ErrorBody),
+        (status = 503, description = "No payment gateway configured", body = ErrorBody)
+    ),
+    tag = "payments"
+)]
+pub async fn capture(
+    State(state): State<AppState>,
+    _admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Json<OrderResponse>, ApiError> {
+    let gateway = gateway(&state)?;
+
+    OrderPaymentService::capture(&state.db, gateway, mid, id)
+        .await
+        .map(|order| Json(order.into()))
+        .map_err(ApiError::from)
+}
+
+/// Refund a paid order, newest payment first, or void uncaptured authorizations
+#[utoipa::path(
+    post,
+    path = "/api/orders/{mid}/{id}/refund",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Order ID")
+    ),
+    request_body = RefundRequest,
+    responses(
+        (status = 200, description = "Payment refunded or voided", body = OrderResponse),
+        (status = 400, description = "Invalid amount", body = ErrorBody),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Order not found", body = ErrorBody),
+        (status = 409, description = "Order has no payment to refund", body = ErrorBody),
+        (status = 502, description = "Payment gateway error", body = ErrorBody),
+        (status = 503, description = "No payment gateway configured", body = ErrorBody)
+    ),
+    tag = "payments"
+)]
+pub async fn refund(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+    ValidatedJson(req): ValidatedJson<RefundRequest>,
+) -> Result<Json<OrderResponse>, ApiError> {
+    let gateway = gateway(&state)?;
+    let amount = req
+        .amount
+        .as_deref()
+        .map(|amount| parse_decimal("amount", amount))
+        .transpose()?;
+    let before = OrderService::find_by_id(&*state.db, mid, id)
+        .await?
+        .ok_or(OrderError::NotFound)?;
+
+    let order = OrderPaymentService::refund(&state.db, gateway, mid, id, amount).await?;
+    audit::record(&state, &admin.0, mid, Change::new("order", id, "refund").diff(&before, &order)).await;
+    Ok(Json(order.into()))
+}
+
+/// Start a buyer-approved payment (e.g. PayPal) for an order
+#[utoipa::path(
+    post,
+    path = "/api/orders/{mid}/{id}/payment-session",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Order ID")
+    ),
+    request_body = SessionRequest,
+    responses(
+        (status = 201, description = "Session created", body = SessionResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 404, description = "Order not found", body = ErrorBody),
+        (status = 409, description = "Order is already paid", body = ErrorBody),
+        (status = 501, description = "Gateway charges payment methods directly", body = ErrorBody),
+        (status = 502, description = "Payment gateway error", body = ErrorBody),
+        (status = 503, description = "No payment gateway configured", body = ErrorBody)
+    ),
+    tag = "payments"
+)]
+pub async fn create_session(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((mid, id)): Path<(i32, i32)>,
+    ValidatedJson(req): ValidatedJson<SessionRequest>,
+) -> Result<(StatusCode, Json<SessionResponse>), ApiError> {
+    let gateway = gateway(&state)?;
+    ensure_owner(&state, &claims, mid, id).await?;
+
+    let session = OrderPaymentService::create_session(&state.db, gateway, mid, id, req.capture)
+        .await
+        .map_err(ApiError::from)?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(SessionResponse {
+            id: session.id,
+            approve_url: session.approve_url,
+        }),
+    ))
+}
+
+/// Receive asynchronous payment notifications from the gateway
+#[utoipa::path(
+    post,
+    path = "/api/payments/webhook",
+    request_body = String,
+    responses(
+        (status = 200, description = "Notification processed or ignored"),
+        (status = 400, description = "Signature verification failed or malformed event", body = ErrorBody),
+        (status = 501, description = "Gateway does not send webhooks", body = ErrorBody),
+        (status = 503, description = "No payment gateway configured", body = ErrorBody)
+    ),
+    tag = "payments"
+)]
+pub async fn webhook(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: String,
+) -> Result<StatusCode, ApiError> {
+    let gateway = gateway(&state)?;
+
+    let headers: HashMap<String, String> = headers
+        .iter()
+        .filter_map(|(name, value)| Some((name.as_str().to_string(), value.to_str().ok()?.to_string())))
+        .collect();
+
+    let txn = gateway.parse_webhook(&headers, &body).await.map_err(|e| match e {
+        PaymentError::Unsupported(_) | PaymentError::Http(_) => e.into(),
+        // Anything else means the notification itself is bad
+        _ => ApiError::BadRequest(e.to_string()),
+    })?;
+
+    if let Some(txn) = txn {
+        OrderPaymentService::apply_notification(&state.db, &txn).await?;
+    }
+
+    Ok(StatusCode::OK)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sea_orm::{DatabaseBackend, MockDatabase};
+
+    #[tokio::test]
+    async fn test_pay_without_gateway() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+        let state = AppState::mock(db);
+        let req = PayRequest {
+            payment_method: "pm_card_visa".to_string(),
+            capture: true,
+        };
+
+        let result = pay(State(state), Claims::new(1, 1), Path((1, 9)), ValidatedJson(req)).await;
+        assert_eq!(result.err().map(|e| e.status()), Some(StatusCode::SERVICE_UNAVAILABLE));
+    }
+}
@@ -0,0 +1,163 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use commercerack_audit::Change;
+use commercerack_core::Timestamp;
+use commercerack_product::pricing::{NewPriceTier, PricingService};
+use ::entity::prelude::PriceTier;
+use serde::{Deserialize, Serialize};
+use crate::auth::RequireMerchantAdmin;
+use crate::error::{ApiError, ErrorBody};
+use crate::routes::{audit, parse_decimal};
+use crate::validation::{ValidatedJson, Validate, Validator};
+use crate::AppState;
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct PriceTierRequest {
+    pub mid: i32,
+    pub sku: String,
+    /// Customer price group the tier is for; omit for every shopper
+    #[serde(default)]
+    pub price_group: String,
+    /// Quantity from which the tier applies
+    #[serde(default = "default_min_qty")]
+    pub min_qty: i32,
+    /// Unit price as a decimal string
+    pub price: String,
+}
+
+impl Validate for PriceTierRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("sku", &self.sku, 45)
+            .max_len("price_group", &self.price_group, 20)
+            .positive("min_qty", self.min_qty)
+            .amount("price", &self.price);
+    }
+}
+
+fn default_min_qty() -> i32 {
+    1
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct PriceTierResponse {
+    pub id: i32,
+    pub mid: i32,
+    pub sku: String,
+    /// Empty when every shopper gets the tier
+    pub price_group: String,
+    pub min_qty: i32,
+    pub price: String,
+    #[schema(value_type = i64)]
+    pub created_gmt: Timestamp,
+    #[schema(value_type = i64)]
+    pub modified_gmt: Timestamp,
+}
+
+impl From<PriceTier> for PriceTierResponse {
+    fn from(tier: PriceTier) -> Self {
+        Self {
+            id: tier.id,
+            mid: tier.mid,
+            sku: tier.sku,
+            price_group: tier.price_group,
+            min_qty: tier.min_qty,
+            price: tier.price.to_string(),
+            created_gmt: tier.created_gmt,
+            modified_gmt: tier.modified_gmt,
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct ListQuery {
+    pub mid: i32,
+    /// Only tiers of this SKU
+    pub sku: Option<String>,
+}
+
+/// Add a price tier, or change the price of the tier with the same SKU,
+/// group and minimum quantity
+#[utoipa::path(
+    post,
+    path = "/api/price-tiers",
+    request_body = PriceTierRequest,
+    responses(
+        (status = 201, description = "Tier saved", body = PriceTierResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 422, description = "Unknown SKU, or invalid quantity or price", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "pricing"
+)]
+pub async fn set_tier(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    ValidatedJson(req): ValidatedJson<PriceTierRequest>,
+) -> Result<(StatusCode, Json<PriceTierResponse>), ApiError> {
+    let mid = admin.0.scoped_mid(req.mid);
+    let tier = NewPriceTier {
+        price: parse_decimal("price", &req.price)?,
+        sku: req.sku,
+        price_group: req.price_group.trim().to_string(),
+        min_qty: req.min_qty,
+    };
+
+    let tier = PricingService::set_tier(&*state.db, mid, tier).await?;
+    audit::record(&state, &admin.0, mid, Change::new("price_tier", tier.id, "set").created(&tier)).await;
+    Ok((StatusCode::CREATED, Json(tier.into())))
+}
+
+/// A merchant's price tiers
+#[utoipa::path(
+    get,
+    path = "/api/price-tiers",
+    params(ListQuery),
+    responses(
+        (status = 200, description = "Tiers by SKU, group and minimum quantity", body = Vec<PriceTierResponse>),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "pricing"
+)]
+pub async fn list_tiers(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Query(query): Query<ListQuery>,
+) -> Result<Json<Vec<PriceTierResponse>>, ApiError> {
+    PricingService::list_tiers(&*state.db, admin.0.scoped_mid(query.mid), query.sku.as_deref())
+        .await
+        .map(|tiers| Json(tiers.into_iter().map(|t| t.into()).collect()))
+        .map_err(ApiError::from)
+}
+
+/// Delete a price tier
+#[utoipa::path(
+    delete,
+    path = "/api/price-tiers/{mid}/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Price tier ID")
+    ),
+    responses(
+        (status = 204, description = "Tier deleted"),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Tier not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "pricing"
+)]
+pub async fn delete_tier(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<StatusCode, ApiError> {
+    PricingService::delete_tier(&*state.db, mid, id).await?;
+    audit::record(&state, &admin.0, mid, Change::new("price_tier", id, "delete")).await;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,196 @@
+//! Customer data export and erasure
+//!
+//! Both run in the background: the POST answers 202 with the request,
+//! which is polled until its status is `done`. A finished export is then
+//! downloaded as a JSON archive.
+
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use commercerack_core::Timestamp;
+use commercerack_customer::privacy::{DataRequestKind, PrivacyService, STATUS_PENDING};
+use commercerack_jobs::privacy::ProcessDataRequest;
+use commercerack_jobs::JobQueue;
+use ::entity::prelude::CustomerDataRequest;
+use sea_orm::TransactionTrait;
+use serde::Serialize;
+use crate::auth::{ActiveSession, Claims};
+use crate::error::{ApiError, ErrorBody};
+use crate::AppState;
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct DataRequestResponse {
+    pub id: i32,
+    pub cid: i32,
+    /// `export` or `erasure`
+    pub kind: String,
+    /// `pending` or `done`
+    pub status: String,
+    /// Whether an export archive can be downloaded
+    pub ready: bool,
+    #[schema(value_type = i64)]
+    pub created_gmt: Timestamp,
+    #[schema(value_type = Option<i64>)]
+    pub completed_gmt: Option<Timestamp>,
+}
+
+impl From<CustomerDataRequest> for DataRequestResponse {
+    fn from(request: CustomerDataRequest) -> Self {
+        Self {
+            id: request.id,
+            cid: request.cid,
+            kind: request.kind,
+            status: request.status,
+            ready: request.archive.is_some(),
+            created_gmt: request.created_gmt,
+            completed_gmt: request.completed_gmt,
+        }
+    }
+}
+
+/// Record a request and queue its job together, unless one is already
+/// pending
+async fn submit(
+    state: &AppState,
+    claims: &Claims,
+    mid: i32,
+    cid: i32,
+    kind: DataRequestKind,
+) -> Result<(StatusCode, Json<DataRequestResponse>), ApiError> {
+    claims.ensure_self(cid)?;
+    let mid = claims.scoped_mid(mid);
+
+    if let Some(pending) = PrivacyService::pending(&*state.db, mid, cid, kind).await? {
+        return Ok((StatusCode::ACCEPTED, Json(pending.into())));
+    }
+    let txn = state.db.begin().await?;
+    let request = PrivacyService::request(&txn, mid, cid, kind).await?;
+    JobQueue::enqueue(&txn, &ProcessDataRequest { request_id: request.id }).await?;
+    txn.commit().await?;
+    Ok((StatusCode::ACCEPTED, Json(request.into())))
+}
+
+/// Export everything stored about a customer
+#[utoipa::path(
+    post,
+    path = "/api/customers/{mid}/{cid}/data-export",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("cid" = i32, Path, description = "Customer ID")
+    ),
+    responses(
+        (status = 202, description = "Export queued, or the one already pending", body = DataRequestResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Another customer's data", body = ErrorBody),
+        (status = 404, description = "Customer not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "customers"
+)]
+pub async fn export(
+    State(state): State<AppState>,
+    ActiveSession(claims): ActiveSession,
+    Path((mid, cid)): Path<(i32, i32)>,
+) -> Result<(StatusCode, Json<DataRequestResponse>), ApiError> {
+    submit(&state, &claims, mid, cid, DataRequestKind::Export).await
+}
+
+/// Erase a customer's personal data, keeping their orders' financial records
+#[utoipa::path(
+    post,
+    path = "/api/customers/{mid}/{cid}/erasure",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("cid" = i32, Path, description = "Customer ID")
+    ),
+    responses(
+        (status = 202, description = "Erasure queued, or the one already pending", body = DataRequestResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Another customer's data", body = ErrorBody),
+        (status = 404, description = "Customer not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "customers"
+)]
+pub async fn erasure(
+    State(state): State<AppState>,
+    ActiveSession(claims): ActiveSession,
+    Path((mid, cid)): Path<(i32, i32)>,
+) -> Result<(StatusCode, Json<DataRequestResponse>), ApiError> {
+    submit(&state, &claims, mid, cid, DataRequestKind::Erasure).await
+}
+
+/// Status of an export or erasure
+#[utoipa::path(
+    get,
+    path = "/api/customers/{mid}/{cid}/data-requests/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("cid" = i32, Path, description = "Customer ID"),
+        ("id" = i32, Path, description = "Data request ID")
+    ),
+    responses(
+        (status = 200, description = "Request status", body = DataRequestResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Another customer's data", body = ErrorBody),
+        (status = 404, description = "Data request not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "customers"
+)]
+pub async fn get(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((mid, cid, id)): Path<(i32, i32, i32)>,
+) -> Result<Json<DataRequestResponse>, ApiError> {
+    claims.ensure_self(cid)?;
+    let request = PrivacyService::find(&*state.db, claims.scoped_mid(mid), cid, id).await?;
+    Ok(Json(request.into()))
+}
+
+/// Download a finished export
+#[utoipa::path(
+    get,
+    path = "/api/customers/{mid}/{cid}/data-requests/{id}/download",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("cid" = i32, Path, description = "Customer ID"),
+        ("id" = i32, Path, description = "Data request ID")
+    ),
+    responses(
+        (status = 200, description = "Profile, addresses, orders, wishlist, notes and tags", content_type = "application/json"),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Another customer's data", body = ErrorBody),
+        (status = 404, description = "Data request not found", body = ErrorBody),
+        (status = 409, description = "Not an export, still pending, or erased since", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "customers"
+)]
+pub async fn download(
+    State(state): State<AppState>,
+    ActiveSession(claims): ActiveSession,
+    Path((mid, cid, id)): Path<(i32, i32, i32)>,
+) -> Result<Response, ApiError> {
+    claims.ensure_self(cid)?;
+    let request = PrivacyService::find(&*state.db, claims.scoped_mid(mid), cid, id).await?;
+    let archive = request.archive.ok_or_else(|| {
+        ApiError::Conflict(match request.status.as_str() {
+            STATUS_PENDING => "Export has not finished yet".to_string(),
+            _ => "No export archive to download".to_string(),
+        })
+    })?;
+
+    let disposition = format!("attachment; filename=\"customer-{}-{}-export-{}.json\"", request.mid, cid, request.id);
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/json".to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        archive,
+    )
+        .into_response())
+}
@@ -1,12 +1,32 @@
 use axum::{
+    body::{Body, Bytes},
     extract::{Path, Query, State},
-    http::StatusCode,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
     Json,
 };
-use commercerack_product::ProductService;
+use commercerack_core::Timestamp;
+use commercerack_db::pagination::Cursor;
+use commercerack_product::export::{self, ExportFormat, EXPORT_PAGE_SIZE};
+use commercerack_product::availability::Visibility;
+use commercerack_product::media::MediaService;
+use commercerack_product::{ProductError, ProductSearch, ProductService, ProductSort};
 use ::entity::prelude::Product;
+use sea_orm::DatabaseConnection;
 use serde::{Deserialize, Serialize};
-use rust_decimal::Decimal;
+use std::convert::Infallible;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::error;
+use crate::auth::RequireMerchantAdmin;
+use crate::conditional::http_date;
+use crate::error::{ApiError, ErrorBody};
+use crate::routes::availability::AvailabilityRequest;
+use crate::routes::batch::{self, BatchGetError, BatchGetRequest};
+use crate::routes::digital::DeliveryRequest;
+use crate::routes::media::MediaResponse;
+use crate::routes::parse_decimal;
+use crate::validation::{self, ValidatedJson, Validate, Validator};
 use crate::AppState;
 
 #[derive(Deserialize, utoipa::ToSchema)]
@@ -18,6 +38,26 @@ pub struct CreateProductRequest {
     pub category: String,
     pub base_price: String,
     pub base_cost: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(flatten)]
+    pub delivery: DeliveryRequest,
+    #[serde(flatten)]
+    pub availability: AvailabilityRequest,
+}
+
+impl Validate for CreateProductRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.max_len("merchant", &self.merchant, 20)
+            .required("product_id", &self.product_id, 20)
+            .required("product_name", &self.product_name, 80)
+            .max_len("category", &self.category, 60)
+            .amount("base_price", &self.base_price)
+            .amount("base_cost", &self.base_cost)
+            .max_len("description", &self.description, 10_000);
+        self.delivery.validate(v);
+        self.availability.validate(v);
+    }
 }
 
 #[derive(Serialize, utoipa::ToSchema)]
@@ -26,16 +66,33 @@ pub struct ProductResponse {
     pub mid: i32,
     pub merchant: String,
     pub product: String,
-    pub ts: i32,
+    /// When the product last changed
+    #[schema(value_type = i64)]
+    pub ts: Timestamp,
     pub product_name: String,
     pub category: String,
+    pub description: String,
     pub base_price: String,
     pub base_cost: String,
     pub supplier: String,
     pub supplier_id: String,
     pub upc: String,
-    pub created_gmt: i32,
-    pub lastsold_gmt: Option<i32>,
+    #[schema(value_type = i64)]
+    pub created_gmt: Timestamp,
+    #[schema(value_type = Option<i64>)]
+    pub lastsold_gmt: Option<Timestamp>,
+    /// `physical` or `digital`
+    pub product_type: String,
+    /// Downloads per purchase of a digital product
+    pub download_limit: Option<i32>,
+    /// `draft`, `active` or `archived`
+    pub status: String,
+    #[schema(value_type = Option<i64>)]
+    pub available_from: Option<Timestamp>,
+    #[schema(value_type = Option<i64>)]
+    pub available_until: Option<Timestamp>,
+    /// Images and videos in display order
+    pub media: Vec<MediaResponse>,
 }
 
 impl From<Product> for ProductResponse {
@@ -48,6 +105,7 @@ impl From<Product> for ProductResponse {
             ts: product.ts,
             product_name: product.product_name,
             category: product.category,
+            description: product.description,
             base_price: product.base_price.to_string(),
             base_cost: product.base_cost.to_string(),
             supplier: product.supplier,
@@ -55,23 +113,144 @@ impl From<Product> for ProductResponse {
             upc: product.upc,
             created_gmt: product.created_gmt,
             lastsold_gmt: product.lastsold_gmt,
+            product_type: product.product_type,
+            download_limit: product.download_limit,
+            status: product.status,
+            available_from: product.available_from,
+            available_until: product.available_until,
+            media: Vec::new(),
         }
     }
 }
 
+/// Responses for a merchant's products, with their media loaded in one query
+pub(crate) async fn with_media(
+    db: &DatabaseConnection,
+    mid: i32,
+    products: Vec<Product>,
+) -> Result<Vec<ProductResponse>, ApiError> {
+    let ids: Vec<i32> = products.iter().map(|p| p.id).collect();
+    let mut media = MediaService::list_for_products(db, mid, &ids).await?;
+
+    Ok(products
+        .into_iter()
+        .map(|product| {
+            let items = media.remove(&product.id).unwrap_or_default();
+            let mut response = ProductResponse::from(product);
+            response.media = items.into_iter().map(|m| m.into()).collect();
+            response
+        })
+        .collect())
+}
+
 #[derive(Deserialize, utoipa::IntoParams)]
 pub struct ListQuery {
     pub mid: i32,
+    /// Merchant admins only: `draft`, `active`, `archived` or `all`, with
+    /// any sale window. Without it, lists what shoppers see: active
+    /// products within their window.
+    pub status: Option<String>,
     #[serde(default = "default_limit")]
     pub limit: u64,
-    #[serde(default)]
-    pub offset: u64,
+    /// `next_cursor` of the previous page
+    pub cursor: Option<String>,
+}
+
+impl Validate for ListQuery {
+    fn validate(&self, v: &mut Validator) {
+        validate_status(v, &self.status);
+        if let Some(cursor) = &self.cursor {
+            v.check(cursor.parse::<Cursor>().is_ok(), "cursor", "is not a cursor from a previous page");
+        }
+        v.check((1..=100).contains(&self.limit), "limit", "must be between 1 and 100");
+    }
 }
 
 fn default_limit() -> u64 {
     20
 }
 
+fn validate_status(v: &mut Validator, status: &Option<String>) {
+    if let Some(status) = status {
+        v.check(status.parse::<Visibility>().is_ok(), "status", "must be one of draft, active, archived, all");
+    }
+}
+
+/// The merchant and products a list shows: what shoppers see, unless a
+/// merchant admin asked for a `status`
+fn listing(mid: i32, status: Option<&str>, admin: Option<RequireMerchantAdmin>) -> Result<(i32, Visibility), ApiError> {
+    let Some(status) = status else {
+        return Ok((mid, Visibility::Storefront));
+    };
+    let admin = admin.ok_or_else(|| ApiError::Forbidden("Listing by status needs the merchant admin role".to_string()))?;
+    Ok((admin.0.scoped_mid(mid), status.parse().map_err(ApiError::BadRequest)?))
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct SearchQuery {
+    pub mid: i32,
+    /// Case-insensitive match on product name, UPC or supplier ID
+    pub q: Option<String>,
+    pub category: Option<String>,
+    pub min_price: Option<String>,
+    pub max_price: Option<String>,
+    /// One of `name` (default), `price_asc`, `price_desc`, `newest`
+    pub sort: Option<String>,
+    /// Merchant admins only: `draft`, `active`, `archived` or `all`, as
+    /// for listing
+    pub status: Option<String>,
+    #[serde(default = "default_limit")]
+    pub limit: u64,
+    /// `next_cursor` of the previous page, searched with the same sort
+    pub cursor: Option<String>,
+}
+
+impl Validate for SearchQuery {
+    fn validate(&self, v: &mut Validator) {
+        if let Some(min_price) = &self.min_price {
+            v.amount("min_price", min_price);
+        }
+        if let Some(max_price) = &self.max_price {
+            v.amount("max_price", max_price);
+        }
+        if let Some(sort) = &self.sort {
+            v.check(sort.parse::<ProductSort>().is_ok(), "sort", "must be one of name, price_asc, price_desc, newest");
+        }
+        validate_status(v, &self.status);
+        if let Some(cursor) = &self.cursor {
+            v.check(cursor.parse::<Cursor>().is_ok(), "cursor", "is not a cursor from a previous page");
+        }
+        v.check((1..=100).contains(&self.limit), "limit", "must be between 1 and 100");
+    }
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct ExportQuery {
+    pub mid: i32,
+    /// `csv` (default, one row per SKU) or `jsonl` (one product per line)
+    pub format: Option<String>,
+}
+
+/// Pages encoded ahead of a slow client; the export pauses once these are buffered
+const EXPORT_BUFFERED_PAGES: usize = 4;
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ProductListResponse {
+    pub products: Vec<ProductResponse>,
+    /// Pass as `cursor` to get the next page; absent on the last page
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ProductSearchResponse {
+    pub products: Vec<ProductResponse>,
+    /// Number of matching products across all pages
+    pub total: u64,
+    pub limit: u64,
+    /// Pass as `cursor` to get the next page; absent on the last page
+    pub next_cursor: Option<String>,
+}
+
 /// Create a new product
 #[utoipa::path(
     post,
@@ -79,35 +258,39 @@ fn default_limit() -> u64 {
     request_body = CreateProductRequest,
     responses(
         (status = 201, description = "Product created successfully", body = ProductResponse),
-        (status = 500, description = "Internal server error")
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
     ),
     tag = "products"
 )]
 pub async fn create(
     State(state): State<AppState>,
-    Json(req): Json<CreateProductRequest>,
-) -> Result<(StatusCode, Json<ProductResponse>), StatusCode> {
-    let base_price = req.base_price.parse::<Decimal>()
-        .map_err(|_| StatusCode::BAD_REQUEST)?;
-    let base_cost = req.base_cost.parse::<Decimal>()
-        .map_err(|_| StatusCode::BAD_REQUEST)?;
+    admin: RequireMerchantAdmin,
+    ValidatedJson(req): ValidatedJson<CreateProductRequest>,
+) -> Result<(StatusCode, Json<ProductResponse>), ApiError> {
+    let base_price = parse_decimal("base_price", &req.base_price)?;
+    let base_cost = parse_decimal("base_cost", &req.base_cost)?;
 
     ProductService::create(
-        &*state.db,
-        req.mid,
+        &state.db,
+        admin.0.scoped_mid(req.mid),
         &req.merchant,
         &req.product_id,
         &req.product_name,
         &req.category,
         base_price,
         base_cost,
+        &req.description,
+        req.delivery.delivery(),
+        req.availability.availability(),
     )
     .await
     .map(|product| (StatusCode::CREATED, Json(product.into())))
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+    .map_err(ApiError::from)
 }
 
-/// Get a product by ID
+/// Get a product on sale by ID
 #[utoipa::path(
     get,
     path = "/api/products/{mid}/{id}",
@@ -116,38 +299,221 @@ pub async fn create(
         ("id" = i32, Path, description = "Product ID")
     ),
     responses(
-        (status = 200, description = "Product found", body = ProductResponse),
-        (status = 404, description = "Product not found"),
-        (status = 500, description = "Internal server error")
+        (status = 200, description = "Product found", body = ProductResponse,
+            headers(
+                ("ETag" = String, description = "Send back in If-None-Match to revalidate"),
+                ("Last-Modified" = String, description = "When the product last changed")
+            )),
+        (status = 304, description = "Unchanged since the If-None-Match tag"),
+        (status = 404, description = "Product not found or not on sale", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
     ),
     tag = "products"
 )]
 pub async fn get(
     State(state): State<AppState>,
     Path((mid, id)): Path<(i32, i32)>,
-) -> Result<Json<ProductResponse>, StatusCode> {
-    ProductService::find_by_id(&*state.db, mid, id)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-        .map(|product| Json(product.into()))
-        .ok_or(StatusCode::NOT_FOUND)
+) -> Result<(HeaderMap, Json<ProductResponse>), ApiError> {
+    let product = ProductService::find_by_id(&state.db, mid, id)
+        .await?
+        .filter(|product| Visibility::Storefront.includes(product, Timestamp::now()))
+        .ok_or(ProductError::NotFound)?;
+
+    let mut headers = HeaderMap::new();
+    headers.insert(header::LAST_MODIFIED, http_date(product.ts));
+    let mut responses = with_media(&state.db, mid, vec![product]).await?;
+    Ok((headers, Json(responses.remove(0))))
+}
+
+/// Products of a batch-get, with the IDs that could not be returned
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ProductBatchResponse {
+    /// In the order they were asked for
+    pub products: Vec<ProductResponse>,
+    pub errors: Vec<BatchGetError>,
+}
+
+/// Get many products on sale by ID in one request
+#[utoipa::path(
+    post,
+    path = "/api/products/batch-get",
+    request_body = BatchGetRequest,
+    responses(
+        (status = 200, description = "Found products, and an error for each ID that was not", body = ProductBatchResponse),
+        (status = 422, description = "Too few or many IDs", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "products"
+)]
+pub async fn batch_get(
+    State(state): State<AppState>,
+    ValidatedJson(req): ValidatedJson<BatchGetRequest>,
+) -> Result<Json<ProductBatchResponse>, ApiError> {
+    let ids = req.unique_ids();
+    let now = Timestamp::now();
+    let mut found = ProductService::find_by_ids(&state.db, req.mid, &ids).await?;
+    found.retain(|product| Visibility::Storefront.includes(product, now));
+
+    let mut errors = Vec::new();
+    let products = batch::in_request_order(&ids, found, |p| p.id, |id| errors.push(BatchGetError::not_found(id, "Product")));
+    Ok(Json(ProductBatchResponse {
+        products: with_media(&state.db, req.mid, products).await?,
+        errors,
+    }))
 }
 
-/// List products
+/// List a merchant's products, one page at a time
+#[utoipa::path(
+    get,
+    path = "/api/products",
+    params(ListQuery),
+    responses(
+        (status = 200, description = "One page of products", body = ProductListResponse),
+        (status = 403, description = "A status was asked for without the merchant admin role", body = ErrorBody),
+        (status = 422, description = "Invalid status, cursor or limit", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "products"
+)]
 pub async fn list(
     State(state): State<AppState>,
+    admin: Option<RequireMerchantAdmin>,
     Query(query): Query<ListQuery>,
-) -> Result<Json<Vec<ProductResponse>>, StatusCode> {
-    ProductService::list(&*state.db, query.mid, query.limit, query.offset)
-        .await
-        .map(|products| Json(products.into_iter().map(|p| p.into()).collect()))
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+) -> Result<Json<ProductListResponse>, ApiError> {
+    validation::validate(&query)?;
+    let (mid, visibility) = listing(query.mid, query.status.as_deref(), admin)?;
+    let cursor = query.cursor.as_deref().map(str::parse::<Cursor>).transpose()?;
+
+    let (products, next) = ProductService::list(&state.db, mid, visibility, query.limit, cursor.as_ref()).await?;
+    Ok(Json(ProductListResponse {
+        products: with_media(&state.db, mid, products).await?,
+        next_cursor: next.map(|cursor| cursor.to_string()),
+    }))
+}
+
+/// Search products by text, category and price range
+#[utoipa::path(
+    get,
+    path = "/api/products/search",
+    params(SearchQuery),
+    responses(
+        (status = 200, description = "One page of matching products", body = ProductSearchResponse),
+        (status = 403, description = "A status was asked for without the merchant admin role", body = ErrorBody),
+        (status = 422, description = "Invalid price, sort order, status, cursor or limit", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "products"
+)]
+pub async fn search(
+    State(state): State<AppState>,
+    admin: Option<RequireMerchantAdmin>,
+    Query(query): Query<SearchQuery>,
+) -> Result<Json<ProductSearchResponse>, ApiError> {
+    validation::validate(&query)?;
+    let (mid, visibility) = listing(query.mid, query.status.as_deref(), admin)?;
+
+    let search = ProductSearch {
+        q: query.q,
+        category: query.category,
+        min_price: query.min_price.as_deref().map(|p| parse_decimal("min_price", p)).transpose()?,
+        max_price: query.max_price.as_deref().map(|p| parse_decimal("max_price", p)).transpose()?,
+        sort: query.sort.as_deref().map(str::parse).transpose().map_err(ApiError::BadRequest)?.unwrap_or_default(),
+        visibility,
+    };
+
+    let cursor = query.cursor.as_deref().map(str::parse::<Cursor>).transpose()?;
+
+    let (products, total, next) = ProductService::search(&state.db, mid, &search, query.limit, cursor.as_ref()).await?;
+    Ok(Json(ProductSearchResponse {
+        products: with_media(&state.db, mid, products).await?,
+        total,
+        limit: query.limit,
+        next_cursor: next.map(|cursor| cursor.to_string()),
+    }))
+}
+
+/// Stream a merchant's full catalog, SKUs and prices included
+#[utoipa::path(
+    get,
+    path = "/api/products/export",
+    params(ExportQuery),
+    responses(
+        (status = 200, description = "Catalog as CSV or JSON Lines, sent with chunked transfer encoding", content_type = "text/csv"),
+        (status = 400, description = "Unknown export format", body = ErrorBody),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody)
+    ),
+    tag = "products"
+)]
+pub async fn export(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Query(query): Query<ExportQuery>,
+) -> Result<Response, ApiError> {
+    let format: ExportFormat = query.format.as_deref().unwrap_or("csv").parse().map_err(ApiError::BadRequest)?;
+    let mid = admin.0.scoped_mid(query.mid);
+
+    // The bounded channel is the backpressure: the exporter waits for the
+    // client to drain pages before reading more, and stops if it disconnects
+    let (tx, rx) = mpsc::channel::<Result<Bytes, Infallible>>(EXPORT_BUFFERED_PAGES);
+    let db = state.db.clone();
+    tokio::spawn(async move {
+        if tx.send(Ok(Bytes::from(format.header()))).await.is_err() {
+            return;
+        }
+        let mut after_id = 0;
+        loop {
+            let page = match export::export_page(&db, mid, after_id, EXPORT_PAGE_SIZE).await {
+                Ok(page) => page,
+                Err(e) => {
+                    // Headers are already sent; the truncated body is all the client can see
+                    error!("Product export for merchant {} failed after id {}: {}", mid, after_id, e);
+                    return;
+                }
+            };
+            let Some(last) = page.last() else {
+                return;
+            };
+            after_id = last.product.id;
+            if tx.send(Ok(Bytes::from(format.encode(&page)))).await.is_err() {
+                return;
+            }
+        }
+    });
+
+    let disposition = format!("attachment; filename=\"products-{}.{}\"", mid, format.extension());
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        Body::from_stream(ReceiverStream::new(rx)),
+    )
+        .into_response())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use sea_orm::{DatabaseBackend, MockDatabase, MockExecResult};
+    use crate::auth::{Claims, Role};
+    use ::entity::prelude::ProductMediaItem;
+    use rust_decimal::Decimal;
+    use sea_orm::{DatabaseBackend, MockDatabase, MockExecResult, Value};
+    use std::collections::BTreeMap;
+
+    fn search_query(sort: Option<&str>, min_price: Option<&str>) -> SearchQuery {
+        SearchQuery {
+            mid: 1,
+            q: Some("widget".to_string()),
+            category: None,
+            min_price: min_price.map(str::to_string),
+            max_price: None,
+            sort: sort.map(str::to_string),
+            status: None,
+            limit: 1,
+            cursor: None,
+        }
+    }
 
     #[tokio::test]
     async fn test_create_product() {
@@ -160,12 +526,7 @@ mod tests {
             ])
             .into_connection();
 
-        let state = AppState {
-            db: std::sync::Arc::new(db),
-            cart_store: std::sync::Arc::new(std::sync::Mutex::new(
-                commercerack_cart::CartStore::new()
-            )),
-        };
+        let state = AppState::mock(db);
 
         let req = CreateProductRequest {
             mid: 1,
@@ -175,10 +536,155 @@ mod tests {
             category: "Electronics".to_string(),
             base_price: "99.99".to_string(),
             base_cost: "49.99".to_string(),
+            description: String::new(),
+            delivery: DeliveryRequest::default(),
+            availability: AvailabilityRequest::default(),
         };
 
         // This will fail in mock but validates the structure
-        let result = create(State(state), Json(req)).await;
+        let admin = RequireMerchantAdmin::new(Claims::new(1, 1).with_role(Role::MerchantAdmin));
+        let result = create(State(state), admin, ValidatedJson(req)).await;
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_search_returns_total() {
+        let product = Product {
+            id: 1,
+            mid: 1,
+            merchant: "testmerchant".to_string(),
+            product: "PROD001".to_string(),
+            ts: Timestamp::EPOCH,
+            product_name: "Blue Widget".to_string(),
+            category: "Widgets".to_string(),
+            description: "A small blue widget".to_string(),
+            base_price: Decimal::new(1999, 2),
+            base_cost: Decimal::new(999, 2),
+            supplier: String::new(),
+            supplier_id: String::new(),
+            upc: String::new(),
+            created_gmt: Timestamp::EPOCH,
+            lastsold_gmt: None,
+            product_type: "physical".to_string(),
+            download_url: None,
+            download_limit: None,
+            status: "active".to_string(),
+            available_from: None,
+            available_until: None,
+        };
+        let count = BTreeMap::from([("num_items".to_string(), Value::BigInt(Some(3)))]);
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![count]])
+            .append_query_results([vec![product]])
+            .append_query_results([vec![ProductMediaItem {
+                id: 5,
+                mid: 1,
+                product_id: 1,
+                sku_id: None,
+                kind: "image".to_string(),
+                url: "https://cdn.example.com/widget.jpg".to_string(),
+                alt_text: "Blue widget".to_string(),
+                position: 0,
+                is_primary: true,
+                created_gmt: Timestamp::EPOCH,
+            }]])
+            .into_connection();
+        let state = AppState::mock(db);
+
+        let Json(page) = search(State(state), None, Query(search_query(Some("price_asc"), Some("10"))))
+            .await
+            .unwrap();
+        assert_eq!(page.total, 3);
+        assert_eq!(page.products.len(), 1);
+        assert_eq!(page.next_cursor, None);
+        assert_eq!(page.products[0].base_price, "19.99");
+        assert_eq!(page.products[0].media[0].alt_text, "Blue widget");
+    }
+
+    #[tokio::test]
+    async fn test_search_rejects_invalid_filters() {
+        let state = AppState::mock(MockDatabase::new(DatabaseBackend::Postgres).into_connection());
+
+        let result = search(State(state), None, Query(search_query(Some("cheapest"), Some("-1")))).await;
+        match result.err() {
+            Some(ApiError::Validation(errors)) => {
+                let fields: Vec<_> = errors.iter().map(|e| e.field.as_str()).collect();
+                assert_eq!(fields, vec!["min_price", "sort"]);
+            }
+            other => panic!("unexpected result {:?}", other.map(|e| e.status())),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_export_streams_every_page() {
+        let product = Product {
+            id: 3,
+            mid: 1,
+            merchant: "testmerchant".to_string(),
+            product: "PROD003".to_string(),
+            ts: Timestamp::EPOCH,
+            product_name: "Widget".to_string(),
+            category: "Widgets".to_string(),
+            description: String::new(),
+            base_price: Decimal::new(500, 2),
+            base_cost: Decimal::new(200, 2),
+            supplier: String::new(),
+            supplier_id: String::new(),
+            upc: String::new(),
+            created_gmt: Timestamp::EPOCH,
+            lastsold_gmt: None,
+            product_type: "physical".to_string(),
+            download_url: None,
+            download_limit: None,
+            status: "active".to_string(),
+            available_from: None,
+            available_until: None,
+        };
+        let sku = ::entity::prelude::Sku {
+            id: 9,
+            pid: 3,
+            mid: 1,
+            sku: "W-3".to_string(),
+            title: "Widget".to_string(),
+            price: Decimal::new(500, 2),
+            cost: Decimal::new(200, 2),
+            upc: String::new(),
+            inv_available: 2,
+            qty_onshelf: 2,
+            weight: Decimal::ZERO,
+            reorder_point: None,
+            reorder_quantity: 0,
+            low_stock_gmt: None,
+        };
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![product]])
+            .append_query_results([vec![sku]])
+            .append_query_results([Vec::<Product>::new()])
+            .into_connection();
+        let state = AppState::mock(db);
+        let admin = RequireMerchantAdmin::new(Claims::new(1, 1).with_role(Role::MerchantAdmin));
+
+        let query = ExportQuery { mid: 1, format: Some("jsonl".to_string()) };
+        let response = export(State(state), admin, Query(query)).await.unwrap();
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/x-ndjson");
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let lines: Vec<serde_json::Value> = body
+            .split(|b| *b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0]["skus"][0]["sku"], "W-3");
+    }
+
+    #[tokio::test]
+    async fn test_export_rejects_unknown_format() {
+        let state = AppState::mock(MockDatabase::new(DatabaseBackend::Postgres).into_connection());
+        let admin = RequireMerchantAdmin::new(Claims::new(1, 1).with_role(Role::MerchantAdmin));
+
+        let query = ExportQuery { mid: 1, format: Some("xlsx".to_string()) };
+        let result = export(State(state), admin, Query(query)).await;
+        assert_eq!(result.err().map(|e| e.status()), Some(StatusCode::BAD_REQUEST));
+    }
 }
@@ -0,0 +1,411 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use commercerack_core::Timestamp;
+use commercerack_inventory::purchasing::{
+    PurchaseOrderInput, PurchaseOrderLine, PurchaseOrderService, PurchaseOrderStatus, PurchaseOrderWithItems,
+};
+use entity::prelude::{PurchaseOrder, PurchaseOrderItem};
+use serde::{Deserialize, Serialize};
+use super::parse_decimal;
+use crate::auth::RequireMerchantAdmin;
+use crate::error::{ApiError, ErrorBody};
+use crate::validation::{ValidatedJson, Validate, Validator};
+use crate::AppState;
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct PurchaseOrderItemRequest {
+    pub sku: String,
+    pub quantity: i32,
+    /// Decimal string; the SKU's cost when omitted
+    pub unit_cost: Option<String>,
+}
+
+impl Validate for PurchaseOrderItemRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("sku", &self.sku, 45).positive("quantity", self.quantity);
+        if let Some(unit_cost) = &self.unit_cost {
+            v.amount("unit_cost", unit_cost);
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct PurchaseOrderRequest {
+    pub supplier: String,
+    /// Warehouse the delivery is booked into
+    pub warehouse_id: Option<i32>,
+    pub note: Option<String>,
+    /// Delivery date promised by the supplier
+    #[schema(value_type = Option<i64>)]
+    pub expected_gmt: Option<Timestamp>,
+    pub items: Vec<PurchaseOrderItemRequest>,
+}
+
+impl Validate for PurchaseOrderRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("supplier", &self.supplier, 60)
+            .check(!self.items.is_empty(), "items", "must not be empty")
+            .each("items", &self.items);
+    }
+}
+
+impl TryFrom<PurchaseOrderRequest> for PurchaseOrderInput {
+    type Error = ApiError;
+
+    fn try_from(req: PurchaseOrderRequest) -> Result<Self, ApiError> {
+        let items = req
+            .items
+            .into_iter()
+            .map(|item| {
+                Ok(PurchaseOrderLine {
+                    unit_cost: item.unit_cost.as_deref().map(|cost| parse_decimal("unit_cost", cost.trim())).transpose()?,
+                    sku: item.sku,
+                    quantity: item.quantity,
+                })
+            })
+            .collect::<Result<_, ApiError>>()?;
+
+        Ok(Self {
+            supplier: req.supplier,
+            warehouse_id: req.warehouse_id,
+            note: req.note,
+            expected_gmt: req.expected_gmt,
+            items,
+        })
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct CreatePurchaseOrderRequest {
+    pub mid: i32,
+    #[serde(flatten)]
+    pub purchase_order: PurchaseOrderRequest,
+}
+
+impl Validate for CreatePurchaseOrderRequest {
+    fn validate(&self, v: &mut Validator) {
+        self.purchase_order.validate(v);
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct ReceiveItemRequest {
+    pub sku: String,
+    pub quantity: i32,
+}
+
+impl Validate for ReceiveItemRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("sku", &self.sku, 45).positive("quantity", self.quantity);
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct ReceivePurchaseOrderRequest {
+    /// Units in this delivery
+    pub items: Vec<ReceiveItemRequest>,
+}
+
+impl Validate for ReceivePurchaseOrderRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.check(!self.items.is_empty(), "items", "must not be empty")
+            .each("items", &self.items);
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct PurchaseOrderItemResponse {
+    pub sku: String,
+    pub quantity: i32,
+    /// Units received so far
+    pub received: i32,
+    pub unit_cost: String,
+}
+
+impl From<PurchaseOrderItem> for PurchaseOrderItemResponse {
+    fn from(item: PurchaseOrderItem) -> Self {
+        Self {
+            sku: item.sku,
+            quantity: item.quantity,
+            received: item.received,
+            unit_cost: item.unit_cost.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct PurchaseOrderResponse {
+    pub id: i32,
+    pub mid: i32,
+    pub supplier: String,
+    /// `draft`, `sent`, `partially_received` or `received`
+    pub status: String,
+    pub warehouse_id: Option<i32>,
+    pub note: Option<String>,
+    #[schema(value_type = Option<i64>)]
+    pub expected_gmt: Option<Timestamp>,
+    pub actor: Option<i32>,
+    #[schema(value_type = i64)]
+    pub created_gmt: Timestamp,
+    #[schema(value_type = Option<i64>)]
+    pub sent_gmt: Option<Timestamp>,
+    /// When the last units came in
+    #[schema(value_type = Option<i64>)]
+    pub received_gmt: Option<Timestamp>,
+    /// Omitted in lists
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub items: Option<Vec<PurchaseOrderItemResponse>>,
+}
+
+impl From<PurchaseOrder> for PurchaseOrderResponse {
+    fn from(purchase_order: PurchaseOrder) -> Self {
+        Self {
+            id: purchase_order.id,
+            mid: purchase_order.mid,
+            supplier: purchase_order.supplier,
+            status: purchase_order.status,
+            warehouse_id: purchase_order.warehouse_id,
+            note: purchase_order.note,
+            expected_gmt: purchase_order.expected_gmt,
+            actor: purchase_order.actor,
+            created_gmt: purchase_order.created_gmt,
+            sent_gmt: purchase_order.sent_gmt,
+            received_gmt: purchase_order.received_gmt,
+            items: None,
+        }
+    }
+}
+
+impl From<PurchaseOrderWithItems> for PurchaseOrderResponse {
+    fn from(purchase_order: PurchaseOrderWithItems) -> Self {
+        Self {
+            items: Some(purchase_order.items.into_iter().map(|item| item.into()).collect()),
+            ..purchase_order.purchase_order.into()
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct PurchaseOrderListQuery {
+    pub mid: i32,
+    /// `draft`, `sent`, `partially_received` or `received`
+    pub status: Option<String>,
+}
+
+/// Draft a purchase order to a supplier
+#[utoipa::path(
+    post,
+    path = "/api/purchase-orders",
+    request_body = CreatePurchaseOrderRequest,
+    responses(
+        (status = 201, description = "Purchase order drafted", body = PurchaseOrderResponse),
+        (status = 400, description = "Unknown SKU or duplicate lines", body = ErrorBody),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Warehouse not found", body = ErrorBody),
+        (status = 422, description = "Invalid supplier or items", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "inventory"
+)]
+pub async fn create(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    ValidatedJson(req): ValidatedJson<CreatePurchaseOrderRequest>,
+) -> Result<(StatusCode, Json<PurchaseOrderResponse>), ApiError> {
+    let mid = admin.0.scoped_mid(req.mid);
+    let actor = admin.0.sub.parse().ok();
+
+    PurchaseOrderService::create(&*state.db, mid, req.purchase_order.try_into()?, actor)
+        .await
+        .map(|purchase_order| (StatusCode::CREATED, Json(purchase_order.into())))
+        .map_err(ApiError::from)
+}
+
+/// A merchant's purchase orders, newest first
+#[utoipa::path(
+    get,
+    path = "/api/purchase-orders",
+    params(PurchaseOrderListQuery),
+    responses(
+        (status = 200, description = "Purchase orders without their items", body = Vec<PurchaseOrderResponse>),
+        (status = 400, description = "Unknown status", body = ErrorBody),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "inventory"
+)]
+pub async fn list(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Query(query): Query<PurchaseOrderListQuery>,
+) -> Result<Json<Vec<PurchaseOrderResponse>>, ApiError> {
+    let status = query
+        .status
+        .as_deref()
+        .map(str::parse::<PurchaseOrderStatus>)
+        .transpose()
+        .map_err(ApiError::BadRequest)?;
+
+    PurchaseOrderService::list(&state.db, admin.0.scoped_mid(query.mid), status)
+        .await
+        .map(|purchase_orders| Json(purchase_orders.into_iter().map(|p| p.into()).collect()))
+        .map_err(ApiError::from)
+}
+
+/// A purchase order with its items
+#[utoipa::path(
+    get,
+    path = "/api/purchase-orders/{mid}/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Purchase order ID")
+    ),
+    responses(
+        (status = 200, description = "Purchase order found", body = PurchaseOrderResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Purchase order not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "inventory"
+)]
+pub async fn get(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Json<PurchaseOrderResponse>, ApiError> {
+    PurchaseOrderService::find(&state.db, admin.0.scoped_mid(mid), id)
+        .await
+        .map(|purchase_order| Json(purchase_order.into()))
+        .map_err(ApiError::from)
+}
+
+/// Replace a draft purchase order
+#[utoipa::path(
+    put,
+    path = "/api/purchase-orders/{mid}/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Purchase order ID")
+    ),
+    request_body = PurchaseOrderRequest,
+    responses(
+        (status = 200, description = "Purchase order updated", body = PurchaseOrderResponse),
+        (status = 400, description = "Unknown SKU or duplicate lines", body = ErrorBody),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Purchase order or warehouse not found", body = ErrorBody),
+        (status = 409, description = "Purchase order was already sent", body = ErrorBody),
+        (status = 422, description = "Invalid supplier or items", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "inventory"
+)]
+pub async fn update(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+    ValidatedJson(req): ValidatedJson<PurchaseOrderRequest>,
+) -> Result<Json<PurchaseOrderResponse>, ApiError> {
+    PurchaseOrderService::update(&*state.db, admin.0.scoped_mid(mid), id, req.try_into()?)
+        .await
+        .map(|purchase_order| Json(purchase_order.into()))
+        .map_err(ApiError::from)
+}
+
+/// Mark a draft as sent to the supplier
+#[utoipa::path(
+    post,
+    path = "/api/purchase-orders/{mid}/{id}/send",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Purchase order ID")
+    ),
+    responses(
+        (status = 200, description = "Purchase order sent", body = PurchaseOrderResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Purchase order not found", body = ErrorBody),
+        (status = 409, description = "Purchase order was already sent", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "inventory"
+)]
+pub async fn send(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Json<PurchaseOrderResponse>, ApiError> {
+    PurchaseOrderService::send(&*state.db, admin.0.scoped_mid(mid), id)
+        .await
+        .map(|purchase_order| Json(purchase_order.into()))
+        .map_err(ApiError::from)
+}
+
+/// Book a delivery against a sent purchase order, adding it to stock
+#[utoipa::path(
+    post,
+    path = "/api/purchase-orders/{mid}/{id}/receive",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Purchase order ID")
+    ),
+    request_body = ReceivePurchaseOrderRequest,
+    responses(
+        (status = 200, description = "Delivery booked", body = PurchaseOrderResponse),
+        (status = 400, description = "SKU not on the order or more units than outstanding", body = ErrorBody),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Purchase order not found", body = ErrorBody),
+        (status = 409, description = "Purchase order is a draft or fully received", body = ErrorBody),
+        (status = 422, description = "Invalid items", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "inventory"
+)]
+pub async fn receive(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+    ValidatedJson(req): ValidatedJson<ReceivePurchaseOrderRequest>,
+) -> Result<Json<PurchaseOrderResponse>, ApiError> {
+    let actor = admin.0.sub.parse().ok();
+    let lines: Vec<(&str, i32)> = req.items.iter().map(|item| (item.sku.as_str(), item.quantity)).collect();
+
+    PurchaseOrderService::receive(&*state.db, admin.0.scoped_mid(mid), id, &lines, actor)
+        .await
+        .map(|purchase_order| Json(purchase_order.into()))
+        .map_err(ApiError::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_purchase_order_validation() {
+        let req = PurchaseOrderRequest {
+            supplier: "Acme".to_string(),
+            warehouse_id: None,
+            note: None,
+            expected_gmt: None,
+            items: vec![PurchaseOrderItemRequest {
+                sku: "SKU001".to_string(),
+                quantity: 12,
+                unit_cost: Some("-1.00".to_string()),
+            }],
+        };
+        match crate::validation::validate(&req) {
+            Err(ApiError::Validation(errors)) => {
+                let fields: Vec<_> = errors.iter().map(|e| e.field.as_str()).collect();
+                assert_eq!(fields, vec!["items[0].unit_cost"]);
+            }
+            other => panic!("unexpected result {:?}", other.err().map(|e| e.status())),
+        }
+    }
+}
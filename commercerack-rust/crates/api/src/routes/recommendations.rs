@@ -0,0 +1,91 @@
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use commercerack_core::Timestamp;
+use commercerack_product::recommendations::{RecommendationService, RECOMMENDATIONS_PER_CUSTOMER};
+use serde::{Deserialize, Serialize};
+use crate::auth::Claims;
+use crate::error::{ApiError, ErrorBody};
+use crate::routes::products::{with_media, ProductResponse};
+use crate::validation::{self, Validate, Validator};
+use crate::AppState;
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct RecommendationsQuery {
+    #[serde(default = "default_limit")]
+    pub limit: u64,
+}
+
+fn default_limit() -> u64 {
+    RECOMMENDATIONS_PER_CUSTOMER as u64
+}
+
+impl Validate for RecommendationsQuery {
+    fn validate(&self, v: &mut Validator) {
+        v.check(
+            (1..=default_limit()).contains(&self.limit),
+            "limit",
+            &format!("must be between 1 and {}", RECOMMENDATIONS_PER_CUSTOMER),
+        );
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct RecommendationsResponse {
+    pub mid: i32,
+    pub cid: i32,
+    /// Best first; empty until the customer has paid orders and the nightly
+    /// refresh has run
+    pub products: Vec<ProductResponse>,
+    /// When the recommendations were worked out
+    #[schema(value_type = Option<i64>)]
+    pub computed_gmt: Option<Timestamp>,
+}
+
+/// Products a customer may also like, from their order history
+#[utoipa::path(
+    get,
+    path = "/api/customers/{mid}/{cid}/recommendations",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("cid" = i32, Path, description = "Customer ID"),
+        RecommendationsQuery
+    ),
+    responses(
+        (status = 200, description = "Recommended products still on sale, best first", body = RecommendationsResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Another customer's recommendations", body = ErrorBody),
+        (status = 422, description = "Invalid limit", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "products"
+)]
+pub async fn list(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((mid, cid)): Path<(i32, i32)>,
+    Query(query): Query<RecommendationsQuery>,
+) -> Result<Json<RecommendationsResponse>, ApiError> {
+    validation::validate(&query)?;
+    claims.ensure_self(cid)?;
+    let mid = claims.scoped_mid(mid);
+
+    let (products, computed_gmt) = RecommendationService::for_customer(&state.db, mid, cid, query.limit).await?;
+    Ok(Json(RecommendationsResponse {
+        mid,
+        cid,
+        products: with_media(&state.db, mid, products).await?,
+        computed_gmt,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limit_is_bounded() {
+        assert!(validation::validate(&RecommendationsQuery { limit: 13 }).is_err());
+    }
+}
@@ -0,0 +1,278 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use commercerack_audit::Change;
+use commercerack_core::Timestamp;
+use commercerack_order::refunds::{OrderRefundWithItems, RefundLine, RefundQuote, RefundService};
+use ::entity::prelude::OrderRefundItem;
+use serde::{Deserialize, Serialize};
+use crate::auth::RequireMerchantAdmin;
+use crate::error::{ApiError, ErrorBody};
+use crate::routes::audit;
+use crate::routes::payments::gateway;
+use crate::validation::{ValidatedJson, Validate, Validator};
+use crate::AppState;
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct OrderRefundRequest {
+    pub items: Vec<OrderRefundItemRequest>,
+    #[serde(default)]
+    pub reason: String,
+}
+
+impl Validate for OrderRefundRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.max_len("reason", &self.reason, 255)
+            .check(!self.items.is_empty(), "items", "must contain at least one item")
+            .each("items", &self.items);
+    }
+}
+
+impl OrderRefundRequest {
+    fn lines(&self) -> Vec<RefundLine> {
+        self.items
+            .iter()
+            .map(|item| RefundLine { order_item_id: item.order_item_id, quantity: item.quantity })
+            .collect()
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct OrderRefundItemRequest {
+    pub order_item_id: i32,
+    pub quantity: i32,
+}
+
+impl Validate for OrderRefundItemRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.positive("quantity", self.quantity);
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct OrderRefundItemResponse {
+    pub order_item_id: i32,
+    pub quantity: i32,
+}
+
+impl From<OrderRefundItem> for OrderRefundItemResponse {
+    fn from(item: OrderRefundItem) -> Self {
+        Self {
+            order_item_id: item.order_item_id,
+            quantity: item.quantity,
+        }
+    }
+}
+
+/// What refunding the requested items would give back
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct RefundQuoteResponse {
+    /// The items, net of their share of discounts
+    pub items_amount: String,
+    /// Their share of the order's tax
+    pub tax_amount: String,
+    /// Their share of the order's shipping
+    pub shipping_amount: String,
+    /// What would be refunded, no more than `available`
+    pub amount: String,
+    /// What the order's captured payments can still give back
+    pub available: String,
+    pub items: Vec<OrderRefundItemResponse>,
+}
+
+impl From<RefundQuote> for RefundQuoteResponse {
+    fn from(quote: RefundQuote) -> Self {
+        Self {
+            items_amount: quote.items.to_string(),
+            tax_amount: quote.tax.to_string(),
+            shipping_amount: quote.shipping.to_string(),
+            amount: quote.amount.to_string(),
+            available: quote.available.to_string(),
+            items: quote
+                .lines
+                .into_iter()
+                .map(|line| OrderRefundItemResponse { order_item_id: line.order_item_id, quantity: line.quantity })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct OrderRefundResponse {
+    pub id: i32,
+    pub order_id: i32,
+    pub items_amount: String,
+    pub tax_amount: String,
+    pub shipping_amount: String,
+    /// What was refunded through the payment gateway
+    pub amount: String,
+    pub reason: String,
+    #[schema(value_type = i64)]
+    pub created_gmt: Timestamp,
+    /// When the gateway gave the amount back; `null` while that is
+    /// unconfirmed, e.g. after it failed part way
+    #[schema(value_type = Option<i64>)]
+    pub refunded_gmt: Option<Timestamp>,
+    pub items: Vec<OrderRefundItemResponse>,
+}
+
+impl From<OrderRefundWithItems> for OrderRefundResponse {
+    fn from(refund: OrderRefundWithItems) -> Self {
+        Self {
+            id: refund.refund.id,
+            order_id: refund.refund.order_id,
+            items_amount: refund.refund.items_amount.to_string(),
+            tax_amount: refund.refund.tax_amount.to_string(),
+            shipping_amount: refund.refund.shipping_amount.to_string(),
+            amount: refund.refund.amount.to_string(),
+            reason: refund.refund.reason,
+            created_gmt: refund.refund.created_gmt,
+            refunded_gmt: refund.refund.refunded_gmt,
+            items: refund.items.into_iter().map(|i| i.into()).collect(),
+        }
+    }
+}
+
+/// Work out what refunding some of an order's items would give back
+///
+/// Nothing is refunded. The items are valued at what they sold for less
+/// their share of discounts, plus the same share of the order's tax and
+/// shipping, and the total is held to what the captured payments can still
+/// give back.
+#[utoipa::path(
+    post,
+    path = "/api/orders/{mid}/{id}/refunds/quote",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Order ID")
+    ),
+    request_body = OrderRefundRequest,
+    responses(
+        (status = 200, description = "What the refund would come to", body = RefundQuoteResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Order not found", body = ErrorBody),
+        (status = 409, description = "Order has no captured payment left to refund", body = ErrorBody),
+        (status = 422, description = "More than the refundable quantity", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "payments"
+)]
+pub async fn quote_refund(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+    ValidatedJson(req): ValidatedJson<OrderRefundRequest>,
+) -> Result<Json<RefundQuoteResponse>, ApiError> {
+    let mid = admin.0.scoped_mid(mid);
+
+    RefundService::quote(&*state.db, mid, id, &req.lines())
+        .await
+        .map(|quote| Json(quote.into()))
+        .map_err(ApiError::from)
+}
+
+/// Refund some of an order's items
+///
+/// Works the amount out as the quote does, records it, then refunds it
+/// through the payment gateway, newest payment first. Units refunded once
+/// can't be refunded again. If the gateway fails part way the refund is
+/// kept without a `refunded_gmt`, holding its units until it's reconciled.
+#[utoipa::path(
+    post,
+    path = "/api/orders/{mid}/{id}/refunds",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Order ID")
+    ),
+    request_body = OrderRefundRequest,
+    responses(
+        (status = 201, description = "Items refunded", body = OrderRefundResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Order not found", body = ErrorBody),
+        (status = 409, description = "Order has no captured payment left to refund", body = ErrorBody),
+        (status = 422, description = "More than the refundable quantity", body = ErrorBody),
+        (status = 502, description = "Payment gateway error", body = ErrorBody),
+        (status = 503, description = "No payment gateway configured", body = ErrorBody)
+    ),
+    tag = "payments"
+)]
+pub async fn create_refund(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+    ValidatedJson(req): ValidatedJson<OrderRefundRequest>,
+) -> Result<(StatusCode, Json<OrderRefundResponse>), ApiError> {
+    let mid = admin.0.scoped_mid(mid);
+    let gateway = gateway(&state)?;
+
+    let refund = RefundService::refund(&state.db, gateway, mid, id, &req.lines(), &req.reason).await?;
+    let change = Change::new("order_refund", refund.refund.id, "create").created(&refund.refund);
+    audit::record(&state, &admin.0, mid, change).await;
+    Ok((StatusCode::CREATED, Json(refund.into())))
+}
+
+/// List the item refunds of an order
+#[utoipa::path(
+    get,
+    path = "/api/orders/{mid}/{id}/refunds",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Order ID")
+    ),
+    responses(
+        (status = 200, description = "Refunds, oldest first", body = Vec<OrderRefundResponse>),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "payments"
+)]
+pub async fn list_refunds(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Json<Vec<OrderRefundResponse>>, ApiError> {
+    let mid = admin.0.scoped_mid(mid);
+
+    RefundService::list(&*state.db, mid, id)
+        .await
+        .map(|refunds| Json(refunds.into_iter().map(|r| r.into()).collect()))
+        .map_err(ApiError::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::{Claims, Role};
+    use crate::validation;
+    use sea_orm::{DatabaseBackend, MockDatabase};
+
+    #[test]
+    fn test_refund_request_validation() {
+        let req: OrderRefundRequest = serde_json::from_value(serde_json::json!({
+            "items": [{ "order_item_id": 4, "quantity": 0 }]
+        }))
+        .unwrap();
+        match validation::validate(&req) {
+            Err(ApiError::Validation(errors)) => assert_eq!(errors[0].field, "items[0].quantity"),
+            other => panic!("unexpected result {:?}", other.err().map(|e| e.status())),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refund_requires_gateway() {
+        let state = AppState::mock(MockDatabase::new(DatabaseBackend::Postgres).into_connection());
+        let admin = RequireMerchantAdmin::new(Claims::new(1, 1).with_role(Role::MerchantAdmin));
+
+        let req = OrderRefundRequest {
+            items: vec![OrderRefundItemRequest { order_item_id: 4, quantity: 1 }],
+            reason: String::new(),
+        };
+        let result = create_refund(State(state), admin, Path((1, 9)), ValidatedJson(req)).await;
+        assert_eq!(result.err().map(|e| e.status()), Some(StatusCode::SERVICE_UNAVAILABLE));
+    }
+}
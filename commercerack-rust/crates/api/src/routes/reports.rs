@@ -0,0 +1,182 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+    Json,
+};
+use commercerack_core::Timestamp;
+use commercerack_reports::{ReportPeriod, ReportService};
+use ::entity::prelude::Report;
+use serde::{Deserialize, Serialize};
+use crate::auth::RequireMerchantAdmin;
+use crate::error::{ApiError, ErrorBody};
+use crate::validation::{self, Validate, Validator};
+use crate::AppState;
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ReportResponse {
+    pub id: i32,
+    /// `daily`, `weekly` or `monthly`
+    pub period: String,
+    #[schema(value_type = i64)]
+    pub from: Timestamp,
+    /// Exclusive
+    #[schema(value_type = i64)]
+    pub to: Timestamp,
+    /// Paid orders created in the period
+    pub orders: i32,
+    pub revenue: String,
+    pub tax: String,
+    pub shipping: String,
+    /// Return refunds issued in the period
+    pub refunds: String,
+    /// Whether the figures have been computed and the CSV can be downloaded
+    pub ready: bool,
+    #[schema(value_type = Option<i64>)]
+    pub generated_gmt: Option<Timestamp>,
+}
+
+impl From<Report> for ReportResponse {
+    fn from(report: Report) -> Self {
+        Self {
+            id: report.id,
+            period: report.period,
+            from: report.from_gmt,
+            to: report.to_gmt,
+            orders: report.orders,
+            revenue: report.revenue.to_string(),
+            tax: report.tax.to_string(),
+            shipping: report.shipping.to_string(),
+            refunds: report.refunds.to_string(),
+            ready: report.csv.is_some(),
+            generated_gmt: report.generated_gmt,
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct ListQuery {
+    pub mid: i32,
+    /// `daily`, `weekly` or `monthly`; all periods if omitted
+    pub period: Option<String>,
+    #[serde(default = "default_limit")]
+    pub limit: u64,
+}
+
+impl Validate for ListQuery {
+    fn validate(&self, v: &mut Validator) {
+        if let Some(period) = &self.period {
+            v.check(period.parse::<ReportPeriod>().is_ok(), "period", "must be daily, weekly or monthly");
+        }
+        v.check((1..=100).contains(&self.limit), "limit", "must be between 1 and 100");
+    }
+}
+
+fn default_limit() -> u64 {
+    50
+}
+
+/// A merchant's sales reports, latest period first
+#[utoipa::path(
+    get,
+    path = "/api/reports",
+    params(ListQuery),
+    responses(
+        (status = 200, description = "Reports without their CSV", body = Vec<ReportResponse>),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 422, description = "Invalid period or limit", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "reports"
+)]
+pub async fn list(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Query(query): Query<ListQuery>,
+) -> Result<Json<Vec<ReportResponse>>, ApiError> {
+    validation::validate(&query)?;
+
+    let period = query.period.as_deref().and_then(|period| period.parse().ok());
+    let mid = admin.0.scoped_mid(query.mid);
+    let reports = ReportService::list(&*state.db, mid, period, query.limit).await?;
+    Ok(Json(reports.into_iter().map(|report| report.into()).collect()))
+}
+
+/// One sales report's totals
+#[utoipa::path(
+    get,
+    path = "/api/reports/{mid}/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Report ID")
+    ),
+    responses(
+        (status = 200, description = "Report totals", body = ReportResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Report not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "reports"
+)]
+pub async fn get(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Json<ReportResponse>, ApiError> {
+    let report = ReportService::find_by_id(&*state.db, admin.0.scoped_mid(mid), id).await?;
+    Ok(Json(report.into()))
+}
+
+/// Download a sales report with its product and category breakdown
+#[utoipa::path(
+    get,
+    path = "/api/reports/{mid}/{id}/download",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Report ID")
+    ),
+    responses(
+        (status = 200, description = "Report as CSV", content_type = "text/csv"),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Report not found", body = ErrorBody),
+        (status = 409, description = "Report has not been generated yet", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "reports"
+)]
+pub async fn download(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Response, ApiError> {
+    let report = ReportService::find_by_id(&*state.db, admin.0.scoped_mid(mid), id).await?;
+    let csv = report
+        .csv
+        .ok_or_else(|| ApiError::Conflict("Report has not been generated yet".to_string()))?;
+
+    let disposition = format!("attachment; filename=\"report-{}-{}-{}.csv\"", report.mid, report.period, report.from_gmt);
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        csv,
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_period_validation() {
+        let query = |period: Option<&str>| ListQuery { mid: 1, period: period.map(String::from), limit: 50 };
+        assert!(validation::validate(&query(None)).is_ok());
+        assert!(validation::validate(&query(Some("weekly"))).is_ok());
+        assert!(validation::validate(&query(Some("yearly"))).is_err());
+    }
+}
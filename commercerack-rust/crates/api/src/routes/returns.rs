@@ -0,0 +1,303 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use commercerack_audit::Change;
+use commercerack_core::Timestamp;
+use commercerack_order::returns::{ReturnLine, ReturnService, ReturnWithItems};
+use ::entity::prelude::ReturnItem;
+use serde::{Deserialize, Serialize};
+use crate::auth::{Claims, RequireMerchantAdmin};
+use crate::error::{ApiError, ErrorBody};
+use crate::routes::{audit, parse_decimal};
+use crate::routes::payments::{ensure_owner, gateway};
+use crate::validation::{ValidatedJson, Validate, Validator};
+use crate::AppState;
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct ReturnRequest {
+    #[serde(default)]
+    pub reason: String,
+    pub items: Vec<ReturnItemRequest>,
+}
+
+impl Validate for ReturnRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.max_len("reason", &self.reason, 255)
+            .check(!self.items.is_empty(), "items", "must contain at least one item")
+            .each("items", &self.items);
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct ReturnItemRequest {
+    pub order_item_id: i32,
+    pub quantity: i32,
+}
+
+impl Validate for ReturnItemRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.positive("quantity", self.quantity);
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct ApproveReturnRequest {
+    /// Put the returned items back into sellable stock
+    #[serde(default)]
+    pub restock: bool,
+}
+
+impl Validate for ApproveReturnRequest {
+    fn validate(&self, _v: &mut Validator) {}
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct ReturnRefundRequest {
+    /// Amount to refund; what the returned items sold for when omitted
+    pub amount: Option<String>,
+}
+
+impl Validate for ReturnRefundRequest {
+    fn validate(&self, v: &mut Validator) {
+        if let Some(amount) = &self.amount {
+            v.positive_amount("amount", amount);
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ReturnItemResponse {
+    pub order_item_id: i32,
+    pub quantity: i32,
+}
+
+impl From<ReturnItem> for ReturnItemResponse {
+    fn from(item: ReturnItem) -> Self {
+        Self {
+            order_item_id: item.order_item_id,
+            quantity: item.quantity,
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ReturnResponse {
+    pub id: i32,
+    pub order_id: i32,
+    /// `requested`, `approved`, `rejected` or `refunded`
+    pub status: String,
+    pub reason: String,
+    pub restocked: bool,
+    pub refund_amount: Option<String>,
+    #[schema(value_type = i64)]
+    pub created_gmt: Timestamp,
+    #[schema(value_type = i64)]
+    pub modified_gmt: Timestamp,
+    pub items: Vec<ReturnItemResponse>,
+}
+
+impl From<ReturnWithItems> for ReturnResponse {
+    fn from(rma: ReturnWithItems) -> Self {
+        Self {
+            id: rma.rma.id,
+            order_id: rma.rma.order_id,
+            status: rma.rma.status,
+            reason: rma.rma.reason,
+            restocked: rma.rma.restocked,
+            refund_amount: rma.rma.refund_amount.map(|amount| amount.to_string()),
+            created_gmt: rma.rma.created_gmt,
+            modified_gmt: rma.rma.modified_gmt,
+            items: rma.items.into_iter().map(|i| i.into()).collect(),
+        }
+    }
+}
+
+/// Request a return of some of an order's items
+#[utoipa::path(
+    post,
+    path = "/api/orders/{mid}/{id}/returns",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Order ID")
+    ),
+    request_body = ReturnRequest,
+    responses(
+        (status = 201, description = "Return requested", body = ReturnResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 404, description = "Order or item not found", body = ErrorBody),
+        (status = 422, description = "More than the returnable quantity", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "returns"
+)]
+pub async fn create_return(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((mid, id)): Path<(i32, i32)>,
+    ValidatedJson(req): ValidatedJson<ReturnRequest>,
+) -> Result<(StatusCode, Json<ReturnResponse>), ApiError> {
+    ensure_owner(&state, &claims, mid, id).await?;
+
+    let lines: Vec<ReturnLine> = req
+        .items
+        .iter()
+        .map(|item| ReturnLine { order_item_id: item.order_item_id, quantity: item.quantity })
+        .collect();
+
+    ReturnService::create(&state.db, mid, id, req.reason.trim(), &lines)
+        .await
+        .map(|rma| (StatusCode::CREATED, Json(rma.into())))
+        .map_err(ApiError::from)
+}
+
+/// List the returns of an order
+#[utoipa::path(
+    get,
+    path = "/api/orders/{mid}/{id}/returns",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Order ID")
+    ),
+    responses(
+        (status = 200, description = "Returns, oldest first", body = Vec<ReturnResponse>),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 404, description = "Order not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "returns"
+)]
+pub async fn list_returns(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Json<Vec<ReturnResponse>>, ApiError> {
+    ensure_owner(&state, &claims, mid, id).await?;
+
+    ReturnService::list(&state.db, mid, id)
+        .await
+        .map(|returns| Json(returns.into_iter().map(|r| r.into()).collect()))
+        .map_err(ApiError::from)
+}
+
+/// Approve a requested return
+#[utoipa::path(
+    post,
+    path = "/api/orders/{mid}/{id}/returns/{return_id}/approve",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Order ID"),
+        ("return_id" = i32, Path, description = "Return ID")
+    ),
+    request_body = ApproveReturnRequest,
+    responses(
+        (status = 200, description = "Return approved", body = ReturnResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Return not found", body = ErrorBody),
+        (status = 409, description = "Return is no longer awaiting a decision", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "returns"
+)]
+pub async fn approve_return(
+    State(state): State<AppState>,
+    _admin: RequireMerchantAdmin,
+    Path((mid, id, return_id)): Path<(i32, i32, i32)>,
+    ValidatedJson(req): ValidatedJson<ApproveReturnRequest>,
+) -> Result<Json<ReturnResponse>, ApiError> {
+    ReturnService::approve(&state.db, mid, id, return_id, req.restock)
+        .await
+        .map(|rma| Json(rma.into()))
+        .map_err(ApiError::from)
+}
+
+/// Reject a requested return
+#[utoipa::path(
+    post,
+    path = "/api/orders/{mid}/{id}/returns/{return_id}/reject",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Order ID"),
+        ("return_id" = i32, Path, description = "Return ID")
+    ),
+    responses(
+        (status = 200, description = "Return rejected", body = ReturnResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Return not found", body = ErrorBody),
+        (status = 409, description = "Return is no longer awaiting a decision", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "returns"
+)]
+pub async fn reject_return(
+    State(state): State<AppState>,
+    _admin: RequireMerchantAdmin,
+    Path((mid, id, return_id)): Path<(i32, i32, i32)>,
+) -> Result<Json<ReturnResponse>, ApiError> {
+    ReturnService::reject(&state.db, mid, id, return_id)
+        .await
+        .map(|rma| Json(rma.into()))
+        .map_err(ApiError::from)
+}
+
+/// Refund an approved return through the payment gateway
+#[utoipa::path(
+    post,
+    path = "/api/orders/{mid}/{id}/returns/{return_id}/refund",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Order ID"),
+        ("return_id" = i32, Path, description = "Return ID")
+    ),
+    request_body = ReturnRefundRequest,
+    responses(
+        (status = 200, description = "Return refunded", body = ReturnResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Return not found", body = ErrorBody),
+        (status = 409, description = "Return is not approved, or the order has no payment to refund", body = ErrorBody),
+        (status = 422, description = "Invalid amount", body = ErrorBody),
+        (status = 502, description = "Payment gateway error", body = ErrorBody),
+        (status = 503, description = "No payment gateway configured", body = ErrorBody)
+    ),
+    tag = "returns"
+)]
+pub async fn refund_return(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id, return_id)): Path<(i32, i32, i32)>,
+    ValidatedJson(req): ValidatedJson<ReturnRefundRequest>,
+) -> Result<Json<ReturnResponse>, ApiError> {
+    let gateway = gateway(&state)?;
+    let amount = req
+        .amount
+        .as_deref()
+        .map(|amount| parse_decimal("amount", amount))
+        .transpose()?;
+    let before = ReturnService::find(&state.db, mid, id, return_id).await?.rma;
+
+    let refunded = ReturnService::refund(&state.db, gateway, mid, id, return_id, amount).await?;
+    let change = Change::new("return", return_id, "refund").diff(&before, &refunded.rma);
+    audit::record(&state, &admin.0, mid, change).await;
+    Ok(Json(refunded.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::Role;
+    use sea_orm::{DatabaseBackend, MockDatabase};
+
+    #[tokio::test]
+    async fn test_refund_requires_gateway() {
+        let state = AppState::mock(MockDatabase::new(DatabaseBackend::Postgres).into_connection());
+        let admin = RequireMerchantAdmin::new(Claims::new(1, 1).with_role(Role::MerchantAdmin));
+
+        let req = ReturnRefundRequest { amount: None };
+        let result = refund_return(State(state), admin, Path((1, 9, 3)), ValidatedJson(req)).await;
+        assert_eq!(result.err().map(|e| e.status()), Some(StatusCode::SERVICE_UNAVAILABLE));
+    }
+}
@@ -0,0 +1,400 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use commercerack_core::Timestamp;
+use commercerack_customer::carts::CustomerCartService;
+use entity::prelude::CustomerCart;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use crate::auth::Claims;
+use crate::error::{ApiError, ErrorBody, FieldError};
+use crate::routes::cart::{load_cart, save_cart, CartResponse};
+use crate::routes::cart_sync;
+use crate::validation::{ValidatedJson, Validate, Validator};
+use crate::AppState;
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct CreateSavedCartRequest {
+    pub name: String,
+    /// Existing cart to keep under the name; a new empty cart when omitted
+    #[serde(default)]
+    pub cart_id: Option<String>,
+}
+
+impl Validate for CreateSavedCartRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("name", &self.name, 60);
+        if let Some(cart_id) = &self.cart_id {
+            v.required("cart_id", cart_id, 36);
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct RenameSavedCartRequest {
+    pub name: String,
+}
+
+impl Validate for RenameSavedCartRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("name", &self.name, 60);
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct MoveItemsRequest {
+    /// Customer cart the items move to
+    pub to: String,
+    /// Line IDs (or SKUs) to move; every line when empty
+    #[serde(default)]
+    pub lines: Vec<String>,
+}
+
+impl Validate for MoveItemsRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("to", &self.to, 36)
+            .check(self.lines.len() <= 100, "lines", "must have at most 100 entries");
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct SavedCartResponse {
+    pub cart_id: String,
+    pub name: String,
+    /// The cart being filled and checked out; the others are saved for later
+    pub active: bool,
+    pub item_count: i32,
+    #[schema(value_type = String)]
+    pub subtotal: Decimal,
+    #[schema(value_type = i64)]
+    pub created_gmt: Timestamp,
+    #[schema(value_type = i64)]
+    pub modified_gmt: Timestamp,
+}
+
+impl SavedCartResponse {
+    fn new(saved: CustomerCart, cart: &CartResponse) -> Self {
+        Self {
+            cart_id: saved.cart_id,
+            name: saved.name,
+            active: saved.active,
+            item_count: cart.item_count,
+            subtotal: cart.subtotal,
+            created_gmt: saved.created_gmt,
+            modified_gmt: saved.modified_gmt,
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct MoveItemsResponse {
+    pub from: CartResponse,
+    pub to: CartResponse,
+}
+
+/// The customer's record of `cart_id`, or 404
+async fn owned(state: &AppState, mid: i32, cid: i32, cart_id: &str) -> Result<CustomerCart, ApiError> {
+    let saved = CustomerCartService::find(&*state.db, mid, cid, cart_id).await?;
+    saved.ok_or_else(|| ApiError::not_found("Cart"))
+}
+
+async fn render(state: &AppState, saved: CustomerCart) -> Result<Json<SavedCartResponse>, ApiError> {
+    let cart = load_cart(state, &saved.cart_id).await?;
+    Ok(Json(SavedCartResponse::new(saved, &CartResponse::from(&cart))))
+}
+
+/// List a customer's carts
+#[utoipa::path(
+    get,
+    path = "/api/customers/{mid}/{cid}/carts",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("cid" = i32, Path, description = "Customer ID")
+    ),
+    responses(
+        (status = 200, description = "The customer's carts, the active one first, then by name", body = Vec<SavedCartResponse>),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Another customer's carts", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "saved_carts"
+)]
+pub async fn list(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((mid, cid)): Path<(i32, i32)>,
+) -> Result<Json<Vec<SavedCartResponse>>, ApiError> {
+    claims.ensure_self(cid)?;
+    let mut carts = Vec::new();
+    for saved in CustomerCartService::list(&*state.db, mid, cid).await? {
+        match state.cart_store.get_cart(&saved.cart_id).await? {
+            Some(cart) => carts.push(SavedCartResponse::new(saved, &CartResponse::from(&cart))),
+            // Expired from the cart store
+            None => CustomerCartService::forget(&*state.db, &saved.cart_id).await?,
+        }
+    }
+    Ok(Json(carts))
+}
+
+/// Keep a cart under a name: a new empty one, or an existing cart such as
+/// the one the customer is filling. It becomes their active cart if they
+/// have none.
+#[utoipa::path(
+    post,
+    path = "/api/customers/{mid}/{cid}/carts",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("cid" = i32, Path, description = "Customer ID")
+    ),
+    request_body = CreateSavedCartRequest,
+    responses(
+        (status = 201, description = "The named cart", body = SavedCartResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Another customer's carts", body = ErrorBody),
+        (status = 404, description = "Cart not found", body = ErrorBody),
+        (status = 409, description = "A cart with the name exists, or the cart is already kept", body = ErrorBody),
+        (status = 422, description = "Validation failed", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "saved_carts"
+)]
+pub async fn create(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((mid, cid)): Path<(i32, i32)>,
+    ValidatedJson(req): ValidatedJson<CreateSavedCartRequest>,
+) -> Result<(StatusCode, Json<SavedCartResponse>), ApiError> {
+    claims.ensure_self(cid)?;
+    let cart = match &req.cart_id {
+        Some(cart_id) => {
+            let cart = load_cart(&state, cart_id).await?;
+            if CustomerCartService::owner(&*state.db, cart_id).await?.is_some() {
+                return Err(ApiError::Conflict("Cart is already kept".to_string()));
+            }
+            cart
+        }
+        None => state.cart_store.create_cart().await?,
+    };
+
+    let saved = CustomerCartService::add(&*state.db, mid, cid, &cart.cart_id, req.name.trim()).await?;
+    Ok((StatusCode::CREATED, Json(SavedCartResponse::new(saved, &CartResponse::from(&cart)))))
+}
+
+/// Rename a customer's cart
+#[utoipa::path(
+    put,
+    path = "/api/customers/{mid}/{cid}/carts/{cart_id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("cid" = i32, Path, description = "Customer ID"),
+        ("cart_id" = String, Path, description = "Cart ID")
+    ),
+    request_body = RenameSavedCartRequest,
+    responses(
+        (status = 200, description = "The renamed cart", body = SavedCartResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Another customer's carts", body = ErrorBody),
+        (status = 404, description = "Cart not found", body = ErrorBody),
+        (status = 409, description = "Another cart has the name", body = ErrorBody),
+        (status = 422, description = "Validation failed", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "saved_carts"
+)]
+pub async fn rename(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((mid, cid, cart_id)): Path<(i32, i32, String)>,
+    ValidatedJson(req): ValidatedJson<RenameSavedCartRequest>,
+) -> Result<Json<SavedCartResponse>, ApiError> {
+    claims.ensure_self(cid)?;
+    let saved = CustomerCartService::rename(&*state.db, mid, cid, &cart_id, req.name.trim()).await?;
+    render(&state, saved).await
+}
+
+/// Delete a customer's cart and everything in it
+#[utoipa::path(
+    delete,
+    path = "/api/customers/{mid}/{cid}/carts/{cart_id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("cid" = i32, Path, description = "Customer ID"),
+        ("cart_id" = String, Path, description = "Cart ID")
+    ),
+    responses(
+        (status = 204, description = "Cart deleted"),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Another customer's carts", body = ErrorBody),
+        (status = 404, description = "Cart not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "saved_carts"
+)]
+pub async fn delete(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((mid, cid, cart_id)): Path<(i32, i32, String)>,
+) -> Result<StatusCode, ApiError> {
+    claims.ensure_self(cid)?;
+    if !CustomerCartService::remove(&*state.db, mid, cid, &cart_id).await? {
+        return Err(ApiError::not_found("Cart"));
+    }
+    if state.cart_store.delete_cart(&cart_id).await? {
+        cart_sync::publish_deleted(&cart_id);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Make a cart the customer's active cart, the one they fill and check
+/// out. The cart that was active is saved for later.
+#[utoipa::path(
+    post,
+    path = "/api/customers/{mid}/{cid}/carts/{cart_id}/activate",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("cid" = i32, Path, description = "Customer ID"),
+        ("cart_id" = String, Path, description = "Cart ID")
+    ),
+    responses(
+        (status = 200, description = "The now active cart", body = SavedCartResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Another customer's carts", body = ErrorBody),
+        (status = 404, description = "Cart not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "saved_carts"
+)]
+pub async fn activate(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((mid, cid, cart_id)): Path<(i32, i32, String)>,
+) -> Result<Json<SavedCartResponse>, ApiError> {
+    claims.ensure_self(cid)?;
+    let saved = CustomerCartService::activate(&*state.db, mid, cid, &cart_id).await?;
+    render(&state, saved).await
+}
+
+/// Move lines from one of a customer's carts to another, e.g. to save
+/// them for later. A line the other cart already holds has its quantity
+/// added there.
+#[utoipa::path(
+    post,
+    path = "/api/customers/{mid}/{cid}/carts/{cart_id}/move",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("cid" = i32, Path, description = "Customer ID"),
+        ("cart_id" = String, Path, description = "Cart the lines move from")
+    ),
+    request_body = MoveItemsRequest,
+    responses(
+        (status = 200, description = "Both carts after the move", body = MoveItemsResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Another customer's carts", body = ErrorBody),
+        (status = 404, description = "Either cart not found, or a line is not in the cart", body = ErrorBody),
+        (status = 422, description = "Validation failed", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "saved_carts"
+)]
+pub async fn move_items(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((mid, cid, cart_id)): Path<(i32, i32, String)>,
+    ValidatedJson(req): ValidatedJson<MoveItemsRequest>,
+) -> Result<Json<MoveItemsResponse>, ApiError> {
+    claims.ensure_self(cid)?;
+    if req.to == cart_id {
+        return Err(ApiError::Validation(vec![FieldError::new("to", "must be a different cart")]));
+    }
+    owned(&state, mid, cid, &cart_id).await?;
+    owned(&state, mid, cid, &req.to).await?;
+
+    // Always lock in the same order so two moves between one pair can't deadlock
+    let (first, second) = if cart_id < req.to { (&cart_id, &req.to) } else { (&req.to, &cart_id) };
+    let _first = state.cart_store.lock_cart(first).await;
+    let _second = state.cart_store.lock_cart(second).await;
+    let mut from = load_cart(&state, &cart_id).await?;
+    let mut to = load_cart(&state, &req.to).await?;
+
+    let lines = if req.lines.is_empty() {
+        // Carts saved before line IDs name their lines by SKU
+        from.items
+            .iter()
+            .map(|item| if item.line_id.is_empty() { item.sku.clone() } else { item.line_id.clone() })
+            .collect()
+    } else {
+        req.lines
+    };
+    for line in &lines {
+        let item = from
+            .get_item(line)
+            .cloned()
+            .ok_or_else(|| ApiError::NotFound(format!("Item {} is not in the cart", line)))?;
+        from.remove_item(line);
+        to.add_line(item);
+    }
+
+    let Json(to) = save_cart(&state, &mut to).await?;
+    let Json(from) = save_cart(&state, &mut from).await?;
+    Ok(Json(MoveItemsResponse { from, to }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sea_orm::{DatabaseBackend, MockDatabase};
+
+    fn kept(cart_id: &str, name: &str, active: bool) -> CustomerCart {
+        CustomerCart {
+            id: 1,
+            mid: 1,
+            cid: 7,
+            cart_id: cart_id.to_string(),
+            name: name.to_string(),
+            active,
+            created_gmt: Timestamp::from_unix(1_700_000_000),
+            modified_gmt: Timestamp::from_unix(1_700_000_000),
+        }
+    }
+
+    fn state(db: MockDatabase) -> AppState {
+        AppState::mock(db.into_connection())
+    }
+
+    #[tokio::test]
+    async fn test_move_items_between_carts() {
+        let state = state(
+            MockDatabase::new(DatabaseBackend::Postgres)
+                .append_query_results([vec![kept("from", "Cart", true)]])
+                .append_query_results([vec![kept("to", "Later", false)]]),
+        );
+        let mut from = commercerack_cart::Cart::with_id("from".to_string());
+        from.add_item("SKU001".to_string(), "Widget".to_string(), 2, Decimal::new(1000, 2));
+        from.add_item("SKU002".to_string(), "Gadget".to_string(), 1, Decimal::new(500, 2));
+        state.cart_store.save_cart(&from).await.unwrap();
+        let mut to = commercerack_cart::Cart::with_id("to".to_string());
+        to.add_item("SKU001".to_string(), "Widget".to_string(), 1, Decimal::new(1000, 2));
+        state.cart_store.save_cart(&to).await.unwrap();
+
+        let req = MoveItemsRequest { to: "to".to_string(), lines: vec!["SKU001".to_string()] };
+        let Json(moved) =
+            move_items(State(state), Claims::new(7, 1), Path((1, 7, "from".to_string())), ValidatedJson(req))
+                .await
+                .unwrap();
+
+        assert_eq!(moved.from.items.len(), 1);
+        assert_eq!(moved.from.items[0].sku, "SKU002");
+        assert_eq!(moved.to.items.len(), 1);
+        assert_eq!(moved.to.items[0].quantity, 3);
+    }
+
+    #[tokio::test]
+    async fn test_customers_only_reach_their_own_carts() {
+        let state = state(MockDatabase::new(DatabaseBackend::Postgres));
+
+        let result = list(State(state), Claims::new(8, 1), Path((1, 7))).await;
+        assert!(matches!(result, Err(ApiError::Forbidden(_))));
+    }
+}
@@ -0,0 +1,549 @@
+//! Customer segment routes
+//!
+//! Merchant staff define segments as rules over customers' lifetime value,
+//! order history and RFM scores. Members are worked out nightly, and right
+//! after a segment is created or changed, by a queued refresh; until it
+//! has run a segment's `computed_gmt` is absent.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use commercerack_core::Timestamp;
+use commercerack_customer::segments::{
+    Comparison, Match, RfmCount, RuleField, SegmentDefinition, SegmentRule, SegmentRules, SegmentService, MAX_RULES,
+    MAX_WINDOW_DAYS,
+};
+use commercerack_db::pagination::Cursor;
+use commercerack_jobs::segments::RefreshSegments;
+use commercerack_jobs::JobQueue;
+use entity::prelude::{CustomerMetric, Segment};
+use serde::{Deserialize, Serialize};
+use crate::auth::RequireMerchantAdmin;
+use crate::error::{ApiError, ErrorBody};
+use crate::validation::{self, ValidatedJson, Validate, Validator};
+use crate::AppState;
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct SegmentListQuery {
+    pub mid: i32,
+}
+
+/// One condition, e.g. `{"field": "spent", "op": "gt", "value": "500",
+/// "within_days": 90}` for customers who spent more than 500 in 90 days
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct SegmentRuleRequest {
+    /// `spent`, `orders`, `average_order_value`, `days_since_last_order`,
+    /// `days_since_first_order`, `recency_score`, `frequency_score` or
+    /// `monetary_score`
+    pub field: String,
+    /// `gt`, `gte`, `lt`, `lte` or `eq`
+    pub op: String,
+    pub value: String,
+    /// Only count orders from this many days back; `spent` and `orders` only
+    pub within_days: Option<u32>,
+}
+
+impl Validate for SegmentRuleRequest {
+    fn validate(&self, v: &mut Validator) {
+        let field = self.field.parse::<RuleField>();
+        v.check(field.is_ok(), "field", "is not a known rule field")
+            .check(self.op.parse::<Comparison>().is_ok(), "op", "must be one of gt, gte, lt, lte, eq")
+            .amount("value", &self.value);
+        if let (Some(days), Ok(field)) = (self.within_days, field) {
+            v.check(field.windowed(), "within_days", "only applies to spent and orders")
+                .check(
+                    (1..=MAX_WINDOW_DAYS).contains(&days),
+                    "within_days",
+                    &format!("must be between 1 and {}", MAX_WINDOW_DAYS),
+                );
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct SegmentRequest {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// `all` (default) when customers must match every rule, `any` when
+    /// one will do
+    #[serde(rename = "match")]
+    pub matches: Option<String>,
+    pub rules: Vec<SegmentRuleRequest>,
+}
+
+impl Validate for SegmentRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("name", &self.name, 64)
+            .max_len("description", &self.description, 255)
+            .check(
+                (1..=MAX_RULES).contains(&self.rules.len()),
+                "rules",
+                &format!("must list between 1 and {} rules", MAX_RULES),
+            )
+            .each("rules", &self.rules);
+        if let Some(matches) = &self.matches {
+            v.check(matches.parse::<Match>().is_ok(), "match", "must be all or any");
+        }
+    }
+}
+
+impl SegmentRequest {
+    /// Definition of a validated request
+    fn definition(self) -> Result<SegmentDefinition, ApiError> {
+        let rules = self
+            .rules
+            .into_iter()
+            .map(|rule| {
+                Ok(SegmentRule {
+                    field: rule.field.parse().map_err(ApiError::BadRequest)?,
+                    op: rule.op.parse().map_err(ApiError::BadRequest)?,
+                    value: rule.value.trim().parse().map_err(|_| ApiError::BadRequest("value".to_string()))?,
+                    within_days: rule.within_days,
+                })
+            })
+            .collect::<Result<_, ApiError>>()?;
+        Ok(SegmentDefinition {
+            name: self.name.trim().to_string(),
+            description: self.description,
+            rules: SegmentRules {
+                matches: self.matches.as_deref().map(str::parse).transpose().map_err(ApiError::BadRequest)?.unwrap_or_default(),
+                rules,
+            },
+        })
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct CreateSegmentRequest {
+    pub mid: i32,
+    #[serde(flatten)]
+    pub segment: SegmentRequest,
+}
+
+impl Validate for CreateSegmentRequest {
+    fn validate(&self, v: &mut Validator) {
+        self.segment.validate(v);
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct SegmentRuleResponse {
+    pub field: &'static str,
+    pub op: &'static str,
+    pub value: String,
+    pub within_days: Option<u32>,
+}
+
+impl From<SegmentRule> for SegmentRuleResponse {
+    fn from(rule: SegmentRule) -> Self {
+        Self {
+            field: rule.field.as_str(),
+            op: rule.op.as_str(),
+            value: rule.value.to_string(),
+            within_days: rule.within_days,
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct SegmentResponse {
+    pub id: i32,
+    pub mid: i32,
+    pub name: String,
+    pub description: String,
+    #[serde(rename = "match")]
+    pub matches: &'static str,
+    pub rules: Vec<SegmentRuleResponse>,
+    /// Customers in the segment at the last refresh
+    pub members: i32,
+    #[schema(value_type = i64)]
+    pub created_gmt: Timestamp,
+    #[schema(value_type = i64)]
+    pub modified_gmt: Timestamp,
+    /// When members were last worked out; absent until the first refresh
+    #[schema(value_type = Option<i64>)]
+    pub computed_gmt: Option<Timestamp>,
+}
+
+impl TryFrom<Segment> for SegmentResponse {
+    type Error = ApiError;
+
+    fn try_from(segment: Segment) -> Result<Self, ApiError> {
+        let rules = SegmentRules::of(&segment)?;
+        Ok(Self {
+            id: segment.id,
+            mid: segment.mid,
+            name: segment.name,
+            description: segment.description,
+            matches: rules.matches.as_str(),
+            rules: rules.rules.into_iter().map(Into::into).collect(),
+            members: segment.members,
+            created_gmt: segment.created_gmt,
+            modified_gmt: segment.modified_gmt,
+            computed_gmt: segment.computed_gmt,
+        })
+    }
+}
+
+/// A customer's lifetime value and RFM scores as of the last refresh
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct CustomerMetricsResponse {
+    pub cid: i32,
+    /// Paid orders
+    pub orders: i32,
+    /// Total of paid orders
+    pub lifetime_value: String,
+    #[schema(value_type = i64)]
+    pub first_order_gmt: Timestamp,
+    #[schema(value_type = i64)]
+    pub last_order_gmt: Timestamp,
+    /// 1 to 5; 5 for the fifth of customers who ordered most recently
+    pub recency_score: i32,
+    /// 1 to 5; 5 for the fifth who ordered most often
+    pub frequency_score: i32,
+    /// 1 to 5; 5 for the fifth who spent most
+    pub monetary_score: i32,
+    /// `champions`, `loyal`, `new`, `promising`, `at_risk`, `lost` or
+    /// `needs_attention`
+    pub rfm_segment: String,
+    #[schema(value_type = i64)]
+    pub computed_gmt: Timestamp,
+}
+
+impl From<CustomerMetric> for CustomerMetricsResponse {
+    fn from(metrics: CustomerMetric) -> Self {
+        Self {
+            cid: metrics.cid,
+            orders: metrics.orders,
+            lifetime_value: metrics.lifetime_value.to_string(),
+            first_order_gmt: metrics.first_order_gmt,
+            last_order_gmt: metrics.last_order_gmt,
+            recency_score: metrics.recency_score,
+            frequency_score: metrics.frequency_score,
+            monetary_score: metrics.monetary_score,
+            rfm_segment: metrics.rfm_segment,
+            computed_gmt: metrics.computed_gmt,
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct MembersQuery {
+    #[serde(default = "default_limit")]
+    pub limit: u64,
+    /// `next_cursor` of the previous page
+    pub cursor: Option<String>,
+}
+
+fn default_limit() -> u64 {
+    50
+}
+
+impl Validate for MembersQuery {
+    fn validate(&self, v: &mut Validator) {
+        if let Some(cursor) = &self.cursor {
+            v.check(cursor.parse::<Cursor>().is_ok(), "cursor", "is not a cursor from a previous page");
+        }
+        v.check((1..=500).contains(&self.limit), "limit", "must be between 1 and 500");
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct SegmentMembersResponse {
+    /// By customer ID
+    pub customers: Vec<CustomerMetricsResponse>,
+    /// Pass as `cursor` to get the next page; absent on the last page
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct RfmCountResponse {
+    pub rfm_segment: String,
+    pub customers: i64,
+}
+
+impl From<RfmCount> for RfmCountResponse {
+    fn from(count: RfmCount) -> Self {
+        Self {
+            rfm_segment: count.rfm_segment,
+            customers: count.customers,
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct CustomerSegmentsResponse {
+    /// Absent until the customer has a paid order and a refresh has run
+    pub metrics: Option<CustomerMetricsResponse>,
+    /// The merchant's segments the customer is in, by name
+    pub segments: Vec<SegmentResponse>,
+}
+
+fn responses(segments: Vec<Segment>) -> Result<Vec<SegmentResponse>, ApiError> {
+    segments.into_iter().map(SegmentResponse::try_from).collect()
+}
+
+/// List the merchant's segments
+#[utoipa::path(
+    get,
+    path = "/api/segments",
+    params(SegmentListQuery),
+    responses(
+        (status = 200, description = "Segments by name", body = Vec<SegmentResponse>),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "customers"
+)]
+pub async fn list(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Query(query): Query<SegmentListQuery>,
+) -> Result<Json<Vec<SegmentResponse>>, ApiError> {
+    let segments = SegmentService::list(&*state.db, admin.0.scoped_mid(query.mid)).await?;
+    Ok(Json(responses(segments)?))
+}
+
+/// Define a segment and queue its first refresh
+#[utoipa::path(
+    post,
+    path = "/api/segments",
+    request_body = CreateSegmentRequest,
+    responses(
+        (status = 201, description = "Segment created; members follow once the queued refresh runs", body = SegmentResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 409, description = "Name already taken", body = ErrorBody),
+        (status = 422, description = "Validation failed", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "customers"
+)]
+pub async fn create(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    ValidatedJson(req): ValidatedJson<CreateSegmentRequest>,
+) -> Result<(StatusCode, Json<SegmentResponse>), ApiError> {
+    let mid = admin.0.scoped_mid(req.mid);
+    let segment = SegmentService::create(&*state.db, mid, req.segment.definition()?).await?;
+    JobQueue::enqueue(&*state.db, &RefreshSegments { mid }).await?;
+    Ok((StatusCode::CREATED, Json(segment.try_into()?)))
+}
+
+/// Get a segment
+#[utoipa::path(
+    get,
+    path = "/api/segments/{mid}/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Segment ID")
+    ),
+    responses(
+        (status = 200, description = "The segment", body = SegmentResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Segment not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "customers"
+)]
+pub async fn get(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Json<SegmentResponse>, ApiError> {
+    let segment = SegmentService::find(&*state.db, admin.0.scoped_mid(mid), id)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Segment"))?;
+    Ok(Json(segment.try_into()?))
+}
+
+/// Change a segment and queue a refresh of its members
+#[utoipa::path(
+    put,
+    path = "/api/segments/{mid}/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Segment ID")
+    ),
+    request_body = SegmentRequest,
+    responses(
+        (status = 200, description = "Segment updated; members follow once the queued refresh runs", body = SegmentResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Segment not found", body = ErrorBody),
+        (status = 409, description = "Name already taken", body = ErrorBody),
+        (status = 422, description = "Validation failed", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "customers"
+)]
+pub async fn update(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+    ValidatedJson(req): ValidatedJson<SegmentRequest>,
+) -> Result<Json<SegmentResponse>, ApiError> {
+    let mid = admin.0.scoped_mid(mid);
+    let segment = SegmentService::update(&*state.db, mid, id, req.definition()?).await?;
+    JobQueue::enqueue(&*state.db, &RefreshSegments { mid }).await?;
+    Ok(Json(segment.try_into()?))
+}
+
+/// Delete a segment
+#[utoipa::path(
+    delete,
+    path = "/api/segments/{mid}/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Segment ID")
+    ),
+    responses(
+        (status = 204, description = "Segment deleted"),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Segment not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "customers"
+)]
+pub async fn delete(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<StatusCode, ApiError> {
+    if SegmentService::delete(&state.db, admin.0.scoped_mid(mid), id).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::not_found("Segment"))
+    }
+}
+
+/// List a segment's customers with their metrics
+#[utoipa::path(
+    get,
+    path = "/api/segments/{mid}/{id}/customers",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Segment ID"),
+        MembersQuery
+    ),
+    responses(
+        (status = 200, description = "Members at the last refresh, by customer ID", body = SegmentMembersResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Segment not found", body = ErrorBody),
+        (status = 422, description = "Invalid cursor or limit", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "customers"
+)]
+pub async fn members(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+    Query(query): Query<MembersQuery>,
+) -> Result<Json<SegmentMembersResponse>, ApiError> {
+    validation::validate(&query)?;
+    let cursor = query.cursor.as_deref().map(str::parse::<Cursor>).transpose()?;
+
+    let (customers, next) =
+        SegmentService::members(&*state.db, admin.0.scoped_mid(mid), id, query.limit, cursor.as_ref()).await?;
+    Ok(Json(SegmentMembersResponse {
+        customers: customers.into_iter().map(Into::into).collect(),
+        next_cursor: next.map(|cursor| cursor.to_string()),
+    }))
+}
+
+/// Count the merchant's customers in each RFM segment
+#[utoipa::path(
+    get,
+    path = "/api/segments/rfm",
+    params(SegmentListQuery),
+    responses(
+        (status = 200, description = "RFM segments with customers, by name", body = Vec<RfmCountResponse>),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "customers"
+)]
+pub async fn rfm(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Query(query): Query<SegmentListQuery>,
+) -> Result<Json<Vec<RfmCountResponse>>, ApiError> {
+    let counts = SegmentService::rfm_counts(&*state.db, admin.0.scoped_mid(query.mid)).await?;
+    Ok(Json(counts.into_iter().map(Into::into).collect()))
+}
+
+/// Get a customer's metrics and segments
+#[utoipa::path(
+    get,
+    path = "/api/customers/{mid}/{id}/segments",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Customer ID")
+    ),
+    responses(
+        (status = 200, description = "Metrics and segments as of the last refresh", body = CustomerSegmentsResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "customers"
+)]
+pub async fn customer_segments(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Json<CustomerSegmentsResponse>, ApiError> {
+    let mid = admin.0.scoped_mid(mid);
+    let metrics = SegmentService::metrics_of(&*state.db, mid, id).await?;
+    let segments = SegmentService::segments_of(&*state.db, mid, id).await?;
+    Ok(Json(CustomerSegmentsResponse {
+        metrics: metrics.map(Into::into),
+        segments: responses(segments)?,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+
+    fn invalid_fields(req: &SegmentRequest) -> Vec<String> {
+        match validation::validate(req) {
+            Err(ApiError::Validation(errors)) => errors.into_iter().map(|e| e.field).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn request(rules: serde_json::Value) -> SegmentRequest {
+        serde_json::from_value(serde_json::json!({ "name": "Big spenders", "rules": rules })).unwrap()
+    }
+
+    #[test]
+    fn test_segment_request_validation() {
+        let big_spenders = request(serde_json::json!([{ "field": "spent", "op": "gt", "value": "500", "within_days": 90 }]));
+        assert!(invalid_fields(&big_spenders).is_empty());
+
+        let definition = big_spenders.definition().unwrap();
+        assert_eq!(definition.rules.matches, Match::All);
+        assert_eq!(definition.rules.rules[0].value, Decimal::from(500));
+
+        let invalid = request(serde_json::json!([
+            { "field": "recency_score", "op": "eq", "value": "5", "within_days": 90 },
+            { "field": "lifetime", "op": "over", "value": "lots" }
+        ]));
+        assert_eq!(
+            invalid_fields(&invalid),
+            vec!["rules[0].within_days", "rules[1].field", "rules[1].op", "rules[1].value"]
+        );
+        assert_eq!(invalid_fields(&request(serde_json::json!([]))), vec!["rules"]);
+    }
+}
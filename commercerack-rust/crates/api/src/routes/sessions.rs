@@ -0,0 +1,111 @@
+//! Login session routes
+//!
+//! A customer sees where they are signed in and can sign any of those
+//! sessions out, e.g. a lost phone. See `commercerack_customer::sessions`.
+
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    Json,
+};
+use commercerack_core::Timestamp;
+use commercerack_customer::sessions::{SessionService, DEVICE_MAX_LEN};
+use serde::Serialize;
+use crate::auth::ActiveSession;
+use crate::error::{ApiError, ErrorBody};
+use crate::routes::two_factor::account;
+use crate::AppState;
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct LoginSessionResponse {
+    pub id: i32,
+    /// User-Agent of the login
+    pub device: String,
+    /// IP the session was last used from
+    pub ip: String,
+    #[schema(value_type = i64)]
+    pub created_gmt: Timestamp,
+    #[schema(value_type = i64)]
+    pub last_seen_gmt: Timestamp,
+    /// Whether this is the session making the request
+    pub current: bool,
+}
+
+/// Device description of a login: its User-Agent, trimmed to what is kept
+pub(crate) fn device(headers: &HeaderMap) -> String {
+    headers
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(|agent| agent.chars().take(DEVICE_MAX_LEN).collect())
+        .unwrap_or_default()
+}
+
+/// List the caller's signed-in sessions
+#[utoipa::path(
+    get,
+    path = "/api/auth/sessions",
+    responses(
+        (status = 200, description = "Sessions, most recently used first", body = Vec<LoginSessionResponse>),
+        (status = 401, description = "Missing or invalid token, or session signed out", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "auth"
+)]
+pub async fn list(
+    State(state): State<AppState>,
+    ActiveSession(claims): ActiveSession,
+) -> Result<Json<Vec<LoginSessionResponse>>, ApiError> {
+    let cid = account(&claims)?;
+    let sessions = SessionService::list(&*state.db, claims.mid, cid).await?;
+
+    Ok(Json(
+        sessions
+            .into_iter()
+            .map(|session| LoginSessionResponse {
+                current: claims.sid == Some(session.id),
+                id: session.id,
+                device: session.device,
+                ip: session.ip,
+                created_gmt: session.created_gmt,
+                last_seen_gmt: session.last_seen_gmt,
+            })
+            .collect(),
+    ))
+}
+
+/// Sign one of the caller's sessions out
+#[utoipa::path(
+    delete,
+    path = "/api/auth/sessions/{id}",
+    params(("id" = i32, Path, description = "Session ID")),
+    responses(
+        (status = 204, description = "Session signed out; its refresh tokens no longer work"),
+        (status = 401, description = "Missing or invalid token, or session signed out", body = ErrorBody),
+        (status = 404, description = "Session not found, or already signed out", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "auth"
+)]
+pub async fn revoke(
+    State(state): State<AppState>,
+    ActiveSession(claims): ActiveSession,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, ApiError> {
+    let cid = account(&claims)?;
+    SessionService::revoke(&*state.db, claims.mid, cid, id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_device_is_the_trimmed_user_agent() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(device(&headers), "");
+
+        headers.insert(header::USER_AGENT, "x".repeat(300).parse().unwrap());
+        assert_eq!(device(&headers).len(), DEVICE_MAX_LEN);
+    }
+}
@@ -0,0 +1,480 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use commercerack_shipping::{
+    RateBasis, ShippingError, ShippingRateInput, ShippingTransitInput, ShippingZoneInput, ShippingZoneService,
+};
+use ::entity::prelude::{ShippingRate, ShippingTransitTime, ShippingZone};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use crate::auth::RequireMerchantAdmin;
+use crate::error::{ApiError, ErrorBody};
+use crate::routes::parse_decimal;
+use crate::validation::{ValidatedJson, Validate, Validator};
+use crate::AppState;
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct ZoneRequest {
+    pub mid: i32,
+    pub name: String,
+    /// ISO 3166-1 alpha-2 codes; omit when `zone_id` is set
+    #[serde(default)]
+    pub countries: Vec<String>,
+    /// Omit to cover every state of the countries
+    #[serde(default)]
+    pub states: Vec<String>,
+    /// Zip prefixes; omit to cover every zip
+    #[serde(default)]
+    pub zips: Vec<String>,
+    /// Zones are matched in ascending position
+    #[serde(default)]
+    pub position: i32,
+    /// Geo zone to cover instead of listing countries, states and zips
+    #[serde(default)]
+    pub zone_id: Option<i32>,
+}
+
+impl Validate for ZoneRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("name", &self.name, 60);
+        if self.zone_id.is_some() {
+            v.check(
+                self.countries.is_empty() && self.states.is_empty() && self.zips.is_empty(),
+                "zone_id",
+                "cannot be combined with countries, states or zips",
+            );
+            return;
+        }
+        v.check(!self.countries.is_empty(), "countries", "must list at least one country")
+            .check(
+                self.countries
+                    .iter()
+                    .all(|c| c.trim().len() == 2 && c.trim().chars().all(|c| c.is_ascii_alphabetic())),
+                "countries",
+                "must be two-letter country codes",
+            );
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct RateRequest {
+    /// Code stored on orders, e.g. `ground`
+    pub method: String,
+    /// Shown to shoppers, e.g. `Ground (3-5 days)`
+    pub name: String,
+    /// `flat`, `weight` or `price`
+    pub basis: String,
+    /// Lower bound of the tier, inclusive
+    #[serde(default = "default_min")]
+    pub min_value: String,
+    /// Upper bound of the tier, exclusive; omit for no limit
+    pub max_value: Option<String>,
+    pub amount: String,
+}
+
+fn default_min() -> String {
+    "0".to_string()
+}
+
+impl Validate for RateRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("method", &self.method, 40)
+            .required("name", &self.name, 80)
+            .check(self.basis.parse::<RateBasis>().is_ok(), "basis", "must be flat, weight or price")
+            .amount("min_value", &self.min_value)
+            .amount("amount", &self.amount);
+
+        if let Some(max_value) = &self.max_value {
+            v.amount("max_value", max_value);
+            let min = self.min_value.trim().parse::<Decimal>().unwrap_or_default();
+            let max = max_value.trim().parse::<Decimal>().unwrap_or_default();
+            v.check(max > min, "max_value", "must be greater than min_value");
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct RateResponse {
+    pub id: i32,
+    pub zone_id: i32,
+    pub method: String,
+    pub name: String,
+    pub basis: String,
+    pub min_value: String,
+    pub max_value: Option<String>,
+    pub amount: String,
+}
+
+impl From<ShippingRate> for RateResponse {
+    fn from(rate: ShippingRate) -> Self {
+        Self {
+            id: rate.id,
+            zone_id: rate.zone_id,
+            method: rate.method,
+            name: rate.name,
+            basis: rate.basis,
+            min_value: rate.min_value.normalize().to_string(),
+            max_value: rate.max_value.map(|max| max.normalize().to_string()),
+            amount: rate.amount.to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct TransitRequest {
+    /// Fewest business days the carrier takes
+    pub min_days: i32,
+    /// Most business days the carrier takes
+    pub max_days: i32,
+}
+
+impl Validate for TransitRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.check((0..=90).contains(&self.min_days), "min_days", "must be between 0 and 90")
+            .check((0..=90).contains(&self.max_days), "max_days", "must be between 0 and 90")
+            .check(self.max_days >= self.min_days, "max_days", "must be at least min_days");
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct TransitResponse {
+    pub zone_id: i32,
+    pub method: String,
+    pub min_days: i32,
+    pub max_days: i32,
+}
+
+impl From<ShippingTransitTime> for TransitResponse {
+    fn from(transit: ShippingTransitTime) -> Self {
+        Self {
+            zone_id: transit.zone_id,
+            method: transit.method,
+            min_days: transit.min_days,
+            max_days: transit.max_days,
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ZoneResponse {
+    pub id: i32,
+    pub mid: i32,
+    pub name: String,
+    pub countries: Vec<String>,
+    pub states: Vec<String>,
+    pub zips: Vec<String>,
+    pub position: i32,
+    pub zone_id: Option<i32>,
+    pub rates: Vec<RateResponse>,
+    /// Business days each method takes, for delivery estimates
+    pub transit: Vec<TransitResponse>,
+}
+
+fn split(list: &str) -> Vec<String> {
+    list.split(',').filter(|s| !s.is_empty()).map(String::from).collect()
+}
+
+impl ZoneResponse {
+    fn new(zone: ShippingZone, rates: Vec<ShippingRate>, transit: Vec<ShippingTransitTime>) -> Self {
+        Self {
+            id: zone.id,
+            mid: zone.mid,
+            name: zone.name,
+            countries: split(&zone.countries),
+            states: split(&zone.states),
+            zips: split(&zone.zips),
+            position: zone.position,
+            zone_id: zone.zone_id,
+            rates: rates.into_iter().map(|r| r.into()).collect(),
+            transit: transit.into_iter().map(|t| t.into()).collect(),
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct ListQuery {
+    pub mid: i32,
+}
+
+/// Create a shipping zone
+#[utoipa::path(
+    post,
+    path = "/api/shipping/zones",
+    request_body = ZoneRequest,
+    responses(
+        (status = 201, description = "Zone created", body = ZoneResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 422, description = "Missing name, invalid countries or unknown zone", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "shipping"
+)]
+pub async fn create_zone(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    ValidatedJson(req): ValidatedJson<ZoneRequest>,
+) -> Result<(StatusCode, Json<ZoneResponse>), ApiError> {
+    let input = ShippingZoneInput {
+        name: req.name,
+        countries: req.countries,
+        states: req.states,
+        zips: req.zips,
+        position: req.position,
+        zone_id: req.zone_id,
+    };
+
+    ShippingZoneService::create_zone(&state.db, admin.0.scoped_mid(req.mid), input)
+        .await
+        .map(|zone| (StatusCode::CREATED, Json(ZoneResponse::new(zone, Vec::new(), Vec::new()))))
+        .map_err(ApiError::from)
+}
+
+/// A merchant's shipping zones in matching order, with their rates and
+/// transit times
+#[utoipa::path(
+    get,
+    path = "/api/shipping/zones",
+    params(ListQuery),
+    responses(
+        (status = 200, description = "Zones with rates and transit times", body = Vec<ZoneResponse>),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "shipping"
+)]
+pub async fn list_zones(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Query(query): Query<ListQuery>,
+) -> Result<Json<Vec<ZoneResponse>>, ApiError> {
+    ShippingZoneService::list_zones(&state.db, admin.0.scoped_mid(query.mid))
+        .await
+        .map(|zones| {
+            Json(zones.into_iter().map(|(zone, rates, transit)| ZoneResponse::new(zone, rates, transit)).collect())
+        })
+        .map_err(ApiError::from)
+}
+
+/// Delete a shipping zone and its rates
+#[utoipa::path(
+    delete,
+    path = "/api/shipping/zones/{mid}/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Zone ID")
+    ),
+    responses(
+        (status = 204, description = "Zone deleted"),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Zone not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "shipping"
+)]
+pub async fn delete_zone(
+    State(state): State<AppState>,
+    _admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<StatusCode, ApiError> {
+    if ShippingZoneService::delete_zone(&state.db, mid, id).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ShippingError::ZoneNotFound.into())
+    }
+}
+
+/// Add a rate tier to a zone
+#[utoipa::path(
+    post,
+    path = "/api/shipping/zones/{mid}/{id}/rates",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Zone ID")
+    ),
+    request_body = RateRequest,
+    responses(
+        (status = 201, description = "Rate added", body = RateResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Zone not found", body = ErrorBody),
+        (status = 422, description = "Invalid basis, bounds or amount", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "shipping"
+)]
+pub async fn add_rate(
+    State(state): State<AppState>,
+    _admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+    ValidatedJson(req): ValidatedJson<RateRequest>,
+) -> Result<(StatusCode, Json<RateResponse>), ApiError> {
+    let input = ShippingRateInput {
+        basis: req.basis.parse().map_err(ApiError::BadRequest)?,
+        min_value: parse_decimal("min_value", req.min_value.trim())?,
+        max_value: req.max_value.map(|max| parse_decimal("max_value", max.trim())).transpose()?,
+        amount: parse_decimal("amount", req.amount.trim())?,
+        method: req.method,
+        name: req.name,
+    };
+
+    ShippingZoneService::add_rate(&state.db, mid, id, input)
+        .await
+        .map(|rate| (StatusCode::CREATED, Json(rate.into())))
+        .map_err(ApiError::from)
+}
+
+/// Delete a rate tier
+#[utoipa::path(
+    delete,
+    path = "/api/shipping/rates/{mid}/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Rate ID")
+    ),
+    responses(
+        (status = 204, description = "Rate deleted"),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Rate not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "shipping"
+)]
+pub async fn delete_rate(
+    State(state): State<AppState>,
+    _admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<StatusCode, ApiError> {
+    if ShippingZoneService::delete_rate(&state.db, mid, id).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ShippingError::RateNotFound.into())
+    }
+}
+
+/// Record how many business days a method takes within a zone
+#[utoipa::path(
+    put,
+    path = "/api/shipping/zones/{mid}/{id}/transit/{method}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Zone ID"),
+        ("method" = String, Path, description = "Shipping method, e.g. `ground`")
+    ),
+    request_body = TransitRequest,
+    responses(
+        (status = 200, description = "Transit time recorded, replacing any earlier one", body = TransitResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Zone not found", body = ErrorBody),
+        (status = 422, description = "Invalid day counts", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "shipping"
+)]
+pub async fn set_transit(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id, method)): Path<(i32, i32, String)>,
+    ValidatedJson(req): ValidatedJson<TransitRequest>,
+) -> Result<Json<TransitResponse>, ApiError> {
+    let input = ShippingTransitInput { min_days: req.min_days, max_days: req.max_days };
+    ShippingZoneService::set_transit(&state.db, admin.0.scoped_mid(mid), id, method.trim(), input)
+        .await
+        .map(|transit| Json(transit.into()))
+        .map_err(ApiError::from)
+}
+
+/// Forget a method's transit time within a zone
+#[utoipa::path(
+    delete,
+    path = "/api/shipping/zones/{mid}/{id}/transit/{method}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Zone ID"),
+        ("method" = String, Path, description = "Shipping method, e.g. `ground`")
+    ),
+    responses(
+        (status = 204, description = "Transit time deleted"),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Transit time not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "shipping"
+)]
+pub async fn delete_transit(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id, method)): Path<(i32, i32, String)>,
+) -> Result<StatusCode, ApiError> {
+    if ShippingZoneService::delete_transit(&state.db, admin.0.scoped_mid(mid), id, method.trim()).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ShippingError::TransitNotFound.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::{Claims, Role};
+    use sea_orm::{DatabaseBackend, MockDatabase};
+
+    #[test]
+    fn test_rate_validation() {
+        let req = RateRequest {
+            method: "ground".to_string(),
+            name: "Ground".to_string(),
+            basis: "volume".to_string(),
+            min_value: "10".to_string(),
+            max_value: Some("5".to_string()),
+            amount: "-1".to_string(),
+        };
+        match crate::validation::validate(&req) {
+            Err(ApiError::Validation(errors)) => {
+                let fields: Vec<_> = errors.iter().map(|e| e.field.as_str()).collect();
+                assert_eq!(fields, vec!["basis", "amount", "max_value"]);
+            }
+            other => panic!("unexpected result {:?}", other.err().map(|e| e.status())),
+        }
+    }
+
+    #[test]
+    fn test_transit_validation() {
+        let req = TransitRequest { min_days: 5, max_days: 3 };
+        match crate::validation::validate(&req) {
+            Err(ApiError::Validation(errors)) => {
+                let fields: Vec<_> = errors.iter().map(|e| e.field.as_str()).collect();
+                assert_eq!(fields, vec!["max_days"]);
+            }
+            other => panic!("unexpected result {:?}", other.err().map(|e| e.status())),
+        }
+        assert!(crate::validation::validate(&TransitRequest { min_days: 0, max_days: 0 }).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_add_rate_to_unknown_zone() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([Vec::<ShippingZone>::new()])
+            .into_connection();
+        let state = AppState::mock(db);
+        let admin = RequireMerchantAdmin::new(Claims::new(1, 1).with_role(Role::MerchantAdmin));
+
+        let req = RateRequest {
+            method: "ground".to_string(),
+            name: "Ground".to_string(),
+            basis: "flat".to_string(),
+            min_value: "0".to_string(),
+            max_value: None,
+            amount: "7.99".to_string(),
+        };
+        let result = add_rate(State(state), admin, Path((1, 99)), ValidatedJson(req)).await;
+        assert_eq!(result.err().map(|e| e.status()), Some(StatusCode::NOT_FOUND));
+    }
+}
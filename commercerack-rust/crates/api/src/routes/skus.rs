@@ -0,0 +1,325 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use commercerack_audit::Change;
+use commercerack_core::Timestamp;
+use commercerack_product::sku::{SKUService, SKU};
+use serde::{Deserialize, Serialize};
+use crate::auth::RequireMerchantAdmin;
+use crate::error::{ApiError, ErrorBody};
+use crate::routes::{audit, parse_decimal};
+use crate::validation::{ValidatedJson, Validate, Validator};
+use crate::AppState;
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct SkuRequest {
+    pub sku: String,
+    pub title: String,
+    pub price: String,
+    pub cost: String,
+    #[serde(default)]
+    pub upc: String,
+    #[serde(default)]
+    pub inv_available: i32,
+    #[serde(default)]
+    pub qty_onshelf: i32,
+    /// Per-unit weight used for shipping rates, as a decimal string
+    #[serde(default = "default_weight")]
+    pub weight: String,
+    /// Stock at or below this raises an `inventory.low_stock` event; omit to turn alerts off
+    pub reorder_point: Option<i32>,
+    /// Units to order when stock runs low
+    #[serde(default)]
+    pub reorder_quantity: i32,
+}
+
+fn default_weight() -> String {
+    "0".to_string()
+}
+
+impl Validate for SkuRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("sku", &self.sku, 45)
+            .max_len("title", &self.title, 80)
+            .amount("price", &self.price)
+            .amount("cost", &self.cost)
+            .max_len("upc", &self.upc, 13)
+            .non_negative("inv_available", self.inv_available)
+            .non_negative("qty_onshelf", self.qty_onshelf)
+            .amount("weight", &self.weight)
+            .non_negative("reorder_quantity", self.reorder_quantity);
+        if let Some(reorder_point) = self.reorder_point {
+            v.non_negative("reorder_point", reorder_point);
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct SkuResponse {
+    pub id: i32,
+    pub pid: i32,
+    pub mid: i32,
+    pub sku: String,
+    pub title: String,
+    pub price: String,
+    pub cost: String,
+    pub upc: String,
+    pub inv_available: i32,
+    pub qty_onshelf: i32,
+    pub weight: String,
+    pub reorder_point: Option<i32>,
+    pub reorder_quantity: i32,
+    /// When the current low stock alert went out; cleared once stock recovers
+    #[schema(value_type = Option<i64>)]
+    pub low_stock_gmt: Option<Timestamp>,
+}
+
+impl From<SKU> for SkuResponse {
+    fn from(sku: SKU) -> Self {
+        Self {
+            id: sku.id,
+            pid: sku.pid,
+            mid: sku.mid,
+            sku: sku.sku,
+            title: sku.title,
+            price: sku.price.to_string(),
+            cost: sku.cost.to_string(),
+            upc: sku.upc,
+            inv_available: sku.inv_available,
+            qty_onshelf: sku.qty_onshelf,
+            weight: sku.weight.to_string(),
+            reorder_point: sku.reorder_point,
+            reorder_quantity: sku.reorder_quantity,
+            low_stock_gmt: sku.low_stock_gmt,
+        }
+    }
+}
+
+impl SkuRequest {
+    fn into_sku(self, id: i32, mid: i32, pid: i32) -> Result<SKU, ApiError> {
+        let price = parse_decimal("price", &self.price)?;
+        let cost = parse_decimal("cost", &self.cost)?;
+        let weight = parse_decimal("weight", &self.weight)?;
+
+        Ok(SKU {
+            id,
+            pid,
+            mid,
+            sku: self.sku,
+            title: self.title,
+            price,
+            cost,
+            upc: self.upc,
+            inv_available: self.inv_available,
+            qty_onshelf: self.qty_onshelf,
+            weight,
+            reorder_point: self.reorder_point,
+            reorder_quantity: self.reorder_quantity,
+            low_stock_gmt: None,
+        })
+    }
+}
+
+/// Create a new SKU for a product
+#[utoipa::path(
+    post,
+    path = "/api/products/{mid}/{pid}/skus",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("pid" = i32, Path, description = "Product ID")
+    ),
+    request_body = SkuRequest,
+    responses(
+        (status = 201, description = "SKU created successfully", body = SkuResponse),
+        (status = 400, description = "Invalid price or cost", body = ErrorBody),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "products"
+)]
+pub async fn create(
+    State(state): State<AppState>,
+    _admin: RequireMerchantAdmin,
+    Path((mid, pid)): Path<(i32, i32)>,
+    ValidatedJson(req): ValidatedJson<SkuRequest>,
+) -> Result<(StatusCode, Json<SkuResponse>), ApiError> {
+    let sku = req.into_sku(0, mid, pid)?;
+
+    SKUService::create(&state.db, sku)
+        .await
+        .map(|sku| (StatusCode::CREATED, Json(sku.into())))
+        .map_err(ApiError::from)
+}
+
+/// List all SKUs of a product
+#[utoipa::path(
+    get,
+    path = "/api/products/{mid}/{pid}/skus",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("pid" = i32, Path, description = "Product ID")
+    ),
+    responses(
+        (status = 200, description = "SKUs for the product", body = Vec<SkuResponse>),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "products"
+)]
+pub async fn list(
+    State(state): State<AppState>,
+    Path((mid, pid)): Path<(i32, i32)>,
+) -> Result<Json<Vec<SkuResponse>>, ApiError> {
+    SKUService::find_by_product(&state.db, mid, pid)
+        .await
+        .map(|skus| Json(skus.into_iter().map(|s| s.into()).collect()))
+        .map_err(ApiError::from)
+}
+
+/// Get a SKU by ID
+#[utoipa::path(
+    get,
+    path = "/api/products/{mid}/{pid}/skus/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("pid" = i32, Path, description = "Product ID"),
+        ("id" = i32, Path, description = "SKU ID")
+    ),
+    responses(
+        (status = 200, description = "SKU found", body = SkuResponse),
+        (status = 404, description = "SKU not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "products"
+)]
+pub async fn get(
+    State(state): State<AppState>,
+    Path((mid, pid, id)): Path<(i32, i32, i32)>,
+) -> Result<Json<SkuResponse>, ApiError> {
+    SKUService::find_by_id(&state.db, mid, id)
+        .await?
+        .filter(|sku| sku.pid == pid)
+        .map(|sku| Json(sku.into()))
+        .ok_or_else(|| ApiError::not_found("SKU"))
+}
+
+/// Replace a SKU
+#[utoipa::path(
+    put,
+    path = "/api/products/{mid}/{pid}/skus/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("pid" = i32, Path, description = "Product ID"),
+        ("id" = i32, Path, description = "SKU ID")
+    ),
+    request_body = SkuRequest,
+    responses(
+        (status = 200, description = "SKU updated", body = SkuResponse),
+        (status = 400, description = "Invalid price or cost", body = ErrorBody),
+        (status = 404, description = "SKU not found", body = ErrorBody),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "products"
+)]
+pub async fn update(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, pid, id)): Path<(i32, i32, i32)>,
+    ValidatedJson(req): ValidatedJson<SkuRequest>,
+) -> Result<Json<SkuResponse>, ApiError> {
+    let before = SKUService::find_by_id(&state.db, mid, id)
+        .await?
+        .filter(|sku| sku.pid == pid)
+        .ok_or_else(|| ApiError::not_found("SKU"))?;
+
+    let mut sku = req.into_sku(id, mid, pid)?;
+    // Only the low stock scan raises and clears alerts
+    sku.low_stock_gmt = before.low_stock_gmt;
+    let sku = SKUService::update(&state.db, sku).await?;
+    audit::record(&state, &admin.0, mid, Change::new("sku", id, "update").diff(&before, &sku)).await;
+    Ok(Json(sku.into()))
+}
+
+/// Delete a SKU
+#[utoipa::path(
+    delete,
+    path = "/api/products/{mid}/{pid}/skus/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("pid" = i32, Path, description = "Product ID"),
+        ("id" = i32, Path, description = "SKU ID")
+    ),
+    responses(
+        (status = 204, description = "SKU deleted"),
+        (status = 404, description = "SKU not found", body = ErrorBody),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "products"
+)]
+pub async fn delete(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, pid, id)): Path<(i32, i32, i32)>,
+) -> Result<StatusCode, ApiError> {
+    let before = SKUService::find_by_id(&state.db, mid, id)
+        .await?
+        .filter(|sku| sku.pid == pid)
+        .ok_or_else(|| ApiError::not_found("SKU"))?;
+
+    SKUService::delete(&state.db, mid, id).await?;
+    audit::record(&state, &admin.0, mid, Change::new("sku", id, "delete").removed(&before)).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+    use sea_orm::{DatabaseBackend, MockDatabase};
+
+    fn sku_model() -> SKU {
+        SKU {
+            id: 7,
+            pid: 3,
+            mid: 1,
+            sku: "WIDGET-RED".to_string(),
+            title: "Widget (Red)".to_string(),
+            price: Decimal::new(1999, 2),
+            cost: Decimal::new(850, 2),
+            upc: "012345678905".to_string(),
+            inv_available: 10,
+            qty_onshelf: 12,
+            weight: Decimal::new(250, 3),
+            reorder_point: None,
+            reorder_quantity: 0,
+            low_stock_gmt: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_sku() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![sku_model()]])
+            .into_connection();
+
+        let Json(sku) = get(State(AppState::mock(db)), Path((1, 3, 7))).await.unwrap();
+        assert_eq!(sku.sku, "WIDGET-RED");
+        assert_eq!(sku.price, "19.99");
+    }
+
+    #[tokio::test]
+    async fn test_get_sku_wrong_product() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![sku_model()]])
+            .into_connection();
+
+        let result = get(State(AppState::mock(db)), Path((1, 99, 7))).await;
+        assert_eq!(result.err().map(|e| e.status()), Some(StatusCode::NOT_FOUND));
+    }
+}
@@ -0,0 +1,144 @@
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use chrono::{Duration, Utc};
+use commercerack_core::Timestamp;
+use commercerack_customer::CustomerService;
+use commercerack_order::{stats::ProductSales, OrderService};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use crate::auth::RequireMerchantAdmin;
+use crate::error::{ApiError, ErrorBody};
+use crate::validation::{self, Validate, Validator};
+use crate::AppState;
+
+/// Best sellers returned with the stats
+const TOP_PRODUCTS: u64 = 10;
+
+/// Periods the dashboard can ask for, counted back from now
+const PERIODS: [(&str, i64); 5] = [("today", 0), ("7d", 7), ("30d", 30), ("90d", 90), ("365d", 365)];
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct StatsQuery {
+    pub mid: i32,
+    /// One of `today`, `7d`, `30d`, `90d` or `365d`
+    #[serde(default = "default_period")]
+    pub period: String,
+}
+
+impl StatsQuery {
+    /// Days the period covers before today, if it is one we know
+    fn days(&self) -> Option<i64> {
+        PERIODS.iter().find(|(name, _)| *name == self.period).map(|(_, days)| *days)
+    }
+}
+
+impl Validate for StatsQuery {
+    fn validate(&self, v: &mut Validator) {
+        v.check(self.days().is_some(), "period", "must be one of today, 7d, 30d, 90d or 365d");
+    }
+}
+
+fn default_period() -> String {
+    "30d".to_string()
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct TopProductResponse {
+    pub sku: String,
+    pub product_name: String,
+    pub quantity: i64,
+    pub revenue: String,
+}
+
+impl From<ProductSales> for TopProductResponse {
+    fn from(sales: ProductSales) -> Self {
+        Self {
+            sku: sales.sku,
+            product_name: sales.product_name,
+            quantity: sales.quantity,
+            revenue: sales.revenue.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct StatsResponse {
+    pub period: String,
+    #[schema(value_type = i64)]
+    pub from: Timestamp,
+    #[schema(value_type = i64)]
+    pub to: Timestamp,
+    /// Orders created in the period
+    pub orders: i64,
+    /// Of those, the ones that have been paid
+    pub paid_orders: i64,
+    /// Total of the paid orders
+    pub revenue: String,
+    /// `revenue` over `paid_orders`
+    pub average_order_value: String,
+    /// Customer accounts created in the period
+    pub new_customers: u64,
+    /// Best selling SKUs by quantity
+    pub top_products: Vec<TopProductResponse>,
+}
+
+/// Sales figures for a merchant dashboard
+#[utoipa::path(
+    get,
+    path = "/api/admin/stats",
+    params(StatsQuery),
+    responses(
+        (status = 200, description = "Order, revenue and customer totals for the period", body = StatsResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 422, description = "Unknown period", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "stats"
+)]
+pub async fn get(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Query(query): Query<StatsQuery>,
+) -> Result<Json<StatsResponse>, ApiError> {
+    validation::validate(&query)?;
+    let days = query.days().unwrap_or_default();
+
+    let now = Utc::now();
+    let start = now.date_naive().and_hms_opt(0, 0, 0).unwrap_or_default().and_utc() - Duration::days(days);
+    let (from, to) = (Timestamp::from(start), Timestamp::from(now));
+
+    let mid = admin.0.scoped_mid(query.mid);
+    let db = &*state.db;
+    let summary = OrderService::sales_summary(db, mid, from, to).await?;
+    let new_customers = CustomerService::count_new(db, mid, from, to).await?;
+    let top_products = OrderService::top_products(db, mid, from, to, TOP_PRODUCTS).await?;
+
+    Ok(Json(StatsResponse {
+        period: query.period,
+        from,
+        to,
+        orders: summary.orders,
+        paid_orders: summary.paid_orders,
+        average_order_value: summary.average_order_value().to_string(),
+        revenue: summary.revenue.unwrap_or(Decimal::ZERO).to_string(),
+        new_customers,
+        top_products: top_products.into_iter().map(|sales| sales.into()).collect(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_period_validation() {
+        let query = |period: &str| StatsQuery { mid: 1, period: period.to_string() };
+        assert_eq!(query("today").days(), Some(0));
+        assert_eq!(query("90d").days(), Some(90));
+        assert!(validation::validate(&query("30d")).is_ok());
+        assert!(validation::validate(&query("1y")).is_err());
+    }
+}
@@ -0,0 +1,283 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use commercerack_core::Timestamp;
+use commercerack_order::pickup::{StoreLocationInput, StoreLocationService};
+use entity::prelude::StoreLocation;
+use serde::{Deserialize, Serialize};
+use crate::auth::RequireMerchantAdmin;
+use crate::error::{ApiError, ErrorBody};
+use crate::validation::{ValidatedJson, Validate, Validator};
+use crate::AppState;
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct StoreLocationRequest {
+    pub name: String,
+    pub street1: String,
+    #[serde(default)]
+    pub street2: String,
+    pub city: String,
+    #[serde(default)]
+    pub state: String,
+    #[serde(default)]
+    pub zip: String,
+    /// ISO 3166-1 alpha-2 code
+    pub country: String,
+    #[serde(default)]
+    pub phone: String,
+    /// Opening hours as shown to shoppers, e.g. `Mon-Sat 9-6`
+    #[serde(default)]
+    pub hours: String,
+    /// Inactive locations are hidden from shoppers and can't be chosen at checkout
+    #[serde(default = "default_active")]
+    pub active: bool,
+}
+
+fn default_active() -> bool {
+    true
+}
+
+impl Validate for StoreLocationRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("name", &self.name, 80)
+            .required("street1", &self.street1, 100)
+            .max_len("street2", &self.street2, 100)
+            .required("city", &self.city, 60)
+            .max_len("state", &self.state, 20)
+            .max_len("zip", &self.zip, 20)
+            .check(
+                self.country.trim().len() == 2 && self.country.trim().chars().all(|c| c.is_ascii_alphabetic()),
+                "country",
+                "must be a two-letter country code",
+            )
+            .max_len("phone", &self.phone, 32)
+            .max_len("hours", &self.hours, 500);
+    }
+}
+
+impl From<StoreLocationRequest> for StoreLocationInput {
+    fn from(req: StoreLocationRequest) -> Self {
+        Self {
+            name: req.name,
+            street1: req.street1,
+            street2: req.street2,
+            city: req.city,
+            state: req.state,
+            zip: req.zip,
+            country: req.country,
+            phone: req.phone,
+            hours: req.hours,
+            active: req.active,
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct CreateStoreLocationRequest {
+    pub mid: i32,
+    #[serde(flatten)]
+    pub location: StoreLocationRequest,
+}
+
+impl Validate for CreateStoreLocationRequest {
+    fn validate(&self, v: &mut Validator) {
+        self.location.validate(v);
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct StoreLocationResponse {
+    pub id: i32,
+    pub mid: i32,
+    pub name: String,
+    pub street1: String,
+    pub street2: String,
+    pub city: String,
+    pub state: String,
+    pub zip: String,
+    pub country: String,
+    pub phone: String,
+    pub hours: String,
+    pub active: bool,
+    #[schema(value_type = i64)]
+    pub created_gmt: Timestamp,
+}
+
+impl From<StoreLocation> for StoreLocationResponse {
+    fn from(location: StoreLocation) -> Self {
+        Self {
+            id: location.id,
+            mid: location.mid,
+            name: location.name,
+            street1: location.street1,
+            street2: location.street2,
+            city: location.city,
+            state: location.state,
+            zip: location.zip,
+            country: location.country,
+            phone: location.phone,
+            hours: location.hours,
+            active: location.active,
+            created_gmt: location.created_gmt,
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct ListQuery {
+    pub mid: i32,
+}
+
+/// Add a store location buyers can collect orders from
+#[utoipa::path(
+    post,
+    path = "/api/store-locations",
+    request_body = CreateStoreLocationRequest,
+    responses(
+        (status = 201, description = "Store location created", body = StoreLocationResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 422, description = "Invalid store location", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "orders"
+)]
+pub async fn create(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    ValidatedJson(req): ValidatedJson<CreateStoreLocationRequest>,
+) -> Result<(StatusCode, Json<StoreLocationResponse>), ApiError> {
+    let mid = admin.0.scoped_mid(req.mid);
+    StoreLocationService::create(&*state.db, mid, req.location.into())
+        .await
+        .map(|location| (StatusCode::CREATED, Json(location.into())))
+        .map_err(ApiError::from)
+}
+
+/// A merchant's store locations by name. Shoppers see the active ones;
+/// merchant admins see them all.
+#[utoipa::path(
+    get,
+    path = "/api/store-locations",
+    params(ListQuery),
+    responses(
+        (status = 200, description = "Store locations", body = Vec<StoreLocationResponse>),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "orders"
+)]
+pub async fn list(
+    State(state): State<AppState>,
+    admin: Option<RequireMerchantAdmin>,
+    Query(query): Query<ListQuery>,
+) -> Result<Json<Vec<StoreLocationResponse>>, ApiError> {
+    let (mid, include_inactive) = match admin {
+        Some(admin) => (admin.0.scoped_mid(query.mid), true),
+        None => (query.mid, false),
+    };
+    StoreLocationService::list(&*state.db, mid, include_inactive)
+        .await
+        .map(|locations| Json(locations.into_iter().map(|l| l.into()).collect()))
+        .map_err(ApiError::from)
+}
+
+/// A store location; inactive ones only for merchant admins
+#[utoipa::path(
+    get,
+    path = "/api/store-locations/{mid}/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Store location ID")
+    ),
+    responses(
+        (status = 200, description = "Store location", body = StoreLocationResponse),
+        (status = 404, description = "Store location not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "orders"
+)]
+pub async fn get(
+    State(state): State<AppState>,
+    admin: Option<RequireMerchantAdmin>,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Json<StoreLocationResponse>, ApiError> {
+    let location = match admin {
+        Some(admin) => StoreLocationService::find(&*state.db, admin.0.scoped_mid(mid), id).await?,
+        None => StoreLocationService::pickup_location(&*state.db, mid, id).await?,
+    };
+    Ok(Json(location.into()))
+}
+
+/// Replace a store location's details. Deactivate it rather than delete
+/// it; orders placed for it keep pointing at it.
+#[utoipa::path(
+    put,
+    path = "/api/store-locations/{mid}/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Store location ID")
+    ),
+    request_body = StoreLocationRequest,
+    responses(
+        (status = 200, description = "Store location updated", body = StoreLocationResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Store location not found", body = ErrorBody),
+        (status = 422, description = "Invalid store location", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "orders"
+)]
+pub async fn update(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+    ValidatedJson(req): ValidatedJson<StoreLocationRequest>,
+) -> Result<Json<StoreLocationResponse>, ApiError> {
+    StoreLocationService::update(&*state.db, admin.0.scoped_mid(mid), id, req.into())
+        .await
+        .map(|location| Json(location.into()))
+        .map_err(ApiError::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use commercerack_order::OrderError;
+    use sea_orm::{DatabaseBackend, MockDatabase};
+
+    fn location(active: bool) -> StoreLocation {
+        StoreLocation {
+            id: 4,
+            mid: 1,
+            name: "Downtown".to_string(),
+            street1: "1 Main St".to_string(),
+            street2: String::new(),
+            city: "Springfield".to_string(),
+            state: "IL".to_string(),
+            zip: "62701".to_string(),
+            country: "US".to_string(),
+            phone: String::new(),
+            hours: "Mon-Sat 9-6".to_string(),
+            active,
+            created_gmt: Timestamp::EPOCH,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shoppers_do_not_see_inactive_locations() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![location(false)]])
+            .into_connection();
+        let state = AppState::mock(db);
+
+        let result = get(State(state), None, Path((1, 4))).await;
+        assert_eq!(result.err().map(|e| e.status()), Some(StatusCode::NOT_FOUND));
+        assert!(matches!(
+            ApiError::from(OrderError::LocationNotFound),
+            ApiError::NotFound(ref message) if message == "Store location not found"
+        ));
+    }
+}
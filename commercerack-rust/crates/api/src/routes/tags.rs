@@ -0,0 +1,248 @@
+//! Customer tag routes
+//!
+//! Merchant staff tag customers one at a time or in bulk, e.g. from a
+//! selection in the customer list, which filters by tag with `?tag=`.
+
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use commercerack_customer::tags::{normalize_tag, TagCount, TagService};
+use commercerack_customer::CustomerService;
+use serde::{Deserialize, Serialize};
+use crate::auth::RequireMerchantAdmin;
+use crate::error::{ApiError, ErrorBody};
+use crate::validation::{ValidatedJson, Validate, Validator};
+use crate::AppState;
+
+/// Most customers one bulk request may change
+pub const MAX_BULK_CUSTOMERS: usize = 500;
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct TagListQuery {
+    pub mid: i32,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct TagCountResponse {
+    pub tag: String,
+    /// Number of customers with the tag
+    pub customers: i64,
+}
+
+impl From<TagCount> for TagCountResponse {
+    fn from(count: TagCount) -> Self {
+        Self {
+            tag: count.tag,
+            customers: count.customers,
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct BulkTagRequest {
+    pub mid: i32,
+    /// e.g. `vip`; matched case-insensitively
+    pub tag: String,
+    /// Customers to tag or untag; other merchants' customers are skipped
+    pub cids: Vec<i32>,
+}
+
+impl Validate for BulkTagRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.check(normalize_tag(&self.tag).is_ok(), "tag", "may only contain letters, digits, - and _, up to 32")
+            .check(
+                (1..=MAX_BULK_CUSTOMERS).contains(&self.cids.len()),
+                "cids",
+                "must list between 1 and 500 customers",
+            );
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct BulkTagResponse {
+    /// Customers that gained or lost the tag
+    pub changed: u64,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct CustomerTagsResponse {
+    /// Alphabetically
+    pub tags: Vec<String>,
+}
+
+/// List the merchant's tags in use
+#[utoipa::path(
+    get,
+    path = "/api/customer-tags",
+    params(TagListQuery),
+    responses(
+        (status = 200, description = "Tags with their number of customers, alphabetically", body = Vec<TagCountResponse>),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "customers"
+)]
+pub async fn list(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Query(query): Query<TagListQuery>,
+) -> Result<Json<Vec<TagCountResponse>>, ApiError> {
+    let counts = TagService::counts(&*state.db, admin.0.scoped_mid(query.mid)).await?;
+    Ok(Json(counts.into_iter().map(Into::into).collect()))
+}
+
+/// Tag many customers at once
+#[utoipa::path(
+    post,
+    path = "/api/customer-tags/tag",
+    request_body = BulkTagRequest,
+    responses(
+        (status = 200, description = "Customers tagged", body = BulkTagResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 422, description = "Invalid tag, or too few or many customers", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "customers"
+)]
+pub async fn bulk_tag(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    ValidatedJson(req): ValidatedJson<BulkTagRequest>,
+) -> Result<Json<BulkTagResponse>, ApiError> {
+    let changed = TagService::tag(&*state.db, admin.0.scoped_mid(req.mid), &req.cids, &req.tag).await?;
+    Ok(Json(BulkTagResponse { changed }))
+}
+
+/// Untag many customers at once
+#[utoipa::path(
+    post,
+    path = "/api/customer-tags/untag",
+    request_body = BulkTagRequest,
+    responses(
+        (status = 200, description = "Customers untagged", body = BulkTagResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 422, description = "Invalid tag, or too few or many customers", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "customers"
+)]
+pub async fn bulk_untag(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    ValidatedJson(req): ValidatedJson<BulkTagRequest>,
+) -> Result<Json<BulkTagResponse>, ApiError> {
+    let changed = TagService::untag(&*state.db, admin.0.scoped_mid(req.mid), &req.cids, &req.tag).await?;
+    Ok(Json(BulkTagResponse { changed }))
+}
+
+/// List a customer's tags
+#[utoipa::path(
+    get,
+    path = "/api/customers/{mid}/{id}/tags",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Customer ID")
+    ),
+    responses(
+        (status = 200, description = "The customer's tags", body = CustomerTagsResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "customers"
+)]
+pub async fn customer_tags(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Json<CustomerTagsResponse>, ApiError> {
+    let tags = TagService::tags_of(&*state.db, admin.0.scoped_mid(mid), id).await?;
+    Ok(Json(CustomerTagsResponse { tags }))
+}
+
+/// Tag a customer
+#[utoipa::path(
+    put,
+    path = "/api/customers/{mid}/{id}/tags/{tag}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Customer ID"),
+        ("tag" = String, Path, description = "Tag to add")
+    ),
+    responses(
+        (status = 200, description = "The customer's tags", body = CustomerTagsResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Customer not found", body = ErrorBody),
+        (status = 422, description = "Invalid tag", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "customers"
+)]
+pub async fn add(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id, tag)): Path<(i32, i32, String)>,
+) -> Result<Json<CustomerTagsResponse>, ApiError> {
+    let mid = admin.0.scoped_mid(mid);
+    CustomerService::find_by_id(&state.db, mid, id)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Customer"))?;
+
+    TagService::tag(&*state.db, mid, &[id], &tag).await?;
+    let tags = TagService::tags_of(&*state.db, mid, id).await?;
+    Ok(Json(CustomerTagsResponse { tags }))
+}
+
+/// Untag a customer
+#[utoipa::path(
+    delete,
+    path = "/api/customers/{mid}/{id}/tags/{tag}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Customer ID"),
+        ("tag" = String, Path, description = "Tag to remove")
+    ),
+    responses(
+        (status = 200, description = "The customer's remaining tags", body = CustomerTagsResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 422, description = "Invalid tag", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "customers"
+)]
+pub async fn remove(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id, tag)): Path<(i32, i32, String)>,
+) -> Result<Json<CustomerTagsResponse>, ApiError> {
+    let mid = admin.0.scoped_mid(mid);
+    TagService::untag(&*state.db, mid, &[id], &tag).await?;
+    let tags = TagService::tags_of(&*state.db, mid, id).await?;
+    Ok(Json(CustomerTagsResponse { tags }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bulk_request_validation() {
+        let request = |tag: &str, cids: Vec<i32>| BulkTagRequest { mid: 1, tag: tag.to_string(), cids };
+
+        assert!(crate::validation::validate(&request("VIP", vec![7, 8])).is_ok());
+        match crate::validation::validate(&request("big spender", vec![])) {
+            Err(ApiError::Validation(errors)) => {
+                let fields: Vec<_> = errors.iter().map(|e| e.field.as_str()).collect();
+                assert_eq!(fields, vec!["tag", "cids"]);
+            }
+            other => panic!("unexpected result {:?}", other.err().map(|e| e.status())),
+        }
+        assert!(crate::validation::validate(&request("vip", vec![1; MAX_BULK_CUSTOMERS + 1])).is_err());
+    }
+}
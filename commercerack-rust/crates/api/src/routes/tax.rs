@@ -0,0 +1,308 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use commercerack_core::Timestamp;
+use commercerack_tax::{TaxAddress, TaxError, TaxRateInput, TaxRateService};
+use ::entity::prelude::TaxRate;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use crate::auth::RequireMerchantAdmin;
+use crate::error::{ApiError, ErrorBody};
+use crate::routes::parse_decimal;
+use crate::validation::{ValidatedJson, Validate, Validator};
+use crate::AppState;
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct TaxRateRequest {
+    /// Shown on the order's tax line, e.g. `CA State Tax`
+    pub name: String,
+    /// ISO 3166-1 alpha-2 code; omit when `zone_id` is set
+    #[serde(default)]
+    pub country: String,
+    /// Omit to apply to every state of the country
+    #[serde(default)]
+    pub state: String,
+    /// Zip prefix; omit to apply to every zip
+    #[serde(default)]
+    pub zip: String,
+    /// Percent as a decimal string, e.g. `7.25`
+    pub rate: String,
+    /// Geo zone the rate covers, instead of a country, state and zip
+    #[serde(default)]
+    pub zone_id: Option<i32>,
+}
+
+impl Validate for TaxRateRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("name", &self.name, 60);
+        if self.zone_id.is_some() {
+            v.check(
+                self.country.trim().is_empty() && self.state.trim().is_empty() && self.zip.trim().is_empty(),
+                "zone_id",
+                "cannot be combined with a country, state or zip",
+            );
+        } else {
+            v.check(
+                self.country.trim().len() == 2 && self.country.trim().chars().all(|c| c.is_ascii_alphabetic()),
+                "country",
+                "must be a two-letter country code",
+            );
+        }
+        v.max_len("state", &self.state, 20)
+            .max_len("zip", &self.zip, 20)
+            .positive_amount("rate", &self.rate);
+
+        let rate = self.rate.trim().parse::<Decimal>().unwrap_or_default();
+        v.check(rate < Decimal::ONE_HUNDRED, "rate", "must be less than 100 percent")
+            .check(rate.round_dp(4) == rate, "rate", "must have at most 4 decimal places");
+    }
+}
+
+impl TaxRateRequest {
+    pub(crate) fn into_input(self) -> Result<TaxRateInput, ApiError> {
+        Ok(TaxRateInput {
+            rate: parse_decimal("rate", self.rate.trim())?,
+            address: TaxAddress::new(&self.country, &self.state, &self.zip),
+            name: self.name,
+            zone_id: self.zone_id,
+        })
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct CreateTaxRateRequest {
+    pub mid: i32,
+    #[serde(flatten)]
+    pub rate: TaxRateRequest,
+}
+
+impl Validate for CreateTaxRateRequest {
+    fn validate(&self, v: &mut Validator) {
+        self.rate.validate(v);
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct TaxRateResponse {
+    pub id: i32,
+    pub mid: i32,
+    pub name: String,
+    pub country: String,
+    pub state: String,
+    pub zip: String,
+    pub rate: String,
+    pub zone_id: Option<i32>,
+    #[schema(value_type = i64)]
+    pub created_gmt: Timestamp,
+    #[schema(value_type = i64)]
+    pub modified_gmt: Timestamp,
+}
+
+impl From<TaxRate> for TaxRateResponse {
+    fn from(rate: TaxRate) -> Self {
+        Self {
+            id: rate.id,
+            mid: rate.mid,
+            name: rate.name,
+            country: rate.country,
+            state: rate.state,
+            zip: rate.zip,
+            rate: rate.rate.normalize().to_string(),
+            zone_id: rate.zone_id,
+            created_gmt: rate.created_gmt,
+            modified_gmt: rate.modified_gmt,
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct ListQuery {
+    pub mid: i32,
+    /// Only rates of this country
+    pub country: Option<String>,
+}
+
+/// Add a tax rate
+#[utoipa::path(
+    post,
+    path = "/api/tax/rates",
+    request_body = CreateTaxRateRequest,
+    responses(
+        (status = 201, description = "Rate created", body = TaxRateResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 422, description = "Invalid rate, name or location", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "tax"
+)]
+pub async fn create_rate(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    ValidatedJson(req): ValidatedJson<CreateTaxRateRequest>,
+) -> Result<(StatusCode, Json<TaxRateResponse>), ApiError> {
+    let mid = admin.0.scoped_mid(req.mid);
+    TaxRateService::create(&*state.db, mid, req.rate.into_input()?)
+        .await
+        .map(|rate| (StatusCode::CREATED, Json(rate.into())))
+        .map_err(ApiError::from)
+}
+
+/// A merchant's tax rate table
+#[utoipa::path(
+    get,
+    path = "/api/tax/rates",
+    params(ListQuery),
+    responses(
+        (status = 200, description = "Rates by country, state and zip", body = Vec<TaxRateResponse>),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "tax"
+)]
+pub async fn list_rates(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Query(query): Query<ListQuery>,
+) -> Result<Json<Vec<TaxRateResponse>>, ApiError> {
+    TaxRateService::list(&state.db, admin.0.scoped_mid(query.mid), query.country.as_deref())
+        .await
+        .map(|rates| Json(rates.into_iter().map(|r| r.into()).collect()))
+        .map_err(ApiError::from)
+}
+
+/// Get a tax rate
+#[utoipa::path(
+    get,
+    path = "/api/tax/rates/{mid}/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Tax rate ID")
+    ),
+    responses(
+        (status = 200, description = "Rate found", body = TaxRateResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Rate not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "tax"
+)]
+pub async fn get_rate(
+    State(state): State<AppState>,
+    _admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Json<TaxRateResponse>, ApiError> {
+    TaxRateService::find_by_id(&state.db, mid, id)
+        .await?
+        .map(|rate| Json(rate.into()))
+        .ok_or_else(|| TaxError::NotFound.into())
+}
+
+/// Replace a tax rate
+#[utoipa::path(
+    put,
+    path = "/api/tax/rates/{mid}/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Tax rate ID")
+    ),
+    request_body = TaxRateRequest,
+    responses(
+        (status = 200, description = "Rate updated", body = TaxRateResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Rate not found", body = ErrorBody),
+        (status = 422, description = "Invalid rate, name or location", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "tax"
+)]
+pub async fn update_rate(
+    State(state): State<AppState>,
+    _admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+    ValidatedJson(req): ValidatedJson<TaxRateRequest>,
+) -> Result<Json<TaxRateResponse>, ApiError> {
+    let rate = TaxRateService::find_by_id(&state.db, mid, id)
+        .await?
+        .ok_or(TaxError::NotFound)?;
+
+    TaxRateService::update(&state.db, rate, req.into_input()?)
+        .await
+        .map(|rate| Json(rate.into()))
+        .map_err(ApiError::from)
+}
+
+/// Delete a tax rate
+#[utoipa::path(
+    delete,
+    path = "/api/tax/rates/{mid}/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Tax rate ID")
+    ),
+    responses(
+        (status = 204, description = "Rate deleted"),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Rate not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "tax"
+)]
+pub async fn delete_rate(
+    State(state): State<AppState>,
+    _admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<StatusCode, ApiError> {
+    if TaxRateService::delete(&state.db, mid, id).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(TaxError::NotFound.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tax_rate_validation() {
+        let req = TaxRateRequest {
+            name: "State Tax".to_string(),
+            country: "USA".to_string(),
+            state: "CA".to_string(),
+            zip: String::new(),
+            rate: "7.12345".to_string(),
+            zone_id: None,
+        };
+        match crate::validation::validate(&req) {
+            Err(ApiError::Validation(errors)) => {
+                let fields: Vec<_> = errors.iter().map(|e| e.field.as_str()).collect();
+                assert_eq!(fields, vec!["country", "rate"]);
+            }
+            other => panic!("unexpected result {:?}", other.err().map(|e| e.status())),
+        }
+
+        let input = TaxRateRequest { country: "us".to_string(), rate: "7.25".to_string(), ..req }.into_input().unwrap();
+        assert_eq!(input.address.country, "US");
+        assert_eq!(input.rate, Decimal::new(725, 2));
+
+        // A zone rate names no location of its own
+        let zoned = TaxRateRequest {
+            name: "District Tax".to_string(),
+            country: String::new(),
+            state: String::new(),
+            zip: String::new(),
+            rate: "1.25".to_string(),
+            zone_id: Some(3),
+        };
+        assert!(crate::validation::validate(&zoned).is_ok());
+        let located = TaxRateRequest { country: "US".to_string(), ..zoned };
+        assert!(crate::validation::validate(&located).is_err());
+    }
+}
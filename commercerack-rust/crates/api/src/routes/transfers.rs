@@ -0,0 +1,288 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use commercerack_core::Timestamp;
+use commercerack_inventory::transfers::{TransferService, TransferStatus, TransferWithItems};
+use entity::prelude::{StockTransfer, StockTransferItem};
+use serde::{Deserialize, Serialize};
+use crate::auth::RequireMerchantAdmin;
+use crate::error::{ApiError, ErrorBody};
+use crate::validation::{ValidatedJson, Validate, Validator};
+use crate::AppState;
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct TransferItemRequest {
+    pub sku: String,
+    pub quantity: i32,
+}
+
+impl Validate for TransferItemRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("sku", &self.sku, 45).positive("quantity", self.quantity);
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct CreateTransferRequest {
+    pub mid: i32,
+    pub from_warehouse: i32,
+    pub to_warehouse: i32,
+    pub items: Vec<TransferItemRequest>,
+    pub note: Option<String>,
+}
+
+impl Validate for CreateTransferRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.check(self.from_warehouse != self.to_warehouse, "to_warehouse", "must differ from from_warehouse")
+            .check(!self.items.is_empty(), "items", "must not be empty")
+            .each("items", &self.items);
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct TransferItemResponse {
+    pub sku: String,
+    pub quantity: i32,
+}
+
+impl From<StockTransferItem> for TransferItemResponse {
+    fn from(item: StockTransferItem) -> Self {
+        Self {
+            sku: item.sku,
+            quantity: item.quantity,
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct TransferResponse {
+    pub id: i32,
+    pub mid: i32,
+    pub from_warehouse: i32,
+    pub to_warehouse: i32,
+    /// `in_transit`, `received` or `cancelled`
+    pub status: String,
+    pub note: Option<String>,
+    pub actor: Option<i32>,
+    #[schema(value_type = i64)]
+    pub created_gmt: Timestamp,
+    /// When it was received or cancelled
+    #[schema(value_type = Option<i64>)]
+    pub closed_gmt: Option<Timestamp>,
+    /// Omitted in lists
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub items: Option<Vec<TransferItemResponse>>,
+}
+
+impl From<StockTransfer> for TransferResponse {
+    fn from(transfer: StockTransfer) -> Self {
+        Self {
+            id: transfer.id,
+            mid: transfer.mid,
+            from_warehouse: transfer.from_warehouse,
+            to_warehouse: transfer.to_warehouse,
+            status: transfer.status,
+            note: transfer.note,
+            actor: transfer.actor,
+            created_gmt: transfer.created_gmt,
+            closed_gmt: transfer.closed_gmt,
+            items: None,
+        }
+    }
+}
+
+impl From<TransferWithItems> for TransferResponse {
+    fn from(transfer: TransferWithItems) -> Self {
+        Self {
+            items: Some(transfer.items.into_iter().map(|item| item.into()).collect()),
+            ..transfer.transfer.into()
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct TransferListQuery {
+    pub mid: i32,
+    /// `in_transit`, `received` or `cancelled`
+    pub status: Option<String>,
+}
+
+/// Ship stock from one warehouse to another
+#[utoipa::path(
+    post,
+    path = "/api/stock-transfers",
+    request_body = CreateTransferRequest,
+    responses(
+        (status = 201, description = "Transfer in transit", body = TransferResponse),
+        (status = 400, description = "Unknown SKU", body = ErrorBody),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Warehouse not found", body = ErrorBody),
+        (status = 409, description = "Not enough stock at the origin", body = ErrorBody),
+        (status = 422, description = "Invalid warehouses or items", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "inventory"
+)]
+pub async fn create(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    ValidatedJson(req): ValidatedJson<CreateTransferRequest>,
+) -> Result<(StatusCode, Json<TransferResponse>), ApiError> {
+    let mid = admin.0.scoped_mid(req.mid);
+    let actor = admin.0.sub.parse().ok();
+    let lines: Vec<(&str, i32)> = req.items.iter().map(|item| (item.sku.as_str(), item.quantity)).collect();
+
+    TransferService::create(&*state.db, mid, req.from_warehouse, req.to_warehouse, &lines, req.note, actor)
+        .await
+        .map(|transfer| (StatusCode::CREATED, Json(transfer.into())))
+        .map_err(ApiError::from)
+}
+
+/// A merchant's stock transfers, newest first
+#[utoipa::path(
+    get,
+    path = "/api/stock-transfers",
+    params(TransferListQuery),
+    responses(
+        (status = 200, description = "Transfers without their items", body = Vec<TransferResponse>),
+        (status = 400, description = "Unknown status", body = ErrorBody),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "inventory"
+)]
+pub async fn list(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Query(query): Query<TransferListQuery>,
+) -> Result<Json<Vec<TransferResponse>>, ApiError> {
+    let status = query
+        .status
+        .as_deref()
+        .map(str::parse::<TransferStatus>)
+        .transpose()
+        .map_err(ApiError::BadRequest)?;
+
+    TransferService::list(&state.db, admin.0.scoped_mid(query.mid), status)
+        .await
+        .map(|transfers| Json(transfers.into_iter().map(|t| t.into()).collect()))
+        .map_err(ApiError::from)
+}
+
+/// A stock transfer with its items
+#[utoipa::path(
+    get,
+    path = "/api/stock-transfers/{mid}/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Transfer ID")
+    ),
+    responses(
+        (status = 200, description = "Transfer found", body = TransferResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Transfer not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "inventory"
+)]
+pub async fn get(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Json<TransferResponse>, ApiError> {
+    TransferService::find(&state.db, admin.0.scoped_mid(mid), id)
+        .await
+        .map(|transfer| Json(transfer.into()))
+        .map_err(ApiError::from)
+}
+
+/// Book a transfer's stock into its destination
+#[utoipa::path(
+    post,
+    path = "/api/stock-transfers/{mid}/{id}/receive",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Transfer ID")
+    ),
+    responses(
+        (status = 200, description = "Transfer received", body = TransferResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Transfer not found", body = ErrorBody),
+        (status = 409, description = "Transfer was already received or cancelled", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "inventory"
+)]
+pub async fn receive(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Json<TransferResponse>, ApiError> {
+    let actor = admin.0.sub.parse().ok();
+    TransferService::receive(&*state.db, admin.0.scoped_mid(mid), id, actor)
+        .await
+        .map(|transfer| Json(transfer.into()))
+        .map_err(ApiError::from)
+}
+
+/// Call off a transfer, returning its stock to the origin
+#[utoipa::path(
+    post,
+    path = "/api/stock-transfers/{mid}/{id}/cancel",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Transfer ID")
+    ),
+    responses(
+        (status = 200, description = "Transfer cancelled", body = TransferResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Transfer not found", body = ErrorBody),
+        (status = 409, description = "Transfer was already received or cancelled", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "inventory"
+)]
+pub async fn cancel(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Json<TransferResponse>, ApiError> {
+    let actor = admin.0.sub.parse().ok();
+    TransferService::cancel(&*state.db, admin.0.scoped_mid(mid), id, actor)
+        .await
+        .map(|transfer| Json(transfer.into()))
+        .map_err(ApiError::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transfer_validation() {
+        let req = CreateTransferRequest {
+            mid: 1,
+            from_warehouse: 2,
+            to_warehouse: 2,
+            items: vec![TransferItemRequest {
+                sku: "SKU001".to_string(),
+                quantity: 0,
+            }],
+            note: None,
+        };
+        match crate::validation::validate(&req) {
+            Err(ApiError::Validation(errors)) => {
+                let fields: Vec<_> = errors.iter().map(|e| e.field.as_str()).collect();
+                assert_eq!(fields, vec!["to_warehouse", "items[0].quantity"]);
+            }
+            other => panic!("unexpected result {:?}", other.err().map(|e| e.status())),
+        }
+    }
+}
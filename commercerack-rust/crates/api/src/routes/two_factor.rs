@@ -0,0 +1,319 @@
+//! Two-factor authentication routes
+//!
+//! Enrollment happens while logged in: `enroll` returns a secret and
+//! provisioning URI, and `confirm` turns two-factor on given the app's
+//! first code. Afterwards a correct password at `/api/auth/login` returns a
+//! short-lived two-factor token, traded here at `verify` with a code for
+//! the usual tokens. Wrong codes count as failed logins.
+
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use chrono::{Duration, Utc};
+use commercerack_config::JwtConfig;
+use commercerack_customer::throttle::LoginThrottle;
+use commercerack_customer::sessions::SessionService;
+use commercerack_customer::two_factor::TwoFactorService;
+use commercerack_customer::{CustomerError, CustomerService};
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use crate::auth::{ActiveSession, Claims};
+use crate::client_ip::ClientIp;
+use crate::error::{ApiError, ErrorBody};
+use crate::routes::auth::{token_response, TokenResponse, TwoFactorChallenge};
+use crate::routes::sessions;
+use crate::validation::{ValidatedJson, Validate, Validator};
+use crate::AppState;
+
+/// Time between a correct password and the code
+const TWO_FACTOR_TOKEN_TTL_SECS: i64 = 5 * 60;
+
+/// Claims of a two-factor token. Lacking `sub` it never passes as an
+/// access token, and access tokens lack `pending_cid`.
+#[derive(Debug, Serialize, Deserialize)]
+struct PendingClaims {
+    pending_cid: i32,
+    mid: i32,
+    iat: i64,
+    exp: i64,
+}
+
+/// Challenge for a customer whose password was right
+pub(crate) fn challenge(jwt: &JwtConfig, mid: i32, cid: i32) -> Result<TwoFactorChallenge, ApiError> {
+    let now = Utc::now();
+    let claims = PendingClaims {
+        pending_cid: cid,
+        mid,
+        iat: now.timestamp(),
+        exp: (now + Duration::seconds(TWO_FACTOR_TOKEN_TTL_SECS)).timestamp(),
+    };
+    let two_factor_token = encode(&Header::default(), &claims, &EncodingKey::from_secret(jwt.secret.as_bytes()))
+        .map_err(|e| ApiError::Internal(format!("Failed to sign two-factor token: {}", e)))?;
+
+    Ok(TwoFactorChallenge {
+        two_factor_token,
+        expires_in: TWO_FACTOR_TOKEN_TTL_SECS,
+    })
+}
+
+fn pending(token: &str, secret: &str) -> Result<PendingClaims, ApiError> {
+    decode::<PendingClaims>(token, &DecodingKey::from_secret(secret.as_bytes()), &Validation::default())
+        .map(|data| data.claims)
+        .map_err(|e| ApiError::Unauthorized(format!("Invalid two-factor token: {}", e)))
+}
+
+/// The caller's own customer id
+pub(crate) fn account(claims: &Claims) -> Result<i32, ApiError> {
+    claims
+        .sub
+        .parse()
+        .map_err(|_| ApiError::Unauthorized("Token does not name an account".to_string()))
+}
+
+/// Run `check` on a code, refusing while the account's logins are
+/// throttled and counting a wrong code as a failed login, so codes are no
+/// easier to guess than passwords
+async fn throttled<T, F, Fut>(state: &AppState, mid: i32, cid: i32, ip: &str, check: F) -> Result<T, CustomerError>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<T, CustomerError>>,
+{
+    let db = &*state.db;
+    let customer = CustomerService::find_by_id(db, mid, cid).await?.ok_or(CustomerError::NotFound)?;
+    LoginThrottle::check(db, mid, &customer.email, ip).await?;
+
+    let result = check().await;
+    if matches!(result, Err(CustomerError::InvalidTwoFactorCode)) {
+        LoginThrottle::failed(db, mid, &customer.email, ip).await?;
+    }
+    result
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct CodeRequest {
+    /// Current code from the authenticator app, or a backup code
+    pub code: String,
+}
+
+impl Validate for CodeRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("code", &self.code, 16);
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct VerifyRequest {
+    /// From the login response
+    pub two_factor_token: String,
+    /// Current code from the authenticator app, or a backup code
+    pub code: String,
+}
+
+impl Validate for VerifyRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("two_factor_token", &self.two_factor_token, 1024).required("code", &self.code, 16);
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct EnrollmentResponse {
+    /// Base32 secret, for entering into an app by hand
+    pub secret: String,
+    /// `otpauth://` URI to show as a QR code
+    pub provisioning_uri: String,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct BackupCodesResponse {
+    /// Single-use codes for when the app is unavailable; shown only once
+    pub backup_codes: Vec<String>,
+}
+
+/// Start two-factor enrollment with a fresh secret
+#[utoipa::path(
+    post,
+    path = "/api/auth/2fa/enroll",
+    responses(
+        (status = 200, description = "Secret to add to an authenticator app", body = EnrollmentResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 404, description = "Customer not found", body = ErrorBody),
+        (status = 409, description = "Two-factor is already on", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "auth"
+)]
+pub async fn enroll(
+    State(state): State<AppState>,
+    ActiveSession(claims): ActiveSession,
+) -> Result<Json<EnrollmentResponse>, ApiError> {
+    let cid = account(&claims)?;
+    let customer = CustomerService::find_by_id(&state.db, claims.mid, cid)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Customer"))?;
+
+    let enrollment =
+        TwoFactorService::enroll(&*state.db, claims.mid, cid, &state.config.two_factor.issuer, &customer.email).await?;
+    Ok(Json(EnrollmentResponse {
+        secret: enrollment.secret,
+        provisioning_uri: enrollment.provisioning_uri,
+    }))
+}
+
+/// Turn two-factor on with the first code from the app
+#[utoipa::path(
+    post,
+    path = "/api/auth/2fa/confirm",
+    request_body = CodeRequest,
+    responses(
+        (status = 200, description = "Two-factor on", body = BackupCodesResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 409, description = "Not enrolled, or already on", body = ErrorBody),
+        (status = 422, description = "Wrong code", body = ErrorBody),
+        (status = 429, description = "Too many wrong codes", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "auth"
+)]
+pub async fn confirm(
+    State(state): State<AppState>,
+    ActiveSession(claims): ActiveSession,
+    ClientIp(ip): ClientIp,
+    ValidatedJson(req): ValidatedJson<CodeRequest>,
+) -> Result<Json<BackupCodesResponse>, ApiError> {
+    let cid = account(&claims)?;
+    let backup_codes = throttled(&state, claims.mid, cid, &ip, || {
+        TwoFactorService::confirm(&*state.db, claims.mid, cid, &req.code)
+    })
+    .await?;
+    Ok(Json(BackupCodesResponse { backup_codes }))
+}
+
+/// Finish a login with the second factor
+#[utoipa::path(
+    post,
+    path = "/api/auth/2fa/verify",
+    request_body = VerifyRequest,
+    responses(
+        (status = 200, description = "Logged in", body = TokenResponse),
+        (status = 401, description = "Invalid or expired two-factor token, or wrong code", body = ErrorBody),
+        (status = 429, description = "Too many failed logins; retry after the Retry-After header's seconds", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "auth"
+)]
+pub async fn verify(
+    State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
+    headers: HeaderMap,
+    ValidatedJson(req): ValidatedJson<VerifyRequest>,
+) -> Result<Json<TokenResponse>, ApiError> {
+    let pending = pending(&req.two_factor_token, &state.config.jwt.secret)?;
+    let (mid, cid) = (pending.mid, pending.pending_cid);
+
+    throttled(&state, mid, cid, &ip, || TwoFactorService::verify(&*state.db, mid, cid, &req.code))
+        .await
+        .map_err(|e| match e {
+            CustomerError::InvalidTwoFactorCode => ApiError::Unauthorized(e.to_string()),
+            e => e.into(),
+        })?;
+    if let Some(customer) = CustomerService::find_by_id(&state.db, mid, cid).await? {
+        LoginThrottle::succeeded(&*state.db, mid, &customer.email).await?;
+    }
+
+    let issued = SessionService::start(&*state.db, mid, cid, &sessions::device(&headers), &ip).await?;
+    token_response(&state, issued, true).await.map(Json)
+}
+
+/// Turn two-factor off
+#[utoipa::path(
+    post,
+    path = "/api/auth/2fa/disable",
+    request_body = CodeRequest,
+    responses(
+        (status = 204, description = "Two-factor off"),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 409, description = "Two-factor is not on", body = ErrorBody),
+        (status = 422, description = "Wrong code", body = ErrorBody),
+        (status = 429, description = "Too many wrong codes", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "auth"
+)]
+pub async fn disable(
+    State(state): State<AppState>,
+    ActiveSession(claims): ActiveSession,
+    ClientIp(ip): ClientIp,
+    ValidatedJson(req): ValidatedJson<CodeRequest>,
+) -> Result<StatusCode, ApiError> {
+    let cid = account(&claims)?;
+    throttled(&state, claims.mid, cid, &ip, || {
+        TwoFactorService::disable(&*state.db, claims.mid, cid, &req.code)
+    })
+    .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Replace the backup codes
+#[utoipa::path(
+    post,
+    path = "/api/auth/2fa/backup-codes",
+    request_body = CodeRequest,
+    responses(
+        (status = 200, description = "New backup codes; the old ones no longer work", body = BackupCodesResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 409, description = "Two-factor is not on", body = ErrorBody),
+        (status = 422, description = "Wrong code", body = ErrorBody),
+        (status = 429, description = "Too many wrong codes", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "auth"
+)]
+pub async fn backup_codes(
+    State(state): State<AppState>,
+    ActiveSession(claims): ActiveSession,
+    ClientIp(ip): ClientIp,
+    ValidatedJson(req): ValidatedJson<CodeRequest>,
+) -> Result<Json<BackupCodesResponse>, ApiError> {
+    let cid = account(&claims)?;
+    let backup_codes = throttled(&state, claims.mid, cid, &ip, || {
+        TwoFactorService::regenerate_backup_codes(&*state.db, claims.mid, cid, &req.code)
+    })
+    .await?;
+    Ok(Json(BackupCodesResponse { backup_codes }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jwt() -> JwtConfig {
+        JwtConfig {
+            secret: "test-secret".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_two_factor_and_access_tokens_do_not_mix() {
+        let jwt = jwt();
+        let challenge = challenge(&jwt, 3, 42).unwrap();
+        let claims = pending(&challenge.two_factor_token, &jwt.secret).unwrap();
+        assert_eq!((claims.mid, claims.pending_cid), (3, 42));
+        assert!(Claims::decode(&challenge.two_factor_token, &jwt.secret).is_err());
+
+        let access = Claims::new(42, 3).encode(&jwt.secret).unwrap();
+        assert!(matches!(pending(&access, &jwt.secret), Err(ApiError::Unauthorized(_))));
+    }
+
+    #[test]
+    fn test_account_comes_from_the_subject() {
+        assert_eq!(account(&Claims::new(42, 3)).unwrap(), 42);
+
+        let mut claims = Claims::new(42, 3);
+        claims.sub = "service".to_string();
+        assert!(matches!(account(&claims), Err(ApiError::Unauthorized(_))));
+    }
+}
@@ -0,0 +1,363 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use commercerack_core::Timestamp;
+use commercerack_inventory::warehouses::{WarehouseInput, WarehouseService};
+use entity::prelude::{OrderAllocation, Warehouse, WarehouseStock};
+use serde::{Deserialize, Serialize};
+use crate::auth::RequireMerchantAdmin;
+use crate::error::{ApiError, ErrorBody};
+use crate::validation::{ValidatedJson, Validate, Validator};
+use crate::AppState;
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct WarehouseRequest {
+    /// Short code, unique per merchant, e.g. `EAST`
+    pub code: String,
+    pub name: String,
+    /// ISO 3166-1 alpha-2 code
+    pub country: String,
+    #[serde(default)]
+    pub state: String,
+    /// Lower ships first among otherwise equal warehouses
+    #[serde(default)]
+    pub priority: i32,
+    /// Inactive warehouses keep their stock but never ship orders
+    #[serde(default = "default_active")]
+    pub active: bool,
+    /// Business days between an order and its parcel leaving
+    #[serde(default = "default_handling_days")]
+    pub handling_days: i32,
+    /// Hour of the merchant's day, 0 to 23, after which orders count from
+    /// the next business day
+    #[serde(default = "default_cutoff_hour")]
+    pub cutoff_hour: i32,
+}
+
+fn default_active() -> bool {
+    true
+}
+
+fn default_handling_days() -> i32 {
+    commercerack_shipping::delivery::DEFAULT_HANDLING_DAYS
+}
+
+fn default_cutoff_hour() -> i32 {
+    commercerack_shipping::delivery::DEFAULT_CUTOFF_HOUR
+}
+
+impl Validate for WarehouseRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("code", &self.code, 16)
+            .required("name", &self.name, 80)
+            .max_len("state", &self.state, 8)
+            .check(
+                self.country.trim().len() == 2 && self.country.trim().chars().all(|c| c.is_ascii_alphabetic()),
+                "country",
+                "must be a two-letter country code",
+            )
+            .check((0..=30).contains(&self.handling_days), "handling_days", "must be between 0 and 30")
+            .check((0..=23).contains(&self.cutoff_hour), "cutoff_hour", "must be between 0 and 23");
+    }
+}
+
+impl From<WarehouseRequest> for WarehouseInput {
+    fn from(req: WarehouseRequest) -> Self {
+        Self {
+            code: req.code,
+            name: req.name,
+            country: req.country,
+            state: req.state,
+            priority: req.priority,
+            active: req.active,
+            handling_days: req.handling_days,
+            cutoff_hour: req.cutoff_hour,
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct CreateWarehouseRequest {
+    pub mid: i32,
+    #[serde(flatten)]
+    pub warehouse: WarehouseRequest,
+}
+
+impl Validate for CreateWarehouseRequest {
+    fn validate(&self, v: &mut Validator) {
+        self.warehouse.validate(v);
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct WarehouseResponse {
+    pub id: i32,
+    pub mid: i32,
+    pub code: String,
+    pub name: String,
+    pub country: String,
+    pub state: String,
+    pub priority: i32,
+    pub active: bool,
+    pub handling_days: i32,
+    pub cutoff_hour: i32,
+    #[schema(value_type = i64)]
+    pub created_gmt: Timestamp,
+}
+
+impl From<Warehouse> for WarehouseResponse {
+    fn from(warehouse: Warehouse) -> Self {
+        Self {
+            id: warehouse.id,
+            mid: warehouse.mid,
+            code: warehouse.code,
+            name: warehouse.name,
+            country: warehouse.country,
+            state: warehouse.state,
+            priority: warehouse.priority,
+            active: warehouse.active,
+            handling_days: warehouse.handling_days,
+            cutoff_hour: warehouse.cutoff_hour,
+            created_gmt: warehouse.created_gmt,
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct WarehouseStockResponse {
+    pub warehouse_id: i32,
+    pub sku: String,
+    pub quantity: i32,
+    /// Units on their way here from another warehouse
+    pub in_transit: i32,
+}
+
+impl From<WarehouseStock> for WarehouseStockResponse {
+    fn from(stock: WarehouseStock) -> Self {
+        Self {
+            warehouse_id: stock.warehouse_id,
+            sku: stock.sku,
+            quantity: stock.quantity,
+            in_transit: stock.in_transit,
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct AllocationResponse {
+    pub warehouse_id: i32,
+    pub sku: String,
+    /// Units still to ship from the warehouse; cancelled and returned units are not counted
+    pub quantity: i32,
+}
+
+impl From<OrderAllocation> for AllocationResponse {
+    fn from(allocation: OrderAllocation) -> Self {
+        Self {
+            warehouse_id: allocation.warehouse_id,
+            sku: allocation.sku,
+            quantity: allocation.quantity,
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct ListQuery {
+    pub mid: i32,
+}
+
+/// Add a warehouse
+#[utoipa::path(
+    post,
+    path = "/api/warehouses",
+    request_body = CreateWarehouseRequest,
+    responses(
+        (status = 201, description = "Warehouse created", body = WarehouseResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 409, description = "Code already in use", body = ErrorBody),
+        (status = 422, description = "Invalid warehouse settings", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "inventory"
+)]
+pub async fn create(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    ValidatedJson(req): ValidatedJson<CreateWarehouseRequest>,
+) -> Result<(StatusCode, Json<WarehouseResponse>), ApiError> {
+    let mid = admin.0.scoped_mid(req.mid);
+    WarehouseService::create(&*state.db, mid, req.warehouse.into())
+        .await
+        .map(|warehouse| (StatusCode::CREATED, Json(warehouse.into())))
+        .map_err(ApiError::from)
+}
+
+/// A merchant's warehouses by priority
+#[utoipa::path(
+    get,
+    path = "/api/warehouses",
+    params(ListQuery),
+    responses(
+        (status = 200, description = "Warehouses", body = Vec<WarehouseResponse>),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "inventory"
+)]
+pub async fn list(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Query(query): Query<ListQuery>,
+) -> Result<Json<Vec<WarehouseResponse>>, ApiError> {
+    WarehouseService::list(&state.db, admin.0.scoped_mid(query.mid))
+        .await
+        .map(|warehouses| Json(warehouses.into_iter().map(|w| w.into()).collect()))
+        .map_err(ApiError::from)
+}
+
+/// Replace a warehouse's settings
+#[utoipa::path(
+    put,
+    path = "/api/warehouses/{mid}/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Warehouse ID")
+    ),
+    request_body = WarehouseRequest,
+    responses(
+        (status = 200, description = "Warehouse updated", body = WarehouseResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Warehouse not found", body = ErrorBody),
+        (status = 409, description = "Code already in use", body = ErrorBody),
+        (status = 422, description = "Invalid warehouse settings", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "inventory"
+)]
+pub async fn update(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+    ValidatedJson(req): ValidatedJson<WarehouseRequest>,
+) -> Result<Json<WarehouseResponse>, ApiError> {
+    WarehouseService::update(&*state.db, admin.0.scoped_mid(mid), id, req.into())
+        .await
+        .map(|warehouse| Json(warehouse.into()))
+        .map_err(ApiError::from)
+}
+
+/// Stock held by a warehouse
+#[utoipa::path(
+    get,
+    path = "/api/warehouses/{mid}/{id}/stock",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Warehouse ID")
+    ),
+    responses(
+        (status = 200, description = "Stock by SKU", body = Vec<WarehouseStockResponse>),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Warehouse not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "inventory"
+)]
+pub async fn stock(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Json<Vec<WarehouseStockResponse>>, ApiError> {
+    let mid = admin.0.scoped_mid(mid);
+    WarehouseService::find(&*state.db, mid, id).await?;
+
+    WarehouseService::stock(&state.db, mid, Some(id), None)
+        .await
+        .map(|stock| Json(stock.into_iter().map(|s| s.into()).collect()))
+        .map_err(ApiError::from)
+}
+
+/// Where a SKU's stock is held
+#[utoipa::path(
+    get,
+    path = "/api/inventory/{mid}/{sku}/warehouses",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("sku" = String, Path, description = "SKU code")
+    ),
+    responses(
+        (status = 200, description = "Stock by warehouse", body = Vec<WarehouseStockResponse>),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "inventory"
+)]
+pub async fn sku_stock(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, sku)): Path<(i32, String)>,
+) -> Result<Json<Vec<WarehouseStockResponse>>, ApiError> {
+    WarehouseService::stock(&state.db, admin.0.scoped_mid(mid), None, Some(&sku))
+        .await
+        .map(|stock| Json(stock.into_iter().map(|s| s.into()).collect()))
+        .map_err(ApiError::from)
+}
+
+/// Which warehouses ship an order's units
+#[utoipa::path(
+    get,
+    path = "/api/orders/{mid}/{id}/allocations",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Order ID")
+    ),
+    responses(
+        (status = 200, description = "Allocations; empty if the merchant has no warehouses", body = Vec<AllocationResponse>),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "orders"
+)]
+pub async fn allocations(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Json<Vec<AllocationResponse>>, ApiError> {
+    WarehouseService::allocations(&state.db, admin.0.scoped_mid(mid), id)
+        .await
+        .map(|allocations| Json(allocations.into_iter().map(|a| a.into()).collect()))
+        .map_err(ApiError::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_warehouse_validation() {
+        let req = WarehouseRequest {
+            code: String::new(),
+            name: "East coast".to_string(),
+            country: "USA".to_string(),
+            state: "NY".to_string(),
+            priority: 0,
+            active: true,
+            handling_days: 2,
+            cutoff_hour: 24,
+        };
+        match crate::validation::validate(&req) {
+            Err(ApiError::Validation(errors)) => {
+                let fields: Vec<_> = errors.iter().map(|e| e.field.as_str()).collect();
+                assert_eq!(fields, vec!["code", "country", "cutoff_hour"]);
+            }
+            other => panic!("unexpected result {:?}", other.err().map(|e| e.status())),
+        }
+    }
+}
@@ -0,0 +1,347 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use commercerack_core::Timestamp;
+use commercerack_webhooks::{subscribed_events, WebhookEvent, WebhookService};
+use ::entity::prelude::{WebhookDelivery, WebhookEndpoint};
+use serde::{Deserialize, Serialize};
+use crate::auth::RequireMerchantAdmin;
+use crate::error::{ApiError, ErrorBody};
+use crate::validation::{ValidatedJson, Validate, Validator};
+use crate::AppState;
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct CreateEndpointRequest {
+    pub mid: i32,
+    pub url: String,
+    /// Event types, e.g. `order.created`, `order.paid`, `order.shipped`,
+    /// `order.cancelled`, `order.digital_delivered`, `order.approval_requested`,
+    /// `order.ready_for_pickup`, `customer.created`, `product.updated`, `cart.abandoned`,
+    /// `customer.locked_out`, `inventory.low_stock`, `dunning.retry_scheduled`, `dunning.suspended`
+    pub events: Vec<String>,
+}
+
+impl Validate for CreateEndpointRequest {
+    fn validate(&self, v: &mut Validator) {
+        validate_subscription(v, &self.url, &self.events);
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct UpdateEndpointRequest {
+    pub url: String,
+    pub events: Vec<String>,
+    #[serde(default = "default_active")]
+    pub active: bool,
+}
+
+impl Validate for UpdateEndpointRequest {
+    fn validate(&self, v: &mut Validator) {
+        validate_subscription(v, &self.url, &self.events);
+    }
+}
+
+fn default_active() -> bool {
+    true
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct EndpointResponse {
+    pub id: i32,
+    pub mid: i32,
+    pub url: String,
+    pub events: Vec<String>,
+    pub active: bool,
+    #[schema(value_type = i64)]
+    pub created_gmt: Timestamp,
+    #[schema(value_type = i64)]
+    pub modified_gmt: Timestamp,
+    /// Signing secret, only returned when the endpoint is created
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+}
+
+impl From<WebhookEndpoint> for EndpointResponse {
+    fn from(endpoint: WebhookEndpoint) -> Self {
+        Self {
+            events: subscribed_events(&endpoint).iter().map(|e| e.to_string()).collect(),
+            id: endpoint.id,
+            mid: endpoint.mid,
+            url: endpoint.url,
+            active: endpoint.active,
+            created_gmt: endpoint.created_gmt,
+            modified_gmt: endpoint.modified_gmt,
+            secret: None,
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct DeliveryResponse {
+    pub id: i32,
+    pub event: String,
+    pub status: String,
+    pub attempts: i32,
+    #[schema(value_type = i64)]
+    pub next_attempt_gmt: Timestamp,
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    #[schema(value_type = i64)]
+    pub created_gmt: Timestamp,
+    #[schema(value_type = Option<i64>)]
+    pub delivered_gmt: Option<Timestamp>,
+}
+
+impl From<WebhookDelivery> for DeliveryResponse {
+    fn from(delivery: WebhookDelivery) -> Self {
+        Self {
+            id: delivery.id,
+            event: delivery.event,
+            status: delivery.status,
+            attempts: delivery.attempts,
+            next_attempt_gmt: delivery.next_attempt_gmt,
+            last_status_code: delivery.last_status_code,
+            last_error: delivery.last_error,
+            created_gmt: delivery.created_gmt,
+            delivered_gmt: delivery.delivered_gmt,
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct ListQuery {
+    pub mid: i32,
+}
+
+/// Check an endpoint URL and its event subscriptions
+fn validate_subscription(v: &mut Validator, url: &str, events: &[String]) {
+    v.required("url", url, 255).check(
+        url.starts_with("https://") || url.starts_with("http://"),
+        "url",
+        "must be an http(s) URL",
+    );
+
+    v.check(!events.is_empty(), "events", "must subscribe to at least one event");
+    for (i, event) in events.iter().enumerate() {
+        if event.parse::<WebhookEvent>().is_err() {
+            v.error(&format!("events[{}]", i), format!("unknown event type {}", event));
+        }
+    }
+}
+
+/// Subscribed events of a validated request
+fn subscription(events: &[String]) -> Vec<WebhookEvent> {
+    events.iter().filter_map(|event| event.parse().ok()).collect()
+}
+
+/// Register a webhook endpoint
+#[utoipa::path(
+    post,
+    path = "/api/webhooks",
+    request_body = CreateEndpointRequest,
+    responses(
+        (status = 201, description = "Endpoint registered; the response includes its signing secret", body = EndpointResponse),
+        (status = 422, description = "Invalid URL or unknown event type", body = ErrorBody),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "webhooks"
+)]
+pub async fn create(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    ValidatedJson(req): ValidatedJson<CreateEndpointRequest>,
+) -> Result<(StatusCode, Json<EndpointResponse>), ApiError> {
+    let events = subscription(&req.events);
+
+    let endpoint = WebhookService::create_endpoint(&state.db, admin.0.scoped_mid(req.mid), &req.url, &events)
+        .await?;
+
+    let secret = endpoint.secret.clone();
+    let mut response = EndpointResponse::from(endpoint);
+    response.secret = Some(secret);
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+/// List a merchant's webhook endpoints
+#[utoipa::path(
+    get,
+    path = "/api/webhooks",
+    params(ListQuery),
+    responses(
+        (status = 200, description = "Registered endpoints", body = Vec<EndpointResponse>),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "webhooks"
+)]
+pub async fn list(
+    State(state): State<AppState>,
+    _admin: RequireMerchantAdmin,
+    Query(query): Query<ListQuery>,
+) -> Result<Json<Vec<EndpointResponse>>, ApiError> {
+    WebhookService::list_endpoints(&state.db, query.mid)
+        .await
+        .map(|endpoints| Json(endpoints.into_iter().map(|e| e.into()).collect()))
+        .map_err(ApiError::from)
+}
+
+/// Get a webhook endpoint
+#[utoipa::path(
+    get,
+    path = "/api/webhooks/{mid}/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Endpoint ID")
+    ),
+    responses(
+        (status = 200, description = "Endpoint found", body = EndpointResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Endpoint not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "webhooks"
+)]
+pub async fn get(
+    State(state): State<AppState>,
+    _admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Json<EndpointResponse>, ApiError> {
+    WebhookService::find_endpoint(&state.db, mid, id)
+        .await?
+        .map(|endpoint| Json(endpoint.into()))
+        .ok_or_else(|| ApiError::not_found("Endpoint"))
+}
+
+/// Update a webhook endpoint
+#[utoipa::path(
+    put,
+    path = "/api/webhooks/{mid}/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Endpoint ID")
+    ),
+    request_body = UpdateEndpointRequest,
+    responses(
+        (status = 200, description = "Endpoint updated", body = EndpointResponse),
+        (status = 422, description = "Invalid URL or unknown event type", body = ErrorBody),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Endpoint not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "webhooks"
+)]
+pub async fn update(
+    State(state): State<AppState>,
+    _admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+    ValidatedJson(req): ValidatedJson<UpdateEndpointRequest>,
+) -> Result<Json<EndpointResponse>, ApiError> {
+    let events = subscription(&req.events);
+
+    let endpoint = WebhookService::find_endpoint(&state.db, mid, id)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Endpoint"))?;
+
+    WebhookService::update_endpoint(&state.db, endpoint, &req.url, &events, req.active)
+        .await
+        .map(|endpoint| Json(endpoint.into()))
+        .map_err(ApiError::from)
+}
+
+/// Delete a webhook endpoint and its pending deliveries
+#[utoipa::path(
+    delete,
+    path = "/api/webhooks/{mid}/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Endpoint ID")
+    ),
+    responses(
+        (status = 204, description = "Endpoint deleted"),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Endpoint not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "webhooks"
+)]
+pub async fn delete(
+    State(state): State<AppState>,
+    _admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<StatusCode, ApiError> {
+    let deleted = WebhookService::delete_endpoint(&state.db, mid, id).await?;
+
+    if deleted {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::not_found("Endpoint"))
+    }
+}
+
+/// Recent delivery attempts for an endpoint
+#[utoipa::path(
+    get,
+    path = "/api/webhooks/{mid}/{id}/deliveries",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Endpoint ID")
+    ),
+    responses(
+        (status = 200, description = "Deliveries, newest first", body = Vec<DeliveryResponse>),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "webhooks"
+)]
+pub async fn deliveries(
+    State(state): State<AppState>,
+    _admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Json<Vec<DeliveryResponse>>, ApiError> {
+    WebhookService::list_deliveries(&state.db, mid, id, 50)
+        .await
+        .map(|deliveries| Json(deliveries.into_iter().map(|d| d.into()).collect()))
+        .map_err(ApiError::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_subscription() {
+        let request = |url: &str, events: &[&str]| CreateEndpointRequest {
+            mid: 1,
+            url: url.to_string(),
+            events: events.iter().map(|e| e.to_string()).collect(),
+        };
+        let invalid_fields = |req: &CreateEndpointRequest| match crate::validation::validate(req) {
+            Ok(()) => Vec::new(),
+            Err(ApiError::Validation(errors)) => errors.into_iter().map(|e| e.field).collect(),
+            Err(e) => panic!("unexpected error {e:?}"),
+        };
+
+        let valid = request("https://example.com/hooks", &["order.created", "order.paid"]);
+        assert!(invalid_fields(&valid).is_empty());
+        assert_eq!(
+            subscription(&valid.events),
+            vec![WebhookEvent::OrderCreated, WebhookEvent::OrderPaid]
+        );
+
+        assert_eq!(invalid_fields(&request("ftp://example.com", &["order.paid"])), vec!["url"]);
+        assert_eq!(invalid_fields(&request("https://example.com/hooks", &[])), vec!["events"]);
+        assert_eq!(
+            invalid_fields(&request("https://example.com/hooks", &["order.paid", "order.deleted"])),
+            vec!["events[1]"]
+        );
+    }
+}
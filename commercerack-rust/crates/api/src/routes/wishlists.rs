@@ -0,0 +1,448 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use commercerack_core::Timestamp;
+use commercerack_customer::wishlist::{ProductWishlistCount, WishlistEntry, WishlistService};
+use commercerack_customer::CustomerError;
+use entity::prelude::Wishlist;
+use serde::{Deserialize, Serialize};
+use crate::auth::{Claims, RequireMerchantAdmin};
+use crate::error::{ApiError, ErrorBody};
+use crate::routes::cart::{lock_cart, save_cart, CartResponse};
+use crate::validation::{self, ValidatedJson, Validate, Validator};
+use crate::AppState;
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct AddWishlistItemRequest {
+    pub sku: String,
+}
+
+impl Validate for AddWishlistItemRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("sku", &self.sku, 45);
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct MoveToCartRequest {
+    pub cart_id: String,
+    #[serde(default = "default_quantity")]
+    pub quantity: i32,
+}
+
+impl Validate for MoveToCartRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("cart_id", &self.cart_id, 64)
+            .positive("quantity", self.quantity);
+    }
+}
+
+fn default_quantity() -> i32 {
+    1
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct SaveFromCartRequest {
+    pub cart_id: String,
+    pub sku: String,
+}
+
+impl Validate for SaveFromCartRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("cart_id", &self.cart_id, 64)
+            .required("sku", &self.sku, 45);
+    }
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct CountsQuery {
+    pub mid: i32,
+    /// Comma-separated product IDs; every wished-for product when omitted
+    pub pids: Option<String>,
+    #[serde(default = "default_limit")]
+    pub limit: u64,
+}
+
+impl Validate for CountsQuery {
+    fn validate(&self, v: &mut Validator) {
+        if let Some(pids) = &self.pids {
+            v.check(parse_pids(pids).is_some(), "pids", "must be a comma-separated list of product IDs");
+        }
+        v.check((1..=500).contains(&self.limit), "limit", "must be between 1 and 500");
+    }
+}
+
+fn default_limit() -> u64 {
+    50
+}
+
+fn parse_pids(pids: &str) -> Option<Vec<i32>> {
+    pids.split(',').map(|pid| pid.trim().parse().ok()).collect()
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct WishlistItemResponse {
+    pub sku: String,
+    pub pid: i32,
+    /// Current SKU title; absent once the SKU has been deleted
+    pub title: Option<String>,
+    /// Current SKU price; absent once the SKU has been deleted
+    pub price: Option<String>,
+    #[schema(value_type = i64)]
+    pub added_gmt: Timestamp,
+}
+
+impl From<WishlistEntry> for WishlistItemResponse {
+    fn from(entry: WishlistEntry) -> Self {
+        Self {
+            sku: entry.item.sku,
+            pid: entry.item.pid,
+            title: entry.sku.as_ref().map(|sku| sku.title.clone()),
+            price: entry.sku.as_ref().map(|sku| sku.price.to_string()),
+            added_gmt: entry.item.added_gmt,
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct WishlistResponse {
+    pub mid: i32,
+    pub cid: i32,
+    /// Set while the wishlist is shared
+    pub share_token: Option<String>,
+    pub items: Vec<WishlistItemResponse>,
+}
+
+/// A shared wishlist as anyone with the link sees it
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct SharedWishlistResponse {
+    pub mid: i32,
+    pub items: Vec<WishlistItemResponse>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ShareResponse {
+    pub share_token: String,
+    /// Public path the wishlist can be read at
+    pub path: String,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct WishlistCountResponse {
+    pub pid: i32,
+    /// Number of customers with the product on their wishlist
+    pub count: i64,
+}
+
+impl From<ProductWishlistCount> for WishlistCountResponse {
+    fn from(count: ProductWishlistCount) -> Self {
+        Self {
+            pid: count.pid,
+            count: count.count,
+        }
+    }
+}
+
+async fn items(state: &AppState, wishlist: &Wishlist) -> Result<Vec<WishlistItemResponse>, ApiError> {
+    let entries = WishlistService::entries(&*state.db, wishlist).await?;
+    Ok(entries.into_iter().map(WishlistItemResponse::from).collect())
+}
+
+async fn render(state: &AppState, mid: i32, cid: i32) -> Result<Json<WishlistResponse>, ApiError> {
+    let (share_token, items) = match WishlistService::find(&*state.db, mid, cid).await? {
+        Some(wishlist) => (wishlist.share_token.clone(), items(state, &wishlist).await?),
+        None => (None, Vec::new()),
+    };
+    Ok(Json(WishlistResponse { mid, cid, share_token, items }))
+}
+
+/// Get a customer's wishlist
+#[utoipa::path(
+    get,
+    path = "/api/customers/{mid}/{cid}/wishlist",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("cid" = i32, Path, description = "Customer ID")
+    ),
+    responses(
+        (status = 200, description = "Saved items, most recent first; empty if nothing was saved yet", body = WishlistResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Another customer's wishlist", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "wishlists"
+)]
+pub async fn get(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((mid, cid)): Path<(i32, i32)>,
+) -> Result<Json<WishlistResponse>, ApiError> {
+    claims.ensure_self(cid)?;
+    render(&state, mid, cid).await
+}
+
+/// Save a SKU to a customer's wishlist
+#[utoipa::path(
+    post,
+    path = "/api/customers/{mid}/{cid}/wishlist/items",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("cid" = i32, Path, description = "Customer ID")
+    ),
+    request_body = AddWishlistItemRequest,
+    responses(
+        (status = 201, description = "Wishlist with the SKU saved; saving it twice keeps one entry", body = WishlistResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Another customer's wishlist", body = ErrorBody),
+        (status = 404, description = "SKU not found", body = ErrorBody),
+        (status = 422, description = "Validation failed", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "wishlists"
+)]
+pub async fn add_item(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((mid, cid)): Path<(i32, i32)>,
+    ValidatedJson(req): ValidatedJson<AddWishlistItemRequest>,
+) -> Result<(StatusCode, Json<WishlistResponse>), ApiError> {
+    claims.ensure_self(cid)?;
+    WishlistService::add(&*state.db, mid, cid, &req.sku).await?;
+    Ok((StatusCode::CREATED, render(&state, mid, cid).await?))
+}
+
+/// Remove a SKU from a customer's wishlist
+#[utoipa::path(
+    delete,
+    path = "/api/customers/{mid}/{cid}/wishlist/items/{sku}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("cid" = i32, Path, description = "Customer ID"),
+        ("sku" = String, Path, description = "SKU code")
+    ),
+    responses(
+        (status = 204, description = "Item removed"),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Another customer's wishlist", body = ErrorBody),
+        (status = 404, description = "SKU is not on the wishlist", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "wishlists"
+)]
+pub async fn remove_item(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((mid, cid, sku)): Path<(i32, i32, String)>,
+) -> Result<StatusCode, ApiError> {
+    claims.ensure_self(cid)?;
+    if WishlistService::remove(&*state.db, mid, cid, &sku).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::not_found("Wishlist item"))
+    }
+}
+
+/// Move a saved SKU into a cart at its current price
+#[utoipa::path(
+    post,
+    path = "/api/customers/{mid}/{cid}/wishlist/items/{sku}/move-to-cart",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("cid" = i32, Path, description = "Customer ID"),
+        ("sku" = String, Path, description = "SKU code")
+    ),
+    request_body = MoveToCartRequest,
+    responses(
+        (status = 200, description = "The updated cart; the SKU is no longer on the wishlist"),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Another customer's wishlist", body = ErrorBody),
+        (status = 404, description = "Cart not found, SKU not on the wishlist, or SKU deleted", body = ErrorBody),
+        (status = 422, description = "Validation failed", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "wishlists"
+)]
+pub async fn move_to_cart(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((mid, cid, sku)): Path<(i32, i32, String)>,
+    ValidatedJson(req): ValidatedJson<MoveToCartRequest>,
+) -> Result<Json<CartResponse>, ApiError> {
+    claims.ensure_self(cid)?;
+    let wishlist = WishlistService::find(&*state.db, mid, cid)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Wishlist item"))?;
+    let entry = WishlistService::entries(&*state.db, &wishlist)
+        .await?
+        .into_iter()
+        .find(|entry| entry.item.sku == sku)
+        .ok_or_else(|| ApiError::not_found("Wishlist item"))?;
+    let found = entry.sku.ok_or(CustomerError::SkuNotFound(sku))?;
+
+    let (_guard, mut cart) = lock_cart(&state, &req.cart_id).await?;
+    cart.add_item(found.sku.clone(), found.title, req.quantity, found.price);
+    let response = save_cart(&state, &mut cart).await?;
+
+    WishlistService::remove(&*state.db, mid, cid, &found.sku).await?;
+    Ok(response)
+}
+
+/// Move an item out of a cart onto the customer's wishlist
+#[utoipa::path(
+    post,
+    path = "/api/customers/{mid}/{cid}/wishlist/from-cart",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("cid" = i32, Path, description = "Customer ID")
+    ),
+    request_body = SaveFromCartRequest,
+    responses(
+        (status = 200, description = "Wishlist with the SKU saved; the cart no longer holds it", body = WishlistResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Another customer's wishlist", body = ErrorBody),
+        (status = 404, description = "Cart not found, SKU not in the cart, or SKU deleted", body = ErrorBody),
+        (status = 422, description = "Validation failed", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "wishlists"
+)]
+pub async fn save_from_cart(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((mid, cid)): Path<(i32, i32)>,
+    ValidatedJson(req): ValidatedJson<SaveFromCartRequest>,
+) -> Result<Json<WishlistResponse>, ApiError> {
+    claims.ensure_self(cid)?;
+    let (_guard, mut cart) = lock_cart(&state, &req.cart_id).await?;
+    if cart.get_item(&req.sku).is_none() {
+        return Err(ApiError::not_found("Cart item"));
+    }
+
+    WishlistService::add(&*state.db, mid, cid, &req.sku).await?;
+    cart.remove_item(&req.sku);
+    let _ = save_cart(&state, &mut cart).await?;
+    render(&state, mid, cid).await
+}
+
+/// Share a customer's wishlist; sharing again returns the same token
+#[utoipa::path(
+    post,
+    path = "/api/customers/{mid}/{cid}/wishlist/share",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("cid" = i32, Path, description = "Customer ID")
+    ),
+    responses(
+        (status = 200, description = "Share token and public path", body = ShareResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Another customer's wishlist", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "wishlists"
+)]
+pub async fn share(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((mid, cid)): Path<(i32, i32)>,
+) -> Result<Json<ShareResponse>, ApiError> {
+    claims.ensure_self(cid)?;
+    let wishlist = WishlistService::share(&*state.db, mid, cid).await?;
+    let share_token = wishlist.share_token.unwrap_or_default();
+    Ok(Json(ShareResponse {
+        path: format!("/api/wishlists/shared/{}", share_token),
+        share_token,
+    }))
+}
+
+/// Stop sharing a customer's wishlist; the old link stops working
+#[utoipa::path(
+    delete,
+    path = "/api/customers/{mid}/{cid}/wishlist/share",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("cid" = i32, Path, description = "Customer ID")
+    ),
+    responses(
+        (status = 204, description = "Wishlist is private"),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Another customer's wishlist", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "wishlists"
+)]
+pub async fn unshare(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path((mid, cid)): Path<(i32, i32)>,
+) -> Result<StatusCode, ApiError> {
+    claims.ensure_self(cid)?;
+    WishlistService::unshare(&*state.db, mid, cid).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Read a shared wishlist
+#[utoipa::path(
+    get,
+    path = "/api/wishlists/shared/{token}",
+    params(
+        ("token" = String, Path, description = "Share token")
+    ),
+    responses(
+        (status = 200, description = "The shared wishlist", body = SharedWishlistResponse),
+        (status = 404, description = "No wishlist is shared under this token", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "wishlists"
+)]
+pub async fn shared(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+) -> Result<Json<SharedWishlistResponse>, ApiError> {
+    let wishlist = WishlistService::find_shared(&*state.db, &token)
+        .await?
+        .ok_or_else(|| ApiError::not_found("Wishlist"))?;
+    Ok(Json(SharedWishlistResponse {
+        mid: wishlist.mid,
+        items: items(&state, &wishlist).await?,
+    }))
+}
+
+/// How many customers have each product on their wishlist
+#[utoipa::path(
+    get,
+    path = "/api/wishlists/counts",
+    params(CountsQuery),
+    responses(
+        (status = 200, description = "Products, most wished for first", body = Vec<WishlistCountResponse>),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 422, description = "Invalid product IDs or limit", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "wishlists"
+)]
+pub async fn counts(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Query(query): Query<CountsQuery>,
+) -> Result<Json<Vec<WishlistCountResponse>>, ApiError> {
+    validation::validate(&query)?;
+    let mid = admin.0.scoped_mid(query.mid);
+    let pids = query.pids.as_deref().and_then(parse_pids).unwrap_or_default();
+
+    let counts = WishlistService::counts_by_product(&*state.db, mid, &pids, query.limit).await?;
+    Ok(Json(counts.into_iter().map(WishlistCountResponse::from).collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pids() {
+        assert_eq!(parse_pids("3, 5,8"), Some(vec![3, 5, 8]));
+        assert_eq!(parse_pids("3,x"), None);
+    }
+}
@@ -0,0 +1,328 @@
+//! Geo zone routes
+//!
+//! Merchants name sets of countries, states and zip ranges once and point
+//! shipping zones and tax rates at them with `zone_id`. A zone still in use
+//! cannot be deleted.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use commercerack_core::Timestamp;
+use commercerack_geo::{check_zip, Address, ZoneError, ZoneInput, ZoneService};
+use ::entity::prelude::Zone;
+use serde::{Deserialize, Serialize};
+use crate::auth::RequireMerchantAdmin;
+use crate::error::{ApiError, ErrorBody};
+use crate::validation::{self, ValidatedJson, Validate, Validator};
+use crate::AppState;
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct GeoZoneListQuery {
+    pub mid: i32,
+}
+
+fn is_country(code: &str) -> bool {
+    code.trim().len() == 2 && code.trim().chars().all(|c| c.is_ascii_alphabetic())
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct GeoZoneRequest {
+    pub name: String,
+    /// ISO 3166-1 alpha-2 codes
+    pub countries: Vec<String>,
+    /// Omit to cover every state of the countries
+    #[serde(default)]
+    pub states: Vec<String>,
+    /// Zip prefixes like `100` or ranges like `94000-94999`; omit to cover
+    /// every zip
+    #[serde(default)]
+    pub zips: Vec<String>,
+}
+
+impl Validate for GeoZoneRequest {
+    fn validate(&self, v: &mut Validator) {
+        v.required("name", &self.name, 64)
+            .check(!self.countries.is_empty(), "countries", "must list at least one country")
+            .check(self.countries.iter().all(|c| is_country(c)), "countries", "must be two-letter country codes")
+            .check(self.states.iter().all(|s| s.trim().len() <= 20), "states", "must be at most 20 characters each");
+        for zip in &self.zips {
+            if let Err(reason) = check_zip(zip) {
+                v.check(false, "zips", &format!("{}: {}", zip.trim(), reason));
+            }
+        }
+    }
+}
+
+impl GeoZoneRequest {
+    fn input(self) -> ZoneInput {
+        ZoneInput {
+            name: self.name.trim().to_string(),
+            countries: self.countries,
+            states: self.states,
+            zips: self.zips,
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct CreateGeoZoneRequest {
+    pub mid: i32,
+    #[serde(flatten)]
+    pub zone: GeoZoneRequest,
+}
+
+impl Validate for CreateGeoZoneRequest {
+    fn validate(&self, v: &mut Validator) {
+        self.zone.validate(v);
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct GeoZoneResponse {
+    pub id: i32,
+    pub mid: i32,
+    pub name: String,
+    pub countries: Vec<String>,
+    pub states: Vec<String>,
+    pub zips: Vec<String>,
+    #[schema(value_type = i64)]
+    pub created_gmt: Timestamp,
+    #[schema(value_type = i64)]
+    pub modified_gmt: Timestamp,
+}
+
+fn split(list: &str) -> Vec<String> {
+    list.split(',').filter(|s| !s.is_empty()).map(String::from).collect()
+}
+
+impl From<Zone> for GeoZoneResponse {
+    fn from(zone: Zone) -> Self {
+        Self {
+            id: zone.id,
+            mid: zone.mid,
+            name: zone.name,
+            countries: split(&zone.countries),
+            states: split(&zone.states),
+            zips: split(&zone.zips),
+            created_gmt: zone.created_gmt,
+            modified_gmt: zone.modified_gmt,
+        }
+    }
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct ResolveQuery {
+    pub mid: i32,
+    /// ISO 3166-1 alpha-2 code
+    pub country: String,
+    #[serde(default)]
+    pub state: String,
+    #[serde(default)]
+    pub zip: String,
+}
+
+impl Validate for ResolveQuery {
+    fn validate(&self, v: &mut Validator) {
+        v.check(is_country(&self.country), "country", "must be a two-letter country code")
+            .max_len("state", &self.state, 20)
+            .max_len("zip", &self.zip, 20);
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ResolvedZoneResponse {
+    /// The most specific zone the address lies in; absent when it lies in none
+    pub zone: Option<GeoZoneResponse>,
+    /// Every zone the address lies in, by ID
+    pub matching: Vec<i32>,
+}
+
+/// List the merchant's zones
+#[utoipa::path(
+    get,
+    path = "/api/zones",
+    params(GeoZoneListQuery),
+    responses(
+        (status = 200, description = "Zones by name", body = Vec<GeoZoneResponse>),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "zones"
+)]
+pub async fn list(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Query(query): Query<GeoZoneListQuery>,
+) -> Result<Json<Vec<GeoZoneResponse>>, ApiError> {
+    let zones = ZoneService::list(&*state.db, admin.0.scoped_mid(query.mid)).await?;
+    Ok(Json(zones.into_iter().map(Into::into).collect()))
+}
+
+/// Define a zone
+#[utoipa::path(
+    post,
+    path = "/api/zones",
+    request_body = CreateGeoZoneRequest,
+    responses(
+        (status = 201, description = "Zone created", body = GeoZoneResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 409, description = "Name already taken", body = ErrorBody),
+        (status = 422, description = "Missing name, invalid countries or zips", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "zones"
+)]
+pub async fn create(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    ValidatedJson(req): ValidatedJson<CreateGeoZoneRequest>,
+) -> Result<(StatusCode, Json<GeoZoneResponse>), ApiError> {
+    let zone = ZoneService::create(&*state.db, admin.0.scoped_mid(req.mid), req.zone.input()).await?;
+    Ok((StatusCode::CREATED, Json(zone.into())))
+}
+
+/// Get a zone
+#[utoipa::path(
+    get,
+    path = "/api/zones/{mid}/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Zone ID")
+    ),
+    responses(
+        (status = 200, description = "The zone", body = GeoZoneResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Zone not found", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "zones"
+)]
+pub async fn get(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<Json<GeoZoneResponse>, ApiError> {
+    let zone = ZoneService::find(&*state.db, admin.0.scoped_mid(mid), id)
+        .await?
+        .ok_or(ZoneError::NotFound)?;
+    Ok(Json(zone.into()))
+}
+
+/// Replace a zone's countries, states and zips
+#[utoipa::path(
+    put,
+    path = "/api/zones/{mid}/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Zone ID")
+    ),
+    request_body = GeoZoneRequest,
+    responses(
+        (status = 200, description = "Zone updated; shipping zones and tax rates covering it follow", body = GeoZoneResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Zone not found", body = ErrorBody),
+        (status = 409, description = "Name already taken", body = ErrorBody),
+        (status = 422, description = "Missing name, invalid countries or zips", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "zones"
+)]
+pub async fn update(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+    ValidatedJson(req): ValidatedJson<GeoZoneRequest>,
+) -> Result<Json<GeoZoneResponse>, ApiError> {
+    let zone = ZoneService::update(&*state.db, admin.0.scoped_mid(mid), id, req.input()).await?;
+    Ok(Json(zone.into()))
+}
+
+/// Delete a zone no shipping zone or tax rate covers
+#[utoipa::path(
+    delete,
+    path = "/api/zones/{mid}/{id}",
+    params(
+        ("mid" = i32, Path, description = "Merchant ID"),
+        ("id" = i32, Path, description = "Zone ID")
+    ),
+    responses(
+        (status = 204, description = "Zone deleted"),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 404, description = "Zone not found", body = ErrorBody),
+        (status = 409, description = "Zone is used by shipping zones or tax rates", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "zones"
+)]
+pub async fn delete(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Path((mid, id)): Path<(i32, i32)>,
+) -> Result<StatusCode, ApiError> {
+    if ZoneService::delete(&*state.db, admin.0.scoped_mid(mid), id).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ZoneError::NotFound.into())
+    }
+}
+
+/// Which zone an address falls in
+#[utoipa::path(
+    get,
+    path = "/api/zones/resolve",
+    params(ResolveQuery),
+    responses(
+        (status = 200, description = "The most specific zone and every matching zone", body = ResolvedZoneResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorBody),
+        (status = 403, description = "Merchant admin role required", body = ErrorBody),
+        (status = 422, description = "Invalid address", body = ErrorBody),
+        (status = 500, description = "Internal server error", body = ErrorBody)
+    ),
+    tag = "zones"
+)]
+pub async fn resolve(
+    State(state): State<AppState>,
+    admin: RequireMerchantAdmin,
+    Query(query): Query<ResolveQuery>,
+) -> Result<Json<ResolvedZoneResponse>, ApiError> {
+    validation::validate(&query)?;
+    let address = Address::new(&query.country, &query.state, &query.zip);
+    let zones = ZoneService::matching(&*state.db, admin.0.scoped_mid(query.mid), &address).await?;
+
+    Ok(Json(ResolvedZoneResponse {
+        matching: zones.iter().map(|zone| zone.id).collect(),
+        zone: commercerack_geo::most_specific(&zones).cloned().map(Into::into),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zone_validation() {
+        let req = GeoZoneRequest {
+            name: "Bay Area".to_string(),
+            countries: vec!["USA".to_string()],
+            states: vec!["CA".to_string()],
+            zips: vec!["94000-94999".to_string(), "95999-95000".to_string()],
+        };
+        match validation::validate(&req) {
+            Err(ApiError::Validation(errors)) => {
+                let fields: Vec<_> = errors.iter().map(|e| e.field.as_str()).collect();
+                assert_eq!(fields, vec!["countries", "zips"]);
+            }
+            other => panic!("unexpected result {:?}", other.err().map(|e| e.status())),
+        }
+
+        let req = GeoZoneRequest { countries: vec!["us".to_string()], zips: vec!["94000-94999".to_string()], ..req };
+        assert!(validation::validate(&req).is_ok());
+    }
+}
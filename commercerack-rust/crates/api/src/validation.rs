@@ -0,0 +1,248 @@
+//! Request body validation
+//!
+//! Request DTOs implement [`Validate`] by running checks against a
+//! [`Validator`], which records every failing field rather than stopping at
+//! the first. Handlers take bodies as [`ValidatedJson`], which rejects
+//! invalid requests with 422 and the list of field errors.
+
+use axum::{
+    async_trait,
+    extract::{rejection::JsonRejection, FromRequest, Request},
+    Json,
+};
+use rust_decimal::Decimal;
+use serde::de::DeserializeOwned;
+
+use crate::error::{ApiError, FieldError};
+
+/// A request body that can check its own fields
+pub trait Validate {
+    fn validate(&self, v: &mut Validator);
+}
+
+/// Collects field errors for one request
+#[derive(Debug, Default)]
+pub struct Validator {
+    prefix: String,
+    errors: Vec<FieldError>,
+}
+
+impl Validator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a failure for `field`
+    pub fn error(&mut self, field: &str, message: impl Into<String>) {
+        let field = format!("{}{}", self.prefix, field);
+        self.errors.push(FieldError::new(field, message));
+    }
+
+    /// Record a failure for `field` unless `ok`
+    pub fn check(&mut self, ok: bool, field: &str, message: &str) -> &mut Self {
+        if !ok {
+            self.error(field, message);
+        }
+        self
+    }
+
+    /// Non-blank and at most `max` characters
+    pub fn required(&mut self, field: &str, value: &str, max: usize) -> &mut Self {
+        if value.trim().is_empty() {
+            self.error(field, "is required");
+            self
+        } else {
+            self.max_len(field, value, max)
+        }
+    }
+
+    /// At most `max` characters
+    pub fn max_len(&mut self, field: &str, value: &str, max: usize) -> &mut Self {
+        if value.chars().count() > max {
+            self.error(field, format!("must be at most {} characters", max));
+        }
+        self
+    }
+
+    /// A plausible email address of at most `max` characters
+    pub fn email(&mut self, field: &str, value: &str, max: usize) -> &mut Self {
+        if value.trim().is_empty() {
+            self.error(field, "is required");
+        } else if !is_email(value) {
+            self.error(field, "must be a valid email address");
+        } else {
+            self.max_len(field, value, max);
+        }
+        self
+    }
+
+    /// Greater than zero
+    pub fn positive(&mut self, field: &str, value: i32) -> &mut Self {
+        self.check(value > 0, field, "must be greater than zero")
+    }
+
+    /// Zero or more
+    pub fn non_negative(&mut self, field: &str, value: i32) -> &mut Self {
+        self.check(value >= 0, field, "must not be negative")
+    }
+
+    /// A decimal amount sent as a string, zero or more
+    pub fn amount(&mut self, field: &str, value: &str) -> &mut Self {
+        match value.trim().parse::<Decimal>() {
+            Ok(amount) if amount.is_sign_negative() => self.error(field, "must not be negative"),
+            Ok(_) => {}
+            Err(_) => self.error(field, "must be a decimal number"),
+        }
+        self
+    }
+
+    /// A decimal amount sent as a string, greater than zero
+    pub fn positive_amount(&mut self, field: &str, value: &str) -> &mut Self {
+        match value.trim().parse::<Decimal>() {
+            Ok(amount) if amount <= Decimal::ZERO => self.error(field, "must be greater than zero"),
+            Ok(_) => {}
+            Err(_) => self.error(field, "must be a decimal number"),
+        }
+        self
+    }
+
+    /// Validate a nested value, reporting its errors under `field.`
+    pub fn nested(&mut self, field: &str, value: &impl Validate) -> &mut Self {
+        let inner = format!("{}{}.", self.prefix, field);
+        let outer = std::mem::replace(&mut self.prefix, inner);
+        value.validate(self);
+        self.prefix = outer;
+        self
+    }
+
+    /// Validate each element of a list, reporting errors under `field[i].`
+    pub fn each<T: Validate>(&mut self, field: &str, values: &[T]) -> &mut Self {
+        for (i, value) in values.iter().enumerate() {
+            self.nested(&format!("{}[{}]", field, i), value);
+        }
+        self
+    }
+
+    pub fn finish(self) -> Result<(), ApiError> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ApiError::Validation(self.errors))
+        }
+    }
+}
+
+/// Good enough to catch typos; deliverability is the mail server's problem
+fn is_email(value: &str) -> bool {
+    let Some((local, domain)) = value.split_once('@') else {
+        return false;
+    };
+    !local.is_empty()
+        && !value.chars().any(char::is_whitespace)
+        && !domain.contains('@')
+        && domain.contains('.')
+        && !domain.starts_with('.')
+        && !domain.ends_with('.')
+}
+
+/// Run a value's checks
+pub fn validate(value: &impl Validate) -> Result<(), ApiError> {
+    let mut v = Validator::new();
+    value.validate(&mut v);
+    v.finish()
+}
+
+/// JSON body extractor that also runs the body's [`Validate`] checks
+pub struct ValidatedJson<T>(pub T);
+
+#[async_trait]
+impl<S, T> FromRequest<S> for ValidatedJson<T>
+where
+    S: Send + Sync,
+    T: DeserializeOwned + Validate,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::<T>::from_request(req, state).await.map_err(|rejection| match rejection {
+            // Well-formed JSON of the wrong shape, e.g. a missing field
+            JsonRejection::JsonDataError(e) => ApiError::Validation(vec![FieldError::new("body", e.body_text())]),
+            rejection => ApiError::BadRequest(rejection.body_text()),
+        })?;
+
+        validate(&value)?;
+        Ok(Self(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Line {
+        sku: String,
+        quantity: i32,
+    }
+
+    impl Validate for Line {
+        fn validate(&self, v: &mut Validator) {
+            v.required("sku", &self.sku, 5).positive("quantity", self.quantity);
+        }
+    }
+
+    struct Order {
+        email: String,
+        total: String,
+        lines: Vec<Line>,
+    }
+
+    impl Validate for Order {
+        fn validate(&self, v: &mut Validator) {
+            v.email("email", &self.email, 65)
+                .amount("total", &self.total)
+                .check(!self.lines.is_empty(), "lines", "must not be empty")
+                .each("lines", &self.lines);
+        }
+    }
+
+    fn field_errors(order: &Order) -> Vec<String> {
+        match validate(order) {
+            Ok(()) => Vec::new(),
+            Err(ApiError::Validation(errors)) => errors.into_iter().map(|e| e.field).collect(),
+            Err(e) => panic!("unexpected error {e:?}"),
+        }
+    }
+
+    #[test]
+    fn test_collects_every_field_error() {
+        let valid = Order {
+            email: "shopper@example.com".to_string(),
+            total: "19.99".to_string(),
+            lines: vec![Line { sku: "SKU1".to_string(), quantity: 1 }],
+        };
+        assert!(field_errors(&valid).is_empty());
+
+        let invalid = Order {
+            email: "not-an-email".to_string(),
+            total: "-1".to_string(),
+            lines: vec![
+                Line { sku: "SKU1".to_string(), quantity: 1 },
+                Line { sku: "TOO-LONG".to_string(), quantity: 0 },
+            ],
+        };
+        assert_eq!(
+            field_errors(&invalid),
+            vec!["email", "total", "lines[1].sku", "lines[1].quantity"]
+        );
+    }
+
+    #[test]
+    fn test_is_email() {
+        assert!(is_email("a@b.co"));
+        assert!(!is_email("a@b"));
+        assert!(!is_email("@b.co"));
+        assert!(!is_email("a b@c.co"));
+        assert!(!is_email("a@b@c.co"));
+        assert!(!is_email("a@.co"));
+    }
+}
@@ -0,0 +1,200 @@
+//! API versioning
+//!
+//! Routes are registered once, without a version, and served under every
+//! supported prefix: `/api/v1/orders` and `/api/v2/orders` both reach the
+//! `/api/orders` handler, which reads the [`ApiVersion`] extractor where
+//! its response differs between versions. Unknown versions are 404.
+//!
+//! The unversioned `/api/...` paths predate versioning and alias v1. Their
+//! responses carry `Deprecation`, a `Link` to the v1 path and, once
+//! `api.unversioned_sunset` is configured, a `Sunset` date (RFC 8594).
+//!
+//! The rewrite has to happen before routing, so [`route_version`] is
+//! layered on a router wrapping the application's rather than on it.
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Request, State},
+    http::{header, request::Parts, uri::PathAndQuery, HeaderName, HeaderValue, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::NaiveTime;
+use commercerack_config::ApiConfig;
+use std::convert::Infallible;
+use std::str::FromStr;
+
+use crate::error::ApiError;
+
+pub const DEPRECATION_HEADER: &str = "deprecation";
+pub const SUNSET_HEADER: &str = "sunset";
+
+const API_PREFIX: &str = "/api";
+
+/// Version a request was made against
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ApiVersion {
+    #[default]
+    V1,
+    V2,
+}
+
+impl ApiVersion {
+    pub const ALL: [ApiVersion; 2] = [ApiVersion::V1, ApiVersion::V2];
+
+    /// The path segment naming this version
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiVersion::V1 => "v1",
+            ApiVersion::V2 => "v2",
+        }
+    }
+}
+
+impl FromStr for ApiVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ApiVersion::ALL
+            .into_iter()
+            .find(|version| version.as_str() == s)
+            .ok_or_else(|| format!("unsupported API version {}", s))
+    }
+}
+
+/// Requests that bypassed [`route_version`] (tests, non-API routes) are v1
+#[async_trait]
+impl<S> FromRequestParts<S> for ApiVersion
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts.extensions.get::<ApiVersion>().copied().unwrap_or_default())
+    }
+}
+
+/// Headers added to responses of unversioned paths
+#[derive(Clone, Default)]
+pub struct Versioning {
+    sunset: Option<HeaderValue>,
+}
+
+impl Versioning {
+    pub fn new(config: &ApiConfig) -> Self {
+        let sunset = config.unversioned_sunset.and_then(|date| {
+            let http_date = date.and_time(NaiveTime::MIN).and_utc().format("%a, %d %b %Y %H:%M:%S GMT");
+            HeaderValue::from_str(&http_date.to_string()).ok()
+        });
+        Self { sunset }
+    }
+}
+
+/// How an API path names its version
+#[derive(Debug, PartialEq)]
+enum Versioned<'a> {
+    /// `/api/{version}{rest}`
+    Explicit(ApiVersion, &'a str),
+    /// `/api/v{n}...` for a version that doesn't exist
+    Unsupported(&'a str),
+    /// `/api{rest}`
+    Unversioned(&'a str),
+}
+
+/// Split an `/api` path into its version and the path after it
+fn parse_path(path: &str) -> Option<Versioned<'_>> {
+    let rest = path.strip_prefix(API_PREFIX)?;
+    if !(rest.is_empty() || rest.starts_with('/')) {
+        return None;
+    }
+
+    let segment = rest.get(1..).unwrap_or_default().split('/').next().unwrap_or_default();
+    let names_version =
+        segment.len() > 1 && segment.starts_with('v') && segment[1..].bytes().all(|b| b.is_ascii_digit());
+    if !names_version {
+        return Some(Versioned::Unversioned(rest));
+    }
+
+    let after = &rest[1 + segment.len()..];
+    Some(match segment.parse() {
+        Ok(version) => Versioned::Explicit(version, after),
+        Err(_) => Versioned::Unsupported(segment),
+    })
+}
+
+/// `uri` with its path replaced, keeping the query
+fn with_path(uri: &Uri, path: &str) -> Option<Uri> {
+    let path_and_query = match uri.query() {
+        Some(query) => format!("{}?{}", path, query),
+        None => path.to_string(),
+    };
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = Some(PathAndQuery::from_str(&path_and_query).ok()?);
+    Uri::from_parts(parts).ok()
+}
+
+/// Middleware mapping versioned paths onto the routes and marking
+/// unversioned ones deprecated
+pub async fn route_version(State(versioning): State<Versioning>, mut request: Request, next: Next) -> Response {
+    let path = request.uri().path().to_string();
+    match parse_path(&path) {
+        None => next.run(request).await,
+        Some(Versioned::Unsupported(segment)) => {
+            ApiError::NotFound(format!("API version {} is not supported", segment)).into_response()
+        }
+        Some(Versioned::Explicit(version, rest)) => {
+            if let Some(uri) = with_path(request.uri(), &format!("{}{}", API_PREFIX, rest)) {
+                *request.uri_mut() = uri;
+            }
+            request.extensions_mut().insert(version);
+            next.run(request).await
+        }
+        Some(Versioned::Unversioned(rest)) => {
+            request.extensions_mut().insert(ApiVersion::V1);
+            let mut response = next.run(request).await;
+
+            let headers = response.headers_mut();
+            headers.insert(HeaderName::from_static(DEPRECATION_HEADER), HeaderValue::from_static("true"));
+            if let Some(sunset) = &versioning.sunset {
+                headers.insert(HeaderName::from_static(SUNSET_HEADER), sunset.clone());
+            }
+            let link = format!("<{}/{}{}>; rel=\"successor-version\"", API_PREFIX, ApiVersion::V1.as_str(), rest);
+            if let Ok(link) = HeaderValue::from_str(&link) {
+                headers.append(header::LINK, link);
+            }
+            response
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_parse_path() {
+        assert_eq!(parse_path("/api/v1/orders"), Some(Versioned::Explicit(ApiVersion::V1, "/orders")));
+        assert_eq!(parse_path("/api/v2"), Some(Versioned::Explicit(ApiVersion::V2, "")));
+        assert_eq!(parse_path("/api/v9/orders"), Some(Versioned::Unsupported("v9")));
+        assert_eq!(parse_path("/api/orders/1/2"), Some(Versioned::Unversioned("/orders/1/2")));
+        // Only whole segments name a version
+        assert_eq!(parse_path("/api/v1beta/orders"), Some(Versioned::Unversioned("/v1beta/orders")));
+        assert_eq!(parse_path("/api-docs/openapi.json"), None);
+        assert_eq!(parse_path("/health"), None);
+    }
+
+    #[test]
+    fn test_rewrite_keeps_the_query() {
+        let uri: Uri = "/api/v1/orders?mid=1&limit=5".parse().unwrap();
+        assert_eq!(with_path(&uri, "/api/orders").unwrap(), "/api/orders?mid=1&limit=5");
+    }
+
+    #[test]
+    fn test_sunset_is_an_http_date() {
+        let config = ApiConfig { unversioned_sunset: NaiveDate::from_ymd_opt(2027, 6, 30), ..Default::default() };
+        assert_eq!(Versioning::new(&config).sunset.unwrap(), "Wed, 30 Jun 2027 00:00:00 GMT");
+        assert!(Versioning::new(&ApiConfig::default()).sunset.is_none());
+    }
+}
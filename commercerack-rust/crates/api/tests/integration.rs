@@ -5,14 +5,21 @@ use axum::{
     body::Body,
     http::{Request, StatusCode},
 };
-use commercerack_api::app;
+use commercerack_api::auth::{Claims, Role};
+use commercerack_api::{app, AppConfig};
 use sea_orm::{DatabaseBackend, MockDatabase};
 use tower::ServiceExt;
 
+fn config() -> AppConfig {
+    let mut config = AppConfig::default();
+    config.jwt.secret = "integration-test-secret-0123456789".to_string();
+    config
+}
+
 #[tokio::test]
 async fn test_health_endpoint() {
     let db = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
-    let app = app(db);
+    let app = app(db, config());
 
     let request = Request::builder()
         .uri("/health")
@@ -26,7 +33,7 @@ async fn test_health_endpoint() {
 #[tokio::test]
 async fn test_swagger_ui_available() {
     let db = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
-    let app = app(db);
+    let app = app(db, config());
 
     let request = Request::builder()
         .uri("/swagger-ui/")
@@ -40,7 +47,7 @@ async fn test_swagger_ui_available() {
 #[tokio::test]
 async fn test_rapidoc_available() {
     let db = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
-    let app = app(db);
+    let app = app(db, config());
 
     let request = Request::builder()
         .uri("/rapidoc")
@@ -50,3 +57,55 @@ async fn test_rapidoc_available() {
     let response = app.oneshot(request).await.unwrap();
     assert_eq!(response.status(), StatusCode::OK);
 }
+
+#[tokio::test]
+async fn test_admin_route_requires_token() {
+    let db = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let app = app(db, config());
+
+    let request = Request::builder()
+        .method("GET")
+        .uri("/api/orders?mid=1")
+        .body(Body::empty())
+        .unwrap();
+
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_admin_route_rejects_customer_token() {
+    let db = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let app = app(db, config());
+
+    let token = Claims::new(1, 1).encode(&config().jwt.secret).unwrap();
+    let request = Request::builder()
+        .method("GET")
+        .uri("/api/orders?mid=1")
+        .header("Authorization", format!("Bearer {}", token))
+        .body(Body::empty())
+        .unwrap();
+
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_token_rejected_for_other_merchant() {
+    let db = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+    let app = app(db, config());
+
+    let token = Claims::new(1, 1)
+        .with_role(Role::MerchantAdmin)
+        .encode(&config().jwt.secret)
+        .unwrap();
+    let request = Request::builder()
+        .method("GET")
+        .uri("/api/products/2/5")
+        .header("Authorization", format!("Bearer {}", token))
+        .body(Body::empty())
+        .unwrap();
+
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
@@ -0,0 +1,255 @@
+//! Audit log of admin changes
+//!
+//! An admin change — a price, an order edit, a refund, a customer's
+//! settings — is described as a [`Change`]: what kind of record, which one,
+//! what was done, and the fields that differ before and after. Only changed
+//! fields are kept, and bookkeeping fields (versions, modification times)
+//! never count as a change, so an entry shows exactly what the actor did.
+
+use commercerack_core::Timestamp;
+use sea_orm::*;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use thiserror::Error;
+use tracing::instrument;
+use ::entity::audit_log;
+use ::entity::prelude::*;
+
+#[derive(Error, Debug)]
+pub enum AuditError {
+    #[error("Database error: {0}")]
+    Db(#[from] DbErr),
+}
+
+/// Fields that change on every write and say nothing about what changed
+const BOOKKEEPING_FIELDS: [&str; 3] = ["v", "ts", "modified_gmt"];
+
+/// Who made a change
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Actor {
+    /// Their token's subject
+    pub id: String,
+    pub role: String,
+}
+
+/// What changed on one record
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    entity_type: &'static str,
+    entity_id: String,
+    action: &'static str,
+    before: Map<String, Value>,
+    after: Map<String, Value>,
+    /// Set by a diff that found no differences
+    unchanged: bool,
+}
+
+impl Change {
+    /// A change without field values, e.g. a deletion of something not
+    /// loaded first
+    pub fn new(entity_type: &'static str, entity_id: impl ToString, action: &'static str) -> Self {
+        Self {
+            entity_type,
+            entity_id: entity_id.to_string(),
+            action,
+            before: Map::new(),
+            after: Map::new(),
+            unchanged: false,
+        }
+    }
+
+    /// Keep the fields whose values differ between `before` and `after`
+    pub fn diff<T: Serialize>(mut self, before: &T, after: &T) -> Self {
+        let (mut before, mut after) = (fields(before), fields(after));
+        let unchanged: Vec<String> = before
+            .iter()
+            .filter(|(key, value)| after.get(*key) == Some(value))
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in unchanged {
+            before.remove(&key);
+            after.remove(&key);
+        }
+        self.unchanged = before.is_empty() && after.is_empty();
+        self.before = before;
+        self.after = after;
+        self
+    }
+
+    /// Record every field of a record that was created
+    pub fn created<T: Serialize>(mut self, after: &T) -> Self {
+        self.after = fields(after);
+        self
+    }
+
+    /// Record every field of a record that was removed
+    pub fn removed<T: Serialize>(mut self, before: &T) -> Self {
+        self.before = fields(before);
+        self
+    }
+
+    /// Whether a diff found nothing to record
+    pub fn is_unchanged(&self) -> bool {
+        self.unchanged
+    }
+}
+
+/// A record's fields as a JSON object, without bookkeeping
+fn fields<T: Serialize>(value: &T) -> Map<String, Value> {
+    // Models and plain structs always serialize
+    match serde_json::to_value(value).unwrap_or_default() {
+        Value::Object(mut map) => {
+            for field in BOOKKEEPING_FIELDS {
+                map.remove(field);
+            }
+            map
+        }
+        Value::Null => Map::new(),
+        other => Map::from_iter([("value".to_string(), other)]),
+    }
+}
+
+fn to_column(fields: Map<String, Value>) -> Option<String> {
+    (!fields.is_empty()).then(|| Value::Object(fields).to_string())
+}
+
+/// Filters for [`AuditService::list`]; unset ones match everything
+#[derive(Debug, Clone, Default)]
+pub struct AuditFilter {
+    pub entity_type: Option<String>,
+    pub entity_id: Option<String>,
+    pub actor: Option<String>,
+    pub from: Option<Timestamp>,
+    /// Exclusive
+    pub to: Option<Timestamp>,
+}
+
+pub struct AuditService;
+
+impl AuditService {
+    /// Store `change` to one of `mid`'s records. A diff that found no
+    /// changed fields is not stored.
+    #[instrument(skip_all, fields(mid = mid, entity_type = change.entity_type, action = change.action))]
+    pub async fn record<C: ConnectionTrait>(db: &C, mid: i32, actor: &Actor, change: Change) -> Result<(), AuditError> {
+        if change.is_unchanged() {
+            return Ok(());
+        }
+
+        let entry = audit_log::ActiveModel {
+            mid: Set(mid),
+            actor: Set(actor.id.clone()),
+            actor_role: Set(actor.role.clone()),
+            entity_type: Set(change.entity_type.to_string()),
+            entity_id: Set(change.entity_id),
+            action: Set(change.action.to_string()),
+            before: Set(to_column(change.before)),
+            after: Set(to_column(change.after)),
+            created_gmt: Set(Timestamp::now()),
+            ..Default::default()
+        };
+        AuditLog::insert(entry).exec_without_returning(db).await?;
+        Ok(())
+    }
+
+    /// A merchant's audit entries, newest first
+    pub async fn list<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        filter: &AuditFilter,
+        limit: u64,
+        offset: u64,
+    ) -> Result<Vec<AuditEntry>, AuditError> {
+        use ::entity::audit_log::Column;
+
+        let mut query = AuditLog::find().filter(Column::Mid.eq(mid));
+        if let Some(entity_type) = &filter.entity_type {
+            query = query.filter(Column::EntityType.eq(entity_type));
+        }
+        if let Some(entity_id) = &filter.entity_id {
+            query = query.filter(Column::EntityId.eq(entity_id));
+        }
+        if let Some(actor) = &filter.actor {
+            query = query.filter(Column::Actor.eq(actor));
+        }
+        if let Some(from) = filter.from {
+            query = query.filter(Column::CreatedGmt.gte(from));
+        }
+        if let Some(to) = filter.to {
+            query = query.filter(Column::CreatedGmt.lt(to));
+        }
+
+        Ok(query
+            .order_by_desc(Column::Id)
+            .limit(limit)
+            .offset(offset)
+            .all(db)
+            .await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Sku {
+        id: i32,
+        price: String,
+        name: String,
+        v: i32,
+    }
+
+    fn sku(price: &str, v: i32) -> Sku {
+        Sku { id: 4, price: price.to_string(), name: "Widget".to_string(), v }
+    }
+
+    fn admin() -> Actor {
+        Actor { id: "12".to_string(), role: "merchant_admin".to_string() }
+    }
+
+    #[test]
+    fn test_diff_keeps_only_changed_fields() {
+        let change = Change::new("sku", 4, "update").diff(&sku("9.99", 1), &sku("7.99", 2));
+        assert_eq!(Value::Object(change.before), serde_json::json!({"price": "9.99"}));
+        assert_eq!(Value::Object(change.after), serde_json::json!({"price": "7.99"}));
+
+        // A version bump alone is not a change
+        assert!(Change::new("sku", 4, "update").diff(&sku("9.99", 1), &sku("9.99", 2)).is_unchanged());
+    }
+
+    #[tokio::test]
+    async fn test_record_stores_actor_and_diff() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_exec_results([MockExecResult { last_insert_id: 1, rows_affected: 1 }])
+            .into_connection();
+
+        let change = Change::new("sku", 4, "update").diff(&sku("9.99", 1), &sku("7.99", 2));
+        AuditService::record(&db, 3, &admin(), change).await.unwrap();
+        // Nothing changed, nothing stored
+        let unchanged = Change::new("sku", 4, "update").diff(&sku("7.99", 2), &sku("7.99", 3));
+        AuditService::record(&db, 3, &admin(), unchanged).await.unwrap();
+
+        let log = db.into_transaction_log();
+        assert_eq!(log.len(), 1);
+        let statement = &log[0].statements()[0];
+        assert!(statement.sql.starts_with(r#"INSERT INTO "audit_log""#), "{}", statement.sql);
+        let values = &statement.values.as_ref().unwrap().0;
+        assert_eq!(values[1], sea_orm::Value::String(Some(Box::new("12".to_string()))));
+        assert_eq!(values[6], sea_orm::Value::String(Some(Box::new(r#"{"price":"9.99"}"#.to_string()))));
+    }
+
+    #[tokio::test]
+    async fn test_list_applies_filters() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([Vec::<AuditEntry>::new()])
+            .into_connection();
+
+        let filter = AuditFilter { entity_type: Some("order".to_string()), entity_id: Some("9".to_string()), ..Default::default() };
+        AuditService::list(&db, 3, &filter, 20, 0).await.unwrap();
+
+        let log = db.into_transaction_log();
+        let sql = log[0].statements()[0].to_string();
+        assert!(sql.contains(r#""audit_log"."entity_type" = 'order' AND "audit_log"."entity_id" = '9'"#), "{}", sql);
+        assert!(sql.contains(r#"ORDER BY "audit_log"."id" DESC LIMIT 20"#), "{}", sql);
+    }
+}
@@ -0,0 +1,375 @@
+//! Abandoned cart tracking and recovery
+//!
+//! A cart with a [`CartContact`] that goes untouched for the idle period is
+//! archived into `abandoned_carts` with a recovery token, and a
+//! `cart.abandoned` event is recorded so the merchant's integrations can
+//! send the shopper a reminder. Database carts are found by
+//! [`AbandonedCartService::detect_idle`]; Redis carts are archived by the
+//! store's sweep once their TTL lapses; in-memory carts are never tracked.
+//!
+//! The recovery link restores the archived cart under its original ID.
+//! When that cart is checked out, the order is stamped on the archive row,
+//! which is what [`AbandonedCartService::metrics`] counts as recovered.
+
+use anyhow::Result;
+use commercerack_core::Timestamp;
+use commercerack_events::{outbox, DomainEvent};
+use rust_decimal::Decimal;
+use sea_orm::sea_query::Expr;
+use sea_orm::*;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{info, instrument, warn};
+use uuid::Uuid;
+use ::entity::abandoned_carts;
+use ::entity::prelude::*;
+
+use crate::{Cart, CartContact, CartItem};
+
+/// Idle database carts looked at per detection pass
+const DETECT_BATCH_SIZE: u64 = 100;
+
+/// Totals over the carts abandoned in a period
+#[derive(Debug, Clone, Default, PartialEq, FromQueryResult, Serialize)]
+pub struct AbandonmentMetrics {
+    pub abandoned: i64,
+    /// Subtotal of the abandoned carts; `None` when there were none
+    pub abandoned_value: Option<Decimal>,
+    /// Carts whose recovery link was opened
+    pub recovered: i64,
+    /// Carts later checked out
+    pub ordered: i64,
+    pub ordered_value: Option<Decimal>,
+}
+
+impl AbandonmentMetrics {
+    /// Share of abandoned carts that were checked out, 0 to 1
+    pub fn recovery_rate(&self) -> f64 {
+        if self.abandoned == 0 {
+            0.0
+        } else {
+            self.ordered as f64 / self.abandoned as f64
+        }
+    }
+}
+
+/// The cart an archive row holds, with its original ID and contact
+pub fn restore(record: &AbandonedCart) -> Result<Cart> {
+    let mut cart = Cart::with_id(record.cart_id.clone());
+    cart.items = serde_json::from_str::<Vec<CartItem>>(&record.items)?;
+    cart.contact = (record.mid != 0).then(|| CartContact {
+        mid: record.mid,
+        cid: record.cid,
+        email: record.email.clone(),
+    });
+    Ok(cart)
+}
+
+/// Abandoned cart service
+pub struct AbandonedCartService;
+
+impl AbandonedCartService {
+    /// Archive a cart as abandoned at `now`. Carts with a contact get a
+    /// recovery token and a `cart.abandoned` event in the same transaction.
+    pub async fn archive<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        cart: &Cart,
+        now: Timestamp,
+    ) -> Result<AbandonedCart> {
+        let txn = db.begin().await?;
+        let record = Self::insert(&txn, cart, now).await?;
+        txn.commit().await?;
+        Ok(record)
+    }
+
+    async fn insert<C: ConnectionTrait>(db: &C, cart: &Cart, now: Timestamp) -> Result<AbandonedCart> {
+        let contact = cart.contact.clone();
+        let record = abandoned_carts::ActiveModel {
+            cart_id: Set(cart.cart_id.clone()),
+            items: Set(serde_json::to_string(&cart.items)?),
+            item_count: Set(cart.item_count()),
+            subtotal: Set(cart.subtotal().amount()),
+            abandoned_gmt: Set(now),
+            mid: Set(contact.as_ref().map_or(0, |c| c.mid)),
+            cid: Set(contact.as_ref().map_or(0, |c| c.cid)),
+            email: Set(contact.as_ref().map(|c| c.email.clone()).unwrap_or_default()),
+            recovery_token: Set(contact.as_ref().map(|_| Uuid::new_v4().simple().to_string())),
+            recovered_gmt: Set(None),
+            order_id: Set(None),
+            order_total: Set(None),
+            ordered_gmt: Set(None),
+            ..Default::default()
+        }
+        .insert(db)
+        .await?;
+
+        if contact.is_some() {
+            outbox::record(db, &DomainEvent::CartAbandoned(record.clone())).await?;
+        }
+        Ok(record)
+    }
+
+    /// Archive database carts with a contact that haven't changed for
+    /// `idle`. Each is archived once per idle spell: saving the cart again
+    /// makes it eligible for the next one. Returns how many were archived.
+    #[instrument(skip_all)]
+    pub async fn detect_idle<C: ConnectionTrait + TransactionTrait>(db: &C, idle: Duration) -> Result<usize> {
+        use ::entity::carts::Column;
+
+        let now = Timestamp::now();
+        let cutoff = now - chrono::Duration::seconds(idle.as_secs() as i64);
+        let idle_carts = Carts::find()
+            .filter(Column::Mid.ne(0))
+            .filter(Column::Email.ne(""))
+            .filter(Column::AbandonedGmt.is_null())
+            .filter(Column::ModifiedGmt.lte(cutoff))
+            .order_by_asc(Column::ModifiedGmt)
+            .limit(DETECT_BATCH_SIZE)
+            .all(db)
+            .await?;
+
+        let mut archived = 0;
+        for record in idle_carts {
+            let txn = db.begin().await?;
+
+            // Claim the cart unless it changed since it was read
+            let claimed = Carts::update_many()
+                .col_expr(Column::AbandonedGmt, Expr::value(now))
+                .filter(Column::CartId.eq(record.cart_id.as_str()))
+                .filter(Column::AbandonedGmt.is_null())
+                .filter(Column::ModifiedGmt.lte(cutoff))
+                .exec(&txn)
+                .await?;
+            if claimed.rows_affected == 0 {
+                continue;
+            }
+
+            let items: Vec<CartItem> = CartItems::find()
+                .filter(::entity::cart_items::Column::CartId.eq(record.cart_id.as_str()))
+                .order_by_asc(::entity::cart_items::Column::Id)
+                .all(&txn)
+                .await?
+                .into_iter()
+                .map(crate::storage::from_record)
+                .collect();
+
+            // An empty cart is claimed so it isn't looked at again, but
+            // there is nothing to win back
+            if !items.is_empty() {
+                let mut cart = Cart::with_id(record.cart_id.clone());
+                cart.items = items;
+                cart.contact = Some(CartContact {
+                    mid: record.mid,
+                    cid: record.cid,
+                    email: record.email.clone(),
+                });
+                Self::insert(&txn, &cart, now).await?;
+                archived += 1;
+            }
+            txn.commit().await?;
+        }
+
+        Ok(archived)
+    }
+
+    /// Run `detect_idle` on a fixed interval until the task is aborted
+    pub fn spawn_detector(db: Arc<DatabaseConnection>, idle: Duration, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                match Self::detect_idle(db.as_ref(), idle).await {
+                    Ok(0) => {}
+                    Ok(n) => info!("🛒 Marked {} idle carts as abandoned", n),
+                    Err(e) => warn!("Idle cart detection failed: {}", e),
+                }
+            }
+        })
+    }
+
+    /// Find an archived cart by its recovery token
+    pub async fn find_by_token<C: ConnectionTrait>(db: &C, token: &str) -> Result<Option<AbandonedCart>, DbErr> {
+        AbandonedCarts::find()
+            .filter(abandoned_carts::Column::RecoveryToken.eq(token))
+            .one(db)
+            .await
+    }
+
+    /// Note that a recovery link was opened. Only the first time counts.
+    pub async fn mark_recovered<C: ConnectionTrait>(db: &C, id: i32) -> Result<(), DbErr> {
+        AbandonedCarts::update_many()
+            .col_expr(abandoned_carts::Column::RecoveredGmt, Expr::value(Timestamp::now()))
+            .filter(abandoned_carts::Column::Id.eq(id))
+            .filter(abandoned_carts::Column::RecoveredGmt.is_null())
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+
+    /// Stamp a checked-out cart's archive rows with its order. Call in the
+    /// checkout transaction.
+    pub async fn mark_ordered<C: ConnectionTrait>(
+        db: &C,
+        cart_id: &str,
+        order_id: i32,
+        total: Decimal,
+    ) -> Result<u64, DbErr> {
+        let result = AbandonedCarts::update_many()
+            .col_expr(abandoned_carts::Column::OrderId, Expr::value(order_id))
+            .col_expr(abandoned_carts::Column::OrderTotal, Expr::value(total))
+            .col_expr(abandoned_carts::Column::OrderedGmt, Expr::value(Timestamp::now()))
+            .filter(abandoned_carts::Column::CartId.eq(cart_id))
+            .filter(abandoned_carts::Column::OrderId.is_null())
+            .exec(db)
+            .await?;
+        Ok(result.rows_affected)
+    }
+
+    /// A merchant's abandoned carts, newest first. `ordered` narrows to
+    /// carts that were, or weren't, checked out afterwards.
+    pub async fn list<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        ordered: Option<bool>,
+        limit: u64,
+    ) -> Result<Vec<AbandonedCart>, DbErr> {
+        let mut query = AbandonedCarts::find().filter(abandoned_carts::Column::Mid.eq(mid));
+        query = match ordered {
+            Some(true) => query.filter(abandoned_carts::Column::OrderId.is_not_null()),
+            Some(false) => query.filter(abandoned_carts::Column::OrderId.is_null()),
+            None => query,
+        };
+
+        query
+            .order_by_desc(abandoned_carts::Column::AbandonedGmt)
+            .order_by_desc(abandoned_carts::Column::Id)
+            .limit(limit)
+            .all(db)
+            .await
+    }
+
+    /// Totals over the carts a merchant lost between `from` and `to`
+    pub async fn metrics<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        from: Timestamp,
+        to: Timestamp,
+    ) -> Result<AbandonmentMetrics, DbErr> {
+        use abandoned_carts::Column;
+
+        // COUNT of a nullable column counts only the rows where it is set
+        let metrics = AbandonedCarts::find()
+            .select_only()
+            .column_as(Expr::col(Column::Id).count(), "abandoned")
+            .column_as(Expr::col(Column::Subtotal).sum(), "abandoned_value")
+            .column_as(Expr::col(Column::RecoveredGmt).count(), "recovered")
+            .column_as(Expr::col(Column::OrderId).count(), "ordered")
+            .column_as(Expr::col(Column::OrderTotal).sum(), "ordered_value")
+            .filter(Column::Mid.eq(mid))
+            .filter(Column::AbandonedGmt.between(from, to))
+            .into_model::<AbandonmentMetrics>()
+            .one(db)
+            .await?;
+
+        Ok(metrics.unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contact() -> CartContact {
+        CartContact {
+            mid: 3,
+            cid: 0,
+            email: "guest@example.com".to_string(),
+        }
+    }
+
+    fn archived(cart: &Cart, token: Option<&str>) -> AbandonedCart {
+        AbandonedCart {
+            id: 1,
+            cart_id: cart.cart_id.clone(),
+            items: serde_json::to_string(&cart.items).unwrap(),
+            item_count: cart.item_count(),
+            subtotal: cart.subtotal().amount(),
+            abandoned_gmt: Timestamp::from_unix(1_700_000_000),
+            mid: cart.contact.as_ref().map_or(0, |c| c.mid),
+            cid: 0,
+            email: cart.contact.as_ref().map(|c| c.email.clone()).unwrap_or_default(),
+            recovery_token: token.map(str::to_string),
+            recovered_gmt: None,
+            order_id: None,
+            order_total: None,
+            ordered_gmt: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_archive_with_contact_records_event() {
+        let mut cart = Cart::new();
+        cart.add_item("SKU001".to_string(), "Widget".to_string(), 2, Decimal::new(1000, 2));
+        cart.contact = Some(contact());
+
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![archived(&cart, Some("token"))]])
+            .append_exec_results([MockExecResult { last_insert_id: 0, rows_affected: 1 }])
+            .into_connection();
+
+        let record = AbandonedCartService::archive(&db, &cart, Timestamp::from_unix(1_700_000_000)).await.unwrap();
+        assert_eq!(record.recovery_token.as_deref(), Some("token"));
+
+        let log = db.into_transaction_log();
+        let statements = log[0].statements();
+        let insert = statements[1].to_string();
+        assert!(insert.contains(r#"INSERT INTO "abandoned_carts""#), "{}", insert);
+        assert!(insert.contains("'guest@example.com'"), "{}", insert);
+        let event = statements[2].to_string();
+        assert!(event.contains(r#"INSERT INTO "event_outbox""#), "{}", event);
+        assert!(event.contains("'cart.abandoned'"), "{}", event);
+    }
+
+    #[tokio::test]
+    async fn test_archive_without_contact_has_no_token() {
+        let mut cart = Cart::new();
+        cart.add_item("SKU001".to_string(), "Widget".to_string(), 1, Decimal::ONE);
+
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![archived(&cart, None)]])
+            .into_connection();
+
+        AbandonedCartService::archive(&db, &cart, Timestamp::from_unix(1_700_000_000)).await.unwrap();
+
+        let log = db.into_transaction_log();
+        let statements = log[0].statements();
+        assert!(statements[1].to_string().contains("NULL"), "{}", statements[1]);
+        // Begin, insert, commit: no event without someone to contact
+        assert_eq!(statements.len(), 3);
+    }
+
+    #[test]
+    fn test_restore_keeps_id_items_and_contact() {
+        let mut cart = Cart::new();
+        cart.add_item("SKU001".to_string(), "Widget".to_string(), 2, Decimal::new(1000, 2));
+        cart.contact = Some(contact());
+
+        let restored = restore(&archived(&cart, Some("token"))).unwrap();
+        assert_eq!(restored.cart_id, cart.cart_id);
+        assert_eq!(restored.items, cart.items);
+        assert_eq!(restored.contact, Some(contact()));
+    }
+
+    #[test]
+    fn test_recovery_rate() {
+        let metrics = AbandonmentMetrics {
+            abandoned: 8,
+            ordered: 2,
+            ..Default::default()
+        };
+        assert_eq!(metrics.recovery_rate(), 0.25);
+        assert_eq!(AbandonmentMetrics::default().recovery_rate(), 0.0);
+    }
+}
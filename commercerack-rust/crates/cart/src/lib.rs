@@ -1,37 +1,186 @@
+use commercerack_core::money::{self, Currency, Money};
+use commercerack_product::pricing::{PricingError, PricingService};
 use rust_decimal::Decimal;
+use sea_orm::ConnectionTrait;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 use uuid::Uuid;
 
+pub mod abandoned;
+pub mod lock;
+pub mod redis_store;
+pub mod storage;
+
+pub use abandoned::{AbandonedCartService, AbandonmentMetrics};
+pub use lock::{CartGuard, CartLocks};
+pub use redis_store::RedisCartStorage;
+pub use storage::{CartStorage, DbCartStorage, MemoryCartStorage};
+
+/// How the shopper wants a cart item made up
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ItemOptions {
+    /// Chosen variations by name, e.g. `size` → `M`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub selections: BTreeMap<String, String>,
+    /// Text to engrave or print on the item
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub engraving: String,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub gift_wrap: bool,
+}
+
+impl ItemOptions {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// ID of the cart line holding `sku` made up with `options`: the SKU
+/// itself without options, otherwise the SKU and a hash of the options
+pub fn line_id(sku: &str, options: &ItemOptions) -> String {
+    if options.is_empty() {
+        return sku.to_string();
+    }
+    // FNV-1a, so IDs stay the same across builds
+    let json = serde_json::to_string(options).unwrap_or_default();
+    let hash = json.bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    });
+    format!("{}~{:016x}", sku, hash)
+}
+
 /// Represents a single item in the shopping cart
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct CartItem {
+    /// Tells apart lines of one SKU with different options; see [`line_id`]
+    #[serde(default)]
+    pub line_id: String,
     pub sku: String,
     pub product_name: String,
     pub quantity: i32,
+    /// Catalog price, without the surcharge
     pub unit_price: Decimal,
+    #[serde(default, skip_serializing_if = "ItemOptions::is_empty")]
+    pub options: ItemOptions,
+    /// Added to the unit price for the options, e.g. gift wrap
+    #[serde(default, skip_serializing_if = "Decimal::is_zero")]
+    pub surcharge: Decimal,
+    /// How the item differed from the catalog when the cart was last
+    /// revalidated
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<ItemWarning>,
 }
 
 impl CartItem {
     pub fn new(sku: String, product_name: String, quantity: i32, unit_price: Decimal) -> Self {
         Self {
+            line_id: sku.clone(),
             sku,
             product_name,
             quantity,
             unit_price,
+            options: ItemOptions::default(),
+            surcharge: Decimal::ZERO,
+            warnings: Vec::new(),
         }
     }
 
+    /// Make the item up with `options`, which cost `surcharge` more a unit
+    pub fn with_options(mut self, options: ItemOptions, surcharge: Decimal) -> Self {
+        self.line_id = line_id(&self.sku, &options);
+        self.options = options;
+        self.surcharge = surcharge;
+        self
+    }
+
+    /// Whether `line` names this item: its line ID, or for carts saved
+    /// before options, its SKU
+    fn is_line(&self, line: &str) -> bool {
+        self.line_id == line || (self.line_id.is_empty() && self.sku == line)
+    }
+
+    /// Unit price including the surcharge
+    pub fn price(&self) -> Decimal {
+        self.unit_price + self.surcharge
+    }
+
     pub fn subtotal(&self) -> Decimal {
-        self.unit_price * Decimal::from(self.quantity)
+        self.price() * Decimal::from(self.quantity)
+    }
+}
+
+/// A way a cart item no longer matches the catalog
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ItemWarning {
+    /// The item was repriced from `previous` to `current`
+    PriceChanged { previous: Decimal, current: Decimal },
+    /// Fewer than the cart's quantity are in stock
+    OutOfStock { available: i32 },
+    /// The SKU was removed from the catalog, or its product is no longer
+    /// on the storefront
+    Unavailable,
+}
+
+impl fmt::Display for ItemWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ItemWarning::PriceChanged { previous, current } => {
+                write!(f, "price changed from {} to {}", previous, current)
+            }
+            ItemWarning::OutOfStock { available } => write!(f, "only {} in stock", available),
+            ItemWarning::Unavailable => f.write_str("no longer sold"),
+        }
     }
 }
 
+/// A coupon that has been checked against the cart and applied to it.
+/// The discount is recalculated whenever the cart's items change.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AppliedCoupon {
+    pub coupon_id: i32,
+    pub code: String,
+    pub discount: Decimal,
+    pub free_shipping: bool,
+}
+
+/// A gift card checked and applied to the cart. How much of it is used is
+/// only settled at checkout, once tax and shipping are known.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AppliedGiftCard {
+    pub gift_card_id: i32,
+    pub code: String,
+    /// Balance when the card was applied
+    pub balance: Decimal,
+}
+
+/// Who to remind about the cart if it is abandoned
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CartContact {
+    pub mid: i32,
+    /// 0 for guests
+    pub cid: i32,
+    pub email: String,
+}
+
 /// Shopping cart with in-memory storage
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Cart {
     pub cart_id: String,
     pub items: Vec<CartItem>,
+    #[serde(default)]
+    pub coupon: Option<AppliedCoupon>,
+    /// Redeemed in this order at checkout
+    #[serde(default)]
+    pub gift_cards: Vec<AppliedGiftCard>,
+    /// Needed for the cart to be tracked as abandoned
+    #[serde(default)]
+    pub contact: Option<CartContact>,
+    /// Currency the cart's amounts are in: its merchant's, once the cart is
+    /// priced from their catalog
+    #[serde(default = "money::default_currency")]
+    pub currency: Currency,
 }
 
 impl Cart {
@@ -40,6 +189,10 @@ impl Cart {
         Self {
             cart_id: Uuid::new_v4().to_string(),
             items: Vec::new(),
+            coupon: None,
+            gift_cards: Vec::new(),
+            contact: None,
+            currency: money::default_currency(),
         }
     }
 
@@ -48,21 +201,41 @@ impl Cart {
         Self {
             cart_id,
             items: Vec::new(),
+            coupon: None,
+            gift_cards: Vec::new(),
+            contact: None,
+            currency: money::default_currency(),
         }
     }
 
     /// Add an item to the cart. If SKU already exists, increase quantity
     pub fn add_item(&mut self, sku: String, product_name: String, quantity: i32, unit_price: Decimal) {
-        if let Some(existing) = self.items.iter_mut().find(|item| item.sku == sku) {
-            existing.quantity += quantity;
-        } else {
-            self.items.push(CartItem::new(sku, product_name, quantity, unit_price));
+        self.add_line(CartItem::new(sku, product_name, quantity, unit_price));
+    }
+
+    /// Add `item` to the cart, or its quantity to the line with the same
+    /// SKU and options. Returns the line ID.
+    pub fn add_line(&mut self, item: CartItem) -> String {
+        let line_id = item.line_id.clone();
+        match self.items.iter_mut().find(|existing| existing.is_line(&line_id)) {
+            Some(existing) => existing.quantity += item.quantity,
+            None => self.items.push(item),
         }
+        line_id
+    }
+
+    /// Index of the line named by `line`: a line ID, or else a SKU for the
+    /// first line holding it
+    fn position(&self, line: &str) -> Option<usize> {
+        self.items
+            .iter()
+            .position(|item| item.is_line(line))
+            .or_else(|| self.items.iter().position(|item| item.sku == line))
     }
 
-    /// Remove an item completely from the cart
-    pub fn remove_item(&mut self, sku: &str) -> bool {
-        if let Some(pos) = self.items.iter().position(|item| item.sku == sku) {
+    /// Remove a line completely from the cart
+    pub fn remove_item(&mut self, line: &str) -> bool {
+        if let Some(pos) = self.position(line) {
             self.items.remove(pos);
             true
         } else {
@@ -70,28 +243,120 @@ impl Cart {
         }
     }
 
-    /// Update quantity for a specific SKU. Returns false if SKU not found
-    pub fn update_quantity(&mut self, sku: &str, new_quantity: i32) -> bool {
+    /// Update quantity of a line. Returns false if the line is not found
+    pub fn update_quantity(&mut self, line: &str, new_quantity: i32) -> bool {
         if new_quantity <= 0 {
-            return self.remove_item(sku);
+            return self.remove_item(line);
         }
 
-        if let Some(item) = self.items.iter_mut().find(|item| item.sku == sku) {
+        if let Some(item) = self.position(line).map(|pos| &mut self.items[pos]) {
             item.quantity = new_quantity;
+            item.warnings.clear();
             true
         } else {
             false
         }
     }
 
-    /// Get item by SKU
-    pub fn get_item(&self, sku: &str) -> Option<&CartItem> {
-        self.items.iter().find(|item| item.sku == sku)
+    /// Reprice a line already in the cart. Returns false if the line is not
+    /// found
+    pub fn set_unit_price(&mut self, line: &str, unit_price: Decimal) -> bool {
+        if let Some(item) = self.position(line).map(|pos| &mut self.items[pos]) {
+            item.unit_price = unit_price;
+            item.warnings.clear();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Check every item against `mid`'s catalog, repricing it at the
+    /// current price for a shopper in `group`. Items whose price changed,
+    /// that are short of stock or that left the catalog are marked with
+    /// [`ItemWarning`]s; the rest have theirs cleared. Returns whether any
+    /// item was marked.
+    pub async fn revalidate<C: ConnectionTrait>(
+        &mut self,
+        db: &C,
+        mid: i32,
+        group: Option<&str>,
+    ) -> Result<bool, PricingError> {
+        for item in &mut self.items {
+            item.warnings.clear();
+            let resolved = match PricingService::resolve_price(db, mid, &item.sku, group, item.quantity).await {
+                Ok(resolved) => resolved,
+                Err(PricingError::UnknownSku(_)) => {
+                    item.warnings.push(ItemWarning::Unavailable);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+
+            if resolved.unit_price != item.unit_price {
+                item.warnings.push(ItemWarning::PriceChanged {
+                    previous: item.unit_price,
+                    current: resolved.unit_price,
+                });
+                item.unit_price = resolved.unit_price;
+            }
+            if resolved.sku.inv_available < item.quantity {
+                item.warnings.push(ItemWarning::OutOfStock {
+                    available: resolved.sku.inv_available.max(0),
+                });
+            }
+        }
+        Ok(self.has_warnings())
+    }
+
+    /// Whether the last revalidation marked any item
+    pub fn has_warnings(&self) -> bool {
+        self.items.iter().any(|item| !item.warnings.is_empty())
+    }
+
+    /// Get a line by line ID, or the first line holding a SKU
+    pub fn get_item(&self, line: &str) -> Option<&CartItem> {
+        self.position(line).map(|pos| &self.items[pos])
     }
 
     /// Calculate cart subtotal (sum of all item subtotals)
-    pub fn subtotal(&self) -> Decimal {
-        self.items.iter().map(|item| item.subtotal()).sum()
+    pub fn subtotal(&self) -> Money {
+        Money::new(self.items.iter().map(|item| item.subtotal()).sum(), self.currency)
+    }
+
+    /// Apply a coupon, replacing any coupon already applied
+    pub fn apply_coupon(&mut self, coupon: AppliedCoupon) {
+        self.coupon = Some(coupon);
+    }
+
+    /// Remove the applied coupon. Returns false if there was none
+    pub fn remove_coupon(&mut self) -> bool {
+        self.coupon.take().is_some()
+    }
+
+    /// Apply a gift card, replacing the same card if it was already applied
+    pub fn apply_gift_card(&mut self, gift_card: AppliedGiftCard) {
+        match self.gift_cards.iter_mut().find(|applied| applied.gift_card_id == gift_card.gift_card_id) {
+            Some(applied) => *applied = gift_card,
+            None => self.gift_cards.push(gift_card),
+        }
+    }
+
+    /// Remove a gift card by code. Returns false if it was not applied
+    pub fn remove_gift_card(&mut self, code: &str) -> bool {
+        let before = self.gift_cards.len();
+        self.gift_cards.retain(|applied| !applied.code.eq_ignore_ascii_case(code.trim()));
+        self.gift_cards.len() < before
+    }
+
+    /// Discount from the applied coupon, never more than the subtotal
+    pub fn discount(&self) -> Money {
+        let subtotal = self.subtotal().amount();
+        Money::new(self.coupon.as_ref().map_or(Decimal::ZERO, |coupon| coupon.discount.min(subtotal)), self.currency)
+    }
+
+    /// Subtotal less the coupon discount
+    pub fn total(&self) -> Money {
+        Money::new(self.subtotal().amount() - self.discount().amount(), self.currency)
     }
 
     /// Get total item count in cart
@@ -99,9 +364,39 @@ impl Cart {
         self.items.iter().map(|item| item.quantity).sum()
     }
 
-    /// Clear all items from cart
+    /// Fold `other`, a visitor's session cart, into this one. Lines both
+    /// carts hold have their quantities combined and take `other`'s price,
+    /// as the more recently priced; the rest of `other`'s lines are added.
+    /// `other`'s coupon is kept only if this cart has none, and its gift
+    /// cards are applied after this cart's.
+    pub fn merge(&mut self, other: Cart) {
+        for item in other.items {
+            let line = if item.line_id.is_empty() { item.sku.clone() } else { item.line_id.clone() };
+            match self.items.iter_mut().find(|existing| existing.is_line(&line)) {
+                Some(existing) => {
+                    existing.quantity += item.quantity;
+                    existing.unit_price = item.unit_price;
+                    existing.surcharge = item.surcharge;
+                    existing.warnings.clear();
+                }
+                None => self.items.push(item),
+            }
+        }
+        if self.coupon.is_none() {
+            self.coupon = other.coupon;
+        }
+        for gift_card in other.gift_cards {
+            if !self.gift_cards.iter().any(|applied| applied.gift_card_id == gift_card.gift_card_id) {
+                self.gift_cards.push(gift_card);
+            }
+        }
+    }
+
+    /// Clear all items, any coupon and any gift cards from cart
     pub fn clear(&mut self) {
         self.items.clear();
+        self.coupon = None;
+        self.gift_cards.clear();
     }
 
     /// Check if cart is empty
@@ -151,6 +446,19 @@ impl CartStore {
     pub fn delete_cart(&mut self, cart_id: &str) -> bool {
         self.carts.remove(cart_id).is_some()
     }
+
+    /// Merge the cart `anonymous_id` into `customer_cart_id` (see
+    /// [`Cart::merge`]) and delete it. Returns the merged cart, or `None`,
+    /// changing nothing, if either cart does not exist.
+    pub fn merge(&mut self, anonymous_id: &str, customer_cart_id: &str) -> Option<&Cart> {
+        if anonymous_id == customer_cart_id || !self.carts.contains_key(customer_cart_id) {
+            return None;
+        }
+        let anonymous = self.carts.remove(anonymous_id)?;
+        let cart = self.carts.get_mut(customer_cart_id)?;
+        cart.merge(anonymous);
+        Some(cart)
+    }
 }
 
 impl Default for CartStore {
@@ -178,7 +486,7 @@ mod tests {
         );
         assert_eq!(cart.items.len(), 1);
         assert_eq!(cart.item_count(), 2);
-        assert_eq!(cart.subtotal(), Decimal::new(3998, 2)); // $39.98
+        assert_eq!(cart.subtotal(), Money::new(Decimal::new(3998, 2), Currency::USD)); // $39.98
 
         // Add same SKU again (should merge)
         cart.add_item(
@@ -189,7 +497,7 @@ mod tests {
         );
         assert_eq!(cart.items.len(), 1); // Still 1 unique item
         assert_eq!(cart.item_count(), 5); // 2 + 3 = 5 total
-        assert_eq!(cart.subtotal(), Decimal::new(9995, 2)); // $99.95
+        assert_eq!(cart.subtotal().amount(), Decimal::new(9995, 2)); // $99.95
 
         // Add different SKU
         cart.add_item(
@@ -200,7 +508,7 @@ mod tests {
         );
         assert_eq!(cart.items.len(), 2);
         assert_eq!(cart.item_count(), 6);
-        assert_eq!(cart.subtotal(), Decimal::new(12994, 2)); // $129.94
+        assert_eq!(cart.subtotal().amount(), Decimal::new(12994, 2)); // $129.94
     }
 
     #[test]
@@ -222,7 +530,101 @@ mod tests {
         // Remove item
         assert!(cart.remove_item("SKU002"));
         assert!(cart.is_empty());
-        assert_eq!(cart.subtotal(), Decimal::ZERO);
+        assert_eq!(cart.subtotal().amount(), Decimal::ZERO);
+    }
+
+    #[test]
+    fn test_cart_item_options() {
+        let mut cart = Cart::new();
+        let engraved = ItemOptions { engraving: "For Sam".to_string(), ..Default::default() };
+        let wrapped = ItemOptions { gift_wrap: true, ..Default::default() };
+
+        cart.add_item("SKU001".to_string(), "Widget".to_string(), 1, Decimal::new(1000, 2));
+        let engraved_line = cart.add_line(
+            CartItem::new("SKU001".to_string(), "Widget".to_string(), 1, Decimal::new(1000, 2))
+                .with_options(engraved.clone(), Decimal::ZERO),
+        );
+        let wrapped_line = cart.add_line(
+            CartItem::new("SKU001".to_string(), "Widget".to_string(), 2, Decimal::new(1000, 2))
+                .with_options(wrapped, Decimal::new(250, 2)),
+        );
+        assert_eq!(cart.items.len(), 3);
+        assert_ne!(engraved_line, wrapped_line);
+        assert_eq!(engraved_line, line_id("SKU001", &engraved));
+        // 10.00 + 10.00 + 2 × (10.00 + 2.50)
+        assert_eq!(cart.subtotal().amount(), Decimal::new(4500, 2));
+
+        // The same options again land on the same line
+        cart.add_line(
+            CartItem::new("SKU001".to_string(), "Widget".to_string(), 1, Decimal::new(1000, 2))
+                .with_options(engraved, Decimal::ZERO),
+        );
+        assert_eq!(cart.items.len(), 3);
+        assert_eq!(cart.get_item(&engraved_line).unwrap().quantity, 2);
+
+        // A bare SKU names the line without options
+        assert!(cart.remove_item("SKU001"));
+        assert!(cart.get_item(&wrapped_line).is_some());
+        assert!(cart.remove_item(&wrapped_line));
+        assert_eq!(cart.items.len(), 1);
+    }
+
+    #[test]
+    fn test_cart_store_merge() {
+        let mut store = CartStore::new();
+        let anonymous_id = store.create_cart();
+        let customer_cart_id = store.create_cart();
+
+        let customer_cart = store.get_cart_mut(&customer_cart_id).unwrap();
+        customer_cart.add_item("SKU001".to_string(), "Widget".to_string(), 2, Decimal::new(1000, 2));
+        customer_cart.add_item("SKU002".to_string(), "Gadget".to_string(), 1, Decimal::new(2000, 2));
+        let anonymous = store.get_cart_mut(&anonymous_id).unwrap();
+        anonymous.add_item("SKU001".to_string(), "Widget".to_string(), 1, Decimal::new(900, 2));
+        anonymous.add_line(
+            CartItem::new("SKU001".to_string(), "Widget".to_string(), 1, Decimal::new(900, 2))
+                .with_options(ItemOptions { gift_wrap: true, ..Default::default() }, Decimal::new(300, 2)),
+        );
+        anonymous.apply_coupon(AppliedCoupon {
+            coupon_id: 1,
+            code: "SAVE5".to_string(),
+            discount: Decimal::new(500, 2),
+            free_shipping: false,
+        });
+
+        assert!(store.merge("missing", &customer_cart_id).is_none());
+        assert!(store.merge(&anonymous_id, "missing").is_none());
+        assert!(store.merge(&anonymous_id, &anonymous_id).is_none());
+
+        let merged = store.merge(&anonymous_id, &customer_cart_id).unwrap();
+        assert_eq!(merged.items.len(), 3);
+        // Quantities combine at the session cart's price
+        assert_eq!(merged.get_item("SKU001").unwrap().quantity, 3);
+        assert_eq!(merged.get_item("SKU001").unwrap().unit_price, Decimal::new(900, 2));
+        assert_eq!(merged.get_item("SKU002").unwrap().quantity, 1);
+        assert_eq!(merged.coupon.as_ref().unwrap().code, "SAVE5");
+        assert!(store.get_cart(&anonymous_id).is_none());
+    }
+
+    #[test]
+    fn test_cart_coupon() {
+        let mut cart = Cart::new();
+        cart.add_item("SKU001".to_string(), "Widget".to_string(), 1, Decimal::new(1000, 2));
+
+        cart.apply_coupon(AppliedCoupon {
+            coupon_id: 1,
+            code: "SAVE5".to_string(),
+            discount: Decimal::new(500, 2),
+            free_shipping: false,
+        });
+        assert_eq!(cart.total().amount(), Decimal::new(500, 2));
+
+        // The discount never exceeds what is in the cart
+        cart.coupon.as_mut().unwrap().discount = Decimal::new(2500, 2);
+        assert_eq!(cart.total().amount(), Decimal::ZERO);
+
+        assert!(cart.remove_coupon());
+        assert!(!cart.remove_coupon());
+        assert_eq!(cart.total().amount(), Decimal::new(1000, 2));
     }
 
     #[test]
@@ -247,4 +649,56 @@ mod tests {
         assert!(store.delete_cart(&cart_id));
         assert!(store.get_cart(&cart_id).is_none());
     }
+
+    #[tokio::test]
+    async fn test_revalidate_marks_changed_items() {
+        use sea_orm::{DatabaseBackend, MockDatabase};
+
+        let sku = |code: &str, price: i64, inv_available: i32| ::entity::prelude::Sku {
+            id: 1,
+            pid: 1,
+            mid: 1,
+            sku: code.to_string(),
+            title: code.to_string(),
+            price: Decimal::new(price, 2),
+            cost: Decimal::ZERO,
+            upc: String::new(),
+            inv_available,
+            qty_onshelf: inv_available,
+            weight: Decimal::ZERO,
+            reorder_point: None,
+            reorder_quantity: 0,
+            low_stock_gmt: None,
+        };
+        let no_tiers = Vec::<::entity::prelude::PriceTier>::new;
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![sku("SKU001", 1000, 50)]])
+            .append_query_results([no_tiers()])
+            .append_query_results([vec![sku("SKU002", 2500, 1)]])
+            .append_query_results([no_tiers()])
+            .append_query_results([Vec::<::entity::prelude::Sku>::new()])
+            .into_connection();
+
+        let mut cart = Cart::new();
+        cart.add_item("SKU001".to_string(), "Widget".to_string(), 2, Decimal::new(1000, 2));
+        cart.add_item("SKU002".to_string(), "Gadget".to_string(), 3, Decimal::new(2000, 2));
+        cart.add_item("SKU003".to_string(), "Gizmo".to_string(), 1, Decimal::new(500, 2));
+
+        assert!(cart.revalidate(&db, 1, None).await.unwrap());
+        assert!(cart.items[0].warnings.is_empty());
+        assert_eq!(
+            cart.items[1].warnings,
+            vec![
+                ItemWarning::PriceChanged { previous: Decimal::new(2000, 2), current: Decimal::new(2500, 2) },
+                ItemWarning::OutOfStock { available: 1 },
+            ]
+        );
+        assert_eq!(cart.items[1].unit_price, Decimal::new(2500, 2));
+        assert_eq!(cart.items[2].warnings, vec![ItemWarning::Unavailable]);
+        assert_eq!(cart.items[1].warnings[0].to_string(), "price changed from 20.00 to 25.00");
+
+        // Changing the item clears its warnings
+        cart.update_quantity("SKU002", 1);
+        assert!(cart.items[1].warnings.is_empty());
+    }
 }
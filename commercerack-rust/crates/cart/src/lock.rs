@@ -0,0 +1,80 @@
+//! Per-cart locks
+//!
+//! Cart changes are read-modify-write: load the cart, change it, save it.
+//! Two concurrent changes to the same cart would otherwise lose one of
+//! them. Holding the cart's lock for the round trip serializes changes to
+//! that cart while changes to other carts carry on in parallel.
+//!
+//! Locks live in the process. With several API instances sharing a store,
+//! they only serialize requests that reach the same instance.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Weak};
+use tokio::sync::{Mutex, OwnedMutexGuard};
+
+/// Exclusive hold on one cart; released when dropped
+pub struct CartGuard {
+    _guard: OwnedMutexGuard<()>,
+}
+
+/// Lock per cart ID, created on first use
+#[derive(Default)]
+pub struct CartLocks {
+    // Weak so a cart's lock goes away once nobody holds or waits for it
+    locks: Mutex<HashMap<String, Weak<Mutex<()>>>>,
+}
+
+impl CartLocks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wait for exclusive access to `cart_id`
+    pub async fn lock(&self, cart_id: &str) -> CartGuard {
+        let lock = {
+            let mut locks = self.locks.lock().await;
+            match locks.get(cart_id).and_then(Weak::upgrade) {
+                Some(lock) => lock,
+                None => {
+                    locks.retain(|_, lock| lock.strong_count() > 0);
+                    let lock = Arc::new(Mutex::new(()));
+                    locks.insert(cart_id.to_string(), Arc::downgrade(&lock));
+                    lock
+                }
+            }
+        };
+        CartGuard {
+            _guard: lock.lock_owned().await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_same_cart_waits_other_carts_do_not() {
+        let locks = CartLocks::new();
+        let held = locks.lock("A").await;
+
+        let other = tokio::time::timeout(Duration::from_millis(50), locks.lock("B")).await;
+        assert!(other.is_ok(), "a different cart must not wait");
+
+        let same = tokio::time::timeout(Duration::from_millis(50), locks.lock("A")).await;
+        assert!(same.is_err(), "the same cart must wait for the holder");
+
+        drop(held);
+        let same = tokio::time::timeout(Duration::from_millis(50), locks.lock("A")).await;
+        assert!(same.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_released_locks_are_dropped() {
+        let locks = CartLocks::new();
+        drop(locks.lock("A").await);
+        drop(locks.lock("B").await);
+        assert_eq!(locks.locks.lock().await.len(), 1);
+    }
+}
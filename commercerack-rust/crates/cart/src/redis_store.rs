@@ -0,0 +1,319 @@
+//! Redis-backed cart storage with TTL expiration
+//!
+//! Each cart is stored as JSON under `cart:{id}` and its logical expiry is
+//! tracked in the `cart:expiry` sorted set. Every read or write renews the
+//! TTL. Keys are given a grace period past their logical expiry so the
+//! background sweep can still read an expired cart and archive it into the
+//! `abandoned_carts` table before Redis drops it. The sweep takes each cart
+//! out with [`CLAIM_EXPIRED`], which leaves alone a cart renewed since the
+//! sweep found it.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::Utc;
+use commercerack_core::Timestamp;
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+use sea_orm::*;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::OnceCell;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::abandoned::AbandonedCartService;
+use crate::storage::CartStorage;
+use crate::{Cart, CartGuard, CartLocks};
+
+/// Default cart lifetime since last access
+pub const DEFAULT_CART_TTL: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Extra time a key survives past its logical expiry, so the sweep can archive it
+const SWEEP_GRACE: Duration = Duration::from_secs(24 * 60 * 60);
+
+const EXPIRY_KEY: &str = "cart:expiry";
+
+/// Remove the cart `ARGV[1]` if its expiry is still no later than
+/// `ARGV[2]`, returning what it held. `KEYS` are its key and the expiry set.
+const CLAIM_EXPIRED: &str = r#"
+local expires = redis.call('ZSCORE', KEYS[2], ARGV[1])
+if not expires or tonumber(expires) > tonumber(ARGV[2]) then
+    return false
+end
+local payload = redis.call('GET', KEYS[1])
+redis.call('DEL', KEYS[1])
+redis.call('ZREM', KEYS[2], ARGV[1])
+return payload
+"#;
+
+fn cart_key(cart_id: &str) -> String {
+    format!("cart:{}", cart_id)
+}
+
+/// Redis cart storage
+pub struct RedisCartStorage {
+    client: redis::Client,
+    conn: OnceCell<ConnectionManager>,
+    ttl: Duration,
+    locks: CartLocks,
+}
+
+impl RedisCartStorage {
+    /// Create a store with the default 30 day TTL. The connection is
+    /// established lazily on first use.
+    pub fn new(client: redis::Client) -> Self {
+        Self::with_ttl(client, DEFAULT_CART_TTL)
+    }
+
+    /// Create a store from a `redis://` URL
+    pub fn open(url: &str) -> Result<Self> {
+        Ok(Self::new(redis::Client::open(url)?))
+    }
+
+    pub fn with_ttl(client: redis::Client, ttl: Duration) -> Self {
+        Self {
+            client,
+            conn: OnceCell::new(),
+            ttl,
+            locks: CartLocks::new(),
+        }
+    }
+
+    async fn connection(&self) -> Result<ConnectionManager> {
+        let conn = self
+            .conn
+            .get_or_try_init(|| ConnectionManager::new(self.client.clone()))
+            .await?;
+        Ok(conn.clone())
+    }
+
+    /// When a cart touched now expires
+    fn expires_at(&self) -> i64 {
+        Utc::now().timestamp() + self.ttl.as_secs() as i64
+    }
+
+    /// Write the cart and push its expiry forward
+    async fn store(&self, conn: &mut ConnectionManager, cart: &Cart) -> Result<()> {
+        let payload = serde_json::to_string(cart)?;
+
+        redis::pipe()
+            .atomic()
+            .set_ex(cart_key(&cart.cart_id), payload, (self.ttl + SWEEP_GRACE).as_secs())
+            .zadd(EXPIRY_KEY, &cart.cart_id, self.expires_at())
+            .query_async::<_, ()>(conn)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Take a cart out if it is still expired at `now`, returning its
+    /// payload. `None` if it was renewed or deleted meanwhile.
+    async fn claim_expired(
+        &self,
+        conn: &mut ConnectionManager,
+        cart_id: &str,
+        now: Timestamp,
+    ) -> Result<Option<String>> {
+        let payload = redis::Script::new(CLAIM_EXPIRED)
+            .key(cart_key(cart_id))
+            .key(EXPIRY_KEY)
+            .arg(cart_id)
+            .arg(now.unix())
+            .invoke_async(conn)
+            .await?;
+        Ok(payload)
+    }
+
+    /// Put back a claimed cart, already expired, so the next sweep tries it
+    /// again. A cart saved under the same ID meanwhile is kept instead.
+    async fn unclaim(&self, conn: &mut ConnectionManager, cart_id: &str, payload: &str, now: Timestamp) -> Result<()> {
+        redis::pipe()
+            .atomic()
+            .cmd("SET")
+            .arg(cart_key(cart_id))
+            .arg(payload)
+            .arg("EX")
+            .arg(SWEEP_GRACE.as_secs())
+            .arg("NX")
+            .ignore()
+            .cmd("ZADD")
+            .arg(EXPIRY_KEY)
+            .arg("NX")
+            .arg(now.unix())
+            .arg(cart_id)
+            .ignore()
+            .query_async::<_, ()>(conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Archive carts whose TTL has lapsed into `abandoned_carts` and remove
+    /// them from Redis. Empty carts are dropped without archiving. See
+    /// [`crate::abandoned`] for what happens to carts with a contact.
+    pub async fn sweep_expired(&self, db: &DatabaseConnection) -> Result<usize> {
+        let mut conn = self.connection().await?;
+        let now = Timestamp::now();
+
+        let expired: Vec<String> = conn.zrangebyscore(EXPIRY_KEY, "-inf", now.unix()).await?;
+        let mut archived = 0;
+
+        for cart_id in expired {
+            let Some(payload) = self.claim_expired(&mut conn, &cart_id, now).await? else {
+                continue;
+            };
+
+            if let Some(cart) = serde_json::from_str::<Cart>(&payload).ok().filter(|cart| !cart.is_empty()) {
+                if let Err(e) = AbandonedCartService::archive(db, &cart, now).await {
+                    self.unclaim(&mut conn, &cart_id, &payload, now).await?;
+                    return Err(e);
+                }
+                archived += 1;
+            }
+        }
+
+        Ok(archived)
+    }
+
+    /// Run `sweep_expired` on a fixed interval until the task is aborted
+    pub fn spawn_sweeper(
+        self: Arc<Self>,
+        db: Arc<DatabaseConnection>,
+        interval: Duration,
+    ) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                match self.sweep_expired(&db).await {
+                    Ok(0) => {}
+                    Ok(n) => info!("🛒 Archived {} abandoned carts", n),
+                    Err(e) => warn!("Abandoned cart sweep failed: {}", e),
+                }
+            }
+        })
+    }
+}
+
+#[async_trait]
+impl CartStorage for RedisCartStorage {
+    async fn create_cart(&self) -> Result<Cart> {
+        let cart = Cart::new();
+        let mut conn = self.connection().await?;
+        self.store(&mut conn, &cart).await?;
+        Ok(cart)
+    }
+
+    async fn get_cart(&self, cart_id: &str) -> Result<Option<Cart>> {
+        let mut conn = self.connection().await?;
+        let payload: Option<String> = conn.get(cart_key(cart_id)).await?;
+
+        let Some(payload) = payload else {
+            return Ok(None);
+        };
+        let cart: Cart = serde_json::from_str(&payload)?;
+
+        // Renew the TTL on access without writing the cart back, which
+        // could undo a save made since it was read
+        redis::pipe()
+            .atomic()
+            .expire(cart_key(cart_id), (self.ttl + SWEEP_GRACE).as_secs() as i64)
+            .zadd(EXPIRY_KEY, cart_id, self.expires_at())
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+        Ok(Some(cart))
+    }
+
+    async fn save_cart(&self, cart: &Cart) -> Result<()> {
+        let mut conn = self.connection().await?;
+        self.store(&mut conn, cart).await
+    }
+
+    async fn delete_cart(&self, cart_id: &str) -> Result<bool> {
+        let mut conn = self.connection().await?;
+        let (deleted, _): (i64, i64) = redis::pipe()
+            .atomic()
+            .del(cart_key(cart_id))
+            .zrem(EXPIRY_KEY, cart_id)
+            .query_async(&mut conn)
+            .await?;
+        Ok(deleted > 0)
+    }
+
+    async fn lock_cart(&self, cart_id: &str) -> CartGuard {
+        self.locks.lock(cart_id).await
+    }
+
+    async fn ping(&self) -> Result<()> {
+        let mut conn = self.connection().await?;
+        redis::cmd("PING").query_async::<_, ()>(&mut conn).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+
+    /// A store on the Redis in `TEST_REDIS_URL`, whose carts expire `ttl`
+    /// after they are touched. The tests below are skipped without one.
+    fn storage(ttl: Duration) -> Option<RedisCartStorage> {
+        let url = std::env::var("TEST_REDIS_URL").ok()?;
+        Some(RedisCartStorage::with_ttl(redis::Client::open(url).unwrap(), ttl))
+    }
+
+    #[tokio::test]
+    async fn test_reads_renew_the_ttl_without_rewriting_the_cart() {
+        let (Some(short), Some(long)) = (storage(Duration::from_secs(60)), storage(DEFAULT_CART_TTL)) else {
+            return;
+        };
+        let mut conn = short.connection().await.unwrap();
+
+        let mut cart = short.create_cart().await.unwrap();
+        cart.add_item("SKU001".to_string(), "Widget".to_string(), 2, Decimal::new(1999, 2));
+        short.save_cart(&cart).await.unwrap();
+        let payload: String = conn.get(cart_key(&cart.cart_id)).await.unwrap();
+
+        let loaded = long.get_cart(&cart.cart_id).await.unwrap().unwrap();
+        assert_eq!(loaded.items, cart.items);
+        let stored: String = conn.get(cart_key(&cart.cart_id)).await.unwrap();
+        assert_eq!(stored, payload);
+
+        let expires: i64 = conn.zscore(EXPIRY_KEY, &cart.cart_id).await.unwrap();
+        assert!(expires > Utc::now().timestamp() + 60, "{}", expires);
+        let ttl: i64 = conn.ttl(cart_key(&cart.cart_id)).await.unwrap();
+        assert!(ttl > (DEFAULT_CART_TTL.as_secs() as i64), "{}", ttl);
+
+        assert!(long.delete_cart(&cart.cart_id).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_sweep_leaves_renewed_and_unarchived_carts() {
+        let Some(storage) = storage(Duration::ZERO) else {
+            return;
+        };
+        let mut conn = storage.connection().await.unwrap();
+
+        let empty = storage.create_cart().await.unwrap();
+        let mut full = storage.create_cart().await.unwrap();
+        full.add_item("SKU001".to_string(), "Widget".to_string(), 1, Decimal::new(1000, 2));
+        storage.save_cart(&full).await.unwrap();
+
+        // Renewed after a sweep listed it as expired
+        let renewed = storage.create_cart().await.unwrap();
+        let listed_at = Timestamp::now();
+        let _: () = conn.zadd(EXPIRY_KEY, &renewed.cart_id, Utc::now().timestamp() + 3600).await.unwrap();
+        assert_eq!(storage.claim_expired(&mut conn, &renewed.cart_id, listed_at).await.unwrap(), None);
+        assert!(conn.exists::<_, bool>(cart_key(&renewed.cart_id)).await.unwrap());
+
+        // A database that takes no archive keeps the cart for the next sweep
+        let db = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+        assert!(storage.sweep_expired(&db).await.is_err());
+        assert!(storage.get_cart(&full.cart_id).await.unwrap().is_some());
+
+        assert!(storage.delete_cart(&full.cart_id).await.unwrap());
+        assert_eq!(storage.sweep_expired(&db).await.unwrap(), 0);
+        assert!(storage.get_cart(&empty.cart_id).await.unwrap().is_none());
+        assert!(storage.delete_cart(&renewed.cart_id).await.unwrap());
+    }
+}
@@ -0,0 +1,334 @@
+//! Cart persistence backends
+//!
+//! `CartStorage` abstracts where carts live. `DbCartStorage` persists carts
+//! to the `carts`/`cart_items` tables so they survive restarts and can be
+//! shared across instances; `MemoryCartStorage` keeps them in-process and
+//! is intended for tests and local development.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use commercerack_core::money;
+use commercerack_core::Timestamp;
+use sea_orm::*;
+use ::entity::prelude::*;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+use crate::{Cart, CartContact, CartGuard, CartItem, CartLocks, CartStore};
+
+/// A stored cart line as a cart item. Options that no longer parse are
+/// dropped rather than failing the cart.
+pub(crate) fn from_record(record: CartItemRecord) -> CartItem {
+    let options = serde_json::from_str(&record.options).unwrap_or_default();
+    CartItem::new(record.sku, record.product_name, record.quantity, record.unit_price)
+        .with_options(options, record.surcharge)
+}
+
+/// Storage backend for shopping carts
+#[async_trait]
+pub trait CartStorage: Send + Sync {
+    /// Create and persist a new empty cart
+    async fn create_cart(&self) -> Result<Cart>;
+
+    /// Load a cart by ID
+    async fn get_cart(&self, cart_id: &str) -> Result<Option<Cart>>;
+
+    /// Persist the full state of a cart, replacing any stored items
+    async fn save_cart(&self, cart: &Cart) -> Result<()>;
+
+    /// Delete a cart. Returns false if it did not exist
+    async fn delete_cart(&self, cart_id: &str) -> Result<bool>;
+
+    /// Hold a cart exclusively while loading, changing and saving it, so
+    /// concurrent changes don't overwrite each other
+    async fn lock_cart(&self, cart_id: &str) -> CartGuard;
+
+    /// Merge the cart `anonymous_id` into `customer_cart_id`, as when a
+    /// visitor signs in, and delete it. See [`Cart::merge`]. Returns the
+    /// merged cart, or `None` if either cart does not exist. Callers hold
+    /// both carts' locks.
+    async fn merge(&self, anonymous_id: &str, customer_cart_id: &str) -> Result<Option<Cart>> {
+        if anonymous_id == customer_cart_id {
+            return Ok(None);
+        }
+        let (Some(anonymous), Some(mut cart)) =
+            (self.get_cart(anonymous_id).await?, self.get_cart(customer_cart_id).await?)
+        else {
+            return Ok(None);
+        };
+        cart.merge(anonymous);
+        self.save_cart(&cart).await?;
+        self.delete_cart(anonymous_id).await?;
+        Ok(Some(cart))
+    }
+
+    /// Check that the backend is reachable
+    async fn ping(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// In-memory cart storage (not shared across instances)
+#[derive(Default)]
+pub struct MemoryCartStorage {
+    store: RwLock<CartStore>,
+    locks: CartLocks,
+}
+
+impl MemoryCartStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl CartStorage for MemoryCartStorage {
+    async fn create_cart(&self) -> Result<Cart> {
+        let mut store = self.store.write().await;
+        let cart_id = store.create_cart();
+        store
+            .get_cart(&cart_id)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Cart vanished after creation"))
+    }
+
+    async fn get_cart(&self, cart_id: &str) -> Result<Option<Cart>> {
+        Ok(self.store.read().await.get_cart(cart_id).cloned())
+    }
+
+    async fn save_cart(&self, cart: &Cart) -> Result<()> {
+        self.store.write().await.save_cart(cart.clone());
+        Ok(())
+    }
+
+    async fn delete_cart(&self, cart_id: &str) -> Result<bool> {
+        Ok(self.store.write().await.delete_cart(cart_id))
+    }
+
+    async fn merge(&self, anonymous_id: &str, customer_cart_id: &str) -> Result<Option<Cart>> {
+        Ok(self.store.write().await.merge(anonymous_id, customer_cart_id).cloned())
+    }
+
+    async fn lock_cart(&self, cart_id: &str) -> CartGuard {
+        self.locks.lock(cart_id).await
+    }
+}
+
+/// Database-backed cart storage using SeaORM
+pub struct DbCartStorage {
+    db: Arc<DatabaseConnection>,
+    locks: CartLocks,
+}
+
+impl DbCartStorage {
+    pub fn new(db: Arc<DatabaseConnection>) -> Self {
+        Self {
+            db,
+            locks: CartLocks::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl CartStorage for DbCartStorage {
+    async fn create_cart(&self) -> Result<Cart> {
+        let cart = Cart::new();
+        let now = Timestamp::now();
+
+        ::entity::carts::ActiveModel {
+            cart_id: Set(cart.cart_id.clone()),
+            coupon: Set(None),
+            gift_cards: Set(None),
+            mid: Set(0),
+            cid: Set(0),
+            email: Set(String::new()),
+            currency: Set(cart.currency.to_string()),
+            abandoned_gmt: Set(None),
+            created_gmt: Set(now),
+            modified_gmt: Set(now),
+        }
+        .insert(self.db.as_ref())
+        .await?;
+
+        Ok(cart)
+    }
+
+    async fn get_cart(&self, cart_id: &str) -> Result<Option<Cart>> {
+        let Some(record) = Carts::find_by_id(cart_id.to_string()).one(self.db.as_ref()).await? else {
+            return Ok(None);
+        };
+
+        let items = CartItems::find()
+            .filter(::entity::cart_items::Column::CartId.eq(cart_id))
+            .order_by_asc(::entity::cart_items::Column::Id)
+            .all(self.db.as_ref())
+            .await?
+            .into_iter()
+            .map(from_record)
+            .collect();
+
+        // A coupon that no longer parses is dropped rather than failing the cart
+        let coupon = record.coupon.as_deref().and_then(|c| serde_json::from_str(c).ok());
+        let gift_cards = record
+            .gift_cards
+            .as_deref()
+            .and_then(|g| serde_json::from_str(g).ok())
+            .unwrap_or_default();
+        let contact = (record.mid != 0).then_some(CartContact {
+            mid: record.mid,
+            cid: record.cid,
+            email: record.email,
+        });
+        let currency = record.currency.parse().unwrap_or_else(|_| money::default_currency());
+
+        Ok(Some(Cart {
+            cart_id: record.cart_id,
+            items,
+            coupon,
+            gift_cards,
+            contact,
+            currency,
+        }))
+    }
+
+    async fn save_cart(&self, cart: &Cart) -> Result<()> {
+        let now = Timestamp::now();
+        let coupon = cart.coupon.as_ref().map(serde_json::to_string).transpose()?;
+        let gift_cards = if cart.gift_cards.is_empty() {
+            None
+        } else {
+            Some(serde_json::to_string(&cart.gift_cards)?)
+        };
+        let (mid, cid, email) = match &cart.contact {
+            Some(contact) => (contact.mid, contact.cid, contact.email.clone()),
+            None => (0, 0, String::new()),
+        };
+        let txn = self.db.begin().await?;
+
+        match Carts::find_by_id(cart.cart_id.clone()).one(&txn).await? {
+            Some(record) => {
+                let mut active: ::entity::carts::ActiveModel = record.into();
+                active.coupon = Set(coupon);
+                active.gift_cards = Set(gift_cards);
+                active.mid = Set(mid);
+                active.cid = Set(cid);
+                active.email = Set(email);
+                active.currency = Set(cart.currency.to_string());
+                // Any change makes an abandoned cart active again
+                active.abandoned_gmt = Set(None);
+                active.modified_gmt = Set(now);
+                active.update(&txn).await?;
+            }
+            None => {
+                ::entity::carts::ActiveModel {
+                    cart_id: Set(cart.cart_id.clone()),
+                    coupon: Set(coupon),
+                    gift_cards: Set(gift_cards),
+                    mid: Set(mid),
+                    cid: Set(cid),
+                    email: Set(email),
+                    currency: Set(cart.currency.to_string()),
+                    abandoned_gmt: Set(None),
+                    created_gmt: Set(now),
+                    modified_gmt: Set(now),
+                }
+                .insert(&txn)
+                .await?;
+            }
+        }
+
+        CartItems::delete_many()
+            .filter(::entity::cart_items::Column::CartId.eq(cart.cart_id.as_str()))
+            .exec(&txn)
+            .await?;
+
+        if !cart.items.is_empty() {
+            let mut rows = Vec::with_capacity(cart.items.len());
+            for item in &cart.items {
+                let options = if item.options.is_empty() { String::new() } else { serde_json::to_string(&item.options)? };
+                rows.push(::entity::cart_items::ActiveModel {
+                    cart_id: Set(cart.cart_id.clone()),
+                    sku: Set(item.sku.clone()),
+                    product_name: Set(item.product_name.clone()),
+                    quantity: Set(item.quantity),
+                    unit_price: Set(item.unit_price),
+                    options: Set(options),
+                    surcharge: Set(item.surcharge),
+                    ..Default::default()
+                });
+            }
+            CartItems::insert_many(rows).exec(&txn).await?;
+        }
+
+        txn.commit().await?;
+        Ok(())
+    }
+
+    async fn delete_cart(&self, cart_id: &str) -> Result<bool> {
+        let txn = self.db.begin().await?;
+
+        CartItems::delete_many()
+            .filter(::entity::cart_items::Column::CartId.eq(cart_id))
+            .exec(&txn)
+            .await?;
+        let result = Carts::delete_by_id(cart_id.to_string()).exec(&txn).await?;
+
+        txn.commit().await?;
+        Ok(result.rows_affected > 0)
+    }
+
+    async fn lock_cart(&self, cart_id: &str) -> CartGuard {
+        self.locks.lock(cart_id).await
+    }
+
+    async fn ping(&self) -> Result<()> {
+        Ok(self.db.ping().await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+
+    #[tokio::test]
+    async fn test_memory_storage_roundtrip() {
+        let storage = MemoryCartStorage::new();
+
+        let mut cart = storage.create_cart().await.unwrap();
+        cart.add_item("SKU001".to_string(), "Widget".to_string(), 2, Decimal::new(1999, 2));
+        storage.save_cart(&cart).await.unwrap();
+
+        let loaded = storage.get_cart(&cart.cart_id).await.unwrap().unwrap();
+        assert_eq!(loaded.items, cart.items);
+
+        assert!(storage.delete_cart(&cart.cart_id).await.unwrap());
+        assert!(storage.get_cart(&cart.cart_id).await.unwrap().is_none());
+        assert!(!storage.delete_cart(&cart.cart_id).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_locked_updates_are_not_lost() {
+        let storage = Arc::new(MemoryCartStorage::new());
+        let cart_id = storage.create_cart().await.unwrap().cart_id;
+
+        let tasks: Vec<_> = (0..8)
+            .map(|i| {
+                let storage = storage.clone();
+                let cart_id = cart_id.clone();
+                tokio::spawn(async move {
+                    let _guard = storage.lock_cart(&cart_id).await;
+                    let mut cart = storage.get_cart(&cart_id).await.unwrap().unwrap();
+                    tokio::task::yield_now().await;
+                    cart.add_item(format!("SKU{:03}", i), "Widget".to_string(), 1, Decimal::ONE);
+                    storage.save_cart(&cart).await.unwrap();
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        assert_eq!(storage.get_cart(&cart_id).await.unwrap().unwrap().items.len(), 8);
+    }
+}
@@ -0,0 +1,343 @@
+//! Amazon connector
+//!
+//! Shaped after the Selling Partner API: orders are pulled by creation
+//! time or pushed to us as [`OrderPayload`]s, and stock and tracking go
+//! back as feeds of at most [`FEED_LIMIT`] SKUs. This is a stub that
+//! never calls Amazon. Orders handed to [`AmazonConnector::with_orders`]
+//! are what it "pulls", submitted feeds are kept for
+//! [`AmazonConnector::feeds`] and the stock they set is what the account
+//! lists, which is enough to run channel syncs end to end in development
+//! and tests.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use commercerack_core::Timestamp;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::{ChannelError, ChannelOrder, ChannelOrderLine, InventoryLevel, Marketplace, MarketplaceConnector, TrackingUpdate};
+
+/// Seller account settings, stored as the channel's `settings`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct AmazonSettings {
+    /// Merchant token of the seller account
+    pub seller_id: String,
+    /// Marketplace the account sells on, e.g. `ATVPDKIKX0DER` for amazon.com
+    pub marketplace_id: String,
+}
+
+/// Most SKUs one inventory feed takes
+pub const FEED_LIMIT: usize = 1_000;
+
+/// An SP-API order with its order items, as ingested
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct OrderPayload {
+    pub amazon_order_id: String,
+    pub purchase_date: DateTime<Utc>,
+    /// `Pending` until Amazon has collected payment, then `Unshipped`,
+    /// `PartiallyShipped` or `Shipped`
+    pub order_status: String,
+    #[serde(default)]
+    pub buyer_info: BuyerInfo,
+    pub order_items: Vec<OrderItemPayload>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BuyerInfo {
+    /// Anonymized relay address; missing for some order types
+    pub buyer_email: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct OrderItemPayload {
+    #[serde(rename = "SellerSKU")]
+    pub seller_sku: String,
+    pub title: String,
+    pub quantity_ordered: i32,
+    /// Price of the whole quantity; missing once an item is cancelled
+    pub item_price: Option<MoneyPayload>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MoneyPayload {
+    pub amount: Decimal,
+}
+
+/// Statuses of orders Amazon has not collected payment for
+const UNPAID_STATUSES: [&str; 3] = ["Pending", "PendingAvailability", "Canceled"];
+
+/// Map an SP-API order to a channel order. Cancelled items are dropped.
+pub fn parse_order(payload: &serde_json::Value) -> Result<ChannelOrder, ChannelError> {
+    let order = OrderPayload::deserialize(payload).map_err(|e| ChannelError::InvalidOrder(e.to_string()))?;
+    let placed_gmt = Timestamp::from(order.purchase_date);
+
+    let lines = order
+        .order_items
+        .into_iter()
+        .filter(|item| item.quantity_ordered > 0)
+        .map(|item| {
+            let total = item.item_price.map_or(Decimal::ZERO, |price| price.amount);
+            ChannelOrderLine {
+                unit_price: (total / Decimal::from(item.quantity_ordered)).round_dp(2),
+                sku: item.seller_sku,
+                title: item.title,
+                quantity: item.quantity_ordered,
+            }
+        })
+        .collect::<Vec<_>>();
+    if lines.is_empty() {
+        return Err(ChannelError::InvalidOrder(format!("order {} has no items", order.amazon_order_id)));
+    }
+
+    Ok(ChannelOrder {
+        paid_gmt: (!UNPAID_STATUSES.contains(&order.order_status.as_str())).then_some(placed_gmt),
+        external_id: order.amazon_order_id,
+        buyer_email: order.buyer_info.buyer_email.unwrap_or_default(),
+        placed_gmt,
+        lines,
+    })
+}
+
+/// A feed submitted to Amazon
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Feed {
+    Inventory(Vec<InventoryLevel>),
+    Tracking(Vec<TrackingUpdate>),
+}
+
+/// Amazon seller account
+pub struct AmazonConnector {
+    settings: AmazonSettings,
+    orders: Vec<ChannelOrder>,
+    feeds: Mutex<Vec<Feed>>,
+    listings: Mutex<BTreeMap<String, i32>>,
+    retry_after_secs: Option<i64>,
+}
+
+impl AmazonConnector {
+    pub fn new(settings: AmazonSettings) -> Result<Self, ChannelError> {
+        if settings.seller_id.trim().is_empty() {
+            return Err(ChannelError::InvalidSettings("seller_id is required".to_string()));
+        }
+        if settings.marketplace_id.trim().is_empty() {
+            return Err(ChannelError::InvalidSettings("marketplace_id is required".to_string()));
+        }
+        Ok(Self {
+            settings,
+            orders: Vec::new(),
+            feeds: Mutex::new(Vec::new()),
+            listings: Mutex::new(BTreeMap::new()),
+            retry_after_secs: None,
+        })
+    }
+
+    pub fn from_settings(settings: &serde_json::Value) -> Result<Self, ChannelError> {
+        let settings = AmazonSettings::deserialize(settings).map_err(|e| ChannelError::InvalidSettings(e.to_string()))?;
+        Self::new(settings)
+    }
+
+    /// Orders placed on the account, for pulls to return
+    pub fn with_orders(mut self, orders: Vec<ChannelOrder>) -> Self {
+        self.orders = orders;
+        self
+    }
+
+    /// Stock the account already lists
+    pub fn with_inventory(self, levels: &[InventoryLevel]) -> Self {
+        self.list(levels);
+        self
+    }
+
+    /// Turn every feed away as over Amazon's request quota
+    pub fn throttled(mut self, retry_after_secs: i64) -> Self {
+        self.retry_after_secs = Some(retry_after_secs);
+        self
+    }
+
+    pub fn settings(&self) -> &AmazonSettings {
+        &self.settings
+    }
+
+    /// Feeds submitted so far, oldest first
+    pub fn feeds(&self) -> Vec<Feed> {
+        self.feeds.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn submit(&self, feed: Feed) -> Result<(), ChannelError> {
+        if let Some(retry_after_secs) = self.retry_after_secs {
+            return Err(ChannelError::RateLimited { retry_after_secs });
+        }
+        if let Feed::Inventory(levels) = &feed {
+            self.list(levels);
+        }
+        self.feeds.lock().unwrap_or_else(|e| e.into_inner()).push(feed);
+        Ok(())
+    }
+
+    fn list(&self, levels: &[InventoryLevel]) {
+        let mut listings = self.listings.lock().unwrap_or_else(|e| e.into_inner());
+        for level in levels {
+            listings.insert(level.sku.clone(), level.quantity);
+        }
+    }
+}
+
+#[async_trait]
+impl MarketplaceConnector for AmazonConnector {
+    fn marketplace(&self) -> Marketplace {
+        Marketplace::Amazon
+    }
+
+    async fn pull_orders(&self, since: Timestamp) -> Result<Vec<ChannelOrder>, ChannelError> {
+        Ok(self.orders.iter().filter(|order| order.placed_gmt >= since).cloned().collect())
+    }
+
+    fn parse_order(&self, payload: &serde_json::Value) -> Result<ChannelOrder, ChannelError> {
+        parse_order(payload)
+    }
+
+    async fn push_inventory(&self, levels: &[InventoryLevel]) -> Result<(), ChannelError> {
+        if levels.len() > FEED_LIMIT {
+            return Err(ChannelError::Marketplace(format!("inventory feeds take at most {} SKUs", FEED_LIMIT)));
+        }
+        // Amazon refuses negative quantities in inventory feeds
+        let levels = levels
+            .iter()
+            .map(|level| InventoryLevel { sku: level.sku.clone(), quantity: level.quantity.max(0) })
+            .collect();
+        self.submit(Feed::Inventory(levels))
+    }
+
+    async fn fetch_inventory(&self, skus: &[String]) -> Result<Vec<InventoryLevel>, ChannelError> {
+        let listings = self.listings.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(skus
+            .iter()
+            .filter_map(|sku| Some(InventoryLevel { sku: sku.clone(), quantity: *listings.get(sku)? }))
+            .collect())
+    }
+
+    fn inventory_batch_size(&self) -> usize {
+        FEED_LIMIT
+    }
+
+    /// Feed submissions are limited to a burst of 15, refilled every two
+    /// minutes; a short pause keeps a large catalogue from draining it
+    fn batch_interval(&self) -> Duration {
+        Duration::from_secs(1)
+    }
+
+    async fn push_tracking(&self, updates: &[TrackingUpdate]) -> Result<(), ChannelError> {
+        if let Some(update) = updates.iter().find(|update| update.tracking_number.trim().is_empty()) {
+            return Err(ChannelError::Marketplace(format!(
+                "order {} has no tracking number",
+                update.external_id
+            )));
+        }
+        self.submit(Feed::Tracking(updates.to_vec()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn connector() -> AmazonConnector {
+        AmazonConnector::from_settings(&json!({ "seller_id": "A1B2C3", "marketplace_id": "ATVPDKIKX0DER" })).unwrap()
+    }
+
+    fn order(external_id: &str, placed_gmt: i64) -> ChannelOrder {
+        ChannelOrder {
+            external_id: external_id.to_string(),
+            buyer_email: "buyer@marketplace.amazon.com".to_string(),
+            placed_gmt: Timestamp::from_unix(placed_gmt),
+            paid_gmt: None,
+            lines: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_settings_are_required() {
+        assert_eq!(connector().settings().seller_id, "A1B2C3");
+        let missing = AmazonConnector::from_settings(&json!({ "seller_id": "A1B2C3" }));
+        assert!(matches!(missing, Err(ChannelError::InvalidSettings(_))));
+        let blank = AmazonConnector::from_settings(&json!({ "seller_id": " ", "marketplace_id": "ATVPDKIKX0DER" }));
+        assert!(matches!(blank, Err(ChannelError::InvalidSettings(_))));
+    }
+
+    #[tokio::test]
+    async fn test_pulls_orders_since() {
+        let amazon = connector().with_orders(vec![order("111-1", 100), order("111-2", 200)]);
+        let pulled = amazon.pull_orders(Timestamp::from_unix(200)).await.unwrap();
+        assert_eq!(pulled, vec![order("111-2", 200)]);
+    }
+
+    #[test]
+    fn test_parses_sp_api_order() {
+        let payload = json!({
+            "AmazonOrderId": "111-2",
+            "PurchaseDate": "2023-11-14T22:15:00Z",
+            "OrderStatus": "Unshipped",
+            "BuyerInfo": { "BuyerEmail": "buyer@marketplace.amazon.com" },
+            "OrderItems": [
+                { "SellerSKU": "SKU001", "Title": "Widget", "QuantityOrdered": 2, "ItemPrice": { "CurrencyCode": "USD", "Amount": "19.98" } },
+                { "SellerSKU": "SKU002", "Title": "Gadget", "QuantityOrdered": 0 }
+            ]
+        });
+        let order = parse_order(&payload).unwrap();
+        assert_eq!(order.external_id, "111-2");
+        assert_eq!(order.placed_gmt, Timestamp::from_unix(1_700_000_100));
+        assert_eq!(order.paid_gmt, Some(order.placed_gmt));
+        assert_eq!(
+            order.lines,
+            vec![ChannelOrderLine {
+                sku: "SKU001".to_string(),
+                title: "Widget".to_string(),
+                quantity: 2,
+                unit_price: Decimal::new(999, 2),
+            }]
+        );
+
+        let mut pending = payload.clone();
+        pending["OrderStatus"] = json!("Pending");
+        assert_eq!(parse_order(&pending).unwrap().paid_gmt, None);
+
+        let mut missing = payload;
+        missing.as_object_mut().unwrap().remove("AmazonOrderId");
+        assert!(matches!(parse_order(&missing), Err(ChannelError::InvalidOrder(_))));
+    }
+
+    #[tokio::test]
+    async fn test_pushes_feeds() {
+        let amazon = connector();
+        amazon.push_inventory(&[InventoryLevel { sku: "SKU001".to_string(), quantity: -2 }]).await.unwrap();
+
+        let untracked = TrackingUpdate {
+            external_id: "111-1".to_string(),
+            carrier: "UPS".to_string(),
+            tracking_number: String::new(),
+            shipped_gmt: Timestamp::from_unix(100),
+        };
+        assert!(matches!(amazon.push_tracking(&[untracked]).await, Err(ChannelError::Marketplace(_))));
+
+        assert_eq!(
+            amazon.feeds(),
+            vec![Feed::Inventory(vec![InventoryLevel { sku: "SKU001".to_string(), quantity: 0 }])]
+        );
+
+        let listed = amazon.fetch_inventory(&["SKU001".to_string(), "SKU002".to_string()]).await.unwrap();
+        assert_eq!(listed, vec![InventoryLevel { sku: "SKU001".to_string(), quantity: 0 }]);
+
+        let throttled = connector().throttled(120);
+        let result = throttled.push_inventory(&[InventoryLevel { sku: "SKU001".to_string(), quantity: 1 }]).await;
+        assert!(matches!(result, Err(ChannelError::RateLimited { retry_after_secs: 120 })));
+        assert!(throttled.feeds().is_empty());
+    }
+}
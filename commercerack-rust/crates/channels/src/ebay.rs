@@ -0,0 +1,286 @@
+//! eBay connector
+//!
+//! Shaped after the Sell APIs: orders come from the Fulfillment API as
+//! [`OrderPayload`]s, stock goes back through the Inventory API's bulk
+//! update, at most [`BULK_LIMIT`] SKUs a call, and each shipped order gets
+//! its own shipping fulfillment. Like the Amazon connector this is a stub;
+//! the calls it would make are kept for [`EbayConnector::calls`] and the
+//! stock they set is what the account lists.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use commercerack_core::Timestamp;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use crate::{ChannelError, ChannelOrder, ChannelOrderLine, InventoryLevel, Marketplace, MarketplaceConnector, TrackingUpdate};
+
+/// Most SKUs one bulk quantity update takes
+pub const BULK_LIMIT: usize = 25;
+
+/// Seller account settings, stored as the channel's `settings`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct EbaySettings {
+    pub seller_username: String,
+    /// Site the account sells on, e.g. `EBAY_US`
+    pub marketplace_id: String,
+}
+
+/// A Fulfillment API order, as ingested
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderPayload {
+    pub order_id: String,
+    pub creation_date: DateTime<Utc>,
+    /// `PAID` once the buyer has paid in full
+    pub order_payment_status: String,
+    #[serde(default)]
+    pub buyer: Buyer,
+    #[serde(default)]
+    pub payment_summary: PaymentSummary,
+    pub line_items: Vec<LineItemPayload>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Buyer {
+    pub buyer_registration_address: Option<RegistrationAddress>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RegistrationAddress {
+    pub email: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PaymentSummary {
+    #[serde(default)]
+    pub payments: Vec<Payment>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Payment {
+    pub payment_date: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LineItemPayload {
+    /// Listings without a SKU can't be matched to ours and are refused
+    pub sku: String,
+    pub title: String,
+    pub quantity: i32,
+    /// Price of the whole quantity
+    pub line_item_cost: AmountPayload,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AmountPayload {
+    pub value: Decimal,
+}
+
+/// Map a Fulfillment API order to a channel order
+pub fn parse_order(payload: &serde_json::Value) -> Result<ChannelOrder, ChannelError> {
+    let order = OrderPayload::deserialize(payload).map_err(|e| ChannelError::InvalidOrder(e.to_string()))?;
+    if order.line_items.is_empty() {
+        return Err(ChannelError::InvalidOrder(format!("order {} has no line items", order.order_id)));
+    }
+    let placed_gmt = Timestamp::from(order.creation_date);
+
+    // Paid when the last payment cleared, for orders paid in instalments
+    let paid_gmt = (order.order_payment_status == "PAID").then(|| {
+        order
+            .payment_summary
+            .payments
+            .iter()
+            .filter_map(|payment| payment.payment_date)
+            .max()
+            .map_or(placed_gmt, Timestamp::from)
+    });
+
+    Ok(ChannelOrder {
+        buyer_email: order
+            .buyer
+            .buyer_registration_address
+            .and_then(|address| address.email)
+            .unwrap_or_default(),
+        lines: order
+            .line_items
+            .into_iter()
+            .map(|item| ChannelOrderLine {
+                unit_price: (item.line_item_cost.value / Decimal::from(item.quantity.max(1))).round_dp(2),
+                sku: item.sku,
+                title: item.title,
+                quantity: item.quantity,
+            })
+            .collect(),
+        external_id: order.order_id,
+        placed_gmt,
+        paid_gmt,
+    })
+}
+
+/// A call made to eBay
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Call {
+    BulkUpdateQuantity(Vec<InventoryLevel>),
+    CreateShippingFulfillment(TrackingUpdate),
+}
+
+/// eBay seller account
+pub struct EbayConnector {
+    settings: EbaySettings,
+    orders: Vec<ChannelOrder>,
+    calls: Mutex<Vec<Call>>,
+    listings: Mutex<BTreeMap<String, i32>>,
+}
+
+impl EbayConnector {
+    pub fn new(settings: EbaySettings) -> Result<Self, ChannelError> {
+        if settings.seller_username.trim().is_empty() {
+            return Err(ChannelError::InvalidSettings("seller_username is required".to_string()));
+        }
+        if settings.marketplace_id.trim().is_empty() {
+            return Err(ChannelError::InvalidSettings("marketplace_id is required".to_string()));
+        }
+        Ok(Self {
+            settings,
+            orders: Vec::new(),
+            calls: Mutex::new(Vec::new()),
+            listings: Mutex::new(BTreeMap::new()),
+        })
+    }
+
+    pub fn from_settings(settings: &serde_json::Value) -> Result<Self, ChannelError> {
+        let settings = EbaySettings::deserialize(settings).map_err(|e| ChannelError::InvalidSettings(e.to_string()))?;
+        Self::new(settings)
+    }
+
+    /// Orders placed on the account, for pulls to return
+    pub fn with_orders(mut self, orders: Vec<ChannelOrder>) -> Self {
+        self.orders = orders;
+        self
+    }
+
+    pub fn settings(&self) -> &EbaySettings {
+        &self.settings
+    }
+
+    /// Calls made so far, oldest first
+    pub fn calls(&self) -> Vec<Call> {
+        self.calls.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn call(&self, call: Call) {
+        self.calls.lock().unwrap_or_else(|e| e.into_inner()).push(call);
+    }
+}
+
+#[async_trait]
+impl MarketplaceConnector for EbayConnector {
+    fn marketplace(&self) -> Marketplace {
+        Marketplace::Ebay
+    }
+
+    async fn pull_orders(&self, since: Timestamp) -> Result<Vec<ChannelOrder>, ChannelError> {
+        Ok(self.orders.iter().filter(|order| order.placed_gmt >= since).cloned().collect())
+    }
+
+    fn parse_order(&self, payload: &serde_json::Value) -> Result<ChannelOrder, ChannelError> {
+        parse_order(payload)
+    }
+
+    async fn push_inventory(&self, levels: &[InventoryLevel]) -> Result<(), ChannelError> {
+        if levels.len() > BULK_LIMIT {
+            return Err(ChannelError::Marketplace(format!("bulk updates take at most {} SKUs", BULK_LIMIT)));
+        }
+        let levels: Vec<_> = levels
+            .iter()
+            .map(|level| InventoryLevel { sku: level.sku.clone(), quantity: level.quantity.max(0) })
+            .collect();
+        let mut listings = self.listings.lock().unwrap_or_else(|e| e.into_inner());
+        for level in &levels {
+            listings.insert(level.sku.clone(), level.quantity);
+        }
+        self.call(Call::BulkUpdateQuantity(levels));
+        Ok(())
+    }
+
+    async fn fetch_inventory(&self, skus: &[String]) -> Result<Vec<InventoryLevel>, ChannelError> {
+        let listings = self.listings.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(skus
+            .iter()
+            .filter_map(|sku| Some(InventoryLevel { sku: sku.clone(), quantity: *listings.get(sku)? }))
+            .collect())
+    }
+
+    fn inventory_batch_size(&self) -> usize {
+        BULK_LIMIT
+    }
+
+    async fn push_tracking(&self, updates: &[TrackingUpdate]) -> Result<(), ChannelError> {
+        if let Some(update) = updates.iter().find(|update| update.tracking_number.trim().is_empty()) {
+            return Err(ChannelError::Marketplace(format!(
+                "order {} has no tracking number",
+                update.external_id
+            )));
+        }
+        for update in updates {
+            self.call(Call::CreateShippingFulfillment(update.clone()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn connector() -> EbayConnector {
+        EbayConnector::from_settings(&json!({ "seller_username": "widgets-r-us", "marketplace_id": "EBAY_US" })).unwrap()
+    }
+
+    #[test]
+    fn test_parses_fulfillment_order() {
+        let payload = json!({
+            "orderId": "12-34567-89012",
+            "creationDate": "2023-11-14T22:15:00.000Z",
+            "orderPaymentStatus": "PAID",
+            "buyer": { "username": "jdoe", "buyerRegistrationAddress": { "email": "jdoe@members.ebay.com" } },
+            "paymentSummary": { "payments": [{ "paymentDate": "2023-11-14T22:16:40.000Z" }] },
+            "lineItems": [
+                { "sku": "SKU001", "title": "Widget", "quantity": 3, "lineItemCost": { "value": "29.97", "currency": "USD" } }
+            ]
+        });
+        let order = parse_order(&payload).unwrap();
+        assert_eq!(order.external_id, "12-34567-89012");
+        assert_eq!(order.buyer_email, "jdoe@members.ebay.com");
+        assert_eq!(order.placed_gmt, Timestamp::from_unix(1_700_000_100));
+        assert_eq!(order.paid_gmt, Some(Timestamp::from_unix(1_700_000_200)));
+        assert_eq!(order.lines[0].unit_price, Decimal::new(999, 2));
+
+        let mut unpaid = payload.clone();
+        unpaid["orderPaymentStatus"] = json!("PENDING");
+        assert_eq!(parse_order(&unpaid).unwrap().paid_gmt, None);
+
+        let mut no_sku = payload;
+        no_sku["lineItems"][0].as_object_mut().unwrap().remove("sku");
+        assert!(matches!(parse_order(&no_sku), Err(ChannelError::InvalidOrder(_))));
+    }
+
+    #[tokio::test]
+    async fn test_bulk_updates_are_limited() {
+        let ebay = connector();
+        let levels: Vec<_> = (0..30).map(|i| InventoryLevel { sku: format!("SKU{:03}", i), quantity: 1 }).collect();
+        assert!(matches!(ebay.push_inventory(&levels).await, Err(ChannelError::Marketplace(_))));
+
+        ebay.push_inventory(&levels[..BULK_LIMIT]).await.unwrap();
+        assert_eq!(ebay.calls(), vec![Call::BulkUpdateQuantity(levels[..BULK_LIMIT].to_vec())]);
+        let listed = ebay.fetch_inventory(&["SKU000".to_string(), "SKU029".to_string()]).await.unwrap();
+        assert_eq!(listed, vec![InventoryLevel { sku: "SKU000".to_string(), quantity: 1 }]);
+    }
+}
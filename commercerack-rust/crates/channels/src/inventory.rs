@@ -0,0 +1,266 @@
+//! Pushing stock to marketplaces
+//!
+//! A channel's first push sends every SKU. After that only SKUs whose
+//! stock moved since `inventory_synced_gmt` are sent, found through the
+//! inventory adjustment trail. Pushes are split into batches the
+//! connector takes, paced by its [`batch_interval`]. A marketplace that
+//! answers with [`ChannelError::RateLimited`] ends the push; the mark is
+//! not moved, so the next push sends those SKUs again.
+//!
+//! [`reconcile`] compares our stock with what the marketplace lists, for
+//! SKUs a push missed or that were changed on the marketplace itself.
+//!
+//! [`batch_interval`]: crate::MarketplaceConnector::batch_interval
+
+use commercerack_core::Timestamp;
+use sea_orm::sea_query::Expr;
+use sea_orm::*;
+use serde::Serialize;
+use ::entity::prelude::{Channel, Channels, InventoryAdjustments, Skus};
+
+use crate::{ChannelError, InventoryLevel, MarketplaceConnector};
+use tracing::instrument;
+
+/// A SKU whose stock on the marketplace differs from ours
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StockMismatch {
+    pub sku: String,
+    /// Our sellable stock, as pushed; pushes never send less than 0
+    pub internal: i32,
+    /// What the marketplace lists; `None` if it does not list the SKU
+    pub channel: Option<i32>,
+}
+
+/// Outcome of comparing a channel's stock with ours
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ReconciliationReport {
+    /// SKUs compared
+    pub checked: usize,
+    pub mismatches: Vec<StockMismatch>,
+}
+
+/// Sellable stock of every SKU of `mid`
+pub(crate) async fn inventory_levels<C: ConnectionTrait>(db: &C, mid: i32) -> Result<Vec<InventoryLevel>, ChannelError> {
+    let skus = Skus::find()
+        .filter(::entity::skus::Column::Mid.eq(mid))
+        .order_by_asc(::entity::skus::Column::Sku)
+        .all(db)
+        .await?;
+
+    Ok(skus
+        .into_iter()
+        .map(|sku| InventoryLevel { sku: sku.sku, quantity: sku.inv_available })
+        .collect())
+}
+
+/// Sellable stock of `mid`'s SKUs whose stock moved from `since` up to and
+/// including `until`. Adjustments are timed to the second, so ones made
+/// in the second of the last push are included again.
+async fn changed_levels<C: ConnectionTrait>(
+    db: &C,
+    mid: i32,
+    since: Timestamp,
+    until: Timestamp,
+) -> Result<Vec<InventoryLevel>, ChannelError> {
+    let changed: Vec<String> = InventoryAdjustments::find()
+        .select_only()
+        .column(::entity::inventory_adjustments::Column::Sku)
+        .distinct()
+        .filter(::entity::inventory_adjustments::Column::Mid.eq(mid))
+        .filter(::entity::inventory_adjustments::Column::CreatedGmt.gte(since))
+        .filter(::entity::inventory_adjustments::Column::CreatedGmt.lte(until))
+        .into_tuple()
+        .all(db)
+        .await?;
+    if changed.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let skus = Skus::find()
+        .filter(::entity::skus::Column::Mid.eq(mid))
+        .filter(::entity::skus::Column::Sku.is_in(changed))
+        .order_by_asc(::entity::skus::Column::Sku)
+        .all(db)
+        .await?;
+
+    Ok(skus
+        .into_iter()
+        .map(|sku| InventoryLevel { sku: sku.sku, quantity: sku.inv_available })
+        .collect())
+}
+
+/// Push the stock `channel`'s marketplace has not seen yet, in batches;
+/// returns how many SKUs were pushed
+pub(crate) async fn push_changed<C: ConnectionTrait>(
+    db: &C,
+    channel: &Channel,
+    connector: &dyn MarketplaceConnector,
+    now: Timestamp,
+) -> Result<usize, ChannelError> {
+    let levels = match channel.inventory_synced_gmt {
+        Some(since) => changed_levels(db, channel.mid, since, now).await?,
+        None => inventory_levels(db, channel.mid).await?,
+    };
+
+    for (i, batch) in levels.chunks(connector.inventory_batch_size().max(1)).enumerate() {
+        if i > 0 {
+            tokio::time::sleep(connector.batch_interval()).await;
+        }
+        connector.push_inventory(batch).await?;
+    }
+    Ok(levels.len())
+}
+
+/// Push the stock that moved since `channel`'s last push
+#[instrument(skip_all, fields(mid = channel.mid, channel = channel.id))]
+pub async fn sync_inventory<C: ConnectionTrait>(
+    db: &C,
+    channel: &Channel,
+    connector: &dyn MarketplaceConnector,
+    now: Timestamp,
+) -> Result<usize, ChannelError> {
+    let pushed = push_changed(db, channel, connector, now).await?;
+
+    // Only the inventory mark, so an order sync running alongside keeps its own
+    Channels::update_many()
+        .col_expr(::entity::channels::Column::InventorySyncedGmt, Expr::value(now))
+        .col_expr(::entity::channels::Column::LastError, Expr::value(Option::<String>::None))
+        .filter(::entity::channels::Column::Id.eq(channel.id))
+        .exec(db)
+        .await?;
+    Ok(pushed)
+}
+
+/// Compare every SKU's stock with what `channel`'s marketplace lists
+#[instrument(skip_all, fields(mid = channel.mid, channel = channel.id))]
+pub async fn reconcile<C: ConnectionTrait>(
+    db: &C,
+    channel: &Channel,
+    connector: &dyn MarketplaceConnector,
+) -> Result<ReconciliationReport, ChannelError> {
+    let levels = inventory_levels(db, channel.mid).await?;
+
+    let mut report = ReconciliationReport { checked: levels.len(), mismatches: Vec::new() };
+    for (i, batch) in levels.chunks(connector.inventory_batch_size().max(1)).enumerate() {
+        if i > 0 {
+            tokio::time::sleep(connector.batch_interval()).await;
+        }
+        let skus: Vec<String> = batch.iter().map(|level| level.sku.clone()).collect();
+        let listed = connector.fetch_inventory(&skus).await?;
+
+        for level in batch {
+            let internal = level.quantity.max(0);
+            let channel = listed.iter().find(|listed| listed.sku == level.sku).map(|listed| listed.quantity);
+            if channel != Some(internal) {
+                report.mismatches.push(StockMismatch { sku: level.sku.clone(), internal, channel });
+            }
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::channel;
+    use crate::AmazonConnector;
+    use rust_decimal::Decimal;
+    use std::collections::BTreeMap;
+    use ::entity::prelude::Sku;
+
+    const SETTINGS: &str = r#"{"seller_id":"A1B2C3","marketplace_id":"ATVPDKIKX0DER"}"#;
+
+    fn sku(id: i32, sku: &str, inv_available: i32) -> Sku {
+        Sku {
+            id,
+            pid: 1,
+            mid: 1,
+            sku: sku.to_string(),
+            title: "Widget".to_string(),
+            price: Decimal::new(999, 2),
+            cost: Decimal::ZERO,
+            upc: String::new(),
+            inv_available,
+            qty_onshelf: inv_available,
+            weight: Decimal::ZERO,
+            reorder_point: None,
+            reorder_quantity: 0,
+            low_stock_gmt: None,
+        }
+    }
+
+    fn amazon() -> AmazonConnector {
+        AmazonConnector::from_settings(&serde_json::from_str(SETTINGS).unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_pushes_only_changed_stock() {
+        let mut channel = channel(SETTINGS);
+        channel.inventory_synced_gmt = Some(Timestamp::from_unix(1_700_000_500));
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![BTreeMap::from([("sku".to_string(), Value::from("SKU002"))])]])
+            .append_query_results([vec![sku(2, "SKU002", 4)]])
+            .append_exec_results([MockExecResult { last_insert_id: 0, rows_affected: 1 }])
+            .into_connection();
+
+        let amazon = amazon();
+        let pushed = sync_inventory(&db, &channel, &amazon, Timestamp::from_unix(1_700_001_000)).await.unwrap();
+        assert_eq!(pushed, 1);
+        assert_eq!(
+            amazon.fetch_inventory(&["SKU001".to_string(), "SKU002".to_string()]).await.unwrap(),
+            vec![InventoryLevel { sku: "SKU002".to_string(), quantity: 4 }]
+        );
+
+        let log = db.into_transaction_log();
+        let changed = log[0].statements()[0].to_string();
+        assert!(changed.contains(r#"SELECT DISTINCT "inventory_adjustments"."sku""#), "{}", changed);
+        assert!(changed.contains(r#""created_gmt" >= 1700000500"#), "{}", changed);
+        let marked = log[2].statements()[0].to_string();
+        assert!(marked.contains(r#""inventory_synced_gmt" = 1700001000"#), "{}", marked);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_keeps_the_mark() {
+        let channel = channel(SETTINGS);
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![sku(1, "SKU001", 7)]])
+            .into_connection();
+
+        let amazon = amazon().throttled(120);
+        let result = sync_inventory(&db, &channel, &amazon, Timestamp::from_unix(1_700_001_000)).await;
+        assert!(matches!(result, Err(ChannelError::RateLimited { retry_after_secs: 120 })));
+        // Only the SKU lookup ran; the channel was not marked as pushed
+        assert_eq!(db.into_transaction_log().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_reports_mismatches() {
+        let channel = channel(SETTINGS);
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![sku(1, "SKU001", 7), sku(2, "SKU002", -1), sku(3, "SKU003", 5)]])
+            .into_connection();
+
+        let amazon = amazon().with_inventory(&[
+            InventoryLevel { sku: "SKU001".to_string(), quantity: 7 },
+            InventoryLevel { sku: "SKU002".to_string(), quantity: 0 },
+            InventoryLevel { sku: "SKU003".to_string(), quantity: 9 },
+        ]);
+        let report = reconcile(&db, &channel, &amazon).await.unwrap();
+        assert_eq!(report.checked, 3);
+        assert_eq!(
+            report.mismatches,
+            vec![StockMismatch { sku: "SKU003".to_string(), internal: 5, channel: Some(9) }]
+        );
+
+        let unlisted = reconcile(
+            &MockDatabase::new(DatabaseBackend::Postgres)
+                .append_query_results([vec![sku(1, "SKU001", 7)]])
+                .into_connection(),
+            &channel,
+            &self::amazon(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(unlisted.mismatches, vec![StockMismatch { sku: "SKU001".to_string(), internal: 7, channel: None }]);
+    }
+}
@@ -0,0 +1,458 @@
+//! Marketplace channels
+//!
+//! A channel is a merchant's seller account on a marketplace. Its
+//! [`MarketplaceConnector`] pulls the orders placed there, which are
+//! recorded as guest orders carrying the marketplace's bit in `orders.mkt`
+//! and its reference in `orders.erefid`, and pushes back stock levels and
+//! the tracking numbers of shipped orders. [`sync::sync_channel`] does all
+//! three; the jobs crate runs it for every enabled channel on a schedule,
+//! and [`inventory::sync_inventory`] more often so stock changes reach the
+//! marketplaces quickly.
+//! Marketplaces that push orders to us instead hand them to
+//! [`ingest_order`], in their own payload format.
+
+use async_trait::async_trait;
+use commercerack_core::Timestamp;
+use commercerack_order::items::{NewOrderItem, OrderItemService};
+use commercerack_order::{ImportedOrder, OrderError, OrderService, OrderWithItems};
+use rust_decimal::Decimal;
+use sea_orm::sea_query::Expr;
+use sea_orm::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+use thiserror::Error;
+use tracing::instrument;
+use ::entity::prelude::{Channel, Channels};
+
+pub mod amazon;
+pub mod ebay;
+pub mod inventory;
+pub mod sync;
+
+pub use amazon::AmazonConnector;
+pub use ebay::EbayConnector;
+
+#[derive(Error, Debug)]
+pub enum ChannelError {
+    #[error("Channel not found")]
+    NotFound,
+
+    #[error("Unknown marketplace {0}")]
+    UnknownMarketplace(String),
+
+    #[error("A {0} channel is already registered")]
+    AlreadyRegistered(Marketplace),
+
+    #[error("Invalid channel settings: {0}")]
+    InvalidSettings(String),
+
+    #[error("Invalid marketplace order: {0}")]
+    InvalidOrder(String),
+
+    #[error("Marketplace error: {0}")]
+    Marketplace(String),
+
+    #[error("Rate limited by the marketplace; retry in {retry_after_secs}s")]
+    RateLimited { retry_after_secs: i64 },
+
+    #[error(transparent)]
+    Order(#[from] OrderError),
+
+    #[error("Database error: {0}")]
+    Db(#[from] DbErr),
+}
+
+/// A marketplace a channel can sell on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Marketplace {
+    Amazon,
+    Ebay,
+}
+
+impl Marketplace {
+    pub const ALL: [Marketplace; 2] = [Marketplace::Amazon, Marketplace::Ebay];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Marketplace::Amazon => "amazon",
+            Marketplace::Ebay => "ebay",
+        }
+    }
+
+    /// The marketplace's bit in `orders.mkt`. Bits are the legacy
+    /// marketplace numbering and must never be reused.
+    pub fn bit(&self) -> i32 {
+        match self {
+            Marketplace::Amazon => 1 << 0,
+            Marketplace::Ebay => 1 << 1,
+        }
+    }
+}
+
+impl fmt::Display for Marketplace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Marketplace {
+    type Err = ChannelError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|marketplace| marketplace.as_str() == s)
+            .ok_or_else(|| ChannelError::UnknownMarketplace(s.to_string()))
+    }
+}
+
+/// One line of a marketplace order
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChannelOrderLine {
+    pub sku: String,
+    pub title: String,
+    pub quantity: i32,
+    pub unit_price: Decimal,
+}
+
+/// An order as a marketplace reports it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChannelOrder {
+    /// The marketplace's order reference
+    pub external_id: String,
+    pub buyer_email: String,
+    pub placed_gmt: Timestamp,
+    /// When the marketplace collected payment, if it has
+    pub paid_gmt: Option<Timestamp>,
+    pub lines: Vec<ChannelOrderLine>,
+}
+
+impl ChannelOrder {
+    /// The order to record for a pull from `marketplace`
+    pub fn to_imported(&self, marketplace: Marketplace) -> ImportedOrder {
+        ImportedOrder {
+            mkt: marketplace.bit(),
+            erefid: self.external_id.clone(),
+            bill_email: self.buyer_email.clone(),
+            paid_gmt: self.paid_gmt,
+            items: self
+                .lines
+                .iter()
+                .map(|line| NewOrderItem {
+                    sku: line.sku.clone(),
+                    product_name: line.title.clone(),
+                    quantity: line.quantity,
+                    unit_price: line.unit_price,
+                    options: Default::default(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Sellable stock of a SKU
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InventoryLevel {
+    pub sku: String,
+    pub quantity: i32,
+}
+
+/// A shipment of a marketplace order
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrackingUpdate {
+    /// The marketplace's order reference
+    pub external_id: String,
+    pub carrier: String,
+    pub tracking_number: String,
+    pub shipped_gmt: Timestamp,
+}
+
+/// A seller account on a marketplace
+#[async_trait]
+pub trait MarketplaceConnector: Send + Sync {
+    fn marketplace(&self) -> Marketplace;
+
+    /// Orders placed at or after `since`. Returning an order again is
+    /// harmless; it is only recorded once.
+    async fn pull_orders(&self, since: Timestamp) -> Result<Vec<ChannelOrder>, ChannelError>;
+
+    /// Map an order the marketplace pushed to us, in its own format
+    fn parse_order(&self, payload: &serde_json::Value) -> Result<ChannelOrder, ChannelError>;
+
+    /// Set the stock the marketplace may sell, at most
+    /// [`inventory_batch_size`](Self::inventory_batch_size) SKUs at a time
+    async fn push_inventory(&self, levels: &[InventoryLevel]) -> Result<(), ChannelError>;
+
+    /// Stock the marketplace lists for `skus`; SKUs it does not list are
+    /// left out
+    async fn fetch_inventory(&self, skus: &[String]) -> Result<Vec<InventoryLevel>, ChannelError>;
+
+    /// Most SKUs one [`push_inventory`](Self::push_inventory) may carry
+    fn inventory_batch_size(&self) -> usize {
+        100
+    }
+
+    /// Pause between inventory batches, to stay under the marketplace's
+    /// request rate
+    fn batch_interval(&self) -> Duration {
+        Duration::ZERO
+    }
+
+    /// Confirm shipment of marketplace orders
+    async fn push_tracking(&self, updates: &[TrackingUpdate]) -> Result<(), ChannelError>;
+}
+
+/// A connector for a `marketplace` account with `settings`
+pub fn build(marketplace: Marketplace, settings: &serde_json::Value) -> Result<Box<dyn MarketplaceConnector>, ChannelError> {
+    match marketplace {
+        Marketplace::Amazon => Ok(Box::new(AmazonConnector::from_settings(settings)?)),
+        Marketplace::Ebay => Ok(Box::new(EbayConnector::from_settings(settings)?)),
+    }
+}
+
+/// The connector for a registered channel
+pub fn connector(channel: &Channel) -> Result<Box<dyn MarketplaceConnector>, ChannelError> {
+    let settings = serde_json::from_str(&channel.settings).map_err(|e| ChannelError::InvalidSettings(e.to_string()))?;
+    build(channel.marketplace.parse()?, &settings)
+}
+
+/// Whether `channel`'s marketplace asked us to back off until after `now`
+pub fn throttled(channel: &Channel, now: Timestamp) -> bool {
+    channel.throttled_until_gmt.is_some_and(|until| until > now)
+}
+
+/// An order pushed by a channel's marketplace
+#[derive(Debug, Clone, Serialize)]
+pub struct Ingested {
+    pub order: OrderWithItems,
+    /// Whether the order was recorded now, rather than by an earlier
+    /// delivery or pull of the same marketplace order
+    pub created: bool,
+}
+
+/// Record an order `channel`'s marketplace pushed to us. The marketplace's
+/// order reference is the idempotency key: delivering an order again
+/// returns the order recorded the first time.
+#[instrument(skip_all, fields(mid = channel.mid, channel = channel.id))]
+pub async fn ingest_order<C: ConnectionTrait + TransactionTrait>(
+    db: &C,
+    channel: &Channel,
+    payload: &serde_json::Value,
+) -> Result<Ingested, ChannelError> {
+    let connector = connector(channel)?;
+    let imported = connector.parse_order(payload)?.to_imported(connector.marketplace());
+
+    if let Some(order) = OrderService::import(db, channel.mid, &imported).await? {
+        return Ok(Ingested { order, created: true });
+    }
+    let order = OrderService::find_imported(db, channel.mid, imported.mkt, &imported.erefid)
+        .await?
+        .ok_or(OrderError::NotFound)?;
+    let items = OrderItemService::list(db, channel.mid, order.id).await?;
+    Ok(Ingested { order: OrderWithItems { order, items }, created: false })
+}
+
+/// A channel to register
+#[derive(Debug, Clone)]
+pub struct NewChannel {
+    pub marketplace: Marketplace,
+    pub name: String,
+    pub settings: serde_json::Value,
+}
+
+/// Channel registration per merchant
+pub struct ChannelService;
+
+impl ChannelService {
+    /// Register a merchant's account on a marketplace; one per marketplace
+    pub async fn register<C: ConnectionTrait>(db: &C, mid: i32, channel: NewChannel) -> Result<Channel, ChannelError> {
+        build(channel.marketplace, &channel.settings)?;
+
+        let existing = Channels::find()
+            .filter(::entity::channels::Column::Mid.eq(mid))
+            .filter(::entity::channels::Column::Marketplace.eq(channel.marketplace.as_str()))
+            .one(db)
+            .await?;
+        if existing.is_some() {
+            return Err(ChannelError::AlreadyRegistered(channel.marketplace));
+        }
+
+        let now = Timestamp::now();
+        let record = ::entity::channels::ActiveModel {
+            mid: Set(mid),
+            marketplace: Set(channel.marketplace.as_str().to_string()),
+            name: Set(channel.name),
+            settings: Set(channel.settings.to_string()),
+            enabled: Set(true),
+            orders_synced_gmt: Set(None),
+            tracking_synced_gmt: Set(None),
+            inventory_synced_gmt: Set(None),
+            throttled_until_gmt: Set(None),
+            last_error: Set(None),
+            created_gmt: Set(now),
+            modified_gmt: Set(now),
+            ..Default::default()
+        }
+        .insert(db)
+        .await?;
+
+        Ok(record)
+    }
+
+    pub async fn find<C: ConnectionTrait>(db: &C, mid: i32, id: i32) -> Result<Option<Channel>, ChannelError> {
+        Ok(Channels::find()
+            .filter(::entity::channels::Column::Mid.eq(mid))
+            .filter(::entity::channels::Column::Id.eq(id))
+            .one(db)
+            .await?)
+    }
+
+    pub async fn list<C: ConnectionTrait>(db: &C, mid: i32) -> Result<Vec<Channel>, ChannelError> {
+        Ok(Channels::find()
+            .filter(::entity::channels::Column::Mid.eq(mid))
+            .order_by_asc(::entity::channels::Column::Id)
+            .all(db)
+            .await?)
+    }
+
+    /// Every merchant's enabled channels, for the sync scheduler
+    pub async fn enabled<C: ConnectionTrait>(db: &C) -> Result<Vec<Channel>, ChannelError> {
+        Ok(Channels::find()
+            .filter(::entity::channels::Column::Enabled.eq(true))
+            .order_by_asc(::entity::channels::Column::Id)
+            .all(db)
+            .await?)
+    }
+
+    /// Rename, reconfigure, pause or resume a channel
+    pub async fn update<C: ConnectionTrait>(
+        db: &C,
+        channel: Channel,
+        name: String,
+        settings: serde_json::Value,
+        enabled: bool,
+    ) -> Result<Channel, ChannelError> {
+        build(channel.marketplace.parse()?, &settings)?;
+
+        let mut active: ::entity::channels::ActiveModel = channel.into();
+        active.name = Set(name);
+        active.settings = Set(settings.to_string());
+        active.enabled = Set(enabled);
+        active.modified_gmt = Set(Timestamp::now());
+        Ok(active.update(db).await?)
+    }
+
+    /// Stop syncing and forget a channel. Its orders stay.
+    pub async fn delete<C: ConnectionTrait>(db: &C, mid: i32, id: i32) -> Result<bool, ChannelError> {
+        let result = Channels::delete_many()
+            .filter(::entity::channels::Column::Mid.eq(mid))
+            .filter(::entity::channels::Column::Id.eq(id))
+            .exec(db)
+            .await?;
+        Ok(result.rows_affected > 0)
+    }
+
+    /// Hold off syncing a channel until `until`, as its marketplace asked
+    pub async fn throttle<C: ConnectionTrait>(db: &C, channel: &Channel, until: Timestamp) -> Result<(), ChannelError> {
+        Channels::update_many()
+            .col_expr(::entity::channels::Column::ThrottledUntilGmt, Expr::value(until))
+            .filter(::entity::channels::Column::Id.eq(channel.id))
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+
+    /// Note why a sync failed, for the merchant to see
+    pub async fn record_failure<C: ConnectionTrait>(db: &C, channel: &Channel, error: &str) -> Result<(), ChannelError> {
+        Channels::update_many()
+            .col_expr(::entity::channels::Column::LastError, Expr::value(error))
+            .filter(::entity::channels::Column::Id.eq(channel.id))
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    pub(crate) fn channel(settings: &str) -> Channel {
+        Channel {
+            id: 1,
+            mid: 1,
+            marketplace: "amazon".to_string(),
+            name: "Amazon US".to_string(),
+            settings: settings.to_string(),
+            enabled: true,
+            orders_synced_gmt: None,
+            tracking_synced_gmt: None,
+            inventory_synced_gmt: None,
+            throttled_until_gmt: None,
+            last_error: None,
+            created_gmt: Timestamp::from_unix(1_700_000_000),
+            modified_gmt: Timestamp::from_unix(1_700_000_000),
+        }
+    }
+
+    #[test]
+    fn test_marketplace_roundtrip() {
+        for marketplace in Marketplace::ALL {
+            assert_eq!(marketplace.as_str().parse::<Marketplace>().unwrap(), marketplace);
+            assert_eq!(serde_json::to_string(&marketplace).unwrap(), format!("\"{}\"", marketplace));
+        }
+        assert!(matches!("walmart".parse::<Marketplace>(), Err(ChannelError::UnknownMarketplace(_))));
+    }
+
+    #[test]
+    fn test_connector_for_channel() {
+        let settings = r#"{"seller_id":"A1B2C3","marketplace_id":"ATVPDKIKX0DER"}"#;
+        assert_eq!(connector(&channel(settings)).unwrap().marketplace(), Marketplace::Amazon);
+        assert!(matches!(connector(&channel("{}")), Err(ChannelError::InvalidSettings(_))));
+    }
+
+    #[tokio::test]
+    async fn test_ingest_returns_order_recorded_before() {
+        let mut recorded = crate::sync::tests::order(2, Some("12-34567-89012"));
+        recorded.mkt = Marketplace::Ebay.bit();
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![recorded.clone()]])
+            .append_query_results([vec![recorded]])
+            .append_query_results([Vec::<::entity::prelude::OrderItem>::new()])
+            .into_connection();
+
+        let mut ebay = channel(r#"{"seller_username":"widgets-r-us","marketplace_id":"EBAY_US"}"#);
+        ebay.marketplace = "ebay".to_string();
+        let payload = serde_json::json!({
+            "orderId": "12-34567-89012",
+            "creationDate": "2023-11-14T22:15:00.000Z",
+            "orderPaymentStatus": "PAID",
+            "lineItems": [{ "sku": "SKU001", "title": "Widget", "quantity": 1, "lineItemCost": { "value": "9.99" } }]
+        });
+        let ingested = ingest_order(&db, &ebay, &payload).await.unwrap();
+        assert!(!ingested.created);
+        assert_eq!(ingested.order.order.id, 2);
+
+        let log = db.into_transaction_log();
+        let sql = log[0].statements()[1].to_string();
+        assert!(sql.contains(r#""orders"."mkt" = 2 AND "orders"."order_erefid" = '12-34567-89012'"#), "{}", sql);
+    }
+
+    #[tokio::test]
+    async fn test_register_refuses_second_account() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![channel("{}")]])
+            .into_connection();
+
+        let new = NewChannel {
+            marketplace: Marketplace::Amazon,
+            name: "Amazon US".to_string(),
+            settings: serde_json::json!({ "seller_id": "A1B2C3", "marketplace_id": "ATVPDKIKX0DER" }),
+        };
+        let result = ChannelService::register(&db, 1, new).await;
+        assert!(matches!(result, Err(ChannelError::AlreadyRegistered(Marketplace::Amazon))));
+    }
+}
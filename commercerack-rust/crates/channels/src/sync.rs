@@ -0,0 +1,258 @@
+//! Syncing a channel with its marketplace
+//!
+//! A sync pulls the orders placed since the last one, confirms the
+//! shipments made since the last one and pushes the stock that moved since
+//! the last push, as [`inventory::sync_inventory`] does between syncs. The
+//! channel's `*_synced_gmt` marks only move once the step they belong to
+//! has succeeded, so a failed sync is simply repeated.
+//!
+//! [`inventory::sync_inventory`]: crate::inventory::sync_inventory
+
+use commercerack_core::Timestamp;
+use commercerack_order::OrderService;
+use sea_orm::*;
+use serde::Serialize;
+use ::entity::prelude::{Channel, Orders, Shipments};
+
+use crate::inventory::push_changed;
+use crate::{ChannelError, Marketplace, MarketplaceConnector, TrackingUpdate};
+use tracing::instrument;
+
+/// What a sync did
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SyncReport {
+    /// New orders recorded; orders pulled before are not counted
+    pub orders_imported: usize,
+    pub tracking_pushed: usize,
+    pub inventory_pushed: usize,
+}
+
+/// Shipments of `mid`'s orders from `marketplace` made after `since`, up
+/// to and including `until`
+async fn shipped_since<C: ConnectionTrait>(
+    db: &C,
+    mid: i32,
+    marketplace: Marketplace,
+    since: Timestamp,
+    until: Timestamp,
+) -> Result<Vec<TrackingUpdate>, ChannelError> {
+    let shipments = Shipments::find()
+        .filter(::entity::shipments::Column::Mid.eq(mid))
+        .filter(::entity::shipments::Column::ShippedGmt.gt(since))
+        .filter(::entity::shipments::Column::ShippedGmt.lte(until))
+        .order_by_asc(::entity::shipments::Column::ShippedGmt)
+        .order_by_asc(::entity::shipments::Column::Id)
+        .all(db)
+        .await?;
+    if shipments.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let orders = Orders::find()
+        .filter(::entity::orders::Column::Mid.eq(mid))
+        .filter(::entity::orders::Column::Mkt.eq(marketplace.bit()))
+        .filter(::entity::orders::Column::Id.is_in(shipments.iter().map(|s| s.order_id)))
+        .all(db)
+        .await?;
+
+    Ok(shipments
+        .into_iter()
+        .filter_map(|shipment| {
+            let order = orders.iter().find(|order| order.id == shipment.order_id)?;
+            Some(TrackingUpdate {
+                external_id: order.erefid.clone()?,
+                carrier: shipment.carrier,
+                tracking_number: shipment.tracking_number,
+                shipped_gmt: shipment.shipped_gmt,
+            })
+        })
+        .collect())
+}
+
+/// Pull new orders into `channel`'s merchant, then push tracking and stock
+#[instrument(skip_all, fields(mid = channel.mid, channel = channel.id))]
+pub async fn sync_channel<C: ConnectionTrait + TransactionTrait>(
+    db: &C,
+    channel: &Channel,
+    connector: &dyn MarketplaceConnector,
+    now: Timestamp,
+) -> Result<SyncReport, ChannelError> {
+    let marketplace = connector.marketplace();
+    let mut report = SyncReport::default();
+    let mut record: ::entity::channels::ActiveModel = channel.clone().into();
+
+    let orders_since = channel.orders_synced_gmt.unwrap_or(channel.created_gmt);
+    let pulled = connector.pull_orders(orders_since).await?;
+    for order in &pulled {
+        if OrderService::import(db, channel.mid, &order.to_imported(marketplace)).await?.is_some() {
+            report.orders_imported += 1;
+        }
+    }
+    let latest = pulled.iter().map(|order| order.placed_gmt).max();
+    record.orders_synced_gmt = Set(Some(latest.map_or(orders_since, |latest| latest.max(orders_since))));
+
+    let tracking_since = channel.tracking_synced_gmt.unwrap_or(channel.created_gmt);
+    let updates = shipped_since(db, channel.mid, marketplace, tracking_since, now).await?;
+    if !updates.is_empty() {
+        connector.push_tracking(&updates).await?;
+    }
+    report.tracking_pushed = updates.len();
+    record.tracking_synced_gmt = Set(Some(now));
+
+    report.inventory_pushed = push_changed(db, channel, connector, now).await?;
+    record.inventory_synced_gmt = Set(Some(now));
+
+    record.last_error = Set(None);
+    record.modified_gmt = Set(now);
+    record.update(db).await?;
+
+    Ok(report)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::amazon::Feed;
+    use crate::tests::channel;
+    use crate::{AmazonConnector, ChannelOrder, ChannelOrderLine, InventoryLevel};
+    use rust_decimal::Decimal;
+    use ::entity::prelude::{Order, OrderItem, Shipment, Sku};
+
+    pub(crate) fn order(id: i32, erefid: Option<&str>) -> Order {
+        Order {
+            id,
+            mid: 1,
+            orderid: format!("2025-11-18-{}", id),
+            cartid: String::new(),
+            customer: 0,
+            pool: "RECENT".to_string(),
+            total: Decimal::new(1998, 2),
+            created_gmt: Timestamp::from_unix(1_700_000_100),
+            paid_gmt: None,
+            paid_txn: None,
+            order_payment_status: None,
+            order_payment_lookup: None,
+            bs_settlement: None,
+            shipped_gmt: None,
+            inv_gmt: None,
+            company_id: None,
+            review_status: None,
+            ship_method: None,
+            bill_email: "buyer@marketplace.amazon.com".to_string(),
+            v: 1,
+            mkt: Marketplace::Amazon.bit(),
+            erefid: erefid.map(str::to_string),
+            mkt_bitstr: String::new(),
+            pickup_location_id: None,
+            pickup_ready_gmt: None,
+            picked_up_gmt: None,
+            cancelled_gmt: None,
+            cancel_reason: None,
+        }
+    }
+
+    fn shipment(order_id: i32) -> Shipment {
+        Shipment {
+            id: 5,
+            mid: 1,
+            order_id,
+            carrier: "UPS".to_string(),
+            tracking_number: "1Z999".to_string(),
+            shipped_gmt: Timestamp::from_unix(1_700_000_500),
+            label_provider: None,
+            label_id: None,
+            label_url: None,
+            label_cost: None,
+        }
+    }
+
+    fn sku() -> Sku {
+        Sku {
+            id: 1,
+            pid: 1,
+            mid: 1,
+            sku: "SKU001".to_string(),
+            title: "Widget".to_string(),
+            price: Decimal::new(999, 2),
+            cost: Decimal::ZERO,
+            upc: String::new(),
+            inv_available: 7,
+            qty_onshelf: 7,
+            weight: Decimal::ZERO,
+            reorder_point: None,
+            reorder_quantity: 0,
+            low_stock_gmt: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sync_imports_new_orders_and_pushes_tracking_and_stock() {
+        let settings = r#"{"seller_id":"A1B2C3","marketplace_id":"ATVPDKIKX0DER"}"#;
+        let channel = channel(settings);
+        let pulled = ChannelOrder {
+            external_id: "111-2".to_string(),
+            buyer_email: "buyer@marketplace.amazon.com".to_string(),
+            placed_gmt: Timestamp::from_unix(1_700_000_100),
+            paid_gmt: None,
+            lines: vec![ChannelOrderLine {
+                sku: "SKU001".to_string(),
+                title: "Widget".to_string(),
+                quantity: 2,
+                unit_price: Decimal::new(999, 2),
+            }],
+        };
+        let amazon = AmazonConnector::from_settings(&serde_json::from_str(settings).unwrap())
+            .unwrap()
+            .with_orders(vec![pulled]);
+
+        let item = OrderItem {
+            id: 1,
+            mid: 1,
+            order_id: 2,
+            sku: "SKU001".to_string(),
+            product_name: "Widget".to_string(),
+            quantity: 2,
+            unit_price: Decimal::new(999, 2),
+            options: String::new(),
+        };
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            // Import: not pulled before, insert the order and its item, then
+            // tag it with the marketplace
+            .append_query_results([Vec::<Order>::new()])
+            .append_query_results([vec![order(2, None)]])
+            .append_query_results([vec![item]])
+            .append_query_results([vec![order(2, Some("111-2"))]])
+            .append_exec_results([MockExecResult { last_insert_id: 1, rows_affected: 1 }])
+            // Tracking for an earlier marketplace order
+            .append_query_results([vec![shipment(1)]])
+            .append_query_results([vec![order(1, Some("111-1"))]])
+            .append_query_results([vec![sku()]])
+            .append_query_results([vec![channel.clone()]])
+            .into_connection();
+
+        let now = Timestamp::from_unix(1_700_001_000);
+        let report = sync_channel(&db, &channel, &amazon, now).await.unwrap();
+        assert_eq!(report, SyncReport { orders_imported: 1, tracking_pushed: 1, inventory_pushed: 1 });
+
+        assert_eq!(
+            amazon.feeds(),
+            vec![
+                Feed::Tracking(vec![TrackingUpdate {
+                    external_id: "111-1".to_string(),
+                    carrier: "UPS".to_string(),
+                    tracking_number: "1Z999".to_string(),
+                    shipped_gmt: Timestamp::from_unix(1_700_000_500),
+                }]),
+                Feed::Inventory(vec![InventoryLevel { sku: "SKU001".to_string(), quantity: 7 }]),
+            ]
+        );
+
+        let log = db.into_transaction_log();
+        let tagged = log.iter().flat_map(|t| t.statements()).map(|s| s.to_string()).find(|s| s.contains("\"order_erefid\" = '111-2'"));
+        assert!(tagged.is_some_and(|s| s.contains("\"mkt\" = 1")), "{:?}", log);
+        let synced = log.last().unwrap().statements()[0].to_string();
+        assert!(synced.contains(r#"UPDATE "channels""#), "{}", synced);
+        assert!(synced.contains(r#""orders_synced_gmt" = 1700000100"#), "{}", synced);
+        assert!(synced.contains(r#""tracking_synced_gmt" = 1700001000"#), "{}", synced);
+    }
+}
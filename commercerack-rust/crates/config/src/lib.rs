@@ -0,0 +1,791 @@
+//! Application configuration
+//!
+//! [`AppConfig::load`] layers these sources, later ones overriding earlier:
+//!
+//! 1. built-in defaults
+//! 2. a TOML file, `$COMMERCERACK_CONFIG` or else `commercerack.toml` in the
+//!    working directory; skipped when missing
+//! 3. the environment variables the services read before this crate existed
+//!    (`DATABASE_URL`, `JWT_SECRET`, `CART_STORAGE`, ...), see [`LEGACY_ENV`]
+//! 4. `COMMERCERACK_` variables naming any key with `__` between sections,
+//!    e.g. `COMMERCERACK_JWT__ACCESS_TOKEN_TTL_MINUTES=30`
+//!
+//! The result is validated so a bad deployment fails at startup rather than
+//! on the first request that needs the broken setting.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::NaiveDate;
+use figment::providers::{Env, Format, Toml};
+use figment::Figment;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// Variable naming the TOML file to read
+pub const CONFIG_FILE_VAR: &str = "COMMERCERACK_CONFIG";
+
+/// File read when `COMMERCERACK_CONFIG` is unset
+pub const DEFAULT_CONFIG_FILE: &str = "commercerack.toml";
+
+const ENV_PREFIX: &str = "COMMERCERACK_";
+
+/// Shortest accepted JWT signing secret, in bytes
+pub const MIN_JWT_SECRET_LEN: usize = 32;
+
+/// Bytes in a PII encryption key
+pub const ENCRYPTION_KEY_LEN: usize = 32;
+
+/// Unprefixed environment variables still honoured, and the key each sets
+pub const LEGACY_ENV: &[(&str, &str)] = &[
+    ("DATABASE_URL", "database.url"),
+    ("JWT_SECRET", "jwt.secret"),
+    ("CART_STORAGE", "cart.backend"),
+    ("REDIS_URL", "cart.redis_url"),
+    ("PAYMENT_GATEWAY", "payments.gateway"),
+    ("STRIPE_SECRET_KEY", "payments.stripe.secret_key"),
+    ("STRIPE_API_BASE", "payments.stripe.api_base"),
+    ("PAYPAL_CLIENT_ID", "payments.paypal.client_id"),
+    ("PAYPAL_CLIENT_SECRET", "payments.paypal.client_secret"),
+    ("PAYPAL_API_BASE", "payments.paypal.api_base"),
+    ("PAYPAL_WEBHOOK_ID", "payments.paypal.webhook_id"),
+    ("TAX_PROVIDER", "tax.provider"),
+];
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("Could not read configuration: {0}")]
+    Load(#[from] Box<figment::Error>),
+
+    #[error("Invalid configuration: {}", .0.join("; "))]
+    Invalid(Vec<String>),
+}
+
+/// Everything the API needs to start
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct AppConfig {
+    pub database: DatabaseConfig,
+    pub jwt: JwtConfig,
+    pub cart: CartConfig,
+    pub orders: OrdersConfig,
+    pub archive: ArchiveConfig,
+    pub catalog_cache: CatalogCacheConfig,
+    pub inventory: InventoryConfig,
+    pub money: MoneyConfig,
+    pub digital: DigitalConfig,
+    pub payments: PaymentsConfig,
+    pub tax: TaxConfig,
+    pub shipping: ShippingConfig,
+    pub cors: CorsConfig,
+    pub api: ApiConfig,
+    pub encryption: EncryptionConfig,
+    pub two_factor: TwoFactorConfig,
+    pub oauth: OAuthConfig,
+}
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct DatabaseConfig {
+    /// `postgres://` connection URL; required
+    pub url: String,
+    /// Apply pending migrations when the API starts. For development;
+    /// deployments run `migrate up` as a separate step.
+    pub auto_migrate: bool,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default)]
+pub struct JwtConfig {
+    /// Access token signing secret; required, at least
+    /// [`MIN_JWT_SECRET_LEN`] bytes
+    pub secret: String,
+    /// Lifetime of access tokens issued at login and refresh
+    pub access_token_ttl_minutes: i64,
+}
+
+impl Default for JwtConfig {
+    fn default() -> Self {
+        Self {
+            secret: String::new(),
+            access_token_ttl_minutes: 15,
+        }
+    }
+}
+
+/// Where carts are kept
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CartBackend {
+    #[default]
+    Database,
+    Redis,
+    /// In-process; for tests and local development
+    Memory,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default)]
+pub struct CartConfig {
+    pub backend: CartBackend,
+    /// Used by the `redis` backend
+    pub redis_url: String,
+    /// Hours without changes before a `database` cart with a contact is
+    /// archived as abandoned; 0 turns detection off. Redis carts are
+    /// archived when their TTL lapses instead.
+    pub abandoned_after_hours: i64,
+    /// Added to the unit price of items the shopper has gift wrapped
+    pub gift_wrap_surcharge: Decimal,
+}
+
+impl Default for CartConfig {
+    fn default() -> Self {
+        Self {
+            backend: CartBackend::default(),
+            redis_url: "redis://127.0.0.1/".to_string(),
+            abandoned_after_hours: 24,
+            gift_wrap_surcharge: Decimal::ZERO,
+        }
+    }
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default)]
+pub struct OrdersConfig {
+    /// Minutes within which a second order from the same customer with the
+    /// same items and total is held for review as a likely duplicate; 0
+    /// turns detection off
+    pub duplicate_window_minutes: i64,
+}
+
+impl Default for OrdersConfig {
+    fn default() -> Self {
+        Self { duplicate_window_minutes: 10 }
+    }
+}
+
+/// Cold storage of long archived orders in an S3-compatible bucket; off
+/// until `bucket` is set
+#[derive(Clone, Deserialize)]
+#[serde(default)]
+pub struct ArchiveConfig {
+    /// The object store's URL, e.g. `https://s3.eu-west-1.amazonaws.com`
+    /// or a MinIO host. Buckets are addressed by path.
+    pub endpoint: String,
+    pub region: String,
+    pub bucket: Option<String>,
+    pub access_key_id: Option<String>,
+    pub secret_access_key: Option<String>,
+    /// Days an order stays in the ARCHIVE pool before it moves to the
+    /// bucket
+    pub cold_after_days: i64,
+}
+
+impl Default for ArchiveConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: "https://s3.amazonaws.com".to_string(),
+            region: "us-east-1".to_string(),
+            bucket: None,
+            access_key_id: None,
+            secret_access_key: None,
+            cold_after_days: 365,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CatalogCacheBackend {
+    /// Every catalog read goes to the database
+    Off,
+    /// In each API instance; another instance's changes show once entries
+    /// expire
+    #[default]
+    Memory,
+    /// Shared by every API instance
+    Redis,
+}
+
+/// Cache of public product and category responses
+#[derive(Clone, Deserialize)]
+#[serde(default)]
+pub struct CatalogCacheConfig {
+    pub backend: CatalogCacheBackend,
+    /// Used by the `redis` backend
+    pub redis_url: String,
+    /// Seconds a cached response is served for when no change event
+    /// drops it sooner
+    pub ttl_seconds: u64,
+    /// Responses kept by the `memory` backend
+    pub max_entries: u64,
+}
+
+impl Default for CatalogCacheConfig {
+    fn default() -> Self {
+        Self {
+            backend: CatalogCacheBackend::default(),
+            redis_url: "redis://127.0.0.1/".to_string(),
+            ttl_seconds: 60,
+            max_entries: 10_000,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AllocationRule {
+    /// Warehouses closest to where the order ships
+    #[default]
+    Nearest,
+    /// Warehouses holding the most stock of the SKU
+    MostStock,
+}
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct InventoryConfig {
+    /// Which warehouses an order's stock is taken from first
+    pub allocation: AllocationRule,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoundingRule {
+    /// Halves round to the even digit
+    #[default]
+    Bankers,
+    /// Halves round away from zero
+    HalfUp,
+}
+
+/// The default currency and rounding
+#[derive(Clone, Deserialize)]
+#[serde(default)]
+pub struct MoneyConfig {
+    /// ISO 4217 code of merchants that have not set a currency of their own
+    pub currency: String,
+    /// How amounts are rounded to the currency's minor unit
+    pub rounding: RoundingRule,
+}
+
+impl Default for MoneyConfig {
+    fn default() -> Self {
+        Self {
+            currency: "USD".to_string(),
+            rounding: RoundingRule::default(),
+        }
+    }
+}
+
+/// Download links of digital products
+#[derive(Clone, Deserialize)]
+#[serde(default)]
+pub struct DigitalConfig {
+    /// Hours a download link works after the order is paid
+    pub download_ttl_hours: i64,
+    /// Downloads per purchase for products that don't set their own limit
+    pub download_limit: i32,
+}
+
+impl Default for DigitalConfig {
+    fn default() -> Self {
+        Self {
+            download_ttl_hours: 72,
+            download_limit: 5,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PaymentProvider {
+    #[default]
+    Stripe,
+    PayPal,
+}
+
+/// Payments are disabled when the chosen gateway has no credentials
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PaymentsConfig {
+    pub gateway: PaymentProvider,
+    pub stripe: StripeConfig,
+    pub paypal: PayPalConfig,
+}
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct StripeConfig {
+    pub secret_key: Option<String>,
+    /// Overrides the Stripe API host (stripe-mock, proxies)
+    pub api_base: Option<String>,
+}
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PayPalConfig {
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+    /// Overrides the PayPal API host, e.g. the sandbox
+    pub api_base: Option<String>,
+    /// Enables webhook signature verification
+    pub webhook_id: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaxProvider {
+    #[default]
+    RateTable,
+    /// Orders are not taxed
+    None,
+}
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TaxConfig {
+    pub provider: TaxProvider,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LabelBackend {
+    /// Labels are not bought; shipments carry the tracking number staff enter
+    #[default]
+    None,
+    /// Fake labels for development, see `commercerack_shipping::StubLabelProvider`
+    Stub,
+}
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ShippingConfig {
+    /// Service shipping labels are bought from
+    pub labels: LabelBackend,
+}
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct CorsConfig {
+    /// Origins browsers may call the API from, e.g. `https://shop.example`,
+    /// or `*` for any. Empty allows no cross-origin calls.
+    pub allowed_origins: Vec<String>,
+}
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ApiConfig {
+    /// Day (`YYYY-MM-DD`, UTC) the unversioned `/api/...` aliases of v1 go
+    /// away, announced in their `Sunset` header. None until one is chosen.
+    pub unversioned_sunset: Option<NaiveDate>,
+    /// Take the client IP from the last `X-Forwarded-For` entry. Only for
+    /// deployments behind one proxy that appends to it; otherwise clients
+    /// could pick their IP.
+    pub trust_forwarded_for: bool,
+}
+
+/// Encryption of customer PII at rest; off until `active_key` is set
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct EncryptionConfig {
+    /// Id of the key new values are encrypted with
+    pub active_key: Option<String>,
+    /// Base64 of [`ENCRYPTION_KEY_LEN`] random bytes by key id. Retired
+    /// keys stay listed until stored values have been re-encrypted.
+    pub keys: BTreeMap<String, String>,
+    /// Encrypt customer emails too; email search then matches whole emails
+    pub encrypt_email: bool,
+}
+
+impl EncryptionConfig {
+    /// The key named `id`, decoded
+    pub fn key(&self, id: &str) -> Option<[u8; ENCRYPTION_KEY_LEN]> {
+        let bytes = STANDARD.decode(self.keys.get(id)?.trim()).ok()?;
+        bytes.try_into().ok()
+    }
+}
+
+/// TOTP two-factor authentication
+#[derive(Clone, Deserialize)]
+#[serde(default)]
+pub struct TwoFactorConfig {
+    /// Name authenticator apps show next to the account
+    pub issuer: String,
+    /// Merchants whose admins must have passed two-factor at login to use
+    /// their admin role
+    pub required_for_admins: Vec<i32>,
+}
+
+impl Default for TwoFactorConfig {
+    fn default() -> Self {
+        Self {
+            issuer: "CommerceRack".to_string(),
+            required_for_admins: Vec::new(),
+        }
+    }
+}
+
+impl TwoFactorConfig {
+    pub fn required_for_admins_of(&self, mid: i32) -> bool {
+        self.required_for_admins.contains(&mid)
+    }
+}
+
+/// Sign-in with external providers. Each is off until its client is set;
+/// logins return to a storefront listed in `cors.allowed_origins`.
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct OAuthConfig {
+    pub google: GoogleOAuthConfig,
+    pub apple: AppleOAuthConfig,
+}
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct GoogleOAuthConfig {
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+    /// This API's `/api/auth/oauth/google/callback` URL, as registered
+    /// with Google
+    pub redirect_uri: Option<String>,
+}
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct AppleOAuthConfig {
+    /// The Services ID
+    pub client_id: Option<String>,
+    pub team_id: Option<String>,
+    /// Id of the Sign in with Apple key
+    pub key_id: Option<String>,
+    /// The key's PKCS#8 PEM, which signs the client secret
+    pub private_key: Option<String>,
+    /// This API's `/api/auth/oauth/apple/callback` URL, as registered
+    /// with Apple
+    pub redirect_uri: Option<String>,
+}
+
+impl AppConfig {
+    /// Read and validate the configuration from the file and environment
+    pub fn load() -> Result<Self, ConfigError> {
+        let file = std::env::var(CONFIG_FILE_VAR).unwrap_or_else(|_| DEFAULT_CONFIG_FILE.to_string());
+        let config: Self = Self::figment(&file).extract().map_err(Box::new)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// The layered sources, reading `file` as the TOML layer
+    pub fn figment(file: &str) -> Figment {
+        let legacy = Env::raw().filter_map(|key| {
+            LEGACY_ENV
+                .iter()
+                .find(|(name, _)| key.as_str().eq_ignore_ascii_case(name))
+                .map(|(_, path)| (*path).into())
+        });
+
+        Figment::new()
+            .merge(Toml::file(file))
+            .merge(legacy)
+            .merge(Env::prefixed(ENV_PREFIX).ignore(&["config"]).split("__"))
+    }
+
+    /// Check every setting, reporting all problems at once
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+
+        let url = &self.database.url;
+        if url.is_empty() {
+            problems.push("database.url is required".to_string());
+        } else if !(url.starts_with("postgres://") || url.starts_with("postgresql://")) {
+            problems.push("database.url must be a postgres:// URL".to_string());
+        }
+
+        if self.jwt.secret.len() < MIN_JWT_SECRET_LEN {
+            problems.push(format!("jwt.secret must be at least {} bytes", MIN_JWT_SECRET_LEN));
+        }
+        if self.jwt.access_token_ttl_minutes <= 0 {
+            problems.push("jwt.access_token_ttl_minutes must be positive".to_string());
+        }
+
+        if self.cart.backend == CartBackend::Redis
+            && !(self.cart.redis_url.starts_with("redis://") || self.cart.redis_url.starts_with("rediss://"))
+        {
+            problems.push("cart.redis_url must be a redis:// or rediss:// URL".to_string());
+        }
+
+        if self.cart.abandoned_after_hours < 0 {
+            problems.push("cart.abandoned_after_hours must not be negative".to_string());
+        }
+        if self.cart.gift_wrap_surcharge.is_sign_negative() {
+            problems.push("cart.gift_wrap_surcharge must not be negative".to_string());
+        }
+        if self.orders.duplicate_window_minutes < 0 {
+            problems.push("orders.duplicate_window_minutes must not be negative".to_string());
+        }
+        let currency = self.money.currency.trim();
+        if currency.len() != 3 || !currency.bytes().all(|b| b.is_ascii_alphabetic()) {
+            problems.push("money.currency must be a three-letter code like USD".to_string());
+        }
+        let archive = &self.archive;
+        if archive.bucket.is_some() && (archive.access_key_id.is_none() || archive.secret_access_key.is_none()) {
+            problems.push("archive.bucket needs archive.access_key_id and secret_access_key".to_string());
+        }
+        if !(archive.endpoint.starts_with("http://") || archive.endpoint.starts_with("https://")) {
+            problems.push("archive.endpoint must be an http:// or https:// URL".to_string());
+        }
+        if archive.cold_after_days <= 0 {
+            problems.push("archive.cold_after_days must be positive".to_string());
+        }
+        let catalog_cache = &self.catalog_cache;
+        if catalog_cache.backend == CatalogCacheBackend::Redis
+            && !(catalog_cache.redis_url.starts_with("redis://") || catalog_cache.redis_url.starts_with("rediss://"))
+        {
+            problems.push("catalog_cache.redis_url must be a redis:// or rediss:// URL".to_string());
+        }
+        if catalog_cache.backend != CatalogCacheBackend::Off && catalog_cache.ttl_seconds == 0 {
+            problems.push("catalog_cache.ttl_seconds must be positive".to_string());
+        }
+        if self.digital.download_ttl_hours <= 0 {
+            problems.push("digital.download_ttl_hours must be positive".to_string());
+        }
+        if self.digital.download_limit <= 0 {
+            problems.push("digital.download_limit must be positive".to_string());
+        }
+
+        let paypal = &self.payments.paypal;
+        if paypal.client_id.is_some() != paypal.client_secret.is_some() {
+            problems.push("payments.paypal.client_id and client_secret must be set together".to_string());
+        }
+
+        let origins = &self.cors.allowed_origins;
+        if origins.len() > 1 && origins.iter().any(|origin| origin == "*") {
+            problems.push("cors.allowed_origins cannot combine * with other origins".to_string());
+        }
+        for origin in origins.iter().filter(|origin| *origin != "*") {
+            let scheme = origin.starts_with("http://") || origin.starts_with("https://");
+            if !scheme || origin.ends_with('/') || origin.chars().any(|c| c.is_whitespace() || c.is_control()) {
+                problems.push(format!(
+                    "cors.allowed_origins entry {:?} must be a scheme and host like https://shop.example",
+                    origin
+                ));
+            }
+        }
+
+        let encryption = &self.encryption;
+        for id in encryption.keys.keys() {
+            if id.len() > 16 || !id.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-') {
+                problems.push(format!("encryption.keys id {:?} must be up to 16 letters, digits, - or _", id));
+            }
+            if encryption.key(id).is_none() {
+                problems.push(format!("encryption.keys.{} must be base64 of {} bytes", id, ENCRYPTION_KEY_LEN));
+            }
+        }
+        match &encryption.active_key {
+            Some(active) if !encryption.keys.contains_key(active) => {
+                problems.push(format!("encryption.active_key {} is not in encryption.keys", active));
+            }
+            None if encryption.encrypt_email => {
+                problems.push("encryption.encrypt_email needs encryption.active_key".to_string());
+            }
+            _ => {}
+        }
+
+        if self.two_factor.issuer.trim().is_empty() || self.two_factor.issuer.contains(':') {
+            problems.push("two_factor.issuer must be set and cannot contain ':'".to_string());
+        }
+
+        let google = &self.oauth.google;
+        let set = [&google.client_id, &google.client_secret, &google.redirect_uri].map(Option::is_some);
+        if set.contains(&true) && set.contains(&false) {
+            problems.push("oauth.google.client_id, client_secret and redirect_uri must be set together".to_string());
+        }
+        let apple = &self.oauth.apple;
+        let set = [&apple.client_id, &apple.team_id, &apple.key_id, &apple.private_key, &apple.redirect_uri]
+            .map(Option::is_some);
+        if set.contains(&true) && set.contains(&false) {
+            problems.push("oauth.apple.client_id, team_id, key_id, private_key and redirect_uri must be set together".to_string());
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(problems))
+        }
+    }
+}
+
+#[cfg(test)]
+// Jail closures must return figment's own (large) error type
+#[allow(clippy::result_large_err)]
+mod tests {
+    use super::*;
+    use figment::Jail;
+
+    const SECRET: &str = "0123456789abcdef0123456789abcdef";
+    /// Base64 of 32 bytes of 7
+    const KEY: &str = "BwcHBwcHBwcHBwcHBwcHBwcHBwcHBwcHBwcHBwcHBwc=";
+
+    #[test]
+    fn test_layers_override_in_order() {
+        Jail::expect_with(|jail| {
+            jail.clear_env();
+            jail.create_file(
+                DEFAULT_CONFIG_FILE,
+                r#"
+                [database]
+                url = "postgres://file/commercerack"
+
+                [jwt]
+                secret = "from-the-file-but-overridden-by-env"
+                access_token_ttl_minutes = 20
+
+                [cart]
+                backend = "redis"
+                redis_url = "redis://file/"
+
+                [cors]
+                allowed_origins = ["https://shop.example"]
+
+                [two_factor]
+                required_for_admins = [7]
+                "#,
+            )?;
+            jail.set_env("JWT_SECRET", SECRET);
+            jail.set_env("REDIS_URL", "redis://legacy/");
+            jail.set_env("COMMERCERACK_CART__REDIS_URL", "redis://prefixed/");
+            jail.set_env("COMMERCERACK_TAX__PROVIDER", "none");
+            jail.set_env("COMMERCERACK_SHIPPING__LABELS", "stub");
+            jail.set_env("COMMERCERACK_DATABASE__AUTO_MIGRATE", "true");
+            jail.set_env("COMMERCERACK_ORDERS__DUPLICATE_WINDOW_MINUTES", "0");
+            jail.set_env("COMMERCERACK_INVENTORY__ALLOCATION", "most_stock");
+            jail.set_env("COMMERCERACK_DIGITAL__DOWNLOAD_LIMIT", "3");
+            jail.set_env("COMMERCERACK_MONEY__CURRENCY", "EUR");
+            jail.set_env("COMMERCERACK_MONEY__ROUNDING", "half_up");
+            jail.set_env("COMMERCERACK_API__UNVERSIONED_SUNSET", "2027-06-30");
+            jail.set_env("COMMERCERACK_ENCRYPTION__ACTIVE_KEY", "k1");
+            jail.set_env("COMMERCERACK_ENCRYPTION__KEYS__K1", KEY);
+            jail.set_env("COMMERCERACK_OAUTH__GOOGLE__CLIENT_ID", "google-client");
+            jail.set_env("COMMERCERACK_OAUTH__GOOGLE__CLIENT_SECRET", "google-secret");
+            jail.set_env("COMMERCERACK_OAUTH__GOOGLE__REDIRECT_URI", "https://api.example/api/auth/oauth/google/callback");
+
+            let config = AppConfig::load().expect("valid configuration");
+            assert_eq!(config.database.url, "postgres://file/commercerack");
+            assert_eq!(config.jwt.secret, SECRET);
+            assert_eq!(config.jwt.access_token_ttl_minutes, 20);
+            assert_eq!(config.cart.backend, CartBackend::Redis);
+            assert_eq!(config.cart.redis_url, "redis://prefixed/");
+            assert_eq!(config.tax.provider, TaxProvider::None);
+            assert_eq!(config.shipping.labels, LabelBackend::Stub);
+            assert!(config.database.auto_migrate);
+            assert_eq!(config.orders.duplicate_window_minutes, 0);
+            assert_eq!(config.inventory.allocation, AllocationRule::MostStock);
+            assert_eq!(config.digital.download_limit, 3);
+            assert_eq!(config.money.currency, "EUR");
+            assert_eq!(config.money.rounding, RoundingRule::HalfUp);
+            assert_eq!(config.cors.allowed_origins, vec!["https://shop.example"]);
+            assert_eq!(config.api.unversioned_sunset, NaiveDate::from_ymd_opt(2027, 6, 30));
+            assert_eq!(config.encryption.active_key.as_deref(), Some("k1"));
+            assert_eq!(config.encryption.key("k1"), Some([7; ENCRYPTION_KEY_LEN]));
+            assert!(config.two_factor.required_for_admins_of(7));
+            assert!(!config.two_factor.required_for_admins_of(8));
+            assert_eq!(config.oauth.google.client_id.as_deref(), Some("google-client"));
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_defaults_fill_what_is_not_set() {
+        Jail::expect_with(|jail| {
+            jail.clear_env();
+            jail.set_env("DATABASE_URL", "postgresql://localhost/commercerack");
+            jail.set_env("JWT_SECRET", SECRET);
+            jail.set_env("PAYMENT_GATEWAY", "paypal");
+            jail.set_env("PAYPAL_CLIENT_ID", "client");
+            jail.set_env("PAYPAL_CLIENT_SECRET", "secret");
+
+            let config = AppConfig::load().expect("valid configuration");
+            assert_eq!(config.jwt.access_token_ttl_minutes, 15);
+            assert!(!config.database.auto_migrate);
+            assert_eq!(config.cart.backend, CartBackend::Database);
+            assert_eq!(config.cart.abandoned_after_hours, 24);
+            assert_eq!(config.cart.gift_wrap_surcharge, Decimal::ZERO);
+            assert_eq!(config.orders.duplicate_window_minutes, 10);
+            assert!(config.archive.bucket.is_none());
+            assert_eq!(config.archive.cold_after_days, 365);
+            assert_eq!(config.catalog_cache.backend, CatalogCacheBackend::Memory);
+            assert_eq!(config.catalog_cache.ttl_seconds, 60);
+            assert_eq!(config.inventory.allocation, AllocationRule::Nearest);
+            assert_eq!(config.money.currency, "USD");
+            assert_eq!(config.money.rounding, RoundingRule::Bankers);
+            assert_eq!(config.digital.download_ttl_hours, 72);
+            assert_eq!(config.digital.download_limit, 5);
+            assert_eq!(config.payments.gateway, PaymentProvider::PayPal);
+            assert_eq!(config.payments.paypal.client_id.as_deref(), Some("client"));
+            assert!(config.payments.stripe.secret_key.is_none());
+            assert!(config.cors.allowed_origins.is_empty());
+            assert!(config.api.unversioned_sunset.is_none());
+            assert!(config.encryption.active_key.is_none());
+            assert_eq!(config.two_factor.issuer, "CommerceRack");
+            assert!(config.two_factor.required_for_admins.is_empty());
+            assert!(config.oauth.apple.client_id.is_none());
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_file_named_by_variable() {
+        Jail::expect_with(|jail| {
+            jail.clear_env();
+            jail.create_file("prod.toml", "[database]\nurl = \"postgres://prod/commercerack\"")?;
+            jail.set_env(CONFIG_FILE_VAR, "prod.toml");
+            jail.set_env("COMMERCERACK_JWT__SECRET", SECRET);
+
+            let config = AppConfig::load().expect("valid configuration");
+            assert_eq!(config.database.url, "postgres://prod/commercerack");
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_validation_reports_every_problem() {
+        let mut config = AppConfig::default();
+        config.jwt.access_token_ttl_minutes = 0;
+        config.cart.backend = CartBackend::Redis;
+        config.cart.redis_url = "localhost:6379".to_string();
+        config.cart.abandoned_after_hours = -1;
+        config.cart.gift_wrap_surcharge = Decimal::new(-100, 2);
+        config.orders.duplicate_window_minutes = -5;
+        config.archive.bucket = Some("orders".to_string());
+        config.archive.cold_after_days = 0;
+        config.catalog_cache.backend = CatalogCacheBackend::Redis;
+        config.catalog_cache.redis_url = "localhost:6379".to_string();
+        config.catalog_cache.ttl_seconds = 0;
+        config.digital.download_ttl_hours = 0;
+        config.money.currency = "dollars".to_string();
+        config.payments.paypal.client_id = Some("client".to_string());
+        config.cors.allowed_origins = vec!["*".to_string(), "shop.example/".to_string()];
+        config.encryption.active_key = Some("k2".to_string());
+        config.encryption.keys.insert("k1".to_string(), "c2hvcnQ=".to_string());
+        config.two_factor.issuer = "Acme:Shop".to_string();
+        config.oauth.google.client_id = Some("client".to_string());
+
+        let Err(ConfigError::Invalid(problems)) = config.validate() else {
+            panic!("expected validation to fail");
+        };
+        assert_eq!(problems.len(), 20, "{:?}", problems);
+        assert!(problems.contains(&"database.url is required".to_string()));
+        assert!(problems.contains(&format!("jwt.secret must be at least {} bytes", MIN_JWT_SECRET_LEN)));
+    }
+
+    #[test]
+    fn test_unknown_backend_is_rejected() {
+        Jail::expect_with(|jail| {
+            jail.clear_env();
+            jail.set_env("CART_STORAGE", "memcached");
+            assert!(AppConfig::figment(DEFAULT_CONFIG_FILE).extract::<AppConfig>().is_err());
+            Ok(())
+        });
+    }
+}
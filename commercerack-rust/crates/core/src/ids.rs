@@ -0,0 +1,99 @@
+//! Typed identifiers
+//!
+//! Merchant, customer and order ids are all `i32` in the database, which
+//! makes it easy to pass one where another belongs. These wrappers keep
+//! them apart at compile time while storing and serializing as the plain
+//! number.
+
+use sea_orm::DeriveValueType;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+macro_rules! id {
+    ($(#[$meta:meta])* $name:ident) => {
+        $(#[$meta])*
+        #[derive(
+            Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, DeriveValueType,
+        )]
+        #[serde(transparent)]
+        pub struct $name(i32);
+
+        impl $name {
+            pub const fn new(id: i32) -> Self {
+                Self(id)
+            }
+
+            pub const fn get(self) -> i32 {
+                self.0
+            }
+        }
+
+        impl From<i32> for $name {
+            fn from(id: i32) -> Self {
+                Self(id)
+            }
+        }
+
+        impl From<$name> for i32 {
+            fn from(id: $name) -> Self {
+                id.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.0.fmt(f)
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = ParseIntError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                s.parse().map(Self)
+            }
+        }
+    };
+}
+
+id! {
+    /// A merchant (store) id, the `mid` column
+    MerchantId
+}
+
+id! {
+    /// A customer id, the `cid` column; 0 is a guest
+    CustomerId
+}
+
+id! {
+    /// An order's row id, as opposed to its `orderid` reference
+    OrderId
+}
+
+impl CustomerId {
+    /// Orders placed without an account
+    pub const GUEST: CustomerId = CustomerId(0);
+
+    pub fn is_guest(self) -> bool {
+        self == Self::GUEST
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ids_round_trip() {
+        let mid: MerchantId = "7".parse().unwrap();
+        assert_eq!(mid, MerchantId::new(7));
+        assert_eq!(i32::from(mid), 7);
+        assert_eq!(serde_json::to_string(&CustomerId::new(42)).unwrap(), "42");
+        assert_eq!(serde_json::from_str::<OrderId>("9").unwrap().get(), 9);
+        assert!(CustomerId::new(0).is_guest());
+        assert!("x".parse::<MerchantId>().is_err());
+    }
+}
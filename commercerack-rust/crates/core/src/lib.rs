@@ -1,14 +1,9 @@
-pub fn add(left: u64, right: u64) -> u64 {
-    left + right
-}
+//! Types shared by every CommerceRack crate
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+pub mod ids;
+pub mod money;
+pub mod time;
 
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
-    }
-}
+pub use ids::{CustomerId, MerchantId, OrderId};
+pub use money::{Currency, Money, MoneyError, MoneySettings, Rounding};
+pub use time::Timestamp;
@@ -0,0 +1,293 @@
+//! Amounts of money that know their currency
+//!
+//! A [`Money`] is a decimal amount and a [`Currency`]. Arithmetic is
+//! checked: adding amounts in different currencies, or overflowing, is a
+//! [`MoneyError`] rather than a wrong total. [`Money::round`] rounds to the
+//! currency's minor unit (cents, or whole yen) the way the store is
+//! configured to, banker's rounding unless [`configure`]d otherwise.
+//!
+//! Each merchant sells in its own currency, its `currency` setting. Stored
+//! amounts, SKU and product prices, cart lines, order totals and items,
+//! stay bare decimals in that currency; a `Money` is built from one where
+//! the currency matters, e.g. to round it or to send it to a payment
+//! gateway, with the currency handed in by whoever knows the merchant.
+//! [`default_currency`] is only what merchants that never set one sell in.
+
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum MoneyError {
+    #[error("Invalid currency code {0:?}: use three letters, e.g. USD")]
+    InvalidCurrency(String),
+
+    #[error("Cannot combine {0} and {1} amounts")]
+    CurrencyMismatch(Currency, Currency),
+
+    #[error("Amount out of range")]
+    Overflow,
+}
+
+/// ISO 4217 currency code, e.g. `USD`
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Currency([u8; 3]);
+
+impl Currency {
+    pub const USD: Currency = Currency(*b"USD");
+    pub const EUR: Currency = Currency(*b"EUR");
+    pub const GBP: Currency = Currency(*b"GBP");
+    pub const JPY: Currency = Currency(*b"JPY");
+
+    /// Parse a three-letter code in either case
+    pub fn new(code: &str) -> Result<Self, MoneyError> {
+        let code = code.trim();
+        match <[u8; 3]>::try_from(code.as_bytes()) {
+            Ok(bytes) if bytes.iter().all(u8::is_ascii_alphabetic) => Ok(Self(bytes.map(|b| b.to_ascii_uppercase()))),
+            _ => Err(MoneyError::InvalidCurrency(code.to_string())),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        // Only ever built from ASCII letters
+        std::str::from_utf8(&self.0).unwrap_or("XXX")
+    }
+
+    /// Digits after the decimal point in the currency's smallest unit
+    pub fn minor_units(&self) -> u32 {
+        match self.as_str() {
+            "BIF" | "CLP" | "DJF" | "GNF" | "ISK" | "JPY" | "KMF" | "KRW" | "PYG" | "RWF" | "UGX" | "UYI" | "VND"
+            | "VUV" | "XAF" | "XOF" | "XPF" => 0,
+            "BHD" | "IQD" | "JOD" | "KWD" | "LYD" | "OMR" | "TND" => 3,
+            _ => 2,
+        }
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Debug for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Currency({})", self.as_str())
+    }
+}
+
+impl FromStr for Currency {
+    type Err = MoneyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s)
+    }
+}
+
+impl Serialize for Currency {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Currency {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let code = String::deserialize(deserializer)?;
+        Self::new(&code).map_err(serde::de::Error::custom)
+    }
+}
+
+/// How amounts are rounded to a currency's minor unit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Rounding {
+    /// Halves go to the even digit, so rounding many amounts adds no bias
+    #[default]
+    Bankers,
+    /// Halves go away from zero, as most shoppers expect on a receipt
+    HalfUp,
+}
+
+impl Rounding {
+    fn strategy(self) -> RoundingStrategy {
+        match self {
+            Rounding::Bankers => RoundingStrategy::MidpointNearestEven,
+            Rounding::HalfUp => RoundingStrategy::MidpointAwayFromZero,
+        }
+    }
+}
+
+/// The default currency and the store's rounding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MoneySettings {
+    pub currency: Currency,
+    pub rounding: Rounding,
+}
+
+impl Default for MoneySettings {
+    fn default() -> Self {
+        Self {
+            currency: Currency::USD,
+            rounding: Rounding::default(),
+        }
+    }
+}
+
+static SETTINGS: OnceLock<MoneySettings> = OnceLock::new();
+
+/// Set the default currency and the rounding. Only the first call takes
+/// effect.
+pub fn configure(settings: MoneySettings) {
+    let _ = SETTINGS.set(settings);
+}
+
+fn settings() -> MoneySettings {
+    SETTINGS.get().copied().unwrap_or_default()
+}
+
+/// Currency of merchants that have not set their own
+pub fn default_currency() -> Currency {
+    settings().currency
+}
+
+/// An amount in a currency
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Money {
+    amount: Decimal,
+    currency: Currency,
+}
+
+impl Money {
+    pub fn new(amount: Decimal, currency: Currency) -> Self {
+        Self { amount, currency }
+    }
+
+    pub fn zero(currency: Currency) -> Self {
+        Self::new(Decimal::ZERO, currency)
+    }
+
+    pub fn amount(&self) -> Decimal {
+        self.amount
+    }
+
+    pub fn currency(&self) -> Currency {
+        self.currency
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.amount.is_zero()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.amount.is_sign_negative() && !self.amount.is_zero()
+    }
+
+    fn same_currency(&self, other: &Money) -> Result<(), MoneyError> {
+        if self.currency != other.currency {
+            return Err(MoneyError::CurrencyMismatch(self.currency, other.currency));
+        }
+        Ok(())
+    }
+
+    pub fn checked_add(self, other: Money) -> Result<Money, MoneyError> {
+        self.same_currency(&other)?;
+        let amount = self.amount.checked_add(other.amount).ok_or(MoneyError::Overflow)?;
+        Ok(Self::new(amount, self.currency))
+    }
+
+    pub fn checked_sub(self, other: Money) -> Result<Money, MoneyError> {
+        self.same_currency(&other)?;
+        let amount = self.amount.checked_sub(other.amount).ok_or(MoneyError::Overflow)?;
+        Ok(Self::new(amount, self.currency))
+    }
+
+    /// The amount times `factor`, e.g. a unit price times a quantity or a
+    /// tax rate
+    pub fn checked_mul(self, factor: Decimal) -> Result<Money, MoneyError> {
+        let amount = self.amount.checked_mul(factor).ok_or(MoneyError::Overflow)?;
+        Ok(Self::new(amount, self.currency))
+    }
+
+    /// The smaller of two amounts in the same currency
+    pub fn min(self, other: Money) -> Result<Money, MoneyError> {
+        self.same_currency(&other)?;
+        Ok(if other.amount < self.amount { other } else { self })
+    }
+
+    /// Sum of `amounts`, all in `currency`
+    pub fn sum(currency: Currency, amounts: impl IntoIterator<Item = Money>) -> Result<Money, MoneyError> {
+        amounts.into_iter().try_fold(Self::zero(currency), Money::checked_add)
+    }
+
+    /// Rounded to the currency's minor unit the configured way
+    pub fn round(self) -> Money {
+        self.round_with(settings().rounding)
+    }
+
+    pub fn round_with(self, rounding: Rounding) -> Money {
+        let amount = self.amount.round_dp_with_strategy(self.currency.minor_units(), rounding.strategy());
+        Self::new(amount, self.currency)
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rounded = self.round();
+        write!(f, "{:.*} {}", self.currency.minor_units() as usize, rounded.amount, self.currency)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usd(amount: i64) -> Money {
+        Money::new(Decimal::new(amount, 2), Currency::USD)
+    }
+
+    #[test]
+    fn test_currency_codes() {
+        assert_eq!(Currency::new("usd").unwrap(), Currency::USD);
+        assert_eq!(Currency::new(" EUR ").unwrap().as_str(), "EUR");
+        assert!(Currency::new("US").is_err());
+        assert!(Currency::new("U$D").is_err());
+        assert_eq!(Currency::JPY.minor_units(), 0);
+        assert_eq!(Currency::new("KWD").unwrap().minor_units(), 3);
+    }
+
+    #[test]
+    fn test_checked_arithmetic() {
+        assert_eq!(usd(1050).checked_add(usd(250)).unwrap(), usd(1300));
+        assert_eq!(usd(1050).checked_sub(usd(2000)).unwrap(), usd(-950));
+        assert_eq!(usd(250).checked_mul(Decimal::from(3)).unwrap(), usd(750));
+        assert_eq!(Money::sum(Currency::USD, [usd(100), usd(200)]).unwrap(), usd(300));
+
+        let euros = Money::new(Decimal::ONE, Currency::EUR);
+        assert_eq!(
+            usd(100).checked_add(euros),
+            Err(MoneyError::CurrencyMismatch(Currency::USD, Currency::EUR))
+        );
+        assert_eq!(Money::new(Decimal::MAX, Currency::USD).checked_add(usd(100)), Err(MoneyError::Overflow));
+    }
+
+    #[test]
+    fn test_rounding() {
+        let half = Money::new(Decimal::new(12345, 3), Currency::USD);
+        assert_eq!(half.round_with(Rounding::Bankers).amount(), Decimal::new(1234, 2));
+        assert_eq!(half.round_with(Rounding::HalfUp).amount(), Decimal::new(1235, 2));
+
+        let yen = Money::new(Decimal::new(1005, 1), Currency::JPY);
+        assert_eq!(yen.round_with(Rounding::Bankers).amount(), Decimal::from(100));
+    }
+
+    #[test]
+    fn test_serde() {
+        let json = serde_json::to_string(&usd(1999)).unwrap();
+        assert_eq!(json, r#"{"amount":"19.99","currency":"USD"}"#);
+        assert_eq!(serde_json::from_str::<Money>(&json).unwrap(), usd(1999));
+        assert!(serde_json::from_str::<Money>(r#"{"amount":"1","currency":"dollars"}"#).is_err());
+        assert_eq!(usd(500).to_string(), "5.00 USD");
+    }
+}
@@ -0,0 +1,214 @@
+//! Points in time, stored as unix seconds
+//!
+//! Every `*_gmt` column holds a [`Timestamp`]: seconds since 1970-01-01 UTC
+//! in a 64-bit integer, so dates past January 2038 (where a signed 32-bit
+//! count runs out) are representable. It serializes as the bare number of
+//! seconds, the same shape the API has always returned.
+//!
+//! Reading is lenient about the column type: `integer` (before the
+//! widening migration has run), `bigint`, and `timestamptz` all decode, so
+//! a release can roll out ahead of the migration and keep working while
+//! it runs. Writes always bind a `bigint`, which Postgres narrows on
+//! assignment to a not yet widened column.
+
+use chrono::{DateTime, Duration, Utc};
+use sea_orm::sea_query::{ArrayType, ColumnType, Nullable, ValueType, ValueTypeErr};
+use sea_orm::{ColIdx, QueryResult, TryGetError, TryGetable, Value};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::{Add, Sub};
+
+/// Seconds since the unix epoch, UTC
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Timestamp(i64);
+
+impl Timestamp {
+    pub const EPOCH: Timestamp = Timestamp(0);
+
+    pub fn now() -> Self {
+        Self(Utc::now().timestamp())
+    }
+
+    pub const fn from_unix(seconds: i64) -> Self {
+        Self(seconds)
+    }
+
+    pub const fn unix(self) -> i64 {
+        self.0
+    }
+
+    /// The same instant as a date and time; out-of-range values clamp to
+    /// the epoch
+    pub fn to_datetime(self) -> DateTime<Utc> {
+        DateTime::from_timestamp(self.0, 0).unwrap_or_default()
+    }
+
+    /// Time since `earlier`, negative if `earlier` is in fact later
+    pub fn since(self, earlier: Timestamp) -> Duration {
+        Duration::seconds(self.0 - earlier.0)
+    }
+}
+
+impl From<i64> for Timestamp {
+    fn from(seconds: i64) -> Self {
+        Self(seconds)
+    }
+}
+
+impl From<Timestamp> for i64 {
+    fn from(timestamp: Timestamp) -> Self {
+        timestamp.0
+    }
+}
+
+impl From<DateTime<Utc>> for Timestamp {
+    fn from(datetime: DateTime<Utc>) -> Self {
+        Self(datetime.timestamp())
+    }
+}
+
+impl Add<Duration> for Timestamp {
+    type Output = Timestamp;
+
+    fn add(self, duration: Duration) -> Timestamp {
+        Timestamp(self.0 + duration.num_seconds())
+    }
+}
+
+impl Sub<Duration> for Timestamp {
+    type Output = Timestamp;
+
+    fn sub(self, duration: Duration) -> Timestamp {
+        Timestamp(self.0 - duration.num_seconds())
+    }
+}
+
+impl Sub for Timestamp {
+    type Output = Duration;
+
+    fn sub(self, earlier: Timestamp) -> Duration {
+        self.since(earlier)
+    }
+}
+
+impl From<Timestamp> for Value {
+    fn from(timestamp: Timestamp) -> Self {
+        Value::BigInt(Some(timestamp.0))
+    }
+}
+
+impl TryGetable for Timestamp {
+    fn try_get_by<I: ColIdx>(res: &QueryResult, idx: I) -> Result<Self, TryGetError> {
+        // A NULL fails all three the same way, and is reported as the first
+        let error = match i64::try_get_by(res, idx) {
+            Ok(seconds) => return Ok(Self(seconds)),
+            Err(e) => e,
+        };
+        if let Ok(seconds) = i32::try_get_by(res, idx) {
+            return Ok(Self(seconds.into()));
+        }
+        if let Ok(datetime) = DateTime::<Utc>::try_get_by(res, idx) {
+            return Ok(datetime.into());
+        }
+        Err(error)
+    }
+}
+
+impl ValueType for Timestamp {
+    fn try_from(value: Value) -> Result<Self, ValueTypeErr> {
+        match value {
+            Value::BigInt(Some(seconds)) => Ok(Self(seconds)),
+            Value::Int(Some(seconds)) => Ok(Self(seconds.into())),
+            Value::ChronoDateTimeUtc(Some(datetime)) => Ok((*datetime).into()),
+            _ => Err(ValueTypeErr),
+        }
+    }
+
+    fn type_name() -> String {
+        "Timestamp".to_string()
+    }
+
+    fn array_type() -> ArrayType {
+        ArrayType::BigInt
+    }
+
+    fn column_type() -> ColumnType {
+        ColumnType::BigInteger
+    }
+}
+
+impl Nullable for Timestamp {
+    fn null() -> Value {
+        Value::BigInt(None)
+    }
+}
+
+/// Unix seconds, as stored
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_past_2038() {
+        // 2040-01-01T00:00:00Z, beyond i32::MAX seconds
+        let later = Timestamp::from_unix(2_208_988_800);
+        assert!(later.unix() > i64::from(i32::MAX));
+        assert_eq!(later.to_datetime().to_rfc3339(), "2040-01-01T00:00:00+00:00");
+        assert_eq!(Timestamp::from(later.to_datetime()), later);
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let start = Timestamp::from_unix(1_700_000_000);
+        let end = start + Duration::hours(2);
+        assert_eq!(end.unix(), 1_700_007_200);
+        assert_eq!(end - start, Duration::hours(2));
+        assert_eq!(end - Duration::hours(2), start);
+        assert!(start < end);
+    }
+
+    #[test]
+    fn test_reads_any_column_type() {
+        let expected = Timestamp::from_unix(1_700_000_000);
+        assert_eq!(<Timestamp as ValueType>::try_from(Value::BigInt(Some(1_700_000_000))).ok(), Some(expected));
+        assert_eq!(<Timestamp as ValueType>::try_from(Value::Int(Some(1_700_000_000))).ok(), Some(expected));
+        let datetime = Value::ChronoDateTimeUtc(Some(Box::new(expected.to_datetime())));
+        assert_eq!(<Timestamp as ValueType>::try_from(datetime).ok(), Some(expected));
+        assert!(<Timestamp as ValueType>::try_from(Value::BigInt(None)).is_err());
+        assert_eq!(Value::from(expected), Value::BigInt(Some(1_700_000_000)));
+    }
+
+    #[derive(Debug, sea_orm::FromQueryResult)]
+    struct Row {
+        created_gmt: Timestamp,
+        shipped_gmt: Option<Timestamp>,
+    }
+
+    #[tokio::test]
+    async fn test_decodes_narrow_columns() {
+        use sea_orm::{DatabaseBackend, FromQueryResult, MockDatabase, Statement};
+        use std::collections::BTreeMap;
+
+        let row = BTreeMap::from([("created_gmt", Value::Int(Some(1_700_000_000))), ("shipped_gmt", Value::Int(None))]);
+        let db = MockDatabase::new(DatabaseBackend::Postgres).append_query_results([vec![row]]).into_connection();
+
+        let statement = Statement::from_string(DatabaseBackend::Postgres, "SELECT created_gmt, shipped_gmt FROM orders");
+        let row = Row::find_by_statement(statement).one(&db).await.unwrap().unwrap();
+        assert_eq!(row.created_gmt, Timestamp::from_unix(1_700_000_000));
+        assert_eq!(row.shipped_gmt, None);
+    }
+
+    #[test]
+    fn test_serializes_as_seconds() {
+        let timestamp = Timestamp::from_unix(1_700_000_000);
+        assert_eq!(serde_json::to_string(&timestamp).unwrap(), "1700000000");
+        assert_eq!(serde_json::from_str::<Timestamp>("1700000000").unwrap(), timestamp);
+    }
+}
@@ -1,33 +1,180 @@
-//! Customer address management (placeholder - to be implemented with SeaORM)
+//! Customer address management using SeaORM
 //!
-//! TODO: Implement CustomerAddress entity and service layer
-//! For now, this is a stub to allow compilation
+//! Customers keep an address book; one entry each may be flagged as the
+//! default billing and default shipping address used by checkout.
 
+use sea_orm::*;
+use sea_orm::sea_query::Expr;
 use serde::{Deserialize, Serialize};
+use ::entity::prelude::*;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct CustomerAddress {
-    pub id: i32,
-    pub cid: i32,
-    pub mid: i32,
-    pub label: String,
-    pub firstname: String,
-    pub lastname: String,
-    pub company: String,
-    pub address1: String,
-    pub address2: String,
-    pub city: String,
-    pub state: String,
-    pub zip: String,
-    pub country: String,
-    pub phone: String,
+use crate::{pii, CustomerError};
+
+/// Customer address model (CUSTOMER_ADDRS table)
+pub type CustomerAddress = CustomerAddr;
+
+/// Which default an address is being promoted to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AddressKind {
+    Billing,
+    Shipping,
 }
 
-// TODO: Implement AddressService with SeaORM
-// pub struct AddressService;
-//
-// impl AddressService {
-//     pub async fn create(db: &DatabaseConnection, addr: CustomerAddress) -> Result<CustomerAddress> { ... }
-//     pub async fn get_by_customer(db: &DatabaseConnection, mid: i32, cid: i32) -> Result<Vec<CustomerAddress>> { ... }
-//     pub async fn delete(db: &DatabaseConnection, mid: i32, id: i32) -> Result<()> { ... }
-// }
+/// Address service for managing a customer's address book
+pub struct AddressService;
+
+impl AddressService {
+    /// Create new address
+    pub async fn create(
+        db: &DatabaseConnection,
+        addr: CustomerAddress,
+    ) -> Result<CustomerAddress, CustomerError> {
+        let addr = pii::seal_address(addr);
+        let active = ::entity::customer_addrs::ActiveModel {
+            cid: Set(addr.cid),
+            mid: Set(addr.mid),
+            label: Set(addr.label),
+            firstname: Set(addr.firstname),
+            lastname: Set(addr.lastname),
+            company: Set(addr.company),
+            address1: Set(addr.address1),
+            address2: Set(addr.address2),
+            city: Set(addr.city),
+            state: Set(addr.state),
+            zip: Set(addr.zip),
+            country: Set(addr.country),
+            phone: Set(addr.phone),
+            is_default_billing: Set(false),
+            is_default_shipping: Set(false),
+            ..Default::default()
+        };
+
+        let result = active.insert(db).await?;
+        Ok(pii::open_address(result)?)
+    }
+
+    /// Find address by ID
+    pub async fn find_by_id(
+        db: &DatabaseConnection,
+        mid: i32,
+        id: i32,
+    ) -> Result<Option<CustomerAddress>, CustomerError> {
+        let addr = CustomerAddrs::find()
+            .filter(::entity::customer_addrs::Column::Mid.eq(mid))
+            .filter(::entity::customer_addrs::Column::Id.eq(id))
+            .one(db)
+            .await?;
+
+        Ok(addr.map(pii::open_address).transpose()?)
+    }
+
+    /// List all addresses for a customer
+    pub async fn get_by_customer(
+        db: &DatabaseConnection,
+        mid: i32,
+        cid: i32,
+    ) -> Result<Vec<CustomerAddress>, CustomerError> {
+        let addrs = CustomerAddrs::find()
+            .filter(::entity::customer_addrs::Column::Mid.eq(mid))
+            .filter(::entity::customer_addrs::Column::Cid.eq(cid))
+            .order_by_asc(::entity::customer_addrs::Column::Id)
+            .all(db)
+            .await?;
+
+        Ok(addrs.into_iter().map(pii::open_address).collect::<Result<_, _>>()?)
+    }
+
+    /// Find the customer's default address of the given kind
+    pub async fn find_default(
+        db: &DatabaseConnection,
+        mid: i32,
+        cid: i32,
+        kind: AddressKind,
+    ) -> Result<Option<CustomerAddress>, CustomerError> {
+        let flag = match kind {
+            AddressKind::Billing => ::entity::customer_addrs::Column::IsDefaultBilling,
+            AddressKind::Shipping => ::entity::customer_addrs::Column::IsDefaultShipping,
+        };
+
+        let addr = CustomerAddrs::find()
+            .filter(::entity::customer_addrs::Column::Mid.eq(mid))
+            .filter(::entity::customer_addrs::Column::Cid.eq(cid))
+            .filter(flag.eq(true))
+            .one(db)
+            .await?;
+
+        Ok(addr.map(pii::open_address).transpose()?)
+    }
+
+    /// Update address (default flags are managed by `set_default`)
+    pub async fn update(
+        db: &DatabaseConnection,
+        addr: CustomerAddress,
+    ) -> Result<CustomerAddress, CustomerError> {
+        let mut active: ::entity::customer_addrs::ActiveModel = pii::seal_address(addr).into();
+        active = active.reset_all();
+        active.is_default_billing = NotSet;
+        active.is_default_shipping = NotSet;
+
+        let result = active.update(db).await?;
+        Ok(pii::open_address(result)?)
+    }
+
+    /// Delete address
+    pub async fn delete(
+        db: &DatabaseConnection,
+        mid: i32,
+        id: i32,
+    ) -> Result<(), CustomerError> {
+        CustomerAddrs::delete_many()
+            .filter(::entity::customer_addrs::Column::Mid.eq(mid))
+            .filter(::entity::customer_addrs::Column::Id.eq(id))
+            .exec(db)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Make an address the customer's default billing or shipping address,
+    /// clearing the flag on every other address they own
+    pub async fn set_default(
+        db: &DatabaseConnection,
+        mid: i32,
+        cid: i32,
+        id: i32,
+        kind: AddressKind,
+    ) -> Result<CustomerAddress, CustomerError> {
+        let flag = match kind {
+            AddressKind::Billing => ::entity::customer_addrs::Column::IsDefaultBilling,
+            AddressKind::Shipping => ::entity::customer_addrs::Column::IsDefaultShipping,
+        };
+
+        let txn = db.begin().await?;
+
+        let addr = CustomerAddrs::find()
+            .filter(::entity::customer_addrs::Column::Mid.eq(mid))
+            .filter(::entity::customer_addrs::Column::Cid.eq(cid))
+            .filter(::entity::customer_addrs::Column::Id.eq(id))
+            .one(&txn)
+            .await?
+            .ok_or(CustomerError::AddressNotFound)?;
+
+        CustomerAddrs::update_many()
+            .col_expr(flag, Expr::value(false))
+            .filter(::entity::customer_addrs::Column::Mid.eq(mid))
+            .filter(::entity::customer_addrs::Column::Cid.eq(cid))
+            .exec(&txn)
+            .await?;
+
+        let mut active: ::entity::customer_addrs::ActiveModel = addr.into();
+        match kind {
+            AddressKind::Billing => active.is_default_billing = Set(true),
+            AddressKind::Shipping => active.is_default_shipping = Set(true),
+        }
+        let result = active.update(&txn).await?;
+
+        txn.commit().await?;
+        Ok(pii::open_address(result)?)
+    }
+}
@@ -0,0 +1,241 @@
+//! Named carts a customer keeps
+//!
+//! A signed-in customer can keep several carts, each under a name unique to
+//! them ("office order", "birthday ideas"). One is active: the cart they
+//! are filling and check out. The rest are saved for later, and switching
+//! the active cart brings one back. This service only records which carts
+//! a customer owns; the carts themselves live in the cart store.
+
+use commercerack_core::Timestamp;
+use sea_orm::sea_query::Expr;
+use sea_orm::*;
+use ::entity::customer_carts::{self, Column};
+use ::entity::prelude::*;
+use tracing::instrument;
+
+use crate::CustomerError;
+
+/// Customer cart service
+pub struct CustomerCartService;
+
+impl CustomerCartService {
+    /// A customer's carts, the active one first, then by name
+    pub async fn list<C: ConnectionTrait>(db: &C, mid: i32, cid: i32) -> Result<Vec<CustomerCart>, CustomerError> {
+        Ok(CustomerCarts::find()
+            .filter(Column::Mid.eq(mid))
+            .filter(Column::Cid.eq(cid))
+            .order_by_desc(Column::Active)
+            .order_by_asc(Column::Name)
+            .all(db)
+            .await?)
+    }
+
+    pub async fn find<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        cid: i32,
+        cart_id: &str,
+    ) -> Result<Option<CustomerCart>, CustomerError> {
+        Ok(CustomerCarts::find()
+            .filter(Column::Mid.eq(mid))
+            .filter(Column::Cid.eq(cid))
+            .filter(Column::CartId.eq(cart_id))
+            .one(db)
+            .await?)
+    }
+
+    /// Who keeps `cart_id`, if anyone
+    pub async fn owner<C: ConnectionTrait>(db: &C, cart_id: &str) -> Result<Option<CustomerCart>, CustomerError> {
+        Ok(CustomerCarts::find().filter(Column::CartId.eq(cart_id)).one(db).await?)
+    }
+
+    /// The cart the customer is filling, if they have picked one
+    pub async fn active<C: ConnectionTrait>(db: &C, mid: i32, cid: i32) -> Result<Option<CustomerCart>, CustomerError> {
+        Ok(CustomerCarts::find()
+            .filter(Column::Mid.eq(mid))
+            .filter(Column::Cid.eq(cid))
+            .filter(Column::Active.eq(true))
+            .one(db)
+            .await?)
+    }
+
+    async fn ensure_name_free<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        cid: i32,
+        name: &str,
+    ) -> Result<(), CustomerError> {
+        let taken = CustomerCarts::find()
+            .filter(Column::Mid.eq(mid))
+            .filter(Column::Cid.eq(cid))
+            .filter(Column::Name.eq(name))
+            .count(db)
+            .await?;
+        if taken > 0 {
+            return Err(CustomerError::CartNameTaken(name.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Give the customer the cart `cart_id` under `name`. It becomes their
+    /// active cart if they have none.
+    #[instrument(skip_all, fields(mid = mid, cid = cid, cart_id = cart_id))]
+    pub async fn add<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        cid: i32,
+        cart_id: &str,
+        name: &str,
+    ) -> Result<CustomerCart, CustomerError> {
+        Self::ensure_name_free(db, mid, cid, name).await?;
+        let active = Self::active(db, mid, cid).await?.is_none();
+
+        let now = Timestamp::now();
+        Ok(customer_carts::ActiveModel {
+            mid: Set(mid),
+            cid: Set(cid),
+            cart_id: Set(cart_id.to_string()),
+            name: Set(name.to_string()),
+            active: Set(active),
+            created_gmt: Set(now),
+            modified_gmt: Set(now),
+            ..Default::default()
+        }
+        .insert(db)
+        .await?)
+    }
+
+    #[instrument(skip_all, fields(mid = mid, cid = cid, cart_id = cart_id))]
+    pub async fn rename<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        cid: i32,
+        cart_id: &str,
+        name: &str,
+    ) -> Result<CustomerCart, CustomerError> {
+        let cart = Self::find(db, mid, cid, cart_id).await?.ok_or(CustomerError::CartNotFound)?;
+        if cart.name == name {
+            return Ok(cart);
+        }
+        Self::ensure_name_free(db, mid, cid, name).await?;
+
+        let mut active: customer_carts::ActiveModel = cart.into();
+        active.name = Set(name.to_string());
+        active.modified_gmt = Set(Timestamp::now());
+        Ok(active.update(db).await?)
+    }
+
+    /// Make `cart_id` the customer's active cart, saving the one that was
+    /// active for later
+    #[instrument(skip_all, fields(mid = mid, cid = cid, cart_id = cart_id))]
+    pub async fn activate<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: i32,
+        cid: i32,
+        cart_id: &str,
+    ) -> Result<CustomerCart, CustomerError> {
+        let txn = db.begin().await?;
+        let cart = Self::find(&txn, mid, cid, cart_id).await?.ok_or(CustomerError::CartNotFound)?;
+        if cart.active {
+            return Ok(cart);
+        }
+
+        let now = Timestamp::now();
+        CustomerCarts::update_many()
+            .col_expr(Column::Active, Expr::value(false))
+            .col_expr(Column::ModifiedGmt, Expr::value(now))
+            .filter(Column::Mid.eq(mid))
+            .filter(Column::Cid.eq(cid))
+            .filter(Column::Active.eq(true))
+            .exec(&txn)
+            .await?;
+        let mut active: customer_carts::ActiveModel = cart.into();
+        active.active = Set(true);
+        active.modified_gmt = Set(now);
+        let cart = active.update(&txn).await?;
+        txn.commit().await?;
+        Ok(cart)
+    }
+
+    /// Stop the customer owning `cart_id`. Returns whether they did.
+    #[instrument(skip_all, fields(mid = mid, cid = cid, cart_id = cart_id))]
+    pub async fn remove<C: ConnectionTrait>(db: &C, mid: i32, cid: i32, cart_id: &str) -> Result<bool, CustomerError> {
+        let deleted = CustomerCarts::delete_many()
+            .filter(Column::Mid.eq(mid))
+            .filter(Column::Cid.eq(cid))
+            .filter(Column::CartId.eq(cart_id))
+            .exec(db)
+            .await?;
+        Ok(deleted.rows_affected > 0)
+    }
+
+    /// Drop whichever customer owns `cart_id`, e.g. once it is checked out
+    #[instrument(skip_all, fields(cart_id = cart_id))]
+    pub async fn forget<C: ConnectionTrait>(db: &C, cart_id: &str) -> Result<(), CustomerError> {
+        CustomerCarts::delete_many().filter(Column::CartId.eq(cart_id)).exec(db).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn kept(cart_id: &str, name: &str, active: bool) -> CustomerCart {
+        CustomerCart {
+            id: 1,
+            mid: 1,
+            cid: 42,
+            cart_id: cart_id.to_string(),
+            name: name.to_string(),
+            active,
+            created_gmt: Timestamp::from_unix(1_700_000_000),
+            modified_gmt: Timestamp::from_unix(1_700_000_000),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_refuses_a_taken_name() {
+        let count = BTreeMap::from([("num_items".to_string(), Value::BigInt(Some(1)))]);
+        let db = MockDatabase::new(DatabaseBackend::Postgres).append_query_results([[count]]).into_connection();
+
+        let err = CustomerCartService::add(&db, 1, 42, "cart-2", "Office order").await.unwrap_err();
+        assert!(matches!(err, CustomerError::CartNameTaken(name) if name == "Office order"));
+    }
+
+    #[tokio::test]
+    async fn test_first_cart_is_active() {
+        let count = BTreeMap::from([("num_items".to_string(), Value::BigInt(Some(0)))]);
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([[count]])
+            .append_query_results([Vec::<CustomerCart>::new()])
+            .append_query_results([vec![kept("cart-1", "Cart", true)]])
+            .into_connection();
+
+        let cart = CustomerCartService::add(&db, 1, 42, "cart-1", "Cart").await.unwrap();
+        assert!(cart.active);
+
+        let log = db.into_transaction_log();
+        assert!(log[2].statements()[0].values.as_ref().unwrap().0.contains(&Value::Bool(Some(true))));
+    }
+
+    #[tokio::test]
+    async fn test_activate_saves_the_active_cart_for_later() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![kept("cart-2", "Office order", false)]])
+            .append_exec_results([MockExecResult { last_insert_id: 0, rows_affected: 1 }])
+            .append_query_results([vec![kept("cart-2", "Office order", true)]])
+            .into_connection();
+
+        let cart = CustomerCartService::activate(&db, 1, 42, "cart-2").await.unwrap();
+        assert!(cart.active);
+
+        let sql: Vec<String> = db
+            .into_transaction_log()
+            .iter()
+            .flat_map(|txn| txn.statements().iter().map(|stmt| stmt.sql.clone()).collect::<Vec<_>>())
+            .collect();
+        assert!(sql.iter().any(|sql| sql.starts_with(r#"UPDATE "customer_carts" SET "active" = $1, "modified_gmt" = $2"#)));
+    }
+}
@@ -0,0 +1,357 @@
+//! B2B company accounts
+//!
+//! A company is an account several of a merchant's customers buy for. Its
+//! buyers share the company's price group (see [`crate::pricing_group`])
+//! and its orders. Each buyer may have a spending limit, the largest order
+//! they can place. Orders above the company's approval threshold are placed
+//! but wait for one of its approvers; see `commercerack_order::approvals`.
+
+use commercerack_core::Timestamp;
+use sea_orm::prelude::Decimal;
+use sea_orm::*;
+use ::entity::company_buyers::Column;
+use ::entity::prelude::*;
+use std::fmt;
+use std::str::FromStr;
+use tracing::instrument;
+
+use crate::{pii, CustomerError};
+
+/// What a buyer may do for their company
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BuyerRole {
+    /// Places orders
+    #[default]
+    Buyer,
+    /// Places orders and approves or rejects those over the threshold
+    Approver,
+}
+
+impl BuyerRole {
+    pub const ALL: [BuyerRole; 2] = [BuyerRole::Buyer, BuyerRole::Approver];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BuyerRole::Buyer => "buyer",
+            BuyerRole::Approver => "approver",
+        }
+    }
+}
+
+impl fmt::Display for BuyerRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for BuyerRole {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|role| role.as_str() == s)
+            .ok_or_else(|| format!("unknown buyer role {}", s))
+    }
+}
+
+/// Whether `buyer` may approve their company's orders
+pub fn is_approver(buyer: &CompanyBuyer) -> bool {
+    buyer.role == BuyerRole::Approver.as_str()
+}
+
+/// Settings of a company
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompanyDetails {
+    pub name: String,
+    /// Price group of its buyers; empty leaves each buyer's own
+    pub price_group: String,
+    /// Orders with a larger total wait for approval; `None` approves all
+    pub approval_threshold: Option<Decimal>,
+}
+
+/// Company account service
+pub struct CompanyService;
+
+impl CompanyService {
+    #[instrument(skip_all, fields(mid = mid))]
+    pub async fn create<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        details: CompanyDetails,
+    ) -> Result<Company, CustomerError> {
+        let now = Timestamp::now();
+        Ok(::entity::companies::ActiveModel {
+            mid: Set(mid),
+            name: Set(details.name),
+            price_group: Set(details.price_group),
+            approval_threshold: Set(details.approval_threshold),
+            created_gmt: Set(now),
+            modified_gmt: Set(now),
+            ..Default::default()
+        }
+        .insert(db)
+        .await?)
+    }
+
+    /// A merchant's companies by name
+    pub async fn list<C: ConnectionTrait>(db: &C, mid: i32) -> Result<Vec<Company>, CustomerError> {
+        Ok(Companies::find()
+            .filter(::entity::companies::Column::Mid.eq(mid))
+            .order_by_asc(::entity::companies::Column::Name)
+            .order_by_asc(::entity::companies::Column::Id)
+            .all(db)
+            .await?)
+    }
+
+    pub async fn find<C: ConnectionTrait>(db: &C, mid: i32, id: i32) -> Result<Option<Company>, CustomerError> {
+        Ok(Companies::find()
+            .filter(::entity::companies::Column::Mid.eq(mid))
+            .filter(::entity::companies::Column::Id.eq(id))
+            .one(db)
+            .await?)
+    }
+
+    #[instrument(skip_all, fields(mid = mid, id = id))]
+    pub async fn update<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        id: i32,
+        details: CompanyDetails,
+    ) -> Result<Company, CustomerError> {
+        let company = Self::find(db, mid, id).await?.ok_or(CustomerError::CompanyNotFound)?;
+
+        let mut active: ::entity::companies::ActiveModel = company.into();
+        active.name = Set(details.name);
+        active.price_group = Set(details.price_group);
+        active.approval_threshold = Set(details.approval_threshold);
+        active.modified_gmt = Set(Timestamp::now());
+        Ok(active.update(db).await?)
+    }
+
+    /// Delete a company and its buyer list. Its orders keep their
+    /// `company_id`. Returns whether the company existed.
+    #[instrument(skip_all, fields(mid = mid, id = id))]
+    pub async fn delete<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: i32,
+        id: i32,
+    ) -> Result<bool, CustomerError> {
+        let txn = db.begin().await?;
+        CompanyBuyers::delete_many()
+            .filter(Column::Mid.eq(mid))
+            .filter(Column::CompanyId.eq(id))
+            .exec(&txn)
+            .await?;
+        let deleted = Companies::delete_many()
+            .filter(::entity::companies::Column::Mid.eq(mid))
+            .filter(::entity::companies::Column::Id.eq(id))
+            .exec(&txn)
+            .await?;
+        txn.commit().await?;
+        Ok(deleted.rows_affected > 0)
+    }
+
+    /// A company's buyers in the order they were added
+    pub async fn buyers<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        company_id: i32,
+    ) -> Result<Vec<CompanyBuyer>, CustomerError> {
+        Ok(CompanyBuyers::find()
+            .filter(Column::Mid.eq(mid))
+            .filter(Column::CompanyId.eq(company_id))
+            .order_by_asc(Column::Id)
+            .all(db)
+            .await?)
+    }
+
+    /// Add a customer to a company's buyers, or change their role and
+    /// spending limit if they are one already
+    #[instrument(skip_all, fields(mid = mid, company_id = company_id, cid = cid))]
+    pub async fn set_buyer<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: i32,
+        company_id: i32,
+        cid: i32,
+        role: BuyerRole,
+        spending_limit: Option<Decimal>,
+    ) -> Result<CompanyBuyer, CustomerError> {
+        let txn = db.begin().await?;
+        Self::find(&txn, mid, company_id).await?.ok_or(CustomerError::CompanyNotFound)?;
+        Customers::find()
+            .filter(::entity::customers::Column::Mid.eq(mid))
+            .filter(::entity::customers::Column::Cid.eq(cid))
+            .one(&txn)
+            .await?
+            .ok_or(CustomerError::NotFound)?;
+
+        let existing = CompanyBuyers::find()
+            .filter(Column::Mid.eq(mid))
+            .filter(Column::Cid.eq(cid))
+            .lock_exclusive()
+            .one(&txn)
+            .await?;
+        let buyer = match existing {
+            Some(buyer) if buyer.company_id != company_id => {
+                return Err(CustomerError::InOtherCompany(buyer.company_id));
+            }
+            Some(buyer) => {
+                let mut active: ::entity::company_buyers::ActiveModel = buyer.into();
+                active.role = Set(role.as_str().to_string());
+                active.spending_limit = Set(spending_limit);
+                active.update(&txn).await?
+            }
+            None => {
+                ::entity::company_buyers::ActiveModel {
+                    mid: Set(mid),
+                    company_id: Set(company_id),
+                    cid: Set(cid),
+                    role: Set(role.as_str().to_string()),
+                    spending_limit: Set(spending_limit),
+                    created_gmt: Set(Timestamp::now()),
+                    ..Default::default()
+                }
+                .insert(&txn)
+                .await?
+            }
+        };
+        txn.commit().await?;
+        Ok(buyer)
+    }
+
+    /// Take a customer off a company's buyers
+    #[instrument(skip_all, fields(mid = mid, company_id = company_id, cid = cid))]
+    pub async fn remove_buyer<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        company_id: i32,
+        cid: i32,
+    ) -> Result<(), CustomerError> {
+        let removed = CompanyBuyers::delete_many()
+            .filter(Column::Mid.eq(mid))
+            .filter(Column::CompanyId.eq(company_id))
+            .filter(Column::Cid.eq(cid))
+            .exec(db)
+            .await?;
+        if removed.rows_affected == 0 {
+            return Err(CustomerError::BuyerNotFound);
+        }
+        Ok(())
+    }
+
+    /// The company a customer buys for and their place in it
+    pub async fn membership<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        cid: i32,
+    ) -> Result<Option<(Company, CompanyBuyer)>, CustomerError> {
+        let Some(buyer) = CompanyBuyers::find()
+            .filter(Column::Mid.eq(mid))
+            .filter(Column::Cid.eq(cid))
+            .one(db)
+            .await?
+        else {
+            return Ok(None);
+        };
+        let company = Self::find(db, mid, buyer.company_id).await?;
+        Ok(company.map(|company| (company, buyer)))
+    }
+
+    /// Customers who may approve a company's orders
+    pub async fn approvers<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        company_id: i32,
+    ) -> Result<Vec<Customer>, CustomerError> {
+        let cids: Vec<i32> = CompanyBuyers::find()
+            .select_only()
+            .column(Column::Cid)
+            .filter(Column::Mid.eq(mid))
+            .filter(Column::CompanyId.eq(company_id))
+            .filter(Column::Role.eq(BuyerRole::Approver.as_str()))
+            .into_tuple()
+            .all(db)
+            .await?;
+        if cids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let customers = Customers::find()
+            .filter(::entity::customers::Column::Mid.eq(mid))
+            .filter(::entity::customers::Column::Cid.is_in(cids))
+            .order_by_asc(::entity::customers::Column::Cid)
+            .all(db)
+            .await?;
+        Ok(customers.into_iter().map(pii::open_customer).collect::<Result<_, _>>()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sea_orm::{DatabaseBackend, MockDatabase};
+
+    fn buyer(company_id: i32, role: BuyerRole) -> CompanyBuyer {
+        CompanyBuyer {
+            id: 1,
+            mid: 1,
+            company_id,
+            cid: 7,
+            role: role.as_str().to_string(),
+            spending_limit: None,
+            created_gmt: Timestamp::EPOCH,
+        }
+    }
+
+    fn company(id: i32) -> Company {
+        Company {
+            id,
+            mid: 1,
+            name: "Acme".to_string(),
+            price_group: String::new(),
+            approval_threshold: Some(Decimal::new(100000, 2)),
+            created_gmt: Timestamp::EPOCH,
+            modified_gmt: Timestamp::EPOCH,
+        }
+    }
+
+    fn customer() -> Customer {
+        Customer {
+            cid: 7,
+            mid: 1,
+            email: "buyer@example.com".to_string(),
+            firstname: "Bo".to_string(),
+            lastname: "Buyer".to_string(),
+            created_gmt: Timestamp::EPOCH,
+            modified_gmt: Timestamp::EPOCH,
+            passhash: String::new(),
+            passsalt: String::new(),
+            price_group: String::new(),
+            customer_group: "wholesale".to_string(),
+            role: "customer".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_role_round_trip() {
+        for role in BuyerRole::ALL {
+            assert_eq!(role.as_str().parse::<BuyerRole>(), Ok(role));
+        }
+        assert!("owner".parse::<BuyerRole>().is_err());
+        assert!(is_approver(&buyer(3, BuyerRole::Approver)));
+        assert!(!is_approver(&buyer(3, BuyerRole::Buyer)));
+    }
+
+    #[tokio::test]
+    async fn test_buyer_stays_in_one_company() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![company(4)]])
+            .append_query_results([vec![customer()]])
+            .append_query_results([vec![buyer(3, BuyerRole::Buyer)]])
+            .into_connection();
+
+        let result = CompanyService::set_buyer(&db, 1, 4, 7, BuyerRole::Buyer, None).await;
+        assert!(matches!(result, Err(CustomerError::InOtherCompany(3))));
+    }
+}
@@ -0,0 +1,151 @@
+//! Sign-in with external identity providers (Google, Apple)
+//!
+//! A provider identity is linked to at most one customer per merchant. The
+//! first sign-in links it to the customer with the same email, or creates
+//! that customer, but only when the provider says it verified the email;
+//! otherwise anyone could claim an account by registering its email with a
+//! provider.
+
+use commercerack_core::Timestamp;
+use sea_orm::*;
+use ::entity::customer_identities::Column;
+use ::entity::prelude::*;
+use tracing::{info, instrument};
+
+use crate::{CustomerError, CustomerService};
+
+/// What a provider vouches for about the person signing in
+#[derive(Debug, Clone, Default)]
+pub struct ExternalIdentity {
+    /// e.g. `google`
+    pub provider: String,
+    /// The provider's stable user id
+    pub subject: String,
+    pub email: Option<String>,
+    pub email_verified: bool,
+    pub firstname: String,
+    pub lastname: String,
+}
+
+/// Identity linking service
+pub struct IdentityService;
+
+impl IdentityService {
+    /// The customer `identity` signs in as, linking or creating one on
+    /// first sign-in
+    #[instrument(skip_all, fields(mid = mid, provider = %identity.provider))]
+    pub async fn sign_in<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: i32,
+        identity: &ExternalIdentity,
+    ) -> Result<Customer, CustomerError> {
+        let linked = CustomerIdentities::find()
+            .filter(Column::Mid.eq(mid))
+            .filter(Column::Provider.eq(identity.provider.as_str()))
+            .filter(Column::Subject.eq(identity.subject.as_str()))
+            .one(db)
+            .await?;
+        if let Some(linked) = linked {
+            let customer = Customers::find()
+                .filter(::entity::customers::Column::Mid.eq(mid))
+                .filter(::entity::customers::Column::Cid.eq(linked.cid))
+                .one(db)
+                .await?
+                .ok_or(CustomerError::NotFound)?;
+            return Ok(crate::pii::open_customer(customer)?);
+        }
+
+        let email = match identity.email.as_deref().map(str::trim) {
+            Some(email) if identity.email_verified && !email.is_empty() => email,
+            _ => return Err(CustomerError::UnverifiedIdentity),
+        };
+
+        let txn = db.begin().await?;
+        let customer = match CustomerService::find_by_email(&txn, mid, email).await? {
+            Some(customer) => customer,
+            None => {
+                info!(mid, "creating customer on first sign-in");
+                let customer =
+                    CustomerService::create(&txn, mid, email, &identity.firstname, &identity.lastname, None).await?;
+                CustomerService::claim_guest_orders(&txn, mid, email, customer.cid).await?;
+                customer
+            }
+        };
+        ::entity::customer_identities::ActiveModel {
+            mid: Set(mid),
+            cid: Set(customer.cid),
+            provider: Set(identity.provider.clone()),
+            subject: Set(identity.subject.clone()),
+            created_gmt: Set(Timestamp::now()),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+        txn.commit().await?;
+        Ok(customer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity(email_verified: bool) -> ExternalIdentity {
+        ExternalIdentity {
+            provider: "google".to_string(),
+            subject: "1234567890".to_string(),
+            email: Some("sam@example.com".to_string()),
+            email_verified,
+            firstname: "Sam".to_string(),
+            lastname: "Shopper".to_string(),
+        }
+    }
+
+    fn customer() -> Customer {
+        Customer {
+            cid: 42,
+            mid: 1,
+            email: "sam@example.com".to_string(),
+            firstname: "Sam".to_string(),
+            lastname: "Shopper".to_string(),
+            created_gmt: Timestamp::EPOCH,
+            modified_gmt: Timestamp::EPOCH,
+            passhash: String::new(),
+            passsalt: String::new(),
+            price_group: String::new(),
+            customer_group: "retail".to_string(),
+            role: "customer".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_linked_identity_signs_in_as_its_customer() {
+        let link = CustomerIdentity {
+            id: 1,
+            mid: 1,
+            cid: 42,
+            provider: "google".to_string(),
+            subject: "1234567890".to_string(),
+            created_gmt: Timestamp::EPOCH,
+        };
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![link]])
+            .append_query_results([vec![customer()]])
+            .into_connection();
+
+        // A link made while the email was verified still holds without it
+        let customer = IdentityService::sign_in(&db, 1, &identity(false)).await.unwrap();
+        assert_eq!(customer.cid, 42);
+    }
+
+    #[tokio::test]
+    async fn test_unverified_email_is_not_linked() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([Vec::<CustomerIdentity>::new()])
+            .into_connection();
+
+        let result = IdentityService::sign_in(&db, 1, &identity(false)).await;
+        assert!(matches!(result, Err(CustomerError::UnverifiedIdentity)));
+        assert_eq!(db.into_transaction_log().len(), 1);
+    }
+}
@@ -1,34 +1,323 @@
 //! Customer management module using SeaORM
 
-use anyhow::Result;
 use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
 use argon2::password_hash::{SaltString, rand_core::OsRng};
-use chrono::Utc;
+use commercerack_core::{CustomerId, MerchantId, Timestamp};
+use sea_orm::sea_query::extension::postgres::PgExpr;
+use sea_orm::sea_query::{Expr, Func, LikeExpr, Query};
 use sea_orm::*;
+use std::str::FromStr;
+use std::sync::OnceLock;
 use ::entity::prelude::*;
+use commercerack_db::pagination::{Cursor, CursorError, Keyset, KeyValue};
+use commercerack_events::{outbox, DomainEvent};
+use thiserror::Error;
 
 pub mod auth;
 pub mod address;
+pub mod carts;
+pub mod companies;
+pub mod identities;
+pub mod notes;
+pub mod pii;
+pub mod tokens;
+pub mod privacy;
+pub mod segments;
+pub mod sessions;
+pub mod tags;
+pub mod throttle;
+pub mod two_factor;
+pub mod wishlist;
+
+use pii::PiiError;
+use tokens::RefreshError;
+use tracing::instrument;
+
+#[derive(Error, Debug)]
+pub enum CustomerError {
+    #[error("Customer not found")]
+    NotFound,
+
+    #[error("Address not found")]
+    AddressNotFound,
+
+    #[error("Data request not found")]
+    DataRequestNotFound,
+
+    #[error("Session not found")]
+    SessionNotFound,
+
+    #[error("Note not found")]
+    NoteNotFound,
+
+    #[error("Company not found")]
+    CompanyNotFound,
+
+    #[error("Customer is not a buyer for this company")]
+    BuyerNotFound,
+
+    #[error("Customer already buys for company {0}")]
+    InOtherCompany(i32),
+
+    #[error("Cart not found")]
+    CartNotFound,
+
+    #[error("A cart named {0:?} already exists")]
+    CartNameTaken(String),
+
+    #[error("Invalid tag {0:?}: use up to 32 letters, digits, '-' or '_'")]
+    InvalidTag(String),
+
+    #[error("SKU {0} not found")]
+    SkuNotFound(String),
+
+    #[error("Segment not found")]
+    SegmentNotFound,
+
+    #[error("A segment named {0:?} already exists")]
+    SegmentNameTaken(String),
+
+    #[error("Invalid segment rules: {0}")]
+    InvalidSegmentRule(String),
+
+    #[error("Password hashing failed: {0}")]
+    Password(String),
+
+    #[error("PII encryption error: {0}")]
+    Pii(#[from] PiiError),
+
+    #[error("Too many failed logins; try again in {retry_after} seconds")]
+    Throttled { retry_after: u64 },
+
+    #[error("Two-factor authentication is already on")]
+    TwoFactorEnabled,
+
+    #[error("Two-factor authentication is not set up")]
+    TwoFactorNotEnrolled,
+
+    #[error("Invalid two-factor code")]
+    InvalidTwoFactorCode,
+
+    #[error("Sign-in provider has not verified this email")]
+    UnverifiedIdentity,
+
+    #[error(transparent)]
+    Token(#[from] RefreshError),
+
+    #[error(transparent)]
+    Cursor(#[from] CursorError),
+
+    #[error("Database error: {0}")]
+    Db(#[from] DbErr),
+}
+
+/// Kind of shopper a customer is. Price tiers for a group's name apply to
+/// its members; see [`pricing_group`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CustomerGroup {
+    #[default]
+    Retail,
+    Wholesale,
+    Employee,
+}
+
+impl CustomerGroup {
+    pub const ALL: [CustomerGroup; 3] = [CustomerGroup::Retail, CustomerGroup::Wholesale, CustomerGroup::Employee];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CustomerGroup::Retail => "retail",
+            CustomerGroup::Wholesale => "wholesale",
+            CustomerGroup::Employee => "employee",
+        }
+    }
+}
+
+impl std::fmt::Display for CustomerGroup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for CustomerGroup {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|group| group.as_str() == s)
+            .ok_or_else(|| format!("unknown customer group {}", s))
+    }
+}
+
+/// Price group whose tiers price a customer's carts: the one assigned to
+/// them, else their company's, else their customer group's name
+pub fn pricing_group(customer: &Customer, company: Option<&Company>) -> String {
+    if !customer.price_group.is_empty() {
+        return customer.price_group.clone();
+    }
+    match company.filter(|company| !company.price_group.is_empty()) {
+        Some(company) => company.price_group.clone(),
+        None => customer.customer_group.clone(),
+    }
+}
+
+/// Result ordering for [`CustomerService::list`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CustomerSort {
+    #[default]
+    Newest,
+    Oldest,
+    Email,
+    Name,
+}
+
+impl FromStr for CustomerSort {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "newest" => Ok(CustomerSort::Newest),
+            "oldest" => Ok(CustomerSort::Oldest),
+            "email" => Ok(CustomerSort::Email),
+            "name" => Ok(CustomerSort::Name),
+            other => Err(format!("unknown sort order {}", other)),
+        }
+    }
+}
+
+impl CustomerSort {
+    fn keyset(self) -> Keyset<::entity::customers::Column> {
+        use ::entity::customers::Column;
+
+        match self {
+            CustomerSort::Newest => Keyset::new("newest", vec![(Column::CreatedGmt, sea_orm::Order::Desc), (Column::Cid, sea_orm::Order::Asc)]),
+            CustomerSort::Oldest => Keyset::new("oldest", vec![(Column::CreatedGmt, sea_orm::Order::Asc), (Column::Cid, sea_orm::Order::Asc)]),
+            CustomerSort::Email => Keyset::new("email", vec![(Column::Email, sea_orm::Order::Asc), (Column::Cid, sea_orm::Order::Asc)]),
+            CustomerSort::Name => Keyset::new(
+                "name",
+                vec![(Column::Lastname, sea_orm::Order::Asc), (Column::Firstname, sea_orm::Order::Asc), (Column::Cid, sea_orm::Order::Asc)],
+            ),
+        }
+    }
+
+    /// Values of `customer` for the columns of [`CustomerSort::keyset`]
+    fn key(self, customer: &Customer) -> Vec<KeyValue> {
+        match self {
+            CustomerSort::Newest | CustomerSort::Oldest => vec![customer.created_gmt.into(), customer.cid.into()],
+            CustomerSort::Email => vec![customer.email.as_str().into(), customer.cid.into()],
+            CustomerSort::Name => vec![
+                customer.lastname.as_str().into(),
+                customer.firstname.as_str().into(),
+                customer.cid.into(),
+            ],
+        }
+    }
+}
+
+/// Customer list filters; unset filters match everything
+#[derive(Debug, Clone, Default)]
+pub struct CustomerFilter {
+    /// Case-insensitive substring of the email; the whole email while
+    /// emails are encrypted (see [`pii`])
+    pub email: Option<String>,
+    /// Case-insensitive substring of the first or last name
+    pub name: Option<String>,
+    /// Created at or after this time
+    pub created_from: Option<Timestamp>,
+    /// Created at or before this time
+    pub created_to: Option<Timestamp>,
+    /// Has this tag; see [`tags`]
+    pub tag: Option<String>,
+    pub group: Option<CustomerGroup>,
+    pub sort: CustomerSort,
+}
+
+/// `LIKE` pattern matching `text` literally anywhere in a column
+fn contains(text: &str) -> LikeExpr {
+    let escaped = text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+    LikeExpr::new(format!("%{}%", escaped)).escape('\\')
+}
+
+impl CustomerFilter {
+    fn condition(&self, mid: i32) -> Condition {
+        use ::entity::customers::Column;
+
+        let mut condition = Condition::all().add(Column::Mid.eq(mid));
+        if let Some(email) = self.email.as_deref().map(str::trim).filter(|e| !e.is_empty()) {
+            condition = if pii::encrypts_email() {
+                condition.add(Column::Email.is_in(pii::email_lookups(email)))
+            } else {
+                condition.add(Expr::col(Column::Email).ilike(contains(email)))
+            };
+        }
+        if let Some(name) = self.name.as_deref().map(str::trim).filter(|n| !n.is_empty()) {
+            condition = condition.add(
+                Condition::any()
+                    .add(Expr::col(Column::Firstname).ilike(contains(name)))
+                    .add(Expr::col(Column::Lastname).ilike(contains(name))),
+            );
+        }
+        if let Some(from) = self.created_from {
+            condition = condition.add(Column::CreatedGmt.gte(from));
+        }
+        if let Some(to) = self.created_to {
+            condition = condition.add(Column::CreatedGmt.lte(to));
+        }
+        if let Some(tag) = &self.tag {
+            let tagged = Query::select()
+                .column(::entity::customer_tags::Column::Cid)
+                .from(CustomerTags)
+                .and_where(::entity::customer_tags::Column::Mid.eq(mid))
+                .and_where(::entity::customer_tags::Column::Tag.eq(tag.trim().to_lowercase()))
+                .to_owned();
+            condition = condition.add(Column::Cid.in_subquery(tagged));
+        }
+        if let Some(group) = self.group {
+            condition = condition.add(Column::CustomerGroup.eq(group.as_str()));
+        }
+        condition
+    }
+}
+
+fn verify_hash(hash: &str, password: &str) -> Result<bool, CustomerError> {
+    let parsed_hash = PasswordHash::new(hash).map_err(|e| CustomerError::Password(e.to_string()))?;
+    Ok(Argon2::default().verify_password(password.as_bytes(), &parsed_hash).is_ok())
+}
+
+/// A hash no password matches, checked against when there is no customer
+/// so that answering takes the same time
+fn decoy_hash() -> &'static str {
+    static DECOY: OnceLock<String> = OnceLock::new();
+    DECOY.get_or_init(|| {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password(uuid::Uuid::new_v4().as_bytes(), &salt)
+            .expect("hashing a random password")
+            .to_string()
+    })
+}
 
 /// Customer service for managing customer operations
 pub struct CustomerService;
 
 impl CustomerService {
     /// Create new customer
-    pub async fn create(
-        db: &DatabaseConnection,
+    #[instrument(skip_all, fields(mid = mid))]
+    pub async fn create<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
         mid: i32,
         email: &str,
         firstname: &str,
         lastname: &str,
         password: Option<&str>,
-    ) -> Result<Customer> {
-        let now = Utc::now().timestamp() as i32;
+    ) -> Result<Customer, CustomerError> {
+        let now = Timestamp::now();
         let (passhash, passsalt) = if let Some(pwd) = password {
             let salt = SaltString::generate(&mut OsRng);
             let argon2 = Argon2::default();
             let hash = argon2.hash_password(pwd.as_bytes(), &salt)
-                .map_err(|e| anyhow::anyhow!("Password hashing failed: {:?}", e))?
+                .map_err(|e| CustomerError::Password(e.to_string()))?
                 .to_string();
             (hash, salt.to_string())
         } else {
@@ -37,7 +326,7 @@ impl CustomerService {
 
         let customer = ::entity::customers::ActiveModel {
             mid: Set(mid),
-            email: Set(email.to_string()),
+            email: Set(pii::seal_email(email)),
             firstname: Set(firstname.to_string()),
             lastname: Set(lastname.to_string()),
             created_gmt: Set(now),
@@ -47,7 +336,10 @@ impl CustomerService {
             ..Default::default()
         };
 
-        let result = customer.insert(db).await?;
+        let txn = db.begin().await?;
+        let result = pii::open_customer(customer.insert(&txn).await?)?;
+        outbox::record(&txn, &DomainEvent::CustomerCreated(result.clone())).await?;
+        txn.commit().await?;
         Ok(result)
     }
 
@@ -56,55 +348,212 @@ impl CustomerService {
         db: &DatabaseConnection,
         mid: i32,
         cid: i32,
-    ) -> Result<Option<Customer>> {
+    ) -> Result<Option<Customer>, CustomerError> {
         let customer = Customers::find()
             .filter(::entity::customers::Column::Mid.eq(mid))
             .filter(::entity::customers::Column::Cid.eq(cid))
             .one(db)
             .await?;
 
-        Ok(customer)
+        Ok(customer.map(pii::open_customer).transpose()?)
+    }
+
+    /// Price group that prices a customer's carts; see [`pricing_group`].
+    /// Empty for an unknown customer.
+    pub async fn pricing_group_of<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        cid: i32,
+    ) -> Result<String, CustomerError> {
+        let Some(customer) = Customers::find()
+            .filter(::entity::customers::Column::Mid.eq(mid))
+            .filter(::entity::customers::Column::Cid.eq(cid))
+            .one(db)
+            .await?
+        else {
+            return Ok(String::new());
+        };
+        let company = companies::CompanyService::membership(db, mid, cid).await?;
+        Ok(pricing_group(&customer, company.as_ref().map(|(company, _)| company)))
+    }
+
+    /// What a customer's logins may do, as stored: `customer`,
+    /// `merchant_admin` or `platform_admin`. None for an unknown customer.
+    pub async fn role_of<C: ConnectionTrait>(db: &C, mid: i32, cid: i32) -> Result<Option<String>, CustomerError> {
+        let customer = Customers::find()
+            .filter(::entity::customers::Column::Mid.eq(mid))
+            .filter(::entity::customers::Column::Cid.eq(cid))
+            .one(db)
+            .await?;
+        Ok(customer.map(|customer| customer.role))
     }
 
     /// Find customer by email
-    pub async fn find_by_email(
-        db: &DatabaseConnection,
+    pub async fn find_by_email<C: ConnectionTrait>(
+        db: &C,
         mid: i32,
         email: &str,
-    ) -> Result<Option<Customer>> {
+    ) -> Result<Option<Customer>, CustomerError> {
         let customer = Customers::find()
             .filter(::entity::customers::Column::Mid.eq(mid))
-            .filter(::entity::customers::Column::Email.eq(email))
+            .filter(::entity::customers::Column::Email.is_in(pii::email_lookups(email)))
             .one(db)
             .await?;
 
-        Ok(customer)
+        Ok(customer.map(pii::open_customer).transpose()?)
+    }
+
+    /// List a merchant's customers one page after `cursor`, returning the
+    /// total number of matches and the cursor of the next page, if any
+    #[instrument(skip_all, fields(mid = mid))]
+    pub async fn list(
+        db: &DatabaseConnection,
+        mid: i32,
+        filter: &CustomerFilter,
+        limit: u64,
+        cursor: Option<&Cursor>,
+    ) -> Result<(Vec<Customer>, u64, Option<Cursor>), CustomerError> {
+        let query = Customers::find().filter(filter.condition(mid));
+        let total = query.clone().count(db).await?;
+
+        let keyset = filter.sort.keyset();
+        let mut customers = keyset.page(query, cursor, limit)?.all(db).await?;
+
+        // Cursors hold the stored values, so decrypt after paging
+        let next = keyset.next(&mut customers, limit, |c| filter.sort.key(c));
+        let customers = customers.into_iter().map(pii::open_customer).collect::<Result<_, _>>()?;
+        Ok((customers, total, next))
+    }
+
+    /// Number of customers a merchant gained between `from` and `to`
+    pub async fn count_new<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        from: Timestamp,
+        to: Timestamp,
+    ) -> Result<u64, CustomerError> {
+        let count = Customers::find()
+            .filter(::entity::customers::Column::Mid.eq(mid))
+            .filter(::entity::customers::Column::CreatedGmt.between(from, to))
+            .count(db)
+            .await?;
+
+        Ok(count)
+    }
+
+    /// Link a merchant's guest orders billed to `email` to customer `cid`,
+    /// e.g. once the guest registers. Returns the number of orders claimed.
+    #[instrument(skip_all, fields(mid = mid, cid = cid))]
+    pub async fn claim_guest_orders<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        email: &str,
+        cid: i32,
+    ) -> Result<u64, CustomerError> {
+        use ::entity::orders::Column;
+
+        let email = email.trim();
+        if email.is_empty() {
+            return Ok(0);
+        }
+
+        // Guest orders carry customer 0
+        let result = Orders::update_many()
+            .col_expr(Column::Customer, Expr::value(cid))
+            .col_expr(Column::V, Expr::col(Column::V).add(1))
+            .filter(Column::Mid.eq(mid))
+            .filter(Column::Customer.eq(0))
+            .filter(Expr::expr(Func::lower(Expr::col(Column::BillEmail))).eq(email.to_lowercase()))
+            .exec(db)
+            .await?;
+
+        Ok(result.rows_affected)
     }
 
     /// Update customer
+    #[instrument(skip_all, fields(mid = customer.mid, cid = customer.cid))]
     pub async fn update(
         db: &DatabaseConnection,
         customer: Customer,
-    ) -> Result<Customer> {
+    ) -> Result<Customer, CustomerError> {
+        let mut active: ::entity::customers::ActiveModel = customer.into();
+        active.modified_gmt = Set(Timestamp::now());
+
+        let txn = db.begin().await?;
+        let result = pii::open_customer(active.update(&txn).await?)?;
+        outbox::record(&txn, &DomainEvent::CustomerUpdated(result.clone())).await?;
+        txn.commit().await?;
+        Ok(result)
+    }
+
+    /// Assign the customer to a price group; empty removes them from any
+    #[instrument(skip_all, fields(mid = mid, cid = cid))]
+    pub async fn set_price_group<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: i32,
+        cid: i32,
+        price_group: &str,
+    ) -> Result<Customer, CustomerError> {
+        let txn = db.begin().await?;
+        let customer = Customers::find()
+            .filter(::entity::customers::Column::Mid.eq(mid))
+            .filter(::entity::customers::Column::Cid.eq(cid))
+            .one(&txn)
+            .await?
+            .ok_or(CustomerError::NotFound)?;
+
         let mut active: ::entity::customers::ActiveModel = customer.into();
-        active.modified_gmt = Set(Utc::now().timestamp() as i32);
+        active.price_group = Set(price_group.to_string());
+        active.modified_gmt = Set(Timestamp::now());
+        let result = pii::open_customer(active.update(&txn).await?)?;
+        outbox::record(&txn, &DomainEvent::CustomerUpdated(result.clone())).await?;
+        txn.commit().await?;
+        Ok(result)
+    }
 
-        let result = active.update(db).await?;
+    /// Move the customer to another customer group
+    #[instrument(skip_all, fields(mid = mid, cid = cid, group = %group))]
+    pub async fn set_group<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: i32,
+        cid: i32,
+        group: CustomerGroup,
+    ) -> Result<Customer, CustomerError> {
+        let txn = db.begin().await?;
+        let customer = Customers::find()
+            .filter(::entity::customers::Column::Mid.eq(mid))
+            .filter(::entity::customers::Column::Cid.eq(cid))
+            .one(&txn)
+            .await?
+            .ok_or(CustomerError::NotFound)?;
+
+        let mut active: ::entity::customers::ActiveModel = customer.into();
+        active.customer_group = Set(group.as_str().to_string());
+        active.modified_gmt = Set(Timestamp::now());
+        let result = pii::open_customer(active.update(&txn).await?)?;
+        outbox::record(&txn, &DomainEvent::CustomerUpdated(result.clone())).await?;
+        txn.commit().await?;
         Ok(result)
     }
 
     /// Delete customer
+    #[instrument(skip_all, fields(mid = %mid, cid = %cid))]
     pub async fn delete(
         db: &DatabaseConnection,
-        mid: i32,
-        cid: i32,
-    ) -> Result<()> {
-        Customers::delete_many()
+        mid: MerchantId,
+        cid: CustomerId,
+    ) -> Result<(), CustomerError> {
+        let txn = db.begin().await?;
+        let result = Customers::delete_many()
             .filter(::entity::customers::Column::Mid.eq(mid))
             .filter(::entity::customers::Column::Cid.eq(cid))
-            .exec(db)
+            .exec(&txn)
             .await?;
 
+        if result.rows_affected > 0 {
+            outbox::record(&txn, &DomainEvent::CustomerDeleted { mid, cid }).await?;
+        }
+        txn.commit().await?;
         Ok(())
     }
 
@@ -112,34 +561,53 @@ impl CustomerService {
     pub async fn verify_password(
         customer: &Customer,
         password: &str,
-    ) -> Result<bool> {
+    ) -> Result<bool, CustomerError> {
         if customer.passhash.is_empty() {
             return Ok(false);
         }
+        verify_hash(&customer.passhash, password)
+    }
 
-        let parsed_hash = PasswordHash::new(&customer.passhash)
-            .map_err(|e| anyhow::anyhow!("Invalid password hash: {:?}", e))?;
-
-        let argon2 = Argon2::default();
-        Ok(argon2.verify_password(password.as_bytes(), &parsed_hash).is_ok())
+    /// The customer with `email` if `password` is theirs. A password is
+    /// hashed either way, so an unknown email takes as long to reject as a
+    /// wrong password.
+    pub async fn authenticate<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        email: &str,
+        password: &str,
+    ) -> Result<Option<Customer>, CustomerError> {
+        let customer = Self::find_by_email(db, mid, email).await?;
+        match customer {
+            Some(customer) if !customer.passhash.is_empty() => {
+                Ok(verify_hash(&customer.passhash, password)?.then_some(customer))
+            }
+            _ => {
+                verify_hash(decoy_hash(), password)?;
+                Ok(None)
+            }
+        }
     }
 
-    /// Set customer password
+    /// Set customer password and revoke all outstanding refresh tokens
+    #[instrument(skip_all, fields(mid = customer.mid, cid = customer.cid))]
     pub async fn set_password(
         db: &DatabaseConnection,
         mut customer: Customer,
         password: &str,
-    ) -> Result<Customer> {
+    ) -> Result<Customer, CustomerError> {
         let salt = SaltString::generate(&mut OsRng);
         let argon2 = Argon2::default();
         let hash = argon2.hash_password(password.as_bytes(), &salt)
-            .map_err(|e| anyhow::anyhow!("Password hashing failed: {:?}", e))?
+            .map_err(|e| CustomerError::Password(e.to_string()))?
             .to_string();
 
         customer.passhash = hash;
         customer.passsalt = salt.to_string();
 
-        Self::update(db, customer).await
+        let customer = Self::update(db, customer).await?;
+        tokens::RefreshTokenService::revoke_all(db, customer.mid, customer.cid).await?;
+        Ok(customer)
     }
 }
 
@@ -147,6 +615,115 @@ impl CustomerService {
 mod tests {
     use super::*;
 
-    // Tests will be added when we have a test database setup
-    // For now, compilation success validates the API design
+    #[test]
+    fn test_filter_condition() {
+        let filter = CustomerFilter {
+            email: Some("50%_off@".to_string()),
+            name: Some(" smith ".to_string()),
+            created_from: Some(Timestamp::from_unix(1_700_000_000)),
+            tag: Some(" VIP ".to_string()),
+            group: Some(CustomerGroup::Wholesale),
+            ..Default::default()
+        };
+        let sql = Customers::find()
+            .filter(filter.condition(1))
+            .build(DbBackend::Postgres)
+            .to_string();
+
+        assert!(sql.contains(r#""email" ILIKE (E'%50\\%\\_off@%' ESCAPE E'\\')"#), "{}", sql);
+        assert!(sql.contains(r#"("firstname" ILIKE ('%smith%' ESCAPE E'\\')) OR ("lastname" ILIKE ('%smith%' ESCAPE E'\\'))"#), "{}", sql);
+        assert!(sql.contains(r#""customers"."created_gmt" >= 1700000000"#), "{}", sql);
+        assert!(!sql.contains("<="), "{}", sql);
+        assert!(sql.contains(r#""customers"."cid" IN (SELECT "cid" FROM "customer_tags" WHERE "customer_tags"."mid" = 1 AND "customer_tags"."tag" = 'vip')"#), "{}", sql);
+        assert!(sql.contains(r#""customers"."customer_group" = 'wholesale'"#), "{}", sql);
+    }
+
+    #[test]
+    fn test_name_sort_pages_by_cid() {
+        let customer = Customer {
+            cid: 7,
+            mid: 1,
+            email: "ann@example.com".to_string(),
+            firstname: "Ann".to_string(),
+            lastname: "Smith".to_string(),
+            created_gmt: Timestamp::EPOCH,
+            modified_gmt: Timestamp::EPOCH,
+            passhash: String::new(),
+            passsalt: String::new(),
+            price_group: String::new(),
+            customer_group: "retail".to_string(),
+            role: "customer".to_string(),
+        };
+        let cursor = CustomerSort::Name.keyset().cursor(CustomerSort::Name.key(&customer));
+        let sql = CustomerSort::Name
+            .keyset()
+            .page(Customers::find(), Some(&cursor), 10)
+            .unwrap()
+            .build(DbBackend::Postgres)
+            .to_string();
+
+        assert!(sql.contains(r#""customers"."lastname" = 'Smith' AND "customers"."firstname" = 'Ann' AND "customers"."cid" > 7"#), "{}", sql);
+        assert!(CustomerSort::Email.keyset().after(&cursor).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_claim_guest_orders() {
+        let db = MockDatabase::new(DbBackend::Postgres)
+            .append_exec_results([MockExecResult { last_insert_id: 0, rows_affected: 2 }])
+            .into_connection();
+
+        let claimed = CustomerService::claim_guest_orders(&db, 1, " Ann@Example.com ", 7).await.unwrap();
+        assert_eq!(claimed, 2);
+
+        let log = db.into_transaction_log();
+        let sql = log[0].statements()[0].to_string();
+        assert!(sql.starts_with(r#"UPDATE "orders" SET "customer" = 7"#), "{}", sql);
+        assert!(sql.contains(r#""orders"."customer" = 0"#), "{}", sql);
+        assert!(sql.contains(r#"LOWER("bill_email") = 'ann@example.com'"#), "{}", sql);
+    }
+
+    #[test]
+    fn test_pricing_group() {
+        let mut customer = Customer {
+            cid: 7,
+            mid: 1,
+            email: "buyer@example.com".to_string(),
+            firstname: "Bo".to_string(),
+            lastname: "Buyer".to_string(),
+            created_gmt: Timestamp::EPOCH,
+            modified_gmt: Timestamp::EPOCH,
+            passhash: String::new(),
+            passsalt: String::new(),
+            price_group: String::new(),
+            customer_group: CustomerGroup::Wholesale.to_string(),
+            role: "customer".to_string(),
+        };
+        let mut company = Company {
+            id: 3,
+            mid: 1,
+            name: "Acme".to_string(),
+            price_group: String::new(),
+            approval_threshold: None,
+            created_gmt: Timestamp::EPOCH,
+            modified_gmt: Timestamp::EPOCH,
+        };
+
+        assert_eq!(pricing_group(&customer, None), "wholesale");
+        assert_eq!(pricing_group(&customer, Some(&company)), "wholesale");
+        company.price_group = "contract".to_string();
+        assert_eq!(pricing_group(&customer, Some(&company)), "contract");
+        customer.price_group = "vip".to_string();
+        assert_eq!(pricing_group(&customer, Some(&company)), "vip");
+
+        for group in CustomerGroup::ALL {
+            assert_eq!(group.as_str().parse::<CustomerGroup>(), Ok(group));
+        }
+        assert!("reseller".parse::<CustomerGroup>().is_err());
+    }
+
+    #[test]
+    fn test_sort_parse() {
+        assert_eq!("name".parse::<CustomerSort>(), Ok(CustomerSort::Name));
+        assert!("price".parse::<CustomerSort>().is_err());
+    }
 }
@@ -0,0 +1,96 @@
+//! Merchant notes on customers
+//!
+//! Freeform notes staff keep about a customer, e.g. from a support call.
+//! Customers never see them except in their data export.
+
+use commercerack_core::Timestamp;
+use sea_orm::*;
+use ::entity::customer_notes::{self, Column};
+use ::entity::prelude::*;
+use tracing::instrument;
+
+use crate::CustomerError;
+
+/// Room for the author in the legacy `luser` column
+const AUTHOR_MAX_LEN: usize = 10;
+
+/// Customer note service
+pub struct NoteService;
+
+impl NoteService {
+    /// A customer's notes, newest first
+    pub async fn list<C: ConnectionTrait>(db: &C, mid: i32, cid: i32) -> Result<Vec<CustomerNote>, CustomerError> {
+        Ok(CustomerNotes::find()
+            .filter(Column::Mid.eq(mid))
+            .filter(Column::Cid.eq(cid))
+            .order_by_desc(Column::CreatedGmt)
+            .order_by_desc(Column::Id)
+            .all(db)
+            .await?)
+    }
+
+    /// Add a note to one of the merchant's customers. `author` is the token
+    /// subject of whoever wrote it.
+    #[instrument(skip_all, fields(mid = mid, cid = cid))]
+    pub async fn add<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        cid: i32,
+        author: &str,
+        note: &str,
+    ) -> Result<CustomerNote, CustomerError> {
+        let exists = Customers::find()
+            .filter(::entity::customers::Column::Mid.eq(mid))
+            .filter(::entity::customers::Column::Cid.eq(cid))
+            .count(db)
+            .await?
+            > 0;
+        if !exists {
+            return Err(CustomerError::NotFound);
+        }
+
+        Ok(customer_notes::ActiveModel {
+            mid: Set(mid),
+            username: Set(String::new()),
+            cid: Set(cid),
+            created_gmt: Set(Timestamp::now()),
+            luser: Set(author.chars().take(AUTHOR_MAX_LEN).collect()),
+            note: Set(note.trim().to_string()),
+            kind: Set(String::new()),
+            ..Default::default()
+        }
+        .insert(db)
+        .await?)
+    }
+
+    /// Delete one of a customer's notes, returning it
+    #[instrument(skip_all, fields(mid = mid, cid = cid, note = id))]
+    pub async fn delete<C: ConnectionTrait>(db: &C, mid: i32, cid: i32, id: i32) -> Result<CustomerNote, CustomerError> {
+        let note = CustomerNotes::find_by_id(id)
+            .filter(Column::Mid.eq(mid))
+            .filter(Column::Cid.eq(cid))
+            .one(db)
+            .await?
+            .ok_or(CustomerError::NoteNotFound)?;
+        CustomerNotes::delete_by_id(note.id).exec(db).await?;
+        Ok(note)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[tokio::test]
+    async fn test_note_for_unknown_customer_is_not_added() {
+        let count = BTreeMap::from([("num_items".to_string(), Value::BigInt(Some(0)))]);
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![count]])
+            .into_connection();
+
+        let result = NoteService::add(&db, 1, 42, "7", "Called about a late parcel").await;
+        assert!(matches!(result, Err(CustomerError::NotFound)));
+        assert_eq!(db.into_transaction_log().len(), 1);
+    }
+}
@@ -0,0 +1,427 @@
+//! Encryption of customer PII at rest
+//!
+//! Address lines and phone numbers, and optionally customer emails, are
+//! stored AES-256-GCM encrypted as `enc:{key id}:{base64 nonce+ciphertext}`.
+//! The key id lets keys rotate: new values are written with the active key
+//! while values under retired keys still decrypt, until `reencrypt_pii`
+//! moves them over. Values without the prefix are plaintext written before
+//! encryption was configured and read as they are.
+//!
+//! Emails are looked up on login, so they are encrypted deterministically
+//! (the nonce is derived from the key and the email) and a lookup compares
+//! against the email's ciphertext under every key. Substring search and
+//! sorting can't see through that: with email encryption on, the customer
+//! list's email filter matches whole emails and the email sort follows the
+//! ciphertext.
+//!
+//! The services seal values on write and open them on read, so callers
+//! only ever see plaintext. Encryption is off until the API [`install`]s a
+//! [`Keyring`].
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use sea_orm::*;
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+use thiserror::Error;
+use ::entity::prelude::*;
+use tracing::instrument;
+
+use crate::CustomerError;
+
+const PREFIX: &str = "enc:";
+const NONCE_LEN: usize = 12;
+
+/// Bytes in an AES-256 key
+pub const KEY_LEN: usize = 32;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum PiiError {
+    #[error("Invalid encryption key id {0:?}")]
+    InvalidKeyId(String),
+
+    #[error("Encryption key {0} is not configured")]
+    UnknownKey(String),
+
+    #[error("Encrypted value found but no encryption keys are configured")]
+    NoKeyring,
+
+    #[error("Encrypted value is malformed or was tampered with")]
+    Corrupt,
+}
+
+struct DataKey {
+    cipher: Aes256Gcm,
+    /// Derives the nonces of deterministic encryption
+    nonce_mac: Hmac<Sha256>,
+}
+
+/// The active key, the retired keys still readable, and what to encrypt
+pub struct Keyring {
+    active: String,
+    keys: BTreeMap<String, DataKey>,
+    encrypt_email: bool,
+}
+
+impl Keyring {
+    /// A keyring writing with `id`'s key
+    pub fn new(id: &str, key: [u8; KEY_LEN]) -> Result<Self, PiiError> {
+        let mut keyring = Self { active: id.to_string(), keys: BTreeMap::new(), encrypt_email: false };
+        keyring.add(id, key)?;
+        Ok(keyring)
+    }
+
+    /// Keep reading values written with a retired key
+    pub fn with_retired_key(mut self, id: &str, key: [u8; KEY_LEN]) -> Result<Self, PiiError> {
+        self.add(id, key)?;
+        Ok(self)
+    }
+
+    /// Whether customer emails are encrypted too
+    pub fn with_email_encryption(mut self, encrypt_email: bool) -> Self {
+        self.encrypt_email = encrypt_email;
+        self
+    }
+
+    fn add(&mut self, id: &str, key: [u8; KEY_LEN]) -> Result<(), PiiError> {
+        if !valid_key_id(id) {
+            return Err(PiiError::InvalidKeyId(id.to_string()));
+        }
+        let nonce_mac = <Hmac<Sha256> as Mac>::new_from_slice(&key).expect("HMAC accepts any key length");
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+        self.keys.insert(id.to_string(), DataKey { cipher, nonce_mac });
+        Ok(())
+    }
+
+    pub fn encrypts_email(&self) -> bool {
+        self.encrypt_email
+    }
+
+    /// Encrypt with the active key and a random nonce
+    pub fn encrypt(&self, plaintext: &str) -> String {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        self.seal_with(&self.active, &nonce, plaintext)
+    }
+
+    /// Encrypt with the active key so equal plaintexts give equal values
+    pub fn encrypt_deterministic(&self, plaintext: &str) -> String {
+        self.deterministic_with(&self.active, plaintext)
+    }
+
+    fn deterministic_with(&self, id: &str, plaintext: &str) -> String {
+        let mut mac = self.keys[id].nonce_mac.clone();
+        mac.update(plaintext.as_bytes());
+        let digest = mac.finalize().into_bytes();
+        self.seal_with(id, Nonce::from_slice(&digest[..NONCE_LEN]), plaintext)
+    }
+
+    fn seal_with(&self, id: &str, nonce: &Nonce<<Aes256Gcm as AeadCore>::NonceSize>, plaintext: &str) -> String {
+        // Encryption only fails for inputs far beyond any column
+        let ciphertext = self.keys[id]
+            .cipher
+            .encrypt(nonce, plaintext.as_bytes())
+            .expect("AES-GCM encrypts inputs under 64 GiB");
+        let mut payload = nonce.to_vec();
+        payload.extend_from_slice(&ciphertext);
+        format!("{}{}:{}", PREFIX, id, STANDARD.encode(payload))
+    }
+
+    /// Decrypt a stored value; plaintext values come back unchanged
+    pub fn decrypt(&self, stored: &str) -> Result<String, PiiError> {
+        let Some((id, payload)) = split(stored) else {
+            return Ok(stored.to_string());
+        };
+        let key = self.keys.get(id).ok_or_else(|| PiiError::UnknownKey(id.to_string()))?;
+        let payload = STANDARD.decode(payload).map_err(|_| PiiError::Corrupt)?;
+        if payload.len() < NONCE_LEN {
+            return Err(PiiError::Corrupt);
+        }
+        let (nonce, ciphertext) = payload.split_at(NONCE_LEN);
+        let plaintext = key.cipher.decrypt(Nonce::from_slice(nonce), ciphertext).map_err(|_| PiiError::Corrupt)?;
+        String::from_utf8(plaintext).map_err(|_| PiiError::Corrupt)
+    }
+
+    /// Values an email may be stored as: as it is, and encrypted under
+    /// each key
+    pub fn email_lookups(&self, email: &str) -> Vec<String> {
+        let mut values = vec![email.to_string()];
+        if self.encrypt_email {
+            values.extend(self.keys.keys().map(|id| self.deterministic_with(id, email)));
+        }
+        values
+    }
+
+    /// `stored` rewritten in its current form: under the active key, or
+    /// plaintext for an email no longer encrypted. None if already current.
+    fn reseal(&self, stored: &str, email: bool) -> Result<Option<String>, PiiError> {
+        let plaintext = self.decrypt(stored)?;
+        let current = match (email, self.encrypt_email) {
+            _ if plaintext.is_empty() => plaintext,
+            (true, true) => self.encrypt_deterministic(&plaintext),
+            (true, false) => plaintext,
+            (false, _) => match split(stored) {
+                Some((id, _)) if id == self.active => return Ok(None),
+                _ => self.encrypt(&plaintext),
+            },
+        };
+        Ok((current != stored).then_some(current))
+    }
+}
+
+/// Key ids are written into every value, so they are kept short and plain
+fn valid_key_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= 16 && id.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-')
+}
+
+/// Key id and payload of an encrypted value
+fn split(stored: &str) -> Option<(&str, &str)> {
+    stored.strip_prefix(PREFIX)?.split_once(':')
+}
+
+static KEYRING: OnceLock<Keyring> = OnceLock::new();
+
+/// Turn encryption on for this process. Only the first keyring installed
+/// is used.
+pub fn install(keyring: Keyring) {
+    let _ = KEYRING.set(keyring);
+}
+
+fn keyring() -> Option<&'static Keyring> {
+    KEYRING.get()
+}
+
+/// Encrypt a PII field for storage. Empty values stay empty.
+pub fn seal(value: &str) -> String {
+    match keyring() {
+        Some(keyring) if !value.is_empty() => keyring.encrypt(value),
+        _ => value.to_string(),
+    }
+}
+
+/// Encrypt an email for storage, if emails are encrypted
+pub fn seal_email(email: &str) -> String {
+    match keyring() {
+        Some(keyring) if keyring.encrypts_email() && !email.is_empty() => keyring.encrypt_deterministic(email),
+        _ => email.to_string(),
+    }
+}
+
+/// Decrypt a stored PII field
+pub fn open(stored: &str) -> Result<String, PiiError> {
+    match keyring() {
+        Some(keyring) => keyring.decrypt(stored),
+        None if split(stored).is_some() => Err(PiiError::NoKeyring),
+        None => Ok(stored.to_string()),
+    }
+}
+
+/// Whether emails are stored encrypted
+pub fn encrypts_email() -> bool {
+    keyring().is_some_and(Keyring::encrypts_email)
+}
+
+/// Values to match a stored email against
+pub fn email_lookups(email: &str) -> Vec<String> {
+    match keyring() {
+        Some(keyring) => keyring.email_lookups(email),
+        None => vec![email.to_string()],
+    }
+}
+
+pub(crate) fn open_customer(mut customer: Customer) -> Result<Customer, PiiError> {
+    customer.email = open(&customer.email)?;
+    Ok(customer)
+}
+
+pub(crate) fn seal_address(mut addr: CustomerAddr) -> CustomerAddr {
+    addr.address1 = seal(&addr.address1);
+    addr.address2 = seal(&addr.address2);
+    addr.phone = seal(&addr.phone);
+    addr
+}
+
+pub(crate) fn open_address(mut addr: CustomerAddr) -> Result<CustomerAddr, PiiError> {
+    addr.address1 = open(&addr.address1)?;
+    addr.address2 = open(&addr.address2)?;
+    addr.phone = open(&addr.phone)?;
+    Ok(addr)
+}
+
+/// Rows rewritten by [`reencrypt`]
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ReencryptStats {
+    pub customers: u64,
+    pub addresses: u64,
+    pub two_factor: u64,
+}
+
+/// Bring every stored PII field to its current form: encrypting plaintext
+/// written before encryption was on and moving values off retired keys.
+/// Walks the tables in batches of `batch` rows; safe to interrupt and rerun.
+#[instrument(skip_all, fields(active = %keyring.active))]
+pub async fn reencrypt<C: ConnectionTrait>(
+    db: &C,
+    keyring: &Keyring,
+    batch: u64,
+) -> Result<ReencryptStats, CustomerError> {
+    use ::entity::{customer_addrs, customer_two_factor, customers};
+
+    let mut stats = ReencryptStats::default();
+
+    let mut after = 0;
+    loop {
+        let rows = Customers::find()
+            .filter(customers::Column::Cid.gt(after))
+            .order_by_asc(customers::Column::Cid)
+            .limit(batch)
+            .all(db)
+            .await?;
+        let Some(last) = rows.last() else { break };
+        after = last.cid;
+
+        for customer in rows {
+            if let Some(email) = keyring.reseal(&customer.email, true)? {
+                Customers::update_many()
+                    .col_expr(customers::Column::Email, sea_query::Expr::value(email))
+                    .filter(customers::Column::Cid.eq(customer.cid))
+                    .exec(db)
+                    .await?;
+                stats.customers += 1;
+            }
+        }
+    }
+
+    let mut after = 0;
+    loop {
+        let rows = CustomerAddrs::find()
+            .filter(customer_addrs::Column::Id.gt(after))
+            .order_by_asc(customer_addrs::Column::Id)
+            .limit(batch)
+            .all(db)
+            .await?;
+        let Some(last) = rows.last() else { break };
+        after = last.id;
+
+        for addr in rows {
+            let mut active: customer_addrs::ActiveModel = addr.clone().into();
+            let mut changed = false;
+            for (column, stored) in [
+                (customer_addrs::Column::Address1, &addr.address1),
+                (customer_addrs::Column::Address2, &addr.address2),
+                (customer_addrs::Column::Phone, &addr.phone),
+            ] {
+                if let Some(value) = keyring.reseal(stored, false)? {
+                    active.set(column, value.into());
+                    changed = true;
+                }
+            }
+            if changed {
+                active.update(db).await?;
+                stats.addresses += 1;
+            }
+        }
+    }
+
+    let mut after = 0;
+    loop {
+        let rows = CustomerTwoFactors::find()
+            .filter(customer_two_factor::Column::Id.gt(after))
+            .order_by_asc(customer_two_factor::Column::Id)
+            .limit(batch)
+            .all(db)
+            .await?;
+        let Some(last) = rows.last() else { break };
+        after = last.id;
+
+        for row in rows {
+            if let Some(secret) = keyring.reseal(&row.secret, false)? {
+                CustomerTwoFactors::update_many()
+                    .col_expr(customer_two_factor::Column::Secret, sea_query::Expr::value(secret))
+                    .filter(customer_two_factor::Column::Id.eq(row.id))
+                    .exec(db)
+                    .await?;
+                stats.two_factor += 1;
+            }
+        }
+    }
+
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyring() -> Keyring {
+        Keyring::new("k2", [2; KEY_LEN]).unwrap().with_retired_key("k1", [1; KEY_LEN]).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip_with_key_id_prefix() {
+        let keyring = keyring();
+        let stored = keyring.encrypt("12 High Street");
+        assert!(stored.starts_with("enc:k2:"), "{}", stored);
+        assert_ne!(stored, keyring.encrypt("12 High Street"));
+        assert_eq!(keyring.decrypt(&stored).unwrap(), "12 High Street");
+
+        // Plaintext from before encryption reads as it is
+        assert_eq!(keyring.decrypt("12 High Street").unwrap(), "12 High Street");
+    }
+
+    #[test]
+    fn test_retired_keys_still_decrypt() {
+        let old = Keyring::new("k1", [1; KEY_LEN]).unwrap().encrypt("555-0100");
+        assert_eq!(keyring().decrypt(&old).unwrap(), "555-0100");
+
+        let unknown = Keyring::new("k9", [9; KEY_LEN]).unwrap().encrypt("555-0100");
+        assert_eq!(keyring().decrypt(&unknown), Err(PiiError::UnknownKey("k9".to_string())));
+    }
+
+    #[test]
+    fn test_tampering_is_detected() {
+        let keyring = keyring();
+        let stored = keyring.encrypt("555-0100");
+        let (head, payload) = stored.rsplit_once(':').unwrap();
+        let mut bytes = STANDARD.decode(payload).unwrap();
+        *bytes.last_mut().unwrap() ^= 1;
+        let tampered = format!("{}:{}", head, STANDARD.encode(bytes));
+        assert_eq!(keyring.decrypt(&tampered), Err(PiiError::Corrupt));
+    }
+
+    #[test]
+    fn test_email_lookups_cover_every_key() {
+        let keyring = keyring().with_email_encryption(true);
+        let email = "ann@example.com";
+        assert_eq!(keyring.encrypt_deterministic(email), keyring.encrypt_deterministic(email));
+
+        let old = Keyring::new("k1", [1; KEY_LEN]).unwrap().encrypt_deterministic(email);
+        let lookups = keyring.email_lookups(email);
+        assert_eq!(lookups.len(), 3);
+        assert!(lookups.contains(&email.to_string()));
+        assert!(lookups.contains(&old));
+        assert!(lookups.contains(&keyring.encrypt_deterministic(email)));
+    }
+
+    #[test]
+    fn test_reseal_moves_values_to_the_active_key() {
+        let keyring = keyring();
+        let old = Keyring::new("k1", [1; KEY_LEN]).unwrap().encrypt("555-0100");
+        let resealed = keyring.reseal(&old, false).unwrap().unwrap();
+        assert!(resealed.starts_with("enc:k2:"));
+        assert_eq!(keyring.reseal(&resealed, false).unwrap(), None);
+        assert!(keyring.reseal("555-0100", false).unwrap().is_some());
+        assert_eq!(keyring.reseal("", false).unwrap(), None);
+
+        // Emails are only encrypted when configured
+        assert_eq!(keyring.reseal("ann@example.com", true).unwrap(), None);
+    }
+
+    #[test]
+    fn test_invalid_key_id() {
+        assert!(matches!(Keyring::new("a:b", [0; KEY_LEN]), Err(PiiError::InvalidKeyId(_))));
+    }
+}
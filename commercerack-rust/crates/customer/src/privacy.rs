@@ -0,0 +1,467 @@
+//! Personal data export and erasure (GDPR)
+//!
+//! A customer's right of access and right to be forgotten are handled as
+//! data requests: recorded when asked for and carried out by a background
+//! job, which a client polls for. An export is a JSON archive
+//! of the profile, addresses, orders, wishlist and merchant notes and tags,
+//! kept on the request for download. An erasure anonymizes the customer in
+//! place: names, contact details and credentials are blanked, addresses,
+//! notes, tags, wishlist and sessions are deleted, and their orders lose the billing
+//! email but keep every amount, so the merchant's books still add up.
+//! Exports made earlier are discarded along with the rest.
+//!
+//! The schema has no product reviews, so there are none to export or erase.
+
+use commercerack_core::Timestamp;
+use sea_orm::sea_query::{Expr, Func};
+use sea_orm::*;
+use serde::Serialize;
+use std::fmt;
+use std::str::FromStr;
+use ::entity::prelude::*;
+use ::entity::{customer_data_requests, orders};
+use commercerack_events::{outbox, DomainEvent};
+use tracing::instrument;
+
+use crate::{pii, CustomerError};
+
+/// Request states stored in `customer_data_requests.status`
+pub const STATUS_PENDING: &str = "pending";
+pub const STATUS_DONE: &str = "done";
+
+/// What a customer asked for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataRequestKind {
+    Export,
+    Erasure,
+}
+
+impl DataRequestKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DataRequestKind::Export => "export",
+            DataRequestKind::Erasure => "erasure",
+        }
+    }
+}
+
+impl fmt::Display for DataRequestKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for DataRequestKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "export" => Ok(DataRequestKind::Export),
+            "erasure" => Ok(DataRequestKind::Erasure),
+            _ => Err(format!("unknown data request kind {}", s)),
+        }
+    }
+}
+
+/// The profile as exported; credentials are left out
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfileExport {
+    pub cid: i32,
+    pub email: String,
+    pub firstname: String,
+    pub lastname: String,
+    pub price_group: String,
+    pub customer_group: String,
+    pub created_gmt: Timestamp,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OrderExport {
+    #[serde(flatten)]
+    pub order: orders::Model,
+    pub items: Vec<OrderItem>,
+}
+
+#[derive(Debug, Clone, Serialize, FromQueryResult)]
+pub struct NoteExport {
+    pub note: String,
+    pub created_gmt: Timestamp,
+}
+
+/// Everything stored about a customer
+#[derive(Debug, Clone, Serialize)]
+pub struct CustomerExport {
+    pub exported_gmt: Timestamp,
+    pub profile: ProfileExport,
+    pub addresses: Vec<CustomerAddr>,
+    pub orders: Vec<OrderExport>,
+    pub wishlist: Vec<WishlistItem>,
+    /// Notes the merchant keeps on the customer
+    pub notes: Vec<NoteExport>,
+    /// Tags the merchant gave the customer
+    pub tags: Vec<String>,
+}
+
+/// The email an erased customer is left with; unique per customer and
+/// undeliverable
+pub fn erased_email(cid: i32) -> String {
+    format!("erased-{}@erased.invalid", cid)
+}
+
+/// The customer's orders, including guest orders billed to their email
+fn their_orders(mid: i32, cid: i32, email: &str) -> Select<Orders> {
+    let guest = Condition::all()
+        .add(orders::Column::Customer.eq(0))
+        .add(Expr::expr(Func::lower(Expr::col(orders::Column::BillEmail))).eq(email.trim().to_lowercase()));
+    Orders::find()
+        .filter(orders::Column::Mid.eq(mid))
+        .filter(Condition::any().add(orders::Column::Customer.eq(cid)).add(guest))
+}
+
+pub struct PrivacyService;
+
+impl PrivacyService {
+    /// A request of `kind` not yet carried out, if the customer has one
+    pub async fn pending<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        cid: i32,
+        kind: DataRequestKind,
+    ) -> Result<Option<CustomerDataRequest>, CustomerError> {
+        use ::entity::customer_data_requests::Column;
+
+        Ok(CustomerDataRequests::find()
+            .filter(Column::Mid.eq(mid))
+            .filter(Column::Cid.eq(cid))
+            .filter(Column::Kind.eq(kind.as_str()))
+            .filter(Column::Status.eq(STATUS_PENDING))
+            .one(db)
+            .await?)
+    }
+
+    /// Record a request to be carried out in the background; the caller
+    /// queues the job that does it
+    #[instrument(skip_all, fields(mid = mid, cid = cid, kind = %kind))]
+    pub async fn request<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        cid: i32,
+        kind: DataRequestKind,
+    ) -> Result<CustomerDataRequest, CustomerError> {
+        Customers::find()
+            .filter(::entity::customers::Column::Mid.eq(mid))
+            .filter(::entity::customers::Column::Cid.eq(cid))
+            .one(db)
+            .await?
+            .ok_or(CustomerError::NotFound)?;
+
+        let request = customer_data_requests::ActiveModel {
+            mid: Set(mid),
+            cid: Set(cid),
+            kind: Set(kind.as_str().to_string()),
+            status: Set(STATUS_PENDING.to_string()),
+            archive: Set(None),
+            created_gmt: Set(Timestamp::now()),
+            completed_gmt: Set(None),
+            ..Default::default()
+        };
+        Ok(request.insert(db).await?)
+    }
+
+    /// One of a customer's requests
+    pub async fn find<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        cid: i32,
+        id: i32,
+    ) -> Result<CustomerDataRequest, CustomerError> {
+        use ::entity::customer_data_requests::Column;
+
+        CustomerDataRequests::find_by_id(id)
+            .filter(Column::Mid.eq(mid))
+            .filter(Column::Cid.eq(cid))
+            .one(db)
+            .await?
+            .ok_or(CustomerError::DataRequestNotFound)
+    }
+
+    /// Carry out a request. Safe to repeat: a request already done is
+    /// returned as it is.
+    #[instrument(skip_all, fields(id = id))]
+    pub async fn process<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        id: i32,
+    ) -> Result<CustomerDataRequest, CustomerError> {
+        let request = CustomerDataRequests::find_by_id(id)
+            .one(db)
+            .await?
+            .ok_or(CustomerError::DataRequestNotFound)?;
+        if request.status == STATUS_DONE {
+            return Ok(request);
+        }
+
+        let txn = db.begin().await?;
+        let archive = match request.kind.parse::<DataRequestKind>().map_err(DbErr::Custom)? {
+            DataRequestKind::Export => {
+                let export = Self::export(&txn, request.mid, request.cid).await?;
+                Some(serde_json::to_string(&export).map_err(|e| DbErr::Custom(e.to_string()))?)
+            }
+            DataRequestKind::Erasure => {
+                Self::erase(&txn, request.mid, request.cid).await?;
+                None
+            }
+        };
+
+        let mut active: customer_data_requests::ActiveModel = request.into();
+        active.status = Set(STATUS_DONE.to_string());
+        active.archive = Set(archive);
+        active.completed_gmt = Set(Some(Timestamp::now()));
+        let done = active.update(&txn).await?;
+        txn.commit().await?;
+        Ok(done)
+    }
+
+    /// Gather everything stored about a customer
+    pub async fn export<C: ConnectionTrait>(db: &C, mid: i32, cid: i32) -> Result<CustomerExport, CustomerError> {
+        let customer = Customers::find()
+            .filter(::entity::customers::Column::Mid.eq(mid))
+            .filter(::entity::customers::Column::Cid.eq(cid))
+            .one(db)
+            .await?
+            .map(pii::open_customer)
+            .transpose()?
+            .ok_or(CustomerError::NotFound)?;
+
+        let addresses = CustomerAddrs::find()
+            .filter(::entity::customer_addrs::Column::Mid.eq(mid))
+            .filter(::entity::customer_addrs::Column::Cid.eq(cid))
+            .order_by_asc(::entity::customer_addrs::Column::Id)
+            .all(db)
+            .await?
+            .into_iter()
+            .map(pii::open_address)
+            .collect::<Result<_, _>>()?;
+
+        let mut orders = Vec::new();
+        for order in their_orders(mid, cid, &customer.email).order_by_asc(orders::Column::Id).all(db).await? {
+            let items = OrderItems::find()
+                .filter(::entity::order_items::Column::Mid.eq(mid))
+                .filter(::entity::order_items::Column::OrderId.eq(order.id))
+                .order_by_asc(::entity::order_items::Column::Id)
+                .all(db)
+                .await?;
+            orders.push(OrderExport { order, items });
+        }
+
+        let wishlist = match crate::wishlist::WishlistService::find(db, mid, cid).await? {
+            Some(wishlist) => WishlistItems::find()
+                .filter(::entity::wishlist_items::Column::WishlistId.eq(wishlist.id))
+                .order_by_asc(::entity::wishlist_items::Column::Id)
+                .all(db)
+                .await?,
+            None => Vec::new(),
+        };
+
+        let notes = CustomerNotes::find()
+            .select_only()
+            .column(::entity::customer_notes::Column::Note)
+            .column(::entity::customer_notes::Column::CreatedGmt)
+            .filter(::entity::customer_notes::Column::Mid.eq(mid))
+            .filter(::entity::customer_notes::Column::Cid.eq(cid))
+            .order_by_asc(::entity::customer_notes::Column::Id)
+            .into_model::<NoteExport>()
+            .all(db)
+            .await?;
+        let tags = crate::tags::TagService::tags_of(db, mid, cid).await?;
+
+        Ok(CustomerExport {
+            exported_gmt: Timestamp::now(),
+            profile: ProfileExport {
+                cid: customer.cid,
+                email: customer.email,
+                firstname: customer.firstname,
+                lastname: customer.lastname,
+                price_group: customer.price_group,
+                customer_group: customer.customer_group,
+                created_gmt: customer.created_gmt,
+            },
+            addresses,
+            orders,
+            wishlist,
+            notes,
+            tags,
+        })
+    }
+
+    /// Anonymize a customer, keeping their orders' financial records.
+    /// Call on a transaction so the erasure is all or nothing.
+    #[instrument(skip_all, fields(mid = mid, cid = cid))]
+    pub async fn erase<C: ConnectionTrait>(db: &C, mid: i32, cid: i32) -> Result<(), CustomerError> {
+        let customer = Customers::find()
+            .filter(::entity::customers::Column::Mid.eq(mid))
+            .filter(::entity::customers::Column::Cid.eq(cid))
+            .one(db)
+            .await?
+            .map(pii::open_customer)
+            .transpose()?
+            .ok_or(CustomerError::NotFound)?;
+        let email = erased_email(cid);
+
+        // Orders first, while guest orders can still be found by email
+        let order_ids: Vec<i32> = their_orders(mid, cid, &customer.email)
+            .select_only()
+            .column(orders::Column::Id)
+            .into_tuple()
+            .all(db)
+            .await?;
+        if !order_ids.is_empty() {
+            Orders::update_many()
+                .col_expr(orders::Column::BillEmail, Expr::value(email.clone()))
+                .col_expr(orders::Column::V, Expr::col(orders::Column::V).add(1))
+                .filter(orders::Column::Id.is_in(order_ids.iter().copied()))
+                .exec(db)
+                .await?;
+            // Reasons are free text written by the customer
+            Returns::update_many()
+                .col_expr(::entity::returns::Column::Reason, Expr::value(""))
+                .filter(::entity::returns::Column::Mid.eq(mid))
+                .filter(::entity::returns::Column::OrderId.is_in(order_ids))
+                .exec(db)
+                .await?;
+        }
+
+        // Columns the entity doesn't map are cleared too
+        db.execute(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            "UPDATE customers SET email = $1, firstname = '', lastname = '', username = NULL, phone = NULL, \
+             password = NULL, passhash = '', passsalt = '', hint_answer = NULL, ip = NULL, newsletter = 0, has_notes = 0, \
+             modified_gmt = $2 WHERE mid = $3 AND cid = $4",
+            [pii::seal_email(&email).into(), Timestamp::now().into(), mid.into(), cid.into()],
+        ))
+        .await?;
+        CustomerNotes::delete_many()
+            .filter(::entity::customer_notes::Column::Mid.eq(mid))
+            .filter(::entity::customer_notes::Column::Cid.eq(cid))
+            .exec(db)
+            .await?;
+        CustomerTags::delete_many()
+            .filter(::entity::customer_tags::Column::Mid.eq(mid))
+            .filter(::entity::customer_tags::Column::Cid.eq(cid))
+            .exec(db)
+            .await?;
+
+        CustomerAddrs::delete_many()
+            .filter(::entity::customer_addrs::Column::Mid.eq(mid))
+            .filter(::entity::customer_addrs::Column::Cid.eq(cid))
+            .exec(db)
+            .await?;
+        // Items go with their wishlist
+        Wishlists::delete_many()
+            .filter(::entity::wishlists::Column::Mid.eq(mid))
+            .filter(::entity::wishlists::Column::Cid.eq(cid))
+            .exec(db)
+            .await?;
+        RefreshTokens::delete_many()
+            .filter(::entity::refresh_tokens::Column::Mid.eq(mid))
+            .filter(::entity::refresh_tokens::Column::Cid.eq(cid))
+            .exec(db)
+            .await?;
+        CustomerTwoFactors::delete_many()
+            .filter(::entity::customer_two_factor::Column::Mid.eq(mid))
+            .filter(::entity::customer_two_factor::Column::Cid.eq(cid))
+            .exec(db)
+            .await?;
+        CustomerBackupCodes::delete_many()
+            .filter(::entity::customer_backup_codes::Column::Mid.eq(mid))
+            .filter(::entity::customer_backup_codes::Column::Cid.eq(cid))
+            .exec(db)
+            .await?;
+        CustomerIdentities::delete_many()
+            .filter(::entity::customer_identities::Column::Mid.eq(mid))
+            .filter(::entity::customer_identities::Column::Cid.eq(cid))
+            .exec(db)
+            .await?;
+        Sessions::delete_many()
+            .filter(::entity::sessions::Column::Mid.eq(mid))
+            .filter(::entity::sessions::Column::Cid.eq(cid))
+            .exec(db)
+            .await?;
+        Carts::update_many()
+            .col_expr(::entity::carts::Column::Email, Expr::value(""))
+            .filter(::entity::carts::Column::Mid.eq(mid))
+            .filter(::entity::carts::Column::Cid.eq(cid))
+            .exec(db)
+            .await?;
+        AbandonedCarts::update_many()
+            .col_expr(::entity::abandoned_carts::Column::Email, Expr::value(""))
+            .col_expr(::entity::abandoned_carts::Column::RecoveryToken, Expr::value(Option::<String>::None))
+            .filter(::entity::abandoned_carts::Column::Mid.eq(mid))
+            .filter(::entity::abandoned_carts::Column::Cid.eq(cid))
+            .exec(db)
+            .await?;
+        CustomerDataRequests::update_many()
+            .col_expr(customer_data_requests::Column::Archive, Expr::value(Option::<String>::None))
+            .filter(customer_data_requests::Column::Mid.eq(mid))
+            .filter(customer_data_requests::Column::Cid.eq(cid))
+            .exec(db)
+            .await?;
+
+        let erased = Customers::find()
+            .filter(::entity::customers::Column::Mid.eq(mid))
+            .filter(::entity::customers::Column::Cid.eq(cid))
+            .one(db)
+            .await?
+            .map(pii::open_customer)
+            .transpose()?
+            .ok_or(CustomerError::NotFound)?;
+        outbox::record(db, &DomainEvent::CustomerUpdated(erased)).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(kind: DataRequestKind, status: &str) -> CustomerDataRequest {
+        CustomerDataRequest {
+            id: 5,
+            mid: 1,
+            cid: 42,
+            kind: kind.as_str().to_string(),
+            status: status.to_string(),
+            archive: None,
+            created_gmt: Timestamp::from_unix(1_700_000_000),
+            completed_gmt: None,
+        }
+    }
+
+    #[test]
+    fn test_kind_round_trip() {
+        for kind in [DataRequestKind::Export, DataRequestKind::Erasure] {
+            assert_eq!(kind.as_str().parse::<DataRequestKind>(), Ok(kind));
+        }
+        assert!("delete".parse::<DataRequestKind>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_done_request_is_not_run_again() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![request(DataRequestKind::Erasure, STATUS_DONE)]])
+            .into_connection();
+
+        let done = PrivacyService::process(&db, 5).await.unwrap();
+        assert_eq!(done.status, STATUS_DONE);
+        assert_eq!(db.into_transaction_log().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_request_for_unknown_customer_fails() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([Vec::<Customer>::new()])
+            .into_connection();
+
+        let err = PrivacyService::request(&db, 1, 42, DataRequestKind::Export).await.unwrap_err();
+        assert!(matches!(err, CustomerError::NotFound));
+    }
+}
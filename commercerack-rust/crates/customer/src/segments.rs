@@ -0,0 +1,801 @@
+//! Customer lifetime value, RFM scores and segments
+//!
+//! A scheduled refresh works out each customer's metrics from their paid
+//! orders: lifetime value, number of orders, and recency, frequency and
+//! monetary (RFM) scores from 1 to 5, where 5 is the best fifth of the
+//! merchant's customers. The scores place every customer in an
+//! [`RfmSegment`] such as `champions` or `at_risk`.
+//!
+//! Merchants also define their own segments as rules over those figures,
+//! e.g. spent more than 500 in the last 90 days. Membership is worked out
+//! by the same refresh and stored, so listing a segment is one query.
+//! Guests and customers without paid orders have no metrics and are in no
+//! segment.
+
+use chrono::Duration;
+use commercerack_core::Timestamp;
+use commercerack_db::pagination::{Cursor, Keyset};
+use sea_orm::prelude::Decimal;
+use sea_orm::sea_query::{LockType, Query};
+use sea_orm::*;
+use ::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use tracing::instrument;
+
+use crate::CustomerError;
+
+/// Most rules one segment may have
+pub const MAX_RULES: usize = 10;
+
+/// Longest window a rule may look back over
+pub const MAX_WINDOW_DAYS: u32 = 3650;
+
+/// Rows per insert when saving a merchant's metrics and memberships
+const INSERT_BATCH: usize = 1000;
+
+/// What a segment rule compares
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleField {
+    /// Total of paid orders, in `within_days` if set
+    Spent,
+    /// Number of paid orders, in `within_days` if set
+    Orders,
+    AverageOrderValue,
+    DaysSinceLastOrder,
+    DaysSinceFirstOrder,
+    RecencyScore,
+    FrequencyScore,
+    MonetaryScore,
+}
+
+impl RuleField {
+    pub const ALL: [RuleField; 8] = [
+        RuleField::Spent,
+        RuleField::Orders,
+        RuleField::AverageOrderValue,
+        RuleField::DaysSinceLastOrder,
+        RuleField::DaysSinceFirstOrder,
+        RuleField::RecencyScore,
+        RuleField::FrequencyScore,
+        RuleField::MonetaryScore,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RuleField::Spent => "spent",
+            RuleField::Orders => "orders",
+            RuleField::AverageOrderValue => "average_order_value",
+            RuleField::DaysSinceLastOrder => "days_since_last_order",
+            RuleField::DaysSinceFirstOrder => "days_since_first_order",
+            RuleField::RecencyScore => "recency_score",
+            RuleField::FrequencyScore => "frequency_score",
+            RuleField::MonetaryScore => "monetary_score",
+        }
+    }
+
+    /// Whether the field can be limited to recent orders
+    pub fn windowed(&self) -> bool {
+        matches!(self, RuleField::Spent | RuleField::Orders)
+    }
+}
+
+impl FromStr for RuleField {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        RuleField::ALL
+            .into_iter()
+            .find(|field| field.as_str() == s)
+            .ok_or_else(|| format!("unknown rule field {}", s))
+    }
+}
+
+/// How a rule compares its field with its value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Comparison {
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    Eq,
+}
+
+impl Comparison {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Comparison::Gt => "gt",
+            Comparison::Gte => "gte",
+            Comparison::Lt => "lt",
+            Comparison::Lte => "lte",
+            Comparison::Eq => "eq",
+        }
+    }
+
+    fn holds(&self, actual: Decimal, value: Decimal) -> bool {
+        match self {
+            Comparison::Gt => actual > value,
+            Comparison::Gte => actual >= value,
+            Comparison::Lt => actual < value,
+            Comparison::Lte => actual <= value,
+            Comparison::Eq => actual == value,
+        }
+    }
+}
+
+impl FromStr for Comparison {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gt" => Ok(Comparison::Gt),
+            "gte" => Ok(Comparison::Gte),
+            "lt" => Ok(Comparison::Lt),
+            "lte" => Ok(Comparison::Lte),
+            "eq" => Ok(Comparison::Eq),
+            other => Err(format!("unknown comparison {}", other)),
+        }
+    }
+}
+
+/// Whether a customer must match every rule of a segment or one of them
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Match {
+    #[default]
+    All,
+    Any,
+}
+
+impl Match {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Match::All => "all",
+            Match::Any => "any",
+        }
+    }
+}
+
+impl FromStr for Match {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(Match::All),
+            "any" => Ok(Match::Any),
+            other => Err(format!("unknown match {}", other)),
+        }
+    }
+}
+
+/// One condition of a segment, e.g. `spent gt 500 within 90 days`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SegmentRule {
+    pub field: RuleField,
+    pub op: Comparison,
+    pub value: Decimal,
+    /// Only count orders from this many days back; `spent` and `orders` only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub within_days: Option<u32>,
+}
+
+impl SegmentRule {
+    fn matches(&self, customer: &Metrics, orders: &[PaidOrder], now: Timestamp) -> bool {
+        let since = self.within_days.map(|days| now - Duration::days(days.into()));
+        let counted = || orders.iter().filter(|order| since.is_none_or(|since| order.created_gmt >= since));
+        let actual = match self.field {
+            RuleField::Spent => counted().map(|order| order.total).sum(),
+            RuleField::Orders => Decimal::from(counted().count()),
+            RuleField::AverageOrderValue => customer.average_order_value(),
+            RuleField::DaysSinceLastOrder => Decimal::from(now.since(customer.last_order_gmt).num_days()),
+            RuleField::DaysSinceFirstOrder => Decimal::from(now.since(customer.first_order_gmt).num_days()),
+            RuleField::RecencyScore => Decimal::from(customer.recency_score),
+            RuleField::FrequencyScore => Decimal::from(customer.frequency_score),
+            RuleField::MonetaryScore => Decimal::from(customer.monetary_score),
+        };
+        self.op.holds(actual, self.value)
+    }
+}
+
+/// A segment's rules, as stored in `segments.rules`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SegmentRules {
+    #[serde(rename = "match")]
+    pub matches: Match,
+    pub rules: Vec<SegmentRule>,
+}
+
+impl SegmentRules {
+    /// The rules of a stored segment
+    pub fn of(segment: &Segment) -> Result<Self, CustomerError> {
+        serde_json::from_str(&segment.rules).map_err(|e| CustomerError::InvalidSegmentRule(e.to_string()))
+    }
+
+    /// Why these rules can't be used, if they can't
+    pub fn validate(&self) -> Result<(), CustomerError> {
+        if !(1..=MAX_RULES).contains(&self.rules.len()) {
+            return Err(CustomerError::InvalidSegmentRule(format!("a segment needs between 1 and {} rules", MAX_RULES)));
+        }
+        for rule in &self.rules {
+            match rule.within_days {
+                Some(_) if !rule.field.windowed() => {
+                    return Err(CustomerError::InvalidSegmentRule(format!(
+                        "{} can't be limited to recent orders",
+                        rule.field.as_str()
+                    )));
+                }
+                Some(days) if !(1..=MAX_WINDOW_DAYS).contains(&days) => {
+                    return Err(CustomerError::InvalidSegmentRule(format!(
+                        "within_days must be between 1 and {}",
+                        MAX_WINDOW_DAYS
+                    )));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether a customer with these metrics and paid orders is in the segment
+    pub fn matches(&self, customer: &Metrics, orders: &[PaidOrder], now: Timestamp) -> bool {
+        let mut rules = self.rules.iter();
+        match self.matches {
+            Match::All => rules.all(|rule| rule.matches(customer, orders, now)),
+            Match::Any => rules.any(|rule| rule.matches(customer, orders, now)),
+        }
+    }
+}
+
+/// A segment's name, description and rules
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SegmentDefinition {
+    pub name: String,
+    pub description: String,
+    pub rules: SegmentRules,
+}
+
+/// Where a customer stands by their RFM scores
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RfmSegment {
+    /// Recent, frequent and big spenders
+    Champions,
+    /// Frequent buyers who still come back
+    Loyal,
+    /// A single recent order
+    New,
+    /// Recent but not yet frequent
+    Promising,
+    /// Good customers who have not ordered for a while
+    AtRisk,
+    /// Longest since an order
+    Lost,
+    /// Everyone in between
+    NeedsAttention,
+}
+
+impl RfmSegment {
+    pub const ALL: [RfmSegment; 7] = [
+        RfmSegment::Champions,
+        RfmSegment::Loyal,
+        RfmSegment::New,
+        RfmSegment::Promising,
+        RfmSegment::AtRisk,
+        RfmSegment::Lost,
+        RfmSegment::NeedsAttention,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RfmSegment::Champions => "champions",
+            RfmSegment::Loyal => "loyal",
+            RfmSegment::New => "new",
+            RfmSegment::Promising => "promising",
+            RfmSegment::AtRisk => "at_risk",
+            RfmSegment::Lost => "lost",
+            RfmSegment::NeedsAttention => "needs_attention",
+        }
+    }
+}
+
+impl fmt::Display for RfmSegment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A paid order of a known customer
+#[derive(Debug, Clone, PartialEq, Eq, FromQueryResult)]
+pub struct PaidOrder {
+    pub cid: i32,
+    pub total: Decimal,
+    pub created_gmt: Timestamp,
+}
+
+/// A customer's figures from their paid orders
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Metrics {
+    pub cid: i32,
+    pub orders: i32,
+    pub lifetime_value: Decimal,
+    pub first_order_gmt: Timestamp,
+    pub last_order_gmt: Timestamp,
+    pub recency_score: i32,
+    pub frequency_score: i32,
+    pub monetary_score: i32,
+}
+
+impl Metrics {
+    pub fn average_order_value(&self) -> Decimal {
+        (self.lifetime_value / Decimal::from(self.orders.max(1))).round_dp(2)
+    }
+
+    pub fn rfm_segment(&self) -> RfmSegment {
+        let (r, f, m) = (self.recency_score, self.frequency_score, self.monetary_score);
+        if self.orders == 1 && r >= 4 {
+            RfmSegment::New
+        } else if r >= 4 && f >= 4 && m >= 4 {
+            RfmSegment::Champions
+        } else if r >= 3 && f >= 4 {
+            RfmSegment::Loyal
+        } else if r >= 4 {
+            RfmSegment::Promising
+        } else if r <= 2 && (f >= 3 || m >= 4) {
+            RfmSegment::AtRisk
+        } else if r == 1 {
+            RfmSegment::Lost
+        } else {
+            RfmSegment::NeedsAttention
+        }
+    }
+}
+
+/// Score each value from 1 to 5 by its fifth among all of them, higher
+/// values scoring higher. Equal values score the same.
+fn quintiles<K: Ord + Copy>(values: &[K]) -> Vec<i32> {
+    let mut sorted = values.to_vec();
+    sorted.sort();
+    let n = sorted.len();
+    values
+        .iter()
+        .map(|value| {
+            let below = sorted.partition_point(|other| other < value);
+            ((below + 1) * 5).div_ceil(n) as i32
+        })
+        .collect()
+}
+
+/// Each customer's metrics from their paid orders, by customer ID
+pub fn metrics(orders: &[PaidOrder]) -> Vec<Metrics> {
+    let mut by_customer: BTreeMap<i32, Metrics> = BTreeMap::new();
+    for order in orders {
+        let customer = by_customer.entry(order.cid).or_insert_with(|| Metrics {
+            cid: order.cid,
+            orders: 0,
+            lifetime_value: Decimal::ZERO,
+            first_order_gmt: order.created_gmt,
+            last_order_gmt: order.created_gmt,
+            recency_score: 0,
+            frequency_score: 0,
+            monetary_score: 0,
+        });
+        customer.orders += 1;
+        customer.lifetime_value += order.total;
+        customer.first_order_gmt = customer.first_order_gmt.min(order.created_gmt);
+        customer.last_order_gmt = customer.last_order_gmt.max(order.created_gmt);
+    }
+
+    let mut customers: Vec<Metrics> = by_customer.into_values().collect();
+    let recency = quintiles(&customers.iter().map(|c| c.last_order_gmt).collect::<Vec<_>>());
+    let frequency = quintiles(&customers.iter().map(|c| c.orders).collect::<Vec<_>>());
+    let monetary = quintiles(&customers.iter().map(|c| c.lifetime_value).collect::<Vec<_>>());
+    for (i, customer) in customers.iter_mut().enumerate() {
+        customer.recency_score = recency[i];
+        customer.frequency_score = frequency[i];
+        customer.monetary_score = monetary[i];
+    }
+    customers
+}
+
+/// Number of a merchant's customers in an RFM segment
+#[derive(Debug, Clone, PartialEq, Eq, FromQueryResult)]
+pub struct RfmCount {
+    pub rfm_segment: String,
+    pub customers: i64,
+}
+
+fn members_keyset() -> Keyset<::entity::customer_metrics::Column> {
+    Keyset::new("cid", vec![(::entity::customer_metrics::Column::Cid, sea_orm::Order::Asc)])
+}
+
+/// Customer segment service
+pub struct SegmentService;
+
+impl SegmentService {
+    /// The merchant's segments, by name
+    pub async fn list<C: ConnectionTrait>(db: &C, mid: i32) -> Result<Vec<Segment>, CustomerError> {
+        use ::entity::segments::Column;
+
+        Ok(Segments::find()
+            .filter(Column::Mid.eq(mid))
+            .order_by_asc(Column::Name)
+            .all(db)
+            .await?)
+    }
+
+    pub async fn find<C: ConnectionTrait>(db: &C, mid: i32, id: i32) -> Result<Option<Segment>, CustomerError> {
+        use ::entity::segments::Column;
+
+        Ok(Segments::find()
+            .filter(Column::Mid.eq(mid))
+            .filter(Column::Id.eq(id))
+            .one(db)
+            .await?)
+    }
+
+    async fn ensure_name_free<C: ConnectionTrait>(db: &C, mid: i32, name: &str, except: Option<i32>) -> Result<(), CustomerError> {
+        use ::entity::segments::Column;
+
+        let mut query = Segments::find().filter(Column::Mid.eq(mid)).filter(Column::Name.eq(name));
+        if let Some(id) = except {
+            query = query.filter(Column::Id.ne(id));
+        }
+        if query.one(db).await?.is_some() {
+            return Err(CustomerError::SegmentNameTaken(name.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Define a segment. It has no members until the next refresh.
+    #[instrument(skip_all, fields(mid = mid))]
+    pub async fn create<C: ConnectionTrait>(db: &C, mid: i32, definition: SegmentDefinition) -> Result<Segment, CustomerError> {
+        definition.rules.validate()?;
+        Self::ensure_name_free(db, mid, &definition.name, None).await?;
+
+        let now = Timestamp::now();
+        let segment = ::entity::segments::ActiveModel {
+            mid: Set(mid),
+            name: Set(definition.name),
+            description: Set(definition.description),
+            rules: Set(serde_json::to_string(&definition.rules).expect("segment rules serialize")),
+            members: Set(0),
+            created_gmt: Set(now),
+            modified_gmt: Set(now),
+            computed_gmt: Set(None),
+            ..Default::default()
+        };
+        Ok(segment.insert(db).await?)
+    }
+
+    /// Change a segment. Its members stay as they were until the next
+    /// refresh.
+    #[instrument(skip_all, fields(mid = mid, id = id))]
+    pub async fn update<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        id: i32,
+        definition: SegmentDefinition,
+    ) -> Result<Segment, CustomerError> {
+        definition.rules.validate()?;
+        let segment = Self::find(db, mid, id).await?.ok_or(CustomerError::SegmentNotFound)?;
+        Self::ensure_name_free(db, mid, &definition.name, Some(id)).await?;
+
+        let mut active: ::entity::segments::ActiveModel = segment.into();
+        active.name = Set(definition.name);
+        active.description = Set(definition.description);
+        active.rules = Set(serde_json::to_string(&definition.rules).expect("segment rules serialize"));
+        active.modified_gmt = Set(Timestamp::now());
+        Ok(active.update(db).await?)
+    }
+
+    /// Remove a segment and its memberships, returning whether it existed
+    #[instrument(skip_all, fields(mid = mid, id = id))]
+    pub async fn delete(db: &DatabaseConnection, mid: i32, id: i32) -> Result<bool, CustomerError> {
+        let txn = db.begin().await?;
+        let deleted = Segments::delete_many()
+            .filter(::entity::segments::Column::Mid.eq(mid))
+            .filter(::entity::segments::Column::Id.eq(id))
+            .exec(&txn)
+            .await?
+            .rows_affected;
+        if deleted > 0 {
+            SegmentMembers::delete_many()
+                .filter(::entity::segment_members::Column::SegmentId.eq(id))
+                .exec(&txn)
+                .await?;
+        }
+        txn.commit().await?;
+        Ok(deleted > 0)
+    }
+
+    /// A page of a segment's members with their metrics, by customer ID
+    pub async fn members<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        id: i32,
+        limit: u64,
+        cursor: Option<&Cursor>,
+    ) -> Result<(Vec<CustomerMetric>, Option<Cursor>), CustomerError> {
+        use ::entity::customer_metrics::Column;
+
+        Self::find(db, mid, id).await?.ok_or(CustomerError::SegmentNotFound)?;
+        let members = Query::select()
+            .column(::entity::segment_members::Column::Cid)
+            .from(SegmentMembers)
+            .and_where(::entity::segment_members::Column::SegmentId.eq(id))
+            .to_owned();
+        let query = CustomerMetrics::find()
+            .filter(Column::Mid.eq(mid))
+            .filter(Column::Cid.in_subquery(members));
+
+        let keyset = members_keyset();
+        let mut customers = keyset.page(query, cursor, limit)?.all(db).await?;
+        let next = keyset.next(&mut customers, limit, |c| vec![c.cid.into()]);
+        Ok((customers, next))
+    }
+
+    /// Number of the merchant's customers in each RFM segment they fill
+    pub async fn rfm_counts<C: ConnectionTrait>(db: &C, mid: i32) -> Result<Vec<RfmCount>, CustomerError> {
+        use ::entity::customer_metrics::Column;
+
+        Ok(CustomerMetrics::find()
+            .select_only()
+            .column(Column::RfmSegment)
+            .column_as(Column::Cid.count(), "customers")
+            .filter(Column::Mid.eq(mid))
+            .group_by(Column::RfmSegment)
+            .order_by_asc(Column::RfmSegment)
+            .into_model::<RfmCount>()
+            .all(db)
+            .await?)
+    }
+
+    /// A customer's metrics as of the last refresh; none without paid orders
+    pub async fn metrics_of<C: ConnectionTrait>(db: &C, mid: i32, cid: i32) -> Result<Option<CustomerMetric>, CustomerError> {
+        use ::entity::customer_metrics::Column;
+
+        Ok(CustomerMetrics::find()
+            .filter(Column::Mid.eq(mid))
+            .filter(Column::Cid.eq(cid))
+            .one(db)
+            .await?)
+    }
+
+    /// The segments a customer was in at the last refresh, by name
+    pub async fn segments_of<C: ConnectionTrait>(db: &C, mid: i32, cid: i32) -> Result<Vec<Segment>, CustomerError> {
+        use ::entity::segments::Column;
+
+        let theirs = Query::select()
+            .column(::entity::segment_members::Column::SegmentId)
+            .from(SegmentMembers)
+            .and_where(::entity::segment_members::Column::Mid.eq(mid))
+            .and_where(::entity::segment_members::Column::Cid.eq(cid))
+            .to_owned();
+        Ok(Segments::find()
+            .filter(Column::Mid.eq(mid))
+            .filter(Column::Id.in_subquery(theirs))
+            .order_by_asc(Column::Name)
+            .all(db)
+            .await?)
+    }
+
+    /// Merchants to refresh: those with paid orders of known customers,
+    /// and those with segments
+    pub async fn merchants(db: &DatabaseConnection) -> Result<Vec<i32>, CustomerError> {
+        let mut mids: Vec<i32> = Orders::find()
+            .select_only()
+            .column(::entity::orders::Column::Mid)
+            .distinct()
+            .filter(::entity::orders::Column::Customer.ne(0))
+            .filter(::entity::orders::Column::PaidGmt.is_not_null())
+            .into_tuple()
+            .all(db)
+            .await?;
+        mids.extend(
+            Segments::find()
+                .select_only()
+                .column(::entity::segments::Column::Mid)
+                .distinct()
+                .into_tuple::<i32>()
+                .all(db)
+                .await?,
+        );
+        mids.sort_unstable();
+        mids.dedup();
+        Ok(mids)
+    }
+
+    /// Work out a merchant's customer metrics and segment members again,
+    /// replacing the stored ones. Returns how many customers have metrics.
+    #[instrument(skip_all, fields(mid = mid))]
+    pub async fn refresh(db: &DatabaseConnection, mid: i32, now: Timestamp) -> Result<usize, CustomerError> {
+        use ::entity::orders::Column;
+
+        let orders = Orders::find()
+            .select_only()
+            .column_as(Column::Customer, "cid")
+            .column(Column::Total)
+            .column(Column::CreatedGmt)
+            .filter(Column::Mid.eq(mid))
+            .filter(Column::Customer.ne(0))
+            .filter(Column::PaidGmt.is_not_null())
+            .order_by_asc(Column::Customer)
+            .into_model::<PaidOrder>()
+            .all(db)
+            .await?;
+        let customers = metrics(&orders);
+
+        let rows: Vec<::entity::customer_metrics::ActiveModel> = customers
+            .iter()
+            .map(|customer| ::entity::customer_metrics::ActiveModel {
+                mid: Set(mid),
+                cid: Set(customer.cid),
+                orders: Set(customer.orders),
+                lifetime_value: Set(customer.lifetime_value),
+                first_order_gmt: Set(customer.first_order_gmt),
+                last_order_gmt: Set(customer.last_order_gmt),
+                recency_score: Set(customer.recency_score),
+                frequency_score: Set(customer.frequency_score),
+                monetary_score: Set(customer.monetary_score),
+                rfm_segment: Set(customer.rfm_segment().as_str().to_string()),
+                computed_gmt: Set(now),
+                ..Default::default()
+            })
+            .collect();
+
+        let txn = db.begin().await?;
+        CustomerMetrics::delete_many()
+            .filter(::entity::customer_metrics::Column::Mid.eq(mid))
+            .exec(&txn)
+            .await?;
+        for batch in rows.chunks(INSERT_BATCH) {
+            CustomerMetrics::insert_many(batch.to_vec()).exec_without_returning(&txn).await?;
+        }
+
+        // Segments are locked so one deleted meanwhile gets no members
+        let segments = Segments::find()
+            .filter(::entity::segments::Column::Mid.eq(mid))
+            .lock(LockType::Update)
+            .all(&txn)
+            .await?;
+        SegmentMembers::delete_many()
+            .filter(::entity::segment_members::Column::Mid.eq(mid))
+            .exec(&txn)
+            .await?;
+        for segment in segments {
+            let members: Vec<i32> = match SegmentRules::of(&segment) {
+                Ok(rules) => {
+                    let mut members = Vec::new();
+                    // Orders are sorted by customer, and so are the metrics
+                    let mut rest = orders.as_slice();
+                    for customer in &customers {
+                        let split = rest.partition_point(|order| order.cid == customer.cid);
+                        let (theirs, others) = rest.split_at(split);
+                        rest = others;
+                        if rules.matches(customer, theirs, now) {
+                            members.push(customer.cid);
+                        }
+                    }
+                    members
+                }
+                Err(e) => {
+                    tracing::warn!("Segment {} has unreadable rules: {}", segment.id, e);
+                    Vec::new()
+                }
+            };
+
+            let rows: Vec<::entity::segment_members::ActiveModel> = members
+                .iter()
+                .map(|cid| ::entity::segment_members::ActiveModel {
+                    mid: Set(mid),
+                    segment_id: Set(segment.id),
+                    cid: Set(*cid),
+                    ..Default::default()
+                })
+                .collect();
+            for batch in rows.chunks(INSERT_BATCH) {
+                SegmentMembers::insert_many(batch.to_vec()).exec_without_returning(&txn).await?;
+            }
+
+            let mut active: ::entity::segments::ActiveModel = segment.into();
+            active.members = Set(members.len() as i32);
+            active.computed_gmt = Set(Some(now));
+            active.update(&txn).await?;
+        }
+        txn.commit().await?;
+        Ok(customers.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: i64 = 24 * 60 * 60;
+
+    fn order(cid: i32, total: i64, day: i64) -> PaidOrder {
+        PaidOrder { cid, total: Decimal::from(total), created_gmt: Timestamp::from_unix(day * DAY) }
+    }
+
+    fn rule(field: RuleField, op: Comparison, value: i64, within_days: Option<u32>) -> SegmentRule {
+        SegmentRule { field, op, value: Decimal::from(value), within_days }
+    }
+
+    #[test]
+    fn test_quintiles_score_ties_alike() {
+        assert_eq!(quintiles(&[10, 20, 30, 40, 50]), vec![1, 2, 3, 4, 5]);
+        assert_eq!(quintiles(&[50, 10, 10, 10, 10, 10, 10, 10, 10, 10]), vec![5, 1, 1, 1, 1, 1, 1, 1, 1, 1]);
+        assert_eq!(quintiles(&[7]), vec![5]);
+    }
+
+    #[test]
+    fn test_metrics_and_rfm_segments() {
+        let orders = vec![
+            // A regular big spender who still orders
+            order(1, 300, 95),
+            order(1, 300, 99),
+            order(1, 300, 100),
+            // Spent well but stopped a year ago
+            order(2, 400, 1),
+            order(2, 400, 2),
+            order(3, 20, 40),
+            order(4, 30, 60),
+            // Bought once, yesterday
+            order(5, 25, 99),
+        ];
+        let customers = metrics(&orders);
+        assert_eq!(customers.iter().map(|c| c.cid).collect::<Vec<_>>(), vec![1, 2, 3, 4, 5]);
+
+        let regular = &customers[0];
+        assert_eq!(regular.orders, 3);
+        assert_eq!(regular.lifetime_value, Decimal::from(900));
+        assert_eq!(regular.first_order_gmt, Timestamp::from_unix(95 * DAY));
+        assert_eq!(regular.average_order_value(), Decimal::from(300));
+        assert_eq!((regular.recency_score, regular.frequency_score, regular.monetary_score), (5, 5, 5));
+        assert_eq!(regular.rfm_segment(), RfmSegment::Champions);
+        assert_eq!(customers[1].rfm_segment(), RfmSegment::AtRisk);
+        assert_eq!(customers[2].rfm_segment(), RfmSegment::NeedsAttention);
+        assert_eq!(customers[4].rfm_segment(), RfmSegment::New);
+    }
+
+    #[test]
+    fn test_windowed_rules() {
+        let orders = vec![order(1, 400, 10), order(1, 200, 95)];
+        let customer = &metrics(&orders)[0];
+        let now = Timestamp::from_unix(100 * DAY);
+
+        let lifetime = SegmentRules { matches: Match::All, rules: vec![rule(RuleField::Spent, Comparison::Gt, 500, None)] };
+        assert!(lifetime.matches(customer, &orders, now));
+        let recent = SegmentRules { matches: Match::All, rules: vec![rule(RuleField::Spent, Comparison::Gt, 500, Some(30))] };
+        assert!(!recent.matches(customer, &orders, now));
+
+        let either = SegmentRules {
+            matches: Match::Any,
+            rules: vec![
+                rule(RuleField::Spent, Comparison::Gt, 500, Some(30)),
+                rule(RuleField::DaysSinceLastOrder, Comparison::Lte, 5, None),
+            ],
+        };
+        assert!(either.matches(customer, &orders, now));
+        assert!(!SegmentRules { matches: Match::All, ..either }.matches(customer, &orders, now));
+    }
+
+    #[test]
+    fn test_rules_validation_and_storage() {
+        let valid = SegmentRules { matches: Match::All, rules: vec![rule(RuleField::Orders, Comparison::Gte, 3, Some(90))] };
+        assert!(valid.validate().is_ok());
+        assert_eq!(
+            serde_json::to_string(&valid).unwrap(),
+            r#"{"match":"all","rules":[{"field":"orders","op":"gte","value":"3","within_days":90}]}"#
+        );
+
+        let unwindowed = SegmentRules { matches: Match::All, rules: vec![rule(RuleField::RecencyScore, Comparison::Eq, 5, Some(90))] };
+        assert!(matches!(unwindowed.validate(), Err(CustomerError::InvalidSegmentRule(_))));
+        assert!(matches!(SegmentRules::default().validate(), Err(CustomerError::InvalidSegmentRule(_))));
+        assert_eq!("days_since_last_order".parse(), Ok(RuleField::DaysSinceLastOrder));
+        assert!("lifetime".parse::<RuleField>().is_err());
+    }
+}
@@ -0,0 +1,152 @@
+//! Login sessions
+//!
+//! Every login starts a session, recording the device and IP it came from.
+//! The refresh tokens rotated from that login all belong to it, and access
+//! tokens carry its id. Signing a session out revokes its refresh tokens at
+//! once; its access tokens still work until they expire except on routes
+//! that check the session (see the API's `ActiveSession` extractor).
+
+use commercerack_core::Timestamp;
+use sea_orm::sea_query::Expr;
+use sea_orm::*;
+use ::entity::prelude::*;
+use ::entity::sessions::Column;
+use tracing::instrument;
+
+use crate::tokens::{IssuedToken, RefreshTokenService};
+use crate::CustomerError;
+
+/// Longest device description kept
+pub const DEVICE_MAX_LEN: usize = 255;
+
+/// Session service
+pub struct SessionService;
+
+impl SessionService {
+    /// Start a session for a login and issue its first refresh token
+    #[instrument(skip_all, fields(mid = mid, cid = cid))]
+    pub async fn start<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: i32,
+        cid: i32,
+        device: &str,
+        ip: &str,
+    ) -> Result<IssuedToken, CustomerError> {
+        let now = Timestamp::now();
+        let device: String = device.chars().take(DEVICE_MAX_LEN).collect();
+
+        let txn = db.begin().await?;
+        let session = ::entity::sessions::ActiveModel {
+            mid: Set(mid),
+            cid: Set(cid),
+            device: Set(device),
+            ip: Set(ip.to_string()),
+            created_gmt: Set(now),
+            last_seen_gmt: Set(now),
+            revoked_gmt: Set(None),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+        let issued = RefreshTokenService::issue(&txn, mid, cid, Some(session.id)).await?;
+        txn.commit().await?;
+        Ok(issued)
+    }
+
+    /// A customer's sessions that are not signed out, most recently used first
+    pub async fn list<C: ConnectionTrait>(db: &C, mid: i32, cid: i32) -> Result<Vec<Session>, CustomerError> {
+        Ok(Sessions::find()
+            .filter(Column::Mid.eq(mid))
+            .filter(Column::Cid.eq(cid))
+            .filter(Column::RevokedGmt.is_null())
+            .order_by_desc(Column::LastSeenGmt)
+            .order_by_desc(Column::Id)
+            .all(db)
+            .await?)
+    }
+
+    /// Whether a session is still signed in
+    pub async fn is_active<C: ConnectionTrait>(db: &C, id: i32) -> Result<bool, CustomerError> {
+        let session = Sessions::find_by_id(id).one(db).await?;
+        Ok(session.is_some_and(|session| session.revoked_gmt.is_none()))
+    }
+
+    /// Note that a session was just used from `ip`
+    pub async fn touch<C: ConnectionTrait>(db: &C, id: i32, ip: &str) -> Result<(), CustomerError> {
+        Sessions::update_many()
+            .col_expr(Column::LastSeenGmt, Expr::value(Timestamp::now()))
+            .col_expr(Column::Ip, Expr::value(ip))
+            .filter(Column::Id.eq(id))
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+
+    /// Sign one of a customer's sessions out, revoking its refresh tokens
+    #[instrument(skip_all, fields(mid = mid, cid = cid, session = id))]
+    pub async fn revoke<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: i32,
+        cid: i32,
+        id: i32,
+    ) -> Result<(), CustomerError> {
+        let now = Timestamp::now();
+
+        let txn = db.begin().await?;
+        let result = Sessions::update_many()
+            .col_expr(Column::RevokedGmt, Expr::value(now))
+            .filter(Column::Id.eq(id))
+            .filter(Column::Mid.eq(mid))
+            .filter(Column::Cid.eq(cid))
+            .filter(Column::RevokedGmt.is_null())
+            .exec(&txn)
+            .await?;
+        if result.rows_affected == 0 {
+            return Err(CustomerError::SessionNotFound);
+        }
+        RefreshTokens::update_many()
+            .col_expr(::entity::refresh_tokens::Column::RevokedGmt, Expr::value(now))
+            .filter(::entity::refresh_tokens::Column::SessionId.eq(id))
+            .filter(::entity::refresh_tokens::Column::RevokedGmt.is_null())
+            .exec(&txn)
+            .await?;
+        txn.commit().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_revoking_someone_elses_session_is_not_found() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_exec_results([MockExecResult { last_insert_id: 0, rows_affected: 0 }])
+            .into_connection();
+
+        let result = SessionService::revoke(&db, 1, 42, 7).await;
+        assert!(matches!(result, Err(CustomerError::SessionNotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_signed_out_session_is_not_active() {
+        let session = Session {
+            id: 7,
+            mid: 1,
+            cid: 42,
+            device: "Firefox".to_string(),
+            ip: "203.0.113.9".to_string(),
+            created_gmt: Timestamp::from_unix(1_700_000_000),
+            last_seen_gmt: Timestamp::from_unix(1_700_000_000),
+            revoked_gmt: Some(Timestamp::from_unix(1_700_000_100)),
+        };
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![session]])
+            .append_query_results([Vec::<Session>::new()])
+            .into_connection();
+
+        assert!(!SessionService::is_active(&db, 7).await.unwrap());
+        assert!(!SessionService::is_active(&db, 8).await.unwrap());
+    }
+}
@@ -0,0 +1,147 @@
+//! Merchant tags on customers
+//!
+//! Tags such as `vip`, `wholesale` or `fraud-risk` group a merchant's
+//! customers: the customer list filters by them and coupons can be limited
+//! to customers with one (see `commercerack_promotion`). They are matched
+//! case-insensitively and stored lowercase.
+
+use commercerack_core::Timestamp;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::*;
+use ::entity::customer_tags::{self, Column};
+use ::entity::prelude::*;
+use serde::Serialize;
+use tracing::instrument;
+
+use crate::CustomerError;
+
+/// Longest tag kept
+pub const TAG_MAX_LEN: usize = 32;
+
+/// A tag in its stored form: trimmed, lowercase, and only letters, digits,
+/// `-` and `_`
+pub fn normalize_tag(tag: &str) -> Result<String, CustomerError> {
+    let tag = tag.trim().to_lowercase();
+    let valid = !tag.is_empty()
+        && tag.chars().count() <= TAG_MAX_LEN
+        && tag.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(CustomerError::InvalidTag(tag));
+    }
+    Ok(tag)
+}
+
+/// A tag in use and how many customers have it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, FromQueryResult)]
+pub struct TagCount {
+    pub tag: String,
+    pub customers: i64,
+}
+
+/// Customer tag service
+pub struct TagService;
+
+impl TagService {
+    /// A customer's tags, alphabetically
+    pub async fn tags_of<C: ConnectionTrait>(db: &C, mid: i32, cid: i32) -> Result<Vec<String>, CustomerError> {
+        Ok(CustomerTags::find()
+            .select_only()
+            .column(Column::Tag)
+            .filter(Column::Mid.eq(mid))
+            .filter(Column::Cid.eq(cid))
+            .order_by_asc(Column::Tag)
+            .into_tuple()
+            .all(db)
+            .await?)
+    }
+
+    /// The merchant's tags in use, alphabetically
+    pub async fn counts<C: ConnectionTrait>(db: &C, mid: i32) -> Result<Vec<TagCount>, CustomerError> {
+        Ok(CustomerTags::find()
+            .select_only()
+            .column(Column::Tag)
+            .column_as(Column::Cid.count(), "customers")
+            .filter(Column::Mid.eq(mid))
+            .group_by(Column::Tag)
+            .order_by_asc(Column::Tag)
+            .into_model::<TagCount>()
+            .all(db)
+            .await?)
+    }
+
+    /// Give `tag` to the merchant's customers among `cids`, returning how
+    /// many did not have it yet. Ids of other merchants' customers are
+    /// skipped.
+    #[instrument(skip_all, fields(mid = mid, customers = cids.len()))]
+    pub async fn tag<C: ConnectionTrait>(db: &C, mid: i32, cids: &[i32], tag: &str) -> Result<u64, CustomerError> {
+        let tag = normalize_tag(tag)?;
+        let theirs: Vec<i32> = Customers::find()
+            .select_only()
+            .column(::entity::customers::Column::Cid)
+            .filter(::entity::customers::Column::Mid.eq(mid))
+            .filter(::entity::customers::Column::Cid.is_in(cids.iter().copied()))
+            .into_tuple()
+            .all(db)
+            .await?;
+        if theirs.is_empty() {
+            return Ok(0);
+        }
+
+        let now = Timestamp::now();
+        let rows = theirs.into_iter().map(|cid| customer_tags::ActiveModel {
+            mid: Set(mid),
+            cid: Set(cid),
+            tag: Set(tag.clone()),
+            created_gmt: Set(now),
+            ..Default::default()
+        });
+        // Customers who already have the tag keep it as it was
+        Ok(CustomerTags::insert_many(rows)
+            .on_conflict(
+                OnConflict::columns([Column::Mid, Column::Cid, Column::Tag])
+                    .do_nothing()
+                    .to_owned(),
+            )
+            .exec_without_returning(db)
+            .await?)
+    }
+
+    /// Take `tag` away from the merchant's customers among `cids`,
+    /// returning how many had it
+    #[instrument(skip_all, fields(mid = mid, customers = cids.len()))]
+    pub async fn untag<C: ConnectionTrait>(db: &C, mid: i32, cids: &[i32], tag: &str) -> Result<u64, CustomerError> {
+        let tag = normalize_tag(tag)?;
+        let result = CustomerTags::delete_many()
+            .filter(Column::Mid.eq(mid))
+            .filter(Column::Cid.is_in(cids.iter().copied()))
+            .filter(Column::Tag.eq(tag))
+            .exec(db)
+            .await?;
+
+        Ok(result.rows_affected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_tag() {
+        assert_eq!(normalize_tag(" VIP ").unwrap(), "vip");
+        assert_eq!(normalize_tag("fraud-risk").unwrap(), "fraud-risk");
+        for invalid in ["", "   ", "big spender", "a,b", &"x".repeat(TAG_MAX_LEN + 1)] {
+            assert!(matches!(normalize_tag(invalid), Err(CustomerError::InvalidTag(_))), "{:?}", invalid);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tagging_only_another_merchants_customers_inserts_nothing() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([Vec::<Customer>::new()])
+            .into_connection();
+
+        assert_eq!(TagService::tag(&db, 1, &[7, 8], "vip").await.unwrap(), 0);
+        assert_eq!(db.into_transaction_log().len(), 1);
+    }
+}
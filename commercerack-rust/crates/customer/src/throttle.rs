@@ -0,0 +1,251 @@
+//! Login throttling and account lockout
+//!
+//! Failed logins are counted per (merchant, email, client IP) and, in a
+//! row with an empty IP, per (merchant, email) across every IP. After a
+//! few failures from one IP each further attempt from it waits twice as
+//! long as the last. Enough failures across all IPs lock the account for
+//! a while and record a `customer.locked_out` event carrying an unlock
+//! token, for the merchant's integration to email to the customer.
+//!
+//! Emails are counted whether or not a customer has them, so throttling
+//! says nothing about which emails exist. Only hashes of emails are kept.
+
+use chrono::{Duration, Utc};
+use commercerack_core::Timestamp;
+use sea_orm::sea_query::Expr;
+use sea_orm::*;
+use sha2::{Digest, Sha256};
+use ::entity::login_attempts::Column;
+use ::entity::prelude::*;
+use commercerack_events::{outbox, DomainEvent};
+use tracing::{instrument, warn};
+
+use crate::tokens::{generate_token, hash_token};
+use crate::{CustomerError, CustomerService};
+
+/// Failures from one IP before backoff starts
+pub const FREE_ATTEMPTS: i32 = 3;
+
+/// Longest wait between attempts from one IP
+pub const MAX_BACKOFF_SECS: i64 = 15 * 60;
+
+/// Failures across all IPs that lock the account
+pub const LOCKOUT_FAILURES: i32 = 10;
+
+pub const LOCKOUT_SECS: i64 = 30 * 60;
+
+/// Failures older than this are forgotten
+pub const WINDOW_SECS: i64 = 60 * 60;
+
+/// IP of the row counting failures from every IP
+const EVERY_IP: &str = "";
+
+/// Seconds an IP must wait after its `failures`th failure
+pub fn backoff_secs(failures: i32) -> i64 {
+    if failures < FREE_ATTEMPTS {
+        return 0;
+    }
+    let doublings = (failures - FREE_ATTEMPTS).min(20) as u32;
+    (2i64 << doublings).min(MAX_BACKOFF_SECS)
+}
+
+fn email_hash(email: &str) -> String {
+    format!("{:x}", Sha256::digest(email.trim().to_lowercase().as_bytes()))
+}
+
+pub struct LoginThrottle;
+
+impl LoginThrottle {
+    /// Fail with [`CustomerError::Throttled`] if `ip` must wait before
+    /// trying `email` again or the account is locked
+    pub async fn check<C: ConnectionTrait>(db: &C, mid: i32, email: &str, ip: &str) -> Result<(), CustomerError> {
+        let now = Utc::now().timestamp();
+        let rows = LoginAttempts::find()
+            .filter(Column::Mid.eq(mid))
+            .filter(Column::EmailHash.eq(email_hash(email)))
+            .filter(Column::Ip.is_in([ip, EVERY_IP]))
+            .all(db)
+            .await?;
+
+        let wait = rows
+            .iter()
+            .map(|row| {
+                let until = match row.ip.as_str() {
+                    EVERY_IP => row.locked_until_gmt.map_or(0, i64::from),
+                    _ if now - i64::from(row.last_failed_gmt) > WINDOW_SECS => 0,
+                    _ => i64::from(row.last_failed_gmt) + backoff_secs(row.failures),
+                };
+                until - now
+            })
+            .max()
+            .unwrap_or(0);
+        if wait > 0 {
+            return Err(CustomerError::Throttled { retry_after: wait as u64 });
+        }
+        Ok(())
+    }
+
+    /// Count a failed login, locking the account once it has failed too
+    /// often from anywhere
+    #[instrument(skip_all, fields(mid = mid))]
+    pub async fn failed<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: i32,
+        email: &str,
+        ip: &str,
+    ) -> Result<(), CustomerError> {
+        let now = Timestamp::now();
+        let hash = email_hash(email);
+
+        let txn = db.begin().await?;
+        Self::count(&txn, mid, &hash, ip, now).await?;
+        let account = Self::count(&txn, mid, &hash, EVERY_IP, now).await?;
+
+        if account.failures >= LOCKOUT_FAILURES {
+            let token = generate_token();
+            let locked_until = now + Duration::seconds(LOCKOUT_SECS);
+            // Counting starts over once the lock ends
+            LoginAttempts::update_many()
+                .col_expr(Column::Failures, Expr::value(0))
+                .col_expr(Column::LockedUntilGmt, Expr::value(locked_until))
+                .col_expr(Column::UnlockTokenHash, Expr::value(hash_token(&token)))
+                .filter(Column::Id.eq(account.id))
+                .exec(&txn)
+                .await?;
+
+            match CustomerService::find_by_email(&txn, mid, email).await? {
+                Some(customer) => {
+                    let event = DomainEvent::CustomerLockedOut {
+                        mid: mid.into(),
+                        cid: customer.cid.into(),
+                        email: customer.email,
+                        unlock_token: token,
+                        locked_until_gmt: locked_until,
+                    };
+                    outbox::record(&txn, &event).await?;
+                }
+                None => warn!(mid, "login locked for an unknown email"),
+            }
+        }
+        txn.commit().await?;
+        Ok(())
+    }
+
+    /// Add a failure to one row, starting over if the last was long ago
+    async fn count<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        email_hash: &str,
+        ip: &str,
+        now: Timestamp,
+    ) -> Result<LoginAttempt, CustomerError> {
+        let row = LoginAttempt::find_by_statement(Statement::from_sql_and_values(
+            DbBackend::Postgres,
+            "INSERT INTO login_attempts (mid, email_hash, ip, failures, last_failed_gmt) \
+             VALUES ($1, $2, $3, 1, $4) \
+             ON CONFLICT (mid, email_hash, ip) DO UPDATE SET \
+             failures = CASE WHEN login_attempts.last_failed_gmt < $5 THEN 1 ELSE login_attempts.failures + 1 END, \
+             last_failed_gmt = $4 \
+             RETURNING *",
+            [
+                mid.into(),
+                email_hash.into(),
+                ip.into(),
+                now.into(),
+                (now - Duration::seconds(WINDOW_SECS)).into(),
+            ],
+        ))
+        .one(db)
+        .await?
+        .ok_or_else(|| DbErr::RecordNotInserted)?;
+        Ok(row)
+    }
+
+    /// Forget the failures for `email` after a successful login
+    pub async fn succeeded<C: ConnectionTrait>(db: &C, mid: i32, email: &str) -> Result<(), CustomerError> {
+        LoginAttempts::delete_many()
+            .filter(Column::Mid.eq(mid))
+            .filter(Column::EmailHash.eq(email_hash(email)))
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+
+    /// Lift the lock an unlock token was issued for. Returns false for an
+    /// unknown or already used token.
+    #[instrument(skip_all)]
+    pub async fn unlock<C: ConnectionTrait>(db: &C, token: &str) -> Result<bool, CustomerError> {
+        let Some(account) = LoginAttempts::find()
+            .filter(Column::UnlockTokenHash.eq(hash_token(token)))
+            .one(db)
+            .await?
+        else {
+            return Ok(false);
+        };
+
+        LoginAttempts::delete_many()
+            .filter(Column::Mid.eq(account.mid))
+            .filter(Column::EmailHash.eq(account.email_hash))
+            .exec(db)
+            .await?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attempts(ip: &str, failures: i32, last_failed_gmt: i64, locked_until_gmt: Option<i64>) -> LoginAttempt {
+        LoginAttempt {
+            id: 1,
+            mid: 1,
+            email_hash: email_hash("shopper@example.com"),
+            ip: ip.to_string(),
+            failures,
+            last_failed_gmt: Timestamp::from_unix(last_failed_gmt),
+            locked_until_gmt: locked_until_gmt.map(Timestamp::from_unix),
+            unlock_token_hash: None,
+        }
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_the_cap() {
+        assert_eq!(backoff_secs(FREE_ATTEMPTS - 1), 0);
+        assert_eq!(backoff_secs(FREE_ATTEMPTS), 2);
+        assert_eq!(backoff_secs(FREE_ATTEMPTS + 1), 4);
+        assert_eq!(backoff_secs(FREE_ATTEMPTS + 3), 16);
+        assert_eq!(backoff_secs(1000), MAX_BACKOFF_SECS);
+    }
+
+    #[test]
+    fn test_email_hash_ignores_case_and_spaces() {
+        assert_eq!(email_hash(" Shopper@Example.com"), email_hash("shopper@example.com"));
+    }
+
+    #[tokio::test]
+    async fn test_locked_account_is_throttled_from_any_ip() {
+        let now = Utc::now().timestamp();
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![attempts(EVERY_IP, 0, now, Some(now + 600))]])
+            .into_connection();
+
+        match LoginThrottle::check(&db, 1, "shopper@example.com", "203.0.113.9").await {
+            Err(CustomerError::Throttled { retry_after }) => assert!((599..=600).contains(&retry_after)),
+            other => panic!("expected Throttled, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_old_failures_do_not_throttle() {
+        let now = Utc::now().timestamp();
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![
+                attempts("203.0.113.9", 8, now - WINDOW_SECS - 1, None),
+                attempts(EVERY_IP, 8, now - WINDOW_SECS - 1, Some(now - 60)),
+            ]])
+            .into_connection();
+
+        assert!(LoginThrottle::check(&db, 1, "shopper@example.com", "203.0.113.9").await.is_ok());
+    }
+}
@@ -0,0 +1,235 @@
+//! Long-lived refresh tokens with rotation and revocation
+//!
+//! Only a SHA-256 hash of each token is stored. Every successful refresh
+//! revokes the presented token and issues a replacement; presenting an
+//! already-rotated token is treated as theft and revokes every token the
+//! customer holds.
+
+use chrono::Duration;
+use commercerack_core::Timestamp;
+use sea_orm::*;
+use sea_orm::sea_query::Expr;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use uuid::Uuid;
+use ::entity::prelude::*;
+
+/// Refresh token lifetime
+pub const REFRESH_TOKEN_TTL_SECS: i64 = 30 * 24 * 60 * 60;
+
+#[derive(Error, Debug)]
+pub enum RefreshError {
+    #[error("Refresh token not recognised")]
+    Unknown,
+
+    #[error("Refresh token expired")]
+    Expired,
+
+    #[error("Refresh token was already used")]
+    Reused,
+
+    #[error("Session was signed out")]
+    SessionRevoked,
+
+    #[error("Database error: {0}")]
+    Db(#[from] DbErr),
+}
+
+/// A freshly issued refresh token. `token` is only ever available here.
+#[derive(Debug, Clone)]
+pub struct IssuedToken {
+    pub token: String,
+    pub record: RefreshToken,
+}
+
+pub(crate) fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+pub(crate) fn generate_token() -> String {
+    format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
+}
+
+/// Refresh token service
+pub struct RefreshTokenService;
+
+impl RefreshTokenService {
+    /// Issue a new refresh token for a customer, in `session_id` if the
+    /// customer signed in with one (see [`crate::sessions`])
+    pub async fn issue<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        cid: i32,
+        session_id: Option<i32>,
+    ) -> Result<IssuedToken, RefreshError> {
+        let token = generate_token();
+        let now = Timestamp::now();
+
+        let record = ::entity::refresh_tokens::ActiveModel {
+            mid: Set(mid),
+            cid: Set(cid),
+            token_hash: Set(hash_token(&token)),
+            created_gmt: Set(now),
+            expires_gmt: Set(now + Duration::seconds(REFRESH_TOKEN_TTL_SECS)),
+            revoked_gmt: Set(None),
+            replaced_by: Set(None),
+            session_id: Set(session_id),
+            ..Default::default()
+        }
+        .insert(db)
+        .await?;
+
+        Ok(IssuedToken { token, record })
+    }
+
+    /// Find the stored record for a raw token
+    pub async fn find<C: ConnectionTrait>(
+        db: &C,
+        token: &str,
+    ) -> Result<Option<RefreshToken>, RefreshError> {
+        let record = RefreshTokens::find()
+            .filter(::entity::refresh_tokens::Column::TokenHash.eq(hash_token(token)))
+            .one(db)
+            .await?;
+
+        Ok(record)
+    }
+
+    /// Exchange a refresh token for a new one, revoking the old token
+    pub async fn rotate(
+        db: &DatabaseConnection,
+        token: &str,
+    ) -> Result<IssuedToken, RefreshError> {
+        let txn = db.begin().await?;
+
+        // Lock the row so two refreshes with one token can't both rotate it;
+        // the second waits and then sees the token revoked
+        let record = RefreshTokens::find()
+            .filter(::entity::refresh_tokens::Column::TokenHash.eq(hash_token(token)))
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .ok_or(RefreshError::Unknown)?;
+
+        // Signing a session out revokes its tokens, which is not a replay
+        if let Some(session_id) = record.session_id {
+            let session = Sessions::find_by_id(session_id).one(&txn).await?;
+            if session.is_none_or(|session| session.revoked_gmt.is_some()) {
+                return Err(RefreshError::SessionRevoked);
+            }
+        }
+        if record.revoked_gmt.is_some() {
+            // Somebody is replaying a rotated token; burn the whole family
+            Self::revoke_all(&txn, record.mid, record.cid).await?;
+            txn.commit().await?;
+            return Err(RefreshError::Reused);
+        }
+        if record.expires_gmt <= Timestamp::now() {
+            return Err(RefreshError::Expired);
+        }
+
+        let issued = Self::issue(&txn, record.mid, record.cid, record.session_id).await?;
+
+        let mut active: ::entity::refresh_tokens::ActiveModel = record.into();
+        active.revoked_gmt = Set(Some(Timestamp::now()));
+        active.replaced_by = Set(Some(issued.record.id));
+        active.update(&txn).await?;
+
+        txn.commit().await?;
+        Ok(issued)
+    }
+
+    /// Revoke a single token (logout). Returns false if it was unknown or already revoked
+    pub async fn revoke(
+        db: &DatabaseConnection,
+        token: &str,
+    ) -> Result<bool, RefreshError> {
+        let result = RefreshTokens::update_many()
+            .col_expr(
+                ::entity::refresh_tokens::Column::RevokedGmt,
+                Expr::value(Timestamp::now()),
+            )
+            .filter(::entity::refresh_tokens::Column::TokenHash.eq(hash_token(token)))
+            .filter(::entity::refresh_tokens::Column::RevokedGmt.is_null())
+            .exec(db)
+            .await?;
+
+        Ok(result.rows_affected > 0)
+    }
+
+    /// Revoke every outstanding token and session of a customer (password
+    /// change, reuse detection)
+    pub async fn revoke_all<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        cid: i32,
+    ) -> Result<u64, RefreshError> {
+        Sessions::update_many()
+            .col_expr(::entity::sessions::Column::RevokedGmt, Expr::value(Timestamp::now()))
+            .filter(::entity::sessions::Column::Mid.eq(mid))
+            .filter(::entity::sessions::Column::Cid.eq(cid))
+            .filter(::entity::sessions::Column::RevokedGmt.is_null())
+            .exec(db)
+            .await?;
+
+        let result = RefreshTokens::update_many()
+            .col_expr(
+                ::entity::refresh_tokens::Column::RevokedGmt,
+                Expr::value(Timestamp::now()),
+            )
+            .filter(::entity::refresh_tokens::Column::Mid.eq(mid))
+            .filter(::entity::refresh_tokens::Column::Cid.eq(cid))
+            .filter(::entity::refresh_tokens::Column::RevokedGmt.is_null())
+            .exec(db)
+            .await?;
+
+        Ok(result.rows_affected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens_are_unique_and_hashed() {
+        let a = generate_token();
+        let b = generate_token();
+        assert_ne!(a, b);
+        assert_eq!(a.len(), 64);
+
+        let hash = hash_token(&a);
+        assert_eq!(hash.len(), 64);
+        assert_ne!(hash, a);
+        assert_eq!(hash, hash_token(&a));
+    }
+
+    #[tokio::test]
+    async fn test_rotate_locks_the_token() {
+        let rotated = RefreshToken {
+            id: 1,
+            mid: 1,
+            cid: 42,
+            token_hash: hash_token("token"),
+            created_gmt: Timestamp::from_unix(1_700_000_000),
+            expires_gmt: Timestamp::from_unix(4_000_000_000),
+            revoked_gmt: Some(Timestamp::from_unix(1_700_000_100)),
+            replaced_by: Some(2),
+            session_id: None,
+        };
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![rotated]])
+            .append_exec_results([
+                MockExecResult { last_insert_id: 0, rows_affected: 0 },
+                MockExecResult { last_insert_id: 0, rows_affected: 1 },
+            ])
+            .into_connection();
+
+        let result = RefreshTokenService::rotate(&db, "token").await;
+        assert!(matches!(result, Err(RefreshError::Reused)));
+
+        let log = db.into_transaction_log();
+        let select = log[0].statements()[1].to_string();
+        assert!(select.contains("refresh_tokens") && select.ends_with("FOR UPDATE"), "{select}");
+    }
+}
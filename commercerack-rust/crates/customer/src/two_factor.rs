@@ -0,0 +1,436 @@
+//! TOTP two-factor authentication (RFC 6238)
+//!
+//! Enrolling stores a random secret and returns an `otpauth://` URI for an
+//! authenticator app to scan. Nothing changes until a first code from the
+//! app is confirmed, which turns two-factor on and issues single-use backup
+//! codes. From then on logging in takes a current code or an unused backup
+//! code as well as the password. Staff are customer records whose tokens
+//! carry an elevated role, so they enroll the same way.
+//!
+//! Secrets are sealed like other PII (see [`crate::pii`]) and backup codes
+//! are only kept as hashes. Each time step's code is accepted once.
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use chrono::Utc;
+use commercerack_core::Timestamp;
+use hmac::{Hmac, Mac};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use sea_orm::sea_query::Expr;
+use sea_orm::*;
+use sha1::Sha1;
+use ::entity::prelude::*;
+use ::entity::{customer_backup_codes, customer_two_factor};
+use tracing::instrument;
+
+use crate::pii;
+use crate::tokens::hash_token;
+use crate::CustomerError;
+
+/// Digits in a code
+pub const DIGITS: usize = 6;
+
+/// Seconds each code is current for
+pub const STEP_SECS: i64 = 30;
+
+/// Steps either side of the current one still accepted, for clock drift
+pub const SKEW_STEPS: i64 = 1;
+
+/// Backup codes issued at a time
+pub const BACKUP_CODES: usize = 10;
+
+/// Secret length in bytes, the HMAC-SHA1 output size RFC 4226 recommends
+const SECRET_LEN: usize = 20;
+
+const BASE32: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// RFC 4648 base32 without padding, the form authenticator apps take
+/// secrets in
+pub fn base32_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(5) * 8);
+    let (mut buffer, mut bits) = (0u32, 0);
+    for &byte in bytes {
+        buffer = (buffer << 8) | u32::from(byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(BASE32[((buffer >> bits) & 31) as usize] as char);
+        }
+        buffer &= (1 << bits) - 1;
+    }
+    if bits > 0 {
+        out.push(BASE32[((buffer << (5 - bits)) & 31) as usize] as char);
+    }
+    out
+}
+
+/// Inverse of [`base32_encode`], ignoring case and padding
+pub fn base32_decode(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(text.len() * 5 / 8);
+    let (mut buffer, mut bits) = (0u32, 0);
+    for c in text.trim_end_matches('=').bytes() {
+        let value = BASE32.iter().position(|&b| b == c.to_ascii_uppercase())? as u32;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Some(out)
+}
+
+/// RFC 4226 code for counter `step`
+pub fn code_at(secret: &[u8], step: i64) -> String {
+    let mut mac = <Hmac<Sha1> as Mac>::new_from_slice(secret).expect("HMAC takes keys of any length");
+    mac.update(&step.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    let offset = usize::from(digest[digest.len() - 1] & 0x0f);
+    let binary = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+    format!("{:0width$}", binary % 10u32.pow(DIGITS as u32), width = DIGITS)
+}
+
+/// `otpauth://` URI that authenticator apps enroll from, usually shown as
+/// a QR code
+pub fn provisioning_uri(issuer: &str, account: &str, secret: &str) -> String {
+    let issuer = utf8_percent_encode(issuer, NON_ALPHANUMERIC).to_string();
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        issuer,
+        utf8_percent_encode(account, NON_ALPHANUMERIC),
+        secret,
+        issuer,
+        DIGITS,
+        STEP_SECS
+    )
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// The step `code` belongs to, if it is current at `now` and later than
+/// `last_step`
+fn matching_step(secret: &[u8], code: &str, now: i64, last_step: i64) -> Option<i64> {
+    let current = now.div_euclid(STEP_SECS);
+    (current - SKEW_STEPS..=current + SKEW_STEPS)
+        .filter(|&step| step > last_step)
+        .find(|&step| constant_time_eq(code_at(secret, step).as_bytes(), code.as_bytes()))
+}
+
+fn is_totp_code(code: &str) -> bool {
+    code.len() == DIGITS && code.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Backup codes may be typed in any case, with or without the dash
+fn normalize_backup_code(code: &str) -> String {
+    code.chars().filter(char::is_ascii_alphanumeric).map(|c| c.to_ascii_lowercase()).collect()
+}
+
+fn generate_backup_code() -> String {
+    let mut bytes = [0u8; 5];
+    OsRng.fill_bytes(&mut bytes);
+    let code = base32_encode(&bytes).to_lowercase();
+    format!("{}-{}", &code[..4], &code[4..])
+}
+
+/// A started enrollment. `secret` is only ever available here.
+#[derive(Debug, Clone)]
+pub struct Enrollment {
+    /// Base32, for entering into an app by hand
+    pub secret: String,
+    pub provisioning_uri: String,
+}
+
+/// Two-factor service
+pub struct TwoFactorService;
+
+impl TwoFactorService {
+    async fn find<C: ConnectionTrait>(db: &C, mid: i32, cid: i32) -> Result<Option<CustomerTwoFactor>, CustomerError> {
+        Ok(CustomerTwoFactors::find()
+            .filter(customer_two_factor::Column::Mid.eq(mid))
+            .filter(customer_two_factor::Column::Cid.eq(cid))
+            .one(db)
+            .await?)
+    }
+
+    fn secret(row: &CustomerTwoFactor) -> Result<Vec<u8>, CustomerError> {
+        base32_decode(&pii::open(&row.secret)?)
+            .ok_or_else(|| DbErr::Custom(format!("two-factor secret of customer {} is not base32", row.cid)).into())
+    }
+
+    /// Whether a customer has confirmed enrollment, and so needs a code to
+    /// log in
+    pub async fn is_enabled<C: ConnectionTrait>(db: &C, mid: i32, cid: i32) -> Result<bool, CustomerError> {
+        Ok(Self::find(db, mid, cid).await?.is_some_and(|row| row.enabled_gmt.is_some()))
+    }
+
+    /// Start enrolling with a fresh secret, replacing any unconfirmed one
+    #[instrument(skip_all, fields(mid = mid, cid = cid))]
+    pub async fn enroll<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: i32,
+        cid: i32,
+        issuer: &str,
+        account: &str,
+    ) -> Result<Enrollment, CustomerError> {
+        let mut bytes = [0u8; SECRET_LEN];
+        OsRng.fill_bytes(&mut bytes);
+        let secret = base32_encode(&bytes);
+
+        let txn = db.begin().await?;
+        match Self::find(&txn, mid, cid).await? {
+            Some(row) if row.enabled_gmt.is_some() => return Err(CustomerError::TwoFactorEnabled),
+            Some(row) => {
+                CustomerTwoFactors::delete_by_id(row.id).exec(&txn).await?;
+            }
+            None => {}
+        }
+        customer_two_factor::ActiveModel {
+            mid: Set(mid),
+            cid: Set(cid),
+            secret: Set(pii::seal(&secret)),
+            enabled_gmt: Set(None),
+            last_step: Set(0),
+            created_gmt: Set(Timestamp::now()),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+        txn.commit().await?;
+
+        Ok(Enrollment {
+            provisioning_uri: provisioning_uri(issuer, account, &secret),
+            secret,
+        })
+    }
+
+    /// Turn two-factor on once `code` shows the app holds the secret.
+    /// Returns the backup codes, which are never available again.
+    #[instrument(skip_all, fields(mid = mid, cid = cid))]
+    pub async fn confirm<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: i32,
+        cid: i32,
+        code: &str,
+    ) -> Result<Vec<String>, CustomerError> {
+        let row = Self::find(db, mid, cid).await?.ok_or(CustomerError::TwoFactorNotEnrolled)?;
+        if row.enabled_gmt.is_some() {
+            return Err(CustomerError::TwoFactorEnabled);
+        }
+        let now = Utc::now().timestamp();
+        let step = matching_step(&Self::secret(&row)?, code.trim(), now, row.last_step)
+            .ok_or(CustomerError::InvalidTwoFactorCode)?;
+
+        let txn = db.begin().await?;
+        CustomerTwoFactors::update_many()
+            .col_expr(customer_two_factor::Column::EnabledGmt, Expr::value(now as i32))
+            .col_expr(customer_two_factor::Column::LastStep, Expr::value(step))
+            .filter(customer_two_factor::Column::Id.eq(row.id))
+            .exec(&txn)
+            .await?;
+        let codes = Self::replace_backup_codes(&txn, mid, cid).await?;
+        txn.commit().await?;
+        Ok(codes)
+    }
+
+    /// Check a second factor: a current code, or an unused backup code,
+    /// which is spent
+    #[instrument(skip_all, fields(mid = mid, cid = cid))]
+    pub async fn verify<C: ConnectionTrait>(db: &C, mid: i32, cid: i32, code: &str) -> Result<(), CustomerError> {
+        let row = Self::find(db, mid, cid)
+            .await?
+            .filter(|row| row.enabled_gmt.is_some())
+            .ok_or(CustomerError::TwoFactorNotEnrolled)?;
+        let code = code.trim();
+        let now = Utc::now().timestamp();
+
+        // Both updates are conditional so a code raced in twice counts once
+        let spent = if is_totp_code(code) {
+            let Some(step) = matching_step(&Self::secret(&row)?, code, now, row.last_step) else {
+                return Err(CustomerError::InvalidTwoFactorCode);
+            };
+            CustomerTwoFactors::update_many()
+                .col_expr(customer_two_factor::Column::LastStep, Expr::value(step))
+                .filter(customer_two_factor::Column::Id.eq(row.id))
+                .filter(customer_two_factor::Column::LastStep.lt(step))
+                .exec(db)
+                .await?
+        } else {
+            CustomerBackupCodes::update_many()
+                .col_expr(customer_backup_codes::Column::UsedGmt, Expr::value(now as i32))
+                .filter(customer_backup_codes::Column::Mid.eq(mid))
+                .filter(customer_backup_codes::Column::Cid.eq(cid))
+                .filter(customer_backup_codes::Column::CodeHash.eq(hash_token(&normalize_backup_code(code))))
+                .filter(customer_backup_codes::Column::UsedGmt.is_null())
+                .exec(db)
+                .await?
+        };
+        if spent.rows_affected == 0 {
+            return Err(CustomerError::InvalidTwoFactorCode);
+        }
+        Ok(())
+    }
+
+    /// Turn two-factor off, given a code to prove it is the owner asking
+    #[instrument(skip_all, fields(mid = mid, cid = cid))]
+    pub async fn disable<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: i32,
+        cid: i32,
+        code: &str,
+    ) -> Result<(), CustomerError> {
+        Self::verify(db, mid, cid, code).await?;
+
+        let txn = db.begin().await?;
+        CustomerTwoFactors::delete_many()
+            .filter(customer_two_factor::Column::Mid.eq(mid))
+            .filter(customer_two_factor::Column::Cid.eq(cid))
+            .exec(&txn)
+            .await?;
+        CustomerBackupCodes::delete_many()
+            .filter(customer_backup_codes::Column::Mid.eq(mid))
+            .filter(customer_backup_codes::Column::Cid.eq(cid))
+            .exec(&txn)
+            .await?;
+        txn.commit().await?;
+        Ok(())
+    }
+
+    /// Replace every backup code, given a code to prove it is the owner asking
+    #[instrument(skip_all, fields(mid = mid, cid = cid))]
+    pub async fn regenerate_backup_codes<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: i32,
+        cid: i32,
+        code: &str,
+    ) -> Result<Vec<String>, CustomerError> {
+        Self::verify(db, mid, cid, code).await?;
+
+        let txn = db.begin().await?;
+        let codes = Self::replace_backup_codes(&txn, mid, cid).await?;
+        txn.commit().await?;
+        Ok(codes)
+    }
+
+    async fn replace_backup_codes<C: ConnectionTrait>(db: &C, mid: i32, cid: i32) -> Result<Vec<String>, CustomerError> {
+        CustomerBackupCodes::delete_many()
+            .filter(customer_backup_codes::Column::Mid.eq(mid))
+            .filter(customer_backup_codes::Column::Cid.eq(cid))
+            .exec(db)
+            .await?;
+
+        let codes: Vec<String> = (0..BACKUP_CODES).map(|_| generate_backup_code()).collect();
+        CustomerBackupCodes::insert_many(codes.iter().map(|code| customer_backup_codes::ActiveModel {
+            mid: Set(mid),
+            cid: Set(cid),
+            code_hash: Set(hash_token(&normalize_backup_code(code))),
+            used_gmt: Set(None),
+            ..Default::default()
+        }))
+        .exec(db)
+        .await?;
+        Ok(codes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    fn enrolled(enabled: bool, last_step: i64) -> CustomerTwoFactor {
+        CustomerTwoFactor {
+            id: 3,
+            mid: 1,
+            cid: 42,
+            secret: base32_encode(RFC_SECRET),
+            enabled_gmt: enabled.then_some(Timestamp::from_unix(1_700_000_000)),
+            last_step,
+            created_gmt: Timestamp::from_unix(1_700_000_000),
+        }
+    }
+
+    #[test]
+    fn test_base32_round_trip() {
+        assert_eq!(base32_encode(b"foobar"), "MZXW6YTBOI");
+        assert_eq!(base32_decode("mzxw6ytboi======").unwrap(), b"foobar");
+        assert_eq!(base32_decode(&base32_encode(RFC_SECRET)).unwrap(), RFC_SECRET);
+        assert!(base32_decode("not base32!").is_none());
+    }
+
+    #[test]
+    fn test_rfc_6238_vectors() {
+        // The RFC lists 8 digits; these are their last 6
+        assert_eq!(code_at(RFC_SECRET, 59 / STEP_SECS), "287082");
+        assert_eq!(code_at(RFC_SECRET, 1_111_111_109 / STEP_SECS), "081804");
+        assert_eq!(code_at(RFC_SECRET, 2_000_000_000 / STEP_SECS), "279037");
+    }
+
+    #[test]
+    fn test_codes_match_within_skew_and_only_once() {
+        let now = 1_111_111_109;
+        let step = now / STEP_SECS;
+        let previous = code_at(RFC_SECRET, step - 1);
+
+        assert_eq!(matching_step(RFC_SECRET, &previous, now, 0), Some(step - 1));
+        assert_eq!(matching_step(RFC_SECRET, &previous, now, step - 1), None);
+        assert_eq!(matching_step(RFC_SECRET, &code_at(RFC_SECRET, step - 2), now, 0), None);
+    }
+
+    #[test]
+    fn test_provisioning_uri_escapes_labels() {
+        let uri = provisioning_uri("Acme Shop", "sam@example.com", "MZXW6YTBOI");
+        assert_eq!(
+            uri,
+            "otpauth://totp/Acme%20Shop:sam%40example%2Ecom?secret=MZXW6YTBOI&issuer=Acme%20Shop&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+
+    #[test]
+    fn test_backup_codes_are_typed_loosely() {
+        let code = generate_backup_code();
+        assert_eq!(code.len(), 9);
+        assert!(!is_totp_code(&code));
+        assert_eq!(normalize_backup_code(&code.to_uppercase().replace('-', " ")), code.replace('-', ""));
+    }
+
+    #[tokio::test]
+    async fn test_verify_rejects_wrong_code_without_writing() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![enrolled(true, 0)]])
+            .into_connection();
+
+        let result = TwoFactorService::verify(&db, 1, 42, "000000").await;
+        assert!(matches!(result, Err(CustomerError::InvalidTwoFactorCode)));
+        assert_eq!(db.into_transaction_log().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_verify_needs_confirmed_enrollment() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![enrolled(false, 0)]])
+            .into_connection();
+
+        let code = code_at(RFC_SECRET, Utc::now().timestamp() / STEP_SECS);
+        let result = TwoFactorService::verify(&db, 1, 42, &code).await;
+        assert!(matches!(result, Err(CustomerError::TwoFactorNotEnrolled)));
+    }
+
+    #[tokio::test]
+    async fn test_enroll_refuses_when_enabled() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![enrolled(true, 0)]])
+            .into_connection();
+
+        let result = TwoFactorService::enroll(&db, 1, 42, "Acme", "sam@example.com").await;
+        assert!(matches!(result, Err(CustomerError::TwoFactorEnabled)));
+    }
+}
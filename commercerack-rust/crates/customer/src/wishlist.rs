@@ -0,0 +1,356 @@
+//! Customer wishlists using SeaORM
+//!
+//! Every customer has at most one wishlist of saved SKUs, created the first
+//! time something is saved. Sharing gives the wishlist an unguessable token
+//! that anyone can read it by; unsharing clears the token so old links stop
+//! working.
+
+use commercerack_core::Timestamp;
+use sea_orm::sea_query::{Expr, OnConflict};
+use sea_orm::*;
+use ::entity::prelude::*;
+use ::entity::{wishlist_items, wishlists};
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::CustomerError;
+
+/// A saved item together with its SKU's current title and price
+#[derive(Debug, Clone)]
+pub struct WishlistEntry {
+    pub item: WishlistItem,
+    /// `None` once the SKU has been deleted
+    pub sku: Option<Sku>,
+}
+
+/// How many wishlists a product is saved in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromQueryResult)]
+pub struct ProductWishlistCount {
+    pub pid: i32,
+    pub count: i64,
+}
+
+/// Wishlist service
+pub struct WishlistService;
+
+impl WishlistService {
+    /// The customer's wishlist, if they have one
+    pub async fn find<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        cid: i32,
+    ) -> Result<Option<Wishlist>, CustomerError> {
+        let wishlist = Wishlists::find()
+            .filter(wishlists::Column::Mid.eq(mid))
+            .filter(wishlists::Column::Cid.eq(cid))
+            .one(db)
+            .await?;
+
+        Ok(wishlist)
+    }
+
+    /// The customer's wishlist, created empty if they have none yet
+    pub async fn get_or_create<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        cid: i32,
+    ) -> Result<Wishlist, CustomerError> {
+        if let Some(wishlist) = Self::find(db, mid, cid).await? {
+            return Ok(wishlist);
+        }
+
+        // Two first saves may race; the unique (mid, cid) index keeps one
+        let now = Timestamp::now();
+        Wishlists::insert(wishlists::ActiveModel {
+            mid: Set(mid),
+            cid: Set(cid),
+            share_token: Set(None),
+            created_gmt: Set(now),
+            modified_gmt: Set(now),
+            ..Default::default()
+        })
+        .on_conflict(
+            OnConflict::columns([wishlists::Column::Mid, wishlists::Column::Cid])
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec_without_returning(db)
+        .await?;
+
+        Self::find(db, mid, cid).await?.ok_or(CustomerError::NotFound)
+    }
+
+    /// Items of a wishlist, most recently saved first
+    pub async fn entries<C: ConnectionTrait>(
+        db: &C,
+        wishlist: &Wishlist,
+    ) -> Result<Vec<WishlistEntry>, CustomerError> {
+        let items = WishlistItems::find()
+            .filter(wishlist_items::Column::WishlistId.eq(wishlist.id))
+            .order_by_desc(wishlist_items::Column::AddedGmt)
+            .order_by_desc(wishlist_items::Column::Id)
+            .all(db)
+            .await?;
+        if items.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let skus = Skus::find()
+            .filter(::entity::skus::Column::Mid.eq(wishlist.mid))
+            .filter(::entity::skus::Column::Sku.is_in(items.iter().map(|item| item.sku.clone())))
+            .all(db)
+            .await?;
+
+        Ok(items
+            .into_iter()
+            .map(|item| {
+                let sku = skus.iter().find(|sku| sku.sku == item.sku).cloned();
+                WishlistEntry { item, sku }
+            })
+            .collect())
+    }
+
+    /// Save a SKU to the customer's wishlist. Saving one that is already
+    /// there returns the existing item.
+    #[instrument(skip_all, fields(mid = mid, cid = cid, sku = sku))]
+    pub async fn add<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: i32,
+        cid: i32,
+        sku: &str,
+    ) -> Result<WishlistItem, CustomerError> {
+        let txn = db.begin().await?;
+
+        let found = Skus::find()
+            .filter(::entity::skus::Column::Mid.eq(mid))
+            .filter(::entity::skus::Column::Sku.eq(sku))
+            .one(&txn)
+            .await?
+            .ok_or_else(|| CustomerError::SkuNotFound(sku.to_string()))?;
+
+        let wishlist = Self::get_or_create(&txn, mid, cid).await?;
+
+        let existing = WishlistItems::find()
+            .filter(wishlist_items::Column::WishlistId.eq(wishlist.id))
+            .filter(wishlist_items::Column::Sku.eq(sku))
+            .one(&txn)
+            .await?;
+        if let Some(item) = existing {
+            txn.commit().await?;
+            return Ok(item);
+        }
+
+        let now = Timestamp::now();
+        let item = wishlist_items::ActiveModel {
+            wishlist_id: Set(wishlist.id),
+            mid: Set(mid),
+            pid: Set(found.pid),
+            sku: Set(found.sku),
+            added_gmt: Set(now),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+
+        Self::touch(&txn, wishlist.id, now).await?;
+        txn.commit().await?;
+        Ok(item)
+    }
+
+    /// Remove a SKU from the customer's wishlist. Returns whether it was there.
+    #[instrument(skip_all, fields(mid = mid, cid = cid, sku = sku))]
+    pub async fn remove<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        cid: i32,
+        sku: &str,
+    ) -> Result<bool, CustomerError> {
+        let Some(wishlist) = Self::find(db, mid, cid).await? else {
+            return Ok(false);
+        };
+
+        let result = WishlistItems::delete_many()
+            .filter(wishlist_items::Column::WishlistId.eq(wishlist.id))
+            .filter(wishlist_items::Column::Sku.eq(sku))
+            .exec(db)
+            .await?;
+        if result.rows_affected == 0 {
+            return Ok(false);
+        }
+
+        Self::touch(db, wishlist.id, Timestamp::now()).await?;
+        Ok(true)
+    }
+
+    /// Share the customer's wishlist, keeping the token it already has
+    #[instrument(skip_all, fields(mid = mid, cid = cid))]
+    pub async fn share<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        cid: i32,
+    ) -> Result<Wishlist, CustomerError> {
+        let wishlist = Self::get_or_create(db, mid, cid).await?;
+        if wishlist.share_token.is_some() {
+            return Ok(wishlist);
+        }
+
+        let mut active: wishlists::ActiveModel = wishlist.into();
+        active.share_token = Set(Some(Uuid::new_v4().simple().to_string()));
+        active.modified_gmt = Set(Timestamp::now());
+        Ok(active.update(db).await?)
+    }
+
+    /// Stop sharing the customer's wishlist; its old token no longer resolves
+    #[instrument(skip_all, fields(mid = mid, cid = cid))]
+    pub async fn unshare<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        cid: i32,
+    ) -> Result<(), CustomerError> {
+        Wishlists::update_many()
+            .col_expr(wishlists::Column::ShareToken, Expr::value(Option::<String>::None))
+            .col_expr(wishlists::Column::ModifiedGmt, Expr::value(Timestamp::now()))
+            .filter(wishlists::Column::Mid.eq(mid))
+            .filter(wishlists::Column::Cid.eq(cid))
+            .exec(db)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Look up a shared wishlist by its token
+    pub async fn find_shared<C: ConnectionTrait>(
+        db: &C,
+        token: &str,
+    ) -> Result<Option<Wishlist>, CustomerError> {
+        let wishlist = Wishlists::find()
+            .filter(wishlists::Column::ShareToken.eq(token))
+            .one(db)
+            .await?;
+
+        Ok(wishlist)
+    }
+
+    /// Number of wishlists each product is saved in, most wished first.
+    /// An empty `pids` covers every product of the merchant.
+    pub async fn counts_by_product<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        pids: &[i32],
+        limit: u64,
+    ) -> Result<Vec<ProductWishlistCount>, CustomerError> {
+        let mut query = WishlistItems::find()
+            .select_only()
+            .column(wishlist_items::Column::Pid)
+            .column_as(Expr::col(wishlist_items::Column::WishlistId).count_distinct(), "count")
+            .filter(wishlist_items::Column::Mid.eq(mid));
+        if !pids.is_empty() {
+            query = query.filter(wishlist_items::Column::Pid.is_in(pids.iter().copied()));
+        }
+
+        let counts = query
+            .group_by(wishlist_items::Column::Pid)
+            .order_by_desc(Expr::cust("count"))
+            .order_by_asc(wishlist_items::Column::Pid)
+            .limit(limit)
+            .into_model::<ProductWishlistCount>()
+            .all(db)
+            .await?;
+
+        Ok(counts)
+    }
+
+    async fn touch<C: ConnectionTrait>(db: &C, wishlist_id: i32, now: Timestamp) -> Result<(), CustomerError> {
+        Wishlists::update_many()
+            .col_expr(wishlists::Column::ModifiedGmt, Expr::value(now))
+            .filter(wishlists::Column::Id.eq(wishlist_id))
+            .exec(db)
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wishlist(share_token: Option<&str>) -> Wishlist {
+        Wishlist {
+            id: 3,
+            mid: 1,
+            cid: 42,
+            share_token: share_token.map(str::to_string),
+            created_gmt: Timestamp::from_unix(1_700_000_000),
+            modified_gmt: Timestamp::from_unix(1_700_000_000),
+        }
+    }
+
+    fn item(id: i32, sku: &str) -> WishlistItem {
+        WishlistItem {
+            id,
+            wishlist_id: 3,
+            mid: 1,
+            pid: 10,
+            sku: sku.to_string(),
+            added_gmt: Timestamp::from_unix(1_700_000_000 + i64::from(id)),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_unknown_sku_fails() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([Vec::<Sku>::new()])
+            .into_connection();
+
+        let err = WishlistService::add(&db, 1, 42, "NOPE").await.unwrap_err();
+        assert!(matches!(err, CustomerError::SkuNotFound(sku) if sku == "NOPE"));
+    }
+
+    #[tokio::test]
+    async fn test_share_keeps_existing_token() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![wishlist(Some("abc123"))]])
+            .into_connection();
+
+        let shared = WishlistService::share(&db, 1, 42).await.unwrap();
+        assert_eq!(shared.share_token.as_deref(), Some("abc123"));
+    }
+
+    #[tokio::test]
+    async fn test_remove_without_wishlist() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([Vec::<Wishlist>::new()])
+            .into_connection();
+
+        assert!(!WishlistService::remove(&db, 1, 42, "SKU-1").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_entries_keep_items_whose_sku_is_gone() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![item(2, "SKU-2"), item(1, "SKU-1")]])
+            .append_query_results([vec![Sku {
+                id: 7,
+                pid: 10,
+                mid: 1,
+                sku: "SKU-1".to_string(),
+                title: "Blue".to_string(),
+                price: Default::default(),
+                cost: Default::default(),
+                upc: String::new(),
+                inv_available: 0,
+                qty_onshelf: 0,
+                weight: Default::default(),
+                reorder_point: None,
+                reorder_quantity: 0,
+                low_stock_gmt: None,
+            }]])
+            .into_connection();
+
+        let entries = WishlistService::entries(&db, &wishlist(None)).await.unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].sku.is_none());
+        assert_eq!(entries[1].sku.as_ref().map(|sku| sku.title.as_str()), Some("Blue"));
+    }
+}
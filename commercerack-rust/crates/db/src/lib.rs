@@ -10,6 +10,7 @@ use tracing::{info, warn};
 
 pub mod errors;
 pub mod models;
+pub mod pagination;
 
 use errors::DbError;
 
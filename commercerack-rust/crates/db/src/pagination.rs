@@ -0,0 +1,227 @@
+//! Cursor (keyset) pagination shared by the list endpoints
+//!
+//! A page ends at a row; its cursor holds that row's sort key and ID, as
+//! URL-safe base64 so clients treat it as opaque. The next page is the rows
+//! strictly after that row in the listing's order. Unlike an offset this
+//! stays cheap deep into large tables and doesn't skip or repeat rows when
+//! new ones are inserted between requests.
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use commercerack_core::Timestamp;
+use rust_decimal::Decimal;
+use sea_orm::{ColumnTrait, Condition, EntityTrait, Order, QueryFilter, QueryOrder, QuerySelect, Select, Value};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum CursorError {
+    #[error("Cursor is not one returned by a previous page")]
+    Malformed,
+
+    #[error("Cursor belongs to a listing sorted by {0}")]
+    WrongSort(String),
+}
+
+/// One column value of a row's sort key
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum KeyValue {
+    #[serde(rename = "i")]
+    Int(i64),
+    #[serde(rename = "s")]
+    Text(String),
+    #[serde(rename = "d")]
+    Decimal(Decimal),
+}
+
+impl From<i32> for KeyValue {
+    fn from(value: i32) -> Self {
+        KeyValue::Int(value.into())
+    }
+}
+
+impl From<i64> for KeyValue {
+    fn from(value: i64) -> Self {
+        KeyValue::Int(value)
+    }
+}
+
+impl From<Timestamp> for KeyValue {
+    fn from(value: Timestamp) -> Self {
+        KeyValue::Int(value.unix())
+    }
+}
+
+impl From<&str> for KeyValue {
+    fn from(value: &str) -> Self {
+        KeyValue::Text(value.to_string())
+    }
+}
+
+impl From<Decimal> for KeyValue {
+    fn from(value: Decimal) -> Self {
+        KeyValue::Decimal(value)
+    }
+}
+
+impl From<KeyValue> for Value {
+    fn from(value: KeyValue) -> Self {
+        match value {
+            KeyValue::Int(value) => value.into(),
+            KeyValue::Text(value) => value.into(),
+            KeyValue::Decimal(value) => value.into(),
+        }
+    }
+}
+
+/// Position after the last row of a page
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Cursor {
+    /// Name of the ordering the key belongs to
+    #[serde(rename = "o")]
+    pub sort: String,
+    /// Sort key of the row, ending with its ID
+    #[serde(rename = "k")]
+    pub key: Vec<KeyValue>,
+}
+
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let json = serde_json::to_vec(self).map_err(|_| fmt::Error)?;
+        f.write_str(&URL_SAFE_NO_PAD.encode(json))
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = CursorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let json = URL_SAFE_NO_PAD.decode(s).map_err(|_| CursorError::Malformed)?;
+        serde_json::from_slice(&json).map_err(|_| CursorError::Malformed)
+    }
+}
+
+/// A listing's ordering: its columns, each ascending or descending, ending
+/// with a unique column (the ID) so every row has a distinct position
+#[derive(Debug, Clone)]
+pub struct Keyset<C> {
+    sort: &'static str,
+    columns: Vec<(C, Order)>,
+}
+
+impl<C: ColumnTrait> Keyset<C> {
+    pub fn new(sort: &'static str, columns: Vec<(C, Order)>) -> Self {
+        Self { sort, columns }
+    }
+
+    /// Cursor of a row given its values for the keyset's columns
+    pub fn cursor(&self, key: Vec<KeyValue>) -> Cursor {
+        Cursor {
+            sort: self.sort.to_string(),
+            key,
+        }
+    }
+
+    /// Rows that come after `cursor` in this ordering
+    pub fn after(&self, cursor: &Cursor) -> Result<Condition, CursorError> {
+        if cursor.sort != self.sort {
+            return Err(CursorError::WrongSort(cursor.sort.clone()));
+        }
+        if cursor.key.len() != self.columns.len() {
+            return Err(CursorError::Malformed);
+        }
+
+        // (a > x) OR (a = x AND b > y) OR ...
+        let mut condition = Condition::any();
+        for (i, (column, order)) in self.columns.iter().enumerate() {
+            let mut tier = Condition::all();
+            for ((equal, _), value) in self.columns[..i].iter().zip(&cursor.key) {
+                tier = tier.add(equal.eq(Value::from(value.clone())));
+            }
+            let value = Value::from(cursor.key[i].clone());
+            tier = tier.add(match order {
+                Order::Desc => column.lt(value),
+                _ => column.gt(value),
+            });
+            condition = condition.add(tier);
+        }
+        Ok(condition)
+    }
+
+    /// Order `select` by the keyset and fetch the page after `cursor`,
+    /// plus one row to tell whether another page follows
+    pub fn page<E>(&self, mut select: Select<E>, cursor: Option<&Cursor>, limit: u64) -> Result<Select<E>, CursorError>
+    where
+        E: EntityTrait<Column = C>,
+    {
+        if let Some(cursor) = cursor {
+            select = select.filter(self.after(cursor)?);
+        }
+        for (column, order) in &self.columns {
+            select = select.order_by(*column, order.clone());
+        }
+        Ok(select.limit(limit + 1))
+    }
+
+    /// Drop the extra row fetched by [`Keyset::page`], returning the cursor
+    /// of the next page if there is one
+    pub fn next<M>(&self, rows: &mut Vec<M>, limit: u64, key: impl Fn(&M) -> Vec<KeyValue>) -> Option<Cursor> {
+        if rows.len() as u64 <= limit {
+            return None;
+        }
+        rows.truncate(limit as usize);
+        rows.last().map(|row| self.cursor(key(row)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use entity::customers::{Column, Entity};
+    use sea_orm::{DbBackend, QueryTrait};
+
+    fn keyset() -> Keyset<Column> {
+        Keyset::new("name", vec![(Column::Lastname, Order::Asc), (Column::CreatedGmt, Order::Desc), (Column::Cid, Order::Asc)])
+    }
+
+    #[test]
+    fn test_cursor_roundtrip() {
+        let cursor = keyset().cursor(vec!["Smith".into(), 1_763_424_000.into(), Decimal::new(1999, 2).into()]);
+        let encoded = cursor.to_string();
+        assert!(encoded.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'), "{}", encoded);
+        assert_eq!(encoded.parse::<Cursor>(), Ok(cursor));
+        assert_eq!("not a cursor".parse::<Cursor>(), Err(CursorError::Malformed));
+    }
+
+    #[test]
+    fn test_page_after_cursor() {
+        let keyset = keyset();
+        let cursor = keyset.cursor(vec!["Smith".into(), 500.into(), 7.into()]);
+        let sql = keyset
+            .page(Entity::find(), Some(&cursor), 20)
+            .unwrap()
+            .build(DbBackend::Postgres)
+            .to_string();
+
+        assert!(sql.contains(concat!(
+            r#"WHERE "customers"."lastname" > 'Smith' "#,
+            r#"OR ("customers"."lastname" = 'Smith' AND "customers"."created_gmt" < 500) "#,
+            r#"OR ("customers"."lastname" = 'Smith' AND "customers"."created_gmt" = 500 AND "customers"."cid" > 7)"#,
+        )), "{}", sql);
+        assert!(sql.ends_with(r#"ORDER BY "customers"."lastname" ASC, "customers"."created_gmt" DESC, "customers"."cid" ASC LIMIT 21"#), "{}", sql);
+
+        let other = Keyset::<Column>::new("newest", vec![(Column::Cid, Order::Asc)]).cursor(vec![7.into()]);
+        assert_eq!(keyset.after(&other).err(), Some(CursorError::WrongSort("newest".to_string())));
+    }
+
+    #[test]
+    fn test_next_cursor() {
+        let keyset = keyset();
+        let mut rows = vec![1, 2, 3];
+        assert_eq!(keyset.next(&mut rows, 2, |id| vec!["Smith".into(), 0.into(), (*id).into()]), Some(keyset.cursor(vec!["Smith".into(), 0.into(), 2.into()])));
+        assert_eq!(rows, vec![1, 2]);
+        assert_eq!(keyset.next(&mut rows, 2, |id| vec![(*id).into()]), None);
+    }
+}
@@ -0,0 +1,232 @@
+//! Domain events shared across crates
+//!
+//! Services [`outbox::record`] a [`DomainEvent`] in the same transaction as
+//! the change it describes; a relay publishes it once committed.
+//! Downstream features (webhooks, email, analytics) subscribe to the bus
+//! instead of being called directly by the service that made the change.
+//! Publishing never blocks and never fails: events published while nobody
+//! is subscribed are dropped.
+
+use async_trait::async_trait;
+use commercerack_core::{CustomerId, MerchantId, OrderId, Timestamp};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, OnceLock};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::task::JoinHandle;
+use tracing::warn;
+use ::entity::prelude::{
+    AbandonedCart, Customer, DunningCase, LicenseKey, Order, OrderDownload, OrderItem, Product, Sku, StoreLocation,
+};
+
+pub mod outbox;
+
+/// Events buffered per subscriber before slow subscribers start missing events
+const BUS_CAPACITY: usize = 1024;
+
+/// Something that happened to a merchant's data
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum DomainEvent {
+    OrderCreated { order: Order, items: Vec<OrderItem> },
+    OrderUpdated(Order),
+    OrderPaid(Order),
+    OrderShipped(Order),
+    /// The order was cancelled; its stock is back and its payments were
+    /// voided or refunded
+    OrderCancelled(Order),
+    /// A paid order's digital items were delivered; carries the buyer's
+    /// download tokens and license keys
+    DigitalDelivered { order: Order, downloads: Vec<OrderDownload>, license_keys: Vec<LicenseKey> },
+    OrderDeleted { mid: MerchantId, id: OrderId },
+    /// A company buyer's order is over the approval threshold; carries the
+    /// company's approvers
+    ApprovalRequested { order: Order, approvers: Vec<Customer> },
+    /// A pickup order can be collected; carries the store it waits at
+    OrderReadyForPickup { order: Order, location: StoreLocation },
+    /// An order's payment was declined and will be charged again at the
+    /// case's `next_attempt_gmt`
+    PaymentRetryScheduled { order: Order, case: DunningCase },
+    /// An order's last payment retry was declined; the order is on hold
+    DunningSuspended { order: Order, case: DunningCase },
+    CustomerCreated(Customer),
+    CustomerUpdated(Customer),
+    CustomerDeleted { mid: MerchantId, cid: CustomerId },
+    /// Too many failed logins; carries the token that unlocks the account
+    CustomerLockedOut { mid: MerchantId, cid: CustomerId, email: String, unlock_token: String, locked_until_gmt: Timestamp },
+    ProductCreated(Product),
+    ProductUpdated(Product),
+    ProductDeleted { mid: MerchantId, id: i32 },
+    /// A product's images or videos were added, reordered or removed
+    ProductMediaChanged { mid: MerchantId, product_id: i32 },
+    /// A category was created, renamed, moved or deleted, or had products
+    /// assigned to or removed from it
+    CategoryChanged { mid: MerchantId, id: i32 },
+    SkuCreated(Sku),
+    SkuUpdated(Sku),
+    SkuDeleted { mid: MerchantId, id: i32 },
+    /// Sellable stock fell to or below the SKU's reorder point
+    LowStock(Sku),
+    /// A cart with a contact went idle; carries its recovery token
+    CartAbandoned(AbandonedCart),
+}
+
+impl DomainEvent {
+    /// Merchant the event belongs to
+    pub fn mid(&self) -> MerchantId {
+        match self {
+            DomainEvent::OrderCreated { order, .. }
+            | DomainEvent::DigitalDelivered { order, .. }
+            | DomainEvent::ApprovalRequested { order, .. }
+            | DomainEvent::OrderReadyForPickup { order, .. }
+            | DomainEvent::PaymentRetryScheduled { order, .. }
+            | DomainEvent::DunningSuspended { order, .. } => order.mid.into(),
+            DomainEvent::OrderUpdated(order)
+            | DomainEvent::OrderPaid(order)
+            | DomainEvent::OrderShipped(order)
+            | DomainEvent::OrderCancelled(order) => order.mid.into(),
+            DomainEvent::CustomerCreated(customer) | DomainEvent::CustomerUpdated(customer) => customer.mid.into(),
+            DomainEvent::ProductCreated(product) | DomainEvent::ProductUpdated(product) => product.mid.into(),
+            DomainEvent::SkuCreated(sku) | DomainEvent::SkuUpdated(sku) | DomainEvent::LowStock(sku) => sku.mid.into(),
+            DomainEvent::CartAbandoned(cart) => cart.mid.into(),
+            DomainEvent::OrderDeleted { mid, .. }
+            | DomainEvent::CustomerDeleted { mid, .. }
+            | DomainEvent::CustomerLockedOut { mid, .. }
+            | DomainEvent::ProductDeleted { mid, .. }
+            | DomainEvent::ProductMediaChanged { mid, .. }
+            | DomainEvent::CategoryChanged { mid, .. }
+            | DomainEvent::SkuDeleted { mid, .. } => *mid,
+        }
+    }
+
+    /// Dotted event name, e.g. `order.created`
+    pub fn name(&self) -> &'static str {
+        match self {
+            DomainEvent::OrderCreated { .. } => "order.created",
+            DomainEvent::OrderUpdated(_) => "order.updated",
+            DomainEvent::OrderPaid(_) => "order.paid",
+            DomainEvent::OrderShipped(_) => "order.shipped",
+            DomainEvent::OrderCancelled(_) => "order.cancelled",
+            DomainEvent::DigitalDelivered { .. } => "order.digital_delivered",
+            DomainEvent::OrderDeleted { .. } => "order.deleted",
+            DomainEvent::ApprovalRequested { .. } => "order.approval_requested",
+            DomainEvent::OrderReadyForPickup { .. } => "order.ready_for_pickup",
+            DomainEvent::PaymentRetryScheduled { .. } => "dunning.retry_scheduled",
+            DomainEvent::DunningSuspended { .. } => "dunning.suspended",
+            DomainEvent::CustomerCreated(_) => "customer.created",
+            DomainEvent::CustomerUpdated(_) => "customer.updated",
+            DomainEvent::CustomerDeleted { .. } => "customer.deleted",
+            DomainEvent::CustomerLockedOut { .. } => "customer.locked_out",
+            DomainEvent::ProductCreated(_) => "product.created",
+            DomainEvent::ProductUpdated(_) => "product.updated",
+            DomainEvent::ProductDeleted { .. } => "product.deleted",
+            DomainEvent::ProductMediaChanged { .. } => "product.media_changed",
+            DomainEvent::CategoryChanged { .. } => "category.changed",
+            DomainEvent::SkuCreated(_) => "sku.created",
+            DomainEvent::SkuUpdated(_) => "sku.updated",
+            DomainEvent::SkuDeleted { .. } => "sku.deleted",
+            DomainEvent::LowStock(_) => "inventory.low_stock",
+            DomainEvent::CartAbandoned(_) => "cart.abandoned",
+        }
+    }
+}
+
+/// Subscriber run for every published event
+#[async_trait]
+pub trait EventHandler: Send + Sync {
+    async fn handle(&self, event: &DomainEvent);
+}
+
+/// In-process publish/subscribe bus for domain events
+#[derive(Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<Arc<DomainEvent>>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(BUS_CAPACITY);
+        Self { sender }
+    }
+
+    /// Deliver an event to every current subscriber
+    pub fn publish(&self, event: DomainEvent) {
+        // An error only means nobody is subscribed
+        let _ = self.sender.send(Arc::new(event));
+    }
+
+    /// Receive events published from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<DomainEvent>> {
+        self.sender.subscribe()
+    }
+
+    /// Run a handler for each event on a background task. Events are handled
+    /// one at a time in publish order.
+    pub fn spawn_handler(&self, handler: Arc<dyn EventHandler>) -> JoinHandle<()> {
+        let mut receiver = self.subscribe();
+        tokio::spawn(async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => handler.handle(&event).await,
+                    Err(RecvError::Lagged(missed)) => {
+                        warn!("Event handler fell behind and missed {} events", missed);
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        })
+    }
+}
+
+/// The process-wide bus services publish to
+pub fn global() -> &'static EventBus {
+    static BUS: OnceLock<EventBus> = OnceLock::new();
+    BUS.get_or_init(EventBus::new)
+}
+
+/// Publish an event on the process-wide bus
+pub fn publish(event: DomainEvent) {
+    global().publish(event);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    struct Recorder(Mutex<Vec<&'static str>>);
+
+    #[async_trait]
+    impl EventHandler for Recorder {
+        async fn handle(&self, event: &DomainEvent) {
+            self.0.lock().unwrap().push(event.name());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_publish_reaches_subscribers() {
+        let bus = EventBus::new();
+        // Nobody is listening yet; the event is dropped
+        bus.publish(DomainEvent::ProductDeleted { mid: MerchantId::new(1), id: 1 });
+
+        let mut receiver = bus.subscribe();
+        let recorder = Arc::new(Recorder(Mutex::new(Vec::new())));
+        let task = bus.spawn_handler(recorder.clone());
+
+        bus.publish(DomainEvent::CustomerDeleted { mid: MerchantId::new(2), cid: CustomerId::new(7) });
+        bus.publish(DomainEvent::SkuDeleted { mid: MerchantId::new(2), id: 3 });
+
+        let first = receiver.recv().await.unwrap();
+        assert_eq!(first.mid(), MerchantId::new(2));
+        assert_eq!(first.name(), "customer.deleted");
+        assert_eq!(receiver.recv().await.unwrap().name(), "sku.deleted");
+
+        drop(bus);
+        task.await.unwrap();
+        assert_eq!(*recorder.0.lock().unwrap(), vec!["customer.deleted", "sku.deleted"]);
+    }
+}
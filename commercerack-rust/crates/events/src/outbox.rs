@@ -0,0 +1,111 @@
+//! Transactional outbox
+//!
+//! An event recorded on the transaction that makes a change is stored if
+//! and only if that change commits, so a crash right after the commit can't
+//! lose it. The relay reads undelivered rows in insertion order, hands each
+//! event to its handlers and marks the rows delivered. A crash before that
+//! last step means the events are handled again: delivery is at least once.
+
+use commercerack_core::Timestamp;
+use sea_orm::sea_query::{Expr, LockBehavior, LockType};
+use sea_orm::*;
+use ::entity::prelude::{EventOutbox, OutboxEvent};
+
+use crate::DomainEvent;
+
+/// Store `event` for publication; call on the transaction making the change
+pub async fn record<C: ConnectionTrait>(db: &C, event: &DomainEvent) -> Result<(), DbErr> {
+    let payload = serde_json::to_string(event).map_err(|e| DbErr::Custom(e.to_string()))?;
+
+    let row = ::entity::event_outbox::ActiveModel {
+        mid: Set(event.mid().get()),
+        event: Set(event.name().to_string()),
+        payload: Set(payload),
+        created_gmt: Set(Timestamp::now()),
+        delivered_gmt: Set(None),
+        ..Default::default()
+    };
+    EventOutbox::insert(row).exec_without_returning(db).await?;
+    Ok(())
+}
+
+/// The oldest undelivered events, locked until `db`'s transaction ends.
+/// Rows another relay has locked are skipped rather than waited for.
+pub async fn pending<C: ConnectionTrait>(db: &C, limit: u64) -> Result<Vec<OutboxEvent>, DbErr> {
+    use ::entity::event_outbox::Column;
+
+    EventOutbox::find()
+        .filter(Column::DeliveredGmt.is_null())
+        .order_by_asc(Column::Id)
+        .limit(limit)
+        .lock_with_behavior(LockType::Update, LockBehavior::SkipLocked)
+        .all(db)
+        .await
+}
+
+/// The event a row holds
+pub fn decode(row: &OutboxEvent) -> Result<DomainEvent, serde_json::Error> {
+    serde_json::from_str(&row.payload)
+}
+
+/// Stamp rows as published
+pub async fn mark_delivered<C: ConnectionTrait>(db: &C, ids: &[i32]) -> Result<(), DbErr> {
+    use ::entity::event_outbox::Column;
+
+    if ids.is_empty() {
+        return Ok(());
+    }
+    EventOutbox::update_many()
+        .col_expr(Column::DeliveredGmt, Expr::value(Timestamp::now()))
+        .filter(Column::Id.is_in(ids.iter().copied()))
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use commercerack_core::MerchantId;
+
+    #[tokio::test]
+    async fn test_record_stores_decodable_event() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_exec_results([MockExecResult { last_insert_id: 1, rows_affected: 1 }])
+            .into_connection();
+
+        let event = DomainEvent::ProductDeleted { mid: MerchantId::new(3), id: 42 };
+        record(&db, &event).await.unwrap();
+
+        let log = db.into_transaction_log();
+        let statement = &log[0].statements()[0];
+        assert!(statement.sql.starts_with(r#"INSERT INTO "event_outbox""#), "{}", statement.sql);
+
+        let values = statement.values.as_ref().unwrap();
+        let Value::String(Some(payload)) = &values.0[2] else {
+            panic!("unexpected payload {:?}", values.0[2]);
+        };
+        let row = OutboxEvent {
+            id: 1,
+            mid: 3,
+            event: "product.deleted".to_string(),
+            payload: payload.to_string(),
+            created_gmt: Timestamp::EPOCH,
+            delivered_gmt: None,
+        };
+        assert_eq!(decode(&row).unwrap(), event);
+    }
+
+    #[tokio::test]
+    async fn test_pending_skips_locked_rows() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([Vec::<OutboxEvent>::new()])
+            .into_connection();
+
+        pending(&db, 50).await.unwrap();
+
+        let log = db.into_transaction_log();
+        let sql = log[0].statements()[0].to_string();
+        assert!(sql.contains(r#"WHERE "event_outbox"."delivered_gmt" IS NULL ORDER BY "event_outbox"."id" ASC LIMIT 50 FOR UPDATE SKIP LOCKED"#), "{}", sql);
+    }
+}
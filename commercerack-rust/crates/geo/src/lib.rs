@@ -0,0 +1,317 @@
+//! Geographic zones
+//!
+//! A zone is a named set of countries, optionally narrowed to states and
+//! zips. Each zip entry is either a prefix (`100`) or an inclusive range of
+//! equal-length bounds (`94000-94999`) compared against the same number of
+//! leading characters of the zip. Shipping zones and tax rates can cover a
+//! zone instead of repeating the lists, so a merchant defines e.g. "Bay
+//! Area" once and both subsystems agree on who is in it.
+
+use commercerack_core::Timestamp;
+use sea_orm::*;
+use ::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ZoneError {
+    #[error("Zone not found")]
+    NotFound,
+
+    #[error("A zone named {0} already exists")]
+    NameTaken(String),
+
+    #[error("Invalid zip entry {0}: {1}")]
+    InvalidZip(String, String),
+
+    #[error("Zone is used by shipping zones or tax rates")]
+    InUse,
+
+    #[error("Database error: {0}")]
+    Db(#[from] DbErr),
+}
+
+/// An address being placed in zones
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Address {
+    pub country: String,
+    pub state: String,
+    pub zip: String,
+}
+
+impl Address {
+    /// Codes are compared case-insensitively, so they are kept uppercase
+    pub fn new(country: &str, state: &str, zip: &str) -> Self {
+        Self {
+            country: country.trim().to_uppercase(),
+            state: state.trim().to_uppercase(),
+            zip: zip.trim().to_uppercase(),
+        }
+    }
+}
+
+/// Settings of a zone being created or updated
+#[derive(Debug, Clone)]
+pub struct ZoneInput {
+    pub name: String,
+    pub countries: Vec<String>,
+    pub states: Vec<String>,
+    pub zips: Vec<String>,
+}
+
+fn split(list: &str) -> impl Iterator<Item = &str> {
+    list.split(',').map(str::trim).filter(|s| !s.is_empty())
+}
+
+fn join(values: &[String]) -> String {
+    values
+        .iter()
+        .map(|v| v.trim().to_uppercase())
+        .filter(|v| !v.is_empty())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Why a zip entry is malformed, if it is
+pub fn check_zip(entry: &str) -> Result<(), String> {
+    let Some((lo, hi)) = entry.trim().split_once('-') else {
+        return Ok(());
+    };
+    let (lo, hi) = (lo.trim(), hi.trim());
+    if lo.is_empty() || hi.is_empty() {
+        return Err("a range needs both bounds".to_string());
+    }
+    if lo.len() != hi.len() {
+        return Err("range bounds must be the same length".to_string());
+    }
+    if lo > hi {
+        return Err("range starts after it ends".to_string());
+    }
+    Ok(())
+}
+
+/// Whether `zip` falls under a prefix or range entry
+pub fn zip_matches(entry: &str, zip: &str) -> bool {
+    match entry.split_once('-') {
+        Some((lo, hi)) => {
+            let (lo, hi) = (lo.trim(), hi.trim());
+            zip.get(..lo.len()).is_some_and(|head| lo <= head && head <= hi)
+        }
+        None => zip.starts_with(entry),
+    }
+}
+
+/// Whether `address` lies in `zone`
+pub fn zone_matches(zone: &Zone, address: &Address) -> bool {
+    split(&zone.countries).any(|country| country == address.country)
+        && (zone.states.is_empty() || split(&zone.states).any(|state| state == address.state))
+        && (zone.zips.is_empty() || split(&zone.zips).any(|entry| zip_matches(entry, &address.zip)))
+}
+
+/// The narrowest of several matching zones: zones listing zips beat zones
+/// listing states, which beat whole countries; then the fewest countries
+/// and states, then the oldest zone
+pub fn most_specific(zones: &[Zone]) -> Option<&Zone> {
+    zones.iter().min_by_key(|zone| {
+        let level = if !zone.zips.is_empty() {
+            2
+        } else if !zone.states.is_empty() {
+            1
+        } else {
+            0
+        };
+        (Reverse(level), split(&zone.countries).count(), split(&zone.states).count(), zone.id)
+    })
+}
+
+/// Zone service for managing zones and placing addresses in them
+pub struct ZoneService;
+
+impl ZoneService {
+    /// A merchant's zones by name
+    pub async fn list<C: ConnectionTrait>(db: &C, mid: i32) -> Result<Vec<Zone>, ZoneError> {
+        use ::entity::zones::Column;
+
+        Ok(Zones::find()
+            .filter(Column::Mid.eq(mid))
+            .order_by_asc(Column::Name)
+            .order_by_asc(Column::Id)
+            .all(db)
+            .await?)
+    }
+
+    /// Find zone by ID
+    pub async fn find<C: ConnectionTrait>(db: &C, mid: i32, id: i32) -> Result<Option<Zone>, ZoneError> {
+        use ::entity::zones::Column;
+
+        Ok(Zones::find()
+            .filter(Column::Mid.eq(mid))
+            .filter(Column::Id.eq(id))
+            .one(db)
+            .await?)
+    }
+
+    async fn check<C: ConnectionTrait>(db: &C, mid: i32, input: &ZoneInput, except: Option<i32>) -> Result<(), ZoneError> {
+        use ::entity::zones::Column;
+
+        for entry in &input.zips {
+            check_zip(entry).map_err(|reason| ZoneError::InvalidZip(entry.clone(), reason))?;
+        }
+
+        let mut query = Zones::find().filter(Column::Mid.eq(mid)).filter(Column::Name.eq(input.name.as_str()));
+        if let Some(id) = except {
+            query = query.filter(Column::Id.ne(id));
+        }
+        if query.one(db).await?.is_some() {
+            return Err(ZoneError::NameTaken(input.name.clone()));
+        }
+        Ok(())
+    }
+
+    /// Define a zone
+    pub async fn create<C: ConnectionTrait>(db: &C, mid: i32, input: ZoneInput) -> Result<Zone, ZoneError> {
+        Self::check(db, mid, &input, None).await?;
+
+        let now = Timestamp::now();
+        let zone = ::entity::zones::ActiveModel {
+            mid: Set(mid),
+            name: Set(input.name),
+            countries: Set(join(&input.countries)),
+            states: Set(join(&input.states)),
+            zips: Set(join(&input.zips)),
+            created_gmt: Set(now),
+            modified_gmt: Set(now),
+            ..Default::default()
+        };
+        Ok(zone.insert(db).await?)
+    }
+
+    /// Replace a zone's settings. Shipping zones and tax rates covering it
+    /// follow the change.
+    pub async fn update<C: ConnectionTrait>(db: &C, mid: i32, id: i32, input: ZoneInput) -> Result<Zone, ZoneError> {
+        let zone = Self::find(db, mid, id).await?.ok_or(ZoneError::NotFound)?;
+        Self::check(db, mid, &input, Some(id)).await?;
+
+        let mut active: ::entity::zones::ActiveModel = zone.into();
+        active.name = Set(input.name);
+        active.countries = Set(join(&input.countries));
+        active.states = Set(join(&input.states));
+        active.zips = Set(join(&input.zips));
+        active.modified_gmt = Set(Timestamp::now());
+        Ok(active.update(db).await?)
+    }
+
+    /// Delete a zone no shipping zone or tax rate covers. Returns whether it
+    /// existed.
+    pub async fn delete<C: ConnectionTrait>(db: &C, mid: i32, id: i32) -> Result<bool, ZoneError> {
+        let shipping = ShippingZones::find()
+            .filter(::entity::shipping_zones::Column::Mid.eq(mid))
+            .filter(::entity::shipping_zones::Column::ZoneId.eq(id))
+            .count(db)
+            .await?;
+        let tax = TaxRates::find()
+            .filter(::entity::tax_rates::Column::Mid.eq(mid))
+            .filter(::entity::tax_rates::Column::ZoneId.eq(id))
+            .count(db)
+            .await?;
+        if shipping + tax > 0 {
+            return Err(ZoneError::InUse);
+        }
+
+        let result = Zones::delete_many()
+            .filter(::entity::zones::Column::Mid.eq(mid))
+            .filter(::entity::zones::Column::Id.eq(id))
+            .exec(db)
+            .await?;
+        Ok(result.rows_affected > 0)
+    }
+
+    /// Every zone of a merchant that `address` lies in
+    pub async fn matching<C: ConnectionTrait>(db: &C, mid: i32, address: &Address) -> Result<Vec<Zone>, ZoneError> {
+        use ::entity::zones::Column;
+
+        // Narrow by country in SQL; the lists are matched exactly below
+        let zones = Zones::find()
+            .filter(Column::Mid.eq(mid))
+            .filter(Column::Countries.contains(address.country.as_str()))
+            .order_by_asc(Column::Id)
+            .all(db)
+            .await?;
+        Ok(zones.into_iter().filter(|zone| zone_matches(zone, address)).collect())
+    }
+
+    /// The zone `address` falls in: the most specific one it matches
+    pub async fn resolve<C: ConnectionTrait>(db: &C, mid: i32, address: &Address) -> Result<Option<Zone>, ZoneError> {
+        let zones = Self::matching(db, mid, address).await?;
+        Ok(most_specific(&zones).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn zone(id: i32, countries: &str, states: &str, zips: &str) -> Zone {
+        Zone {
+            id,
+            mid: 1,
+            name: format!("Zone {}", id),
+            countries: countries.to_string(),
+            states: states.to_string(),
+            zips: zips.to_string(),
+            created_gmt: Timestamp::EPOCH,
+            modified_gmt: Timestamp::EPOCH,
+        }
+    }
+
+    #[test]
+    fn test_zip_prefixes_and_ranges() {
+        assert!(zip_matches("100", "10012"));
+        assert!(!zip_matches("100", "11012"));
+        assert!(zip_matches("94000-94999", "94103-1234"));
+        assert!(zip_matches("940-949", "94999"));
+        assert!(!zip_matches("94000-94999", "95014"));
+        assert!(!zip_matches("94000-94999", "940"));
+
+        assert!(check_zip("94000-94999").is_ok());
+        assert!(check_zip("M5V").is_ok());
+        assert!(check_zip("940-9499").is_err());
+        assert!(check_zip("949-940").is_err());
+        assert!(check_zip("-940").is_err());
+    }
+
+    #[test]
+    fn test_most_specific_zone_wins() {
+        let zones = [
+            zone(1, "US,CA,MX", "", ""),
+            zone(2, "US", "", ""),
+            zone(3, "US", "CA,OR,WA", ""),
+            zone(4, "US", "CA", "94000-94999"),
+        ];
+        let san_francisco = Address::new("us", "ca", "94103");
+        let matching: Vec<Zone> = zones.iter().filter(|z| zone_matches(z, &san_francisco)).cloned().collect();
+        assert_eq!(matching.len(), 4);
+        assert_eq!(most_specific(&matching).map(|z| z.id), Some(4));
+
+        let portland = Address::new("US", "OR", "97201");
+        let matching: Vec<Zone> = zones.iter().filter(|z| zone_matches(z, &portland)).cloned().collect();
+        assert_eq!(most_specific(&matching).map(|z| z.id), Some(3));
+
+        let toronto = Address::new("CA", "ON", "M5V");
+        let matching: Vec<Zone> = zones.iter().filter(|z| zone_matches(z, &toronto)).cloned().collect();
+        assert_eq!(most_specific(&matching).map(|z| z.id), Some(1));
+        assert!(most_specific(&[]).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_zone_in_use_is_kept() {
+        let count = |n: i64| std::collections::BTreeMap::from([("num_items".to_string(), Value::BigInt(Some(n)))]);
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![count(1)]])
+            .append_query_results([vec![count(0)]])
+            .into_connection();
+
+        assert!(matches!(ZoneService::delete(&db, 1, 4).await, Err(ZoneError::InUse)));
+    }
+}
@@ -0,0 +1,478 @@
+//! Gift cards and store credit using SeaORM
+//!
+//! A gift card is a code with a balance. Shoppers apply cards to a cart;
+//! at checkout, after tax and shipping, the cards pay as much of the order
+//! as their balances cover and only the rest is charged to the payment
+//! gateway. Every change to a balance, whether an order spending it or an
+//! admin adjusting or voiding the card, is kept in the card's transaction
+//! ledger.
+
+use commercerack_cart::{AppliedGiftCard, Cart};
+use commercerack_core::Timestamp;
+use rust_decimal::Decimal;
+use sea_orm::sea_query::Expr;
+use sea_orm::*;
+use ::entity::prelude::*;
+use ::entity::{gift_card_transactions, gift_cards};
+use std::fmt;
+use thiserror::Error;
+use tracing::instrument;
+use uuid::Uuid;
+
+#[derive(Error, Debug)]
+pub enum GiftCardError {
+    #[error("Gift card not found")]
+    NotFound,
+
+    #[error("Gift card code {0} is already in use")]
+    DuplicateCode(String),
+
+    #[error("Gift card has been voided")]
+    Voided,
+
+    #[error("Gift card has expired")]
+    Expired,
+
+    #[error("Gift card has no balance left")]
+    Empty,
+
+    #[error("Adjustment would take the balance below zero")]
+    NegativeBalance,
+
+    #[error("Database error: {0}")]
+    Db(#[from] DbErr),
+}
+
+/// What changed a gift card's balance
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionKind {
+    Issue,
+    Redeem,
+    Adjust,
+    Void,
+}
+
+impl TransactionKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransactionKind::Issue => "issue",
+            TransactionKind::Redeem => "redeem",
+            TransactionKind::Adjust => "adjust",
+            TransactionKind::Void => "void",
+        }
+    }
+}
+
+impl fmt::Display for TransactionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A gift card being issued
+#[derive(Debug, Clone)]
+pub struct NewGiftCard {
+    /// Generated when `None`
+    pub code: Option<String>,
+    pub amount: Decimal,
+    /// Customer the card is issued to, 0 if none
+    pub cid: i32,
+    pub expires_gmt: Option<Timestamp>,
+    pub note: String,
+}
+
+/// Codes are matched case-insensitively and stored uppercase
+pub fn normalize_code(code: &str) -> String {
+    code.trim().to_uppercase()
+}
+
+/// A random 16 character code
+pub fn generate_code() -> String {
+    Uuid::new_v4().simple().to_string()[..16].to_uppercase()
+}
+
+/// The code as shown on receipts and order lines, e.g. `****3F9A`
+pub fn mask_code(code: &str) -> String {
+    let visible = code.len().saturating_sub(4);
+    format!("****{}", &code[visible..])
+}
+
+/// Whether a card can pay for anything at `now`
+pub fn check_usable(card: &GiftCard, now: Timestamp) -> Result<(), GiftCardError> {
+    if card.voided {
+        return Err(GiftCardError::Voided);
+    }
+    if card.expires_gmt.is_some_and(|expires| expires <= now) {
+        return Err(GiftCardError::Expired);
+    }
+    if card.balance <= Decimal::ZERO {
+        return Err(GiftCardError::Empty);
+    }
+    Ok(())
+}
+
+/// How much of `amount_due` each card pays, in the order the cards were
+/// applied. Cards not needed once the amount is covered are left out.
+pub fn allocate(cards: &[GiftCard], amount_due: Decimal) -> Vec<(GiftCard, Decimal)> {
+    let mut remaining = amount_due.max(Decimal::ZERO);
+    let mut allocations = Vec::new();
+    for card in cards {
+        if remaining <= Decimal::ZERO {
+            break;
+        }
+        let amount = card.balance.min(remaining);
+        remaining -= amount;
+        allocations.push((card.clone(), amount));
+    }
+    allocations
+}
+
+/// Gift card service
+pub struct GiftCardService;
+
+impl GiftCardService {
+    /// Issue a card with its opening balance. Codes are unique per merchant.
+    #[instrument(skip_all, fields(mid = mid))]
+    pub async fn issue<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: i32,
+        card: NewGiftCard,
+    ) -> Result<GiftCard, GiftCardError> {
+        let code = card.code.as_deref().map(normalize_code).unwrap_or_else(generate_code);
+        if Self::find_by_code(db, mid, &code).await?.is_some() {
+            return Err(GiftCardError::DuplicateCode(code));
+        }
+
+        let now = Timestamp::now();
+        let txn = db.begin().await?;
+        let issued = gift_cards::ActiveModel {
+            mid: Set(mid),
+            code: Set(code),
+            initial_balance: Set(card.amount),
+            balance: Set(card.amount),
+            cid: Set(card.cid),
+            voided: Set(false),
+            expires_gmt: Set(card.expires_gmt),
+            created_gmt: Set(now),
+            modified_gmt: Set(now),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+        Self::record(&txn, &issued, TransactionKind::Issue, card.amount, None, &card.note).await?;
+        txn.commit().await?;
+
+        Ok(issued)
+    }
+
+    /// Find gift card by ID
+    pub async fn find_by_id<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        id: i32,
+    ) -> Result<Option<GiftCard>, GiftCardError> {
+        let card = GiftCards::find()
+            .filter(gift_cards::Column::Mid.eq(mid))
+            .filter(gift_cards::Column::Id.eq(id))
+            .one(db)
+            .await?;
+
+        Ok(card)
+    }
+
+    /// Find gift card by code, ignoring case
+    pub async fn find_by_code<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        code: &str,
+    ) -> Result<Option<GiftCard>, GiftCardError> {
+        let card = GiftCards::find()
+            .filter(gift_cards::Column::Mid.eq(mid))
+            .filter(gift_cards::Column::Code.eq(normalize_code(code)))
+            .one(db)
+            .await?;
+
+        Ok(card)
+    }
+
+    /// A merchant's gift cards, newest first, optionally only those issued
+    /// to one customer
+    pub async fn list<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        cid: Option<i32>,
+        limit: u64,
+    ) -> Result<Vec<GiftCard>, GiftCardError> {
+        let mut query = GiftCards::find().filter(gift_cards::Column::Mid.eq(mid));
+        if let Some(cid) = cid {
+            query = query.filter(gift_cards::Column::Cid.eq(cid));
+        }
+
+        let cards = query
+            .order_by_desc(gift_cards::Column::Id)
+            .limit(limit)
+            .all(db)
+            .await?;
+
+        Ok(cards)
+    }
+
+    /// Ledger of a card, oldest first
+    pub async fn transactions<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        id: i32,
+    ) -> Result<Vec<GiftCardTransaction>, GiftCardError> {
+        let transactions = GiftCardTransactions::find()
+            .filter(gift_card_transactions::Column::Mid.eq(mid))
+            .filter(gift_card_transactions::Column::GiftCardId.eq(id))
+            .order_by_asc(gift_card_transactions::Column::Id)
+            .all(db)
+            .await?;
+
+        Ok(transactions)
+    }
+
+    /// Check a code and apply the card to a cart
+    #[instrument(skip_all, fields(mid = mid))]
+    pub async fn apply<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        code: &str,
+        cart: &mut Cart,
+    ) -> Result<AppliedGiftCard, GiftCardError> {
+        let card = Self::find_by_code(db, mid, code).await?.ok_or(GiftCardError::NotFound)?;
+        check_usable(&card, Timestamp::now())?;
+
+        let applied = AppliedGiftCard {
+            gift_card_id: card.id,
+            code: card.code,
+            balance: card.balance,
+        };
+        cart.apply_gift_card(applied.clone());
+        Ok(applied)
+    }
+
+    /// Lock and re-check the cart's gift cards during checkout. Use
+    /// [`allocate`] to split the order total across them and
+    /// [`Self::redeem`] once the order exists.
+    pub async fn revalidate<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        cart: &Cart,
+    ) -> Result<Vec<GiftCard>, GiftCardError> {
+        let now = Timestamp::now();
+        let mut cards = Vec::with_capacity(cart.gift_cards.len());
+        for applied in &cart.gift_cards {
+            let card = GiftCards::find()
+                .filter(gift_cards::Column::Mid.eq(mid))
+                .filter(gift_cards::Column::Id.eq(applied.gift_card_id))
+                .lock_exclusive()
+                .one(db)
+                .await?
+                .ok_or(GiftCardError::NotFound)?;
+            check_usable(&card, now)?;
+            cards.push(card);
+        }
+
+        Ok(cards)
+    }
+
+    /// Take `amount` off a card for an order. Must run in the order's
+    /// transaction, after [`Self::revalidate`] locked the card.
+    #[instrument(skip_all, fields(gift_card_id = card.id, order_id = order_id))]
+    pub async fn redeem<C: ConnectionTrait>(
+        db: &C,
+        card: &GiftCard,
+        order_id: i32,
+        amount: Decimal,
+    ) -> Result<GiftCardTransaction, GiftCardError> {
+        let claimed = GiftCards::update_many()
+            .col_expr(gift_cards::Column::Balance, Expr::col(gift_cards::Column::Balance).sub(amount))
+            .col_expr(gift_cards::Column::ModifiedGmt, Expr::value(Timestamp::now()))
+            .filter(gift_cards::Column::Id.eq(card.id))
+            .filter(gift_cards::Column::Balance.gte(amount))
+            .exec(db)
+            .await?;
+        if claimed.rows_affected == 0 {
+            return Err(GiftCardError::Empty);
+        }
+
+        let after = GiftCard {
+            balance: card.balance - amount,
+            ..card.clone()
+        };
+        Self::record(db, &after, TransactionKind::Redeem, -amount, Some(order_id), "").await
+    }
+
+    /// Add to or, with a negative `amount`, take from a card's balance
+    #[instrument(skip_all, fields(mid = mid, id = id))]
+    pub async fn adjust<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: i32,
+        id: i32,
+        amount: Decimal,
+        note: &str,
+    ) -> Result<GiftCard, GiftCardError> {
+        let txn = db.begin().await?;
+        let card = Self::lock(&txn, mid, id).await?;
+        if card.voided {
+            return Err(GiftCardError::Voided);
+        }
+        let balance = card.balance + amount;
+        if balance < Decimal::ZERO {
+            return Err(GiftCardError::NegativeBalance);
+        }
+
+        let mut active: gift_cards::ActiveModel = card.into();
+        active.balance = Set(balance);
+        active.modified_gmt = Set(Timestamp::now());
+        let card = active.update(&txn).await?;
+        Self::record(&txn, &card, TransactionKind::Adjust, amount, None, note).await?;
+        txn.commit().await?;
+
+        Ok(card)
+    }
+
+    /// Void a card, forfeiting its remaining balance
+    #[instrument(skip_all, fields(mid = mid, id = id))]
+    pub async fn void<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: i32,
+        id: i32,
+        note: &str,
+    ) -> Result<GiftCard, GiftCardError> {
+        let txn = db.begin().await?;
+        let card = Self::lock(&txn, mid, id).await?;
+        if card.voided {
+            return Err(GiftCardError::Voided);
+        }
+        let forfeited = card.balance;
+
+        let mut active: gift_cards::ActiveModel = card.into();
+        active.balance = Set(Decimal::ZERO);
+        active.voided = Set(true);
+        active.modified_gmt = Set(Timestamp::now());
+        let card = active.update(&txn).await?;
+        Self::record(&txn, &card, TransactionKind::Void, -forfeited, None, note).await?;
+        txn.commit().await?;
+
+        Ok(card)
+    }
+
+    async fn lock<C: ConnectionTrait>(db: &C, mid: i32, id: i32) -> Result<GiftCard, GiftCardError> {
+        GiftCards::find()
+            .filter(gift_cards::Column::Mid.eq(mid))
+            .filter(gift_cards::Column::Id.eq(id))
+            .lock_exclusive()
+            .one(db)
+            .await?
+            .ok_or(GiftCardError::NotFound)
+    }
+
+    /// Append to the ledger; `card` carries the balance after the change
+    async fn record<C: ConnectionTrait>(
+        db: &C,
+        card: &GiftCard,
+        kind: TransactionKind,
+        amount: Decimal,
+        order_id: Option<i32>,
+        note: &str,
+    ) -> Result<GiftCardTransaction, GiftCardError> {
+        let transaction = gift_card_transactions::ActiveModel {
+            gift_card_id: Set(card.id),
+            mid: Set(card.mid),
+            kind: Set(kind.to_string()),
+            amount: Set(amount),
+            balance_after: Set(card.balance),
+            order_id: Set(order_id),
+            note: Set(note.to_string()),
+            created_gmt: Set(Timestamp::now()),
+            ..Default::default()
+        }
+        .insert(db)
+        .await?;
+
+        Ok(transaction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn card(id: i32, cents: i64) -> GiftCard {
+        GiftCard {
+            id,
+            mid: 1,
+            code: format!("CARD{:012}", id),
+            initial_balance: Decimal::new(cents, 2),
+            balance: Decimal::new(cents, 2),
+            cid: 0,
+            voided: false,
+            expires_gmt: None,
+            created_gmt: Timestamp::EPOCH,
+            modified_gmt: Timestamp::EPOCH,
+        }
+    }
+
+    #[test]
+    fn test_allocate_spends_cards_in_order() {
+        let cards = [card(1, 2000), card(2, 5000), card(3, 1000)];
+
+        let allocations = allocate(&cards, Decimal::new(4500, 2));
+        let amounts: Vec<_> = allocations.iter().map(|(card, amount)| (card.id, *amount)).collect();
+        assert_eq!(amounts, vec![(1, Decimal::new(2000, 2)), (2, Decimal::new(2500, 2))]);
+
+        // Cards covering less than the total leave the rest to the gateway
+        let total: Decimal = allocate(&cards, Decimal::new(10000, 2)).iter().map(|(_, amount)| *amount).sum();
+        assert_eq!(total, Decimal::new(8000, 2));
+        assert!(allocate(&cards, Decimal::ZERO).is_empty());
+    }
+
+    #[test]
+    fn test_check_usable() {
+        let now = Timestamp::from_unix(1_700_000_000);
+        assert!(check_usable(&card(1, 100), now).is_ok());
+        assert!(matches!(check_usable(&card(1, 0), now), Err(GiftCardError::Empty)));
+        assert!(matches!(
+            check_usable(&GiftCard { voided: true, ..card(1, 100) }, now),
+            Err(GiftCardError::Voided)
+        ));
+        assert!(matches!(
+            check_usable(&GiftCard { expires_gmt: Some(now), ..card(1, 100) }, now),
+            Err(GiftCardError::Expired)
+        ));
+    }
+
+    #[test]
+    fn test_codes() {
+        let code = generate_code();
+        assert_eq!(code.len(), 16);
+        assert_eq!(normalize_code(&code.to_lowercase()), code);
+        assert_eq!(mask_code("ABCD1234EFGH5678"), "****5678");
+        assert_eq!(mask_code("AB"), "****AB");
+    }
+
+    #[tokio::test]
+    async fn test_adjust_below_zero_fails() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![card(1, 500)]])
+            .into_connection();
+
+        let err = GiftCardService::adjust(&db, 1, 1, Decimal::new(-600, 2), "").await.unwrap_err();
+        assert!(matches!(err, GiftCardError::NegativeBalance));
+    }
+
+    #[tokio::test]
+    async fn test_apply_unknown_code() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([Vec::<GiftCard>::new()])
+            .into_connection();
+
+        let mut cart = Cart::new();
+        let err = GiftCardService::apply(&db, 1, "nope", &mut cart).await.unwrap_err();
+        assert!(matches!(err, GiftCardError::NotFound));
+        assert!(cart.gift_cards.is_empty());
+    }
+}
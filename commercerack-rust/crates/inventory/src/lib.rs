@@ -1 +1,627 @@
-fn main() {}
+//! Inventory management module using SeaORM
+//!
+//! `inv_available` on a SKU is the sellable stock and `qty_onshelf` the
+//! physical count. Checkout may hold stock for a cart with a short-lived
+//! reservation; placing the order decrements `inv_available` atomically and
+//! consumes the cart's reservations. Every stock movement is written to the
+//! `inventory_adjustments` audit trail.
+//!
+//! Merchants that keep stock in several places split it over
+//! [`warehouses`] and move it between them with [`transfers`].
+//! SKUs that run low are reported by [`low_stock`] and restocked through
+//! [`purchasing`].
+
+use chrono::Duration;
+use commercerack_core::Timestamp;
+use sea_orm::sea_query::Expr;
+use sea_orm::*;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use ::entity::prelude::*;
+use tracing::instrument;
+
+pub mod low_stock;
+pub mod purchasing;
+pub mod transfers;
+pub mod warehouses;
+
+use warehouses::{ShipTo, WarehouseService};
+
+/// How long a checkout reservation holds stock
+pub const RESERVATION_TTL_SECS: i64 = 15 * 60;
+
+#[derive(Error, Debug)]
+pub enum InventoryError {
+    #[error("Unknown SKU {0}")]
+    UnknownSku(String),
+
+    #[error("Insufficient stock for {sku}: requested {requested}, available {available}")]
+    Insufficient {
+        sku: String,
+        requested: i32,
+        available: i32,
+    },
+
+    #[error("Warehouse not found")]
+    WarehouseNotFound,
+
+    #[error("Warehouse code {0} is already in use")]
+    DuplicateWarehouse(String),
+
+    #[error("Stock transfer not found")]
+    TransferNotFound,
+
+    #[error("Stock transfer is already {0}")]
+    TransferClosed(String),
+
+    #[error("Invalid stock transfer: {0}")]
+    InvalidTransfer(&'static str),
+
+    #[error("Purchase order not found")]
+    PurchaseOrderNotFound,
+
+    #[error("Purchase order is {0}")]
+    PurchaseOrderStatus(String),
+
+    #[error("Invalid purchase order: {0}")]
+    InvalidPurchaseOrder(&'static str),
+
+    #[error("Database error: {0}")]
+    Db(#[from] DbErr),
+}
+
+/// Why stock moved, as recorded in the audit trail
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AdjustmentReason {
+    Manual,
+    Order,
+    Cancellation,
+    Return,
+    Transfer,
+    Purchase,
+    /// A placed order's items were changed
+    Edit,
+}
+
+impl AdjustmentReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AdjustmentReason::Manual => "manual",
+            AdjustmentReason::Order => "order",
+            AdjustmentReason::Cancellation => "cancellation",
+            AdjustmentReason::Return => "return",
+            AdjustmentReason::Transfer => "transfer",
+            AdjustmentReason::Purchase => "purchase",
+            AdjustmentReason::Edit => "edit",
+        }
+    }
+}
+
+/// Quantity still held by reservations that are neither released nor expired
+pub fn reserved_quantity(reservations: &[InventoryReservation], now: Timestamp) -> i32 {
+    reservations
+        .iter()
+        .filter(|r| r.released_gmt.is_none() && r.expires_gmt > now)
+        .map(|r| r.quantity)
+        .sum()
+}
+
+/// Inventory service for stock levels, reservations and adjustments
+pub struct InventoryService;
+
+impl InventoryService {
+    /// A SKU, locked until the end of `db`'s transaction so its stock
+    /// can't change between being read and being acted on
+    async fn find_sku<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        sku: &str,
+    ) -> Result<Sku, InventoryError> {
+        Skus::find()
+            .filter(::entity::skus::Column::Mid.eq(mid))
+            .filter(::entity::skus::Column::Sku.eq(sku))
+            .lock_exclusive()
+            .one(db)
+            .await?
+            .ok_or_else(|| InventoryError::UnknownSku(sku.to_string()))
+    }
+
+    /// Quantity of a SKU held by other carts' live reservations
+    async fn reserved_by_others<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        sku: &str,
+        cart_id: &str,
+    ) -> Result<i32, InventoryError> {
+        let reservations = InventoryReservations::find()
+            .filter(::entity::inventory_reservations::Column::Mid.eq(mid))
+            .filter(::entity::inventory_reservations::Column::Sku.eq(sku))
+            .filter(::entity::inventory_reservations::Column::CartId.ne(cart_id))
+            .filter(::entity::inventory_reservations::Column::ReleasedGmt.is_null())
+            .all(db)
+            .await?;
+
+        Ok(reserved_quantity(&reservations, Timestamp::now()))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn record<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        sku: &str,
+        delta: i32,
+        inv_available_after: i32,
+        reason: AdjustmentReason,
+        note: Option<String>,
+        order_id: Option<i32>,
+        actor: Option<i32>,
+        warehouse_id: Option<i32>,
+    ) -> Result<InventoryAdjustment, InventoryError> {
+        let adjustment = ::entity::inventory_adjustments::ActiveModel {
+            mid: Set(mid),
+            sku: Set(sku.to_string()),
+            delta: Set(delta),
+            inv_available_after: Set(inv_available_after),
+            reason: Set(reason.as_str().to_string()),
+            note: Set(note),
+            order_id: Set(order_id),
+            actor: Set(actor),
+            warehouse_id: Set(warehouse_id),
+            created_gmt: Set(Timestamp::now()),
+            ..Default::default()
+        }
+        .insert(db)
+        .await?;
+
+        Ok(adjustment)
+    }
+
+    /// Move both the sellable and the on-shelf counts of a SKU by `delta`;
+    /// sellable stock can not go negative
+    async fn move_sku<C: ConnectionTrait>(
+        db: &C,
+        record: &Sku,
+        delta: i32,
+    ) -> Result<(), InventoryError> {
+        let result = Skus::update_many()
+            .col_expr(
+                ::entity::skus::Column::InvAvailable,
+                Expr::col(::entity::skus::Column::InvAvailable).add(delta),
+            )
+            .col_expr(
+                ::entity::skus::Column::QtyOnshelf,
+                Expr::col(::entity::skus::Column::QtyOnshelf).add(delta),
+            )
+            .filter(::entity::skus::Column::Id.eq(record.id))
+            .filter(::entity::skus::Column::InvAvailable.gte(-delta))
+            .exec(db)
+            .await?;
+        if result.rows_affected == 0 {
+            return Err(InventoryError::Insufficient {
+                sku: record.sku.clone(),
+                requested: -delta,
+                available: record.inv_available,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Stock of a SKU that is neither sold nor held by a reservation
+    pub async fn available(
+        db: &DatabaseConnection,
+        mid: i32,
+        sku: &str,
+    ) -> Result<i32, InventoryError> {
+        let record = Self::find_sku(db, mid, sku).await?;
+        let reserved = Self::reserved_by_others(db, mid, sku, "").await?;
+        Ok(record.inv_available - reserved)
+    }
+
+    /// Hold stock for a cart during checkout, replacing any earlier hold.
+    /// Reservations lapse after `RESERVATION_TTL_SECS`.
+    #[instrument(skip_all, fields(mid = mid, cart_id = cart_id))]
+    pub async fn reserve<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: i32,
+        cart_id: &str,
+        lines: &[(&str, i32)],
+    ) -> Result<Vec<InventoryReservation>, InventoryError> {
+        let txn = db.begin().await?;
+        Self::release_cart(&txn, mid, cart_id).await?;
+
+        // Taken in SKU order, so carts holding the same SKUs lock them in
+        // the same order and can't deadlock
+        let mut lines = lines.to_vec();
+        lines.sort_by_key(|&(sku, _)| sku);
+
+        let now = Timestamp::now();
+        let mut reservations = Vec::with_capacity(lines.len());
+        for (sku, quantity) in lines {
+            let record = Self::find_sku(&txn, mid, sku).await?;
+            let available = record.inv_available - Self::reserved_by_others(&txn, mid, sku, cart_id).await?;
+            if available < quantity {
+                return Err(InventoryError::Insufficient {
+                    sku: sku.to_string(),
+                    requested: quantity,
+                    available,
+                });
+            }
+
+            let reservation = ::entity::inventory_reservations::ActiveModel {
+                mid: Set(mid),
+                sku: Set(sku.to_string()),
+                cart_id: Set(cart_id.to_string()),
+                quantity: Set(quantity),
+                created_gmt: Set(now),
+                expires_gmt: Set(now + Duration::seconds(RESERVATION_TTL_SECS)),
+                released_gmt: Set(None),
+                order_id: Set(None),
+                ..Default::default()
+            }
+            .insert(&txn)
+            .await?;
+            reservations.push(reservation);
+        }
+
+        txn.commit().await?;
+        Ok(reservations)
+    }
+
+    /// Release a cart's reservations (checkout abandoned or cancelled)
+    #[instrument(skip_all, fields(mid = mid, cart_id = cart_id))]
+    pub async fn release<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        cart_id: &str,
+    ) -> Result<u64, InventoryError> {
+        Self::release_cart(db, mid, cart_id).await
+    }
+
+    async fn release_cart<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        cart_id: &str,
+    ) -> Result<u64, InventoryError> {
+        let result = InventoryReservations::update_many()
+            .col_expr(
+                ::entity::inventory_reservations::Column::ReleasedGmt,
+                Expr::value(Timestamp::now()),
+            )
+            .filter(::entity::inventory_reservations::Column::Mid.eq(mid))
+            .filter(::entity::inventory_reservations::Column::CartId.eq(cart_id))
+            .filter(::entity::inventory_reservations::Column::ReleasedGmt.is_null())
+            .exec(db)
+            .await?;
+
+        Ok(result.rows_affected)
+    }
+
+    /// Take units of an order's SKUs out of sellable stock, allocating
+    /// them from the merchant's warehouses. Stock held by `cart_id`'s own
+    /// reservations counts as available.
+    #[allow(clippy::too_many_arguments)]
+    async fn take<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        cart_id: &str,
+        order_id: i32,
+        lines: &[(&str, i32)],
+        ship_to: Option<&ShipTo>,
+        reason: AdjustmentReason,
+    ) -> Result<(), InventoryError> {
+        let warehouses = Warehouses::find()
+            .filter(::entity::warehouses::Column::Mid.eq(mid))
+            .filter(::entity::warehouses::Column::Active.eq(true))
+            .all(db)
+            .await?;
+
+        for &(sku, quantity) in lines {
+            let record = Self::find_sku(db, mid, sku).await?;
+            let available = record.inv_available - Self::reserved_by_others(db, mid, sku, cart_id).await?;
+            if available < quantity {
+                return Err(InventoryError::Insufficient {
+                    sku: sku.to_string(),
+                    requested: quantity,
+                    available,
+                });
+            }
+
+            // The guard keeps concurrent orders from overselling
+            let result = Skus::update_many()
+                .col_expr(
+                    ::entity::skus::Column::InvAvailable,
+                    Expr::col(::entity::skus::Column::InvAvailable).sub(quantity),
+                )
+                .filter(::entity::skus::Column::Id.eq(record.id))
+                .filter(::entity::skus::Column::InvAvailable.gte(quantity))
+                .exec(db)
+                .await?;
+            if result.rows_affected == 0 {
+                return Err(InventoryError::Insufficient {
+                    sku: sku.to_string(),
+                    requested: quantity,
+                    available: record.inv_available,
+                });
+            }
+
+            let allocated = WarehouseService::allocate(db, &warehouses, mid, order_id, sku, quantity, ship_to).await?;
+            Self::record(
+                db,
+                mid,
+                sku,
+                -quantity,
+                record.inv_available - quantity,
+                reason,
+                None,
+                Some(order_id),
+                None,
+                warehouses::single_warehouse(&allocated),
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Decrement stock for a placed order, allocate it from the merchant's
+    /// warehouses and consume the cart's reservations. `ship_to` is where
+    /// the order goes, if known. Meant to run inside the transaction that
+    /// creates the order.
+    #[instrument(skip_all, fields(mid = mid, cart_id = cart_id, order_id = order_id))]
+    pub async fn commit_order<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        cart_id: &str,
+        order_id: i32,
+        lines: &[(&str, i32)],
+        ship_to: Option<&ShipTo>,
+    ) -> Result<(), InventoryError> {
+        Self::take(db, mid, cart_id, order_id, lines, ship_to, AdjustmentReason::Order).await?;
+
+        InventoryReservations::update_many()
+            .col_expr(
+                ::entity::inventory_reservations::Column::ReleasedGmt,
+                Expr::value(Timestamp::now()),
+            )
+            .col_expr(
+                ::entity::inventory_reservations::Column::OrderId,
+                Expr::value(order_id),
+            )
+            .filter(::entity::inventory_reservations::Column::Mid.eq(mid))
+            .filter(::entity::inventory_reservations::Column::CartId.eq(cart_id))
+            .filter(::entity::inventory_reservations::Column::ReleasedGmt.is_null())
+            .exec(db)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Put units of an order's SKUs back into sellable stock, and into the
+    /// warehouses they were allocated from
+    async fn credit<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        order_id: i32,
+        lines: &[(&str, i32)],
+        reason: AdjustmentReason,
+    ) -> Result<(), InventoryError> {
+        for &(sku, quantity) in lines {
+            let record = Self::find_sku(db, mid, sku).await?;
+            Skus::update_many()
+                .col_expr(
+                    ::entity::skus::Column::InvAvailable,
+                    Expr::col(::entity::skus::Column::InvAvailable).add(quantity),
+                )
+                .filter(::entity::skus::Column::Id.eq(record.id))
+                .exec(db)
+                .await?;
+
+            let credited = WarehouseService::deallocate(db, mid, order_id, sku, quantity).await?;
+            Self::record(
+                db,
+                mid,
+                sku,
+                quantity,
+                record.inv_available + quantity,
+                reason,
+                None,
+                Some(order_id),
+                None,
+                warehouses::single_warehouse(&credited),
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Return the stock of a cancelled order
+    #[instrument(skip_all, fields(mid = mid, order_id = order_id))]
+    pub async fn restock_order<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: i32,
+        order_id: i32,
+    ) -> Result<(), InventoryError> {
+        let txn = db.begin().await?;
+
+        let items = OrderItems::find()
+            .filter(::entity::order_items::Column::Mid.eq(mid))
+            .filter(::entity::order_items::Column::OrderId.eq(order_id))
+            .all(&txn)
+            .await?;
+        // Adjustment lines such as `%COUPON` carry no stock
+        let lines: Vec<(&str, i32)> = items
+            .iter()
+            .filter(|item| !item.sku.starts_with('%'))
+            .map(|item| (item.sku.as_str(), item.quantity))
+            .collect();
+        Self::credit(&txn, mid, order_id, &lines, AdjustmentReason::Cancellation).await?;
+
+        txn.commit().await?;
+        Ok(())
+    }
+
+    /// Return the stock of items sent back by the customer
+    #[instrument(skip_all, fields(mid = mid, order_id = order_id))]
+    pub async fn restock_return<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        order_id: i32,
+        lines: &[(&str, i32)],
+    ) -> Result<(), InventoryError> {
+        Self::credit(db, mid, order_id, lines, AdjustmentReason::Return).await
+    }
+
+    /// Move stock for an edited order: take more of each SKU with a
+    /// positive quantity and put back units of those with a negative one.
+    /// Meant to run inside the transaction that edits the order.
+    #[instrument(skip_all, fields(mid = mid, order_id = order_id))]
+    pub async fn adjust_order<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        cart_id: &str,
+        order_id: i32,
+        changes: &[(&str, i32)],
+    ) -> Result<(), InventoryError> {
+        let taken: Vec<(&str, i32)> = changes.iter().copied().filter(|&(_, quantity)| quantity > 0).collect();
+        let returned: Vec<(&str, i32)> = changes
+            .iter()
+            .filter(|&&(_, quantity)| quantity < 0)
+            .map(|&(sku, quantity)| (sku, -quantity))
+            .collect();
+        // Returned units first, so swapping one variant for another can't run short
+        Self::credit(db, mid, order_id, &returned, AdjustmentReason::Edit).await?;
+        Self::take(db, mid, cart_id, order_id, &taken, None, AdjustmentReason::Edit).await
+    }
+
+    /// Manually adjust stock (receiving, shrinkage, recounts). Both the
+    /// sellable and the on-shelf counts move by `delta`, as does the count
+    /// of `warehouse_id` if given; none of them can go negative.
+    #[instrument(skip_all, fields(mid = mid, sku = sku, delta = delta))]
+    #[allow(clippy::too_many_arguments)]
+    pub async fn adjust<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: i32,
+        sku: &str,
+        delta: i32,
+        note: Option<String>,
+        actor: Option<i32>,
+        warehouse_id: Option<i32>,
+    ) -> Result<InventoryAdjustment, InventoryError> {
+        let txn = db.begin().await?;
+        let record = Self::find_sku(&txn, mid, sku).await?;
+        if let Some(warehouse_id) = warehouse_id {
+            WarehouseService::find(&txn, mid, warehouse_id).await?;
+            WarehouseService::move_stock(&txn, mid, warehouse_id, sku, delta).await?;
+        }
+        Self::move_sku(&txn, &record, delta).await?;
+
+        let adjustment = Self::record(
+            &txn,
+            mid,
+            sku,
+            delta,
+            record.inv_available + delta,
+            AdjustmentReason::Manual,
+            note,
+            None,
+            actor,
+            warehouse_id,
+        )
+        .await?;
+
+        txn.commit().await?;
+        Ok(adjustment)
+    }
+
+    /// Audit trail of a SKU, newest first
+    pub async fn history(
+        db: &DatabaseConnection,
+        mid: i32,
+        sku: &str,
+    ) -> Result<Vec<InventoryAdjustment>, InventoryError> {
+        let adjustments = InventoryAdjustments::find()
+            .filter(::entity::inventory_adjustments::Column::Mid.eq(mid))
+            .filter(::entity::inventory_adjustments::Column::Sku.eq(sku))
+            .order_by_desc(::entity::inventory_adjustments::Column::Id)
+            .all(db)
+            .await?;
+
+        Ok(adjustments)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+
+    fn sku(id: i32, sku: &str, inv_available: i32) -> Sku {
+        Sku {
+            id,
+            pid: 1,
+            mid: 1,
+            sku: sku.to_string(),
+            title: sku.to_string(),
+            price: Decimal::new(1000, 2),
+            cost: Decimal::ZERO,
+            upc: String::new(),
+            inv_available,
+            qty_onshelf: inv_available,
+            weight: Decimal::ZERO,
+            reorder_point: None,
+            reorder_quantity: 0,
+            low_stock_gmt: None,
+        }
+    }
+
+    fn reservation(quantity: i32, expires_gmt: i64, released_gmt: Option<i64>) -> InventoryReservation {
+        InventoryReservation {
+            id: 1,
+            mid: 1,
+            sku: "SKU001".to_string(),
+            cart_id: "cart".to_string(),
+            quantity,
+            created_gmt: Timestamp::EPOCH,
+            expires_gmt: Timestamp::from_unix(expires_gmt),
+            released_gmt: released_gmt.map(Timestamp::from_unix),
+            order_id: None,
+        }
+    }
+
+    #[test]
+    fn test_reserved_quantity_ignores_released_and_expired() {
+        let reservations = vec![
+            reservation(2, 200, None),
+            reservation(3, 200, Some(150)),
+            reservation(5, 50, None),
+        ];
+        assert_eq!(reserved_quantity(&reservations, Timestamp::from_unix(100)), 2);
+        assert_eq!(reserved_quantity(&reservations, Timestamp::from_unix(40)), 7);
+    }
+
+    #[tokio::test]
+    async fn test_reserve_locks_skus_in_order() {
+        let db = MockDatabase::new(DbBackend::Postgres)
+            .append_exec_results([MockExecResult { last_insert_id: 0, rows_affected: 0 }])
+            .append_query_results([vec![sku(1, "A", 5)]])
+            .append_query_results([Vec::<InventoryReservation>::new()])
+            .append_query_results([vec![InventoryReservation { sku: "A".to_string(), ..reservation(1, 4_000_000_000, None) }]])
+            .append_query_results([vec![sku(2, "B", 5)]])
+            .append_query_results([vec![reservation(4, 4_000_000_000, None)]])
+            .into_connection();
+
+        // Another cart holds 4 of B's 5
+        assert!(matches!(
+            InventoryService::reserve(&db, 1, "cart", &[("B", 2), ("A", 1)]).await,
+            Err(InventoryError::Insufficient { requested: 2, available: 1, .. })
+        ));
+
+        let log = db.into_transaction_log();
+        let sql: Vec<String> = log[0].statements().iter().map(|statement| statement.to_string()).collect();
+        assert!(sql[2].contains(r#""sku_lookup"."sku" = 'A'"#) && sql[2].ends_with("FOR UPDATE"), "{}", sql[2]);
+        assert!(sql[5].contains(r#""sku_lookup"."sku" = 'B'"#) && sql[5].ends_with("FOR UPDATE"), "{}", sql[5]);
+    }
+}
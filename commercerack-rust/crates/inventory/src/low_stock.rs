@@ -0,0 +1,141 @@
+//! Low stock alerts
+//!
+//! A SKU with a reorder point is low once its sellable stock is at or
+//! below it. A periodic [`LowStockService::scan`] records one
+//! `inventory.low_stock` event per SKU that has gone low and marks it
+//! alerted, so the merchant hears about it once; the mark is cleared when
+//! stock is back above the reorder point and the next dip alerts again.
+
+use commercerack_core::Timestamp;
+use commercerack_events::{outbox, DomainEvent};
+use sea_orm::sea_query::Expr;
+use sea_orm::*;
+use ::entity::prelude::*;
+use tracing::instrument;
+
+use crate::InventoryError;
+
+/// Whether a SKU's stock is at or below its reorder point
+pub fn is_low(sku: &Sku) -> bool {
+    sku.reorder_point.is_some_and(|point| sku.inv_available <= point)
+}
+
+fn low_condition() -> Condition {
+    use ::entity::skus::Column;
+
+    Condition::all()
+        .add(Column::ReorderPoint.is_not_null())
+        .add(Expr::col(Column::InvAvailable).lte(Expr::col(Column::ReorderPoint)))
+}
+
+/// Low stock detection and listing
+pub struct LowStockService;
+
+impl LowStockService {
+    /// A merchant's SKUs that are low right now, emptiest first
+    pub async fn list(
+        db: &DatabaseConnection,
+        mid: i32,
+    ) -> Result<Vec<Sku>, InventoryError> {
+        let skus = Skus::find()
+            .filter(::entity::skus::Column::Mid.eq(mid))
+            .filter(low_condition())
+            .order_by_asc(::entity::skus::Column::InvAvailable)
+            .order_by_asc(::entity::skus::Column::Sku)
+            .all(db)
+            .await?;
+
+        Ok(skus)
+    }
+
+    /// Alert on every SKU, of any merchant, that has gone low since the
+    /// last scan and re-arm the ones that recovered. Returns how many
+    /// alerts were raised.
+    #[instrument(skip_all)]
+    pub async fn scan<C: ConnectionTrait + TransactionTrait>(db: &C) -> Result<usize, InventoryError> {
+        use ::entity::skus::Column;
+
+        let now = Timestamp::now();
+        let txn = db.begin().await?;
+
+        Skus::update_many()
+            .col_expr(Column::LowStockGmt, Expr::value(Option::<i32>::None))
+            .filter(Column::LowStockGmt.is_not_null())
+            .filter(low_condition().not())
+            .exec(&txn)
+            .await?;
+
+        let skus = Skus::find()
+            .filter(low_condition())
+            .filter(Column::LowStockGmt.is_null())
+            .all(&txn)
+            .await?;
+        if !skus.is_empty() {
+            Skus::update_many()
+                .col_expr(Column::LowStockGmt, Expr::value(now))
+                .filter(Column::Id.is_in(skus.iter().map(|sku| sku.id)))
+                .exec(&txn)
+                .await?;
+        }
+        for mut sku in skus.iter().cloned() {
+            sku.low_stock_gmt = Some(now);
+            outbox::record(&txn, &DomainEvent::LowStock(sku)).await?;
+        }
+
+        txn.commit().await?;
+        Ok(skus.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+    use sea_orm::{DatabaseBackend, MockDatabase, MockExecResult};
+
+    fn sku(inv_available: i32, reorder_point: Option<i32>) -> Sku {
+        Sku {
+            id: 3,
+            pid: 1,
+            mid: 1,
+            sku: "SKU001".to_string(),
+            title: "Widget".to_string(),
+            price: Decimal::ZERO,
+            cost: Decimal::ZERO,
+            upc: String::new(),
+            inv_available,
+            qty_onshelf: inv_available,
+            weight: Decimal::ZERO,
+            reorder_point,
+            reorder_quantity: 24,
+            low_stock_gmt: None,
+        }
+    }
+
+    #[test]
+    fn test_is_low_at_or_below_reorder_point() {
+        assert!(is_low(&sku(5, Some(5))));
+        assert!(is_low(&sku(0, Some(0))));
+        assert!(!is_low(&sku(6, Some(5))));
+        assert!(!is_low(&sku(0, None)));
+    }
+
+    #[tokio::test]
+    async fn test_scan_alerts_once_per_dip() {
+        let exec = || MockExecResult { last_insert_id: 0, rows_affected: 1 };
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_exec_results([exec()])
+            .append_query_results([vec![sku(2, Some(5))]])
+            .append_exec_results([exec(), exec()])
+            .into_connection();
+
+        assert_eq!(LowStockService::scan(&db).await.unwrap(), 1);
+
+        let log = db.into_transaction_log();
+        let statements: Vec<String> = log[0].statements().iter().map(|s| s.to_string()).collect();
+        assert!(statements[1].contains(r#""low_stock_gmt" = NULL"#), "{}", statements[1]);
+        assert!(statements[2].contains(r#""inv_available" <= "reorder_point""#), "{}", statements[2]);
+        assert!(statements[2].contains(r#""low_stock_gmt" IS NULL"#), "{}", statements[2]);
+        assert!(statements[4].contains("'inventory.low_stock'"), "{}", statements[4]);
+    }
+}
@@ -0,0 +1,431 @@
+//! Purchase orders to suppliers
+//!
+//! A purchase order is drafted, edited while still a draft, then sent to
+//! the supplier. Deliveries are booked against it as they arrive: each
+//! receipt adds its units to stock (and to the order's warehouse, if it
+//! names one) and the order stays partially received until every line has
+//! come in.
+
+use commercerack_core::Timestamp;
+use sea_orm::prelude::Decimal;
+use sea_orm::*;
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+use ::entity::prelude::*;
+use tracing::instrument;
+
+use crate::warehouses::WarehouseService;
+use crate::{AdjustmentReason, InventoryError, InventoryService};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PurchaseOrderStatus {
+    Draft,
+    Sent,
+    PartiallyReceived,
+    Received,
+}
+
+impl PurchaseOrderStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PurchaseOrderStatus::Draft => "draft",
+            PurchaseOrderStatus::Sent => "sent",
+            PurchaseOrderStatus::PartiallyReceived => "partially_received",
+            PurchaseOrderStatus::Received => "received",
+        }
+    }
+}
+
+impl fmt::Display for PurchaseOrderStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for PurchaseOrderStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "draft" => Ok(PurchaseOrderStatus::Draft),
+            "sent" => Ok(PurchaseOrderStatus::Sent),
+            "partially_received" => Ok(PurchaseOrderStatus::PartiallyReceived),
+            "received" => Ok(PurchaseOrderStatus::Received),
+            other => Err(format!("unknown purchase order status {}", other)),
+        }
+    }
+}
+
+/// A line to order
+#[derive(Debug, Clone)]
+pub struct PurchaseOrderLine {
+    pub sku: String,
+    pub quantity: i32,
+    /// Defaults to the SKU's cost
+    pub unit_cost: Option<Decimal>,
+}
+
+/// Everything about a purchase order that can be edited while it is a draft
+#[derive(Debug, Clone)]
+pub struct PurchaseOrderInput {
+    pub supplier: String,
+    pub warehouse_id: Option<i32>,
+    pub note: Option<String>,
+    pub expected_gmt: Option<Timestamp>,
+    pub items: Vec<PurchaseOrderLine>,
+}
+
+/// A purchase order with its lines
+#[derive(Debug, Clone)]
+pub struct PurchaseOrderWithItems {
+    pub purchase_order: PurchaseOrder,
+    pub items: Vec<PurchaseOrderItem>,
+}
+
+/// Why these lines can't be ordered, if they can't
+pub fn validate_lines(items: &[PurchaseOrderLine]) -> Result<(), InventoryError> {
+    if items.is_empty() {
+        return Err(InventoryError::InvalidPurchaseOrder("a purchase order needs at least one item"));
+    }
+    if items.iter().any(|item| item.quantity <= 0) {
+        return Err(InventoryError::InvalidPurchaseOrder("quantities must be positive"));
+    }
+    if items.iter().any(|item| item.unit_cost.is_some_and(|cost| cost.is_sign_negative())) {
+        return Err(InventoryError::InvalidPurchaseOrder("unit costs can not be negative"));
+    }
+    let mut seen = HashSet::new();
+    if !items.iter().all(|item| seen.insert(item.sku.as_str())) {
+        return Err(InventoryError::InvalidPurchaseOrder("each SKU may only be ordered once"));
+    }
+    Ok(())
+}
+
+/// Where an order stands once its lines have received what they have
+pub fn receipt_status(items: &[PurchaseOrderItem]) -> PurchaseOrderStatus {
+    if items.iter().all(|item| item.received >= item.quantity) {
+        PurchaseOrderStatus::Received
+    } else if items.iter().any(|item| item.received > 0) {
+        PurchaseOrderStatus::PartiallyReceived
+    } else {
+        PurchaseOrderStatus::Sent
+    }
+}
+
+/// Purchase order service
+pub struct PurchaseOrderService;
+
+impl PurchaseOrderService {
+    /// Draft a purchase order
+    #[instrument(skip_all, fields(mid = mid))]
+    pub async fn create<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: i32,
+        input: PurchaseOrderInput,
+        actor: Option<i32>,
+    ) -> Result<PurchaseOrderWithItems, InventoryError> {
+        validate_lines(&input.items)?;
+
+        let txn = db.begin().await?;
+        if let Some(warehouse_id) = input.warehouse_id {
+            WarehouseService::find(&txn, mid, warehouse_id).await?;
+        }
+
+        let purchase_order = ::entity::purchase_orders::ActiveModel {
+            mid: Set(mid),
+            supplier: Set(input.supplier),
+            status: Set(PurchaseOrderStatus::Draft.as_str().to_string()),
+            warehouse_id: Set(input.warehouse_id),
+            note: Set(input.note),
+            expected_gmt: Set(input.expected_gmt),
+            actor: Set(actor),
+            created_gmt: Set(Timestamp::now()),
+            sent_gmt: Set(None),
+            received_gmt: Set(None),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+        let items = Self::insert_items(&txn, mid, purchase_order.id, &input.items).await?;
+
+        txn.commit().await?;
+        Ok(PurchaseOrderWithItems { purchase_order, items })
+    }
+
+    /// Replace a draft's supplier details and lines
+    #[instrument(skip_all, fields(mid = mid, id = id))]
+    pub async fn update<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: i32,
+        id: i32,
+        input: PurchaseOrderInput,
+    ) -> Result<PurchaseOrderWithItems, InventoryError> {
+        validate_lines(&input.items)?;
+
+        let txn = db.begin().await?;
+        let existing = Self::load(&txn, mid, id).await?.purchase_order;
+        if existing.status != PurchaseOrderStatus::Draft.as_str() {
+            return Err(InventoryError::PurchaseOrderStatus(existing.status));
+        }
+        if let Some(warehouse_id) = input.warehouse_id {
+            WarehouseService::find(&txn, mid, warehouse_id).await?;
+        }
+
+        let mut active: ::entity::purchase_orders::ActiveModel = existing.into();
+        active.supplier = Set(input.supplier);
+        active.warehouse_id = Set(input.warehouse_id);
+        active.note = Set(input.note);
+        active.expected_gmt = Set(input.expected_gmt);
+        let purchase_order = active.update(&txn).await?;
+
+        PurchaseOrderItems::delete_many()
+            .filter(::entity::purchase_order_items::Column::PurchaseOrderId.eq(id))
+            .exec(&txn)
+            .await?;
+        let items = Self::insert_items(&txn, mid, id, &input.items).await?;
+
+        txn.commit().await?;
+        Ok(PurchaseOrderWithItems { purchase_order, items })
+    }
+
+    async fn insert_items<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        purchase_order_id: i32,
+        lines: &[PurchaseOrderLine],
+    ) -> Result<Vec<PurchaseOrderItem>, InventoryError> {
+        let mut items = Vec::with_capacity(lines.len());
+        for line in lines {
+            let record = InventoryService::find_sku(db, mid, &line.sku).await?;
+            let item = ::entity::purchase_order_items::ActiveModel {
+                purchase_order_id: Set(purchase_order_id),
+                sku: Set(record.sku),
+                quantity: Set(line.quantity),
+                received: Set(0),
+                unit_cost: Set(line.unit_cost.unwrap_or(record.cost)),
+                ..Default::default()
+            }
+            .insert(db)
+            .await?;
+            items.push(item);
+        }
+
+        Ok(items)
+    }
+
+    /// Mark a draft as sent to the supplier; it can no longer be edited
+    #[instrument(skip_all, fields(mid = mid, id = id))]
+    pub async fn send<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        id: i32,
+    ) -> Result<PurchaseOrderWithItems, InventoryError> {
+        let PurchaseOrderWithItems { purchase_order, items } = Self::load(db, mid, id).await?;
+        if purchase_order.status != PurchaseOrderStatus::Draft.as_str() {
+            return Err(InventoryError::PurchaseOrderStatus(purchase_order.status));
+        }
+
+        let mut active: ::entity::purchase_orders::ActiveModel = purchase_order.into();
+        active.status = Set(PurchaseOrderStatus::Sent.as_str().to_string());
+        active.sent_gmt = Set(Some(Timestamp::now()));
+        let purchase_order = active.update(db).await?;
+
+        Ok(PurchaseOrderWithItems { purchase_order, items })
+    }
+
+    /// Book a delivery of `(sku, quantity)` lines into stock. A line can't
+    /// bring in more units than are still outstanding on the order.
+    #[instrument(skip_all, fields(mid = mid, id = id))]
+    pub async fn receive<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: i32,
+        id: i32,
+        lines: &[(&str, i32)],
+        actor: Option<i32>,
+    ) -> Result<PurchaseOrderWithItems, InventoryError> {
+        if lines.is_empty() {
+            return Err(InventoryError::InvalidPurchaseOrder("a delivery needs at least one item"));
+        }
+        if lines.iter().any(|&(_, quantity)| quantity <= 0) {
+            return Err(InventoryError::InvalidPurchaseOrder("quantities must be positive"));
+        }
+
+        let txn = db.begin().await?;
+        let PurchaseOrderWithItems { purchase_order, mut items } = Self::load(&txn, mid, id).await?;
+        let status: PurchaseOrderStatus = purchase_order.status.parse().map_err(|_| {
+            InventoryError::PurchaseOrderStatus(purchase_order.status.clone())
+        })?;
+        if !matches!(status, PurchaseOrderStatus::Sent | PurchaseOrderStatus::PartiallyReceived) {
+            return Err(InventoryError::PurchaseOrderStatus(purchase_order.status));
+        }
+
+        for &(sku, quantity) in lines {
+            let item = items
+                .iter_mut()
+                .find(|item| item.sku == sku)
+                .ok_or(InventoryError::InvalidPurchaseOrder("SKU is not on this purchase order"))?;
+            if item.received + quantity > item.quantity {
+                return Err(InventoryError::InvalidPurchaseOrder("more units than are outstanding"));
+            }
+
+            let record = InventoryService::find_sku(&txn, mid, sku).await?;
+            if let Some(warehouse_id) = purchase_order.warehouse_id {
+                WarehouseService::move_stock(&txn, mid, warehouse_id, sku, quantity).await?;
+            }
+            InventoryService::move_sku(&txn, &record, quantity).await?;
+            InventoryService::record(
+                &txn,
+                mid,
+                sku,
+                quantity,
+                record.inv_available + quantity,
+                AdjustmentReason::Purchase,
+                Some(format!("Purchase order #{} received", purchase_order.id)),
+                None,
+                actor,
+                purchase_order.warehouse_id,
+            )
+            .await?;
+
+            let mut active: ::entity::purchase_order_items::ActiveModel = item.clone().into();
+            active.received = Set(item.received + quantity);
+            *item = active.update(&txn).await?;
+        }
+
+        let status = receipt_status(&items);
+        let mut active: ::entity::purchase_orders::ActiveModel = purchase_order.into();
+        active.status = Set(status.as_str().to_string());
+        if status == PurchaseOrderStatus::Received {
+            active.received_gmt = Set(Some(Timestamp::now()));
+        }
+        let purchase_order = active.update(&txn).await?;
+
+        txn.commit().await?;
+        Ok(PurchaseOrderWithItems { purchase_order, items })
+    }
+
+    async fn load<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        id: i32,
+    ) -> Result<PurchaseOrderWithItems, InventoryError> {
+        let purchase_order = PurchaseOrders::find_by_id(id)
+            .filter(::entity::purchase_orders::Column::Mid.eq(mid))
+            .one(db)
+            .await?
+            .ok_or(InventoryError::PurchaseOrderNotFound)?;
+        let items = PurchaseOrderItems::find()
+            .filter(::entity::purchase_order_items::Column::PurchaseOrderId.eq(purchase_order.id))
+            .order_by_asc(::entity::purchase_order_items::Column::Id)
+            .all(db)
+            .await?;
+
+        Ok(PurchaseOrderWithItems { purchase_order, items })
+    }
+
+    /// A purchase order with its lines
+    pub async fn find(
+        db: &DatabaseConnection,
+        mid: i32,
+        id: i32,
+    ) -> Result<PurchaseOrderWithItems, InventoryError> {
+        Self::load(db, mid, id).await
+    }
+
+    /// A merchant's purchase orders, newest first
+    pub async fn list(
+        db: &DatabaseConnection,
+        mid: i32,
+        status: Option<PurchaseOrderStatus>,
+    ) -> Result<Vec<PurchaseOrder>, InventoryError> {
+        let mut query = PurchaseOrders::find().filter(::entity::purchase_orders::Column::Mid.eq(mid));
+        if let Some(status) = status {
+            query = query.filter(::entity::purchase_orders::Column::Status.eq(status.as_str()));
+        }
+
+        let purchase_orders = query
+            .order_by_desc(::entity::purchase_orders::Column::Id)
+            .all(db)
+            .await?;
+        Ok(purchase_orders)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sea_orm::{DatabaseBackend, MockDatabase};
+
+    fn line(sku: &str, quantity: i32) -> PurchaseOrderLine {
+        PurchaseOrderLine {
+            sku: sku.to_string(),
+            quantity,
+            unit_cost: None,
+        }
+    }
+
+    fn item(quantity: i32, received: i32) -> PurchaseOrderItem {
+        PurchaseOrderItem {
+            id: 1,
+            purchase_order_id: 7,
+            sku: "SKU001".to_string(),
+            quantity,
+            received,
+            unit_cost: Decimal::ZERO,
+        }
+    }
+
+    #[test]
+    fn test_validate_lines() {
+        assert!(validate_lines(&[line("SKU001", 12), line("SKU002", 6)]).is_ok());
+        assert!(matches!(validate_lines(&[]), Err(InventoryError::InvalidPurchaseOrder(_))));
+        assert!(matches!(validate_lines(&[line("SKU001", 0)]), Err(InventoryError::InvalidPurchaseOrder(_))));
+        assert!(matches!(
+            validate_lines(&[line("SKU001", 1), line("SKU001", 2)]),
+            Err(InventoryError::InvalidPurchaseOrder(_))
+        ));
+    }
+
+    #[test]
+    fn test_receipt_status() {
+        assert_eq!(receipt_status(&[item(10, 0), item(5, 0)]), PurchaseOrderStatus::Sent);
+        assert_eq!(receipt_status(&[item(10, 4), item(5, 0)]), PurchaseOrderStatus::PartiallyReceived);
+        assert_eq!(receipt_status(&[item(10, 10), item(5, 5)]), PurchaseOrderStatus::Received);
+    }
+
+    #[tokio::test]
+    async fn test_draft_cannot_be_received() {
+        let purchase_order = PurchaseOrder {
+            id: 7,
+            mid: 1,
+            supplier: "Acme".to_string(),
+            status: "draft".to_string(),
+            warehouse_id: None,
+            note: None,
+            expected_gmt: None,
+            actor: None,
+            created_gmt: Timestamp::EPOCH,
+            sent_gmt: None,
+            received_gmt: None,
+        };
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![purchase_order]])
+            .append_query_results([vec![item(10, 0)]])
+            .into_connection();
+
+        let result = PurchaseOrderService::receive(&db, 1, 7, &[("SKU001", 4)], None).await;
+        assert!(matches!(result, Err(InventoryError::PurchaseOrderStatus(status)) if status == "draft"));
+    }
+
+    #[test]
+    fn test_status_round_trip() {
+        for status in [
+            PurchaseOrderStatus::Draft,
+            PurchaseOrderStatus::Sent,
+            PurchaseOrderStatus::PartiallyReceived,
+            PurchaseOrderStatus::Received,
+        ] {
+            assert_eq!(status.as_str().parse::<PurchaseOrderStatus>(), Ok(status));
+        }
+    }
+}
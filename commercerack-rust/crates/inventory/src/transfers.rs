@@ -0,0 +1,332 @@
+//! Stock transfers between warehouses
+//!
+//! Creating a transfer ships it: its units leave the origin warehouse at
+//! once and show as in transit at the destination until it is received.
+//! Units in transit are in no warehouse and not sellable. Cancelling a
+//! transfer still in transit puts its units back at the origin.
+
+use commercerack_core::Timestamp;
+use sea_orm::sea_query::Expr;
+use sea_orm::*;
+use std::fmt;
+use std::str::FromStr;
+use ::entity::prelude::*;
+use tracing::instrument;
+
+use crate::warehouses::WarehouseService;
+use crate::{AdjustmentReason, InventoryError, InventoryService};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferStatus {
+    InTransit,
+    Received,
+    Cancelled,
+}
+
+impl TransferStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransferStatus::InTransit => "in_transit",
+            TransferStatus::Received => "received",
+            TransferStatus::Cancelled => "cancelled",
+        }
+    }
+}
+
+impl fmt::Display for TransferStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for TransferStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "in_transit" => Ok(TransferStatus::InTransit),
+            "received" => Ok(TransferStatus::Received),
+            "cancelled" => Ok(TransferStatus::Cancelled),
+            other => Err(format!("unknown transfer status {}", other)),
+        }
+    }
+}
+
+/// A transfer with its lines
+#[derive(Debug, Clone)]
+pub struct TransferWithItems {
+    pub transfer: StockTransfer,
+    pub items: Vec<StockTransferItem>,
+}
+
+/// Why a transfer can't be created, if it can't
+pub fn validate_transfer(from: i32, to: i32, lines: &[(&str, i32)]) -> Result<(), InventoryError> {
+    if from == to {
+        return Err(InventoryError::InvalidTransfer("origin and destination must differ"));
+    }
+    if lines.is_empty() {
+        return Err(InventoryError::InvalidTransfer("a transfer needs at least one item"));
+    }
+    if lines.iter().any(|&(_, quantity)| quantity <= 0) {
+        return Err(InventoryError::InvalidTransfer("quantities must be positive"));
+    }
+    Ok(())
+}
+
+/// Stock transfer service
+pub struct TransferService;
+
+impl TransferService {
+    /// Ship units from one warehouse to another. Units held by checkout
+    /// reservations can't be moved.
+    #[instrument(skip_all, fields(mid = mid, from = from, to = to))]
+    pub async fn create<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: i32,
+        from: i32,
+        to: i32,
+        lines: &[(&str, i32)],
+        note: Option<String>,
+        actor: Option<i32>,
+    ) -> Result<TransferWithItems, InventoryError> {
+        validate_transfer(from, to, lines)?;
+
+        let txn = db.begin().await?;
+        WarehouseService::find(&txn, mid, from).await?;
+        WarehouseService::find(&txn, mid, to).await?;
+
+        let transfer = ::entity::stock_transfers::ActiveModel {
+            mid: Set(mid),
+            from_warehouse: Set(from),
+            to_warehouse: Set(to),
+            status: Set(TransferStatus::InTransit.as_str().to_string()),
+            note: Set(note),
+            actor: Set(actor),
+            created_gmt: Set(Timestamp::now()),
+            closed_gmt: Set(None),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+
+        let mut items = Vec::with_capacity(lines.len());
+        for &(sku, quantity) in lines {
+            let record = InventoryService::find_sku(&txn, mid, sku).await?;
+            let available = record.inv_available - InventoryService::reserved_by_others(&txn, mid, sku, "").await?;
+            if available < quantity {
+                return Err(InventoryError::Insufficient {
+                    sku: sku.to_string(),
+                    requested: quantity,
+                    available,
+                });
+            }
+
+            WarehouseService::move_stock(&txn, mid, from, sku, -quantity).await?;
+            InventoryService::move_sku(&txn, &record, -quantity).await?;
+            Self::move_in_transit(&txn, mid, to, sku, quantity).await?;
+            InventoryService::record(
+                &txn,
+                mid,
+                sku,
+                -quantity,
+                record.inv_available - quantity,
+                AdjustmentReason::Transfer,
+                Some(format!("Transfer #{} shipped", transfer.id)),
+                None,
+                actor,
+                Some(from),
+            )
+            .await?;
+
+            let item = ::entity::stock_transfer_items::ActiveModel {
+                transfer_id: Set(transfer.id),
+                sku: Set(sku.to_string()),
+                quantity: Set(quantity),
+                ..Default::default()
+            }
+            .insert(&txn)
+            .await?;
+            items.push(item);
+        }
+
+        txn.commit().await?;
+        Ok(TransferWithItems { transfer, items })
+    }
+
+    /// Book a transfer's units into its destination
+    #[instrument(skip_all, fields(mid = mid, id = id))]
+    pub async fn receive<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: i32,
+        id: i32,
+        actor: Option<i32>,
+    ) -> Result<TransferWithItems, InventoryError> {
+        Self::close(db, mid, id, TransferStatus::Received, actor).await
+    }
+
+    /// Call off a transfer, putting its units back at the origin
+    #[instrument(skip_all, fields(mid = mid, id = id))]
+    pub async fn cancel<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: i32,
+        id: i32,
+        actor: Option<i32>,
+    ) -> Result<TransferWithItems, InventoryError> {
+        Self::close(db, mid, id, TransferStatus::Cancelled, actor).await
+    }
+
+    async fn close<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: i32,
+        id: i32,
+        status: TransferStatus,
+        actor: Option<i32>,
+    ) -> Result<TransferWithItems, InventoryError> {
+        let txn = db.begin().await?;
+        let TransferWithItems { transfer, items } = Self::load(&txn, mid, id).await?;
+        if transfer.status != TransferStatus::InTransit.as_str() {
+            return Err(InventoryError::TransferClosed(transfer.status));
+        }
+
+        let (warehouse_id, action) = match status {
+            TransferStatus::Received => (transfer.to_warehouse, "received"),
+            _ => (transfer.from_warehouse, "cancelled"),
+        };
+        for item in &items {
+            let record = InventoryService::find_sku(&txn, mid, &item.sku).await?;
+            Self::move_in_transit(&txn, mid, transfer.to_warehouse, &item.sku, -item.quantity).await?;
+            WarehouseService::move_stock(&txn, mid, warehouse_id, &item.sku, item.quantity).await?;
+            InventoryService::move_sku(&txn, &record, item.quantity).await?;
+            InventoryService::record(
+                &txn,
+                mid,
+                &item.sku,
+                item.quantity,
+                record.inv_available + item.quantity,
+                AdjustmentReason::Transfer,
+                Some(format!("Transfer #{} {}", transfer.id, action)),
+                None,
+                actor,
+                Some(warehouse_id),
+            )
+            .await?;
+        }
+
+        let mut active: ::entity::stock_transfers::ActiveModel = transfer.into();
+        active.status = Set(status.as_str().to_string());
+        active.closed_gmt = Set(Some(Timestamp::now()));
+        let transfer = active.update(&txn).await?;
+
+        txn.commit().await?;
+        Ok(TransferWithItems { transfer, items })
+    }
+
+    async fn move_in_transit<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        warehouse_id: i32,
+        sku: &str,
+        delta: i32,
+    ) -> Result<(), InventoryError> {
+        let row = WarehouseService::stock_row(db, mid, warehouse_id, sku).await?;
+        WarehouseStocks::update_many()
+            .col_expr(
+                ::entity::warehouse_stock::Column::InTransit,
+                Expr::col(::entity::warehouse_stock::Column::InTransit).add(delta),
+            )
+            .filter(::entity::warehouse_stock::Column::Id.eq(row.id))
+            .exec(db)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn load<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        id: i32,
+    ) -> Result<TransferWithItems, InventoryError> {
+        let transfer = StockTransfers::find_by_id(id)
+            .filter(::entity::stock_transfers::Column::Mid.eq(mid))
+            .one(db)
+            .await?
+            .ok_or(InventoryError::TransferNotFound)?;
+        let items = StockTransferItems::find()
+            .filter(::entity::stock_transfer_items::Column::TransferId.eq(transfer.id))
+            .order_by_asc(::entity::stock_transfer_items::Column::Id)
+            .all(db)
+            .await?;
+
+        Ok(TransferWithItems { transfer, items })
+    }
+
+    /// A transfer with its lines
+    pub async fn find(
+        db: &DatabaseConnection,
+        mid: i32,
+        id: i32,
+    ) -> Result<TransferWithItems, InventoryError> {
+        Self::load(db, mid, id).await
+    }
+
+    /// A merchant's transfers, newest first
+    pub async fn list(
+        db: &DatabaseConnection,
+        mid: i32,
+        status: Option<TransferStatus>,
+    ) -> Result<Vec<StockTransfer>, InventoryError> {
+        let mut query = StockTransfers::find().filter(::entity::stock_transfers::Column::Mid.eq(mid));
+        if let Some(status) = status {
+            query = query.filter(::entity::stock_transfers::Column::Status.eq(status.as_str()));
+        }
+
+        let transfers = query
+            .order_by_desc(::entity::stock_transfers::Column::Id)
+            .all(db)
+            .await?;
+        Ok(transfers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sea_orm::{DatabaseBackend, MockDatabase};
+
+    #[test]
+    fn test_validate_transfer() {
+        assert!(validate_transfer(1, 2, &[("SKU001", 3)]).is_ok());
+        assert!(matches!(validate_transfer(1, 1, &[("SKU001", 3)]), Err(InventoryError::InvalidTransfer(_))));
+        assert!(matches!(validate_transfer(1, 2, &[]), Err(InventoryError::InvalidTransfer(_))));
+        assert!(matches!(validate_transfer(1, 2, &[("SKU001", 0)]), Err(InventoryError::InvalidTransfer(_))));
+    }
+
+    #[tokio::test]
+    async fn test_received_transfer_cannot_be_cancelled() {
+        let transfer = StockTransfer {
+            id: 7,
+            mid: 1,
+            from_warehouse: 1,
+            to_warehouse: 2,
+            status: "received".to_string(),
+            note: None,
+            actor: None,
+            created_gmt: Timestamp::EPOCH,
+            closed_gmt: Some(Timestamp::from_unix(10)),
+        };
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![transfer]])
+            .append_query_results([Vec::<StockTransferItem>::new()])
+            .into_connection();
+
+        let result = TransferService::cancel(&db, 1, 7, None).await;
+        assert!(matches!(result, Err(InventoryError::TransferClosed(status)) if status == "received"));
+    }
+
+    #[test]
+    fn test_status_round_trip() {
+        for status in [TransferStatus::InTransit, TransferStatus::Received, TransferStatus::Cancelled] {
+            assert_eq!(status.as_str().parse::<TransferStatus>(), Ok(status));
+        }
+    }
+}
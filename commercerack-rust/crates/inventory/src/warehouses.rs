@@ -0,0 +1,490 @@
+//! Stock locations and order allocation
+//!
+//! A merchant without warehouses keeps a single stock count per SKU. Once
+//! warehouses exist, each holds its own count of every SKU and
+//! `inv_available` stays the sellable total: adjustments that name a
+//! warehouse move both. Placing an order allocates its units from
+//! warehouses according to the configured [`AllocationStrategy`] and records
+//! where each unit ships from; cancelled and returned units go back to the
+//! warehouse they were allocated from.
+//!
+//! Stock counted before the merchant had warehouses belongs to none of
+//! them. Orders can still sell it; those units are simply left unallocated.
+
+use commercerack_core::Timestamp;
+use sea_orm::sea_query::Expr;
+use sea_orm::*;
+use ::entity::prelude::*;
+use std::cmp::Reverse;
+use std::sync::OnceLock;
+use tracing::{instrument, warn};
+
+use crate::InventoryError;
+
+/// How an order's units are spread over the warehouses holding them
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AllocationStrategy {
+    /// Warehouses in the destination's state, then its country, then the rest
+    #[default]
+    Nearest,
+    /// Warehouses holding the most units of the SKU first
+    MostStock,
+}
+
+static STRATEGY: OnceLock<AllocationStrategy> = OnceLock::new();
+
+/// Choose the allocation strategy for this process. Only the first call
+/// takes effect.
+pub fn configure(strategy: AllocationStrategy) {
+    let _ = STRATEGY.set(strategy);
+}
+
+/// The allocation strategy of this process
+pub fn strategy() -> AllocationStrategy {
+    STRATEGY.get().copied().unwrap_or_default()
+}
+
+/// Where an order ships to, as far as picking a warehouse cares
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShipTo {
+    pub country: String,
+    pub state: String,
+}
+
+impl ShipTo {
+    /// Codes are compared case-insensitively, so they are kept uppercase
+    pub fn new(country: &str, state: &str) -> Self {
+        Self {
+            country: country.trim().to_uppercase(),
+            state: state.trim().to_uppercase(),
+        }
+    }
+
+    /// 0 for the same state, 1 for the same country, 2 for anywhere else
+    fn distance(&self, warehouse: &Warehouse) -> u8 {
+        match (warehouse.country == self.country, warehouse.state == self.state) {
+            (true, true) if !self.state.is_empty() => 0,
+            (true, _) => 1,
+            _ => 2,
+        }
+    }
+}
+
+/// Settings of a warehouse
+#[derive(Debug, Clone)]
+pub struct WarehouseInput {
+    pub code: String,
+    pub name: String,
+    pub country: String,
+    pub state: String,
+    pub priority: i32,
+    pub active: bool,
+    /// Business days between an order and its parcel leaving
+    pub handling_days: i32,
+    /// Hour of the merchant's day after which orders count from the next
+    /// business day
+    pub cutoff_hour: i32,
+}
+
+/// Units of a SKU to take from each warehouse, best first. Only active
+/// warehouses with stock are used; a shortfall is left out of the plan.
+pub fn allocation_plan(
+    strategy: AllocationStrategy,
+    warehouses: &[Warehouse],
+    stock: &[WarehouseStock],
+    ship_to: Option<&ShipTo>,
+    quantity: i32,
+) -> Vec<(i32, i32)> {
+    let mut candidates: Vec<(&Warehouse, i32)> = stock
+        .iter()
+        .filter(|row| row.quantity > 0)
+        .filter_map(|row| {
+            warehouses
+                .iter()
+                .find(|warehouse| warehouse.id == row.warehouse_id && warehouse.active)
+                .map(|warehouse| (warehouse, row.quantity))
+        })
+        .collect();
+
+    match strategy {
+        AllocationStrategy::Nearest => candidates.sort_by_key(|&(warehouse, on_hand)| {
+            let distance = ship_to.map_or(0, |ship_to| ship_to.distance(warehouse));
+            (distance, warehouse.priority, Reverse(on_hand), warehouse.id)
+        }),
+        AllocationStrategy::MostStock => candidates
+            .sort_by_key(|&(warehouse, on_hand)| (Reverse(on_hand), warehouse.priority, warehouse.id)),
+    }
+
+    let mut remaining = quantity;
+    let mut plan = Vec::new();
+    for (warehouse, on_hand) in candidates {
+        if remaining == 0 {
+            break;
+        }
+        let take = on_hand.min(remaining);
+        plan.push((warehouse.id, take));
+        remaining -= take;
+    }
+    plan
+}
+
+/// The warehouse an adjustment is recorded against: the only one involved, if any
+pub(crate) fn single_warehouse(moves: &[(i32, i32)]) -> Option<i32> {
+    match moves {
+        [(warehouse_id, _)] => Some(*warehouse_id),
+        _ => None,
+    }
+}
+
+/// Warehouse service for stock locations, their stock and order allocations
+pub struct WarehouseService;
+
+impl WarehouseService {
+    fn active_model(input: WarehouseInput) -> ::entity::warehouses::ActiveModel {
+        ::entity::warehouses::ActiveModel {
+            code: Set(input.code.trim().to_uppercase()),
+            name: Set(input.name.trim().to_string()),
+            country: Set(input.country.trim().to_uppercase()),
+            state: Set(input.state.trim().to_uppercase()),
+            priority: Set(input.priority),
+            active: Set(input.active),
+            handling_days: Set(input.handling_days),
+            cutoff_hour: Set(input.cutoff_hour),
+            ..Default::default()
+        }
+    }
+
+    async fn code_taken<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        code: &str,
+        except: Option<i32>,
+    ) -> Result<bool, InventoryError> {
+        let mut query = Warehouses::find()
+            .filter(::entity::warehouses::Column::Mid.eq(mid))
+            .filter(::entity::warehouses::Column::Code.eq(code.trim().to_uppercase()));
+        if let Some(id) = except {
+            query = query.filter(::entity::warehouses::Column::Id.ne(id));
+        }
+
+        Ok(query.one(db).await?.is_some())
+    }
+
+    /// Add a warehouse. Codes are unique per merchant.
+    #[instrument(skip_all, fields(mid = mid, code = input.code.as_str()))]
+    pub async fn create<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        input: WarehouseInput,
+    ) -> Result<Warehouse, InventoryError> {
+        if Self::code_taken(db, mid, &input.code, None).await? {
+            return Err(InventoryError::DuplicateWarehouse(input.code.trim().to_uppercase()));
+        }
+
+        let mut warehouse = Self::active_model(input);
+        warehouse.mid = Set(mid);
+        warehouse.created_gmt = Set(Timestamp::now());
+        Ok(warehouse.insert(db).await?)
+    }
+
+    /// Change a warehouse's settings. Deactivating it keeps its stock but
+    /// stops orders being allocated from it.
+    #[instrument(skip_all, fields(mid = mid, id = id))]
+    pub async fn update<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        id: i32,
+        input: WarehouseInput,
+    ) -> Result<Warehouse, InventoryError> {
+        let existing = Self::find(db, mid, id).await?;
+        if Self::code_taken(db, mid, &input.code, Some(id)).await? {
+            return Err(InventoryError::DuplicateWarehouse(input.code.trim().to_uppercase()));
+        }
+
+        let mut warehouse = Self::active_model(input);
+        warehouse.id = Unchanged(existing.id);
+        Ok(warehouse.update(db).await?)
+    }
+
+    /// A merchant's warehouse
+    pub async fn find<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        id: i32,
+    ) -> Result<Warehouse, InventoryError> {
+        Warehouses::find_by_id(id)
+            .filter(::entity::warehouses::Column::Mid.eq(mid))
+            .one(db)
+            .await?
+            .ok_or(InventoryError::WarehouseNotFound)
+    }
+
+    /// A merchant's warehouses by priority
+    pub async fn list(
+        db: &DatabaseConnection,
+        mid: i32,
+    ) -> Result<Vec<Warehouse>, InventoryError> {
+        let warehouses = Warehouses::find()
+            .filter(::entity::warehouses::Column::Mid.eq(mid))
+            .order_by_asc(::entity::warehouses::Column::Priority)
+            .order_by_asc(::entity::warehouses::Column::Id)
+            .all(db)
+            .await?;
+
+        Ok(warehouses)
+    }
+
+    /// Stock held by one warehouse, or by every warehouse for one SKU
+    pub async fn stock(
+        db: &DatabaseConnection,
+        mid: i32,
+        warehouse_id: Option<i32>,
+        sku: Option<&str>,
+    ) -> Result<Vec<WarehouseStock>, InventoryError> {
+        let mut query = WarehouseStocks::find().filter(::entity::warehouse_stock::Column::Mid.eq(mid));
+        if let Some(warehouse_id) = warehouse_id {
+            query = query.filter(::entity::warehouse_stock::Column::WarehouseId.eq(warehouse_id));
+        }
+        if let Some(sku) = sku {
+            query = query.filter(::entity::warehouse_stock::Column::Sku.eq(sku));
+        }
+
+        let stock = query
+            .order_by_asc(::entity::warehouse_stock::Column::WarehouseId)
+            .order_by_asc(::entity::warehouse_stock::Column::Sku)
+            .all(db)
+            .await?;
+        Ok(stock)
+    }
+
+    /// Where a placed order's units ship from
+    pub async fn allocations(
+        db: &DatabaseConnection,
+        mid: i32,
+        order_id: i32,
+    ) -> Result<Vec<OrderAllocation>, InventoryError> {
+        let allocations = OrderAllocations::find()
+            .filter(::entity::order_allocations::Column::Mid.eq(mid))
+            .filter(::entity::order_allocations::Column::OrderId.eq(order_id))
+            .order_by_asc(::entity::order_allocations::Column::Id)
+            .all(db)
+            .await?;
+
+        Ok(allocations)
+    }
+
+    /// The stock row of a SKU in a warehouse, created empty if missing
+    pub(crate) async fn stock_row<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        warehouse_id: i32,
+        sku: &str,
+    ) -> Result<WarehouseStock, InventoryError> {
+        let existing = WarehouseStocks::find()
+            .filter(::entity::warehouse_stock::Column::WarehouseId.eq(warehouse_id))
+            .filter(::entity::warehouse_stock::Column::Sku.eq(sku))
+            .one(db)
+            .await?;
+        if let Some(row) = existing {
+            return Ok(row);
+        }
+
+        let row = ::entity::warehouse_stock::ActiveModel {
+            mid: Set(mid),
+            warehouse_id: Set(warehouse_id),
+            sku: Set(sku.to_string()),
+            quantity: Set(0),
+            in_transit: Set(0),
+            ..Default::default()
+        }
+        .insert(db)
+        .await?;
+        Ok(row)
+    }
+
+    /// Move a warehouse's count of a SKU by `delta`, which may not take it
+    /// below zero
+    pub(crate) async fn move_stock<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        warehouse_id: i32,
+        sku: &str,
+        delta: i32,
+    ) -> Result<(), InventoryError> {
+        let row = Self::stock_row(db, mid, warehouse_id, sku).await?;
+        let result = WarehouseStocks::update_many()
+            .col_expr(
+                ::entity::warehouse_stock::Column::Quantity,
+                Expr::col(::entity::warehouse_stock::Column::Quantity).add(delta),
+            )
+            .filter(::entity::warehouse_stock::Column::Id.eq(row.id))
+            .filter(::entity::warehouse_stock::Column::Quantity.gte(-delta))
+            .exec(db)
+            .await?;
+        if result.rows_affected == 0 {
+            return Err(InventoryError::Insufficient {
+                sku: sku.to_string(),
+                requested: -delta,
+                available: row.quantity,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Take an order's units of a SKU from the merchant's active
+    /// warehouses, returning `(warehouse_id, quantity)` for each one used
+    pub(crate) async fn allocate<C: ConnectionTrait>(
+        db: &C,
+        warehouses: &[Warehouse],
+        mid: i32,
+        order_id: i32,
+        sku: &str,
+        quantity: i32,
+        ship_to: Option<&ShipTo>,
+    ) -> Result<Vec<(i32, i32)>, InventoryError> {
+        if warehouses.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let stock = WarehouseStocks::find()
+            .filter(::entity::warehouse_stock::Column::Mid.eq(mid))
+            .filter(::entity::warehouse_stock::Column::Sku.eq(sku))
+            .filter(::entity::warehouse_stock::Column::Quantity.gt(0))
+            .all(db)
+            .await?;
+
+        let mut allocated = Vec::new();
+        for (warehouse_id, take) in allocation_plan(strategy(), warehouses, &stock, ship_to, quantity) {
+            // Another order may have emptied the warehouse since it was read
+            match Self::move_stock(db, mid, warehouse_id, sku, -take).await {
+                Err(InventoryError::Insufficient { .. }) => continue,
+                result => result?,
+            }
+            ::entity::order_allocations::ActiveModel {
+                mid: Set(mid),
+                order_id: Set(order_id),
+                warehouse_id: Set(warehouse_id),
+                sku: Set(sku.to_string()),
+                quantity: Set(take),
+                ..Default::default()
+            }
+            .insert(db)
+            .await?;
+            allocated.push((warehouse_id, take));
+        }
+
+        let short = quantity - allocated.iter().map(|(_, take)| take).sum::<i32>();
+        if short > 0 {
+            warn!(mid, order_id, sku, short, "order units not held by any warehouse");
+        }
+        Ok(allocated)
+    }
+
+    /// Put up to `quantity` of an order's units of a SKU back into the
+    /// warehouses they were allocated from, returning `(warehouse_id,
+    /// quantity)` for each one credited
+    pub(crate) async fn deallocate<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        order_id: i32,
+        sku: &str,
+        quantity: i32,
+    ) -> Result<Vec<(i32, i32)>, InventoryError> {
+        let allocations = OrderAllocations::find()
+            .filter(::entity::order_allocations::Column::Mid.eq(mid))
+            .filter(::entity::order_allocations::Column::OrderId.eq(order_id))
+            .filter(::entity::order_allocations::Column::Sku.eq(sku))
+            .filter(::entity::order_allocations::Column::Quantity.gt(0))
+            .order_by_asc(::entity::order_allocations::Column::Id)
+            .all(db)
+            .await?;
+
+        let mut remaining = quantity;
+        let mut credited = Vec::new();
+        for allocation in allocations {
+            if remaining == 0 {
+                break;
+            }
+            let back = allocation.quantity.min(remaining);
+            Self::move_stock(db, mid, allocation.warehouse_id, sku, back).await?;
+
+            let (warehouse_id, left) = (allocation.warehouse_id, allocation.quantity - back);
+            let mut active: ::entity::order_allocations::ActiveModel = allocation.into();
+            active.quantity = Set(left);
+            active.update(db).await?;
+
+            credited.push((warehouse_id, back));
+            remaining -= back;
+        }
+
+        Ok(credited)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn warehouse(id: i32, country: &str, state: &str, priority: i32) -> Warehouse {
+        Warehouse {
+            id,
+            mid: 1,
+            code: format!("W{}", id),
+            name: format!("Warehouse {}", id),
+            country: country.to_string(),
+            state: state.to_string(),
+            priority,
+            active: true,
+            handling_days: 1,
+            cutoff_hour: 14,
+            created_gmt: Timestamp::EPOCH,
+        }
+    }
+
+    fn stock(warehouse_id: i32, quantity: i32) -> WarehouseStock {
+        WarehouseStock {
+            id: warehouse_id,
+            mid: 1,
+            warehouse_id,
+            sku: "SKU001".to_string(),
+            quantity,
+            in_transit: 0,
+        }
+    }
+
+    #[test]
+    fn test_nearest_prefers_state_then_country() {
+        let warehouses = vec![
+            warehouse(1, "DE", "", 0),
+            warehouse(2, "US", "NY", 0),
+            warehouse(3, "US", "CA", 0),
+        ];
+        let rows = vec![stock(1, 50), stock(2, 2), stock(3, 2)];
+        let ship_to = ShipTo::new("us", "ca");
+
+        let plan = allocation_plan(AllocationStrategy::Nearest, &warehouses, &rows, Some(&ship_to), 5);
+        assert_eq!(plan, vec![(3, 2), (2, 2), (1, 1)]);
+    }
+
+    #[test]
+    fn test_most_stock_first_and_inactive_skipped() {
+        let mut warehouses = vec![warehouse(1, "US", "CA", 0), warehouse(2, "US", "NY", 1), warehouse(3, "US", "TX", 2)];
+        warehouses[2].active = false;
+        let rows = vec![stock(1, 3), stock(2, 8), stock(3, 20)];
+
+        let plan = allocation_plan(AllocationStrategy::MostStock, &warehouses, &rows, None, 10);
+        assert_eq!(plan, vec![(2, 8), (1, 2)]);
+
+        // Not enough stock anywhere: the shortfall is left out
+        let plan = allocation_plan(AllocationStrategy::MostStock, &warehouses, &rows, None, 30);
+        assert_eq!(plan.iter().map(|(_, take)| take).sum::<i32>(), 11);
+    }
+
+    #[test]
+    fn test_single_warehouse() {
+        assert_eq!(single_warehouse(&[(4, 2)]), Some(4));
+        assert_eq!(single_warehouse(&[(4, 1), (5, 1)]), None);
+        assert_eq!(single_warehouse(&[]), None);
+    }
+}
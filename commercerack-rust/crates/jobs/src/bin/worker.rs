@@ -0,0 +1,129 @@
+//! Background job worker
+//!
+//! Connects to `DATABASE_URL` and runs queued jobs until interrupted. Run
+//! as many as the queue needs; they coordinate through row locks.
+
+use anyhow::Context;
+use commercerack_config::{AppConfig, ArchiveConfig, CONFIG_FILE_VAR, DEFAULT_CONFIG_FILE};
+use commercerack_jobs::channels::{self, PushChannelInventory, ScheduleChannelSyncs, ScheduleInventoryPushes, SyncChannel};
+use commercerack_jobs::inventory::{self, CheckLowStock};
+use commercerack_jobs::orders::{self, ArchiveOrders, FreezeOrders};
+use commercerack_jobs::privacy::ProcessDataRequest;
+use commercerack_jobs::products::{self, ActivateProducts, RefreshRecommendations, ScheduleRecommendations};
+use commercerack_jobs::reports::{self, GenerateReport};
+use commercerack_jobs::segments::{self, RefreshSegments, ScheduleSegmentRefreshes};
+use commercerack_jobs::Worker;
+use commercerack_order::cold_storage::{self, S3Settings, S3Store};
+use sea_orm::Database;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::info;
+use tracing_subscriber::EnvFilter;
+
+/// How often the queue is polled for due jobs
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often ended report periods are looked for
+const REPORT_SCHEDULE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How often SKUs are checked against their reorder points
+const LOW_STOCK_CHECK_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// How often done orders are moved to the archive pool
+const ORDER_ARCHIVE_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// How often orders long in the archive pool are moved to cold storage
+const COLD_STORAGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How often scheduled drafts are checked for activation
+const PRODUCT_ACTIVATION_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Hour of the night, UTC, when recommendations are worked out again
+const RECOMMENDATION_HOUR: u32 = 3;
+
+/// Hour of the night, UTC, when customer metrics and segments are worked
+/// out again
+const SEGMENT_HOUR: u32 = 2;
+
+/// How often marketplace channels are synced
+const CHANNEL_SYNC_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// How often stock changes are pushed to marketplace channels
+const CHANNEL_INVENTORY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The `archive` section of the API's configuration, from the same file
+/// and variables
+fn archive_config() -> anyhow::Result<ArchiveConfig> {
+    let file = std::env::var(CONFIG_FILE_VAR).unwrap_or_else(|_| DEFAULT_CONFIG_FILE.to_string());
+    AppConfig::figment(&file).focus("archive").extract().context("reading the archive configuration")
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    dotenvy::dotenv().ok();
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")))
+        .init();
+
+    let url = std::env::var("DATABASE_URL").context("DATABASE_URL must be set")?;
+    let db = Arc::new(Database::connect(&url).await.context("connecting to the database")?);
+
+    // Job types are registered here as features add them
+    let worker = Arc::new(
+        Worker::new(db.clone())
+            .register::<GenerateReport>()
+            .register::<ProcessDataRequest>()
+            .register::<CheckLowStock>()
+            .register::<ScheduleChannelSyncs>()
+            .register::<SyncChannel>()
+            .register::<ScheduleInventoryPushes>()
+            .register::<PushChannelInventory>()
+            .register::<ArchiveOrders>()
+            .register::<FreezeOrders>()
+            .register::<ActivateProducts>()
+            .register::<ScheduleRecommendations>()
+            .register::<RefreshRecommendations>()
+            .register::<ScheduleSegmentRefreshes>()
+            .register::<RefreshSegments>(),
+    );
+    let handle = worker.spawn(POLL_INTERVAL);
+    let scheduler = reports::spawn_scheduler(db.clone(), REPORT_SCHEDULE_INTERVAL);
+    let low_stock = inventory::spawn_scheduler(db.clone(), LOW_STOCK_CHECK_INTERVAL);
+    let order_archive = orders::spawn_scheduler(db.clone(), ORDER_ARCHIVE_INTERVAL);
+    let archive = archive_config()?;
+    let cold_storage = match archive.bucket {
+        Some(bucket) => {
+            cold_storage::install(Arc::new(S3Store::new(S3Settings {
+                endpoint: archive.endpoint,
+                region: archive.region,
+                bucket,
+                access_key_id: archive.access_key_id.context("archive.bucket needs archive.access_key_id")?,
+                secret_access_key: archive.secret_access_key.context("archive.bucket needs archive.secret_access_key")?,
+            })));
+            Some(orders::spawn_cold_storage_scheduler(db.clone(), COLD_STORAGE_INTERVAL, archive.cold_after_days))
+        }
+        None => None,
+    };
+    let product_activation = products::spawn_scheduler(db.clone(), PRODUCT_ACTIVATION_INTERVAL);
+    let recommendations = products::spawn_recommendation_scheduler(db.clone(), RECOMMENDATION_HOUR);
+    let segment_refreshes = segments::spawn_scheduler(db.clone(), SEGMENT_HOUR);
+    let channel_syncs = channels::spawn_scheduler(db.clone(), CHANNEL_SYNC_INTERVAL);
+    let channel_inventory = channels::spawn_inventory_scheduler(db, CHANNEL_INVENTORY_INTERVAL);
+    info!("⚙️ Job worker started");
+
+    tokio::signal::ctrl_c().await?;
+    handle.abort();
+    scheduler.abort();
+    low_stock.abort();
+    order_archive.abort();
+    if let Some(cold_storage) = cold_storage {
+        cold_storage.abort();
+    }
+    product_activation.abort();
+    recommendations.abort();
+    segment_refreshes.abort();
+    channel_syncs.abort();
+    channel_inventory.abort();
+    info!("Job worker stopped");
+    Ok(())
+}
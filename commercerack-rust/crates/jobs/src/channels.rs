@@ -0,0 +1,182 @@
+//! Marketplace channel syncs
+//!
+//! The scheduler queues a [`ScheduleChannelSyncs`] every interval, which
+//! queues a [`SyncChannel`] for each enabled channel. Channels are synced
+//! and retried independently, so one marketplace being down holds up no
+//! other. Stock changes are pushed more often by [`PushChannelInventory`],
+//! queued the same way by [`ScheduleInventoryPushes`].
+//!
+//! A marketplace that rate limits us is left alone until the time it
+//! asked for; that is not a failure and is not retried.
+
+use async_trait::async_trait;
+use commercerack_channels::inventory::sync_inventory;
+use commercerack_channels::sync::sync_channel;
+use commercerack_channels::{connector, throttled, ChannelError, ChannelService};
+use commercerack_core::Timestamp;
+use ::entity::prelude::Channel;
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::{Job, JobContext, JobQueue};
+
+/// Pull a channel's new orders and push it tracking and stock
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SyncChannel {
+    pub mid: i32,
+    pub channel_id: i32,
+}
+
+#[async_trait]
+impl Job for SyncChannel {
+    const KIND: &'static str = "channels.sync";
+
+    async fn run(&self, ctx: &JobContext) -> anyhow::Result<()> {
+        let db = ctx.db.as_ref();
+        let now = Timestamp::now();
+        let Some(channel) = syncable(db, self.mid, self.channel_id, now).await? else {
+            return Ok(());
+        };
+
+        let result = match connector(&channel) {
+            Ok(connector) => sync_channel(db, &channel, connector.as_ref(), now).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(report) => {
+                if report.orders_imported > 0 {
+                    info!("🛒 {} orders pulled from {} channel {}", report.orders_imported, channel.marketplace, channel.id);
+                }
+                Ok(())
+            }
+            Err(e) => failed(db, &channel, e, now).await,
+        }
+    }
+}
+
+/// Push the stock that moved since a channel's last push
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PushChannelInventory {
+    pub mid: i32,
+    pub channel_id: i32,
+}
+
+#[async_trait]
+impl Job for PushChannelInventory {
+    const KIND: &'static str = "channels.push_inventory";
+
+    /// The next scheduled push sends whatever this one did not
+    const MAX_ATTEMPTS: i32 = 1;
+
+    async fn run(&self, ctx: &JobContext) -> anyhow::Result<()> {
+        let db = ctx.db.as_ref();
+        let now = Timestamp::now();
+        let Some(channel) = syncable(db, self.mid, self.channel_id, now).await? else {
+            return Ok(());
+        };
+
+        let result = match connector(&channel) {
+            Ok(connector) => sync_inventory(db, &channel, connector.as_ref(), now).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(_) => Ok(()),
+            Err(e) => failed(db, &channel, e, now).await,
+        }
+    }
+}
+
+/// The channel to sync, unless it was removed or paused since the job
+/// was queued or its marketplace asked us to back off
+async fn syncable(db: &DatabaseConnection, mid: i32, id: i32, now: Timestamp) -> anyhow::Result<Option<Channel>> {
+    Ok(ChannelService::find(db, mid, id)
+        .await?
+        .filter(|channel| channel.enabled && !throttled(channel, now)))
+}
+
+/// Back off from a rate limiting marketplace, or record why a sync failed
+/// and fail the job
+async fn failed(db: &DatabaseConnection, channel: &Channel, e: ChannelError, now: Timestamp) -> anyhow::Result<()> {
+    if let ChannelError::RateLimited { retry_after_secs } = e {
+        warn!("{} channel {} is rate limited for {}s", channel.marketplace, channel.id, retry_after_secs);
+        ChannelService::throttle(db, channel, now + chrono::Duration::seconds(retry_after_secs)).await?;
+        return Ok(());
+    }
+    ChannelService::record_failure(db, channel, &e.to_string()).await?;
+    Err(e.into())
+}
+
+/// Queue a [`SyncChannel`] for every enabled channel
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ScheduleChannelSyncs {}
+
+#[async_trait]
+impl Job for ScheduleChannelSyncs {
+    const KIND: &'static str = "channels.schedule";
+
+    /// The next scheduled run does the same work
+    const MAX_ATTEMPTS: i32 = 1;
+
+    async fn run(&self, ctx: &JobContext) -> anyhow::Result<()> {
+        let now = Timestamp::now();
+        for channel in ChannelService::enabled(ctx.db.as_ref()).await? {
+            if !throttled(&channel, now) {
+                JobQueue::enqueue(ctx.db.as_ref(), &SyncChannel { mid: channel.mid, channel_id: channel.id }).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Queue a [`PushChannelInventory`] for every enabled channel
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ScheduleInventoryPushes {}
+
+#[async_trait]
+impl Job for ScheduleInventoryPushes {
+    const KIND: &'static str = "channels.schedule_inventory";
+
+    /// The next scheduled run does the same work
+    const MAX_ATTEMPTS: i32 = 1;
+
+    async fn run(&self, ctx: &JobContext) -> anyhow::Result<()> {
+        let now = Timestamp::now();
+        for channel in ChannelService::enabled(ctx.db.as_ref()).await? {
+            if !throttled(&channel, now) {
+                let push = PushChannelInventory { mid: channel.mid, channel_id: channel.id };
+                JobQueue::enqueue(ctx.db.as_ref(), &push).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Queue a [`ScheduleChannelSyncs`] on a fixed interval until the task is aborted
+pub fn spawn_scheduler(db: Arc<DatabaseConnection>, interval: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = JobQueue::enqueue(db.as_ref(), &ScheduleChannelSyncs::default()).await {
+                warn!("Channel sync scheduling failed: {}", e);
+            }
+        }
+    })
+}
+
+/// Queue a [`ScheduleInventoryPushes`] on a fixed interval until the task is aborted
+pub fn spawn_inventory_scheduler(db: Arc<DatabaseConnection>, interval: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = JobQueue::enqueue(db.as_ref(), &ScheduleInventoryPushes::default()).await {
+                warn!("Channel inventory push scheduling failed: {}", e);
+            }
+        }
+    })
+}
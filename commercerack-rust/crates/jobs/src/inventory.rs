@@ -0,0 +1,49 @@
+//! Low stock checks
+//!
+//! The scheduler queues a [`CheckLowStock`] every interval; whichever
+//! worker picks it up scans all merchants' SKUs. The scan only alerts on
+//! SKUs not yet alerted, so overlapping checks are harmless.
+
+use async_trait::async_trait;
+use commercerack_inventory::low_stock::LowStockService;
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::{Job, JobContext, JobQueue};
+
+/// Raise `inventory.low_stock` events for SKUs that have run low
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CheckLowStock {}
+
+#[async_trait]
+impl Job for CheckLowStock {
+    const KIND: &'static str = "inventory.low_stock";
+
+    /// The next scheduled check does the same work
+    const MAX_ATTEMPTS: i32 = 1;
+
+    async fn run(&self, ctx: &JobContext) -> anyhow::Result<()> {
+        let alerted = LowStockService::scan(ctx.db.as_ref()).await?;
+        if alerted > 0 {
+            info!("📉 {} SKUs ran low on stock", alerted);
+        }
+        Ok(())
+    }
+}
+
+/// Queue a [`CheckLowStock`] on a fixed interval until the task is aborted
+pub fn spawn_scheduler(db: Arc<DatabaseConnection>, interval: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = JobQueue::enqueue(db.as_ref(), &CheckLowStock::default()).await {
+                warn!("Low stock check scheduling failed: {}", e);
+            }
+        }
+    })
+}
@@ -0,0 +1,191 @@
+//! Background jobs
+//!
+//! Work that shouldn't run inside a request (email, exports, retries,
+//! cleanup) is queued as a typed [`Job`] in the `jobs` table and run by a
+//! [`Worker`]. Workers claim due rows with `FOR UPDATE SKIP LOCKED`, so any
+//! number of them can share one queue. A failed job is retried with
+//! exponential backoff until it reaches its attempt limit.
+
+use async_trait::async_trait;
+use commercerack_core::Timestamp;
+use sea_orm::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::sync::Arc;
+use thiserror::Error;
+use ::entity::prelude::*;
+
+pub mod channels;
+pub mod inventory;
+pub mod orders;
+pub mod privacy;
+pub mod products;
+pub mod reports;
+pub mod segments;
+pub mod worker;
+
+pub use worker::Worker;
+
+/// Job states stored in `jobs.status`
+pub const STATUS_PENDING: &str = "pending";
+pub const STATUS_RUNNING: &str = "running";
+pub const STATUS_DONE: &str = "done";
+pub const STATUS_FAILED: &str = "failed";
+
+/// Attempts before a job is given up on, unless the job type says otherwise
+pub const DEFAULT_MAX_ATTEMPTS: i32 = 5;
+
+/// Delay before the first retry; doubles on every further failure
+const BASE_RETRY_DELAY_SECS: i64 = 30;
+
+/// Longest wait between two attempts
+const MAX_RETRY_DELAY_SECS: i64 = 6 * 60 * 60;
+
+#[derive(Error, Debug)]
+pub enum JobError {
+    #[error("Job payload could not be encoded: {0}")]
+    Payload(#[from] serde_json::Error),
+
+    #[error("Database error: {0}")]
+    Db(#[from] DbErr),
+}
+
+pub type Result<T> = std::result::Result<T, JobError>;
+
+/// What a running job can reach
+#[derive(Clone)]
+pub struct JobContext {
+    pub db: Arc<DatabaseConnection>,
+}
+
+/// A unit of background work. The value itself is the payload: it is
+/// stored as JSON when queued and decoded again by the worker.
+#[async_trait]
+pub trait Job: Serialize + DeserializeOwned + Send + Sync + 'static {
+    /// Name stored in `jobs.kind`. Must stay stable while jobs of this
+    /// type may still be queued.
+    const KIND: &'static str;
+
+    /// Attempts before the job is marked failed
+    const MAX_ATTEMPTS: i32 = DEFAULT_MAX_ATTEMPTS;
+
+    /// Do the work. An error schedules a retry; a job may run more than
+    /// once, so it must be safe to repeat.
+    async fn run(&self, ctx: &JobContext) -> anyhow::Result<()>;
+}
+
+/// Delay before the next attempt after `attempts` failures
+pub fn retry_delay(attempts: i32) -> i64 {
+    let exponent = attempts.saturating_sub(1).clamp(0, 20) as u32;
+    (BASE_RETRY_DELAY_SECS << exponent).min(MAX_RETRY_DELAY_SECS)
+}
+
+/// Time from `now` until the next `hour` o'clock UTC, for schedulers
+/// that run once a night
+pub fn until_hour(now: Timestamp, hour: u32) -> std::time::Duration {
+    let today = now.to_datetime().date_naive().and_hms_opt(hour, 0, 0).expect("hour is below 24").and_utc();
+    let next = if Timestamp::from(today) > now { today } else { today + chrono::Duration::days(1) };
+    (Timestamp::from(next) - now).to_std().unwrap_or_default()
+}
+
+pub struct JobQueue;
+
+impl JobQueue {
+    /// Queue `job` to run as soon as a worker is free. Pass a transaction
+    /// to queue it only if that transaction commits.
+    pub async fn enqueue<C: ConnectionTrait, J: Job>(db: &C, job: &J) -> Result<QueuedJob> {
+        Self::schedule(db, job, Timestamp::now()).await
+    }
+
+    /// Queue `job` to run no earlier than `run_at_gmt`
+    pub async fn schedule<C: ConnectionTrait, J: Job>(db: &C, job: &J, run_at_gmt: Timestamp) -> Result<QueuedJob> {
+        let row = ::entity::jobs::ActiveModel {
+            kind: Set(J::KIND.to_string()),
+            payload: Set(serde_json::to_string(job)?),
+            status: Set(STATUS_PENDING.to_string()),
+            attempts: Set(0),
+            max_attempts: Set(J::MAX_ATTEMPTS),
+            run_at_gmt: Set(run_at_gmt),
+            locked_gmt: Set(None),
+            last_error: Set(None),
+            created_gmt: Set(Timestamp::now()),
+            finished_gmt: Set(None),
+            ..Default::default()
+        };
+        Ok(row.insert(db).await?)
+    }
+
+    /// Look up a queued job, e.g. to report on an export's progress
+    pub async fn get<C: ConnectionTrait>(db: &C, id: i32) -> Result<Option<QueuedJob>> {
+        Ok(Jobs::find_by_id(id).one(db).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Serialize, Deserialize)]
+    struct Noop {
+        order_id: i32,
+    }
+
+    #[async_trait]
+    impl Job for Noop {
+        const KIND: &'static str = "noop";
+        const MAX_ATTEMPTS: i32 = 3;
+
+        async fn run(&self, _ctx: &JobContext) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_until_hour_waits_for_the_next_one() {
+        // 2023-11-14 22:13:20 UTC
+        let now = Timestamp::from_unix(1_700_000_000);
+        assert_eq!(until_hour(now, 3), std::time::Duration::from_secs(4 * 3600 + 46 * 60 + 40));
+        assert_eq!(until_hour(now, 23), std::time::Duration::from_secs(46 * 60 + 40));
+        assert_eq!(until_hour(Timestamp::from_unix(1_699_930_800), 3), std::time::Duration::from_secs(24 * 3600));
+    }
+
+    fn queued(id: i32) -> QueuedJob {
+        QueuedJob {
+            id,
+            kind: "noop".to_string(),
+            payload: r#"{"order_id":7}"#.to_string(),
+            status: STATUS_PENDING.to_string(),
+            attempts: 0,
+            max_attempts: 3,
+            run_at_gmt: Timestamp::from_unix(1_800_000_000),
+            locked_gmt: None,
+            last_error: None,
+            created_gmt: Timestamp::from_unix(1_700_000_000),
+            finished_gmt: None,
+        }
+    }
+
+    #[test]
+    fn test_retry_delay_backs_off_exponentially() {
+        assert_eq!(retry_delay(1), 30);
+        assert_eq!(retry_delay(2), 60);
+        assert_eq!(retry_delay(3), 120);
+        assert_eq!(retry_delay(30), MAX_RETRY_DELAY_SECS);
+    }
+
+    #[tokio::test]
+    async fn test_schedule_stores_typed_payload() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![queued(1)]])
+            .into_connection();
+
+        let job = JobQueue::schedule(&db, &Noop { order_id: 7 }, Timestamp::from_unix(1_800_000_000)).await.unwrap();
+        assert_eq!(job.id, 1);
+
+        let log = db.into_transaction_log();
+        let sql = log[0].statements()[0].to_string();
+        assert!(sql.contains(r#"INSERT INTO "jobs""#), "{}", sql);
+        assert!(sql.contains(r#"'noop', E'{\"order_id\":7}', 'pending', 0, 3, 1800000000"#), "{}", sql);
+    }
+}
@@ -0,0 +1,102 @@
+//! Order archival
+//!
+//! The scheduler queues an [`ArchiveOrders`] every interval; whichever
+//! worker picks it up moves every merchant's long done orders into the
+//! archive pool. Archived orders are skipped, so overlapping runs are
+//! harmless. Where cold storage is configured, a second scheduler queues
+//! [`FreezeOrders`] to move orders long in that pool out of the database.
+
+use async_trait::async_trait;
+use chrono::Utc;
+use commercerack_order::cold_storage::{self, ColdStorageService};
+use commercerack_order::pools::PoolService;
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::{Job, JobContext, JobQueue};
+
+/// Orders moved to cold storage per batch; a run keeps going while
+/// batches come back full
+const FREEZE_BATCH: u64 = 500;
+
+/// Move orders done for longer than their merchant keeps them to ARCHIVE
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ArchiveOrders {}
+
+#[async_trait]
+impl Job for ArchiveOrders {
+    const KIND: &'static str = "orders.archive";
+
+    /// The next scheduled run does the same work
+    const MAX_ATTEMPTS: i32 = 1;
+
+    async fn run(&self, ctx: &JobContext) -> anyhow::Result<()> {
+        let archived = PoolService::archive_due(ctx.db.as_ref(), Utc::now()).await?;
+        if archived > 0 {
+            info!("🗄️ Archived {} orders", archived);
+        }
+        Ok(())
+    }
+}
+
+/// Move orders that have been in the ARCHIVE pool for more than
+/// `after_days` to the installed cold storage
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FreezeOrders {
+    pub after_days: i64,
+}
+
+#[async_trait]
+impl Job for FreezeOrders {
+    const KIND: &'static str = "orders.cold_storage";
+
+    /// The next scheduled run does the same work
+    const MAX_ATTEMPTS: i32 = 1;
+
+    async fn run(&self, ctx: &JobContext) -> anyhow::Result<()> {
+        let store = cold_storage::store()?;
+        let after = chrono::Duration::days(self.after_days);
+        let mut frozen = 0;
+        loop {
+            let batch = ColdStorageService::freeze_due(ctx.db.as_ref(), store.as_ref(), Utc::now(), after, FREEZE_BATCH).await?;
+            frozen += batch;
+            if batch < FREEZE_BATCH {
+                break;
+            }
+        }
+        if frozen > 0 {
+            info!("🧊 Moved {} orders to cold storage", frozen);
+        }
+        Ok(())
+    }
+}
+
+/// Queue an [`ArchiveOrders`] on a fixed interval until the task is aborted
+pub fn spawn_scheduler(db: Arc<DatabaseConnection>, interval: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = JobQueue::enqueue(db.as_ref(), &ArchiveOrders::default()).await {
+                warn!("Order archival scheduling failed: {}", e);
+            }
+        }
+    })
+}
+
+/// Queue a [`FreezeOrders`] on a fixed interval until the task is aborted
+pub fn spawn_cold_storage_scheduler(db: Arc<DatabaseConnection>, interval: Duration, after_days: i64) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = JobQueue::enqueue(db.as_ref(), &FreezeOrders { after_days }).await {
+                warn!("Cold storage scheduling failed: {}", e);
+            }
+        }
+    })
+}
@@ -0,0 +1,27 @@
+//! Customer data requests
+//!
+//! Exports and erasures touch every table holding a customer's data, so
+//! the API records the request and queues a [`ProcessDataRequest`] in one
+//! transaction and the client polls the request for its status.
+
+use async_trait::async_trait;
+use commercerack_customer::privacy::PrivacyService;
+use serde::{Deserialize, Serialize};
+
+use crate::{Job, JobContext};
+
+/// Carry out one export or erasure
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProcessDataRequest {
+    pub request_id: i32,
+}
+
+#[async_trait]
+impl Job for ProcessDataRequest {
+    const KIND: &'static str = "customers.data_request";
+
+    async fn run(&self, ctx: &JobContext) -> anyhow::Result<()> {
+        PrivacyService::process(ctx.db.as_ref(), self.request_id).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,110 @@
+//! Scheduled product activation and nightly recommendations
+//!
+//! The scheduler queues an [`ActivateProducts`] every interval; whichever
+//! worker picks it up activates every merchant's drafts whose
+//! `available_from` has passed. The drafts are locked while they are
+//! activated, so overlapping runs are harmless.
+//!
+//! Once a night the recommendation scheduler queues a
+//! [`ScheduleRecommendations`], which queues a [`RefreshRecommendations`]
+//! for each merchant with recent orders, so merchants are worked out and
+//! retried independently.
+
+use async_trait::async_trait;
+use commercerack_core::Timestamp;
+use commercerack_product::recommendations::RecommendationService;
+use commercerack_product::ProductService;
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+use crate::{until_hour, Job, JobContext, JobQueue};
+
+/// Put scheduled drafts on sale
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ActivateProducts {}
+
+#[async_trait]
+impl Job for ActivateProducts {
+    const KIND: &'static str = "products.activate";
+
+    /// The next scheduled run does the same work
+    const MAX_ATTEMPTS: i32 = 1;
+
+    async fn run(&self, ctx: &JobContext) -> anyhow::Result<()> {
+        let activated = ProductService::activate_due(ctx.db.as_ref(), Timestamp::now()).await?;
+        if activated > 0 {
+            info!("🛍️ Activated {} scheduled products", activated);
+        }
+        Ok(())
+    }
+}
+
+/// Queue an [`ActivateProducts`] on a fixed interval until the task is aborted
+pub fn spawn_scheduler(db: Arc<DatabaseConnection>, interval: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = JobQueue::enqueue(db.as_ref(), &ActivateProducts::default()).await {
+                warn!("Product activation scheduling failed: {}", e);
+            }
+        }
+    })
+}
+
+/// Work out a merchant's recommendations again
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RefreshRecommendations {
+    pub mid: i32,
+}
+
+#[async_trait]
+impl Job for RefreshRecommendations {
+    const KIND: &'static str = "products.refresh_recommendations";
+
+    async fn run(&self, ctx: &JobContext) -> anyhow::Result<()> {
+        let customers = RecommendationService::refresh(ctx.db.as_ref(), self.mid, Timestamp::now()).await?;
+        info!("💡 Refreshed recommendations for {} customers of merchant {}", customers, self.mid);
+        Ok(())
+    }
+}
+
+/// Queue a [`RefreshRecommendations`] for every merchant with recent
+/// orders or stored recommendations
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ScheduleRecommendations {}
+
+#[async_trait]
+impl Job for ScheduleRecommendations {
+    const KIND: &'static str = "products.schedule_recommendations";
+
+    /// The next night's run does the same work
+    const MAX_ATTEMPTS: i32 = 1;
+
+    async fn run(&self, ctx: &JobContext) -> anyhow::Result<()> {
+        for mid in RecommendationService::merchants(ctx.db.as_ref(), Timestamp::now()).await? {
+            JobQueue::enqueue(ctx.db.as_ref(), &RefreshRecommendations { mid }).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Queue a [`ScheduleRecommendations`] every day at `hour` o'clock UTC
+/// until the task is aborted
+pub fn spawn_recommendation_scheduler(db: Arc<DatabaseConnection>, hour: u32) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let first = Instant::now() + until_hour(Timestamp::now(), hour);
+        let mut ticker = tokio::time::interval_at(first, Duration::from_secs(24 * 60 * 60));
+        loop {
+            ticker.tick().await;
+            if let Err(e) = JobQueue::enqueue(db.as_ref(), &ScheduleRecommendations::default()).await {
+                warn!("Recommendation scheduling failed: {}", e);
+            }
+        }
+    })
+}
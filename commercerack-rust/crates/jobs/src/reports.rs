@@ -0,0 +1,60 @@
+//! Sales report generation
+//!
+//! The scheduler creates the reports of periods that have just ended and
+//! queues a [`GenerateReport`] for each, in one transaction, so a report
+//! row always has a job to fill it in.
+
+use async_trait::async_trait;
+use chrono::Utc;
+use commercerack_reports::ReportService;
+use sea_orm::{ConnectionTrait, DatabaseConnection, TransactionTrait};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::{Job, JobContext, JobQueue};
+
+/// Compute the figures and CSV of one report
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GenerateReport {
+    pub report_id: i32,
+}
+
+#[async_trait]
+impl Job for GenerateReport {
+    const KIND: &'static str = "reports.generate";
+
+    async fn run(&self, ctx: &JobContext) -> anyhow::Result<()> {
+        ReportService::generate(ctx.db.as_ref(), self.report_id).await?;
+        Ok(())
+    }
+}
+
+/// Create the reports that are due and queue their generation. Returns
+/// how many were scheduled.
+pub async fn schedule<C: ConnectionTrait + TransactionTrait>(db: &C) -> anyhow::Result<usize> {
+    let txn = db.begin().await?;
+    let reports = ReportService::schedule_due(&txn, Utc::now()).await?;
+    for report in &reports {
+        JobQueue::enqueue(&txn, &GenerateReport { report_id: report.id }).await?;
+    }
+    txn.commit().await?;
+    Ok(reports.len())
+}
+
+/// Run `schedule` on a fixed interval until the task is aborted
+pub fn spawn_scheduler(db: Arc<DatabaseConnection>, interval: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match schedule(db.as_ref()).await {
+                Ok(0) => {}
+                Ok(n) => info!("📊 Scheduled {} sales reports", n),
+                Err(e) => warn!("Report scheduling failed: {:#}", e),
+            }
+        }
+    })
+}
@@ -0,0 +1,71 @@
+//! Nightly customer metrics and segment refresh
+//!
+//! Once a night the scheduler queues a [`ScheduleSegmentRefreshes`], which
+//! queues a [`RefreshSegments`] for each merchant with paid orders or
+//! segments, so merchants are worked out and retried independently.
+//! Creating or changing a segment also queues its merchant's refresh.
+
+use async_trait::async_trait;
+use commercerack_core::Timestamp;
+use commercerack_customer::segments::SegmentService;
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+use crate::{until_hour, Job, JobContext, JobQueue};
+
+/// Work out a merchant's customer metrics and segment members again
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RefreshSegments {
+    pub mid: i32,
+}
+
+#[async_trait]
+impl Job for RefreshSegments {
+    const KIND: &'static str = "customers.refresh_segments";
+
+    async fn run(&self, ctx: &JobContext) -> anyhow::Result<()> {
+        let customers = SegmentService::refresh(ctx.db.as_ref(), self.mid, Timestamp::now()).await?;
+        info!("📊 Refreshed metrics and segments for {} customers of merchant {}", customers, self.mid);
+        Ok(())
+    }
+}
+
+/// Queue a [`RefreshSegments`] for every merchant with paid orders or
+/// segments
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ScheduleSegmentRefreshes {}
+
+#[async_trait]
+impl Job for ScheduleSegmentRefreshes {
+    const KIND: &'static str = "customers.schedule_segments";
+
+    /// The next night's run does the same work
+    const MAX_ATTEMPTS: i32 = 1;
+
+    async fn run(&self, ctx: &JobContext) -> anyhow::Result<()> {
+        for mid in SegmentService::merchants(ctx.db.as_ref()).await? {
+            JobQueue::enqueue(ctx.db.as_ref(), &RefreshSegments { mid }).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Queue a [`ScheduleSegmentRefreshes`] every day at `hour` o'clock UTC
+/// until the task is aborted
+pub fn spawn_scheduler(db: Arc<DatabaseConnection>, hour: u32) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let first = Instant::now() + until_hour(Timestamp::now(), hour);
+        let mut ticker = tokio::time::interval_at(first, Duration::from_secs(24 * 60 * 60));
+        loop {
+            ticker.tick().await;
+            if let Err(e) = JobQueue::enqueue(db.as_ref(), &ScheduleSegmentRefreshes::default()).await {
+                warn!("Segment refresh scheduling failed: {}", e);
+            }
+        }
+    })
+}
@@ -0,0 +1,260 @@
+//! Claiming and running queued jobs
+
+use commercerack_core::Timestamp;
+use sea_orm::sea_query::{Expr, LockBehavior, LockType};
+use sea_orm::*;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+use ::entity::prelude::*;
+
+use crate::{retry_delay, Job, JobContext, STATUS_DONE, STATUS_FAILED, STATUS_PENDING, STATUS_RUNNING};
+
+/// Jobs claimed per pass
+const BATCH_SIZE: u64 = 20;
+
+/// A running job whose worker hasn't reported back for this long is assumed
+/// lost (crashed or killed) and becomes claimable again
+const LOCK_TIMEOUT_SECS: i64 = 15 * 60;
+
+type JobFuture = Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send>>;
+
+/// Decodes a payload and runs it as its job type
+type Runner = Arc<dyn Fn(JobContext, String) -> JobFuture + Send + Sync>;
+
+/// Runs the job types registered with it
+pub struct Worker {
+    ctx: JobContext,
+    runners: HashMap<&'static str, Runner>,
+}
+
+impl Worker {
+    pub fn new(db: Arc<DatabaseConnection>) -> Self {
+        Self {
+            ctx: JobContext { db },
+            runners: HashMap::new(),
+        }
+    }
+
+    /// Run jobs of type `J`. Kinds no worker registers stay queued.
+    pub fn register<J: Job>(mut self) -> Self {
+        let runner: Runner = Arc::new(|ctx, payload| {
+            Box::pin(async move {
+                let job: J = serde_json::from_str(&payload)?;
+                job.run(&ctx).await
+            })
+        });
+        self.runners.insert(J::KIND, runner);
+        self
+    }
+
+    /// Claim due jobs and run each once. Returns how many succeeded.
+    pub async fn run_due(&self) -> Result<usize, DbErr> {
+        let claimed = self.claim().await?;
+
+        let mut succeeded = 0;
+        for job in claimed {
+            let outcome = match self.runners.get(job.kind.as_str()) {
+                // A separate task so a panicking job counts as a failure
+                // instead of taking the worker down
+                Some(runner) => tokio::spawn(runner(self.ctx.clone(), job.payload.clone()))
+                    .await
+                    .unwrap_or_else(|e| Err(anyhow::anyhow!("Job panicked: {}", e))),
+                None => Err(anyhow::anyhow!("No runner for job kind {}", job.kind)),
+            };
+            if outcome.is_ok() {
+                succeeded += 1;
+            }
+            self.finish(job, outcome).await?;
+        }
+
+        Ok(succeeded)
+    }
+
+    /// Lock due jobs of the registered kinds and mark them running, in one
+    /// transaction so two workers never claim the same row
+    async fn claim(&self) -> Result<Vec<QueuedJob>, DbErr> {
+        use ::entity::jobs::Column;
+
+        if self.runners.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let now = Timestamp::now();
+        let txn = self.ctx.db.begin().await?;
+
+        let due = Condition::all()
+            .add(Column::Status.eq(STATUS_PENDING))
+            .add(Column::RunAtGmt.lte(now));
+        let stale = Condition::all()
+            .add(Column::Status.eq(STATUS_RUNNING))
+            .add(Column::LockedGmt.lt(now - chrono::Duration::seconds(LOCK_TIMEOUT_SECS)));
+
+        let mut jobs = Jobs::find()
+            .filter(Column::Kind.is_in(self.runners.keys().copied()))
+            .filter(Condition::any().add(due).add(stale))
+            .order_by_asc(Column::RunAtGmt)
+            .order_by_asc(Column::Id)
+            .limit(BATCH_SIZE)
+            .lock_with_behavior(LockType::Update, LockBehavior::SkipLocked)
+            .all(&txn)
+            .await?;
+
+        if !jobs.is_empty() {
+            Jobs::update_many()
+                .col_expr(Column::Status, Expr::value(STATUS_RUNNING))
+                .col_expr(Column::LockedGmt, Expr::value(now))
+                .col_expr(Column::Attempts, Expr::col(Column::Attempts).add(1))
+                .filter(Column::Id.is_in(jobs.iter().map(|job| job.id)))
+                .exec(&txn)
+                .await?;
+        }
+        txn.commit().await?;
+
+        for job in &mut jobs {
+            job.status = STATUS_RUNNING.to_string();
+            job.locked_gmt = Some(now);
+            job.attempts += 1;
+        }
+        Ok(jobs)
+    }
+
+    /// Record the outcome of one attempt
+    async fn finish(&self, job: QueuedJob, outcome: anyhow::Result<()>) -> Result<(), DbErr> {
+        let now = Timestamp::now();
+        let attempts = job.attempts;
+        let max_attempts = job.max_attempts;
+        let mut active: ::entity::jobs::ActiveModel = job.into();
+        active.locked_gmt = Set(None);
+
+        match outcome {
+            Ok(()) => {
+                active.status = Set(STATUS_DONE.to_string());
+                active.last_error = Set(None);
+                active.finished_gmt = Set(Some(now));
+            }
+            Err(e) => {
+                warn!("Job attempt {} of {} failed: {:#}", attempts, max_attempts, e);
+                active.last_error = Set(Some(format!("{:#}", e)));
+                if attempts >= max_attempts {
+                    active.status = Set(STATUS_FAILED.to_string());
+                    active.finished_gmt = Set(Some(now));
+                } else {
+                    active.status = Set(STATUS_PENDING.to_string());
+                    active.run_at_gmt = Set(now + chrono::Duration::seconds(retry_delay(attempts)));
+                }
+            }
+        }
+        active.update(self.ctx.db.as_ref()).await?;
+        Ok(())
+    }
+
+    /// Run `run_due` on a fixed interval until the task is aborted. The
+    /// first pass happens one interval after start.
+    pub fn spawn(self: Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+            loop {
+                ticker.tick().await;
+                match self.run_due().await {
+                    Ok(0) => {}
+                    Ok(n) => info!("⚙️ Ran {} background jobs", n),
+                    Err(e) => warn!("Job pass failed: {}", e),
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize)]
+    struct Flaky {
+        fail: bool,
+    }
+
+    #[async_trait]
+    impl Job for Flaky {
+        const KIND: &'static str = "flaky";
+
+        async fn run(&self, _ctx: &JobContext) -> anyhow::Result<()> {
+            if self.fail {
+                anyhow::bail!("upstream unavailable");
+            }
+            Ok(())
+        }
+    }
+
+    fn queued(fail: bool, attempts: i32) -> QueuedJob {
+        QueuedJob {
+            id: 4,
+            kind: Flaky::KIND.to_string(),
+            payload: serde_json::to_string(&Flaky { fail }).unwrap(),
+            status: STATUS_PENDING.to_string(),
+            attempts,
+            max_attempts: 3,
+            run_at_gmt: Timestamp::from_unix(1_700_000_000),
+            locked_gmt: None,
+            last_error: None,
+            created_gmt: Timestamp::from_unix(1_700_000_000),
+            finished_gmt: None,
+        }
+    }
+
+    /// Run one pass over a single claimed job; returns the successes and
+    /// the SQL of the claim query, the claim update and the final update
+    async fn run_pass(job: QueuedJob) -> (usize, [String; 3]) {
+        let updated = job.clone();
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![job]])
+            .append_exec_results([MockExecResult { last_insert_id: 0, rows_affected: 1 }])
+            .append_query_results([vec![updated]])
+            .into_connection();
+
+        let worker = Worker::new(Arc::new(db)).register::<Flaky>();
+        let succeeded = worker.run_due().await.unwrap();
+
+        let db = Arc::try_unwrap(worker.ctx.db).ok().unwrap();
+        let log = db.into_transaction_log();
+        let claim = log[0].statements();
+        (succeeded, [claim[1].to_string(), claim[2].to_string(), log[1].statements()[0].to_string()])
+    }
+
+    #[tokio::test]
+    async fn test_claims_registered_kinds_skipping_locked_rows() {
+        let (succeeded, [select, claim, finish]) = run_pass(queued(false, 0)).await;
+        assert_eq!(succeeded, 1);
+        assert!(select.contains(r#""jobs"."kind" IN ('flaky')"#), "{}", select);
+        assert!(select.ends_with("FOR UPDATE SKIP LOCKED"), "{}", select);
+        assert!(claim.contains(r#""status" = 'running'"#), "{}", claim);
+        assert!(claim.contains(r#""attempts" = "attempts" + 1"#), "{}", claim);
+        assert!(finish.contains(r#""status" = 'done'"#), "{}", finish);
+    }
+
+    #[tokio::test]
+    async fn test_failure_is_retried_then_given_up() {
+        let (succeeded, [_, _, finish]) = run_pass(queued(true, 0)).await;
+        assert_eq!(succeeded, 0);
+        assert!(finish.contains(r#""status" = 'pending'"#), "{}", finish);
+        assert!(finish.contains(r#""run_at_gmt" = "#), "{}", finish);
+        assert!(finish.contains("'upstream unavailable'"), "{}", finish);
+
+        // The claim makes this the third and last attempt
+        let (_, [_, _, finish]) = run_pass(queued(true, 2)).await;
+        assert!(finish.contains(r#""status" = 'failed'"#), "{}", finish);
+    }
+
+    #[tokio::test]
+    async fn test_no_registered_kinds_claims_nothing() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+        assert_eq!(Worker::new(Arc::new(db)).run_due().await.unwrap(), 0);
+    }
+}
@@ -0,0 +1,352 @@
+//! Merchants
+//!
+//! A merchant is a store on the platform; its `mid` keys every other
+//! table. Merchants are onboarded by a platform admin, which creates the
+//! merchant with default settings (the order pools it works with and how
+//! its tax is calculated) and its first tax rates in one transaction.
+//! Storefront requests find their merchant by domain through
+//! [`MerchantService::find_store`], which only answers for active stores.
+//! Settings the merchant changes day to day live in [`settings`].
+
+use commercerack_core::Timestamp;
+use commercerack_tax::{TaxError, TaxRateInput, TaxRateService};
+use sea_orm::*;
+use serde_json::json;
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+use tracing::instrument;
+use ::entity::merchants::Column;
+use ::entity::prelude::*;
+
+pub mod settings;
+
+#[derive(Error, Debug)]
+pub enum MerchantError {
+    #[error("Merchant not found")]
+    NotFound,
+
+    #[error("Domain {0} is taken by another store")]
+    DomainTaken(String),
+
+    #[error("Invalid domain {0}")]
+    InvalidDomain(String),
+
+    #[error("Unknown setting {0}")]
+    UnknownSetting(String),
+
+    #[error("Setting {key} {reason}")]
+    InvalidSetting { key: String, reason: String },
+
+    #[error(transparent)]
+    Tax(#[from] TaxError),
+
+    #[error("Database error: {0}")]
+    Db(#[from] DbErr),
+}
+
+/// Whether a merchant trades
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MerchantStatus {
+    #[default]
+    Active,
+    /// Temporarily off; its storefront is not served
+    Suspended,
+    /// Off for good; its data stays for reporting
+    Closed,
+}
+
+impl MerchantStatus {
+    pub const ALL: [MerchantStatus; 3] = [MerchantStatus::Active, MerchantStatus::Suspended, MerchantStatus::Closed];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MerchantStatus::Active => "active",
+            MerchantStatus::Suspended => "suspended",
+            MerchantStatus::Closed => "closed",
+        }
+    }
+}
+
+impl fmt::Display for MerchantStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for MerchantStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|status| status.as_str() == s)
+            .ok_or_else(|| format!("unknown merchant status {}", s))
+    }
+}
+
+/// Order pools a new merchant works with, in workflow order; new orders
+/// land in the first
+pub const DEFAULT_POOLS: [&str; 8] =
+    ["RECENT", "REVIEW", "HOLD", "PENDING", "APPROVED", "PROCESS", "COMPLETED", "ARCHIVE"];
+
+/// Settings a merchant starts with
+pub fn default_settings() -> serde_json::Value {
+    json!({
+        "pools": DEFAULT_POOLS,
+        "tax": { "calculator": "rate_table" },
+    })
+}
+
+/// Lowercase `domain` and check it is a host name, e.g. `shop.example.com`
+pub fn normalize_domain(domain: &str) -> Result<String, MerchantError> {
+    let normalized = domain.trim().trim_end_matches('.').to_lowercase();
+    let valid = !normalized.is_empty()
+        && normalized.len() <= 100
+        && normalized.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        });
+    if valid {
+        Ok(normalized)
+    } else {
+        Err(MerchantError::InvalidDomain(domain.to_string()))
+    }
+}
+
+/// What is needed to onboard a merchant
+#[derive(Debug, Clone)]
+pub struct Onboarding {
+    pub name: String,
+    pub sdomain: String,
+    /// Rates to start the tax table with; may be empty
+    pub tax_rates: Vec<TaxRateInput>,
+}
+
+/// A merchant just onboarded, with what was provisioned for it
+#[derive(Debug, Clone)]
+pub struct Onboarded {
+    pub merchant: Merchant,
+    pub tax_rates: Vec<TaxRate>,
+}
+
+/// Changes to a merchant; `None` leaves a field as it is
+#[derive(Debug, Clone, Default)]
+pub struct MerchantUpdate {
+    pub name: Option<String>,
+    pub sdomain: Option<String>,
+    /// Replaces the settings as a whole
+    pub settings: Option<serde_json::Value>,
+    pub status: Option<MerchantStatus>,
+}
+
+/// Merchant service
+pub struct MerchantService;
+
+impl MerchantService {
+    /// Create a merchant with `settings`; most callers want [`Self::onboard`]
+    #[instrument(skip_all, fields(sdomain = %sdomain))]
+    pub async fn create<C: ConnectionTrait>(
+        db: &C,
+        name: String,
+        sdomain: &str,
+        settings: &serde_json::Value,
+    ) -> Result<Merchant, MerchantError> {
+        let sdomain = normalize_domain(sdomain)?;
+        if Self::find_by_sdomain(db, &sdomain).await?.is_some() {
+            return Err(MerchantError::DomainTaken(sdomain));
+        }
+
+        let now = Timestamp::now();
+        Ok(::entity::merchants::ActiveModel {
+            name: Set(name),
+            sdomain: Set(sdomain),
+            settings: Set(settings.to_string()),
+            status: Set(MerchantStatus::Active.as_str().to_string()),
+            created_gmt: Set(now),
+            modified_gmt: Set(now),
+            ..Default::default()
+        }
+        .insert(db)
+        .await?)
+    }
+
+    /// Create a merchant with the default settings and its first tax rates
+    #[instrument(skip_all, fields(sdomain = %onboarding.sdomain))]
+    pub async fn onboard<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        onboarding: Onboarding,
+    ) -> Result<Onboarded, MerchantError> {
+        let txn = db.begin().await?;
+        let merchant = Self::create(&txn, onboarding.name, &onboarding.sdomain, &default_settings()).await?;
+
+        let mut tax_rates = Vec::with_capacity(onboarding.tax_rates.len());
+        for rate in onboarding.tax_rates {
+            tax_rates.push(TaxRateService::create(&txn, merchant.mid, rate).await?);
+        }
+        txn.commit().await?;
+
+        tracing::info!(mid = merchant.mid, "merchant onboarded");
+        Ok(Onboarded { merchant, tax_rates })
+    }
+
+    pub async fn find<C: ConnectionTrait>(db: &C, mid: i32) -> Result<Option<Merchant>, MerchantError> {
+        Ok(Merchants::find_by_id(mid).one(db).await?)
+    }
+
+    /// The merchant on `sdomain`, whatever its status
+    pub async fn find_by_sdomain<C: ConnectionTrait>(db: &C, sdomain: &str) -> Result<Option<Merchant>, MerchantError> {
+        let Ok(sdomain) = normalize_domain(sdomain) else {
+            return Ok(None);
+        };
+        Ok(Merchants::find().filter(Column::Sdomain.eq(sdomain)).one(db).await?)
+    }
+
+    /// The store to serve on `sdomain`; `None` unless its merchant is active
+    pub async fn find_store<C: ConnectionTrait>(db: &C, sdomain: &str) -> Result<Option<Merchant>, MerchantError> {
+        Ok(Self::find_by_sdomain(db, sdomain)
+            .await?
+            .filter(|merchant| merchant.status == MerchantStatus::Active.as_str()))
+    }
+
+    /// Every merchant, oldest first
+    pub async fn list<C: ConnectionTrait>(db: &C) -> Result<Vec<Merchant>, MerchantError> {
+        Ok(Merchants::find().order_by_asc(Column::Mid).all(db).await?)
+    }
+
+    #[instrument(skip_all, fields(mid = mid))]
+    pub async fn update<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        update: MerchantUpdate,
+    ) -> Result<Merchant, MerchantError> {
+        let merchant = Self::find(db, mid).await?.ok_or(MerchantError::NotFound)?;
+
+        let mut active: ::entity::merchants::ActiveModel = merchant.into();
+        if let Some(name) = update.name {
+            active.name = Set(name);
+        }
+        if let Some(sdomain) = update.sdomain {
+            let sdomain = normalize_domain(&sdomain)?;
+            if Self::find_by_sdomain(db, &sdomain).await?.is_some_and(|other| other.mid != mid) {
+                return Err(MerchantError::DomainTaken(sdomain));
+            }
+            active.sdomain = Set(sdomain);
+        }
+        if let Some(settings) = update.settings {
+            active.settings = Set(settings.to_string());
+        }
+        if let Some(status) = update.status {
+            active.status = Set(status.as_str().to_string());
+        }
+        active.modified_gmt = Set(Timestamp::now());
+        Ok(active.update(db).await?)
+    }
+
+    /// Close a merchant. Nothing is deleted, so its orders stay reportable.
+    pub async fn close<C: ConnectionTrait>(db: &C, mid: i32) -> Result<Merchant, MerchantError> {
+        Self::update(db, mid, MerchantUpdate { status: Some(MerchantStatus::Closed), ..Default::default() }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use commercerack_tax::TaxAddress;
+    use rust_decimal::Decimal;
+
+    fn merchant(mid: i32, sdomain: &str, status: MerchantStatus) -> Merchant {
+        Merchant {
+            mid,
+            name: "Widgets".to_string(),
+            sdomain: sdomain.to_string(),
+            settings: default_settings().to_string(),
+            status: status.as_str().to_string(),
+            created_gmt: Timestamp::from_unix(1_700_000_000),
+            modified_gmt: Timestamp::from_unix(1_700_000_000),
+        }
+    }
+
+    #[test]
+    fn test_normalize_domain() {
+        assert_eq!(normalize_domain(" Shop.Example.COM. ").unwrap(), "shop.example.com");
+        assert_eq!(normalize_domain("localhost").unwrap(), "localhost");
+        for invalid in ["", "shop..example.com", "-shop.example.com", "shop_1.example.com", "https://example.com"] {
+            assert!(matches!(normalize_domain(invalid), Err(MerchantError::InvalidDomain(_))), "{}", invalid);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_onboard_provisions_defaults() {
+        let rate = TaxRate {
+            id: 1,
+            mid: 7,
+            name: "WA state".to_string(),
+            country: "US".to_string(),
+            state: "WA".to_string(),
+            zip: String::new(),
+            rate: Decimal::new(650, 2),
+            zone_id: None,
+            created_gmt: Timestamp::from_unix(1_700_000_000),
+            modified_gmt: Timestamp::from_unix(1_700_000_000),
+        };
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([Vec::<Merchant>::new()])
+            .append_query_results([vec![merchant(7, "shop.example.com", MerchantStatus::Active)]])
+            .append_query_results([vec![rate]])
+            .into_connection();
+
+        let onboarded = MerchantService::onboard(
+            &db,
+            Onboarding {
+                name: "Widgets".to_string(),
+                sdomain: "Shop.Example.com".to_string(),
+                tax_rates: vec![TaxRateInput {
+                    name: "WA state".to_string(),
+                    address: TaxAddress::new("us", "wa", ""),
+                    rate: Decimal::new(650, 2),
+                    zone_id: None,
+                }],
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(onboarded.merchant.mid, 7);
+        assert_eq!(onboarded.tax_rates.len(), 1);
+
+        let log = db.into_transaction_log();
+        assert_eq!(log.len(), 1, "onboarding is one transaction");
+        let inserted = log[0].statements()[2].to_string();
+        assert!(inserted.contains(r#"INSERT INTO "merchants""#), "{}", inserted);
+        assert!(inserted.contains("'shop.example.com'"), "{}", inserted);
+        assert!(inserted.contains(r#"\"pools\":[\"RECENT\""#), "{}", inserted);
+        let taxed = log[0].statements()[3].to_string();
+        assert!(taxed.contains(r#"INSERT INTO "tax_rates""#), "{}", taxed);
+        assert!(taxed.contains("7"), "{}", taxed);
+    }
+
+    #[tokio::test]
+    async fn test_domain_is_unique() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![merchant(3, "shop.example.com", MerchantStatus::Active)]])
+            .into_connection();
+        let result = MerchantService::create(&db, "Other".to_string(), "shop.example.com", &default_settings()).await;
+        assert!(matches!(result, Err(MerchantError::DomainTaken(domain)) if domain == "shop.example.com"));
+    }
+
+    #[tokio::test]
+    async fn test_find_store_only_serves_active_merchants() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![merchant(3, "shop.example.com", MerchantStatus::Active)]])
+            .append_query_results([vec![merchant(4, "closed.example.com", MerchantStatus::Suspended)]])
+            .into_connection();
+        assert_eq!(MerchantService::find_store(&db, "SHOP.example.com").await.unwrap().unwrap().mid, 3);
+        assert!(MerchantService::find_store(&db, "closed.example.com").await.unwrap().is_none());
+        // Not a domain at all; nothing is queried
+        assert!(MerchantService::find_store(&db, "not a domain").await.unwrap().is_none());
+        assert_eq!(db.into_transaction_log().len(), 2);
+    }
+}
@@ -0,0 +1,456 @@
+//! Per-merchant settings
+//!
+//! Each setting is a row of `merchant_settings` holding a JSON value under
+//! a key. [`Settings`] is the typed view checkout and reporting read them
+//! through, as will emails to the merchant's customers. A key the merchant
+//! never set has its default: the store's currency, UTC, order numbers
+//! like `2025-11-18-3F9A1C2B`, the platform's sender, done orders archived
+//! after 90 days, declined payments retried 1, 3 and 7 days on and every
+//! feature off.
+//!
+//! [`SettingsService::get`] keeps each merchant's settings in memory for
+//! [`CACHE_TTL`]. Changing a setting through [`SettingsService`] drops the
+//! cached copy, so this process sees the change at once and any other
+//! within the TTL.
+
+use chrono::{DateTime, Datelike, NaiveDate, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use commercerack_core::money::{self, Currency};
+use commercerack_core::Timestamp;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::*;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::{Duration, Instant};
+use tracing::{instrument, warn};
+use uuid::Uuid;
+use ::entity::merchant_settings::{self, Column};
+use ::entity::prelude::*;
+
+use crate::MerchantError;
+
+/// Three-letter code the merchant prices in, e.g. `"EUR"`
+pub const CURRENCY: &str = "currency";
+/// IANA timezone name, e.g. `"Europe/Berlin"`
+pub const TIMEZONE: &str = "timezone";
+/// Template of new order numbers; see [`Settings::order_number`]
+pub const ORDER_NUMBER_FORMAT: &str = "order_number_format";
+/// Address emails to the merchant's customers are sent from
+pub const EMAIL_SENDER: &str = "email_sender";
+/// Days a done order stays in the working pools before it is archived
+pub const ARCHIVE_AFTER_DAYS: &str = "archive_after_days";
+/// Days after a declined payment to charge it again, e.g. `[1, 3, 7]`
+pub const DUNNING_RETRY_DAYS: &str = "dunning_retry_days";
+/// Prefix of feature flag keys, e.g. `features.gift_wrap`; their values
+/// are `true` or `false`
+pub const FEATURE_PREFIX: &str = "features.";
+
+/// Order numbers until the merchant sets a format
+pub const DEFAULT_ORDER_NUMBER_FORMAT: &str = "{date}-{random}";
+
+/// Days until done orders are archived, until the merchant sets them
+pub const DEFAULT_ARCHIVE_AFTER_DAYS: u32 = 90;
+
+/// Longest a merchant can keep done orders out of the archive
+const MAX_ARCHIVE_AFTER_DAYS: u64 = 3650;
+
+/// Retries of a declined payment until the merchant sets them
+pub const DEFAULT_DUNNING_RETRY_DAYS: [u32; 3] = [1, 3, 7];
+
+/// Most retries a merchant can set, and the latest day they can fall on
+const MAX_DUNNING_RETRIES: usize = 10;
+const MAX_DUNNING_RETRY_DAY: u64 = 90;
+
+/// How long settings are cached
+pub const CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// Order numbers are stored in `orders.orderid`
+const ORDER_NUMBER_MAX_LEN: usize = 30;
+
+/// A merchant's settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    pub currency: Currency,
+    /// Where the merchant's days start and end
+    pub timezone: Tz,
+    pub order_number_format: String,
+    /// `None` sends from the platform's address
+    pub email_sender: Option<String>,
+    pub archive_after_days: u32,
+    /// When to retry a declined payment, in days after it was declined,
+    /// ascending
+    pub dunning_retry_days: Vec<u32>,
+    /// Feature flags the merchant set, by name
+    pub features: BTreeMap<String, bool>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            currency: money::default_currency(),
+            timezone: Tz::UTC,
+            order_number_format: DEFAULT_ORDER_NUMBER_FORMAT.to_string(),
+            email_sender: None,
+            archive_after_days: DEFAULT_ARCHIVE_AFTER_DAYS,
+            dunning_retry_days: DEFAULT_DUNNING_RETRY_DAYS.to_vec(),
+            features: BTreeMap::new(),
+        }
+    }
+}
+
+impl Settings {
+    /// Settings with `rows` applied over the defaults. A row that no longer
+    /// parses is skipped, so one bad value can't stop checkout.
+    pub fn from_rows(rows: &[MerchantSetting]) -> Self {
+        let mut settings = Self::default();
+        for row in rows {
+            let applied = serde_json::from_str(&row.value)
+                .map_err(|e| MerchantError::InvalidSetting { key: row.key.clone(), reason: e.to_string() })
+                .and_then(|value| settings.apply(&row.key, &value));
+            if let Err(e) = applied {
+                warn!(mid = row.mid, key = row.key.as_str(), error = %e, "ignoring stored setting");
+            }
+        }
+        settings
+    }
+
+    /// Set `key` to `value`, checking both
+    pub fn apply(&mut self, key: &str, value: &Value) -> Result<(), MerchantError> {
+        let invalid = |reason: &str| MerchantError::InvalidSetting { key: key.to_string(), reason: reason.to_string() };
+        let text = || value.as_str().map(str::trim).ok_or_else(|| invalid("must be a string"));
+
+        match key {
+            CURRENCY => self.currency = Currency::new(text()?).map_err(|_| invalid("must be a three-letter code"))?,
+            TIMEZONE => self.timezone = text()?.parse().map_err(|_| invalid("must be an IANA timezone, e.g. Europe/Berlin"))?,
+            ORDER_NUMBER_FORMAT => {
+                let format = text()?;
+                check_order_number_format(format).map_err(invalid)?;
+                self.order_number_format = format.to_string();
+            }
+            EMAIL_SENDER => {
+                let sender = text()?;
+                if !is_email(sender) {
+                    return Err(invalid("must be an email address"));
+                }
+                self.email_sender = Some(sender.to_lowercase());
+            }
+            ARCHIVE_AFTER_DAYS => {
+                self.archive_after_days = value
+                    .as_u64()
+                    .filter(|days| (1..=MAX_ARCHIVE_AFTER_DAYS).contains(days))
+                    .ok_or_else(|| invalid("must be a whole number of days from 1 to 3650"))? as u32;
+            }
+            DUNNING_RETRY_DAYS => {
+                let days = value
+                    .as_array()
+                    .filter(|days| (1..=MAX_DUNNING_RETRIES).contains(&days.len()))
+                    .and_then(|days| {
+                        days.iter()
+                            .map(|day| day.as_u64().filter(|day| (1..=MAX_DUNNING_RETRY_DAY).contains(day)))
+                            .collect::<Option<Vec<_>>>()
+                    })
+                    .filter(|days| days.windows(2).all(|pair| pair[0] < pair[1]))
+                    .ok_or_else(|| invalid("must be 1 to 10 ascending days from 1 to 90"))?;
+                self.dunning_retry_days = days.into_iter().map(|day| day as u32).collect();
+            }
+            _ => {
+                let name = key.strip_prefix(FEATURE_PREFIX).filter(|name| is_feature_name(name));
+                let Some(name) = name else {
+                    return Err(MerchantError::UnknownSetting(key.to_string()));
+                };
+                let enabled = value.as_bool().ok_or_else(|| invalid("must be true or false"))?;
+                self.features.insert(name.to_string(), enabled);
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the merchant turned feature `name` on
+    pub fn feature(&self, name: &str) -> bool {
+        self.features.get(name).copied().unwrap_or(false)
+    }
+
+    /// The merchant's calendar date at `at`
+    pub fn local_date(&self, at: DateTime<Utc>) -> NaiveDate {
+        at.with_timezone(&self.timezone).date_naive()
+    }
+
+    /// When `date` starts for the merchant. Where the clocks skip midnight
+    /// the day starts when they land.
+    pub fn midnight(&self, date: NaiveDate) -> Timestamp {
+        let local = date.and_time(NaiveTime::MIN);
+        (0..=2)
+            .find_map(|hour| self.timezone.from_local_datetime(&(local + chrono::Duration::hours(hour))).earliest())
+            .map(|start| start.with_timezone(&Utc))
+            .unwrap_or_else(|| local.and_utc())
+            .into()
+    }
+
+    /// A new order number from the merchant's format. `{date}` is the
+    /// merchant's date as `YYYY-MM-DD`, `{yyyy}`, `{mm}` and `{dd}` its
+    /// parts, and `{random}` eight random hex digits.
+    pub fn order_number(&self, now: DateTime<Utc>) -> String {
+        let random = Uuid::new_v4().simple().to_string()[..8].to_uppercase();
+        render_order_number(&self.order_number_format, self.local_date(now), &random)
+    }
+}
+
+fn render_order_number(format: &str, date: NaiveDate, random: &str) -> String {
+    format
+        .replace("{date}", &date.format("%Y-%m-%d").to_string())
+        .replace("{yyyy}", &format!("{:04}", date.year()))
+        .replace("{mm}", &format!("{:02}", date.month()))
+        .replace("{dd}", &format!("{:02}", date.day()))
+        .replace("{random}", random)
+}
+
+/// A format must make unique numbers that fit an order
+fn check_order_number_format(format: &str) -> Result<(), &'static str> {
+    if !format.contains("{random}") {
+        return Err("must contain {random}");
+    }
+    let sample = render_order_number(format, NaiveDate::from_ymd_opt(2025, 11, 18).unwrap_or_default(), "3F9A1C2B");
+    if !sample.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err("may only use letters, digits, - and _ besides {date}, {yyyy}, {mm}, {dd} and {random}");
+    }
+    if sample.len() > ORDER_NUMBER_MAX_LEN {
+        return Err("makes order numbers over 30 characters");
+    }
+    Ok(())
+}
+
+fn is_email(value: &str) -> bool {
+    value.len() <= 100
+        && !value.contains(char::is_whitespace)
+        && value
+            .split_once('@')
+            .is_some_and(|(user, domain)| !user.is_empty() && domain.contains('.') && !domain.contains('@'))
+}
+
+fn is_feature_name(name: &str) -> bool {
+    !name.is_empty() && name.len() <= 40 && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+/// Settings loaded per merchant, and when
+type Cache = RwLock<HashMap<i32, (Instant, Arc<Settings>)>>;
+
+static CACHE: OnceLock<Cache> = OnceLock::new();
+
+fn cache() -> &'static Cache {
+    CACHE.get_or_init(Default::default)
+}
+
+/// Merchant settings service
+pub struct SettingsService;
+
+impl SettingsService {
+    /// A merchant's settings, from the cache when fresh
+    pub async fn get<C: ConnectionTrait>(db: &C, mid: i32) -> Result<Arc<Settings>, MerchantError> {
+        if let Some((loaded, settings)) = cache().read().unwrap_or_else(|e| e.into_inner()).get(&mid) {
+            if loaded.elapsed() < CACHE_TTL {
+                return Ok(settings.clone());
+            }
+        }
+
+        let settings = Arc::new(Settings::from_rows(&Self::stored(db, mid).await?));
+        cache().write().unwrap_or_else(|e| e.into_inner()).insert(mid, (Instant::now(), settings.clone()));
+        Ok(settings)
+    }
+
+    /// The settings a merchant set, by key
+    pub async fn stored<C: ConnectionTrait>(db: &C, mid: i32) -> Result<Vec<MerchantSetting>, MerchantError> {
+        Ok(MerchantSettings::find()
+            .filter(Column::Mid.eq(mid))
+            .order_by_asc(Column::Key)
+            .all(db)
+            .await?)
+    }
+
+    /// Set each of `values` by key, all or none
+    #[instrument(skip_all, fields(mid = mid))]
+    pub async fn set<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: i32,
+        values: &BTreeMap<String, Value>,
+    ) -> Result<Arc<Settings>, MerchantError> {
+        let mut check = Settings::default();
+        for (key, value) in values {
+            check.apply(key, value)?;
+        }
+
+        let txn = db.begin().await?;
+        let now = Timestamp::now();
+        for (key, value) in values {
+            MerchantSettings::insert(merchant_settings::ActiveModel {
+                mid: Set(mid),
+                key: Set(key.clone()),
+                value: Set(value.to_string()),
+                modified_gmt: Set(now),
+                ..Default::default()
+            })
+            .on_conflict(
+                OnConflict::columns([Column::Mid, Column::Key])
+                    .update_columns([Column::Value, Column::ModifiedGmt])
+                    .to_owned(),
+            )
+            .exec_without_returning(&txn)
+            .await?;
+        }
+        txn.commit().await?;
+
+        Self::invalidate(mid);
+        Self::get(db, mid).await
+    }
+
+    /// Put `key` back to its default
+    #[instrument(skip_all, fields(mid = mid, key = key))]
+    pub async fn unset<C: ConnectionTrait>(db: &C, mid: i32, key: &str) -> Result<Arc<Settings>, MerchantError> {
+        MerchantSettings::delete_many()
+            .filter(Column::Mid.eq(mid))
+            .filter(Column::Key.eq(key))
+            .exec(db)
+            .await?;
+
+        Self::invalidate(mid);
+        Self::get(db, mid).await
+    }
+
+    /// Drop the cached settings of `mid`, so the next read loads them
+    pub fn invalidate(mid: i32) {
+        cache().write().unwrap_or_else(|e| e.into_inner()).remove(&mid);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn row(mid: i32, key: &str, value: Value) -> MerchantSetting {
+        MerchantSetting {
+            id: 1,
+            mid,
+            key: key.to_string(),
+            value: value.to_string(),
+            modified_gmt: Timestamp::from_unix(1_700_000_000),
+        }
+    }
+
+    #[test]
+    fn test_rows_override_defaults() {
+        let settings = Settings::from_rows(&[
+            row(1, CURRENCY, json!("eur")),
+            row(1, TIMEZONE, json!("Europe/Berlin")),
+            row(1, "features.gift_wrap", json!(true)),
+            row(1, ARCHIVE_AFTER_DAYS, json!(30)),
+            // Skipped rather than failing every read
+            row(1, TIMEZONE, json!("Mars/Olympus")),
+            row(1, "colour", json!("blue")),
+        ]);
+        assert_eq!(settings.currency, Currency::EUR);
+        assert_eq!(settings.timezone, chrono_tz::Europe::Berlin);
+        assert!(settings.feature("gift_wrap"));
+        assert!(!settings.feature("loyalty"));
+        assert_eq!(settings.order_number_format, DEFAULT_ORDER_NUMBER_FORMAT);
+        assert_eq!(settings.email_sender, None);
+        assert_eq!(settings.archive_after_days, 30);
+    }
+
+    #[test]
+    fn test_apply_checks_values() {
+        let mut settings = Settings::default();
+        assert!(settings.apply(EMAIL_SENDER, &json!("Orders@Shop.example.com")).is_ok());
+        assert_eq!(settings.email_sender.as_deref(), Some("orders@shop.example.com"));
+
+        for (key, value) in [
+            (CURRENCY, json!("dollars")),
+            (TIMEZONE, json!(10)),
+            (EMAIL_SENDER, json!("orders")),
+            (ORDER_NUMBER_FORMAT, json!("{date}")),
+            (ORDER_NUMBER_FORMAT, json!("{date} {random}")),
+            (ORDER_NUMBER_FORMAT, json!("WIDGETS-SHOP-{date}-{random}")),
+            ("features.gift_wrap", json!("yes")),
+            (ARCHIVE_AFTER_DAYS, json!(0)),
+            (ARCHIVE_AFTER_DAYS, json!(7.5)),
+            (DUNNING_RETRY_DAYS, json!([])),
+            (DUNNING_RETRY_DAYS, json!([3, 1])),
+            (DUNNING_RETRY_DAYS, json!([1, 91])),
+            (DUNNING_RETRY_DAYS, json!(3)),
+        ] {
+            assert!(matches!(settings.apply(key, &value), Err(MerchantError::InvalidSetting { .. })), "{} {}", key, value);
+        }
+        assert!(matches!(settings.apply("features.", &json!(true)), Err(MerchantError::UnknownSetting(_))));
+
+        assert!(settings.apply(DUNNING_RETRY_DAYS, &json!([2, 5, 10, 20])).is_ok());
+        assert_eq!(settings.dunning_retry_days, vec![2, 5, 10, 20]);
+    }
+
+    #[test]
+    fn test_order_number_uses_local_date() {
+        let mut settings = Settings::default();
+        settings.apply(TIMEZONE, &json!("Australia/Sydney")).unwrap();
+        settings.apply(ORDER_NUMBER_FORMAT, &json!("WS{yyyy}{mm}{dd}-{random}")).unwrap();
+
+        // Already the 19th in Sydney
+        let now = Utc.with_ymd_and_hms(2025, 11, 18, 20, 0, 0).unwrap();
+        let number = settings.order_number(now);
+        assert!(number.starts_with("WS20251119-"), "{}", number);
+        assert_eq!(number.len(), "WS20251119-3F9A1C2B".len());
+        assert_ne!(number, settings.order_number(now));
+
+        let number = Settings::default().order_number(now);
+        assert!(number.starts_with("2025-11-18-"), "{}", number);
+    }
+
+    #[test]
+    fn test_midnight() {
+        let mut settings = Settings::default();
+        settings.apply(TIMEZONE, &json!("America/New_York")).unwrap();
+        let date = NaiveDate::from_ymd_opt(2025, 3, 12).unwrap();
+        assert_eq!(settings.midnight(date), Utc.with_ymd_and_hms(2025, 3, 12, 4, 0, 0).unwrap().into());
+
+        // Clocks in Santiago go from 23:59 to 01:00 in September
+        settings.apply(TIMEZONE, &json!("America/Santiago")).unwrap();
+        let date = NaiveDate::from_ymd_opt(2025, 9, 7).unwrap();
+        assert_eq!(settings.midnight(date), Utc.with_ymd_and_hms(2025, 9, 7, 4, 0, 0).unwrap().into());
+    }
+
+    #[tokio::test]
+    async fn test_set_invalidates_cache() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([Vec::<MerchantSetting>::new()])
+            .append_exec_results([MockExecResult { last_insert_id: 1, rows_affected: 1 }])
+            .append_query_results([vec![row(41, TIMEZONE, json!("Europe/Paris"))]])
+            .into_connection();
+
+        assert_eq!(SettingsService::get(&db, 41).await.unwrap().timezone, Tz::UTC);
+        // Cached; nothing is queried
+        assert_eq!(SettingsService::get(&db, 41).await.unwrap().timezone, Tz::UTC);
+
+        let values = [(TIMEZONE.to_string(), json!("Europe/Paris"))].into_iter().collect();
+        let settings = SettingsService::set(&db, 41, &values).await.unwrap();
+        assert_eq!(settings.timezone, chrono_tz::Europe::Paris);
+        assert_eq!(SettingsService::get(&db, 41).await.unwrap().timezone, chrono_tz::Europe::Paris);
+
+        let log = db.into_transaction_log();
+        assert_eq!(log.len(), 3);
+        let upsert = log[1].statements()[1].to_string();
+        assert!(upsert.contains(r#"INSERT INTO "merchant_settings""#), "{}", upsert);
+        assert!(upsert.contains("ON CONFLICT"), "{}", upsert);
+    }
+
+    #[tokio::test]
+    async fn test_set_refuses_unknown_keys() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+        let values = [
+            (CURRENCY.to_string(), json!("GBP")),
+            ("colour".to_string(), json!("blue")),
+        ]
+        .into_iter()
+        .collect();
+        let result = SettingsService::set(&db, 42, &values).await;
+        assert!(matches!(result, Err(MerchantError::UnknownSetting(key)) if key == "colour"));
+        assert!(db.into_transaction_log().is_empty(), "nothing is written");
+    }
+}
@@ -0,0 +1,272 @@
+//! Approval of company orders
+//!
+//! An order a company buyer places over the company's approval threshold
+//! (see `commercerack_customer::companies`) gets review status
+//! [`AWAITING_APPROVAL`] and can't be paid until one of the company's
+//! approvers, or the merchant, approves it. Approving releases it as
+//! [`APPROVED`]; a rejected order stays unpayable at [`REJECTED`] for the
+//! merchant to cancel.
+
+use commercerack_customer::companies::{is_approver, CompanyService};
+use commercerack_customer::CustomerError;
+use commercerack_events::{outbox, DomainEvent};
+use rust_decimal::Decimal;
+use sea_orm::{entity::*, query::*, ConnectionTrait, DbErr, Set, TransactionTrait};
+use ::entity::prelude::{Company, Order as OrderModel, Orders};
+use thiserror::Error;
+use tracing::instrument;
+
+/// Review status of orders waiting for a company approver
+pub const AWAITING_APPROVAL: &str = "APR";
+/// Review status of approved orders
+pub const APPROVED: &str = "AOK";
+/// Review status of rejected orders
+pub const REJECTED: &str = "REJ";
+
+#[derive(Error, Debug)]
+pub enum ApprovalError {
+    #[error("Order not found")]
+    OrderNotFound,
+
+    #[error("Order is not awaiting approval")]
+    NotAwaitingApproval,
+
+    #[error("Only the company's approvers can approve or reject its orders")]
+    NotApprover,
+
+    #[error(transparent)]
+    Customer(#[from] CustomerError),
+
+    #[error("Database error: {0}")]
+    Db(#[from] DbErr),
+}
+
+/// Who approves or rejects an order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decider {
+    /// The merchant, who may decide on any company's orders
+    Merchant,
+    /// A customer, who must be an approver of the order's company
+    Customer(i32),
+}
+
+/// Whether approval holds the order back from payment
+pub fn blocks_payment(order: &OrderModel) -> bool {
+    matches!(order.review_status.as_deref(), Some(AWAITING_APPROVAL | REJECTED))
+}
+
+/// Whether an order of `total` needs approval under `company`'s threshold
+pub fn needs_approval(company: &Company, total: Decimal) -> bool {
+    company.approval_threshold.is_some_and(|threshold| total > threshold)
+}
+
+/// Place a just-placed order under its buyer's company, holding it for
+/// approval if its total is over the threshold. Run it in the checkout
+/// transaction.
+pub(crate) async fn assign<C: ConnectionTrait>(
+    conn: &C,
+    order: OrderModel,
+    company: &Company,
+    total: Decimal,
+) -> Result<OrderModel, DbErr> {
+    let hold = needs_approval(company, total);
+    let mut active: ::entity::orders::ActiveModel = order.into();
+    active.company_id = Set(Some(company.id));
+    if hold {
+        active.review_status = Set(Some(AWAITING_APPROVAL.to_string()));
+    }
+    active.update(conn).await
+}
+
+/// Tell the company's approvers an order is waiting for them
+pub(crate) async fn request<C: ConnectionTrait>(conn: &C, order: &OrderModel) -> Result<(), CustomerError> {
+    let Some(company_id) = order.company_id else {
+        return Ok(());
+    };
+    let approvers = CompanyService::approvers(conn, order.mid, company_id).await?;
+    outbox::record(conn, &DomainEvent::ApprovalRequested { order: order.clone(), approvers }).await?;
+    Ok(())
+}
+
+/// Company order approval service
+pub struct ApprovalService;
+
+impl ApprovalService {
+    /// A company's orders waiting for approval, oldest first
+    pub async fn pending<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        company_id: i32,
+    ) -> Result<Vec<OrderModel>, DbErr> {
+        Orders::find()
+            .filter(::entity::orders::Column::Mid.eq(mid))
+            .filter(::entity::orders::Column::CompanyId.eq(company_id))
+            .filter(::entity::orders::Column::ReviewStatus.eq(AWAITING_APPROVAL))
+            .order_by_asc(::entity::orders::Column::Id)
+            .all(db)
+            .await
+    }
+
+    /// Release an order for payment
+    pub async fn approve<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: i32,
+        id: i32,
+        decider: Decider,
+    ) -> Result<OrderModel, ApprovalError> {
+        Self::decide(db, mid, id, decider, APPROVED).await
+    }
+
+    /// Turn an order down; it stays unpayable
+    pub async fn reject<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: i32,
+        id: i32,
+        decider: Decider,
+    ) -> Result<OrderModel, ApprovalError> {
+        Self::decide(db, mid, id, decider, REJECTED).await
+    }
+
+    #[instrument(skip_all, fields(mid = mid, id = id, decision = decision))]
+    async fn decide<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: i32,
+        id: i32,
+        decider: Decider,
+        decision: &'static str,
+    ) -> Result<OrderModel, ApprovalError> {
+        let txn = db.begin().await?;
+        let order = Orders::find()
+            .filter(::entity::orders::Column::Mid.eq(mid))
+            .filter(::entity::orders::Column::Id.eq(id))
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .ok_or(ApprovalError::OrderNotFound)?;
+        if order.review_status.as_deref() != Some(AWAITING_APPROVAL) {
+            return Err(ApprovalError::NotAwaitingApproval);
+        }
+        if let Decider::Customer(cid) = decider {
+            let approves = CompanyService::membership(&txn, mid, cid)
+                .await?
+                .is_some_and(|(company, buyer)| Some(company.id) == order.company_id && is_approver(&buyer));
+            if !approves {
+                return Err(ApprovalError::NotApprover);
+            }
+        }
+
+        let version = order.v;
+        let mut active: ::entity::orders::ActiveModel = order.into();
+        active.review_status = Set(Some(decision.to_string()));
+        active.v = Set(version + 1);
+        let result = active.update(&txn).await?;
+        outbox::record(&txn, &DomainEvent::OrderUpdated(result.clone())).await?;
+        txn.commit().await?;
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sea_orm::{DatabaseBackend, MockDatabase};
+    use ::entity::prelude::CompanyBuyer;
+    use commercerack_core::Timestamp;
+
+    fn order(review_status: Option<&str>) -> OrderModel {
+        OrderModel {
+            id: 9,
+            mid: 1,
+            orderid: "2025-11-18-00000009".to_string(),
+            cartid: String::new(),
+            customer: 7,
+            pool: "RECENT".to_string(),
+            total: Decimal::new(250000, 2),
+            created_gmt: Timestamp::EPOCH,
+            paid_gmt: None,
+            paid_txn: None,
+            order_payment_status: None,
+            order_payment_lookup: None,
+            bs_settlement: None,
+            shipped_gmt: None,
+            inv_gmt: None,
+            company_id: Some(3),
+            review_status: review_status.map(str::to_string),
+            ship_method: None,
+            bill_email: String::new(),
+            v: 1,
+            mkt: 0,
+            erefid: None,
+            mkt_bitstr: String::new(),
+            pickup_location_id: None,
+            pickup_ready_gmt: None,
+            picked_up_gmt: None,
+            cancelled_gmt: None,
+            cancel_reason: None,
+        }
+    }
+
+    fn company(id: i32) -> Company {
+        Company {
+            id,
+            mid: 1,
+            name: "Acme".to_string(),
+            price_group: String::new(),
+            approval_threshold: Some(Decimal::new(100000, 2)),
+            created_gmt: Timestamp::EPOCH,
+            modified_gmt: Timestamp::EPOCH,
+        }
+    }
+
+    fn buyer(company_id: i32, role: &str) -> CompanyBuyer {
+        CompanyBuyer {
+            id: 1,
+            mid: 1,
+            company_id,
+            cid: 8,
+            role: role.to_string(),
+            spending_limit: None,
+            created_gmt: Timestamp::EPOCH,
+        }
+    }
+
+    #[test]
+    fn test_threshold_and_payment_hold() {
+        let acme = company(3);
+        assert!(needs_approval(&acme, Decimal::new(100001, 2)));
+        assert!(!needs_approval(&acme, Decimal::new(100000, 2)));
+        assert!(!needs_approval(&Company { approval_threshold: None, ..acme }, Decimal::from(1_000_000)));
+
+        assert!(blocks_payment(&order(Some(AWAITING_APPROVAL))));
+        assert!(blocks_payment(&order(Some(REJECTED))));
+        assert!(!blocks_payment(&order(Some(APPROVED))));
+        assert!(!blocks_payment(&order(None)));
+    }
+
+    #[tokio::test]
+    async fn test_only_the_companys_approvers_decide() {
+        // A plain buyer of the company
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![order(Some(AWAITING_APPROVAL))]])
+            .append_query_results([vec![buyer(3, "buyer")]])
+            .append_query_results([vec![company(3)]])
+            .into_connection();
+        let result = ApprovalService::approve(&db, 1, 9, Decider::Customer(8)).await;
+        assert!(matches!(result, Err(ApprovalError::NotApprover)));
+
+        // An approver of another company
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![order(Some(AWAITING_APPROVAL))]])
+            .append_query_results([vec![buyer(4, "approver")]])
+            .append_query_results([vec![company(4)]])
+            .into_connection();
+        let result = ApprovalService::approve(&db, 1, 9, Decider::Customer(8)).await;
+        assert!(matches!(result, Err(ApprovalError::NotApprover)));
+
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![order(Some(APPROVED))]])
+            .into_connection();
+        let result = ApprovalService::reject(&db, 1, 9, Decider::Merchant).await;
+        assert!(matches!(result, Err(ApprovalError::NotAwaitingApproval)));
+    }
+}
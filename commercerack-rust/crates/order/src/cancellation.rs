@@ -0,0 +1,246 @@
+//! Cancelling placed orders
+//!
+//! An order can be cancelled until any of it ships or is collected.
+//! Cancelling it
+//!
+//! - voids its authorizations and refunds its captures through the payment
+//!   gateway, with a credit memo for what was refunded,
+//! - puts its stock back, recorded as `cancellation` adjustments, and
+//!   releases any holds its cart still has, and
+//! - stamps it with when and why it was cancelled, a [`CancelReason`] code.
+//!
+//! The gateway is called before the order is written, so a cancellation
+//! that fails part way leaves the payments it already released behind;
+//! trying again picks up the rest. Gift card redemptions and coupon uses
+//! are not given back.
+
+use commercerack_core::Timestamp;
+use commercerack_events::{outbox, DomainEvent};
+use commercerack_inventory::InventoryService;
+use commercerack_payment::PaymentGateway;
+use sea_orm::{entity::*, query::*, ConnectionTrait, DatabaseConnection, Set, TransactionTrait};
+use std::fmt;
+use std::str::FromStr;
+use ::entity::prelude::{Order as OrderModel, Orders};
+use tracing::instrument;
+
+use crate::payment::{is_open, OrderPaymentService};
+use crate::pools::{ARCHIVE_POOL, COMPLETED_POOL};
+use crate::shipments::has_shipments;
+use crate::OrderError;
+
+/// Why an order was cancelled, stored in `orders.cancel_reason`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CancelReason {
+    CustomerRequest,
+    OutOfStock,
+    Fraud,
+    PaymentFailed,
+    Duplicate,
+    Other,
+}
+
+impl CancelReason {
+    pub const ALL: [CancelReason; 6] = [
+        CancelReason::CustomerRequest,
+        CancelReason::OutOfStock,
+        CancelReason::Fraud,
+        CancelReason::PaymentFailed,
+        CancelReason::Duplicate,
+        CancelReason::Other,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CancelReason::CustomerRequest => "customer_request",
+            CancelReason::OutOfStock => "out_of_stock",
+            CancelReason::Fraud => "fraud",
+            CancelReason::PaymentFailed => "payment_failed",
+            CancelReason::Duplicate => "duplicate",
+            CancelReason::Other => "other",
+        }
+    }
+}
+
+impl fmt::Display for CancelReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for CancelReason {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|reason| reason.as_str() == s)
+            .ok_or_else(|| format!("unknown cancel reason {}", s))
+    }
+}
+
+/// Refuse orders that were already cancelled, shipped or are done
+async fn check_cancellable<C: ConnectionTrait>(conn: &C, order: &OrderModel) -> Result<(), OrderError> {
+    if order.cancelled_gmt.is_some() {
+        return Err(OrderError::Cancelled);
+    }
+    if order.shipped_gmt.is_some() || order.picked_up_gmt.is_some() {
+        return Err(OrderError::NotCancellable("it has shipped"));
+    }
+    if order.pool == COMPLETED_POOL || order.pool == ARCHIVE_POOL {
+        return Err(OrderError::NotCancellable("it is complete"));
+    }
+    if has_shipments(conn, order).await? {
+        return Err(OrderError::NotCancellable("part of it has shipped"));
+    }
+    Ok(())
+}
+
+/// Order cancellation service
+pub struct CancellationService;
+
+impl CancellationService {
+    /// Cancel an order for `reason`. `gateway` is only needed when the
+    /// order has payments still holding the buyer's money.
+    #[instrument(skip_all, fields(mid = mid, id = id, reason = %reason))]
+    pub async fn cancel(
+        db: &DatabaseConnection,
+        gateway: Option<&dyn PaymentGateway>,
+        mid: i32,
+        id: i32,
+        reason: CancelReason,
+    ) -> Result<OrderModel, OrderError> {
+        let order = Orders::find()
+            .filter(::entity::orders::Column::Mid.eq(mid))
+            .filter(::entity::orders::Column::Id.eq(id))
+            .one(db)
+            .await?
+            .ok_or(OrderError::NotFound)?;
+        check_cancellable(db, &order).await?;
+
+        let payments = OrderPaymentService::payments(db, mid, id).await?;
+        if payments.iter().any(is_open) {
+            let gateway = gateway.ok_or(OrderError::NoPaymentGateway)?;
+            OrderPaymentService::refund(db, gateway, mid, id, None).await?;
+        }
+
+        let txn = db.begin().await?;
+        // Locked again, now that the payments are settled, so a shipment
+        // recorded meanwhile stops the cancellation
+        let order = Orders::find()
+            .filter(::entity::orders::Column::Mid.eq(mid))
+            .filter(::entity::orders::Column::Id.eq(id))
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .ok_or(OrderError::NotFound)?;
+        check_cancellable(&txn, &order).await?;
+
+        InventoryService::restock_order(&txn, mid, id).await?;
+        if !order.cartid.is_empty() {
+            InventoryService::release(&txn, mid, &order.cartid).await?;
+        }
+
+        let mut active: ::entity::orders::ActiveModel = order.into();
+        active.cancelled_gmt = Set(Some(Timestamp::now()));
+        active.cancel_reason = Set(Some(reason.as_str().to_string()));
+        let order = active.update(&txn).await?;
+        outbox::record(&txn, &DomainEvent::OrderCancelled(order.clone())).await?;
+        txn.commit().await?;
+
+        Ok(order)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use commercerack_payment::TransactionStatus;
+    use ::entity::prelude::{OrderPayment, Shipment};
+    use rust_decimal::Decimal;
+    use sea_orm::{DatabaseBackend, MockDatabase};
+
+    fn order(cancelled: bool, shipped: bool) -> OrderModel {
+        OrderModel {
+            id: 9,
+            mid: 1,
+            orderid: "2025-11-18-00000009".to_string(),
+            cartid: "cart".to_string(),
+            customer: 7,
+            pool: "RECENT".to_string(),
+            total: Decimal::new(5000, 2),
+            created_gmt: Timestamp::EPOCH,
+            paid_gmt: None,
+            paid_txn: None,
+            order_payment_status: None,
+            order_payment_lookup: None,
+            bs_settlement: None,
+            shipped_gmt: shipped.then_some(Timestamp::EPOCH),
+            inv_gmt: None,
+            company_id: None,
+            review_status: None,
+            ship_method: None,
+            bill_email: "buyer@example.com".to_string(),
+            v: 2,
+            mkt: 0,
+            erefid: None,
+            mkt_bitstr: String::new(),
+            pickup_location_id: None,
+            pickup_ready_gmt: None,
+            picked_up_gmt: None,
+            cancelled_gmt: cancelled.then_some(Timestamp::EPOCH),
+            cancel_reason: cancelled.then(|| "other".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_cancel_reason_round_trips() {
+        for reason in CancelReason::ALL {
+            assert_eq!(reason.as_str().parse::<CancelReason>().unwrap(), reason);
+        }
+        assert!("changed_mind".parse::<CancelReason>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_shipped_and_cancelled_orders_are_refused() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![order(true, false)]])
+            .into_connection();
+        assert!(matches!(
+            CancellationService::cancel(&db, None, 1, 9, CancelReason::Other).await,
+            Err(OrderError::Cancelled)
+        ));
+
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![order(false, true)]])
+            .into_connection();
+        assert!(matches!(
+            CancellationService::cancel(&db, None, 1, 9, CancelReason::Other).await,
+            Err(OrderError::NotCancellable("it has shipped"))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_open_payments_need_a_gateway() {
+        let authorized = OrderPayment {
+            id: 1,
+            mid: 1,
+            order_id: 9,
+            reference: Some("txn_1".to_string()),
+            amount: Decimal::new(5000, 2),
+            refunded: Decimal::ZERO,
+            status: TransactionStatus::Authorized.as_str().to_string(),
+            created_gmt: Timestamp::EPOCH,
+            modified_gmt: Timestamp::EPOCH,
+        };
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![order(false, false)]])
+            .append_query_results([Vec::<Shipment>::new()])
+            .append_query_results([vec![authorized]])
+            .into_connection();
+        assert!(matches!(
+            CancellationService::cancel(&db, None, 1, 9, CancelReason::CustomerRequest).await,
+            Err(OrderError::NoPaymentGateway)
+        ));
+    }
+}
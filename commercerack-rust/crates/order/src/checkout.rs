@@ -0,0 +1,216 @@
+//! Cart-to-order checkout
+//!
+//! Turns a validated cart into an order plus line items inside a single
+//! database transaction, decrementing stock in the same transaction. Each
+//! line is priced from the catalog in that transaction; a cart holding
+//! another price, or a SKU no longer for sale, is refused. An
+//! applied coupon is re-checked in that transaction and becomes a negative
+//! `%COUPON` line item; tax on the discounted subtotal is added as one
+//! `%TAX` line item per tax, and each planned shipment's method as a
+//! `%SHIP` line item; a cart stocked in several warehouses ships as several
+//! [fulfillment groups](crate::fulfillment_groups). Gift cards applied to the cart then pay what they can of
+//! that total, each as a negative `%GIFTCARD` line item; an order they
+//! cover in full is placed already paid.
+//!
+//! Guests check out as customer [`GUEST_CUSTOMER`] and must give a billing
+//! email, which later links the order to the account they register.
+//!
+//! An order repeating one the customer placed moments ago is still placed
+//! but held for review (see [`crate::duplicates`]).
+//!
+//! Company buyers can't place orders over their spending limit, and their
+//! orders over the company's approval threshold wait for an approver (see
+//! [`crate::approvals`]).
+//!
+//! Orders collected from a store (see [`crate::pickup`]) carry no shipping
+//! charge and record the store they wait at.
+//!
+//! Orders are numbered in the merchant's order number format, and stages
+//! can read the rest of the merchant's settings from the [`Checkout`].
+//!
+//! Each of these steps is a stage of the checkout pipeline; see
+//! [`crate::pipeline`] for running custom stages among them.
+
+use chrono::Utc;
+use commercerack_cart::Cart;
+use commercerack_customer::CustomerError;
+use commercerack_giftcards::GiftCardError;
+use commercerack_inventory::warehouses::ShipTo;
+use commercerack_inventory::InventoryError;
+use commercerack_merchant::MerchantError;
+use commercerack_product::pricing::PricingError;
+use commercerack_promotion::CouponError;
+use commercerack_shipping::QuotedShipment;
+use commercerack_tax::{TaxAddress, TaxCalculator, TaxError};
+use ::entity::prelude::StoreLocation;
+use rust_decimal::Decimal;
+use sea_orm::{ConnectionTrait, DbErr, TransactionTrait};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::pipeline::{self, Checkout};
+use crate::OrderWithItems;
+
+/// Pool that newly placed orders land in
+pub const NEW_ORDER_POOL: &str = "RECENT";
+/// SKU of the line item carrying a coupon discount
+pub const COUPON_SKU: &str = "%COUPON";
+/// SKU of the line items carrying tax
+pub const TAX_SKU: &str = "%TAX";
+/// SKU of the line item carrying the shipping charge
+pub const SHIP_SKU: &str = "%SHIP";
+/// SKU of the line items carrying gift card payments
+pub const GIFT_CARD_SKU: &str = "%GIFTCARD";
+
+#[derive(Error, Debug)]
+pub enum CheckoutError {
+    #[error("Cart is empty")]
+    EmptyCart,
+
+    #[error("Invalid cart item {sku}: {reason}")]
+    InvalidItem { sku: String, reason: &'static str },
+
+    #[error("Cart {0} has already been checked out")]
+    AlreadyCheckedOut(String),
+
+    #[error("Guest checkout requires a billing email")]
+    GuestEmailRequired,
+
+    #[error("Price of {sku} changed from {previous} to {current}")]
+    PriceChanged { sku: String, previous: Decimal, current: Decimal },
+
+    #[error("Order total {total} is over your spending limit of {limit}")]
+    SpendingLimit { total: Decimal, limit: Decimal },
+
+    /// Refused by a custom checkout stage
+    #[error("{0}")]
+    Rejected(String),
+
+    #[error(transparent)]
+    Pricing(#[from] PricingError),
+
+    #[error(transparent)]
+    Inventory(#[from] InventoryError),
+
+    #[error(transparent)]
+    Coupon(#[from] CouponError),
+
+    #[error(transparent)]
+    Tax(#[from] TaxError),
+
+    #[error(transparent)]
+    GiftCard(#[from] GiftCardError),
+
+    #[error(transparent)]
+    Customer(#[from] CustomerError),
+
+    #[error(transparent)]
+    Merchant(#[from] MerchantError),
+
+    #[error("Database error: {0}")]
+    Db(#[from] DbErr),
+}
+
+/// Check that a cart can be turned into an order
+pub fn validate_cart(cart: &Cart) -> Result<(), CheckoutError> {
+    if cart.is_empty() {
+        return Err(CheckoutError::EmptyCart);
+    }
+
+    for item in &cart.items {
+        if item.quantity <= 0 {
+            return Err(CheckoutError::InvalidItem {
+                sku: item.sku.clone(),
+                reason: "quantity must be positive",
+            });
+        }
+        if item.unit_price < Decimal::ZERO {
+            return Err(CheckoutError::InvalidItem {
+                sku: item.sku.clone(),
+                reason: "unit price cannot be negative",
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Generate a merchant-facing order ID like `2025-11-18-3F9A1C2B`, for
+/// merchants that haven't set an order number format
+pub fn generate_orderid() -> String {
+    let suffix = Uuid::new_v4().simple().to_string()[..8].to_uppercase();
+    format!("{}-{}", Utc::now().format("%Y-%m-%d"), suffix)
+}
+
+/// How to tax an order: the calculator to use and where the order ships
+pub struct TaxContext<'a> {
+    pub calculator: &'a dyn TaxCalculator,
+    pub address: TaxAddress,
+}
+
+/// How the buyer gets the order
+#[derive(Debug, Clone)]
+pub enum Fulfillment {
+    /// Shipped; carries the shipments the order was planned as, each with
+    /// a quote the caller just obtained for its method. None when no
+    /// method was chosen.
+    Ship(Vec<QuotedShipment>),
+    /// Collected from an active store location
+    Pickup(Box<StoreLocation>),
+}
+
+/// Checkout service for converting carts into orders
+pub struct CheckoutService;
+
+impl CheckoutService {
+    /// Create an order and its line items from a cart atomically, through
+    /// the [installed](pipeline::installed) checkout pipeline.
+    ///
+    /// The caller is responsible for clearing the cart once this succeeds.
+    /// Without a tax context no tax is charged. The shipments in
+    /// `fulfillment` are charged unless a free shipping coupon waives them;
+    /// pickup orders pay no shipping. `bill_email` is required for guests and may be
+    /// empty for customers. `ship_to` picks the warehouses stock is
+    /// allocated from.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn place_order<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: i32,
+        customer: i32,
+        bill_email: &str,
+        cart: &Cart,
+        tax: Option<TaxContext<'_>>,
+        fulfillment: Fulfillment,
+        ship_to: Option<&ShipTo>,
+    ) -> Result<OrderWithItems, CheckoutError> {
+        let checkout = Checkout::new(mid, customer, bill_email, cart, tax, fulfillment, ship_to);
+        pipeline::installed().run(db, checkout).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_cart() {
+        let mut cart = Cart::new();
+        assert!(matches!(validate_cart(&cart), Err(CheckoutError::EmptyCart)));
+
+        cart.add_item("SKU001".to_string(), "Widget".to_string(), 2, Decimal::new(1999, 2));
+        assert!(validate_cart(&cart).is_ok());
+
+        cart.add_item("SKU002".to_string(), "Gadget".to_string(), -1, Decimal::new(500, 2));
+        assert!(matches!(
+            validate_cart(&cart),
+            Err(CheckoutError::InvalidItem { ref sku, .. }) if sku == "SKU002"
+        ));
+    }
+
+    #[test]
+    fn test_generate_orderid() {
+        let orderid = generate_orderid();
+        assert_eq!(orderid.len(), "2025-11-18-3F9A1C2B".len());
+        assert_ne!(orderid, generate_orderid());
+    }
+}
@@ -0,0 +1,473 @@
+//! Cold storage of long archived orders
+//!
+//! Orders that have sat in the [`ARCHIVE_POOL`] long enough are written to
+//! an object store as gzipped JSON, together with their items, payments and
+//! shipments, and removed from those tables. An `archived_orders` row keeps
+//! enough of each (number, customer, total) to find it again, and
+//! [`ColdStorageService::rehydrate`] reads the snapshot back for viewing.
+//! Invoices and returns are records in their own right and stay where
+//! they are, still pointing at the order's old `id`.
+//!
+//! The store is chosen once per process with [`install`]; without one,
+//! nothing leaves the database.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use sea_orm::sea_query::Expr;
+use sea_orm::{entity::*, query::*, ConnectionTrait, DbErr, Set, TransactionTrait};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex, OnceLock};
+use thiserror::Error;
+use ::entity::prelude::{
+    ArchivedOrder, ArchivedOrders, Order, OrderItem, OrderItems, OrderPayment, OrderPayments, Orders, Shipment,
+    ShipmentItem, ShipmentItems, Shipments,
+};
+use tracing::{info, instrument};
+
+use crate::pools::{done_at, ARCHIVE_POOL};
+
+mod s3;
+pub use s3::{S3Settings, S3Store};
+
+#[derive(Error, Debug)]
+pub enum ColdStorageError {
+    #[error("Archived order not found")]
+    NotFound,
+
+    #[error("Cold storage is not configured")]
+    NotConfigured,
+
+    #[error("Object storage error: {0}")]
+    Storage(String),
+
+    #[error("Archived order could not be read: {0}")]
+    Corrupt(String),
+
+    #[error("Database error: {0}")]
+    Db(#[from] DbErr),
+}
+
+/// Where archived orders are written
+#[async_trait]
+pub trait ArchiveStore: Send + Sync {
+    /// Store `body` under `key`, replacing anything already there
+    async fn put(&self, key: &str, body: Vec<u8>) -> Result<(), ColdStorageError>;
+
+    /// What is stored under `key`; `NotFound` if nothing is
+    async fn get(&self, key: &str) -> Result<Vec<u8>, ColdStorageError>;
+}
+
+/// Archive store in process memory, for tests and local development
+#[derive(Default)]
+pub struct MemoryStore {
+    objects: Mutex<HashMap<String, Vec<u8>>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl ArchiveStore for MemoryStore {
+    async fn put(&self, key: &str, body: Vec<u8>) -> Result<(), ColdStorageError> {
+        self.objects.lock().unwrap().insert(key.to_string(), body);
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Vec<u8>, ColdStorageError> {
+        self.objects.lock().unwrap().get(key).cloned().ok_or(ColdStorageError::NotFound)
+    }
+}
+
+static STORE: OnceLock<Arc<dyn ArchiveStore>> = OnceLock::new();
+
+/// Use `store` for cold storage in this process. Only the first call takes
+/// effect.
+pub fn install(store: Arc<dyn ArchiveStore>) {
+    let _ = STORE.set(store);
+}
+
+/// The installed store
+pub fn store() -> Result<Arc<dyn ArchiveStore>, ColdStorageError> {
+    STORE.get().cloned().ok_or(ColdStorageError::NotConfigured)
+}
+
+/// An order as it was when it left the database
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColdOrder {
+    pub order: Order,
+    pub items: Vec<OrderItem>,
+    pub payments: Vec<OrderPayment>,
+    pub shipments: Vec<Shipment>,
+    pub shipment_items: Vec<ShipmentItem>,
+}
+
+impl ColdOrder {
+    /// Gzipped JSON, as stored
+    pub fn encode(&self) -> Result<Vec<u8>, ColdStorageError> {
+        let json = serde_json::to_vec(self).map_err(|e| ColdStorageError::Corrupt(e.to_string()))?;
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&json).and_then(|_| encoder.finish()).map_err(|e| ColdStorageError::Storage(e.to_string()))
+    }
+
+    pub fn decode(body: &[u8]) -> Result<Self, ColdStorageError> {
+        let mut json = Vec::new();
+        GzDecoder::new(body).read_to_end(&mut json).map_err(|e| ColdStorageError::Corrupt(e.to_string()))?;
+        serde_json::from_slice(&json).map_err(|e| ColdStorageError::Corrupt(e.to_string()))
+    }
+}
+
+/// Where an order's snapshot is kept
+pub fn object_key(order: &Order) -> String {
+    format!("orders/{}/{}.json.gz", order.mid, order.id)
+}
+
+/// Cold storage service
+pub struct ColdStorageService;
+
+impl ColdStorageService {
+    /// Move up to `limit` orders that were done more than `after` before
+    /// `now` and are in the archive pool to `store`, oldest first. Returns
+    /// how many were moved.
+    #[instrument(skip(db, store))]
+    pub async fn freeze_due<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        store: &dyn ArchiveStore,
+        now: DateTime<Utc>,
+        after: Duration,
+        limit: u64,
+    ) -> Result<u64, ColdStorageError> {
+        let orders = Orders::find()
+            .filter(::entity::orders::Column::Pool.eq(ARCHIVE_POOL))
+            .filter(Expr::expr(done_at()).lt((now - after).timestamp()))
+            .order_by_asc(::entity::orders::Column::Id)
+            .limit(limit)
+            .all(db)
+            .await?;
+
+        let mut frozen = 0;
+        for order in orders {
+            if Self::freeze(db, store, order, now).await? {
+                frozen += 1;
+            }
+        }
+        if frozen > 0 {
+            info!(frozen, "moved archived orders to cold storage");
+        }
+        Ok(frozen)
+    }
+
+    /// Write one order to `store` and remove it from the database. The
+    /// object is written first, so a failure leaves the order where it was;
+    /// an order changed in the meantime is left for the next run.
+    async fn freeze<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        store: &dyn ArchiveStore,
+        order: Order,
+        now: DateTime<Utc>,
+    ) -> Result<bool, ColdStorageError> {
+        let items = OrderItems::find()
+            .filter(::entity::order_items::Column::OrderId.eq(order.id))
+            .all(db)
+            .await?;
+        let payments = OrderPayments::find()
+            .filter(::entity::order_payments::Column::OrderId.eq(order.id))
+            .all(db)
+            .await?;
+        let shipments = Shipments::find()
+            .filter(::entity::shipments::Column::OrderId.eq(order.id))
+            .all(db)
+            .await?;
+        let shipment_ids: Vec<i32> = shipments.iter().map(|s| s.id).collect();
+        let shipment_items = if shipment_ids.is_empty() {
+            Vec::new()
+        } else {
+            ShipmentItems::find()
+                .filter(::entity::shipment_items::Column::ShipmentId.is_in(shipment_ids.clone()))
+                .all(db)
+                .await?
+        };
+
+        let key = object_key(&order);
+        let cold = ColdOrder { order, items, payments, shipments, shipment_items };
+        let body = cold.encode()?;
+        let size = body.len() as i64;
+        store.put(&key, body).await?;
+
+        let order = cold.order;
+        let txn = db.begin().await?;
+        let deleted = Orders::delete_many()
+            .filter(::entity::orders::Column::Id.eq(order.id))
+            .filter(::entity::orders::Column::V.eq(order.v))
+            .exec(&txn)
+            .await?
+            .rows_affected;
+        if deleted == 0 {
+            return Ok(false);
+        }
+        if !shipment_ids.is_empty() {
+            ShipmentItems::delete_many()
+                .filter(::entity::shipment_items::Column::ShipmentId.is_in(shipment_ids))
+                .exec(&txn)
+                .await?;
+        }
+        Shipments::delete_many()
+            .filter(::entity::shipments::Column::OrderId.eq(order.id))
+            .exec(&txn)
+            .await?;
+        OrderPayments::delete_many()
+            .filter(::entity::order_payments::Column::OrderId.eq(order.id))
+            .exec(&txn)
+            .await?;
+        OrderItems::delete_many()
+            .filter(::entity::order_items::Column::OrderId.eq(order.id))
+            .exec(&txn)
+            .await?;
+        ArchivedOrders::insert(::entity::archived_orders::ActiveModel {
+            mid: Set(order.mid),
+            order_id: Set(order.id),
+            orderid: Set(order.orderid),
+            customer: Set(order.customer),
+            total: Set(order.total),
+            created_gmt: Set(order.created_gmt),
+            object_key: Set(key),
+            size: Set(size),
+            archived_gmt: Set(now.into()),
+            ..Default::default()
+        })
+        .exec_without_returning(&txn)
+        .await?;
+        txn.commit().await?;
+        Ok(true)
+    }
+
+    /// A merchant's orders in cold storage, most recently placed first,
+    /// optionally only those numbered `orderid`
+    pub async fn list<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        orderid: Option<&str>,
+        limit: u64,
+        offset: u64,
+    ) -> Result<Vec<ArchivedOrder>, ColdStorageError> {
+        let mut query = ArchivedOrders::find().filter(::entity::archived_orders::Column::Mid.eq(mid));
+        if let Some(orderid) = orderid {
+            query = query.filter(::entity::archived_orders::Column::Orderid.eq(orderid));
+        }
+        Ok(query
+            .order_by_desc(::entity::archived_orders::Column::CreatedGmt)
+            .order_by_desc(::entity::archived_orders::Column::Id)
+            .limit(limit)
+            .offset(offset)
+            .all(db)
+            .await?)
+    }
+
+    /// Which of `order_ids` belong to orders moved to cold storage
+    pub async fn archived_ids<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        order_ids: &[i32],
+    ) -> Result<HashSet<i32>, ColdStorageError> {
+        if order_ids.is_empty() {
+            return Ok(HashSet::new());
+        }
+        let ids: Vec<i32> = ArchivedOrders::find()
+            .select_only()
+            .column(::entity::archived_orders::Column::OrderId)
+            .filter(::entity::archived_orders::Column::Mid.eq(mid))
+            .filter(::entity::archived_orders::Column::OrderId.is_in(order_ids.iter().copied()))
+            .into_tuple()
+            .all(db)
+            .await?;
+        Ok(ids.into_iter().collect())
+    }
+
+    /// Read an order back from `store` by the `id` it had
+    pub async fn rehydrate<C: ConnectionTrait>(
+        db: &C,
+        store: &dyn ArchiveStore,
+        mid: i32,
+        order_id: i32,
+    ) -> Result<(ArchivedOrder, ColdOrder), ColdStorageError> {
+        let archived = ArchivedOrders::find()
+            .filter(::entity::archived_orders::Column::Mid.eq(mid))
+            .filter(::entity::archived_orders::Column::OrderId.eq(order_id))
+            .one(db)
+            .await?
+            .ok_or(ColdStorageError::NotFound)?;
+        let cold = ColdOrder::decode(&store.get(&archived.object_key).await?)?;
+        Ok((archived, cold))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use commercerack_core::Timestamp;
+    use rust_decimal::Decimal;
+    use sea_orm::{DatabaseBackend, MockDatabase, MockExecResult};
+
+    fn order() -> Order {
+        Order {
+            id: 12,
+            mid: 7,
+            orderid: "2024-01-05-ABC123".to_string(),
+            cartid: String::new(),
+            customer: 3,
+            pool: ARCHIVE_POOL.to_string(),
+            total: Decimal::new(4500, 2),
+            created_gmt: Timestamp::from_unix(1_704_412_800),
+            paid_gmt: Some(Timestamp::from_unix(1_704_412_900)),
+            paid_txn: None,
+            order_payment_status: Some("000".to_string()),
+            order_payment_lookup: None,
+            bs_settlement: None,
+            shipped_gmt: Some(Timestamp::from_unix(1_704_500_000)),
+            inv_gmt: None,
+            company_id: None,
+            review_status: None,
+            ship_method: None,
+            bill_email: "buyer@example.com".to_string(),
+            mkt: 0,
+            erefid: None,
+            mkt_bitstr: String::new(),
+            pickup_location_id: None,
+            pickup_ready_gmt: None,
+            picked_up_gmt: None,
+            cancelled_gmt: None,
+            cancel_reason: None,
+            v: 4,
+        }
+    }
+
+    fn item() -> OrderItem {
+        OrderItem {
+            id: 30,
+            mid: 7,
+            order_id: 12,
+            sku: "MUG".to_string(),
+            product_name: "Mug".to_string(),
+            quantity: 3,
+            unit_price: Decimal::new(1500, 2),
+            options: String::new(),
+        }
+    }
+
+    fn shipment() -> Shipment {
+        Shipment {
+            id: 5,
+            mid: 7,
+            order_id: 12,
+            carrier: "UPS".to_string(),
+            tracking_number: "1Z999".to_string(),
+            shipped_gmt: Timestamp::from_unix(1_704_500_000),
+            label_provider: None,
+            label_id: None,
+            label_url: None,
+            label_cost: None,
+        }
+    }
+
+    fn exec(rows_affected: u64) -> MockExecResult {
+        MockExecResult { last_insert_id: 0, rows_affected }
+    }
+
+    #[tokio::test]
+    async fn test_freeze_moves_order_to_store() {
+        let now = Utc.with_ymd_and_hms(2025, 11, 18, 3, 0, 0).unwrap();
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![order()]])
+            .append_query_results([vec![item()]])
+            .append_query_results([Vec::<OrderPayment>::new()])
+            .append_query_results([vec![shipment()]])
+            .append_query_results([vec![ShipmentItem { id: 9, mid: 7, shipment_id: 5, order_item_id: 30, quantity: 3 }]])
+            .append_exec_results([exec(1), exec(1), exec(1), exec(0), exec(1), exec(1)])
+            .into_connection();
+        let store = MemoryStore::new();
+
+        let frozen = ColdStorageService::freeze_due(&db, &store, now, Duration::days(365), 100).await.unwrap();
+        assert_eq!(frozen, 1);
+
+        let cold = ColdOrder::decode(&store.get("orders/7/12.json.gz").await.unwrap()).unwrap();
+        assert_eq!(cold.order, order());
+        assert_eq!(cold.items, vec![item()]);
+        assert_eq!(cold.shipments, vec![shipment()]);
+        assert_eq!(cold.shipment_items.len(), 1);
+
+        let log = db.into_transaction_log();
+        let select = log[0].statements()[0].to_string();
+        assert!(select.contains(r#""pool" = 'ARCHIVE'"#), "{}", select);
+        let cutoff = (now - Duration::days(365)).timestamp();
+        assert!(select.contains(&format!(r#"COALESCE("shipped_gmt", "created_gmt") < {}"#, cutoff)), "{}", select);
+
+        let txn = log[5].statements();
+        assert!(txn[1].to_string().contains(r#"DELETE FROM "orders" WHERE "orders"."id" = 12 AND "orders"."v" = 4"#));
+        assert!(txn[2].to_string().contains(r#"DELETE FROM "shipment_items""#));
+        assert!(txn[6].to_string().contains(r#"INSERT INTO "archived_orders""#));
+        assert!(txn[6].to_string().contains("'orders/7/12.json.gz'"));
+    }
+
+    #[tokio::test]
+    async fn test_freeze_leaves_changed_order() {
+        let now = Utc.with_ymd_and_hms(2025, 11, 18, 3, 0, 0).unwrap();
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![order()]])
+            .append_query_results([Vec::<OrderItem>::new()])
+            .append_query_results([Vec::<OrderPayment>::new()])
+            .append_query_results([Vec::<Shipment>::new()])
+            .append_exec_results([exec(0)])
+            .into_connection();
+        let store = MemoryStore::new();
+
+        let frozen = ColdStorageService::freeze_due(&db, &store, now, Duration::days(365), 100).await.unwrap();
+        assert_eq!(frozen, 0);
+
+        let log = db.into_transaction_log();
+        assert_eq!(log.len(), 5);
+        assert!(!log[4].statements().iter().any(|s| s.to_string().contains("archived_orders")));
+    }
+
+    #[tokio::test]
+    async fn test_rehydrate_reads_snapshot() {
+        let cold = ColdOrder { order: order(), items: vec![item()], payments: vec![], shipments: vec![], shipment_items: vec![] };
+        let store = MemoryStore::new();
+        store.put("orders/7/12.json.gz", cold.encode().unwrap()).await.unwrap();
+        let archived = ArchivedOrder {
+            id: 1,
+            mid: 7,
+            order_id: 12,
+            orderid: "2024-01-05-ABC123".to_string(),
+            customer: 3,
+            total: Decimal::new(4500, 2),
+            created_gmt: Timestamp::from_unix(1_704_412_800),
+            object_key: "orders/7/12.json.gz".to_string(),
+            size: 200,
+            archived_gmt: Timestamp::from_unix(1_763_434_800),
+        };
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![archived.clone()]])
+            .append_query_results([Vec::<ArchivedOrder>::new()])
+            .into_connection();
+
+        let (found, read) = ColdStorageService::rehydrate(&db, &store, 7, 12).await.unwrap();
+        assert_eq!(found, archived);
+        assert_eq!(read, cold);
+
+        let missing = ColdStorageService::rehydrate(&db, &store, 7, 13).await;
+        assert!(matches!(missing, Err(ColdStorageError::NotFound)));
+    }
+
+    #[test]
+    fn test_corrupt_snapshot_is_reported() {
+        assert!(matches!(ColdOrder::decode(b"not gzip"), Err(ColdStorageError::Corrupt(_))));
+    }
+}
@@ -0,0 +1,474 @@
+//! Automatic fulfillment of digital items
+//!
+//! When an order is paid, its lines for digital products are delivered in
+//! the same transaction: each gets a download token (if the product has a
+//! file) and one license key per unit from the SKU's pool (if it has one).
+//! The lines are then recorded as a [`DIGITAL_CARRIER`] shipment, so an
+//! order of only digital items is marked shipped straight away and a mixed
+//! order waits only on its physical items.
+//!
+//! A download token is the buyer's credential: `/api/downloads/{token}`
+//! redirects to the file until the token expires or its download limit is
+//! used up.
+
+use chrono::Duration;
+use commercerack_core::Timestamp;
+use sea_orm::sea_query::{Expr, LockBehavior, LockType};
+use sea_orm::{entity::*, query::*, ConnectionTrait, DatabaseConnection, DbErr, Set};
+use ::entity::prelude::{
+    LicenseKey, LicenseKeys, Order, OrderDownload, OrderDownloads, OrderItems, Products, Skus,
+};
+use commercerack_events::{outbox, DomainEvent};
+use commercerack_product::digital::ProductType;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use thiserror::Error;
+use tracing::{instrument, warn};
+use uuid::Uuid;
+
+use crate::shipments::{self, ShipmentLine};
+
+/// Carrier of the shipments that record digital deliveries
+pub const DIGITAL_CARRIER: &str = "digital";
+
+#[derive(Error, Debug)]
+pub enum DownloadError {
+    #[error("Download not found")]
+    NotFound,
+
+    #[error("Download link has expired")]
+    Expired,
+
+    #[error("Download limit reached")]
+    LimitReached,
+
+    #[error("Database error: {0}")]
+    Db(#[from] DbErr),
+}
+
+/// How long download links last and how often they can be used
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DownloadSettings {
+    pub ttl_hours: i64,
+    /// For products without a download limit of their own
+    pub max_downloads: i32,
+}
+
+impl Default for DownloadSettings {
+    fn default() -> Self {
+        Self { ttl_hours: 72, max_downloads: 5 }
+    }
+}
+
+static SETTINGS: OnceLock<DownloadSettings> = OnceLock::new();
+
+/// Set the download link lifetime and default limit for this process.
+/// Only the first call takes effect.
+pub fn configure(settings: DownloadSettings) {
+    let _ = SETTINGS.set(settings);
+}
+
+fn settings() -> DownloadSettings {
+    SETTINGS.get().copied().unwrap_or_default()
+}
+
+/// What a buyer received for an order's digital items
+#[derive(Debug, Clone, Default)]
+pub struct DigitalDelivery {
+    pub downloads: Vec<OrderDownload>,
+    pub license_keys: Vec<LicenseKey>,
+}
+
+fn new_token() -> String {
+    format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
+}
+
+/// Deliver a paid order's digital items that have not been delivered yet
+/// and return the order as it now is. Running it again for the same order
+/// does nothing.
+pub(crate) async fn fulfill<C: ConnectionTrait>(conn: &C, order: Order) -> Result<Order, DbErr> {
+    let items = OrderItems::find()
+        .filter(::entity::order_items::Column::Mid.eq(order.mid))
+        .filter(::entity::order_items::Column::OrderId.eq(order.id))
+        .order_by_asc(::entity::order_items::Column::Id)
+        .all(conn)
+        .await?;
+    let skus: Vec<&str> = items
+        .iter()
+        .filter(|item| shipments::is_shippable(item))
+        .map(|item| item.sku.as_str())
+        .collect();
+    if skus.is_empty() {
+        return Ok(order);
+    }
+
+    let pids: HashMap<String, i32> = Skus::find()
+        .filter(::entity::skus::Column::Mid.eq(order.mid))
+        .filter(::entity::skus::Column::Sku.is_in(skus))
+        .all(conn)
+        .await?
+        .into_iter()
+        .map(|sku| (sku.sku, sku.pid))
+        .collect();
+    let products: HashMap<i32, _> = Products::find()
+        .filter(::entity::products::Column::Mid.eq(order.mid))
+        .filter(::entity::products::Column::Id.is_in(pids.values().copied()))
+        .filter(::entity::products::Column::ProductType.eq(ProductType::Digital.as_str()))
+        .all(conn)
+        .await?
+        .into_iter()
+        .map(|product| (product.id, product))
+        .collect();
+    if products.is_empty() {
+        return Ok(order);
+    }
+
+    let remaining = shipments::outstanding(&items, &shipments::shipped_lines(conn, order.mid, &items).await?);
+    let settings = settings();
+    let now = Timestamp::now();
+    let mut lines = Vec::new();
+    let mut delivery = DigitalDelivery::default();
+    for item in &items {
+        let Some(&quantity) = remaining.get(&item.id) else {
+            continue;
+        };
+        let Some(product) = pids.get(&item.sku).and_then(|pid| products.get(pid)) else {
+            continue;
+        };
+        lines.push(ShipmentLine { order_item_id: item.id, quantity });
+
+        if product.download_url.is_some() {
+            let download = ::entity::order_downloads::ActiveModel {
+                mid: Set(order.mid),
+                order_id: Set(order.id),
+                order_item_id: Set(item.id),
+                sku: Set(item.sku.clone()),
+                token: Set(new_token()),
+                downloads: Set(0),
+                max_downloads: Set(product.download_limit.unwrap_or(settings.max_downloads)),
+                expires_gmt: Set(now + Duration::hours(settings.ttl_hours)),
+                created_gmt: Set(now),
+                ..Default::default()
+            }
+            .insert(conn)
+            .await?;
+            delivery.downloads.push(download);
+        }
+
+        let keys = assign_keys(conn, &order, item.id, &item.sku, quantity, now).await?;
+        if !keys.is_empty() && (keys.len() as i32) < quantity {
+            warn!(
+                "License keys for {} ran out: order {} got {} of {}",
+                item.sku, order.orderid, keys.len(), quantity
+            );
+        }
+        delivery.license_keys.extend(keys);
+    }
+    if lines.is_empty() {
+        return Ok(order);
+    }
+
+    let (_, order) = shipments::record_shipment(
+        conn,
+        order,
+        &remaining,
+        &lines,
+        DIGITAL_CARRIER.to_string(),
+        String::new(),
+        None,
+    )
+    .await?;
+    let event = DomainEvent::DigitalDelivered {
+        order: order.clone(),
+        downloads: delivery.downloads,
+        license_keys: delivery.license_keys,
+    };
+    outbox::record(conn, &event).await?;
+
+    Ok(order)
+}
+
+/// Take up to `quantity` unassigned keys of a SKU for an order item.
+/// Keys locked by a concurrent checkout are skipped rather than waited on.
+async fn assign_keys<C: ConnectionTrait>(
+    conn: &C,
+    order: &Order,
+    order_item_id: i32,
+    sku: &str,
+    quantity: i32,
+    now: Timestamp,
+) -> Result<Vec<LicenseKey>, DbErr> {
+    use ::entity::license_keys::Column;
+
+    let keys = LicenseKeys::find()
+        .filter(Column::Mid.eq(order.mid))
+        .filter(Column::Sku.eq(sku))
+        .filter(Column::OrderId.is_null())
+        .order_by_asc(Column::Id)
+        .limit(quantity as u64)
+        .lock_with_behavior(LockType::Update, LockBehavior::SkipLocked)
+        .all(conn)
+        .await?;
+    if keys.is_empty() {
+        return Ok(keys);
+    }
+
+    LicenseKeys::update_many()
+        .col_expr(Column::OrderId, Expr::value(order.id))
+        .col_expr(Column::OrderItemId, Expr::value(order_item_id))
+        .col_expr(Column::AssignedGmt, Expr::value(now))
+        .filter(Column::Id.is_in(keys.iter().map(|key| key.id)))
+        .exec(conn)
+        .await?;
+
+    Ok(keys
+        .into_iter()
+        .map(|key| LicenseKey {
+            order_id: Some(order.id),
+            order_item_id: Some(order_item_id),
+            assigned_gmt: Some(now),
+            ..key
+        })
+        .collect())
+}
+
+/// Download links and license keys of orders
+pub struct DownloadService;
+
+impl DownloadService {
+    /// What an order's digital items delivered
+    pub async fn for_order(
+        db: &DatabaseConnection,
+        mid: i32,
+        order_id: i32,
+    ) -> Result<DigitalDelivery, DbErr> {
+        let downloads = OrderDownloads::find()
+            .filter(::entity::order_downloads::Column::Mid.eq(mid))
+            .filter(::entity::order_downloads::Column::OrderId.eq(order_id))
+            .order_by_asc(::entity::order_downloads::Column::Id)
+            .all(db)
+            .await?;
+        let license_keys = LicenseKeys::find()
+            .filter(::entity::license_keys::Column::Mid.eq(mid))
+            .filter(::entity::license_keys::Column::OrderId.eq(order_id))
+            .order_by_asc(::entity::license_keys::Column::Id)
+            .all(db)
+            .await?;
+
+        Ok(DigitalDelivery { downloads, license_keys })
+    }
+
+    /// Use one download of a token, returning where the file is
+    #[instrument(skip_all)]
+    pub async fn redeem(db: &DatabaseConnection, token: &str) -> Result<String, DownloadError> {
+        use ::entity::order_downloads::Column;
+
+        let download = OrderDownloads::find()
+            .filter(Column::Token.eq(token))
+            .one(db)
+            .await?
+            .ok_or(DownloadError::NotFound)?;
+        if download.expires_gmt <= Timestamp::now() {
+            return Err(DownloadError::Expired);
+        }
+
+        let sku = Skus::find()
+            .filter(::entity::skus::Column::Mid.eq(download.mid))
+            .filter(::entity::skus::Column::Sku.eq(&download.sku))
+            .one(db)
+            .await?
+            .ok_or(DownloadError::NotFound)?;
+        let url = Products::find_by_id(sku.pid)
+            .filter(::entity::products::Column::Mid.eq(download.mid))
+            .one(db)
+            .await?
+            .and_then(|product| product.download_url)
+            .ok_or(DownloadError::NotFound)?;
+
+        let used = OrderDownloads::update_many()
+            .col_expr(Column::Downloads, Expr::col(Column::Downloads).add(1))
+            .filter(Column::Id.eq(download.id))
+            .filter(Expr::col(Column::Downloads).lt(Expr::col(Column::MaxDownloads)))
+            .exec(db)
+            .await?;
+        if used.rows_affected == 0 {
+            return Err(DownloadError::LimitReached);
+        }
+
+        Ok(url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::entity::prelude::{OrderItem, Product, Shipment, ShipmentItem, Sku};
+    use rust_decimal::Decimal;
+    use sea_orm::{DatabaseBackend, MockDatabase, MockExecResult};
+
+    fn order(shipped_gmt: Option<i64>) -> Order {
+        Order {
+            id: 9,
+            mid: 1,
+            orderid: "2025-11-0009".to_string(),
+            cartid: "cart".to_string(),
+            customer: 7,
+            pool: "RECENT".to_string(),
+            total: Decimal::new(999, 2),
+            created_gmt: Timestamp::from_unix(100),
+            paid_gmt: Some(Timestamp::from_unix(100)),
+            paid_txn: None,
+            order_payment_status: Some("001".to_string()),
+            order_payment_lookup: None,
+            bs_settlement: None,
+            shipped_gmt: shipped_gmt.map(Timestamp::from_unix),
+            inv_gmt: None,
+            company_id: None,
+            review_status: None,
+            ship_method: None,
+            bill_email: "buyer@example.com".to_string(),
+            v: 1,
+            mkt: 0,
+            erefid: None,
+            mkt_bitstr: String::new(),
+            pickup_location_id: None,
+            pickup_ready_gmt: None,
+            picked_up_gmt: None,
+            cancelled_gmt: None,
+            cancel_reason: None,
+        }
+    }
+
+    fn item(id: i32, sku: &str) -> OrderItem {
+        OrderItem {
+            id,
+            mid: 1,
+            order_id: 9,
+            sku: sku.to_string(),
+            product_name: sku.to_string(),
+            quantity: 1,
+            unit_price: Decimal::new(999, 2),
+            options: String::new(),
+        }
+    }
+
+    fn sku(sku: &str) -> Sku {
+        Sku {
+            id: 3,
+            pid: 4,
+            mid: 1,
+            sku: sku.to_string(),
+            title: sku.to_string(),
+            price: Decimal::new(999, 2),
+            cost: Decimal::ZERO,
+            upc: String::new(),
+            inv_available: 100,
+            qty_onshelf: 100,
+            weight: Decimal::ZERO,
+            reorder_point: None,
+            reorder_quantity: 0,
+            low_stock_gmt: None,
+        }
+    }
+
+    fn ebook() -> Product {
+        Product {
+            id: 4,
+            mid: 1,
+            merchant: String::new(),
+            product: "EBOOK".to_string(),
+            ts: Timestamp::EPOCH,
+            product_name: "Ebook".to_string(),
+            category: String::new(),
+            description: String::new(),
+            base_price: Decimal::new(999, 2),
+            base_cost: Decimal::ZERO,
+            supplier: String::new(),
+            supplier_id: String::new(),
+            upc: String::new(),
+            created_gmt: Timestamp::EPOCH,
+            lastsold_gmt: None,
+            product_type: "digital".to_string(),
+            download_url: Some("https://files.example/ebook.pdf".to_string()),
+            download_limit: Some(3),
+            status: "active".to_string(),
+            available_from: None,
+            available_until: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_physical_order_is_left_alone() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![item(1, "BOOK"), item(2, "%SHIP")]])
+            .append_query_results([vec![sku("BOOK")]])
+            .append_query_results([Vec::<Product>::new()])
+            .into_connection();
+
+        let order = fulfill(&db, order(None)).await.unwrap();
+        assert!(order.shipped_gmt.is_none());
+        assert_eq!(db.into_transaction_log().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_digital_order_ships_with_download_and_key() {
+        let download = OrderDownload {
+            id: 5,
+            mid: 1,
+            order_id: 9,
+            order_item_id: 1,
+            sku: "EBOOK".to_string(),
+            token: new_token(),
+            downloads: 0,
+            max_downloads: 3,
+            expires_gmt: Timestamp::from_unix(1000),
+            created_gmt: Timestamp::from_unix(100),
+        };
+        let key = LicenseKey {
+            id: 11,
+            mid: 1,
+            sku: "EBOOK".to_string(),
+            license_key: "AAAA-BBBB".to_string(),
+            order_id: None,
+            order_item_id: None,
+            assigned_gmt: None,
+            created_gmt: Timestamp::EPOCH,
+        };
+        let shipment = Shipment {
+            id: 6,
+            mid: 1,
+            order_id: 9,
+            carrier: DIGITAL_CARRIER.to_string(),
+            tracking_number: String::new(),
+            shipped_gmt: Timestamp::from_unix(100),
+            label_provider: None,
+            label_id: None,
+            label_url: None,
+            label_cost: None,
+        };
+        let exec = || MockExecResult { last_insert_id: 0, rows_affected: 1 };
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![item(1, "EBOOK")]])
+            .append_query_results([vec![sku("EBOOK")]])
+            .append_query_results([vec![ebook()]])
+            .append_query_results([Vec::<ShipmentItem>::new()])
+            .append_query_results([vec![download]])
+            .append_query_results([vec![key]])
+            .append_exec_results([exec()])
+            .append_query_results([vec![shipment]])
+            .append_query_results([vec![ShipmentItem { id: 7, mid: 1, shipment_id: 6, order_item_id: 1, quantity: 1 }]])
+            .append_query_results([vec![order(Some(100))]])
+            .append_exec_results([exec(), exec()])
+            .into_connection();
+
+        let order = fulfill(&db, order(None)).await.unwrap();
+        assert_eq!(order.shipped_gmt, Some(Timestamp::from_unix(100)));
+
+        let statements: Vec<String> =
+            db.into_transaction_log().iter().map(|t| t.statements()[0].to_string()).collect();
+        assert!(statements[4].contains(r#""max_downloads""#), "{}", statements[4]);
+        assert!(statements[5].contains("FOR UPDATE SKIP LOCKED"), "{}", statements[5]);
+        assert!(statements[7].contains("'digital'"), "{}", statements[7]);
+        assert!(statements.last().unwrap().contains("'order.digital_delivered'"));
+    }
+}
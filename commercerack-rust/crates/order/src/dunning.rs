@@ -0,0 +1,431 @@
+//! Dunning: charging declined order payments again
+//!
+//! Orders paid later, on a tokenized payment method, can be declined long
+//! after checkout. When a charge the buyer asked to be retried is declined,
+//! [`DunningService::open`] starts a case for the order. The runner charges
+//! the order's open balance again on the days of the merchant's
+//! `dunning_retry_days`, counted from the first decline, until
+//!
+//! - a charge goes through, or the balance was paid some other way: the
+//!   case is `recovered`, and `order.paid` tells the merchant as usual,
+//! - the order is cancelled: the case is `closed`, or
+//! - the last retry is declined: the case is `suspended` and the order is
+//!   moved to the [`HOLD_POOL`] for the merchant to sort out.
+//!
+//! There is no mailer; each declined charge with a retry left raises
+//! `dunning.retry_scheduled` and a suspension `dunning.suspended`, for the
+//! merchant to email the buyer from their webhook. The store has no
+//! subscriptions, so suspending holds the order rather than a plan.
+
+use commercerack_core::Timestamp;
+use commercerack_events::{outbox, DomainEvent};
+use commercerack_merchant::settings::SettingsService;
+use commercerack_payment::{PaymentError, PaymentGateway};
+use chrono::Duration;
+use sea_orm::sea_query::{Expr, LockBehavior, LockType};
+use sea_orm::{entity::*, query::*, ConnectionTrait, DatabaseConnection, Set, TransactionTrait};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+use ::entity::dunning_cases::{self, Column};
+use ::entity::prelude::{DunningCase, DunningCases, Order as OrderModel, Orders};
+use tracing::{info, instrument, warn};
+
+use crate::payment::{OrderPaymentError, OrderPaymentService};
+use crate::pools::HOLD_POOL;
+use crate::OrderError;
+
+/// How long a claimed case is kept from other runners while it is retried
+const RETRY_LEASE: Duration = Duration::minutes(10);
+
+/// Cases retried per pass
+const RETRY_BATCH: u64 = 100;
+
+/// `dunning_cases.last_error` holds up to 255 characters
+const LAST_ERROR_MAX_LEN: usize = 255;
+
+/// Where a dunning case stands, stored in `dunning_cases.status`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DunningStatus {
+    /// Waiting for its next retry
+    Retrying,
+    /// The balance was paid
+    Recovered,
+    /// Every retry was declined; the order is on hold
+    Suspended,
+    /// The order was cancelled before the balance was paid
+    Closed,
+}
+
+impl DunningStatus {
+    pub const ALL: [DunningStatus; 4] =
+        [DunningStatus::Retrying, DunningStatus::Recovered, DunningStatus::Suspended, DunningStatus::Closed];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DunningStatus::Retrying => "retrying",
+            DunningStatus::Recovered => "recovered",
+            DunningStatus::Suspended => "suspended",
+            DunningStatus::Closed => "closed",
+        }
+    }
+}
+
+impl fmt::Display for DunningStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for DunningStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|status| status.as_str() == s)
+            .ok_or_else(|| format!("unknown dunning status {}", s))
+    }
+}
+
+/// When to charge a case opened at `opened` again after `attempts`
+/// declines, the first included; `None` once `schedule` has run out
+pub fn next_attempt(schedule: &[u32], opened: Timestamp, attempts: i32) -> Option<Timestamp> {
+    let retry = usize::try_from(attempts).ok()?.checked_sub(1)?;
+    schedule.get(retry).map(|days| opened + Duration::days((*days).into()))
+}
+
+/// A dunning case with the order it is collecting for
+#[derive(Debug, Clone, Serialize)]
+pub struct DunningCaseWithOrder {
+    pub case: DunningCase,
+    pub order: OrderModel,
+}
+
+/// Record a declined charge on `case` and schedule the next one, or
+/// suspend the case and hold the order when there is none left
+async fn declined<C: ConnectionTrait>(
+    conn: &C,
+    order: OrderModel,
+    mut case: dunning_cases::ActiveModel,
+    opened: Timestamp,
+    attempts: i32,
+    reason: &str,
+) -> Result<DunningCase, OrderError> {
+    let settings = SettingsService::get(conn, order.mid).await?;
+    let next = next_attempt(&settings.dunning_retry_days, opened, attempts);
+    let status = if next.is_some() { DunningStatus::Retrying } else { DunningStatus::Suspended };
+
+    case.status = Set(status.as_str().to_string());
+    case.attempts = Set(attempts);
+    case.next_attempt_gmt = Set(next);
+    case.last_error = Set(reason.chars().take(LAST_ERROR_MAX_LEN).collect());
+    case.modified_gmt = Set(Timestamp::now());
+    let case = case.save(conn).await?.try_into_model()?;
+
+    if next.is_some() {
+        outbox::record(conn, &DomainEvent::PaymentRetryScheduled { order, case: case.clone() }).await?;
+        return Ok(case);
+    }
+
+    let version = order.v;
+    let mut active: ::entity::orders::ActiveModel = order.into();
+    active.pool = Set(HOLD_POOL.to_string());
+    active.v = Set(version + 1);
+    let order = active.update(conn).await?;
+    outbox::record(conn, &DomainEvent::OrderUpdated(order.clone())).await?;
+    outbox::record(conn, &DomainEvent::DunningSuspended { order, case: case.clone() }).await?;
+    Ok(case)
+}
+
+/// Dunning case service
+pub struct DunningService;
+
+impl DunningService {
+    /// Start retrying an order whose charge to `payment_method` was just
+    /// declined for `reason`. An order already being retried keeps its case.
+    #[instrument(skip_all, fields(mid = mid, id = order_id))]
+    pub async fn open<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: i32,
+        order_id: i32,
+        payment_method: &str,
+        reason: &str,
+    ) -> Result<DunningCase, OrderError> {
+        let txn = db.begin().await?;
+        let order = Orders::find()
+            .filter(::entity::orders::Column::Mid.eq(mid))
+            .filter(::entity::orders::Column::Id.eq(order_id))
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .ok_or(OrderError::NotFound)?;
+        if order.cancelled_gmt.is_some() {
+            return Err(OrderError::Cancelled);
+        }
+
+        let open = DunningCases::find()
+            .filter(Column::Mid.eq(mid))
+            .filter(Column::OrderId.eq(order_id))
+            .filter(Column::Status.eq(DunningStatus::Retrying.as_str()))
+            .one(&txn)
+            .await?;
+        if let Some(case) = open {
+            return Ok(case);
+        }
+
+        let now = Timestamp::now();
+        let case = dunning_cases::ActiveModel {
+            mid: Set(mid),
+            order_id: Set(order_id),
+            payment_method: Set(payment_method.to_string()),
+            created_gmt: Set(now),
+            ..Default::default()
+        };
+        let case = declined(&txn, order, case, now, 1, reason).await?;
+        txn.commit().await?;
+        Ok(case)
+    }
+
+    /// Charge a case's order its open balance again, and move the case on
+    /// by how that went. Errors other than a decline leave the attempt
+    /// uncounted, to be tried again once the runner's lease runs out.
+    #[instrument(skip_all, fields(mid = case.mid, case = case.id))]
+    pub async fn retry(
+        db: &DatabaseConnection,
+        gateway: &dyn PaymentGateway,
+        case: DunningCase,
+    ) -> Result<DunningCase, OrderError> {
+        let charged =
+            OrderPaymentService::add_payment(db, gateway, case.mid, case.order_id, &case.payment_method, None, true)
+                .await;
+        let reason = match charged {
+            // Nothing left to pay means someone paid it meanwhile
+            Ok(_) | Err(OrderPaymentError::InvalidState(_)) => {
+                return Self::finish(db, case, DunningStatus::Recovered).await
+            }
+            Err(OrderPaymentError::Cancelled | OrderPaymentError::NotApproved) => {
+                return Self::finish(db, case, DunningStatus::Closed).await
+            }
+            Err(OrderPaymentError::Gateway(PaymentError::Declined(reason))) => reason,
+            Err(e) => return Err(e.into()),
+        };
+
+        let txn = db.begin().await?;
+        let order = Orders::find()
+            .filter(::entity::orders::Column::Mid.eq(case.mid))
+            .filter(::entity::orders::Column::Id.eq(case.order_id))
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .ok_or(OrderError::NotFound)?;
+        let (opened, attempts) = (case.created_gmt, case.attempts + 1);
+        let case = declined(&txn, order, case.into(), opened, attempts, &reason).await?;
+        txn.commit().await?;
+        Ok(case)
+    }
+
+    async fn finish(
+        db: &DatabaseConnection,
+        case: DunningCase,
+        status: DunningStatus,
+    ) -> Result<DunningCase, OrderError> {
+        let mut active: dunning_cases::ActiveModel = case.into();
+        active.status = Set(status.as_str().to_string());
+        active.next_attempt_gmt = Set(None);
+        active.modified_gmt = Set(Timestamp::now());
+        Ok(active.update(db).await?)
+    }
+
+    /// Retry every merchant's cases that are due by `now`, up to `limit`.
+    /// Claimed cases are pushed back by a lease first, so runners in other
+    /// processes skip them. Returns how many were retried.
+    #[instrument(skip_all)]
+    pub async fn run_due(
+        db: &DatabaseConnection,
+        gateway: &dyn PaymentGateway,
+        now: Timestamp,
+        limit: u64,
+    ) -> Result<usize, OrderError> {
+        let txn = db.begin().await?;
+        let due = DunningCases::find()
+            .filter(Column::Status.eq(DunningStatus::Retrying.as_str()))
+            .filter(Column::NextAttemptGmt.lte(now))
+            .order_by_asc(Column::NextAttemptGmt)
+            .limit(limit)
+            .lock_with_behavior(LockType::Update, LockBehavior::SkipLocked)
+            .all(&txn)
+            .await?;
+        if due.is_empty() {
+            return Ok(0);
+        }
+        DunningCases::update_many()
+            .col_expr(Column::NextAttemptGmt, Expr::value(now + RETRY_LEASE))
+            .filter(Column::Id.is_in(due.iter().map(|case| case.id)))
+            .exec(&txn)
+            .await?;
+        txn.commit().await?;
+
+        let mut retried = 0;
+        for case in due {
+            let id = case.id;
+            match Self::retry(db, gateway, case).await {
+                Ok(_) => retried += 1,
+                Err(e) => warn!(case = id, error = %e, "dunning retry failed"),
+            }
+        }
+        Ok(retried)
+    }
+
+    /// Retry due cases every `interval` on a background task
+    pub fn spawn_runner(
+        db: Arc<DatabaseConnection>,
+        gateway: Arc<dyn PaymentGateway>,
+        interval: std::time::Duration,
+    ) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                match Self::run_due(db.as_ref(), gateway.as_ref(), Timestamp::now(), RETRY_BATCH).await {
+                    Ok(0) => {}
+                    Ok(n) => info!("💳 Retried {} declined payments", n),
+                    Err(e) => warn!("Dunning pass failed: {}", e),
+                }
+            }
+        })
+    }
+
+    /// A merchant's dunning cases, optionally only those in `status`,
+    /// soonest retry first and then newest
+    pub async fn list<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        status: Option<DunningStatus>,
+    ) -> Result<Vec<DunningCaseWithOrder>, OrderError> {
+        let mut query = DunningCases::find().filter(Column::Mid.eq(mid));
+        if let Some(status) = status {
+            query = query.filter(Column::Status.eq(status.as_str()));
+        }
+        let cases = query
+            .order_by_asc(Column::NextAttemptGmt)
+            .order_by_desc(Column::Id)
+            .all(db)
+            .await?;
+
+        let orders: HashMap<i32, OrderModel> = Orders::find()
+            .filter(::entity::orders::Column::Mid.eq(mid))
+            .filter(::entity::orders::Column::Id.is_in(cases.iter().map(|case| case.order_id)))
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|order| (order.id, order))
+            .collect();
+
+        // Cases go when their order is deleted, so each has one
+        Ok(cases
+            .into_iter()
+            .filter_map(|case| Some(DunningCaseWithOrder { order: orders.get(&case.order_id)?.clone(), case }))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+    use sea_orm::{DatabaseBackend, MockDatabase};
+
+    fn order() -> OrderModel {
+        OrderModel {
+            id: 9,
+            mid: 1,
+            orderid: "2025-11-18-00000009".to_string(),
+            cartid: String::new(),
+            customer: 7,
+            pool: "RECENT".to_string(),
+            total: Decimal::new(5000, 2),
+            created_gmt: Timestamp::EPOCH,
+            paid_gmt: None,
+            paid_txn: None,
+            order_payment_status: Some("declined".to_string()),
+            order_payment_lookup: None,
+            bs_settlement: None,
+            shipped_gmt: None,
+            inv_gmt: None,
+            company_id: None,
+            review_status: None,
+            ship_method: None,
+            bill_email: "buyer@example.com".to_string(),
+            v: 2,
+            mkt: 0,
+            erefid: None,
+            mkt_bitstr: String::new(),
+            pickup_location_id: None,
+            pickup_ready_gmt: None,
+            picked_up_gmt: None,
+            cancelled_gmt: None,
+            cancel_reason: None,
+        }
+    }
+
+    #[test]
+    fn test_status_round_trips() {
+        for status in DunningStatus::ALL {
+            assert_eq!(status.as_str().parse::<DunningStatus>().unwrap(), status);
+        }
+        assert!("paused".parse::<DunningStatus>().is_err());
+    }
+
+    #[test]
+    fn test_next_attempt_counts_from_the_first_decline() {
+        let opened = Timestamp::from_unix(1_700_000_000);
+        let day = 24 * 60 * 60;
+        let schedule = [1, 3, 7];
+
+        assert_eq!(next_attempt(&schedule, opened, 1), Some(Timestamp::from_unix(1_700_000_000 + day)));
+        assert_eq!(next_attempt(&schedule, opened, 3), Some(Timestamp::from_unix(1_700_000_000 + 7 * day)));
+        assert_eq!(next_attempt(&schedule, opened, 4), None);
+        assert_eq!(next_attempt(&schedule, opened, 0), None);
+        assert_eq!(next_attempt(&[], opened, 1), None);
+    }
+
+    #[tokio::test]
+    async fn test_open_keeps_a_case_already_retrying() {
+        let retrying = DunningCase {
+            id: 3,
+            mid: 1,
+            order_id: 9,
+            payment_method: "pm_old".to_string(),
+            status: DunningStatus::Retrying.as_str().to_string(),
+            attempts: 2,
+            next_attempt_gmt: Some(Timestamp::from_unix(1_700_000_000)),
+            last_error: "insufficient funds".to_string(),
+            created_gmt: Timestamp::EPOCH,
+            modified_gmt: Timestamp::EPOCH,
+        };
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![order()]])
+            .append_query_results([vec![retrying.clone()]])
+            .into_connection();
+
+        let case = DunningService::open(&db, 1, 9, "pm_new", "card_declined").await.unwrap();
+        assert_eq!(case, retrying);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_orders_are_not_retried() {
+        let cancelled = OrderModel { cancelled_gmt: Some(Timestamp::EPOCH), ..order() };
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![cancelled]])
+            .into_connection();
+
+        assert!(matches!(
+            DunningService::open(&db, 1, 9, "pm_card", "card_declined").await,
+            Err(OrderError::Cancelled)
+        ));
+    }
+}
@@ -0,0 +1,189 @@
+//! Duplicate order detection
+//!
+//! A double-clicked pay button or a retried checkout can place the same
+//! order twice. An order from the same customer (for guests, the same
+//! billing email) with the same products and total as one placed shortly
+//! before is still created, but lands in the review queue with review
+//! status [`DUPLICATE_REVIEW_STATUS`] for the merchant to cancel or release.
+
+use chrono::Duration;
+use sea_orm::sea_query::{Expr, Func};
+use sea_orm::{entity::*, query::*, ConnectionTrait, DbErr, Set};
+use ::entity::prelude::{Order as OrderModel, OrderItem, OrderItems, Orders};
+use std::sync::OnceLock;
+use tracing::{instrument, warn};
+
+use crate::GUEST_CUSTOMER;
+
+/// Review status of orders held as likely duplicates
+pub const DUPLICATE_REVIEW_STATUS: &str = "DUP";
+
+/// Window used until [`configure`] is called
+pub const DEFAULT_WINDOW_MINUTES: i64 = 10;
+
+static WINDOW_MINUTES: OnceLock<i64> = OnceLock::new();
+
+/// Set how many minutes apart two orders may be and still count as
+/// duplicates; 0 turns detection off. Only the first call takes effect.
+pub fn configure(window_minutes: i64) {
+    let _ = WINDOW_MINUTES.set(window_minutes);
+}
+
+fn window_minutes() -> i64 {
+    WINDOW_MINUTES.get().copied().unwrap_or(DEFAULT_WINDOW_MINUTES)
+}
+
+/// Product lines by SKU and quantity, in a fixed order. Coupon, tax,
+/// shipping and gift card lines (`%` SKUs) only count through the total.
+fn contents(items: &[OrderItem], order_id: i32) -> Vec<(&str, i32)> {
+    let mut lines: Vec<(&str, i32)> = items
+        .iter()
+        .filter(|item| item.order_id == order_id && !item.sku.starts_with('%'))
+        .map(|item| (item.sku.as_str(), item.quantity))
+        .collect();
+    lines.sort_unstable();
+    lines
+}
+
+/// The earlier order that `order`, with line items `items`, duplicates
+pub async fn find_original<C: ConnectionTrait>(
+    db: &C,
+    order: &OrderModel,
+    items: &[OrderItem],
+) -> Result<Option<OrderModel>, DbErr> {
+    use ::entity::orders::Column;
+
+    let window = window_minutes();
+    let email = order.bill_email.trim();
+    let guest = order.customer == GUEST_CUSTOMER;
+    if window <= 0 || (guest && email.is_empty()) {
+        return Ok(None);
+    }
+
+    let mut candidates = Orders::find()
+        .filter(Column::Mid.eq(order.mid))
+        .filter(Column::Id.ne(order.id))
+        .filter(Column::Customer.eq(order.customer))
+        .filter(Column::Total.eq(order.total))
+        .filter(Column::CreatedGmt.gte(order.created_gmt - Duration::minutes(window)));
+    if guest {
+        candidates = candidates.filter(Expr::expr(Func::lower(Expr::col(Column::BillEmail))).eq(email.to_lowercase()));
+    }
+    let candidates = candidates.order_by_asc(Column::Id).all(db).await?;
+    if candidates.is_empty() {
+        return Ok(None);
+    }
+
+    let their_items = OrderItems::find()
+        .filter(::entity::order_items::Column::Mid.eq(order.mid))
+        .filter(::entity::order_items::Column::OrderId.is_in(candidates.iter().map(|candidate| candidate.id)))
+        .all(db)
+        .await?;
+    let wanted = contents(items, order.id);
+    Ok(candidates
+        .into_iter()
+        .find(|candidate| contents(&their_items, candidate.id) == wanted))
+}
+
+/// Hold a just-placed order for review if it duplicates an earlier one.
+/// Run it in the transaction that placed the order, once its billing email
+/// is set.
+#[instrument(skip_all, fields(mid = order.mid, order = order.id))]
+pub(crate) async fn flag_if_duplicate<C: ConnectionTrait>(
+    db: &C,
+    order: OrderModel,
+    items: &[OrderItem],
+) -> Result<OrderModel, DbErr> {
+    let Some(original) = find_original(db, &order, items).await? else {
+        return Ok(order);
+    };
+
+    warn!(original = original.id, "holding likely duplicate order for review");
+    let mut active: ::entity::orders::ActiveModel = order.into();
+    active.review_status = Set(Some(DUPLICATE_REVIEW_STATUS.to_string()));
+    active.update(db).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+    use sea_orm::{DatabaseBackend, MockDatabase};
+    use commercerack_core::Timestamp;
+
+    fn order(id: i32, created_gmt: i64) -> OrderModel {
+        OrderModel {
+            id,
+            mid: 1,
+            orderid: format!("2025-11-18-{}", id),
+            cartid: format!("cart-{}", id),
+            customer: 42,
+            pool: "RECENT".to_string(),
+            total: Decimal::new(2500, 2),
+            created_gmt: Timestamp::from_unix(created_gmt),
+            paid_gmt: None,
+            paid_txn: None,
+            order_payment_status: None,
+            order_payment_lookup: None,
+            bs_settlement: None,
+            shipped_gmt: None,
+            inv_gmt: None,
+            company_id: None,
+            review_status: None,
+            ship_method: None,
+            bill_email: String::new(),
+            v: 0,
+            mkt: 0,
+            erefid: None,
+            mkt_bitstr: String::new(),
+            pickup_location_id: None,
+            pickup_ready_gmt: None,
+            picked_up_gmt: None,
+            cancelled_gmt: None,
+            cancel_reason: None,
+        }
+    }
+
+    fn item(order_id: i32, sku: &str, quantity: i32) -> OrderItem {
+        OrderItem {
+            id: 0,
+            mid: 1,
+            order_id,
+            sku: sku.to_string(),
+            product_name: String::new(),
+            quantity,
+            unit_price: Decimal::ZERO,
+            options: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_contents_ignore_line_order_and_charges() {
+        let first = vec![item(1, "B", 1), item(1, "A", 2), item(1, "%TAX", 1)];
+        let second = vec![item(2, "A", 2), item(2, "%SHIP", 1), item(2, "B", 1)];
+        assert_eq!(contents(&first, 1), contents(&second, 2));
+        assert_ne!(contents(&first, 1), contents(&[item(2, "A", 1), item(2, "B", 1)], 2));
+    }
+
+    #[tokio::test]
+    async fn test_same_items_and_total_is_a_duplicate() {
+        let items = vec![item(8, "A", 2)];
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![order(5, 1_700_000_000), order(7, 1_700_000_100)]])
+            .append_query_results([vec![item(5, "A", 1), item(7, "A", 2)]])
+            .into_connection();
+
+        let original = find_original(&db, &order(8, 1_700_000_200), &items).await.unwrap();
+        assert_eq!(original.map(|order| order.id), Some(7));
+    }
+
+    #[tokio::test]
+    async fn test_guest_without_email_is_not_checked() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres).into_connection();
+        let mut guest = order(8, 1_700_000_000);
+        guest.customer = GUEST_CUSTOMER;
+
+        assert!(find_original(&db, &guest, &[]).await.unwrap().is_none());
+        assert!(db.into_transaction_log().is_empty());
+    }
+}
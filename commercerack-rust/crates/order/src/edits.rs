@@ -0,0 +1,387 @@
+//! Editing placed orders
+//!
+//! Until any of it ships, a merchant can change an order: add line items,
+//! change their quantities, remove them, and take money off with a manual
+//! discount, a negative `%DISCOUNT` line item. A batch of [`OrderEdit`]s is
+//! applied in one transaction that also
+//!
+//! - moves stock with the quantities, recorded as `edit` adjustments,
+//! - rescales each `%TAX` line to the new taxable amount, keeping its
+//!   effective rate, since orders don't keep the address they were taxed
+//!   for, and
+//! - recomputes the total and, for orders with payments, the payment
+//!   status, so an edit to a paid order leaves a balance to collect or a
+//!   refund to give.
+//!
+//! Shipping, coupon and gift card lines stay as they were placed, and the
+//! [fulfillment groups](crate::fulfillment_groups) planned at checkout are
+//! not planned again.
+
+use commercerack_events::{outbox, DomainEvent};
+use commercerack_inventory::InventoryService;
+use rust_decimal::Decimal;
+use sea_orm::{entity::*, query::*, ConnectionTrait, DatabaseTransaction, DbErr, Set, TransactionTrait};
+use ::entity::prelude::{Order as OrderModel, OrderItem, OrderItems, Orders};
+use tracing::instrument;
+
+use crate::checkout::{COUPON_SKU, TAX_SKU};
+use crate::items::{insert_items, line_total, NewOrderItem, OrderItemService};
+use crate::payment::{derive_status, OrderPaymentService, PaymentStatus};
+use crate::pools::{ARCHIVE_POOL, COMPLETED_POOL};
+use crate::shipments::{has_shipments, is_shippable};
+use crate::{digital, OrderError, OrderWithItems};
+
+/// SKU of the line items carrying manual discounts
+pub const DISCOUNT_SKU: &str = "%DISCOUNT";
+
+/// One change to a placed order
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrderEdit {
+    /// Add a product line
+    AddItem(NewOrderItem),
+    /// Change how many of a product line the buyer gets
+    SetQuantity { item_id: i32, quantity: i32 },
+    /// Remove a product line or a manual discount
+    RemoveItem { item_id: i32 },
+    /// Take `amount` off the order, shown to the buyer as `reason`
+    Discount { amount: Decimal, reason: String },
+}
+
+impl OrderEdit {
+    /// What the edit does, as recorded in the audit log
+    pub fn action(&self) -> &'static str {
+        match self {
+            OrderEdit::AddItem(_) => "add_item",
+            OrderEdit::SetQuantity { .. } => "set_quantity",
+            OrderEdit::RemoveItem { .. } => "remove_item",
+            OrderEdit::Discount { .. } => "discount",
+        }
+    }
+}
+
+/// An edit and the line item it changed, before and after
+#[derive(Debug, Clone)]
+pub struct AppliedEdit {
+    pub edit: OrderEdit,
+    /// `None` for lines the edit added
+    pub before: Option<OrderItem>,
+    /// `None` for lines the edit removed
+    pub after: Option<OrderItem>,
+}
+
+/// An edited order and what each of its edits did
+#[derive(Debug, Clone)]
+pub struct EditedOrder {
+    pub order: OrderWithItems,
+    pub edits: Vec<AppliedEdit>,
+}
+
+/// Whether an edit may change `item`: product lines and manual discounts
+fn is_editable(item: &OrderItem) -> bool {
+    is_shippable(item) || item.sku == DISCOUNT_SKU
+}
+
+/// The amount of `items` tax is charged on: products less coupon and
+/// manual discounts
+pub fn taxable(items: &[OrderItem]) -> Decimal {
+    items
+        .iter()
+        .filter(|item| is_shippable(item) || item.sku == COUPON_SKU || item.sku == DISCOUNT_SKU)
+        .map(line_total)
+        .sum()
+}
+
+/// A tax of `tax` on `before`, charged instead on `after` at the same rate
+pub fn rescale_tax(tax: Decimal, before: Decimal, after: Decimal) -> Decimal {
+    if before <= Decimal::ZERO {
+        return tax;
+    }
+    if after <= Decimal::ZERO {
+        return Decimal::ZERO;
+    }
+    (tax * after / before).round_dp(2)
+}
+
+/// Add `quantity` units of `sku` to the net stock change of an edit
+fn move_stock(stock: &mut Vec<(String, i32)>, sku: &str, quantity: i32) {
+    match stock.iter_mut().find(|(moved, _)| moved == sku) {
+        Some((_, units)) => *units += quantity,
+        None => stock.push((sku.to_string(), quantity)),
+    }
+}
+
+/// Position of line `item_id` among `items`, if an edit may change it
+fn editable_line(items: &[OrderItem], item_id: i32, allow: fn(&OrderItem) -> bool) -> Result<usize, OrderError> {
+    let index = items.iter().position(|item| item.id == item_id).ok_or(OrderError::ItemNotFound)?;
+    if !allow(&items[index]) {
+        return Err(OrderError::ItemNotEditable);
+    }
+    Ok(index)
+}
+
+/// Order editing service
+pub struct OrderEditService;
+
+impl OrderEditService {
+    /// Refuse orders that were cancelled, shipped, are done or were refunded
+    async fn check_editable(txn: &DatabaseTransaction, order: &OrderModel) -> Result<(), OrderError> {
+        if order.cancelled_gmt.is_some() {
+            return Err(OrderError::NotEditable("it has been cancelled"));
+        }
+        if order.shipped_gmt.is_some() || order.picked_up_gmt.is_some() {
+            return Err(OrderError::NotEditable("it has shipped"));
+        }
+        if order.pool == COMPLETED_POOL || order.pool == ARCHIVE_POOL {
+            return Err(OrderError::NotEditable("it is complete"));
+        }
+        if matches!(PaymentStatus::of(order), Some(PaymentStatus::Refunded | PaymentStatus::PartiallyRefunded)) {
+            return Err(OrderError::NotEditable("it has been refunded"));
+        }
+        if has_shipments(txn, order).await? {
+            return Err(OrderError::NotEditable("part of it has shipped"));
+        }
+        Ok(())
+    }
+
+    /// Apply `edits` to an order in the order given. `v` must be the
+    /// version the order was read at; if it has changed since, nothing is
+    /// written and the error carries its current state.
+    #[instrument(skip_all, fields(mid = mid, id = id, v = v))]
+    pub async fn apply<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: i32,
+        id: i32,
+        v: i32,
+        edits: Vec<OrderEdit>,
+    ) -> Result<EditedOrder, OrderError> {
+        let txn = db.begin().await?;
+        let order = Orders::find()
+            .filter(::entity::orders::Column::Mid.eq(mid))
+            .filter(::entity::orders::Column::Id.eq(id))
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .ok_or(OrderError::NotFound)?;
+        if order.v != v {
+            return Err(OrderError::VersionConflict(Box::new(order)));
+        }
+        Self::check_editable(&txn, &order).await?;
+
+        let mut items = OrderItemService::list(&txn, mid, id).await?;
+        let taxable_before = taxable(&items);
+        let mut stock: Vec<(String, i32)> = Vec::new();
+        let mut applied = Vec::with_capacity(edits.len());
+        for edit in edits {
+            let (before, after) = match &edit {
+                OrderEdit::AddItem(item) => {
+                    if item.sku.starts_with('%') {
+                        return Err(OrderError::ItemNotEditable);
+                    }
+                    let added = Self::insert(&txn, mid, id, item).await?;
+                    move_stock(&mut stock, &added.sku, added.quantity);
+                    items.push(added.clone());
+                    (None, Some(added))
+                }
+                OrderEdit::SetQuantity { item_id, quantity } => {
+                    let index = editable_line(&items, *item_id, is_shippable)?;
+                    let before = items[index].clone();
+                    let mut active: ::entity::order_items::ActiveModel = before.clone().into();
+                    active.quantity = Set(*quantity);
+                    let after = active.update(&txn).await?;
+                    move_stock(&mut stock, &after.sku, after.quantity - before.quantity);
+                    items[index] = after.clone();
+                    (Some(before), Some(after))
+                }
+                OrderEdit::RemoveItem { item_id } => {
+                    let index = editable_line(&items, *item_id, is_editable)?;
+                    let before = items.remove(index);
+                    OrderItems::delete_by_id(before.id).exec(&txn).await?;
+                    if is_shippable(&before) {
+                        move_stock(&mut stock, &before.sku, -before.quantity);
+                    }
+                    (Some(before), None)
+                }
+                OrderEdit::Discount { amount, reason } => {
+                    let reason = reason.trim();
+                    let line = NewOrderItem {
+                        sku: DISCOUNT_SKU.to_string(),
+                        product_name: if reason.is_empty() { "Discount".to_string() } else { reason.to_string() },
+                        quantity: 1,
+                        unit_price: -*amount,
+                        options: Default::default(),
+                    };
+                    let added = Self::insert(&txn, mid, id, &line).await?;
+                    items.push(added.clone());
+                    (None, Some(added))
+                }
+            };
+            applied.push(AppliedEdit { edit, before, after });
+        }
+
+        let taxable_after = taxable(&items);
+        for item in items.iter_mut().filter(|item| item.sku == TAX_SKU) {
+            let amount = rescale_tax(item.unit_price, taxable_before, taxable_after);
+            if amount != item.unit_price {
+                let mut active: ::entity::order_items::ActiveModel = item.clone().into();
+                active.unit_price = Set(amount);
+                *item = active.update(&txn).await?;
+            }
+        }
+
+        let changes: Vec<(&str, i32)> = stock
+            .iter()
+            .filter(|(_, quantity)| *quantity != 0)
+            .map(|(sku, quantity)| (sku.as_str(), *quantity))
+            .collect();
+        InventoryService::adjust_order(&txn, mid, &order.cartid, id, &changes).await?;
+
+        let total: Decimal = items.iter().map(line_total).sum();
+        if total < Decimal::ZERO {
+            return Err(OrderError::NegativeTotal);
+        }
+        let order = Self::retotal(&txn, order, total).await?;
+        txn.commit().await?;
+
+        Ok(EditedOrder { order: OrderWithItems { order, items }, edits: applied })
+    }
+
+    async fn insert(txn: &DatabaseTransaction, mid: i32, id: i32, item: &NewOrderItem) -> Result<OrderItem, OrderError> {
+        insert_items(txn, mid, id, std::slice::from_ref(item))
+            .await?
+            .pop()
+            .ok_or_else(|| DbErr::RecordNotInserted.into())
+    }
+
+    /// Save the order's new total, bringing its payment status in line
+    /// with what its payments now cover. Marketplace orders without
+    /// payments keep the status they were imported with.
+    async fn retotal(txn: &DatabaseTransaction, order: OrderModel, total: Decimal) -> Result<OrderModel, OrderError> {
+        let payments = OrderPaymentService::payments(txn, order.mid, order.id).await?;
+        let status = if payments.is_empty() {
+            PaymentStatus::of(&order)
+        } else {
+            derive_status(total, &payments)
+        };
+        let paid = status == Some(PaymentStatus::Paid) && PaymentStatus::of(&order) != status;
+
+        let mut active: ::entity::orders::ActiveModel = order.into();
+        active.total = Set(total);
+        if !payments.is_empty() {
+            active.order_payment_status = Set(status.map(|status| status.code().to_string()));
+        }
+        if paid {
+            active.paid_gmt = Set(Some(commercerack_core::Timestamp::now()));
+        }
+        let order = active.update(txn).await?;
+
+        outbox::record(txn, &DomainEvent::OrderUpdated(order.clone())).await?;
+        if paid {
+            // Taking items off can leave what was already paid covering the rest
+            outbox::record(txn, &DomainEvent::OrderPaid(order.clone())).await?;
+            return Ok(digital::fulfill(txn, order).await?);
+        }
+        Ok(order)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use commercerack_core::Timestamp;
+    use sea_orm::{DatabaseBackend, MockDatabase};
+
+    fn item(id: i32, sku: &str, quantity: i32, unit_price: i64) -> OrderItem {
+        OrderItem {
+            id,
+            mid: 1,
+            order_id: 9,
+            sku: sku.to_string(),
+            product_name: sku.to_string(),
+            quantity,
+            unit_price: Decimal::new(unit_price, 2),
+            options: String::new(),
+        }
+    }
+
+    fn order(shipped: bool, v: i32) -> OrderModel {
+        OrderModel {
+            id: 9,
+            mid: 1,
+            orderid: "2025-11-18-00000009".to_string(),
+            cartid: "cart".to_string(),
+            customer: 7,
+            pool: "RECENT".to_string(),
+            total: Decimal::new(5000, 2),
+            created_gmt: Timestamp::EPOCH,
+            paid_gmt: None,
+            paid_txn: None,
+            order_payment_status: None,
+            order_payment_lookup: None,
+            bs_settlement: None,
+            shipped_gmt: shipped.then_some(Timestamp::EPOCH),
+            inv_gmt: None,
+            company_id: None,
+            review_status: None,
+            ship_method: None,
+            bill_email: "buyer@example.com".to_string(),
+            v,
+            mkt: 0,
+            erefid: None,
+            mkt_bitstr: String::new(),
+            pickup_location_id: None,
+            pickup_ready_gmt: None,
+            picked_up_gmt: None,
+            cancelled_gmt: None,
+            cancel_reason: None,
+        }
+    }
+
+    #[test]
+    fn test_tax_follows_the_taxable_amount() {
+        let items = [
+            item(1, "WIDGET", 2, 2000),
+            item(2, COUPON_SKU, 1, -500),
+            item(3, DISCOUNT_SKU, 1, -300),
+            item(4, TAX_SKU, 1, 320),
+            item(5, "%SHIP", 1, 799),
+            item(6, "%GIFTCARD", 1, -1000),
+        ];
+        assert_eq!(taxable(&items), Decimal::new(3200, 2));
+
+        // 10% of 32.00, then of 16.00 once half of it is taken off
+        assert_eq!(rescale_tax(Decimal::new(320, 2), Decimal::new(3200, 2), Decimal::new(1600, 2)), Decimal::new(160, 2));
+        assert_eq!(rescale_tax(Decimal::new(333, 2), Decimal::new(3000, 2), Decimal::new(1000, 2)), Decimal::new(111, 2));
+        assert_eq!(rescale_tax(Decimal::new(320, 2), Decimal::new(3200, 2), Decimal::ZERO), Decimal::ZERO);
+    }
+
+    #[tokio::test]
+    async fn test_only_current_unshipped_orders_are_edited() {
+        let edits = vec![OrderEdit::RemoveItem { item_id: 1 }];
+
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![order(false, 4)]])
+            .into_connection();
+        match OrderEditService::apply(&db, 1, 9, 3, edits.clone()).await {
+            Err(OrderError::VersionConflict(current)) => assert_eq!(current.v, 4),
+            other => panic!("expected a version conflict, got {:?}", other),
+        }
+
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![order(true, 4)]])
+            .into_connection();
+        assert!(matches!(
+            OrderEditService::apply(&db, 1, 9, 4, edits).await,
+            Err(OrderError::NotEditable("it has shipped"))
+        ));
+    }
+
+    #[test]
+    fn test_only_product_lines_and_discounts_are_edited() {
+        let items = [item(1, "WIDGET", 1, 1000), item(2, TAX_SKU, 1, 100), item(3, DISCOUNT_SKU, 1, -200)];
+        assert_eq!(editable_line(&items, 1, is_shippable).unwrap(), 0);
+        assert_eq!(editable_line(&items, 3, is_editable).unwrap(), 2);
+        assert!(matches!(editable_line(&items, 2, is_editable), Err(OrderError::ItemNotEditable)));
+        assert!(matches!(editable_line(&items, 3, is_shippable), Err(OrderError::ItemNotEditable)));
+        assert!(matches!(editable_line(&items, 4, is_editable), Err(OrderError::ItemNotFound)));
+    }
+}
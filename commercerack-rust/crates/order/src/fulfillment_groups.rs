@@ -0,0 +1,108 @@
+//! Fulfillment groups of an order
+//!
+//! An order whose units are stocked in several warehouses is planned at
+//! checkout as several shipments (see `commercerack_shipping::split`), each
+//! with its own shipping method and cost and its own `%SHIP` line item.
+//! The plan is kept as the order's fulfillment groups, one per warehouse,
+//! listing the SKUs and quantities the warehouse is to send. Groups record
+//! what was planned and charged; the shipments actually sent are recorded
+//! separately (see [`crate::shipments`]).
+
+use commercerack_core::Timestamp;
+use commercerack_shipping::QuotedShipment;
+use rust_decimal::Decimal;
+use sea_orm::{entity::*, query::*, ConnectionTrait, DbErr, Set};
+use serde::Serialize;
+use ::entity::prelude::{
+    OrderFulfillmentGroup, OrderFulfillmentGroupItem, OrderFulfillmentGroupItems, OrderFulfillmentGroups,
+};
+
+/// A fulfillment group together with the units it ships
+#[derive(Debug, Clone, Serialize)]
+pub struct FulfillmentGroup {
+    pub group: OrderFulfillmentGroup,
+    pub items: Vec<OrderFulfillmentGroupItem>,
+}
+
+/// Record the shipments an order was placed with, charging nothing for
+/// them when `free`. Run it in the checkout transaction.
+pub(crate) async fn record<C: ConnectionTrait>(
+    conn: &C,
+    mid: i32,
+    order_id: i32,
+    shipments: &[QuotedShipment],
+    free: bool,
+) -> Result<Vec<FulfillmentGroup>, DbErr> {
+    let now = Timestamp::now();
+    let mut groups = Vec::with_capacity(shipments.len());
+    for quoted in shipments {
+        let group = ::entity::order_fulfillment_groups::ActiveModel {
+            mid: Set(mid),
+            order_id: Set(order_id),
+            warehouse_id: Set(quoted.shipment.warehouse_id),
+            carrier: Set(quoted.quote.carrier.clone()),
+            ship_method: Set(quoted.quote.method.clone()),
+            ship_name: Set(quoted.quote.name.clone()),
+            shipping: Set(if free { Decimal::ZERO } else { quoted.quote.amount }),
+            created_gmt: Set(now),
+            ..Default::default()
+        }
+        .insert(conn)
+        .await?;
+
+        let mut items = Vec::with_capacity(quoted.shipment.lines.len());
+        for (sku, quantity) in &quoted.shipment.lines {
+            let item = ::entity::order_fulfillment_group_items::ActiveModel {
+                mid: Set(mid),
+                group_id: Set(group.id),
+                sku: Set(sku.clone()),
+                quantity: Set(*quantity),
+                ..Default::default()
+            }
+            .insert(conn)
+            .await?;
+            items.push(item);
+        }
+        groups.push(FulfillmentGroup { group, items });
+    }
+    Ok(groups)
+}
+
+/// Fulfillment group service
+pub struct FulfillmentGroupService;
+
+impl FulfillmentGroupService {
+    /// An order's fulfillment groups in the order they were planned; none
+    /// for orders placed without a shipping method
+    pub async fn for_order<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        order_id: i32,
+    ) -> Result<Vec<FulfillmentGroup>, DbErr> {
+        let groups = OrderFulfillmentGroups::find()
+            .filter(::entity::order_fulfillment_groups::Column::Mid.eq(mid))
+            .filter(::entity::order_fulfillment_groups::Column::OrderId.eq(order_id))
+            .order_by_asc(::entity::order_fulfillment_groups::Column::Id)
+            .all(db)
+            .await?;
+        if groups.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut lines = OrderFulfillmentGroupItems::find()
+            .filter(::entity::order_fulfillment_group_items::Column::Mid.eq(mid))
+            .filter(::entity::order_fulfillment_group_items::Column::GroupId.is_in(groups.iter().map(|g| g.id)))
+            .order_by_asc(::entity::order_fulfillment_group_items::Column::Id)
+            .all(db)
+            .await?;
+
+        Ok(groups
+            .into_iter()
+            .map(|group| {
+                let (items, rest) = lines.drain(..).partition(|line| line.group_id == group.id);
+                lines = rest;
+                FulfillmentGroup { group, items }
+            })
+            .collect())
+    }
+}
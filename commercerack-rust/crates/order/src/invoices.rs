@@ -0,0 +1,692 @@
+//! Order invoices and credit memos
+//!
+//! An order's invoice is rendered to PDF the first time it is asked for
+//! and stored, so every later download is the same document; the order's
+//! `inv_gmt` records when it was issued. Each refund issues a credit memo
+//! for the amount given back, in the refund's transaction. Both carry the
+//! merchant's branding from `invoice_branding`.
+
+use commercerack_core::{Currency, Money, Timestamp};
+use commercerack_merchant::settings::SettingsService;
+use commercerack_merchant::MerchantError;
+use rust_decimal::Decimal;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{entity::*, query::*, ConnectionTrait, DbErr, Set, TransactionTrait};
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+use ::entity::prelude::{
+    Invoice, InvoiceBranding, InvoiceBrandings, Invoices, Order as OrderModel, OrderItem, OrderPayment, Orders,
+};
+use tracing::instrument;
+
+use crate::checkout::{COUPON_SKU, GIFT_CARD_SKU, SHIP_SKU, TAX_SKU};
+use crate::items::OrderItemService;
+use crate::payment::{self, OrderPaymentService};
+use crate::pdf::{self, Document, Font, Page, Rgb, PAGE_HEIGHT, PAGE_WIDTH};
+
+#[derive(Error, Debug)]
+pub enum InvoiceError {
+    #[error("Order not found")]
+    OrderNotFound,
+
+    #[error("Invalid branding: {0}")]
+    InvalidBranding(&'static str),
+
+    #[error(transparent)]
+    Merchant(#[from] MerchantError),
+
+    #[error("Database error: {0}")]
+    Db(#[from] DbErr),
+}
+
+/// What a document in `invoices` is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvoiceKind {
+    Invoice,
+    /// Money given back by a refund
+    CreditMemo,
+}
+
+impl InvoiceKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InvoiceKind::Invoice => "invoice",
+            InvoiceKind::CreditMemo => "credit_memo",
+        }
+    }
+}
+
+impl fmt::Display for InvoiceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for InvoiceKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "invoice" => Ok(InvoiceKind::Invoice),
+            "credit_memo" => Ok(InvoiceKind::CreditMemo),
+            other => Err(format!("unknown invoice kind {}", other)),
+        }
+    }
+}
+
+/// How a merchant's invoices look
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Branding {
+    pub name: String,
+    /// One address line per line
+    pub address: String,
+    pub tax_id: String,
+    pub footer: String,
+    /// `#RRGGBB` of the title band
+    pub accent_color: String,
+}
+
+impl Default for Branding {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            address: String::new(),
+            tax_id: String::new(),
+            footer: String::new(),
+            accent_color: "#333333".to_string(),
+        }
+    }
+}
+
+impl Branding {
+    /// Why this branding can't be used, if it can't
+    pub fn validate(&self) -> Result<(), InvoiceError> {
+        if Rgb::from_hex(&self.accent_color).is_none() {
+            return Err(InvoiceError::InvalidBranding("accent color must be #RRGGBB"));
+        }
+        Ok(())
+    }
+
+    fn of(model: InvoiceBranding) -> Self {
+        Self {
+            name: model.name,
+            address: model.address,
+            tax_id: model.tax_id,
+            footer: model.footer,
+            accent_color: model.accent_color,
+        }
+    }
+}
+
+pub fn invoice_number(order: &OrderModel) -> String {
+    format!("INV-{}", order.orderid)
+}
+
+fn credit_memo_number(order: &OrderModel, sequence: u64) -> String {
+    format!("CM-{}-{}", order.orderid, sequence)
+}
+
+/// Invoice service
+pub struct InvoiceService;
+
+impl InvoiceService {
+    /// A merchant's branding; the defaults until they set their own
+    pub async fn branding<C: ConnectionTrait>(db: &C, mid: i32) -> Result<Branding, DbErr> {
+        Ok(InvoiceBrandings::find_by_id(mid)
+            .one(db)
+            .await?
+            .map(Branding::of)
+            .unwrap_or_default())
+    }
+
+    /// Replace a merchant's branding. Invoices already issued keep theirs.
+    #[instrument(skip_all, fields(mid = mid))]
+    pub async fn set_branding<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        branding: Branding,
+    ) -> Result<Branding, InvoiceError> {
+        branding.validate()?;
+        use ::entity::invoice_branding::Column;
+
+        let model = ::entity::invoice_branding::ActiveModel {
+            mid: Set(mid),
+            name: Set(branding.name.clone()),
+            address: Set(branding.address.clone()),
+            tax_id: Set(branding.tax_id.clone()),
+            footer: Set(branding.footer.clone()),
+            accent_color: Set(branding.accent_color.clone()),
+            modified_gmt: Set(Timestamp::now()),
+        };
+        InvoiceBrandings::insert(model)
+            .on_conflict(
+                OnConflict::column(Column::Mid)
+                    .update_columns([
+                        Column::Name,
+                        Column::Address,
+                        Column::TaxId,
+                        Column::Footer,
+                        Column::AccentColor,
+                        Column::ModifiedGmt,
+                    ])
+                    .to_owned(),
+            )
+            .exec_without_returning(db)
+            .await?;
+
+        Ok(branding)
+    }
+
+    /// An order's invoice, issuing it on first request
+    #[instrument(skip_all, fields(mid = mid, order_id = order_id))]
+    pub async fn invoice<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: i32,
+        order_id: i32,
+    ) -> Result<Invoice, InvoiceError> {
+        if let Some(invoice) = Self::find_kind(db, mid, order_id, InvoiceKind::Invoice).await? {
+            return Ok(invoice);
+        }
+
+        let txn = db.begin().await?;
+        // Whoever locks the order first issues the invoice
+        let order = Orders::find()
+            .filter(::entity::orders::Column::Mid.eq(mid))
+            .filter(::entity::orders::Column::Id.eq(order_id))
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .ok_or(InvoiceError::OrderNotFound)?;
+        if let Some(invoice) = Self::find_kind(&txn, mid, order_id, InvoiceKind::Invoice).await? {
+            return Ok(invoice);
+        }
+
+        let branding = Self::branding(&txn, mid).await?;
+        let items = OrderItemService::list(&txn, mid, order_id).await.map_err(|e| match e {
+            crate::OrderError::Db(e) => InvoiceError::Db(e),
+            _ => InvoiceError::OrderNotFound,
+        })?;
+        let payments = OrderPaymentService::payments(&txn, mid, order_id).await?;
+        let currency = SettingsService::get(&txn, mid).await?.currency;
+        let now = Timestamp::now();
+        let number = invoice_number(&order);
+        let pdf = render_invoice(&branding, &number, &order, &items, &payments, currency, now);
+
+        let invoice = ::entity::invoices::ActiveModel {
+            mid: Set(mid),
+            order_id: Set(order_id),
+            kind: Set(InvoiceKind::Invoice.as_str().to_string()),
+            number: Set(number),
+            amount: Set(order.total),
+            pdf: Set(pdf),
+            created_gmt: Set(now),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+
+        let mut active: ::entity::orders::ActiveModel = order.into();
+        active.inv_gmt = Set(Some(now));
+        active.update(&txn).await?;
+        txn.commit().await?;
+
+        Ok(invoice)
+    }
+
+    /// Issue a credit memo for `amount` refunded on an order. Runs in the
+    /// refund's transaction.
+    pub(crate) async fn credit_memo<C: ConnectionTrait>(
+        conn: &C,
+        order: &OrderModel,
+        amount: Money,
+    ) -> Result<Invoice, DbErr> {
+        let issued = Invoices::find()
+            .filter(::entity::invoices::Column::Mid.eq(order.mid))
+            .filter(::entity::invoices::Column::OrderId.eq(order.id))
+            .filter(::entity::invoices::Column::Kind.eq(InvoiceKind::CreditMemo.as_str()))
+            .count(conn)
+            .await?;
+        let invoice = Self::find_kind(conn, order.mid, order.id, InvoiceKind::Invoice).await?;
+
+        let branding = Self::branding(conn, order.mid).await?;
+        let now = Timestamp::now();
+        let number = credit_memo_number(order, issued + 1);
+        let pdf = render_credit_memo(
+            &branding,
+            &number,
+            order,
+            invoice.as_ref().map(|invoice| invoice.number.as_str()),
+            amount,
+            now,
+        );
+
+        ::entity::invoices::ActiveModel {
+            mid: Set(order.mid),
+            order_id: Set(order.id),
+            kind: Set(InvoiceKind::CreditMemo.as_str().to_string()),
+            number: Set(number),
+            amount: Set(amount.amount()),
+            pdf: Set(pdf),
+            created_gmt: Set(now),
+            ..Default::default()
+        }
+        .insert(conn)
+        .await
+    }
+
+    /// An order's credit memos, oldest first
+    pub async fn credit_memos<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        order_id: i32,
+    ) -> Result<Vec<Invoice>, DbErr> {
+        Invoices::find()
+            .filter(::entity::invoices::Column::Mid.eq(mid))
+            .filter(::entity::invoices::Column::OrderId.eq(order_id))
+            .filter(::entity::invoices::Column::Kind.eq(InvoiceKind::CreditMemo.as_str()))
+            .order_by_asc(::entity::invoices::Column::Id)
+            .all(db)
+            .await
+    }
+
+    /// One of an order's documents
+    pub async fn find<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        order_id: i32,
+        id: i32,
+    ) -> Result<Option<Invoice>, DbErr> {
+        Invoices::find()
+            .filter(::entity::invoices::Column::Mid.eq(mid))
+            .filter(::entity::invoices::Column::OrderId.eq(order_id))
+            .filter(::entity::invoices::Column::Id.eq(id))
+            .one(db)
+            .await
+    }
+
+    async fn find_kind<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        order_id: i32,
+        kind: InvoiceKind,
+    ) -> Result<Option<Invoice>, DbErr> {
+        Invoices::find()
+            .filter(::entity::invoices::Column::Mid.eq(mid))
+            .filter(::entity::invoices::Column::OrderId.eq(order_id))
+            .filter(::entity::invoices::Column::Kind.eq(kind.as_str()))
+            .order_by_asc(::entity::invoices::Column::Id)
+            .one(db)
+            .await
+    }
+}
+
+const MARGIN: f32 = 50.0;
+const RIGHT: f32 = PAGE_WIDTH - MARGIN;
+/// Lowest baseline for body text; the footer goes below it
+const BOTTOM: f32 = 90.0;
+const ROW: f32 = 16.0;
+
+fn money(amount: Decimal) -> String {
+    format!("{:.2}", amount)
+}
+
+fn date(gmt: Timestamp) -> String {
+    gmt.to_datetime().format("%Y-%m-%d").to_string()
+}
+
+/// Draws a document top to bottom, starting pages as it fills them
+struct Layout {
+    doc: Document,
+    title: &'static str,
+    branding: Branding,
+    y: f32,
+}
+
+impl Layout {
+    fn new(title: &'static str, number: &str, branding: &Branding) -> Self {
+        let mut layout = Self {
+            doc: Document::new(&format!("{} {}", title, number)),
+            title,
+            branding: branding.clone(),
+            y: 0.0,
+        };
+        layout.new_page();
+        layout
+    }
+
+    fn page(&mut self) -> &mut Page {
+        self.doc.pages_mut().last_mut().expect("layout always has a page")
+    }
+
+    /// Title band, then the merchant's name and address
+    fn new_page(&mut self) {
+        let accent = Rgb::from_hex(&self.branding.accent_color).unwrap_or(Rgb::BLACK);
+        let (title, branding) = (self.title, self.branding.clone());
+        let page = self.doc.add_page();
+        page.fill_rect(0.0, PAGE_HEIGHT - 60.0, PAGE_WIDTH, 60.0, accent);
+        page.color(Rgb::WHITE);
+        page.text(MARGIN, PAGE_HEIGHT - 40.0, 22.0, Font::Bold, title);
+        page.color(Rgb::BLACK);
+
+        let mut y = PAGE_HEIGHT - 90.0;
+        if !branding.name.is_empty() {
+            page.text(MARGIN, y, 14.0, Font::Bold, &branding.name);
+            y -= 16.0;
+        }
+        for line in branding.address.lines().filter(|line| !line.trim().is_empty()) {
+            page.text(MARGIN, y, 10.0, Font::Regular, line.trim());
+            y -= 13.0;
+        }
+        if !branding.tax_id.is_empty() {
+            page.text(MARGIN, y, 10.0, Font::Regular, &format!("Tax ID: {}", branding.tax_id));
+            y -= 13.0;
+        }
+        self.y = y.min(PAGE_HEIGHT - 150.0);
+    }
+
+    /// Room for `height` more points, on a new page if need be
+    fn reserve(&mut self, height: f32) {
+        if self.y - height < BOTTOM {
+            self.new_page();
+        }
+    }
+
+    /// Label and value pairs at the right, next to the branding
+    fn details(&mut self, rows: &[(&str, String)]) {
+        let mut y = PAGE_HEIGHT - 90.0;
+        let page = self.page();
+        for (label, value) in rows {
+            page.color(Rgb::GRAY);
+            page.text(360.0, y, 10.0, Font::Regular, label);
+            page.color(Rgb::BLACK);
+            page.text_right(RIGHT, y, 10.0, Font::Regular, value);
+            y -= 14.0;
+        }
+        self.y = self.y.min(y - 10.0);
+    }
+
+    fn heading(&mut self, text: &str) {
+        self.reserve(ROW * 2.0);
+        let y = self.y;
+        self.page().text(MARGIN, y, 11.0, Font::Bold, text);
+        self.y -= ROW;
+    }
+
+    fn line(&mut self, text: &str) {
+        self.reserve(ROW);
+        let y = self.y;
+        let text = pdf::fit(text, RIGHT - MARGIN, 10.0, Font::Regular);
+        self.page().text(MARGIN, y, 10.0, Font::Regular, &text);
+        self.y -= 13.0;
+    }
+
+    fn gap(&mut self) {
+        self.y -= ROW;
+    }
+
+    fn table_header(&mut self) {
+        self.reserve(ROW * 3.0);
+        let y = self.y;
+        let page = self.page();
+        page.text(MARGIN, y, 10.0, Font::Bold, "Item");
+        page.text_right(380.0, y, 10.0, Font::Bold, "Qty");
+        page.text_right(470.0, y, 10.0, Font::Bold, "Unit price");
+        page.text_right(RIGHT, y, 10.0, Font::Bold, "Amount");
+        page.rule(MARGIN, RIGHT, y - 5.0, 0.75);
+        self.y -= ROW + 4.0;
+    }
+
+    fn item(&mut self, item: &OrderItem) {
+        if self.y - ROW < BOTTOM {
+            self.new_page();
+            self.table_header();
+        }
+        let y = self.y;
+        let name = pdf::fit(&format!("{} ({})", item.product_name, item.sku), 300.0, 10.0, Font::Regular);
+        let page = self.page();
+        page.text(MARGIN, y, 10.0, Font::Regular, &name);
+        page.text_right(380.0, y, 10.0, Font::Regular, &item.quantity.to_string());
+        page.text_right(470.0, y, 10.0, Font::Regular, &money(item.unit_price));
+        page.text_right(RIGHT, y, 10.0, Font::Regular, &money(item.unit_price * Decimal::from(item.quantity)));
+        self.y -= ROW;
+    }
+
+    /// A line of the totals block
+    fn total(&mut self, label: &str, amount: Decimal, font: Font) {
+        self.reserve(ROW);
+        let y = self.y;
+        let label = pdf::fit(label, 200.0, 10.0, font);
+        let page = self.page();
+        page.text_right(470.0, y, 10.0, font, &label);
+        page.text_right(RIGHT, y, 10.0, font, &money(amount));
+        self.y -= ROW;
+    }
+
+    fn rule(&mut self) {
+        let y = self.y + ROW - 5.0;
+        self.page().rule(330.0, RIGHT, y, 0.5);
+        self.y -= 4.0;
+    }
+
+    /// The footer and page numbers go on last, once the page count is known
+    fn finish(mut self) -> Vec<u8> {
+        let count = self.doc.pages_mut().len();
+        let footer = self.branding.footer.clone();
+        for (i, page) in self.doc.pages_mut().iter_mut().enumerate() {
+            page.color(Rgb::GRAY);
+            if !footer.is_empty() {
+                let footer = pdf::fit(&footer, RIGHT - MARGIN, 9.0, Font::Regular);
+                page.text_centered(PAGE_WIDTH / 2.0, 50.0, 9.0, Font::Regular, &footer);
+            }
+            page.text_right(RIGHT, 30.0, 8.0, Font::Regular, &format!("Page {} of {}", i + 1, count));
+        }
+        self.doc.render()
+    }
+}
+
+fn bill_to(order: &OrderModel) -> String {
+    if !order.bill_email.is_empty() {
+        order.bill_email.clone()
+    } else {
+        format!("Customer {}", order.customer)
+    }
+}
+
+/// An order's invoice: its products, then discounts, shipping, each tax,
+/// gift cards and what is left to pay
+pub fn render_invoice(
+    branding: &Branding,
+    number: &str,
+    order: &OrderModel,
+    items: &[OrderItem],
+    payments: &[OrderPayment],
+    currency: Currency,
+    issued_gmt: Timestamp,
+) -> Vec<u8> {
+    let mut layout = Layout::new("INVOICE", number, branding);
+    layout.details(&[
+        ("Invoice", number.to_string()),
+        ("Date", date(issued_gmt)),
+        ("Order", order.orderid.clone()),
+        ("Order date", date(order.created_gmt)),
+        ("Currency", currency.to_string()),
+    ]);
+    layout.heading("Bill to");
+    layout.line(&bill_to(order));
+    layout.gap();
+
+    let is_charge = |sku: &str| [COUPON_SKU, TAX_SKU, SHIP_SKU, GIFT_CARD_SKU].contains(&sku);
+    layout.table_header();
+    let mut subtotal = Decimal::ZERO;
+    for item in items.iter().filter(|item| !is_charge(&item.sku)) {
+        subtotal += item.unit_price * Decimal::from(item.quantity);
+        layout.item(item);
+    }
+    layout.gap();
+
+    layout.total("Subtotal", subtotal, Font::Regular);
+    for sku in [COUPON_SKU, SHIP_SKU, TAX_SKU, GIFT_CARD_SKU] {
+        for item in items.iter().filter(|item| item.sku == sku) {
+            layout.total(&item.product_name, item.unit_price * Decimal::from(item.quantity), Font::Regular);
+        }
+    }
+    layout.rule();
+    layout.total("Total", order.total, Font::Bold);
+    let balance = payment::balance(order.total, payments);
+    layout.total("Payments", order.total.max(Decimal::ZERO) - balance, Font::Regular);
+    layout.total("Balance due", balance, Font::Bold);
+
+    layout.finish()
+}
+
+/// A credit memo for `amount` refunded on an order
+pub fn render_credit_memo(
+    branding: &Branding,
+    number: &str,
+    order: &OrderModel,
+    invoice_number: Option<&str>,
+    amount: Money,
+    issued_gmt: Timestamp,
+) -> Vec<u8> {
+    let mut layout = Layout::new("CREDIT MEMO", number, branding);
+    let mut details = vec![
+        ("Credit memo", number.to_string()),
+        ("Date", date(issued_gmt)),
+        ("Order", order.orderid.clone()),
+    ];
+    if let Some(invoice_number) = invoice_number {
+        details.push(("Invoice", invoice_number.to_string()));
+    }
+    details.push(("Currency", amount.currency().to_string()));
+    layout.details(&details);
+    layout.heading("Credit to");
+    layout.line(&bill_to(order));
+    layout.gap();
+
+    layout.line(&format!("Refund on order {}, placed {}", order.orderid, date(order.created_gmt)));
+    layout.gap();
+    layout.rule();
+    layout.total("Total credited", amount.amount(), Font::Bold);
+
+    layout.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order() -> OrderModel {
+        OrderModel {
+            id: 9,
+            mid: 1,
+            orderid: "2025-11-18-3F9A1C2B".to_string(),
+            cartid: "cart-1".to_string(),
+            customer: 7,
+            pool: "RECENT".to_string(),
+            total: Decimal::new(5497, 2),
+            created_gmt: Timestamp::from_unix(1_763_424_000),
+            paid_gmt: None,
+            paid_txn: None,
+            order_payment_status: None,
+            order_payment_lookup: None,
+            bs_settlement: None,
+            shipped_gmt: None,
+            inv_gmt: None,
+            company_id: None,
+            review_status: None,
+            ship_method: None,
+            bill_email: "shopper@example.com".to_string(),
+            v: 0,
+            mkt: 0,
+            erefid: None,
+            mkt_bitstr: String::new(),
+            pickup_location_id: None,
+            pickup_ready_gmt: None,
+            picked_up_gmt: None,
+            cancelled_gmt: None,
+            cancel_reason: None,
+        }
+    }
+
+    fn item(id: i32, sku: &str, name: &str, quantity: i32, unit_price: i64) -> OrderItem {
+        OrderItem {
+            id,
+            mid: 1,
+            order_id: 9,
+            sku: sku.to_string(),
+            product_name: name.to_string(),
+            quantity,
+            unit_price: Decimal::new(unit_price, 2),
+            options: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_invoice_kind_round_trip() {
+        for kind in [InvoiceKind::Invoice, InvoiceKind::CreditMemo] {
+            assert_eq!(kind.as_str().parse::<InvoiceKind>(), Ok(kind));
+        }
+    }
+
+    #[test]
+    fn test_render_invoice() {
+        let branding = Branding {
+            name: "Widget Works".to_string(),
+            address: "1 Main St\nSpringfield".to_string(),
+            tax_id: "US-123".to_string(),
+            footer: "Thank you for your order".to_string(),
+            ..Default::default()
+        };
+        let items = [
+            item(1, "SKU001", "Widget", 2, 1999),
+            item(2, TAX_SKU, "State tax", 1, 300),
+            item(3, SHIP_SKU, "Ground", 1, 1199),
+        ];
+        let number = invoice_number(&order());
+        let issued = Timestamp::from_unix(1_763_510_400);
+        let pdf = render_invoice(&branding, &number, &order(), &items, &[], Currency::EUR, issued);
+        let text = String::from_utf8_lossy(&pdf);
+
+        assert!(pdf.starts_with(b"%PDF"));
+        for expected in [
+            "(INVOICE)",
+            "(INV-2025-11-18-3F9A1C2B)",
+            "(Widget Works)",
+            "(Springfield)",
+            "(Tax ID: US-123)",
+            "(Widget \\(SKU001\\))",
+            "(39.98)",
+            "(State tax)",
+            "(Ground)",
+            "(54.97)",
+            "(Balance due)",
+            "(EUR)",
+            "(2025-11-19)",
+            "(Page 1 of 1)",
+        ] {
+            assert!(text.contains(expected), "missing {expected}");
+        }
+    }
+
+    #[test]
+    fn test_long_invoices_continue_on_new_pages() {
+        let items: Vec<OrderItem> = (0..80).map(|i| item(i, &format!("SKU{i:03}"), "Widget", 1, 100)).collect();
+        let pdf = render_invoice(&Branding::default(), "INV-1", &order(), &items, &[], Currency::USD, Timestamp::EPOCH);
+        let text = String::from_utf8_lossy(&pdf);
+
+        assert!(text.contains("/Count 3"), "expected three pages");
+        assert!(text.contains("(Page 3 of 3)"));
+        assert!(text.contains("(SKU079)") || text.contains("(Widget \\(SKU079\\))"));
+    }
+
+    #[test]
+    fn test_branding_validation() {
+        assert!(Branding::default().validate().is_ok());
+        let bad = Branding {
+            accent_color: "red".to_string(),
+            ..Default::default()
+        };
+        assert!(matches!(bad.validate(), Err(InvoiceError::InvalidBranding(_))));
+    }
+}
@@ -0,0 +1,234 @@
+//! Order line items using SeaORM
+//!
+//! Every change to an order's items recomputes the order total so the two
+//! never drift apart.
+
+use commercerack_cart::ItemOptions;
+use rust_decimal::Decimal;
+use sea_orm::{entity::*, query::*, ConnectionTrait, DatabaseConnection, DbErr, Set, TransactionTrait};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use ::entity::prelude::{OrderItems, OrderItem, Orders};
+
+use crate::OrderError;
+use tracing::instrument;
+
+/// A line item to be added to an order
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NewOrderItem {
+    pub sku: String,
+    pub product_name: String,
+    pub quantity: i32,
+    pub unit_price: Decimal,
+    /// Selections, engraving and gift wrap of the cart line
+    #[serde(default)]
+    pub options: ItemOptions,
+}
+
+impl NewOrderItem {
+    pub fn subtotal(&self) -> Decimal {
+        self.unit_price * Decimal::from(self.quantity)
+    }
+}
+
+/// Options a stored order item was made up with
+pub fn options(item: &OrderItem) -> ItemOptions {
+    serde_json::from_str(&item.options).unwrap_or_default()
+}
+
+/// Line total of a stored order item
+pub fn line_total(item: &OrderItem) -> Decimal {
+    item.unit_price * Decimal::from(item.quantity)
+}
+
+/// Insert line items for an order on any connection (used inside transactions)
+pub(crate) async fn insert_items<C: ConnectionTrait>(
+    conn: &C,
+    mid: i32,
+    order_id: i32,
+    items: &[NewOrderItem],
+) -> Result<Vec<OrderItem>, DbErr> {
+    let mut inserted = Vec::with_capacity(items.len());
+    for item in items {
+        let options = if item.options.is_empty() {
+            String::new()
+        } else {
+            serde_json::to_string(&item.options).map_err(|e| DbErr::Custom(e.to_string()))?
+        };
+        let model = ::entity::order_items::ActiveModel {
+            mid: Set(mid),
+            order_id: Set(order_id),
+            sku: Set(item.sku.clone()),
+            product_name: Set(item.product_name.clone()),
+            quantity: Set(item.quantity),
+            unit_price: Set(item.unit_price),
+            options: Set(options),
+            ..Default::default()
+        }
+        .insert(conn)
+        .await?;
+        inserted.push(model);
+    }
+    Ok(inserted)
+}
+
+/// Order item service for managing the line items of an existing order
+pub struct OrderItemService;
+
+impl OrderItemService {
+    /// List items of an order
+    pub async fn list<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        order_id: i32,
+    ) -> Result<Vec<OrderItem>, OrderError> {
+        let items = OrderItems::find()
+            .filter(::entity::order_items::Column::Mid.eq(mid))
+            .filter(::entity::order_items::Column::OrderId.eq(order_id))
+            .order_by_asc(::entity::order_items::Column::Id)
+            .all(db)
+            .await?;
+
+        Ok(items)
+    }
+
+    /// Items of several orders in one query, keyed by order ID
+    pub async fn list_for_orders<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        order_ids: &[i32],
+    ) -> Result<HashMap<i32, Vec<OrderItem>>, OrderError> {
+        let mut by_order: HashMap<i32, Vec<OrderItem>> = HashMap::new();
+        if order_ids.is_empty() {
+            return Ok(by_order);
+        }
+
+        let items = OrderItems::find()
+            .filter(::entity::order_items::Column::Mid.eq(mid))
+            .filter(::entity::order_items::Column::OrderId.is_in(order_ids.iter().copied()))
+            .order_by_asc(::entity::order_items::Column::Id)
+            .all(db)
+            .await?;
+
+        for item in items {
+            by_order.entry(item.order_id).or_default().push(item);
+        }
+        Ok(by_order)
+    }
+
+    /// Find a single item
+    pub async fn find_by_id(
+        db: &DatabaseConnection,
+        mid: i32,
+        id: i32,
+    ) -> Result<Option<OrderItem>, OrderError> {
+        let item = OrderItems::find()
+            .filter(::entity::order_items::Column::Mid.eq(mid))
+            .filter(::entity::order_items::Column::Id.eq(id))
+            .one(db)
+            .await?;
+
+        Ok(item)
+    }
+
+    /// Add an item to an order
+    #[instrument(skip_all, fields(mid = mid, order_id = order_id))]
+    pub async fn add<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: i32,
+        order_id: i32,
+        item: NewOrderItem,
+    ) -> Result<OrderItem, OrderError> {
+        let txn = db.begin().await?;
+        let mut inserted = insert_items(&txn, mid, order_id, std::slice::from_ref(&item)).await?;
+        Self::recompute_total(&txn, mid, order_id).await?;
+        txn.commit().await?;
+
+        inserted
+            .pop()
+            .ok_or_else(|| DbErr::RecordNotInserted.into())
+    }
+
+    /// Change quantity and/or unit price of an item
+    #[instrument(skip_all, fields(mid = mid, id = id))]
+    pub async fn update<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: i32,
+        id: i32,
+        quantity: Option<i32>,
+        unit_price: Option<Decimal>,
+    ) -> Result<OrderItem, OrderError> {
+        let txn = db.begin().await?;
+
+        let item = OrderItems::find()
+            .filter(::entity::order_items::Column::Mid.eq(mid))
+            .filter(::entity::order_items::Column::Id.eq(id))
+            .one(&txn)
+            .await?
+            .ok_or(OrderError::ItemNotFound)?;
+        let order_id = item.order_id;
+
+        let mut active: ::entity::order_items::ActiveModel = item.into();
+        if let Some(quantity) = quantity {
+            active.quantity = Set(quantity);
+        }
+        if let Some(unit_price) = unit_price {
+            active.unit_price = Set(unit_price);
+        }
+        let result = active.update(&txn).await?;
+
+        Self::recompute_total(&txn, mid, order_id).await?;
+        txn.commit().await?;
+        Ok(result)
+    }
+
+    /// Remove an item from its order
+    #[instrument(skip_all, fields(mid = mid, id = id))]
+    pub async fn remove<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: i32,
+        id: i32,
+    ) -> Result<(), OrderError> {
+        let txn = db.begin().await?;
+
+        let item = OrderItems::find()
+            .filter(::entity::order_items::Column::Mid.eq(mid))
+            .filter(::entity::order_items::Column::Id.eq(id))
+            .one(&txn)
+            .await?
+            .ok_or(OrderError::ItemNotFound)?;
+        let order_id = item.order_id;
+
+        OrderItems::delete_by_id(id).exec(&txn).await?;
+
+        Self::recompute_total(&txn, mid, order_id).await?;
+        txn.commit().await?;
+        Ok(())
+    }
+
+    /// Set the order total to the sum of its line items
+    async fn recompute_total<C: ConnectionTrait>(
+        conn: &C,
+        mid: i32,
+        order_id: i32,
+    ) -> Result<Decimal, OrderError> {
+        let total: Decimal = Self::list(conn, mid, order_id)
+            .await?
+            .iter()
+            .map(line_total)
+            .sum();
+
+        let order = Orders::find()
+            .filter(::entity::orders::Column::Mid.eq(mid))
+            .filter(::entity::orders::Column::Id.eq(order_id))
+            .one(conn)
+            .await?
+            .ok_or(OrderError::NotFound)?;
+
+        let mut active: ::entity::orders::ActiveModel = order.into();
+        active.total = Set(total);
+        active.update(conn).await?;
+
+        Ok(total)
+    }
+}
@@ -1,50 +1,330 @@
 //! Order management module using SeaORM
+//!
+//! Every `OrderService` method takes any connection, so orders share the
+//! pool the rest of the services use and can be read and written inside a
+//! caller's transaction alongside customers, inventory and coupons.
 
-use anyhow::Result;
-use chrono::Utc;
-use sea_orm::{entity::*, query::*, DatabaseConnection, DbErr, Set};
-use ::entity::prelude::{Orders, Order as OrderModel};
+use commercerack_core::{MerchantId, OrderId, Timestamp};
+use sea_orm::{entity::*, query::*, Condition, ConnectionTrait, DbErr, Set, SqlErr, TransactionTrait};
+use serde::Serialize;
+use thiserror::Error;
+use ::entity::prelude::{Orders, Order as OrderModel, OrderItem};
 use rust_decimal::Decimal;
+use commercerack_db::pagination::{Cursor, CursorError, Keyset};
+use commercerack_events::{outbox, DomainEvent};
+
+pub mod approvals;
+pub mod cancellation;
+pub mod checkout;
+pub mod cold_storage;
+pub mod digital;
+pub mod duplicates;
+pub mod dunning;
+pub mod edits;
+pub mod fulfillment_groups;
+pub mod invoices;
+pub mod items;
+pub mod payment;
+pub mod pdf;
+pub mod pickup;
+pub mod pipeline;
+pub mod pools;
+pub mod refunds;
+pub mod returns;
+mod search;
+pub mod shipments;
+pub mod stats;
+
+use items::{insert_items, NewOrderItem};
+use payment::PaymentStatus;
+use tracing::instrument;
+
+#[derive(Error, Debug)]
+pub enum OrderError {
+    #[error("Order not found")]
+    NotFound,
+
+    #[error("Order item not found")]
+    ItemNotFound,
+
+    #[error("Order has nothing left to ship")]
+    NothingToShip,
+
+    #[error("Cannot ship {quantity} of order item {order_item_id}; {remaining} not shipped yet")]
+    OverShipment { order_item_id: i32, quantity: i32, remaining: i32 },
+
+    #[error("Shipment not found")]
+    ShipmentNotFound,
+
+    #[error("Shipment was not sent with a bought label")]
+    NoLabel,
+
+    #[error("Store location not found")]
+    LocationNotFound,
+
+    #[error("Order is not for pickup")]
+    NotPickup,
+
+    #[error("Order is not ready for pickup yet")]
+    NotReadyForPickup,
+
+    #[error("Order has already been picked up")]
+    AlreadyPickedUp,
+
+    #[error("Order can't be edited once {0}")]
+    NotEditable(&'static str),
+
+    #[error("Only product lines and manual discounts can be edited")]
+    ItemNotEditable,
+
+    #[error("Edits would take the order total below zero")]
+    NegativeTotal,
+
+    #[error("Order has been cancelled")]
+    Cancelled,
+
+    #[error("Order can't be cancelled once {0}")]
+    NotCancellable(&'static str),
+
+    #[error("Order has open payments but no payment gateway is configured to release them")]
+    NoPaymentGateway,
+
+    #[error(transparent)]
+    Payment(#[from] payment::OrderPaymentError),
+
+    #[error(transparent)]
+    Inventory(#[from] commercerack_inventory::InventoryError),
+
+    #[error(transparent)]
+    Shipping(#[from] commercerack_shipping::ShippingError),
+
+    #[error("Order was changed by someone else; now at version {}", .0.v)]
+    VersionConflict(Box<OrderModel>),
+
+    #[error(transparent)]
+    Cursor(#[from] CursorError),
+
+    #[error(transparent)]
+    Merchant(#[from] commercerack_merchant::MerchantError),
+
+    #[error("Database error: {0}")]
+    Db(#[from] DbErr),
+}
+
+/// `customer` of orders placed through guest checkout, until claimed by
+/// an account registered with their billing email
+pub const GUEST_CUSTOMER: i32 = 0;
+
+/// An order together with its line items
+#[derive(Debug, Clone, Serialize)]
+pub struct OrderWithItems {
+    pub order: OrderModel,
+    pub items: Vec<OrderItem>,
+}
+
+/// An order placed on a marketplace, recorded as the marketplace took it
+#[derive(Debug, Clone)]
+pub struct ImportedOrder {
+    /// Bit of the marketplace, stored as `orders.mkt`
+    pub mkt: i32,
+    /// The marketplace's order reference
+    pub erefid: String,
+    pub bill_email: String,
+    /// When the marketplace collected payment, if it has
+    pub paid_gmt: Option<Timestamp>,
+    pub items: Vec<NewOrderItem>,
+}
+
+/// `mkt` as the legacy `mkt_bitstr`: its set bits, lowest first and
+/// comma-separated, e.g. `0,1` for an order on marketplaces 0 and 1
+pub fn mkt_bitstr(mkt: i32) -> String {
+    (0..i32::BITS)
+        .filter(|bit| mkt & (1 << bit) != 0)
+        .map(|bit| bit.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Order list filters; unset filters match everything
+#[derive(Debug, Clone, Default)]
+pub struct OrderFilter {
+    pub pool: Option<String>,
+    pub payment_status: Option<PaymentStatus>,
+    /// Legacy three character review code, e.g. `AOK`
+    pub review_status: Option<String>,
+    pub customer: Option<i32>,
+    /// Created at or after this time
+    pub created_from: Option<Timestamp>,
+    /// Created at or before this time
+    pub created_to: Option<Timestamp>,
+}
+
+impl OrderFilter {
+    fn condition(&self, mid: i32) -> Condition {
+        use ::entity::orders::Column;
+
+        let mut condition = Condition::all().add(Column::Mid.eq(mid));
+        if let Some(pool) = self.pool.as_deref().filter(|p| !p.is_empty()) {
+            condition = condition.add(Column::Pool.eq(pool));
+        }
+        if let Some(status) = self.payment_status {
+            condition = condition.add(Column::OrderPaymentStatus.eq(status.code()));
+        }
+        if let Some(review) = self.review_status.as_deref().filter(|r| !r.is_empty()) {
+            condition = condition.add(Column::ReviewStatus.eq(review));
+        }
+        if let Some(customer) = self.customer {
+            condition = condition.add(Column::Customer.eq(customer));
+        }
+        if let Some(from) = self.created_from {
+            condition = condition.add(Column::CreatedGmt.gte(from));
+        }
+        if let Some(to) = self.created_to {
+            condition = condition.add(Column::CreatedGmt.lte(to));
+        }
+        condition
+    }
+}
+
+/// Order listings run newest first
+fn newest() -> Keyset<::entity::orders::Column> {
+    use ::entity::orders::Column;
+
+    Keyset::new("newest", vec![(Column::CreatedGmt, Order::Desc), (Column::Id, Order::Desc)])
+}
+
+/// Insert an order and its line items on any connection (used inside transactions)
+pub(crate) async fn insert_order<C: ConnectionTrait>(
+    conn: &C,
+    mid: i32,
+    orderid: &str,
+    cartid: &str,
+    customer: i32,
+    pool: &str,
+    items: &[NewOrderItem],
+) -> Result<OrderWithItems, DbErr> {
+    let now = Timestamp::now();
+    let total: Decimal = items.iter().map(|item| item.subtotal()).sum();
+
+    let order = ::entity::orders::ActiveModel {
+        mid: Set(mid),
+        orderid: Set(orderid.to_string()),
+        cartid: Set(cartid.to_string()),
+        customer: Set(customer),
+        pool: Set(pool.to_string()),
+        total: Set(total),
+        created_gmt: Set(now),
+        paid_gmt: Set(None),
+        paid_txn: Set(None),
+        order_payment_status: Set(None),
+        order_payment_lookup: Set(None),
+        bs_settlement: Set(None),
+        shipped_gmt: Set(None),
+        inv_gmt: Set(None),
+        company_id: Set(None),
+        ..Default::default()
+    }
+    .insert(conn)
+    .await?;
+
+    let items = insert_items(conn, mid, order.id, items).await?;
+    Ok(OrderWithItems { order, items })
+}
 
 /// Order service for managing order operations
 pub struct OrderService;
 
 impl OrderService {
-    /// Create new order
-    pub async fn create(
-        db: &DatabaseConnection,
+    /// Create new order with its line items. The order total is the sum of
+    /// the items. A likely duplicate of a recent order is held for review;
+    /// see [`duplicates`].
+    #[instrument(skip_all, fields(mid = mid, orderid = orderid))]
+    pub async fn create<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
         mid: i32,
         orderid: &str,
         cartid: &str,
         customer: i32,
         pool: &str,
-        total: Decimal,
-    ) -> Result<OrderModel> {
-        let now = Utc::now().timestamp() as i32;
-
-        let order = ::entity::orders::ActiveModel {
-            mid: Set(mid),
-            orderid: Set(orderid.to_string()),
-            cartid: Set(cartid.to_string()),
-            customer: Set(customer),
-            pool: Set(pool.to_string()),
-            total: Set(total),
-            created_gmt: Set(now),
-            paid_gmt: Set(None),
-            shipped_gmt: Set(None),
-            ..Default::default()
+        items: &[NewOrderItem],
+    ) -> Result<OrderWithItems, OrderError> {
+        let txn = db.begin().await?;
+        let mut result = insert_order(&txn, mid, orderid, cartid, customer, pool, items).await?;
+        result.order = duplicates::flag_if_duplicate(&txn, result.order, &result.items).await?;
+        let event = DomainEvent::OrderCreated {
+            order: result.order.clone(),
+            items: result.items.clone(),
         };
+        outbox::record(&txn, &event).await?;
+        txn.commit().await?;
 
-        let result = order.insert(db).await?;
         Ok(result)
     }
 
+    /// Record an order placed on a marketplace as a guest order. An order
+    /// already imported from the same marketplace under the same reference
+    /// is left alone and `None` returned, so pulling one twice is harmless.
+    #[instrument(skip_all, fields(mid = mid, mkt = imported.mkt, erefid = imported.erefid.as_str()))]
+    pub async fn import<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: i32,
+        imported: &ImportedOrder,
+    ) -> Result<Option<OrderWithItems>, OrderError> {
+        let txn = db.begin().await?;
+        if Self::find_imported(&txn, mid, imported.mkt, &imported.erefid).await?.is_some() {
+            return Ok(None);
+        }
+
+        let orderid = checkout::generate_orderid();
+        let mut result =
+            insert_order(&txn, mid, &orderid, "", GUEST_CUSTOMER, checkout::NEW_ORDER_POOL, &imported.items).await?;
+        let mut order: ::entity::orders::ActiveModel = result.order.into();
+        order.mkt = Set(imported.mkt);
+        order.erefid = Set(Some(imported.erefid.clone()));
+        order.mkt_bitstr = Set(mkt_bitstr(imported.mkt));
+        order.bill_email = Set(imported.bill_email.clone());
+        if let Some(paid_gmt) = imported.paid_gmt {
+            order.order_payment_status = Set(Some(PaymentStatus::Paid.code().to_string()));
+            order.paid_gmt = Set(Some(paid_gmt));
+        }
+        result.order = match order.update(&txn).await {
+            // Imported by a concurrent pull or delivery since the check above
+            Err(e) if matches!(e.sql_err(), Some(SqlErr::UniqueConstraintViolation(_))) => return Ok(None),
+            result => result?,
+        };
+        let event = DomainEvent::OrderCreated {
+            order: result.order.clone(),
+            items: result.items.clone(),
+        };
+        outbox::record(&txn, &event).await?;
+        txn.commit().await?;
+
+        Ok(Some(result))
+    }
+
+    /// The order imported from marketplace `mkt` under its reference `erefid`
+    pub async fn find_imported<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        mkt: i32,
+        erefid: &str,
+    ) -> Result<Option<OrderModel>, OrderError> {
+        let order = Orders::find()
+            .filter(::entity::orders::Column::Mid.eq(mid))
+            .filter(::entity::orders::Column::Mkt.eq(mkt))
+            .filter(::entity::orders::Column::Erefid.eq(erefid))
+            .one(db)
+            .await?;
+
+        Ok(order)
+    }
+
     /// Find order by ID
-    pub async fn find_by_id(
-        db: &DatabaseConnection,
+    pub async fn find_by_id<C: ConnectionTrait>(
+        db: &C,
         mid: i32,
         id: i32,
-    ) -> Result<Option<OrderModel>> {
+    ) -> Result<Option<OrderModel>, OrderError> {
         let order = Orders::find()
             .filter(::entity::orders::Column::Mid.eq(mid))
             .filter(::entity::orders::Column::Id.eq(id))
@@ -54,12 +334,31 @@ impl OrderService {
         Ok(order)
     }
 
+    /// Find several orders by ID, in no particular order. IDs that don't
+    /// exist are left out.
+    pub async fn find_by_ids<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        ids: &[i32],
+    ) -> Result<Vec<OrderModel>, OrderError> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let orders = Orders::find()
+            .filter(::entity::orders::Column::Mid.eq(mid))
+            .filter(::entity::orders::Column::Id.is_in(ids.iter().copied()))
+            .all(db)
+            .await?;
+
+        Ok(orders)
+    }
+
     /// Find order by order ID
-    pub async fn find_by_orderid(
-        db: &DatabaseConnection,
+    pub async fn find_by_orderid<C: ConnectionTrait>(
+        db: &C,
         mid: i32,
         orderid: &str,
-    ) -> Result<Option<OrderModel>> {
+    ) -> Result<Option<OrderModel>, OrderError> {
         let order = Orders::find()
             .filter(::entity::orders::Column::Mid.eq(mid))
             .filter(::entity::orders::Column::Orderid.eq(orderid))
@@ -70,11 +369,11 @@ impl OrderService {
     }
 
     /// Find order by cart ID
-    pub async fn find_by_cartid(
-        db: &DatabaseConnection,
+    pub async fn find_by_cartid<C: ConnectionTrait>(
+        db: &C,
         mid: i32,
         cartid: &str,
-    ) -> Result<Option<OrderModel>> {
+    ) -> Result<Option<OrderModel>, OrderError> {
         let order = Orders::find()
             .filter(::entity::orders::Column::Mid.eq(mid))
             .filter(::entity::orders::Column::Cartid.eq(cartid))
@@ -85,13 +384,13 @@ impl OrderService {
     }
 
     /// List orders by customer
-    pub async fn list_by_customer(
-        db: &DatabaseConnection,
+    pub async fn list_by_customer<C: ConnectionTrait>(
+        db: &C,
         mid: i32,
         customer: i32,
         limit: u64,
         offset: u64,
-    ) -> Result<Vec<OrderModel>> {
+    ) -> Result<Vec<OrderModel>, OrderError> {
         let orders = Orders::find()
             .filter(::entity::orders::Column::Mid.eq(mid))
             .filter(::entity::orders::Column::Customer.eq(customer))
@@ -104,14 +403,75 @@ impl OrderService {
         Ok(orders)
     }
 
+    /// Orders a company's buyers placed for it, newest first
+    pub async fn list_by_company<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        company_id: i32,
+        limit: u64,
+        offset: u64,
+    ) -> Result<Vec<OrderModel>, OrderError> {
+        let orders = Orders::find()
+            .filter(::entity::orders::Column::Mid.eq(mid))
+            .filter(::entity::orders::Column::CompanyId.eq(company_id))
+            .order_by_desc(::entity::orders::Column::CreatedGmt)
+            .limit(limit)
+            .offset(offset)
+            .all(db)
+            .await?;
+
+        Ok(orders)
+    }
+
+    /// List a merchant's orders newest first, one page after `cursor`.
+    /// Also returns the cursor of the next page, if there is one.
+    #[instrument(skip_all, fields(mid = mid))]
+    pub async fn list<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        filter: &OrderFilter,
+        limit: u64,
+        cursor: Option<&Cursor>,
+    ) -> Result<(Vec<OrderModel>, Option<Cursor>), OrderError> {
+        let keyset = newest();
+        let mut orders = keyset
+            .page(Orders::find().filter(filter.condition(mid)), cursor, limit)?
+            .all(db)
+            .await?;
+
+        let next = keyset.next(&mut orders, limit, |o| vec![o.created_gmt.into(), o.id.into()]);
+        Ok((orders, next))
+    }
+
+    /// Orders whose billing name, email or phone contains `term`, or whose
+    /// payment transaction ID ends with it, newest first
+    #[instrument(skip_all, fields(mid = mid))]
+    pub async fn search<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        term: &str,
+        limit: u64,
+    ) -> Result<Vec<OrderModel>, OrderError> {
+        let orders = Orders::find()
+            .filter(::entity::orders::Column::Mid.eq(mid))
+            .filter(search::matches(term))
+            .order_by_desc(::entity::orders::Column::CreatedGmt)
+            .order_by_desc(::entity::orders::Column::Id)
+            .limit(limit)
+            .all(db)
+            .await?;
+
+        Ok(orders)
+    }
+
     /// List orders by pool
-    pub async fn list_by_pool(
-        db: &DatabaseConnection,
+    pub async fn list_by_pool<C: ConnectionTrait>(
+        db: &C,
         mid: i32,
         pool: &str,
         limit: u64,
         offset: u64,
-    ) -> Result<Vec<OrderModel>> {
+    ) -> Result<Vec<OrderModel>, OrderError> {
         let orders = Orders::find()
             .filter(::entity::orders::Column::Mid.eq(mid))
             .filter(::entity::orders::Column::Pool.eq(pool))
@@ -124,60 +484,107 @@ impl OrderService {
         Ok(orders)
     }
 
-    /// Update order
-    pub async fn update(
-        db: &DatabaseConnection,
+    /// Save an edited order. `order.v` must be the version it was read at;
+    /// if the order has changed since, nothing is written and the error
+    /// carries its current state.
+    #[instrument(skip_all, fields(mid = order.mid, id = order.id, v = order.v))]
+    pub async fn update<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
         order: OrderModel,
-    ) -> Result<OrderModel> {
-        let active: ::entity::orders::ActiveModel = order.into();
-        let result = active.update(db).await?;
+    ) -> Result<OrderModel, OrderError> {
+        use ::entity::orders::Column;
+
+        let (mid, id, version) = (order.mid, order.id, order.v);
+        let mut active = ::entity::orders::ActiveModel::from(order).reset_all();
+        active.id = NotSet;
+        active.v = Set(version + 1);
+
+        let txn = db.begin().await?;
+        let updated = Orders::update_many()
+            .set(active)
+            .filter(Column::Mid.eq(mid))
+            .filter(Column::Id.eq(id))
+            .filter(Column::V.eq(version))
+            .exec_with_returning(&txn)
+            .await?;
+        let Some(result) = updated.into_iter().next() else {
+            let current = Orders::find()
+                .filter(Column::Mid.eq(mid))
+                .filter(Column::Id.eq(id))
+                .one(&txn)
+                .await?;
+            return Err(match current {
+                Some(current) => OrderError::VersionConflict(Box::new(current)),
+                None => OrderError::NotFound,
+            });
+        };
+        outbox::record(&txn, &DomainEvent::OrderUpdated(result.clone())).await?;
+        txn.commit().await?;
         Ok(result)
     }
 
-    /// Mark order as paid
-    pub async fn mark_paid(
-        db: &DatabaseConnection,
+    /// Mark order as paid, delivering its digital items
+    #[instrument(skip_all, fields(mid = mid, id = id))]
+    pub async fn mark_paid<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
         mid: i32,
         id: i32,
-    ) -> Result<OrderModel> {
+    ) -> Result<OrderModel, OrderError> {
         let order = Self::find_by_id(db, mid, id).await?
-            .ok_or_else(|| anyhow::anyhow!("Order not found"))?;
+            .ok_or(OrderError::NotFound)?;
 
         let mut active: ::entity::orders::ActiveModel = order.into();
-        active.paid_gmt = Set(Some(Utc::now().timestamp() as i32));
+        active.paid_gmt = Set(Some(Timestamp::now()));
 
-        let result = active.update(db).await?;
+        let txn = db.begin().await?;
+        let result = active.update(&txn).await?;
+        outbox::record(&txn, &DomainEvent::OrderPaid(result.clone())).await?;
+        let result = digital::fulfill(&txn, result).await?;
+        txn.commit().await?;
         Ok(result)
     }
 
     /// Mark order as shipped
-    pub async fn mark_shipped(
-        db: &DatabaseConnection,
+    #[instrument(skip_all, fields(mid = mid, id = id))]
+    pub async fn mark_shipped<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
         mid: i32,
         id: i32,
-    ) -> Result<OrderModel> {
+    ) -> Result<OrderModel, OrderError> {
         let order = Self::find_by_id(db, mid, id).await?
-            .ok_or_else(|| anyhow::anyhow!("Order not found"))?;
+            .ok_or(OrderError::NotFound)?;
+        if order.cancelled_gmt.is_some() {
+            return Err(OrderError::Cancelled);
+        }
 
         let mut active: ::entity::orders::ActiveModel = order.into();
-        active.shipped_gmt = Set(Some(Utc::now().timestamp() as i32));
+        active.shipped_gmt = Set(Some(Timestamp::now()));
 
-        let result = active.update(db).await?;
+        let txn = db.begin().await?;
+        let result = active.update(&txn).await?;
+        outbox::record(&txn, &DomainEvent::OrderShipped(result.clone())).await?;
+        txn.commit().await?;
         Ok(result)
     }
 
     /// Delete order
-    pub async fn delete(
-        db: &DatabaseConnection,
-        mid: i32,
-        id: i32,
-    ) -> Result<()> {
-        Orders::delete_many()
+    #[instrument(skip_all, fields(mid = %mid, id = %id))]
+    pub async fn delete<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: MerchantId,
+        id: OrderId,
+    ) -> Result<(), OrderError> {
+        let txn = db.begin().await?;
+        let result = Orders::delete_many()
             .filter(::entity::orders::Column::Mid.eq(mid))
             .filter(::entity::orders::Column::Id.eq(id))
-            .exec(db)
+            .exec(&txn)
             .await?;
 
+        if result.rows_affected > 0 {
+            outbox::record(&txn, &DomainEvent::OrderDeleted { mid, id }).await?;
+        }
+        txn.commit().await?;
         Ok(())
     }
 }
@@ -185,7 +592,122 @@ impl OrderService {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use sea_orm::{DatabaseBackend, MockDatabase, MockExecResult};
+
+    fn order(id: i32, created_gmt: i64) -> OrderModel {
+        OrderModel {
+            id,
+            mid: 1,
+            orderid: format!("2025-11-18-{:08}", id),
+            cartid: String::new(),
+            customer: 1,
+            pool: "RECENT".to_string(),
+            total: Decimal::ZERO,
+            created_gmt: Timestamp::from_unix(created_gmt),
+            paid_gmt: None,
+            paid_txn: None,
+            order_payment_status: None,
+            order_payment_lookup: None,
+            bs_settlement: None,
+            shipped_gmt: None,
+            inv_gmt: None,
+            company_id: None,
+            review_status: None,
+            ship_method: None,
+            bill_email: String::new(),
+            v: 0,
+            mkt: 0,
+            erefid: None,
+            mkt_bitstr: String::new(),
+            pickup_location_id: None,
+            pickup_ready_gmt: None,
+            picked_up_gmt: None,
+            cancelled_gmt: None,
+            cancel_reason: None,
+        }
+    }
+
+    #[test]
+    fn test_mkt_bitstr() {
+        assert_eq!(mkt_bitstr(0), "");
+        assert_eq!(mkt_bitstr(1), "0");
+        assert_eq!(mkt_bitstr(0b1010), "1,3");
+    }
 
-    // Tests will be added when we have a test database setup
-    // For now, compilation success validates the API design
+    #[tokio::test]
+    async fn test_list_pages_with_cursor() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![order(9, 300), order(8, 300), order(7, 200)]])
+            .into_connection();
+
+        let filter = OrderFilter {
+            payment_status: Some(PaymentStatus::Paid),
+            ..Default::default()
+        };
+        let after = newest().cursor(vec![400.into(), 10.into()]);
+        let (orders, next) = OrderService::list(&db, 1, &filter, 2, Some(&after)).await.unwrap();
+        assert_eq!(orders.iter().map(|o| o.id).collect::<Vec<_>>(), vec![9, 8]);
+        assert_eq!(next, Some(newest().cursor(vec![300.into(), 8.into()])));
+
+        let log = db.into_transaction_log();
+        let sql = log[0].statements()[0].to_string();
+        assert!(sql.contains(r#""orders"."order_payment_status" = '000'"#), "{}", sql);
+        assert!(sql.contains(r#"("orders"."created_gmt" < 400 OR ("orders"."created_gmt" = 400 AND "orders"."id" < 10))"#), "{}", sql);
+        assert!(sql.contains("LIMIT 3"), "{}", sql);
+    }
+
+    #[tokio::test]
+    async fn test_search_matches_billing_details_and_txn_suffix() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![order(9, 300)]])
+            .into_connection();
+
+        let orders = OrderService::search(&db, 1, " 555-0142 ", 20).await.unwrap();
+        assert_eq!(orders.len(), 1);
+
+        let log = db.into_transaction_log();
+        let sql = log[0].statements()[0].to_string();
+        assert!(sql.contains("order_bill_name ILIKE '%555-0142%'"), "{}", sql);
+        assert!(sql.contains("bill_email ILIKE '%555-0142%'"), "{}", sql);
+        assert!(sql.contains("paid_txn ILIKE '%555-0142'"), "{}", sql);
+        assert!(sql.contains("LIKE '%5550142%'"), "{}", sql);
+        assert!(sql.contains(r#""orders"."mid" = 1"#), "{}", sql);
+    }
+
+    #[tokio::test]
+    async fn test_update_requires_current_version() {
+        let mut edited = order(9, 300);
+        edited.v = 3;
+        edited.review_status = Some("AOK".to_string());
+        let mut saved = edited.clone();
+        saved.v = 4;
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![saved]])
+            .append_exec_results([MockExecResult { last_insert_id: 0, rows_affected: 1 }])
+            .into_connection();
+
+        assert_eq!(OrderService::update(&db, edited).await.unwrap().v, 4);
+
+        let log = db.into_transaction_log();
+        let sql = log[0].statements()[1].to_string();
+        assert!(sql.contains(r#""review_status" = 'AOK'"#), "{}", sql);
+        assert!(sql.contains(r#""v" = 4 WHERE "orders"."mid" = 1 AND "orders"."id" = 9 AND "orders"."v" = 3"#), "{}", sql);
+    }
+
+    #[tokio::test]
+    async fn test_update_with_stale_version_returns_current() {
+        let mut current = order(9, 300);
+        current.v = 5;
+        current.paid_gmt = Some(Timestamp::from_unix(400));
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([Vec::<OrderModel>::new(), vec![current.clone()]])
+            .into_connection();
+
+        let mut stale = order(9, 300);
+        stale.v = 4;
+        match OrderService::update(&db, stale).await {
+            Err(OrderError::VersionConflict(order)) => assert_eq!(*order, current),
+            other => panic!("expected a version conflict, got {:?}", other),
+        }
+    }
 }
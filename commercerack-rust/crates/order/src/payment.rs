@@ -0,0 +1,675 @@
+//! Order payments through a `PaymentGateway`
+//!
+//! An order may be paid in several parts, e.g. a deposit and later the
+//! balance, each an `order_payments` row with its own gateway reference,
+//! amount and state. The order's `order_payment_status` is derived from
+//! those rows (see [`derive_status`]) and uses the legacy three character
+//! codes whose first digit is the class (0 paid, 1 pending, 2 denied,
+//! 3 returned, 6 voided). The order also keeps the latest gateway reference
+//! in `paid_txn`, and settlement details reported by the gateway in
+//! `order_payment_lookup` and `bs_settlement`.
+//!
+//! Gift cards applied at checkout are already taken off the order total,
+//! so payments here only cover what is left.
+
+use commercerack_core::{Money, Timestamp};
+use commercerack_events::{outbox, DomainEvent};
+use commercerack_merchant::settings::SettingsService;
+use commercerack_merchant::MerchantError;
+use commercerack_payment::{
+    AuthorizeRequest, GatewayTransaction, PaymentError, PaymentGateway, PaymentSession, TransactionStatus,
+};
+use rust_decimal::Decimal;
+use sea_orm::{
+    entity::*, query::*, ConnectionTrait, DatabaseConnection, DatabaseTransaction, DbErr, Set, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use thiserror::Error;
+use ::entity::prelude::{Order as OrderModel, OrderPayment, OrderPayments, Orders};
+use tracing::instrument;
+
+use crate::{approvals, digital};
+use crate::invoices::InvoiceService;
+
+/// Payment state of an order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentStatus {
+    Paid,
+    /// Payments so far cover only part of the total
+    PartiallyPaid,
+    Authorized,
+    /// Captured but not yet confirmed by the gateway
+    Pending,
+    Denied,
+    Refunded,
+    PartiallyRefunded,
+    Voided,
+}
+
+impl PaymentStatus {
+    pub fn code(&self) -> &'static str {
+        match self {
+            PaymentStatus::Paid => "000",
+            PaymentStatus::PartiallyPaid => "120",
+            PaymentStatus::Authorized => "100",
+            PaymentStatus::Pending => "110",
+            PaymentStatus::Denied => "200",
+            PaymentStatus::Refunded => "300",
+            PaymentStatus::PartiallyRefunded => "301",
+            PaymentStatus::Voided => "600",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        match code {
+            "000" => Some(PaymentStatus::Paid),
+            "100" => Some(PaymentStatus::Authorized),
+            "110" => Some(PaymentStatus::Pending),
+            "120" => Some(PaymentStatus::PartiallyPaid),
+            "200" => Some(PaymentStatus::Denied),
+            "300" => Some(PaymentStatus::Refunded),
+            "301" => Some(PaymentStatus::PartiallyRefunded),
+            "600" => Some(PaymentStatus::Voided),
+            _ => None,
+        }
+    }
+
+    /// Order payment state matching a gateway transaction state
+    pub fn from_transaction(status: TransactionStatus) -> Self {
+        match status {
+            TransactionStatus::Captured => PaymentStatus::Paid,
+            TransactionStatus::Authorized => PaymentStatus::Authorized,
+            TransactionStatus::Pending => PaymentStatus::Pending,
+            TransactionStatus::Declined => PaymentStatus::Denied,
+            TransactionStatus::Refunded => PaymentStatus::Refunded,
+            TransactionStatus::Voided => PaymentStatus::Voided,
+        }
+    }
+
+    /// Payment state of an order; `None` if it was never paid
+    pub fn of(order: &OrderModel) -> Option<Self> {
+        order.order_payment_status.as_deref().and_then(Self::from_code)
+    }
+}
+
+impl FromStr for PaymentStatus {
+    type Err = String;
+
+    /// Parse the snake_case name used in the API, e.g. `partially_refunded`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "paid" => Ok(PaymentStatus::Paid),
+            "partially_paid" => Ok(PaymentStatus::PartiallyPaid),
+            "authorized" => Ok(PaymentStatus::Authorized),
+            "pending" => Ok(PaymentStatus::Pending),
+            "denied" => Ok(PaymentStatus::Denied),
+            "refunded" => Ok(PaymentStatus::Refunded),
+            "partially_refunded" => Ok(PaymentStatus::PartiallyRefunded),
+            "voided" => Ok(PaymentStatus::Voided),
+            other => Err(format!("unknown payment status {}", other)),
+        }
+    }
+}
+
+/// State of one payment; unknown values count as declined
+fn transaction_status(payment: &OrderPayment) -> TransactionStatus {
+    payment.status.parse().unwrap_or(TransactionStatus::Declined)
+}
+
+/// Whether a payment still holds the buyer's money at the gateway: an
+/// authorization to void or a capture not refunded in full
+pub fn is_open(payment: &OrderPayment) -> bool {
+    payment.reference.is_some()
+        && match transaction_status(payment) {
+            TransactionStatus::Authorized => true,
+            TransactionStatus::Captured => payment.refunded < payment.amount,
+            _ => false,
+        }
+}
+
+/// What of the captured payments can still be refunded
+pub fn refundable(payments: &[OrderPayment]) -> Decimal {
+    payments
+        .iter()
+        .filter(|payment| payment.reference.is_some() && transaction_status(payment) == TransactionStatus::Captured)
+        .map(|payment| (payment.amount - payment.refunded).max(Decimal::ZERO))
+        .sum()
+}
+
+/// What is left to pay of `total` after the payments that went through
+pub fn balance(total: Decimal, payments: &[OrderPayment]) -> Decimal {
+    let covered: Decimal = payments
+        .iter()
+        .filter(|payment| !matches!(transaction_status(payment), TransactionStatus::Declined | TransactionStatus::Voided))
+        .map(|payment| payment.amount)
+        .sum();
+    (total - covered).max(Decimal::ZERO)
+}
+
+/// Payment state of an order with `total` from its payments; `None`
+/// without any
+pub fn derive_status(total: Decimal, payments: &[OrderPayment]) -> Option<PaymentStatus> {
+    let (mut captured, mut held, mut pending, mut refunded) =
+        (Decimal::ZERO, Decimal::ZERO, Decimal::ZERO, Decimal::ZERO);
+    for payment in payments {
+        match transaction_status(payment) {
+            TransactionStatus::Captured | TransactionStatus::Refunded => {
+                captured += payment.amount - payment.refunded;
+                refunded += payment.refunded;
+            }
+            TransactionStatus::Authorized => held += payment.amount,
+            TransactionStatus::Pending => pending += payment.amount,
+            TransactionStatus::Declined | TransactionStatus::Voided => {}
+        }
+    }
+
+    let covered = captured + held + pending;
+    let status = if refunded > Decimal::ZERO {
+        if covered > Decimal::ZERO {
+            PaymentStatus::PartiallyRefunded
+        } else {
+            PaymentStatus::Refunded
+        }
+    } else if captured >= total && captured > Decimal::ZERO {
+        PaymentStatus::Paid
+    } else if covered >= total && pending > Decimal::ZERO {
+        PaymentStatus::Pending
+    } else if covered >= total && held > Decimal::ZERO {
+        PaymentStatus::Authorized
+    } else if covered > Decimal::ZERO {
+        PaymentStatus::PartiallyPaid
+    } else {
+        // Nothing went through; the last attempt says why
+        return payments.last().map(|payment| match transaction_status(payment) {
+            TransactionStatus::Voided => PaymentStatus::Voided,
+            _ => PaymentStatus::Denied,
+        });
+    };
+    Some(status)
+}
+
+#[derive(Error, Debug)]
+pub enum OrderPaymentError {
+    #[error("Order not found")]
+    NotFound,
+
+    #[error("Order payment is {0:?}, which does not allow this operation")]
+    InvalidState(Option<PaymentStatus>),
+
+    #[error("Amount {amount} is more than the {available} available")]
+    ExceedsAvailable { amount: Decimal, available: Decimal },
+
+    #[error("Order is waiting for approval or was rejected")]
+    NotApproved,
+
+    #[error("Order has been cancelled")]
+    Cancelled,
+
+    #[error(transparent)]
+    Gateway(#[from] PaymentError),
+
+    #[error(transparent)]
+    Merchant(#[from] MerchantError),
+
+    #[error("Database error: {0}")]
+    Db(#[from] DbErr),
+}
+
+fn new_payment(
+    order: &OrderModel,
+    reference: Option<String>,
+    amount: Decimal,
+    status: TransactionStatus,
+) -> ::entity::order_payments::ActiveModel {
+    let now = Timestamp::now();
+    ::entity::order_payments::ActiveModel {
+        mid: Set(order.mid),
+        order_id: Set(order.id),
+        reference: Set(reference),
+        amount: Set(amount),
+        refunded: Set(Decimal::ZERO),
+        status: Set(status.as_str().to_string()),
+        created_gmt: Set(now),
+        modified_gmt: Set(now),
+        ..Default::default()
+    }
+}
+
+fn update_payment(payment: OrderPayment, status: TransactionStatus) -> ::entity::order_payments::ActiveModel {
+    let mut active: ::entity::order_payments::ActiveModel = payment.into();
+    active.status = Set(status.as_str().to_string());
+    active.modified_gmt = Set(Timestamp::now());
+    active
+}
+
+/// Order payment service
+pub struct OrderPaymentService;
+
+impl OrderPaymentService {
+    async fn load(db: &DatabaseConnection, mid: i32, id: i32) -> Result<OrderModel, OrderPaymentError> {
+        Orders::find()
+            .filter(::entity::orders::Column::Mid.eq(mid))
+            .filter(::entity::orders::Column::Id.eq(id))
+            .one(db)
+            .await?
+            .ok_or(OrderPaymentError::NotFound)
+    }
+
+    /// An order's payments, oldest first
+    pub async fn payments<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        order_id: i32,
+    ) -> Result<Vec<OrderPayment>, DbErr> {
+        OrderPayments::find()
+            .filter(::entity::order_payments::Column::Mid.eq(mid))
+            .filter(::entity::order_payments::Column::OrderId.eq(order_id))
+            .order_by_asc(::entity::order_payments::Column::Id)
+            .all(db)
+            .await
+    }
+
+    /// Write payment changes and bring the order's payment state in line
+    /// with them
+    async fn save(
+        db: &DatabaseConnection,
+        order: OrderModel,
+        changes: Vec<::entity::order_payments::ActiveModel>,
+        txn: Option<&GatewayTransaction>,
+    ) -> Result<OrderModel, OrderPaymentError> {
+        let transaction = db.begin().await?;
+        let order = Self::save_in(&transaction, order, changes, txn).await?;
+        transaction.commit().await?;
+        Ok(order)
+    }
+
+    async fn save_in(
+        transaction: &DatabaseTransaction,
+        order: OrderModel,
+        changes: Vec<::entity::order_payments::ActiveModel>,
+        txn: Option<&GatewayTransaction>,
+    ) -> Result<OrderModel, OrderPaymentError> {
+        for change in changes {
+            change.save(transaction).await?;
+        }
+        let payments = Self::payments(transaction, order.mid, order.id).await?;
+        let status = derive_status(order.total, &payments);
+        let paid = status == Some(PaymentStatus::Paid) && PaymentStatus::of(&order) != status;
+
+        let mut active: ::entity::orders::ActiveModel = order.into();
+        active.order_payment_status = Set(status.map(|status| status.code().to_string()));
+        if let Some(txn) = txn {
+            active.paid_txn = Set(Some(txn.id.clone()));
+            if let Some(lookup) = &txn.lookup {
+                active.order_payment_lookup = Set(Some(lookup.clone()));
+            }
+            if let Some(settled_gmt) = txn.settled_gmt {
+                active.bs_settlement = Set(Some(Timestamp::from_unix(settled_gmt)));
+            }
+        }
+        if paid {
+            active.paid_gmt = Set(Some(Timestamp::now()));
+        }
+        let order = active.update(transaction).await?;
+
+        let event = if paid {
+            DomainEvent::OrderPaid(order.clone())
+        } else {
+            DomainEvent::OrderUpdated(order.clone())
+        };
+        outbox::record(transaction, &event).await?;
+        let order = if paid {
+            digital::fulfill(transaction, order).await?
+        } else {
+            order
+        };
+        Ok(order)
+    }
+
+    /// Save refunded payments with a credit memo for what they gave back
+    async fn save_refund(
+        db: &DatabaseConnection,
+        order: OrderModel,
+        changes: Vec<::entity::order_payments::ActiveModel>,
+        credited: Money,
+    ) -> Result<OrderModel, OrderPaymentError> {
+        let transaction = db.begin().await?;
+        let order = Self::save_in(&transaction, order, changes, None).await?;
+        if credited.amount() > Decimal::ZERO {
+            InvoiceService::credit_memo(&transaction, &order, credited).await?;
+        }
+        transaction.commit().await?;
+        Ok(order)
+    }
+
+    /// Keep what the gateway already did before `error`, then report it
+    async fn save_before_error(
+        db: &DatabaseConnection,
+        order: OrderModel,
+        changes: Vec<::entity::order_payments::ActiveModel>,
+        txn: Option<&GatewayTransaction>,
+        error: PaymentError,
+    ) -> Result<OrderModel, OrderPaymentError> {
+        if !changes.is_empty() {
+            Self::save(db, order, changes, txn).await?;
+        }
+        Err(error.into())
+    }
+
+    /// Charge the order's open balance to a tokenized payment method. With
+    /// `capture == false` the funds are only held until `capture`.
+    #[instrument(skip_all, fields(mid = mid, id = id))]
+    pub async fn pay(
+        db: &DatabaseConnection,
+        gateway: &dyn PaymentGateway,
+        mid: i32,
+        id: i32,
+        payment_method: &str,
+        capture: bool,
+    ) -> Result<OrderModel, OrderPaymentError> {
+        Self::add_payment(db, gateway, mid, id, payment_method, None, capture).await
+    }
+
+    /// Charge `amount` of the order's open balance, or all of it, to a
+    /// tokenized payment method. Orders held for approval can't be paid;
+    /// see [`approvals`].
+    #[instrument(skip_all, fields(mid = mid, id = id))]
+    pub async fn add_payment(
+        db: &DatabaseConnection,
+        gateway: &dyn PaymentGateway,
+        mid: i32,
+        id: i32,
+        payment_method: &str,
+        amount: Option<Decimal>,
+        capture: bool,
+    ) -> Result<OrderModel, OrderPaymentError> {
+        let order = Self::load(db, mid, id).await?;
+        if order.cancelled_gmt.is_some() {
+            return Err(OrderPaymentError::Cancelled);
+        }
+        if approvals::blocks_payment(&order) {
+            return Err(OrderPaymentError::NotApproved);
+        }
+        let payments = Self::payments(db, mid, id).await?;
+
+        let open = balance(order.total, &payments);
+        if open <= Decimal::ZERO {
+            return Err(OrderPaymentError::InvalidState(PaymentStatus::of(&order)));
+        }
+        let amount = amount.unwrap_or(open);
+        if amount > open {
+            return Err(OrderPaymentError::ExceedsAvailable { amount, available: open });
+        }
+
+        let request = AuthorizeRequest {
+            amount,
+            currency: SettingsService::get(db, mid).await?.currency.to_string(),
+            payment_method: payment_method.to_string(),
+            description: Some(format!("Order {}", order.orderid)),
+            capture,
+        };
+
+        match gateway.authorize(&request).await {
+            Ok(txn) => {
+                let payment = new_payment(&order, Some(txn.id.clone()), amount, txn.status);
+                Self::save(db, order, vec![payment], Some(&txn)).await
+            }
+            Err(PaymentError::Declined(reason)) => {
+                let payment = new_payment(&order, None, amount, TransactionStatus::Declined);
+                Self::save(db, order, vec![payment], None).await?;
+                Err(PaymentError::Declined(reason).into())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Collect the order's authorized payments
+    #[instrument(skip_all, fields(mid = mid, id = id))]
+    pub async fn capture(
+        db: &DatabaseConnection,
+        gateway: &dyn PaymentGateway,
+        mid: i32,
+        id: i32,
+    ) -> Result<OrderModel, OrderPaymentError> {
+        let order = Self::load(db, mid, id).await?;
+        let authorized: Vec<OrderPayment> = Self::payments(db, mid, id)
+            .await?
+            .into_iter()
+            .filter(|payment| transaction_status(payment) == TransactionStatus::Authorized && payment.reference.is_some())
+            .collect();
+        if authorized.is_empty() {
+            return Err(OrderPaymentError::InvalidState(PaymentStatus::of(&order)));
+        }
+
+        let mut changes = Vec::with_capacity(authorized.len());
+        let mut last = None;
+        for payment in authorized {
+            let reference = payment.reference.clone().unwrap_or_default();
+            match gateway.capture(&reference, None).await {
+                Ok(txn) => {
+                    // Some gateways hand back a new reference for the capture
+                    let mut change = update_payment(payment, txn.status);
+                    change.reference = Set(Some(txn.id.clone()));
+                    changes.push(change);
+                    last = Some(txn);
+                }
+                Err(e) => return Self::save_before_error(db, order, changes, last.as_ref(), e).await,
+            }
+        }
+        Self::save(db, order, changes, last.as_ref()).await
+    }
+
+    /// Refund an order, fully or by `amount`, newest payment first, and
+    /// issue a credit memo for it. Authorizations that were never captured
+    /// are voided instead.
+    #[instrument(skip_all, fields(mid = mid, id = id))]
+    pub async fn refund(
+        db: &DatabaseConnection,
+        gateway: &dyn PaymentGateway,
+        mid: i32,
+        id: i32,
+        amount: Option<Decimal>,
+    ) -> Result<OrderModel, OrderPaymentError> {
+        let order = Self::load(db, mid, id).await?;
+        let (captured, authorized): (Vec<OrderPayment>, Vec<OrderPayment>) = Self::payments(db, mid, id)
+            .await?
+            .into_iter()
+            .filter(is_open)
+            .partition(|payment| transaction_status(payment) == TransactionStatus::Captured);
+        let refundable: Decimal = captured.iter().map(|payment| payment.amount - payment.refunded).sum();
+        if refundable <= Decimal::ZERO && authorized.is_empty() {
+            return Err(OrderPaymentError::InvalidState(PaymentStatus::of(&order)));
+        }
+        if let Some(amount) = amount.filter(|amount| refundable > Decimal::ZERO && *amount > refundable) {
+            return Err(OrderPaymentError::ExceedsAvailable { amount, available: refundable });
+        }
+
+        let mut changes = Vec::new();
+        if amount.is_none() || refundable <= Decimal::ZERO {
+            for payment in authorized {
+                let reference = payment.reference.clone().unwrap_or_default();
+                if let Err(e) = gateway.void(&reference).await {
+                    return Self::save_before_error(db, order, changes, None, e).await;
+                }
+                changes.push(update_payment(payment, TransactionStatus::Voided));
+            }
+        }
+
+        let currency = SettingsService::get(db, mid).await?.currency;
+        let mut remaining = amount.unwrap_or(refundable);
+        let mut credited = Decimal::ZERO;
+        for payment in captured.into_iter().rev() {
+            if remaining <= Decimal::ZERO {
+                break;
+            }
+            let part = remaining.min(payment.amount - payment.refunded);
+            let reference = payment.reference.clone().unwrap_or_default();
+            if let Err(e) = gateway.refund(&reference, Some(Money::new(part, currency))).await {
+                if !changes.is_empty() {
+                    Self::save_refund(db, order, changes, Money::new(credited, currency)).await?;
+                }
+                return Err(e.into());
+            }
+            remaining -= part;
+            credited += part;
+
+            let refunded = payment.refunded + part;
+            let status = if refunded >= payment.amount {
+                TransactionStatus::Refunded
+            } else {
+                TransactionStatus::Captured
+            };
+            let mut change = update_payment(payment, status);
+            change.refunded = Set(refunded);
+            changes.push(change);
+        }
+
+        Self::save_refund(db, order, changes, Money::new(credited, currency)).await
+    }
+
+    /// Start a buyer-approved payment for the order's open balance (e.g. PayPal)
+    #[instrument(skip_all, fields(mid = mid, id = id))]
+    pub async fn create_session(
+        db: &DatabaseConnection,
+        gateway: &dyn PaymentGateway,
+        mid: i32,
+        id: i32,
+        capture: bool,
+    ) -> Result<PaymentSession, OrderPaymentError> {
+        let order = Self::load(db, mid, id).await?;
+        if order.cancelled_gmt.is_some() {
+            return Err(OrderPaymentError::Cancelled);
+        }
+        if approvals::blocks_payment(&order) {
+            return Err(OrderPaymentError::NotApproved);
+        }
+        let payments = Self::payments(db, mid, id).await?;
+
+        let open = balance(order.total, &payments);
+        if open <= Decimal::ZERO {
+            return Err(OrderPaymentError::InvalidState(PaymentStatus::of(&order)));
+        }
+
+        let currency = SettingsService::get(db, mid).await?.currency;
+        let description = format!("Order {}", order.orderid);
+        Ok(gateway
+            .create_session(open, currency.as_str(), &description, capture)
+            .await?)
+    }
+
+    /// Apply an asynchronous gateway notification to the payment it belongs
+    /// to. Returns the updated order, or `None` if no payment carries the
+    /// transaction or the payment was already in the reported state.
+    #[instrument(skip_all, fields(gateway_txn = txn.id.as_str()))]
+    pub async fn apply_notification(
+        db: &DatabaseConnection,
+        txn: &GatewayTransaction,
+    ) -> Result<Option<OrderModel>, OrderPaymentError> {
+        // Gateway references are globally unique, so no merchant is needed
+        let Some(payment) = OrderPayments::find()
+            .filter(::entity::order_payments::Column::Reference.eq(txn.id.as_str()))
+            .one(db)
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        // Don't stamp paid_gmt again for a repeated delivery
+        if transaction_status(&payment) == txn.status {
+            return Ok(None);
+        }
+        let Some(order) = Orders::find()
+            .filter(::entity::orders::Column::Mid.eq(payment.mid))
+            .filter(::entity::orders::Column::Id.eq(payment.order_id))
+            .one(db)
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        let refunded = if txn.status == TransactionStatus::Refunded {
+            payment.amount
+        } else {
+            payment.refunded
+        };
+        let mut change = update_payment(payment, txn.status);
+        change.refunded = Set(refunded);
+        Self::save(db, order, vec![change], Some(txn)).await.map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payment(amount: i64, refunded: i64, status: TransactionStatus) -> OrderPayment {
+        OrderPayment {
+            id: 1,
+            mid: 1,
+            order_id: 9,
+            reference: Some("txn_1".to_string()),
+            amount: Decimal::new(amount, 2),
+            refunded: Decimal::new(refunded, 2),
+            status: status.as_str().to_string(),
+            created_gmt: Timestamp::EPOCH,
+            modified_gmt: Timestamp::EPOCH,
+        }
+    }
+
+    #[test]
+    fn test_status_codes_roundtrip() {
+        for status in [
+            PaymentStatus::Paid,
+            PaymentStatus::PartiallyPaid,
+            PaymentStatus::Authorized,
+            PaymentStatus::Pending,
+            PaymentStatus::Denied,
+            PaymentStatus::Refunded,
+            PaymentStatus::PartiallyRefunded,
+            PaymentStatus::Voided,
+        ] {
+            assert_eq!(PaymentStatus::from_code(status.code()), Some(status));
+            let name = serde_json::to_value(status).unwrap();
+            assert_eq!(name.as_str().unwrap().parse::<PaymentStatus>(), Ok(status));
+        }
+        assert_eq!(PaymentStatus::from_code("999"), None);
+    }
+
+    #[test]
+    fn test_split_payments() {
+        use TransactionStatus::*;
+        let total = Decimal::new(10000, 2);
+
+        assert_eq!(derive_status(total, &[]), None);
+        assert_eq!(balance(total, &[]), total);
+
+        // A deposit, then the balance
+        let deposit = [payment(3000, 0, Captured)];
+        assert_eq!(derive_status(total, &deposit), Some(PaymentStatus::PartiallyPaid));
+        assert_eq!(balance(total, &deposit), Decimal::new(7000, 2));
+        let both = [payment(3000, 0, Captured), payment(7000, 0, Captured)];
+        assert_eq!(derive_status(total, &both), Some(PaymentStatus::Paid));
+        assert_eq!(balance(total, &both), Decimal::ZERO);
+
+        // Held or in-flight parts only count towards the balance
+        let held = [payment(3000, 0, Captured), payment(7000, 0, Authorized)];
+        assert_eq!(derive_status(total, &held), Some(PaymentStatus::Authorized));
+        let in_flight = [payment(3000, 0, Captured), payment(7000, 0, Pending)];
+        assert_eq!(derive_status(total, &in_flight), Some(PaymentStatus::Pending));
+
+        // Declined attempts don't
+        let declined = [payment(3000, 0, Captured), payment(7000, 0, Declined)];
+        assert_eq!(derive_status(total, &declined), Some(PaymentStatus::PartiallyPaid));
+        assert_eq!(balance(total, &declined), Decimal::new(7000, 2));
+        assert_eq!(derive_status(total, &[payment(10000, 0, Declined)]), Some(PaymentStatus::Denied));
+        assert_eq!(derive_status(total, &[payment(10000, 0, Voided)]), Some(PaymentStatus::Voided));
+
+        let partly_refunded = [payment(3000, 3000, Refunded), payment(7000, 0, Captured)];
+        assert_eq!(derive_status(total, &partly_refunded), Some(PaymentStatus::PartiallyRefunded));
+        let refunded = [payment(3000, 3000, Refunded), payment(7000, 7000, Refunded)];
+        assert_eq!(derive_status(total, &refunded), Some(PaymentStatus::Refunded));
+        assert_eq!(balance(total, &refunded), Decimal::ZERO);
+    }
+}
@@ -0,0 +1,297 @@
+//! Minimal PDF writer for generated documents
+//!
+//! Covers what invoices need and nothing more: US Letter pages of text in
+//! the built-in Helvetica faces, rules and filled boxes. Text is WinAnsi
+//! encoded, so characters it lacks print as `?`. Nothing is compressed;
+//! the documents are small.
+
+use std::fmt::Write as _;
+
+/// Page size in points
+pub const PAGE_WIDTH: f32 = 612.0;
+pub const PAGE_HEIGHT: f32 = 792.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Font {
+    Regular,
+    Bold,
+}
+
+impl Font {
+    fn resource(&self) -> &'static str {
+        match self {
+            Font::Regular => "F1",
+            Font::Bold => "F2",
+        }
+    }
+}
+
+/// A color with components from 0 to 255
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    pub const BLACK: Rgb = Rgb(0, 0, 0);
+    pub const WHITE: Rgb = Rgb(255, 255, 255);
+    pub const GRAY: Rgb = Rgb(110, 110, 110);
+
+    /// Parse `#RRGGBB`
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        let digits = hex.strip_prefix('#')?;
+        if digits.len() != 6 || !digits.is_ascii() {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(&digits[i..i + 2], 16).ok();
+        Some(Rgb(channel(0)?, channel(2)?, channel(4)?))
+    }
+
+    fn operands(&self) -> String {
+        format!(
+            "{:.3} {:.3} {:.3}",
+            self.0 as f32 / 255.0,
+            self.1 as f32 / 255.0,
+            self.2 as f32 / 255.0
+        )
+    }
+}
+
+/// Advance widths of ASCII 32..=126 in thousandths of the font size
+const HELVETICA: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556, 556, 556, 556,
+    556, 556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667,
+    556, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556, 333, 556,
+    556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556, 556, 556, 333, 500, 278, 556, 500, 722,
+    500, 500, 500, 334, 260, 334, 584,
+];
+const HELVETICA_BOLD: [u16; 95] = [
+    278, 333, 474, 556, 556, 889, 722, 238, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556, 556, 556, 556,
+    556, 556, 556, 556, 333, 333, 584, 584, 584, 611, 975, 722, 722, 722, 722, 667, 611, 778, 722, 278, 556, 722,
+    611, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 333, 278, 333, 584, 556, 333, 556,
+    611, 556, 611, 556, 333, 611, 611, 278, 278, 556, 278, 889, 611, 611, 611, 611, 389, 556, 333, 611, 556, 778,
+    556, 556, 500, 389, 280, 389, 584,
+];
+
+/// Width of `text` in points; characters outside ASCII count as a digit
+pub fn text_width(text: &str, size: f32, font: Font) -> f32 {
+    let widths = match font {
+        Font::Regular => &HELVETICA,
+        Font::Bold => &HELVETICA_BOLD,
+    };
+    let units: u32 = text
+        .chars()
+        .map(|c| match c as u32 {
+            code @ 32..=126 => widths[(code - 32) as usize] as u32,
+            _ => 556,
+        })
+        .sum();
+    units as f32 * size / 1000.0
+}
+
+/// `text` shortened with an ellipsis to fit `max_width`
+pub fn fit(text: &str, max_width: f32, size: f32, font: Font) -> String {
+    if text_width(text, size, font) <= max_width {
+        return text.to_string();
+    }
+    let mut fitted: String = text.to_string();
+    while !fitted.is_empty() && text_width(&format!("{}...", fitted), size, font) > max_width {
+        fitted.pop();
+    }
+    format!("{}...", fitted.trim_end())
+}
+
+/// A PDF string literal of `text` in WinAnsi
+fn literal(text: &str) -> Vec<u8> {
+    let mut out = vec![b'('];
+    for c in text.chars() {
+        let byte = match c {
+            '\u{20ac}' => 0x80,
+            c if (c as u32) < 0x20 => b' ',
+            c if (c as u32) < 0x7f || (0xa0..=0xff).contains(&(c as u32)) => c as u32 as u8,
+            _ => b'?',
+        };
+        if matches!(byte, b'(' | b')' | b'\\') {
+            out.push(b'\\');
+        }
+        out.push(byte);
+    }
+    out.push(b')');
+    out
+}
+
+/// One page's drawing operations
+#[derive(Debug, Default)]
+pub struct Page {
+    content: Vec<u8>,
+}
+
+impl Page {
+    /// Color of the text and boxes drawn next
+    pub fn color(&mut self, color: Rgb) {
+        self.content.extend(format!("{} rg\n", color.operands()).into_bytes());
+    }
+
+    /// Text starting at `x`, on the baseline `y`
+    pub fn text(&mut self, x: f32, y: f32, size: f32, font: Font, text: &str) {
+        self.content
+            .extend(format!("BT /{} {:.1} Tf {:.2} {:.2} Td ", font.resource(), size, x, y).into_bytes());
+        self.content.extend(literal(text));
+        self.content.extend(b" Tj ET\n");
+    }
+
+    /// Text ending at `right`
+    pub fn text_right(&mut self, right: f32, y: f32, size: f32, font: Font, text: &str) {
+        self.text(right - text_width(text, size, font), y, size, font, text);
+    }
+
+    /// Text centered on `center`
+    pub fn text_centered(&mut self, center: f32, y: f32, size: f32, font: Font, text: &str) {
+        self.text(center - text_width(text, size, font) / 2.0, y, size, font, text);
+    }
+
+    /// A rule from (`x1`, `y`) to (`x2`, `y`)
+    pub fn rule(&mut self, x1: f32, x2: f32, y: f32, width: f32) {
+        self.content
+            .extend(format!("{:.2} w {:.2} {:.2} m {:.2} {:.2} l S\n", width, x1, y, x2, y).into_bytes());
+    }
+
+    /// A filled box with its lower left corner at (`x`, `y`), leaving the
+    /// current color as it was
+    pub fn fill_rect(&mut self, x: f32, y: f32, width: f32, height: f32, color: Rgb) {
+        self.content.extend(
+            format!("q {} rg {:.2} {:.2} {:.2} {:.2} re f Q\n", color.operands(), x, y, width, height).into_bytes(),
+        );
+    }
+}
+
+/// A document being built page by page
+#[derive(Debug, Default)]
+pub struct Document {
+    title: String,
+    pages: Vec<Page>,
+}
+
+impl Document {
+    pub fn new(title: &str) -> Self {
+        Self {
+            title: title.to_string(),
+            pages: Vec::new(),
+        }
+    }
+
+    /// Start a new page and draw on it
+    pub fn add_page(&mut self) -> &mut Page {
+        self.pages.push(Page::default());
+        self.pages.last_mut().expect("page was just added")
+    }
+
+    pub fn pages_mut(&mut self) -> &mut [Page] {
+        &mut self.pages
+    }
+
+    /// The finished file
+    pub fn render(self) -> Vec<u8> {
+        // 1 catalog, 2 page tree, 3 and 4 fonts, 5 info, then a page and
+        // its content stream for each page
+        let page_ids: Vec<usize> = (0..self.pages.len()).map(|i| 6 + 2 * i).collect();
+        let mut objects: Vec<Vec<u8>> = vec![
+            b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+            format!(
+                "<< /Type /Pages /Kids [{}] /Count {} >>",
+                page_ids.iter().map(|id| format!("{} 0 R", id)).collect::<Vec<_>>().join(" "),
+                page_ids.len()
+            )
+            .into_bytes(),
+            b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>".to_vec(),
+            b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding /WinAnsiEncoding >>".to_vec(),
+            [b"<< /Title ".to_vec(), literal(&self.title), b" /Producer (CommerceRack) >>".to_vec()].concat(),
+        ];
+        for (page, id) in self.pages.into_iter().zip(&page_ids) {
+            objects.push(
+                format!(
+                    "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+                     /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {} 0 R >>",
+                    PAGE_WIDTH,
+                    PAGE_HEIGHT,
+                    id + 1
+                )
+                .into_bytes(),
+            );
+            let mut stream = format!("<< /Length {} >>\nstream\n", page.content.len()).into_bytes();
+            stream.extend(page.content);
+            stream.extend(b"\nendstream");
+            objects.push(stream);
+        }
+
+        let mut out = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+        let mut offsets = Vec::with_capacity(objects.len());
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(out.len());
+            out.extend(format!("{} 0 obj\n", i + 1).into_bytes());
+            out.extend(object);
+            out.extend(b"\nendobj\n");
+        }
+
+        let xref = out.len();
+        let mut table = format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+        for offset in offsets {
+            let _ = writeln!(table, "{:010} 00000 n ", offset);
+        }
+        let _ = write!(
+            table,
+            "trailer\n<< /Size {} /Root 1 0 R /Info 5 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref
+        );
+        out.extend(table.into_bytes());
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_has_valid_xref() {
+        let mut doc = Document::new("Test");
+        doc.add_page().text(50.0, 700.0, 12.0, Font::Regular, "Hello (world)");
+        doc.add_page().rule(50.0, 562.0, 700.0, 0.5);
+        let pdf = doc.render();
+        let text = String::from_utf8_lossy(&pdf);
+
+        assert!(pdf.starts_with(b"%PDF-1.4"));
+        assert!(text.ends_with("%%EOF\n"));
+        assert!(text.contains("/Count 2"));
+        assert!(text.contains(r"(Hello \(world\)) Tj"));
+
+        // Every xref entry points at the start of its object
+        let xref = pdf.windows(6).rposition(|window| window == b"\nxref\n").unwrap() + 1;
+        let table = std::str::from_utf8(&pdf[xref..]).unwrap();
+        let entries: Vec<usize> = table
+            .lines()
+            .skip(3)
+            .take_while(|line| line.ends_with(" n "))
+            .map(|line| line[..10].parse().unwrap())
+            .collect();
+        assert_eq!(entries.len(), 9);
+        for (i, offset) in entries.into_iter().enumerate() {
+            assert!(pdf[offset..].starts_with(format!("{} 0 obj", i + 1).as_bytes()));
+        }
+        let startxref: usize = table.rsplit("startxref\n").next().unwrap().lines().next().unwrap().parse().unwrap();
+        assert_eq!(startxref, xref);
+    }
+
+    #[test]
+    fn test_text_encoding_and_width() {
+        assert_eq!(literal(r"a\b"), br"(a\\b)".to_vec());
+        assert_eq!(literal("Café €5 日"), b"(Caf\xe9 \x805 ?)".to_vec());
+        assert_eq!(text_width("10.00", 10.0, Font::Regular), 25.02);
+
+        let fitted = fit("A very long product name indeed", 80.0, 10.0, Font::Regular);
+        assert!(fitted.ends_with("..."));
+        assert!(text_width(&fitted, 10.0, Font::Regular) <= 80.0);
+        assert_eq!(fit("Short", 80.0, 10.0, Font::Regular), "Short");
+        assert_eq!(Rgb::from_hex("#1a2B3c"), Some(Rgb(0x1a, 0x2b, 0x3c)));
+        assert_eq!(Rgb::from_hex("1a2b3c"), None);
+    }
+}
@@ -0,0 +1,297 @@
+//! Pickup in store
+//!
+//! Merchants list the stores buyers can collect orders from. An order
+//! placed for pickup names its store in `pickup_location_id` and carries no
+//! shipping charge. Once it is packed, the merchant marks it
+//! [ready](PickupService::ready), which tells the buyer where to collect
+//! it; [confirming](PickupService::confirm) the collection completes the
+//! order.
+//!
+//! Locations are never deleted, since orders point at them; deactivating
+//! one takes it off the storefront and out of checkout.
+
+use commercerack_core::Timestamp;
+use commercerack_events::{outbox, DomainEvent};
+use commercerack_inventory::warehouses::ShipTo;
+use sea_orm::{entity::*, query::*, ConnectionTrait, DatabaseTransaction, Set, TransactionTrait};
+use ::entity::prelude::{Order as OrderModel, Orders, StoreLocation, StoreLocations};
+use tracing::instrument;
+
+use crate::pools::COMPLETED_POOL;
+use crate::OrderError;
+
+/// `fulfillment` of orders that are shipped
+pub const SHIP: &str = "ship";
+/// `fulfillment` of orders collected from a store
+pub const PICKUP: &str = "pickup";
+
+/// How `order` reaches the buyer: [`PICKUP`] or [`SHIP`]
+pub fn fulfillment(order: &OrderModel) -> &'static str {
+    if order.pickup_location_id.is_some() {
+        PICKUP
+    } else {
+        SHIP
+    }
+}
+
+/// Where stock for an order collected at `location` is allocated from
+pub fn ship_to(location: &StoreLocation) -> ShipTo {
+    ShipTo::new(&location.country, &location.state)
+}
+
+/// Details of a store location
+#[derive(Debug, Clone)]
+pub struct StoreLocationInput {
+    pub name: String,
+    pub street1: String,
+    pub street2: String,
+    pub city: String,
+    pub state: String,
+    pub zip: String,
+    pub country: String,
+    pub phone: String,
+    /// Opening hours as shown to shoppers
+    pub hours: String,
+    pub active: bool,
+}
+
+/// Store location service
+pub struct StoreLocationService;
+
+impl StoreLocationService {
+    fn active_model(input: StoreLocationInput) -> ::entity::store_locations::ActiveModel {
+        ::entity::store_locations::ActiveModel {
+            name: Set(input.name.trim().to_string()),
+            street1: Set(input.street1.trim().to_string()),
+            street2: Set(input.street2.trim().to_string()),
+            city: Set(input.city.trim().to_string()),
+            state: Set(input.state.trim().to_uppercase()),
+            zip: Set(input.zip.trim().to_string()),
+            country: Set(input.country.trim().to_uppercase()),
+            phone: Set(input.phone.trim().to_string()),
+            hours: Set(input.hours.trim().to_string()),
+            active: Set(input.active),
+            ..Default::default()
+        }
+    }
+
+    /// Add a store location
+    #[instrument(skip_all, fields(mid = mid))]
+    pub async fn create<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        input: StoreLocationInput,
+    ) -> Result<StoreLocation, OrderError> {
+        let mut location = Self::active_model(input);
+        location.mid = Set(mid);
+        location.created_gmt = Set(Timestamp::now());
+        Ok(location.insert(db).await?)
+    }
+
+    /// Change a store location. Orders already placed for it keep pointing
+    /// at it.
+    #[instrument(skip_all, fields(mid = mid, id = id))]
+    pub async fn update<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        id: i32,
+        input: StoreLocationInput,
+    ) -> Result<StoreLocation, OrderError> {
+        let existing = Self::find(db, mid, id).await?;
+        let mut location = Self::active_model(input);
+        location.id = Unchanged(existing.id);
+        Ok(location.update(db).await?)
+    }
+
+    /// A merchant's store location
+    pub async fn find<C: ConnectionTrait>(db: &C, mid: i32, id: i32) -> Result<StoreLocation, OrderError> {
+        StoreLocations::find_by_id(id)
+            .filter(::entity::store_locations::Column::Mid.eq(mid))
+            .one(db)
+            .await?
+            .ok_or(OrderError::LocationNotFound)
+    }
+
+    /// A store location buyers can choose at checkout
+    pub async fn pickup_location<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        id: i32,
+    ) -> Result<StoreLocation, OrderError> {
+        let location = Self::find(db, mid, id).await?;
+        if !location.active {
+            return Err(OrderError::LocationNotFound);
+        }
+        Ok(location)
+    }
+
+    /// A merchant's store locations by name; only active ones unless
+    /// `include_inactive`
+    pub async fn list<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        include_inactive: bool,
+    ) -> Result<Vec<StoreLocation>, OrderError> {
+        let mut query = StoreLocations::find().filter(::entity::store_locations::Column::Mid.eq(mid));
+        if !include_inactive {
+            query = query.filter(::entity::store_locations::Column::Active.eq(true));
+        }
+
+        let locations = query
+            .order_by_asc(::entity::store_locations::Column::Name)
+            .order_by_asc(::entity::store_locations::Column::Id)
+            .all(db)
+            .await?;
+        Ok(locations)
+    }
+}
+
+/// Pickup order workflow
+pub struct PickupService;
+
+impl PickupService {
+    /// A pickup order, locked for the rest of `txn`
+    async fn pickup_order(txn: &DatabaseTransaction, mid: i32, id: i32) -> Result<OrderModel, OrderError> {
+        let order = Orders::find()
+            .filter(::entity::orders::Column::Mid.eq(mid))
+            .filter(::entity::orders::Column::Id.eq(id))
+            .lock_exclusive()
+            .one(txn)
+            .await?
+            .ok_or(OrderError::NotFound)?;
+        if order.pickup_location_id.is_none() {
+            return Err(OrderError::NotPickup);
+        }
+        if order.cancelled_gmt.is_some() {
+            return Err(OrderError::Cancelled);
+        }
+        if order.picked_up_gmt.is_some() {
+            return Err(OrderError::AlreadyPickedUp);
+        }
+        Ok(order)
+    }
+
+    /// Mark a pickup order ready to collect and tell the buyer where. An
+    /// order already marked ready is returned as it is, without telling
+    /// the buyer again.
+    #[instrument(skip_all, fields(mid = mid, id = id))]
+    pub async fn ready<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: i32,
+        id: i32,
+    ) -> Result<OrderModel, OrderError> {
+        let txn = db.begin().await?;
+        let order = Self::pickup_order(&txn, mid, id).await?;
+        if order.pickup_ready_gmt.is_some() {
+            return Ok(order);
+        }
+        let location = StoreLocationService::find(&txn, mid, order.pickup_location_id.unwrap_or_default()).await?;
+
+        let mut active: ::entity::orders::ActiveModel = order.into();
+        active.pickup_ready_gmt = Set(Some(Timestamp::now()));
+        let result = active.update(&txn).await?;
+        outbox::record(&txn, &DomainEvent::OrderReadyForPickup { order: result.clone(), location }).await?;
+        txn.commit().await?;
+        Ok(result)
+    }
+
+    /// Record that the buyer collected a ready pickup order, completing it.
+    /// Handing it over counts as shipping it.
+    #[instrument(skip_all, fields(mid = mid, id = id))]
+    pub async fn confirm<C: ConnectionTrait + TransactionTrait>(
+        db: &C,
+        mid: i32,
+        id: i32,
+    ) -> Result<OrderModel, OrderError> {
+        let txn = db.begin().await?;
+        let order = Self::pickup_order(&txn, mid, id).await?;
+        if order.pickup_ready_gmt.is_none() {
+            return Err(OrderError::NotReadyForPickup);
+        }
+
+        let now = Timestamp::now();
+        let shipped = order.shipped_gmt;
+        let mut active: ::entity::orders::ActiveModel = order.into();
+        active.picked_up_gmt = Set(Some(now));
+        active.shipped_gmt = Set(Some(shipped.unwrap_or(now)));
+        active.pool = Set(COMPLETED_POOL.to_string());
+        let result = active.update(&txn).await?;
+        outbox::record(&txn, &DomainEvent::OrderUpdated(result.clone())).await?;
+        txn.commit().await?;
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+    use sea_orm::{DatabaseBackend, MockDatabase};
+
+    fn order(pickup_location_id: Option<i32>, ready: Option<i64>, picked_up: Option<i64>) -> OrderModel {
+        OrderModel {
+            id: 9,
+            mid: 1,
+            orderid: "2025-11-18-00000009".to_string(),
+            cartid: String::new(),
+            customer: 7,
+            pool: "RECENT".to_string(),
+            total: Decimal::new(2500, 2),
+            created_gmt: Timestamp::EPOCH,
+            paid_gmt: Some(Timestamp::EPOCH),
+            paid_txn: None,
+            order_payment_status: None,
+            order_payment_lookup: None,
+            bs_settlement: None,
+            shipped_gmt: None,
+            inv_gmt: None,
+            company_id: None,
+            review_status: None,
+            ship_method: None,
+            bill_email: "buyer@example.com".to_string(),
+            v: 1,
+            mkt: 0,
+            erefid: None,
+            mkt_bitstr: String::new(),
+            pickup_location_id,
+            pickup_ready_gmt: ready.map(Timestamp::from_unix),
+            picked_up_gmt: picked_up.map(Timestamp::from_unix),
+            cancelled_gmt: None,
+            cancel_reason: None,
+        }
+    }
+
+    #[test]
+    fn test_fulfillment_follows_pickup_location() {
+        assert_eq!(fulfillment(&order(None, None, None)), SHIP);
+        assert_eq!(fulfillment(&order(Some(3), None, None)), PICKUP);
+    }
+
+    #[tokio::test]
+    async fn test_only_ready_pickup_orders_are_confirmed() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![order(None, None, None)]])
+            .into_connection();
+        assert!(matches!(PickupService::ready(&db, 1, 9).await, Err(OrderError::NotPickup)));
+
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![order(Some(3), None, None)]])
+            .into_connection();
+        assert!(matches!(PickupService::confirm(&db, 1, 9).await, Err(OrderError::NotReadyForPickup)));
+
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![order(Some(3), Some(100), Some(200))]])
+            .into_connection();
+        assert!(matches!(PickupService::confirm(&db, 1, 9).await, Err(OrderError::AlreadyPickedUp)));
+    }
+
+    #[tokio::test]
+    async fn test_ready_twice_tells_the_buyer_once() {
+        // Nothing is written the second time
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![order(Some(3), Some(100), None)]])
+            .into_connection();
+        let result = PickupService::ready(&db, 1, 9).await.unwrap();
+        assert_eq!(result.pickup_ready_gmt, Some(Timestamp::from_unix(100)));
+    }
+}
@@ -0,0 +1,758 @@
+//! Checkout as a pipeline of stages
+//!
+//! [`CheckoutService::place_order`](crate::checkout::CheckoutService::place_order)
+//! runs the [`installed`] pipeline, by default [`CheckoutPipeline::standard`]:
+//!
+//! ```text
+//! validate_cart → resolve_pricing → apply_promotions → compute_tax →
+//! quote_shipping → reserve_inventory → check_spending_limit →
+//! apply_gift_cards → create_order
+//! ```
+//!
+//! Every stage is a [`CheckoutStage`] trait object and runs in two passes,
+//! both inside the checkout transaction. First each stage [prepares], in
+//! order, adding line items to the [`Checkout`] or refusing it. The order
+//! is then written from those line items, and each stage [completes] in
+//! the same order, doing whatever needs the order's ID. A deployment adds
+//! its own stages by installing a pipeline at startup:
+//!
+//! ```ignore
+//! let pipeline = CheckoutPipeline::standard().insert_before("apply_gift_cards", Arc::new(FraudScreen));
+//! commercerack_order::pipeline::install(pipeline);
+//! ```
+//!
+//! [prepares]: CheckoutStage::prepare
+//! [completes]: CheckoutStage::complete
+
+use async_trait::async_trait;
+use chrono::Utc;
+use commercerack_cart::{AbandonedCartService, AppliedCoupon, Cart, ItemOptions};
+use commercerack_core::Timestamp;
+use commercerack_customer::companies::CompanyService;
+use commercerack_customer::CustomerService;
+use commercerack_events::{outbox, DomainEvent};
+use commercerack_giftcards::GiftCardService;
+use commercerack_inventory::warehouses::ShipTo;
+use commercerack_inventory::InventoryService;
+use commercerack_merchant::settings::{Settings, SettingsService};
+use commercerack_product::pricing::PricingService;
+use commercerack_promotion::CouponService;
+use commercerack_shipping::QuotedShipment;
+use rust_decimal::Decimal;
+use sea_orm::{entity::*, query::*, DatabaseTransaction, TransactionTrait};
+use ::entity::prelude::{Company, CompanyBuyer, Coupon, GiftCard, Orders, StoreLocation};
+use std::sync::{Arc, OnceLock};
+use tracing::instrument;
+
+use crate::checkout::{
+    generate_orderid, validate_cart, CheckoutError, Fulfillment, TaxContext, COUPON_SKU, GIFT_CARD_SKU, NEW_ORDER_POOL, SHIP_SKU,
+    TAX_SKU,
+};
+use crate::items::NewOrderItem;
+use crate::payment::PaymentStatus;
+use crate::{approvals, digital, duplicates, fulfillment_groups, insert_order, OrderWithItems, GUEST_CUSTOMER};
+
+/// A cart on its way to becoming an order, passed from stage to stage
+pub struct Checkout<'a> {
+    pub mid: i32,
+    pub customer: i32,
+    /// Required for guests; may be empty for customers
+    pub bill_email: &'a str,
+    pub cart: &'a Cart,
+    /// How to tax the order; `None` charges no tax
+    pub tax: Option<TaxContext<'a>>,
+    /// Shipments the order leaves in, each quoted for its chosen method
+    pub shipments: Vec<QuotedShipment>,
+    /// Store the buyer collects the order from; such orders have no
+    /// shipments
+    pub pickup: Option<StoreLocation>,
+    /// Picks the warehouses stock is allocated from
+    pub ship_to: Option<&'a ShipTo>,
+    pub orderid: String,
+    /// The merchant's settings, loaded by the `validate_cart` stage; the
+    /// defaults until then
+    pub settings: Arc<Settings>,
+    /// Line items the order will be written with
+    pub items: Vec<NewOrderItem>,
+    /// Company the customer buys for, if any
+    pub membership: Option<(Company, CompanyBuyer)>,
+    /// The cart's coupon, checked again in the checkout transaction
+    pub coupon: Option<(Coupon, AppliedCoupon)>,
+    /// Gift cards paying for the order and how much each pays
+    pub gift_cards: Vec<(GiftCard, Decimal)>,
+}
+
+impl<'a> Checkout<'a> {
+    pub fn new(
+        mid: i32,
+        customer: i32,
+        bill_email: &'a str,
+        cart: &'a Cart,
+        tax: Option<TaxContext<'a>>,
+        fulfillment: Fulfillment,
+        ship_to: Option<&'a ShipTo>,
+    ) -> Self {
+        let (shipments, pickup) = match fulfillment {
+            Fulfillment::Ship(shipments) => (shipments, None),
+            Fulfillment::Pickup(location) => (Vec::new(), Some(*location)),
+        };
+        Self {
+            mid,
+            customer,
+            bill_email,
+            cart,
+            tax,
+            shipments,
+            pickup,
+            ship_to,
+            orderid: generate_orderid(),
+            settings: Arc::default(),
+            items: Vec::new(),
+            membership: None,
+            coupon: None,
+            gift_cards: Vec::new(),
+        }
+    }
+
+    /// Check out under the merchant's `settings`, numbering the order in
+    /// their format
+    pub fn use_settings(&mut self, settings: Arc<Settings>) {
+        self.orderid = settings.order_number(Utc::now());
+        self.settings = settings;
+    }
+
+    pub fn is_guest(&self) -> bool {
+        self.customer == GUEST_CUSTOMER
+    }
+
+    /// Coupon discount on the order
+    pub fn discount(&self) -> Decimal {
+        self.coupon.as_ref().map(|(_, applied)| applied.discount).unwrap_or_default()
+    }
+
+    /// Order total before gift cards pay any of it
+    pub fn due(&self) -> Decimal {
+        self.items.iter().filter(|item| item.sku != GIFT_CARD_SKU).map(|item| item.subtotal()).sum()
+    }
+
+    /// Product lines by SKU and quantity
+    pub fn lines(&self) -> Vec<(&str, i32)> {
+        self.cart.items.iter().map(|item| (item.sku.as_str(), item.quantity)).collect()
+    }
+
+    /// Whether a free shipping coupon waives the shipping charges
+    pub fn free_shipping(&self) -> bool {
+        self.coupon.as_ref().is_some_and(|(_, applied)| applied.free_shipping)
+    }
+
+    /// Whether gift cards cover all of `order`
+    fn paid_by_gift_cards(&self, order: &::entity::prelude::Order) -> bool {
+        !self.gift_cards.is_empty() && order.total <= Decimal::ZERO
+    }
+}
+
+/// One step of checkout
+#[async_trait]
+pub trait CheckoutStage: Send + Sync {
+    /// Short identifier other stages are placed around, e.g. `compute_tax`
+    fn name(&self) -> &'static str;
+
+    /// Run before the order is written. Add line items to `checkout`, or
+    /// return an error to refuse it.
+    async fn prepare(&self, _txn: &DatabaseTransaction, _checkout: &mut Checkout<'_>) -> Result<(), CheckoutError> {
+        Ok(())
+    }
+
+    /// Run once `placed` is written; changes to it are kept
+    async fn complete(
+        &self,
+        _txn: &DatabaseTransaction,
+        _checkout: &Checkout<'_>,
+        _placed: &mut OrderWithItems,
+    ) -> Result<(), CheckoutError> {
+        Ok(())
+    }
+}
+
+/// The stages checkout runs, in order
+#[derive(Clone)]
+pub struct CheckoutPipeline {
+    stages: Vec<Arc<dyn CheckoutStage>>,
+}
+
+impl CheckoutPipeline {
+    /// The stages every checkout needs
+    pub fn standard() -> Self {
+        Self {
+            stages: vec![
+                Arc::new(ValidateCart),
+                Arc::new(ResolvePricing),
+                Arc::new(ApplyPromotions),
+                Arc::new(ComputeTax),
+                Arc::new(QuoteShipping),
+                Arc::new(ReserveInventory),
+                Arc::new(CheckSpendingLimit),
+                Arc::new(ApplyGiftCards),
+                Arc::new(CreateOrder),
+            ],
+        }
+    }
+
+    pub fn stage_names(&self) -> Vec<&'static str> {
+        self.stages.iter().map(|stage| stage.name()).collect()
+    }
+
+    /// Run `stage` right before the stage called `name`
+    ///
+    /// # Panics
+    ///
+    /// If the pipeline has no stage called `name`.
+    pub fn insert_before(mut self, name: &str, stage: Arc<dyn CheckoutStage>) -> Self {
+        let at = self.position(name);
+        self.stages.insert(at, stage);
+        self
+    }
+
+    /// Run `stage` right after the stage called `name`
+    ///
+    /// # Panics
+    ///
+    /// If the pipeline has no stage called `name`.
+    pub fn insert_after(mut self, name: &str, stage: Arc<dyn CheckoutStage>) -> Self {
+        let at = self.position(name) + 1;
+        self.stages.insert(at, stage);
+        self
+    }
+
+    /// Run `stage` last
+    pub fn push(mut self, stage: Arc<dyn CheckoutStage>) -> Self {
+        self.stages.push(stage);
+        self
+    }
+
+    fn position(&self, name: &str) -> usize {
+        self.stages
+            .iter()
+            .position(|stage| stage.name() == name)
+            .unwrap_or_else(|| panic!("checkout pipeline has no stage {}", name))
+    }
+
+    /// Turn `checkout` into an order in one transaction
+    #[instrument(skip_all, fields(mid = checkout.mid, customer = checkout.customer, cart_id = checkout.cart.cart_id.as_str()))]
+    pub async fn run<C: TransactionTrait>(
+        &self,
+        db: &C,
+        mut checkout: Checkout<'_>,
+    ) -> Result<OrderWithItems, CheckoutError> {
+        let txn = db.begin().await?;
+        for stage in &self.stages {
+            stage.prepare(&txn, &mut checkout).await?;
+        }
+
+        let mut placed = insert_order(
+            &txn,
+            checkout.mid,
+            &checkout.orderid,
+            &checkout.cart.cart_id,
+            checkout.customer,
+            NEW_ORDER_POOL,
+            &checkout.items,
+        )
+        .await?;
+        let paid = checkout.paid_by_gift_cards(&placed.order);
+        let bill_email = checkout.bill_email.trim();
+        // The first shipment's method stands for the order's
+        let ship_method = checkout.shipments.first().map(|shipped| shipped.quote.method.clone());
+        if ship_method.is_some() || checkout.pickup.is_some() || !bill_email.is_empty() || paid {
+            let mut order: ::entity::orders::ActiveModel = placed.order.into();
+            order.ship_method = Set(ship_method);
+            order.pickup_location_id = Set(checkout.pickup.as_ref().map(|location| location.id));
+            order.bill_email = Set(bill_email.to_string());
+            if paid {
+                order.order_payment_status = Set(Some(PaymentStatus::Paid.code().to_string()));
+                order.paid_gmt = Set(Some(Timestamp::now()));
+            }
+            placed.order = order.update(&txn).await?;
+        }
+
+        for stage in &self.stages {
+            stage.complete(&txn, &checkout, &mut placed).await?;
+        }
+        txn.commit().await?;
+
+        Ok(placed)
+    }
+}
+
+static PIPELINE: OnceLock<CheckoutPipeline> = OnceLock::new();
+
+/// Use `pipeline` for every checkout in this process. Only the first
+/// pipeline installed is used.
+pub fn install(pipeline: CheckoutPipeline) {
+    let _ = PIPELINE.set(pipeline);
+}
+
+/// The pipeline checkout runs: the installed one, or else the standard one
+pub fn installed() -> &'static CheckoutPipeline {
+    PIPELINE.get_or_init(CheckoutPipeline::standard)
+}
+
+/// Refuses empty or invalid carts, guests without an email and carts that
+/// were already checked out, then loads the merchant's settings (numbering
+/// the order in their format) and finds the customer's company
+pub struct ValidateCart;
+
+#[async_trait]
+impl CheckoutStage for ValidateCart {
+    fn name(&self) -> &'static str {
+        "validate_cart"
+    }
+
+    async fn prepare(&self, txn: &DatabaseTransaction, checkout: &mut Checkout<'_>) -> Result<(), CheckoutError> {
+        validate_cart(checkout.cart)?;
+        if checkout.is_guest() && checkout.bill_email.trim().is_empty() {
+            return Err(CheckoutError::GuestEmailRequired);
+        }
+
+        // A cart can only ever produce one order
+        let existing = Orders::find()
+            .filter(::entity::orders::Column::Mid.eq(checkout.mid))
+            .filter(::entity::orders::Column::Cartid.eq(checkout.cart.cart_id.as_str()))
+            .one(txn)
+            .await?;
+        if existing.is_some() {
+            return Err(CheckoutError::AlreadyCheckedOut(checkout.cart.cart_id.clone()));
+        }
+
+        checkout.use_settings(SettingsService::get(txn, checkout.mid).await?);
+
+        if !checkout.is_guest() {
+            checkout.membership = CompanyService::membership(txn, checkout.mid, checkout.customer).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Prices each cart line from the catalog for the customer's price group
+/// and adds it as a line item, plus any surcharge for its options. Refuses
+/// SKUs that are no longer for sale, and lines the cart holds at another
+/// price than the catalog's.
+pub struct ResolvePricing;
+
+#[async_trait]
+impl CheckoutStage for ResolvePricing {
+    fn name(&self) -> &'static str {
+        "resolve_pricing"
+    }
+
+    async fn prepare(&self, txn: &DatabaseTransaction, checkout: &mut Checkout<'_>) -> Result<(), CheckoutError> {
+        let group = if checkout.is_guest() {
+            String::new()
+        } else {
+            CustomerService::pricing_group_of(txn, checkout.mid, checkout.customer).await?
+        };
+
+        for item in &checkout.cart.items {
+            let resolved =
+                PricingService::resolve_price(txn, checkout.mid, &item.sku, Some(&group), item.quantity).await?;
+            if resolved.unit_price != item.unit_price {
+                return Err(CheckoutError::PriceChanged {
+                    sku: item.sku.clone(),
+                    previous: item.unit_price,
+                    current: resolved.unit_price,
+                });
+            }
+            checkout.items.push(NewOrderItem {
+                sku: item.sku.clone(),
+                product_name: item.product_name.clone(),
+                quantity: item.quantity,
+                unit_price: resolved.unit_price + item.surcharge,
+                options: item.options.clone(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Checks the cart's coupon again and adds its discount as a `%COUPON`
+/// line item; redeems it once the order exists
+pub struct ApplyPromotions;
+
+#[async_trait]
+impl CheckoutStage for ApplyPromotions {
+    fn name(&self) -> &'static str {
+        "apply_promotions"
+    }
+
+    async fn prepare(&self, txn: &DatabaseTransaction, checkout: &mut Checkout<'_>) -> Result<(), CheckoutError> {
+        // Guests share one customer ID, so per-customer limits can't apply
+        let customer = (!checkout.is_guest()).then_some(checkout.customer);
+        checkout.coupon = CouponService::revalidate(txn, checkout.mid, checkout.cart, customer).await?;
+        let discount = checkout.discount();
+        if let Some((_, applied)) = checkout.coupon.as_ref().filter(|_| discount > Decimal::ZERO) {
+            checkout.items.push(NewOrderItem {
+                sku: COUPON_SKU.to_string(),
+                product_name: format!("Coupon {}", applied.code),
+                quantity: 1,
+                unit_price: -discount,
+                options: ItemOptions::default(),
+            });
+        }
+        Ok(())
+    }
+
+    async fn complete(
+        &self,
+        txn: &DatabaseTransaction,
+        checkout: &Checkout<'_>,
+        placed: &mut OrderWithItems,
+    ) -> Result<(), CheckoutError> {
+        if let Some((coupon, applied)) = &checkout.coupon {
+            CouponService::redeem(txn, coupon, placed.order.id, checkout.customer, applied.discount).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Adds a `%TAX` line item per tax due on the discounted subtotal
+pub struct ComputeTax;
+
+#[async_trait]
+impl CheckoutStage for ComputeTax {
+    fn name(&self) -> &'static str {
+        "compute_tax"
+    }
+
+    async fn prepare(&self, _txn: &DatabaseTransaction, checkout: &mut Checkout<'_>) -> Result<(), CheckoutError> {
+        let Some(tax) = &checkout.tax else {
+            return Ok(());
+        };
+        let taxable = checkout.cart.subtotal().amount() - checkout.discount();
+        let lines = tax.calculator.calculate(checkout.mid, &tax.address, taxable).await?;
+        checkout.items.extend(lines.into_iter().filter(|line| line.amount > Decimal::ZERO).map(|line| NewOrderItem {
+            sku: TAX_SKU.to_string(),
+            product_name: line.name,
+            quantity: 1,
+            unit_price: line.amount,
+            options: ItemOptions::default(),
+        }));
+        Ok(())
+    }
+}
+
+/// Adds each shipment's method as a `%SHIP` line item, free with a free
+/// shipping coupon; records the shipments as the order's fulfillment
+/// groups once it exists
+pub struct QuoteShipping;
+
+#[async_trait]
+impl CheckoutStage for QuoteShipping {
+    fn name(&self) -> &'static str {
+        "quote_shipping"
+    }
+
+    async fn prepare(&self, _txn: &DatabaseTransaction, checkout: &mut Checkout<'_>) -> Result<(), CheckoutError> {
+        let free = checkout.free_shipping();
+        let count = checkout.shipments.len();
+        let lines: Vec<NewOrderItem> = checkout
+            .shipments
+            .iter()
+            .enumerate()
+            .map(|(index, shipped)| NewOrderItem {
+                sku: SHIP_SKU.to_string(),
+                product_name: shipment_name(&shipped.quote.name, index, count),
+                quantity: 1,
+                unit_price: if free { Decimal::ZERO } else { shipped.quote.amount },
+                options: ItemOptions::default(),
+            })
+            .collect();
+        checkout.items.extend(lines);
+        Ok(())
+    }
+
+    async fn complete(
+        &self,
+        txn: &DatabaseTransaction,
+        checkout: &Checkout<'_>,
+        placed: &mut OrderWithItems,
+    ) -> Result<(), CheckoutError> {
+        fulfillment_groups::record(txn, checkout.mid, placed.order.id, &checkout.shipments, checkout.free_shipping())
+            .await?;
+        Ok(())
+    }
+}
+
+/// Name of the `%SHIP` line of shipment `index` of `count`, numbered when
+/// the order ships in several
+fn shipment_name(name: &str, index: usize, count: usize) -> String {
+    if count > 1 {
+        format!("{} (shipment {} of {})", name, index + 1, count)
+    } else {
+        name.to_string()
+    }
+}
+
+/// Takes the order's products out of stock, consuming the cart's
+/// reservations
+pub struct ReserveInventory;
+
+#[async_trait]
+impl CheckoutStage for ReserveInventory {
+    fn name(&self) -> &'static str {
+        "reserve_inventory"
+    }
+
+    async fn complete(
+        &self,
+        txn: &DatabaseTransaction,
+        checkout: &Checkout<'_>,
+        placed: &mut OrderWithItems,
+    ) -> Result<(), CheckoutError> {
+        let lines = checkout.lines();
+        InventoryService::commit_order(txn, checkout.mid, &checkout.cart.cart_id, placed.order.id, &lines, checkout.ship_to)
+            .await?;
+        Ok(())
+    }
+}
+
+/// Holds company buyers to their spending limit on the order total,
+/// including tax and shipping
+pub struct CheckSpendingLimit;
+
+#[async_trait]
+impl CheckoutStage for CheckSpendingLimit {
+    fn name(&self) -> &'static str {
+        "check_spending_limit"
+    }
+
+    async fn prepare(&self, _txn: &DatabaseTransaction, checkout: &mut Checkout<'_>) -> Result<(), CheckoutError> {
+        let due = checkout.due();
+        if let Some(limit) = checkout.membership.as_ref().and_then(|(_, buyer)| buyer.spending_limit) {
+            if due > limit {
+                return Err(CheckoutError::SpendingLimit { total: due, limit });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Lets the cart's gift cards pay what they can as negative `%GIFTCARD`
+/// line items, and redeems them once the order exists. Gift cards pay
+/// last, out of the total including tax and shipping. Nothing here charges
+/// a card through the payment gateway; orders are paid for once placed.
+pub struct ApplyGiftCards;
+
+#[async_trait]
+impl CheckoutStage for ApplyGiftCards {
+    fn name(&self) -> &'static str {
+        "apply_gift_cards"
+    }
+
+    async fn prepare(&self, txn: &DatabaseTransaction, checkout: &mut Checkout<'_>) -> Result<(), CheckoutError> {
+        let cards = GiftCardService::revalidate(txn, checkout.mid, checkout.cart).await?;
+        let due = checkout.due();
+        checkout.gift_cards = commercerack_giftcards::allocate(&cards, due);
+        checkout.items.extend(checkout.gift_cards.iter().map(|(card, amount)| NewOrderItem {
+            sku: GIFT_CARD_SKU.to_string(),
+            product_name: format!("Gift card {}", commercerack_giftcards::mask_code(&card.code)),
+            quantity: 1,
+            unit_price: -*amount,
+            options: ItemOptions::default(),
+        }));
+        Ok(())
+    }
+
+    async fn complete(
+        &self,
+        txn: &DatabaseTransaction,
+        checkout: &Checkout<'_>,
+        placed: &mut OrderWithItems,
+    ) -> Result<(), CheckoutError> {
+        for (card, amount) in &checkout.gift_cards {
+            GiftCardService::redeem(txn, card, placed.order.id, *amount).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Holds the written order for review or approval if needed, and
+/// announces it
+pub struct CreateOrder;
+
+#[async_trait]
+impl CheckoutStage for CreateOrder {
+    fn name(&self) -> &'static str {
+        "create_order"
+    }
+
+    async fn complete(
+        &self,
+        txn: &DatabaseTransaction,
+        checkout: &Checkout<'_>,
+        placed: &mut OrderWithItems,
+    ) -> Result<(), CheckoutError> {
+        let mut order = duplicates::flag_if_duplicate(txn, placed.order.clone(), &placed.items).await?;
+        // Waiting for approval takes over from a duplicate hold; approvers see the order either way
+        if let Some((company, _)) = &checkout.membership {
+            order = approvals::assign(txn, order, company, checkout.due()).await?;
+        }
+
+        // Counts toward recovery if the cart had been abandoned
+        AbandonedCartService::mark_ordered(txn, &checkout.cart.cart_id, order.id, order.total).await?;
+
+        let event = DomainEvent::OrderCreated {
+            order: order.clone(),
+            items: placed.items.clone(),
+        };
+        outbox::record(txn, &event).await?;
+        if order.review_status.as_deref() == Some(approvals::AWAITING_APPROVAL) {
+            approvals::request(txn, &order).await?;
+        }
+        if checkout.paid_by_gift_cards(&order) {
+            outbox::record(txn, &DomainEvent::OrderPaid(order.clone())).await?;
+            order = digital::fulfill(txn, order).await?;
+        }
+        placed.order = order;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sea_orm::{DatabaseBackend, MockDatabase};
+    use std::sync::Mutex;
+
+    /// Refuses every order, noting what was due when it did
+    struct Refuse(Mutex<Option<Decimal>>);
+
+    #[async_trait]
+    impl CheckoutStage for Refuse {
+        fn name(&self) -> &'static str {
+            "refuse"
+        }
+
+        async fn prepare(&self, _txn: &DatabaseTransaction, checkout: &mut Checkout<'_>) -> Result<(), CheckoutError> {
+            *self.0.lock().unwrap() = Some(checkout.due());
+            Err(CheckoutError::Rejected("Orders are paused".to_string()))
+        }
+    }
+
+    fn catalog_sku(price: Decimal) -> ::entity::prelude::Sku {
+        ::entity::prelude::Sku {
+            id: 5,
+            pid: 2,
+            mid: 1,
+            sku: "SKU001".to_string(),
+            title: "Widget".to_string(),
+            price,
+            cost: Decimal::ZERO,
+            upc: String::new(),
+            inv_available: 100,
+            qty_onshelf: 100,
+            weight: Decimal::ZERO,
+            reorder_point: None,
+            reorder_quantity: 0,
+            low_stock_gmt: None,
+        }
+    }
+
+    #[test]
+    fn test_custom_stages_are_placed_around_standard_ones() {
+        let standard = CheckoutPipeline::standard();
+        assert_eq!(
+            standard.stage_names(),
+            [
+                "validate_cart",
+                "resolve_pricing",
+                "apply_promotions",
+                "compute_tax",
+                "quote_shipping",
+                "reserve_inventory",
+                "check_spending_limit",
+                "apply_gift_cards",
+                "create_order"
+            ]
+        );
+
+        let pipeline = standard
+            .insert_before("validate_cart", Arc::new(Refuse(Mutex::new(None))))
+            .insert_after("create_order", Arc::new(Refuse(Mutex::new(None))));
+        let names = pipeline.stage_names();
+        assert_eq!(names.first(), Some(&"refuse"));
+        assert_eq!(names.last(), Some(&"refuse"));
+        assert_eq!(names.len(), 11);
+    }
+
+    #[test]
+    fn test_split_shipments_are_numbered() {
+        assert_eq!(shipment_name("Ground", 0, 1), "Ground");
+        assert_eq!(shipment_name("Ground", 1, 2), "Ground (shipment 2 of 2)");
+    }
+
+    #[tokio::test]
+    async fn test_stage_sees_earlier_line_items_and_can_refuse() {
+        let refuse = Arc::new(Refuse(Mutex::new(None)));
+        let pipeline = CheckoutPipeline::standard().insert_before("reserve_inventory", refuse.clone());
+
+        let mut cart = Cart::new();
+        cart.add_item("SKU001".to_string(), "Widget".to_string(), 2, Decimal::new(1999, 2));
+        // No earlier order from this cart, nor merchant settings, nor tiers
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([Vec::<::entity::prelude::Order>::new()])
+            .append_query_results([Vec::<::entity::prelude::MerchantSetting>::new()])
+            .append_query_results([vec![catalog_sku(Decimal::new(1999, 2))]])
+            .append_query_results([Vec::<::entity::prelude::PriceTier>::new()])
+            .into_connection();
+
+        let checkout = Checkout::new(1, GUEST_CUSTOMER, "guest@example.com", &cart, None, Fulfillment::Ship(Vec::new()), None);
+        let result = pipeline.run(&db, checkout).await;
+        assert!(matches!(result, Err(CheckoutError::Rejected(ref reason)) if reason == "Orders are paused"));
+        assert_eq!(*refuse.0.lock().unwrap(), Some(Decimal::new(3998, 2)));
+    }
+
+    #[tokio::test]
+    async fn test_order_is_numbered_in_merchant_format() {
+        let mut cart = Cart::new();
+        cart.add_item("SKU001".to_string(), "Widget".to_string(), 1, Decimal::new(1999, 2));
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([Vec::<::entity::prelude::Order>::new()])
+            .append_query_results([vec![::entity::prelude::MerchantSetting {
+                id: 1,
+                mid: 2,
+                key: "order_number_format".to_string(),
+                value: r#""WS-{random}""#.to_string(),
+                modified_gmt: Timestamp::from_unix(1_700_000_000),
+            }]])
+            .into_connection();
+
+        let mut checkout = Checkout::new(2, GUEST_CUSTOMER, "guest@example.com", &cart, None, Fulfillment::Ship(Vec::new()), None);
+        assert!(!checkout.orderid.starts_with("WS-"));
+        let txn = db.begin().await.unwrap();
+        ValidateCart.prepare(&txn, &mut checkout).await.unwrap();
+        assert!(checkout.orderid.starts_with("WS-"), "{}", checkout.orderid);
+        assert_eq!(checkout.orderid.len(), "WS-3F9A1C2B".len());
+    }
+
+    #[tokio::test]
+    async fn test_lines_are_priced_from_the_catalog() {
+        let mut cart = Cart::new();
+        cart.add_item("SKU001".to_string(), "Widget".to_string(), 1, Decimal::new(1, 2));
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![catalog_sku(Decimal::new(1999, 2))]])
+            .append_query_results([Vec::<::entity::prelude::PriceTier>::new()])
+            .into_connection();
+
+        // A cart holding its own price is refused, not charged that price
+        let mut checkout = Checkout::new(1, GUEST_CUSTOMER, "guest@example.com", &cart, None, Fulfillment::Ship(Vec::new()), None);
+        let txn = db.begin().await.unwrap();
+        let result = ResolvePricing.prepare(&txn, &mut checkout).await;
+        match result {
+            Err(CheckoutError::PriceChanged { sku, current, .. }) => {
+                assert_eq!((sku.as_str(), current), ("SKU001", Decimal::new(1999, 2)))
+            }
+            other => panic!("unexpected result {:?}", other.err()),
+        }
+        assert!(checkout.items.is_empty());
+    }
+}
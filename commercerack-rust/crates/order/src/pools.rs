@@ -0,0 +1,201 @@
+//! Order pools
+//!
+//! An order's `pool` is where it sits in the merchant's workflow. Checkout
+//! puts new orders in [`NEW_ORDER_POOL`](crate::checkout::NEW_ORDER_POOL);
+//! the merchant moves them through the working pools by editing the order.
+//! An order is done once it is in [`COMPLETED_POOL`], or has shipped and
+//! been paid. [`PoolService::archive_due`], run on a schedule, moves orders
+//! that have been done for the merchant's `archive_after_days` into
+//! [`ARCHIVE_POOL`], keeping the working pools (above all `RECENT`, which
+//! every order list opens on) small. Moving orders to the archive is
+//! bookkeeping, so it raises no `order.updated` events.
+
+use chrono::{DateTime, Duration, Utc};
+use commercerack_merchant::settings::SettingsService;
+use commercerack_merchant::DEFAULT_POOLS;
+use sea_orm::sea_query::{Expr, Func, SimpleExpr};
+use sea_orm::{entity::*, query::*, Condition, ConnectionTrait};
+use ::entity::orders::Column;
+use ::entity::prelude::Orders;
+use tracing::{info, instrument};
+
+use crate::payment::PaymentStatus;
+use crate::OrderError;
+
+/// Pool of orders set aside until something is sorted out, e.g. a
+/// payment that kept being declined
+pub const HOLD_POOL: &str = "HOLD";
+/// Pool of orders the merchant marked done
+pub const COMPLETED_POOL: &str = "COMPLETED";
+/// Pool done orders end up in
+pub const ARCHIVE_POOL: &str = "ARCHIVE";
+
+/// Shortest time a merchant can set before done orders are archived
+const MIN_ARCHIVE_AGE: Duration = Duration::days(1);
+
+/// Whether `pool` is one orders can be in
+pub fn is_pool(pool: &str) -> bool {
+    DEFAULT_POOLS.contains(&pool)
+}
+
+/// How many of a merchant's orders are in a pool
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolCount {
+    pub pool: String,
+    pub orders: i64,
+}
+
+/// When an order is taken to have been done: when it shipped, or for
+/// orders that never ship, when it was placed
+pub(crate) fn done_at() -> SimpleExpr {
+    Func::coalesce([Expr::col(Column::ShippedGmt).into(), Expr::col(Column::CreatedGmt).into()]).into()
+}
+
+/// Orders outside the archive that were done by `cutoff`: marked complete,
+/// or shipped and paid for (refunds included)
+fn done_by(cutoff: DateTime<Utc>) -> Condition {
+    let settled = [PaymentStatus::Paid, PaymentStatus::PartiallyRefunded, PaymentStatus::Refunded].map(|s| s.code());
+    Condition::all()
+        .add(Column::Pool.ne(ARCHIVE_POOL))
+        .add(
+            Condition::any().add(Column::Pool.eq(COMPLETED_POOL)).add(
+                Condition::all()
+                    .add(Column::ShippedGmt.is_not_null())
+                    .add(Column::OrderPaymentStatus.is_in(settled)),
+            ),
+        )
+        .add(Expr::expr(done_at()).lt(cutoff.timestamp()))
+}
+
+/// Order pool service
+pub struct PoolService;
+
+impl PoolService {
+    /// How many orders a merchant has in each pool, in workflow order.
+    /// Pools without orders are listed with none.
+    pub async fn counts<C: ConnectionTrait>(db: &C, mid: i32) -> Result<Vec<PoolCount>, OrderError> {
+        let found: Vec<(String, i64)> = Orders::find()
+            .select_only()
+            .column(Column::Pool)
+            .column_as(Column::Id.count(), "orders")
+            .filter(Column::Mid.eq(mid))
+            .group_by(Column::Pool)
+            .into_tuple()
+            .all(db)
+            .await?;
+
+        let mut counts: Vec<PoolCount> = DEFAULT_POOLS
+            .iter()
+            .map(|pool| PoolCount {
+                pool: pool.to_string(),
+                orders: found.iter().find(|(p, _)| p == pool).map_or(0, |(_, n)| *n),
+            })
+            .collect();
+        // Pools from before the workflow was fixed, after the known ones
+        counts.extend(
+            found
+                .into_iter()
+                .filter(|(pool, _)| !is_pool(pool))
+                .map(|(pool, orders)| PoolCount { pool, orders }),
+        );
+        Ok(counts)
+    }
+
+    /// Archive every merchant's orders that have been done for as long as
+    /// their settings say. Returns how many orders were moved.
+    #[instrument(skip_all)]
+    pub async fn archive_due<C: ConnectionTrait>(db: &C, now: DateTime<Utc>) -> Result<u64, OrderError> {
+        let mids: Vec<i32> = Orders::find()
+            .select_only()
+            .column(Column::Mid)
+            .distinct()
+            .filter(done_by(now - MIN_ARCHIVE_AGE))
+            .into_tuple()
+            .all(db)
+            .await?;
+
+        let mut archived = 0;
+        for mid in mids {
+            let settings = SettingsService::get(db, mid).await?;
+            let cutoff = now - Duration::days(settings.archive_after_days.into());
+            let moved = Orders::update_many()
+                .col_expr(Column::Pool, Expr::value(ARCHIVE_POOL))
+                .col_expr(Column::V, Expr::col(Column::V).add(1))
+                .filter(Column::Mid.eq(mid))
+                .filter(done_by(cutoff))
+                .exec(db)
+                .await?
+                .rows_affected;
+            if moved > 0 {
+                info!(mid, moved, "archived done orders");
+            }
+            archived += moved;
+        }
+        Ok(archived)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use sea_orm::{DatabaseBackend, MockDatabase, MockExecResult, Value};
+    use std::collections::BTreeMap;
+
+    fn row(values: &[(&str, Value)]) -> BTreeMap<String, Value> {
+        values.iter().map(|(k, v)| (k.to_string(), v.clone())).collect()
+    }
+
+    #[tokio::test]
+    async fn test_counts_in_workflow_order() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            // Mock rows decode into tuples by position
+            .append_query_results([vec![
+                row(&[("0", Value::from("ARCHIVE")), ("1", Value::BigInt(Some(40)))]),
+                row(&[("0", Value::from("RECENT")), ("1", Value::BigInt(Some(3)))]),
+                row(&[("0", Value::from("BACKORDER")), ("1", Value::BigInt(Some(1)))]),
+            ]])
+            .into_connection();
+
+        let counts = PoolService::counts(&db, 1).await.unwrap();
+        assert_eq!(counts.len(), DEFAULT_POOLS.len() + 1);
+        assert_eq!(counts[0], PoolCount { pool: "RECENT".to_string(), orders: 3 });
+        assert_eq!(counts[1].orders, 0);
+        assert_eq!(counts[DEFAULT_POOLS.len() - 1], PoolCount { pool: ARCHIVE_POOL.to_string(), orders: 40 });
+        assert_eq!(counts.last().unwrap().pool, "BACKORDER");
+    }
+
+    #[tokio::test]
+    async fn test_archive_uses_merchant_setting() {
+        let now = Utc.with_ymd_and_hms(2025, 11, 18, 3, 0, 0).unwrap();
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![row(&[("mid", Value::Int(Some(5)))])]])
+            .append_query_results([vec![::entity::prelude::MerchantSetting {
+                id: 1,
+                mid: 5,
+                key: "archive_after_days".to_string(),
+                value: "30".to_string(),
+                modified_gmt: commercerack_core::Timestamp::from_unix(1_700_000_000),
+            }]])
+            .append_exec_results([MockExecResult { last_insert_id: 0, rows_affected: 12 }])
+            .into_connection();
+
+        assert_eq!(PoolService::archive_due(&db, now).await.unwrap(), 12);
+
+        let log = db.into_transaction_log();
+        let update = log[2].statements()[0].to_string();
+        assert!(update.contains(r#"SET "pool" = 'ARCHIVE', "v" = "v" + 1"#), "{}", update);
+        assert!(update.contains(r#""pool" <> 'ARCHIVE'"#), "{}", update);
+        assert!(update.contains(r#""order_payment_status" IN ('000', '301', '300')"#), "{}", update);
+        let cutoff = (now - Duration::days(30)).timestamp();
+        assert!(update.contains(&format!(r#"COALESCE("shipped_gmt", "created_gmt") < {}"#, cutoff)), "{}", update);
+    }
+
+    #[test]
+    fn test_is_pool() {
+        assert!(is_pool("RECENT"));
+        assert!(is_pool(ARCHIVE_POOL));
+        assert!(!is_pool("recent"));
+        assert!(!is_pool(""));
+    }
+}
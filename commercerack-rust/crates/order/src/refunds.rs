@@ -0,0 +1,475 @@
+//! Partial refunds by line item
+//!
+//! A merchant picks some of an order's product lines and how many of each
+//! to refund, and [`calculate`] works out what that is worth:
+//!
+//! - the items at what they sold for, less their share of coupon and
+//!   manual discounts,
+//! - the same share of the order's tax and of its shipping, and
+//! - once every unit has been refunded, whatever is left of each, so
+//!   rounding across several refunds still adds up to what was charged.
+//!
+//! Units already refunded can't be refunded again, and no part goes over
+//! what is left of it after earlier refunds. The total is held to what the
+//! order's captured payments can still give back; anything over that, e.g.
+//! the part a gift card paid, comes off the shipping first, then the tax,
+//! then the items. [`RefundService::refund`] sends the amount back through
+//! the payment gateway and records it as an `order_refunds` row.
+//!
+//! The row is written, with its items, before the gateway is asked, and
+//! only gets its `refunded_gmt` once the money has gone back. Until then it
+//! holds its units and amount against other refunds of the order, so two
+//! refunds can't both give back the same items. If the gateway refuses and
+//! nothing was given back the row is removed; if it fails part way the row
+//! is left pending for the merchant to reconcile.
+
+use commercerack_core::Timestamp;
+use commercerack_payment::PaymentGateway;
+use rust_decimal::Decimal;
+use sea_orm::{entity::*, query::*, ConnectionTrait, DatabaseConnection, DbErr, Set, TransactionTrait};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use thiserror::Error;
+use ::entity::prelude::{OrderItem, OrderPayment, OrderRefund, OrderRefundItem, OrderRefundItems, OrderRefunds, Orders};
+use tracing::instrument;
+
+use crate::checkout::{COUPON_SKU, SHIP_SKU, TAX_SKU};
+use crate::edits::DISCOUNT_SKU;
+use crate::items::{line_total, OrderItemService};
+use crate::payment::{refundable, OrderPaymentError, OrderPaymentService};
+use crate::shipments::is_shippable;
+use crate::{OrderError, OrderService};
+
+#[derive(Error, Debug)]
+pub enum RefundError {
+    #[error(transparent)]
+    Order(#[from] OrderError),
+
+    #[error("Refund must include at least one item")]
+    Empty,
+
+    #[error("Cannot refund {quantity} of order item {order_item_id}; {refundable} not refunded yet")]
+    NotRefundable { order_item_id: i32, quantity: i32, refundable: i32 },
+
+    #[error("Order has no captured payment left to refund")]
+    NothingCaptured,
+
+    #[error(transparent)]
+    Payment(#[from] OrderPaymentError),
+
+    #[error("Database error: {0}")]
+    Db(#[from] DbErr),
+}
+
+/// Quantity of one order item being refunded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RefundLine {
+    pub order_item_id: i32,
+    pub quantity: i32,
+}
+
+/// What refunding some lines is worth, before it is sent
+#[derive(Debug, Clone, PartialEq)]
+pub struct RefundQuote {
+    /// The requested lines, merged by item
+    pub lines: Vec<RefundLine>,
+    pub items: Decimal,
+    pub tax: Decimal,
+    pub shipping: Decimal,
+    /// `items + tax + shipping`, no more than `available`
+    pub amount: Decimal,
+    /// What the captured payments could still give back before this refund
+    pub available: Decimal,
+}
+
+/// A refund together with the items it covers
+#[derive(Debug, Clone, Serialize)]
+pub struct OrderRefundWithItems {
+    pub refund: OrderRefund,
+    pub items: Vec<OrderRefundItem>,
+}
+
+/// Units of each product line not refunded yet, given the lines of the
+/// order's earlier refunds
+pub fn unrefunded(items: &[OrderItem], refunded: &[OrderRefundItem]) -> BTreeMap<i32, i32> {
+    let mut remaining: BTreeMap<i32, i32> = items
+        .iter()
+        .filter(|item| is_shippable(item))
+        .map(|item| (item.id, item.quantity))
+        .collect();
+    for line in refunded {
+        if let Some(quantity) = remaining.get_mut(&line.order_item_id) {
+            *quantity -= line.quantity;
+        }
+    }
+    remaining.retain(|_, quantity| *quantity > 0);
+    remaining
+}
+
+/// Check the requested lines against what may still be refunded, merging
+/// lines that name the same item
+pub fn plan_refund(remaining: &BTreeMap<i32, i32>, requested: &[RefundLine]) -> Result<Vec<RefundLine>, RefundError> {
+    if requested.is_empty() {
+        return Err(RefundError::Empty);
+    }
+
+    let mut merged: BTreeMap<i32, i32> = BTreeMap::new();
+    for line in requested {
+        *merged.entry(line.order_item_id).or_default() += line.quantity;
+    }
+
+    merged
+        .into_iter()
+        .map(|(order_item_id, quantity)| {
+            let refundable = remaining.get(&order_item_id).copied().unwrap_or_default();
+            if quantity <= 0 || quantity > refundable {
+                return Err(RefundError::NotRefundable { order_item_id, quantity, refundable });
+            }
+            Ok(RefundLine { order_item_id, quantity })
+        })
+        .collect()
+}
+
+/// What refunding `requested` of an order's `items` is worth, after the
+/// order's `prior` refunds and with `available` left to give back of its
+/// captured payments
+pub fn calculate(
+    items: &[OrderItem],
+    prior: &[OrderRefund],
+    prior_items: &[OrderRefundItem],
+    requested: &[RefundLine],
+    available: Decimal,
+) -> Result<RefundQuote, RefundError> {
+    let remaining = unrefunded(items, prior_items);
+    let lines = plan_refund(&remaining, requested)?;
+    if available <= Decimal::ZERO {
+        return Err(RefundError::NothingCaptured);
+    }
+
+    let charged = |include: fn(&OrderItem) -> bool| -> Decimal {
+        items.iter().filter(|item| include(item)).map(line_total).sum()
+    };
+    let goods = charged(is_shippable);
+    let discounts = charged(|item| item.sku == COUPON_SKU || item.sku == DISCOUNT_SKU);
+    let selected: Decimal = lines
+        .iter()
+        .filter_map(|line| {
+            items
+                .iter()
+                .find(|item| item.id == line.order_item_id)
+                .map(|item| item.unit_price * Decimal::from(line.quantity))
+        })
+        .sum();
+    let everything = lines.len() == remaining.len()
+        && lines.iter().all(|line| remaining.get(&line.order_item_id) == Some(&line.quantity));
+
+    // `selected`'s share of `total`, no more than earlier refunds left of it
+    let share = |total: Decimal, refunded: Decimal| -> Decimal {
+        let left = (total - refunded).max(Decimal::ZERO);
+        if everything {
+            return left;
+        }
+        if goods <= Decimal::ZERO {
+            return Decimal::ZERO;
+        }
+        (total * selected / goods).round_dp(2).clamp(Decimal::ZERO, left)
+    };
+    let mut refunded_items = share(goods + discounts, prior.iter().map(|refund| refund.items_amount).sum());
+    let mut tax = share(charged(|item| item.sku == TAX_SKU), prior.iter().map(|refund| refund.tax_amount).sum());
+    let mut shipping = share(
+        charged(|item| item.sku == SHIP_SKU),
+        prior.iter().map(|refund| refund.shipping_amount).sum(),
+    );
+
+    let mut over = (refunded_items + tax + shipping - available).max(Decimal::ZERO);
+    for part in [&mut shipping, &mut tax, &mut refunded_items] {
+        let cut = over.min(*part);
+        *part -= cut;
+        over -= cut;
+    }
+
+    Ok(RefundQuote {
+        lines,
+        items: refunded_items,
+        tax,
+        shipping,
+        amount: refunded_items + tax + shipping,
+        available,
+    })
+}
+
+/// What the captured `payments` can still give back, less what the
+/// order's `prior` refunds still waiting on the gateway will take
+fn available(payments: &[OrderPayment], prior: &[OrderRefund]) -> Decimal {
+    let pending: Decimal = prior.iter().filter(|refund| refund.refunded_gmt.is_none()).map(|refund| refund.amount).sum();
+    refundable(payments) - pending
+}
+
+/// Everything the order's payments have given back so far
+fn refunded_total(payments: &[OrderPayment]) -> Decimal {
+    payments.iter().map(|payment| payment.refunded).sum()
+}
+
+/// Line item refund service
+pub struct RefundService;
+
+impl RefundService {
+    async fn prior<C: ConnectionTrait>(
+        conn: &C,
+        mid: i32,
+        order_id: i32,
+    ) -> Result<(Vec<OrderRefund>, Vec<OrderRefundItem>), DbErr> {
+        let refunds = OrderRefunds::find()
+            .filter(::entity::order_refunds::Column::Mid.eq(mid))
+            .filter(::entity::order_refunds::Column::OrderId.eq(order_id))
+            .order_by_asc(::entity::order_refunds::Column::Id)
+            .all(conn)
+            .await?;
+        let items = if refunds.is_empty() {
+            Vec::new()
+        } else {
+            OrderRefundItems::find()
+                .filter(::entity::order_refund_items::Column::Mid.eq(mid))
+                .filter(::entity::order_refund_items::Column::RefundId.is_in(refunds.iter().map(|refund| refund.id)))
+                .order_by_asc(::entity::order_refund_items::Column::Id)
+                .all(conn)
+                .await?
+        };
+        Ok((refunds, items))
+    }
+
+    async fn quote_in<C: ConnectionTrait>(
+        conn: &C,
+        mid: i32,
+        order_id: i32,
+        lines: &[RefundLine],
+    ) -> Result<(RefundQuote, Vec<OrderPayment>), RefundError> {
+        let items = OrderItemService::list(conn, mid, order_id).await?;
+        let (prior, prior_items) = Self::prior(conn, mid, order_id).await?;
+        let payments = OrderPaymentService::payments(conn, mid, order_id).await?;
+
+        let quote = calculate(&items, &prior, &prior_items, lines, available(&payments, &prior))?;
+        Ok((quote, payments))
+    }
+
+    /// Work out what refunding `lines` of an order would give back,
+    /// without sending anything
+    #[instrument(skip_all, fields(mid = mid, order_id = order_id))]
+    pub async fn quote<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        order_id: i32,
+        lines: &[RefundLine],
+    ) -> Result<RefundQuote, RefundError> {
+        OrderService::find_by_id(db, mid, order_id).await?.ok_or(OrderError::NotFound)?;
+        Self::quote_in(db, mid, order_id, lines).await.map(|(quote, _)| quote)
+    }
+
+    /// Refund `lines` of an order through the gateway that took its
+    /// payments and record what was given back
+    #[instrument(skip_all, fields(mid = mid, order_id = order_id))]
+    pub async fn refund(
+        db: &DatabaseConnection,
+        gateway: &dyn PaymentGateway,
+        mid: i32,
+        order_id: i32,
+        lines: &[RefundLine],
+        reason: &str,
+    ) -> Result<OrderRefundWithItems, RefundError> {
+        let txn = db.begin().await?;
+        // Locked so refunds of the order are quoted and recorded one at a
+        // time, each seeing those before it
+        Orders::find()
+            .filter(::entity::orders::Column::Mid.eq(mid))
+            .filter(::entity::orders::Column::Id.eq(order_id))
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .ok_or(OrderError::NotFound)?;
+        let (quote, payments) = Self::quote_in(&txn, mid, order_id, lines).await?;
+
+        let refund = ::entity::order_refunds::ActiveModel {
+            mid: Set(mid),
+            order_id: Set(order_id),
+            items_amount: Set(quote.items),
+            tax_amount: Set(quote.tax),
+            shipping_amount: Set(quote.shipping),
+            amount: Set(quote.amount),
+            reason: Set(reason.trim().to_string()),
+            created_gmt: Set(Timestamp::now()),
+            refunded_gmt: Set(None),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+
+        let mut items = Vec::with_capacity(quote.lines.len());
+        for line in &quote.lines {
+            let item = ::entity::order_refund_items::ActiveModel {
+                mid: Set(mid),
+                refund_id: Set(refund.id),
+                order_item_id: Set(line.order_item_id),
+                quantity: Set(line.quantity),
+                ..Default::default()
+            }
+            .insert(&txn)
+            .await?;
+            items.push(item);
+        }
+        txn.commit().await?;
+
+        if quote.amount > Decimal::ZERO {
+            if let Err(e) = OrderPaymentService::refund(db, gateway, mid, order_id, Some(quote.amount)).await {
+                let after = OrderPaymentService::payments(db, mid, order_id).await?;
+                if refunded_total(&after) == refunded_total(&payments) {
+                    // Nothing went back, so the units are free to refund again
+                    OrderRefunds::delete_by_id(refund.id).exec(db).await?;
+                }
+                return Err(e.into());
+            }
+        }
+
+        let mut active: ::entity::order_refunds::ActiveModel = refund.into();
+        active.refunded_gmt = Set(Some(Timestamp::now()));
+        let refund = active.update(db).await?;
+
+        Ok(OrderRefundWithItems { refund, items })
+    }
+
+    /// An order's line item refunds, oldest first
+    pub async fn list<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        order_id: i32,
+    ) -> Result<Vec<OrderRefundWithItems>, RefundError> {
+        let (refunds, mut items) = Self::prior(db, mid, order_id).await?;
+        Ok(refunds
+            .into_iter()
+            .map(|refund| {
+                let (mine, rest) = items.drain(..).partition(|item| item.refund_id == refund.id);
+                items = rest;
+                OrderRefundWithItems { refund, items: mine }
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use commercerack_payment::TransactionStatus;
+
+    fn item(id: i32, sku: &str, quantity: i32, cents: i64) -> OrderItem {
+        OrderItem {
+            id,
+            mid: 1,
+            order_id: 9,
+            sku: sku.to_string(),
+            product_name: sku.to_string(),
+            quantity,
+            unit_price: Decimal::new(cents, 2),
+            options: String::new(),
+        }
+    }
+
+    fn refunded(order_item_id: i32, quantity: i32) -> OrderRefundItem {
+        OrderRefundItem { id: 0, mid: 1, refund_id: 1, order_item_id, quantity }
+    }
+
+    fn prior(items: i64, tax: i64, shipping: i64) -> OrderRefund {
+        OrderRefund {
+            id: 1,
+            mid: 1,
+            order_id: 9,
+            items_amount: Decimal::new(items, 2),
+            tax_amount: Decimal::new(tax, 2),
+            shipping_amount: Decimal::new(shipping, 2),
+            amount: Decimal::new(items + tax + shipping, 2),
+            reason: String::new(),
+            created_gmt: Timestamp::EPOCH,
+            refunded_gmt: Some(Timestamp::EPOCH),
+        }
+    }
+
+    fn line(order_item_id: i32, quantity: i32) -> RefundLine {
+        RefundLine { order_item_id, quantity }
+    }
+
+    /// 3 x 10.00 and 1 x 30.00, 6.00 off, 5.40 tax and 9.00 shipping
+    fn order() -> Vec<OrderItem> {
+        vec![
+            item(1, "WIDGET", 3, 1000),
+            item(2, "GADGET", 1, 3000),
+            item(3, COUPON_SKU, 1, -600),
+            item(4, TAX_SKU, 1, 540),
+            item(5, SHIP_SKU, 1, 900),
+        ]
+    }
+
+    #[test]
+    fn test_tax_and_shipping_follow_the_refunded_share() {
+        // One widget is a sixth of the goods
+        let quote = calculate(&order(), &[], &[], &[line(1, 1)], Decimal::new(6840, 2)).unwrap();
+        assert_eq!(quote.lines, vec![line(1, 1)]);
+        assert_eq!(quote.items, Decimal::new(900, 2));
+        assert_eq!(quote.tax, Decimal::new(90, 2));
+        assert_eq!(quote.shipping, Decimal::new(150, 2));
+        assert_eq!(quote.amount, Decimal::new(1140, 2));
+    }
+
+    #[test]
+    fn test_last_refund_takes_what_is_left() {
+        let items = [item(1, "WIDGET", 3, 1000), item(2, TAX_SKU, 1, 100)];
+        let earlier = [prior(1000, 33, 0), prior(1000, 33, 0)];
+        let quote = calculate(&items, &earlier, &[refunded(1, 1), refunded(1, 1)], &[line(1, 1)], Decimal::new(2000, 2))
+            .unwrap();
+        assert_eq!(quote.items, Decimal::new(1000, 2));
+        assert_eq!(quote.tax, Decimal::new(34, 2));
+
+        assert!(matches!(
+            calculate(&items, &earlier, &[refunded(1, 2)], &[line(1, 2)], Decimal::new(1000, 2)),
+            Err(RefundError::NotRefundable { order_item_id: 1, quantity: 2, refundable: 1 })
+        ));
+        assert!(matches!(
+            calculate(&items, &[], &[], &[line(2, 1)], Decimal::ONE),
+            Err(RefundError::NotRefundable { .. })
+        ));
+        assert!(matches!(calculate(&items, &[], &[], &[], Decimal::ONE), Err(RefundError::Empty)));
+    }
+
+    #[test]
+    fn test_refund_never_exceeds_what_was_captured() {
+        // A gift card paid all but 20.00
+        let quote = calculate(&order(), &[], &[], &[line(1, 3), line(2, 1)], Decimal::new(2000, 2)).unwrap();
+        assert_eq!(quote.shipping, Decimal::ZERO);
+        assert_eq!(quote.tax, Decimal::ZERO);
+        assert_eq!(quote.items, Decimal::new(2000, 2));
+        assert_eq!(quote.amount, Decimal::new(2000, 2));
+
+        assert!(matches!(
+            calculate(&order(), &[], &[], &[line(1, 1)], Decimal::ZERO),
+            Err(RefundError::NothingCaptured)
+        ));
+    }
+
+    #[test]
+    fn test_pending_refunds_hold_their_amount() {
+        let captured = OrderPayment {
+            id: 1,
+            mid: 1,
+            order_id: 9,
+            reference: Some("txn_1".to_string()),
+            amount: Decimal::new(5000, 2),
+            refunded: Decimal::new(1000, 2),
+            status: TransactionStatus::Captured.as_str().to_string(),
+            created_gmt: Timestamp::EPOCH,
+            modified_gmt: Timestamp::EPOCH,
+        };
+        let payments = [captured];
+        let refunds = [prior(1000, 0, 0), OrderRefund { refunded_gmt: None, ..prior(1500, 0, 0) }];
+
+        // The refund already sent is in the payment's `refunded`
+        assert_eq!(available(&payments, &refunds[..1]), Decimal::new(4000, 2));
+        assert_eq!(available(&payments, &refunds), Decimal::new(2500, 2));
+    }
+}
@@ -0,0 +1,467 @@
+//! Returns (RMAs)
+//!
+//! A customer asks to send back some of an order's items and the merchant
+//! approves or rejects the request. Approving can put the items back into
+//! sellable stock. An approved return is then refunded through the payment
+//! gateway, by default for what the returned items cost.
+
+use commercerack_core::Timestamp;
+use commercerack_inventory::{InventoryError, InventoryService};
+use commercerack_payment::PaymentGateway;
+use rust_decimal::Decimal;
+use sea_orm::{entity::*, query::*, ConnectionTrait, DatabaseConnection, DbErr, Set, TransactionTrait};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+use ::entity::prelude::{OrderItem, Orders, Return, ReturnItem, ReturnItems, Returns};
+
+use crate::items::OrderItemService;
+use crate::payment::{OrderPaymentError, OrderPaymentService};
+use crate::shipments::is_shippable;
+use crate::OrderError;
+use tracing::instrument;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReturnStatus {
+    Requested,
+    Approved,
+    Rejected,
+    Refunded,
+}
+
+impl ReturnStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReturnStatus::Requested => "requested",
+            ReturnStatus::Approved => "approved",
+            ReturnStatus::Rejected => "rejected",
+            ReturnStatus::Refunded => "refunded",
+        }
+    }
+}
+
+impl fmt::Display for ReturnStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ReturnStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "requested" => Ok(ReturnStatus::Requested),
+            "approved" => Ok(ReturnStatus::Approved),
+            "rejected" => Ok(ReturnStatus::Rejected),
+            "refunded" => Ok(ReturnStatus::Refunded),
+            other => Err(format!("unknown return status {}", other)),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ReturnError {
+    #[error("Return not found")]
+    NotFound,
+
+    #[error(transparent)]
+    Order(#[from] OrderError),
+
+    #[error("Return must include at least one item")]
+    Empty,
+
+    #[error("Cannot return {quantity} of order item {order_item_id}; {returnable} returnable")]
+    NotReturnable { order_item_id: i32, quantity: i32, returnable: i32 },
+
+    #[error("Return is {0}, which does not allow this operation")]
+    InvalidState(ReturnStatus),
+
+    #[error(transparent)]
+    Inventory(#[from] InventoryError),
+
+    #[error(transparent)]
+    Payment(#[from] OrderPaymentError),
+
+    #[error("Database error: {0}")]
+    Db(#[from] DbErr),
+}
+
+/// Quantity of one order item being returned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReturnLine {
+    pub order_item_id: i32,
+    pub quantity: i32,
+}
+
+/// A return together with the items it covers
+#[derive(Debug, Clone, Serialize)]
+pub struct ReturnWithItems {
+    pub rma: Return,
+    pub items: Vec<ReturnItem>,
+}
+
+/// Quantity of each order item that may still be returned, given the lines
+/// of the order's earlier returns that were not rejected
+pub fn returnable(items: &[OrderItem], returned: &[ReturnItem]) -> BTreeMap<i32, i32> {
+    let mut remaining: BTreeMap<i32, i32> = items
+        .iter()
+        .filter(|item| is_shippable(item))
+        .map(|item| (item.id, item.quantity))
+        .collect();
+    for line in returned {
+        if let Some(quantity) = remaining.get_mut(&line.order_item_id) {
+            *quantity -= line.quantity;
+        }
+    }
+    remaining.retain(|_, quantity| *quantity > 0);
+    remaining
+}
+
+/// Check the requested lines against what may still be returned, merging
+/// lines that name the same item
+pub fn plan_return(
+    remaining: &BTreeMap<i32, i32>,
+    requested: &[ReturnLine],
+) -> Result<Vec<ReturnLine>, ReturnError> {
+    if requested.is_empty() {
+        return Err(ReturnError::Empty);
+    }
+
+    let mut merged: BTreeMap<i32, i32> = BTreeMap::new();
+    for line in requested {
+        *merged.entry(line.order_item_id).or_default() += line.quantity;
+    }
+
+    merged
+        .into_iter()
+        .map(|(order_item_id, quantity)| {
+            let returnable = remaining.get(&order_item_id).copied().unwrap_or_default();
+            if quantity <= 0 || quantity > returnable {
+                return Err(ReturnError::NotReturnable { order_item_id, quantity, returnable });
+            }
+            Ok(ReturnLine { order_item_id, quantity })
+        })
+        .collect()
+}
+
+/// What the returned items were sold for
+pub fn refund_value(items: &[OrderItem], lines: &[ReturnItem]) -> Decimal {
+    lines
+        .iter()
+        .filter_map(|line| {
+            items
+                .iter()
+                .find(|item| item.id == line.order_item_id)
+                .map(|item| item.unit_price * Decimal::from(line.quantity))
+        })
+        .sum()
+}
+
+/// Return service for the RMA workflow
+pub struct ReturnService;
+
+impl ReturnService {
+    async fn load<C: ConnectionTrait>(
+        conn: &C,
+        mid: i32,
+        order_id: i32,
+        id: i32,
+    ) -> Result<ReturnWithItems, ReturnError> {
+        let rma = Returns::find()
+            .filter(::entity::returns::Column::Mid.eq(mid))
+            .filter(::entity::returns::Column::OrderId.eq(order_id))
+            .filter(::entity::returns::Column::Id.eq(id))
+            .one(conn)
+            .await?
+            .ok_or(ReturnError::NotFound)?;
+
+        let items = ReturnItems::find()
+            .filter(::entity::return_items::Column::Mid.eq(mid))
+            .filter(::entity::return_items::Column::ReturnId.eq(id))
+            .order_by_asc(::entity::return_items::Column::Id)
+            .all(conn)
+            .await?;
+
+        Ok(ReturnWithItems { rma, items })
+    }
+
+    async fn set_status<C: ConnectionTrait>(
+        conn: &C,
+        rma: Return,
+        status: ReturnStatus,
+        update: impl FnOnce(&mut ::entity::returns::ActiveModel),
+    ) -> Result<Return, DbErr> {
+        let mut active: ::entity::returns::ActiveModel = rma.into();
+        active.status = Set(status.as_str().to_string());
+        active.modified_gmt = Set(Timestamp::now());
+        update(&mut active);
+        active.update(conn).await
+    }
+
+    /// Make sure a return is in `status` before moving it on
+    fn expect(rma: &Return, status: ReturnStatus) -> Result<(), ReturnError> {
+        match rma.status.parse::<ReturnStatus>().ok() {
+            Some(current) if current == status => Ok(()),
+            Some(current) => Err(ReturnError::InvalidState(current)),
+            None => Err(ReturnError::InvalidState(status)),
+        }
+    }
+
+    /// Request a return of some of an order's items
+    #[instrument(skip_all, fields(mid = mid, order_id = order_id))]
+    pub async fn create(
+        db: &DatabaseConnection,
+        mid: i32,
+        order_id: i32,
+        reason: &str,
+        lines: &[ReturnLine],
+    ) -> Result<ReturnWithItems, ReturnError> {
+        let txn = db.begin().await?;
+
+        Orders::find()
+            .filter(::entity::orders::Column::Mid.eq(mid))
+            .filter(::entity::orders::Column::Id.eq(order_id))
+            .one(&txn)
+            .await?
+            .ok_or(OrderError::NotFound)?;
+
+        let items = OrderItemService::list(&txn, mid, order_id).await?;
+        if lines.iter().any(|line| !items.iter().any(|item| item.id == line.order_item_id)) {
+            return Err(OrderError::ItemNotFound.into());
+        }
+
+        // Rejected returns don't count against what may still be returned
+        let open: Vec<i32> = Returns::find()
+            .filter(::entity::returns::Column::Mid.eq(mid))
+            .filter(::entity::returns::Column::OrderId.eq(order_id))
+            .filter(::entity::returns::Column::Status.ne(ReturnStatus::Rejected.as_str()))
+            .all(&txn)
+            .await?
+            .into_iter()
+            .map(|rma| rma.id)
+            .collect();
+        let returned = if open.is_empty() {
+            Vec::new()
+        } else {
+            ReturnItems::find()
+                .filter(::entity::return_items::Column::Mid.eq(mid))
+                .filter(::entity::return_items::Column::ReturnId.is_in(open))
+                .all(&txn)
+                .await?
+        };
+        let lines = plan_return(&returnable(&items, &returned), lines)?;
+
+        let now = Timestamp::now();
+        let rma = ::entity::returns::ActiveModel {
+            mid: Set(mid),
+            order_id: Set(order_id),
+            status: Set(ReturnStatus::Requested.as_str().to_string()),
+            reason: Set(reason.to_string()),
+            restocked: Set(false),
+            refund_amount: Set(None),
+            created_gmt: Set(now),
+            modified_gmt: Set(now),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+
+        let mut inserted = Vec::with_capacity(lines.len());
+        for line in &lines {
+            let item = ::entity::return_items::ActiveModel {
+                mid: Set(mid),
+                return_id: Set(rma.id),
+                order_item_id: Set(line.order_item_id),
+                quantity: Set(line.quantity),
+                ..Default::default()
+            }
+            .insert(&txn)
+            .await?;
+            inserted.push(item);
+        }
+
+        txn.commit().await?;
+        Ok(ReturnWithItems { rma, items: inserted })
+    }
+
+    /// Find a return of an order
+    pub async fn find(
+        db: &DatabaseConnection,
+        mid: i32,
+        order_id: i32,
+        id: i32,
+    ) -> Result<ReturnWithItems, ReturnError> {
+        Self::load(db, mid, order_id, id).await
+    }
+
+    /// An order's returns, oldest first
+    pub async fn list(
+        db: &DatabaseConnection,
+        mid: i32,
+        order_id: i32,
+    ) -> Result<Vec<ReturnWithItems>, ReturnError> {
+        let returns = Returns::find()
+            .filter(::entity::returns::Column::Mid.eq(mid))
+            .filter(::entity::returns::Column::OrderId.eq(order_id))
+            .order_by_asc(::entity::returns::Column::Id)
+            .all(db)
+            .await?;
+        if returns.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut lines = ReturnItems::find()
+            .filter(::entity::return_items::Column::Mid.eq(mid))
+            .filter(::entity::return_items::Column::ReturnId.is_in(returns.iter().map(|r| r.id)))
+            .order_by_asc(::entity::return_items::Column::Id)
+            .all(db)
+            .await?;
+
+        Ok(returns
+            .into_iter()
+            .map(|rma| {
+                let (items, rest) = lines.drain(..).partition(|line| line.return_id == rma.id);
+                lines = rest;
+                ReturnWithItems { rma, items }
+            })
+            .collect())
+    }
+
+    /// Accept a requested return, optionally putting its items back into
+    /// sellable stock
+    #[instrument(skip_all, fields(mid = mid, order_id = order_id, id = id, restock = restock))]
+    pub async fn approve(
+        db: &DatabaseConnection,
+        mid: i32,
+        order_id: i32,
+        id: i32,
+        restock: bool,
+    ) -> Result<ReturnWithItems, ReturnError> {
+        let txn = db.begin().await?;
+        let ReturnWithItems { rma, items } = Self::load(&txn, mid, order_id, id).await?;
+        Self::expect(&rma, ReturnStatus::Requested)?;
+
+        if restock {
+            let order_items = OrderItemService::list(&txn, mid, order_id).await?;
+            let lines: Vec<(&str, i32)> = items
+                .iter()
+                .filter_map(|line| {
+                    order_items
+                        .iter()
+                        .find(|item| item.id == line.order_item_id)
+                        .map(|item| (item.sku.as_str(), line.quantity))
+                })
+                .collect();
+            InventoryService::restock_return(&txn, mid, order_id, &lines).await?;
+        }
+
+        let rma = Self::set_status(&txn, rma, ReturnStatus::Approved, |active| {
+            active.restocked = Set(restock);
+        })
+        .await?;
+
+        txn.commit().await?;
+        Ok(ReturnWithItems { rma, items })
+    }
+
+    /// Turn down a requested return
+    #[instrument(skip_all, fields(mid = mid, order_id = order_id, id = id))]
+    pub async fn reject(
+        db: &DatabaseConnection,
+        mid: i32,
+        order_id: i32,
+        id: i32,
+    ) -> Result<ReturnWithItems, ReturnError> {
+        let ReturnWithItems { rma, items } = Self::load(db, mid, order_id, id).await?;
+        Self::expect(&rma, ReturnStatus::Requested)?;
+
+        let rma = Self::set_status(db, rma, ReturnStatus::Rejected, |_| {}).await?;
+        Ok(ReturnWithItems { rma, items })
+    }
+
+    /// Refund an approved return through the gateway that took the
+    /// payment. Without an `amount`, the returned items' value is refunded.
+    #[instrument(skip_all, fields(mid = mid, order_id = order_id, id = id))]
+    pub async fn refund(
+        db: &DatabaseConnection,
+        gateway: &dyn PaymentGateway,
+        mid: i32,
+        order_id: i32,
+        id: i32,
+        amount: Option<Decimal>,
+    ) -> Result<ReturnWithItems, ReturnError> {
+        let ReturnWithItems { rma, items } = Self::load(db, mid, order_id, id).await?;
+        Self::expect(&rma, ReturnStatus::Approved)?;
+
+        let amount = match amount {
+            Some(amount) => amount,
+            None => refund_value(&OrderItemService::list(db, mid, order_id).await?, &items),
+        };
+        OrderPaymentService::refund(db, gateway, mid, order_id, Some(amount)).await?;
+
+        let rma = Self::set_status(db, rma, ReturnStatus::Refunded, |active| {
+            active.refund_amount = Set(Some(amount));
+        })
+        .await?;
+        Ok(ReturnWithItems { rma, items })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(id: i32, sku: &str, quantity: i32, cents: i64) -> OrderItem {
+        OrderItem {
+            id,
+            mid: 1,
+            order_id: 9,
+            sku: sku.to_string(),
+            product_name: sku.to_string(),
+            quantity,
+            unit_price: Decimal::new(cents, 2),
+            options: String::new(),
+        }
+    }
+
+    fn returned(order_item_id: i32, quantity: i32) -> ReturnItem {
+        ReturnItem { id: 0, mid: 1, return_id: 1, order_item_id, quantity }
+    }
+
+    #[test]
+    fn test_plan_return() {
+        let items = vec![item(1, "SKU001", 3, 1999), item(2, "SKU002", 1, 500), item(3, "%TAX", 1, 150)];
+        let remaining = returnable(&items, &[returned(2, 1)]);
+        assert_eq!(remaining, BTreeMap::from([(1, 3)]));
+
+        let lines = plan_return(&remaining, &[
+            ReturnLine { order_item_id: 1, quantity: 1 },
+            ReturnLine { order_item_id: 1, quantity: 1 },
+        ])
+        .unwrap();
+        assert_eq!(lines, vec![ReturnLine { order_item_id: 1, quantity: 2 }]);
+
+        assert!(matches!(
+            plan_return(&remaining, &[ReturnLine { order_item_id: 2, quantity: 1 }]),
+            Err(ReturnError::NotReturnable { order_item_id: 2, returnable: 0, .. })
+        ));
+        assert!(matches!(plan_return(&remaining, &[]), Err(ReturnError::Empty)));
+    }
+
+    #[test]
+    fn test_refund_value() {
+        let items = vec![item(1, "SKU001", 3, 1999), item(2, "SKU002", 1, 500)];
+        assert_eq!(refund_value(&items, &[returned(1, 2), returned(2, 1)]), Decimal::new(4498, 2));
+    }
+
+    #[test]
+    fn test_status_roundtrip() {
+        for status in [ReturnStatus::Requested, ReturnStatus::Approved, ReturnStatus::Rejected, ReturnStatus::Refunded] {
+            assert_eq!(status.as_str().parse::<ReturnStatus>(), Ok(status));
+        }
+    }
+}
@@ -0,0 +1,60 @@
+//! Order lookup by partial customer details
+//!
+//! Merchants find orders from whatever the shopper can tell them: part of
+//! a name or email, a phone number, or the last digits of a payment
+//! reference. Terms match case-insensitively anywhere in the billing name
+//! and either billing email, digit-for-digit anywhere in the billing phone,
+//! and at the end of the gateway transaction ID. The legacy `order_bill_*`
+//! columns are not part of the entity and are queried through raw
+//! expressions.
+
+use sea_orm::sea_query::Expr;
+use sea_orm::Condition;
+
+/// Fewest digits a term needs before it is also matched against phones
+const MIN_PHONE_DIGITS: usize = 3;
+
+/// Escape `LIKE` wildcards so a term matches literally
+fn escape_like(term: &str) -> String {
+    let mut escaped = String::with_capacity(term.len());
+    for c in term.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Orders whose billing details or transaction ID match `term`
+pub(crate) fn matches(term: &str) -> Condition {
+    let escaped = escape_like(term.trim());
+    let contains = format!("%{}%", escaped);
+
+    let mut condition = Condition::any()
+        .add(Expr::cust_with_values("order_bill_name ILIKE $1", [contains.clone()]))
+        .add(Expr::cust_with_values("order_bill_email ILIKE $1", [contains.clone()]))
+        .add(Expr::cust_with_values("bill_email ILIKE $1", [contains]))
+        .add(Expr::cust_with_values("paid_txn ILIKE $1", [format!("%{}", escaped)]));
+
+    // Phones are stored however they were typed; compare digits only
+    let digits: String = term.chars().filter(char::is_ascii_digit).collect();
+    if digits.len() >= MIN_PHONE_DIGITS {
+        condition = condition.add(Expr::cust_with_values(
+            "regexp_replace(coalesce(order_bill_phone, ''), '[^0-9]', '', 'g') LIKE $1",
+            [format!("%{}%", digits)],
+        ));
+    }
+    condition
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wildcards_match_literally() {
+        assert_eq!(escape_like("50%_off\\"), "50\\%\\_off\\\\");
+        assert_eq!(escape_like("smith"), "smith");
+    }
+}
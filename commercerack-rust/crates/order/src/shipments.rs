@@ -0,0 +1,536 @@
+//! Shipments of an order
+//!
+//! An order can go out in several parts. Each shipment records the carrier,
+//! tracking number and how many of each order item it carried. Once every
+//! item has been shipped in full, the order's `shipped_gmt` is set.
+//! Adjustment lines (`%COUPON`, `%TAX`, `%SHIP`) never ship.
+//!
+//! A shipment can also be sent with a label bought from a
+//! [`LabelProvider`], which then supplies the carrier and tracking number.
+//! Voiding the label takes the shipment back, so its items are outstanding
+//! again.
+
+use commercerack_core::Timestamp;
+use commercerack_shipping::{Label, LabelAddress, LabelProvider, LabelRequest, Tracking};
+use rust_decimal::Decimal;
+use sea_orm::{entity::*, query::*, ConnectionTrait, DatabaseConnection, DbErr, Set, TransactionTrait};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use ::entity::prelude::{Order, Orders, OrderItem, Shipment, ShipmentItem, ShipmentItems, Shipments, Skus};
+use commercerack_events::{outbox, DomainEvent};
+use tracing::warn;
+
+use crate::items::OrderItemService;
+use crate::{OrderError, OrderService};
+use tracing::instrument;
+
+/// Quantity of one order item going out in a shipment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShipmentLine {
+    pub order_item_id: i32,
+    pub quantity: i32,
+}
+
+/// A shipment to record against an order
+#[derive(Debug, Clone)]
+pub struct NewShipment {
+    pub carrier: String,
+    pub tracking_number: String,
+    /// Empty to ship everything not shipped yet
+    pub lines: Vec<ShipmentLine>,
+}
+
+/// A shipment to send with a bought label
+#[derive(Debug, Clone)]
+pub struct LabeledShipment {
+    /// Service level to buy, e.g. `ground`
+    pub service: String,
+    pub ship_to: LabelAddress,
+    /// Empty to ship everything not shipped yet
+    pub lines: Vec<ShipmentLine>,
+}
+
+/// A shipment together with what it carried
+#[derive(Debug, Clone, Serialize)]
+pub struct ShipmentWithItems {
+    pub shipment: Shipment,
+    pub items: Vec<ShipmentItem>,
+}
+
+/// Whether an order item is a physical good rather than an adjustment line
+pub fn is_shippable(item: &OrderItem) -> bool {
+    !item.sku.starts_with('%')
+}
+
+/// Quantity of each shippable order item not shipped yet, keyed by item ID
+pub fn outstanding(items: &[OrderItem], shipped: &[ShipmentItem]) -> BTreeMap<i32, i32> {
+    let mut remaining: BTreeMap<i32, i32> = items
+        .iter()
+        .filter(|item| is_shippable(item))
+        .map(|item| (item.id, item.quantity))
+        .collect();
+    for line in shipped {
+        if let Some(quantity) = remaining.get_mut(&line.order_item_id) {
+            *quantity -= line.quantity;
+        }
+    }
+    remaining.retain(|_, quantity| *quantity > 0);
+    remaining
+}
+
+/// Check the requested lines against what is still outstanding.
+///
+/// No lines means everything outstanding; lines naming the same item are
+/// merged.
+pub fn plan_shipment(
+    remaining: &BTreeMap<i32, i32>,
+    requested: &[ShipmentLine],
+) -> Result<Vec<ShipmentLine>, OrderError> {
+    if requested.is_empty() {
+        if remaining.is_empty() {
+            return Err(OrderError::NothingToShip);
+        }
+        return Ok(remaining
+            .iter()
+            .map(|(&order_item_id, &quantity)| ShipmentLine { order_item_id, quantity })
+            .collect());
+    }
+
+    let mut merged: BTreeMap<i32, i32> = BTreeMap::new();
+    for line in requested {
+        *merged.entry(line.order_item_id).or_default() += line.quantity;
+    }
+
+    merged
+        .into_iter()
+        .map(|(order_item_id, quantity)| {
+            let available = remaining.get(&order_item_id).copied().unwrap_or_default();
+            if quantity <= 0 || quantity > available {
+                return Err(OrderError::OverShipment { order_item_id, quantity, remaining: available });
+            }
+            Ok(ShipmentLine { order_item_id, quantity })
+        })
+        .collect()
+}
+
+/// Weight of the planned lines, from their SKUs. Items whose SKU is
+/// unknown weigh nothing.
+pub fn shipment_weight(items: &[OrderItem], lines: &[ShipmentLine], skus: &[::entity::prelude::Sku]) -> Decimal {
+    lines
+        .iter()
+        .filter_map(|line| {
+            let item = items.iter().find(|item| item.id == line.order_item_id)?;
+            let sku = skus.iter().find(|sku| sku.sku == item.sku)?;
+            Some(sku.weight * Decimal::from(line.quantity))
+        })
+        .sum()
+}
+
+/// Shipment lines already recorded against an order's items
+pub(crate) async fn shipped_lines<C: ConnectionTrait>(
+    conn: &C,
+    mid: i32,
+    items: &[OrderItem],
+) -> Result<Vec<ShipmentItem>, DbErr> {
+    ShipmentItems::find()
+        .filter(::entity::shipment_items::Column::Mid.eq(mid))
+        .filter(::entity::shipment_items::Column::OrderItemId.is_in(items.iter().map(|item| item.id)))
+        .all(conn)
+        .await
+}
+
+/// Whether any shipment has been recorded for an order
+pub(crate) async fn has_shipments<C: ConnectionTrait>(conn: &C, order: &Order) -> Result<bool, DbErr> {
+    let shipment = Shipments::find()
+        .filter(::entity::shipments::Column::Mid.eq(order.mid))
+        .filter(::entity::shipments::Column::OrderId.eq(order.id))
+        .one(conn)
+        .await?;
+    Ok(shipment.is_some())
+}
+
+/// Write a shipment of already planned `lines`, marking the order shipped
+/// if they cover everything `remaining`. Returns the order as it now is.
+pub(crate) async fn record_shipment<C: ConnectionTrait>(
+    conn: &C,
+    order: Order,
+    remaining: &BTreeMap<i32, i32>,
+    lines: &[ShipmentLine],
+    carrier: String,
+    tracking_number: String,
+    label: Option<(&str, &Label)>,
+) -> Result<(ShipmentWithItems, Order), DbErr> {
+    let now = Timestamp::now();
+    let record = ::entity::shipments::ActiveModel {
+        mid: Set(order.mid),
+        order_id: Set(order.id),
+        carrier: Set(carrier),
+        tracking_number: Set(tracking_number),
+        shipped_gmt: Set(now),
+        label_provider: Set(label.map(|(provider, _)| provider.to_string())),
+        label_id: Set(label.map(|(_, label)| label.id.clone())),
+        label_url: Set(label.map(|(_, label)| label.label_url.clone())),
+        label_cost: Set(label.map(|(_, label)| label.cost)),
+        ..Default::default()
+    }
+    .insert(conn)
+    .await?;
+
+    let mut shipped = Vec::with_capacity(lines.len());
+    for line in lines {
+        let item = ::entity::shipment_items::ActiveModel {
+            mid: Set(order.mid),
+            shipment_id: Set(record.id),
+            order_item_id: Set(line.order_item_id),
+            quantity: Set(line.quantity),
+            ..Default::default()
+        }
+        .insert(conn)
+        .await?;
+        shipped.push(item);
+    }
+
+    let complete = lines.len() == remaining.len()
+        && lines.iter().all(|line| remaining.get(&line.order_item_id) == Some(&line.quantity));
+    let order = if complete {
+        let mut active: ::entity::orders::ActiveModel = order.into();
+        active.shipped_gmt = Set(Some(now));
+        let order = active.update(conn).await?;
+        outbox::record(conn, &DomainEvent::OrderShipped(order.clone())).await?;
+        order
+    } else {
+        order
+    };
+
+    Ok((ShipmentWithItems { shipment: record, items: shipped }, order))
+}
+
+impl OrderService {
+    /// Record a shipment of some or all of an order's outstanding items.
+    ///
+    /// Marks the order shipped once nothing is left outstanding.
+    #[instrument(skip_all, fields(mid = mid, order_id = order_id))]
+    pub async fn create_shipment(
+        db: &DatabaseConnection,
+        mid: i32,
+        order_id: i32,
+        shipment: NewShipment,
+    ) -> Result<ShipmentWithItems, OrderError> {
+        let txn = db.begin().await?;
+
+        let order = Orders::find()
+            .filter(::entity::orders::Column::Mid.eq(mid))
+            .filter(::entity::orders::Column::Id.eq(order_id))
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .ok_or(OrderError::NotFound)?;
+        if order.cancelled_gmt.is_some() {
+            return Err(OrderError::Cancelled);
+        }
+
+        let items = OrderItemService::list(&txn, mid, order_id).await?;
+        if shipment.lines.iter().any(|line| !items.iter().any(|item| item.id == line.order_item_id)) {
+            return Err(OrderError::ItemNotFound);
+        }
+        let remaining = outstanding(&items, &shipped_lines(&txn, mid, &items).await?);
+        let lines = plan_shipment(&remaining, &shipment.lines)?;
+
+        let (shipped, _) =
+            record_shipment(&txn, order, &remaining, &lines, shipment.carrier, shipment.tracking_number, None).await?;
+        txn.commit().await?;
+
+        Ok(shipped)
+    }
+
+    /// Buy a label for some or all of an order's outstanding items and
+    /// record the shipment it sends. If the shipment cannot be recorded the
+    /// label is voided again.
+    #[instrument(skip_all, fields(mid = mid, order_id = order_id, provider = provider.name()))]
+    pub async fn create_labeled_shipment(
+        db: &DatabaseConnection,
+        provider: &dyn LabelProvider,
+        mid: i32,
+        order_id: i32,
+        shipment: LabeledShipment,
+    ) -> Result<ShipmentWithItems, OrderError> {
+        let txn = db.begin().await?;
+
+        let order = Orders::find()
+            .filter(::entity::orders::Column::Mid.eq(mid))
+            .filter(::entity::orders::Column::Id.eq(order_id))
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .ok_or(OrderError::NotFound)?;
+        if order.cancelled_gmt.is_some() {
+            return Err(OrderError::Cancelled);
+        }
+
+        let items = OrderItemService::list(&txn, mid, order_id).await?;
+        if shipment.lines.iter().any(|line| !items.iter().any(|item| item.id == line.order_item_id)) {
+            return Err(OrderError::ItemNotFound);
+        }
+        let remaining = outstanding(&items, &shipped_lines(&txn, mid, &items).await?);
+        let lines = plan_shipment(&remaining, &shipment.lines)?;
+        let skus = Skus::find()
+            .filter(::entity::skus::Column::Mid.eq(mid))
+            .filter(::entity::skus::Column::Sku.is_in(items.iter().map(|item| item.sku.clone())))
+            .all(&txn)
+            .await?;
+
+        let label = provider
+            .buy(&LabelRequest {
+                mid,
+                reference: order.orderid.clone(),
+                service: shipment.service,
+                ship_to: shipment.ship_to,
+                weight: shipment_weight(&items, &lines, &skus),
+            })
+            .await?;
+
+        let recorded = async {
+            let (shipped, _) = record_shipment(
+                &txn,
+                order,
+                &remaining,
+                &lines,
+                label.carrier.clone(),
+                label.tracking_number.clone(),
+                Some((provider.name(), &label)),
+            )
+            .await?;
+            txn.commit().await?;
+            Ok::<_, DbErr>(shipped)
+        }
+        .await;
+
+        match recorded {
+            Ok(shipped) => Ok(shipped),
+            Err(e) => {
+                if let Err(void) = provider.void(&label.id).await {
+                    warn!("Label {} was bought but its shipment was not recorded and voiding failed: {}", label.id, void);
+                }
+                Err(e.into())
+            }
+        }
+    }
+
+    async fn find_shipment<C: ConnectionTrait>(
+        conn: &C,
+        mid: i32,
+        order_id: i32,
+        shipment_id: i32,
+    ) -> Result<Shipment, OrderError> {
+        Shipments::find()
+            .filter(::entity::shipments::Column::Mid.eq(mid))
+            .filter(::entity::shipments::Column::OrderId.eq(order_id))
+            .filter(::entity::shipments::Column::Id.eq(shipment_id))
+            .one(conn)
+            .await?
+            .ok_or(OrderError::ShipmentNotFound)
+    }
+
+    /// Void a shipment's label and take the shipment back: its items are
+    /// outstanding again and the order is no longer shipped. Nothing
+    /// changes if the provider refuses.
+    #[instrument(skip_all, fields(mid = mid, order_id = order_id, shipment_id = shipment_id))]
+    pub async fn void_label(
+        db: &DatabaseConnection,
+        provider: &dyn LabelProvider,
+        mid: i32,
+        order_id: i32,
+        shipment_id: i32,
+    ) -> Result<(), OrderError> {
+        let txn = db.begin().await?;
+
+        let shipment = Self::find_shipment(&txn, mid, order_id, shipment_id).await?;
+        let Some(label_id) = shipment.label_id else {
+            return Err(OrderError::NoLabel);
+        };
+
+        ShipmentItems::delete_many()
+            .filter(::entity::shipment_items::Column::Mid.eq(mid))
+            .filter(::entity::shipment_items::Column::ShipmentId.eq(shipment.id))
+            .exec(&txn)
+            .await?;
+        Shipments::delete_many()
+            .filter(::entity::shipments::Column::Mid.eq(mid))
+            .filter(::entity::shipments::Column::Id.eq(shipment.id))
+            .exec(&txn)
+            .await?;
+        let order = Orders::find()
+            .filter(::entity::orders::Column::Mid.eq(mid))
+            .filter(::entity::orders::Column::Id.eq(order_id))
+            .one(&txn)
+            .await?
+            .ok_or(OrderError::NotFound)?;
+        if order.shipped_gmt.is_some() {
+            let mut active: ::entity::orders::ActiveModel = order.into();
+            active.shipped_gmt = Set(None);
+            active.update(&txn).await?;
+        }
+
+        // Last, so a refusal rolls the shipment back into place
+        provider.void(&label_id).await?;
+        txn.commit().await?;
+        Ok(())
+    }
+
+    /// Latest tracking of a shipment sent with a bought label
+    pub async fn track_shipment(
+        db: &DatabaseConnection,
+        provider: &dyn LabelProvider,
+        mid: i32,
+        order_id: i32,
+        shipment_id: i32,
+    ) -> Result<Tracking, OrderError> {
+        let shipment = Self::find_shipment(db, mid, order_id, shipment_id).await?;
+        if shipment.label_id.is_none() {
+            return Err(OrderError::NoLabel);
+        }
+        Ok(provider.track(&shipment.carrier, &shipment.tracking_number).await?)
+    }
+
+    /// An order's shipments, oldest first
+    pub async fn list_shipments(
+        db: &DatabaseConnection,
+        mid: i32,
+        order_id: i32,
+    ) -> Result<Vec<ShipmentWithItems>, OrderError> {
+        let shipments = Shipments::find()
+            .filter(::entity::shipments::Column::Mid.eq(mid))
+            .filter(::entity::shipments::Column::OrderId.eq(order_id))
+            .order_by_asc(::entity::shipments::Column::ShippedGmt)
+            .order_by_asc(::entity::shipments::Column::Id)
+            .all(db)
+            .await?;
+        if shipments.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut lines = ShipmentItems::find()
+            .filter(::entity::shipment_items::Column::Mid.eq(mid))
+            .filter(::entity::shipment_items::Column::ShipmentId.is_in(shipments.iter().map(|s| s.id)))
+            .order_by_asc(::entity::shipment_items::Column::Id)
+            .all(db)
+            .await?;
+
+        Ok(shipments
+            .into_iter()
+            .map(|shipment| {
+                let (items, rest) = lines.drain(..).partition(|line| line.shipment_id == shipment.id);
+                lines = rest;
+                ShipmentWithItems { shipment, items }
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+
+    fn item(id: i32, sku: &str, quantity: i32) -> OrderItem {
+        OrderItem {
+            id,
+            mid: 1,
+            order_id: 9,
+            sku: sku.to_string(),
+            product_name: sku.to_string(),
+            quantity,
+            unit_price: Decimal::new(500, 2),
+            options: String::new(),
+        }
+    }
+
+    fn shipped(order_item_id: i32, quantity: i32) -> ShipmentItem {
+        ShipmentItem { id: 0, mid: 1, shipment_id: 1, order_item_id, quantity }
+    }
+
+    #[test]
+    fn test_outstanding_skips_adjustments_and_shipped() {
+        let items = vec![item(1, "SKU001", 3), item(2, "SKU002", 1), item(3, "%SHIP", 1)];
+        let remaining = outstanding(&items, &[shipped(1, 2), shipped(2, 1)]);
+        assert_eq!(remaining, BTreeMap::from([(1, 1)]));
+    }
+
+    #[test]
+    fn test_plan_shipment() {
+        let remaining = BTreeMap::from([(1, 3), (2, 1)]);
+
+        let all = plan_shipment(&remaining, &[]).unwrap();
+        assert_eq!(all, vec![
+            ShipmentLine { order_item_id: 1, quantity: 3 },
+            ShipmentLine { order_item_id: 2, quantity: 1 },
+        ]);
+
+        let partial = plan_shipment(&remaining, &[
+            ShipmentLine { order_item_id: 1, quantity: 1 },
+            ShipmentLine { order_item_id: 1, quantity: 1 },
+        ])
+        .unwrap();
+        assert_eq!(partial, vec![ShipmentLine { order_item_id: 1, quantity: 2 }]);
+
+        assert!(matches!(
+            plan_shipment(&remaining, &[ShipmentLine { order_item_id: 2, quantity: 2 }]),
+            Err(OrderError::OverShipment { order_item_id: 2, remaining: 1, .. })
+        ));
+        assert!(matches!(plan_shipment(&BTreeMap::new(), &[]), Err(OrderError::NothingToShip)));
+    }
+
+    #[test]
+    fn test_shipment_weight_counts_planned_lines() {
+        let items = vec![item(1, "SKU001", 3), item(2, "SKU002", 1), item(3, "SKU003", 1)];
+        let sku = |sku: &str, weight: i64| ::entity::prelude::Sku {
+            id: 0,
+            pid: 0,
+            mid: 1,
+            sku: sku.to_string(),
+            title: sku.to_string(),
+            price: Decimal::ZERO,
+            cost: Decimal::ZERO,
+            upc: String::new(),
+            inv_available: 0,
+            qty_onshelf: 0,
+            weight: Decimal::new(weight, 1),
+            reorder_point: None,
+            reorder_quantity: 0,
+            low_stock_gmt: None,
+        };
+        let skus = vec![sku("SKU001", 15), sku("SKU002", 40)];
+
+        // SKU003 is unknown and weighs nothing
+        let lines = [
+            ShipmentLine { order_item_id: 1, quantity: 2 },
+            ShipmentLine { order_item_id: 3, quantity: 1 },
+        ];
+        assert_eq!(shipment_weight(&items, &lines, &skus), Decimal::new(30, 1));
+    }
+
+    #[tokio::test]
+    async fn test_only_labeled_shipments_can_be_voided() {
+        use sea_orm::{DatabaseBackend, MockDatabase};
+
+        let shipment = Shipment {
+            id: 4,
+            mid: 1,
+            order_id: 9,
+            carrier: "UPS".to_string(),
+            tracking_number: "1Z999".to_string(),
+            shipped_gmt: Timestamp::EPOCH,
+            label_provider: None,
+            label_id: None,
+            label_url: None,
+            label_cost: None,
+        };
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![shipment]])
+            .into_connection();
+
+        let provider = commercerack_shipping::StubLabelProvider::new();
+        let result = OrderService::void_label(&db, &provider, 1, 9, 4).await;
+        assert!(matches!(result, Err(OrderError::NoLabel)));
+    }
+}
@@ -0,0 +1,136 @@
+//! Sales figures for merchant dashboards
+//!
+//! Everything is aggregated in SQL over the orders created in a period.
+//! Revenue only counts orders that have been paid; adjustment lines
+//! (`%COUPON`, `%TAX`, `%SHIP`, `%GIFTCARD`) are left out of product sales.
+
+use commercerack_core::Timestamp;
+use rust_decimal::Decimal;
+use sea_orm::sea_query::Expr;
+use sea_orm::{entity::*, query::*, ConnectionTrait, DbErr, FromQueryResult};
+use serde::Serialize;
+use ::entity::prelude::{OrderItems, Orders};
+
+use crate::OrderService;
+
+/// Order totals for a period
+#[derive(Debug, Clone, Default, PartialEq, FromQueryResult)]
+pub struct SalesSummary {
+    pub orders: i64,
+    pub paid_orders: i64,
+    /// Total of the paid orders; `None` when there were none
+    pub revenue: Option<Decimal>,
+}
+
+impl SalesSummary {
+    /// Revenue per paid order
+    pub fn average_order_value(&self) -> Decimal {
+        match self.revenue {
+            Some(revenue) if self.paid_orders > 0 => (revenue / Decimal::from(self.paid_orders)).round_dp(2),
+            _ => Decimal::ZERO,
+        }
+    }
+}
+
+/// How much of one SKU sold in a period
+#[derive(Debug, Clone, PartialEq, FromQueryResult, Serialize)]
+pub struct ProductSales {
+    pub sku: String,
+    pub product_name: String,
+    pub quantity: i64,
+    pub revenue: Decimal,
+}
+
+impl OrderService {
+    /// Order count and paid revenue of orders created between `from` and `to`
+    pub async fn sales_summary<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        from: Timestamp,
+        to: Timestamp,
+    ) -> Result<SalesSummary, DbErr> {
+        use ::entity::orders::Column;
+
+        let summary = Orders::find()
+            .select_only()
+            .column_as(Expr::col(Column::Id).count(), "orders")
+            // COUNT of a nullable column counts only the rows where it is set
+            .column_as(Expr::col(Column::PaidGmt).count(), "paid_orders")
+            .column_as(Expr::cust("SUM(CASE WHEN paid_gmt IS NOT NULL THEN total END)"), "revenue")
+            .filter(Column::Mid.eq(mid))
+            .filter(Column::CreatedGmt.between(from, to))
+            .into_model::<SalesSummary>()
+            .one(db)
+            .await?;
+
+        Ok(summary.unwrap_or_default())
+    }
+
+    /// Best selling SKUs by quantity in orders created between `from` and `to`
+    pub async fn top_products<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        from: Timestamp,
+        to: Timestamp,
+        limit: u64,
+    ) -> Result<Vec<ProductSales>, DbErr> {
+        use ::entity::order_items::Column;
+
+        let order_ids = Orders::find()
+            .select_only()
+            .column(::entity::orders::Column::Id)
+            .filter(::entity::orders::Column::Mid.eq(mid))
+            .filter(::entity::orders::Column::CreatedGmt.between(from, to))
+            .into_query();
+
+        OrderItems::find()
+            .select_only()
+            .column(Column::Sku)
+            .column_as(Expr::col(Column::ProductName).max(), "product_name")
+            .column_as(Expr::col(Column::Quantity).sum(), "quantity")
+            .column_as(Expr::cust("SUM(quantity * unit_price)"), "revenue")
+            .filter(Column::Mid.eq(mid))
+            .filter(Column::OrderId.in_subquery(order_ids))
+            .filter(Column::Sku.not_like("\\%%"))
+            .group_by(Column::Sku)
+            .order_by_desc(Expr::cust("quantity"))
+            .order_by_asc(Column::Sku)
+            .limit(limit)
+            .into_model::<ProductSales>()
+            .all(db)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sea_orm::{DatabaseBackend, MockDatabase};
+
+    #[test]
+    fn test_average_order_value() {
+        let summary = SalesSummary {
+            orders: 5,
+            paid_orders: 3,
+            revenue: Some(Decimal::new(10000, 2)),
+        };
+        assert_eq!(summary.average_order_value(), Decimal::new(3333, 2));
+        assert_eq!(SalesSummary::default().average_order_value(), Decimal::ZERO);
+    }
+
+    #[tokio::test]
+    async fn test_top_products_skip_adjustment_lines() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([Vec::<::entity::prelude::OrderItem>::new()])
+            .into_connection();
+
+        OrderService::top_products(&db, 1, Timestamp::from_unix(100), Timestamp::from_unix(200), 5).await.unwrap();
+
+        let log = db.into_transaction_log();
+        let sql = log[0].statements()[0].to_string();
+        assert!(sql.contains(r#""order_items"."sku" NOT LIKE E'\\%%'"#), "{}", sql);
+        assert!(sql.contains(r#"IN (SELECT "orders"."id" FROM "orders""#), "{}", sql);
+        assert!(sql.contains(r#"GROUP BY "order_items"."sku""#), "{}", sql);
+        assert!(sql.contains("LIMIT 5"), "{}", sql);
+    }
+}
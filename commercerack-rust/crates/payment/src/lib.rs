@@ -1 +1,216 @@
-fn main() {}
+//! Payment processing module
+//!
+//! `PaymentGateway` is the seam between orders and a payment processor.
+//! Amounts are passed as `Decimal` in major units (dollars); adapters
+//! convert to whatever the processor expects.
+
+use async_trait::async_trait;
+use commercerack_core::money::Money;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+pub mod paypal;
+pub mod stripe;
+
+pub use paypal::PayPalGateway;
+pub use stripe::StripeGateway;
+
+#[derive(Error, Debug)]
+pub enum PaymentError {
+    #[error("Payment declined: {0}")]
+    Declined(String),
+
+    #[error("Invalid amount {0}")]
+    InvalidAmount(Decimal),
+
+    #[error("Gateway error: {0}")]
+    Gateway(String),
+
+    #[error("{0} is not supported by this gateway")]
+    Unsupported(&'static str),
+
+    #[error("Webhook signature verification failed")]
+    InvalidSignature,
+
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+}
+
+/// State of a transaction as reported by the gateway
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransactionStatus {
+    /// Funds are held but not yet collected
+    Authorized,
+    Captured,
+    Refunded,
+    Voided,
+    /// Accepted by the gateway but not final yet (e.g. a refund in flight)
+    Pending,
+    /// Refused after the fact, reported asynchronously via webhook
+    Declined,
+}
+
+impl TransactionStatus {
+    pub const ALL: [TransactionStatus; 6] = [
+        TransactionStatus::Authorized,
+        TransactionStatus::Captured,
+        TransactionStatus::Refunded,
+        TransactionStatus::Voided,
+        TransactionStatus::Pending,
+        TransactionStatus::Declined,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransactionStatus::Authorized => "authorized",
+            TransactionStatus::Captured => "captured",
+            TransactionStatus::Refunded => "refunded",
+            TransactionStatus::Voided => "voided",
+            TransactionStatus::Pending => "pending",
+            TransactionStatus::Declined => "declined",
+        }
+    }
+}
+
+impl fmt::Display for TransactionStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for TransactionStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|status| status.as_str() == s)
+            .ok_or_else(|| format!("unknown transaction status {}", s))
+    }
+}
+
+/// Result of a gateway operation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GatewayTransaction {
+    /// Gateway reference, used for follow-up capture/refund/void calls
+    pub id: String,
+    pub status: TransactionStatus,
+    pub amount: Decimal,
+    /// Provider-side reference for reconciliation (e.g. the PayPal order ID)
+    pub lookup: Option<String>,
+    /// When the funds settled, if the gateway reports it
+    pub settled_gmt: Option<i64>,
+}
+
+/// A redirect-based payment the buyer still has to approve with the provider
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PaymentSession {
+    /// Pass back as `AuthorizeRequest::payment_method` once approved
+    pub id: String,
+    pub approve_url: Option<String>,
+}
+
+/// Request to authorize (and optionally capture) a payment
+#[derive(Debug, Clone)]
+pub struct AuthorizeRequest {
+    pub amount: Decimal,
+    pub currency: String,
+    /// Tokenized payment method from the client (never raw card data)
+    pub payment_method: String,
+    pub description: Option<String>,
+    /// Capture immediately instead of only holding the funds
+    pub capture: bool,
+}
+
+/// A payment processor
+#[async_trait]
+pub trait PaymentGateway: Send + Sync {
+    /// Short identifier, e.g. `stripe`
+    fn name(&self) -> &'static str;
+
+    /// Authorize a payment, capturing it too if `request.capture` is set
+    async fn authorize(&self, request: &AuthorizeRequest) -> Result<GatewayTransaction, PaymentError>;
+
+    /// Collect an authorized payment. `None` captures the full amount
+    async fn capture(&self, transaction_id: &str, amount: Option<Money>) -> Result<GatewayTransaction, PaymentError>;
+
+    /// Refund a captured payment. `None` refunds the full amount
+    async fn refund(&self, transaction_id: &str, amount: Option<Money>) -> Result<GatewayTransaction, PaymentError>;
+
+    /// Release an authorization that was never captured
+    async fn void(&self, transaction_id: &str) -> Result<GatewayTransaction, PaymentError>;
+
+    /// Start a payment the buyer approves on the provider's site
+    async fn create_session(
+        &self,
+        _amount: Decimal,
+        _currency: &str,
+        _description: &str,
+        _capture: bool,
+    ) -> Result<PaymentSession, PaymentError> {
+        Err(PaymentError::Unsupported("Payment sessions"))
+    }
+
+    /// Verify and decode an asynchronous notification. `Ok(None)` means the
+    /// event is valid but irrelevant to order payments. Header names are
+    /// expected in lowercase.
+    async fn parse_webhook(
+        &self,
+        _headers: &HashMap<String, String>,
+        _body: &str,
+    ) -> Result<Option<GatewayTransaction>, PaymentError> {
+        Err(PaymentError::Unsupported("Webhooks"))
+    }
+
+    /// Check that the gateway is reachable and accepts our credentials
+    async fn ping(&self) -> Result<(), PaymentError> {
+        Ok(())
+    }
+}
+
+/// Convert a major-unit amount to minor units (cents)
+pub fn to_minor_units(amount: Decimal) -> Result<i64, PaymentError> {
+    if amount <= Decimal::ZERO || amount.round_dp(2) != amount {
+        return Err(PaymentError::InvalidAmount(amount));
+    }
+    (amount * Decimal::ONE_HUNDRED)
+        .trunc()
+        .to_string()
+        .parse()
+        .map_err(|_| PaymentError::InvalidAmount(amount))
+}
+
+/// Convert minor units (cents) back to a major-unit amount
+pub fn from_minor_units(amount: i64) -> Decimal {
+    Decimal::new(amount, 2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_minor_units() {
+        assert_eq!(to_minor_units(Decimal::new(1999, 2)).unwrap(), 1999);
+        assert_eq!(to_minor_units(Decimal::new(5, 0)).unwrap(), 500);
+        assert!(to_minor_units(Decimal::ZERO).is_err());
+        assert!(to_minor_units(Decimal::new(-100, 2)).is_err());
+        assert!(to_minor_units(Decimal::new(1, 3)).is_err());
+
+        assert_eq!(from_minor_units(1999), Decimal::new(1999, 2));
+    }
+
+    #[test]
+    fn test_transaction_status_roundtrip() {
+        for status in TransactionStatus::ALL {
+            assert_eq!(status.as_str().parse::<TransactionStatus>(), Ok(status));
+            assert_eq!(serde_json::to_string(&status).unwrap(), format!("\"{}\"", status));
+        }
+        assert!("settled".parse::<TransactionStatus>().is_err());
+    }
+}
@@ -0,0 +1,571 @@
+//! PayPal adapter built on the Orders v2 and Payments v2 APIs
+//!
+//! PayPal payments are buyer-approved: `create_session` creates a PayPal
+//! order whose approve link the buyer follows, and `authorize` is then
+//! called with that PayPal order ID. Captures can complete asynchronously,
+//! in which case the final state arrives through `parse_webhook`.
+//!
+//! Transaction IDs are authorization IDs until captured and capture IDs
+//! afterwards; the PayPal order ID is reported as the `lookup`.
+
+use async_trait::async_trait;
+use chrono::DateTime;
+use commercerack_core::money;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+use crate::{
+    AuthorizeRequest, GatewayTransaction, PaymentError, PaymentGateway, PaymentSession,
+    TransactionStatus,
+};
+
+const PAYPAL_API: &str = "https://api-m.paypal.com";
+
+#[derive(Deserialize)]
+struct AccessToken {
+    access_token: String,
+    expires_in: u64,
+}
+
+#[derive(Deserialize)]
+struct Money {
+    currency_code: String,
+    value: String,
+}
+
+#[derive(Deserialize)]
+struct Link {
+    href: String,
+    rel: String,
+}
+
+#[derive(Deserialize)]
+struct RelatedIds {
+    order_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SupplementaryData {
+    related_ids: Option<RelatedIds>,
+}
+
+/// A capture, authorization or refund resource
+#[derive(Deserialize)]
+struct Payment {
+    id: String,
+    status: String,
+    amount: Option<Money>,
+    update_time: Option<String>,
+    supplementary_data: Option<SupplementaryData>,
+    #[serde(default)]
+    links: Vec<Link>,
+}
+
+#[derive(Deserialize, Default)]
+struct Payments {
+    #[serde(default)]
+    authorizations: Vec<Payment>,
+    #[serde(default)]
+    captures: Vec<Payment>,
+}
+
+#[derive(Deserialize)]
+struct PurchaseUnit {
+    amount: Option<Money>,
+    payments: Option<Payments>,
+}
+
+#[derive(Deserialize)]
+struct Order {
+    id: String,
+    #[serde(default)]
+    purchase_units: Vec<PurchaseUnit>,
+    #[serde(default)]
+    links: Vec<Link>,
+}
+
+#[derive(Deserialize)]
+struct WebhookEvent {
+    event_type: String,
+    resource: Payment,
+}
+
+#[derive(Deserialize)]
+struct VerifyResponse {
+    verification_status: String,
+}
+
+/// PayPal payment gateway
+pub struct PayPalGateway {
+    client: reqwest::Client,
+    client_id: String,
+    client_secret: String,
+    base_url: String,
+    /// Webhook ID from the PayPal dashboard, needed to verify notifications
+    webhook_id: Option<String>,
+    token: Mutex<Option<(String, Instant)>>,
+}
+
+impl PayPalGateway {
+    pub fn new(client_id: impl Into<String>, client_secret: impl Into<String>) -> Self {
+        Self::with_base_url(client_id, client_secret, PAYPAL_API)
+    }
+
+    /// Point at a different API host, e.g. `https://api-m.sandbox.paypal.com`
+    pub fn with_base_url(
+        client_id: impl Into<String>,
+        client_secret: impl Into<String>,
+        base_url: impl Into<String>,
+    ) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            client_id: client_id.into(),
+            client_secret: client_secret.into(),
+            base_url: base_url.into(),
+            webhook_id: None,
+            token: Mutex::new(None),
+        }
+    }
+
+    pub fn with_webhook_id(mut self, webhook_id: impl Into<String>) -> Self {
+        self.webhook_id = Some(webhook_id.into());
+        self
+    }
+
+    /// Create from `PAYPAL_CLIENT_ID`, `PAYPAL_CLIENT_SECRET` and the optional
+    /// `PAYPAL_API_BASE` and `PAYPAL_WEBHOOK_ID`
+    pub fn from_env() -> Option<Self> {
+        let client_id = std::env::var("PAYPAL_CLIENT_ID").ok()?;
+        let client_secret = std::env::var("PAYPAL_CLIENT_SECRET").ok()?;
+        let base_url = std::env::var("PAYPAL_API_BASE").unwrap_or_else(|_| PAYPAL_API.to_string());
+
+        let gateway = Self::with_base_url(client_id, client_secret, base_url);
+        Some(match std::env::var("PAYPAL_WEBHOOK_ID") {
+            Ok(webhook_id) => gateway.with_webhook_id(webhook_id),
+            Err(_) => gateway,
+        })
+    }
+
+    /// OAuth access token, cached until shortly before it expires
+    async fn access_token(&self) -> Result<String, PaymentError> {
+        let mut cached = self.token.lock().await;
+        if let Some((token, expires)) = cached.as_ref() {
+            if Instant::now() < *expires {
+                return Ok(token.clone());
+            }
+        }
+
+        let response = self
+            .client
+            .post(format!("{}/v1/oauth2/token", self.base_url))
+            .basic_auth(&self.client_id, Some(&self.client_secret))
+            .form(&[("grant_type", "client_credentials")])
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(parse_error(&response.text().await?));
+        }
+
+        let token: AccessToken = response.json().await?;
+        let lifetime = Duration::from_secs(token.expires_in.saturating_sub(60));
+        *cached = Some((token.access_token.clone(), Instant::now() + lifetime));
+        Ok(token.access_token)
+    }
+
+    async fn request<T: for<'de> Deserialize<'de>>(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        body: Option<Value>,
+    ) -> Result<T, PaymentError> {
+        let mut request = self
+            .client
+            .request(method, format!("{}{}", self.base_url, path))
+            .bearer_auth(self.access_token().await?)
+            .header("Prefer", "return=representation");
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+
+        let response = request.send().await?;
+        let status = response.status();
+        let body = response.text().await?;
+        if status.is_success() {
+            serde_json::from_str(&body).map_err(|e| PaymentError::Gateway(e.to_string()))
+        } else {
+            Err(parse_error(&body))
+        }
+    }
+
+    fn amount(&self, amount: money::Money) -> Result<Value, PaymentError> {
+        if amount.amount() <= Decimal::ZERO {
+            return Err(PaymentError::InvalidAmount(amount.amount()));
+        }
+        Ok(json!({ "currency_code": amount.currency().as_str(), "value": format_amount(amount.amount()) }))
+    }
+
+    async fn verify_webhook(&self, headers: &HashMap<String, String>, event: &Value) -> Result<(), PaymentError> {
+        let webhook_id = self.webhook_id.as_ref().ok_or(PaymentError::InvalidSignature)?;
+        let header = |name: &str| headers.get(name).cloned().ok_or(PaymentError::InvalidSignature);
+
+        let body = json!({
+            "auth_algo": header("paypal-auth-algo")?,
+            "cert_url": header("paypal-cert-url")?,
+            "transmission_id": header("paypal-transmission-id")?,
+            "transmission_sig": header("paypal-transmission-sig")?,
+            "transmission_time": header("paypal-transmission-time")?,
+            "webhook_id": webhook_id,
+            "webhook_event": event,
+        });
+
+        let result: VerifyResponse = self
+            .request(reqwest::Method::POST, "/v1/notifications/verify-webhook-signature", Some(body))
+            .await?;
+        if result.verification_status == "SUCCESS" {
+            Ok(())
+        } else {
+            Err(PaymentError::InvalidSignature)
+        }
+    }
+}
+
+/// PayPal amounts are strings with two decimals
+fn format_amount(amount: Decimal) -> String {
+    format!("{:.2}", amount)
+}
+
+fn parse_amount(money: Option<&Money>) -> Decimal {
+    money
+        .and_then(|money| money.value.parse().ok())
+        .unwrap_or(Decimal::ZERO)
+}
+
+/// Map a PayPal error body to a `PaymentError`
+fn parse_error(body: &str) -> PaymentError {
+    let Ok(error) = serde_json::from_str::<Value>(body) else {
+        return PaymentError::Gateway(body.to_string());
+    };
+
+    // Instrument and payer problems come back as UNPROCESSABLE_ENTITY issues
+    let issue = error["details"][0]["issue"].as_str().unwrap_or_default();
+    let message = error["message"]
+        .as_str()
+        .or_else(|| error["error_description"].as_str())
+        .unwrap_or(body)
+        .to_string();
+    if error["name"] == "UNPROCESSABLE_ENTITY"
+        && (issue.starts_with("INSTRUMENT_") || issue.starts_with("PAYER_") || issue == "TRANSACTION_REFUSED")
+    {
+        PaymentError::Declined(message)
+    } else {
+        PaymentError::Gateway(message)
+    }
+}
+
+/// Map a capture, authorization or refund to a transaction
+fn payment_transaction(payment: Payment, lookup: Option<String>) -> Result<GatewayTransaction, PaymentError> {
+    let status = match payment.status.as_str() {
+        "CREATED" => TransactionStatus::Authorized,
+        "CAPTURED" | "COMPLETED" | "PARTIALLY_CAPTURED" => TransactionStatus::Captured,
+        "VOIDED" => TransactionStatus::Voided,
+        "PENDING" => TransactionStatus::Pending,
+        "DENIED" | "DECLINED" | "FAILED" => TransactionStatus::Declined,
+        "REFUNDED" | "PARTIALLY_REFUNDED" => TransactionStatus::Refunded,
+        other => return Err(PaymentError::Gateway(format!("Unexpected payment status {}", other))),
+    };
+
+    let settled_gmt = match status {
+        TransactionStatus::Captured => payment
+            .update_time
+            .as_deref()
+            .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+            .map(|time| time.timestamp()),
+        _ => None,
+    };
+    let lookup = lookup.or_else(|| {
+        payment
+            .supplementary_data
+            .and_then(|data| data.related_ids)
+            .and_then(|ids| ids.order_id)
+    });
+
+    Ok(GatewayTransaction {
+        id: payment.id,
+        status,
+        amount: parse_amount(payment.amount.as_ref()),
+        lookup,
+        settled_gmt,
+    })
+}
+
+/// Synchronous calls report refusals as errors rather than a status
+fn refuse_declined(txn: GatewayTransaction) -> Result<GatewayTransaction, PaymentError> {
+    match txn.status {
+        TransactionStatus::Declined => Err(PaymentError::Declined(format!("Payment {} was declined", txn.id))),
+        _ => Ok(txn),
+    }
+}
+
+/// Capture ID a refund belongs to, from its `up` link
+fn refunded_capture_id(refund: &Payment) -> Option<String> {
+    refund
+        .links
+        .iter()
+        .find(|link| link.rel == "up" && link.href.contains("/captures/"))
+        .and_then(|link| link.href.rsplit('/').next())
+        .map(str::to_string)
+}
+
+#[async_trait]
+impl PaymentGateway for PayPalGateway {
+    fn name(&self) -> &'static str {
+        "paypal"
+    }
+
+    /// `request.payment_method` is the ID of a PayPal order the buyer approved
+    async fn authorize(&self, request: &AuthorizeRequest) -> Result<GatewayTransaction, PaymentError> {
+        let order_id = &request.payment_method;
+
+        // The buyer approved whatever the PayPal order says; make sure it covers ours
+        let order: Order = self
+            .request(reqwest::Method::GET, &format!("/v2/checkout/orders/{}", order_id), None)
+            .await?;
+        let approved = order.purchase_units.first().and_then(|unit| unit.amount.as_ref());
+        let currency_matches = approved
+            .map(|money| money.currency_code.eq_ignore_ascii_case(&request.currency))
+            .unwrap_or(false);
+        if !currency_matches || parse_amount(approved) != request.amount {
+            return Err(PaymentError::Gateway(format!(
+                "PayPal order {} does not match the order amount",
+                order_id
+            )));
+        }
+
+        let action = if request.capture { "capture" } else { "authorize" };
+        let order: Order = self
+            .request(
+                reqwest::Method::POST,
+                &format!("/v2/checkout/orders/{}/{}", order_id, action),
+                Some(json!({})),
+            )
+            .await?;
+
+        let payments = order
+            .purchase_units
+            .into_iter()
+            .next()
+            .and_then(|unit| unit.payments)
+            .unwrap_or_default();
+        let payment = if request.capture {
+            payments.captures.into_iter().next()
+        } else {
+            payments.authorizations.into_iter().next()
+        }
+        .ok_or_else(|| PaymentError::Gateway(format!("PayPal returned no {} for {}", action, order.id)))?;
+
+        refuse_declined(payment_transaction(payment, Some(order.id))?)
+    }
+
+    async fn capture(
+        &self,
+        transaction_id: &str,
+        amount: Option<money::Money>,
+    ) -> Result<GatewayTransaction, PaymentError> {
+        let mut body = json!({ "final_capture": true });
+        if let Some(amount) = amount {
+            body["amount"] = self.amount(amount)?;
+        }
+
+        let capture: Payment = self
+            .request(
+                reqwest::Method::POST,
+                &format!("/v2/payments/authorizations/{}/capture", transaction_id),
+                Some(body),
+            )
+            .await?;
+        refuse_declined(payment_transaction(capture, None)?)
+    }
+
+    async fn refund(
+        &self,
+        transaction_id: &str,
+        amount: Option<money::Money>,
+    ) -> Result<GatewayTransaction, PaymentError> {
+        let mut body = json!({});
+        if let Some(amount) = amount {
+            body["amount"] = self.amount(amount)?;
+        }
+
+        let refund: Payment = self
+            .request(
+                reqwest::Method::POST,
+                &format!("/v2/payments/captures/{}/refund", transaction_id),
+                Some(body),
+            )
+            .await?;
+
+        // A completed refund resource reports COMPLETED; that means refunded here
+        let completed = refund.status == "COMPLETED";
+        let mut txn = payment_transaction(refund, None)?;
+        if completed {
+            txn.status = TransactionStatus::Refunded;
+            txn.settled_gmt = None;
+        }
+        refuse_declined(txn)
+    }
+
+    async fn void(&self, transaction_id: &str) -> Result<GatewayTransaction, PaymentError> {
+        let authorization: Payment = self
+            .request(
+                reqwest::Method::POST,
+                &format!("/v2/payments/authorizations/{}/void", transaction_id),
+                None,
+            )
+            .await?;
+        payment_transaction(authorization, None)
+    }
+
+    async fn create_session(
+        &self,
+        amount: Decimal,
+        currency: &str,
+        description: &str,
+        capture: bool,
+    ) -> Result<PaymentSession, PaymentError> {
+        if amount <= Decimal::ZERO {
+            return Err(PaymentError::InvalidAmount(amount));
+        }
+
+        let body = json!({
+            "intent": if capture { "CAPTURE" } else { "AUTHORIZE" },
+            "purchase_units": [{
+                "description": description,
+                "amount": {
+                    "currency_code": currency.to_uppercase(),
+                    "value": format_amount(amount),
+                },
+            }],
+        });
+
+        let order: Order = self
+            .request(reqwest::Method::POST, "/v2/checkout/orders", Some(body))
+            .await?;
+        let approve_url = order
+            .links
+            .iter()
+            .find(|link| link.rel == "approve" || link.rel == "payer-action")
+            .map(|link| link.href.clone());
+
+        Ok(PaymentSession {
+            id: order.id,
+            approve_url,
+        })
+    }
+
+    async fn parse_webhook(
+        &self,
+        headers: &HashMap<String, String>,
+        body: &str,
+    ) -> Result<Option<GatewayTransaction>, PaymentError> {
+        let raw: Value = serde_json::from_str(body).map_err(|e| PaymentError::Gateway(e.to_string()))?;
+        self.verify_webhook(headers, &raw).await?;
+
+        let event: WebhookEvent = serde_json::from_value(raw).map_err(|e| PaymentError::Gateway(e.to_string()))?;
+        webhook_transaction(event)
+    }
+
+    /// Fetches an access token, so a cached token skips the round trip
+    async fn ping(&self) -> Result<(), PaymentError> {
+        self.access_token().await.map(|_| ())
+    }
+}
+
+/// Decode a verified webhook event
+fn webhook_transaction(event: WebhookEvent) -> Result<Option<GatewayTransaction>, PaymentError> {
+    match event.event_type.as_str() {
+        "PAYMENT.CAPTURE.COMPLETED"
+        | "PAYMENT.CAPTURE.PENDING"
+        | "PAYMENT.CAPTURE.DENIED"
+        | "PAYMENT.CAPTURE.DECLINED"
+        | "PAYMENT.AUTHORIZATION.VOIDED" => payment_transaction(event.resource, None).map(Some),
+        "PAYMENT.CAPTURE.REFUNDED" => {
+            // The resource is the refund; orders know the capture it refunds
+            let Some(capture_id) = refunded_capture_id(&event.resource) else {
+                return Ok(None);
+            };
+            Ok(Some(GatewayTransaction {
+                id: capture_id,
+                status: TransactionStatus::Refunded,
+                amount: parse_amount(event.resource.amount.as_ref()),
+                lookup: None,
+                settled_gmt: None,
+            }))
+        }
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capture_completed_webhook() {
+        let event: WebhookEvent = serde_json::from_str(
+            r#"{
+                "event_type": "PAYMENT.CAPTURE.COMPLETED",
+                "resource": {
+                    "id": "2GG279541U471931P",
+                    "status": "COMPLETED",
+                    "amount": {"currency_code": "USD", "value": "39.98"},
+                    "update_time": "2025-11-18T10:00:00Z",
+                    "supplementary_data": {"related_ids": {"order_id": "5O190127TN364715T"}}
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let txn = webhook_transaction(event).unwrap().unwrap();
+        assert_eq!(txn.id, "2GG279541U471931P");
+        assert_eq!(txn.status, TransactionStatus::Captured);
+        assert_eq!(txn.amount, Decimal::new(3998, 2));
+        assert_eq!(txn.lookup.as_deref(), Some("5O190127TN364715T"));
+        assert_eq!(txn.settled_gmt, Some(1763460000));
+    }
+
+    #[test]
+    fn test_refund_webhook_points_at_capture() {
+        let event: WebhookEvent = serde_json::from_str(
+            r#"{
+                "event_type": "PAYMENT.CAPTURE.REFUNDED",
+                "resource": {
+                    "id": "1JU08902781691411",
+                    "status": "COMPLETED",
+                    "amount": {"currency_code": "USD", "value": "10.00"},
+                    "links": [
+                        {"rel": "self", "href": "https://api-m.paypal.com/v2/payments/refunds/1JU08902781691411"},
+                        {"rel": "up", "href": "https://api-m.paypal.com/v2/payments/captures/2GG279541U471931P"}
+                    ]
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let txn = webhook_transaction(event).unwrap().unwrap();
+        assert_eq!(txn.id, "2GG279541U471931P");
+        assert_eq!(txn.status, TransactionStatus::Refunded);
+    }
+
+    #[test]
+    fn test_instrument_errors_are_declines() {
+        let body = r#"{"name":"UNPROCESSABLE_ENTITY","message":"The requested action could not be performed","details":[{"issue":"INSTRUMENT_DECLINED"}]}"#;
+        assert!(matches!(parse_error(body), PaymentError::Declined(_)));
+
+        let body = r#"{"name":"RESOURCE_NOT_FOUND","message":"The specified resource does not exist."}"#;
+        assert!(matches!(parse_error(body), PaymentError::Gateway(_)));
+    }
+}
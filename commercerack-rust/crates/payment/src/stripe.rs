@@ -0,0 +1,252 @@
+//! Stripe adapter built on the PaymentIntents API
+//!
+//! Authorization creates and confirms a PaymentIntent with manual capture,
+//! so `capture`, `refund` and `void` all refer to the intent ID.
+
+use async_trait::async_trait;
+use commercerack_core::money::Money;
+use serde::Deserialize;
+
+use crate::{
+    from_minor_units, to_minor_units, AuthorizeRequest, GatewayTransaction, PaymentError,
+    PaymentGateway, TransactionStatus,
+};
+
+const STRIPE_API: &str = "https://api.stripe.com";
+
+#[derive(Deserialize)]
+struct PaymentIntent {
+    id: String,
+    status: String,
+    amount: i64,
+    #[serde(default)]
+    amount_received: i64,
+}
+
+#[derive(Deserialize)]
+struct Refund {
+    id: String,
+    status: String,
+    amount: i64,
+}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    error: StripeError,
+}
+
+#[derive(Deserialize)]
+struct StripeError {
+    #[serde(rename = "type")]
+    kind: String,
+    message: Option<String>,
+}
+
+/// Stripe payment gateway
+pub struct StripeGateway {
+    client: reqwest::Client,
+    secret_key: String,
+    base_url: String,
+}
+
+impl StripeGateway {
+    pub fn new(secret_key: impl Into<String>) -> Self {
+        Self::with_base_url(secret_key, STRIPE_API)
+    }
+
+    /// Point at a different API host (stripe-mock, proxies)
+    pub fn with_base_url(secret_key: impl Into<String>, base_url: impl Into<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            secret_key: secret_key.into(),
+            base_url: base_url.into(),
+        }
+    }
+
+    /// Create from `STRIPE_SECRET_KEY` (and optional `STRIPE_API_BASE`)
+    pub fn from_env() -> Option<Self> {
+        let secret_key = std::env::var("STRIPE_SECRET_KEY").ok()?;
+        let base_url = std::env::var("STRIPE_API_BASE").unwrap_or_else(|_| STRIPE_API.to_string());
+        Some(Self::with_base_url(secret_key, base_url))
+    }
+
+    async fn post<T: for<'de> Deserialize<'de>>(
+        &self,
+        path: &str,
+        form: &[(&str, String)],
+    ) -> Result<T, PaymentError> {
+        let response = self
+            .client
+            .post(format!("{}{}", self.base_url, path))
+            .bearer_auth(&self.secret_key)
+            .form(form)
+            .send()
+            .await?;
+
+        let status = response.status();
+        let body = response.text().await?;
+        if status.is_success() {
+            serde_json::from_str(&body).map_err(|e| PaymentError::Gateway(e.to_string()))
+        } else {
+            Err(parse_error(&body))
+        }
+    }
+}
+
+/// Map a Stripe error body to a `PaymentError`
+fn parse_error(body: &str) -> PaymentError {
+    match serde_json::from_str::<ErrorBody>(body) {
+        Ok(ErrorBody { error }) => {
+            let message = error.message.unwrap_or_else(|| error.kind.clone());
+            if error.kind == "card_error" {
+                PaymentError::Declined(message)
+            } else {
+                PaymentError::Gateway(message)
+            }
+        }
+        Err(_) => PaymentError::Gateway(body.to_string()),
+    }
+}
+
+/// Map a PaymentIntent to a transaction
+fn intent_transaction(intent: PaymentIntent) -> Result<GatewayTransaction, PaymentError> {
+    let (status, amount) = match intent.status.as_str() {
+        "requires_capture" => (TransactionStatus::Authorized, intent.amount),
+        "succeeded" => (TransactionStatus::Captured, intent.amount_received),
+        "canceled" => (TransactionStatus::Voided, intent.amount),
+        "processing" => (TransactionStatus::Pending, intent.amount),
+        // requires_payment_method after confirmation means the card was refused
+        "requires_payment_method" => {
+            return Err(PaymentError::Declined("Payment method was declined".to_string()))
+        }
+        other => return Err(PaymentError::Gateway(format!("Unexpected intent status {}", other))),
+    };
+
+    Ok(GatewayTransaction {
+        id: intent.id,
+        status,
+        amount: from_minor_units(amount),
+        lookup: None,
+        settled_gmt: None,
+    })
+}
+
+/// Map a Refund to a transaction
+fn refund_transaction(refund: Refund) -> Result<GatewayTransaction, PaymentError> {
+    let status = match refund.status.as_str() {
+        "succeeded" => TransactionStatus::Refunded,
+        "pending" | "requires_action" => TransactionStatus::Pending,
+        other => return Err(PaymentError::Gateway(format!("Refund {}", other))),
+    };
+
+    Ok(GatewayTransaction {
+        id: refund.id,
+        status,
+        amount: from_minor_units(refund.amount),
+        lookup: None,
+        settled_gmt: None,
+    })
+}
+
+#[async_trait]
+impl PaymentGateway for StripeGateway {
+    fn name(&self) -> &'static str {
+        "stripe"
+    }
+
+    async fn authorize(&self, request: &AuthorizeRequest) -> Result<GatewayTransaction, PaymentError> {
+        let mut form = vec![
+            ("amount", to_minor_units(request.amount)?.to_string()),
+            ("currency", request.currency.to_lowercase()),
+            ("payment_method", request.payment_method.clone()),
+            ("payment_method_types[]", "card".to_string()),
+            ("confirm", "true".to_string()),
+            (
+                "capture_method",
+                if request.capture { "automatic" } else { "manual" }.to_string(),
+            ),
+        ];
+        if let Some(description) = &request.description {
+            form.push(("description", description.clone()));
+        }
+
+        let intent: PaymentIntent = self.post("/v1/payment_intents", &form).await?;
+        intent_transaction(intent)
+    }
+
+    async fn capture(&self, transaction_id: &str, amount: Option<Money>) -> Result<GatewayTransaction, PaymentError> {
+        let mut form = Vec::new();
+        if let Some(amount) = amount {
+            form.push(("amount_to_capture", to_minor_units(amount.amount())?.to_string()));
+        }
+
+        let intent: PaymentIntent = self
+            .post(&format!("/v1/payment_intents/{}/capture", transaction_id), &form)
+            .await?;
+        intent_transaction(intent)
+    }
+
+    async fn refund(&self, transaction_id: &str, amount: Option<Money>) -> Result<GatewayTransaction, PaymentError> {
+        let mut form = vec![("payment_intent", transaction_id.to_string())];
+        if let Some(amount) = amount {
+            form.push(("amount", to_minor_units(amount.amount())?.to_string()));
+        }
+
+        let refund: Refund = self.post("/v1/refunds", &form).await?;
+        refund_transaction(refund)
+    }
+
+    async fn void(&self, transaction_id: &str) -> Result<GatewayTransaction, PaymentError> {
+        let intent: PaymentIntent = self
+            .post(&format!("/v1/payment_intents/{}/cancel", transaction_id), &[])
+            .await?;
+        intent_transaction(intent)
+    }
+
+    /// Reads the account balance, the cheapest authenticated call
+    async fn ping(&self) -> Result<(), PaymentError> {
+        let response = self
+            .client
+            .get(format!("{}/v1/balance", self.base_url))
+            .bearer_auth(&self.secret_key)
+            .send()
+            .await?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(parse_error(&response.text().await?))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+
+    #[test]
+    fn test_intent_statuses() {
+        let intent: PaymentIntent = serde_json::from_str(
+            r#"{"id":"pi_123","status":"requires_capture","amount":1999,"amount_received":0}"#,
+        )
+        .unwrap();
+        let txn = intent_transaction(intent).unwrap();
+        assert_eq!(txn.status, TransactionStatus::Authorized);
+        assert_eq!(txn.amount, Decimal::new(1999, 2));
+
+        let intent: PaymentIntent = serde_json::from_str(
+            r#"{"id":"pi_123","status":"requires_payment_method","amount":1999}"#,
+        )
+        .unwrap();
+        assert!(matches!(intent_transaction(intent), Err(PaymentError::Declined(_))));
+    }
+
+    #[test]
+    fn test_card_errors_are_declines() {
+        let body = r#"{"error":{"type":"card_error","code":"card_declined","message":"Your card was declined."}}"#;
+        assert!(matches!(parse_error(body), PaymentError::Declined(m) if m == "Your card was declined."));
+
+        let body = r#"{"error":{"type":"invalid_request_error","message":"No such payment_intent"}}"#;
+        assert!(matches!(parse_error(body), PaymentError::Gateway(_)));
+    }
+}
@@ -0,0 +1,297 @@
+//! When products are on sale
+//!
+//! A product's status says whether the merchant has it on sale: drafts are
+//! being prepared, archived products are retired, and only active ones are
+//! shown to shoppers. An active product can also be limited to a window
+//! between `available_from` and `available_until`. A draft with an
+//! `available_from` is scheduled: [`ProductService::activate_due`] makes it
+//! active once that moment passes.
+
+use commercerack_core::Timestamp;
+use sea_orm::sea_query::{LockBehavior, LockType};
+use sea_orm::*;
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+use ::entity::prelude::*;
+use commercerack_events::{outbox, DomainEvent};
+use tracing::instrument;
+
+use crate::ProductService;
+
+#[derive(Error, Debug)]
+pub enum AvailabilityError {
+    #[error("Product not found")]
+    ProductNotFound,
+
+    #[error("available_until must be after available_from")]
+    InvalidWindow,
+
+    #[error("Database error: {0}")]
+    Db(#[from] DbErr),
+}
+
+impl From<crate::ProductError> for AvailabilityError {
+    fn from(e: crate::ProductError) -> Self {
+        match e {
+            crate::ProductError::NotFound => AvailabilityError::ProductNotFound,
+            crate::ProductError::Db(e) => AvailabilityError::Db(e),
+            crate::ProductError::Cursor(e) => AvailabilityError::Db(DbErr::Custom(e.to_string())),
+        }
+    }
+}
+
+/// Where a product is in its life
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ProductStatus {
+    /// Being prepared; hidden from shoppers
+    Draft,
+    /// On sale, within its window
+    #[default]
+    Active,
+    /// Retired; hidden from shoppers but kept for past orders
+    Archived,
+}
+
+impl ProductStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProductStatus::Draft => "draft",
+            ProductStatus::Active => "active",
+            ProductStatus::Archived => "archived",
+        }
+    }
+}
+
+impl fmt::Display for ProductStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ProductStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "draft" => Ok(ProductStatus::Draft),
+            "active" => Ok(ProductStatus::Active),
+            "archived" => Ok(ProductStatus::Archived),
+            other => Err(format!("unknown product status {}", other)),
+        }
+    }
+}
+
+/// A product's status and sale window
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Availability {
+    pub status: ProductStatus,
+    /// On sale from this moment; open when `None`
+    pub available_from: Option<Timestamp>,
+    /// Off sale from this moment; open when `None`
+    pub available_until: Option<Timestamp>,
+}
+
+impl Availability {
+    /// Why these settings can't be used, if they can't
+    pub fn validate(&self) -> Result<(), AvailabilityError> {
+        match (self.available_from, self.available_until) {
+            (Some(from), Some(until)) if until <= from => Err(AvailabilityError::InvalidWindow),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Which products a lookup includes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Visibility {
+    /// Active products within their window: what shoppers see
+    #[default]
+    Storefront,
+    /// Products with this status, whatever their window
+    Status(ProductStatus),
+    /// Every product
+    All,
+}
+
+impl FromStr for Visibility {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(Visibility::All),
+            status => status.parse().map(Visibility::Status),
+        }
+    }
+}
+
+impl Visibility {
+    /// Products included at `now`
+    pub fn condition(self, now: Timestamp) -> Condition {
+        use ::entity::products::Column;
+
+        match self {
+            Visibility::Storefront => Condition::all()
+                .add(Column::Status.eq(ProductStatus::Active.as_str()))
+                .add(Condition::any().add(Column::AvailableFrom.is_null()).add(Column::AvailableFrom.lte(now)))
+                .add(Condition::any().add(Column::AvailableUntil.is_null()).add(Column::AvailableUntil.gt(now))),
+            Visibility::Status(status) => Condition::all().add(Column::Status.eq(status.as_str())),
+            Visibility::All => Condition::all(),
+        }
+    }
+
+    /// Whether `product` is included at `now`
+    pub fn includes(self, product: &Product, now: Timestamp) -> bool {
+        match self {
+            Visibility::Storefront => {
+                product.status == ProductStatus::Active.as_str()
+                    && product.available_from.is_none_or(|from| from <= now)
+                    && product.available_until.is_none_or(|until| until > now)
+            }
+            Visibility::Status(status) => product.status == status.as_str(),
+            Visibility::All => true,
+        }
+    }
+}
+
+impl ProductService {
+    /// Change a product's status and sale window
+    #[instrument(skip_all, fields(mid = mid, id = id))]
+    pub async fn set_availability(
+        db: &DatabaseConnection,
+        mid: i32,
+        id: i32,
+        availability: Availability,
+    ) -> Result<Product, AvailabilityError> {
+        availability.validate()?;
+        let product = Self::find_by_id(db, mid, id).await?
+            .ok_or(AvailabilityError::ProductNotFound)?;
+
+        let mut active: ::entity::products::ActiveModel = product.into();
+        active.status = Set(availability.status.as_str().to_string());
+        active.available_from = Set(availability.available_from);
+        active.available_until = Set(availability.available_until);
+        active.ts = Set(Timestamp::now());
+
+        let txn = db.begin().await?;
+        let result = active.update(&txn).await?;
+        outbox::record(&txn, &DomainEvent::ProductUpdated(result.clone())).await?;
+        txn.commit().await?;
+        Ok(result)
+    }
+
+    /// Activate every merchant's scheduled drafts whose `available_from`
+    /// has passed by `now`. Drafts whose window already closed stay drafts.
+    /// Returns how many were activated.
+    #[instrument(skip_all)]
+    pub async fn activate_due(db: &DatabaseConnection, now: Timestamp) -> Result<u64, AvailabilityError> {
+        use ::entity::products::Column;
+
+        let txn = db.begin().await?;
+        let due = Products::find()
+            .filter(Column::Status.eq(ProductStatus::Draft.as_str()))
+            .filter(Column::AvailableFrom.lte(now))
+            .filter(Condition::any().add(Column::AvailableUntil.is_null()).add(Column::AvailableUntil.gt(now)))
+            .order_by_asc(Column::Id)
+            .lock_with_behavior(LockType::Update, LockBehavior::SkipLocked)
+            .all(&txn)
+            .await?;
+
+        let activated = due.len() as u64;
+        for product in due {
+            let mut active: ::entity::products::ActiveModel = product.into();
+            active.status = Set(ProductStatus::Active.as_str().to_string());
+            active.ts = Set(now);
+            let product = active.update(&txn).await?;
+            outbox::record(&txn, &DomainEvent::ProductUpdated(product)).await?;
+        }
+        txn.commit().await?;
+        Ok(activated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+
+    fn product(status: ProductStatus, from: Option<i64>, until: Option<i64>) -> Product {
+        Product {
+            id: 1,
+            mid: 1,
+            merchant: String::new(),
+            product: "W".to_string(),
+            ts: Timestamp::EPOCH,
+            product_name: "Widget".to_string(),
+            category: String::new(),
+            description: String::new(),
+            base_price: Decimal::new(500, 2),
+            base_cost: Decimal::new(200, 2),
+            supplier: String::new(),
+            supplier_id: String::new(),
+            upc: String::new(),
+            created_gmt: Timestamp::EPOCH,
+            lastsold_gmt: None,
+            product_type: "physical".to_string(),
+            download_url: None,
+            download_limit: None,
+            status: status.as_str().to_string(),
+            available_from: from.map(Timestamp::from_unix),
+            available_until: until.map(Timestamp::from_unix),
+        }
+    }
+
+    #[test]
+    fn test_storefront_shows_active_products_within_their_window() {
+        let now = Timestamp::from_unix(100);
+        let storefront = |p: &Product| Visibility::Storefront.includes(p, now);
+
+        assert!(storefront(&product(ProductStatus::Active, None, None)));
+        assert!(storefront(&product(ProductStatus::Active, Some(100), Some(101))));
+        assert!(!storefront(&product(ProductStatus::Active, Some(101), None)));
+        assert!(!storefront(&product(ProductStatus::Active, None, Some(100))));
+        assert!(!storefront(&product(ProductStatus::Draft, None, None)));
+        assert!(!storefront(&product(ProductStatus::Archived, None, None)));
+        assert!(Visibility::Status(ProductStatus::Draft).includes(&product(ProductStatus::Draft, Some(101), None), now));
+        assert_eq!("all".parse(), Ok(Visibility::All));
+        assert_eq!("archived".parse(), Ok(Visibility::Status(ProductStatus::Archived)));
+        assert!("deleted".parse::<Visibility>().is_err());
+
+        let sql = Products::find().filter(Visibility::Storefront.condition(now)).build(DbBackend::Postgres).to_string();
+        assert!(sql.contains(r#""products"."status" = 'active'"#), "{}", sql);
+        assert!(sql.contains(r#"("products"."available_from" IS NULL OR "products"."available_from" <= 100)"#), "{}", sql);
+        assert!(sql.contains(r#"("products"."available_until" IS NULL OR "products"."available_until" > 100)"#), "{}", sql);
+    }
+
+    #[tokio::test]
+    async fn test_activate_due_publishes_scheduled_drafts() {
+        let scheduled = product(ProductStatus::Draft, Some(90), None);
+        let published = Product { status: "active".to_string(), ts: Timestamp::from_unix(100), ..scheduled.clone() };
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![scheduled]])
+            .append_query_results([vec![published]])
+            .append_exec_results([MockExecResult { last_insert_id: 0, rows_affected: 1 }])
+            .into_connection();
+
+        let activated = ProductService::activate_due(&db, Timestamp::from_unix(100)).await.unwrap();
+        assert_eq!(activated, 1);
+
+        let log = db.into_transaction_log();
+        let statements = log[0].statements();
+        assert!(statements[1].sql.ends_with("FOR UPDATE SKIP LOCKED"), "{}", statements[1].sql);
+        assert!(statements[2].sql.starts_with(r#"UPDATE "products" SET"#), "{}", statements[2].sql);
+        assert!(statements[3].sql.contains("outbox"), "{}", statements[3].sql);
+        assert_eq!(statements.last().unwrap().sql, "COMMIT");
+    }
+
+    #[test]
+    fn test_window_must_end_after_it_starts() {
+        let availability = Availability {
+            status: ProductStatus::Draft,
+            available_from: Some(Timestamp::from_unix(100)),
+            available_until: Some(Timestamp::from_unix(100)),
+        };
+        assert!(matches!(availability.validate(), Err(AvailabilityError::InvalidWindow)));
+        assert!(Availability { available_until: None, ..availability }.validate().is_ok());
+    }
+}
@@ -0,0 +1,350 @@
+//! Bulk price changes
+//!
+//! A bulk change either sets prices and costs of listed products and SKUs,
+//! or moves the price of every product assigned to a category, and
+//! optionally their SKUs, by a percentage. The whole change applies in one
+//! transaction or not at all. A dry run works out the same changes without
+//! saving them.
+
+use commercerack_core::{Currency, Money, Timestamp};
+use commercerack_events::{outbox, DomainEvent};
+use rust_decimal::Decimal;
+use sea_orm::*;
+use ::entity::prelude::*;
+use std::collections::HashMap;
+use thiserror::Error;
+use tracing::instrument;
+
+#[derive(Error, Debug)]
+pub enum BulkPriceError {
+    #[error("Product {0} not found")]
+    ProductNotFound(i32),
+
+    #[error("SKU {0} not found")]
+    SkuNotFound(i32),
+
+    #[error("Category not found")]
+    CategoryNotFound,
+
+    #[error("The adjustment would make the price of {0} negative")]
+    NegativePrice(PriceTarget),
+
+    #[error("Database error: {0}")]
+    Db(#[from] DbErr),
+}
+
+/// What a price belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PriceTarget {
+    Product(i32),
+    Sku(i32),
+}
+
+impl std::fmt::Display for PriceTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PriceTarget::Product(id) => write!(f, "product {}", id),
+            PriceTarget::Sku(id) => write!(f, "SKU {}", id),
+        }
+    }
+}
+
+/// New price and cost of one product or SKU; `None` keeps the current one
+#[derive(Debug, Clone)]
+pub struct PriceChange {
+    pub target: PriceTarget,
+    pub price: Option<Decimal>,
+    pub cost: Option<Decimal>,
+}
+
+/// Price move of a category's products by a percentage, e.g. `-10` for
+/// ten percent off. Costs are kept.
+#[derive(Debug, Clone)]
+pub struct CategoryAdjustment {
+    pub category_id: i32,
+    pub percent: Decimal,
+    /// Adjust the SKUs of those products too
+    pub include_skus: bool,
+}
+
+#[derive(Debug, Clone)]
+pub enum BulkPriceUpdate {
+    Set(Vec<PriceChange>),
+    Adjust(CategoryAdjustment),
+}
+
+/// One price that changed, or would change on a dry run
+#[derive(Debug, Clone, PartialEq)]
+pub struct RepricedItem {
+    pub target: PriceTarget,
+    pub old_price: Decimal,
+    pub new_price: Decimal,
+    pub old_cost: Decimal,
+    pub new_cost: Decimal,
+}
+
+impl RepricedItem {
+    fn is_unchanged(&self) -> bool {
+        self.old_price == self.new_price && self.old_cost == self.new_cost
+    }
+}
+
+/// `price`, in `currency`, moved by `percent` and rounded as the store
+/// rounds money
+pub fn adjust(price: Decimal, percent: Decimal, currency: Currency) -> Decimal {
+    Money::new(price * (Decimal::ONE_HUNDRED + percent) / Decimal::ONE_HUNDRED, currency).round().amount()
+}
+
+/// The products and SKUs a change touches, by ID, and their new prices
+type Plan = (HashMap<i32, Product>, HashMap<i32, Sku>, Vec<RepricedItem>);
+
+/// Bulk price change service
+pub struct BulkPriceService;
+
+impl BulkPriceService {
+    /// Work out the changes of `update` and, unless `dry_run`, save them.
+    /// Adjusted prices are rounded to `currency`, the merchant's. Returns
+    /// the products and SKUs whose price or cost changes, in the order they
+    /// were listed or, for an adjustment, by ID.
+    #[instrument(skip_all, fields(mid = mid, dry_run = dry_run))]
+    pub async fn apply(
+        db: &DatabaseConnection,
+        mid: i32,
+        currency: Currency,
+        update: &BulkPriceUpdate,
+        dry_run: bool,
+    ) -> Result<Vec<RepricedItem>, BulkPriceError> {
+        let txn = db.begin().await?;
+        let (products, skus, repriced) = match update {
+            BulkPriceUpdate::Set(changes) => Self::plan_set(&txn, mid, changes).await?,
+            BulkPriceUpdate::Adjust(adjustment) => Self::plan_adjust(&txn, mid, currency, adjustment).await?,
+        };
+        let repriced: Vec<RepricedItem> = repriced.into_iter().filter(|item| !item.is_unchanged()).collect();
+        if dry_run {
+            txn.rollback().await?;
+            return Ok(repriced);
+        }
+
+        let now = Timestamp::now();
+        for item in &repriced {
+            match item.target {
+                PriceTarget::Product(id) => {
+                    let mut active: ::entity::products::ActiveModel = products[&id].clone().into();
+                    active.base_price = Set(item.new_price);
+                    active.base_cost = Set(item.new_cost);
+                    active.ts = Set(now);
+                    let product = active.update(&txn).await?;
+                    outbox::record(&txn, &DomainEvent::ProductUpdated(product)).await?;
+                }
+                PriceTarget::Sku(id) => {
+                    let mut active: ::entity::skus::ActiveModel = skus[&id].clone().into();
+                    active.price = Set(item.new_price);
+                    active.cost = Set(item.new_cost);
+                    let sku = active.update(&txn).await?;
+                    outbox::record(&txn, &DomainEvent::SkuUpdated(sku)).await?;
+                }
+            }
+        }
+        txn.commit().await?;
+        Ok(repriced)
+    }
+
+    async fn plan_set<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        changes: &[PriceChange],
+    ) -> Result<Plan, BulkPriceError> {
+        let product_ids = changes.iter().filter_map(|c| match c.target {
+            PriceTarget::Product(id) => Some(id),
+            PriceTarget::Sku(_) => None,
+        });
+        let sku_ids = changes.iter().filter_map(|c| match c.target {
+            PriceTarget::Sku(id) => Some(id),
+            PriceTarget::Product(_) => None,
+        });
+        let products = Self::products(db, mid, product_ids.collect()).await?;
+        let skus = Self::skus(db, mid, sku_ids.collect()).await?;
+
+        let mut repriced = Vec::with_capacity(changes.len());
+        for change in changes {
+            let (old_price, old_cost) = match change.target {
+                PriceTarget::Product(id) => products
+                    .get(&id)
+                    .map(|p| (p.base_price, p.base_cost))
+                    .ok_or(BulkPriceError::ProductNotFound(id))?,
+                PriceTarget::Sku(id) => {
+                    skus.get(&id).map(|s| (s.price, s.cost)).ok_or(BulkPriceError::SkuNotFound(id))?
+                }
+            };
+            repriced.push(RepricedItem {
+                target: change.target,
+                old_price,
+                new_price: change.price.unwrap_or(old_price),
+                old_cost,
+                new_cost: change.cost.unwrap_or(old_cost),
+            });
+        }
+        Ok((products, skus, repriced))
+    }
+
+    async fn plan_adjust<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        currency: Currency,
+        adjustment: &CategoryAdjustment,
+    ) -> Result<Plan, BulkPriceError> {
+        Categories::find()
+            .filter(::entity::categories::Column::Mid.eq(mid))
+            .filter(::entity::categories::Column::Id.eq(adjustment.category_id))
+            .one(db)
+            .await?
+            .ok_or(BulkPriceError::CategoryNotFound)?;
+
+        let product_ids = ProductCategories::find()
+            .select_only()
+            .column(::entity::product_categories::Column::ProductId)
+            .filter(::entity::product_categories::Column::Mid.eq(mid))
+            .filter(::entity::product_categories::Column::CategoryId.eq(adjustment.category_id))
+            .into_tuple::<i32>()
+            .all(db)
+            .await?;
+        let products = Self::products(db, mid, product_ids.clone()).await?;
+        let skus = if adjustment.include_skus && !product_ids.is_empty() {
+            Skus::find()
+                .filter(::entity::skus::Column::Mid.eq(mid))
+                .filter(::entity::skus::Column::Pid.is_in(product_ids))
+                .all(db)
+                .await?
+                .into_iter()
+                .map(|s| (s.id, s))
+                .collect()
+        } else {
+            HashMap::new()
+        };
+
+        let mut product_items: Vec<&Product> = products.values().collect();
+        product_items.sort_by_key(|p| p.id);
+        let mut sku_items: Vec<&Sku> = skus.values().collect();
+        sku_items.sort_by_key(|s| s.id);
+
+        let prices = product_items
+            .into_iter()
+            .map(|p| (PriceTarget::Product(p.id), p.base_price, p.base_cost))
+            .chain(sku_items.into_iter().map(|s| (PriceTarget::Sku(s.id), s.price, s.cost)));
+        let mut repriced = Vec::new();
+        for (target, old_price, cost) in prices {
+            let new_price = adjust(old_price, adjustment.percent, currency);
+            if new_price < Decimal::ZERO {
+                return Err(BulkPriceError::NegativePrice(target));
+            }
+            repriced.push(RepricedItem { target, old_price, new_price, old_cost: cost, new_cost: cost });
+        }
+        Ok((products, skus, repriced))
+    }
+
+    async fn products<C: ConnectionTrait>(db: &C, mid: i32, ids: Vec<i32>) -> Result<HashMap<i32, Product>, DbErr> {
+        if ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let products = Products::find()
+            .filter(::entity::products::Column::Mid.eq(mid))
+            .filter(::entity::products::Column::Id.is_in(ids))
+            .all(db)
+            .await?;
+        Ok(products.into_iter().map(|p| (p.id, p)).collect())
+    }
+
+    async fn skus<C: ConnectionTrait>(db: &C, mid: i32, ids: Vec<i32>) -> Result<HashMap<i32, Sku>, DbErr> {
+        if ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let skus = Skus::find()
+            .filter(::entity::skus::Column::Mid.eq(mid))
+            .filter(::entity::skus::Column::Id.is_in(ids))
+            .all(db)
+            .await?;
+        Ok(skus.into_iter().map(|s| (s.id, s)).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_adjust_rounds_to_the_currency() {
+        assert_eq!(adjust(Decimal::new(1999, 2), Decimal::from(-10), Currency::USD), Decimal::new(1799, 2));
+        assert_eq!(adjust(Decimal::new(1000, 2), Decimal::new(125, 1), Currency::USD), Decimal::new(1125, 2));
+        assert_eq!(adjust(Decimal::new(999, 2), Decimal::from(-100), Currency::USD), Decimal::ZERO);
+        assert_eq!(adjust(Decimal::from(1999), Decimal::from(-10), Currency::JPY), Decimal::from(1799));
+    }
+
+    fn product(id: i32, price: i64) -> Product {
+        Product {
+            id,
+            mid: 1,
+            merchant: "acme".to_string(),
+            product: format!("P{}", id),
+            ts: Timestamp::EPOCH,
+            product_name: format!("Product {}", id),
+            category: String::new(),
+            description: String::new(),
+            base_price: Decimal::new(price, 2),
+            base_cost: Decimal::new(500, 2),
+            supplier: String::new(),
+            supplier_id: String::new(),
+            upc: String::new(),
+            created_gmt: Timestamp::EPOCH,
+            lastsold_gmt: None,
+            product_type: "physical".to_string(),
+            download_url: None,
+            download_limit: None,
+            status: "active".to_string(),
+            available_from: None,
+            available_until: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_writes_nothing() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![product(5, 1999), product(6, 2500)]])
+            .into_connection();
+        let update = BulkPriceUpdate::Set(vec![
+            PriceChange { target: PriceTarget::Product(6), price: Some(Decimal::new(2500, 2)), cost: None },
+            PriceChange { target: PriceTarget::Product(5), price: Some(Decimal::new(1499, 2)), cost: Some(Decimal::new(600, 2)) },
+        ]);
+
+        let repriced = BulkPriceService::apply(&db, 1, Currency::USD, &update, true).await.unwrap();
+        assert_eq!(
+            repriced,
+            vec![RepricedItem {
+                target: PriceTarget::Product(5),
+                old_price: Decimal::new(1999, 2),
+                new_price: Decimal::new(1499, 2),
+                old_cost: Decimal::new(500, 2),
+                new_cost: Decimal::new(600, 2),
+            }]
+        );
+
+        let log = db.into_transaction_log();
+        let statements = log[0].statements();
+        assert_eq!(statements.len(), 3, "{:?}", statements);
+        assert_eq!(statements[2].sql, "ROLLBACK");
+    }
+
+    #[tokio::test]
+    async fn test_unknown_sku_fails_the_whole_update() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![product(5, 1999)]])
+            .append_query_results([Vec::<Sku>::new()])
+            .into_connection();
+        let update = BulkPriceUpdate::Set(vec![
+            PriceChange { target: PriceTarget::Product(5), price: Some(Decimal::new(1499, 2)), cost: None },
+            PriceChange { target: PriceTarget::Sku(40), price: Some(Decimal::new(1499, 2)), cost: None },
+        ]);
+
+        let result = BulkPriceService::apply(&db, 1, Currency::USD, &update, false).await;
+        assert!(matches!(result, Err(BulkPriceError::SkuNotFound(40))));
+    }
+}
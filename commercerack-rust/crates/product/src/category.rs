@@ -0,0 +1,490 @@
+//! Product category hierarchy using SeaORM
+//!
+//! Categories form a per-merchant tree through `parent_id`. Moving a
+//! category carries its whole subtree with it, since descendants only
+//! reference their direct parent. Products can be assigned to any number
+//! of categories.
+
+use commercerack_core::Timestamp;
+use commercerack_events::{outbox, DomainEvent};
+use sea_orm::sea_query::{Expr, OnConflict};
+use sea_orm::*;
+use ::entity::prelude::*;
+use std::collections::{HashMap, HashSet};
+use thiserror::Error;
+
+use crate::availability::Visibility;
+use crate::ProductService;
+
+#[derive(Error, Debug)]
+pub enum CategoryError {
+    #[error("Category not found")]
+    NotFound,
+
+    #[error("Parent category not found")]
+    ParentNotFound,
+
+    #[error("Product not found")]
+    ProductNotFound,
+
+    #[error("A category cannot be moved under itself or one of its descendants")]
+    Cycle,
+
+    #[error("Database error: {0}")]
+    Db(#[from] DbErr),
+}
+
+impl From<crate::ProductError> for CategoryError {
+    fn from(e: crate::ProductError) -> Self {
+        match e {
+            crate::ProductError::NotFound => CategoryError::ProductNotFound,
+            crate::ProductError::Db(e) => CategoryError::Db(e),
+            crate::ProductError::Cursor(e) => CategoryError::Db(DbErr::Custom(e.to_string())),
+        }
+    }
+}
+
+/// A category and its descendants
+#[derive(Debug, Clone, PartialEq)]
+pub struct CategoryNode {
+    pub category: Category,
+    pub children: Vec<CategoryNode>,
+}
+
+/// URL-safe slug for a category name, e.g. `Men's Shoes` -> `men-s-shoes`
+pub fn slugify(name: &str) -> String {
+    let mut slug = String::with_capacity(name.len());
+    for c in name.trim().to_lowercase().chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() {
+        "category".to_string()
+    } else {
+        // Leave room for a uniqueness suffix within the 100 character column
+        slug.chars().take(90).collect::<String>().trim_end_matches('-').to_string()
+    }
+}
+
+/// Arrange a merchant's categories into trees ordered by position, then name.
+/// Categories whose parent is missing are treated as roots.
+pub fn build_tree(categories: Vec<Category>) -> Vec<CategoryNode> {
+    let ids: HashSet<i32> = categories.iter().map(|c| c.id).collect();
+    let mut children: HashMap<Option<i32>, Vec<Category>> = HashMap::new();
+    for category in categories {
+        let parent = category.parent_id.filter(|p| ids.contains(p));
+        children.entry(parent).or_default().push(category);
+    }
+
+    fn attach(parent: Option<i32>, children: &mut HashMap<Option<i32>, Vec<Category>>) -> Vec<CategoryNode> {
+        let mut level = children.remove(&parent).unwrap_or_default();
+        level.sort_by(|a, b| a.position.cmp(&b.position).then_with(|| a.name.cmp(&b.name)));
+        level
+            .into_iter()
+            .map(|category| CategoryNode {
+                children: attach(Some(category.id), children),
+                category,
+            })
+            .collect()
+    }
+
+    attach(None, &mut children)
+}
+
+/// Category service for managing the category tree and product assignments
+pub struct CategoryService;
+
+impl CategoryService {
+    /// Create a category under `parent_id`, or at the top level
+    pub async fn create(
+        db: &DatabaseConnection,
+        mid: i32,
+        name: &str,
+        parent_id: Option<i32>,
+        position: i32,
+    ) -> Result<Category, CategoryError> {
+        if let Some(parent_id) = parent_id {
+            Self::find_by_id(db, mid, parent_id)
+                .await?
+                .ok_or(CategoryError::ParentNotFound)?;
+        }
+
+        let now = Timestamp::now();
+        let category = ::entity::categories::ActiveModel {
+            mid: Set(mid),
+            parent_id: Set(parent_id),
+            name: Set(name.trim().to_string()),
+            slug: Set(Self::unique_slug(db, mid, name).await?),
+            position: Set(position),
+            created_gmt: Set(now),
+            modified_gmt: Set(now),
+            ..Default::default()
+        };
+
+        let txn = db.begin().await?;
+        let result = category.insert(&txn).await?;
+        outbox::record(&txn, &DomainEvent::CategoryChanged { mid: mid.into(), id: result.id }).await?;
+        txn.commit().await?;
+        Ok(result)
+    }
+
+    /// Find category by ID
+    pub async fn find_by_id(
+        db: &DatabaseConnection,
+        mid: i32,
+        id: i32,
+    ) -> Result<Option<Category>, CategoryError> {
+        let category = Categories::find()
+            .filter(::entity::categories::Column::Mid.eq(mid))
+            .filter(::entity::categories::Column::Id.eq(id))
+            .one(db)
+            .await?;
+
+        Ok(category)
+    }
+
+    /// Find category by slug
+    pub async fn find_by_slug(
+        db: &DatabaseConnection,
+        mid: i32,
+        slug: &str,
+    ) -> Result<Option<Category>, CategoryError> {
+        let category = Categories::find()
+            .filter(::entity::categories::Column::Mid.eq(mid))
+            .filter(::entity::categories::Column::Slug.eq(slug))
+            .one(db)
+            .await?;
+
+        Ok(category)
+    }
+
+    /// All of a merchant's categories, unordered
+    pub async fn list(
+        db: &DatabaseConnection,
+        mid: i32,
+    ) -> Result<Vec<Category>, CategoryError> {
+        let categories = Categories::find()
+            .filter(::entity::categories::Column::Mid.eq(mid))
+            .all(db)
+            .await?;
+
+        Ok(categories)
+    }
+
+    /// The merchant's full category tree
+    pub async fn tree(
+        db: &DatabaseConnection,
+        mid: i32,
+    ) -> Result<Vec<CategoryNode>, CategoryError> {
+        Ok(build_tree(Self::list(db, mid).await?))
+    }
+
+    /// Rename or reorder a category. The slug is kept so existing links keep working.
+    pub async fn update(
+        db: &DatabaseConnection,
+        category: Category,
+        name: &str,
+        position: i32,
+    ) -> Result<Category, CategoryError> {
+        let mut active: ::entity::categories::ActiveModel = category.into();
+        active.name = Set(name.trim().to_string());
+        active.position = Set(position);
+        active.modified_gmt = Set(Timestamp::now());
+
+        Self::save(db, active).await
+    }
+
+    /// Move a category, and with it its subtree, under a new parent
+    pub async fn move_to(
+        db: &DatabaseConnection,
+        mid: i32,
+        id: i32,
+        parent_id: Option<i32>,
+    ) -> Result<Category, CategoryError> {
+        let categories = Self::list(db, mid).await?;
+        let category = categories
+            .iter()
+            .find(|c| c.id == id)
+            .cloned()
+            .ok_or(CategoryError::NotFound)?;
+
+        if let Some(parent_id) = parent_id {
+            let parents: HashMap<i32, Option<i32>> = categories.iter().map(|c| (c.id, c.parent_id)).collect();
+            if !parents.contains_key(&parent_id) {
+                return Err(CategoryError::ParentNotFound);
+            }
+            if is_descendant(&parents, parent_id, id) {
+                return Err(CategoryError::Cycle);
+            }
+        }
+
+        let mut active: ::entity::categories::ActiveModel = category.into();
+        active.parent_id = Set(parent_id);
+        active.modified_gmt = Set(Timestamp::now());
+
+        Self::save(db, active).await
+    }
+
+    /// Delete a category. Its children move up to its parent and its
+    /// product assignments are removed. Returns whether it existed.
+    pub async fn delete(
+        db: &DatabaseConnection,
+        mid: i32,
+        id: i32,
+    ) -> Result<bool, CategoryError> {
+        let txn = db.begin().await?;
+
+        let Some(category) = Categories::find()
+            .filter(::entity::categories::Column::Mid.eq(mid))
+            .filter(::entity::categories::Column::Id.eq(id))
+            .one(&txn)
+            .await?
+        else {
+            return Ok(false);
+        };
+
+        Categories::update_many()
+            .col_expr(::entity::categories::Column::ParentId, Expr::value(category.parent_id))
+            .filter(::entity::categories::Column::Mid.eq(mid))
+            .filter(::entity::categories::Column::ParentId.eq(id))
+            .exec(&txn)
+            .await?;
+
+        ProductCategories::delete_many()
+            .filter(::entity::product_categories::Column::CategoryId.eq(id))
+            .exec(&txn)
+            .await?;
+
+        category.delete(&txn).await?;
+        outbox::record(&txn, &DomainEvent::CategoryChanged { mid: mid.into(), id }).await?;
+
+        txn.commit().await?;
+        Ok(true)
+    }
+
+    /// Assign a product to a category; assigning twice is a no-op
+    pub async fn assign_product(
+        db: &DatabaseConnection,
+        mid: i32,
+        product_id: i32,
+        category_id: i32,
+    ) -> Result<(), CategoryError> {
+        ProductService::find_by_id(db, mid, product_id)
+            .await?
+            .ok_or(CategoryError::ProductNotFound)?;
+        Self::find_by_id(db, mid, category_id)
+            .await?
+            .ok_or(CategoryError::NotFound)?;
+
+        let assignment = ::entity::product_categories::ActiveModel {
+            product_id: Set(product_id),
+            category_id: Set(category_id),
+            mid: Set(mid),
+            created_gmt: Set(Timestamp::now()),
+        };
+
+        let txn = db.begin().await?;
+        let inserted = ProductCategories::insert(assignment)
+            .on_conflict(
+                OnConflict::columns([
+                    ::entity::product_categories::Column::ProductId,
+                    ::entity::product_categories::Column::CategoryId,
+                ])
+                .do_nothing()
+                .to_owned(),
+            )
+            .exec_without_returning(&txn)
+            .await?;
+        if inserted > 0 {
+            outbox::record(&txn, &DomainEvent::CategoryChanged { mid: mid.into(), id: category_id }).await?;
+        }
+        txn.commit().await?;
+        Ok(())
+    }
+
+    /// Remove a product from a category. Returns whether it was assigned.
+    pub async fn unassign_product(
+        db: &DatabaseConnection,
+        mid: i32,
+        product_id: i32,
+        category_id: i32,
+    ) -> Result<bool, CategoryError> {
+        let txn = db.begin().await?;
+        let result = ProductCategories::delete_many()
+            .filter(::entity::product_categories::Column::Mid.eq(mid))
+            .filter(::entity::product_categories::Column::ProductId.eq(product_id))
+            .filter(::entity::product_categories::Column::CategoryId.eq(category_id))
+            .exec(&txn)
+            .await?;
+        if result.rows_affected > 0 {
+            outbox::record(&txn, &DomainEvent::CategoryChanged { mid: mid.into(), id: category_id }).await?;
+        }
+        txn.commit().await?;
+        Ok(result.rows_affected > 0)
+    }
+
+    /// Categories a product is assigned to
+    pub async fn for_product(
+        db: &DatabaseConnection,
+        mid: i32,
+        product_id: i32,
+    ) -> Result<Vec<Category>, CategoryError> {
+        let category_ids = ProductCategories::find()
+            .select_only()
+            .column(::entity::product_categories::Column::CategoryId)
+            .filter(::entity::product_categories::Column::Mid.eq(mid))
+            .filter(::entity::product_categories::Column::ProductId.eq(product_id))
+            .into_query();
+
+        let categories = Categories::find()
+            .filter(::entity::categories::Column::Mid.eq(mid))
+            .filter(::entity::categories::Column::Id.in_subquery(category_ids))
+            .order_by_asc(::entity::categories::Column::Name)
+            .all(db)
+            .await?;
+
+        Ok(categories)
+    }
+
+    /// Products assigned directly to a category that are on sale now
+    pub async fn products(
+        db: &DatabaseConnection,
+        mid: i32,
+        category_id: i32,
+        limit: u64,
+        offset: u64,
+    ) -> Result<Vec<Product>, CategoryError> {
+        let product_ids = ProductCategories::find()
+            .select_only()
+            .column(::entity::product_categories::Column::ProductId)
+            .filter(::entity::product_categories::Column::Mid.eq(mid))
+            .filter(::entity::product_categories::Column::CategoryId.eq(category_id))
+            .into_query();
+
+        let products = Products::find()
+            .filter(::entity::products::Column::Mid.eq(mid))
+            .filter(::entity::products::Column::Id.in_subquery(product_ids))
+            .filter(Visibility::Storefront.condition(Timestamp::now()))
+            .order_by_asc(::entity::products::Column::ProductName)
+            .limit(limit)
+            .offset(offset)
+            .all(db)
+            .await?;
+
+        Ok(products)
+    }
+
+    /// Save a changed category and record that it changed
+    async fn save(
+        db: &DatabaseConnection,
+        active: ::entity::categories::ActiveModel,
+    ) -> Result<Category, CategoryError> {
+        let txn = db.begin().await?;
+        let result = active.update(&txn).await?;
+        outbox::record(&txn, &DomainEvent::CategoryChanged { mid: result.mid.into(), id: result.id }).await?;
+        txn.commit().await?;
+        Ok(result)
+    }
+
+    /// Slug for `name` not yet used by the merchant, suffixed `-2`, `-3`... on collision
+    async fn unique_slug(
+        db: &DatabaseConnection,
+        mid: i32,
+        name: &str,
+    ) -> Result<String, CategoryError> {
+        let base = slugify(name);
+        let taken: HashSet<String> = Categories::find()
+            .filter(::entity::categories::Column::Mid.eq(mid))
+            .filter(::entity::categories::Column::Slug.starts_with(&base))
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|c| c.slug)
+            .collect();
+
+        let slug = std::iter::once(base.clone())
+            .chain((2..).map(|n| format!("{}-{}", base, n)))
+            .find(|slug| !taken.contains(slug))
+            .expect("unbounded suffixes");
+        Ok(slug)
+    }
+}
+
+/// Whether `id` is `ancestor` or lies somewhere beneath it
+fn is_descendant(parents: &HashMap<i32, Option<i32>>, id: i32, ancestor: i32) -> bool {
+    let mut current = Some(id);
+    let mut seen = HashSet::new();
+    while let Some(node) = current {
+        if node == ancestor {
+            return true;
+        }
+        if !seen.insert(node) {
+            // Existing data already loops; refuse to make it worse
+            return true;
+        }
+        current = parents.get(&node).copied().flatten();
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn category(id: i32, parent_id: Option<i32>, name: &str, position: i32) -> Category {
+        Category {
+            id,
+            mid: 1,
+            parent_id,
+            name: name.to_string(),
+            slug: slugify(name),
+            position,
+            created_gmt: Timestamp::EPOCH,
+            modified_gmt: Timestamp::EPOCH,
+        }
+    }
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("Men's Shoes"), "men-s-shoes");
+        assert_eq!(slugify("  Sale -- 50% Off!  "), "sale-50-off");
+        assert_eq!(slugify("!!!"), "category");
+    }
+
+    #[test]
+    fn test_build_tree() {
+        let tree = build_tree(vec![
+            category(3, Some(1), "Sneakers", 0),
+            category(1, None, "Shoes", 1),
+            category(2, None, "Apparel", 0),
+            category(4, Some(1), "Boots", 0),
+            category(5, Some(99), "Orphan", 2),
+        ]);
+
+        let names: Vec<_> = tree.iter().map(|n| n.category.name.as_str()).collect();
+        assert_eq!(names, vec!["Apparel", "Shoes", "Orphan"]);
+        let children: Vec<_> = tree[1].children.iter().map(|n| n.category.name.as_str()).collect();
+        assert_eq!(children, vec!["Boots", "Sneakers"]);
+    }
+
+    #[tokio::test]
+    async fn test_move_rejects_cycles() {
+        let categories = vec![
+            category(1, None, "Shoes", 0),
+            category(2, Some(1), "Sneakers", 0),
+            category(3, Some(2), "Running", 0),
+        ];
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([categories.clone(), categories])
+            .into_connection();
+
+        let result = CategoryService::move_to(&db, 1, 1, Some(3)).await;
+        assert!(matches!(result, Err(CategoryError::Cycle)));
+        let result = CategoryService::move_to(&db, 1, 2, Some(2)).await;
+        assert!(matches!(result, Err(CategoryError::Cycle)));
+    }
+}
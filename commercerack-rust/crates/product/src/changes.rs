@@ -0,0 +1,191 @@
+//! Catalog change feed
+//!
+//! Every product and SKU change already records an event in the outbox,
+//! in the transaction that makes it. The feed reads those rows back in
+//! outbox order, so search indexes and POS systems can follow the catalog
+//! from a cursor instead of re-exporting it. The newest few seconds are
+//! held back: outbox IDs are taken when a row is written, not when its
+//! transaction commits, and a slower transaction could otherwise commit a
+//! lower ID behind a reader's cursor.
+
+use commercerack_core::Timestamp;
+use commercerack_db::pagination::{Cursor, Keyset};
+use commercerack_events::{outbox, DomainEvent};
+use sea_orm::*;
+use ::entity::prelude::*;
+use tracing::warn;
+
+use crate::ProductError;
+
+/// Seconds the newest events are held back
+pub const SETTLE_SECONDS: i64 = 5;
+
+/// Outbox events the feed reports
+const CATALOG_EVENTS: [&str; 7] = [
+    "product.created",
+    "product.updated",
+    "product.deleted",
+    "product.media_changed",
+    "sku.created",
+    "sku.updated",
+    "sku.deleted",
+];
+
+/// What happened to a product or SKU
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Created,
+    Updated,
+    Deleted,
+}
+
+impl ChangeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChangeKind::Created => "created",
+            ChangeKind::Updated => "updated",
+            ChangeKind::Deleted => "deleted",
+        }
+    }
+}
+
+/// The product or SKU a change is about, as it was right after the change
+#[derive(Debug, Clone, PartialEq)]
+pub enum Changed {
+    Product { id: i32, product: Option<Box<Product>> },
+    Sku { id: i32, sku: Option<Sku> },
+}
+
+/// One entry of the feed
+#[derive(Debug, Clone, PartialEq)]
+pub struct CatalogChange {
+    /// Position in the feed; later changes have higher numbers
+    pub seq: i32,
+    pub kind: ChangeKind,
+    pub changed: Changed,
+    pub at: Timestamp,
+}
+
+impl CatalogChange {
+    /// The feed entry of an outbox event, if it is a catalog event
+    fn from_event(seq: i32, at: Timestamp, event: DomainEvent) -> Option<Self> {
+        let (kind, changed) = match event {
+            DomainEvent::ProductCreated(product) => {
+                (ChangeKind::Created, Changed::Product { id: product.id, product: Some(Box::new(product)) })
+            }
+            DomainEvent::ProductUpdated(product) => {
+                (ChangeKind::Updated, Changed::Product { id: product.id, product: Some(Box::new(product)) })
+            }
+            DomainEvent::ProductMediaChanged { product_id, .. } => {
+                (ChangeKind::Updated, Changed::Product { id: product_id, product: None })
+            }
+            DomainEvent::ProductDeleted { id, .. } => (ChangeKind::Deleted, Changed::Product { id, product: None }),
+            DomainEvent::SkuCreated(sku) => (ChangeKind::Created, Changed::Sku { id: sku.id, sku: Some(sku) }),
+            DomainEvent::SkuUpdated(sku) => (ChangeKind::Updated, Changed::Sku { id: sku.id, sku: Some(sku) }),
+            DomainEvent::SkuDeleted { id, .. } => (ChangeKind::Deleted, Changed::Sku { id, sku: None }),
+            _ => return None,
+        };
+        Some(Self { seq, kind, changed, at })
+    }
+}
+
+fn keyset() -> Keyset<::entity::event_outbox::Column> {
+    Keyset::new("changes", vec![(::entity::event_outbox::Column::Id, sea_orm::Order::Asc)])
+}
+
+/// Catalog change feed reader
+pub struct ChangeFeed;
+
+impl ChangeFeed {
+    /// Up to `limit` of a merchant's catalog changes after `cursor`, or from
+    /// the oldest kept change without one. Also returns the cursor to ask
+    /// with next, which is `cursor` again when nothing new has settled, and
+    /// whether more changes are already waiting.
+    pub async fn since(
+        db: &DatabaseConnection,
+        mid: i32,
+        cursor: Option<&Cursor>,
+        limit: u64,
+    ) -> Result<(Vec<CatalogChange>, Option<Cursor>, bool), ProductError> {
+        use ::entity::event_outbox::Column;
+
+        let settled = Timestamp::now() - chrono::Duration::seconds(SETTLE_SECONDS);
+        let query = EventOutbox::find()
+            .filter(Column::Mid.eq(mid))
+            .filter(Column::Event.is_in(CATALOG_EVENTS))
+            .filter(Column::CreatedGmt.lte(settled));
+        let keyset = keyset();
+        let mut rows = keyset.page(query, cursor, limit)?.all(db).await?;
+
+        let more = keyset.next(&mut rows, limit, |row| vec![row.id.into()]).is_some();
+        let next = rows.last().map(|row| keyset.cursor(vec![row.id.into()])).or_else(|| cursor.cloned());
+        let changes = rows
+            .iter()
+            .filter_map(|row| match outbox::decode(row) {
+                Ok(event) => CatalogChange::from_event(row.id, row.created_gmt, event),
+                Err(e) => {
+                    warn!("Skipping undecodable outbox event {} in the change feed: {}", row.id, e);
+                    None
+                }
+            })
+            .collect();
+        Ok((changes, next, more))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use commercerack_core::MerchantId;
+
+    fn row(id: i32, event: &DomainEvent) -> OutboxEvent {
+        OutboxEvent {
+            id,
+            mid: 1,
+            event: event.name().to_string(),
+            payload: serde_json::to_string(event).unwrap(),
+            created_gmt: Timestamp::from_unix(100),
+            delivered_gmt: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_feed_continues_after_the_cursor() {
+        let deleted = DomainEvent::ProductDeleted { mid: MerchantId::new(1), id: 4 };
+        let media = DomainEvent::ProductMediaChanged { mid: MerchantId::new(1), product_id: 5 };
+        let sku_deleted = DomainEvent::SkuDeleted { mid: MerchantId::new(1), id: 9 };
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![row(11, &deleted), row(12, &media), row(14, &sku_deleted)]])
+            .into_connection();
+
+        let cursor = keyset().cursor(vec![10.into()]);
+        let (changes, next, more) = ChangeFeed::since(&db, 1, Some(&cursor), 2).await.unwrap();
+
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].kind, ChangeKind::Deleted);
+        assert_eq!(changes[0].changed, Changed::Product { id: 4, product: None });
+        assert_eq!(changes[1].kind, ChangeKind::Updated);
+        assert_eq!(changes[1].seq, 12);
+        assert_eq!(next, Some(keyset().cursor(vec![12.into()])));
+        assert!(more);
+
+        let log = db.into_transaction_log();
+        let sql = log[0].statements()[0].to_string();
+        assert!(sql.contains(r#""event_outbox"."event" IN ('product.created', "#), "{}", sql);
+        assert!(sql.contains(r#""event_outbox"."id" > 10"#), "{}", sql);
+        assert!(sql.ends_with(r#"ORDER BY "event_outbox"."id" ASC LIMIT 3"#), "{}", sql);
+    }
+
+    #[tokio::test]
+    async fn test_quiet_feed_keeps_the_cursor() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([Vec::<OutboxEvent>::new()])
+            .into_connection();
+
+        let cursor = keyset().cursor(vec![10.into()]);
+        let (changes, next, more) = ChangeFeed::since(&db, 1, Some(&cursor), 50).await.unwrap();
+        assert!(changes.is_empty());
+        assert_eq!(next, Some(cursor));
+        assert!(!more);
+    }
+}
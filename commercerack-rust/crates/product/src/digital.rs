@@ -0,0 +1,257 @@
+//! Digital products and their license keys
+//!
+//! A digital product is delivered by the order itself instead of a
+//! carrier: once paid, each purchase gets an expiring download link to the
+//! product's file and, if its SKU has a license key pool, a key from it.
+//! The pool is filled by the merchant ahead of sales.
+
+use commercerack_core::Timestamp;
+use sea_orm::*;
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+use ::entity::prelude::*;
+use commercerack_events::{outbox, DomainEvent};
+use tracing::instrument;
+
+use crate::ProductService;
+
+#[derive(Error, Debug)]
+pub enum DigitalError {
+    #[error("Product not found")]
+    ProductNotFound,
+
+    #[error("Unknown SKU {0}")]
+    UnknownSku(String),
+
+    #[error("Invalid delivery settings: {0}")]
+    InvalidDelivery(&'static str),
+
+    #[error("Database error: {0}")]
+    Db(#[from] DbErr),
+}
+
+impl From<crate::ProductError> for DigitalError {
+    fn from(e: crate::ProductError) -> Self {
+        match e {
+            crate::ProductError::NotFound => DigitalError::ProductNotFound,
+            crate::ProductError::Db(e) => DigitalError::Db(e),
+            crate::ProductError::Cursor(e) => DigitalError::Db(DbErr::Custom(e.to_string())),
+        }
+    }
+}
+
+/// How a product reaches the buyer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ProductType {
+    /// Shipped by a carrier
+    #[default]
+    Physical,
+    /// Downloaded and/or unlocked with a license key once paid for
+    Digital,
+}
+
+impl ProductType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProductType::Physical => "physical",
+            ProductType::Digital => "digital",
+        }
+    }
+}
+
+impl fmt::Display for ProductType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ProductType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "physical" => Ok(ProductType::Physical),
+            "digital" => Ok(ProductType::Digital),
+            other => Err(format!("unknown product type {}", other)),
+        }
+    }
+}
+
+/// How a product is delivered
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Delivery {
+    pub product_type: ProductType,
+    /// Where the file of a digital product is kept; buyers are redirected
+    /// here by their download link and never see it otherwise
+    pub download_url: Option<String>,
+    /// Downloads allowed per purchase; the configured default when `None`
+    pub download_limit: Option<i32>,
+}
+
+impl Delivery {
+    /// Why these settings can't be used, if they can't
+    pub fn validate(&self) -> Result<(), DigitalError> {
+        if self.product_type == ProductType::Physical
+            && (self.download_url.is_some() || self.download_limit.is_some())
+        {
+            return Err(DigitalError::InvalidDelivery("physical products have no download"));
+        }
+        if self.download_limit.is_some() && self.download_url.is_none() {
+            return Err(DigitalError::InvalidDelivery("a download limit needs a download URL"));
+        }
+        if self.download_limit.is_some_and(|limit| limit <= 0) {
+            return Err(DigitalError::InvalidDelivery("the download limit must be positive"));
+        }
+        Ok(())
+    }
+}
+
+impl ProductService {
+    /// Change how a product is delivered
+    #[instrument(skip_all, fields(mid = mid, id = id))]
+    pub async fn set_delivery(
+        db: &DatabaseConnection,
+        mid: i32,
+        id: i32,
+        delivery: Delivery,
+    ) -> Result<Product, DigitalError> {
+        delivery.validate()?;
+        let product = Self::find_by_id(db, mid, id).await?
+            .ok_or(DigitalError::ProductNotFound)?;
+
+        let mut active: ::entity::products::ActiveModel = product.into();
+        active.product_type = Set(delivery.product_type.as_str().to_string());
+        active.download_url = Set(delivery.download_url);
+        active.download_limit = Set(delivery.download_limit);
+        active.ts = Set(Timestamp::now());
+
+        let txn = db.begin().await?;
+        let result = active.update(&txn).await?;
+        outbox::record(&txn, &DomainEvent::ProductUpdated(result.clone())).await?;
+        txn.commit().await?;
+        Ok(result)
+    }
+}
+
+/// A SKU's license key pool
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyPool {
+    /// Keys not yet assigned to an order
+    pub available: u64,
+    pub assigned: u64,
+}
+
+/// License key pool service
+pub struct LicenseKeyService;
+
+impl LicenseKeyService {
+    /// Add keys to a SKU's pool, skipping any already in it. Returns the
+    /// keys added.
+    #[instrument(skip_all, fields(mid = mid, sku = sku, keys = keys.len()))]
+    pub async fn add(
+        db: &DatabaseConnection,
+        mid: i32,
+        sku: &str,
+        keys: &[String],
+    ) -> Result<Vec<LicenseKey>, DigitalError> {
+        let exists = Skus::find()
+            .filter(::entity::skus::Column::Mid.eq(mid))
+            .filter(::entity::skus::Column::Sku.eq(sku))
+            .one(db)
+            .await?;
+        if exists.is_none() {
+            return Err(DigitalError::UnknownSku(sku.to_string()));
+        }
+
+        let txn = db.begin().await?;
+        let existing: HashSet<String> = LicenseKeys::find()
+            .filter(::entity::license_keys::Column::Mid.eq(mid))
+            .filter(::entity::license_keys::Column::Sku.eq(sku))
+            .filter(::entity::license_keys::Column::LicenseKey.is_in(keys.iter().map(|key| key.trim())))
+            .all(&txn)
+            .await?
+            .into_iter()
+            .map(|key| key.license_key)
+            .collect();
+
+        let now = Timestamp::now();
+        let mut seen = HashSet::new();
+        let mut added = Vec::new();
+        for key in keys.iter().map(|key| key.trim()) {
+            if existing.contains(key) || !seen.insert(key) {
+                continue;
+            }
+            let record = ::entity::license_keys::ActiveModel {
+                mid: Set(mid),
+                sku: Set(sku.to_string()),
+                license_key: Set(key.to_string()),
+                order_id: Set(None),
+                order_item_id: Set(None),
+                assigned_gmt: Set(None),
+                created_gmt: Set(now),
+                ..Default::default()
+            }
+            .insert(&txn)
+            .await?;
+            added.push(record);
+        }
+
+        txn.commit().await?;
+        Ok(added)
+    }
+
+    /// How many of a SKU's keys are still available
+    pub async fn pool(
+        db: &DatabaseConnection,
+        mid: i32,
+        sku: &str,
+    ) -> Result<KeyPool, DigitalError> {
+        let keys = || {
+            LicenseKeys::find()
+                .filter(::entity::license_keys::Column::Mid.eq(mid))
+                .filter(::entity::license_keys::Column::Sku.eq(sku))
+        };
+        let available = keys()
+            .filter(::entity::license_keys::Column::OrderId.is_null())
+            .count(db)
+            .await?;
+        let assigned = keys()
+            .filter(::entity::license_keys::Column::OrderId.is_not_null())
+            .count(db)
+            .await?;
+
+        Ok(KeyPool { available, assigned })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delivery_validation() {
+        assert!(Delivery::default().validate().is_ok());
+        let digital = Delivery {
+            product_type: ProductType::Digital,
+            download_url: Some("https://files.example/ebook.pdf".to_string()),
+            download_limit: Some(3),
+        };
+        assert!(digital.validate().is_ok());
+        // License-key-only software has no file
+        assert!(Delivery { product_type: ProductType::Digital, ..Default::default() }.validate().is_ok());
+
+        let physical_with_file = Delivery { product_type: ProductType::Physical, ..digital.clone() };
+        assert!(matches!(physical_with_file.validate(), Err(DigitalError::InvalidDelivery(_))));
+        let zero_limit = Delivery { download_limit: Some(0), ..digital };
+        assert!(matches!(zero_limit.validate(), Err(DigitalError::InvalidDelivery(_))));
+    }
+
+    #[test]
+    fn test_product_type_round_trip() {
+        for product_type in [ProductType::Physical, ProductType::Digital] {
+            assert_eq!(product_type.as_str().parse::<ProductType>(), Ok(product_type));
+        }
+    }
+}
@@ -0,0 +1,270 @@
+//! Catalog export
+//!
+//! Products are read in pages ordered by ID, so an export never holds the
+//! whole catalog in memory and rows written concurrently with an export are
+//! either included once or not at all. Each page is encoded independently
+//! as CSV (one row per SKU) or JSON Lines (one line per product).
+
+use sea_orm::*;
+use ::entity::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::ProductError;
+
+/// Products read per query during an export
+pub const EXPORT_PAGE_SIZE: u64 = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Jsonl,
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Jsonl => "application/x-ndjson",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Jsonl => "jsonl",
+        }
+    }
+
+    /// Bytes written before the first page, e.g. the CSV header row
+    pub fn header(&self) -> Vec<u8> {
+        match self {
+            ExportFormat::Csv => csv_line(CSV_COLUMNS.iter().map(|c| String::from(*c))),
+            ExportFormat::Jsonl => Vec::new(),
+        }
+    }
+
+    /// Encode one page of products with their SKUs
+    pub fn encode(&self, page: &[ExportProduct]) -> Vec<u8> {
+        let mut out = Vec::new();
+        for item in page {
+            match self {
+                ExportFormat::Csv => encode_csv(item, &mut out),
+                ExportFormat::Jsonl => {
+                    // Serializing plain data into a Vec cannot fail
+                    serde_json::to_writer(&mut out, item).expect("serializable export row");
+                    out.push(b'\n');
+                }
+            }
+        }
+        out
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(ExportFormat::Csv),
+            "jsonl" => Ok(ExportFormat::Jsonl),
+            other => Err(format!("unknown export format {}", other)),
+        }
+    }
+}
+
+/// A product with its SKUs, as exported
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportProduct {
+    #[serde(flatten)]
+    pub product: Product,
+    pub skus: Vec<Sku>,
+}
+
+const CSV_COLUMNS: [&str; 16] = [
+    "id",
+    "product",
+    "product_name",
+    "category",
+    "description",
+    "base_price",
+    "base_cost",
+    "supplier",
+    "supplier_id",
+    "upc",
+    "sku",
+    "sku_title",
+    "sku_price",
+    "sku_cost",
+    "sku_upc",
+    "sku_inv_available",
+];
+
+/// One row per SKU; products without SKUs get a single row with empty SKU columns
+fn encode_csv(item: &ExportProduct, out: &mut Vec<u8>) {
+    let p = &item.product;
+    let product = [
+        p.id.to_string(),
+        p.product.clone(),
+        p.product_name.clone(),
+        p.category.clone(),
+        p.description.clone(),
+        p.base_price.to_string(),
+        p.base_cost.to_string(),
+        p.supplier.clone(),
+        p.supplier_id.clone(),
+        p.upc.clone(),
+    ];
+
+    if item.skus.is_empty() {
+        out.extend(csv_line(product.iter().cloned().chain(std::iter::repeat_n(String::new(), 6))));
+    }
+    for sku in &item.skus {
+        let sku = [
+            sku.sku.clone(),
+            sku.title.clone(),
+            sku.price.to_string(),
+            sku.cost.to_string(),
+            sku.upc.clone(),
+            sku.inv_available.to_string(),
+        ];
+        out.extend(csv_line(product.iter().cloned().chain(sku)));
+    }
+}
+
+/// RFC 4180 line: fields containing commas, quotes or line breaks are quoted
+fn csv_line(fields: impl Iterator<Item = String>) -> Vec<u8> {
+    let mut line = String::new();
+    for (i, field) in fields.enumerate() {
+        if i > 0 {
+            line.push(',');
+        }
+        if field.contains([',', '"', '\n', '\r']) {
+            line.push('"');
+            line.push_str(&field.replace('"', "\"\""));
+            line.push('"');
+        } else {
+            line.push_str(&field);
+        }
+    }
+    line.push_str("\r\n");
+    line.into_bytes()
+}
+
+/// The next page of a merchant's products after `after_id`, with their SKUs
+pub async fn export_page(
+    db: &DatabaseConnection,
+    mid: i32,
+    after_id: i32,
+    limit: u64,
+) -> Result<Vec<ExportProduct>, ProductError> {
+    let products = Products::find()
+        .filter(::entity::products::Column::Mid.eq(mid))
+        .filter(::entity::products::Column::Id.gt(after_id))
+        .order_by_asc(::entity::products::Column::Id)
+        .limit(limit)
+        .all(db)
+        .await?;
+    if products.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut skus: HashMap<i32, Vec<Sku>> = HashMap::new();
+    for sku in Skus::find()
+        .filter(::entity::skus::Column::Mid.eq(mid))
+        .filter(::entity::skus::Column::Pid.is_in(products.iter().map(|p| p.id)))
+        .order_by_asc(::entity::skus::Column::Sku)
+        .all(db)
+        .await?
+    {
+        skus.entry(sku.pid).or_default().push(sku);
+    }
+
+    Ok(products
+        .into_iter()
+        .map(|product| ExportProduct {
+            skus: skus.remove(&product.id).unwrap_or_default(),
+            product,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal::Decimal;
+    use commercerack_core::Timestamp;
+
+    fn item(skus: Vec<Sku>) -> ExportProduct {
+        ExportProduct {
+            product: Product {
+                id: 1,
+                mid: 1,
+                merchant: "shop".to_string(),
+                product: "PROD001".to_string(),
+                ts: Timestamp::EPOCH,
+                product_name: "Widget, \"Deluxe\"".to_string(),
+                category: "Widgets".to_string(),
+                description: "Line one\nLine two".to_string(),
+                base_price: Decimal::new(1999, 2),
+                base_cost: Decimal::new(999, 2),
+                supplier: String::new(),
+                supplier_id: String::new(),
+                upc: String::new(),
+                created_gmt: Timestamp::EPOCH,
+                lastsold_gmt: None,
+                product_type: "physical".to_string(),
+                download_url: None,
+                download_limit: None,
+                status: "active".to_string(),
+                available_from: None,
+                available_until: None,
+            },
+            skus,
+        }
+    }
+
+    fn sku(code: &str) -> Sku {
+        Sku {
+            id: 1,
+            pid: 1,
+            mid: 1,
+            sku: code.to_string(),
+            title: "Blue".to_string(),
+            price: Decimal::new(2199, 2),
+            cost: Decimal::new(1099, 2),
+            upc: String::new(),
+            inv_available: 4,
+            qty_onshelf: 4,
+            weight: Decimal::ZERO,
+            reorder_point: None,
+            reorder_quantity: 0,
+            low_stock_gmt: None,
+        }
+    }
+
+    #[test]
+    fn test_csv_quotes_and_sku_rows() {
+        let csv = String::from_utf8(ExportFormat::Csv.encode(&[item(vec![sku("W-1"), sku("W-2")]), item(vec![])])).unwrap();
+        let lines: Vec<_> = csv.split("\r\n").collect();
+
+        assert_eq!(
+            lines[0],
+            "1,PROD001,\"Widget, \"\"Deluxe\"\"\",Widgets,\"Line one\nLine two\",19.99,9.99,,,,W-1,Blue,21.99,10.99,,4"
+        );
+        assert!(lines[1].contains(",W-2,"));
+        assert!(lines[2].ends_with("9.99,,,,,,,,,"));
+        assert_eq!(ExportFormat::Csv.header().iter().filter(|b| **b == b',').count(), CSV_COLUMNS.len() - 1);
+    }
+
+    #[test]
+    fn test_jsonl_nests_skus() {
+        let jsonl = ExportFormat::Jsonl.encode(&[item(vec![sku("W-1")])]);
+        assert_eq!(jsonl.last(), Some(&b'\n'));
+
+        let value: serde_json::Value = serde_json::from_slice(&jsonl).unwrap();
+        assert_eq!(value["product"], "PROD001");
+        assert_eq!(value["skus"][0]["sku"], "W-1");
+    }
+}
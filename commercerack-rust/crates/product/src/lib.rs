@@ -1,18 +1,139 @@
 //! Product management module using SeaORM
 
-use anyhow::Result;
-use chrono::Utc;
+use commercerack_core::Timestamp;
+use sea_orm::sea_query::extension::postgres::PgExpr;
+use sea_orm::sea_query::{Expr, LikeExpr};
 use sea_orm::*;
+use std::str::FromStr;
 use ::entity::prelude::*;
 use rust_decimal::Decimal;
+use commercerack_db::pagination::{Cursor, CursorError, Keyset, KeyValue};
+use commercerack_events::{outbox, DomainEvent};
+use availability::{Availability, Visibility};
+use thiserror::Error;
+use tracing::instrument;
 
+pub mod availability;
+pub mod bulk_price;
+pub mod category;
+pub mod changes;
+pub mod digital;
+pub mod export;
+pub mod media;
+pub mod pricing;
+pub mod recommendations;
+pub mod search;
 pub mod sku;
 
+#[derive(Error, Debug)]
+pub enum ProductError {
+    #[error("Product not found")]
+    NotFound,
+
+    #[error(transparent)]
+    Cursor(#[from] CursorError),
+
+    #[error("Database error: {0}")]
+    Db(#[from] DbErr),
+}
+
+/// Result ordering for [`ProductService::search`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ProductSort {
+    #[default]
+    Name,
+    PriceAsc,
+    PriceDesc,
+    Newest,
+}
+
+impl FromStr for ProductSort {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "name" => Ok(ProductSort::Name),
+            "price_asc" => Ok(ProductSort::PriceAsc),
+            "price_desc" => Ok(ProductSort::PriceDesc),
+            "newest" => Ok(ProductSort::Newest),
+            other => Err(format!("unknown sort order {}", other)),
+        }
+    }
+}
+
+impl ProductSort {
+    fn keyset(self) -> Keyset<::entity::products::Column> {
+        use ::entity::products::Column;
+
+        match self {
+            ProductSort::Name => Keyset::new("name", vec![(Column::ProductName, sea_orm::Order::Asc), (Column::Id, sea_orm::Order::Asc)]),
+            ProductSort::PriceAsc => Keyset::new("price_asc", vec![(Column::BasePrice, sea_orm::Order::Asc), (Column::Id, sea_orm::Order::Asc)]),
+            ProductSort::PriceDesc => Keyset::new("price_desc", vec![(Column::BasePrice, sea_orm::Order::Desc), (Column::Id, sea_orm::Order::Asc)]),
+            ProductSort::Newest => Keyset::new("newest", vec![(Column::CreatedGmt, sea_orm::Order::Desc), (Column::Id, sea_orm::Order::Asc)]),
+        }
+    }
+
+    /// Values of `product` for the columns of [`ProductSort::keyset`]
+    fn key(self, product: &Product) -> Vec<KeyValue> {
+        match self {
+            ProductSort::Name => vec![product.product_name.as_str().into(), product.id.into()],
+            ProductSort::PriceAsc | ProductSort::PriceDesc => vec![product.base_price.into(), product.id.into()],
+            ProductSort::Newest => vec![product.created_gmt.into(), product.id.into()],
+        }
+    }
+}
+
+/// Product search filters; unset filters match everything
+#[derive(Debug, Clone, Default)]
+pub struct ProductSearch {
+    /// Case-insensitive substring of the name, UPC or supplier ID
+    pub q: Option<String>,
+    pub category: Option<String>,
+    pub min_price: Option<Decimal>,
+    pub max_price: Option<Decimal>,
+    pub sort: ProductSort,
+    pub visibility: Visibility,
+}
+
+impl ProductSearch {
+    fn condition(&self, mid: i32) -> Condition {
+        use ::entity::products::Column;
+
+        let mut condition = Condition::all()
+            .add(Column::Mid.eq(mid))
+            .add(self.visibility.condition(Timestamp::now()));
+        if let Some(q) = self.q.as_deref().map(str::trim).filter(|q| !q.is_empty()) {
+            // Match the text literally, not as a LIKE pattern
+            let escaped = q.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+            let pattern = || LikeExpr::new(format!("%{}%", escaped)).escape('\\');
+            condition = condition.add(
+                Condition::any()
+                    .add(Expr::col(Column::ProductName).ilike(pattern()))
+                    .add(Expr::col(Column::Upc).ilike(pattern()))
+                    .add(Expr::col(Column::SupplierId).ilike(pattern())),
+            );
+        }
+        if let Some(category) = self.category.as_deref().filter(|c| !c.is_empty()) {
+            condition = condition.add(Column::Category.eq(category));
+        }
+        if let Some(min) = self.min_price {
+            condition = condition.add(Column::BasePrice.gte(min));
+        }
+        if let Some(max) = self.max_price {
+            condition = condition.add(Column::BasePrice.lte(max));
+        }
+        condition
+    }
+}
+
 /// Product service for managing product operations
 pub struct ProductService;
 
 impl ProductService {
-    /// Create new product
+    /// Create new product; `delivery` and `availability` must have passed
+    /// [`digital::Delivery::validate`] and [`Availability::validate`]
+    #[allow(clippy::too_many_arguments)]
+    #[instrument(skip_all, fields(mid = mid, product_id = product_id))]
     pub async fn create(
         db: &DatabaseConnection,
         mid: i32,
@@ -22,8 +143,11 @@ impl ProductService {
         category: &str,
         base_price: Decimal,
         base_cost: Decimal,
-    ) -> Result<Product> {
-        let now = Utc::now().timestamp() as i32;
+        description: &str,
+        delivery: digital::Delivery,
+        availability: Availability,
+    ) -> Result<Product, ProductError> {
+        let now = Timestamp::now();
 
         let product = ::entity::products::ActiveModel {
             mid: Set(mid),
@@ -32,6 +156,7 @@ impl ProductService {
             ts: Set(now),
             product_name: Set(product_name.to_string()),
             category: Set(category.to_string()),
+            description: Set(description.to_string()),
             base_price: Set(base_price),
             base_cost: Set(base_cost),
             supplier: Set(String::new()),
@@ -39,10 +164,20 @@ impl ProductService {
             upc: Set(String::new()),
             created_gmt: Set(now),
             lastsold_gmt: Set(None),
+            product_type: Set(delivery.product_type.as_str().to_string()),
+            download_url: Set(delivery.download_url),
+            download_limit: Set(delivery.download_limit),
+            status: Set(availability.status.as_str().to_string()),
+            available_from: Set(availability.available_from),
+            available_until: Set(availability.available_until),
             ..Default::default()
         };
 
-        let result = product.insert(db).await?;
+        let txn = db.begin().await?;
+        let result = product.insert(&txn).await?;
+        search::reindex(&txn, result.id).await?;
+        outbox::record(&txn, &DomainEvent::ProductCreated(result.clone())).await?;
+        txn.commit().await?;
         Ok(result)
     }
 
@@ -51,7 +186,7 @@ impl ProductService {
         db: &DatabaseConnection,
         mid: i32,
         id: i32,
-    ) -> Result<Option<Product>> {
+    ) -> Result<Option<Product>, ProductError> {
         let product = Products::find()
             .filter(::entity::products::Column::Mid.eq(mid))
             .filter(::entity::products::Column::Id.eq(id))
@@ -61,12 +196,31 @@ impl ProductService {
         Ok(product)
     }
 
+    /// Find several products by ID, in no particular order. IDs that don't
+    /// exist are left out.
+    pub async fn find_by_ids(
+        db: &DatabaseConnection,
+        mid: i32,
+        ids: &[i32],
+    ) -> Result<Vec<Product>, ProductError> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let products = Products::find()
+            .filter(::entity::products::Column::Mid.eq(mid))
+            .filter(::entity::products::Column::Id.is_in(ids.iter().copied()))
+            .all(db)
+            .await?;
+
+        Ok(products)
+    }
+
     /// Find product by merchant product ID
     pub async fn find_by_product_id(
         db: &DatabaseConnection,
         mid: i32,
         product_id: &str,
-    ) -> Result<Option<Product>> {
+    ) -> Result<Option<Product>, ProductError> {
         let product = Products::find()
             .filter(::entity::products::Column::Mid.eq(mid))
             .filter(::entity::products::Column::Product.eq(product_id))
@@ -76,16 +230,66 @@ impl ProductService {
         Ok(product)
     }
 
-    /// List products with pagination
+    /// List a merchant's products by name, one page after `cursor`.
+    /// Also returns the cursor of the next page, if there is one.
     pub async fn list(
         db: &DatabaseConnection,
         mid: i32,
+        visibility: Visibility,
+        limit: u64,
+        cursor: Option<&Cursor>,
+    ) -> Result<(Vec<Product>, Option<Cursor>), ProductError> {
+        let query = Products::find()
+            .filter(::entity::products::Column::Mid.eq(mid))
+            .filter(visibility.condition(Timestamp::now()));
+        let keyset = ProductSort::Name.keyset();
+        let mut products = keyset
+            .page(query, cursor, limit)?
+            .all(db)
+            .await?;
+
+        let next = keyset.next(&mut products, limit, |p| ProductSort::Name.key(p));
+        Ok((products, next))
+    }
+
+    /// Search products one page after `cursor`, returning the total number
+    /// of matches and the cursor of the next page, if any
+    #[instrument(skip_all, fields(mid = mid))]
+    pub async fn search(
+        db: &DatabaseConnection,
+        mid: i32,
+        search: &ProductSearch,
+        limit: u64,
+        cursor: Option<&Cursor>,
+    ) -> Result<(Vec<Product>, u64, Option<Cursor>), ProductError> {
+        let query = Products::find().filter(search.condition(mid));
+        let total = query.clone().count(db).await?;
+
+        let keyset = search.sort.keyset();
+        let mut products = keyset.page(query, cursor, limit)?.all(db).await?;
+
+        let next = keyset.next(&mut products, limit, |p| search.sort.key(p));
+        Ok((products, total, next))
+    }
+
+    /// Ranked full-text search of the storefront over name, category and
+    /// description. Accepts web search syntax: quoted phrases, `or`, and
+    /// `-excluded` words.
+    pub async fn full_text_search(
+        db: &DatabaseConnection,
+        mid: i32,
+        query: &str,
         limit: u64,
         offset: u64,
-    ) -> Result<Vec<Product>> {
+    ) -> Result<Vec<Product>, ProductError> {
+        use ::entity::products::Column;
+
         let products = Products::find()
-            .filter(::entity::products::Column::Mid.eq(mid))
-            .order_by_asc(::entity::products::Column::ProductName)
+            .filter(Column::Mid.eq(mid))
+            .filter(search::matches(query))
+            .filter(Visibility::Storefront.condition(Timestamp::now()))
+            .order_by(search::rank(query), sea_orm::Order::Desc)
+            .order_by_asc(Column::Id)
             .limit(limit)
             .offset(offset)
             .all(db)
@@ -95,65 +299,81 @@ impl ProductService {
     }
 
     /// Update product
+    #[instrument(skip_all, fields(mid = product.mid, id = product.id))]
     pub async fn update(
         db: &DatabaseConnection,
         product: Product,
-    ) -> Result<Product> {
+    ) -> Result<Product, ProductError> {
         let mut active: ::entity::products::ActiveModel = product.into();
-        active.ts = Set(Utc::now().timestamp() as i32);
+        active.ts = Set(Timestamp::now());
 
-        let result = active.update(db).await?;
+        let txn = db.begin().await?;
+        let result = active.update(&txn).await?;
+        search::reindex(&txn, result.id).await?;
+        outbox::record(&txn, &DomainEvent::ProductUpdated(result.clone())).await?;
+        txn.commit().await?;
         Ok(result)
     }
 
     /// Delete product
+    #[instrument(skip_all, fields(mid = mid, id = id))]
     pub async fn delete(
         db: &DatabaseConnection,
         mid: i32,
         id: i32,
-    ) -> Result<()> {
-        Products::delete_many()
+    ) -> Result<(), ProductError> {
+        let txn = db.begin().await?;
+        let result = Products::delete_many()
             .filter(::entity::products::Column::Mid.eq(mid))
             .filter(::entity::products::Column::Id.eq(id))
-            .exec(db)
+            .exec(&txn)
             .await?;
 
+        if result.rows_affected > 0 {
+            outbox::record(&txn, &DomainEvent::ProductDeleted { mid: mid.into(), id }).await?;
+        }
+        txn.commit().await?;
         Ok(())
     }
 
     /// Update product price
+    #[instrument(skip_all, fields(mid = mid, id = id))]
     pub async fn update_price(
         db: &DatabaseConnection,
         mid: i32,
         id: i32,
         base_price: Decimal,
         base_cost: Option<Decimal>,
-    ) -> Result<Product> {
+    ) -> Result<Product, ProductError> {
         let product = Self::find_by_id(db, mid, id).await?
-            .ok_or_else(|| anyhow::anyhow!("Product not found"))?;
+            .ok_or(ProductError::NotFound)?;
 
         let mut active: ::entity::products::ActiveModel = product.into();
         active.base_price = Set(base_price);
         if let Some(cost) = base_cost {
             active.base_cost = Set(cost);
         }
-        active.ts = Set(Utc::now().timestamp() as i32);
+        active.ts = Set(Timestamp::now());
 
-        let result = active.update(db).await?;
+        let txn = db.begin().await?;
+        let result = active.update(&txn).await?;
+        outbox::record(&txn, &DomainEvent::ProductUpdated(result.clone())).await?;
+        txn.commit().await?;
         Ok(result)
     }
 
     /// Mark product as sold
+    #[instrument(skip_all, fields(mid = mid, id = id))]
     pub async fn mark_sold(
         db: &DatabaseConnection,
         mid: i32,
         id: i32,
-    ) -> Result<Product> {
+    ) -> Result<Product, ProductError> {
         let product = Self::find_by_id(db, mid, id).await?
-            .ok_or_else(|| anyhow::anyhow!("Product not found"))?;
+            .ok_or(ProductError::NotFound)?;
 
         let mut active: ::entity::products::ActiveModel = product.into();
-        active.lastsold_gmt = Set(Some(Utc::now().timestamp() as i32));
+        active.lastsold_gmt = Set(Some(Timestamp::now()));
 
         let result = active.update(db).await?;
         Ok(result)
@@ -164,6 +384,33 @@ impl ProductService {
 mod tests {
     use super::*;
 
-    // Tests will be added when we have a test database setup
-    // For now, compilation success validates the API design
+    #[test]
+    fn test_price_desc_breaks_ties_by_id() {
+        let cursor = ProductSort::PriceDesc.keyset().cursor(vec![Decimal::new(1999, 2).into(), 4.into()]);
+        let sql = ProductSort::PriceDesc
+            .keyset()
+            .page(Products::find(), Some(&cursor), 20)
+            .unwrap()
+            .build(DbBackend::Postgres)
+            .to_string();
+
+        assert!(sql.contains(r#""products"."base_price" < 19.99 OR ("products"."base_price" = 19.99 AND "products"."id" > 4)"#), "{}", sql);
+        assert!(sql.ends_with(r#"ORDER BY "products"."base_price" DESC, "products"."id" ASC LIMIT 21"#), "{}", sql);
+    }
+
+    #[tokio::test]
+    async fn test_full_text_search_ranks_matches() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([Vec::<Product>::new()])
+            .into_connection();
+
+        let products = ProductService::full_text_search(&db, 1, "red shoes", 20, 0).await.unwrap();
+        assert!(products.is_empty());
+
+        let log = db.into_transaction_log();
+        let sql = log[0].statements()[0].sql.clone();
+        assert!(sql.contains("search_vector @@ websearch_to_tsquery('english', $2)"), "{}", sql);
+        assert!(sql.contains(r#""products"."status" = $3"#), "{}", sql);
+        assert!(sql.contains("ORDER BY ts_rank(search_vector, websearch_to_tsquery('english', $6)) DESC"), "{}", sql);
+    }
 }
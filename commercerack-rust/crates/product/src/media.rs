@@ -0,0 +1,316 @@
+//! Product images and videos using SeaORM
+//!
+//! Media is stored as metadata only; the files themselves live on a CDN or
+//! object store and are referenced by URL. Each product has at most one
+//! primary item, which storefronts use as the listing thumbnail.
+
+use commercerack_core::Timestamp;
+use commercerack_events::{outbox, DomainEvent};
+use sea_orm::sea_query::Expr;
+use sea_orm::*;
+use ::entity::prelude::*;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+use crate::sku::SKUService;
+use crate::ProductService;
+
+#[derive(Error, Debug)]
+pub enum MediaError {
+    #[error("Media not found")]
+    NotFound,
+
+    #[error("Product not found")]
+    ProductNotFound,
+
+    #[error("SKU not found for this product")]
+    SkuNotFound,
+
+    #[error("Reorder must list each of the product's media exactly once")]
+    InvalidOrder,
+
+    #[error("Database error: {0}")]
+    Db(#[from] DbErr),
+}
+
+impl From<crate::ProductError> for MediaError {
+    fn from(e: crate::ProductError) -> Self {
+        match e {
+            crate::ProductError::NotFound => MediaError::ProductNotFound,
+            crate::ProductError::Db(e) => MediaError::Db(e),
+            crate::ProductError::Cursor(e) => MediaError::Db(DbErr::Custom(e.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaKind {
+    Image,
+    Video,
+}
+
+impl MediaKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MediaKind::Image => "image",
+            MediaKind::Video => "video",
+        }
+    }
+}
+
+impl fmt::Display for MediaKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for MediaKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "image" => Ok(MediaKind::Image),
+            "video" => Ok(MediaKind::Video),
+            other => Err(format!("unknown media kind {}", other)),
+        }
+    }
+}
+
+/// Media to attach to a product
+#[derive(Debug, Clone)]
+pub struct NewMedia {
+    pub sku_id: Option<i32>,
+    pub kind: MediaKind,
+    pub url: String,
+    pub alt_text: String,
+    pub is_primary: bool,
+}
+
+/// Media service for managing product images and videos
+pub struct MediaService;
+
+impl MediaService {
+    /// Attach media to a product, after its existing media. The first media
+    /// of a product always becomes primary.
+    pub async fn add(
+        db: &DatabaseConnection,
+        mid: i32,
+        product_id: i32,
+        media: NewMedia,
+    ) -> Result<ProductMediaItem, MediaError> {
+        ProductService::find_by_id(db, mid, product_id)
+            .await?
+            .ok_or(MediaError::ProductNotFound)?;
+        if let Some(sku_id) = media.sku_id {
+            SKUService::find_by_id(db, mid, sku_id)
+                .await?
+                .filter(|sku| sku.pid == product_id)
+                .ok_or(MediaError::SkuNotFound)?;
+        }
+
+        let txn = db.begin().await?;
+
+        let existing = Self::for_product(&txn, mid, product_id).await?;
+        let is_primary = media.is_primary || existing.is_empty();
+        if is_primary {
+            Self::clear_primary(&txn, mid, product_id).await?;
+        }
+
+        let item = ::entity::product_media::ActiveModel {
+            mid: Set(mid),
+            product_id: Set(product_id),
+            sku_id: Set(media.sku_id),
+            kind: Set(media.kind.to_string()),
+            url: Set(media.url),
+            alt_text: Set(media.alt_text),
+            position: Set(existing.iter().map(|m| m.position + 1).max().unwrap_or(0)),
+            is_primary: Set(is_primary),
+            created_gmt: Set(Timestamp::now()),
+            ..Default::default()
+        };
+        let result = item.insert(&txn).await?;
+        Self::changed(&txn, mid, product_id).await?;
+
+        txn.commit().await?;
+        Ok(result)
+    }
+
+    /// Media of one product in display order
+    pub async fn list(
+        db: &DatabaseConnection,
+        mid: i32,
+        product_id: i32,
+    ) -> Result<Vec<ProductMediaItem>, MediaError> {
+        Self::for_product(db, mid, product_id).await
+    }
+
+    /// Media of several products in one query, keyed by product ID
+    pub async fn list_for_products(
+        db: &DatabaseConnection,
+        mid: i32,
+        product_ids: &[i32],
+    ) -> Result<HashMap<i32, Vec<ProductMediaItem>>, MediaError> {
+        let mut by_product: HashMap<i32, Vec<ProductMediaItem>> = HashMap::new();
+        if product_ids.is_empty() {
+            return Ok(by_product);
+        }
+
+        let media = ProductMedia::find()
+            .filter(::entity::product_media::Column::Mid.eq(mid))
+            .filter(::entity::product_media::Column::ProductId.is_in(product_ids.iter().copied()))
+            .order_by_asc(::entity::product_media::Column::Position)
+            .order_by_asc(::entity::product_media::Column::Id)
+            .all(db)
+            .await?;
+
+        for item in media {
+            by_product.entry(item.product_id).or_default().push(item);
+        }
+        Ok(by_product)
+    }
+
+    /// Set the display order of a product's media. `ids` must list every
+    /// media item of the product once.
+    pub async fn reorder(
+        db: &DatabaseConnection,
+        mid: i32,
+        product_id: i32,
+        ids: &[i32],
+    ) -> Result<Vec<ProductMediaItem>, MediaError> {
+        let txn = db.begin().await?;
+
+        let existing = Self::for_product(&txn, mid, product_id).await?;
+        let mut current: Vec<i32> = existing.iter().map(|m| m.id).collect();
+        let mut requested = ids.to_vec();
+        current.sort_unstable();
+        requested.sort_unstable();
+        if current != requested {
+            return Err(MediaError::InvalidOrder);
+        }
+
+        for (position, id) in ids.iter().enumerate() {
+            ProductMedia::update_many()
+                .col_expr(::entity::product_media::Column::Position, Expr::value(position as i32))
+                .filter(::entity::product_media::Column::Mid.eq(mid))
+                .filter(::entity::product_media::Column::Id.eq(*id))
+                .exec(&txn)
+                .await?;
+        }
+
+        let result = Self::for_product(&txn, mid, product_id).await?;
+        Self::changed(&txn, mid, product_id).await?;
+        txn.commit().await?;
+        Ok(result)
+    }
+
+    /// Make one media item the product's primary
+    pub async fn set_primary(
+        db: &DatabaseConnection,
+        mid: i32,
+        product_id: i32,
+        id: i32,
+    ) -> Result<ProductMediaItem, MediaError> {
+        let txn = db.begin().await?;
+
+        let item = Self::find(&txn, mid, product_id, id).await?.ok_or(MediaError::NotFound)?;
+        Self::clear_primary(&txn, mid, product_id).await?;
+
+        let mut active: ::entity::product_media::ActiveModel = item.into();
+        active.is_primary = Set(true);
+        let result = active.update(&txn).await?;
+        Self::changed(&txn, mid, product_id).await?;
+
+        txn.commit().await?;
+        Ok(result)
+    }
+
+    /// Remove a media item. If it was primary, the next item takes over.
+    /// Returns whether it existed.
+    pub async fn delete(
+        db: &DatabaseConnection,
+        mid: i32,
+        product_id: i32,
+        id: i32,
+    ) -> Result<bool, MediaError> {
+        let txn = db.begin().await?;
+
+        let Some(item) = Self::find(&txn, mid, product_id, id).await? else {
+            return Ok(false);
+        };
+        let was_primary = item.is_primary;
+        item.delete(&txn).await?;
+
+        if was_primary {
+            if let Some(next) = Self::for_product(&txn, mid, product_id).await?.into_iter().next() {
+                let mut active: ::entity::product_media::ActiveModel = next.into();
+                active.is_primary = Set(true);
+                active.update(&txn).await?;
+            }
+        }
+        Self::changed(&txn, mid, product_id).await?;
+
+        txn.commit().await?;
+        Ok(true)
+    }
+
+    /// Mark the product as changed: its media are part of it
+    async fn changed<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        product_id: i32,
+    ) -> Result<(), MediaError> {
+        Products::update_many()
+            .col_expr(::entity::products::Column::Ts, Expr::value(Timestamp::now()))
+            .filter(::entity::products::Column::Mid.eq(mid))
+            .filter(::entity::products::Column::Id.eq(product_id))
+            .exec(db)
+            .await?;
+        outbox::record(db, &DomainEvent::ProductMediaChanged { mid: mid.into(), product_id }).await?;
+        Ok(())
+    }
+
+    async fn find<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        product_id: i32,
+        id: i32,
+    ) -> Result<Option<ProductMediaItem>, MediaError> {
+        let item = ProductMedia::find()
+            .filter(::entity::product_media::Column::Mid.eq(mid))
+            .filter(::entity::product_media::Column::ProductId.eq(product_id))
+            .filter(::entity::product_media::Column::Id.eq(id))
+            .one(db)
+            .await?;
+
+        Ok(item)
+    }
+
+    async fn for_product<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        product_id: i32,
+    ) -> Result<Vec<ProductMediaItem>, MediaError> {
+        let media = ProductMedia::find()
+            .filter(::entity::product_media::Column::Mid.eq(mid))
+            .filter(::entity::product_media::Column::ProductId.eq(product_id))
+            .order_by_asc(::entity::product_media::Column::Position)
+            .order_by_asc(::entity::product_media::Column::Id)
+            .all(db)
+            .await?;
+
+        Ok(media)
+    }
+
+    async fn clear_primary<C: ConnectionTrait>(db: &C, mid: i32, product_id: i32) -> Result<(), DbErr> {
+        ProductMedia::update_many()
+            .col_expr(::entity::product_media::Column::IsPrimary, Expr::value(false))
+            .filter(::entity::product_media::Column::Mid.eq(mid))
+            .filter(::entity::product_media::Column::ProductId.eq(product_id))
+            .exec(db)
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,253 @@
+//! Quantity-break and customer-group pricing using SeaORM
+//!
+//! A price tier undercuts a SKU's list price once the quantity bought
+//! reaches its `min_qty`. Tiers with an empty price group apply to every
+//! shopper; the others only to customers assigned that group. A shopper
+//! pays the lowest of the list price and every tier that applies to them.
+
+use commercerack_core::Timestamp;
+use rust_decimal::Decimal;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::*;
+use ::entity::prelude::*;
+use ::entity::price_tiers;
+use crate::availability::Visibility;
+use thiserror::Error;
+use tracing::instrument;
+
+#[derive(Error, Debug)]
+pub enum PricingError {
+    #[error("Price tier not found")]
+    NotFound,
+
+    #[error("SKU {0} not found")]
+    UnknownSku(String),
+
+    #[error("Database error: {0}")]
+    Db(#[from] DbErr),
+}
+
+/// A tier to create, or to replace the price of
+#[derive(Debug, Clone)]
+pub struct NewPriceTier {
+    pub sku: String,
+    /// Empty for a tier every shopper gets
+    pub price_group: String,
+    pub min_qty: i32,
+    pub price: Decimal,
+}
+
+/// The unit price a shopper pays for a SKU at some quantity
+#[derive(Debug, Clone)]
+pub struct ResolvedPrice {
+    pub sku: Sku,
+    pub unit_price: Decimal,
+    /// The tier that set the price; `None` when the list price is lowest
+    pub tier_id: Option<i32>,
+}
+
+/// Price tier service
+pub struct PricingService;
+
+impl PricingService {
+    /// Unit price of `sku` for a shopper in `group` buying `quantity`.
+    /// SKUs of products the storefront doesn't show, such as drafts or
+    /// products outside their sale window, are not for sale and so unknown.
+    pub async fn resolve_price<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        sku: &str,
+        group: Option<&str>,
+        quantity: i32,
+    ) -> Result<ResolvedPrice, PricingError> {
+        let on_sale = Products::find()
+            .select_only()
+            .column(::entity::products::Column::Id)
+            .filter(::entity::products::Column::Mid.eq(mid))
+            .filter(Visibility::Storefront.condition(Timestamp::now()))
+            .into_query();
+
+        let found = Skus::find()
+            .filter(::entity::skus::Column::Mid.eq(mid))
+            .filter(::entity::skus::Column::Sku.eq(sku))
+            .filter(::entity::skus::Column::Pid.in_subquery(on_sale))
+            .one(db)
+            .await?
+            .ok_or_else(|| PricingError::UnknownSku(sku.to_string()))?;
+
+        let groups: Vec<&str> = match group {
+            Some(group) if !group.is_empty() => vec!["", group],
+            _ => vec![""],
+        };
+        let tiers = PriceTiers::find()
+            .filter(price_tiers::Column::Mid.eq(mid))
+            .filter(price_tiers::Column::Sku.eq(sku))
+            .filter(price_tiers::Column::PriceGroup.is_in(groups))
+            .filter(price_tiers::Column::MinQty.lte(quantity))
+            .all(db)
+            .await?;
+
+        let (unit_price, tier_id) = Self::best_price(found.price, &tiers, group, quantity);
+        Ok(ResolvedPrice { sku: found, unit_price, tier_id })
+    }
+
+    /// Lowest of `list_price` and the tiers applying to `group` at `quantity`
+    pub fn best_price(
+        list_price: Decimal,
+        tiers: &[PriceTier],
+        group: Option<&str>,
+        quantity: i32,
+    ) -> (Decimal, Option<i32>) {
+        tiers
+            .iter()
+            .filter(|tier| tier.min_qty <= quantity)
+            .filter(|tier| tier.price_group.is_empty() || Some(tier.price_group.as_str()) == group)
+            .fold((list_price, None), |best, tier| {
+                if tier.price < best.0 {
+                    (tier.price, Some(tier.id))
+                } else {
+                    best
+                }
+            })
+    }
+
+    /// A merchant's tiers, optionally for one SKU only
+    pub async fn list_tiers<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        sku: Option<&str>,
+    ) -> Result<Vec<PriceTier>, PricingError> {
+        let mut query = PriceTiers::find().filter(price_tiers::Column::Mid.eq(mid));
+        if let Some(sku) = sku {
+            query = query.filter(price_tiers::Column::Sku.eq(sku));
+        }
+
+        let tiers = query
+            .order_by_asc(price_tiers::Column::Sku)
+            .order_by_asc(price_tiers::Column::PriceGroup)
+            .order_by_asc(price_tiers::Column::MinQty)
+            .all(db)
+            .await?;
+
+        Ok(tiers)
+    }
+
+    /// Create a tier, or change the price of the one with the same SKU,
+    /// group and minimum quantity
+    #[instrument(skip_all, fields(mid = mid, sku = %tier.sku))]
+    pub async fn set_tier<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        tier: NewPriceTier,
+    ) -> Result<PriceTier, PricingError> {
+        let known = Skus::find()
+            .filter(::entity::skus::Column::Mid.eq(mid))
+            .filter(::entity::skus::Column::Sku.eq(&tier.sku))
+            .count(db)
+            .await?;
+        if known == 0 {
+            return Err(PricingError::UnknownSku(tier.sku));
+        }
+
+        let now = Timestamp::now();
+        let saved = PriceTiers::insert(price_tiers::ActiveModel {
+            mid: Set(mid),
+            sku: Set(tier.sku),
+            price_group: Set(tier.price_group),
+            min_qty: Set(tier.min_qty),
+            price: Set(tier.price),
+            created_gmt: Set(now),
+            modified_gmt: Set(now),
+            ..Default::default()
+        })
+        .on_conflict(
+            OnConflict::columns([
+                price_tiers::Column::Mid,
+                price_tiers::Column::Sku,
+                price_tiers::Column::PriceGroup,
+                price_tiers::Column::MinQty,
+            ])
+            .update_columns([price_tiers::Column::Price, price_tiers::Column::ModifiedGmt])
+            .to_owned(),
+        )
+        .exec_with_returning(db)
+        .await?;
+
+        Ok(saved)
+    }
+
+    /// Delete a tier
+    #[instrument(skip_all, fields(mid = mid, id = id))]
+    pub async fn delete_tier<C: ConnectionTrait>(db: &C, mid: i32, id: i32) -> Result<(), PricingError> {
+        let result = PriceTiers::delete_many()
+            .filter(price_tiers::Column::Mid.eq(mid))
+            .filter(price_tiers::Column::Id.eq(id))
+            .exec(db)
+            .await?;
+        if result.rows_affected == 0 {
+            return Err(PricingError::NotFound);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tier(id: i32, price_group: &str, min_qty: i32, cents: i64) -> PriceTier {
+        PriceTier {
+            id,
+            mid: 1,
+            sku: "SKU-1".to_string(),
+            price_group: price_group.to_string(),
+            min_qty,
+            price: Decimal::new(cents, 2),
+            created_gmt: Timestamp::EPOCH,
+            modified_gmt: Timestamp::EPOCH,
+        }
+    }
+
+    #[test]
+    fn test_best_price() {
+        let list = Decimal::new(1000, 2);
+        let tiers = [tier(1, "", 10, 900), tier(2, "", 50, 800), tier(3, "wholesale", 1, 850)];
+
+        assert_eq!(PricingService::best_price(list, &tiers, None, 1), (list, None));
+        assert_eq!(PricingService::best_price(list, &tiers, None, 10), (Decimal::new(900, 2), Some(1)));
+        assert_eq!(PricingService::best_price(list, &tiers, None, 60), (Decimal::new(800, 2), Some(2)));
+        assert_eq!(PricingService::best_price(list, &tiers, Some("wholesale"), 1), (Decimal::new(850, 2), Some(3)));
+        assert_eq!(PricingService::best_price(list, &tiers, Some("retail"), 1), (list, None));
+        // A group price never beats a better quantity break everyone gets
+        assert_eq!(PricingService::best_price(list, &tiers, Some("wholesale"), 50), (Decimal::new(800, 2), Some(2)));
+    }
+
+    #[test]
+    fn test_tier_above_list_price_is_ignored() {
+        let list = Decimal::new(500, 2);
+        assert_eq!(PricingService::best_price(list, &[tier(1, "", 1, 600)], None, 5), (list, None));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_unknown_sku() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([Vec::<Sku>::new()])
+            .into_connection();
+
+        let err = PricingService::resolve_price(&db, 1, "NOPE", None, 1).await.unwrap_err();
+        assert!(matches!(err, PricingError::UnknownSku(sku) if sku == "NOPE"));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_only_prices_skus_on_sale() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([Vec::<Sku>::new()])
+            .into_connection();
+
+        PricingService::resolve_price(&db, 1, "DRAFT", None, 1).await.unwrap_err();
+        let sql = db.into_transaction_log()[0].statements()[0].to_string();
+        assert!(sql.contains(r#""sku_lookup"."pid" IN (SELECT "products"."id" FROM "products""#), "{}", sql);
+        assert!(sql.contains(r#""products"."status" = 'active'"#), "{}", sql);
+    }
+}
@@ -0,0 +1,321 @@
+//! "You may also like" recommendations
+//!
+//! Recommendations are worked out per merchant from the paid orders of the
+//! last year and stored, so reading them is one indexed query. A product
+//! scores for a customer in two ways:
+//!
+//! - co-purchase: one point for every order that has it together with a
+//!   product the customer bought
+//! - category affinity: the share of the customer's purchases filed under
+//!   each of its categories
+//!
+//! Products the customer already bought, and products not on sale, are
+//! left out. Customers without paid orders get no recommendations.
+
+use chrono::Duration;
+use commercerack_core::Timestamp;
+use sea_orm::*;
+use ::entity::prelude::*;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use tracing::instrument;
+
+use crate::availability::Visibility;
+
+/// Recommendations kept per customer
+pub const RECOMMENDATIONS_PER_CUSTOMER: usize = 12;
+
+/// How far back purchases count
+pub const HISTORY_DAYS: i64 = 365;
+
+/// Rows per insert when saving a merchant's recommendations
+const INSERT_BATCH: usize = 1000;
+
+/// One product in a paid order of a known customer
+#[derive(Debug, Clone, PartialEq, Eq, FromQueryResult)]
+pub struct Purchase {
+    pub cid: i32,
+    pub order_id: i32,
+    pub product_id: i32,
+}
+
+/// A product worked out for a customer
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scored {
+    pub product_id: i32,
+    pub score: f64,
+}
+
+const PURCHASES_SQL: &str = "\
+    SELECT o.customer AS cid, o.id AS order_id, s.pid AS product_id \
+    FROM orders o \
+    JOIN order_items oi ON oi.mid = o.mid AND oi.order_id = o.id \
+    JOIN sku_lookup s ON s.mid = oi.mid AND s.sku = oi.sku \
+    WHERE o.mid = $1 AND o.customer <> 0 AND o.paid_gmt IS NOT NULL AND o.created_gmt >= $2 \
+    GROUP BY o.customer, o.id, s.pid";
+
+/// Each customer's best `limit` products, best first
+pub fn recommend(
+    purchases: &[Purchase],
+    categories: &HashMap<i32, Vec<i32>>,
+    on_sale: &HashSet<i32>,
+    limit: usize,
+) -> HashMap<i32, Vec<Scored>> {
+    // Orders per product pair, both ways round
+    let mut orders: HashMap<i32, BTreeSet<i32>> = HashMap::new();
+    for purchase in purchases {
+        orders.entry(purchase.order_id).or_default().insert(purchase.product_id);
+    }
+    let mut together: HashMap<i32, HashMap<i32, u32>> = HashMap::new();
+    for products in orders.values() {
+        for a in products {
+            for b in products.iter().filter(|b| *b != a) {
+                *together.entry(*a).or_default().entry(*b).or_default() += 1;
+            }
+        }
+    }
+
+    let mut in_category: HashMap<i32, Vec<i32>> = HashMap::new();
+    for (product_id, category_ids) in categories {
+        for category_id in category_ids {
+            in_category.entry(*category_id).or_default().push(*product_id);
+        }
+    }
+
+    let mut bought: HashMap<i32, HashSet<i32>> = HashMap::new();
+    for purchase in purchases {
+        bought.entry(purchase.cid).or_default().insert(purchase.product_id);
+    }
+
+    bought
+        .into_iter()
+        .map(|(cid, products)| {
+            let mut scores: HashMap<i32, f64> = HashMap::new();
+            for product_id in &products {
+                for (other, count) in together.get(product_id).into_iter().flatten() {
+                    *scores.entry(*other).or_default() += f64::from(*count);
+                }
+            }
+
+            let mut affinity: HashMap<i32, u32> = HashMap::new();
+            for category_id in products.iter().filter_map(|p| categories.get(p)).flatten() {
+                *affinity.entry(*category_id).or_default() += 1;
+            }
+            let filed: u32 = affinity.values().sum();
+            for (category_id, count) in affinity {
+                let share = f64::from(count) / f64::from(filed);
+                for other in in_category.get(&category_id).into_iter().flatten() {
+                    *scores.entry(*other).or_default() += share;
+                }
+            }
+
+            let mut scored: Vec<Scored> = scores
+                .into_iter()
+                .filter(|(product_id, _)| !products.contains(product_id) && on_sale.contains(product_id))
+                .map(|(product_id, score)| Scored { product_id, score })
+                .collect();
+            scored.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.product_id.cmp(&b.product_id)));
+            scored.truncate(limit);
+            (cid, scored)
+        })
+        .collect()
+}
+
+/// Recommendation service
+pub struct RecommendationService;
+
+impl RecommendationService {
+    /// Merchants to refresh at `now`: those with paid orders in the
+    /// history window, and those with recommendations to clear
+    pub async fn merchants(db: &DatabaseConnection, now: Timestamp) -> Result<Vec<i32>, DbErr> {
+        let since = now - Duration::days(HISTORY_DAYS);
+        let mut mids: BTreeSet<i32> = Orders::find()
+            .select_only()
+            .column(::entity::orders::Column::Mid)
+            .distinct()
+            .filter(::entity::orders::Column::PaidGmt.is_not_null())
+            .filter(::entity::orders::Column::CreatedGmt.gte(since))
+            .into_tuple::<i32>()
+            .all(db)
+            .await?
+            .into_iter()
+            .collect();
+        mids.extend(
+            CustomerRecommendations::find()
+                .select_only()
+                .column(::entity::customer_recommendations::Column::Mid)
+                .distinct()
+                .into_tuple::<i32>()
+                .all(db)
+                .await?,
+        );
+        Ok(mids.into_iter().collect())
+    }
+
+    /// Work out a merchant's recommendations again, replacing the stored
+    /// ones. Returns how many customers got recommendations.
+    #[instrument(skip_all, fields(mid = mid))]
+    pub async fn refresh(db: &DatabaseConnection, mid: i32, now: Timestamp) -> Result<usize, DbErr> {
+        let purchases = Purchase::find_by_statement(Statement::from_sql_and_values(
+            db.get_database_backend(),
+            PURCHASES_SQL,
+            [mid.into(), (now - Duration::days(HISTORY_DAYS)).into()],
+        ))
+        .all(db)
+        .await?;
+
+        let mut categories: HashMap<i32, Vec<i32>> = HashMap::new();
+        let filed: Vec<(i32, i32)> = ProductCategories::find()
+            .select_only()
+            .column(::entity::product_categories::Column::ProductId)
+            .column(::entity::product_categories::Column::CategoryId)
+            .filter(::entity::product_categories::Column::Mid.eq(mid))
+            .into_tuple()
+            .all(db)
+            .await?;
+        for (product_id, category_id) in filed {
+            categories.entry(product_id).or_default().push(category_id);
+        }
+
+        let on_sale: HashSet<i32> = Products::find()
+            .select_only()
+            .column(::entity::products::Column::Id)
+            .filter(::entity::products::Column::Mid.eq(mid))
+            .filter(Visibility::Storefront.condition(now))
+            .into_tuple::<i32>()
+            .all(db)
+            .await?
+            .into_iter()
+            .collect();
+
+        let recommendations = recommend(&purchases, &categories, &on_sale, RECOMMENDATIONS_PER_CUSTOMER);
+        let rows: Vec<::entity::customer_recommendations::ActiveModel> = recommendations
+            .iter()
+            .flat_map(|(cid, scored)| {
+                scored.iter().enumerate().map(move |(position, scored)| ::entity::customer_recommendations::ActiveModel {
+                    mid: Set(mid),
+                    cid: Set(*cid),
+                    product_id: Set(scored.product_id),
+                    position: Set(position as i32),
+                    score: Set(scored.score),
+                    computed_gmt: Set(now),
+                    ..Default::default()
+                })
+            })
+            .collect();
+
+        let txn = db.begin().await?;
+        CustomerRecommendations::delete_many()
+            .filter(::entity::customer_recommendations::Column::Mid.eq(mid))
+            .exec(&txn)
+            .await?;
+        for batch in rows.chunks(INSERT_BATCH) {
+            CustomerRecommendations::insert_many(batch.to_vec()).exec_without_returning(&txn).await?;
+        }
+        txn.commit().await?;
+        Ok(recommendations.values().filter(|scored| !scored.is_empty()).count())
+    }
+
+    /// A customer's recommended products that are still on sale, best
+    /// first, and when they were worked out
+    pub async fn for_customer(
+        db: &DatabaseConnection,
+        mid: i32,
+        cid: i32,
+        limit: u64,
+    ) -> Result<(Vec<Product>, Option<Timestamp>), DbErr> {
+        use ::entity::customer_recommendations::Column;
+
+        let recommendations = CustomerRecommendations::find()
+            .filter(Column::Mid.eq(mid))
+            .filter(Column::Cid.eq(cid))
+            .order_by_asc(Column::Position)
+            .all(db)
+            .await?;
+        let computed = recommendations.first().map(|r| r.computed_gmt);
+
+        let ids: Vec<i32> = recommendations.iter().map(|r| r.product_id).collect();
+        let mut products: HashMap<i32, Product> = Products::find()
+            .filter(::entity::products::Column::Mid.eq(mid))
+            .filter(::entity::products::Column::Id.is_in(ids.iter().copied()))
+            .filter(Visibility::Storefront.condition(Timestamp::now()))
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|product| (product.id, product))
+            .collect();
+
+        let products = ids.iter().filter_map(|id| products.remove(id)).take(limit as usize).collect();
+        Ok((products, computed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn purchase(cid: i32, order_id: i32, product_id: i32) -> Purchase {
+        Purchase { cid, order_id, product_id }
+    }
+
+    #[test]
+    fn test_co_purchases_outrank_category_neighbours() {
+        let purchases = vec![
+            // Customer 1 bought a tent; tents sell with stoves (twice) and lamps
+            purchase(1, 10, 100),
+            purchase(2, 20, 100),
+            purchase(2, 20, 200),
+            purchase(3, 30, 100),
+            purchase(3, 30, 200),
+            purchase(3, 30, 300),
+        ];
+        // Tents, stoves and sleeping bags are camping gear
+        let categories = HashMap::from([(100, vec![7]), (200, vec![7]), (400, vec![7]), (500, vec![8])]);
+        let on_sale = HashSet::from([100, 200, 300, 400, 500]);
+
+        let recommendations = recommend(&purchases, &categories, &on_sale, 3);
+        let ids: Vec<i32> = recommendations[&1].iter().map(|s| s.product_id).collect();
+        assert_eq!(ids, vec![200, 300, 400]);
+        assert_eq!(recommendations[&1][0].score, 3.0);
+        assert_eq!(recommendations[&1][2].score, 1.0);
+
+        // Customer 3 bought everything that sells together; only the
+        // category neighbour is left
+        let ids: Vec<i32> = recommendations[&3].iter().map(|s| s.product_id).collect();
+        assert_eq!(ids, vec![400]);
+
+        let off_sale = HashSet::from([100, 300, 400]);
+        let ids: Vec<i32> = recommend(&purchases, &categories, &off_sale, 3)[&1].iter().map(|s| s.product_id).collect();
+        assert_eq!(ids, vec![300, 400]);
+    }
+
+    fn row(columns: &[(&str, i32)]) -> BTreeMap<String, Value> {
+        columns.iter().map(|(name, value)| ((*name).to_owned(), Value::Int(Some(*value)))).collect()
+    }
+
+    #[tokio::test]
+    async fn test_refresh_replaces_the_merchants_recommendations() {
+        let purchases = [(1, 10, 100), (2, 20, 100), (2, 20, 200)]
+            .map(|(cid, order_id, product_id)| row(&[("cid", cid), ("order_id", order_id), ("product_id", product_id)]));
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([purchases.to_vec()])
+            .append_query_results([Vec::<BTreeMap<String, Value>>::new()])
+            .append_query_results([vec![row(&[("id", 100)]), row(&[("id", 200)])]])
+            .append_exec_results([
+                MockExecResult { last_insert_id: 0, rows_affected: 4 },
+                MockExecResult { last_insert_id: 0, rows_affected: 1 },
+            ])
+            .into_connection();
+
+        // Customer 2 already has both products, so only customer 1 gets one
+        let customers = RecommendationService::refresh(&db, 1, Timestamp::from_unix(1_000_000_000)).await.unwrap();
+        assert_eq!(customers, 1);
+
+        let log = db.into_transaction_log();
+        let statements = log[3].statements();
+        assert_eq!(statements[0].sql, "BEGIN");
+        assert!(statements[1].sql.starts_with(r#"DELETE FROM "customer_recommendations""#), "{}", statements[1].sql);
+        assert!(statements[2].sql.starts_with(r#"INSERT INTO "customer_recommendations""#), "{}", statements[2].sql);
+        assert_eq!(statements[3].sql, "COMMIT");
+    }
+}
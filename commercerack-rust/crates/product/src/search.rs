@@ -0,0 +1,43 @@
+//! Full-text product search
+//!
+//! Products carry a `search_vector` tsvector column weighted by name,
+//! category and description, backed by a GIN index. The column is not part
+//! of the entity; it is rewritten with [`reindex`] whenever a product is
+//! created or updated and queried through raw expressions.
+
+use sea_orm::sea_query::{Expr, SimpleExpr};
+use sea_orm::{ConnectionTrait, DbErr, Statement};
+
+/// Text search configuration used for both indexing and queries
+const CONFIG: &str = "english";
+
+/// Rebuild the search vector of one product from its current columns
+pub(crate) async fn reindex<C: ConnectionTrait>(db: &C, id: i32) -> Result<(), DbErr> {
+    let sql = format!(
+        "UPDATE products SET search_vector = \
+         setweight(to_tsvector('{config}', coalesce(product_name, '')), 'A') || \
+         setweight(to_tsvector('{config}', coalesce(category, '')), 'B') || \
+         setweight(to_tsvector('{config}', coalesce(description, '')), 'C') \
+         WHERE id = $1",
+        config = CONFIG
+    );
+    db.execute(Statement::from_sql_and_values(db.get_database_backend(), sql, [id.into()]))
+        .await?;
+    Ok(())
+}
+
+/// Products whose search vector matches a web-style query (`"red shoes" -kids`)
+pub(crate) fn matches(query: &str) -> SimpleExpr {
+    Expr::cust_with_values(
+        format!("search_vector @@ websearch_to_tsquery('{}', $1)", CONFIG),
+        [query],
+    )
+}
+
+/// Relevance of a product to the query, higher is better
+pub(crate) fn rank(query: &str) -> SimpleExpr {
+    Expr::cust_with_values(
+        format!("ts_rank(search_vector, websearch_to_tsquery('{}', $1))", CONFIG),
+        [query],
+    )
+}
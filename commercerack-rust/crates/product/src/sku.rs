@@ -1,31 +1,125 @@
-//! SKU management (placeholder - to be implemented with SeaORM)
+//! SKU management using SeaORM
 //!
-//! TODO: Implement SKU entity and service layer
-//! For now, this is a stub to allow compilation
-
-use serde::{Deserialize, Serialize};
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct SKU {
-    pub id: i32,
-    pub pid: i32,
-    pub mid: i32,
-    pub sku: String,
-    pub title: String,
-    pub price: rust_decimal::Decimal,
-    pub cost: rust_decimal::Decimal,
-    pub upc: String,
-    pub inv_available: i32,
-    pub qty_onshelf: i32,
-}
+//! SKUs are the sellable variants of a product, each carrying its own
+//! price, cost, UPC, and inventory counts.
+
+use sea_orm::*;
+use ::entity::prelude::*;
+use commercerack_events::{outbox, DomainEvent};
+
+use crate::ProductError;
+
+/// SKU model (SKU_LOOKUP table)
+pub type SKU = Sku;
+
+/// SKU service for managing product variants
+pub struct SKUService;
+
+impl SKUService {
+    /// Create new SKU
+    pub async fn create(
+        db: &DatabaseConnection,
+        sku: SKU,
+    ) -> Result<SKU, ProductError> {
+        let active = ::entity::skus::ActiveModel {
+            pid: Set(sku.pid),
+            mid: Set(sku.mid),
+            sku: Set(sku.sku),
+            title: Set(sku.title),
+            price: Set(sku.price),
+            cost: Set(sku.cost),
+            upc: Set(sku.upc),
+            inv_available: Set(sku.inv_available),
+            qty_onshelf: Set(sku.qty_onshelf),
+            weight: Set(sku.weight),
+            reorder_point: Set(sku.reorder_point),
+            reorder_quantity: Set(sku.reorder_quantity),
+            ..Default::default()
+        };
+
+        let txn = db.begin().await?;
+        let result = active.insert(&txn).await?;
+        outbox::record(&txn, &DomainEvent::SkuCreated(result.clone())).await?;
+        txn.commit().await?;
+        Ok(result)
+    }
+
+    /// Find SKU by ID
+    pub async fn find_by_id(
+        db: &DatabaseConnection,
+        mid: i32,
+        id: i32,
+    ) -> Result<Option<SKU>, ProductError> {
+        let sku = Skus::find()
+            .filter(::entity::skus::Column::Mid.eq(mid))
+            .filter(::entity::skus::Column::Id.eq(id))
+            .one(db)
+            .await?;
 
-// TODO: Implement SKUService with SeaORM
-// pub struct SKUService;
-//
-// impl SKUService {
-//     pub async fn create(db: &DatabaseConnection, sku: SKU) -> Result<SKU> { ... }
-//     pub async fn find_by_id(db: &DatabaseConnection, mid: i32, id: i32) -> Result<Option<SKU>> { ... }
-//     pub async fn find_by_product(db: &DatabaseConnection, mid: i32, pid: i32) -> Result<Vec<SKU>> { ... }
-//     pub async fn update(db: &DatabaseConnection, sku: SKU) -> Result<SKU> { ... }
-//     pub async fn delete(db: &DatabaseConnection, mid: i32, id: i32) -> Result<()> { ... }
-// }
+        Ok(sku)
+    }
+
+    /// Find SKU by its merchant SKU code
+    pub async fn find_by_sku(
+        db: &DatabaseConnection,
+        mid: i32,
+        sku: &str,
+    ) -> Result<Option<SKU>, ProductError> {
+        let sku = Skus::find()
+            .filter(::entity::skus::Column::Mid.eq(mid))
+            .filter(::entity::skus::Column::Sku.eq(sku))
+            .one(db)
+            .await?;
+
+        Ok(sku)
+    }
+
+    /// List all SKUs for a product
+    pub async fn find_by_product(
+        db: &DatabaseConnection,
+        mid: i32,
+        pid: i32,
+    ) -> Result<Vec<SKU>, ProductError> {
+        let skus = Skus::find()
+            .filter(::entity::skus::Column::Mid.eq(mid))
+            .filter(::entity::skus::Column::Pid.eq(pid))
+            .order_by_asc(::entity::skus::Column::Sku)
+            .all(db)
+            .await?;
+
+        Ok(skus)
+    }
+
+    /// Update SKU
+    pub async fn update(
+        db: &DatabaseConnection,
+        sku: SKU,
+    ) -> Result<SKU, ProductError> {
+        let active: ::entity::skus::ActiveModel = sku.into();
+        let txn = db.begin().await?;
+        let result = active.reset_all().update(&txn).await?;
+        outbox::record(&txn, &DomainEvent::SkuUpdated(result.clone())).await?;
+        txn.commit().await?;
+        Ok(result)
+    }
+
+    /// Delete SKU
+    pub async fn delete(
+        db: &DatabaseConnection,
+        mid: i32,
+        id: i32,
+    ) -> Result<(), ProductError> {
+        let txn = db.begin().await?;
+        let result = Skus::delete_many()
+            .filter(::entity::skus::Column::Mid.eq(mid))
+            .filter(::entity::skus::Column::Id.eq(id))
+            .exec(&txn)
+            .await?;
+
+        if result.rows_affected > 0 {
+            outbox::record(&txn, &DomainEvent::SkuDeleted { mid: mid.into(), id }).await?;
+        }
+        txn.commit().await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,707 @@
+//! Coupons and discounts using SeaORM
+//!
+//! A coupon is checked against a cart when it is applied, again whenever the
+//! cart's items change, and a final time inside the checkout transaction,
+//! where the redemption is recorded and the usage counter is incremented
+//! atomically. Restricted coupons only discount the cart items they match,
+//! by SKU or by category (including subcategories). Coupons limited to
+//! customer tags (see `commercerack_customer::tags`) need a signed-in
+//! customer with one of them, checked when applied and at checkout.
+
+use commercerack_cart::{AppliedCoupon, Cart, CartItem};
+use commercerack_core::{Currency, Money, Timestamp};
+use commercerack_merchant::settings::SettingsService;
+use commercerack_merchant::MerchantError;
+use rust_decimal::Decimal;
+use sea_orm::sea_query::Expr;
+use sea_orm::*;
+use ::entity::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+use tracing::instrument;
+
+#[derive(Error, Debug)]
+pub enum CouponError {
+    #[error("Coupon not found")]
+    NotFound,
+
+    #[error("Coupon code {0} is already in use")]
+    DuplicateCode(String),
+
+    #[error("Coupon is not active")]
+    Inactive,
+
+    #[error("Coupon is not valid yet")]
+    NotStarted,
+
+    #[error("Coupon has expired")]
+    Expired,
+
+    #[error("Cart subtotal must be at least {0}")]
+    MinSubtotal(Decimal),
+
+    #[error("Coupon has reached its usage limit")]
+    UsageLimitReached,
+
+    #[error("Coupon has already been used the maximum number of times by this customer")]
+    CustomerLimitReached,
+
+    #[error("Coupon does not apply to any item in the cart")]
+    NotApplicable,
+
+    #[error("Coupon is limited to selected customers")]
+    CustomerNotEligible,
+
+    #[error(transparent)]
+    Merchant(#[from] MerchantError),
+
+    #[error("Database error: {0}")]
+    Db(#[from] DbErr),
+}
+
+impl CouponError {
+    /// Whether the coupon exists but cannot be used for this cart
+    pub fn is_rejection(&self) -> bool {
+        !matches!(
+            self,
+            CouponError::NotFound | CouponError::DuplicateCode(_) | CouponError::Merchant(_) | CouponError::Db(_)
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CouponKind {
+    /// `value` percent off the eligible items
+    Percent,
+    /// `value` off the eligible items
+    Fixed,
+    /// Shipping is free; nothing is taken off the items
+    FreeShipping,
+}
+
+impl CouponKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CouponKind::Percent => "percent",
+            CouponKind::Fixed => "fixed",
+            CouponKind::FreeShipping => "free_shipping",
+        }
+    }
+}
+
+impl fmt::Display for CouponKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for CouponKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "percent" => Ok(CouponKind::Percent),
+            "fixed" => Ok(CouponKind::Fixed),
+            "free_shipping" => Ok(CouponKind::FreeShipping),
+            other => Err(format!("unknown coupon kind {}", other)),
+        }
+    }
+}
+
+/// Settings of a coupon being created or updated
+#[derive(Debug, Clone)]
+pub struct CouponInput {
+    pub code: String,
+    pub description: String,
+    pub kind: CouponKind,
+    pub value: Decimal,
+    pub min_subtotal: Option<Decimal>,
+    pub starts_gmt: Option<Timestamp>,
+    pub ends_gmt: Option<Timestamp>,
+    pub max_uses: Option<i32>,
+    pub max_uses_per_customer: Option<i32>,
+    pub skus: Vec<String>,
+    pub category_ids: Vec<i32>,
+    /// Only customers with one of these tags may use it
+    pub customer_tags: Vec<String>,
+    pub active: bool,
+}
+
+/// Codes are matched case-insensitively and stored uppercase
+pub fn normalize_code(code: &str) -> String {
+    code.trim().to_uppercase()
+}
+
+/// SKUs a coupon is restricted to
+pub fn coupon_skus(coupon: &Coupon) -> Vec<String> {
+    coupon.skus.split(',').map(str::trim).filter(|s| !s.is_empty()).map(String::from).collect()
+}
+
+/// Categories a coupon is restricted to
+pub fn coupon_category_ids(coupon: &Coupon) -> Vec<i32> {
+    coupon.category_ids.split(',').filter_map(|id| id.trim().parse().ok()).collect()
+}
+
+/// Customer tags a coupon is limited to
+pub fn coupon_customer_tags(coupon: &Coupon) -> Vec<String> {
+    coupon.customer_tags.split(',').map(str::trim).filter(|s| !s.is_empty()).map(String::from).collect()
+}
+
+/// Tags are stored lowercase
+fn tags(tags: &[String]) -> Vec<String> {
+    tags.iter().map(|tag| tag.trim().to_lowercase()).filter(|tag| !tag.is_empty()).collect()
+}
+
+fn join<T: ToString>(values: &[T]) -> String {
+    values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(",")
+}
+
+/// Check a coupon's own rules and compute its discount for the cart items
+/// accepted by `eligible`, rounded to the merchant's `currency`. Usage
+/// limits are checked separately.
+pub fn evaluate(
+    coupon: &Coupon,
+    cart: &Cart,
+    eligible: impl Fn(&CartItem) -> bool,
+    currency: Currency,
+    now: Timestamp,
+) -> Result<AppliedCoupon, CouponError> {
+    if !coupon.active {
+        return Err(CouponError::Inactive);
+    }
+    if coupon.starts_gmt.is_some_and(|starts| now < starts) {
+        return Err(CouponError::NotStarted);
+    }
+    if coupon.ends_gmt.is_some_and(|ends| now >= ends) {
+        return Err(CouponError::Expired);
+    }
+    if let Some(min) = coupon.min_subtotal {
+        if cart.subtotal().amount() < min {
+            return Err(CouponError::MinSubtotal(min));
+        }
+    }
+
+    let eligible_subtotal: Decimal = cart.items.iter().filter(|item| eligible(item)).map(|item| item.subtotal()).sum();
+    if eligible_subtotal <= Decimal::ZERO {
+        return Err(CouponError::NotApplicable);
+    }
+
+    let kind = coupon.kind.parse().unwrap_or(CouponKind::Fixed);
+    let discount = match kind {
+        CouponKind::Percent => {
+            Money::new(eligible_subtotal * coupon.value / Decimal::ONE_HUNDRED, currency).round().amount()
+        }
+        CouponKind::Fixed => coupon.value,
+        CouponKind::FreeShipping => Decimal::ZERO,
+    };
+
+    Ok(AppliedCoupon {
+        coupon_id: coupon.id,
+        code: coupon.code.clone(),
+        discount: discount.min(eligible_subtotal).max(Decimal::ZERO),
+        free_shipping: kind == CouponKind::FreeShipping,
+    })
+}
+
+/// Coupon service for managing coupons and applying them to carts
+pub struct CouponService;
+
+impl CouponService {
+    /// Create a coupon. Codes are unique per merchant.
+    #[instrument(skip_all, fields(mid = mid))]
+    pub async fn create(
+        db: &DatabaseConnection,
+        mid: i32,
+        input: CouponInput,
+    ) -> Result<Coupon, CouponError> {
+        let code = normalize_code(&input.code);
+        if Self::find_by_code(db, mid, &code).await?.is_some() {
+            return Err(CouponError::DuplicateCode(code));
+        }
+
+        let now = Timestamp::now();
+        let coupon = ::entity::coupons::ActiveModel {
+            mid: Set(mid),
+            code: Set(code),
+            description: Set(input.description),
+            kind: Set(input.kind.to_string()),
+            value: Set(input.value),
+            min_subtotal: Set(input.min_subtotal),
+            starts_gmt: Set(input.starts_gmt),
+            ends_gmt: Set(input.ends_gmt),
+            max_uses: Set(input.max_uses),
+            max_uses_per_customer: Set(input.max_uses_per_customer),
+            times_used: Set(0),
+            skus: Set(join(&input.skus)),
+            category_ids: Set(join(&input.category_ids)),
+            customer_tags: Set(join(&tags(&input.customer_tags))),
+            active: Set(input.active),
+            created_gmt: Set(now),
+            modified_gmt: Set(now),
+            ..Default::default()
+        };
+
+        Ok(coupon.insert(db).await?)
+    }
+
+    /// Find coupon by ID
+    pub async fn find_by_id(
+        db: &DatabaseConnection,
+        mid: i32,
+        id: i32,
+    ) -> Result<Option<Coupon>, CouponError> {
+        let coupon = Coupons::find()
+            .filter(::entity::coupons::Column::Mid.eq(mid))
+            .filter(::entity::coupons::Column::Id.eq(id))
+            .one(db)
+            .await?;
+
+        Ok(coupon)
+    }
+
+    /// Find coupon by code, ignoring case
+    pub async fn find_by_code<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        code: &str,
+    ) -> Result<Option<Coupon>, CouponError> {
+        let coupon = Coupons::find()
+            .filter(::entity::coupons::Column::Mid.eq(mid))
+            .filter(::entity::coupons::Column::Code.eq(normalize_code(code)))
+            .one(db)
+            .await?;
+
+        Ok(coupon)
+    }
+
+    /// List a merchant's coupons by code
+    pub async fn list(
+        db: &DatabaseConnection,
+        mid: i32,
+    ) -> Result<Vec<Coupon>, CouponError> {
+        let coupons = Coupons::find()
+            .filter(::entity::coupons::Column::Mid.eq(mid))
+            .order_by_asc(::entity::coupons::Column::Code)
+            .all(db)
+            .await?;
+
+        Ok(coupons)
+    }
+
+    /// Replace a coupon's settings. Its usage count is kept.
+    #[instrument(skip_all, fields(mid = coupon.mid, id = coupon.id))]
+    pub async fn update(
+        db: &DatabaseConnection,
+        coupon: Coupon,
+        input: CouponInput,
+    ) -> Result<Coupon, CouponError> {
+        let code = normalize_code(&input.code);
+        if code != coupon.code && Self::find_by_code(db, coupon.mid, &code).await?.is_some() {
+            return Err(CouponError::DuplicateCode(code));
+        }
+
+        let mut active: ::entity::coupons::ActiveModel = coupon.into();
+        active.code = Set(code);
+        active.description = Set(input.description);
+        active.kind = Set(input.kind.to_string());
+        active.value = Set(input.value);
+        active.min_subtotal = Set(input.min_subtotal);
+        active.starts_gmt = Set(input.starts_gmt);
+        active.ends_gmt = Set(input.ends_gmt);
+        active.max_uses = Set(input.max_uses);
+        active.max_uses_per_customer = Set(input.max_uses_per_customer);
+        active.skus = Set(join(&input.skus));
+        active.category_ids = Set(join(&input.category_ids));
+        active.customer_tags = Set(join(&tags(&input.customer_tags)));
+        active.active = Set(input.active);
+        active.modified_gmt = Set(Timestamp::now());
+
+        Ok(active.update(db).await?)
+    }
+
+    /// Delete a coupon and its redemption history. Returns whether it existed.
+    #[instrument(skip_all, fields(mid = mid, id = id))]
+    pub async fn delete(
+        db: &DatabaseConnection,
+        mid: i32,
+        id: i32,
+    ) -> Result<bool, CouponError> {
+        let result = Coupons::delete_many()
+            .filter(::entity::coupons::Column::Mid.eq(mid))
+            .filter(::entity::coupons::Column::Id.eq(id))
+            .exec(db)
+            .await?;
+
+        Ok(result.rows_affected > 0)
+    }
+
+    /// Check a coupon code against a cart and apply it, replacing any
+    /// coupon already applied. `customer` enables the per-customer limit.
+    #[instrument(skip_all, fields(mid = mid, code = code))]
+    pub async fn apply(
+        db: &DatabaseConnection,
+        mid: i32,
+        code: &str,
+        cart: &mut Cart,
+        customer: Option<i32>,
+    ) -> Result<AppliedCoupon, CouponError> {
+        let coupon = Self::find_by_code(db, mid, code).await?.ok_or(CouponError::NotFound)?;
+        Self::check_customer(db, &coupon, customer).await?;
+        let applied = Self::check(db, &coupon, cart, customer).await?;
+        cart.apply_coupon(applied.clone());
+        Ok(applied)
+    }
+
+    /// Recalculate the cart's coupon after its items changed, removing the
+    /// coupon if it no longer applies. Who may use it was settled when it
+    /// was applied.
+    pub async fn refresh(
+        db: &DatabaseConnection,
+        cart: &mut Cart,
+    ) -> Result<(), CouponError> {
+        let Some(applied) = &cart.coupon else {
+            return Ok(());
+        };
+
+        let Some(coupon) = Coupons::find_by_id(applied.coupon_id).one(db).await? else {
+            cart.remove_coupon();
+            return Ok(());
+        };
+        match Self::check(db, &coupon, cart, None).await {
+            Ok(applied) => cart.apply_coupon(applied),
+            Err(e) if e.is_rejection() => {
+                cart.remove_coupon();
+            }
+            Err(e) => return Err(e),
+        }
+        Ok(())
+    }
+
+    /// Re-check the cart's coupon during checkout, returning the coupon and
+    /// the discount to charge. Call [`Self::redeem`] once the order exists.
+    /// `customer` enables the per-customer limit.
+    pub async fn revalidate<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        cart: &Cart,
+        customer: Option<i32>,
+    ) -> Result<Option<(Coupon, AppliedCoupon)>, CouponError> {
+        let Some(applied) = &cart.coupon else {
+            return Ok(None);
+        };
+
+        let coupon = Coupons::find()
+            .filter(::entity::coupons::Column::Mid.eq(mid))
+            .filter(::entity::coupons::Column::Id.eq(applied.coupon_id))
+            .one(db)
+            .await?
+            .ok_or(CouponError::NotFound)?;
+        Self::check_customer(db, &coupon, customer).await?;
+        let applied = Self::check(db, &coupon, cart, customer).await?;
+        Ok(Some((coupon, applied)))
+    }
+
+    /// Record that an order used a coupon. Fails if the coupon's usage limit
+    /// was reached in the meantime, so it must run in the order's transaction.
+    #[instrument(skip_all, fields(coupon_id = coupon.id, order_id = order_id))]
+    pub async fn redeem<C: ConnectionTrait>(
+        db: &C,
+        coupon: &Coupon,
+        order_id: i32,
+        customer: i32,
+        discount: Decimal,
+    ) -> Result<CouponRedemption, CouponError> {
+        use ::entity::coupons::Column;
+
+        let claimed = Coupons::update_many()
+            .col_expr(Column::TimesUsed, Expr::col(Column::TimesUsed).add(1))
+            .filter(Column::Id.eq(coupon.id))
+            .filter(
+                Condition::any()
+                    .add(Column::MaxUses.is_null())
+                    .add(Expr::col(Column::TimesUsed).lt(Expr::col(Column::MaxUses))),
+            )
+            .exec(db)
+            .await?;
+        if claimed.rows_affected == 0 {
+            return Err(CouponError::UsageLimitReached);
+        }
+
+        let redemption = ::entity::coupon_redemptions::ActiveModel {
+            mid: Set(coupon.mid),
+            coupon_id: Set(coupon.id),
+            order_id: Set(order_id),
+            customer: Set(customer),
+            discount: Set(discount),
+            created_gmt: Set(Timestamp::now()),
+            ..Default::default()
+        };
+
+        Ok(redemption.insert(db).await?)
+    }
+
+    /// Whether `customer` has one of the tags the coupon is limited to.
+    /// Guests can't use limited coupons.
+    async fn check_customer<C: ConnectionTrait>(
+        db: &C,
+        coupon: &Coupon,
+        customer: Option<i32>,
+    ) -> Result<(), CouponError> {
+        let tags = coupon_customer_tags(coupon);
+        if tags.is_empty() {
+            return Ok(());
+        }
+        let Some(customer) = customer else {
+            return Err(CouponError::CustomerNotEligible);
+        };
+
+        let tagged = CustomerTags::find()
+            .filter(::entity::customer_tags::Column::Mid.eq(coupon.mid))
+            .filter(::entity::customer_tags::Column::Cid.eq(customer))
+            .filter(::entity::customer_tags::Column::Tag.is_in(tags))
+            .count(db)
+            .await?;
+        if tagged == 0 {
+            return Err(CouponError::CustomerNotEligible);
+        }
+        Ok(())
+    }
+
+    /// Usage limits plus the coupon's own rules
+    async fn check<C: ConnectionTrait>(
+        db: &C,
+        coupon: &Coupon,
+        cart: &Cart,
+        customer: Option<i32>,
+    ) -> Result<AppliedCoupon, CouponError> {
+        if coupon.max_uses.is_some_and(|max| coupon.times_used >= max) {
+            return Err(CouponError::UsageLimitReached);
+        }
+        if let (Some(max), Some(customer)) = (coupon.max_uses_per_customer, customer) {
+            let used = CouponRedemptions::find()
+                .filter(::entity::coupon_redemptions::Column::CouponId.eq(coupon.id))
+                .filter(::entity::coupon_redemptions::Column::Customer.eq(customer))
+                .count(db)
+                .await?;
+            if used >= max.max(0) as u64 {
+                return Err(CouponError::CustomerLimitReached);
+            }
+        }
+
+        let currency = SettingsService::get(db, coupon.mid).await?.currency;
+        let skus: HashSet<String> = coupon_skus(coupon).into_iter().collect();
+        let category_ids = coupon_category_ids(coupon);
+        if skus.is_empty() && category_ids.is_empty() {
+            return evaluate(coupon, cart, |_| true, currency, Timestamp::now());
+        }
+
+        let category_skus = Self::skus_in_categories(db, coupon.mid, &category_ids, cart).await?;
+        evaluate(
+            coupon,
+            cart,
+            |item| skus.contains(&item.sku) || category_skus.contains(&item.sku),
+            currency,
+            Timestamp::now(),
+        )
+    }
+
+    /// Cart SKUs whose product is in one of the categories or their subcategories
+    async fn skus_in_categories<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        category_ids: &[i32],
+        cart: &Cart,
+    ) -> Result<HashSet<String>, CouponError> {
+        if category_ids.is_empty() || cart.is_empty() {
+            return Ok(HashSet::new());
+        }
+
+        let categories = Categories::find()
+            .filter(::entity::categories::Column::Mid.eq(mid))
+            .all(db)
+            .await?;
+        let categories = with_descendants(category_ids, &categories);
+
+        let skus = Skus::find()
+            .filter(::entity::skus::Column::Mid.eq(mid))
+            .filter(::entity::skus::Column::Sku.is_in(cart.items.iter().map(|item| item.sku.clone())))
+            .all(db)
+            .await?;
+        let product_ids: HashSet<i32> = ProductCategories::find()
+            .filter(::entity::product_categories::Column::Mid.eq(mid))
+            .filter(::entity::product_categories::Column::ProductId.is_in(skus.iter().map(|sku| sku.pid)))
+            .filter(::entity::product_categories::Column::CategoryId.is_in(categories))
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|assignment| assignment.product_id)
+            .collect();
+
+        Ok(skus
+            .into_iter()
+            .filter(|sku| product_ids.contains(&sku.pid))
+            .map(|sku| sku.sku)
+            .collect())
+    }
+}
+
+/// The given categories plus everything beneath them
+fn with_descendants(roots: &[i32], categories: &[Category]) -> Vec<i32> {
+    let mut children: HashMap<i32, Vec<i32>> = HashMap::new();
+    for category in categories {
+        if let Some(parent) = category.parent_id {
+            children.entry(parent).or_default().push(category.id);
+        }
+    }
+
+    let mut found: HashSet<i32> = HashSet::new();
+    let mut pending = roots.to_vec();
+    while let Some(id) = pending.pop() {
+        if found.insert(id) {
+            pending.extend(children.get(&id).into_iter().flatten());
+        }
+    }
+    found.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn coupon(kind: CouponKind, value: Decimal) -> Coupon {
+        Coupon {
+            id: 1,
+            mid: 1,
+            code: "SAVE".to_string(),
+            description: String::new(),
+            kind: kind.to_string(),
+            value,
+            min_subtotal: None,
+            starts_gmt: None,
+            ends_gmt: None,
+            max_uses: None,
+            max_uses_per_customer: None,
+            times_used: 0,
+            skus: String::new(),
+            category_ids: String::new(),
+            customer_tags: String::new(),
+            active: true,
+            created_gmt: Timestamp::EPOCH,
+            modified_gmt: Timestamp::EPOCH,
+        }
+    }
+
+    fn cart() -> Cart {
+        let mut cart = Cart::new();
+        cart.add_item("SKU001".to_string(), "Widget".to_string(), 2, Decimal::new(1000, 2));
+        cart.add_item("SKU002".to_string(), "Gadget".to_string(), 1, Decimal::new(3000, 2));
+        cart
+    }
+
+    #[test]
+    fn test_discounts() {
+        let all = |_: &CartItem| true;
+        let usd = Currency::USD;
+
+        let percent = evaluate(&coupon(CouponKind::Percent, Decimal::new(15, 0)), &cart(), all, usd, Timestamp::EPOCH);
+        assert_eq!(percent.unwrap().discount, Decimal::new(750, 2));
+
+        // Restricted coupons only discount matching items, and never below zero
+        let only_widgets = |item: &CartItem| item.sku == "SKU001";
+        let fixed = coupon(CouponKind::Fixed, Decimal::new(5000, 2));
+        let fixed = evaluate(&fixed, &cart(), only_widgets, usd, Timestamp::EPOCH).unwrap();
+        assert_eq!(fixed.discount, Decimal::new(2000, 2));
+
+        let shipping = coupon(CouponKind::FreeShipping, Decimal::ZERO);
+        let shipping = evaluate(&shipping, &cart(), all, usd, Timestamp::EPOCH).unwrap();
+        assert!(shipping.free_shipping);
+        assert_eq!(shipping.discount, Decimal::ZERO);
+
+        let none = evaluate(&coupon(CouponKind::Fixed, Decimal::ONE), &cart(), |_| false, usd, Timestamp::EPOCH);
+        assert!(matches!(none, Err(CouponError::NotApplicable)));
+    }
+
+    #[test]
+    fn test_percent_discounts_round_to_the_currency() {
+        let percent = coupon(CouponKind::Percent, Decimal::new(17, 0));
+        let discount = |currency| evaluate(&percent, &cart(), |item| item.sku == "SKU002", currency, Timestamp::EPOCH);
+
+        assert_eq!(discount(Currency::USD).unwrap().discount, Decimal::new(510, 2));
+        assert_eq!(discount(Currency::JPY).unwrap().discount, Decimal::new(5, 0));
+    }
+
+    #[test]
+    fn test_constraints() {
+        let all = |_: &CartItem| true;
+        let check = |rules: &Coupon, now| evaluate(rules, &cart(), all, Currency::USD, now);
+        let mut rules = coupon(CouponKind::Fixed, Decimal::ONE);
+        rules.starts_gmt = Some(Timestamp::from_unix(100));
+        rules.ends_gmt = Some(Timestamp::from_unix(200));
+        assert!(matches!(check(&rules, Timestamp::from_unix(99)), Err(CouponError::NotStarted)));
+        assert!(check(&rules, Timestamp::from_unix(150)).is_ok());
+        assert!(matches!(check(&rules, Timestamp::from_unix(200)), Err(CouponError::Expired)));
+
+        let mut rules = coupon(CouponKind::Fixed, Decimal::ONE);
+        rules.min_subtotal = Some(Decimal::new(10000, 2));
+        assert!(matches!(check(&rules, Timestamp::EPOCH), Err(CouponError::MinSubtotal(_))));
+
+        rules.min_subtotal = None;
+        rules.active = false;
+        assert!(matches!(check(&rules, Timestamp::EPOCH), Err(CouponError::Inactive)));
+    }
+
+    #[tokio::test]
+    async fn test_apply_enforces_usage_limit() {
+        let mut used_up = coupon(CouponKind::Percent, Decimal::TEN);
+        used_up.max_uses = Some(3);
+        used_up.times_used = 3;
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![used_up]])
+            .into_connection();
+
+        let mut cart = cart();
+        let result = CouponService::apply(&db, 1, "save", &mut cart, None).await;
+        assert!(matches!(result, Err(CouponError::UsageLimitReached)));
+        assert!(cart.coupon.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_tag_limited_coupon_needs_a_tagged_customer() {
+        let mut vip_only = coupon(CouponKind::Percent, Decimal::TEN);
+        vip_only.customer_tags = "vip,wholesale".to_string();
+        let not_tagged = BTreeMap::from([("num_items".to_string(), Value::BigInt(Some(0)))]);
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![vip_only.clone()], vec![vip_only]])
+            .append_query_results([vec![not_tagged]])
+            .into_connection();
+
+        let mut cart = cart();
+        let guest = CouponService::apply(&db, 1, "save", &mut cart, None).await;
+        assert!(matches!(guest, Err(CouponError::CustomerNotEligible)));
+        let untagged = CouponService::apply(&db, 1, "save", &mut cart, Some(42)).await;
+        assert!(matches!(untagged, Err(CouponError::CustomerNotEligible)));
+        assert!(cart.coupon.is_none());
+    }
+
+    #[test]
+    fn test_with_descendants() {
+        let category = |id, parent_id| Category {
+            id,
+            mid: 1,
+            parent_id,
+            name: String::new(),
+            slug: String::new(),
+            position: 0,
+            created_gmt: Timestamp::EPOCH,
+            modified_gmt: Timestamp::EPOCH,
+        };
+        let categories = vec![category(1, None), category(2, Some(1)), category(3, Some(2)), category(4, None)];
+
+        let mut ids = with_descendants(&[1], &categories);
+        ids.sort_unstable();
+        assert_eq!(ids, vec![1, 2, 3]);
+    }
+}
@@ -0,0 +1,104 @@
+//! CSV rendering of a sales report
+//!
+//! One table for everything, so the file opens cleanly in a spreadsheet:
+//! the `section` column says whether a row is a period total, a product
+//! (keyed by SKU) or a category (keyed by ID).
+
+use crate::sales::SalesReport;
+
+const COLUMNS: [&str; 5] = ["section", "key", "name", "quantity", "amount"];
+
+pub fn render(report: &SalesReport) -> String {
+    let totals = &report.totals;
+    let mut out = csv_line(&COLUMNS.map(String::from));
+    out.push_str(&csv_line(&["summary".into(), "orders".into(), String::new(), totals.orders.to_string(), String::new()]));
+    for (key, amount) in [
+        ("revenue", totals.revenue),
+        ("tax", totals.tax),
+        ("shipping", totals.shipping),
+        ("refunds", totals.refunds),
+    ] {
+        out.push_str(&csv_line(&["summary".into(), key.into(), String::new(), String::new(), amount.to_string()]));
+    }
+    for product in &report.products {
+        out.push_str(&csv_line(&[
+            "product".into(),
+            product.sku.clone(),
+            product.product_name.clone(),
+            product.quantity.to_string(),
+            product.revenue.to_string(),
+        ]));
+    }
+    for category in &report.categories {
+        out.push_str(&csv_line(&[
+            "category".into(),
+            category.category_id.to_string(),
+            category.name.clone(),
+            category.quantity.to_string(),
+            category.revenue.to_string(),
+        ]));
+    }
+    out
+}
+
+/// RFC 4180 line: fields containing commas, quotes or line breaks are quoted
+fn csv_line(fields: &[String]) -> String {
+    let mut line = String::new();
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            line.push(',');
+        }
+        if field.contains([',', '"', '\n', '\r']) {
+            line.push('"');
+            line.push_str(&field.replace('"', "\"\""));
+            line.push('"');
+        } else {
+            line.push_str(field);
+        }
+    }
+    line.push_str("\r\n");
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sales::{CategorySales, SalesTotals};
+    use commercerack_order::stats::ProductSales;
+    use rust_decimal::Decimal;
+
+    #[test]
+    fn test_sections_and_quoting() {
+        let report = SalesReport {
+            totals: SalesTotals {
+                orders: 2,
+                revenue: Decimal::new(5450, 2),
+                tax: Decimal::new(400, 2),
+                shipping: Decimal::new(500, 2),
+                refunds: Decimal::ZERO,
+            },
+            products: vec![ProductSales {
+                sku: "W-1".to_string(),
+                product_name: "Widget, \"Deluxe\"".to_string(),
+                quantity: 3,
+                revenue: Decimal::new(4500, 2),
+            }],
+            categories: vec![CategorySales {
+                category_id: 4,
+                name: "Widgets".to_string(),
+                quantity: 3,
+                revenue: Decimal::new(4500, 2),
+            }],
+        };
+
+        let csv = render(&report);
+        let lines: Vec<_> = csv.split("\r\n").collect();
+        assert_eq!(lines[0], "section,key,name,quantity,amount");
+        assert_eq!(lines[1], "summary,orders,,2,");
+        assert_eq!(lines[2], "summary,revenue,,,54.50");
+        assert_eq!(lines[5], "summary,refunds,,,0");
+        assert_eq!(lines[6], "product,W-1,\"Widget, \"\"Deluxe\"\"\",3,45.00");
+        assert_eq!(lines[7], "category,4,Widgets,3,45.00");
+        assert_eq!(lines[8], "");
+    }
+}
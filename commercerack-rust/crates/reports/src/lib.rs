@@ -0,0 +1,300 @@
+//! Scheduled sales reports
+//!
+//! Every merchant with orders in a period gets a daily, weekly and monthly
+//! report once the period is over, by its own clock: revenue, tax
+//! collected, shipping
+//! charged and refunds, broken down by product and category. Reports are
+//! created empty by [`ReportService::schedule_due`] and filled in by
+//! [`ReportService::generate`], which the background job runs. The
+//! rendered CSV is stored with the totals so downloads never recompute it.
+
+use chrono::{DateTime, Datelike, Days, Duration, Months, Utc};
+use commercerack_core::Timestamp;
+use commercerack_merchant::settings::{Settings, SettingsService};
+use commercerack_merchant::MerchantError;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::*;
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+use tracing::instrument;
+use ::entity::prelude::*;
+use ::entity::reports;
+
+pub mod csv;
+pub mod sales;
+
+#[derive(Error, Debug)]
+pub enum ReportError {
+    #[error("Report not found")]
+    NotFound,
+
+    #[error(transparent)]
+    Merchant(#[from] MerchantError),
+
+    #[error("Database error: {0}")]
+    Db(#[from] DbErr),
+}
+
+/// How far local midnight can be from UTC midnight, either way
+const MAX_UTC_OFFSET: Duration = Duration::hours(14);
+
+/// How much time a report covers. Periods run midnight to midnight in the
+/// merchant's timezone; weeks start on Monday.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportPeriod {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+impl ReportPeriod {
+    pub const ALL: [ReportPeriod; 3] = [ReportPeriod::Daily, ReportPeriod::Weekly, ReportPeriod::Monthly];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReportPeriod::Daily => "daily",
+            ReportPeriod::Weekly => "weekly",
+            ReportPeriod::Monthly => "monthly",
+        }
+    }
+
+    /// The merchant's last whole period before `now`, as unix times
+    /// `[from, to)`
+    pub fn previous(&self, now: DateTime<Utc>, settings: &Settings) -> (Timestamp, Timestamp) {
+        let today = settings.local_date(now);
+        let (start, end) = match self {
+            ReportPeriod::Daily => (today - Days::new(1), today),
+            ReportPeriod::Weekly => {
+                let monday = today - Days::new(today.weekday().num_days_from_monday() as u64);
+                (monday - Days::new(7), monday)
+            }
+            ReportPeriod::Monthly => {
+                let first = today - Days::new(today.day0() as u64);
+                (first - Months::new(1), first)
+            }
+        };
+        (settings.midnight(start), settings.midnight(end))
+    }
+}
+
+impl fmt::Display for ReportPeriod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ReportPeriod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ReportPeriod::ALL
+            .into_iter()
+            .find(|period| period.as_str() == s)
+            .ok_or_else(|| format!("unknown report period {}", s))
+    }
+}
+
+pub struct ReportService;
+
+impl ReportService {
+    /// Create the reports of every period that ended before `now` for the
+    /// merchants that had orders in it. Returns only the reports this call
+    /// created, so concurrent schedulers never hand out the same one twice.
+    #[instrument(skip_all)]
+    pub async fn schedule_due<C: ConnectionTrait>(db: &C, now: DateTime<Utc>) -> Result<Vec<Report>, ReportError> {
+        use ::entity::orders::Column;
+
+        let mut created = Vec::new();
+        for period in ReportPeriod::ALL {
+            // Merchants with orders around the period in UTC, which the
+            // period in any timezone falls within; then each is checked for
+            // orders in the period by their own clock
+            let (from, to) = period.previous(now, &Settings::default());
+            let mids: Vec<i32> = Orders::find()
+                .select_only()
+                .column(Column::Mid)
+                .distinct()
+                .filter(Column::CreatedGmt.gte(from - MAX_UTC_OFFSET))
+                .filter(Column::CreatedGmt.lt(to + MAX_UTC_OFFSET))
+                .into_tuple()
+                .all(db)
+                .await?;
+
+            for mid in mids {
+                let settings = SettingsService::get(db, mid).await?;
+                let (from, to) = period.previous(now, &settings);
+                let ordered: Option<i32> = Orders::find()
+                    .select_only()
+                    .column(Column::Id)
+                    .filter(Column::Mid.eq(mid))
+                    .filter(Column::CreatedGmt.gte(from))
+                    .filter(Column::CreatedGmt.lt(to))
+                    .into_tuple()
+                    .one(db)
+                    .await?;
+                if ordered.is_none() {
+                    continue;
+                }
+
+                let inserted = Reports::insert(reports::ActiveModel {
+                    mid: Set(mid),
+                    period: Set(period.as_str().to_string()),
+                    from_gmt: Set(from),
+                    to_gmt: Set(to),
+                    created_gmt: Set(now.into()),
+                    ..Default::default()
+                })
+                .on_conflict(
+                    OnConflict::columns([reports::Column::Mid, reports::Column::Period, reports::Column::FromGmt])
+                        .do_nothing()
+                        .to_owned(),
+                )
+                .exec_without_returning(db)
+                .await?;
+                if inserted == 0 {
+                    continue;
+                }
+
+                let report = Reports::find()
+                    .filter(reports::Column::Mid.eq(mid))
+                    .filter(reports::Column::Period.eq(period.as_str()))
+                    .filter(reports::Column::FromGmt.eq(from))
+                    .one(db)
+                    .await?
+                    .ok_or(ReportError::NotFound)?;
+                created.push(report);
+            }
+        }
+        Ok(created)
+    }
+
+    /// Compute a report's figures and CSV. Running it again recomputes
+    /// both, e.g. after late refunds.
+    #[instrument(skip_all, fields(id = id))]
+    pub async fn generate<C: ConnectionTrait>(db: &C, id: i32) -> Result<Report, ReportError> {
+        let report = Reports::find_by_id(id).one(db).await?.ok_or(ReportError::NotFound)?;
+        let sales = sales::collect(db, report.mid, report.from_gmt, report.to_gmt).await?;
+
+        let mut active: reports::ActiveModel = report.into();
+        active.orders = Set(sales.totals.orders as i32);
+        active.revenue = Set(sales.totals.revenue);
+        active.tax = Set(sales.totals.tax);
+        active.shipping = Set(sales.totals.shipping);
+        active.refunds = Set(sales.totals.refunds);
+        active.csv = Set(Some(csv::render(&sales)));
+        active.generated_gmt = Set(Some(Timestamp::now()));
+        Ok(active.update(db).await?)
+    }
+
+    /// A merchant's reports, latest period first
+    pub async fn list<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        period: Option<ReportPeriod>,
+        limit: u64,
+    ) -> Result<Vec<Report>, ReportError> {
+        let mut query = Reports::find().filter(reports::Column::Mid.eq(mid));
+        if let Some(period) = period {
+            query = query.filter(reports::Column::Period.eq(period.as_str()));
+        }
+
+        Ok(query
+            .order_by_desc(reports::Column::FromGmt)
+            .order_by_desc(reports::Column::Id)
+            .limit(limit)
+            .all(db)
+            .await?)
+    }
+
+    /// Find one of a merchant's reports
+    pub async fn find_by_id<C: ConnectionTrait>(db: &C, mid: i32, id: i32) -> Result<Report, ReportError> {
+        Reports::find_by_id(id)
+            .filter(reports::Column::Mid.eq(mid))
+            .one(db)
+            .await?
+            .ok_or(ReportError::NotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(y: i32, m: u32, d: u32) -> Timestamp {
+        Utc.with_ymd_and_hms(y, m, d, 0, 0, 0).unwrap().into()
+    }
+
+    #[test]
+    fn test_previous_periods() {
+        // A Wednesday afternoon
+        let utc = Settings::default();
+        let now = Utc.with_ymd_and_hms(2025, 3, 12, 15, 30, 0).unwrap();
+        assert_eq!(ReportPeriod::Daily.previous(now, &utc), (at(2025, 3, 11), at(2025, 3, 12)));
+        assert_eq!(ReportPeriod::Weekly.previous(now, &utc), (at(2025, 3, 3), at(2025, 3, 10)));
+        assert_eq!(ReportPeriod::Monthly.previous(now, &utc), (at(2025, 2, 1), at(2025, 3, 1)));
+
+        // Across a year boundary
+        let now = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        assert_eq!(ReportPeriod::Monthly.previous(now, &utc), (at(2024, 12, 1), at(2025, 1, 1)));
+    }
+
+    #[test]
+    fn test_previous_periods_by_merchant_clock() {
+        let mut tokyo = Settings::default();
+        tokyo.apply(commercerack_merchant::settings::TIMEZONE, &serde_json::json!("Asia/Tokyo")).unwrap();
+        let nine_hours = Duration::hours(9);
+
+        // Still the 12th in UTC, already the 13th in Tokyo
+        let now = Utc.with_ymd_and_hms(2025, 3, 12, 15, 30, 0).unwrap();
+        assert_eq!(
+            ReportPeriod::Daily.previous(now, &tokyo),
+            (at(2025, 3, 12) - nine_hours, at(2025, 3, 13) - nine_hours)
+        );
+        assert_eq!(
+            ReportPeriod::Weekly.previous(now, &tokyo),
+            (at(2025, 3, 3) - nine_hours, at(2025, 3, 10) - nine_hours)
+        );
+    }
+
+    #[test]
+    fn test_period_round_trip() {
+        for period in ReportPeriod::ALL {
+            assert_eq!(period.as_str().parse::<ReportPeriod>(), Ok(period));
+        }
+        assert!("yearly".parse::<ReportPeriod>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_schedule_skips_existing_reports() {
+        let now = Utc.with_ymd_and_hms(2025, 3, 12, 15, 30, 0).unwrap();
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            // Daily: merchant 1 had orders and already has its report
+            .append_query_results([vec![mid_row(1)]])
+            .append_query_results([Vec::<MerchantSetting>::new()])
+            .append_query_results([vec![id_row(7)]])
+            .append_exec_results([MockExecResult { last_insert_id: 0, rows_affected: 0 }])
+            // Weekly and monthly: no orders
+            .append_query_results([Vec::<std::collections::BTreeMap<String, Value>>::new()])
+            .append_query_results([Vec::<std::collections::BTreeMap<String, Value>>::new()])
+            .into_connection();
+
+        let created = ReportService::schedule_due(&db, now).await.unwrap();
+        assert!(created.is_empty());
+
+        let log = db.into_transaction_log();
+        let insert = log[3].statements()[0].to_string();
+        assert!(insert.contains(r#"INSERT INTO "reports""#), "{}", insert);
+        assert!(insert.contains("ON CONFLICT") && insert.contains("DO NOTHING"), "{}", insert);
+        assert_eq!(log.len(), 6);
+    }
+
+    fn mid_row(mid: i32) -> std::collections::BTreeMap<String, Value> {
+        [("mid".to_string(), Value::Int(Some(mid)))].into_iter().collect()
+    }
+
+    fn id_row(id: i32) -> std::collections::BTreeMap<String, Value> {
+        [("id".to_string(), Value::Int(Some(id)))].into_iter().collect()
+    }
+}
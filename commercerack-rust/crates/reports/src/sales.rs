@@ -0,0 +1,158 @@
+//! Sales figures of one period
+//!
+//! A report covers the orders created in its period that have been paid.
+//! Tax and shipping are the `%TAX` and `%SHIP` lines of those orders;
+//! refunds are the return refunds issued during the period, whichever
+//! order they belong to. A product filed under several categories counts
+//! towards each of them, and uncategorized products towards none.
+
+use commercerack_core::Timestamp;
+use commercerack_order::checkout::{SHIP_SKU, TAX_SKU};
+use commercerack_order::returns::ReturnStatus;
+use commercerack_order::stats::ProductSales;
+use rust_decimal::Decimal;
+use sea_orm::sea_query::{Expr, SelectStatement};
+use sea_orm::{entity::*, query::*, ConnectionTrait, DbErr, FromQueryResult, Statement};
+use ::entity::prelude::{OrderItems, Orders, Returns};
+
+/// Sales of one category
+#[derive(Debug, Clone, PartialEq, FromQueryResult)]
+pub struct CategorySales {
+    pub category_id: i32,
+    pub name: String,
+    pub quantity: i64,
+    pub revenue: Decimal,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SalesTotals {
+    pub orders: i64,
+    pub revenue: Decimal,
+    pub tax: Decimal,
+    pub shipping: Decimal,
+    pub refunds: Decimal,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SalesReport {
+    pub totals: SalesTotals,
+    /// By revenue, highest first
+    pub products: Vec<ProductSales>,
+    /// By revenue, highest first
+    pub categories: Vec<CategorySales>,
+}
+
+#[derive(FromQueryResult)]
+struct OrderTotals {
+    orders: i64,
+    revenue: Option<Decimal>,
+}
+
+const CATEGORY_SALES_SQL: &str = "\
+    SELECT c.id AS category_id, c.name, SUM(oi.quantity) AS quantity, \
+           SUM(oi.quantity * oi.unit_price) AS revenue \
+    FROM order_items oi \
+    JOIN sku_lookup s ON s.mid = oi.mid AND s.sku = oi.sku \
+    JOIN product_categories pc ON pc.mid = oi.mid AND pc.product_id = s.pid \
+    JOIN categories c ON c.id = pc.category_id \
+    WHERE oi.mid = $1 AND oi.order_id IN ( \
+        SELECT id FROM orders \
+        WHERE mid = $1 AND created_gmt >= $2 AND created_gmt < $3 AND paid_gmt IS NOT NULL) \
+    GROUP BY c.id, c.name \
+    ORDER BY revenue DESC, c.id";
+
+/// Paid orders created in `[from, to)`
+fn paid_orders(mid: i32, from: Timestamp, to: Timestamp) -> Select<Orders> {
+    use ::entity::orders::Column;
+
+    Orders::find()
+        .filter(Column::Mid.eq(mid))
+        .filter(Column::CreatedGmt.gte(from))
+        .filter(Column::CreatedGmt.lt(to))
+        .filter(Column::PaidGmt.is_not_null())
+}
+
+fn paid_order_ids(mid: i32, from: Timestamp, to: Timestamp) -> SelectStatement {
+    paid_orders(mid, from, to)
+        .select_only()
+        .column(::entity::orders::Column::Id)
+        .into_query()
+}
+
+/// Gather a merchant's sales between `from` and `to` (exclusive)
+pub async fn collect<C: ConnectionTrait>(db: &C, mid: i32, from: Timestamp, to: Timestamp) -> Result<SalesReport, DbErr> {
+    use ::entity::order_items::Column;
+
+    let orders = paid_orders(mid, from, to)
+        .select_only()
+        .column_as(Expr::col(::entity::orders::Column::Id).count(), "orders")
+        .column_as(Expr::col(::entity::orders::Column::Total).sum(), "revenue")
+        .into_model::<OrderTotals>()
+        .one(db)
+        .await?;
+
+    let adjustments: Vec<(String, Decimal)> = OrderItems::find()
+        .select_only()
+        .column(Column::Sku)
+        .column_as(Expr::cust("SUM(quantity * unit_price)"), "amount")
+        .filter(Column::Mid.eq(mid))
+        .filter(Column::OrderId.in_subquery(paid_order_ids(mid, from, to)))
+        .filter(Column::Sku.is_in([TAX_SKU, SHIP_SKU]))
+        .group_by(Column::Sku)
+        .into_tuple()
+        .all(db)
+        .await?;
+    let adjustment = |sku: &str| {
+        adjustments
+            .iter()
+            .find(|(line, _)| line == sku)
+            .map_or(Decimal::ZERO, |(_, amount)| *amount)
+    };
+
+    let refunds: Option<Option<Decimal>> = Returns::find()
+        .select_only()
+        .column_as(Expr::col(::entity::returns::Column::RefundAmount).sum(), "refunds")
+        .filter(::entity::returns::Column::Mid.eq(mid))
+        .filter(::entity::returns::Column::Status.eq(ReturnStatus::Refunded.as_str()))
+        .filter(::entity::returns::Column::ModifiedGmt.gte(from))
+        .filter(::entity::returns::Column::ModifiedGmt.lt(to))
+        .into_tuple()
+        .one(db)
+        .await?;
+
+    let products = OrderItems::find()
+        .select_only()
+        .column(Column::Sku)
+        .column_as(Expr::col(Column::ProductName).max(), "product_name")
+        .column_as(Expr::col(Column::Quantity).sum(), "quantity")
+        .column_as(Expr::cust("SUM(quantity * unit_price)"), "revenue")
+        .filter(Column::Mid.eq(mid))
+        .filter(Column::OrderId.in_subquery(paid_order_ids(mid, from, to)))
+        .filter(Column::Sku.not_like("\\%%"))
+        .group_by(Column::Sku)
+        .order_by_desc(Expr::cust("revenue"))
+        .order_by_asc(Column::Sku)
+        .into_model::<ProductSales>()
+        .all(db)
+        .await?;
+
+    let categories = CategorySales::find_by_statement(Statement::from_sql_and_values(
+        db.get_database_backend(),
+        CATEGORY_SALES_SQL,
+        [mid.into(), from.into(), to.into()],
+    ))
+    .all(db)
+    .await?;
+
+    Ok(SalesReport {
+        totals: SalesTotals {
+            orders: orders.as_ref().map_or(0, |o| o.orders),
+            revenue: orders.and_then(|o| o.revenue).unwrap_or(Decimal::ZERO),
+            tax: adjustment(TAX_SKU),
+            shipping: adjustment(SHIP_SKU),
+            refunds: refunds.flatten().unwrap_or(Decimal::ZERO),
+        },
+        products,
+        categories,
+    })
+}
@@ -0,0 +1,287 @@
+//! Delivery estimates
+//!
+//! A parcel leaves its warehouse after the warehouse's handling time,
+//! counted in business days (Monday to Friday) from the day the order comes
+//! in, or from the next business day when it comes in on a weekend or after
+//! the warehouse's cutoff hour. The carrier then takes the transit days the
+//! merchant recorded for the shipping method in the destination's zone.
+//!
+//! Units are expected from the warehouses the order would be allocated from
+//! right now, and an order arrives once its last parcel does. Units no
+//! warehouse holds, and every unit of a merchant without warehouses, ship on
+//! [`Handling::default`]. Cutoff hours are in the merchant's timezone, so
+//! callers pass the merchant's local time.
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Timelike, Weekday};
+use commercerack_inventory::warehouses::{allocation_plan, strategy, ShipTo};
+use sea_orm::*;
+use ::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{Destination, ShippingError, ShippingZoneService};
+
+/// Handling days of units no warehouse ships
+pub const DEFAULT_HANDLING_DAYS: i32 = 1;
+
+/// Cutoff hour of units no warehouse ships
+pub const DEFAULT_CUTOFF_HOUR: i32 = 14;
+
+/// How long a warehouse takes to get an order out of the door
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Handling {
+    /// Business days between the order and the parcel leaving
+    pub days: i32,
+    /// Orders from this hour on count from the next business day
+    pub cutoff_hour: i32,
+}
+
+impl Default for Handling {
+    fn default() -> Self {
+        Self {
+            days: DEFAULT_HANDLING_DAYS,
+            cutoff_hour: DEFAULT_CUTOFF_HOUR,
+        }
+    }
+}
+
+impl From<&Warehouse> for Handling {
+    fn from(warehouse: &Warehouse) -> Self {
+        Self {
+            days: warehouse.handling_days,
+            cutoff_hour: warehouse.cutoff_hour,
+        }
+    }
+}
+
+/// Whether carriers and warehouses work on `date`
+pub fn is_business_day(date: NaiveDate) -> bool {
+    !matches!(date.weekday(), Weekday::Sat | Weekday::Sun)
+}
+
+fn next_business_day(date: NaiveDate) -> NaiveDate {
+    let mut next = date + Duration::days(1);
+    while !is_business_day(next) {
+        next += Duration::days(1);
+    }
+    next
+}
+
+/// The business day `days` business days after `date`
+pub fn add_business_days(date: NaiveDate, days: i32) -> NaiveDate {
+    (0..days).fold(date, |date, _| next_business_day(date))
+}
+
+/// The day a parcel leaves a warehouse with `handling` for an order placed
+/// at `placed`, the merchant's local time
+pub fn ship_date(placed: NaiveDateTime, handling: Handling) -> NaiveDate {
+    let day = placed.date();
+    let start = if is_business_day(day) && (placed.hour() as i32) < handling.cutoff_hour {
+        day
+    } else {
+        next_business_day(day)
+    };
+    add_business_days(start, handling.days.max(0))
+}
+
+/// The day the last parcel of an order for `lines` of `(sku, quantity)`
+/// leaves, given the merchant's warehouses and their stock of the SKUs
+pub fn order_ship_date(
+    placed: NaiveDateTime,
+    warehouses: &[Warehouse],
+    stock: &[WarehouseStock],
+    ship_to: &ShipTo,
+    lines: &[(String, i32)],
+) -> NaiveDate {
+    let fallback = ship_date(placed, Handling::default());
+    lines
+        .iter()
+        .filter_map(|(sku, quantity)| {
+            let held: Vec<WarehouseStock> = stock.iter().filter(|row| &row.sku == sku).cloned().collect();
+            let plan = allocation_plan(strategy(), warehouses, &held, Some(ship_to), *quantity);
+
+            let planned: i32 = plan.iter().map(|(_, take)| take).sum();
+            let latest = plan
+                .iter()
+                .filter_map(|(warehouse_id, _)| warehouses.iter().find(|warehouse| warehouse.id == *warehouse_id))
+                .map(|warehouse| ship_date(placed, warehouse.into()))
+                .max();
+            if planned < *quantity {
+                latest.max(Some(fallback))
+            } else {
+                latest
+            }
+        })
+        .max()
+        .unwrap_or(fallback)
+}
+
+/// A merchant's active warehouses and what they hold of the SKUs of
+/// `lines`; no stock is read for merchants without warehouses
+pub(crate) async fn stocked_warehouses<C: ConnectionTrait>(
+    db: &C,
+    mid: i32,
+    lines: &[(String, i32)],
+) -> Result<(Vec<Warehouse>, Vec<WarehouseStock>), ShippingError> {
+    let warehouses = Warehouses::find()
+        .filter(::entity::warehouses::Column::Mid.eq(mid))
+        .filter(::entity::warehouses::Column::Active.eq(true))
+        .all(db)
+        .await?;
+    let stock = if warehouses.is_empty() {
+        Vec::new()
+    } else {
+        WarehouseStocks::find()
+            .filter(::entity::warehouse_stock::Column::Mid.eq(mid))
+            .filter(::entity::warehouse_stock::Column::Sku.is_in(lines.iter().map(|(sku, _)| sku.clone())))
+            .filter(::entity::warehouse_stock::Column::Quantity.gt(0))
+            .all(db)
+            .await?
+    };
+    Ok((warehouses, stock))
+}
+
+/// When a shipping method delivers
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeliveryWindow {
+    pub method: String,
+    pub earliest: NaiveDate,
+    pub latest: NaiveDate,
+}
+
+impl DeliveryWindow {
+    /// The window of a parcel leaving on `ships_on` with `transit`
+    pub fn new(ships_on: NaiveDate, transit: &ShippingTransitTime) -> Self {
+        Self {
+            method: transit.method.clone(),
+            earliest: add_business_days(ships_on, transit.min_days),
+            latest: add_business_days(ships_on, transit.max_days),
+        }
+    }
+}
+
+/// When an order leaves and when each method with a transit time delivers it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeliveryEstimate {
+    pub ships_on: NaiveDate,
+    /// Methods of the destination's zone the merchant recorded transit times for
+    pub windows: Vec<DeliveryWindow>,
+}
+
+impl DeliveryEstimate {
+    /// The window of a method, if its transit time is known
+    pub fn window(&self, method: &str) -> Option<&DeliveryWindow> {
+        self.windows.iter().find(|window| window.method == method)
+    }
+}
+
+/// Delivery service estimating when orders arrive
+pub struct DeliveryService;
+
+impl DeliveryService {
+    /// When an order for `lines` of `(sku, quantity)` placed at `placed`,
+    /// the merchant's local time, would arrive at `destination`
+    pub async fn estimate(
+        db: &DatabaseConnection,
+        mid: i32,
+        lines: &[(String, i32)],
+        destination: &Destination,
+        placed: NaiveDateTime,
+    ) -> Result<DeliveryEstimate, ShippingError> {
+        let (warehouses, stock) = stocked_warehouses(db, mid, lines).await?;
+        let ship_to = ShipTo::new(&destination.country, &destination.state);
+        let ships_on = order_ship_date(placed, &warehouses, &stock, &ship_to, lines);
+        let transit = ShippingZoneService::transit_for(db, mid, destination).await?;
+
+        Ok(DeliveryEstimate {
+            ships_on,
+            windows: transit.iter().map(|transit| DeliveryWindow::new(ships_on, transit)).collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use commercerack_core::Timestamp;
+
+    fn at(day: u32, hour: u32) -> NaiveDateTime {
+        // November 2025: the 17th is a Monday
+        NaiveDate::from_ymd_opt(2025, 11, day).unwrap().and_hms_opt(hour, 30, 0).unwrap()
+    }
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 11, day).unwrap()
+    }
+
+    fn warehouse(id: i32, state: &str, handling_days: i32, cutoff_hour: i32) -> Warehouse {
+        Warehouse {
+            id,
+            mid: 1,
+            code: format!("W{}", id),
+            name: format!("Warehouse {}", id),
+            country: "US".to_string(),
+            state: state.to_string(),
+            priority: id,
+            active: true,
+            handling_days,
+            cutoff_hour,
+            created_gmt: Timestamp::EPOCH,
+        }
+    }
+
+    fn stock(warehouse_id: i32, sku: &str, quantity: i32) -> WarehouseStock {
+        WarehouseStock {
+            id: warehouse_id,
+            mid: 1,
+            warehouse_id,
+            sku: sku.to_string(),
+            quantity,
+            in_transit: 0,
+        }
+    }
+
+    #[test]
+    fn test_ship_date_respects_cutoff_and_weekends() {
+        let same_day = Handling { days: 0, cutoff_hour: 14 };
+        assert_eq!(ship_date(at(17, 9), same_day), date(17));
+        assert_eq!(ship_date(at(17, 14), same_day), date(18));
+        // Friday after the cutoff and Saturday both ship on Monday
+        assert_eq!(ship_date(at(21, 16), same_day), date(24));
+        assert_eq!(ship_date(at(22, 9), same_day), date(24));
+
+        let two_days = Handling { days: 2, cutoff_hour: 12 };
+        assert_eq!(ship_date(at(20, 11), two_days), date(24));
+        assert_eq!(ship_date(at(20, 12), two_days), date(25));
+
+        let transit = ShippingTransitTime {
+            id: 1,
+            mid: 1,
+            zone_id: 1,
+            method: "ground".to_string(),
+            min_days: 3,
+            max_days: 5,
+            created_gmt: Timestamp::EPOCH,
+        };
+        let window = DeliveryWindow::new(date(20), &transit);
+        assert_eq!((window.earliest, window.latest), (date(25), date(27)));
+    }
+
+    #[test]
+    fn test_order_ships_with_its_slowest_unit() {
+        let warehouses = [warehouse(1, "OR", 0, 15), warehouse(2, "NY", 3, 15)];
+        let stock = [stock(1, "A", 2), stock(2, "A", 10), stock(2, "B", 10)];
+        let portland = ShipTo::new("US", "OR");
+
+        let lines = [("A".to_string(), 2)];
+        assert_eq!(order_ship_date(at(17, 9), &warehouses, &stock, &portland, &lines), date(17));
+
+        // The third unit comes from the slower warehouse
+        let lines = [("A".to_string(), 3)];
+        assert_eq!(order_ship_date(at(17, 9), &warehouses, &stock, &portland, &lines), date(20));
+
+        // Units nobody holds ship on the default handling time
+        let lines = [("C".to_string(), 1)];
+        assert_eq!(order_ship_date(at(17, 9), &warehouses, &stock, &portland, &lines), date(18));
+        assert_eq!(order_ship_date(at(17, 9), &[], &[], &portland, &[]), date(18));
+    }
+}
@@ -0,0 +1,212 @@
+//! Shipping label purchase
+//!
+//! `LabelProvider` is the seam to label services in the style of Shippo or
+//! EasyPost: buy a label for a parcel, void a label that was never used,
+//! and follow a shipment's tracking. The origin address is the one on the
+//! merchant's account with the service, so requests only carry where the
+//! parcel goes. [`StubLabelProvider`] issues labels without calling anyone,
+//! for development and tests.
+
+use async_trait::async_trait;
+use commercerack_core::Timestamp;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::ShippingError;
+
+/// Where a label sends the parcel
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LabelAddress {
+    pub name: String,
+    pub street1: String,
+    pub street2: String,
+    pub city: String,
+    pub state: String,
+    pub zip: String,
+    /// ISO 3166-1 alpha-2 code
+    pub country: String,
+}
+
+/// A label to buy
+#[derive(Debug, Clone, PartialEq)]
+pub struct LabelRequest {
+    pub mid: i32,
+    /// Printed on the label, e.g. the order number
+    pub reference: String,
+    /// Service level, e.g. `ground`
+    pub service: String,
+    pub ship_to: LabelAddress,
+    /// Parcel weight, in the merchant's weight unit
+    pub weight: Decimal,
+}
+
+/// A bought label
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Label {
+    /// The provider's reference, used to void the label
+    pub id: String,
+    /// Carrier that will move the parcel, e.g. `USPS`
+    pub carrier: String,
+    pub service: String,
+    pub tracking_number: String,
+    /// Printable label
+    pub label_url: String,
+    /// What the provider charged for the label
+    pub cost: Decimal,
+}
+
+/// Where a parcel is, as its carrier reports it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrackingStatus {
+    /// Label created, parcel not handed over yet
+    PreTransit,
+    InTransit,
+    OutForDelivery,
+    Delivered,
+    Returned,
+    Failure,
+    Unknown,
+}
+
+impl TrackingStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TrackingStatus::PreTransit => "pre_transit",
+            TrackingStatus::InTransit => "in_transit",
+            TrackingStatus::OutForDelivery => "out_for_delivery",
+            TrackingStatus::Delivered => "delivered",
+            TrackingStatus::Returned => "returned",
+            TrackingStatus::Failure => "failure",
+            TrackingStatus::Unknown => "unknown",
+        }
+    }
+}
+
+/// Latest tracking of a parcel
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tracking {
+    pub status: TrackingStatus,
+    /// Carrier's description of the latest scan
+    pub detail: String,
+    /// When the latest scan happened; `None` before the first
+    pub updated_gmt: Option<Timestamp>,
+}
+
+/// A label service
+#[async_trait]
+pub trait LabelProvider: Send + Sync {
+    /// Short identifier stored on shipments, e.g. `shippo`
+    fn name(&self) -> &'static str;
+
+    /// Buy a label
+    async fn buy(&self, request: &LabelRequest) -> Result<Label, ShippingError>;
+
+    /// Void a label that was not used, refunding its cost
+    async fn void(&self, label_id: &str) -> Result<(), ShippingError>;
+
+    /// Latest tracking of a parcel the carrier is moving
+    async fn track(&self, carrier: &str, tracking_number: &str) -> Result<Tracking, ShippingError>;
+}
+
+/// Charged by the stub for every label
+pub const STUB_LABEL_BASE: Decimal = Decimal::from_parts(500, 0, 0, false, 2);
+
+/// Charged by the stub per unit of weight on top of the base
+pub const STUB_LABEL_PER_WEIGHT: Decimal = Decimal::from_parts(50, 0, 0, false, 2);
+
+const STUB_CARRIER: &str = "STUB";
+
+/// Provider issuing labels that cannot be printed or shipped with.
+/// Tracking numbers start with `STUB` and stay in pre-transit.
+#[derive(Default)]
+pub struct StubLabelProvider {
+    issued: AtomicU32,
+}
+
+impl StubLabelProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl LabelProvider for StubLabelProvider {
+    fn name(&self) -> &'static str {
+        "stub"
+    }
+
+    async fn buy(&self, request: &LabelRequest) -> Result<Label, ShippingError> {
+        let n = self.issued.fetch_add(1, Ordering::Relaxed) + 1;
+        let tracking_number = format!("{}{}{:04}", STUB_CARRIER, Timestamp::now().unix(), n % 10_000);
+        Ok(Label {
+            id: format!("stub_{}", tracking_number),
+            carrier: STUB_CARRIER.to_string(),
+            service: request.service.clone(),
+            label_url: format!("https://labels.invalid/{}.pdf", tracking_number),
+            tracking_number,
+            cost: (STUB_LABEL_BASE + STUB_LABEL_PER_WEIGHT * request.weight).round_dp(2),
+        })
+    }
+
+    async fn void(&self, label_id: &str) -> Result<(), ShippingError> {
+        if !label_id.starts_with("stub_") {
+            return Err(ShippingError::Provider(format!("unknown label {}", label_id)));
+        }
+        Ok(())
+    }
+
+    async fn track(&self, carrier: &str, tracking_number: &str) -> Result<Tracking, ShippingError> {
+        if carrier != STUB_CARRIER || !tracking_number.starts_with(STUB_CARRIER) {
+            return Ok(Tracking { status: TrackingStatus::Unknown, detail: String::new(), updated_gmt: None });
+        }
+        Ok(Tracking {
+            status: TrackingStatus::PreTransit,
+            detail: "Label created".to_string(),
+            updated_gmt: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(weight: Decimal) -> LabelRequest {
+        LabelRequest {
+            mid: 1,
+            reference: "2025-11-18-ABCDEF12".to_string(),
+            service: "ground".to_string(),
+            ship_to: LabelAddress {
+                name: "Pat Doe".to_string(),
+                street1: "1 Main St".to_string(),
+                street2: String::new(),
+                city: "Portland".to_string(),
+                state: "OR".to_string(),
+                zip: "97201".to_string(),
+                country: "US".to_string(),
+            },
+            weight,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stub_labels_round_trip() {
+        let provider = StubLabelProvider::new();
+        let first = provider.buy(&request(Decimal::from(3))).await.unwrap();
+        let second = provider.buy(&request(Decimal::ZERO)).await.unwrap();
+
+        assert_ne!(first.tracking_number, second.tracking_number);
+        assert_eq!(first.cost, Decimal::new(650, 2));
+        assert_eq!(second.cost, Decimal::new(500, 2));
+        assert_eq!(first.service, "ground");
+
+        let tracking = provider.track(&first.carrier, &first.tracking_number).await.unwrap();
+        assert_eq!(tracking.status, TrackingStatus::PreTransit);
+        assert_eq!(provider.track("UPS", "1Z999").await.unwrap().status, TrackingStatus::Unknown);
+
+        assert!(provider.void(&first.id).await.is_ok());
+        assert!(matches!(provider.void("shp_123").await, Err(ShippingError::Provider(_))));
+    }
+}
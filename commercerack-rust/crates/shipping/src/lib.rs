@@ -1 +1,213 @@
-fn main() {}
+//! Shipping rate quotes
+//!
+//! `ShippingRateProvider` is the seam between checkout and anything that
+//! prices shipping: the built-in [`TableRateProvider`] reads the merchant's
+//! zones and rate tiers, and carrier integrations implement the same trait.
+//! Quotes from every configured provider are offered side by side; the
+//! shopper's choice is re-quoted at checkout rather than trusted. Labels
+//! for shipments are bought through a [`LabelProvider`], and
+//! [`DeliveryService`] estimates when an order arrives. Orders stocked in
+//! several warehouses are [split] into shipments quoted one by one.
+
+use async_trait::async_trait;
+use commercerack_cart::Cart;
+use commercerack_geo::{Address, ZoneError};
+use rust_decimal::Decimal;
+use sea_orm::*;
+use ::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use thiserror::Error;
+
+pub mod delivery;
+pub mod labels;
+pub mod split;
+pub mod table;
+
+pub use delivery::{DeliveryEstimate, DeliveryService, DeliveryWindow};
+pub use labels::{Label, LabelAddress, LabelProvider, LabelRequest, StubLabelProvider, Tracking, TrackingStatus};
+pub use split::{PlannedShipment, QuotedShipment};
+pub use table::{
+    RateBasis, ShippingRateInput, ShippingTransitInput, ShippingZoneInput, ShippingZoneService, TableRateProvider,
+};
+
+#[derive(Error, Debug)]
+pub enum ShippingError {
+    #[error("Shipping zone not found")]
+    ZoneNotFound,
+
+    #[error("Shipping rate not found")]
+    RateNotFound,
+
+    #[error("Transit time not found")]
+    TransitNotFound,
+
+    #[error("Zone {0} not found")]
+    UnknownZone(i32),
+
+    #[error("Shipping method {0} is not available for this destination")]
+    MethodUnavailable(String),
+
+    #[error("Carrier error: {0}")]
+    Provider(String),
+
+    #[error(transparent)]
+    Zone(#[from] ZoneError),
+
+    #[error("Database error: {0}")]
+    Db(#[from] DbErr),
+}
+
+/// Where a shipment goes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Destination {
+    pub country: String,
+    pub state: String,
+    pub zip: String,
+}
+
+impl Destination {
+    /// Codes are compared case-insensitively, so they are kept uppercase
+    pub fn new(country: &str, state: &str, zip: &str) -> Self {
+        Self {
+            country: country.trim().to_uppercase(),
+            state: state.trim().to_uppercase(),
+            zip: zip.trim().to_uppercase(),
+        }
+    }
+}
+
+impl From<&Destination> for Address {
+    fn from(destination: &Destination) -> Self {
+        Address {
+            country: destination.country.clone(),
+            state: destination.state.clone(),
+            zip: destination.zip.clone(),
+        }
+    }
+}
+
+/// What is being shipped
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Parcel {
+    /// Merchandise subtotal before discounts
+    pub subtotal: Decimal,
+    /// Total weight, in the merchant's weight unit
+    pub weight: Decimal,
+}
+
+impl Parcel {
+    /// The parcel for a cart, weighing its items by their SKUs. Items whose
+    /// SKU is unknown weigh nothing.
+    pub async fn for_cart(db: &DatabaseConnection, mid: i32, cart: &Cart) -> Result<Self, ShippingError> {
+        let skus = Skus::find()
+            .filter(::entity::skus::Column::Mid.eq(mid))
+            .filter(::entity::skus::Column::Sku.is_in(cart.items.iter().map(|item| item.sku.clone())))
+            .all(db)
+            .await?;
+
+        let weight = cart
+            .items
+            .iter()
+            .filter_map(|item| {
+                skus.iter()
+                    .find(|sku| sku.sku == item.sku)
+                    .map(|sku| sku.weight * Decimal::from(item.quantity))
+            })
+            .sum();
+
+        Ok(Self {
+            subtotal: cart.subtotal().amount(),
+            weight,
+        })
+    }
+
+    /// The parcel for some units of a cart's items, such as one shipment's:
+    /// `lines` of `(sku, quantity)`, priced at what the cart charges on
+    /// average for a unit of each SKU
+    pub async fn for_lines(
+        db: &DatabaseConnection,
+        mid: i32,
+        cart: &Cart,
+        lines: &[(String, i32)],
+    ) -> Result<Self, ShippingError> {
+        let skus = Skus::find()
+            .filter(::entity::skus::Column::Mid.eq(mid))
+            .filter(::entity::skus::Column::Sku.is_in(lines.iter().map(|(sku, _)| sku.clone())))
+            .all(db)
+            .await?;
+
+        let mut parcel = Self { subtotal: Decimal::ZERO, weight: Decimal::ZERO };
+        for (sku, quantity) in lines {
+            let (amount, units) = cart
+                .items
+                .iter()
+                .filter(|item| &item.sku == sku)
+                .fold((Decimal::ZERO, 0), |(amount, units), item| (amount + item.subtotal(), units + item.quantity));
+            if units > 0 {
+                parcel.subtotal += amount * Decimal::from(*quantity) / Decimal::from(units);
+            }
+            if let Some(record) = skus.iter().find(|record| &record.sku == sku) {
+                parcel.weight += record.weight * Decimal::from(*quantity);
+            }
+        }
+        Ok(parcel)
+    }
+}
+
+/// One way to ship a parcel, and what it costs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShippingQuote {
+    /// Provider that priced it, e.g. `table`
+    pub carrier: String,
+    /// Code stored on the order, e.g. `ground`
+    pub method: String,
+    pub name: String,
+    pub amount: Decimal,
+}
+
+/// A source of shipping rates
+#[async_trait]
+pub trait ShippingRateProvider: Send + Sync {
+    /// Short identifier, e.g. `table`
+    fn name(&self) -> &'static str;
+
+    /// Ways to ship `parcel` to `destination`. No quotes means the provider
+    /// does not ship there.
+    async fn quote(
+        &self,
+        mid: i32,
+        destination: &Destination,
+        parcel: &Parcel,
+    ) -> Result<Vec<ShippingQuote>, ShippingError>;
+}
+
+/// Quotes from every provider, cheapest first
+pub async fn quote_all(
+    providers: &[Arc<dyn ShippingRateProvider>],
+    mid: i32,
+    destination: &Destination,
+    parcel: &Parcel,
+) -> Result<Vec<ShippingQuote>, ShippingError> {
+    let mut quotes = Vec::new();
+    for provider in providers {
+        quotes.extend(provider.quote(mid, destination, parcel).await?);
+    }
+    quotes.sort_by_key(|quote| quote.amount);
+    Ok(quotes)
+}
+
+/// Re-quote a method the shopper chose earlier
+pub async fn quote_method(
+    providers: &[Arc<dyn ShippingRateProvider>],
+    mid: i32,
+    destination: &Destination,
+    parcel: &Parcel,
+    method: &str,
+) -> Result<ShippingQuote, ShippingError> {
+    quote_all(providers, mid, destination, parcel)
+        .await?
+        .into_iter()
+        .find(|quote| quote.method == method)
+        .ok_or_else(|| ShippingError::MethodUnavailable(method.to_string()))
+}
@@ -0,0 +1,159 @@
+//! Splitting orders into shipments
+//!
+//! An order's units ship from the warehouses they would be allocated from
+//! right now (see `commercerack_inventory::warehouses`), so a cart stocked
+//! in several warehouses leaves as several parcels. [`split`] groups an
+//! order's lines by warehouse; each group is weighed and quoted on its own
+//! and may ship by a different method. Units no warehouse holds, and every
+//! unit of a merchant without warehouses, make up one more group with no
+//! warehouse.
+
+use commercerack_inventory::warehouses::{allocation_plan, strategy, ShipTo};
+use sea_orm::ConnectionTrait;
+use ::entity::prelude::{Warehouse, WarehouseStock};
+use serde::{Deserialize, Serialize};
+
+use crate::delivery::stocked_warehouses;
+use crate::{Destination, ShippingError, ShippingQuote};
+
+/// Units of an order that leave together
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlannedShipment {
+    /// Warehouse they ship from; `None` for units no warehouse holds
+    pub warehouse_id: Option<i32>,
+    /// `(sku, quantity)`, in the order's line order
+    pub lines: Vec<(String, i32)>,
+}
+
+impl PlannedShipment {
+    fn add(shipments: &mut Vec<PlannedShipment>, warehouse_id: Option<i32>, sku: &str, quantity: i32) {
+        let index = match shipments.iter().position(|shipment| shipment.warehouse_id == warehouse_id) {
+            Some(index) => index,
+            None => {
+                shipments.push(PlannedShipment { warehouse_id, lines: Vec::new() });
+                shipments.len() - 1
+            }
+        };
+        let lines = &mut shipments[index].lines;
+        match lines.iter_mut().find(|(line_sku, _)| line_sku == sku) {
+            Some((_, units)) => *units += quantity,
+            None => lines.push((sku.to_string(), quantity)),
+        }
+    }
+}
+
+/// A planned shipment and the quote for the method it ships by
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuotedShipment {
+    pub shipment: PlannedShipment,
+    pub quote: ShippingQuote,
+}
+
+/// Group `lines` of `(sku, quantity)` into shipments by the warehouse each
+/// unit would be allocated from, warehouses by ID and the group with no
+/// warehouse last. Lines for the same SKU are allocated together.
+pub fn split(
+    warehouses: &[Warehouse],
+    stock: &[WarehouseStock],
+    ship_to: &ShipTo,
+    lines: &[(String, i32)],
+) -> Vec<PlannedShipment> {
+    let mut merged: Vec<(String, i32)> = Vec::new();
+    for (sku, quantity) in lines {
+        match merged.iter_mut().find(|(merged_sku, _)| merged_sku == sku) {
+            Some((_, units)) => *units += quantity,
+            None => merged.push((sku.clone(), *quantity)),
+        }
+    }
+
+    let mut shipments = Vec::new();
+    for (sku, quantity) in &merged {
+        let held: Vec<WarehouseStock> = stock.iter().filter(|row| &row.sku == sku).cloned().collect();
+        let plan = allocation_plan(strategy(), warehouses, &held, Some(ship_to), *quantity);
+        for &(warehouse_id, take) in &plan {
+            PlannedShipment::add(&mut shipments, Some(warehouse_id), sku, take);
+        }
+        let planned: i32 = plan.iter().map(|(_, take)| take).sum();
+        if planned < *quantity {
+            PlannedShipment::add(&mut shipments, None, sku, quantity - planned);
+        }
+    }
+    shipments.sort_by_key(|shipment| (shipment.warehouse_id.is_none(), shipment.warehouse_id));
+    shipments
+}
+
+/// The shipments an order for `lines` of `(sku, quantity)` to `destination`
+/// would leave in
+pub async fn plan<C: ConnectionTrait>(
+    db: &C,
+    mid: i32,
+    lines: &[(String, i32)],
+    destination: &Destination,
+) -> Result<Vec<PlannedShipment>, ShippingError> {
+    let (warehouses, stock) = stocked_warehouses(db, mid, lines).await?;
+    let ship_to = ShipTo::new(&destination.country, &destination.state);
+    Ok(split(&warehouses, &stock, &ship_to, lines))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use commercerack_core::Timestamp;
+
+    fn warehouse(id: i32, state: &str) -> Warehouse {
+        Warehouse {
+            id,
+            mid: 1,
+            code: format!("W{}", id),
+            name: format!("Warehouse {}", id),
+            country: "US".to_string(),
+            state: state.to_string(),
+            priority: 0,
+            active: true,
+            handling_days: 1,
+            cutoff_hour: 14,
+            created_gmt: Timestamp::EPOCH,
+        }
+    }
+
+    fn stock(warehouse_id: i32, sku: &str, quantity: i32) -> WarehouseStock {
+        WarehouseStock {
+            id: warehouse_id * 10,
+            mid: 1,
+            warehouse_id,
+            sku: sku.to_string(),
+            quantity,
+            in_transit: 0,
+        }
+    }
+
+    fn lines(lines: &[(&str, i32)]) -> Vec<(String, i32)> {
+        lines.iter().map(|(sku, quantity)| (sku.to_string(), *quantity)).collect()
+    }
+
+    #[test]
+    fn test_split_groups_units_by_warehouse() {
+        let warehouses = [warehouse(1, "OR"), warehouse(2, "NY")];
+        let stock = [stock(1, "A", 2), stock(2, "A", 5), stock(2, "B", 5)];
+        let portland = ShipTo::new("US", "OR");
+
+        // Everything one warehouse holds ships together
+        let one = split(&warehouses, &stock, &portland, &lines(&[("B", 3)]));
+        assert_eq!(one, vec![PlannedShipment { warehouse_id: Some(2), lines: lines(&[("B", 3)]) }]);
+
+        // Two lines of A are allocated as one; the nearest warehouse runs out
+        let many = split(&warehouses, &stock, &portland, &lines(&[("A", 2), ("B", 1), ("A", 1), ("C", 4)]));
+        assert_eq!(
+            many,
+            vec![
+                PlannedShipment { warehouse_id: Some(1), lines: lines(&[("A", 2)]) },
+                PlannedShipment { warehouse_id: Some(2), lines: lines(&[("A", 1), ("B", 1)]) },
+                PlannedShipment { warehouse_id: None, lines: lines(&[("C", 4)]) },
+            ]
+        );
+
+        // Without warehouses the whole order is one shipment
+        let all = split(&[], &[], &portland, &lines(&[("A", 1), ("B", 2)]));
+        assert_eq!(all, vec![PlannedShipment { warehouse_id: None, lines: lines(&[("A", 1), ("B", 2)]) }]);
+    }
+}
@@ -0,0 +1,519 @@
+//! Merchant-managed zones and table rates
+//!
+//! A zone is a set of countries, optionally narrowed to states and zip
+//! prefixes, or covers a merchant-defined geo zone instead; a destination
+//! belongs to the first matching zone by position.
+//! Each rate row of a zone is one tier of a method: flat rates always match,
+//! weight and price rates match when the parcel's weight or subtotal falls
+//! in `[min_value, max_value)`. The first matching tier of each method wins.
+//! A zone may also record how many business days each method's carrier
+//! takes to deliver, for [delivery estimates](crate::delivery).
+
+use async_trait::async_trait;
+use commercerack_core::Timestamp;
+use commercerack_geo::ZoneService;
+use rust_decimal::Decimal;
+use sea_orm::*;
+use ::entity::prelude::*;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::{Destination, Parcel, ShippingError, ShippingQuote, ShippingRateProvider};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateBasis {
+    Flat,
+    Weight,
+    Price,
+}
+
+impl RateBasis {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RateBasis::Flat => "flat",
+            RateBasis::Weight => "weight",
+            RateBasis::Price => "price",
+        }
+    }
+}
+
+impl fmt::Display for RateBasis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for RateBasis {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "flat" => Ok(RateBasis::Flat),
+            "weight" => Ok(RateBasis::Weight),
+            "price" => Ok(RateBasis::Price),
+            other => Err(format!("unknown rate basis {}", other)),
+        }
+    }
+}
+
+/// Settings of a new zone
+#[derive(Debug, Clone)]
+pub struct ShippingZoneInput {
+    pub name: String,
+    pub countries: Vec<String>,
+    pub states: Vec<String>,
+    pub zips: Vec<String>,
+    pub position: i32,
+    /// Geo zone to cover instead of the lists above
+    pub zone_id: Option<i32>,
+}
+
+/// Settings of a new rate tier
+#[derive(Debug, Clone)]
+pub struct ShippingRateInput {
+    pub method: String,
+    pub name: String,
+    pub basis: RateBasis,
+    pub min_value: Decimal,
+    pub max_value: Option<Decimal>,
+    pub amount: Decimal,
+}
+
+/// Business days a method's carrier takes within a zone
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShippingTransitInput {
+    pub min_days: i32,
+    pub max_days: i32,
+}
+
+fn split(list: &str) -> impl Iterator<Item = &str> {
+    list.split(',').map(str::trim).filter(|s| !s.is_empty())
+}
+
+fn join(values: &[String]) -> String {
+    values
+        .iter()
+        .map(|v| v.trim().to_uppercase())
+        .filter(|v| !v.is_empty())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Whether `destination` lies in `zone`, given the IDs of the geo zones it
+/// lies in
+pub fn zone_matches(zone: &ShippingZone, destination: &Destination, geo_zones: &[i32]) -> bool {
+    if let Some(zone_id) = zone.zone_id {
+        return geo_zones.contains(&zone_id);
+    }
+    split(&zone.countries).any(|country| country == destination.country)
+        && (zone.states.is_empty() || split(&zone.states).any(|state| state == destination.state))
+        && (zone.zips.is_empty() || split(&zone.zips).any(|zip| destination.zip.starts_with(zip)))
+}
+
+/// Whether a rate tier applies to `parcel`
+pub fn tier_matches(rate: &ShippingRate, parcel: &Parcel) -> bool {
+    let value = match rate.basis.parse() {
+        Ok(RateBasis::Flat) => return true,
+        Ok(RateBasis::Weight) => parcel.weight,
+        Ok(RateBasis::Price) => parcel.subtotal,
+        Err(_) => return false,
+    };
+    value >= rate.min_value && rate.max_value.is_none_or(|max| value < max)
+}
+
+/// One quote per method of the zone's rates, using each method's first matching tier
+pub fn quotes_for(carrier: &str, rates: &[ShippingRate], parcel: &Parcel) -> Vec<ShippingQuote> {
+    let mut quotes: Vec<ShippingQuote> = Vec::new();
+    for rate in rates.iter().filter(|rate| tier_matches(rate, parcel)) {
+        if quotes.iter().any(|quote| quote.method == rate.method) {
+            continue;
+        }
+        quotes.push(ShippingQuote {
+            carrier: carrier.to_string(),
+            method: rate.method.clone(),
+            name: rate.name.clone(),
+            amount: rate.amount,
+        });
+    }
+    quotes
+}
+
+/// Shipping zone service for managing zones and their rate tiers
+pub struct ShippingZoneService;
+
+impl ShippingZoneService {
+    /// Create a zone without rates
+    pub async fn create_zone(
+        db: &DatabaseConnection,
+        mid: i32,
+        input: ShippingZoneInput,
+    ) -> Result<ShippingZone, ShippingError> {
+        if let Some(zone_id) = input.zone_id {
+            ZoneService::find(db, mid, zone_id).await?.ok_or(ShippingError::UnknownZone(zone_id))?;
+        }
+
+        let zone = ::entity::shipping_zones::ActiveModel {
+            mid: Set(mid),
+            name: Set(input.name),
+            countries: Set(join(&input.countries)),
+            states: Set(join(&input.states)),
+            zips: Set(join(&input.zips)),
+            position: Set(input.position),
+            zone_id: Set(input.zone_id),
+            created_gmt: Set(Timestamp::now()),
+            ..Default::default()
+        };
+
+        Ok(zone.insert(db).await?)
+    }
+
+    /// A merchant's zones in matching order, each with its rates and
+    /// transit times
+    pub async fn list_zones(
+        db: &DatabaseConnection,
+        mid: i32,
+    ) -> Result<Vec<(ShippingZone, Vec<ShippingRate>, Vec<ShippingTransitTime>)>, ShippingError> {
+        let zones = Self::zones(db, mid).await?;
+        let mut rates: HashMap<i32, Vec<ShippingRate>> = HashMap::new();
+        for rate in ShippingRates::find()
+            .filter(::entity::shipping_rates::Column::Mid.eq(mid))
+            .order_by_asc(::entity::shipping_rates::Column::Id)
+            .all(db)
+            .await?
+        {
+            rates.entry(rate.zone_id).or_default().push(rate);
+        }
+        let mut transit: HashMap<i32, Vec<ShippingTransitTime>> = HashMap::new();
+        for row in ShippingTransitTimes::find()
+            .filter(::entity::shipping_transit_times::Column::Mid.eq(mid))
+            .order_by_asc(::entity::shipping_transit_times::Column::Method)
+            .all(db)
+            .await?
+        {
+            transit.entry(row.zone_id).or_default().push(row);
+        }
+
+        Ok(zones
+            .into_iter()
+            .map(|zone| {
+                let zone_rates = rates.remove(&zone.id).unwrap_or_default();
+                let zone_transit = transit.remove(&zone.id).unwrap_or_default();
+                (zone, zone_rates, zone_transit)
+            })
+            .collect())
+    }
+
+    /// Delete a zone and its rates. Returns whether it existed.
+    pub async fn delete_zone(
+        db: &DatabaseConnection,
+        mid: i32,
+        id: i32,
+    ) -> Result<bool, ShippingError> {
+        let result = ShippingZones::delete_many()
+            .filter(::entity::shipping_zones::Column::Mid.eq(mid))
+            .filter(::entity::shipping_zones::Column::Id.eq(id))
+            .exec(db)
+            .await?;
+
+        Ok(result.rows_affected > 0)
+    }
+
+    async fn find_zone(db: &DatabaseConnection, mid: i32, zone_id: i32) -> Result<ShippingZone, ShippingError> {
+        ShippingZones::find()
+            .filter(::entity::shipping_zones::Column::Mid.eq(mid))
+            .filter(::entity::shipping_zones::Column::Id.eq(zone_id))
+            .one(db)
+            .await?
+            .ok_or(ShippingError::ZoneNotFound)
+    }
+
+    /// Add a rate tier to a zone. Tiers are tried in the order they were added.
+    pub async fn add_rate(
+        db: &DatabaseConnection,
+        mid: i32,
+        zone_id: i32,
+        input: ShippingRateInput,
+    ) -> Result<ShippingRate, ShippingError> {
+        Self::find_zone(db, mid, zone_id).await?;
+
+        let rate = ::entity::shipping_rates::ActiveModel {
+            mid: Set(mid),
+            zone_id: Set(zone_id),
+            method: Set(input.method),
+            name: Set(input.name),
+            basis: Set(input.basis.to_string()),
+            min_value: Set(input.min_value),
+            max_value: Set(input.max_value),
+            amount: Set(input.amount),
+            created_gmt: Set(Timestamp::now()),
+            ..Default::default()
+        };
+
+        Ok(rate.insert(db).await?)
+    }
+
+    /// Delete a rate tier. Returns whether it existed.
+    pub async fn delete_rate(
+        db: &DatabaseConnection,
+        mid: i32,
+        id: i32,
+    ) -> Result<bool, ShippingError> {
+        let result = ShippingRates::delete_many()
+            .filter(::entity::shipping_rates::Column::Mid.eq(mid))
+            .filter(::entity::shipping_rates::Column::Id.eq(id))
+            .exec(db)
+            .await?;
+
+        Ok(result.rows_affected > 0)
+    }
+
+    /// Record how long a method takes within a zone, replacing what was
+    /// recorded before
+    pub async fn set_transit(
+        db: &DatabaseConnection,
+        mid: i32,
+        zone_id: i32,
+        method: &str,
+        input: ShippingTransitInput,
+    ) -> Result<ShippingTransitTime, ShippingError> {
+        Self::find_zone(db, mid, zone_id).await?;
+
+        let existing = ShippingTransitTimes::find()
+            .filter(::entity::shipping_transit_times::Column::ZoneId.eq(zone_id))
+            .filter(::entity::shipping_transit_times::Column::Method.eq(method))
+            .one(db)
+            .await?;
+        let transit = match existing {
+            Some(existing) => {
+                let mut transit: ::entity::shipping_transit_times::ActiveModel = existing.into();
+                transit.min_days = Set(input.min_days);
+                transit.max_days = Set(input.max_days);
+                transit.update(db).await?
+            }
+            None => {
+                ::entity::shipping_transit_times::ActiveModel {
+                    mid: Set(mid),
+                    zone_id: Set(zone_id),
+                    method: Set(method.to_string()),
+                    min_days: Set(input.min_days),
+                    max_days: Set(input.max_days),
+                    created_gmt: Set(Timestamp::now()),
+                    ..Default::default()
+                }
+                .insert(db)
+                .await?
+            }
+        };
+
+        Ok(transit)
+    }
+
+    /// Forget how long a method takes within a zone. Returns whether it
+    /// was recorded.
+    pub async fn delete_transit(
+        db: &DatabaseConnection,
+        mid: i32,
+        zone_id: i32,
+        method: &str,
+    ) -> Result<bool, ShippingError> {
+        let result = ShippingTransitTimes::delete_many()
+            .filter(::entity::shipping_transit_times::Column::Mid.eq(mid))
+            .filter(::entity::shipping_transit_times::Column::ZoneId.eq(zone_id))
+            .filter(::entity::shipping_transit_times::Column::Method.eq(method))
+            .exec(db)
+            .await?;
+
+        Ok(result.rows_affected > 0)
+    }
+
+    /// Transit times of the zone a destination belongs to; none when it
+    /// belongs to no zone
+    pub async fn transit_for(
+        db: &DatabaseConnection,
+        mid: i32,
+        destination: &Destination,
+    ) -> Result<Vec<ShippingTransitTime>, ShippingError> {
+        let Some(zone) = Self::zone_for(db, mid, destination).await? else {
+            return Ok(Vec::new());
+        };
+
+        let transit = ShippingTransitTimes::find()
+            .filter(::entity::shipping_transit_times::Column::Mid.eq(mid))
+            .filter(::entity::shipping_transit_times::Column::ZoneId.eq(zone.id))
+            .order_by_asc(::entity::shipping_transit_times::Column::Method)
+            .all(db)
+            .await?;
+
+        Ok(transit)
+    }
+
+    /// The zone a destination belongs to, if any
+    pub async fn zone_for(
+        db: &DatabaseConnection,
+        mid: i32,
+        destination: &Destination,
+    ) -> Result<Option<ShippingZone>, ShippingError> {
+        let zones = Self::zones(db, mid).await?;
+        let geo_zones: Vec<i32> = if zones.iter().any(|zone| zone.zone_id.is_some()) {
+            ZoneService::matching(db, mid, &destination.into())
+                .await?
+                .iter()
+                .map(|zone| zone.id)
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        Ok(zones.into_iter().find(|zone| zone_matches(zone, destination, &geo_zones)))
+    }
+
+    async fn zones(db: &DatabaseConnection, mid: i32) -> Result<Vec<ShippingZone>, ShippingError> {
+        let zones = ShippingZones::find()
+            .filter(::entity::shipping_zones::Column::Mid.eq(mid))
+            .order_by_asc(::entity::shipping_zones::Column::Position)
+            .order_by_asc(::entity::shipping_zones::Column::Id)
+            .all(db)
+            .await?;
+
+        Ok(zones)
+    }
+}
+
+/// Provider backed by the `shipping_zones` and `shipping_rates` tables
+pub struct TableRateProvider {
+    db: Arc<DatabaseConnection>,
+}
+
+impl TableRateProvider {
+    pub fn new(db: Arc<DatabaseConnection>) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl ShippingRateProvider for TableRateProvider {
+    fn name(&self) -> &'static str {
+        "table"
+    }
+
+    async fn quote(
+        &self,
+        mid: i32,
+        destination: &Destination,
+        parcel: &Parcel,
+    ) -> Result<Vec<ShippingQuote>, ShippingError> {
+        let Some(zone) = ShippingZoneService::zone_for(&self.db, mid, destination).await? else {
+            return Ok(Vec::new());
+        };
+
+        let rates = ShippingRates::find()
+            .filter(::entity::shipping_rates::Column::Mid.eq(mid))
+            .filter(::entity::shipping_rates::Column::ZoneId.eq(zone.id))
+            .order_by_asc(::entity::shipping_rates::Column::Id)
+            .all(self.db.as_ref())
+            .await?;
+
+        Ok(quotes_for(self.name(), &rates, parcel))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn zone(id: i32, countries: &str, states: &str, zips: &str) -> ShippingZone {
+        ShippingZone {
+            id,
+            mid: 1,
+            name: format!("Zone {}", id),
+            countries: countries.to_string(),
+            states: states.to_string(),
+            zips: zips.to_string(),
+            position: id,
+            zone_id: None,
+            created_gmt: Timestamp::EPOCH,
+        }
+    }
+
+    fn rate(id: i32, method: &str, basis: RateBasis, min: i64, max: Option<i64>, amount: i64) -> ShippingRate {
+        ShippingRate {
+            id,
+            mid: 1,
+            zone_id: 1,
+            method: method.to_string(),
+            name: method.to_string(),
+            basis: basis.to_string(),
+            min_value: Decimal::from(min),
+            max_value: max.map(Decimal::from),
+            amount: Decimal::new(amount, 2),
+            created_gmt: Timestamp::EPOCH,
+        }
+    }
+
+    #[test]
+    fn test_zone_matches() {
+        let west_coast = zone(1, "US", "CA,OR,WA", "");
+        let manhattan = zone(2, "US", "", "100,101,102");
+        let north_america = zone(3, "US,CA,MX", "", "");
+
+        let portland = Destination::new("us", "or", "97201");
+        assert!(zone_matches(&west_coast, &portland, &[]));
+        assert!(!zone_matches(&manhattan, &portland, &[]));
+
+        let new_york = Destination::new("US", "NY", "10012");
+        assert!(zone_matches(&manhattan, &new_york, &[]));
+        assert!(zone_matches(&north_america, &Destination::new("CA", "ON", "M5V"), &[]));
+        assert!(!zone_matches(&north_america, &Destination::new("GB", "", "SW1"), &[]));
+
+        // A zone covering a geo zone matches wherever that geo zone does
+        let bay_area = ShippingZone { zone_id: Some(7), ..zone(4, "", "", "") };
+        assert!(zone_matches(&bay_area, &portland, &[3, 7]));
+        assert!(!zone_matches(&bay_area, &portland, &[3]));
+    }
+
+    #[test]
+    fn test_first_matching_tier_per_method() {
+        let rates = vec![
+            rate(1, "ground", RateBasis::Weight, 0, Some(5), 599),
+            rate(2, "ground", RateBasis::Weight, 5, Some(20), 1299),
+            rate(3, "free", RateBasis::Price, 100, None, 0),
+            rate(4, "express", RateBasis::Flat, 0, None, 2499),
+        ];
+
+        let light = Parcel { subtotal: Decimal::from(40), weight: Decimal::new(45, 1) };
+        let quotes = quotes_for("table", &rates, &light);
+        let methods: Vec<_> = quotes.iter().map(|q| (q.method.as_str(), q.amount)).collect();
+        assert_eq!(methods, vec![("ground", Decimal::new(599, 2)), ("express", Decimal::new(2499, 2))]);
+
+        // Weight bounds are [min, max); heavier parcels fall off the table
+        let heavy = Parcel { subtotal: Decimal::from(150), weight: Decimal::from(5) };
+        let quotes = quotes_for("table", &rates, &heavy);
+        assert_eq!(quotes[0].amount, Decimal::new(1299, 2));
+        assert_eq!(quotes.len(), 3);
+
+        let oversize = Parcel { subtotal: Decimal::from(40), weight: Decimal::from(25) };
+        assert_eq!(quotes_for("table", &rates, &oversize).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_table_rate_provider() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([vec![zone(1, "US", "", "")]])
+            .append_query_results([vec![rate(1, "ground", RateBasis::Flat, 0, None, 799)]])
+            .into_connection();
+        let provider = TableRateProvider::new(Arc::new(db));
+
+        let parcel = Parcel { subtotal: Decimal::from(10), weight: Decimal::ONE };
+        let quotes = crate::quote_all(&[Arc::new(provider)], 1, &Destination::new("US", "TX", "73301"), &parcel)
+            .await
+            .unwrap();
+        assert_eq!(quotes, vec![ShippingQuote {
+            carrier: "table".to_string(),
+            method: "ground".to_string(),
+            name: "ground".to_string(),
+            amount: Decimal::new(799, 2),
+        }]);
+    }
+}
@@ -0,0 +1,95 @@
+//! Sales tax calculation
+//!
+//! `TaxCalculator` is the seam between checkout and whatever decides how
+//! much tax an order owes. The built-in [`RateTableCalculator`] looks rates
+//! up in the merchant's `tax_rates` table; an adapter for an external tax
+//! service implements the same trait. Each applicable rate becomes its own
+//! tax line so receipts can show e.g. state and county tax separately.
+
+use async_trait::async_trait;
+use commercerack_geo::{Address, ZoneError};
+use rust_decimal::Decimal;
+use sea_orm::DbErr;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+pub mod rates;
+
+pub use rates::{RateTableCalculator, TaxRateInput, TaxRateService};
+
+#[derive(Error, Debug)]
+pub enum TaxError {
+    #[error("Tax rate not found")]
+    NotFound,
+
+    #[error("Zone {0} not found")]
+    UnknownZone(i32),
+
+    #[error("Tax provider error: {0}")]
+    Provider(String),
+
+    #[error(transparent)]
+    Zone(#[from] ZoneError),
+
+    #[error("Database error: {0}")]
+    Db(#[from] DbErr),
+}
+
+/// Where goods are delivered, which decides the rates that apply
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaxAddress {
+    pub country: String,
+    pub state: String,
+    pub zip: String,
+}
+
+impl TaxAddress {
+    /// Codes are compared case-insensitively, so they are stored uppercase
+    pub fn new(country: &str, state: &str, zip: &str) -> Self {
+        Self {
+            country: country.trim().to_uppercase(),
+            state: state.trim().to_uppercase(),
+            zip: zip.trim().to_uppercase(),
+        }
+    }
+}
+
+impl From<&TaxAddress> for Address {
+    fn from(address: &TaxAddress) -> Self {
+        Address {
+            country: address.country.clone(),
+            state: address.state.clone(),
+            zip: address.zip.clone(),
+        }
+    }
+}
+
+/// One tax charged on an order
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaxLine {
+    pub name: String,
+    /// Percent, e.g. `7.25`
+    pub rate: Decimal,
+    pub amount: Decimal,
+}
+
+/// Sum of several tax lines
+pub fn total(lines: &[TaxLine]) -> Decimal {
+    lines.iter().map(|line| line.amount).sum()
+}
+
+/// A source of tax rates
+#[async_trait]
+pub trait TaxCalculator: Send + Sync {
+    /// Short identifier, e.g. `rate_table`
+    fn name(&self) -> &'static str;
+
+    /// Tax lines for `taxable` (the order subtotal after discounts) shipped
+    /// to `address`. No lines means no tax is due.
+    async fn calculate(
+        &self,
+        mid: i32,
+        address: &TaxAddress,
+        taxable: Decimal,
+    ) -> Result<Vec<TaxLine>, TaxError>;
+}
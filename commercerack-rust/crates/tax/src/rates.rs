@@ -0,0 +1,301 @@
+//! Merchant-managed tax rate table
+//!
+//! A rate applies to an address when its country matches, its state is
+//! empty or matches, and its zip is empty or a prefix of the address zip.
+//! Every applicable rate is charged, so a state rate and a county rate
+//! keyed on zip prefixes stack. A rate can instead cover a merchant-defined
+//! geo zone, and then applies wherever that zone does.
+
+use async_trait::async_trait;
+use commercerack_core::Timestamp;
+use commercerack_geo::ZoneService;
+use rust_decimal::Decimal;
+use sea_orm::*;
+use ::entity::prelude::*;
+use std::sync::Arc;
+
+use crate::{TaxAddress, TaxCalculator, TaxError, TaxLine};
+
+/// Settings of a tax rate being created or updated
+#[derive(Debug, Clone)]
+pub struct TaxRateInput {
+    pub name: String,
+    pub address: TaxAddress,
+    /// Percent, e.g. `7.25`
+    pub rate: Decimal,
+    /// Geo zone to cover instead of `address`
+    pub zone_id: Option<i32>,
+}
+
+/// Whether a rate applies to deliveries to `address`, given the IDs of the
+/// geo zones it lies in
+pub fn applies(rate: &TaxRate, address: &TaxAddress, geo_zones: &[i32]) -> bool {
+    if let Some(zone_id) = rate.zone_id {
+        return geo_zones.contains(&zone_id);
+    }
+    rate.country == address.country
+        && (rate.state.is_empty() || rate.state == address.state)
+        && address.zip.starts_with(rate.zip.as_str())
+}
+
+/// Tax lines for `taxable` from the rates that apply to `address`
+pub fn lines_for(rates: &[TaxRate], address: &TaxAddress, geo_zones: &[i32], taxable: Decimal) -> Vec<TaxLine> {
+    if taxable <= Decimal::ZERO {
+        return Vec::new();
+    }
+
+    rates
+        .iter()
+        .filter(|rate| applies(rate, address, geo_zones))
+        .map(|rate| TaxLine {
+            name: rate.name.clone(),
+            rate: rate.rate.normalize(),
+            amount: (taxable * rate.rate / Decimal::ONE_HUNDRED).round_dp(2),
+        })
+        .collect()
+}
+
+/// Tax rate service for managing a merchant's rate table
+pub struct TaxRateService;
+
+impl TaxRateService {
+    /// Add a rate
+    pub async fn create<C: ConnectionTrait>(
+        db: &C,
+        mid: i32,
+        input: TaxRateInput,
+    ) -> Result<TaxRate, TaxError> {
+        Self::check_zone(db, mid, input.zone_id).await?;
+
+        let now = Timestamp::now();
+        let rate = ::entity::tax_rates::ActiveModel {
+            mid: Set(mid),
+            name: Set(input.name),
+            country: Set(input.address.country),
+            state: Set(input.address.state),
+            zip: Set(input.address.zip),
+            rate: Set(input.rate),
+            zone_id: Set(input.zone_id),
+            created_gmt: Set(now),
+            modified_gmt: Set(now),
+            ..Default::default()
+        };
+
+        Ok(rate.insert(db).await?)
+    }
+
+    async fn check_zone<C: ConnectionTrait>(db: &C, mid: i32, zone_id: Option<i32>) -> Result<(), TaxError> {
+        if let Some(zone_id) = zone_id {
+            ZoneService::find(db, mid, zone_id).await?.ok_or(TaxError::UnknownZone(zone_id))?;
+        }
+        Ok(())
+    }
+
+    /// Find rate by ID
+    pub async fn find_by_id(
+        db: &DatabaseConnection,
+        mid: i32,
+        id: i32,
+    ) -> Result<Option<TaxRate>, TaxError> {
+        let rate = TaxRates::find()
+            .filter(::entity::tax_rates::Column::Mid.eq(mid))
+            .filter(::entity::tax_rates::Column::Id.eq(id))
+            .one(db)
+            .await?;
+
+        Ok(rate)
+    }
+
+    /// A merchant's rates, optionally only those of one country
+    pub async fn list(
+        db: &DatabaseConnection,
+        mid: i32,
+        country: Option<&str>,
+    ) -> Result<Vec<TaxRate>, TaxError> {
+        let mut query = TaxRates::find().filter(::entity::tax_rates::Column::Mid.eq(mid));
+        if let Some(country) = country {
+            query = query.filter(::entity::tax_rates::Column::Country.eq(country.trim().to_uppercase()));
+        }
+
+        let rates = query
+            .order_by_asc(::entity::tax_rates::Column::Country)
+            .order_by_asc(::entity::tax_rates::Column::State)
+            .order_by_asc(::entity::tax_rates::Column::Zip)
+            .order_by_asc(::entity::tax_rates::Column::Id)
+            .all(db)
+            .await?;
+
+        Ok(rates)
+    }
+
+    /// Replace a rate's settings
+    pub async fn update(
+        db: &DatabaseConnection,
+        rate: TaxRate,
+        input: TaxRateInput,
+    ) -> Result<TaxRate, TaxError> {
+        Self::check_zone(db, rate.mid, input.zone_id).await?;
+
+        let mut active: ::entity::tax_rates::ActiveModel = rate.into();
+        active.name = Set(input.name);
+        active.country = Set(input.address.country);
+        active.state = Set(input.address.state);
+        active.zip = Set(input.address.zip);
+        active.rate = Set(input.rate);
+        active.zone_id = Set(input.zone_id);
+        active.modified_gmt = Set(Timestamp::now());
+
+        Ok(active.update(db).await?)
+    }
+
+    /// Delete a rate. Returns whether it existed.
+    pub async fn delete(
+        db: &DatabaseConnection,
+        mid: i32,
+        id: i32,
+    ) -> Result<bool, TaxError> {
+        let result = TaxRates::delete_many()
+            .filter(::entity::tax_rates::Column::Mid.eq(mid))
+            .filter(::entity::tax_rates::Column::Id.eq(id))
+            .exec(db)
+            .await?;
+
+        Ok(result.rows_affected > 0)
+    }
+}
+
+/// Calculator backed by the `tax_rates` table
+pub struct RateTableCalculator {
+    db: Arc<DatabaseConnection>,
+}
+
+impl RateTableCalculator {
+    pub fn new(db: Arc<DatabaseConnection>) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl TaxCalculator for RateTableCalculator {
+    fn name(&self) -> &'static str {
+        "rate_table"
+    }
+
+    async fn calculate(
+        &self,
+        mid: i32,
+        address: &TaxAddress,
+        taxable: Decimal,
+    ) -> Result<Vec<TaxLine>, TaxError> {
+        if taxable <= Decimal::ZERO {
+            return Ok(Vec::new());
+        }
+
+        use ::entity::tax_rates::Column;
+
+        let rates = TaxRates::find()
+            .filter(Column::Mid.eq(mid))
+            .filter(Condition::any().add(Column::Country.eq(address.country.as_str())).add(Column::ZoneId.is_not_null()))
+            .order_by_asc(Column::Id)
+            .all(self.db.as_ref())
+            .await?;
+        let geo_zones: Vec<i32> = if rates.iter().any(|rate| rate.zone_id.is_some()) {
+            ZoneService::matching(self.db.as_ref(), mid, &address.into())
+                .await?
+                .iter()
+                .map(|zone| zone.id)
+                .collect()
+        } else {
+            Vec::new()
+        };
+        Ok(lines_for(&rates, address, &geo_zones, taxable))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rate(id: i32, name: &str, state: &str, zip: &str, rate: Decimal) -> TaxRate {
+        TaxRate {
+            id,
+            mid: 1,
+            name: name.to_string(),
+            country: "US".to_string(),
+            state: state.to_string(),
+            zip: zip.to_string(),
+            rate,
+            zone_id: None,
+            created_gmt: Timestamp::EPOCH,
+            modified_gmt: Timestamp::EPOCH,
+        }
+    }
+
+    fn table() -> Vec<TaxRate> {
+        vec![
+            rate(1, "CA State Tax", "CA", "", Decimal::new(72500, 4)),
+            rate(2, "LA County Tax", "CA", "900", Decimal::new(22500, 4)),
+            rate(3, "NY State Tax", "NY", "", Decimal::new(40000, 4)),
+        ]
+    }
+
+    #[test]
+    fn test_rates_stack_by_specificity() {
+        let los_angeles = TaxAddress::new("us", "ca", "90012");
+        let lines = lines_for(&table(), &los_angeles, &[], Decimal::new(10000, 2));
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].amount, Decimal::new(725, 2));
+        assert_eq!(lines[1].amount, Decimal::new(225, 2));
+        assert_eq!(crate::total(&lines), Decimal::new(950, 2));
+
+        let sacramento = TaxAddress::new("US", "CA", "95814");
+        assert_eq!(lines_for(&table(), &sacramento, &[], Decimal::new(10000, 2)).len(), 1);
+
+        let toronto = TaxAddress::new("CA", "ON", "M5V");
+        assert!(lines_for(&table(), &toronto, &[], Decimal::new(10000, 2)).is_empty());
+        assert!(lines_for(&table(), &los_angeles, &[], Decimal::ZERO).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_zone_rates_apply_inside_their_zone() {
+        let mut rates = table();
+        rates.push(TaxRate { zone_id: Some(5), ..rate(4, "Bay Area District Tax", "", "", Decimal::new(12500, 4)) });
+        let bay_area = ::entity::prelude::Zone {
+            id: 5,
+            mid: 1,
+            name: "Bay Area".to_string(),
+            countries: "US".to_string(),
+            states: "CA".to_string(),
+            zips: "94000-94999".to_string(),
+            created_gmt: Timestamp::EPOCH,
+            modified_gmt: Timestamp::EPOCH,
+        };
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([rates])
+            .append_query_results([vec![bay_area]])
+            .into_connection();
+        let calculator = RateTableCalculator::new(Arc::new(db));
+
+        let lines = calculator
+            .calculate(1, &TaxAddress::new("US", "CA", "94103"), Decimal::new(10000, 2))
+            .await
+            .unwrap();
+        let names: Vec<_> = lines.iter().map(|line| line.name.as_str()).collect();
+        assert_eq!(names, vec!["CA State Tax", "Bay Area District Tax"]);
+        assert_eq!(crate::total(&lines), Decimal::new(850, 2));
+    }
+
+    #[tokio::test]
+    async fn test_rate_table_calculator() {
+        let db = MockDatabase::new(DatabaseBackend::Postgres)
+            .append_query_results([table()])
+            .into_connection();
+        let calculator = RateTableCalculator::new(Arc::new(db));
+
+        let lines = calculator
+            .calculate(1, &TaxAddress::new("US", "NY", "10001"), Decimal::new(1999, 2))
+            .await
+            .unwrap();
+        assert_eq!(lines, vec![TaxLine { name: "NY State Tax".to_string(), rate: Decimal::new(4, 0), amount: Decimal::new(80, 2) }]);
+    }
+}
@@ -0,0 +1,194 @@
+//! Background delivery of queued webhooks
+//!
+//! Each request carries `X-CommerceRack-Event`, `X-CommerceRack-Delivery`
+//! and `X-CommerceRack-Signature: t=<unix time>,v1=<hex>`, where `v1` is the
+//! HMAC-SHA256 of `"<t>.<body>"` keyed with the endpoint secret. Receivers
+//! should recompute it and reject stale timestamps.
+
+use anyhow::Result;
+use chrono::Utc;
+use commercerack_core::Timestamp;
+use hmac::{Hmac, Mac};
+use sea_orm::*;
+use sha2::Sha256;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+use ::entity::prelude::*;
+
+use crate::{STATUS_DELIVERED, STATUS_FAILED, STATUS_PENDING};
+
+/// Attempts before a delivery is given up on
+pub const MAX_ATTEMPTS: i32 = 8;
+
+/// Delay before the first retry; doubles on every further failure
+const BASE_RETRY_DELAY_SECS: i64 = 30;
+
+/// Longest wait between two attempts
+const MAX_RETRY_DELAY_SECS: i64 = 6 * 60 * 60;
+
+/// Deliveries sent per pass
+const BATCH_SIZE: u64 = 100;
+
+/// Delay before the next attempt after `attempts` failures
+pub fn retry_delay(attempts: i32) -> i64 {
+    let exponent = attempts.saturating_sub(1).clamp(0, 20) as u32;
+    (BASE_RETRY_DELAY_SECS << exponent).min(MAX_RETRY_DELAY_SECS)
+}
+
+fn hmac_hex(secret: &str, message: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(message.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Value of the `X-CommerceRack-Signature` header
+pub fn signature_header(secret: &str, timestamp: i64, body: &str) -> String {
+    format!("t={},v1={}", timestamp, hmac_hex(secret, &format!("{}.{}", timestamp, body)))
+}
+
+/// Sends due deliveries from the queue
+pub struct WebhookDispatcher {
+    client: reqwest::Client,
+}
+
+impl Default for WebhookDispatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WebhookDispatcher {
+    pub fn new() -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .expect("static reqwest configuration");
+        Self { client }
+    }
+
+    /// Attempt every due delivery once. Returns how many succeeded.
+    pub async fn deliver_due(&self, db: &DatabaseConnection) -> Result<usize> {
+        let now = Timestamp::now();
+
+        let due = WebhookDeliveries::find()
+            .filter(::entity::webhook_deliveries::Column::Status.eq(STATUS_PENDING))
+            .filter(::entity::webhook_deliveries::Column::NextAttemptGmt.lte(now))
+            .order_by_asc(::entity::webhook_deliveries::Column::NextAttemptGmt)
+            .limit(BATCH_SIZE)
+            .all(db)
+            .await?;
+
+        let mut delivered = 0;
+        for delivery in due {
+            let endpoint = WebhookEndpoints::find_by_id(delivery.endpoint_id).one(db).await?;
+            let outcome = match endpoint {
+                Some(endpoint) if endpoint.active => self.send(&endpoint, &delivery).await,
+                _ => Err((None, "Endpoint removed or disabled".to_string())),
+            };
+
+            let attempts = delivery.attempts + 1;
+            let mut active: ::entity::webhook_deliveries::ActiveModel = delivery.into();
+            active.attempts = Set(attempts);
+
+            match outcome {
+                Ok(status_code) => {
+                    active.status = Set(STATUS_DELIVERED.to_string());
+                    active.last_status_code = Set(Some(status_code));
+                    active.last_error = Set(None);
+                    active.delivered_gmt = Set(Some(now));
+                    delivered += 1;
+                }
+                Err((status_code, error)) => {
+                    active.last_status_code = Set(status_code);
+                    active.last_error = Set(Some(error));
+                    if attempts >= MAX_ATTEMPTS {
+                        active.status = Set(STATUS_FAILED.to_string());
+                    } else {
+                        active.next_attempt_gmt = Set(now + chrono::Duration::seconds(retry_delay(attempts)));
+                    }
+                }
+            }
+            active.update(db).await?;
+        }
+
+        Ok(delivered)
+    }
+
+    /// POST one delivery. Any 2xx response counts as success.
+    async fn send(
+        &self,
+        endpoint: &WebhookEndpoint,
+        delivery: &WebhookDelivery,
+    ) -> std::result::Result<i32, (Option<i32>, String)> {
+        let timestamp = Utc::now().timestamp();
+
+        let response = self
+            .client
+            .post(&endpoint.url)
+            .header("Content-Type", "application/json")
+            .header("X-CommerceRack-Event", &delivery.event)
+            .header("X-CommerceRack-Delivery", delivery.id.to_string())
+            .header(
+                "X-CommerceRack-Signature",
+                signature_header(&endpoint.secret, timestamp, &delivery.payload),
+            )
+            .body(delivery.payload.clone())
+            .send()
+            .await
+            .map_err(|e| (None, e.to_string()))?;
+
+        let status = response.status();
+        if status.is_success() {
+            Ok(i32::from(status.as_u16()))
+        } else {
+            Err((Some(i32::from(status.as_u16())), format!("Endpoint responded {}", status)))
+        }
+    }
+
+    /// Run `deliver_due` on a fixed interval until the task is aborted. The
+    /// first pass happens one interval after start.
+    pub fn spawn(
+        self: Arc<Self>,
+        db: Arc<DatabaseConnection>,
+        interval: Duration,
+    ) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+            loop {
+                ticker.tick().await;
+                match self.deliver_due(&db).await {
+                    Ok(0) => {}
+                    Ok(n) => info!("📬 Delivered {} webhooks", n),
+                    Err(e) => warn!("Webhook delivery pass failed: {}", e),
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hmac_matches_reference_vector() {
+        assert_eq!(
+            hmac_hex("key", "The quick brown fox jumps over the lazy dog"),
+            "f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+        assert_eq!(
+            signature_header("key", 1700000000, "{}"),
+            format!("t=1700000000,v1={}", hmac_hex("key", "1700000000.{}"))
+        );
+    }
+
+    #[test]
+    fn test_retry_delay_backs_off_exponentially() {
+        assert_eq!(retry_delay(1), 30);
+        assert_eq!(retry_delay(2), 60);
+        assert_eq!(retry_delay(3), 120);
+        assert_eq!(retry_delay(30), MAX_RETRY_DELAY_SECS);
+    }
+}
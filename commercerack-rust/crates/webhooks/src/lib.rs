@@ -0,0 +1,350 @@
+//! Outbound webhooks for merchant integrations
+//!
+//! Merchants register endpoints subscribed to event types. `enqueue`
+//! writes one delivery per subscribed endpoint into the
+//! `webhook_deliveries` queue, and the `dispatcher` posts them with an
+//! HMAC signature, retrying failures with exponential backoff. The
+//! `subscriber` turns domain events into queued deliveries.
+
+use anyhow::Result;
+use commercerack_core::Timestamp;
+use sea_orm::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+use uuid::Uuid;
+use ::entity::prelude::*;
+
+pub mod dispatcher;
+pub mod subscriber;
+
+pub use dispatcher::WebhookDispatcher;
+pub use subscriber::WebhookSubscriber;
+
+/// Delivery states stored in `webhook_deliveries.status`
+pub const STATUS_PENDING: &str = "pending";
+pub const STATUS_DELIVERED: &str = "delivered";
+pub const STATUS_FAILED: &str = "failed";
+
+/// Events merchants can subscribe to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum WebhookEvent {
+    #[serde(rename = "order.created")]
+    OrderCreated,
+    #[serde(rename = "order.paid")]
+    OrderPaid,
+    #[serde(rename = "order.shipped")]
+    OrderShipped,
+    #[serde(rename = "order.cancelled")]
+    OrderCancelled,
+    #[serde(rename = "order.digital_delivered")]
+    OrderDigitalDelivered,
+    #[serde(rename = "order.approval_requested")]
+    OrderApprovalRequested,
+    #[serde(rename = "order.ready_for_pickup")]
+    OrderReadyForPickup,
+    #[serde(rename = "customer.created")]
+    CustomerCreated,
+    #[serde(rename = "product.updated")]
+    ProductUpdated,
+    #[serde(rename = "cart.abandoned")]
+    CartAbandoned,
+    #[serde(rename = "customer.locked_out")]
+    CustomerLockedOut,
+    #[serde(rename = "inventory.low_stock")]
+    InventoryLowStock,
+    #[serde(rename = "dunning.retry_scheduled")]
+    DunningRetryScheduled,
+    #[serde(rename = "dunning.suspended")]
+    DunningSuspended,
+}
+
+impl WebhookEvent {
+    pub const ALL: [WebhookEvent; 14] = [
+        WebhookEvent::OrderCreated,
+        WebhookEvent::OrderPaid,
+        WebhookEvent::OrderShipped,
+        WebhookEvent::OrderCancelled,
+        WebhookEvent::OrderDigitalDelivered,
+        WebhookEvent::OrderApprovalRequested,
+        WebhookEvent::OrderReadyForPickup,
+        WebhookEvent::CustomerCreated,
+        WebhookEvent::ProductUpdated,
+        WebhookEvent::CartAbandoned,
+        WebhookEvent::CustomerLockedOut,
+        WebhookEvent::InventoryLowStock,
+        WebhookEvent::DunningRetryScheduled,
+        WebhookEvent::DunningSuspended,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEvent::OrderCreated => "order.created",
+            WebhookEvent::OrderPaid => "order.paid",
+            WebhookEvent::OrderShipped => "order.shipped",
+            WebhookEvent::OrderCancelled => "order.cancelled",
+            WebhookEvent::OrderDigitalDelivered => "order.digital_delivered",
+            WebhookEvent::OrderApprovalRequested => "order.approval_requested",
+            WebhookEvent::OrderReadyForPickup => "order.ready_for_pickup",
+            WebhookEvent::CustomerCreated => "customer.created",
+            WebhookEvent::ProductUpdated => "product.updated",
+            WebhookEvent::CartAbandoned => "cart.abandoned",
+            WebhookEvent::CustomerLockedOut => "customer.locked_out",
+            WebhookEvent::InventoryLowStock => "inventory.low_stock",
+            WebhookEvent::DunningRetryScheduled => "dunning.retry_scheduled",
+            WebhookEvent::DunningSuspended => "dunning.suspended",
+        }
+    }
+}
+
+impl fmt::Display for WebhookEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Error, Debug, PartialEq)]
+#[error("Unknown webhook event {0}")]
+pub struct UnknownEvent(pub String);
+
+impl FromStr for WebhookEvent {
+    type Err = UnknownEvent;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|event| event.as_str() == s)
+            .ok_or_else(|| UnknownEvent(s.to_string()))
+    }
+}
+
+/// Event types an endpoint is subscribed to
+pub fn subscribed_events(endpoint: &WebhookEndpoint) -> Vec<WebhookEvent> {
+    endpoint
+        .events
+        .split(',')
+        .filter_map(|event| event.trim().parse().ok())
+        .collect()
+}
+
+fn join_events(events: &[WebhookEvent]) -> String {
+    events.iter().map(|e| e.as_str()).collect::<Vec<_>>().join(",")
+}
+
+fn generate_secret() -> String {
+    format!("whsec_{}", Uuid::new_v4().simple())
+}
+
+/// Body posted to endpoints
+#[derive(Debug, Serialize)]
+struct Envelope<'a> {
+    id: String,
+    #[serde(rename = "type")]
+    event: &'a str,
+    created: Timestamp,
+    data: serde_json::Value,
+}
+
+/// Webhook service for endpoint registration and event queueing
+pub struct WebhookService;
+
+impl WebhookService {
+    /// Register an endpoint. A signing secret is generated for it.
+    pub async fn create_endpoint(
+        db: &DatabaseConnection,
+        mid: i32,
+        url: &str,
+        events: &[WebhookEvent],
+    ) -> Result<WebhookEndpoint> {
+        let now = Timestamp::now();
+
+        let endpoint = ::entity::webhook_endpoints::ActiveModel {
+            mid: Set(mid),
+            url: Set(url.to_string()),
+            secret: Set(generate_secret()),
+            events: Set(join_events(events)),
+            active: Set(true),
+            created_gmt: Set(now),
+            modified_gmt: Set(now),
+            ..Default::default()
+        }
+        .insert(db)
+        .await?;
+
+        Ok(endpoint)
+    }
+
+    /// Find an endpoint
+    pub async fn find_endpoint(
+        db: &DatabaseConnection,
+        mid: i32,
+        id: i32,
+    ) -> Result<Option<WebhookEndpoint>> {
+        let endpoint = WebhookEndpoints::find()
+            .filter(::entity::webhook_endpoints::Column::Mid.eq(mid))
+            .filter(::entity::webhook_endpoints::Column::Id.eq(id))
+            .one(db)
+            .await?;
+
+        Ok(endpoint)
+    }
+
+    /// List a merchant's endpoints
+    pub async fn list_endpoints(
+        db: &DatabaseConnection,
+        mid: i32,
+    ) -> Result<Vec<WebhookEndpoint>> {
+        let endpoints = WebhookEndpoints::find()
+            .filter(::entity::webhook_endpoints::Column::Mid.eq(mid))
+            .order_by_asc(::entity::webhook_endpoints::Column::Id)
+            .all(db)
+            .await?;
+
+        Ok(endpoints)
+    }
+
+    /// Change an endpoint's URL, subscriptions or active flag
+    pub async fn update_endpoint(
+        db: &DatabaseConnection,
+        endpoint: WebhookEndpoint,
+        url: &str,
+        events: &[WebhookEvent],
+        active: bool,
+    ) -> Result<WebhookEndpoint> {
+        let mut active_model: ::entity::webhook_endpoints::ActiveModel = endpoint.into();
+        active_model.url = Set(url.to_string());
+        active_model.events = Set(join_events(events));
+        active_model.active = Set(active);
+        active_model.modified_gmt = Set(Timestamp::now());
+
+        Ok(active_model.update(db).await?)
+    }
+
+    /// Delete an endpoint and its queued deliveries
+    pub async fn delete_endpoint(
+        db: &DatabaseConnection,
+        mid: i32,
+        id: i32,
+    ) -> Result<bool> {
+        let txn = db.begin().await?;
+
+        WebhookDeliveries::delete_many()
+            .filter(::entity::webhook_deliveries::Column::Mid.eq(mid))
+            .filter(::entity::webhook_deliveries::Column::EndpointId.eq(id))
+            .exec(&txn)
+            .await?;
+        let result = WebhookEndpoints::delete_many()
+            .filter(::entity::webhook_endpoints::Column::Mid.eq(mid))
+            .filter(::entity::webhook_endpoints::Column::Id.eq(id))
+            .exec(&txn)
+            .await?;
+
+        txn.commit().await?;
+        Ok(result.rows_affected > 0)
+    }
+
+    /// Queue an event for every active endpoint of the merchant subscribed to it
+    pub async fn enqueue<T: Serialize>(
+        db: &DatabaseConnection,
+        mid: i32,
+        event: WebhookEvent,
+        data: &T,
+    ) -> Result<Vec<WebhookDelivery>> {
+        let endpoints: Vec<WebhookEndpoint> = WebhookEndpoints::find()
+            .filter(::entity::webhook_endpoints::Column::Mid.eq(mid))
+            .filter(::entity::webhook_endpoints::Column::Active.eq(true))
+            .all(db)
+            .await?
+            .into_iter()
+            .filter(|endpoint| subscribed_events(endpoint).contains(&event))
+            .collect();
+        if endpoints.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let now = Timestamp::now();
+        let data = serde_json::to_value(data)?;
+
+        let mut deliveries = Vec::with_capacity(endpoints.len());
+        for endpoint in endpoints {
+            let payload = serde_json::to_string(&Envelope {
+                id: Uuid::new_v4().to_string(),
+                event: event.as_str(),
+                created: now,
+                data: data.clone(),
+            })?;
+
+            let delivery = ::entity::webhook_deliveries::ActiveModel {
+                mid: Set(mid),
+                endpoint_id: Set(endpoint.id),
+                event: Set(event.as_str().to_string()),
+                payload: Set(payload),
+                status: Set(STATUS_PENDING.to_string()),
+                attempts: Set(0),
+                next_attempt_gmt: Set(now),
+                last_status_code: Set(None),
+                last_error: Set(None),
+                created_gmt: Set(now),
+                delivered_gmt: Set(None),
+                ..Default::default()
+            }
+            .insert(db)
+            .await?;
+            deliveries.push(delivery);
+        }
+
+        Ok(deliveries)
+    }
+
+    /// Recent deliveries to an endpoint, newest first
+    pub async fn list_deliveries(
+        db: &DatabaseConnection,
+        mid: i32,
+        endpoint_id: i32,
+        limit: u64,
+    ) -> Result<Vec<WebhookDelivery>> {
+        let deliveries = WebhookDeliveries::find()
+            .filter(::entity::webhook_deliveries::Column::Mid.eq(mid))
+            .filter(::entity::webhook_deliveries::Column::EndpointId.eq(endpoint_id))
+            .order_by_desc(::entity::webhook_deliveries::Column::Id)
+            .limit(limit)
+            .all(db)
+            .await?;
+
+        Ok(deliveries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_names_roundtrip() {
+        for event in WebhookEvent::ALL {
+            assert_eq!(event.as_str().parse::<WebhookEvent>(), Ok(event));
+            assert_eq!(serde_json::to_string(&event).unwrap(), format!("\"{}\"", event));
+        }
+        assert!("order.deleted".parse::<WebhookEvent>().is_err());
+    }
+
+    #[test]
+    fn test_subscribed_events() {
+        let endpoint = WebhookEndpoint {
+            id: 1,
+            mid: 1,
+            url: "https://example.com/hooks".to_string(),
+            secret: generate_secret(),
+            events: join_events(&[WebhookEvent::OrderCreated, WebhookEvent::OrderPaid]),
+            active: true,
+            created_gmt: Timestamp::EPOCH,
+            modified_gmt: Timestamp::EPOCH,
+        };
+        assert_eq!(endpoint.events, "order.created,order.paid");
+        assert_eq!(
+            subscribed_events(&endpoint),
+            vec![WebhookEvent::OrderCreated, WebhookEvent::OrderPaid]
+        );
+    }
+}
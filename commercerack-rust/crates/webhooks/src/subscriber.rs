@@ -0,0 +1,395 @@
+//! Domain event subscriber that queues webhook deliveries
+
+use async_trait::async_trait;
+use commercerack_events::{DomainEvent, EventHandler};
+use sea_orm::DatabaseConnection;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tracing::warn;
+use ::entity::prelude::{AbandonedCart, Customer, DunningCase, Order};
+
+use crate::{WebhookEvent, WebhookService};
+
+/// Customer fields shared with merchant endpoints; password material never leaves
+fn customer_data(customer: &Customer) -> Value {
+    json!({
+        "cid": customer.cid,
+        "mid": customer.mid,
+        "email": customer.email,
+        "firstname": customer.firstname,
+        "lastname": customer.lastname,
+        "created_gmt": customer.created_gmt,
+        "modified_gmt": customer.modified_gmt,
+    })
+}
+
+/// Abandoned cart with its items decoded and the path that restores it,
+/// for the merchant to put in a reminder email
+fn abandoned_cart_data(cart: &AbandonedCart) -> Value {
+    json!({
+        "id": cart.id,
+        "mid": cart.mid,
+        "cart_id": cart.cart_id,
+        "cid": cart.cid,
+        "email": cart.email,
+        "items": serde_json::from_str::<Value>(&cart.items).unwrap_or(Value::Null),
+        "item_count": cart.item_count,
+        "subtotal": cart.subtotal,
+        "abandoned_gmt": cart.abandoned_gmt,
+        "recovery_token": cart.recovery_token,
+        "recovery_path": cart.recovery_token.as_ref().map(|token| format!("/api/abandoned-carts/recover/{}", token)),
+    })
+}
+
+/// An order in dunning, for the merchant to email the buyer that their
+/// payment failed and when it is tried again. The payment method token
+/// stays out.
+fn dunning_data(order: &Order, case: &DunningCase) -> Value {
+    json!({
+        "mid": order.mid,
+        "id": order.id,
+        "orderid": order.orderid,
+        "customer": order.customer,
+        "bill_email": order.bill_email,
+        "total": order.total,
+        "case": {
+            "id": case.id,
+            "status": case.status,
+            "attempts": case.attempts,
+            "next_attempt_gmt": case.next_attempt_gmt,
+            "last_error": case.last_error,
+        },
+    })
+}
+
+/// Webhook event and payload for a domain event, if merchants can subscribe to it
+pub fn webhook_for(event: &DomainEvent) -> Option<(WebhookEvent, Value)> {
+    let webhook = match event {
+        DomainEvent::OrderCreated { order, items } => {
+            let mut data = serde_json::to_value(order).ok()?;
+            data["items"] = serde_json::to_value(items).ok()?;
+            (WebhookEvent::OrderCreated, data)
+        }
+        DomainEvent::OrderPaid(order) => (WebhookEvent::OrderPaid, serde_json::to_value(order).ok()?),
+        DomainEvent::OrderShipped(order) => (WebhookEvent::OrderShipped, serde_json::to_value(order).ok()?),
+        DomainEvent::OrderCancelled(order) => (WebhookEvent::OrderCancelled, serde_json::to_value(order).ok()?),
+        // For the merchant to email the buyer their links and keys
+        DomainEvent::DigitalDelivered { order, downloads, license_keys } => (
+            WebhookEvent::OrderDigitalDelivered,
+            json!({
+                "mid": order.mid,
+                "id": order.id,
+                "orderid": order.orderid,
+                "customer": order.customer,
+                "bill_email": order.bill_email,
+                "downloads": downloads.iter().map(|download| json!({
+                    "order_item_id": download.order_item_id,
+                    "sku": download.sku,
+                    "path": format!("/api/downloads/{}", download.token),
+                    "max_downloads": download.max_downloads,
+                    "expires_gmt": download.expires_gmt,
+                })).collect::<Vec<_>>(),
+                "license_keys": license_keys.iter().map(|key| json!({
+                    "order_item_id": key.order_item_id,
+                    "sku": key.sku,
+                    "license_key": key.license_key,
+                })).collect::<Vec<_>>(),
+            }),
+        ),
+        // For the merchant to email the company's approvers
+        DomainEvent::ApprovalRequested { order, approvers } => {
+            let mut data = serde_json::to_value(order).ok()?;
+            data["approvers"] = approvers.iter().map(customer_data).collect();
+            (WebhookEvent::OrderApprovalRequested, data)
+        }
+        // For the merchant to email the buyer where and when to collect
+        DomainEvent::OrderReadyForPickup { order, location } => (
+            WebhookEvent::OrderReadyForPickup,
+            json!({
+                "mid": order.mid,
+                "id": order.id,
+                "orderid": order.orderid,
+                "customer": order.customer,
+                "bill_email": order.bill_email,
+                "pickup_ready_gmt": order.pickup_ready_gmt,
+                "location": {
+                    "id": location.id,
+                    "name": location.name,
+                    "street1": location.street1,
+                    "street2": location.street2,
+                    "city": location.city,
+                    "state": location.state,
+                    "zip": location.zip,
+                    "country": location.country,
+                    "phone": location.phone,
+                    "hours": location.hours,
+                },
+            }),
+        ),
+        DomainEvent::CustomerCreated(customer) => (WebhookEvent::CustomerCreated, customer_data(customer)),
+        DomainEvent::ProductUpdated(product) => (WebhookEvent::ProductUpdated, serde_json::to_value(product).ok()?),
+        DomainEvent::SkuCreated(sku) | DomainEvent::SkuUpdated(sku) => {
+            (WebhookEvent::ProductUpdated, serde_json::to_value(sku).ok()?)
+        }
+        DomainEvent::CartAbandoned(cart) => (WebhookEvent::CartAbandoned, abandoned_cart_data(cart)),
+        // The merchant emails the unlock token; it is only good for POST /api/auth/unlock
+        DomainEvent::CustomerLockedOut { mid, cid, email, unlock_token, locked_until_gmt } => (
+            WebhookEvent::CustomerLockedOut,
+            json!({
+                "mid": mid,
+                "cid": cid,
+                "email": email,
+                "locked_until_gmt": locked_until_gmt,
+                "unlock_token": unlock_token,
+            }),
+        ),
+        // Enough for the merchant's purchasing email: what is low and how many to order
+        DomainEvent::LowStock(sku) => (
+            WebhookEvent::InventoryLowStock,
+            json!({
+                "mid": sku.mid,
+                "pid": sku.pid,
+                "sku": sku.sku,
+                "title": sku.title,
+                "inv_available": sku.inv_available,
+                "reorder_point": sku.reorder_point,
+                "reorder_quantity": sku.reorder_quantity,
+            }),
+        ),
+        DomainEvent::PaymentRetryScheduled { order, case } => {
+            (WebhookEvent::DunningRetryScheduled, dunning_data(order, case))
+        }
+        DomainEvent::DunningSuspended { order, case } => (WebhookEvent::DunningSuspended, dunning_data(order, case)),
+        _ => return None,
+    };
+    Some(webhook)
+}
+
+/// Queues a delivery for each domain event merchants can subscribe to.
+/// Failures are logged; they never affect the change that raised the event.
+pub struct WebhookSubscriber {
+    db: Arc<DatabaseConnection>,
+}
+
+impl WebhookSubscriber {
+    pub fn new(db: Arc<DatabaseConnection>) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl EventHandler for WebhookSubscriber {
+    async fn handle(&self, event: &DomainEvent) {
+        let Some((webhook, data)) = webhook_for(event) else {
+            return;
+        };
+        let mid = event.mid();
+        if let Err(e) = WebhookService::enqueue(&self.db, mid.get(), webhook, &data).await {
+            warn!("Failed to queue {} webhook for merchant {}: {}", webhook, mid, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use commercerack_core::{MerchantId, OrderId, Timestamp};
+
+    #[test]
+    fn test_customer_payload_omits_password() {
+        let customer = Customer {
+            cid: 7,
+            mid: 1,
+            email: "shopper@example.com".to_string(),
+            firstname: "Sam".to_string(),
+            lastname: "Shopper".to_string(),
+            created_gmt: Timestamp::EPOCH,
+            modified_gmt: Timestamp::EPOCH,
+            passhash: "$argon2id$secret".to_string(),
+            passsalt: "salt".to_string(),
+            price_group: String::new(),
+            customer_group: "retail".to_string(),
+            role: "customer".to_string(),
+        };
+
+        let (webhook, data) = webhook_for(&DomainEvent::CustomerCreated(customer.clone())).unwrap();
+        assert_eq!(webhook, WebhookEvent::CustomerCreated);
+        assert_eq!(data["email"], "shopper@example.com");
+        assert!(data.get("passhash").is_none());
+        assert!(data.get("passsalt").is_none());
+
+        assert!(webhook_for(&DomainEvent::CustomerUpdated(customer)).is_none());
+        assert!(webhook_for(&DomainEvent::OrderDeleted { mid: MerchantId::new(1), id: OrderId::new(2) }).is_none());
+    }
+
+    #[test]
+    fn test_abandoned_cart_payload_has_recovery_path() {
+        let cart = AbandonedCart {
+            id: 4,
+            cart_id: "cart-1".to_string(),
+            items: r#"[{"sku":"SKU001","product_name":"Widget","quantity":2,"unit_price":"10.00"}]"#.to_string(),
+            item_count: 2,
+            subtotal: sea_orm::prelude::Decimal::new(2000, 2),
+            abandoned_gmt: Timestamp::from_unix(1_700_000_000),
+            mid: 1,
+            cid: 0,
+            email: "guest@example.com".to_string(),
+            recovery_token: Some("abc123".to_string()),
+            recovered_gmt: None,
+            order_id: None,
+            order_total: None,
+            ordered_gmt: None,
+        };
+
+        let (webhook, data) = webhook_for(&DomainEvent::CartAbandoned(cart)).unwrap();
+        assert_eq!(webhook, WebhookEvent::CartAbandoned);
+        assert_eq!(data["items"][0]["sku"], "SKU001");
+        assert_eq!(data["recovery_path"], "/api/abandoned-carts/recover/abc123");
+    }
+
+    #[test]
+    fn test_ready_for_pickup_payload_has_location() {
+        let order = ::entity::prelude::Order {
+            id: 9,
+            mid: 1,
+            orderid: "2025-11-18-00000009".to_string(),
+            cartid: String::new(),
+            customer: 7,
+            pool: "RECENT".to_string(),
+            total: sea_orm::prelude::Decimal::new(2500, 2),
+            created_gmt: Timestamp::EPOCH,
+            paid_gmt: None,
+            paid_txn: None,
+            order_payment_status: None,
+            order_payment_lookup: None,
+            bs_settlement: None,
+            shipped_gmt: None,
+            inv_gmt: None,
+            company_id: None,
+            review_status: None,
+            ship_method: None,
+            bill_email: "buyer@example.com".to_string(),
+            v: 2,
+            mkt: 0,
+            erefid: None,
+            mkt_bitstr: String::new(),
+            pickup_location_id: Some(4),
+            pickup_ready_gmt: Some(Timestamp::from_unix(1_700_000_000)),
+            picked_up_gmt: None,
+            cancelled_gmt: None,
+            cancel_reason: None,
+        };
+        let location = ::entity::prelude::StoreLocation {
+            id: 4,
+            mid: 1,
+            name: "Downtown".to_string(),
+            street1: "1 Main St".to_string(),
+            street2: String::new(),
+            city: "Springfield".to_string(),
+            state: "IL".to_string(),
+            zip: "62701".to_string(),
+            country: "US".to_string(),
+            phone: "555-0100".to_string(),
+            hours: "Mon-Sat 9-6".to_string(),
+            active: true,
+            created_gmt: Timestamp::EPOCH,
+        };
+
+        let (webhook, data) = webhook_for(&DomainEvent::OrderReadyForPickup { order, location }).unwrap();
+        assert_eq!(webhook, WebhookEvent::OrderReadyForPickup);
+        assert_eq!(data["bill_email"], "buyer@example.com");
+        assert_eq!(data["location"]["name"], "Downtown");
+        assert_eq!(data["location"]["hours"], "Mon-Sat 9-6");
+    }
+
+    #[test]
+    fn test_cancelled_payload_has_reason() {
+        let order = ::entity::prelude::Order {
+            id: 9,
+            mid: 1,
+            orderid: "2025-11-18-00000009".to_string(),
+            cartid: String::new(),
+            customer: 7,
+            pool: "RECENT".to_string(),
+            total: sea_orm::prelude::Decimal::new(2500, 2),
+            created_gmt: Timestamp::EPOCH,
+            paid_gmt: None,
+            paid_txn: None,
+            order_payment_status: Some("voided".to_string()),
+            order_payment_lookup: None,
+            bs_settlement: None,
+            shipped_gmt: None,
+            inv_gmt: None,
+            company_id: None,
+            review_status: None,
+            ship_method: None,
+            bill_email: "buyer@example.com".to_string(),
+            v: 3,
+            mkt: 0,
+            erefid: None,
+            mkt_bitstr: String::new(),
+            pickup_location_id: None,
+            pickup_ready_gmt: None,
+            picked_up_gmt: None,
+            cancelled_gmt: Some(Timestamp::from_unix(1_700_000_000)),
+            cancel_reason: Some("out_of_stock".to_string()),
+        };
+
+        let (webhook, data) = webhook_for(&DomainEvent::OrderCancelled(order)).unwrap();
+        assert_eq!(webhook, WebhookEvent::OrderCancelled);
+        assert_eq!(data["cancel_reason"], "out_of_stock");
+        assert_eq!(data["order_payment_status"], "voided");
+    }
+
+    #[test]
+    fn test_dunning_payload_leaves_out_payment_method() {
+        let order = ::entity::prelude::Order {
+            id: 9,
+            mid: 1,
+            orderid: "2025-11-18-00000009".to_string(),
+            cartid: String::new(),
+            customer: 7,
+            pool: "HOLD".to_string(),
+            total: sea_orm::prelude::Decimal::new(2500, 2),
+            created_gmt: Timestamp::EPOCH,
+            paid_gmt: None,
+            paid_txn: None,
+            order_payment_status: Some("declined".to_string()),
+            order_payment_lookup: None,
+            bs_settlement: None,
+            shipped_gmt: None,
+            inv_gmt: None,
+            company_id: None,
+            review_status: None,
+            ship_method: None,
+            bill_email: "buyer@example.com".to_string(),
+            v: 4,
+            mkt: 0,
+            erefid: None,
+            mkt_bitstr: String::new(),
+            pickup_location_id: None,
+            pickup_ready_gmt: None,
+            picked_up_gmt: None,
+            cancelled_gmt: None,
+            cancel_reason: None,
+        };
+        let case = DunningCase {
+            id: 3,
+            mid: 1,
+            order_id: 9,
+            payment_method: "pm_card_secret".to_string(),
+            status: "suspended".to_string(),
+            attempts: 4,
+            next_attempt_gmt: None,
+            last_error: "insufficient funds".to_string(),
+            created_gmt: Timestamp::EPOCH,
+            modified_gmt: Timestamp::EPOCH,
+        };
+
+        let (webhook, data) = webhook_for(&DomainEvent::DunningSuspended { order, case }).unwrap();
+        assert_eq!(webhook, WebhookEvent::DunningSuspended);
+        assert_eq!(data["bill_email"], "buyer@example.com");
+        assert_eq!(data["case"]["attempts"], 4);
+        assert!(!data.to_string().contains("pm_card_secret"));
+    }
+}
@@ -0,0 +1,30 @@
+//! Abandoned cart entity definition
+
+use commercerack_core::Timestamp;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "abandoned_carts")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub cart_id: String,
+    pub items: String, // JSON serialized cart items
+    pub item_count: i32,
+    pub subtotal: Decimal,
+    pub abandoned_gmt: Timestamp,
+    pub mid: i32, // 0 = nobody to contact
+    pub cid: i32,
+    pub email: String,
+    pub recovery_token: Option<String>,
+    pub recovered_gmt: Option<Timestamp>, // recovery link first opened
+    pub order_id: Option<i32>, // set once the cart is checked out
+    pub order_total: Option<Decimal>,
+    pub ordered_gmt: Option<Timestamp>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,26 @@
+//! Archived order (an order moved to cold storage) entity definition
+
+use commercerack_core::Timestamp;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "archived_orders")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub mid: i32,
+    pub order_id: i32, // the order's `id` while it was in `orders`; invoices and returns still refer to it
+    pub orderid: String,
+    pub customer: i32,
+    pub total: Decimal,
+    pub created_gmt: Timestamp, // when the order was placed
+    pub object_key: String, // where the snapshot is in the bucket; see commercerack_order::cold_storage
+    pub size: i64, // bytes of the compressed snapshot
+    pub archived_gmt: Timestamp,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
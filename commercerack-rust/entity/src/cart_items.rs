@@ -0,0 +1,36 @@
+//! Cart item entity definition
+
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "cart_items")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub cart_id: String,
+    pub sku: String,
+    pub product_name: String,
+    pub quantity: i32,
+    pub unit_price: Decimal,
+    pub options: String, // JSON of the item options; empty for none
+    pub surcharge: Decimal,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::carts::Entity",
+        from = "Column::CartId",
+        to = "super::carts::Column::CartId"
+    )]
+    Cart,
+}
+
+impl Related<super::carts::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Cart.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,35 @@
+//! Cart entity definition
+
+use commercerack_core::Timestamp;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "carts")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub cart_id: String,
+    pub coupon: Option<String>, // applied coupon as JSON
+    pub gift_cards: Option<String>, // applied gift cards as JSON
+    pub mid: i32, // 0 = no contact yet
+    pub cid: i32, // 0 = guest
+    pub email: String,
+    pub currency: String, // ISO 4217 code
+    pub abandoned_gmt: Option<Timestamp>,
+    pub created_gmt: Timestamp,
+    pub modified_gmt: Timestamp,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(has_many = "super::cart_items::Entity")]
+    CartItems,
+}
+
+impl Related<super::cart_items::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::CartItems.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,24 @@
+//! Product category entity definition
+
+use commercerack_core::Timestamp;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "categories")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub mid: i32,
+    pub parent_id: Option<i32>, // None for top-level categories
+    pub name: String,
+    pub slug: String, // unique per merchant, used in storefront URLs
+    pub position: i32, // sort order among siblings
+    pub created_gmt: Timestamp,
+    pub modified_gmt: Timestamp,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,29 @@
+//! Channel (a merchant's marketplace account) entity definition
+
+use commercerack_core::Timestamp;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "channels")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub mid: i32,
+    pub marketplace: String, // see commercerack_channels::Marketplace; one channel each per merchant
+    pub name: String,
+    pub settings: String, // JSON of the connector's account settings
+    pub enabled: bool,
+    pub orders_synced_gmt: Option<Timestamp>, // orders placed up to here have been pulled
+    pub tracking_synced_gmt: Option<Timestamp>, // shipments up to here have had their tracking pushed
+    pub inventory_synced_gmt: Option<Timestamp>, // stock moved up to here has been pushed
+    pub throttled_until_gmt: Option<Timestamp>, // the marketplace asked us to back off until here
+    pub last_error: Option<String>, // why the last sync failed; cleared by the next to succeed
+    pub created_gmt: Timestamp,
+    pub modified_gmt: Timestamp,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
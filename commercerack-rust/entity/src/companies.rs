@@ -0,0 +1,23 @@
+//! Company (B2B account shared by several buyers) entity definition
+
+use commercerack_core::Timestamp;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "companies")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub mid: i32,
+    pub name: String,
+    pub price_group: String, // empty = each buyer's own
+    pub approval_threshold: Option<Decimal>, // orders above it wait for an approver
+    pub created_gmt: Timestamp,
+    pub modified_gmt: Timestamp,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
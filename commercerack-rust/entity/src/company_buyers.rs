@@ -0,0 +1,23 @@
+//! Company buyer (customer ordering for a company) entity definition
+
+use commercerack_core::Timestamp;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "company_buyers")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub mid: i32,
+    pub company_id: i32,
+    pub cid: i32, // a customer buys for at most one company
+    pub role: String, // buyer or approver
+    pub spending_limit: Option<Decimal>, // largest order allowed; None = no limit
+    pub created_gmt: Timestamp,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,34 @@
+//! Coupon entity definition
+
+use commercerack_core::Timestamp;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "coupons")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub mid: i32,
+    pub code: String, // stored uppercase, unique per merchant
+    pub description: String,
+    pub kind: String, // percent, fixed, free_shipping
+    pub value: Decimal, // percent off or fixed amount off
+    pub min_subtotal: Option<Decimal>,
+    pub starts_gmt: Option<Timestamp>,
+    pub ends_gmt: Option<Timestamp>,
+    pub max_uses: Option<i32>,
+    pub max_uses_per_customer: Option<i32>,
+    pub times_used: i32,
+    pub skus: String, // comma-separated; empty applies to every SKU
+    pub category_ids: String, // comma-separated; empty applies to every category
+    pub customer_tags: String, // comma-separated; empty lets every customer use it
+    pub active: bool,
+    pub created_gmt: Timestamp,
+    pub modified_gmt: Timestamp,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
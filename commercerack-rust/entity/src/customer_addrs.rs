@@ -0,0 +1,31 @@
+//! Customer address entity definition
+
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "customer_addrs")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub cid: i32,
+    pub mid: i32,
+    pub label: String,
+    pub firstname: String,
+    pub lastname: String,
+    pub company: String,
+    pub address1: String,
+    pub address2: String,
+    pub city: String,
+    pub state: String,
+    pub zip: String,
+    pub country: String,
+    pub phone: String,
+    pub is_default_billing: bool,
+    pub is_default_shipping: bool,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
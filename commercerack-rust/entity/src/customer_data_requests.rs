@@ -0,0 +1,24 @@
+//! Customer data export and erasure request entity definition
+
+use commercerack_core::Timestamp;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "customer_data_requests")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub mid: i32,
+    pub cid: i32,
+    pub kind: String, // see commercerack_customer::privacy::DataRequestKind
+    pub status: String,
+    pub archive: Option<String>, // JSON, exports only
+    pub created_gmt: Timestamp,
+    pub completed_gmt: Option<Timestamp>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,28 @@
+//! Customer metrics (lifetime value and RFM scores) entity definition
+
+use commercerack_core::Timestamp;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "customer_metrics")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub mid: i32,
+    pub cid: i32,
+    pub orders: i32, // paid orders
+    pub lifetime_value: Decimal, // sum of paid order totals
+    pub first_order_gmt: Timestamp,
+    pub last_order_gmt: Timestamp,
+    pub recency_score: i32, // 1 to 5; see commercerack_customer::segments
+    pub frequency_score: i32,
+    pub monetary_score: i32,
+    pub rfm_segment: String,
+    pub computed_gmt: Timestamp,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,25 @@
+//! Customer note entity definition
+
+use commercerack_core::Timestamp;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "customer_notes")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub mid: i32,
+    pub username: String, // legacy merchant username
+    pub cid: i32,
+    pub created_gmt: Timestamp,
+    pub luser: String, // who wrote it: the author's token subject
+    pub note: String,
+    #[sea_orm(column_name = "type")]
+    pub kind: String, // legacy note type code, empty for new notes
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
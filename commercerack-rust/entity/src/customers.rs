@@ -1,5 +1,6 @@
 //! Customer entity definition
 
+use commercerack_core::Timestamp;
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 
@@ -12,10 +13,13 @@ pub struct Model {
     pub email: String,
     pub firstname: String,
     pub lastname: String,
-    pub created_gmt: i32,
-    pub modified_gmt: i32,
+    pub created_gmt: Timestamp,
+    pub modified_gmt: Timestamp,
     pub passhash: String,
     pub passsalt: String,
+    pub price_group: String, // empty = list and everyone's tier prices only
+    pub customer_group: String, // retail, wholesale or employee
+    pub role: String, // customer, merchant_admin or platform_admin; what the account's logins may do
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -0,0 +1,27 @@
+//! Dunning case entity definition: an order whose payment was declined,
+//! and when its charge is tried again
+
+use commercerack_core::Timestamp;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "dunning_cases")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub mid: i32,
+    pub order_id: i32,
+    pub payment_method: String,
+    pub status: String, // retrying, recovered, suspended or closed
+    pub attempts: i32,
+    pub next_attempt_gmt: Option<Timestamp>,
+    pub last_error: String,
+    pub created_gmt: Timestamp,
+    pub modified_gmt: Timestamp,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
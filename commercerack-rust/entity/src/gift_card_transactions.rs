@@ -0,0 +1,38 @@
+//! Gift card transaction entity definition
+
+use commercerack_core::Timestamp;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "gift_card_transactions")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub gift_card_id: i32,
+    pub mid: i32,
+    pub kind: String, // issue, redeem, adjust or void
+    pub amount: Decimal, // change to the balance
+    pub balance_after: Decimal,
+    pub order_id: Option<i32>,
+    pub note: String,
+    pub created_gmt: Timestamp,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::gift_cards::Entity",
+        from = "Column::GiftCardId",
+        to = "super::gift_cards::Column::Id"
+    )]
+    GiftCard,
+}
+
+impl Related<super::gift_cards::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::GiftCard.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,35 @@
+//! Gift card entity definition
+
+use commercerack_core::Timestamp;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "gift_cards")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub mid: i32,
+    pub code: String,
+    pub initial_balance: Decimal,
+    pub balance: Decimal,
+    pub cid: i32, // 0 = not issued to a customer
+    pub voided: bool,
+    pub expires_gmt: Option<Timestamp>,
+    pub created_gmt: Timestamp,
+    pub modified_gmt: Timestamp,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(has_many = "super::gift_card_transactions::Entity")]
+    GiftCardTransactions,
+}
+
+impl Related<super::gift_card_transactions::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::GiftCardTransactions.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,27 @@
+//! Inventory adjustment (audit trail) entity definition
+
+use commercerack_core::Timestamp;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "inventory_adjustments")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub mid: i32,
+    pub sku: String,
+    pub delta: i32,
+    pub inv_available_after: i32,
+    pub reason: String, // manual, order, cancellation, return, transfer, purchase
+    pub note: Option<String>,
+    pub order_id: Option<i32>,
+    pub actor: Option<i32>, // user that made a manual adjustment
+    pub warehouse_id: Option<i32>, // the warehouse whose stock moved, if any
+    pub created_gmt: Timestamp,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,25 @@
+//! Inventory reservation entity definition
+
+use commercerack_core::Timestamp;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "inventory_reservations")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub mid: i32,
+    pub sku: String,
+    pub cart_id: String,
+    pub quantity: i32,
+    pub created_gmt: Timestamp,
+    pub expires_gmt: Timestamp,
+    pub released_gmt: Option<Timestamp>,
+    pub order_id: Option<i32>, // set when the reservation was consumed by an order
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,23 @@
+//! Merchant invoice branding entity definition
+
+use commercerack_core::Timestamp;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "invoice_branding")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub mid: i32,
+    pub name: String, // business name heading every invoice
+    pub address: String, // newline separated
+    pub tax_id: String, // e.g. a VAT number; empty if none
+    pub footer: String,
+    pub accent_color: String, // #RRGGBB
+    pub modified_gmt: Timestamp,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
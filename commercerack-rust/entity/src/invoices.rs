@@ -0,0 +1,25 @@
+//! Invoice and credit memo entity definition
+
+use commercerack_core::Timestamp;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "invoices")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub mid: i32,
+    pub order_id: i32,
+    pub kind: String, // see commercerack_order::invoices::InvoiceKind
+    pub number: String, // unique per merchant, e.g. `INV-2025-11-18-3F9A1C2B`
+    pub amount: Decimal, // invoiced total, or the amount credited
+    #[serde(skip)]
+    pub pdf: Vec<u8>, // the rendered document, served as is
+    pub created_gmt: Timestamp,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,27 @@
+//! Background job queue entity definition
+
+use commercerack_core::Timestamp;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "jobs")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub kind: String, // see commercerack_jobs::Job::KIND
+    pub payload: String, // JSON of the typed job
+    pub status: String, // pending, running, done, failed
+    pub attempts: i32,
+    pub max_attempts: i32,
+    pub run_at_gmt: Timestamp, // not run before this time
+    pub locked_gmt: Option<Timestamp>, // when a worker claimed it
+    pub last_error: Option<String>,
+    pub created_gmt: Timestamp,
+    pub finished_gmt: Option<Timestamp>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
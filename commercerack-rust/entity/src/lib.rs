@@ -3,8 +3,79 @@
 //! This crate contains all database entity definitions for CommerceRack.
 
 pub mod customers;
+pub mod customer_addrs;
+pub mod refresh_tokens;
+pub mod wishlists;
+pub mod wishlist_items;
 pub mod products;
+pub mod categories;
+pub mod product_categories;
+pub mod product_media;
 pub mod orders;
+pub mod order_items;
+pub mod shipments;
+pub mod shipment_items;
+pub mod returns;
+pub mod return_items;
+pub mod skus;
+pub mod price_tiers;
+pub mod carts;
+pub mod cart_items;
+pub mod abandoned_carts;
+pub mod coupons;
+pub mod coupon_redemptions;
+pub mod gift_cards;
+pub mod gift_card_transactions;
+pub mod tax_rates;
+pub mod shipping_zones;
+pub mod shipping_rates;
+pub mod shipping_transit_times;
+pub mod inventory_reservations;
+pub mod inventory_adjustments;
+pub mod webhook_endpoints;
+pub mod webhook_deliveries;
+pub mod event_outbox;
+pub mod jobs;
+pub mod reports;
+pub mod audit_log;
+pub mod customer_data_requests;
+pub mod login_attempts;
+pub mod customer_two_factor;
+pub mod customer_backup_codes;
+pub mod customer_identities;
+pub mod sessions;
+pub mod customer_tags;
+pub mod customer_notes;
+pub mod warehouses;
+pub mod warehouse_stock;
+pub mod stock_transfers;
+pub mod stock_transfer_items;
+pub mod order_allocations;
+pub mod purchase_orders;
+pub mod purchase_order_items;
+pub mod license_keys;
+pub mod order_downloads;
+pub mod order_payments;
+pub mod invoices;
+pub mod invoice_branding;
+pub mod companies;
+pub mod company_buyers;
+pub mod customer_carts;
+pub mod channels;
+pub mod merchants;
+pub mod merchant_settings;
+pub mod archived_orders;
+pub mod customer_recommendations;
+pub mod customer_metrics;
+pub mod segments;
+pub mod segment_members;
+pub mod zones;
+pub mod store_locations;
+pub mod order_fulfillment_groups;
+pub mod order_fulfillment_group_items;
+pub mod order_refunds;
+pub mod order_refund_items;
+pub mod dunning_cases;
 
 pub mod prelude;
 
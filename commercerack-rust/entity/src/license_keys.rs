@@ -0,0 +1,24 @@
+//! License key (pool per SKU, assigned when a digital order is fulfilled) entity definition
+
+use commercerack_core::Timestamp;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "license_keys")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub mid: i32,
+    pub sku: String,
+    pub license_key: String,
+    pub order_id: Option<i32>, // None while the key is still in the pool
+    pub order_item_id: Option<i32>,
+    pub assigned_gmt: Option<Timestamp>,
+    pub created_gmt: Timestamp,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
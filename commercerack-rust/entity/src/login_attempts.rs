@@ -0,0 +1,24 @@
+//! Failed login attempt entity definition
+
+use commercerack_core::Timestamp;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "login_attempts")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub mid: i32,
+    pub email_hash: String, // SHA-256 hex of the lowercased email
+    pub ip: String, // empty = every IP, the row that locks the account
+    pub failures: i32,
+    pub last_failed_gmt: Timestamp,
+    pub locked_until_gmt: Option<Timestamp>,
+    pub unlock_token_hash: Option<String>, // SHA-256 hex, the raw token is never stored
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
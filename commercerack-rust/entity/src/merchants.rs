@@ -0,0 +1,23 @@
+//! Merchant (a store on the platform) entity definition
+
+use commercerack_core::Timestamp;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "merchants")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub mid: i32, // the `mid` every other table is keyed on
+    pub name: String,
+    pub sdomain: String, // storefront domain, lowercase; unique
+    pub settings: String, // JSON; onboarding starts it from commercerack_merchant::default_settings
+    pub status: String, // see commercerack_merchant::MerchantStatus
+    pub created_gmt: Timestamp,
+    pub modified_gmt: Timestamp,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
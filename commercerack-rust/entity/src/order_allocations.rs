@@ -0,0 +1,21 @@
+//! Order allocation (which warehouse ships an order's units) entity definition
+
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "order_allocations")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub mid: i32,
+    pub order_id: i32,
+    pub warehouse_id: i32,
+    pub sku: String,
+    pub quantity: i32, // drops as cancelled or returned units go back to the warehouse
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,26 @@
+//! Order download (expiring link to a purchased file) entity definition
+
+use commercerack_core::Timestamp;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "order_downloads")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub mid: i32,
+    pub order_id: i32,
+    pub order_item_id: i32,
+    pub sku: String,
+    pub token: String, // the buyer's credential for /api/downloads/{token}
+    pub downloads: i32, // times the file was fetched
+    pub max_downloads: i32,
+    pub expires_gmt: Timestamp,
+    pub created_gmt: Timestamp,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
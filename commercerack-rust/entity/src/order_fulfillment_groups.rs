@@ -0,0 +1,26 @@
+//! Order fulfillment group entity definition: a planned shipment of an
+//! order's units from one warehouse, with its own shipping method and cost
+
+use commercerack_core::Timestamp;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "order_fulfillment_groups")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub mid: i32,
+    pub order_id: i32,
+    pub warehouse_id: Option<i32>, // none for units no warehouse held
+    pub carrier: String,
+    pub ship_method: String,
+    pub ship_name: String,
+    pub shipping: Decimal, // charged to the buyer
+    pub created_gmt: Timestamp,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
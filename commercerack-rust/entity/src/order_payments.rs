@@ -0,0 +1,25 @@
+//! Order payment (one tender towards an order's total) entity definition
+
+use commercerack_core::Timestamp;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "order_payments")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub mid: i32,
+    pub order_id: i32,
+    pub reference: Option<String>, // gateway transaction, for capture/refund/void and notifications
+    pub amount: Decimal,
+    pub refunded: Decimal, // part of `amount` given back so far
+    pub status: String, // see commercerack_payment::TransactionStatus
+    pub created_gmt: Timestamp,
+    pub modified_gmt: Timestamp,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
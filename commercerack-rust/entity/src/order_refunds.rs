@@ -0,0 +1,27 @@
+//! Order refund entity definition: money given back for some of an
+//! order's items, with their share of its tax and shipping
+
+use commercerack_core::Timestamp;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "order_refunds")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub mid: i32,
+    pub order_id: i32,
+    pub items_amount: Decimal, // net of the items' share of discounts
+    pub tax_amount: Decimal,
+    pub shipping_amount: Decimal,
+    pub amount: Decimal, // refunded through the payment gateway
+    pub reason: String,
+    pub created_gmt: Timestamp,
+    pub refunded_gmt: Option<Timestamp>, // NULL while the gateway has not confirmed it
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
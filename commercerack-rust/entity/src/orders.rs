@@ -1,5 +1,6 @@
 //! Order entity definition
 
+use commercerack_core::Timestamp;
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 
@@ -14,12 +15,46 @@ pub struct Model {
     pub customer: i32,
     pub pool: String,
     pub total: Decimal,
-    pub created_gmt: i32,
-    pub paid_gmt: Option<i32>,
-    pub shipped_gmt: Option<i32>,
+    pub created_gmt: Timestamp,
+    pub paid_gmt: Option<Timestamp>,
+    pub paid_txn: Option<String>, // gateway transaction reference
+    pub order_payment_status: Option<String>, // see commercerack_order::payment::PaymentStatus
+    pub order_payment_lookup: Option<String>, // provider reference, e.g. the PayPal order ID
+    pub bs_settlement: Option<Timestamp>, // when the payment settled
+    pub shipped_gmt: Option<Timestamp>,
+    pub inv_gmt: Option<Timestamp>, // when the invoice was issued; see commercerack_order::invoices
+    pub company_id: Option<i32>, // B2B account the order was placed for
+    pub review_status: Option<String>, // legacy fraud review code, e.g. `AOK`
+    pub ship_method: Option<String>, // shipping method code chosen at checkout
+    pub bill_email: String, // billing email; how guest orders (customer 0) are claimed
+    pub mkt: i32, // bit of the marketplace the order was placed on; 0 for the storefront
+    #[sea_orm(column_name = "order_erefid")]
+    pub erefid: Option<String>, // the marketplace's own order reference
+    pub mkt_bitstr: String, // `mkt` as a comma-separated list of its set bits, for legacy reports
+    pub pickup_location_id: Option<i32>, // store the buyer collects the order from; None when it ships
+    pub pickup_ready_gmt: Option<Timestamp>, // when the buyer was told the order is ready to collect
+    pub picked_up_gmt: Option<Timestamp>, // when the buyer collected it
+    pub cancelled_gmt: Option<Timestamp>,
+    pub cancel_reason: Option<String>, // reason code; see commercerack_order::cancellation::CancelReason
+    pub v: i32, // version, bumped on every update; see commercerack_order::OrderService::update
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {}
 
-impl ActiveModelBehavior for ActiveModel {}
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    /// Bump the version on every update so a client still holding the
+    /// previous one can't overwrite this change
+    async fn before_save<C>(mut self, _db: &C, insert: bool) -> Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if !insert {
+            if let Some(v) = self.v.try_as_ref().copied() {
+                self.v = sea_orm::Set(v + 1);
+            }
+        }
+        Ok(self)
+    }
+}
@@ -1,5 +1,76 @@
 //! Entity prelude - re-exports commonly used types
 
 pub use super::customers::{Entity as Customers, Model as Customer};
+pub use super::customer_addrs::{Entity as CustomerAddrs, Model as CustomerAddr};
+pub use super::refresh_tokens::{Entity as RefreshTokens, Model as RefreshToken};
+pub use super::wishlists::{Entity as Wishlists, Model as Wishlist};
+pub use super::wishlist_items::{Entity as WishlistItems, Model as WishlistItem};
 pub use super::products::{Entity as Products, Model as Product};
+pub use super::categories::{Entity as Categories, Model as Category};
+pub use super::product_categories::{Entity as ProductCategories, Model as ProductCategory};
+pub use super::product_media::{Entity as ProductMedia, Model as ProductMediaItem};
 pub use super::orders::{Entity as Orders, Model as Order};
+pub use super::order_items::{Entity as OrderItems, Model as OrderItem};
+pub use super::shipments::{Entity as Shipments, Model as Shipment};
+pub use super::shipment_items::{Entity as ShipmentItems, Model as ShipmentItem};
+pub use super::returns::{Entity as Returns, Model as Return};
+pub use super::return_items::{Entity as ReturnItems, Model as ReturnItem};
+pub use super::skus::{Entity as Skus, Model as Sku};
+pub use super::price_tiers::{Entity as PriceTiers, Model as PriceTier};
+pub use super::carts::{Entity as Carts, Model as CartRecord};
+pub use super::cart_items::{Entity as CartItems, Model as CartItemRecord};
+pub use super::abandoned_carts::{Entity as AbandonedCarts, Model as AbandonedCart};
+pub use super::coupons::{Entity as Coupons, Model as Coupon};
+pub use super::coupon_redemptions::{Entity as CouponRedemptions, Model as CouponRedemption};
+pub use super::gift_cards::{Entity as GiftCards, Model as GiftCard};
+pub use super::gift_card_transactions::{Entity as GiftCardTransactions, Model as GiftCardTransaction};
+pub use super::tax_rates::{Entity as TaxRates, Model as TaxRate};
+pub use super::shipping_zones::{Entity as ShippingZones, Model as ShippingZone};
+pub use super::shipping_rates::{Entity as ShippingRates, Model as ShippingRate};
+pub use super::shipping_transit_times::{Entity as ShippingTransitTimes, Model as ShippingTransitTime};
+pub use super::inventory_reservations::{Entity as InventoryReservations, Model as InventoryReservation};
+pub use super::inventory_adjustments::{Entity as InventoryAdjustments, Model as InventoryAdjustment};
+pub use super::webhook_endpoints::{Entity as WebhookEndpoints, Model as WebhookEndpoint};
+pub use super::webhook_deliveries::{Entity as WebhookDeliveries, Model as WebhookDelivery};
+pub use super::event_outbox::{Entity as EventOutbox, Model as OutboxEvent};
+pub use super::jobs::{Entity as Jobs, Model as QueuedJob};
+pub use super::reports::{Entity as Reports, Model as Report};
+pub use super::audit_log::{Entity as AuditLog, Model as AuditEntry};
+pub use super::customer_data_requests::{Entity as CustomerDataRequests, Model as CustomerDataRequest};
+pub use super::login_attempts::{Entity as LoginAttempts, Model as LoginAttempt};
+pub use super::customer_two_factor::{Entity as CustomerTwoFactors, Model as CustomerTwoFactor};
+pub use super::customer_backup_codes::{Entity as CustomerBackupCodes, Model as CustomerBackupCode};
+pub use super::customer_identities::{Entity as CustomerIdentities, Model as CustomerIdentity};
+pub use super::sessions::{Entity as Sessions, Model as Session};
+pub use super::customer_tags::{Entity as CustomerTags, Model as CustomerTag};
+pub use super::customer_notes::{Entity as CustomerNotes, Model as CustomerNote};
+pub use super::warehouses::{Entity as Warehouses, Model as Warehouse};
+pub use super::warehouse_stock::{Entity as WarehouseStocks, Model as WarehouseStock};
+pub use super::stock_transfers::{Entity as StockTransfers, Model as StockTransfer};
+pub use super::stock_transfer_items::{Entity as StockTransferItems, Model as StockTransferItem};
+pub use super::order_allocations::{Entity as OrderAllocations, Model as OrderAllocation};
+pub use super::purchase_orders::{Entity as PurchaseOrders, Model as PurchaseOrder};
+pub use super::purchase_order_items::{Entity as PurchaseOrderItems, Model as PurchaseOrderItem};
+pub use super::license_keys::{Entity as LicenseKeys, Model as LicenseKey};
+pub use super::order_downloads::{Entity as OrderDownloads, Model as OrderDownload};
+pub use super::order_payments::{Entity as OrderPayments, Model as OrderPayment};
+pub use super::invoices::{Entity as Invoices, Model as Invoice};
+pub use super::invoice_branding::{Entity as InvoiceBrandings, Model as InvoiceBranding};
+pub use super::companies::{Entity as Companies, Model as Company};
+pub use super::company_buyers::{Entity as CompanyBuyers, Model as CompanyBuyer};
+pub use super::customer_carts::{Entity as CustomerCarts, Model as CustomerCart};
+pub use super::channels::{Entity as Channels, Model as Channel};
+pub use super::merchants::{Entity as Merchants, Model as Merchant};
+pub use super::merchant_settings::{Entity as MerchantSettings, Model as MerchantSetting};
+pub use super::archived_orders::{Entity as ArchivedOrders, Model as ArchivedOrder};
+pub use super::customer_recommendations::{Entity as CustomerRecommendations, Model as CustomerRecommendation};
+pub use super::customer_metrics::{Entity as CustomerMetrics, Model as CustomerMetric};
+pub use super::segments::{Entity as Segments, Model as Segment};
+pub use super::segment_members::{Entity as SegmentMembers, Model as SegmentMember};
+pub use super::zones::{Entity as Zones, Model as Zone};
+pub use super::store_locations::{Entity as StoreLocations, Model as StoreLocation};
+pub use super::order_fulfillment_groups::{Entity as OrderFulfillmentGroups, Model as OrderFulfillmentGroup};
+pub use super::order_fulfillment_group_items::{Entity as OrderFulfillmentGroupItems, Model as OrderFulfillmentGroupItem};
+pub use super::order_refunds::{Entity as OrderRefunds, Model as OrderRefund};
+pub use super::order_refund_items::{Entity as OrderRefundItems, Model as OrderRefundItem};
+pub use super::dunning_cases::{Entity as DunningCases, Model as DunningCase};
@@ -0,0 +1,26 @@
+//! Product image and video entity definition
+
+use commercerack_core::Timestamp;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "product_media")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub mid: i32,
+    pub product_id: i32,
+    pub sku_id: Option<i32>, // set when the media shows one variant
+    pub kind: String, // image, video
+    pub url: String,
+    pub alt_text: String,
+    pub position: i32,
+    pub is_primary: bool,
+    pub created_gmt: Timestamp,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -1,5 +1,6 @@
 //! Product entity definition
 
+use commercerack_core::Timestamp;
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 
@@ -11,16 +12,23 @@ pub struct Model {
     pub mid: i32,
     pub merchant: String,
     pub product: String,
-    pub ts: i32,
+    pub ts: Timestamp,
     pub product_name: String,
     pub category: String,
+    pub description: String,
     pub base_price: Decimal,
     pub base_cost: Decimal,
     pub supplier: String,
     pub supplier_id: String,
     pub upc: String,
-    pub created_gmt: i32,
-    pub lastsold_gmt: Option<i32>,
+    pub created_gmt: Timestamp,
+    pub lastsold_gmt: Option<Timestamp>,
+    pub product_type: String, // physical, digital
+    pub download_url: Option<String>, // where a digital product's file is kept
+    pub download_limit: Option<i32>, // downloads per purchase; None for the configured default
+    pub status: String, // draft, active, archived
+    pub available_from: Option<Timestamp>, // on sale from; None when open
+    pub available_until: Option<Timestamp>, // on sale until; None when open
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
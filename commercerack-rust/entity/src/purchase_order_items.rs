@@ -0,0 +1,21 @@
+//! Purchase order line entity definition
+
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "purchase_order_items")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub purchase_order_id: i32,
+    pub sku: String,
+    pub quantity: i32, // units ordered
+    pub received: i32, // units received so far
+    pub unit_cost: Decimal,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
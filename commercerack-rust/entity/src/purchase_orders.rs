@@ -0,0 +1,27 @@
+//! Purchase order (stock ordered from a supplier) entity definition
+
+use commercerack_core::Timestamp;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "purchase_orders")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub mid: i32,
+    pub supplier: String,
+    pub status: String, // draft, sent, partially_received, received
+    pub warehouse_id: Option<i32>, // where received units are booked
+    pub note: Option<String>,
+    pub expected_gmt: Option<Timestamp>, // delivery date promised by the supplier
+    pub actor: Option<i32>, // user that created the purchase order
+    pub created_gmt: Timestamp,
+    pub sent_gmt: Option<Timestamp>,
+    pub received_gmt: Option<Timestamp>, // when the last units came in
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
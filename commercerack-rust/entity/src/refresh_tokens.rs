@@ -0,0 +1,25 @@
+//! Refresh token entity definition
+
+use commercerack_core::Timestamp;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "refresh_tokens")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub mid: i32,
+    pub cid: i32,
+    pub token_hash: String, // SHA-256 hex, the raw token is never stored
+    pub created_gmt: Timestamp,
+    pub expires_gmt: Timestamp,
+    pub revoked_gmt: Option<Timestamp>,
+    pub replaced_by: Option<i32>,
+    pub session_id: Option<i32>, // null for tokens from before sessions
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,29 @@
+//! Sales report entity definition
+
+use commercerack_core::Timestamp;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "reports")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub mid: i32,
+    pub period: String, // see commercerack_reports::ReportPeriod
+    pub from_gmt: Timestamp,
+    pub to_gmt: Timestamp, // exclusive
+    pub orders: i32,
+    pub revenue: Decimal,
+    pub tax: Decimal,
+    pub shipping: Decimal,
+    pub refunds: Decimal,
+    pub csv: Option<String>, // the generated report
+    pub created_gmt: Timestamp,
+    pub generated_gmt: Option<Timestamp>, // None until the report job has run
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,25 @@
+//! Customer segment entity definition
+
+use commercerack_core::Timestamp;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "segments")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub mid: i32,
+    pub name: String,
+    pub description: String,
+    pub rules: String, // JSON of commercerack_customer::segments::SegmentRules
+    pub members: i32, // customers in the segment at the last refresh
+    pub created_gmt: Timestamp,
+    pub modified_gmt: Timestamp,
+    pub computed_gmt: Option<Timestamp>, // unset until the first refresh
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,26 @@
+//! Shipment entity definition
+
+use commercerack_core::Timestamp;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "shipments")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub mid: i32,
+    pub order_id: i32,
+    pub carrier: String,
+    pub tracking_number: String,
+    pub shipped_gmt: Timestamp,
+    pub label_provider: Option<String>, // label service the label was bought from, e.g. `shippo`
+    pub label_id: Option<String>, // the service's reference, used to void the label
+    pub label_url: Option<String>,
+    pub label_cost: Option<Decimal>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,26 @@
+//! Shipping rate entity definition
+
+use commercerack_core::Timestamp;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "shipping_rates")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub mid: i32,
+    pub zone_id: i32,
+    pub method: String, // code stored on the order, e.g. "ground"
+    pub name: String, // shown to the shopper
+    pub basis: String, // flat, weight, price
+    pub min_value: Decimal, // inclusive lower bound of the tier
+    pub max_value: Option<Decimal>, // exclusive upper bound; None is unbounded
+    pub amount: Decimal,
+    pub created_gmt: Timestamp,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,25 @@
+//! Shipping zone entity definition
+
+use commercerack_core::Timestamp;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "shipping_zones")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub mid: i32,
+    pub name: String,
+    pub countries: String, // comma-separated ISO codes, uppercase
+    pub states: String, // comma-separated; empty matches every state
+    pub zips: String, // comma-separated prefixes; empty matches every zip
+    pub position: i32, // the first matching zone by position wins
+    pub zone_id: Option<i32>, // geo zone covered instead of the lists above
+    pub created_gmt: Timestamp,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,30 @@
+//! SKU entity definition
+
+use commercerack_core::Timestamp;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "sku_lookup")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub pid: i32,
+    pub mid: i32,
+    pub sku: String,
+    pub title: String,
+    pub price: Decimal,
+    pub cost: Decimal,
+    pub upc: String,
+    pub inv_available: i32,
+    pub qty_onshelf: i32,
+    pub weight: Decimal, // per unit, in the merchant's weight unit
+    pub reorder_point: Option<i32>, // stock at or below this is low; None turns alerts off
+    pub reorder_quantity: i32, // units to order when stock runs low
+    pub low_stock_gmt: Option<Timestamp>, // when the current low stock alert went out
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
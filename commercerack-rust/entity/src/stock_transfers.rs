@@ -0,0 +1,25 @@
+//! Stock transfer (between warehouses) entity definition
+
+use commercerack_core::Timestamp;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "stock_transfers")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub mid: i32,
+    pub from_warehouse: i32,
+    pub to_warehouse: i32,
+    pub status: String, // in_transit, received, cancelled
+    pub note: Option<String>,
+    pub actor: Option<i32>, // user that created the transfer
+    pub created_gmt: Timestamp,
+    pub closed_gmt: Option<Timestamp>, // when it was received or cancelled
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
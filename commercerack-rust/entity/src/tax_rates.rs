@@ -0,0 +1,26 @@
+//! Tax rate entity definition
+
+use commercerack_core::Timestamp;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "tax_rates")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub mid: i32,
+    pub name: String, // shown on the tax line, e.g. "CA State Tax"
+    pub country: String, // ISO 3166-1 alpha-2, uppercase
+    pub state: String, // empty matches every state
+    pub zip: String, // prefix; empty matches every zip
+    pub rate: Decimal, // percent, e.g. 7.2500
+    pub zone_id: Option<i32>, // geo zone the rate covers instead of country, state and zip
+    pub created_gmt: Timestamp,
+    pub modified_gmt: Timestamp,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
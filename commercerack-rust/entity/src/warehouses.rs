@@ -0,0 +1,27 @@
+//! Warehouse (stock location) entity definition
+
+use commercerack_core::Timestamp;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "warehouses")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub mid: i32,
+    pub code: String, // uppercase, unique per merchant
+    pub name: String,
+    pub country: String, // ISO 3166-1 alpha-2
+    pub state: String,
+    pub priority: i32, // lower ships first among otherwise equal warehouses
+    pub active: bool, // inactive warehouses are never allocated from
+    pub handling_days: i32, // business days between an order and its parcel leaving
+    pub cutoff_hour: i32, // hour of the merchant's day after which orders count from the next business day
+    pub created_gmt: Timestamp,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,28 @@
+//! Webhook delivery (outbound queue) entity definition
+
+use commercerack_core::Timestamp;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "webhook_deliveries")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub mid: i32,
+    pub endpoint_id: i32,
+    pub event: String,
+    pub payload: String, // JSON body, signed as sent
+    pub status: String, // pending, delivered, failed
+    pub attempts: i32,
+    pub next_attempt_gmt: Timestamp,
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    pub created_gmt: Timestamp,
+    pub delivered_gmt: Option<Timestamp>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,35 @@
+//! Wishlist item entity definition
+
+use commercerack_core::Timestamp;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "wishlist_items")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub wishlist_id: i32,
+    pub mid: i32,
+    pub pid: i32,
+    pub sku: String,
+    pub added_gmt: Timestamp,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::wishlists::Entity",
+        from = "Column::WishlistId",
+        to = "super::wishlists::Column::Id"
+    )]
+    Wishlist,
+}
+
+impl Related<super::wishlists::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Wishlist.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
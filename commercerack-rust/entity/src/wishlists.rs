@@ -0,0 +1,31 @@
+//! Wishlist entity definition
+
+use commercerack_core::Timestamp;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "wishlists")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub mid: i32,
+    pub cid: i32,
+    pub share_token: Option<String>, // set while the wishlist is shared
+    pub created_gmt: Timestamp,
+    pub modified_gmt: Timestamp,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(has_many = "super::wishlist_items::Entity")]
+    WishlistItems,
+}
+
+impl Related<super::wishlist_items::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::WishlistItems.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
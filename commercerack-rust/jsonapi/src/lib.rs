@@ -1 +1 @@
-fn main() {}
+//! JSON API compatibility layer (not yet implemented)
@@ -22,6 +22,73 @@ mod m20251117_000019_create_campaigns;
 mod m20251117_000020_create_campaign_recipients;
 mod m20251117_000021_create_projects;
 mod m20251117_000022_create_checkouts;
+mod m20251117_000023_fix_legacy_tables;
+mod m20251118_000023_alter_customer_addrs;
+mod m20251118_000024_create_carts;
+mod m20251118_000025_create_abandoned_carts;
+mod m20251118_000026_create_order_items;
+mod m20251118_000027_create_refresh_tokens;
+mod m20251118_000028_create_inventory_tracking;
+mod m20251118_000029_widen_orders_paid_txn;
+mod m20251118_000030_widen_orders_payment_lookup;
+mod m20251118_000031_create_webhooks;
+mod m20251118_000032_add_products_search;
+mod m20251118_000033_create_categories;
+mod m20251118_000034_create_product_media;
+mod m20251118_000035_create_coupons;
+mod m20251118_000036_create_tax_rates;
+mod m20251118_000037_create_shipping_rates;
+mod m20251118_000038_create_shipments;
+mod m20251118_000039_create_returns;
+mod m20251118_000040_add_orders_bill_email;
+mod m20251118_000041_create_event_outbox;
+mod m20251118_000042_create_jobs;
+mod m20251118_000043_alter_orders_version;
+mod m20251118_000044_create_wishlists;
+mod m20251118_000045_create_price_tiers;
+mod m20251118_000046_create_gift_cards;
+mod m20251118_000047_track_abandoned_carts;
+mod m20251118_000048_create_reports;
+mod m20251118_000049_create_audit_log;
+mod m20251118_000050_create_customer_data_requests;
+mod m20251118_000051_widen_customer_pii;
+mod m20251118_000052_create_login_attempts;
+mod m20251118_000053_create_two_factor;
+mod m20251118_000054_create_customer_identities;
+mod m20251118_000055_create_sessions;
+mod m20251118_000056_create_customer_tags;
+mod m20251118_000057_create_warehouses;
+mod m20251118_000058_add_sku_reorder_points;
+mod m20251118_000059_create_purchase_orders;
+mod m20251118_000060_create_digital_goods;
+mod m20251118_000061_create_order_payments;
+mod m20251118_000062_create_invoices;
+mod m20251118_000063_create_companies;
+mod m20251118_000064_add_item_options;
+mod m20251118_000065_create_customer_carts;
+mod m20251118_000066_widen_timestamps;
+mod m20251118_000067_create_channels;
+mod m20251118_000068_alter_orders_mkt_bitstr;
+mod m20251118_000069_add_channel_inventory_push;
+mod m20251118_000070_create_merchants;
+mod m20251118_000071_create_merchant_settings;
+mod m20251118_000072_add_orders_pool_indexes;
+mod m20251118_000073_create_archived_orders;
+mod m20251118_000074_add_products_availability;
+mod m20251118_000075_add_event_outbox_catalog_index;
+mod m20251118_000076_create_customer_recommendations;
+mod m20251118_000077_create_customer_segments;
+mod m20251118_000078_create_zones;
+mod m20251118_000079_add_shipments_label;
+mod m20251118_000080_add_delivery_estimates;
+mod m20251118_000081_create_store_locations;
+mod m20251118_000082_create_order_fulfillment_groups;
+mod m20251118_000083_add_order_cancellation;
+mod m20251118_000084_create_order_refunds;
+mod m20251118_000085_create_dunning_cases;
+mod m20251118_000086_add_customers_role;
+mod m20251118_000087_alter_legacy_money_columns;
+mod m20251118_000088_add_carts_currency;
 
 pub struct Migrator;
 
@@ -51,6 +118,192 @@ impl MigratorTrait for Migrator {
             Box::new(m20251117_000020_create_campaign_recipients::Migration),
             Box::new(m20251117_000021_create_projects::Migration),
             Box::new(m20251117_000022_create_checkouts::Migration),
+            Box::new(m20251117_000023_fix_legacy_tables::Migration),
+            Box::new(m20251118_000023_alter_customer_addrs::Migration),
+            Box::new(m20251118_000024_create_carts::Migration),
+            Box::new(m20251118_000025_create_abandoned_carts::Migration),
+            Box::new(m20251118_000026_create_order_items::Migration),
+            Box::new(m20251118_000027_create_refresh_tokens::Migration),
+            Box::new(m20251118_000028_create_inventory_tracking::Migration),
+            Box::new(m20251118_000029_widen_orders_paid_txn::Migration),
+            Box::new(m20251118_000030_widen_orders_payment_lookup::Migration),
+            Box::new(m20251118_000031_create_webhooks::Migration),
+            Box::new(m20251118_000032_add_products_search::Migration),
+            Box::new(m20251118_000033_create_categories::Migration),
+            Box::new(m20251118_000034_create_product_media::Migration),
+            Box::new(m20251118_000035_create_coupons::Migration),
+            Box::new(m20251118_000036_create_tax_rates::Migration),
+            Box::new(m20251118_000037_create_shipping_rates::Migration),
+            Box::new(m20251118_000038_create_shipments::Migration),
+            Box::new(m20251118_000039_create_returns::Migration),
+            Box::new(m20251118_000040_add_orders_bill_email::Migration),
+            Box::new(m20251118_000041_create_event_outbox::Migration),
+            Box::new(m20251118_000042_create_jobs::Migration),
+            Box::new(m20251118_000043_alter_orders_version::Migration),
+            Box::new(m20251118_000044_create_wishlists::Migration),
+            Box::new(m20251118_000045_create_price_tiers::Migration),
+            Box::new(m20251118_000046_create_gift_cards::Migration),
+            Box::new(m20251118_000047_track_abandoned_carts::Migration),
+            Box::new(m20251118_000048_create_reports::Migration),
+            Box::new(m20251118_000049_create_audit_log::Migration),
+            Box::new(m20251118_000050_create_customer_data_requests::Migration),
+            Box::new(m20251118_000051_widen_customer_pii::Migration),
+            Box::new(m20251118_000052_create_login_attempts::Migration),
+            Box::new(m20251118_000053_create_two_factor::Migration),
+            Box::new(m20251118_000054_create_customer_identities::Migration),
+            Box::new(m20251118_000055_create_sessions::Migration),
+            Box::new(m20251118_000056_create_customer_tags::Migration),
+            Box::new(m20251118_000057_create_warehouses::Migration),
+            Box::new(m20251118_000058_add_sku_reorder_points::Migration),
+            Box::new(m20251118_000059_create_purchase_orders::Migration),
+            Box::new(m20251118_000060_create_digital_goods::Migration),
+            Box::new(m20251118_000061_create_order_payments::Migration),
+            Box::new(m20251118_000062_create_invoices::Migration),
+            Box::new(m20251118_000063_create_companies::Migration),
+            Box::new(m20251118_000064_add_item_options::Migration),
+            Box::new(m20251118_000065_create_customer_carts::Migration),
+            Box::new(m20251118_000066_widen_timestamps::Migration),
+            Box::new(m20251118_000067_create_channels::Migration),
+            Box::new(m20251118_000068_alter_orders_mkt_bitstr::Migration),
+            Box::new(m20251118_000069_add_channel_inventory_push::Migration),
+            Box::new(m20251118_000070_create_merchants::Migration),
+            Box::new(m20251118_000071_create_merchant_settings::Migration),
+            Box::new(m20251118_000072_add_orders_pool_indexes::Migration),
+            Box::new(m20251118_000073_create_archived_orders::Migration),
+            Box::new(m20251118_000074_add_products_availability::Migration),
+            Box::new(m20251118_000075_add_event_outbox_catalog_index::Migration),
+            Box::new(m20251118_000076_create_customer_recommendations::Migration),
+            Box::new(m20251118_000077_create_customer_segments::Migration),
+            Box::new(m20251118_000078_create_zones::Migration),
+            Box::new(m20251118_000079_add_shipments_label::Migration),
+            Box::new(m20251118_000080_add_delivery_estimates::Migration),
+            Box::new(m20251118_000081_create_store_locations::Migration),
+            Box::new(m20251118_000082_create_order_fulfillment_groups::Migration),
+            Box::new(m20251118_000083_add_order_cancellation::Migration),
+            Box::new(m20251118_000084_create_order_refunds::Migration),
+            Box::new(m20251118_000085_create_dunning_cases::Migration),
+            Box::new(m20251118_000086_add_customers_role::Migration),
+            Box::new(m20251118_000087_alter_legacy_money_columns::Migration),
+            Box::new(m20251118_000088_add_carts_currency::Migration),
         ]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sea_orm_migration::sea_orm::{
+        ColumnTrait, ColumnType, ConnectionTrait, Database, DatabaseConnection, DbBackend, EntityName, IdenStatic,
+        Iterable, Statement,
+    };
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_every_migration_file_is_registered_in_order() {
+        let registered: Vec<String> = Migrator::migrations().iter().map(|m| m.name().to_string()).collect();
+
+        let mut sorted = registered.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(registered, sorted, "migrations must be registered once each, oldest first");
+
+        let mut files: Vec<String> = std::fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/src"))
+            .unwrap()
+            .filter_map(|entry| entry.unwrap().file_name().into_string().ok())
+            .filter_map(|name| name.strip_suffix(".rs").map(str::to_string))
+            .filter(|name| name.strip_prefix('m').is_some_and(|rest| rest.starts_with(|c: char| c.is_ascii_digit())))
+            .collect();
+        files.sort();
+        assert_eq!(files, registered, "every migration file needs a `mod` and a `Box::new` line");
+    }
+
+    /// Postgres type names each entity column type can be read from
+    fn reads_from(column_type: &ColumnType, udt_name: &str) -> bool {
+        match column_type {
+            ColumnType::Integer => udt_name == "int4",
+            ColumnType::BigInteger => udt_name == "int8",
+            ColumnType::String(_) | ColumnType::Char(_) | ColumnType::Text => {
+                matches!(udt_name, "varchar" | "text" | "bpchar")
+            }
+            ColumnType::Decimal(_) | ColumnType::Money(_) => udt_name == "numeric",
+            ColumnType::Boolean => udt_name == "bool",
+            ColumnType::Double => udt_name == "float8",
+            ColumnType::Binary(_) | ColumnType::VarBinary(_) | ColumnType::Blob => udt_name == "bytea",
+            _ => true,
+        }
+    }
+
+    /// Every way the migrated schema can't hold what the entities read and write
+    async fn entity_mismatches(db: &DatabaseConnection) -> Vec<String> {
+        let rows = db
+            .query_all(Statement::from_string(
+                DbBackend::Postgres,
+                "SELECT table_name::text, column_name::text, udt_name::text, is_nullable::text \
+                 FROM information_schema.columns WHERE table_schema = 'public'",
+            ))
+            .await
+            .unwrap();
+        let mut schema = HashMap::new();
+        for row in rows {
+            let table: String = row.try_get_by_index(0).unwrap();
+            let column: String = row.try_get_by_index(1).unwrap();
+            let udt_name: String = row.try_get_by_index(2).unwrap();
+            let nullable: String = row.try_get_by_index(3).unwrap();
+            schema.insert((table, column), (udt_name, nullable == "YES"));
+        }
+
+        let mut mismatches = Vec::new();
+        macro_rules! check {
+            ($($entity:ident,)*) => {$(
+                let table = entity::$entity::Entity.table_name();
+                for column in entity::$entity::Column::iter() {
+                    let def = column.def();
+                    match schema.get(&(table.to_string(), column.as_str().to_string())) {
+                        None => mismatches.push(format!("{}.{} is missing", table, column.as_str())),
+                        Some((udt_name, _)) if !reads_from(def.get_column_type(), udt_name) => mismatches.push(
+                            format!("{}.{} is {}, not {:?}", table, column.as_str(), udt_name, def.get_column_type()),
+                        ),
+                        Some((_, true)) if !def.is_null() => {
+                            mismatches.push(format!("{}.{} is nullable", table, column.as_str()))
+                        }
+                        Some(_) => {}
+                    }
+                }
+            )*};
+        }
+        check!(
+            customers, customer_addrs, refresh_tokens, wishlists, wishlist_items, products, categories,
+            product_categories, product_media, orders, order_items, shipments, shipment_items, returns, return_items,
+            skus, price_tiers, carts, cart_items, abandoned_carts, coupons, coupon_redemptions, gift_cards,
+            gift_card_transactions, tax_rates, shipping_zones, shipping_rates, shipping_transit_times,
+            inventory_reservations, inventory_adjustments, webhook_endpoints, webhook_deliveries, event_outbox, jobs,
+            reports, audit_log, customer_data_requests, login_attempts, customer_two_factor, customer_backup_codes,
+            customer_identities, sessions, customer_tags, customer_notes, warehouses, warehouse_stock, stock_transfers,
+            stock_transfer_items, order_allocations, purchase_orders, purchase_order_items, license_keys,
+            order_downloads, order_payments, invoices, invoice_branding, companies, company_buyers, customer_carts,
+            channels, merchants, merchant_settings, archived_orders, customer_recommendations, customer_metrics,
+            segments, segment_members, zones, store_locations, order_fulfillment_groups, order_fulfillment_group_items,
+            order_refunds, order_refund_items, dunning_cases,
+        );
+        mismatches
+    }
+
+    /// Runs against the empty Postgres database in `TEST_DATABASE_URL`, and
+    /// is skipped without one
+    #[tokio::test]
+    async fn test_migrations_build_the_schema_the_entities_read() {
+        let Ok(url) = std::env::var("TEST_DATABASE_URL") else {
+            return;
+        };
+        let db = Database::connect(&url).await.unwrap();
+
+        Migrator::reset(&db).await.unwrap();
+        Migrator::up(&db, None).await.unwrap();
+        assert_eq!(entity_mismatches(&db).await, Vec::<String>::new());
+
+        // Going all the way down and back up again lands in the same place
+        Migrator::reset(&db).await.unwrap();
+        Migrator::up(&db, None).await.unwrap();
+        assert_eq!(entity_mismatches(&db).await, Vec::<String>::new());
+    }
+}
@@ -25,7 +25,6 @@ impl MigrationTrait for Migration {
                         ColumnDef::new(Zusers::PasswordChanged)
                             .timestamp()
                             .null()
-                            .default("0000-00-00 00:00:00")
                     )
                     .col(
                         ColumnDef::new(Zusers::Reseller)
@@ -36,7 +35,6 @@ impl MigrationTrait for Migration {
                         ColumnDef::new(Zusers::LastLogin)
                             .timestamp()
                             .null()
-                            .default("0000-00-00 00:00:00")
                     )
                     .col(
                         ColumnDef::new(Zusers::Logins)
@@ -102,25 +100,21 @@ impl MigrationTrait for Migration {
                         ColumnDef::new(Zusers::BillProvisioned)
                             .date()
                             .null()
-                            .default("0000-00-00")
                     )
                     .col(
                         ColumnDef::new(Zusers::BillNextrun)
                             .date()
                             .null()
-                            .default("0000-00-00")
                     )
                     .col(
                         ColumnDef::new(Zusers::BillLastexec)
                             .timestamp()
                             .null()
-                            .default("0000-00-00 00:00:00")
                     )
                     .col(
                         ColumnDef::new(Zusers::BillOrderdate)
                             .timestamp()
                             .null()
-                            .default("0000-00-00 00:00:00")
                     )
                     .col(
                         ColumnDef::new(Zusers::InvoiceCount)
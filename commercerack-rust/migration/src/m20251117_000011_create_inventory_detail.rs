@@ -100,7 +100,6 @@ impl MigrationTrait for Migration {
                         ColumnDef::new(InventoryDetail::MarketEndsTs)
                             .timestamp()
                             .null()
-                            .default("0000-00-00 00:00:00")
                     )
                     .col(
                         ColumnDef::new(InventoryDetail::MarketSoldQty)
@@ -111,7 +110,6 @@ impl MigrationTrait for Migration {
                         ColumnDef::new(InventoryDetail::MarketSaleTs)
                             .timestamp()
                             .null()
-                            .default("0000-00-00 00:00:00")
                     )
                     .col(
                         ColumnDef::new(InventoryDetail::Preference)
@@ -122,7 +120,6 @@ impl MigrationTrait for Migration {
                         ColumnDef::new(InventoryDetail::ModifiedTs)
                             .timestamp()
                             .null()
-                            .default("0000-00-00 00:00:00")
                     )
                     .col(
                         ColumnDef::new(InventoryDetail::ModifiedBy)
@@ -143,7 +140,6 @@ impl MigrationTrait for Migration {
                         ColumnDef::new(InventoryDetail::VerifyTs)
                             .timestamp()
                             .null()
-                            .default("0000-00-00 00:00:00")
                     )
                     .col(
                         ColumnDef::new(InventoryDetail::VerifyInc)
@@ -169,7 +165,6 @@ impl MigrationTrait for Migration {
                         ColumnDef::new(InventoryDetail::PickDoneTs)
                             .timestamp()
                             .null()
-                            .default("0000-00-00 00:00:00")
                     )
                     .col(
                         ColumnDef::new(InventoryDetail::GrpasmRef)
@@ -66,37 +66,31 @@ impl MigrationTrait for Migration {
                         ColumnDef::new(BatchJobs::QueuedTs)
                             .timestamp()
                             .null()
-                            .default("0000-00-00 00:00:00")
                     )
                     .col(
                         ColumnDef::new(BatchJobs::StartTs)
                             .timestamp()
                             .null()
-                            .default("0000-00-00 00:00:00")
                     )
                     .col(
                         ColumnDef::new(BatchJobs::EstdoneTs)
                             .timestamp()
                             .null()
-                            .default("0000-00-00 00:00:00")
                     )
                     .col(
                         ColumnDef::new(BatchJobs::EndTs)
                             .timestamp()
                             .null()
-                            .default("0000-00-00 00:00:00")
                     )
                     .col(
                         ColumnDef::new(BatchJobs::ArchivedTs)
                             .timestamp()
                             .null()
-                            .default("0000-00-00 00:00:00")
                     )
                     .col(
                         ColumnDef::new(BatchJobs::AbortedTs)
                             .timestamp()
                             .null()
-                            .default("0000-00-00 00:00:00")
                     )
                     .col(
                         ColumnDef::new(BatchJobs::Title)
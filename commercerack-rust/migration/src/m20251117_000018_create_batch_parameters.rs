@@ -40,7 +40,6 @@ impl MigrationTrait for Migration {
                         ColumnDef::new(BatchParameters::LastrunTs)
                             .timestamp()
                             .null()
-                            .default("0000-00-00 00:00:00")
                     )
                     .col(
                         ColumnDef::new(BatchParameters::LastjobId)
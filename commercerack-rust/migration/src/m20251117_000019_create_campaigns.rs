@@ -85,7 +85,6 @@ impl MigrationTrait for Migration {
                         ColumnDef::new(Campaigns::Expires)
                             .timestamp()
                             .null()
-                            .default("0000-00-00 00:00:00")
                     )
                     .col(
                         ColumnDef::new(Campaigns::Coupon)
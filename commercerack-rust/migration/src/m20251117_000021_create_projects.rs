@@ -15,7 +15,6 @@ impl MigrationTrait for Migration {
                         ColumnDef::new(Projects::UpdatedTs)
                             .timestamp()
                             .null()
-                            .default("0000-00-00 00:00:00")
                     )
                     .col(
                         ColumnDef::new(Projects::Mid)
@@ -0,0 +1,278 @@
+//! Bring the legacy tables in line with the entities that read them
+//!
+//! The tables above were carried over from MySQL without the surrogate keys
+//! and creation times the entities are read by, with the order total under
+//! its old name, with `sku_lookup.pid` holding the product's code rather
+//! than its id, and with every column nullable. Fix them up before any
+//! later migration points a foreign key at them.
+
+use sea_orm_migration::prelude::*;
+
+/// Legacy numeric columns the entities read as never NULL; NULLs become 0,
+/// which is also their default from now on
+const NUMBER_COLUMNS: &[(&str, &[&str])] = &[
+    ("customer_addrs", &["cid", "mid"]),
+    ("customers", &["mid", "modified_gmt"]),
+    ("orders", &["mid", "customer"]),
+    ("products", &["mid", "ts", "base_price", "base_cost"]),
+    ("sku_lookup", &["mid", "inv_available", "qty_onshelf"]),
+];
+
+/// Legacy text columns the entities read as never NULL; NULLs become empty
+const TEXT_COLUMNS: &[(&str, &[&str])] = &[
+    (
+        "customer_addrs",
+        &["label", "firstname", "lastname", "company", "city", "state", "zip", "country", "phone"],
+    ),
+    ("customers", &["email", "firstname", "lastname", "passhash", "passsalt"]),
+    ("orders", &["orderid", "cartid", "pool"]),
+    ("products", &["merchant", "product", "product_name", "category", "supplier", "supplier_id", "upc"]),
+    ("sku_lookup", &["sku", "title", "upc"]),
+];
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+async fn require(manager: &SchemaManager<'_>, columns: &[(&str, &[&str])], zero: &str) -> Result<(), DbErr> {
+    for (table, columns) in columns {
+        for column in *columns {
+            manager
+                .get_connection()
+                .execute_unprepared(&format!("UPDATE {table} SET {column} = {zero} WHERE {column} IS NULL"))
+                .await?;
+        }
+
+        let mut alter = Table::alter();
+        alter.table(Alias::new(*table));
+        for column in *columns {
+            alter.modify_column(ColumnDef::new(Alias::new(*column)).not_null().default(Expr::cust(zero)));
+        }
+        manager.alter_table(alter).await?;
+    }
+    Ok(())
+}
+
+async fn allow_null(manager: &SchemaManager<'_>, columns: &[(&str, &[&str])]) -> Result<(), DbErr> {
+    for (table, columns) in columns {
+        let mut alter = Table::alter();
+        alter.table(Alias::new(*table));
+        for column in *columns {
+            alter.modify_column(ColumnDef::new(Alias::new(*column)).null().default(Expr::cust("NULL")));
+        }
+        manager.alter_table(alter).await?;
+    }
+    Ok(())
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Orders::Table)
+                    .add_column(
+                        ColumnDef::new(Orders::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .add_column(
+                        ColumnDef::new(Orders::CreatedGmt)
+                            .integer()
+                            .not_null()
+                            .default(0)
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Postgres renames in a statement of its own
+        manager
+            .alter_table(Table::alter().table(Orders::Table).rename_column(Orders::OrderTotal, Orders::Total).to_owned())
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Customers::Table)
+                    .add_column(
+                        ColumnDef::new(Customers::Cid)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .add_column(
+                        ColumnDef::new(Customers::CreatedGmt)
+                            .integer()
+                            .not_null()
+                            .default(0)
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Products::Table)
+                    .add_column(
+                        ColumnDef::new(Products::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .add_column(
+                        ColumnDef::new(Products::CreatedGmt)
+                            .integer()
+                            .not_null()
+                            .default(0)
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(SkuLookup::Table)
+                    .add_column(
+                        ColumnDef::new(SkuLookup::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // A SKU names its product by the product's code; swap that for the
+        // product's new id, or 0 where no such product exists
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "UPDATE sku_lookup SET pid = COALESCE(
+                     (SELECT MIN(products.id)::text FROM products
+                      WHERE products.mid = sku_lookup.mid AND products.product = sku_lookup.pid),
+                     '0')",
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(SkuLookup::Table)
+                    .modify_column(
+                        ColumnDef::new(SkuLookup::Pid)
+                            .integer()
+                            .using(Expr::cust("pid::integer"))
+                            .not_null()
+                            .default(0)
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        require(manager, NUMBER_COLUMNS, "0").await?;
+        require(manager, TEXT_COLUMNS, "''").await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        allow_null(manager, TEXT_COLUMNS).await?;
+        allow_null(manager, NUMBER_COLUMNS).await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(SkuLookup::Table)
+                    .modify_column(
+                        ColumnDef::new(SkuLookup::Pid)
+                            .string_len(30)
+                            .using(Expr::cust("pid::text"))
+                            .null()
+                            .default(Expr::cust("NULL"))
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "UPDATE sku_lookup SET pid = products.product FROM products WHERE products.id::text = sku_lookup.pid",
+            )
+            .await?;
+
+        manager
+            .alter_table(Table::alter().table(SkuLookup::Table).drop_column(SkuLookup::Id).to_owned())
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Products::Table)
+                    .drop_column(Products::Id)
+                    .drop_column(Products::CreatedGmt)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Customers::Table)
+                    .drop_column(Customers::Cid)
+                    .drop_column(Customers::CreatedGmt)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(Table::alter().table(Orders::Table).rename_column(Orders::Total, Orders::OrderTotal).to_owned())
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Orders::Table)
+                    .drop_column(Orders::Id)
+                    .drop_column(Orders::CreatedGmt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Orders {
+    Table,
+    Id,
+    CreatedGmt,
+    OrderTotal,
+    Total,
+}
+
+#[derive(DeriveIden)]
+enum Customers {
+    Table,
+    Cid,
+    CreatedGmt,
+}
+
+#[derive(DeriveIden)]
+enum Products {
+    Table,
+    Id,
+    CreatedGmt,
+}
+
+#[derive(DeriveIden)]
+enum SkuLookup {
+    Table,
+    Id,
+    Pid,
+}
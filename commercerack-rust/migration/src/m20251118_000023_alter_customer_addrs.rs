@@ -0,0 +1,73 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CustomerAddrs::Table)
+                    .add_column(
+                        ColumnDef::new(CustomerAddrs::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .add_column(
+                        ColumnDef::new(CustomerAddrs::Address1)
+                            .string_len(60)
+                            .not_null()
+                            .default("")
+                    )
+                    .add_column(
+                        ColumnDef::new(CustomerAddrs::Address2)
+                            .string_len(60)
+                            .not_null()
+                            .default("")
+                    )
+                    .add_column(
+                        ColumnDef::new(CustomerAddrs::IsDefaultBilling)
+                            .boolean()
+                            .not_null()
+                            .default(false)
+                    )
+                    .add_column(
+                        ColumnDef::new(CustomerAddrs::IsDefaultShipping)
+                            .boolean()
+                            .not_null()
+                            .default(false)
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CustomerAddrs::Table)
+                    .drop_column(CustomerAddrs::IsDefaultShipping)
+                    .drop_column(CustomerAddrs::IsDefaultBilling)
+                    .drop_column(CustomerAddrs::Address2)
+                    .drop_column(CustomerAddrs::Address1)
+                    .drop_column(CustomerAddrs::Id)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum CustomerAddrs {
+    Table,
+    Id,
+    Address1,
+    Address2,
+    IsDefaultBilling,
+    IsDefaultShipping,
+}
@@ -0,0 +1,121 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Carts::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Carts::CartId)
+                            .string_len(36)
+                            .not_null()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(Carts::CreatedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Carts::ModifiedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(CartItems::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(CartItems::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(CartItems::CartId)
+                            .string_len(36)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(CartItems::Sku)
+                            .string_len(45)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(CartItems::ProductName)
+                            .string_len(80)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(CartItems::Quantity)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(CartItems::UnitPrice)
+                            .decimal_len(10, 2)
+                            .not_null()
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_cart_items_cart_id")
+                            .from(CartItems::Table, CartItems::CartId)
+                            .to(Carts::Table, Carts::CartId)
+                            .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_cart_items_cart_id")
+                    .table(CartItems::Table)
+                    .col(CartItems::CartId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(CartItems::Table).to_owned())
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(Carts::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Carts {
+    Table,
+    CartId,
+    CreatedGmt,
+    ModifiedGmt,
+}
+
+#[derive(DeriveIden)]
+enum CartItems {
+    Table,
+    Id,
+    CartId,
+    Sku,
+    ProductName,
+    Quantity,
+    UnitPrice,
+}
@@ -0,0 +1,67 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(AbandonedCarts::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(AbandonedCarts::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(AbandonedCarts::CartId)
+                            .string_len(36)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(AbandonedCarts::Items)
+                            .text()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(AbandonedCarts::ItemCount)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(AbandonedCarts::Subtotal)
+                            .decimal_len(10, 2)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(AbandonedCarts::AbandonedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(AbandonedCarts::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum AbandonedCarts {
+    Table,
+    Id,
+    CartId,
+    Items,
+    ItemCount,
+    Subtotal,
+    AbandonedGmt,
+}
@@ -0,0 +1,84 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(OrderItems::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(OrderItems::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(OrderItems::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(OrderItems::OrderId)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(OrderItems::Sku)
+                            .string_len(45)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(OrderItems::ProductName)
+                            .string_len(80)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(OrderItems::Quantity)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(OrderItems::UnitPrice)
+                            .decimal_len(10, 2)
+                            .not_null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_order_items_order_id")
+                    .table(OrderItems::Table)
+                    .col(OrderItems::Mid)
+                    .col(OrderItems::OrderId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(OrderItems::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum OrderItems {
+    Table,
+    Id,
+    Mid,
+    OrderId,
+    Sku,
+    ProductName,
+    Quantity,
+    UnitPrice,
+}
@@ -0,0 +1,91 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(RefreshTokens::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(RefreshTokens::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(RefreshTokens::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(RefreshTokens::Cid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(RefreshTokens::TokenHash)
+                            .string_len(64)
+                            .not_null()
+                            .unique_key()
+                    )
+                    .col(
+                        ColumnDef::new(RefreshTokens::CreatedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(RefreshTokens::ExpiresGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(RefreshTokens::RevokedGmt)
+                            .integer()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(RefreshTokens::ReplacedBy)
+                            .integer()
+                            .null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_refresh_tokens_customer")
+                    .table(RefreshTokens::Table)
+                    .col(RefreshTokens::Mid)
+                    .col(RefreshTokens::Cid)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(RefreshTokens::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum RefreshTokens {
+    Table,
+    Id,
+    Mid,
+    Cid,
+    TokenHash,
+    CreatedGmt,
+    ExpiresGmt,
+    RevokedGmt,
+    ReplacedBy,
+}
@@ -0,0 +1,187 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(InventoryReservations::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(InventoryReservations::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(InventoryReservations::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(InventoryReservations::Sku)
+                            .string_len(45)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(InventoryReservations::CartId)
+                            .string_len(36)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(InventoryReservations::Quantity)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(InventoryReservations::CreatedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(InventoryReservations::ExpiresGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(InventoryReservations::ReleasedGmt)
+                            .integer()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(InventoryReservations::OrderId)
+                            .integer()
+                            .null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_inventory_reservations_sku")
+                    .table(InventoryReservations::Table)
+                    .col(InventoryReservations::Mid)
+                    .col(InventoryReservations::Sku)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(InventoryAdjustments::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(InventoryAdjustments::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(InventoryAdjustments::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(InventoryAdjustments::Sku)
+                            .string_len(45)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(InventoryAdjustments::Delta)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(InventoryAdjustments::InvAvailableAfter)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(InventoryAdjustments::Reason)
+                            .string_len(20)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(InventoryAdjustments::Note)
+                            .text()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(InventoryAdjustments::OrderId)
+                            .integer()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(InventoryAdjustments::Actor)
+                            .integer()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(InventoryAdjustments::CreatedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_inventory_adjustments_sku")
+                    .table(InventoryAdjustments::Table)
+                    .col(InventoryAdjustments::Mid)
+                    .col(InventoryAdjustments::Sku)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(InventoryAdjustments::Table).to_owned())
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(InventoryReservations::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum InventoryReservations {
+    Table,
+    Id,
+    Mid,
+    Sku,
+    CartId,
+    Quantity,
+    CreatedGmt,
+    ExpiresGmt,
+    ReleasedGmt,
+    OrderId,
+}
+
+#[derive(DeriveIden)]
+enum InventoryAdjustments {
+    Table,
+    Id,
+    Mid,
+    Sku,
+    Delta,
+    InvAvailableAfter,
+    Reason,
+    Note,
+    OrderId,
+    Actor,
+    CreatedGmt,
+}
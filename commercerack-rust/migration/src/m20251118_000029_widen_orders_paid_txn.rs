@@ -0,0 +1,44 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Gateway references (e.g. Stripe PaymentIntent IDs) outgrow 20 chars
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Orders::Table)
+                    .modify_column(
+                        ColumnDef::new(Orders::PaidTxn)
+                            .string_len(64)
+                            .null()
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Orders::Table)
+                    .modify_column(
+                        ColumnDef::new(Orders::PaidTxn)
+                            .string_len(20)
+                            .null()
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Orders {
+    Table,
+    PaidTxn,
+}
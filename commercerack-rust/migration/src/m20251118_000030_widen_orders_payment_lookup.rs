@@ -0,0 +1,44 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Holds provider references such as PayPal order IDs, not just card suffixes
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Orders::Table)
+                    .modify_column(
+                        ColumnDef::new(Orders::OrderPaymentLookup)
+                            .string_len(64)
+                            .null()
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Orders::Table)
+                    .modify_column(
+                        ColumnDef::new(Orders::OrderPaymentLookup)
+                            .string_len(4)
+                            .null()
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Orders {
+    Table,
+    OrderPaymentLookup,
+}
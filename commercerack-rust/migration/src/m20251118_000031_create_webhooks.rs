@@ -0,0 +1,201 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(WebhookEndpoints::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(WebhookEndpoints::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(WebhookEndpoints::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(WebhookEndpoints::Url)
+                            .string_len(255)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(WebhookEndpoints::Secret)
+                            .string_len(80)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(WebhookEndpoints::Events)
+                            .text()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(WebhookEndpoints::Active)
+                            .boolean()
+                            .not_null()
+                            .default(true)
+                    )
+                    .col(
+                        ColumnDef::new(WebhookEndpoints::CreatedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(WebhookEndpoints::ModifiedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_webhook_endpoints_mid")
+                    .table(WebhookEndpoints::Table)
+                    .col(WebhookEndpoints::Mid)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(WebhookDeliveries::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(WebhookDeliveries::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(WebhookDeliveries::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(WebhookDeliveries::EndpointId)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(WebhookDeliveries::Event)
+                            .string_len(40)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(WebhookDeliveries::Payload)
+                            .text()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(WebhookDeliveries::Status)
+                            .string_len(12)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(WebhookDeliveries::Attempts)
+                            .integer()
+                            .not_null()
+                            .default(0)
+                    )
+                    .col(
+                        ColumnDef::new(WebhookDeliveries::NextAttemptGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(WebhookDeliveries::LastStatusCode)
+                            .integer()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(WebhookDeliveries::LastError)
+                            .text()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(WebhookDeliveries::CreatedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(WebhookDeliveries::DeliveredGmt)
+                            .integer()
+                            .null()
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_webhook_deliveries_endpoint")
+                            .from(WebhookDeliveries::Table, WebhookDeliveries::EndpointId)
+                            .to(WebhookEndpoints::Table, WebhookEndpoints::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_webhook_deliveries_due")
+                    .table(WebhookDeliveries::Table)
+                    .col(WebhookDeliveries::Status)
+                    .col(WebhookDeliveries::NextAttemptGmt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(WebhookDeliveries::Table).to_owned())
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(WebhookEndpoints::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum WebhookEndpoints {
+    Table,
+    Id,
+    Mid,
+    Url,
+    Secret,
+    Events,
+    Active,
+    CreatedGmt,
+    ModifiedGmt,
+}
+
+#[derive(DeriveIden)]
+enum WebhookDeliveries {
+    Table,
+    Id,
+    Mid,
+    EndpointId,
+    Event,
+    Payload,
+    Status,
+    Attempts,
+    NextAttemptGmt,
+    LastStatusCode,
+    LastError,
+    CreatedGmt,
+    DeliveredGmt,
+}
@@ -0,0 +1,72 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Products::Table)
+                    .add_column(
+                        ColumnDef::new(Products::Description)
+                            .text()
+                            .not_null()
+                            .default("")
+                    )
+                    .add_column(
+                        ColumnDef::new(Products::SearchVector)
+                            .custom(Alias::new("tsvector"))
+                            .null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Backfill existing rows; the product service keeps it current afterwards
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "UPDATE products SET search_vector = \
+                 setweight(to_tsvector('english', coalesce(product_name, '')), 'A') || \
+                 setweight(to_tsvector('english', coalesce(category, '')), 'B') || \
+                 setweight(to_tsvector('english', coalesce(description, '')), 'C')",
+            )
+            .await?;
+
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "CREATE INDEX IF NOT EXISTS idx_products_search_vector ON products USING GIN (search_vector)",
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .get_connection()
+            .execute_unprepared("DROP INDEX IF EXISTS idx_products_search_vector")
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Products::Table)
+                    .drop_column(Products::SearchVector)
+                    .drop_column(Products::Description)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Products {
+    Table,
+    Description,
+    SearchVector,
+}
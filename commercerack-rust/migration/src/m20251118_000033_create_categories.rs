@@ -0,0 +1,174 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Categories::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Categories::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(Categories::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Categories::ParentId)
+                            .integer()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(Categories::Name)
+                            .string_len(80)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Categories::Slug)
+                            .string_len(100)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Categories::Position)
+                            .integer()
+                            .not_null()
+                            .default(0)
+                    )
+                    .col(
+                        ColumnDef::new(Categories::CreatedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Categories::ModifiedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_categories_parent")
+                            .from(Categories::Table, Categories::ParentId)
+                            .to(Categories::Table, Categories::Id)
+                            .on_delete(ForeignKeyAction::SetNull)
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_categories_mid_slug")
+                    .table(Categories::Table)
+                    .col(Categories::Mid)
+                    .col(Categories::Slug)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_categories_parent")
+                    .table(Categories::Table)
+                    .col(Categories::Mid)
+                    .col(Categories::ParentId)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(ProductCategories::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ProductCategories::ProductId)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ProductCategories::CategoryId)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ProductCategories::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ProductCategories::CreatedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .primary_key(
+                        Index::create()
+                            .col(ProductCategories::ProductId)
+                            .col(ProductCategories::CategoryId)
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_product_categories_category")
+                            .from(ProductCategories::Table, ProductCategories::CategoryId)
+                            .to(Categories::Table, Categories::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_product_categories_category")
+                    .table(ProductCategories::Table)
+                    .col(ProductCategories::CategoryId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ProductCategories::Table).to_owned())
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(Categories::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Categories {
+    Table,
+    Id,
+    Mid,
+    ParentId,
+    Name,
+    Slug,
+    Position,
+    CreatedGmt,
+    ModifiedGmt,
+}
+
+#[derive(DeriveIden)]
+enum ProductCategories {
+    Table,
+    ProductId,
+    CategoryId,
+    Mid,
+    CreatedGmt,
+}
@@ -0,0 +1,105 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ProductMedia::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ProductMedia::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(ProductMedia::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ProductMedia::ProductId)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ProductMedia::SkuId)
+                            .integer()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(ProductMedia::Kind)
+                            .string_len(10)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ProductMedia::Url)
+                            .string_len(500)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ProductMedia::AltText)
+                            .string_len(255)
+                            .not_null()
+                            .default("")
+                    )
+                    .col(
+                        ColumnDef::new(ProductMedia::Position)
+                            .integer()
+                            .not_null()
+                            .default(0)
+                    )
+                    .col(
+                        ColumnDef::new(ProductMedia::IsPrimary)
+                            .boolean()
+                            .not_null()
+                            .default(false)
+                    )
+                    .col(
+                        ColumnDef::new(ProductMedia::CreatedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_product_media_product")
+                    .table(ProductMedia::Table)
+                    .col(ProductMedia::Mid)
+                    .col(ProductMedia::ProductId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ProductMedia::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum ProductMedia {
+    Table,
+    Id,
+    Mid,
+    ProductId,
+    SkuId,
+    Kind,
+    Url,
+    AltText,
+    Position,
+    IsPrimary,
+    CreatedGmt,
+}
@@ -0,0 +1,260 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Coupons::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Coupons::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(Coupons::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Coupons::Code)
+                            .string_len(30)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Coupons::Description)
+                            .string_len(255)
+                            .not_null()
+                            .default("")
+                    )
+                    .col(
+                        ColumnDef::new(Coupons::Kind)
+                            .string_len(16)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Coupons::Value)
+                            .decimal_len(10, 2)
+                            .not_null()
+                            .default(0)
+                    )
+                    .col(
+                        ColumnDef::new(Coupons::MinSubtotal)
+                            .decimal_len(10, 2)
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(Coupons::StartsGmt)
+                            .integer()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(Coupons::EndsGmt)
+                            .integer()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(Coupons::MaxUses)
+                            .integer()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(Coupons::MaxUsesPerCustomer)
+                            .integer()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(Coupons::TimesUsed)
+                            .integer()
+                            .not_null()
+                            .default(0)
+                    )
+                    .col(
+                        ColumnDef::new(Coupons::Skus)
+                            .text()
+                            .not_null()
+                            .default("")
+                    )
+                    .col(
+                        ColumnDef::new(Coupons::CategoryIds)
+                            .text()
+                            .not_null()
+                            .default("")
+                    )
+                    .col(
+                        ColumnDef::new(Coupons::Active)
+                            .boolean()
+                            .not_null()
+                            .default(true)
+                    )
+                    .col(
+                        ColumnDef::new(Coupons::CreatedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Coupons::ModifiedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_coupons_mid_code")
+                    .table(Coupons::Table)
+                    .col(Coupons::Mid)
+                    .col(Coupons::Code)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(CouponRedemptions::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(CouponRedemptions::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(CouponRedemptions::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(CouponRedemptions::CouponId)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(CouponRedemptions::OrderId)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(CouponRedemptions::Customer)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(CouponRedemptions::Discount)
+                            .decimal_len(10, 2)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(CouponRedemptions::CreatedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_coupon_redemptions_coupon")
+                            .from(CouponRedemptions::Table, CouponRedemptions::CouponId)
+                            .to(Coupons::Table, Coupons::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_coupon_redemptions_customer")
+                    .table(CouponRedemptions::Table)
+                    .col(CouponRedemptions::CouponId)
+                    .col(CouponRedemptions::Customer)
+                    .to_owned(),
+            )
+            .await?;
+
+        // Coupon applied to a stored cart, as JSON
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Carts::Table)
+                    .add_column(
+                        ColumnDef::new(Carts::Coupon)
+                            .text()
+                            .null()
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Carts::Table)
+                    .drop_column(Carts::Coupon)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(CouponRedemptions::Table).to_owned())
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(Coupons::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Coupons {
+    Table,
+    Id,
+    Mid,
+    Code,
+    Description,
+    Kind,
+    Value,
+    MinSubtotal,
+    StartsGmt,
+    EndsGmt,
+    MaxUses,
+    MaxUsesPerCustomer,
+    TimesUsed,
+    Skus,
+    CategoryIds,
+    Active,
+    CreatedGmt,
+    ModifiedGmt,
+}
+
+#[derive(DeriveIden)]
+enum CouponRedemptions {
+    Table,
+    Id,
+    Mid,
+    CouponId,
+    OrderId,
+    Customer,
+    Discount,
+    CreatedGmt,
+}
+
+#[derive(DeriveIden)]
+enum Carts {
+    Table,
+    Coupon,
+}
@@ -0,0 +1,98 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(TaxRates::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(TaxRates::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(TaxRates::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(TaxRates::Name)
+                            .string_len(60)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(TaxRates::Country)
+                            .string_len(2)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(TaxRates::State)
+                            .string_len(20)
+                            .not_null()
+                            .default("")
+                    )
+                    .col(
+                        ColumnDef::new(TaxRates::Zip)
+                            .string_len(20)
+                            .not_null()
+                            .default("")
+                    )
+                    .col(
+                        ColumnDef::new(TaxRates::Rate)
+                            .decimal_len(7, 4)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(TaxRates::CreatedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(TaxRates::ModifiedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_tax_rates_mid_country")
+                    .table(TaxRates::Table)
+                    .col(TaxRates::Mid)
+                    .col(TaxRates::Country)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(TaxRates::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum TaxRates {
+    Table,
+    Id,
+    Mid,
+    Name,
+    Country,
+    State,
+    Zip,
+    Rate,
+    CreatedGmt,
+    ModifiedGmt,
+}
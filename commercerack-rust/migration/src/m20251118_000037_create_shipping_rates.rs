@@ -0,0 +1,244 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ShippingZones::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ShippingZones::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(ShippingZones::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ShippingZones::Name)
+                            .string_len(60)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ShippingZones::Countries)
+                            .text()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ShippingZones::States)
+                            .text()
+                            .not_null()
+                            .default("")
+                    )
+                    .col(
+                        ColumnDef::new(ShippingZones::Zips)
+                            .text()
+                            .not_null()
+                            .default("")
+                    )
+                    .col(
+                        ColumnDef::new(ShippingZones::Position)
+                            .integer()
+                            .not_null()
+                            .default(0)
+                    )
+                    .col(
+                        ColumnDef::new(ShippingZones::CreatedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_shipping_zones_mid")
+                    .table(ShippingZones::Table)
+                    .col(ShippingZones::Mid)
+                    .col(ShippingZones::Position)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(ShippingRates::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ShippingRates::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(ShippingRates::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ShippingRates::ZoneId)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ShippingRates::Method)
+                            .string_len(40)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ShippingRates::Name)
+                            .string_len(80)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ShippingRates::Basis)
+                            .string_len(10)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ShippingRates::MinValue)
+                            .decimal_len(12, 3)
+                            .not_null()
+                            .default(0)
+                    )
+                    .col(
+                        ColumnDef::new(ShippingRates::MaxValue)
+                            .decimal_len(12, 3)
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(ShippingRates::Amount)
+                            .decimal_len(10, 2)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ShippingRates::CreatedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_shipping_rates_zone")
+                            .from(ShippingRates::Table, ShippingRates::ZoneId)
+                            .to(ShippingZones::Table, ShippingZones::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Per-unit weight, used by weight-based shipping rates
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(SkuLookup::Table)
+                    .add_column(
+                        ColumnDef::new(SkuLookup::Weight)
+                            .decimal_len(10, 3)
+                            .not_null()
+                            .default(0)
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Room for carrier method codes such as `ups_ground_saver`
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Orders::Table)
+                    .modify_column(
+                        ColumnDef::new(Orders::ShipMethod)
+                            .string_len(40)
+                            .null()
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Orders::Table)
+                    .modify_column(
+                        ColumnDef::new(Orders::ShipMethod)
+                            .string_len(10)
+                            .null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(SkuLookup::Table)
+                    .drop_column(SkuLookup::Weight)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(ShippingRates::Table).to_owned())
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(ShippingZones::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum ShippingZones {
+    Table,
+    Id,
+    Mid,
+    Name,
+    Countries,
+    States,
+    Zips,
+    Position,
+    CreatedGmt,
+}
+
+#[derive(DeriveIden)]
+enum ShippingRates {
+    Table,
+    Id,
+    Mid,
+    ZoneId,
+    Method,
+    Name,
+    Basis,
+    MinValue,
+    MaxValue,
+    Amount,
+    CreatedGmt,
+}
+
+#[derive(DeriveIden)]
+enum SkuLookup {
+    Table,
+    Weight,
+}
+
+#[derive(DeriveIden)]
+enum Orders {
+    Table,
+    ShipMethod,
+}
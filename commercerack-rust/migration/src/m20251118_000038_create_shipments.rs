@@ -0,0 +1,172 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Shipments::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Shipments::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(Shipments::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Shipments::OrderId)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Shipments::Carrier)
+                            .string_len(40)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Shipments::TrackingNumber)
+                            .string_len(80)
+                            .not_null()
+                            .default("")
+                    )
+                    .col(
+                        ColumnDef::new(Shipments::ShippedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_shipments_order")
+                            .from(Shipments::Table, Shipments::OrderId)
+                            .to(Orders::Table, Orders::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_shipments_order_id")
+                    .table(Shipments::Table)
+                    .col(Shipments::Mid)
+                    .col(Shipments::OrderId)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(ShipmentItems::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ShipmentItems::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(ShipmentItems::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ShipmentItems::ShipmentId)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ShipmentItems::OrderItemId)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ShipmentItems::Quantity)
+                            .integer()
+                            .not_null()
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_shipment_items_shipment")
+                            .from(ShipmentItems::Table, ShipmentItems::ShipmentId)
+                            .to(Shipments::Table, Shipments::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_shipment_items_order_item")
+                            .from(ShipmentItems::Table, ShipmentItems::OrderItemId)
+                            .to(OrderItems::Table, OrderItems::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_shipment_items_shipment_id")
+                    .table(ShipmentItems::Table)
+                    .col(ShipmentItems::ShipmentId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ShipmentItems::Table).to_owned())
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(Shipments::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Shipments {
+    Table,
+    Id,
+    Mid,
+    OrderId,
+    Carrier,
+    TrackingNumber,
+    ShippedGmt,
+}
+
+#[derive(DeriveIden)]
+enum ShipmentItems {
+    Table,
+    Id,
+    Mid,
+    ShipmentId,
+    OrderItemId,
+    Quantity,
+}
+
+#[derive(DeriveIden)]
+enum Orders {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum OrderItems {
+    Table,
+    Id,
+}
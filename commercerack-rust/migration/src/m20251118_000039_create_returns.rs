@@ -0,0 +1,191 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Returns::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Returns::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(Returns::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Returns::OrderId)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Returns::Status)
+                            .string_len(12)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Returns::Reason)
+                            .string_len(255)
+                            .not_null()
+                            .default("")
+                    )
+                    .col(
+                        ColumnDef::new(Returns::Restocked)
+                            .boolean()
+                            .not_null()
+                            .default(false)
+                    )
+                    .col(
+                        ColumnDef::new(Returns::RefundAmount)
+                            .decimal_len(10, 2)
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(Returns::CreatedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Returns::ModifiedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_returns_order")
+                            .from(Returns::Table, Returns::OrderId)
+                            .to(Orders::Table, Orders::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_returns_order_id")
+                    .table(Returns::Table)
+                    .col(Returns::Mid)
+                    .col(Returns::OrderId)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(ReturnItems::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ReturnItems::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(ReturnItems::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ReturnItems::ReturnId)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ReturnItems::OrderItemId)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ReturnItems::Quantity)
+                            .integer()
+                            .not_null()
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_return_items_return")
+                            .from(ReturnItems::Table, ReturnItems::ReturnId)
+                            .to(Returns::Table, Returns::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_return_items_order_item")
+                            .from(ReturnItems::Table, ReturnItems::OrderItemId)
+                            .to(OrderItems::Table, OrderItems::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_return_items_return_id")
+                    .table(ReturnItems::Table)
+                    .col(ReturnItems::ReturnId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ReturnItems::Table).to_owned())
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(Returns::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Returns {
+    Table,
+    Id,
+    Mid,
+    OrderId,
+    Status,
+    Reason,
+    Restocked,
+    RefundAmount,
+    CreatedGmt,
+    ModifiedGmt,
+}
+
+#[derive(DeriveIden)]
+enum ReturnItems {
+    Table,
+    Id,
+    Mid,
+    ReturnId,
+    OrderItemId,
+    Quantity,
+}
+
+#[derive(DeriveIden)]
+enum Orders {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum OrderItems {
+    Table,
+    Id,
+}
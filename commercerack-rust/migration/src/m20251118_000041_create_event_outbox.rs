@@ -0,0 +1,77 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(EventOutbox::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(EventOutbox::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(EventOutbox::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(EventOutbox::Event)
+                            .string_len(40)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(EventOutbox::Payload)
+                            .text()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(EventOutbox::CreatedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(EventOutbox::DeliveredGmt)
+                            .integer()
+                            .null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // The relay only ever scans undelivered rows, oldest first
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "CREATE INDEX IF NOT EXISTS idx_event_outbox_pending ON event_outbox (id) WHERE delivered_gmt IS NULL",
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(EventOutbox::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum EventOutbox {
+    Table,
+    Id,
+    Mid,
+    Event,
+    Payload,
+    CreatedGmt,
+    DeliveredGmt,
+}
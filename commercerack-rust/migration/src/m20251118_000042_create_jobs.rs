@@ -0,0 +1,109 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Jobs::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Jobs::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(Jobs::Kind)
+                            .string_len(60)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Jobs::Payload)
+                            .text()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Jobs::Status)
+                            .string_len(12)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Jobs::Attempts)
+                            .integer()
+                            .not_null()
+                            .default(0)
+                    )
+                    .col(
+                        ColumnDef::new(Jobs::MaxAttempts)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Jobs::RunAtGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Jobs::LockedGmt)
+                            .integer()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(Jobs::LastError)
+                            .text()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(Jobs::CreatedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Jobs::FinishedGmt)
+                            .integer()
+                            .null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_jobs_due")
+                    .table(Jobs::Table)
+                    .col(Jobs::Status)
+                    .col(Jobs::RunAtGmt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Jobs::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Jobs {
+    Table,
+    Id,
+    Kind,
+    Payload,
+    Status,
+    Attempts,
+    MaxAttempts,
+    RunAtGmt,
+    LockedGmt,
+    LastError,
+    CreatedGmt,
+    FinishedGmt,
+}
@@ -0,0 +1,50 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // `v` becomes the optimistic locking version of the order
+        manager
+            .get_connection()
+            .execute_unprepared("UPDATE orders SET v = 0 WHERE v IS NULL")
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Orders::Table)
+                    .modify_column(
+                        ColumnDef::new(Orders::V)
+                            .integer()
+                            .not_null()
+                            .default(0)
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Orders::Table)
+                    .modify_column(
+                        ColumnDef::new(Orders::V)
+                            .small_integer()
+                            .null()
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Orders {
+    Table,
+    V,
+}
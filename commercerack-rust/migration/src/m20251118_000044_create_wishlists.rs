@@ -0,0 +1,167 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Wishlists::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Wishlists::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(Wishlists::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Wishlists::Cid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Wishlists::ShareToken)
+                            .string_len(32)
+                            .null()
+                            .unique_key()
+                    )
+                    .col(
+                        ColumnDef::new(Wishlists::CreatedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Wishlists::ModifiedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // One wishlist per customer
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_wishlists_customer")
+                    .table(Wishlists::Table)
+                    .col(Wishlists::Mid)
+                    .col(Wishlists::Cid)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(WishlistItems::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(WishlistItems::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(WishlistItems::WishlistId)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(WishlistItems::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(WishlistItems::Pid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(WishlistItems::Sku)
+                            .string_len(45)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(WishlistItems::AddedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_wishlist_items_wishlist")
+                            .from(WishlistItems::Table, WishlistItems::WishlistId)
+                            .to(Wishlists::Table, Wishlists::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_wishlist_items_sku")
+                    .table(WishlistItems::Table)
+                    .col(WishlistItems::WishlistId)
+                    .col(WishlistItems::Sku)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        // Per-product counts for merchants
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_wishlist_items_product")
+                    .table(WishlistItems::Table)
+                    .col(WishlistItems::Mid)
+                    .col(WishlistItems::Pid)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(WishlistItems::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Wishlists::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Wishlists {
+    Table,
+    Id,
+    Mid,
+    Cid,
+    ShareToken,
+    CreatedGmt,
+    ModifiedGmt,
+}
+
+#[derive(DeriveIden)]
+enum WishlistItems {
+    Table,
+    Id,
+    WishlistId,
+    Mid,
+    Pid,
+    Sku,
+    AddedGmt,
+}
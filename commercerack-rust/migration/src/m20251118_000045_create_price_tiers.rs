@@ -0,0 +1,125 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(PriceTiers::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(PriceTiers::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(PriceTiers::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(PriceTiers::Sku)
+                            .string_len(45)
+                            .not_null()
+                    )
+                    .col(
+                        // Empty for tiers every shopper gets
+                        ColumnDef::new(PriceTiers::PriceGroup)
+                            .string_len(20)
+                            .not_null()
+                            .default("")
+                    )
+                    .col(
+                        ColumnDef::new(PriceTiers::MinQty)
+                            .integer()
+                            .not_null()
+                            .default(1)
+                    )
+                    .col(
+                        ColumnDef::new(PriceTiers::Price)
+                            .decimal_len(10, 2)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(PriceTiers::CreatedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(PriceTiers::ModifiedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_price_tiers_sku_group_qty")
+                    .table(PriceTiers::Table)
+                    .col(PriceTiers::Mid)
+                    .col(PriceTiers::Sku)
+                    .col(PriceTiers::PriceGroup)
+                    .col(PriceTiers::MinQty)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Customers::Table)
+                    .add_column(
+                        ColumnDef::new(Customers::PriceGroup)
+                            .string_len(20)
+                            .not_null()
+                            .default("")
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Customers::Table)
+                    .drop_column(Customers::PriceGroup)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(PriceTiers::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum PriceTiers {
+    Table,
+    Id,
+    Mid,
+    Sku,
+    PriceGroup,
+    MinQty,
+    Price,
+    CreatedGmt,
+    ModifiedGmt,
+}
+
+#[derive(DeriveIden)]
+enum Customers {
+    Table,
+    PriceGroup,
+}
@@ -0,0 +1,225 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(GiftCards::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(GiftCards::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(GiftCards::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(GiftCards::Code)
+                            .string_len(32)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(GiftCards::InitialBalance)
+                            .decimal_len(10, 2)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(GiftCards::Balance)
+                            .decimal_len(10, 2)
+                            .not_null()
+                    )
+                    .col(
+                        // Customer the card was issued to, 0 if none
+                        ColumnDef::new(GiftCards::Cid)
+                            .integer()
+                            .not_null()
+                            .default(0)
+                    )
+                    .col(
+                        ColumnDef::new(GiftCards::Voided)
+                            .boolean()
+                            .not_null()
+                            .default(false)
+                    )
+                    .col(
+                        ColumnDef::new(GiftCards::ExpiresGmt)
+                            .integer()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(GiftCards::CreatedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(GiftCards::ModifiedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_gift_cards_mid_code")
+                    .table(GiftCards::Table)
+                    .col(GiftCards::Mid)
+                    .col(GiftCards::Code)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(GiftCardTransactions::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(GiftCardTransactions::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(GiftCardTransactions::GiftCardId)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(GiftCardTransactions::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        // issue, redeem, adjust or void
+                        ColumnDef::new(GiftCardTransactions::Kind)
+                            .string_len(10)
+                            .not_null()
+                    )
+                    .col(
+                        // Change to the balance; redemptions are negative
+                        ColumnDef::new(GiftCardTransactions::Amount)
+                            .decimal_len(10, 2)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(GiftCardTransactions::BalanceAfter)
+                            .decimal_len(10, 2)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(GiftCardTransactions::OrderId)
+                            .integer()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(GiftCardTransactions::Note)
+                            .string_len(255)
+                            .not_null()
+                            .default("")
+                    )
+                    .col(
+                        ColumnDef::new(GiftCardTransactions::CreatedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_gift_card_transactions_card")
+                            .from(GiftCardTransactions::Table, GiftCardTransactions::GiftCardId)
+                            .to(GiftCards::Table, GiftCards::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_gift_card_transactions_card")
+                    .table(GiftCardTransactions::Table)
+                    .col(GiftCardTransactions::GiftCardId)
+                    .to_owned(),
+            )
+            .await?;
+
+        // Gift cards applied to a cart, as JSON
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Carts::Table)
+                    .add_column(ColumnDef::new(Carts::GiftCards).text().null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Carts::Table)
+                    .drop_column(Carts::GiftCards)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(GiftCardTransactions::Table).to_owned())
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(GiftCards::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum GiftCards {
+    Table,
+    Id,
+    Mid,
+    Code,
+    InitialBalance,
+    Balance,
+    Cid,
+    Voided,
+    ExpiresGmt,
+    CreatedGmt,
+    ModifiedGmt,
+}
+
+#[derive(DeriveIden)]
+enum GiftCardTransactions {
+    Table,
+    Id,
+    GiftCardId,
+    Mid,
+    Kind,
+    Amount,
+    BalanceAfter,
+    OrderId,
+    Note,
+    CreatedGmt,
+}
+
+#[derive(DeriveIden)]
+enum Carts {
+    Table,
+    GiftCards,
+}
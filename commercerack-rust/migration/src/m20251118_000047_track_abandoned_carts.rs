@@ -0,0 +1,142 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Who to contact about a cart; mid 0 means nobody has been
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Carts::Table)
+                    .add_column(ColumnDef::new(Carts::Mid).integer().not_null().default(0))
+                    .add_column(ColumnDef::new(Carts::Cid).integer().not_null().default(0))
+                    .add_column(ColumnDef::new(Carts::Email).string_len(65).not_null().default(""))
+                    // Set once the cart has been archived as abandoned; cleared when it changes
+                    .add_column(ColumnDef::new(Carts::AbandonedGmt).integer().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_carts_modified_gmt")
+                    .table(Carts::Table)
+                    .col(Carts::ModifiedGmt)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(AbandonedCarts::Table)
+                    .add_column(ColumnDef::new(AbandonedCarts::Mid).integer().not_null().default(0))
+                    .add_column(ColumnDef::new(AbandonedCarts::Cid).integer().not_null().default(0))
+                    .add_column(ColumnDef::new(AbandonedCarts::Email).string_len(65).not_null().default(""))
+                    .add_column(ColumnDef::new(AbandonedCarts::RecoveryToken).string_len(32).null())
+                    // When the recovery link was first opened
+                    .add_column(ColumnDef::new(AbandonedCarts::RecoveredGmt).integer().null())
+                    // The order the cart was eventually checked out as
+                    .add_column(ColumnDef::new(AbandonedCarts::OrderId).integer().null())
+                    .add_column(ColumnDef::new(AbandonedCarts::OrderTotal).decimal_len(10, 2).null())
+                    .add_column(ColumnDef::new(AbandonedCarts::OrderedGmt).integer().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_abandoned_carts_recovery_token")
+                    .table(AbandonedCarts::Table)
+                    .col(AbandonedCarts::RecoveryToken)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_abandoned_carts_mid_abandoned")
+                    .table(AbandonedCarts::Table)
+                    .col(AbandonedCarts::Mid)
+                    .col(AbandonedCarts::AbandonedGmt)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_abandoned_carts_cart_id")
+                    .table(AbandonedCarts::Table)
+                    .col(AbandonedCarts::CartId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(AbandonedCarts::Table)
+                    .drop_column(AbandonedCarts::Mid)
+                    .drop_column(AbandonedCarts::Cid)
+                    .drop_column(AbandonedCarts::Email)
+                    .drop_column(AbandonedCarts::RecoveryToken)
+                    .drop_column(AbandonedCarts::RecoveredGmt)
+                    .drop_column(AbandonedCarts::OrderId)
+                    .drop_column(AbandonedCarts::OrderTotal)
+                    .drop_column(AbandonedCarts::OrderedGmt)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_index(Index::drop().name("idx_carts_modified_gmt").table(Carts::Table).to_owned())
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Carts::Table)
+                    .drop_column(Carts::Mid)
+                    .drop_column(Carts::Cid)
+                    .drop_column(Carts::Email)
+                    .drop_column(Carts::AbandonedGmt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Carts {
+    Table,
+    Mid,
+    Cid,
+    Email,
+    ModifiedGmt,
+    AbandonedGmt,
+}
+
+#[derive(DeriveIden)]
+enum AbandonedCarts {
+    Table,
+    CartId,
+    Mid,
+    Cid,
+    Email,
+    AbandonedGmt,
+    RecoveryToken,
+    RecoveredGmt,
+    OrderId,
+    OrderTotal,
+    OrderedGmt,
+}
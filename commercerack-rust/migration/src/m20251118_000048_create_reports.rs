@@ -0,0 +1,130 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Reports::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Reports::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(Reports::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        // daily, weekly or monthly
+                        ColumnDef::new(Reports::Period)
+                            .string_len(10)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Reports::FromGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        // Exclusive
+                        ColumnDef::new(Reports::ToGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Reports::Orders)
+                            .integer()
+                            .not_null()
+                            .default(0)
+                    )
+                    .col(
+                        ColumnDef::new(Reports::Revenue)
+                            .decimal_len(12, 2)
+                            .not_null()
+                            .default(0)
+                    )
+                    .col(
+                        ColumnDef::new(Reports::Tax)
+                            .decimal_len(12, 2)
+                            .not_null()
+                            .default(0)
+                    )
+                    .col(
+                        ColumnDef::new(Reports::Shipping)
+                            .decimal_len(12, 2)
+                            .not_null()
+                            .default(0)
+                    )
+                    .col(
+                        ColumnDef::new(Reports::Refunds)
+                            .decimal_len(12, 2)
+                            .not_null()
+                            .default(0)
+                    )
+                    .col(
+                        ColumnDef::new(Reports::Csv)
+                            .text()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(Reports::CreatedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        // Unset until the report job has run
+                        ColumnDef::new(Reports::GeneratedGmt)
+                            .integer()
+                            .null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_reports_mid_period_from")
+                    .table(Reports::Table)
+                    .col(Reports::Mid)
+                    .col(Reports::Period)
+                    .col(Reports::FromGmt)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Reports::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Reports {
+    Table,
+    Id,
+    Mid,
+    Period,
+    FromGmt,
+    ToGmt,
+    Orders,
+    Revenue,
+    Tax,
+    Shipping,
+    Refunds,
+    Csv,
+    CreatedGmt,
+    GeneratedGmt,
+}
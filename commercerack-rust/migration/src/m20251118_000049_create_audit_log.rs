@@ -0,0 +1,119 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(AuditLog::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(AuditLog::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(AuditLog::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        // JWT subject of whoever made the change
+                        ColumnDef::new(AuditLog::Actor)
+                            .string_len(64)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(AuditLog::ActorRole)
+                            .string_len(20)
+                            .not_null()
+                    )
+                    .col(
+                        // order, order_item, sku, price_tier, customer, ...
+                        ColumnDef::new(AuditLog::EntityType)
+                            .string_len(32)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(AuditLog::EntityId)
+                            .string_len(64)
+                            .not_null()
+                    )
+                    .col(
+                        // update, delete, refund, ...
+                        ColumnDef::new(AuditLog::Action)
+                            .string_len(32)
+                            .not_null()
+                    )
+                    .col(
+                        // JSON object of the changed fields' old values
+                        ColumnDef::new(AuditLog::Before)
+                            .text()
+                            .null()
+                    )
+                    .col(
+                        // JSON object of the changed fields' new values
+                        ColumnDef::new(AuditLog::After)
+                            .text()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(AuditLog::CreatedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_audit_log_mid_entity")
+                    .table(AuditLog::Table)
+                    .col(AuditLog::Mid)
+                    .col(AuditLog::EntityType)
+                    .col(AuditLog::EntityId)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_audit_log_mid_created")
+                    .table(AuditLog::Table)
+                    .col(AuditLog::Mid)
+                    .col(AuditLog::CreatedGmt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(AuditLog::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum AuditLog {
+    Table,
+    Id,
+    Mid,
+    Actor,
+    ActorRole,
+    EntityType,
+    EntityId,
+    Action,
+    Before,
+    After,
+    CreatedGmt,
+}
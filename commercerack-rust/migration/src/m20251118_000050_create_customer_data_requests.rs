@@ -0,0 +1,93 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(CustomerDataRequests::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(CustomerDataRequests::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(CustomerDataRequests::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(CustomerDataRequests::Cid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        // export or erasure
+                        ColumnDef::new(CustomerDataRequests::Kind)
+                            .string_len(10)
+                            .not_null()
+                    )
+                    .col(
+                        // pending or done
+                        ColumnDef::new(CustomerDataRequests::Status)
+                            .string_len(10)
+                            .not_null()
+                    )
+                    .col(
+                        // JSON archive of an export; cleared by an erasure
+                        ColumnDef::new(CustomerDataRequests::Archive)
+                            .text()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(CustomerDataRequests::CreatedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(CustomerDataRequests::CompletedGmt)
+                            .integer()
+                            .null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_customer_data_requests_mid_cid")
+                    .table(CustomerDataRequests::Table)
+                    .col(CustomerDataRequests::Mid)
+                    .col(CustomerDataRequests::Cid)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(CustomerDataRequests::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum CustomerDataRequests {
+    Table,
+    Id,
+    Mid,
+    Cid,
+    Kind,
+    Status,
+    Archive,
+    CreatedGmt,
+    CompletedGmt,
+}
@@ -0,0 +1,68 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Encrypted values (key id, nonce, tag, base64) outgrow the legacy
+        // widths; see commercerack_customer::pii
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CustomerAddrs::Table)
+                    .modify_column(ColumnDef::new(CustomerAddrs::Address1).text().not_null().default(""))
+                    .modify_column(ColumnDef::new(CustomerAddrs::Address2).text().not_null().default(""))
+                    .modify_column(ColumnDef::new(CustomerAddrs::Phone).text().not_null().default(""))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Customers::Table)
+                    .modify_column(ColumnDef::new(Customers::Email).text().not_null().default(""))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Fails while encrypted values are stored
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Customers::Table)
+                    .modify_column(ColumnDef::new(Customers::Email).string_len(65).not_null().default(""))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CustomerAddrs::Table)
+                    .modify_column(ColumnDef::new(CustomerAddrs::Phone).string_len(12).not_null().default(""))
+                    .modify_column(ColumnDef::new(CustomerAddrs::Address2).string_len(60).not_null().default(""))
+                    .modify_column(ColumnDef::new(CustomerAddrs::Address1).string_len(60).not_null().default(""))
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum CustomerAddrs {
+    Table,
+    Address1,
+    Address2,
+    Phone,
+}
+
+#[derive(DeriveIden)]
+enum Customers {
+    Table,
+    Email,
+}
@@ -0,0 +1,104 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(LoginAttempts::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(LoginAttempts::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(LoginAttempts::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        // SHA-256 hex of the lowercased email, known or not
+                        ColumnDef::new(LoginAttempts::EmailHash)
+                            .string_len(64)
+                            .not_null()
+                    )
+                    .col(
+                        // Client IP; empty for the row counting every IP
+                        ColumnDef::new(LoginAttempts::Ip)
+                            .string_len(45)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(LoginAttempts::Failures)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(LoginAttempts::LastFailedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(LoginAttempts::LockedUntilGmt)
+                            .integer()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(LoginAttempts::UnlockTokenHash)
+                            .string_len(64)
+                            .null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_login_attempts_mid_email_ip")
+                    .table(LoginAttempts::Table)
+                    .col(LoginAttempts::Mid)
+                    .col(LoginAttempts::EmailHash)
+                    .col(LoginAttempts::Ip)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_login_attempts_unlock_token_hash")
+                    .table(LoginAttempts::Table)
+                    .col(LoginAttempts::UnlockTokenHash)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(LoginAttempts::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum LoginAttempts {
+    Table,
+    Id,
+    Mid,
+    EmailHash,
+    Ip,
+    Failures,
+    LastFailedGmt,
+    LockedUntilGmt,
+    UnlockTokenHash,
+}
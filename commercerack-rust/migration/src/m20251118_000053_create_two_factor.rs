@@ -0,0 +1,151 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(CustomerTwoFactor::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(CustomerTwoFactor::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(CustomerTwoFactor::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(CustomerTwoFactor::Cid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        // Base32 TOTP secret, encrypted like other PII
+                        ColumnDef::new(CustomerTwoFactor::Secret)
+                            .text()
+                            .not_null()
+                    )
+                    .col(
+                        // Null until the first code confirms enrollment
+                        ColumnDef::new(CustomerTwoFactor::EnabledGmt)
+                            .integer()
+                            .null()
+                    )
+                    .col(
+                        // Time step of the last accepted code, so codes are single use
+                        ColumnDef::new(CustomerTwoFactor::LastStep)
+                            .big_integer()
+                            .not_null()
+                            .default(0)
+                    )
+                    .col(
+                        ColumnDef::new(CustomerTwoFactor::CreatedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_customer_two_factor_mid_cid")
+                    .table(CustomerTwoFactor::Table)
+                    .col(CustomerTwoFactor::Mid)
+                    .col(CustomerTwoFactor::Cid)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(CustomerBackupCodes::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(CustomerBackupCodes::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(CustomerBackupCodes::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(CustomerBackupCodes::Cid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        // SHA-256 hex, the raw code is never stored
+                        ColumnDef::new(CustomerBackupCodes::CodeHash)
+                            .string_len(64)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(CustomerBackupCodes::UsedGmt)
+                            .integer()
+                            .null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_customer_backup_codes_mid_cid")
+                    .table(CustomerBackupCodes::Table)
+                    .col(CustomerBackupCodes::Mid)
+                    .col(CustomerBackupCodes::Cid)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(CustomerBackupCodes::Table).to_owned())
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(CustomerTwoFactor::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum CustomerTwoFactor {
+    Table,
+    Id,
+    Mid,
+    Cid,
+    Secret,
+    EnabledGmt,
+    LastStep,
+    CreatedGmt,
+}
+
+#[derive(DeriveIden)]
+enum CustomerBackupCodes {
+    Table,
+    Id,
+    Mid,
+    Cid,
+    CodeHash,
+    UsedGmt,
+}
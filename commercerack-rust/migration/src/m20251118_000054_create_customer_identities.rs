@@ -0,0 +1,93 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(CustomerIdentities::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(CustomerIdentities::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(CustomerIdentities::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(CustomerIdentities::Cid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        // google, apple
+                        ColumnDef::new(CustomerIdentities::Provider)
+                            .string_len(16)
+                            .not_null()
+                    )
+                    .col(
+                        // The provider's stable user id (`sub` of its ID tokens)
+                        ColumnDef::new(CustomerIdentities::Subject)
+                            .string_len(255)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(CustomerIdentities::CreatedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_customer_identities_mid_provider_subject")
+                    .table(CustomerIdentities::Table)
+                    .col(CustomerIdentities::Mid)
+                    .col(CustomerIdentities::Provider)
+                    .col(CustomerIdentities::Subject)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_customer_identities_mid_cid")
+                    .table(CustomerIdentities::Table)
+                    .col(CustomerIdentities::Mid)
+                    .col(CustomerIdentities::Cid)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(CustomerIdentities::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum CustomerIdentities {
+    Table,
+    Id,
+    Mid,
+    Cid,
+    Provider,
+    Subject,
+    CreatedGmt,
+}
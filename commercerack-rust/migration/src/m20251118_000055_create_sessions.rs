@@ -0,0 +1,133 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Sessions::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Sessions::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(Sessions::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Sessions::Cid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        // User-Agent of the login
+                        ColumnDef::new(Sessions::Device)
+                            .string_len(255)
+                            .not_null()
+                            .default("")
+                    )
+                    .col(
+                        // Client IP at login, then at the latest refresh
+                        ColumnDef::new(Sessions::Ip)
+                            .string_len(45)
+                            .not_null()
+                            .default("")
+                    )
+                    .col(
+                        ColumnDef::new(Sessions::CreatedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Sessions::LastSeenGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Sessions::RevokedGmt)
+                            .integer()
+                            .null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_sessions_mid_cid")
+                    .table(Sessions::Table)
+                    .col(Sessions::Mid)
+                    .col(Sessions::Cid)
+                    .to_owned(),
+            )
+            .await?;
+
+        // Tokens issued before sessions existed have none
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(RefreshTokens::Table)
+                    .add_column(
+                        ColumnDef::new(RefreshTokens::SessionId)
+                            .integer()
+                            .null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_refresh_tokens_session_id")
+                    .table(RefreshTokens::Table)
+                    .col(RefreshTokens::SessionId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(RefreshTokens::Table)
+                    .drop_column(RefreshTokens::SessionId)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(Sessions::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Sessions {
+    Table,
+    Id,
+    Mid,
+    Cid,
+    Device,
+    Ip,
+    CreatedGmt,
+    LastSeenGmt,
+    RevokedGmt,
+}
+
+#[derive(DeriveIden)]
+enum RefreshTokens {
+    Table,
+    SessionId,
+}
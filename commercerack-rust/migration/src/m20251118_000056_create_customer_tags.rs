@@ -0,0 +1,170 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(CustomerTags::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(CustomerTags::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(CustomerTags::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(CustomerTags::Cid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        // Lowercase, e.g. vip, wholesale, fraud-risk
+                        ColumnDef::new(CustomerTags::Tag)
+                            .string_len(32)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(CustomerTags::CreatedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_customer_tags_mid_cid_tag")
+                    .table(CustomerTags::Table)
+                    .col(CustomerTags::Mid)
+                    .col(CustomerTags::Cid)
+                    .col(CustomerTags::Tag)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        // Customer lists filtered by tag
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_customer_tags_mid_tag")
+                    .table(CustomerTags::Table)
+                    .col(CustomerTags::Mid)
+                    .col(CustomerTags::Tag)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Coupons::Table)
+                    .add_column(
+                        // comma-separated; empty lets every customer use the coupon
+                        ColumnDef::new(Coupons::CustomerTags)
+                            .text()
+                            .not_null()
+                            .default("")
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // customer_notes was created without a key or timestamp; bring it in
+        // line with the original schema so notes can be listed and deleted
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CustomerNotes::Table)
+                    .add_column_if_not_exists(
+                        ColumnDef::new(CustomerNotes::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .add_column_if_not_exists(
+                        ColumnDef::new(CustomerNotes::CreatedGmt)
+                            .integer()
+                            .not_null()
+                            .default(0)
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        let db = manager.get_connection();
+        for column in ["mid", "cid"] {
+            db.execute_unprepared(&format!("UPDATE customer_notes SET {0} = 0 WHERE {0} IS NULL", column)).await?;
+            db.execute_unprepared(&format!(
+                "ALTER TABLE customer_notes ALTER COLUMN {0} SET DEFAULT 0, ALTER COLUMN {0} SET NOT NULL",
+                column
+            ))
+            .await?;
+        }
+        for column in ["username", "luser", "note", "type"] {
+            db.execute_unprepared(&format!("UPDATE customer_notes SET {0} = '' WHERE {0} IS NULL", column)).await?;
+            db.execute_unprepared(&format!(
+                "ALTER TABLE customer_notes ALTER COLUMN {0} SET DEFAULT '', ALTER COLUMN {0} SET NOT NULL",
+                column
+            ))
+            .await?;
+        }
+        db.execute_unprepared("CREATE INDEX IF NOT EXISTS idx_customer_notes_mid_cid ON customer_notes (mid, cid)")
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // The notes columns stay; older code ignores them
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Coupons::Table)
+                    .drop_column(Coupons::CustomerTags)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(CustomerTags::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum CustomerTags {
+    Table,
+    Id,
+    Mid,
+    Cid,
+    Tag,
+    CreatedGmt,
+}
+
+#[derive(DeriveIden)]
+enum CustomerNotes {
+    Table,
+    Id,
+    CreatedGmt,
+}
+
+#[derive(DeriveIden)]
+enum Coupons {
+    Table,
+    CustomerTags,
+}
@@ -0,0 +1,418 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Warehouses::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Warehouses::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(Warehouses::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        // Merchant-facing short code, e.g. EAST
+                        ColumnDef::new(Warehouses::Code)
+                            .string_len(16)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Warehouses::Name)
+                            .string_len(80)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Warehouses::Country)
+                            .string_len(2)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Warehouses::State)
+                            .string_len(8)
+                            .not_null()
+                            .default("")
+                    )
+                    .col(
+                        // Lower ships first when warehouses are otherwise equal
+                        ColumnDef::new(Warehouses::Priority)
+                            .integer()
+                            .not_null()
+                            .default(0)
+                    )
+                    .col(
+                        ColumnDef::new(Warehouses::Active)
+                            .boolean()
+                            .not_null()
+                            .default(true)
+                    )
+                    .col(
+                        ColumnDef::new(Warehouses::CreatedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_warehouses_mid_code")
+                    .table(Warehouses::Table)
+                    .col(Warehouses::Mid)
+                    .col(Warehouses::Code)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(WarehouseStock::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(WarehouseStock::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(WarehouseStock::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(WarehouseStock::WarehouseId)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(WarehouseStock::Sku)
+                            .string_len(45)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(WarehouseStock::Quantity)
+                            .integer()
+                            .not_null()
+                            .default(0)
+                    )
+                    .col(
+                        // Units on their way here from another warehouse
+                        ColumnDef::new(WarehouseStock::InTransit)
+                            .integer()
+                            .not_null()
+                            .default(0)
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_warehouse_stock_warehouse_sku")
+                    .table(WarehouseStock::Table)
+                    .col(WarehouseStock::WarehouseId)
+                    .col(WarehouseStock::Sku)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        // Allocation looks up every warehouse holding a SKU
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_warehouse_stock_mid_sku")
+                    .table(WarehouseStock::Table)
+                    .col(WarehouseStock::Mid)
+                    .col(WarehouseStock::Sku)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(StockTransfers::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(StockTransfers::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(StockTransfers::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(StockTransfers::FromWarehouse)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(StockTransfers::ToWarehouse)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        // in_transit, received, cancelled
+                        ColumnDef::new(StockTransfers::Status)
+                            .string_len(16)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(StockTransfers::Note)
+                            .text()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(StockTransfers::Actor)
+                            .integer()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(StockTransfers::CreatedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        // When it was received or cancelled
+                        ColumnDef::new(StockTransfers::ClosedGmt)
+                            .integer()
+                            .null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_stock_transfers_mid_status")
+                    .table(StockTransfers::Table)
+                    .col(StockTransfers::Mid)
+                    .col(StockTransfers::Status)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(StockTransferItems::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(StockTransferItems::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(StockTransferItems::TransferId)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(StockTransferItems::Sku)
+                            .string_len(45)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(StockTransferItems::Quantity)
+                            .integer()
+                            .not_null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_stock_transfer_items_transfer_id")
+                    .table(StockTransferItems::Table)
+                    .col(StockTransferItems::TransferId)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(OrderAllocations::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(OrderAllocations::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(OrderAllocations::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(OrderAllocations::OrderId)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(OrderAllocations::WarehouseId)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(OrderAllocations::Sku)
+                            .string_len(45)
+                            .not_null()
+                    )
+                    .col(
+                        // Drops as cancelled or returned units go back to the warehouse
+                        ColumnDef::new(OrderAllocations::Quantity)
+                            .integer()
+                            .not_null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_order_allocations_mid_order_id")
+                    .table(OrderAllocations::Table)
+                    .col(OrderAllocations::Mid)
+                    .col(OrderAllocations::OrderId)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(InventoryAdjustments::Table)
+                    .add_column(
+                        ColumnDef::new(InventoryAdjustments::WarehouseId)
+                            .integer()
+                            .null()
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(InventoryAdjustments::Table)
+                    .drop_column(InventoryAdjustments::WarehouseId)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(OrderAllocations::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(StockTransferItems::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(StockTransfers::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(WarehouseStock::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Warehouses::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Warehouses {
+    Table,
+    Id,
+    Mid,
+    Code,
+    Name,
+    Country,
+    State,
+    Priority,
+    Active,
+    CreatedGmt,
+}
+
+#[derive(DeriveIden)]
+enum WarehouseStock {
+    Table,
+    Id,
+    Mid,
+    WarehouseId,
+    Sku,
+    Quantity,
+    InTransit,
+}
+
+#[derive(DeriveIden)]
+enum StockTransfers {
+    Table,
+    Id,
+    Mid,
+    FromWarehouse,
+    ToWarehouse,
+    Status,
+    Note,
+    Actor,
+    CreatedGmt,
+    ClosedGmt,
+}
+
+#[derive(DeriveIden)]
+enum StockTransferItems {
+    Table,
+    Id,
+    TransferId,
+    Sku,
+    Quantity,
+}
+
+#[derive(DeriveIden)]
+enum OrderAllocations {
+    Table,
+    Id,
+    Mid,
+    OrderId,
+    WarehouseId,
+    Sku,
+    Quantity,
+}
+
+#[derive(DeriveIden)]
+enum InventoryAdjustments {
+    Table,
+    WarehouseId,
+}
@@ -0,0 +1,73 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(SkuLookup::Table)
+                    .add_column(
+                        // Stock at or below this is low; NULL turns alerts off
+                        ColumnDef::new(SkuLookup::ReorderPoint)
+                            .integer()
+                            .null()
+                    )
+                    .add_column(
+                        // How many units to order when stock runs low
+                        ColumnDef::new(SkuLookup::ReorderQuantity)
+                            .integer()
+                            .not_null()
+                            .default(0)
+                    )
+                    .add_column(
+                        // When the current low stock alert went out
+                        ColumnDef::new(SkuLookup::LowStockGmt)
+                            .integer()
+                            .null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // The low stock scan only looks at SKUs with a reorder point
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "CREATE INDEX IF NOT EXISTS idx_sku_lookup_reorder_point \
+                 ON sku_lookup (mid) WHERE reorder_point IS NOT NULL",
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .get_connection()
+            .execute_unprepared("DROP INDEX IF EXISTS idx_sku_lookup_reorder_point")
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(SkuLookup::Table)
+                    .drop_column(SkuLookup::ReorderPoint)
+                    .drop_column(SkuLookup::ReorderQuantity)
+                    .drop_column(SkuLookup::LowStockGmt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum SkuLookup {
+    Table,
+    ReorderPoint,
+    ReorderQuantity,
+    LowStockGmt,
+}
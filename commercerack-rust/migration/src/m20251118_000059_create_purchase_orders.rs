@@ -0,0 +1,178 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(PurchaseOrders::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(PurchaseOrders::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(PurchaseOrders::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        // Matches products.supplier
+                        ColumnDef::new(PurchaseOrders::Supplier)
+                            .string_len(60)
+                            .not_null()
+                    )
+                    .col(
+                        // draft, sent, partially_received, received
+                        ColumnDef::new(PurchaseOrders::Status)
+                            .string_len(20)
+                            .not_null()
+                    )
+                    .col(
+                        // Where received units are booked; none for merchants without warehouses
+                        ColumnDef::new(PurchaseOrders::WarehouseId)
+                            .integer()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(PurchaseOrders::Note)
+                            .text()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(PurchaseOrders::ExpectedGmt)
+                            .integer()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(PurchaseOrders::Actor)
+                            .integer()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(PurchaseOrders::CreatedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(PurchaseOrders::SentGmt)
+                            .integer()
+                            .null()
+                    )
+                    .col(
+                        // When the last units came in
+                        ColumnDef::new(PurchaseOrders::ReceivedGmt)
+                            .integer()
+                            .null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_purchase_orders_mid_status")
+                    .table(PurchaseOrders::Table)
+                    .col(PurchaseOrders::Mid)
+                    .col(PurchaseOrders::Status)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(PurchaseOrderItems::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(PurchaseOrderItems::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(PurchaseOrderItems::PurchaseOrderId)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(PurchaseOrderItems::Sku)
+                            .string_len(45)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(PurchaseOrderItems::Quantity)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(PurchaseOrderItems::Received)
+                            .integer()
+                            .not_null()
+                            .default(0)
+                    )
+                    .col(
+                        ColumnDef::new(PurchaseOrderItems::UnitCost)
+                            .decimal_len(10, 2)
+                            .not_null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_purchase_order_items_purchase_order_id")
+                    .table(PurchaseOrderItems::Table)
+                    .col(PurchaseOrderItems::PurchaseOrderId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(PurchaseOrderItems::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(PurchaseOrders::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum PurchaseOrders {
+    Table,
+    Id,
+    Mid,
+    Supplier,
+    Status,
+    WarehouseId,
+    Note,
+    ExpectedGmt,
+    Actor,
+    CreatedGmt,
+    SentGmt,
+    ReceivedGmt,
+}
+
+#[derive(DeriveIden)]
+enum PurchaseOrderItems {
+    Table,
+    Id,
+    PurchaseOrderId,
+    Sku,
+    Quantity,
+    Received,
+    UnitCost,
+}
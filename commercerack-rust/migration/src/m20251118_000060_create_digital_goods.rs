@@ -0,0 +1,241 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Products::Table)
+                    .add_column(
+                        // physical or digital
+                        ColumnDef::new(Products::ProductType)
+                            .string_len(16)
+                            .not_null()
+                            .default("physical")
+                    )
+                    .add_column(
+                        // Where the file of a digital product is kept; never shown to buyers
+                        ColumnDef::new(Products::DownloadUrl)
+                            .string_len(1024)
+                            .null()
+                    )
+                    .add_column(
+                        // Downloads allowed per purchase; the configured default when null
+                        ColumnDef::new(Products::DownloadLimit)
+                            .integer()
+                            .null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(LicenseKeys::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(LicenseKeys::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(LicenseKeys::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(LicenseKeys::Sku)
+                            .string_len(45)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(LicenseKeys::LicenseKey)
+                            .string_len(255)
+                            .not_null()
+                    )
+                    .col(
+                        // Null while the key is still in the pool
+                        ColumnDef::new(LicenseKeys::OrderId)
+                            .integer()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(LicenseKeys::OrderItemId)
+                            .integer()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(LicenseKeys::AssignedGmt)
+                            .integer()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(LicenseKeys::CreatedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_license_keys_mid_sku_key")
+                    .table(LicenseKeys::Table)
+                    .col(LicenseKeys::Mid)
+                    .col(LicenseKeys::Sku)
+                    .col(LicenseKeys::LicenseKey)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_license_keys_mid_order_id")
+                    .table(LicenseKeys::Table)
+                    .col(LicenseKeys::Mid)
+                    .col(LicenseKeys::OrderId)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(OrderDownloads::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(OrderDownloads::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(OrderDownloads::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(OrderDownloads::OrderId)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(OrderDownloads::OrderItemId)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(OrderDownloads::Sku)
+                            .string_len(45)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(OrderDownloads::Token)
+                            .string_len(64)
+                            .not_null()
+                            .unique_key()
+                    )
+                    .col(
+                        ColumnDef::new(OrderDownloads::Downloads)
+                            .integer()
+                            .not_null()
+                            .default(0)
+                    )
+                    .col(
+                        ColumnDef::new(OrderDownloads::MaxDownloads)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(OrderDownloads::ExpiresGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(OrderDownloads::CreatedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_order_downloads_mid_order_id")
+                    .table(OrderDownloads::Table)
+                    .col(OrderDownloads::Mid)
+                    .col(OrderDownloads::OrderId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(OrderDownloads::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(LicenseKeys::Table).to_owned())
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Products::Table)
+                    .drop_column(Products::ProductType)
+                    .drop_column(Products::DownloadUrl)
+                    .drop_column(Products::DownloadLimit)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Products {
+    Table,
+    ProductType,
+    DownloadUrl,
+    DownloadLimit,
+}
+
+#[derive(DeriveIden)]
+enum LicenseKeys {
+    Table,
+    Id,
+    Mid,
+    Sku,
+    LicenseKey,
+    OrderId,
+    OrderItemId,
+    AssignedGmt,
+    CreatedGmt,
+}
+
+#[derive(DeriveIden)]
+enum OrderDownloads {
+    Table,
+    Id,
+    Mid,
+    OrderId,
+    OrderItemId,
+    Sku,
+    Token,
+    Downloads,
+    MaxDownloads,
+    ExpiresGmt,
+    CreatedGmt,
+}
@@ -0,0 +1,136 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(OrderPayments::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(OrderPayments::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(OrderPayments::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(OrderPayments::OrderId)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        // Gateway transaction; null when the gateway declined outright
+                        ColumnDef::new(OrderPayments::Reference)
+                            .string_len(255)
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(OrderPayments::Amount)
+                            .decimal_len(10, 2)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(OrderPayments::Refunded)
+                            .decimal_len(10, 2)
+                            .not_null()
+                            .default(0)
+                    )
+                    .col(
+                        // authorized, captured, pending, declined, refunded or voided
+                        ColumnDef::new(OrderPayments::Status)
+                            .string_len(16)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(OrderPayments::CreatedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(OrderPayments::ModifiedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_order_payments_order")
+                    .table(OrderPayments::Table)
+                    .col(OrderPayments::Mid)
+                    .col(OrderPayments::OrderId)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_order_payments_reference")
+                    .table(OrderPayments::Table)
+                    .col(OrderPayments::Reference)
+                    .to_owned(),
+            )
+            .await?;
+
+        // Orders paid so far carry their one payment on the order itself.
+        // How much of a partially refunded order was refunded wasn't kept,
+        // so those start out with nothing refunded. `orders.total` is still
+        // the legacy string here, where blank means 0.
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "INSERT INTO order_payments (mid, order_id, reference, amount, refunded, status, created_gmt, modified_gmt)
+                 SELECT mid, id, paid_txn, COALESCE(NULLIF(total::text, ''), '0')::numeric,
+                        CASE order_payment_status WHEN '300' THEN COALESCE(NULLIF(total::text, ''), '0')::numeric ELSE 0 END,
+                        CASE order_payment_status
+                            WHEN '000' THEN 'captured'
+                            WHEN '100' THEN 'authorized'
+                            WHEN '110' THEN 'pending'
+                            WHEN '300' THEN 'refunded'
+                            WHEN '301' THEN 'captured'
+                            WHEN '600' THEN 'voided'
+                            ELSE 'declined'
+                        END,
+                        COALESCE(paid_gmt, created_gmt), COALESCE(paid_gmt, created_gmt)
+                 FROM orders
+                 WHERE paid_txn IS NOT NULL",
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(OrderPayments::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum OrderPayments {
+    Table,
+    Id,
+    Mid,
+    OrderId,
+    Reference,
+    Amount,
+    Refunded,
+    Status,
+    CreatedGmt,
+    ModifiedGmt,
+}
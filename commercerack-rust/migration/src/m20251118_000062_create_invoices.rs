@@ -0,0 +1,172 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // When the order was invoiced is recorded in the legacy
+        // `orders.inv_gmt`, which the orders table already has
+        manager
+            .create_table(
+                Table::create()
+                    .table(Invoices::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Invoices::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(Invoices::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Invoices::OrderId)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        // invoice or credit_memo
+                        ColumnDef::new(Invoices::Kind)
+                            .string_len(16)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Invoices::Number)
+                            .string_len(64)
+                            .not_null()
+                    )
+                    .col(
+                        // Invoiced total, or the amount credited
+                        ColumnDef::new(Invoices::Amount)
+                            .decimal_len(10, 2)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Invoices::Pdf)
+                            .binary()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Invoices::CreatedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_invoices_number")
+                    .table(Invoices::Table)
+                    .col(Invoices::Mid)
+                    .col(Invoices::Number)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_invoices_order")
+                    .table(Invoices::Table)
+                    .col(Invoices::Mid)
+                    .col(Invoices::OrderId)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(InvoiceBranding::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(InvoiceBranding::Mid)
+                            .integer()
+                            .not_null()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(InvoiceBranding::Name)
+                            .string_len(120)
+                            .not_null()
+                    )
+                    .col(
+                        // One line per address line
+                        ColumnDef::new(InvoiceBranding::Address)
+                            .string_len(500)
+                            .not_null()
+                            .default("")
+                    )
+                    .col(
+                        ColumnDef::new(InvoiceBranding::TaxId)
+                            .string_len(40)
+                            .not_null()
+                            .default("")
+                    )
+                    .col(
+                        ColumnDef::new(InvoiceBranding::Footer)
+                            .string_len(255)
+                            .not_null()
+                            .default("")
+                    )
+                    .col(
+                        // #RRGGBB of the title band
+                        ColumnDef::new(InvoiceBranding::AccentColor)
+                            .string_len(7)
+                            .not_null()
+                            .default("#333333")
+                    )
+                    .col(
+                        ColumnDef::new(InvoiceBranding::ModifiedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(InvoiceBranding::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Invoices::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Invoices {
+    Table,
+    Id,
+    Mid,
+    OrderId,
+    Kind,
+    Number,
+    Amount,
+    Pdf,
+    CreatedGmt,
+}
+
+#[derive(DeriveIden)]
+enum InvoiceBranding {
+    Table,
+    Mid,
+    Name,
+    Address,
+    TaxId,
+    Footer,
+    AccentColor,
+    ModifiedGmt,
+}
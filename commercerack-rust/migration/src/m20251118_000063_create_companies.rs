@@ -0,0 +1,246 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Customers::Table)
+                    .add_column(
+                        // retail, wholesale or employee
+                        ColumnDef::new(Customers::CustomerGroup)
+                            .string_len(16)
+                            .not_null()
+                            .default("retail")
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Orders::Table)
+                    .add_column(
+                        // Company account a buyer placed the order for
+                        ColumnDef::new(Orders::CompanyId)
+                            .integer()
+                            .null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_orders_company")
+                    .table(Orders::Table)
+                    .col(Orders::Mid)
+                    .col(Orders::CompanyId)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(Companies::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Companies::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(Companies::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Companies::Name)
+                            .string_len(120)
+                            .not_null()
+                    )
+                    .col(
+                        // Price tiers the company's buyers get; empty leaves
+                        // each buyer's own
+                        ColumnDef::new(Companies::PriceGroup)
+                            .string_len(20)
+                            .not_null()
+                            .default("")
+                    )
+                    .col(
+                        // Orders above this total wait for an approver
+                        ColumnDef::new(Companies::ApprovalThreshold)
+                            .decimal_len(10, 2)
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(Companies::CreatedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Companies::ModifiedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_companies_mid")
+                    .table(Companies::Table)
+                    .col(Companies::Mid)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(CompanyBuyers::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(CompanyBuyers::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(CompanyBuyers::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(CompanyBuyers::CompanyId)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(CompanyBuyers::Cid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        // buyer or approver
+                        ColumnDef::new(CompanyBuyers::Role)
+                            .string_len(16)
+                            .not_null()
+                    )
+                    .col(
+                        // Largest order the buyer may place; null for no limit
+                        ColumnDef::new(CompanyBuyers::SpendingLimit)
+                            .decimal_len(10, 2)
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(CompanyBuyers::CreatedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // A customer buys for at most one company
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_company_buyers_customer")
+                    .table(CompanyBuyers::Table)
+                    .col(CompanyBuyers::Mid)
+                    .col(CompanyBuyers::Cid)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_company_buyers_company")
+                    .table(CompanyBuyers::Table)
+                    .col(CompanyBuyers::Mid)
+                    .col(CompanyBuyers::CompanyId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(CompanyBuyers::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Companies::Table).to_owned())
+            .await?;
+        manager
+            .drop_index(Index::drop().name("idx_orders_company").table(Orders::Table).to_owned())
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Orders::Table)
+                    .drop_column(Orders::CompanyId)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Customers::Table)
+                    .drop_column(Customers::CustomerGroup)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Customers {
+    Table,
+    CustomerGroup,
+}
+
+#[derive(DeriveIden)]
+enum Orders {
+    Table,
+    Mid,
+    CompanyId,
+}
+
+#[derive(DeriveIden)]
+enum Companies {
+    Table,
+    Id,
+    Mid,
+    Name,
+    PriceGroup,
+    ApprovalThreshold,
+    CreatedGmt,
+    ModifiedGmt,
+}
+
+#[derive(DeriveIden)]
+enum CompanyBuyers {
+    Table,
+    Id,
+    Mid,
+    CompanyId,
+    Cid,
+    Role,
+    SpendingLimit,
+    CreatedGmt,
+}
@@ -0,0 +1,80 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CartItems::Table)
+                    .add_column(
+                        // JSON of the shopper's selections, engraving and
+                        // gift wrap; empty for none
+                        ColumnDef::new(CartItems::Options)
+                            .text()
+                            .not_null()
+                            .default("")
+                    )
+                    .add_column(
+                        // Added to the unit price for the options
+                        ColumnDef::new(CartItems::Surcharge)
+                            .decimal_len(10, 2)
+                            .not_null()
+                            .default(0)
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(OrderItems::Table)
+                    .add_column(
+                        // Options of the cart line the item came from
+                        ColumnDef::new(OrderItems::Options)
+                            .text()
+                            .not_null()
+                            .default("")
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(OrderItems::Table)
+                    .drop_column(OrderItems::Options)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(CartItems::Table)
+                    .drop_column(CartItems::Options)
+                    .drop_column(CartItems::Surcharge)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum CartItems {
+    Table,
+    Options,
+    Surcharge,
+}
+
+#[derive(DeriveIden)]
+enum OrderItems {
+    Table,
+    Options,
+}
@@ -0,0 +1,109 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(CustomerCarts::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(CustomerCarts::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(CustomerCarts::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(CustomerCarts::Cid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        // The cart itself lives in the cart store
+                        ColumnDef::new(CustomerCarts::CartId)
+                            .string_len(36)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(CustomerCarts::Name)
+                            .string_len(60)
+                            .not_null()
+                    )
+                    .col(
+                        // The cart the shopper is filling and checks out;
+                        // the rest are saved for later
+                        ColumnDef::new(CustomerCarts::Active)
+                            .boolean()
+                            .not_null()
+                            .default(false)
+                    )
+                    .col(
+                        ColumnDef::new(CustomerCarts::CreatedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(CustomerCarts::ModifiedGmt)
+                            .integer()
+                            .not_null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // A cart belongs to one customer
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_customer_carts_cart")
+                    .table(CustomerCarts::Table)
+                    .col(CustomerCarts::CartId)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        // Cart names are unique per customer
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_customer_carts_name")
+                    .table(CustomerCarts::Table)
+                    .col(CustomerCarts::Mid)
+                    .col(CustomerCarts::Cid)
+                    .col(CustomerCarts::Name)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(CustomerCarts::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum CustomerCarts {
+    Table,
+    Id,
+    Mid,
+    Cid,
+    CartId,
+    Name,
+    Active,
+    CreatedGmt,
+    ModifiedGmt,
+}
@@ -0,0 +1,107 @@
+//! Widen every unix-seconds column from `integer` to `bigint`
+//!
+//! A signed 32-bit count of seconds runs out in January 2038, and gift
+//! card expiry dates can already reach that far.
+//!
+//! The service reads either width, so deploy it first and run this
+//! afterwards; rows keep working while the tables are rewritten. Going
+//! down fails if any stored value no longer fits in an `integer`.
+
+use sea_orm_migration::prelude::*;
+
+/// Tables and their unix-seconds columns
+const COLUMNS: &[(&str, &[&str])] = &[
+    ("abandoned_carts", &["abandoned_gmt", "recovered_gmt", "ordered_gmt"]),
+    ("amazon_docs", &["retrieved_gmt"]),
+    ("amazon_document_contents", &["ack_gmt"]),
+    ("amazon_order_events", &["lock_gmt", "processed_gmt"]),
+    (
+        "amazon_orders",
+        &[
+            "ack_gmt",
+            "fulfillment_ack_processed_gmt",
+            "fulfillment_ack_requested_gmt",
+            "neworder_ack_processed_gmt",
+            "posted_gmt",
+            "track_gmt",
+        ],
+    ),
+    ("audit_log", &["created_gmt"]),
+    ("campaign_recipients", &["clicked_gmt", "locked_gmt", "opened_gmt", "purchased_gmt", "sent_gmt"]),
+    ("carts", &["abandoned_gmt", "created_gmt", "modified_gmt"]),
+    ("categories", &["created_gmt", "modified_gmt"]),
+    ("checkouts", &["closed_gmt", "handled_gmt"]),
+    ("companies", &["created_gmt", "modified_gmt"]),
+    ("company_buyers", &["created_gmt"]),
+    ("coupon_redemptions", &["created_gmt"]),
+    ("coupons", &["starts_gmt", "ends_gmt", "created_gmt", "modified_gmt"]),
+    ("customer_backup_codes", &["used_gmt"]),
+    ("customer_carts", &["created_gmt", "modified_gmt"]),
+    ("customer_data_requests", &["created_gmt", "completed_gmt"]),
+    ("customer_identities", &["created_gmt"]),
+    ("customer_notes", &["created_gmt"]),
+    ("customer_tags", &["created_gmt"]),
+    ("customer_two_factor", &["enabled_gmt", "created_gmt"]),
+    ("customers", &["created_gmt", "modified_gmt", "lastlogin_gmt", "lastorder_gmt", "optin_gmt"]),
+    ("event_outbox", &["created_gmt", "delivered_gmt"]),
+    ("gift_card_transactions", &["created_gmt"]),
+    ("gift_cards", &["expires_gmt", "created_gmt", "modified_gmt"]),
+    ("inventory_adjustments", &["created_gmt"]),
+    ("inventory_reservations", &["created_gmt", "expires_gmt", "released_gmt"]),
+    ("invoice_branding", &["modified_gmt"]),
+    ("invoices", &["created_gmt"]),
+    ("jobs", &["run_at_gmt", "locked_gmt", "created_gmt", "finished_gmt"]),
+    ("license_keys", &["assigned_gmt", "created_gmt"]),
+    ("login_attempts", &["last_failed_gmt", "locked_until_gmt"]),
+    ("order_downloads", &["expires_gmt", "created_gmt"]),
+    ("order_events", &["lock_gmt"]),
+    ("order_payments", &["created_gmt", "modified_gmt"]),
+    ("orders", &["created_gmt", "paid_gmt", "bs_settlement", "shipped_gmt", "inv_gmt", "modified_gmt", "synced_gmt"]),
+    ("price_tiers", &["created_gmt", "modified_gmt"]),
+    ("product_categories", &["created_gmt"]),
+    ("product_media", &["created_gmt"]),
+    ("products", &["ts", "created_gmt", "lastsold_gmt"]),
+    ("purchase_orders", &["expected_gmt", "created_gmt", "sent_gmt", "received_gmt"]),
+    ("refresh_tokens", &["created_gmt", "expires_gmt", "revoked_gmt"]),
+    ("reports", &["from_gmt", "to_gmt", "created_gmt", "generated_gmt"]),
+    ("returns", &["created_gmt", "modified_gmt"]),
+    ("sessions", &["created_gmt", "last_seen_gmt", "revoked_gmt"]),
+    ("shipments", &["shipped_gmt"]),
+    ("shipping_rates", &["created_gmt"]),
+    ("shipping_zones", &["created_gmt"]),
+    ("sku_lookup", &["low_stock_gmt", "amz_productdb_gmt"]),
+    ("stock_transfers", &["created_gmt", "closed_gmt"]),
+    ("tax_rates", &["created_gmt", "modified_gmt"]),
+    ("warehouses", &["created_gmt"]),
+    ("webhook_deliveries", &["next_attempt_gmt", "created_gmt", "delivered_gmt"]),
+    ("webhook_endpoints", &["created_gmt", "modified_gmt"]),
+    ("wishlist_items", &["added_gmt"]),
+    ("wishlists", &["created_gmt", "modified_gmt"]),
+    ("zusers", &["bill_lock_gmt", "overduenotify_gmt", "published_gmt", "tkts_lastused_gmt"]),
+];
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+async fn retype(manager: &SchemaManager<'_>, column_type: fn(&mut ColumnDef) -> &mut ColumnDef) -> Result<(), DbErr> {
+    for (table, columns) in COLUMNS {
+        let mut alter = Table::alter();
+        alter.table(Alias::new(*table));
+        for column in *columns {
+            alter.modify_column(column_type(&mut ColumnDef::new(Alias::new(*column))));
+        }
+        manager.alter_table(alter).await?;
+    }
+    Ok(())
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        retype(manager, ColumnDef::big_integer).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        retype(manager, ColumnDef::integer).await
+    }
+}
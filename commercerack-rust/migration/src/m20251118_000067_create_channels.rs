@@ -0,0 +1,184 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Channels::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Channels::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(Channels::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        // Marketplace code, e.g. `amazon`
+                        ColumnDef::new(Channels::Marketplace)
+                            .string_len(20)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Channels::Name)
+                            .string_len(60)
+                            .not_null()
+                    )
+                    .col(
+                        // JSON of the connector's account settings
+                        ColumnDef::new(Channels::Settings)
+                            .text()
+                            .not_null()
+                            .default("")
+                    )
+                    .col(
+                        ColumnDef::new(Channels::Enabled)
+                            .boolean()
+                            .not_null()
+                            .default(true)
+                    )
+                    .col(
+                        // Orders placed up to here have been pulled
+                        ColumnDef::new(Channels::OrdersSyncedGmt)
+                            .big_integer()
+                            .null()
+                    )
+                    .col(
+                        // Shipments up to here have had their tracking pushed
+                        ColumnDef::new(Channels::TrackingSyncedGmt)
+                            .big_integer()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(Channels::InventorySyncedGmt)
+                            .big_integer()
+                            .null()
+                    )
+                    .col(
+                        // Why the last sync failed; cleared by the next one to succeed
+                        ColumnDef::new(Channels::LastError)
+                            .text()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(Channels::CreatedGmt)
+                            .big_integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Channels::ModifiedGmt)
+                            .big_integer()
+                            .not_null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // One account per marketplace and merchant; the marketplace's bit
+        // in `orders.mkt` then names the channel an order came from
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_channels_mid_marketplace")
+                    .table(Channels::Table)
+                    .col(Channels::Mid)
+                    .col(Channels::Marketplace)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        // The legacy `orders.mkt` is the bit of the marketplace the order
+        // was placed on, 0 for the merchant's own storefront; orders that
+        // never had one were placed on the storefront
+        manager
+            .get_connection()
+            .execute_unprepared("UPDATE orders SET mkt = 0 WHERE mkt IS NULL")
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Orders::Table)
+                    .modify_column(
+                        ColumnDef::new(Orders::Mkt)
+                            .integer()
+                            .not_null()
+                            .default(0)
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Pulling the same marketplace order twice must not create two. The
+        // marketplace's own reference is the legacy `orders.order_erefid`.
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_orders_mid_mkt_erefid")
+                    .table(Orders::Table)
+                    .col(Orders::Mid)
+                    .col(Orders::Mkt)
+                    .col(Orders::OrderErefid)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(Index::drop().name("idx_orders_mid_mkt_erefid").table(Orders::Table).to_owned())
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Orders::Table)
+                    .modify_column(
+                        ColumnDef::new(Orders::Mkt)
+                            .integer()
+                            .null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Channels::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Channels {
+    Table,
+    Id,
+    Mid,
+    Marketplace,
+    Name,
+    Settings,
+    Enabled,
+    OrdersSyncedGmt,
+    TrackingSyncedGmt,
+    InventorySyncedGmt,
+    LastError,
+    CreatedGmt,
+    ModifiedGmt,
+}
+
+#[derive(DeriveIden)]
+enum Orders {
+    Table,
+    Mid,
+    Mkt,
+    OrderErefid,
+}
@@ -0,0 +1,52 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // The legacy `orders.mkt_bitstr` is `orders.mkt` as a comma-separated
+        // list of its set bits, e.g. `0,1`, kept for legacy reports; imported
+        // orders fill it in and the rest leave it empty
+        manager
+            .get_connection()
+            .execute_unprepared("UPDATE orders SET mkt_bitstr = '' WHERE mkt_bitstr IS NULL")
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Orders::Table)
+                    .modify_column(
+                        ColumnDef::new(Orders::MktBitstr)
+                            .string_len(24)
+                            .not_null()
+                            .default("")
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Orders::Table)
+                    .modify_column(
+                        ColumnDef::new(Orders::MktBitstr)
+                            .string_len(24)
+                            .null()
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Orders {
+    Table,
+    MktBitstr,
+}
@@ -0,0 +1,67 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Channels::Table)
+                    .add_column(
+                        // The marketplace asked us to back off until here
+                        ColumnDef::new(Channels::ThrottledUntilGmt)
+                            .big_integer()
+                            .null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Inventory pushes look for the SKUs whose stock moved since the last
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_inventory_adjustments_mid_created")
+                    .table(InventoryAdjustments::Table)
+                    .col(InventoryAdjustments::Mid)
+                    .col(InventoryAdjustments::CreatedGmt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_inventory_adjustments_mid_created")
+                    .table(InventoryAdjustments::Table)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Channels::Table)
+                    .drop_column(Channels::ThrottledUntilGmt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Channels {
+    Table,
+    ThrottledUntilGmt,
+}
+
+#[derive(DeriveIden)]
+enum InventoryAdjustments {
+    Table,
+    Mid,
+    CreatedGmt,
+}
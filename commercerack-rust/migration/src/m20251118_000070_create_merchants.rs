@@ -0,0 +1,90 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Merchants::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Merchants::Mid)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(Merchants::Name)
+                            .string_len(100)
+                            .not_null()
+                    )
+                    .col(
+                        // Storefront domain, lowercase, e.g. `shop.example.com`
+                        ColumnDef::new(Merchants::Sdomain)
+                            .string_len(100)
+                            .not_null()
+                    )
+                    .col(
+                        // JSON of the merchant's settings; onboarding fills in the defaults
+                        ColumnDef::new(Merchants::Settings)
+                            .text()
+                            .not_null()
+                            .default("{}")
+                    )
+                    .col(
+                        // active, suspended or closed
+                        ColumnDef::new(Merchants::Status)
+                            .string_len(12)
+                            .not_null()
+                            .default("active")
+                    )
+                    .col(
+                        ColumnDef::new(Merchants::CreatedGmt)
+                            .big_integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Merchants::ModifiedGmt)
+                            .big_integer()
+                            .not_null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Storefront requests are routed to their merchant by domain
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_merchants_sdomain")
+                    .table(Merchants::Table)
+                    .col(Merchants::Sdomain)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Merchants::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Merchants {
+    Table,
+    Mid,
+    Name,
+    Sdomain,
+    Settings,
+    Status,
+    CreatedGmt,
+    ModifiedGmt,
+}
@@ -0,0 +1,76 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(MerchantSettings::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(MerchantSettings::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(MerchantSettings::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        // e.g. currency, timezone or features.<name>
+                        ColumnDef::new(MerchantSettings::Key)
+                            .string_len(64)
+                            .not_null()
+                    )
+                    .col(
+                        // JSON; a key without a row has its default
+                        ColumnDef::new(MerchantSettings::Value)
+                            .text()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(MerchantSettings::ModifiedGmt)
+                            .big_integer()
+                            .not_null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // A merchant's settings are always read together; a key is set once
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_merchant_settings_mid_key")
+                    .table(MerchantSettings::Table)
+                    .col(MerchantSettings::Mid)
+                    .col(MerchantSettings::Key)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(MerchantSettings::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum MerchantSettings {
+    Table,
+    Id,
+    Mid,
+    Key,
+    Value,
+    ModifiedGmt,
+}
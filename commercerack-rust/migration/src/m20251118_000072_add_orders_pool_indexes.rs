@@ -0,0 +1,34 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // Order lists open on RECENT; archived orders stay out of its index
+        db.execute_unprepared(
+            "CREATE INDEX IF NOT EXISTS idx_orders_recent \
+             ON orders (mid, created_gmt DESC, id DESC) WHERE pool = 'RECENT'",
+        )
+        .await?;
+
+        // Lists of any other pool, and the per-pool counts
+        db.execute_unprepared(
+            "CREATE INDEX IF NOT EXISTS idx_orders_mid_pool_created \
+             ON orders (mid, pool, created_gmt DESC, id DESC)",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        db.execute_unprepared("DROP INDEX IF EXISTS idx_orders_mid_pool_created").await?;
+        db.execute_unprepared("DROP INDEX IF EXISTS idx_orders_recent").await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,117 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ArchivedOrders::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ArchivedOrders::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(ArchivedOrders::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        // `orders.id` of the order before it left the table
+                        ColumnDef::new(ArchivedOrders::OrderId)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ArchivedOrders::Orderid)
+                            .string_len(30)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ArchivedOrders::Customer)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ArchivedOrders::Total)
+                            .decimal_len(10, 2)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ArchivedOrders::CreatedGmt)
+                            .big_integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ArchivedOrders::ObjectKey)
+                            .string_len(255)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ArchivedOrders::Size)
+                            .big_integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ArchivedOrders::ArchivedGmt)
+                            .big_integer()
+                            .not_null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // An order is archived once; rehydration looks it up by its old id
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_archived_orders_mid_order_id")
+                    .table(ArchivedOrders::Table)
+                    .col(ArchivedOrders::Mid)
+                    .col(ArchivedOrders::OrderId)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        // Merchants find archived orders by their order number
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_archived_orders_mid_orderid")
+                    .table(ArchivedOrders::Table)
+                    .col(ArchivedOrders::Mid)
+                    .col(ArchivedOrders::Orderid)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ArchivedOrders::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum ArchivedOrders {
+    Table,
+    Id,
+    Mid,
+    OrderId,
+    Orderid,
+    Customer,
+    Total,
+    CreatedGmt,
+    ObjectKey,
+    Size,
+    ArchivedGmt,
+}
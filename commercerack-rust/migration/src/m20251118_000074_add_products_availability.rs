@@ -0,0 +1,77 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Products::Table)
+                    .add_column(
+                        // draft, active or archived; existing products stay on sale
+                        ColumnDef::new(Products::Status)
+                            .string_len(16)
+                            .not_null()
+                            .default("active")
+                    )
+                    .add_column(
+                        // First moment the product is on sale; open when null
+                        ColumnDef::new(Products::AvailableFrom)
+                            .big_integer()
+                            .null()
+                    )
+                    .add_column(
+                        // Moment the product comes off sale; open when null
+                        ColumnDef::new(Products::AvailableUntil)
+                            .big_integer()
+                            .null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        let db = manager.get_connection();
+
+        // Storefront lists only read active products
+        db.execute_unprepared(
+            "CREATE INDEX IF NOT EXISTS idx_products_mid_status ON products (mid, status)",
+        )
+        .await?;
+
+        // Drafts waiting for scheduled activation
+        db.execute_unprepared(
+            "CREATE INDEX IF NOT EXISTS idx_products_scheduled \
+             ON products (available_from) WHERE status = 'draft' AND available_from IS NOT NULL",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        db.execute_unprepared("DROP INDEX IF EXISTS idx_products_scheduled").await?;
+        db.execute_unprepared("DROP INDEX IF EXISTS idx_products_mid_status").await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Products::Table)
+                    .drop_column(Products::Status)
+                    .drop_column(Products::AvailableFrom)
+                    .drop_column(Products::AvailableUntil)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Products {
+    Table,
+    Status,
+    AvailableFrom,
+    AvailableUntil,
+}
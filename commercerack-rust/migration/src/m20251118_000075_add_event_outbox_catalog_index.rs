@@ -0,0 +1,28 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // The catalog change feed reads one merchant's product and SKU
+        // events in outbox order
+        db.execute_unprepared(
+            "CREATE INDEX IF NOT EXISTS idx_event_outbox_catalog ON event_outbox (mid, id) \
+             WHERE event IN ('product.created', 'product.updated', 'product.deleted', \
+             'product.media_changed', 'sku.created', 'sku.updated', 'sku.deleted')",
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        db.execute_unprepared("DROP INDEX IF EXISTS idx_event_outbox_catalog").await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,87 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(CustomerRecommendations::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(CustomerRecommendations::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(CustomerRecommendations::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(CustomerRecommendations::Cid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(CustomerRecommendations::ProductId)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        // 0 for the best match
+                        ColumnDef::new(CustomerRecommendations::Position)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(CustomerRecommendations::Score)
+                            .double()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(CustomerRecommendations::ComputedGmt)
+                            .big_integer()
+                            .not_null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // A customer's recommendations are read in order
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_customer_recommendations_mid_cid")
+                    .table(CustomerRecommendations::Table)
+                    .col(CustomerRecommendations::Mid)
+                    .col(CustomerRecommendations::Cid)
+                    .col(CustomerRecommendations::Position)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(CustomerRecommendations::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum CustomerRecommendations {
+    Table,
+    Id,
+    Mid,
+    Cid,
+    ProductId,
+    Position,
+    Score,
+    ComputedGmt,
+}
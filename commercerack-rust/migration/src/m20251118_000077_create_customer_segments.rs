@@ -0,0 +1,291 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(CustomerMetrics::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(CustomerMetrics::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(CustomerMetrics::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(CustomerMetrics::Cid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        // Paid orders
+                        ColumnDef::new(CustomerMetrics::Orders)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        // Sum of paid order totals
+                        ColumnDef::new(CustomerMetrics::LifetimeValue)
+                            .decimal_len(12, 2)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(CustomerMetrics::FirstOrderGmt)
+                            .big_integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(CustomerMetrics::LastOrderGmt)
+                            .big_integer()
+                            .not_null()
+                    )
+                    .col(
+                        // RFM scores, 1 to 5 with 5 the best fifth of the merchant's customers
+                        ColumnDef::new(CustomerMetrics::RecencyScore)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(CustomerMetrics::FrequencyScore)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(CustomerMetrics::MonetaryScore)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        // e.g. champions or at_risk; see commercerack_customer::segments
+                        ColumnDef::new(CustomerMetrics::RfmSegment)
+                            .string_len(32)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(CustomerMetrics::ComputedGmt)
+                            .big_integer()
+                            .not_null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // One row per customer
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_customer_metrics_mid_cid")
+                    .table(CustomerMetrics::Table)
+                    .col(CustomerMetrics::Mid)
+                    .col(CustomerMetrics::Cid)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        // RFM segment counts and listings
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_customer_metrics_mid_rfm")
+                    .table(CustomerMetrics::Table)
+                    .col(CustomerMetrics::Mid)
+                    .col(CustomerMetrics::RfmSegment)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(Segments::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Segments::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(Segments::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Segments::Name)
+                            .string_len(64)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Segments::Description)
+                            .string_len(255)
+                            .not_null()
+                            .default("")
+                    )
+                    .col(
+                        // JSON: whether all or any rules must hold, and the rules
+                        ColumnDef::new(Segments::Rules)
+                            .text()
+                            .not_null()
+                    )
+                    .col(
+                        // Customers in the segment at the last refresh
+                        ColumnDef::new(Segments::Members)
+                            .integer()
+                            .not_null()
+                            .default(0)
+                    )
+                    .col(
+                        ColumnDef::new(Segments::CreatedGmt)
+                            .big_integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Segments::ModifiedGmt)
+                            .big_integer()
+                            .not_null()
+                    )
+                    .col(
+                        // Unset until the segment's first refresh
+                        ColumnDef::new(Segments::ComputedGmt)
+                            .big_integer()
+                            .null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Names are unique per merchant
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_segments_mid_name")
+                    .table(Segments::Table)
+                    .col(Segments::Mid)
+                    .col(Segments::Name)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(SegmentMembers::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(SegmentMembers::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(SegmentMembers::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(SegmentMembers::SegmentId)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(SegmentMembers::Cid)
+                            .integer()
+                            .not_null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Members are listed by customer ID
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_segment_members_segment_cid")
+                    .table(SegmentMembers::Table)
+                    .col(SegmentMembers::SegmentId)
+                    .col(SegmentMembers::Cid)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        // A customer's segments
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_segment_members_mid_cid")
+                    .table(SegmentMembers::Table)
+                    .col(SegmentMembers::Mid)
+                    .col(SegmentMembers::Cid)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(SegmentMembers::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Segments::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(CustomerMetrics::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum CustomerMetrics {
+    Table,
+    Id,
+    Mid,
+    Cid,
+    Orders,
+    LifetimeValue,
+    FirstOrderGmt,
+    LastOrderGmt,
+    RecencyScore,
+    FrequencyScore,
+    MonetaryScore,
+    RfmSegment,
+    ComputedGmt,
+}
+
+#[derive(DeriveIden)]
+enum Segments {
+    Table,
+    Id,
+    Mid,
+    Name,
+    Description,
+    Rules,
+    Members,
+    CreatedGmt,
+    ModifiedGmt,
+    ComputedGmt,
+}
+
+#[derive(DeriveIden)]
+enum SegmentMembers {
+    Table,
+    Id,
+    Mid,
+    SegmentId,
+    Cid,
+}
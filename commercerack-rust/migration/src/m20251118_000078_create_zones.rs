@@ -0,0 +1,152 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Zones::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Zones::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(Zones::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Zones::Name)
+                            .string_len(64)
+                            .not_null()
+                    )
+                    .col(
+                        // Comma-separated ISO codes, uppercase
+                        ColumnDef::new(Zones::Countries)
+                            .text()
+                            .not_null()
+                    )
+                    .col(
+                        // Comma-separated; empty matches every state
+                        ColumnDef::new(Zones::States)
+                            .text()
+                            .not_null()
+                            .default("")
+                    )
+                    .col(
+                        // Comma-separated prefixes or ranges like 94000-94999; empty matches every zip
+                        ColumnDef::new(Zones::Zips)
+                            .text()
+                            .not_null()
+                            .default("")
+                    )
+                    .col(
+                        ColumnDef::new(Zones::CreatedGmt)
+                            .big_integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(Zones::ModifiedGmt)
+                            .big_integer()
+                            .not_null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_zones_mid_name")
+                    .table(Zones::Table)
+                    .col(Zones::Mid)
+                    .col(Zones::Name)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        // Shipping zones and tax rates may cover a geo zone instead of
+        // listing their own countries, states and zips
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ShippingZones::Table)
+                    .add_column(
+                        ColumnDef::new(ShippingZones::ZoneId)
+                            .integer()
+                            .null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TaxRates::Table)
+                    .add_column(
+                        ColumnDef::new(TaxRates::ZoneId)
+                            .integer()
+                            .null()
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TaxRates::Table)
+                    .drop_column(TaxRates::ZoneId)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ShippingZones::Table)
+                    .drop_column(ShippingZones::ZoneId)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Zones::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Zones {
+    Table,
+    Id,
+    Mid,
+    Name,
+    Countries,
+    States,
+    Zips,
+    CreatedGmt,
+    ModifiedGmt,
+}
+
+#[derive(DeriveIden)]
+enum ShippingZones {
+    Table,
+    ZoneId,
+}
+
+#[derive(DeriveIden)]
+enum TaxRates {
+    Table,
+    ZoneId,
+}
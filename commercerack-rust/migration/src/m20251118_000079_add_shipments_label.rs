@@ -0,0 +1,62 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Shipments::Table)
+                    .add_column(
+                        // Label service the label was bought from; null when none was
+                        ColumnDef::new(Shipments::LabelProvider)
+                            .string_len(32)
+                            .null()
+                    )
+                    .add_column(
+                        // The service's reference, used to void the label
+                        ColumnDef::new(Shipments::LabelId)
+                            .string_len(128)
+                            .null()
+                    )
+                    .add_column(
+                        ColumnDef::new(Shipments::LabelUrl)
+                            .text()
+                            .null()
+                    )
+                    .add_column(
+                        ColumnDef::new(Shipments::LabelCost)
+                            .decimal_len(12, 2)
+                            .null()
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Shipments::Table)
+                    .drop_column(Shipments::LabelProvider)
+                    .drop_column(Shipments::LabelId)
+                    .drop_column(Shipments::LabelUrl)
+                    .drop_column(Shipments::LabelCost)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Shipments {
+    Table,
+    LabelProvider,
+    LabelId,
+    LabelUrl,
+    LabelCost,
+}
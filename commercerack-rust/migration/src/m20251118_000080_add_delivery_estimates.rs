@@ -0,0 +1,137 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Warehouses::Table)
+                    .add_column(
+                        // Business days between an order and its parcel leaving
+                        ColumnDef::new(Warehouses::HandlingDays)
+                            .integer()
+                            .not_null()
+                            .default(1)
+                    )
+                    .add_column(
+                        // Hour of the merchant's day after which orders count from the next business day
+                        ColumnDef::new(Warehouses::CutoffHour)
+                            .integer()
+                            .not_null()
+                            .default(14)
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(ShippingTransitTimes::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ShippingTransitTimes::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(ShippingTransitTimes::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ShippingTransitTimes::ZoneId)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ShippingTransitTimes::Method)
+                            .string_len(40)
+                            .not_null()
+                    )
+                    .col(
+                        // Business days in the carrier's hands
+                        ColumnDef::new(ShippingTransitTimes::MinDays)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ShippingTransitTimes::MaxDays)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(ShippingTransitTimes::CreatedGmt)
+                            .big_integer()
+                            .not_null()
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_shipping_transit_times_zone")
+                            .from(ShippingTransitTimes::Table, ShippingTransitTimes::ZoneId)
+                            .to(ShippingZones::Table, ShippingZones::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_shipping_transit_times_zone_method")
+                    .table(ShippingTransitTimes::Table)
+                    .col(ShippingTransitTimes::ZoneId)
+                    .col(ShippingTransitTimes::Method)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ShippingTransitTimes::Table).to_owned())
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Warehouses::Table)
+                    .drop_column(Warehouses::HandlingDays)
+                    .drop_column(Warehouses::CutoffHour)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Warehouses {
+    Table,
+    HandlingDays,
+    CutoffHour,
+}
+
+#[derive(DeriveIden)]
+enum ShippingTransitTimes {
+    Table,
+    Id,
+    Mid,
+    ZoneId,
+    Method,
+    MinDays,
+    MaxDays,
+    CreatedGmt,
+}
+
+#[derive(DeriveIden)]
+enum ShippingZones {
+    Table,
+    Id,
+}
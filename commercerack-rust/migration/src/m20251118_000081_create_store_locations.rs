@@ -0,0 +1,169 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(StoreLocations::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(StoreLocations::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(StoreLocations::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(StoreLocations::Name)
+                            .string_len(80)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(StoreLocations::Street1)
+                            .string_len(100)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(StoreLocations::Street2)
+                            .string_len(100)
+                            .not_null()
+                            .default("")
+                    )
+                    .col(
+                        ColumnDef::new(StoreLocations::City)
+                            .string_len(60)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(StoreLocations::State)
+                            .string_len(20)
+                            .not_null()
+                            .default("")
+                    )
+                    .col(
+                        ColumnDef::new(StoreLocations::Zip)
+                            .string_len(20)
+                            .not_null()
+                            .default("")
+                    )
+                    .col(
+                        ColumnDef::new(StoreLocations::Country)
+                            .string_len(2)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(StoreLocations::Phone)
+                            .string_len(32)
+                            .not_null()
+                            .default("")
+                    )
+                    .col(
+                        // Free text shown to shoppers, e.g. "Mon-Fri 9-6"
+                        ColumnDef::new(StoreLocations::Hours)
+                            .text()
+                            .not_null()
+                            .default("")
+                    )
+                    .col(
+                        // Inactive locations keep their orders but can't be chosen at checkout
+                        ColumnDef::new(StoreLocations::Active)
+                            .boolean()
+                            .not_null()
+                            .default(true)
+                    )
+                    .col(
+                        ColumnDef::new(StoreLocations::CreatedGmt)
+                            .big_integer()
+                            .not_null()
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_store_locations_mid")
+                    .table(StoreLocations::Table)
+                    .col(StoreLocations::Mid)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Orders::Table)
+                    .add_column(
+                        // Store the buyer collects the order from; null for orders that ship
+                        ColumnDef::new(Orders::PickupLocationId)
+                            .integer()
+                            .null()
+                    )
+                    .add_column(
+                        ColumnDef::new(Orders::PickupReadyGmt)
+                            .big_integer()
+                            .null()
+                    )
+                    .add_column(
+                        ColumnDef::new(Orders::PickedUpGmt)
+                            .big_integer()
+                            .null()
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Orders::Table)
+                    .drop_column(Orders::PickupLocationId)
+                    .drop_column(Orders::PickupReadyGmt)
+                    .drop_column(Orders::PickedUpGmt)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_table(Table::drop().table(StoreLocations::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum StoreLocations {
+    Table,
+    Id,
+    Mid,
+    Name,
+    Street1,
+    Street2,
+    City,
+    State,
+    Zip,
+    Country,
+    Phone,
+    Hours,
+    Active,
+    CreatedGmt,
+}
+
+#[derive(DeriveIden)]
+enum Orders {
+    Table,
+    PickupLocationId,
+    PickupReadyGmt,
+    PickedUpGmt,
+}
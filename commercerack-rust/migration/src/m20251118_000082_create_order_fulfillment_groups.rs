@@ -0,0 +1,177 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(OrderFulfillmentGroups::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(OrderFulfillmentGroups::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(OrderFulfillmentGroups::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(OrderFulfillmentGroups::OrderId)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        // Null for units no warehouse held when the order was placed
+                        ColumnDef::new(OrderFulfillmentGroups::WarehouseId)
+                            .integer()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(OrderFulfillmentGroups::Carrier)
+                            .string_len(40)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(OrderFulfillmentGroups::ShipMethod)
+                            .string_len(40)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(OrderFulfillmentGroups::ShipName)
+                            .string_len(80)
+                            .not_null()
+                    )
+                    .col(
+                        // What the buyer was charged; zero under a free shipping coupon
+                        ColumnDef::new(OrderFulfillmentGroups::Shipping)
+                            .decimal_len(12, 2)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(OrderFulfillmentGroups::CreatedGmt)
+                            .big_integer()
+                            .not_null()
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_order_fulfillment_groups_order")
+                            .from(OrderFulfillmentGroups::Table, OrderFulfillmentGroups::OrderId)
+                            .to(Orders::Table, Orders::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_order_fulfillment_groups_order_id")
+                    .table(OrderFulfillmentGroups::Table)
+                    .col(OrderFulfillmentGroups::OrderId)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(OrderFulfillmentGroupItems::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(OrderFulfillmentGroupItems::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(OrderFulfillmentGroupItems::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(OrderFulfillmentGroupItems::GroupId)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(OrderFulfillmentGroupItems::Sku)
+                            .string_len(45)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(OrderFulfillmentGroupItems::Quantity)
+                            .integer()
+                            .not_null()
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_order_fulfillment_group_items_group")
+                            .from(OrderFulfillmentGroupItems::Table, OrderFulfillmentGroupItems::GroupId)
+                            .to(OrderFulfillmentGroups::Table, OrderFulfillmentGroups::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_order_fulfillment_group_items_group_id")
+                    .table(OrderFulfillmentGroupItems::Table)
+                    .col(OrderFulfillmentGroupItems::GroupId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(OrderFulfillmentGroupItems::Table).to_owned())
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(OrderFulfillmentGroups::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum OrderFulfillmentGroups {
+    Table,
+    Id,
+    Mid,
+    OrderId,
+    WarehouseId,
+    Carrier,
+    ShipMethod,
+    ShipName,
+    Shipping,
+    CreatedGmt,
+}
+
+#[derive(DeriveIden)]
+enum OrderFulfillmentGroupItems {
+    Table,
+    Id,
+    Mid,
+    GroupId,
+    Sku,
+    Quantity,
+}
+
+#[derive(DeriveIden)]
+enum Orders {
+    Table,
+    Id,
+}
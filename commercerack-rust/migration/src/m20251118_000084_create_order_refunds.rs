@@ -0,0 +1,198 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(OrderRefunds::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(OrderRefunds::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(OrderRefunds::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(OrderRefunds::OrderId)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        // Refunded items, net of their share of discounts
+                        ColumnDef::new(OrderRefunds::ItemsAmount)
+                            .decimal_len(12, 2)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(OrderRefunds::TaxAmount)
+                            .decimal_len(12, 2)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(OrderRefunds::ShippingAmount)
+                            .decimal_len(12, 2)
+                            .not_null()
+                    )
+                    .col(
+                        // What went back through the payment gateway
+                        ColumnDef::new(OrderRefunds::Amount)
+                            .decimal_len(12, 2)
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(OrderRefunds::Reason)
+                            .string_len(255)
+                            .not_null()
+                            .default("")
+                    )
+                    .col(
+                        ColumnDef::new(OrderRefunds::CreatedGmt)
+                            .big_integer()
+                            .not_null()
+                    )
+                    .col(
+                        // Set once the gateway has given the amount back
+                        ColumnDef::new(OrderRefunds::RefundedGmt)
+                            .big_integer()
+                            .null()
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_order_refunds_order")
+                            .from(OrderRefunds::Table, OrderRefunds::OrderId)
+                            .to(Orders::Table, Orders::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_order_refunds_order_id")
+                    .table(OrderRefunds::Table)
+                    .col(OrderRefunds::OrderId)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(OrderRefundItems::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(OrderRefundItems::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(OrderRefundItems::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(OrderRefundItems::RefundId)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(OrderRefundItems::OrderItemId)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(OrderRefundItems::Quantity)
+                            .integer()
+                            .not_null()
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_order_refund_items_refund")
+                            .from(OrderRefundItems::Table, OrderRefundItems::RefundId)
+                            .to(OrderRefunds::Table, OrderRefunds::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_order_refund_items_order_item")
+                            .from(OrderRefundItems::Table, OrderRefundItems::OrderItemId)
+                            .to(OrderItems::Table, OrderItems::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_order_refund_items_refund_id")
+                    .table(OrderRefundItems::Table)
+                    .col(OrderRefundItems::RefundId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(OrderRefundItems::Table).to_owned())
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(OrderRefunds::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum OrderRefunds {
+    Table,
+    Id,
+    Mid,
+    OrderId,
+    ItemsAmount,
+    TaxAmount,
+    ShippingAmount,
+    Amount,
+    Reason,
+    CreatedGmt,
+    RefundedGmt,
+}
+
+#[derive(DeriveIden)]
+enum OrderRefundItems {
+    Table,
+    Id,
+    Mid,
+    RefundId,
+    OrderItemId,
+    Quantity,
+}
+
+#[derive(DeriveIden)]
+enum Orders {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum OrderItems {
+    Table,
+    Id,
+}
@@ -0,0 +1,131 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(DunningCases::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(DunningCases::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key()
+                    )
+                    .col(
+                        ColumnDef::new(DunningCases::Mid)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(DunningCases::OrderId)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        // Tokenized payment method the retries charge
+                        ColumnDef::new(DunningCases::PaymentMethod)
+                            .string_len(255)
+                            .not_null()
+                    )
+                    .col(
+                        // retrying, recovered, suspended or closed
+                        ColumnDef::new(DunningCases::Status)
+                            .string_len(20)
+                            .not_null()
+                    )
+                    .col(
+                        // Declined charges so far, the first included
+                        ColumnDef::new(DunningCases::Attempts)
+                            .integer()
+                            .not_null()
+                    )
+                    .col(
+                        // Null once the case is no longer retrying
+                        ColumnDef::new(DunningCases::NextAttemptGmt)
+                            .big_integer()
+                            .null()
+                    )
+                    .col(
+                        ColumnDef::new(DunningCases::LastError)
+                            .string_len(255)
+                            .not_null()
+                            .default("")
+                    )
+                    .col(
+                        ColumnDef::new(DunningCases::CreatedGmt)
+                            .big_integer()
+                            .not_null()
+                    )
+                    .col(
+                        ColumnDef::new(DunningCases::ModifiedGmt)
+                            .big_integer()
+                            .not_null()
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_dunning_cases_order")
+                            .from(DunningCases::Table, DunningCases::OrderId)
+                            .to(Orders::Table, Orders::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_dunning_cases_order_id")
+                    .table(DunningCases::Table)
+                    .col(DunningCases::OrderId)
+                    .to_owned(),
+            )
+            .await?;
+
+        // The retry runner looks for retrying cases that are due
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_dunning_cases_status_next_attempt")
+                    .table(DunningCases::Table)
+                    .col(DunningCases::Status)
+                    .col(DunningCases::NextAttemptGmt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(DunningCases::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum DunningCases {
+    Table,
+    Id,
+    Mid,
+    OrderId,
+    PaymentMethod,
+    Status,
+    Attempts,
+    NextAttemptGmt,
+    LastError,
+    CreatedGmt,
+    ModifiedGmt,
+}
+
+#[derive(DeriveIden)]
+enum Orders {
+    Table,
+    Id,
+}
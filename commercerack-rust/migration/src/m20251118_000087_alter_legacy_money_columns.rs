@@ -0,0 +1,49 @@
+//! Store the legacy money columns as `numeric(10,2)`
+//!
+//! The tables carried over from MySQL kept their prices and totals as
+//! strings, while the entities read them as decimals. Blank strings were
+//! the legacy "no amount" and become NULL, or 0 where the column is never
+//! NULL.
+
+use sea_orm_migration::prelude::*;
+
+/// Money columns stored as strings: table, column and whether the column
+/// is never NULL (and so defaults to 0)
+const MONEY_COLUMNS: &[(&str, &str, bool)] = &[
+    ("products", "base_price", true),
+    ("products", "base_cost", true),
+    ("sku_lookup", "cost", true),
+    ("sku_lookup", "price", true),
+    ("orders", "total", true),
+    ("amazon_orders", "order_total", false),
+];
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+async fn retype(manager: &SchemaManager<'_>, to: &str, using: &str) -> Result<(), DbErr> {
+    for (table, column, required) in MONEY_COLUMNS {
+        let using = using.replace("{column}", column);
+        let using = if *required { format!("COALESCE({using}, '0')") } else { using };
+        let default = if *required { format!(", ALTER COLUMN {column} SET DEFAULT '0'") } else { String::new() };
+        manager
+            .get_connection()
+            .execute_unprepared(&format!(
+                "ALTER TABLE {table} ALTER COLUMN {column} DROP DEFAULT, \
+                 ALTER COLUMN {column} TYPE {to} USING {using}{default}"
+            ))
+            .await?;
+    }
+    Ok(())
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        retype(manager, "numeric(10,2)", "NULLIF(btrim({column}), '')::numeric(10,2)").await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        retype(manager, "varchar", "{column}::varchar").await
+    }
+}
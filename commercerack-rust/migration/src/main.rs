@@ -1,6 +1,9 @@
+//! `migrate up`, `migrate down`, `migrate status` and the rest of the
+//! sea-orm-migration commands against `DATABASE_URL`
+
 use sea_orm_migration::prelude::*;
 
-#[async_std::main]
+#[tokio::main]
 async fn main() {
     cli::run_cli(migration::Migrator).await;
 }
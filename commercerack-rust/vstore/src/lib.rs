@@ -1 +1 @@
-fn main() {}
+//! Virtual storefront module (not yet implemented)